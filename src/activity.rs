@@ -0,0 +1,414 @@
+//! Activity timeline of each session
+//!
+//! What happened in a session and when: terminals started and stopped, the
+//! session going quiet or back to work, attention raised, HEAD moving,
+//! the number of changed files crossing one of `CHANGED_FILE_STEPS`,
+//! the worktree being created or synced, and moments marked by hand. The
+//! parts of the app that notice these already report them to the session's
+//! `SessionEvents`, which stamps them and keeps the newest `MAX_ACTIVITY`
+//! in the session state file (see `session_state`). Going idle, attention
+//! and HEAD moves also run the hooks of those events (see `hooks`).
+//!
+//! A session counts as working while its terminals print (within
+//! `busy::RECENT_OUTPUT`) and goes idle after `IDLE_AFTER` without output,
+//! so an agent pausing between steps does not fill the timeline.
+
+use crate::attention::AttentionReason;
+use crate::busy;
+use crate::dirty;
+use crate::hooks::{self, HookEvent, HookSubject};
+use crate::session_state::SessionState;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Entries kept per session
+pub const MAX_ACTIVITY: usize = 300;
+
+/// Quiet time after which a working session counts as idle
+pub const IDLE_AFTER: Duration = Duration::from_secs(30);
+
+/// How often the sessions are checked for going idle
+pub const ACTIVITY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Changed file counts that are worth an entry when crossed
+pub const CHANGED_FILE_STEPS: [usize; 5] = [1, 10, 25, 50, 100];
+
+/// Something that happened in a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ActivityEvent {
+    /// A terminal was started (the session has `count` now)
+    TerminalStarted {
+        count: usize,
+    },
+    /// All terminals were stopped
+    TerminalsStopped,
+    /// Output started after the session was idle
+    Working,
+    /// No output for `IDLE_AFTER`
+    Idle,
+    /// The session asked for attention while in the background
+    Attention {
+        reason: String,
+    },
+    /// HEAD moved to a commit (a commit, reset or rebase)
+    HeadMoved {
+        commit: String,
+        subject: String,
+    },
+    /// The worktree checked out another branch
+    BranchSwitched {
+        branch: String,
+    },
+    /// The number of changed files crossed a step of `CHANGED_FILE_STEPS`
+    ChangedFiles {
+        count: usize,
+    },
+    WorktreeCreated,
+    /// Template files were synced from the main worktree
+    WorktreeSynced {
+        copied: usize,
+    },
+    /// Moment marked by hand
+    Note {
+        text: String,
+    },
+}
+
+impl ActivityEvent {
+    /// Hook event this event runs the hooks of, if any
+    fn hook(&self) -> Option<HookEvent> {
+        match self {
+            Self::Idle => Some(HookEvent::SessionIdle),
+            Self::Attention { .. } => Some(HookEvent::SessionAttention),
+            Self::HeadMoved { .. } => Some(HookEvent::CommitDetected),
+            Self::WorktreeCreated => Some(HookEvent::WorktreeCreated),
+            _ => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::TerminalStarted { count: 1 } => "Terminal started".to_string(),
+            Self::TerminalStarted { count } => format!("Terminal started ({} open)", count),
+            Self::TerminalsStopped => "Terminals stopped".to_string(),
+            Self::Working => "Working".to_string(),
+            Self::Idle => "Idle".to_string(),
+            Self::Attention { reason } => format!("Attention: {}", reason),
+            Self::HeadMoved { commit, subject } => format!("HEAD → {} {}", commit, subject),
+            Self::BranchSwitched { branch } => format!("Switched to {}", branch),
+            Self::ChangedFiles { count: 0 } => "No changed files".to_string(),
+            Self::ChangedFiles { count: 1 } => "1 changed file".to_string(),
+            Self::ChangedFiles { count } => format!("{} changed files", count),
+            Self::WorktreeCreated => "Worktree created".to_string(),
+            Self::WorktreeSynced { copied } => format!("Synced {} files from main", copied),
+            Self::Note { text } => text.clone(),
+        }
+    }
+}
+
+/// An event and when it happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    /// Unix seconds
+    pub at: i64,
+    #[serde(flatten)]
+    pub event: ActivityEvent,
+}
+
+/// Events of one session, oldest first, at most `MAX_ACTIVITY`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ActivityLog(VecDeque<ActivityEntry>);
+
+impl ActivityLog {
+    /// Record `event` at `at` (unix seconds), evicting the oldest entries
+    /// beyond `MAX_ACTIVITY`. An entry older than the newest is put in
+    /// order, so the log stays sorted when entries from elsewhere are added.
+    pub fn push_at(&mut self, at: i64, event: ActivityEvent) {
+        let index = self.0.partition_point(|entry| entry.at <= at);
+        self.0.insert(index, ActivityEntry { at, event });
+        while self.0.len() > MAX_ACTIVITY {
+            self.0.pop_front();
+        }
+    }
+
+    pub fn extend(&mut self, other: ActivityLog) {
+        for entry in other.0 {
+            self.push_at(entry.at, entry.event);
+        }
+    }
+
+    pub fn newest_first(&self) -> impl Iterator<Item = &ActivityEntry> {
+        self.0.iter().rev()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Turns a session's output age into `Working` and `Idle` events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdleTracker {
+    working: bool,
+}
+
+impl IdleTracker {
+    /// The event for a session whose terminals last printed `output_age`
+    /// ago (None = never), if its state changed
+    pub fn observe(&mut self, output_age: Option<Duration>) -> Option<ActivityEvent> {
+        let printing = output_age.is_some_and(|age| age < busy::RECENT_OUTPUT);
+        let quiet = output_age.is_none_or(|age| age >= IDLE_AFTER);
+        if printing && !self.working {
+            self.working = true;
+            Some(ActivityEvent::Working)
+        } else if quiet && self.working {
+            self.working = false;
+            Some(ActivityEvent::Idle)
+        } else {
+            None
+        }
+    }
+}
+
+/// Step of `CHANGED_FILE_STEPS` that `count` changed files reach
+fn changed_file_step(count: usize) -> usize {
+    CHANGED_FILE_STEPS
+        .iter()
+        .filter(|&&step| count >= step)
+        .count()
+}
+
+/// Where a session's event sources report to
+#[derive(Debug, Default)]
+pub struct SessionEvents {
+    state: Rc<SessionState>,
+    idle: Cell<IdleTracker>,
+    /// Step of the changed file count last seen (None before the first)
+    changed_files: Cell<Option<usize>>,
+    /// The session as hooks see it
+    subject: RefCell<HookSubject>,
+}
+
+impl SessionEvents {
+    pub fn new(state: Rc<SessionState>) -> Self {
+        Self {
+            state,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_subject(&self, subject: HookSubject) {
+        *self.subject.borrow_mut() = subject;
+    }
+
+    /// Record `event` as happening now
+    pub fn record(&self, event: ActivityEvent) {
+        if let Some(hook) = event.hook() {
+            hooks::emit(hook, &self.subject.borrow());
+        }
+        self.state.update(|state| {
+            state.activity.push_at(unix_now(), event);
+            true
+        });
+    }
+
+    /// Record the moment a session asked for attention
+    pub fn attention(&self, reason: AttentionReason) {
+        self.record(ActivityEvent::Attention {
+            reason: reason.describe(),
+        });
+    }
+
+    /// Note HEAD at `commit`; moving to the commit it was already at (e.g.
+    /// a checkout of the same branch) is not recorded again
+    pub fn head_moved(&self, commit: String, subject: String) {
+        let same = self.state.read(|state| {
+            state
+                .activity
+                .newest_first()
+                .find_map(|entry| match &entry.event {
+                    ActivityEvent::HeadMoved { commit, .. } => Some(commit.clone()),
+                    _ => None,
+                })
+                .is_some_and(|last| last == commit)
+        });
+        if !same {
+            self.record(ActivityEvent::HeadMoved { commit, subject });
+            self.set_dirty_since(|since| dirty::observe_commit(since, unix_now()));
+        }
+    }
+
+    /// Note whether the worktree has uncommitted changes
+    pub fn dirty(&self, dirty: bool) {
+        self.set_dirty_since(|since| dirty::observe_files(since, dirty, unix_now()));
+    }
+
+    /// When the worktree turned dirty (unix seconds), None while it is clean
+    pub fn dirty_since(&self) -> Option<i64> {
+        self.state.read(|state| state.dirty_since)
+    }
+
+    fn set_dirty_since(&self, update: impl FnOnce(Option<i64>) -> Option<i64>) {
+        self.state.update(|state| {
+            let since = update(state.dirty_since);
+            std::mem::replace(&mut state.dirty_since, since) != since
+        });
+    }
+
+    /// Note that the worktree has `count` changed files; the first count
+    /// seen sets where the steps are counted from
+    pub fn changed_files(&self, count: usize) {
+        let step = changed_file_step(count);
+        let previous = self.changed_files.replace(Some(step));
+        if previous.is_some_and(|previous| previous != step) {
+            self.record(ActivityEvent::ChangedFiles { count });
+        }
+    }
+
+    /// Note the age of the session's latest output (see `IdleTracker`)
+    pub fn output_age(&self, age: Option<Duration>) {
+        let mut idle = self.idle.get();
+        let event = idle.observe(age);
+        self.idle.set(idle);
+        if let Some(event) = event {
+            self.record(event);
+        }
+    }
+
+    /// Entries newest first
+    pub fn entries(&self) -> Vec<ActivityEntry> {
+        self.state
+            .read(|state| state.activity.newest_first().cloned().collect())
+    }
+}
+
+/// Current time in unix seconds
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |age| age.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_state;
+
+    fn events(log: &ActivityLog) -> Vec<(i64, ActivityEvent)> {
+        log.newest_first()
+            .map(|entry| (entry.at, entry.event.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_log_is_newest_first_and_capped() {
+        let mut log = ActivityLog::default();
+        log.push_at(10, ActivityEvent::WorktreeCreated);
+        log.push_at(30, ActivityEvent::Working);
+        // Out of order (e.g. recorded before the state file was read)
+        log.push_at(20, ActivityEvent::TerminalStarted { count: 1 });
+        log.push_at(30, ActivityEvent::Idle);
+        assert_eq!(
+            events(&log),
+            vec![
+                (30, ActivityEvent::Idle),
+                (30, ActivityEvent::Working),
+                (20, ActivityEvent::TerminalStarted { count: 1 }),
+                (10, ActivityEvent::WorktreeCreated),
+            ]
+        );
+
+        for i in 0..MAX_ACTIVITY as i64 {
+            log.push_at(
+                100 + i,
+                ActivityEvent::Note {
+                    text: format!("note {}", i),
+                },
+            );
+        }
+        assert_eq!(log.newest_first().count(), MAX_ACTIVITY);
+        // The oldest went first
+        assert_eq!(log.newest_first().last().map(|entry| entry.at), Some(100));
+        assert_eq!(log.newest_first().next().map(|entry| entry.at), Some(399));
+    }
+
+    #[test]
+    fn test_idle_after_quiet_time() {
+        let mut tracker = IdleTracker::default();
+        assert_eq!(tracker.observe(None), None);
+        assert_eq!(
+            tracker.observe(Some(Duration::from_secs(1))),
+            Some(ActivityEvent::Working)
+        );
+        assert_eq!(tracker.observe(Some(Duration::from_secs(2))), None);
+        // A pause shorter than `IDLE_AFTER` keeps it working
+        assert_eq!(tracker.observe(Some(Duration::from_secs(20))), None);
+        assert_eq!(tracker.observe(Some(Duration::from_secs(0))), None);
+        assert_eq!(tracker.observe(Some(IDLE_AFTER)), Some(ActivityEvent::Idle));
+        assert_eq!(tracker.observe(Some(IDLE_AFTER * 2)), None);
+        assert_eq!(
+            tracker.observe(Some(Duration::ZERO)),
+            Some(ActivityEvent::Working)
+        );
+        // The terminals went away
+        assert_eq!(tracker.observe(None), Some(ActivityEvent::Idle));
+    }
+
+    #[test]
+    fn test_session_events() {
+        let events = SessionEvents::new(Rc::new(SessionState::default()));
+        // The first count only sets the step
+        events.changed_files(3);
+        events.changed_files(9);
+        events.changed_files(12);
+        events.changed_files(0);
+        events.head_moved("abc1234".into(), "Add login".into());
+        events.head_moved("abc1234".into(), "Add login".into());
+        events.record(ActivityEvent::Note {
+            text: "tests pass".into(),
+        });
+
+        let kinds: Vec<_> = events
+            .entries()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ActivityEvent::Note {
+                    text: "tests pass".into()
+                },
+                ActivityEvent::HeadMoved {
+                    commit: "abc1234".into(),
+                    subject: "Add login".into()
+                },
+                ActivityEvent::ChangedFiles { count: 0 },
+                ActivityEvent::ChangedFiles { count: 12 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_activity_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = session_state::state_path(dir.path(), "feature");
+        let state = Rc::new(SessionState::default());
+        state.open(path.clone());
+        let events = SessionEvents::new(state.clone());
+        events.record(ActivityEvent::WorktreeCreated);
+        events.record(ActivityEvent::Attention {
+            reason: "Bell".into(),
+        });
+        state.flush();
+
+        let reopened = Rc::new(SessionState::default());
+        reopened.open(path);
+        assert_eq!(SessionEvents::new(reopened).entries(), events.entries());
+    }
+}
@@ -0,0 +1,413 @@
+//! Agent profiles: coding agents launched into a session's terminals
+//!
+//! A profile lives under `[sashiki "agent.<name>"]`:
+//! - `command`: the program to run
+//! - `arg`: an argument, one per entry
+//! - `env`: `KEY=VALUE`, one per entry, set over the `env` of the session's
+//!   template
+//! - `cwd`: `worktree` (default) starts in the session's terminal directory,
+//!   `last` where the shell of the session's active terminal is
+//! - `launch`: `paste` (default) types the command line into a new shell,
+//!   the way a paste is typed; `spawn` runs the program as the terminal's
+//!   process, so the terminal ends with it
+//!
+//! Profiles are listed in definition order. A profile whose program cannot
+//! be found is still listed, with the reason, but cannot be launched. They
+//! are never read from a repository's `.sashikiconfig` (see
+//! `settings::USER_ONLY_KEYS`).
+
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::git;
+use crate::settings::Settings;
+use crate::shell_escape::Shell;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Where an agent starts (`cwd`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CwdMode {
+    /// The session's terminal directory: the template's working directory
+    /// or the worktree
+    #[default]
+    Worktree,
+    /// Where the shell of the session's active terminal is, falling back to
+    /// the terminal directory
+    Last,
+}
+
+impl CwdMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "worktree" => Some(Self::Worktree),
+            "last" => Some(Self::Last),
+            _ => None,
+        }
+    }
+}
+
+/// How an agent is started in its terminal (`launch`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// A shell starts and the command line is pasted into it and entered
+    #[default]
+    Paste,
+    /// The program is the terminal's process
+    Spawn,
+}
+
+impl LaunchMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "paste" => Some(Self::Paste),
+            "spawn" => Some(Self::Spawn),
+            _ => None,
+        }
+    }
+}
+
+/// A configured agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentProfile {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub cwd: CwdMode,
+    pub launch: LaunchMode,
+}
+
+/// The profiles of `settings`; one without a command is left out, and an
+/// unknown `cwd` or `launch` falls back to the default, with a warning
+pub fn load(settings: &Settings) -> Vec<AgentProfile> {
+    let warn = |message: String| {
+        app_log::record(LogEntry::new(LogLevel::Warn, LogSource::App, message));
+    };
+    let mut profiles = Vec::new();
+    for name in settings.subsection_names(git::CONFIG_AGENT_PREFIX) {
+        let key = |var: &str| git::agent_key(&name, var);
+        let Some(command) = settings.get(&key("command")) else {
+            warn(format!("Agent '{}' has no command and is left out", name));
+            continue;
+        };
+        let cwd = settings
+            .get(&key("cwd"))
+            .map_or(Some(CwdMode::default()), |v| CwdMode::parse(&v));
+        let launch = settings
+            .get(&key("launch"))
+            .map_or(Some(LaunchMode::default()), |v| LaunchMode::parse(&v));
+        if cwd.is_none() {
+            warn(format!("Agent '{}': cwd is worktree or last", name));
+        }
+        if launch.is_none() {
+            warn(format!("Agent '{}': launch is paste or spawn", name));
+        }
+        profiles.push(AgentProfile {
+            command,
+            args: settings.get_all(&key("arg")),
+            env: parse_env(&settings.get_all(&key("env"))),
+            cwd: cwd.unwrap_or_default(),
+            launch: launch.unwrap_or_default(),
+            name,
+        });
+    }
+    profiles
+}
+
+/// `KEY=VALUE` entries as pairs; entries without `=` or a key are left out
+pub fn parse_env(entries: &[String]) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (key, value) = entry.split_once('=')?;
+            let key = key.trim();
+            (!key.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// `base` with the variables of `over` set over it: a variable of both
+/// keeps its place in `base` and takes the value of `over`
+pub fn merge_env(base: &[(String, String)], over: &[(String, String)]) -> Vec<(String, String)> {
+    let mut merged: Vec<(String, String)> = Vec::new();
+    for (key, value) in base.iter().chain(over) {
+        match merged.iter_mut().find(|(k, _)| k == key) {
+            Some(existing) => existing.1 = value.clone(),
+            None => merged.push((key.clone(), value.clone())),
+        }
+    }
+    merged
+}
+
+/// What launching a profile starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub directory: PathBuf,
+    /// Run as the terminal's process (None = a shell)
+    pub program: Option<String>,
+    /// Pasted into the shell and entered
+    pub paste: Option<String>,
+    /// Set for the terminal on top of Sashiki's environment
+    pub env: Vec<(String, String)>,
+}
+
+impl AgentProfile {
+    /// Command and arguments as a shell command line
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.command)
+            .chain(&self.args)
+            .map(|word| Shell::Posix.escape(word))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Command and arguments as a terminal process. Terminals split their
+    /// program on whitespace, so an argument with whitespace needs `paste`.
+    pub fn spawn_command(&self) -> Result<String, String> {
+        let words: Vec<&str> = std::iter::once(&self.command)
+            .chain(&self.args)
+            .map(String::as_str)
+            .collect();
+        if let Some(word) = words
+            .iter()
+            .find(|w| w.is_empty() || w.contains(char::is_whitespace))
+        {
+            return Err(format!(
+                "Agent '{}': argument '{}' cannot be spawned; use launch = paste",
+                self.name, word
+            ));
+        }
+        Ok(words.join(" "))
+    }
+
+    /// Where the program is, or why it cannot be run
+    pub fn resolve(&self) -> Result<PathBuf, String> {
+        resolve_program(&self.command, std::env::var_os("PATH").as_deref())
+    }
+
+    /// The terminal to start for this profile in a session whose terminals
+    /// start in `terminal_dir`, whose active shell is in `last_dir`, and
+    /// whose template sets `template_env`
+    pub fn launch(
+        &self,
+        template_env: &[(String, String)],
+        terminal_dir: &Path,
+        last_dir: Option<&Path>,
+    ) -> Result<Launch, String> {
+        if let Some(word) = std::iter::once(&self.command)
+            .chain(&self.args)
+            .find(|w| w.contains(char::is_control))
+        {
+            return Err(format!(
+                "Agent '{}': '{}' has a line break or control character",
+                self.name,
+                word.escape_debug()
+            ));
+        }
+        let directory = match (self.cwd, last_dir) {
+            (CwdMode::Last, Some(dir)) => dir.to_path_buf(),
+            _ => terminal_dir.to_path_buf(),
+        };
+        let env = merge_env(template_env, &self.env);
+        Ok(match self.launch {
+            LaunchMode::Paste => Launch {
+                directory,
+                program: None,
+                paste: Some(self.command_line()),
+                env,
+            },
+            LaunchMode::Spawn => Launch {
+                directory,
+                program: Some(self.spawn_command()?),
+                paste: None,
+                env,
+            },
+        })
+    }
+}
+
+/// Where `program` is: itself when it is a path, else the first directory of
+/// `path` (a `PATH` value) holding an executable of that name
+pub fn resolve_program(program: &str, path: Option<&OsStr>) -> Result<PathBuf, String> {
+    if program.is_empty() {
+        return Err("No command set".to_string());
+    }
+    let given = Path::new(program);
+    if given.components().count() > 1 {
+        return if is_executable(given) {
+            Ok(given.to_path_buf())
+        } else {
+            Err(format!("{} is not an executable file", program))
+        };
+    }
+    path.into_iter()
+        .flat_map(std::env::split_paths)
+        .flat_map(|dir| candidates(&dir, program))
+        .find(|candidate| is_executable(candidate))
+        .ok_or_else(|| format!("{} not found on PATH", program))
+}
+
+#[cfg(windows)]
+fn candidates(dir: &Path, program: &str) -> Vec<PathBuf> {
+    vec![dir.join(program), dir.join(format!("{}.exe", program))]
+}
+
+#[cfg(not(windows))]
+fn candidates(dir: &Path, program: &str) -> Vec<PathBuf> {
+    vec![dir.join(program)]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(entries: &[(&str, &str)]) -> Settings {
+        Settings::from_layers(vec![
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ])
+    }
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn profile(launch: LaunchMode, args: &[&str]) -> AgentProfile {
+        AgentProfile {
+            name: "aider".to_string(),
+            command: "aider".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            env: env(&[("AIDER_MODEL", "sonnet")]),
+            cwd: CwdMode::Worktree,
+            launch,
+        }
+    }
+
+    #[test]
+    fn test_profiles_are_parsed_in_order() {
+        let settings = settings(&[
+            ("sashiki.agent.codex.command", "codex"),
+            ("sashiki.agent.codex.arg", "--full-auto"),
+            ("sashiki.agent.codex.launch", "spawn"),
+            ("sashiki.agent.broken.arg", "--no-command"),
+            ("sashiki.agent.aider.command", "aider"),
+            ("sashiki.agent.aider.env", "AIDER_MODEL=sonnet"),
+            ("sashiki.agent.aider.env", "NOT A PAIR"),
+            ("sashiki.agent.aider.env", "AIDER_ARGS=--a=b"),
+            ("sashiki.agent.aider.cwd", "Last"),
+            ("sashiki.agent.aider.launch", "later"),
+        ]);
+        let profiles = load(&settings);
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["codex", "aider"]);
+        assert_eq!(profiles[0].args, vec!["--full-auto"]);
+        assert_eq!(profiles[0].launch, LaunchMode::Spawn);
+        assert_eq!(profiles[0].cwd, CwdMode::Worktree);
+        assert_eq!(
+            profiles[1].env,
+            env(&[("AIDER_MODEL", "sonnet"), ("AIDER_ARGS", "--a=b")])
+        );
+        assert_eq!(profiles[1].cwd, CwdMode::Last);
+        assert_eq!(profiles[1].launch, LaunchMode::Paste);
+    }
+
+    #[test]
+    fn test_profile_env_is_set_over_the_template_env() {
+        let template = env(&[("NODE_ENV", "test"), ("AIDER_MODEL", "gpt"), ("CI", "1")]);
+        let launch = profile(LaunchMode::Paste, &[])
+            .launch(&template, Path::new("/w"), None)
+            .unwrap();
+        assert_eq!(
+            launch.env,
+            env(&[("NODE_ENV", "test"), ("AIDER_MODEL", "sonnet"), ("CI", "1")])
+        );
+        assert_eq!(
+            merge_env(&env(&[("A", "1"), ("A", "2")]), &env(&[("B", "3")])),
+            env(&[("A", "2"), ("B", "3")])
+        );
+    }
+
+    #[test]
+    fn test_paste_launch_quotes_the_command_line() {
+        let launch = profile(
+            LaunchMode::Paste,
+            &["--message", "fix the 'login' bug", "--yes"],
+        )
+        .launch(&[], Path::new("/w/app"), Some(Path::new("/w/app/src")))
+        .unwrap();
+        assert_eq!(launch.program, None);
+        assert_eq!(
+            launch.paste.as_deref(),
+            Some(r"aider --message 'fix the '\''login'\'' bug' --yes")
+        );
+        // cwd = worktree ignores where the shell is
+        assert_eq!(launch.directory, PathBuf::from("/w/app"));
+    }
+
+    #[test]
+    fn test_spawn_launch_runs_the_program() {
+        let mut agent = profile(LaunchMode::Spawn, &["--model", "sonnet"]);
+        agent.cwd = CwdMode::Last;
+        let launch = agent
+            .launch(&[], Path::new("/w"), Some(Path::new("/w/src")))
+            .unwrap();
+        assert_eq!(launch.program.as_deref(), Some("aider --model sonnet"));
+        assert_eq!(launch.paste, None);
+        assert_eq!(launch.directory, PathBuf::from("/w/src"));
+        // Without a known shell directory, the terminal directory
+        let launch = agent.launch(&[], Path::new("/w"), None).unwrap();
+        assert_eq!(launch.directory, PathBuf::from("/w"));
+
+        let spaced = profile(LaunchMode::Spawn, &["--message", "two words"]);
+        assert!(spaced.launch(&[], Path::new("/w"), None).is_err());
+        let multiline = profile(LaunchMode::Paste, &["one\ntwo"]);
+        assert!(multiline.launch(&[], Path::new("/w"), None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_program_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        let agent = bin.join("agent");
+        std::fs::write(&agent, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&agent, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(bin.join("notes"), "").unwrap();
+
+        let path = std::env::join_paths([dir.path().join("missing"), bin.clone()]).unwrap();
+        assert_eq!(
+            resolve_program("agent", Some(path.as_os_str())),
+            Ok(agent.clone())
+        );
+        assert_eq!(
+            resolve_program("notes", Some(path.as_os_str())),
+            Err("notes not found on PATH".to_string())
+        );
+        assert_eq!(
+            resolve_program(agent.to_str().unwrap(), None),
+            Ok(agent.clone())
+        );
+        assert!(resolve_program("agent", None).is_err());
+        assert!(
+            resolve_program(bin.join("notes").to_str().unwrap(), Some(path.as_os_str())).is_err()
+        );
+    }
+}
@@ -1,18 +1,90 @@
 //! SashikiApp core module
 
 mod actions;
+mod activity;
+mod adopt;
+mod agents;
+mod bell;
+mod bulk_create;
+mod checkpoints;
+mod code_blocks;
+mod colors;
+mod command_history;
+pub mod commands;
+mod compare;
+mod conflicts;
+mod control;
 mod dialogs;
+mod diff_stats;
+mod discard;
+mod export;
 mod file_ops;
+mod file_sync;
+mod git_lock;
+mod head_watch;
+mod health;
+mod layout;
+mod maintenance;
+mod merge;
+mod minimap;
+mod missing;
+mod multiplexer;
+mod navigation;
+mod nested_repos;
+mod notes;
+mod read_only;
+mod remote;
+mod repositories;
+mod resources;
+mod review;
+mod review_comments;
+mod scrollback;
+mod search;
+mod session_order;
+mod session_state;
+mod snapshots;
+mod squash;
+mod stash;
+mod tags;
+mod transcript;
 
-use crate::dialog::ActiveDialog;
-use crate::git::GitRepo;
-use crate::session::SessionManager;
-use crate::template::TemplateConfig;
+use activity::TimelinePanel;
+use agents::Agents;
+use checkpoints::CheckpointWatch;
+use diff_stats::DiffStatsPopover;
+use minimap::Minimaps;
+use notes::NotesPanel;
+use repositories::RepositoryState;
+use resources::Resources;
+use review::ReviewView;
+
+use crate::app_log::{self, LogEntry, LogFilter, LogLevel, LogSource};
+use crate::attention::NotificationSettings;
+use crate::bell::BellSettings;
+use crate::branch_name::BranchSuggester;
+use crate::bulk_create::BulkCreate;
+use crate::control::ControlServer;
+use crate::dialog::{ActiveDialog, OpenField, OpenMode};
+use crate::diff_job::{CancelToken, DiffRequests};
+use crate::dirty::DirtyThresholds;
+use crate::git::{self, GitRepo};
+use crate::git_queue::GitQueue;
+use crate::health::Problem;
+use crate::hooks::{self, HookEvent, HookSubject};
+use crate::layout::PanelLayout;
+use crate::maintenance::Maintenance;
+use crate::repositories::OpenRepositories;
+use crate::search::WorkspaceSearch;
+use crate::settings::{self, Settings, Startup};
+use crate::template::{CreationPlan, TemplateSet};
 use crate::terminal::TerminalView;
-use crate::ui::{FileListMode, FileTreeNode, FileView};
-use gpui::{AppContext, Context, Entity, FocusHandle};
-use std::collections::HashSet;
-use std::path::PathBuf;
+use crate::transcript::TranscriptBrowser;
+use crate::ui::virtual_rows::{ScrollAnchor, VisibleWindow};
+use crate::ui::{FileListMode, FileRow, FileView};
+use crate::window_title::DEFAULT_TITLE_FORMAT;
+use crate::worktree_guard;
+use gpui::{AppContext, Context, Entity, FocusHandle, ScrollHandle, Task};
+use std::path::{Path, PathBuf};
 
 pub use actions::*;
 
@@ -27,54 +99,215 @@ pub enum MenuId {
 /// Tracks which panel boundary is being dragged for resize
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum ResizeDrag {
-    Sidebar { start_x: f32, initial_width: f32 },
-    FileViewTerminal { start_y: f32, initial_height: f32 },
-    TerminalSplit { start_x: f32, initial_ratio: f32 },
-    FileList { start_x: f32, initial_width: f32 },
+    Sidebar {
+        start_x: f32,
+        initial_width: f32,
+    },
+    FileViewTerminal {
+        start_y: f32,
+        initial_height: f32,
+    },
+    /// Between the file view and a right-docked terminal
+    FileViewTerminalSide {
+        start_x: f32,
+        initial_width: f32,
+    },
+    TerminalSplit {
+        start_x: f32,
+        initial_ratio: f32,
+    },
+    FileList {
+        start_x: f32,
+        initial_width: f32,
+    },
 }
 
 /// Main application state
 pub struct SashikiApp {
-    pub(crate) session_manager: SessionManager,
-    pub(crate) changed_files: Vec<crate::git::ChangedFile>,
+    /// Repositories open in the window other than the shown one (see
+    /// `repositories`)
+    pub(crate) repositories: OpenRepositories<RepositoryState>,
+    /// Sessions, files and caches of the repository shown
+    pub(crate) repo: RepositoryState,
+    /// Files of untracked directories listed before the rest are counted
+    /// (`sashiki.changes.untrackedLimit`)
+    pub(crate) untracked_limit: usize,
+    /// Ages of uncommitted changes at which the sidebar flags a session
+    pub(crate) dirty_thresholds: DirtyThresholds,
     pub(crate) file_list_mode: FileListMode,
-    pub(crate) expanded_dirs: HashSet<PathBuf>,
-    pub(crate) file_tree: Option<FileTreeNode>,
+    /// Flattened file list as shown on screen (rebuilt every frame)
+    pub(crate) file_rows: Vec<FileRow>,
+    /// Rows of `file_rows` that get elements this frame
+    pub(crate) file_rows_window: VisibleWindow,
+    pub(crate) file_list_scroll: ScrollHandle,
+    pub(crate) file_list_anchor: ScrollAnchor,
+    /// Row of the file shown in the file view (or its nearest listed ancestor)
+    pub(crate) revealed_row: Option<PathBuf>,
+    /// Scroll `revealed_row` into view on the next frame
+    pub(crate) reveal_pending: bool,
+    /// Reviewed marks shown in the Changes list
+    pub(crate) review: ReviewView,
+    /// HEAD of the active worktree moved: the open diff is reloaded once the
+    /// changed files are read again
+    pub(crate) open_diff_stale: bool,
     pub(crate) file_view: Entity<FileView>,
-    pub(crate) git_repo: Option<GitRepo>,
-    /// Cached repo for active worktree (avoids repeated Repository::discover() calls)
-    pub(crate) cached_worktree: Option<(GitRepo, PathBuf)>,
     pub(crate) show_sidebar: bool,
     pub(crate) show_file_list: bool,
     pub(crate) show_file_view: bool,
+    /// Whether the log pane is shown below the terminals
+    pub(crate) show_log_pane: bool,
+    /// Session note shown below the terminals
+    pub(crate) notes: NotesPanel,
+    pub(crate) notes_focus: FocusHandle,
+    /// Activity timeline shown below the terminals
+    pub(crate) timeline: TimelinePanel,
+    /// Terminal thumbnails of the sessions not shown, for the sidebar
+    pub(crate) minimaps: Minimaps,
+    /// Memory and CPU of the sessions' processes, for the sidebar
+    pub(crate) resources: Resources,
+    /// Agent profiles and the launcher menu
+    pub(crate) agents: Agents,
+    /// When each session with automatic checkpoints is due for the next
+    pub(crate) checkpoints: CheckpointWatch,
+    /// HEAD polling, running while the shown repository has sessions
+    pub(crate) head_watch: Option<Task<()>>,
+    /// Thumbnail refresh, running while thumbnails are drawn
+    pub(crate) minimap_watch: Option<Task<()>>,
+    /// Checkpoint polling, running while a session takes checkpoints
+    pub(crate) checkpoint_watch: Option<Task<()>>,
+    /// Idle detection, running while a session has a terminal
+    pub(crate) activity_watch: Option<Task<()>>,
+    /// Resource readout refresh, running while the readout is on
+    pub(crate) resource_watch: Option<Task<()>>,
+    pub(crate) log_filter: LogFilter,
     pub(crate) active_dialog: ActiveDialog,
-    pub(crate) create_branch_input: String,
+    /// Text typed in the Open dialog (a path or a branch name, per `open_mode`)
+    pub(crate) open_input: String,
+    pub(crate) open_mode: OpenMode,
+    /// Task description typed in Branch mode, stored with the new session
+    pub(crate) open_description: String,
+    /// Commit, tag or branch a new worktree starts from (empty = default)
+    pub(crate) open_base: String,
+    /// Check out the base with a detached HEAD instead of on a branch
+    pub(crate) open_detach: bool,
+    /// Do not run git hooks when checking out the new worktree
+    pub(crate) open_skip_hooks: bool,
+    /// Field of the Open dialog that takes typing
+    pub(crate) open_field: OpenField,
+    /// Suggests the branch name from the description
+    pub(crate) branch_suggester: BranchSuggester,
+    /// Validation error shown inline in the Open dialog
+    pub(crate) open_error: Option<String>,
+    /// Whether Branch mode shows what creating the worktree would do
+    pub(crate) open_preview_expanded: bool,
+    /// Creation plan for the branch typed (None while it is worked out)
+    pub(crate) open_preview: Option<CreationPlan>,
+    /// Works out `open_preview`; replaced (and so cancelled) when the
+    /// branch changes
+    pub(crate) open_preview_task: Option<Task<()>>,
+    /// Format of the OS window title (`sashiki.window.titleFormat`)
+    pub(crate) window_title_format: String,
+    /// Title last given to the OS window
+    pub(crate) shown_window_title: Option<String>,
+    /// Ask before quitting or deleting while terminals are busy
+    /// (`sashiki.confirmBusySessions`, read with the other settings)
+    pub(crate) confirm_busy_sessions: bool,
     pub(crate) focus_handle: FocusHandle,
-    pub(crate) create_dialog_focus: FocusHandle,
-    /// Template config being edited in the settings dialog
-    pub(crate) template_edit: Option<TemplateConfig>,
+    pub(crate) open_dialog_focus: FocusHandle,
+    /// Templates being edited in the settings dialog
+    pub(crate) template_edit: Option<TemplateSet>,
+    /// Template shown in the settings dialog (index into the named ones,
+    /// None = the unnamed fallback)
+    pub(crate) template_selected: Option<usize>,
+    /// Validation error shown inline in the template settings dialog
+    pub(crate) template_error: Option<String>,
     /// Input fields for template settings dialog (one per section, newline-delimited)
-    pub(crate) settings_inputs: [String; 4],
+    pub(crate) settings_inputs: [String; 6],
     /// Cursor position (char index) per section
-    pub(crate) settings_cursors: [usize; 4],
-    /// Which section is active in settings (0=pre, 1=copy, 2=post, 3=workdir)
+    pub(crate) settings_cursors: [usize; 6],
+    /// Which section is active in settings (0=pre, 1=copy, 2=post, 3=workdir,
+    /// 4=name, 5=branch match; the last two only for named templates)
     pub(crate) settings_active_section: usize,
     pub(crate) settings_dialog_focus: FocusHandle,
     /// Which menu dropdown is currently open (None = all closed)
     pub(crate) open_menu: Option<MenuId>,
     /// Whether the verify terminal (2nd terminal) is shown in single mode
     pub(crate) show_verify_terminal: bool,
-    pub(crate) sidebar_width: f32,
-    pub(crate) file_view_height: f32,
-    pub(crate) terminal_split_ratio: f32,
-    pub(crate) file_list_width: f32,
+    /// Panel sizes, terminal dock and maximized state
+    pub(crate) layout: PanelLayout,
     pub(crate) resize_drag: Option<ResizeDrag>,
+    pub(crate) conflict_scan_running: bool,
+    pub(crate) sidebar_focus: FocusHandle,
+    pub(crate) stash_message_input: String,
+    pub(crate) stash_include_untracked: bool,
+    pub(crate) stash_dialog_focus: FocusHandle,
+    /// Commit message for the squash dialog
+    pub(crate) squash_message_input: String,
+    pub(crate) squash_dialog_focus: FocusHandle,
+    /// Comma-separated tags being edited in the session tags dialog
+    pub(crate) tags_input: String,
+    pub(crate) tags_dialog_focus: FocusHandle,
+    /// Text of the moment being marked in the activity timeline
+    pub(crate) moment_input: String,
+    pub(crate) moment_dialog_focus: FocusHandle,
+    pub(crate) comment_dialog_focus: FocusHandle,
+    /// Focus the comment dialog once it is shown (it is opened from file
+    /// view events, which come without a window)
+    pub(crate) focus_comment_dialog: bool,
+    pub(crate) review_dialog_focus: FocusHandle,
+    pub(crate) palette_focus: FocusHandle,
+    pub(crate) command_history_focus: FocusHandle,
+    /// Running diff prefetch (dropping it cancels the remaining files)
+    pub(crate) diff_prefetch: Option<Task<()>>,
+    /// Numbers the diffs computed for the file view, so stale ones are dropped
+    pub(crate) diff_requests: DiffRequests,
+    /// Diff of the open file being computed (see `diff_job`)
+    pub(crate) diff_job: Option<Task<()>>,
+    /// Worktree maintenance dialog state (sizes are cached across openings)
+    pub(crate) maintenance: Maintenance,
+    /// Running size scan or artifact search of the maintenance dialog
+    pub(crate) maintenance_scan: Option<Task<()>>,
+    /// Task list and progress of the bulk create dialog
+    pub(crate) bulk_create: BulkCreate,
+    pub(crate) bulk_create_focus: FocusHandle,
+    /// Focus of the dialog adding an existing worktree as a session
+    pub(crate) adopt_dialog_focus: FocusHandle,
+    /// Lines and search of the transcript dialog
+    pub(crate) transcript: TranscriptBrowser,
+    pub(crate) transcript_scroll: ScrollHandle,
+    pub(crate) transcript_dialog_focus: FocusHandle,
+    /// Transcript files being read (dropping it cancels the load)
+    pub(crate) transcript_load: Option<Task<()>>,
+    /// Query, options and hits of the workspace search dialog (kept across
+    /// openings)
+    pub(crate) search: WorkspaceSearch,
+    pub(crate) search_scroll: ScrollHandle,
+    pub(crate) search_dialog_focus: FocusHandle,
+    /// Receives the hits of the running search (dropping it stops receiving)
+    pub(crate) search_task: Option<Task<()>>,
+    /// Stops the running search on its worker
+    pub(crate) search_cancel: CancelToken,
+    /// Top changed files listed below the diff stats (None = closed)
+    pub(crate) diff_stats_popover: Option<DiffStatsPopover>,
+    pub(crate) diff_stats_focus: FocusHandle,
+    /// Running pull request lookup (dropping it cancels the update)
+    pub(crate) pr_scan: Option<Task<()>>,
+    /// Cleared once `gh` turned out to be missing, which stops the lookups
+    pub(crate) gh_available: bool,
+    /// Problems found by the last startup health check
+    pub(crate) health_problems: Vec<Problem>,
+    /// Running health check
+    pub(crate) health_check: Option<Task<()>>,
+    /// Whether the health panel was closed (until the next check)
+    pub(crate) health_dismissed: bool,
+    /// Local control server (see `crate::control`), when enabled
+    pub(crate) control_server: Option<ControlServer>,
 }
 
 impl SashikiApp {
     pub fn new(cx: &mut Context<Self>) -> Self {
         let focus_handle = cx.focus_handle();
-        let create_dialog_focus = cx.focus_handle();
+        let open_dialog_focus = cx.focus_handle();
         let file_view = cx.new(FileView::new);
 
         // Subscribe to SendToTerminalEvent from FileView
@@ -86,166 +319,498 @@ impl SashikiApp {
         )
         .detach();
 
-        let git_repo = GitRepo::open(".").ok();
-        let mut session_manager = SessionManager::new();
-        let mut active_dialog = ActiveDialog::None;
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::OpenExternalEvent, cx| {
+                this.open_externally(event.0.clone(), event.1, cx);
+            },
+        )
+        .detach();
 
-        if let Some(repo) = &git_repo {
-            if let Ok(worktrees) = repo.list_worktrees() {
-                if !worktrees.is_empty() {
-                    session_manager.init_from_worktrees(worktrees);
-                    let template = TemplateConfig::load(repo);
-                    session_manager.apply_terminal_default_directory_to_all(
-                        template.working_directory.as_deref(),
-                    );
-                    session_manager.ensure_session_terminal(0, cx);
-                    session_manager.switch_to(0);
-                } else {
-                    active_dialog = ActiveDialog::Error {
-                        message: "No worktrees found in repository".to_string(),
-                    };
-                }
-            } else {
-                active_dialog = ActiveDialog::Error {
-                    message: "Failed to list worktrees".to_string(),
-                };
-            }
-        } else {
-            active_dialog = ActiveDialog::Error {
-                message: "Git repository not found in current directory".to_string(),
-            };
-        }
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::DiscardHunkEvent, cx| {
+                this.open_discard_hunk_dialog(event.0.clone(), event.1.clone(), event.2, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::ConvertToUtf8Event, cx| {
+                this.convert_to_utf8(&event.0, event.1, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::ResolveConflictEvent, cx| {
+                this.write_conflict_resolution(&event.0, &event.1, event.2, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::MarkResolvedEvent, cx| {
+                this.mark_conflict_resolved(&event.0, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::OpenRepositoryEvent, cx| {
+                this.open_project(event.0.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::RevealInSidebarEvent, cx| {
+                this.reveal_in_file_list(&event.0);
+                cx.notify();
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::ForceFullDiffEvent, cx| {
+                this.force_full_diff(event.0.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::NavigateFileEvent, cx| {
+                this.step_changed_file(event.0, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::InsertReferenceEvent, cx| {
+                let path = this.display_path(&event.0);
+                let text = crate::ui::file_view::format_line_reference(&path, &event.1);
+                this.send_to_terminal(&text, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::CommentLineEvent, cx| {
+                this.open_comment_dialog(&event.0, event.1, event.2.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::EditCommentEvent, cx| {
+                this.edit_comment(event.0, event.1.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::DeleteCommentEvent, cx| {
+                this.delete_comment(event.0, cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::CompareWithSessionEvent, cx| {
+                this.open_compare_dialog(event.0.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::SwapComparisonEvent, cx| {
+                this.swap_comparison(event.0.clone(), cx);
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &file_view,
+            |this, _, event: &crate::ui::InsertSnippetEvent, cx| {
+                let path = this.display_path(&event.0);
+                let text = crate::ui::file_view::format_snippet(&path, &event.1);
+                this.paste_to_terminal(&text, cx);
+            },
+        )
+        .detach();
+
+        cx.on_app_quit(|app, cx| {
+            app.save_session_states();
+            app.save_scrollback(cx);
+            async {}
+        })
+        .detach();
 
         let mut app = Self {
-            session_manager,
-            changed_files: Vec::new(),
+            repositories: OpenRepositories::default(),
+            repo: RepositoryState::new(cx),
+            untracked_limit: git::DEFAULT_UNTRACKED_LIMIT,
+            dirty_thresholds: DirtyThresholds::default(),
             file_list_mode: FileListMode::default(),
-            expanded_dirs: HashSet::new(),
-            file_tree: None,
+            file_rows: Vec::new(),
+            file_rows_window: VisibleWindow::default(),
+            file_list_scroll: ScrollHandle::new(),
+            file_list_anchor: ScrollAnchor::default(),
+            revealed_row: None,
+            reveal_pending: false,
+            review: ReviewView::default(),
+            open_diff_stale: false,
             file_view,
-            git_repo,
-            cached_worktree: None,
             show_sidebar: true,
             show_file_list: true,
             show_file_view: false,
-            active_dialog,
-            create_branch_input: String::new(),
+            show_log_pane: false,
+            notes: NotesPanel::default(),
+            notes_focus: cx.focus_handle(),
+            timeline: TimelinePanel::default(),
+            minimaps: Minimaps::default(),
+            resources: Resources::default(),
+            agents: Agents::default(),
+            checkpoints: CheckpointWatch::default(),
+            head_watch: None,
+            minimap_watch: None,
+            checkpoint_watch: None,
+            activity_watch: None,
+            resource_watch: None,
+            log_filter: LogFilter::default(),
+            active_dialog: ActiveDialog::None,
+            open_input: String::new(),
+            open_mode: OpenMode::default(),
+            open_description: String::new(),
+            open_base: String::new(),
+            open_detach: false,
+            open_skip_hooks: false,
+            open_field: OpenField::default(),
+            branch_suggester: BranchSuggester::default(),
+            open_error: None,
+            open_preview_expanded: false,
+            open_preview: None,
+            open_preview_task: None,
+            confirm_busy_sessions: true,
+            window_title_format: DEFAULT_TITLE_FORMAT.to_string(),
+            shown_window_title: None,
             focus_handle,
-            create_dialog_focus,
+            open_dialog_focus,
             template_edit: None,
+            template_selected: None,
+            template_error: None,
             settings_inputs: Default::default(),
             settings_cursors: Default::default(),
             settings_active_section: 0,
             settings_dialog_focus: cx.focus_handle(),
             open_menu: None,
             show_verify_terminal: false,
-            sidebar_width: 224.0,
-            file_view_height: 384.0,
-            terminal_split_ratio: 0.5,
-            file_list_width: 256.0,
+            layout: PanelLayout::default(),
             resize_drag: None,
+            conflict_scan_running: false,
+            sidebar_focus: cx.focus_handle(),
+            stash_message_input: String::new(),
+            stash_include_untracked: false,
+            stash_dialog_focus: cx.focus_handle(),
+            squash_message_input: String::new(),
+            squash_dialog_focus: cx.focus_handle(),
+            tags_input: String::new(),
+            tags_dialog_focus: cx.focus_handle(),
+            moment_input: String::new(),
+            moment_dialog_focus: cx.focus_handle(),
+            comment_dialog_focus: cx.focus_handle(),
+            focus_comment_dialog: false,
+            review_dialog_focus: cx.focus_handle(),
+            palette_focus: cx.focus_handle(),
+            command_history_focus: cx.focus_handle(),
+            diff_prefetch: None,
+            diff_requests: DiffRequests::default(),
+            diff_job: None,
+            maintenance: Maintenance::default(),
+            maintenance_scan: None,
+            bulk_create: BulkCreate::default(),
+            bulk_create_focus: cx.focus_handle(),
+            adopt_dialog_focus: cx.focus_handle(),
+            transcript: TranscriptBrowser::default(),
+            transcript_scroll: ScrollHandle::new(),
+            transcript_dialog_focus: cx.focus_handle(),
+            transcript_load: None,
+            search: WorkspaceSearch::default(),
+            search_scroll: ScrollHandle::new(),
+            search_dialog_focus: cx.focus_handle(),
+            search_task: None,
+            search_cancel: CancelToken::default(),
+            diff_stats_popover: None,
+            diff_stats_focus: cx.focus_handle(),
+            pr_scan: None,
+            gh_available: true,
+            health_problems: Vec::new(),
+            health_check: None,
+            health_dismissed: false,
+            control_server: None,
         };
 
-        app.refresh_changed_files_sync();
-        app.build_file_tree();
+        let (user_settings, _) = Settings::load(None);
+        git::set_limits(user_settings.git_limits());
+        let startup = settings::resolve_startup(
+            settings::cli_args().path.as_deref(),
+            GitRepo::open(".").is_ok(),
+            user_settings.startup_mode(),
+            user_settings.last_repository().as_deref(),
+            &user_settings.open_repositories(),
+        );
+        match startup {
+            Startup::Open(path) => app.open_project(path, cx),
+            Startup::Reopen(paths) => {
+                for path in paths {
+                    app.open_project(path, cx);
+                }
+            }
+            Startup::Ask => app.active_dialog = ActiveDialog::Open,
+            Startup::Nothing => {
+                app.active_dialog =
+                    ActiveDialog::error("Git repository not found in current directory");
+            }
+        }
+        app.run_health_check(cx);
+        app.update_watches(cx);
+        Self::spawn_session_state_saver(cx);
         app
     }
 
+    /// Start the polling loops whose feature is in use and stop the others
+    pub(crate) fn update_watches(&mut self, cx: &mut Context<Self>) {
+        self.update_head_watch(cx);
+        self.update_minimap_watch(cx);
+        self.update_checkpoint_watch(cx);
+        self.update_activity_watch(cx);
+        self.update_resource_watch(cx);
+    }
+
     pub fn active_terminal(&self) -> Option<Entity<TerminalView>> {
-        self.session_manager.active_terminal()
+        self.repo.session_manager.active_terminal()
     }
 
     /// Send text to the active terminal
     pub fn send_to_terminal(&self, text: &str, cx: &mut Context<Self>) {
+        self.repo.session_manager.write_to_active_terminal(text, cx);
+    }
+
+    /// Type `path` into the active terminal: escaped for the shell at the
+    /// prompt, or as a `path` reference for a program such as an agent
+    pub fn insert_path(&self, path: &Path, cx: &mut Context<Self>) {
+        let path = path.to_string_lossy();
+        let text = match self
+            .repo
+            .session_manager
+            .active_session()
+            .and_then(|session| session.prompt_shell(cx))
+        {
+            Some(shell) => shell.escape(&path).into_owned(),
+            None => format!("`{}`", path),
+        };
+        self.send_to_terminal(&text, cx);
+    }
+
+    /// Paste text into the active terminal (bracketed when the program supports it)
+    pub fn paste_to_terminal(&self, text: &str, cx: &mut Context<Self>) {
         if let Some(terminal) = self.active_terminal() {
             terminal.update(cx, |view, _cx| {
-                view.write_text(text);
+                view.paste_text(text);
             });
         }
     }
 
-    /// Open a new project (Git repository) at the given path.
-    /// Shuts down all current terminals, resets state, and initializes from the new repo.
-    pub fn open_project(&mut self, path: PathBuf, cx: &mut Context<Self>) {
-        // 1. Shutdown all session terminals
-        for i in 0..self.session_manager.len() {
-            if let Some(terminal) = self.session_manager.get_session_active_terminal(i) {
-                terminal.update(cx, |view, _cx| view.shutdown());
+    /// Start a git operation queue that re-renders the app when it changes
+    fn spawn_git_queue(cx: &mut Context<Self>) -> GitQueue {
+        let (changed_tx, changed_rx) = smol::channel::bounded(1);
+        let queue = GitQueue::spawn(move || {
+            // A change already waiting to be shown covers this one too
+            let _ = changed_tx.try_send(());
+        });
+        cx.spawn(async move |entity, cx| {
+            while changed_rx.recv().await.is_ok() {
+                if entity.update(cx, |_, cx| cx.notify()).is_err() {
+                    break;
+                }
             }
-            self.session_manager.clear_session_terminals(i);
-        }
-
-        // 2. Close file view and reset verify terminal
-        self.file_view.update(cx, |view, _cx| view.close());
-        self.show_file_view = false;
-        self.show_verify_terminal = false;
+        })
+        .detach();
+        queue
+    }
 
-        // 3. Reset cached state
-        self.cached_worktree = None;
-        self.changed_files.clear();
-        self.expanded_dirs.clear();
-        self.file_tree = None;
+    /// `path` relative to the active worktree when it is inside it
+    fn display_path(&self, path: &Path) -> String {
+        self.repo
+            .session_manager
+            .active_session()
+            .and_then(|session| path.strip_prefix(session.worktree_path()).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
 
-        // 4. Open new repository
+    /// Open a project (Git repository) at the given path next to those
+    /// already open, and show it. The repository shown so far is set aside
+    /// with its terminals running; one already open is switched to.
+    pub fn open_project(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        if self.bulk_create_blocks_switch() {
+            return;
+        }
+        // 1. Open the repository
         let repo = match GitRepo::open(&path) {
             Ok(r) => r,
             Err(e) => {
-                self.active_dialog = crate::dialog::ActiveDialog::Error {
-                    message: format!("Failed to open repository: {}", e),
-                };
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to open repository: {}", e));
                 cx.notify();
                 return;
             }
         };
+        let root = settings::main_worktree_root(&repo);
+        if let Some(index) = self.repositories.position(&root) {
+            self.show_repository(index, cx);
+            return;
+        }
 
-        // 5. List worktrees and initialize sessions
+        // 2. List worktrees
         let worktrees = match repo.list_worktrees() {
             Ok(w) if !w.is_empty() => w,
             Ok(_) => {
-                self.active_dialog = crate::dialog::ActiveDialog::Error {
-                    message: "No worktrees found in repository".to_string(),
-                };
-                self.git_repo = Some(repo);
+                self.active_dialog = ActiveDialog::error("No worktrees found in repository");
                 cx.notify();
                 return;
             }
             Err(e) => {
-                self.active_dialog = crate::dialog::ActiveDialog::Error {
-                    message: format!("Failed to list worktrees: {}", e),
-                };
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to list worktrees: {}", e));
                 cx.notify();
                 return;
             }
         };
 
-        self.git_repo = Some(repo);
-        self.session_manager.init_from_worktrees(worktrees);
+        // 3. Set the shown repository aside and start afresh
+        self.add_repository(root, cx);
 
-        // 6. Apply template defaults
-        if let Some(ref repo) = self.git_repo {
-            let template = TemplateConfig::load(repo);
-            self.session_manager
-                .apply_terminal_default_directory_to_all(template.working_directory.as_deref());
+        // 4. Load settings and initialize sessions
+        let (settings, settings_warning) = Settings::load(Some(&repo));
+        git::set_limits(settings.git_limits());
+        for warning in worktree_guard::set_allowed_links(&settings) {
+            app_log::record(LogEntry::new(LogLevel::Warn, LogSource::App, warning));
         }
+        settings::record_last_repository(&repo);
+        let git_dir = repo.git_dir().to_path_buf();
+        self.repo.submodule_paths = repo.submodule_paths().into_iter().collect();
+        self.repo
+            .session_manager
+            .set_repository(repo.workdir().to_path_buf());
+        self.repo.git_repo = Some(repo);
+        self.repo.session_manager.init_from_worktrees(worktrees);
+        self.check_missing_worktrees(cx);
+        self.repo.session_manager.load_tags(&settings);
+        self.repo.session_manager.load_descriptions(&settings);
+        self.repo.session_manager.load_read_only(&settings);
+        self.repo
+            .session_manager
+            .load_transcripts(&settings, &git_dir);
+        self.repo
+            .session_manager
+            .load_scrollback(&settings, &git_dir);
+        self.repo.session_manager.load_session_states(&git_dir);
+        self.repo.session_manager.load_order(&git_dir);
+        self.repo.session_manager.load_bell_mutes(&settings);
+        self.repo.session_manager.load_auto_checkpoints(&settings);
+        self.repo.session_manager.load_colors(&settings);
+        self.repo.session_manager.load_shell_quoting(&settings);
+        self.checkpoints.apply_settings(&settings);
+        self.load_noted_sessions();
+        self.confirm_busy_sessions = settings.confirm_busy_sessions();
+        self.review.mark_on_leave = settings.review_mark_on_leave();
+        self.set_review_exclude(settings.review_exclude());
+        self.untracked_limit = settings.changes_untracked_limit();
+        self.dirty_thresholds = settings.dirty_thresholds();
+        self.search.limit = settings.search_max_results();
+        self.minimaps.set_enabled(settings.sidebar_minimap());
+        self.resources.set_enabled(settings.sidebar_resources());
+        self.update_watches(cx);
+        self.load_agents(&settings);
+        self.window_title_format = settings.window_title_format();
+        let tab_width = settings.viewer_tab_width();
+        let wrap_patterns = settings.viewer_wrap();
+        self.file_view.update(cx, |view, _cx| {
+            view.set_tab_width(tab_width);
+            view.set_wrap_patterns(wrap_patterns);
+        });
 
-        // 7. Start first session terminal
-        self.session_manager.ensure_session_terminal(0, cx);
-        self.session_manager.switch_to(0);
+        // 5. Apply settings and template defaults
+        self.repo
+            .session_manager
+            .apply_terminal_default_directory_to_all(&TemplateSet::from_settings(&settings));
+        self.repo
+            .session_manager
+            .set_terminal_shell(settings.terminal_shell());
+        self.repo
+            .session_manager
+            .set_layout_mode(settings.layout_mode());
+        self.repo
+            .session_manager
+            .set_notification_settings(NotificationSettings::from_settings(&settings));
+        self.repo
+            .session_manager
+            .set_bell_settings(BellSettings::from_settings(&settings));
+        self.apply_multiplexer(settings.terminal_multiplexer(), cx);
 
-        // 8. Refresh file list
+        // 6. Start the terminal of the first session in the sidebar
+        let first = self.repo.session_manager.session_at(0).unwrap_or(0);
+        self.repo.session_manager.ensure_session_terminal(first, cx);
+        self.repo.session_manager.switch_to(first);
+        self.update_activity_watch(cx);
+
+        // 7. Refresh file list, stashes and overlap detection
         self.refresh_changed_files_sync();
         self.build_file_tree();
+        self.refresh_stashes();
+        self.refresh_conflicts_async(true, cx);
+        self.load_remote(cx);
+
+        if let Some(repo) = &self.repo.git_repo {
+            hooks::configure(&settings, repo.workdir());
+            hooks::emit(
+                HookEvent::RepositoryOpened,
+                &HookSubject::repository(repo.workdir()),
+            );
+        }
+
+        // A broken settings file is reported, not fatal
+        if let Some(message) = settings_warning {
+            self.active_dialog = ActiveDialog::error(message);
+        }
 
         cx.notify();
     }
 
     pub(crate) fn apply_template_working_directory_defaults(&mut self) {
-        let relative = self
+        let templates = self
+            .repo
             .git_repo
             .as_ref()
-            .map(TemplateConfig::load)
-            .and_then(|t| t.working_directory);
-        self.session_manager
-            .apply_terminal_default_directory_to_all(relative.as_deref());
+            .map(TemplateSet::load)
+            .unwrap_or_default();
+        self.repo
+            .session_manager
+            .apply_terminal_default_directory_to_all(&templates);
     }
 }
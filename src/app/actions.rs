@@ -1,6 +1,8 @@
 //! Action definitions and event handlers
 
 use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::settings::Settings;
 use gpui::{Context, Focusable, Window, actions};
 
 actions!(
@@ -10,13 +12,70 @@ actions!(
         ToggleVerifyTerminal,
         NextSession,
         PrevSession,
+        MoveSessionUp,
+        MoveSessionDown,
+        SwitchToSession1,
+        SwitchToSession2,
+        SwitchToSession3,
+        SwitchToSession4,
+        SwitchToSession5,
+        SwitchToSession6,
+        SwitchToSession7,
+        SwitchToSession8,
+        SwitchToSession9,
         ToggleSidebar,
         ToggleFileList,
+        GrowTerminal,
+        ShrinkTerminal,
+        WidenSidebar,
+        NarrowSidebar,
+        ToggleTerminalDock,
+        ToggleMaximizeTerminal,
         RefreshAll,
         CreateWorktree,
+        CreateWorktrees,
+        AddExistingWorktree,
+        DeleteWorktree,
         CloseFileView,
         OpenFolder,
+        NextRepository,
+        PreviousRepository,
+        CloseRepository,
         Quit,
+        FocusSidebar,
+        CommandPalette,
+        SearchWorkspace,
+        RerunLastCommand,
+        RecentCommands,
+        CloseTerminal,
+        LaunchDefaultAgent,
+        ToggleDiffMode,
+        ToggleWordWrap,
+        CompareWithSession,
+        ToggleLog,
+        ToggleReadOnly,
+        ToggleBellMute,
+        ToggleAutoCheckpoint,
+        Checkpoints,
+        ToggleNotes,
+        ToggleTimeline,
+        MarkMoment,
+        TemplateSettings,
+        WorktreeMaintenance,
+        ExportState,
+        ShowDiffStats,
+        NextChangedFile,
+        PrevChangedFile,
+        ReviewComments,
+        CopyReview,
+        SaveReview,
+        SendReviewToTerminal,
+        SidebarUp,
+        SidebarDown,
+        SidebarActivate,
+        SidebarToggleDir,
+        SidebarToggleReviewed,
+        SidebarExit,
     ]
 );
 
@@ -27,7 +86,7 @@ impl SashikiApp {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.session_manager.toggle_layout_mode();
+        self.repo.session_manager.toggle_layout_mode();
         cx.notify();
     }
 
@@ -39,23 +98,31 @@ impl SashikiApp {
     ) {
         self.show_verify_terminal = !self.show_verify_terminal;
         if self.show_verify_terminal {
-            self.session_manager
+            self.repo
+                .session_manager
                 .ensure_active_session_terminal_count(2, cx);
+            self.update_activity_watch(cx);
         }
         cx.notify();
     }
 
     /// Start terminal for active session, focus it, and refresh file list
     pub fn activate_and_focus_session(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        self.session_manager.ensure_active_session_terminal(cx);
+        self.repo.session_manager.ensure_active_session_terminal(cx);
         if self.show_verify_terminal {
-            self.session_manager
+            self.repo
+                .session_manager
                 .ensure_active_session_terminal_count(2, cx);
         }
+        self.update_activity_watch(cx);
         if let Some(terminal) = self.active_terminal() {
             let focus = terminal.read(cx).focus_handle(cx);
             window.focus(&focus, cx);
         }
+        // Prefetched diffs of the previous session are no longer needed first
+        self.diff_prefetch = None;
+        self.cancel_diff_job(cx);
+        self.show_cached_files();
         self.refresh_file_list_async(cx);
         cx.notify();
     }
@@ -66,7 +133,7 @@ impl SashikiApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.session_manager.next_session();
+        self.repo.session_manager.next_session();
         self.activate_and_focus_session(window, cx);
     }
 
@@ -76,7 +143,7 @@ impl SashikiApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.session_manager.prev_session();
+        self.repo.session_manager.prev_session();
         self.activate_and_focus_session(window, cx);
     }
 
@@ -95,9 +162,187 @@ impl SashikiApp {
         cx.notify();
     }
 
+    pub fn on_toggle_log(&mut self, _: &ToggleLog, _: &mut Window, cx: &mut Context<Self>) {
+        self.show_log_pane = !self.show_log_pane;
+        cx.notify();
+    }
+
+    pub fn on_toggle_read_only(
+        &mut self,
+        _: &ToggleReadOnly,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_session_read_only(self.repo.session_manager.active_index(), cx);
+    }
+
+    pub fn on_toggle_bell_mute(
+        &mut self,
+        _: &ToggleBellMute,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_session_bell_mute(self.repo.session_manager.active_index(), cx);
+    }
+
+    pub fn on_toggle_auto_checkpoint(
+        &mut self,
+        _: &ToggleAutoCheckpoint,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_session_auto_checkpoint(self.repo.session_manager.active_index(), cx);
+    }
+
+    pub fn on_checkpoints(&mut self, _: &Checkpoints, _: &mut Window, cx: &mut Context<Self>) {
+        self.open_checkpoints_dialog(self.repo.session_manager.active_index(), cx);
+    }
+
+    pub fn on_toggle_notes(
+        &mut self,
+        _: &ToggleNotes,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_session_notes(self.repo.session_manager.active_index(), window, cx);
+    }
+
+    pub fn on_toggle_timeline(
+        &mut self,
+        _: &ToggleTimeline,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_session_timeline(self.repo.session_manager.active_index(), cx);
+    }
+
+    pub fn on_mark_moment(&mut self, _: &MarkMoment, window: &mut Window, cx: &mut Context<Self>) {
+        self.open_mark_moment_dialog(self.repo.session_manager.active_index(), window, cx);
+    }
+
+    pub fn on_toggle_diff_mode(
+        &mut self,
+        _: &ToggleDiffMode,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.file_view.update(cx, |view, cx| {
+            view.toggle_diff_display_mode();
+            cx.notify();
+        });
+    }
+
+    pub fn on_toggle_word_wrap(
+        &mut self,
+        _: &ToggleWordWrap,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.file_view.update(cx, |view, cx| {
+            view.toggle_wrap();
+            cx.notify();
+        });
+    }
+
+    /// Ctrl+Down/Up step through the changed files while one is open; the
+    /// keys go on to the terminal otherwise
+    pub fn on_next_changed_file(
+        &mut self,
+        _: &NextChangedFile,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.step_changed_file(true, cx) {
+            cx.propagate();
+        }
+    }
+
+    pub fn on_prev_changed_file(
+        &mut self,
+        _: &PrevChangedFile,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.step_changed_file(false, cx) {
+            cx.propagate();
+        }
+    }
+
+    pub fn on_create_worktree(
+        &mut self,
+        _: &CreateWorktree,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.repo.git_repo.is_some() {
+            self.open_create_dialog(window, cx);
+        }
+    }
+
+    /// Create a worktree for each branch of a task list
+    pub fn on_create_worktrees(
+        &mut self,
+        _: &CreateWorktrees,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_bulk_create_dialog(window, cx);
+    }
+
+    /// Ask to delete the active session's worktree
+    pub fn on_delete_worktree(
+        &mut self,
+        _: &DeleteWorktree,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let index = self.repo.session_manager.active_index();
+        self.open_delete_dialog(index, cx);
+    }
+
+    /// Quit, asking first while any session is busy (the global handler in
+    /// `main` quits when no window catches the action)
+    pub fn on_quit(&mut self, _: &Quit, _: &mut Window, cx: &mut Context<Self>) {
+        let busy = if self.confirm_busy_sessions {
+            self.repo.session_manager.busy_sessions(cx)
+        } else {
+            Vec::new()
+        };
+        if busy.is_empty() {
+            cx.quit();
+            return;
+        }
+        self.active_dialog = ActiveDialog::QuitConfirm { busy };
+        cx.notify();
+    }
+
+    pub fn on_template_settings(
+        &mut self,
+        _: &TemplateSettings,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_template_settings(window, cx);
+    }
+
+    pub fn on_worktree_maintenance(
+        &mut self,
+        _: &WorktreeMaintenance,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_maintenance_dialog(cx);
+    }
+
+    pub fn on_export_state(&mut self, _: &ExportState, _: &mut Window, cx: &mut Context<Self>) {
+        self.export_state(cx);
+    }
+
     pub fn on_refresh_all(&mut self, _: &RefreshAll, _: &mut Window, cx: &mut Context<Self>) {
         self.refresh_worktrees(cx);
         self.refresh_file_list_async(cx);
+        self.refresh_conflicts_async(true, cx);
+        self.refresh_pull_requests_async(true, cx);
         cx.notify();
     }
 
@@ -107,22 +352,24 @@ impl SashikiApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.session_manager.switch_to(index);
+        self.repo.session_manager.switch_to(index);
         self.activate_and_focus_session(window, cx);
     }
 
     pub fn on_toggle_parallel_visibility(&mut self, index: usize, cx: &mut Context<Self>) {
         let was_visible = self
+            .repo
             .session_manager
             .sessions()
             .get(index)
             .map(|s| s.is_visible_in_parallel())
             .unwrap_or(false);
 
-        self.session_manager.toggle_parallel_visibility(index);
+        self.repo.session_manager.toggle_parallel_visibility(index);
 
         if !was_visible {
-            self.session_manager.ensure_session_terminal(index, cx);
+            self.repo.session_manager.ensure_session_terminal(index, cx);
+            self.update_activity_watch(cx);
         }
         cx.notify();
     }
@@ -133,6 +380,7 @@ impl SashikiApp {
         _: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        self.leave_reviewed_file();
         self.show_file_view = false;
         self.file_view.update(cx, |view, _cx| {
             view.close();
@@ -141,12 +389,50 @@ impl SashikiApp {
     }
 
     pub fn refresh_worktrees(&mut self, cx: &mut Context<Self>) {
-        if let Some(ref repo) = self.git_repo
+        if let Some(ref repo) = self.repo.git_repo
             && let Ok(worktrees) = repo.list_worktrees()
         {
-            self.session_manager.sync_with_worktrees(worktrees);
+            self.repo.session_manager.sync_with_worktrees(worktrees);
+            self.check_missing_worktrees(cx);
+            let (settings, _) = Settings::load(Some(repo));
+            self.repo.session_manager.load_tags(&settings);
+            self.repo.session_manager.load_descriptions(&settings);
+            self.repo.session_manager.load_read_only(&settings);
+            self.repo
+                .session_manager
+                .load_transcripts(&settings, repo.git_dir());
+            self.repo
+                .session_manager
+                .load_scrollback(&settings, repo.git_dir());
+            self.repo
+                .session_manager
+                .load_session_states(repo.git_dir());
+            self.repo.session_manager.load_bell_mutes(&settings);
+            self.repo.session_manager.load_auto_checkpoints(&settings);
+            self.repo.session_manager.load_colors(&settings);
+            self.repo.session_manager.load_shell_quoting(&settings);
+            self.checkpoints.apply_settings(&settings);
+            self.load_noted_sessions();
+            self.confirm_busy_sessions = settings.confirm_busy_sessions();
+            self.review.mark_on_leave = settings.review_mark_on_leave();
+            self.set_review_exclude(settings.review_exclude());
+            self.untracked_limit = settings.changes_untracked_limit();
+            self.dirty_thresholds = settings.dirty_thresholds();
+            self.search.limit = settings.search_max_results();
+            self.minimaps.set_enabled(settings.sidebar_minimap());
+            self.resources.set_enabled(settings.sidebar_resources());
+            self.update_watches(cx);
+            self.load_agents(&settings);
+            self.window_title_format = settings.window_title_format();
+            let tab_width = settings.viewer_tab_width();
+            let wrap_patterns = settings.viewer_wrap();
+            self.file_view.update(cx, |view, _cx| {
+                view.set_tab_width(tab_width);
+                view.set_wrap_patterns(wrap_patterns);
+            });
             self.apply_template_working_directory_defaults();
         }
+        self.forget_removed_worktrees();
         cx.notify();
     }
 }
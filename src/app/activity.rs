@@ -0,0 +1,149 @@
+//! The activity timeline panel below the terminals, the "Mark moment"
+//! dialog, and the watch that notices sessions going idle (see
+//! `crate::activity`)
+
+use super::SashikiApp;
+use crate::activity::{ACTIVITY_POLL_INTERVAL, ActivityEvent};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::session_state;
+use gpui::{Context, Focusable, Task, Window};
+
+/// Timeline being shown below the terminals
+#[derive(Debug, Default)]
+pub(crate) struct TimelinePanel {
+    /// Worktree name of the session whose timeline is open (None = closed)
+    pub(crate) session: Option<String>,
+}
+
+impl SashikiApp {
+    /// Watch for idle sessions only while a session has a terminal
+    pub(crate) fn update_activity_watch(&mut self, cx: &mut Context<Self>) {
+        let wanted = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .any(|session| session.has_terminals());
+        if wanted != self.activity_watch.is_some() {
+            self.activity_watch = wanted.then(|| Self::spawn_activity_watch(cx));
+        }
+    }
+
+    /// Feed each session's output age to its timeline every
+    /// `ACTIVITY_POLL_INTERVAL` until the task is dropped
+    fn spawn_activity_watch(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(ACTIVITY_POLL_INTERVAL).await;
+                let Ok(()) = entity.update(cx, |app, cx| {
+                    for session in app.repo.session_manager.sessions() {
+                        session.events().output_age(session.output_age(cx));
+                    }
+                    if app.timeline.session.is_some() {
+                        cx.notify();
+                    }
+                }) else {
+                    break;
+                };
+            }
+        })
+    }
+
+    /// Show the timeline of the session at `index`, or close the panel when
+    /// it already shows that timeline
+    pub fn toggle_session_timeline(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(name) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .map(|s| s.name().to_string())
+        else {
+            return;
+        };
+        if self.timeline.session.as_deref() == Some(name.as_str()) {
+            self.timeline.session = None;
+        } else {
+            self.timeline.session = Some(name);
+        }
+        cx.notify();
+    }
+
+    pub fn close_timeline(&mut self, cx: &mut Context<Self>) {
+        self.timeline.session = None;
+        cx.notify();
+    }
+
+    /// Delete the state file of the session named `name` (its worktree is
+    /// being deleted)
+    pub(crate) fn remove_session_state(&mut self, name: &str) {
+        if self.timeline.session.as_deref() == Some(name) {
+            self.timeline.session = None;
+        }
+        if let Some(session) = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .find(|s| s.name() == name)
+        {
+            session.state().discard();
+        }
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        if let Err(e) =
+            session_state::remove_state(&session_state::state_path(repo.git_dir(), name))
+        {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::App,
+                format!("Failed to delete the session state of {}: {}", name, e),
+            ));
+        }
+    }
+
+    /// Ask for a note to put in the timeline of the session at `index`
+    pub fn open_mark_moment_dialog(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if index >= self.repo.session_manager.sessions().len() {
+            return;
+        }
+        self.moment_input.clear();
+        self.active_dialog = ActiveDialog::MarkMoment {
+            target_index: index,
+        };
+        window.focus(&self.moment_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_mark_moment_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        self.moment_input.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Record the typed note in the session's timeline
+    pub fn submit_mark_moment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::MarkMoment { target_index } = self.active_dialog else {
+            return;
+        };
+        let text = self.moment_input.trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        if let Some(session) = self.repo.session_manager.sessions().get(target_index) {
+            session.events().record(ActivityEvent::Note { text });
+        }
+        self.close_mark_moment_dialog(window, cx);
+    }
+}
@@ -0,0 +1,152 @@
+//! Adding worktrees created outside Sashiki as sessions (see
+//! `GitRepo::linked_worktree`)
+
+use super::SashikiApp;
+use super::actions::AddExistingWorktree;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::git::{LinkedWorktreeError, Worktree};
+use gpui::{Context, Focusable, Window};
+use std::path::Path;
+
+impl SashikiApp {
+    pub fn on_add_existing_worktree(
+        &mut self,
+        _: &AddExistingWorktree,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(repo) = &self.repo.git_repo else {
+            return;
+        };
+        let unsessioned = repo
+            .list_worktrees()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|w| {
+                self.repo
+                    .session_manager
+                    .find_session_by_path(&w.path)
+                    .is_none()
+            })
+            .collect();
+        self.active_dialog = ActiveDialog::AddExistingWorktree {
+            unsessioned,
+            input: String::new(),
+            error: None,
+            other_repository: None,
+        };
+        window.focus(&self.adopt_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_adopt_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Change the typed directory; a previous error no longer applies
+    pub fn edit_adopt_input(&mut self, edit: impl FnOnce(&mut String), cx: &mut Context<Self>) {
+        if let ActiveDialog::AddExistingWorktree {
+            input,
+            error,
+            other_repository,
+            ..
+        } = &mut self.active_dialog
+        {
+            edit(input);
+            *error = None;
+            *other_repository = None;
+            cx.notify();
+        }
+    }
+
+    /// Add the typed directory as a session if it is a worktree of this
+    /// repository
+    pub fn submit_adopt_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::AddExistingWorktree { input, .. } = &self.active_dialog else {
+            return;
+        };
+        let input = input.trim().to_string();
+        let Some(repo) = self.repo.git_repo.clone() else {
+            return;
+        };
+        let (message, other) = if input.is_empty() {
+            ("Enter the path of a worktree".to_string(), None)
+        } else {
+            match repo.linked_worktree(Path::new(&input)) {
+                Ok(worktree) => {
+                    self.adopt_worktree(worktree, window, cx);
+                    return;
+                }
+                Err(LinkedWorktreeError::OtherRepository(path)) => (
+                    format!(
+                        "{} is not a worktree of this repository. It belongs to {}, \
+                         which can be opened as a separate repository instead.",
+                        input,
+                        path.display()
+                    ),
+                    Some(path),
+                ),
+                Err(e) => (e.to_string(), None),
+            }
+        };
+        if let ActiveDialog::AddExistingWorktree {
+            error,
+            other_repository,
+            ..
+        } = &mut self.active_dialog
+        {
+            *error = Some(message);
+            *other_repository = other;
+        }
+        cx.notify();
+    }
+
+    /// Make a session of `worktree` (git is left as it is) and switch to it
+    pub fn adopt_worktree(
+        &mut self,
+        worktree: Worktree,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let path = worktree.path.clone();
+        let label = worktree
+            .branch
+            .clone()
+            .unwrap_or_else(|| worktree.name.clone());
+        self.close_adopt_dialog(window, cx);
+        if self.repo.session_manager.add_session(worktree) {
+            app_log::record(LogEntry::new(
+                LogLevel::Info,
+                LogSource::Git,
+                format!(
+                    "Added the worktree at {} as session {}",
+                    path.display(),
+                    label
+                ),
+            ));
+        }
+        if let Some(index) = self.repo.session_manager.find_session_by_path(&path) {
+            self.on_session_selected(index, window, cx);
+        }
+    }
+
+    /// Open the repository the typed directory belongs to instead
+    pub fn open_other_repository(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::AddExistingWorktree {
+            other_repository: Some(path),
+            ..
+        } = &self.active_dialog
+        else {
+            return;
+        };
+        let path = path.clone();
+        self.close_adopt_dialog(window, cx);
+        self.open_project(path, cx);
+    }
+}
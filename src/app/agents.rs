@@ -0,0 +1,217 @@
+//! Launching agent profiles into sessions (see `crate::agents`)
+//!
+//! The launcher menu opens from the terminal header or a session's sidebar
+//! entry and lists the profiles; launching one adds a terminal to the
+//! session. The profile last launched in a session is kept in git config
+//! (`sashiki.session.<name>.agent`) and is what "Launch Default Agent" runs.
+
+use super::SashikiApp;
+use super::actions::LaunchDefaultAgent;
+use crate::agents::{self, AgentProfile};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::git;
+use crate::session::LayoutMode;
+use crate::settings::Settings;
+use crate::template::TemplateSet;
+use gpui::{Context, Focusable, Pixels, Point, Window};
+use std::collections::HashMap;
+
+/// Agent profiles and what was launched with them
+#[derive(Debug, Default)]
+pub(crate) struct Agents {
+    /// Profiles in definition order, with why each cannot be launched (None
+    /// = it can)
+    pub(crate) profiles: Vec<(AgentProfile, Option<String>)>,
+    /// Profile last launched, by worktree name
+    last: HashMap<String, String>,
+    /// Session the launcher menu is open for, and where it is drawn
+    pub(crate) menu: Option<(usize, Point<Pixels>)>,
+}
+
+impl Agents {
+    /// Profile last launched in the session of `worktree_name`
+    pub(crate) fn last_used(&self, worktree_name: &str) -> Option<&str> {
+        self.last.get(worktree_name).map(String::as_str)
+    }
+
+    /// The profile "Launch Default Agent" runs in the session of
+    /// `worktree_name`: the last one launched there, else the first that can
+    /// be launched
+    fn default_for(&self, worktree_name: &str) -> Option<&AgentProfile> {
+        let launchable = |profile: &&(AgentProfile, Option<String>)| profile.1.is_none();
+        self.last_used(worktree_name)
+            .and_then(|name| self.profiles.iter().find(|(p, _)| p.name == name))
+            .or_else(|| self.profiles.iter().find(launchable))
+            .map(|(profile, _)| profile)
+    }
+}
+
+impl SashikiApp {
+    /// Load the profiles, find their programs and read the profile last
+    /// launched in each session
+    pub(crate) fn load_agents(&mut self, settings: &Settings) {
+        self.agents.profiles = agents::load(settings)
+            .into_iter()
+            .map(|profile| {
+                let unavailable = profile.resolve().err();
+                (profile, unavailable)
+            })
+            .collect();
+        self.agents.last = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .filter_map(|session| {
+                let agent = settings.session_agent(session.name())?;
+                Some((session.name().to_string(), agent))
+            })
+            .collect();
+    }
+
+    /// Open the launcher menu of session `index` at `position`, or close it
+    /// when it is open there already
+    pub fn toggle_agent_menu(
+        &mut self,
+        index: usize,
+        position: Point<Pixels>,
+        cx: &mut Context<Self>,
+    ) {
+        self.agents.menu = match self.agents.menu {
+            Some((open, _)) if open == index => None,
+            _ => Some((index, position)),
+        };
+        cx.notify();
+    }
+
+    pub fn close_agent_menu(&mut self, cx: &mut Context<Self>) {
+        self.agents.menu = None;
+        cx.notify();
+    }
+
+    pub fn on_launch_default_agent(
+        &mut self,
+        _: &LaunchDefaultAgent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let index = self.repo.session_manager.active_index();
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return;
+        };
+        let Some(name) = self
+            .agents
+            .default_for(session.name())
+            .map(|profile| profile.name.clone())
+        else {
+            self.active_dialog = ActiveDialog::error(if self.agents.profiles.is_empty() {
+                "No agent profiles are configured (sashiki.agent.<name>.command)".to_string()
+            } else {
+                "None of the agent profiles can be launched".to_string()
+            });
+            cx.notify();
+            return;
+        };
+        self.launch_agent(index, &name, window, cx);
+    }
+
+    /// Open a terminal in session `index` running the profile `name`, show
+    /// it and remember the profile for the session
+    pub fn launch_agent(
+        &mut self,
+        index: usize,
+        name: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.agents.menu = None;
+        let Some((profile, unavailable)) = self
+            .agents
+            .profiles
+            .iter()
+            .find(|(profile, _)| profile.name == name)
+            .cloned()
+        else {
+            return;
+        };
+        let Some(session) = self.repo.session_manager.sessions().get(index) else {
+            return;
+        };
+        if let Some(reason) = unavailable {
+            self.active_dialog =
+                ActiveDialog::error(format!("Cannot launch agent '{}': {}", name, reason));
+            cx.notify();
+            return;
+        }
+
+        let template_env = self
+            .repo
+            .git_repo
+            .as_ref()
+            .map(|repo| {
+                let templates = TemplateSet::load(repo);
+                agents::parse_env(&templates.for_branch(session.branch()).env)
+            })
+            .unwrap_or_default();
+        let last_dir = session
+            .active_terminal()
+            .and_then(|terminal| terminal.read(cx).shell_directory());
+        let launch = match profile.launch(
+            &template_env,
+            &session.terminal_directory(),
+            last_dir.as_deref(),
+        ) {
+            Ok(launch) => launch,
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(e);
+                cx.notify();
+                return;
+            }
+        };
+        let worktree_name = session.name().to_string();
+
+        let Some(terminal) = self.repo.session_manager.add_session_terminal_running(
+            index,
+            launch.directory,
+            launch.program,
+            launch.env,
+            cx,
+        ) else {
+            return;
+        };
+        self.update_activity_watch(cx);
+        if let Some(command) = &launch.paste {
+            terminal.update(cx, |view, _cx| view.paste_command(command));
+        }
+        app_log::record(LogEntry::new(
+            LogLevel::Info,
+            LogSource::Terminal,
+            format!("Launched agent {} in {}", name, worktree_name),
+        ));
+
+        if let Some(repo) = &self.repo.git_repo
+            && self.agents.last_used(&worktree_name) != Some(name)
+        {
+            if let Err(e) = repo.set_config_value(&git::session_agent_key(&worktree_name), name) {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::App,
+                    format!("Failed to remember the agent of {}: {}", worktree_name, e),
+                ));
+            }
+            self.agents.last.insert(worktree_name, name.to_string());
+        }
+
+        match self.repo.session_manager.layout_mode() {
+            LayoutMode::Single if index != self.repo.session_manager.active_index() => {
+                self.on_session_selected(index, window, cx);
+            }
+            LayoutMode::Parallel => self.repo.session_manager.show_in_parallel(index),
+            LayoutMode::Single => {}
+        }
+        let focus = terminal.read(cx).focus_handle(cx);
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+}
@@ -0,0 +1,36 @@
+//! Per-session bell mute, for agents that ring the terminal bell constantly
+//! (see `crate::bell`)
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::git;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Mute or unmute the terminal bell of the session at `index` and store
+    /// the choice in the repository config
+    pub fn toggle_session_bell_mute(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(repo), Some(session)) = (
+            self.repo.git_repo.as_ref(),
+            self.repo.session_manager.sessions().get(index),
+        ) else {
+            return;
+        };
+        let muted = !session.is_bell_muted();
+        let key = git::session_bell_muted_key(session.name());
+        let result = if muted {
+            repo.set_config_value(&key, "true")
+        } else {
+            repo.remove_config_key(&key)
+        };
+
+        match result {
+            Ok(()) => session.set_bell_muted(muted),
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to save the bell setting: {}", e));
+            }
+        }
+        cx.notify();
+    }
+}
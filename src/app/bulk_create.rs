@@ -0,0 +1,324 @@
+//! Creating a worktree for each branch of a task list (see
+//! `crate::bulk_create`)
+//!
+//! Each worktree goes through the pipeline of a single one (template
+//! commands and file copies included) and becomes a session as soon as it
+//! exists. The worktrees are checked out through the git queue one at a
+//! time, and the next one only starts when the one before is done.
+
+use super::SashikiApp;
+use crate::activity::ActivityEvent;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::bulk_create::{self, BulkCreate, BulkRun, PlannedWorktree};
+use crate::dialog::ActiveDialog;
+use crate::git::GitRepo;
+use crate::settings::Settings;
+use crate::template::{self, TemplateConfig, TemplateSet};
+use gpui::{Context, Focusable, Window};
+use std::path::PathBuf;
+
+impl SashikiApp {
+    /// Ask for the branches to create worktrees for
+    pub fn open_bulk_create_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        self.bulk_create = BulkCreate {
+            branches: repo.local_branches().unwrap_or_default(),
+            ..BulkCreate::default()
+        };
+        self.active_dialog = ActiveDialog::BulkCreate;
+        window.focus(&self.bulk_create_focus, cx);
+        cx.notify();
+    }
+
+    /// Close the dialog; while worktrees are being created this only
+    /// cancels the ones not started
+    pub fn close_bulk_create_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(run) = self.bulk_create.run.as_mut()
+            && !run.is_finished()
+        {
+            run.cancel();
+            cx.notify();
+            return;
+        }
+        self.active_dialog = ActiveDialog::None;
+        self.bulk_create = BulkCreate::default();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Whether worktrees are still being created, which keeps the shown
+    /// repository from being switched or closed: the run adds its sessions
+    /// to the repository shown. Logged when it does.
+    pub(crate) fn bulk_create_blocks_switch(&self) -> bool {
+        let running = self
+            .bulk_create
+            .run
+            .as_ref()
+            .is_some_and(|run| !run.is_finished());
+        if running {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::App,
+                "Worktrees are still being created: the repository stays until they are done \
+                 or cancelled",
+            ));
+        }
+        running
+    }
+
+    /// Insert typed or pasted text at the end of the task list
+    pub fn insert_bulk_create_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        if self.bulk_create.run.is_some() {
+            return;
+        }
+        self.bulk_create.input.push_str(text);
+        self.replan_bulk_create(cx);
+    }
+
+    pub fn delete_bulk_create_char(&mut self, cx: &mut Context<Self>) {
+        if self.bulk_create.run.is_some() {
+            return;
+        }
+        self.bulk_create.input.pop();
+        self.replan_bulk_create(cx);
+    }
+
+    pub fn toggle_bulk_create_terminals(&mut self, cx: &mut Context<Self>) {
+        self.bulk_create.start_terminals = !self.bulk_create.start_terminals;
+        cx.notify();
+    }
+
+    /// Check the task list against the branches and worktrees in use
+    fn replan_bulk_create(&mut self, cx: &mut Context<Self>) {
+        let repo = self.repo.git_repo.as_ref();
+        let sessions = self.repo.session_manager.sessions();
+        self.bulk_create.plan = bulk_create::plan(
+            &self.bulk_create.input,
+            &self.bulk_create.branches,
+            |name| {
+                sessions.iter().any(|s| s.name() == name)
+                    || repo
+                        .and_then(|repo| repo.generate_worktree_path(name))
+                        .is_some_and(|path| path.exists())
+            },
+        );
+        cx.notify();
+    }
+
+    /// Create the planned worktrees one after the other
+    pub fn start_bulk_create(&mut self, cx: &mut Context<Self>) {
+        if self.bulk_create.run.is_some() {
+            return;
+        }
+        // Planned again right before starting: a branch or directory may
+        // have appeared since the last keystroke
+        if let Some(repo) = &self.repo.git_repo {
+            self.bulk_create.branches = repo.local_branches().unwrap_or_default();
+        }
+        self.replan_bulk_create(cx);
+        if !self.bulk_create.plan.is_ready() {
+            return;
+        }
+        let worktrees = self.bulk_create.plan.worktrees.clone();
+        self.bulk_create.run = Some(BulkRun::new(worktrees));
+
+        cx.spawn(async move |entity, cx| {
+            loop {
+                let Ok(Some((next, job))) = entity.update(cx, |app, cx| {
+                    let next = app.bulk_create.run.as_mut()?.start_next()?;
+                    cx.notify();
+                    let job = app.bulk_create_job(&next);
+                    Some((next, job))
+                }) else {
+                    break;
+                };
+                let result = match job {
+                    Some(job) => Self::run_bulk_create_job(&entity, cx, next, job).await,
+                    None => Err("Git repository not available".to_string()),
+                };
+                let updated = entity.update(cx, |app, cx| {
+                    if let Some(run) = app.bulk_create.run.as_mut() {
+                        run.finish(result);
+                    }
+                    cx.notify();
+                });
+                if updated.is_err() {
+                    return;
+                }
+            }
+            let _ = entity.update(cx, |app, cx| {
+                if let Some(run) = &app.bulk_create.run {
+                    app_log::record(LogEntry::new(
+                        LogLevel::Info,
+                        LogSource::Git,
+                        format!("Bulk create: {}", run.summary()),
+                    ));
+                }
+                app.refresh_conflicts_async(true, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// What creating `worktree` needs from the app (None without a
+    /// repository)
+    fn bulk_create_job(&self, worktree: &PlannedWorktree) -> Option<BulkCreateJob> {
+        let repo = self.repo.git_repo.as_ref()?;
+        let templates = TemplateSet::load(repo);
+        let template = templates
+            .matching(&worktree.branch)
+            .map(|t| t.config.clone())
+            .unwrap_or_else(|| templates.fallback.clone());
+        Some(BulkCreateJob {
+            main_workdir: repo.workdir().to_path_buf(),
+            git_dir: repo.git_dir().to_path_buf(),
+            path: repo.generate_worktree_path(&worktree.name)?,
+            template,
+            start_terminals: self.bulk_create.start_terminals,
+        })
+    }
+
+    /// Pre-create commands, worktree, file copies and post-create commands
+    /// of one worktree; the session is added as soon as the worktree exists
+    async fn run_bulk_create_job(
+        entity: &gpui::WeakEntity<Self>,
+        cx: &mut gpui::AsyncApp,
+        worktree: PlannedWorktree,
+        job: BulkCreateJob,
+    ) -> Result<(), String> {
+        let BulkCreateJob {
+            main_workdir,
+            git_dir,
+            path,
+            template,
+            start_terminals,
+        } = job;
+
+        for cmd in &template.pre_create_commands {
+            let cmd = cmd.clone();
+            let workdir = main_workdir.clone();
+            smol::unblock(move || template::run_shell_command(&cmd, &workdir))
+                .await
+                .map_err(|e| format!("Pre-create command failed: {}", e))?;
+        }
+
+        let queue = entity
+            .update(cx, |app, _| app.repo.git_queue.clone())
+            .map_err(|e| e.to_string())?;
+        let created = {
+            let wp = path.clone();
+            let mw = main_workdir.clone();
+            let PlannedWorktree { branch, name } = worktree;
+            queue
+                .push(
+                    format!("Create worktree {}", branch),
+                    Some(&path),
+                    move || {
+                        if let Some(parent) = wp.parent() {
+                            std::fs::create_dir_all(parent)
+                                .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+                        }
+                        let repo = GitRepo::from_parts(mw.clone(), git_dir.clone());
+                        repo.create_worktree_with(
+                            &name,
+                            Some(&branch),
+                            &wp,
+                            None,
+                            false,
+                            &mut |line| {
+                                app_log::record(LogEntry::new(
+                                    LogLevel::Info,
+                                    LogSource::Git,
+                                    format!("worktree add {}: {}", branch, line),
+                                ));
+                            },
+                        )
+                        .map_err(|e| format!("Failed to create worktree: {}", e))
+                    },
+                )
+                .wait()
+                .await
+                .unwrap_or_else(|| Err("Worktree creation was cancelled".to_string()))?
+        };
+
+        let workdir = template.resolve_working_directory(&path);
+        entity
+            .update(cx, |app, cx| {
+                // A repository opened meanwhile has its own sessions
+                if app.bulk_create.run.is_none() {
+                    return;
+                }
+                if app.repo.session_manager.add_session(created)
+                    && let Some(session) = app.repo.session_manager.sessions().last()
+                {
+                    session.events().record(ActivityEvent::WorktreeCreated);
+                }
+                if let Some(repo) = &app.repo.git_repo {
+                    let (settings, _) = Settings::load(Some(repo));
+                    app.repo.session_manager.load_descriptions(&settings);
+                    app.repo.session_manager.load_session_states(repo.git_dir());
+                }
+                cx.notify();
+            })
+            .map_err(|e| e.to_string())?;
+
+        if !template.file_copies.is_empty() {
+            let dst = path.clone();
+            let tmpl = template.clone();
+            let copy_results = smol::unblock(move || tmpl.copy_files(&main_workdir, &dst)).await;
+            // Copy errors are non-fatal, as for a single worktree
+            for result in copy_results.iter().filter(|r| !r.success) {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::Template,
+                    format!(
+                        "File copy failed for {}: {}",
+                        result.path,
+                        result.error.as_deref().unwrap_or("unknown error")
+                    ),
+                ));
+            }
+        }
+
+        for cmd in &template.post_create_commands {
+            let cmd = cmd.clone();
+            let dir = workdir.clone();
+            smol::unblock(move || template::run_shell_command(&cmd, &dir))
+                .await
+                .map_err(|e| format!("Post-create command failed: {}", e))?;
+        }
+
+        // Terminals start once the template is done with the worktree, as
+        // for a single one
+        if start_terminals {
+            let _ = entity.update(cx, |app, cx| {
+                if let Some(index) = app.repo.session_manager.find_session_by_path(&path) {
+                    app.repo.session_manager.show_in_parallel(index);
+                    app.repo
+                        .session_manager
+                        .ensure_session_terminal_in(index, workdir, cx);
+                    app.update_activity_watch(cx);
+                    cx.notify();
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Everything creating one worktree of a bulk create needs
+struct BulkCreateJob {
+    main_workdir: PathBuf,
+    git_dir: PathBuf,
+    /// Where the worktree goes
+    path: PathBuf,
+    template: TemplateConfig,
+    /// Show the session in parallel mode with its terminal started
+    start_terminals: bool,
+}
@@ -0,0 +1,174 @@
+//! Automatic checkpoints of the sessions that have them on (see
+//! `crate::checkpoints`)
+
+use super::SashikiApp;
+use crate::activity::unix_now;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::checkpoints::{CHECKPOINT_POLL_INTERVAL, CheckpointSchedule, checkpoint_message};
+use crate::dialog::ActiveDialog;
+use crate::git::{self, GitRepo};
+use crate::settings::Settings;
+use gpui::{Context, Task};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Checkpoint schedules of the sessions, by worktree name
+#[derive(Debug)]
+pub(crate) struct CheckpointWatch {
+    schedules: HashMap<String, CheckpointSchedule>,
+    interval: Duration,
+    keep: usize,
+}
+
+impl Default for CheckpointWatch {
+    fn default() -> Self {
+        Self {
+            schedules: HashMap::new(),
+            interval: Duration::from_secs(git::DEFAULT_CHECKPOINT_INTERVAL),
+            keep: git::DEFAULT_CHECKPOINT_KEEP,
+        }
+    }
+}
+
+impl CheckpointWatch {
+    pub(crate) fn apply_settings(&mut self, settings: &Settings) {
+        self.interval = settings.checkpoint_interval();
+        self.keep = settings.checkpoint_keep();
+    }
+}
+
+impl SashikiApp {
+    /// Poll for checkpoints only while a session of the shown repository
+    /// takes them
+    pub(crate) fn update_checkpoint_watch(&mut self, cx: &mut Context<Self>) {
+        let wanted = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .any(|s| s.auto_checkpoint());
+        if wanted == self.checkpoint_watch.is_some() {
+            return;
+        }
+        // Sessions start over when checkpoints are taken again
+        self.checkpoints.schedules.clear();
+        self.checkpoint_watch = wanted.then(|| Self::spawn_checkpoint_watch(cx));
+    }
+
+    /// Check every `CHECKPOINT_POLL_INTERVAL` whether a session is due for a
+    /// checkpoint, until the task is dropped
+    fn spawn_checkpoint_watch(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(CHECKPOINT_POLL_INTERVAL).await;
+                let Ok(()) = entity.update(cx, |app, cx| app.poll_checkpoints(cx)) else {
+                    break;
+                };
+            }
+        })
+    }
+
+    /// Start a checkpoint in the background for each session that is due
+    fn poll_checkpoints(&mut self, cx: &mut Context<Self>) {
+        let now = Instant::now();
+        let interval = self.checkpoints.interval;
+        let keep = self.checkpoints.keep;
+        let mut due = Vec::new();
+        for session in self.repo.session_manager.sessions() {
+            if !session.auto_checkpoint() {
+                continue;
+            }
+            let files = self
+                .repo
+                .file_cache
+                .get(session.worktree_path())
+                .map_or(0, |files| files.changed_files.len());
+            let schedule = self
+                .checkpoints
+                .schedules
+                .entry(session.name().to_string())
+                .or_default();
+            if schedule.poll(now, interval, files, session.output_age(cx)) {
+                schedule.start(now, files);
+                due.push((
+                    session.name().to_string(),
+                    session.worktree_path().to_path_buf(),
+                    session.branch().unwrap_or(session.name()).to_string(),
+                    files,
+                ));
+            }
+        }
+        // Sessions without checkpoints start over when they get them back
+        let sessions = self.repo.session_manager.sessions();
+        self.checkpoints.schedules.retain(|name, _| {
+            sessions
+                .iter()
+                .any(|s| s.auto_checkpoint() && s.name() == name)
+        });
+
+        for (name, worktree, branch, files) in due {
+            cx.spawn(async move |entity, cx| {
+                let result = smol::unblock(move || {
+                    let repo = GitRepo::open(&worktree)?;
+                    let created =
+                        repo.create_checkpoint(&branch, &checkpoint_message(unix_now(), files))?;
+                    repo.prune_checkpoints(&branch, keep)?;
+                    Ok::<_, git::GitError>(created)
+                })
+                .await;
+                let _ = entity.update(cx, |app, _cx| {
+                    if let Some(schedule) = app.checkpoints.schedules.get_mut(&name) {
+                        schedule.finish();
+                    }
+                    let entry = match result {
+                        Ok(Some(refname)) => LogEntry::new(
+                            LogLevel::Info,
+                            LogSource::Git,
+                            format!("Checkpoint {}", refname),
+                        ),
+                        Ok(None) => return,
+                        Err(e) => LogEntry::new(
+                            LogLevel::Warn,
+                            LogSource::Git,
+                            format!("Failed to take a checkpoint of {}: {}", name, e),
+                        ),
+                    };
+                    app_log::record(entry);
+                });
+            })
+            .detach();
+        }
+    }
+
+    /// Turn automatic checkpoints of the session at `index` on or off and
+    /// store the choice in the repository config
+    pub fn toggle_session_auto_checkpoint(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        let Some(session) = self.repo.session_manager.sessions().get(index) else {
+            return;
+        };
+        let enabled = !session.auto_checkpoint();
+        let key = git::session_auto_checkpoint_key(session.name());
+        let result = if enabled {
+            repo.set_config_value(&key, "true")
+        } else {
+            repo.remove_config_key(&key)
+        };
+
+        match result {
+            Ok(()) => {
+                self.repo
+                    .session_manager
+                    .set_session_auto_checkpoint(index, enabled);
+                self.update_checkpoint_watch(cx);
+            }
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to save the checkpoint setting: {}", e));
+            }
+        }
+        cx.notify();
+    }
+}
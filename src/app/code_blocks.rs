@@ -0,0 +1,93 @@
+//! Applying a code block picked in a terminal's overlay (see
+//! `crate::code_blocks`): the diff it makes is shown, and the file written
+//! once it is accepted. A block that cannot be applied is copied instead.
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::code_blocks::Suggestion;
+use crate::dialog::ActiveDialog;
+use crate::terminal::ApplyCodeBlock;
+use gpui::{ClipboardItem, Context, Window};
+
+impl SashikiApp {
+    pub fn on_apply_code_block(
+        &mut self,
+        _: &ApplyCodeBlock,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return;
+        };
+        let worktree = session.worktree_path().to_path_buf();
+        let terminals: Vec<_> = (0..session.terminal_count())
+            .filter_map(|index| session.get_terminal(index).cloned())
+            .collect();
+        let Some(block) = terminals
+            .iter()
+            .find_map(|terminal| terminal.update(cx, |view, _cx| view.take_chosen_code_block()))
+        else {
+            return;
+        };
+        cx.spawn(async move |entity, cx| {
+            let text = block.text.clone();
+            let prepared = smol::unblock(move || Suggestion::prepare(&worktree, &block)).await;
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.active_dialog = match prepared {
+                    Ok(suggestion) => ActiveDialog::ApplyCodeBlock { suggestion },
+                    Err(e) => {
+                        cx.write_to_clipboard(ClipboardItem::new_string(text));
+                        ActiveDialog::error(format!(
+                            "Cannot apply the code block: {}. It was copied to the clipboard.",
+                            e
+                        ))
+                    }
+                };
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    pub fn close_code_block_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Write the file of the dialog's code block
+    pub fn confirm_apply_code_block(&mut self, cx: &mut Context<Self>) {
+        let suggestion = match std::mem::take(&mut self.active_dialog) {
+            ActiveDialog::ApplyCodeBlock { suggestion } => suggestion,
+            other => {
+                self.active_dialog = other;
+                return;
+            }
+        };
+        let index = self.repo.session_manager.active_index();
+        if !self.ensure_writable(index, "apply a code block", cx) {
+            return;
+        }
+        match suggestion.write() {
+            Ok(()) => app_log::record(LogEntry::new(
+                LogLevel::Info,
+                LogSource::App,
+                format!(
+                    "Applied a code block to {}: {}",
+                    suggestion.path.display(),
+                    suggestion.edit.describe().to_lowercase()
+                ),
+            )),
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!(
+                    "Failed to apply the code block to {}: {}",
+                    suggestion.path.display(),
+                    e
+                ));
+            }
+        }
+        self.refresh_file_list();
+        cx.notify();
+    }
+}
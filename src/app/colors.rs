@@ -0,0 +1,40 @@
+//! Session colors picked by the user in place of the ones derived from the
+//! worktree name (see `crate::session::SessionColor`)
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::git;
+use crate::session::SessionColor;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Use `color` for the session at `index` and store it in the repository
+    /// config; None goes back to the color derived from its name
+    pub fn set_session_color(
+        &mut self,
+        index: usize,
+        color: Option<SessionColor>,
+        cx: &mut Context<Self>,
+    ) {
+        let (Some(repo), Some(session)) = (
+            self.repo.git_repo.as_ref(),
+            self.repo.session_manager.sessions().get(index),
+        ) else {
+            return;
+        };
+        let key = git::session_color_key(session.name());
+        let result = match color {
+            Some(color) => repo.set_config_value(&key, &color.to_hex()),
+            None => repo.remove_config_key(&key),
+        };
+
+        match result {
+            Ok(()) => self.repo.session_manager.set_session_color(index, color),
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to save the session color: {}", e));
+            }
+        }
+        cx.notify();
+    }
+}
@@ -0,0 +1,105 @@
+//! Rerunning recent commands of the active terminal (see
+//! `crate::command_history`)
+
+use super::SashikiApp;
+use super::actions::{RecentCommands, RerunLastCommand};
+use crate::dialog::ActiveDialog;
+use gpui::{ClipboardItem, Context, Focusable, Window};
+
+impl SashikiApp {
+    /// Run the active terminal's last command again, or show the (empty)
+    /// history with why it has nothing to rerun
+    pub fn on_rerun_last_command(
+        &mut self,
+        _: &RerunLastCommand,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(terminal) = self.active_terminal() else {
+            return;
+        };
+        match terminal.read(cx).recent_commands().into_iter().next() {
+            Some(command) => terminal.update(cx, |view, _cx| view.run_command(&command)),
+            None => self.open_command_history(window, cx),
+        }
+    }
+
+    pub fn on_recent_commands(
+        &mut self,
+        _: &RecentCommands,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if matches!(self.active_dialog, ActiveDialog::CommandHistory { .. }) {
+            self.close_command_history(window, cx);
+        } else {
+            self.open_command_history(window, cx);
+        }
+    }
+
+    fn open_command_history(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.active_terminal().is_none() {
+            return;
+        }
+        self.open_menu = None;
+        self.active_dialog = ActiveDialog::CommandHistory { selected: 0 };
+        window.focus(&self.command_history_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_command_history(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Commands of the active terminal, newest first
+    pub fn recent_commands(&self, cx: &Context<Self>) -> Vec<String> {
+        self.active_terminal()
+            .map(|terminal| terminal.read(cx).recent_commands())
+            .unwrap_or_default()
+    }
+
+    pub fn move_command_history_selection(&mut self, delta: isize, cx: &mut Context<Self>) {
+        let count = self.recent_commands(cx).len();
+        if let ActiveDialog::CommandHistory { selected } = &mut self.active_dialog
+            && count > 0
+        {
+            *selected = (*selected as isize + delta).rem_euclid(count as isize) as usize;
+            cx.notify();
+        }
+    }
+
+    /// Run the command at `index` (newest first) in the active terminal
+    pub fn run_history_command(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(command) = self.recent_commands(cx).into_iter().nth(index) else {
+            return;
+        };
+        self.close_command_history(window, cx);
+        if let Some(terminal) = self.active_terminal() {
+            terminal.update(cx, |view, _cx| view.run_command(&command));
+        }
+    }
+
+    /// Put the command at `index` (newest first) on the clipboard
+    pub fn copy_history_command(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(command) = self.recent_commands(cx).into_iter().nth(index) else {
+            return;
+        };
+        cx.write_to_clipboard(ClipboardItem::new_string(command));
+        self.close_command_history(window, cx);
+    }
+}
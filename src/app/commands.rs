@@ -0,0 +1,668 @@
+//! Command registry and the command palette
+//!
+//! Every app-level action is listed once in `COMMANDS` with its title,
+//! category, default key binding and menu. The global key bindings, both
+//! menu bars and the command palette are built from it, so they cannot
+//! drift apart. Running a command dispatches its action, which is the same
+//! path a key binding or menu item takes.
+
+use super::actions::*;
+use super::{MenuId, SashikiApp};
+use crate::dialog::ActiveDialog;
+use crate::layout::TerminalDock;
+use crate::session::LayoutMode;
+use gpui::{Action, App, Context, Focusable, KeyBinding, MenuItem, SharedString, Window};
+
+/// An app action as listed in menus and the command palette
+pub struct Command {
+    /// Stable identifier
+    pub id: &'static str,
+    pub title: &'static str,
+    pub category: &'static str,
+    /// Default key binding (gpui keystroke syntax)
+    pub key: Option<&'static str>,
+    pub menu: Option<MenuId>,
+    /// Whether a separator goes above the command in its menu
+    pub separated: bool,
+    action: fn() -> Box<dyn Action>,
+    binding: fn(&str) -> KeyBinding,
+    menu_item: fn(&'static str) -> MenuItem,
+    /// Why the command cannot run right now (None = available)
+    unavailable: fn(&SashikiApp, &App) -> Option<&'static str>,
+}
+
+fn boxed<A: Action + Default>() -> Box<dyn Action> {
+    Box::new(A::default())
+}
+
+fn binding<A: Action + Default>(key: &str) -> KeyBinding {
+    KeyBinding::new(key, A::default(), None)
+}
+
+fn menu_item<A: Action + Default>(title: &'static str) -> MenuItem {
+    MenuItem::action(title, A::default())
+}
+
+const fn command<A: Action + Default>(
+    id: &'static str,
+    title: &'static str,
+    category: &'static str,
+) -> Command {
+    Command {
+        id,
+        title,
+        category,
+        key: None,
+        menu: None,
+        separated: false,
+        action: boxed::<A>,
+        binding: binding::<A>,
+        menu_item: menu_item::<A>,
+        unavailable: |_, _| None,
+    }
+}
+
+impl Command {
+    const fn key(mut self, key: &'static str) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    const fn menu(mut self, menu: MenuId) -> Self {
+        self.menu = Some(menu);
+        self
+    }
+
+    const fn separated(mut self) -> Self {
+        self.separated = true;
+        self
+    }
+
+    const fn unavailable_when(
+        mut self,
+        unavailable: fn(&SashikiApp, &App) -> Option<&'static str>,
+    ) -> Self {
+        self.unavailable = unavailable;
+        self
+    }
+
+    pub fn action(&self) -> Box<dyn Action> {
+        (self.action)()
+    }
+
+    /// Key binding as shown in menus ("Ctrl+Shift+E")
+    pub fn key_label(&self) -> Option<String> {
+        self.key.map(key_label)
+    }
+
+    pub fn unavailable(&self, app: &SashikiApp, cx: &App) -> Option<&'static str> {
+        (self.unavailable)(app, cx)
+    }
+}
+
+fn requires_repo(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    app.repo.git_repo.is_none().then_some("No repository open")
+}
+
+fn single_repository(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    (app.repositories.len() < 2).then_some("No other repository open")
+}
+
+/// All app commands, in menu order
+pub const COMMANDS: &[Command] = &[
+    command::<CommandPalette>("command_palette", "Command Palette...", "View")
+        .key("ctrl-shift-p")
+        .menu(MenuId::View),
+    command::<TemplateSettings>("template_settings", "Template Settings...", "Repository")
+        .menu(MenuId::App)
+        .unavailable_when(requires_repo),
+    command::<Quit>("quit", "Quit", "App")
+        .menu(MenuId::App)
+        .separated(),
+    command::<OpenFolder>("open_folder", "Open Folder...", "Repository")
+        .key("ctrl-o")
+        .menu(MenuId::File),
+    command::<NextRepository>("next_repository", "Next Repository", "Repository")
+        .key("ctrl-alt-o")
+        .unavailable_when(single_repository),
+    command::<PreviousRepository>("previous_repository", "Previous Repository", "Repository")
+        .key("ctrl-alt-shift-o")
+        .unavailable_when(single_repository),
+    command::<CloseRepository>("close_repository", "Close Repository", "Repository")
+        .menu(MenuId::File)
+        .unavailable_when(requires_repo),
+    command::<CreateWorktree>("create_worktree", "Create Worktree...", "Worktree")
+        .menu(MenuId::File)
+        .unavailable_when(requires_repo),
+    command::<CreateWorktrees>("create_worktrees", "Create Worktrees...", "Worktree")
+        .menu(MenuId::File)
+        .unavailable_when(requires_repo),
+    command::<AddExistingWorktree>(
+        "add_existing_worktree",
+        "Add Existing Worktree...",
+        "Worktree",
+    )
+    .menu(MenuId::File)
+    .unavailable_when(requires_repo),
+    command::<DeleteWorktree>("delete_worktree", "Delete Worktree...", "Worktree")
+        .menu(MenuId::File)
+        .unavailable_when(|app, cx| {
+            requires_repo(app, cx).or_else(|| {
+                app.repo
+                    .session_manager
+                    .active_session()
+                    .is_none_or(|session| session.is_main())
+                    .then_some("The main worktree cannot be deleted")
+            })
+        }),
+    command::<WorktreeMaintenance>(
+        "worktree_maintenance",
+        "Worktree Maintenance...",
+        "Worktree",
+    )
+    .menu(MenuId::File)
+    .separated()
+    .unavailable_when(requires_repo),
+    command::<ExportState>("export_state", "Export State...", "Repository")
+        .menu(MenuId::File)
+        .unavailable_when(requires_repo),
+    command::<ToggleSidebar>("toggle_sidebar", "Toggle Sidebar", "View")
+        .key("ctrl-b")
+        .menu(MenuId::View)
+        .separated(),
+    command::<ToggleFileList>("toggle_file_list", "Toggle File List", "View")
+        .key("ctrl-e")
+        .menu(MenuId::View),
+    command::<FocusSidebar>("focus_sidebar", "Focus Sidebar", "View")
+        .key("ctrl-shift-e")
+        .menu(MenuId::View),
+    command::<ToggleParallelMode>("toggle_parallel", "Toggle Parallel", "View")
+        .key("ctrl-p")
+        .menu(MenuId::View),
+    command::<ToggleTerminalDock>("toggle_terminal_dock", "Toggle Terminal Dock", "View")
+        .key("ctrl-shift-\\")
+        .menu(MenuId::View)
+        .unavailable_when(|app, _| {
+            (app.repo.session_manager.layout_mode() == LayoutMode::Parallel)
+                .then_some("The file view is hidden in parallel mode")
+        }),
+    command::<ToggleMaximizeTerminal>(
+        "toggle_maximize_terminal",
+        "Toggle Maximize Terminal",
+        "View",
+    )
+    .key("ctrl-shift-m")
+    .menu(MenuId::View),
+    command::<GrowTerminal>("grow_terminal", "Grow Terminal", "View")
+        .key("ctrl-alt-up")
+        .unavailable_when(|app, _| {
+            (app.layout.dock != TerminalDock::Bottom).then_some("The terminal is docked right")
+        }),
+    command::<ShrinkTerminal>("shrink_terminal", "Shrink Terminal", "View")
+        .key("ctrl-alt-down")
+        .unavailable_when(|app, _| {
+            (app.layout.dock != TerminalDock::Bottom).then_some("The terminal is docked right")
+        }),
+    command::<WidenSidebar>("widen_sidebar", "Widen Sidebar", "View").key("ctrl-alt-right"),
+    command::<NarrowSidebar>("narrow_sidebar", "Narrow Sidebar", "View").key("ctrl-alt-left"),
+    command::<ToggleVerifyTerminal>(
+        "toggle_verify_terminal",
+        "Toggle Verify Terminal",
+        "Terminal",
+    )
+    .key("ctrl-t")
+    .menu(MenuId::View),
+    command::<RerunLastCommand>("rerun_last_command", "Rerun Last Command", "Terminal")
+        .key("ctrl-shift-r")
+        .menu(MenuId::View)
+        .unavailable_when(no_terminal),
+    command::<RecentCommands>("recent_commands", "Recent Commands...", "Terminal")
+        .key("ctrl-shift-h")
+        .menu(MenuId::View)
+        .unavailable_when(no_terminal),
+    command::<CloseTerminal>("close_terminal", "Close Terminal", "Terminal")
+        .unavailable_when(no_terminal),
+    command::<LaunchDefaultAgent>("launch_default_agent", "Launch Default Agent", "Terminal")
+        .key("ctrl-shift-l")
+        .menu(MenuId::View)
+        .unavailable_when(|app, cx| {
+            requires_repo(app, cx).or_else(|| {
+                app.agents
+                    .profiles
+                    .is_empty()
+                    .then_some("No agent profiles are configured")
+            })
+        }),
+    command::<ToggleLog>("toggle_log", "Toggle Log", "View").menu(MenuId::View),
+    command::<ToggleNotes>("toggle_notes", "Toggle Session Notes", "Session")
+        .key("ctrl-shift-n")
+        .menu(MenuId::View)
+        .unavailable_when(requires_repo),
+    command::<ToggleTimeline>("toggle_timeline", "Toggle Activity Timeline", "Session")
+        .menu(MenuId::View)
+        .unavailable_when(requires_repo),
+    command::<MarkMoment>("mark_moment", "Mark Moment...", "Session")
+        .unavailable_when(requires_repo),
+    command::<ShowDiffStats>("show_diff_stats", "Show Changed Files by Size", "View")
+        .menu(MenuId::View)
+        .unavailable_when(|app, _| {
+            app.top_churn_files()
+                .is_empty()
+                .then_some("No changes since HEAD")
+        }),
+    command::<SearchWorkspace>("search_workspace", "Search in Worktree...", "Repository")
+        .key("ctrl-shift-g")
+        .menu(MenuId::View)
+        .unavailable_when(requires_repo),
+    command::<RefreshAll>("refresh_all", "Refresh All", "Repository")
+        .key("ctrl-r")
+        .menu(MenuId::View)
+        .separated(),
+    command::<NextSession>("next_session", "Next Session", "Session")
+        .key("ctrl-tab")
+        .unavailable_when(single_session),
+    command::<PrevSession>("prev_session", "Previous Session", "Session")
+        .key("ctrl-shift-tab")
+        .unavailable_when(single_session),
+    command::<MoveSessionUp>("move_session_up", "Move Session Up", "Session")
+        .key("ctrl-alt-shift-up")
+        .unavailable_when(single_session),
+    command::<MoveSessionDown>("move_session_down", "Move Session Down", "Session")
+        .key("ctrl-alt-shift-down")
+        .unavailable_when(single_session),
+    command::<ToggleReadOnly>("toggle_read_only", "Toggle Read-Only", "Session")
+        .unavailable_when(requires_repo),
+    command::<ToggleBellMute>("toggle_bell_mute", "Toggle Bell Mute", "Session")
+        .unavailable_when(requires_repo),
+    command::<ToggleAutoCheckpoint>(
+        "toggle_auto_checkpoint",
+        "Toggle Automatic Checkpoints",
+        "Session",
+    )
+    .unavailable_when(requires_repo),
+    command::<Checkpoints>("checkpoints", "Checkpoints...", "Session")
+        .unavailable_when(requires_repo),
+    command::<ToggleDiffMode>("toggle_diff_mode", "Toggle Split/Inline Diff", "File View")
+        .unavailable_when(|app, cx| {
+            (!app.file_view.read(cx).is_diff_mode()).then_some("No diff is open")
+        }),
+    command::<ToggleWordWrap>("toggle_word_wrap", "Toggle Word Wrap", "File View")
+        .key("alt-z")
+        .unavailable_when(|app, cx| {
+            (!app.show_file_view || !app.file_view.read(cx).can_wrap())
+                .then_some("No text file is open")
+        }),
+    command::<CompareWithSession>(
+        "compare_with_session",
+        "Compare File with Session...",
+        "File View",
+    )
+    .unavailable_when(|app, cx| {
+        if !app.show_file_view || app.file_view.read(cx).file_path().is_none() {
+            Some("No file is open")
+        } else if app.repo.session_manager.sessions().len() < 2 {
+            Some("No other session")
+        } else {
+            None
+        }
+    }),
+    command::<NextChangedFile>("next_changed_file", "Next Changed File", "File View")
+        .key("ctrl-down")
+        .unavailable_when(no_changed_file_open),
+    command::<PrevChangedFile>("prev_changed_file", "Previous Changed File", "File View")
+        .key("ctrl-up")
+        .unavailable_when(no_changed_file_open),
+    command::<ReviewComments>("review_comments", "Review Comments...", "File View")
+        .unavailable_when(no_review_comments),
+    command::<CopyReview>("copy_review", "Copy Review", "File View")
+        .unavailable_when(no_review_comments),
+    command::<SaveReview>("save_review", "Save Review...", "File View")
+        .unavailable_when(no_review_comments),
+    command::<SendReviewToTerminal>(
+        "send_review_to_terminal",
+        "Send Review to Terminal",
+        "File View",
+    )
+    .unavailable_when(|app, cx| no_review_comments(app, cx).or_else(|| no_terminal(app, cx))),
+    command::<CloseFileView>("close_file_view", "Close File View", "File View")
+        .key("escape")
+        .unavailable_when(|app, _| (!app.show_file_view).then_some("No file is open")),
+];
+
+fn no_changed_file_open(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    if !app.show_file_view {
+        Some("No file is open")
+    } else if app.repo.changed_files.is_empty() {
+        Some("No changed files")
+    } else {
+        None
+    }
+}
+
+fn no_review_comments(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    (app.review_comment_count() == 0).then_some("No review comments")
+}
+
+fn no_terminal(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    app.active_terminal()
+        .is_none()
+        .then_some("The active session has no terminal")
+}
+
+fn single_session(app: &SashikiApp, _: &App) -> Option<&'static str> {
+    (app.repo.session_manager.len() < 2).then_some("Only one session is open")
+}
+
+/// Global key bindings of all commands
+pub fn key_bindings() -> Vec<KeyBinding> {
+    COMMANDS
+        .iter()
+        .filter_map(|command| command.key.map(command.binding))
+        .collect()
+}
+
+/// Items of a menu bar menu
+pub fn menu_items(menu: MenuId) -> Vec<MenuItem> {
+    let mut items = Vec::new();
+    for command in COMMANDS.iter().filter(|c| c.menu == Some(menu)) {
+        if command.separated && !items.is_empty() {
+            items.push(MenuItem::separator());
+        }
+        items.push((command.menu_item)(command.title));
+    }
+    items
+}
+
+/// "ctrl-shift-e" as "Ctrl+Shift+E"
+pub fn key_label(key: &str) -> String {
+    key.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Score of `text` for a palette query, None if it does not match.
+///
+/// Every non-space query character must appear in order (ignoring case).
+/// Matches at the start of a word score highest, then matches right after
+/// the previous one, so "rea" prefers "Refresh All" over "Create".
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    const WORD_START: i32 = 10;
+    const CONSECUTIVE: i32 = 5;
+    const OTHER: i32 = 1;
+
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let original: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = original
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let word_start = |i: usize| {
+        i == 0
+            || !original[i - 1].is_alphanumeric()
+            || (original[i].is_uppercase() && original[i - 1].is_lowercase())
+    };
+
+    // best[i]: best score with the current query character matched at i
+    let mut best: Vec<Option<i32>> = vec![None; lower.len()];
+    for (qi, &qc) in query.iter().enumerate() {
+        let mut next = vec![None; lower.len()];
+        for i in 0..lower.len() {
+            if lower[i] != qc {
+                continue;
+            }
+            let bonus = |consecutive: bool| {
+                if word_start(i) {
+                    WORD_START
+                } else if consecutive {
+                    CONSECUTIVE
+                } else {
+                    OTHER
+                }
+            };
+            next[i] = if qi == 0 {
+                Some(bonus(false))
+            } else {
+                (0..i)
+                    .filter_map(|j| best[j].map(|score| score + bonus(j + 1 == i)))
+                    .max()
+            };
+        }
+        best = next;
+    }
+    best.into_iter().flatten().max()
+}
+
+/// Indices of `texts` matching `query`, best first (ties keep their order,
+/// shorter texts first)
+pub fn rank<'a>(query: &str, texts: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut scored: Vec<(usize, i32, usize)> = texts
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, text)| {
+            fuzzy_score(query, text).map(|score| (i, score, text.chars().count()))
+        })
+        .collect();
+    if !query.trim().is_empty() {
+        scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+    }
+    scored.into_iter().map(|(i, _, _)| i).collect()
+}
+
+/// What a palette entry runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteTarget {
+    /// Index into `COMMANDS`
+    Command(usize),
+    /// Switch to the session at this index
+    Session(usize),
+}
+
+/// A row of the command palette
+#[derive(Debug, Clone)]
+pub struct PaletteEntry {
+    /// Element id of the row, stable while the ranking reorders rows
+    pub id: SharedString,
+    pub title: String,
+    pub category: &'static str,
+    pub key: Option<String>,
+    /// Why the entry is disabled
+    pub unavailable: Option<&'static str>,
+    pub target: PaletteTarget,
+}
+
+impl SashikiApp {
+    pub fn on_command_palette(
+        &mut self,
+        _: &CommandPalette,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if matches!(self.active_dialog, ActiveDialog::CommandPalette { .. }) {
+            self.close_command_palette(window, cx);
+            return;
+        }
+        self.open_menu = None;
+        self.active_dialog = ActiveDialog::CommandPalette {
+            query: String::new(),
+            selected: 0,
+        };
+        window.focus(&self.palette_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_command_palette(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Palette rows matching `query`, best match first: every command, then
+    /// one "Switch to Session" entry per session in sidebar order
+    pub fn palette_entries(&self, query: &str, cx: &App) -> Vec<PaletteEntry> {
+        let mut entries: Vec<PaletteEntry> = COMMANDS
+            .iter()
+            .enumerate()
+            .map(|(i, command)| PaletteEntry {
+                id: command.id.into(),
+                title: command.title.to_string(),
+                category: command.category,
+                key: command.key_label(),
+                unavailable: command.unavailable(self, cx),
+                target: PaletteTarget::Command(i),
+            })
+            .collect();
+        let active = self.repo.session_manager.active_index();
+        entries.extend(
+            self.repo
+                .session_manager
+                .ordered_sessions()
+                .enumerate()
+                .map(|(position, (i, session))| PaletteEntry {
+                    id: format!("session-{}", i).into(),
+                    title: format!(
+                        "Switch to Session: {}",
+                        session.branch().unwrap_or(session.name())
+                    ),
+                    category: "Session",
+                    key: (position < 9).then(|| key_label(&format!("ctrl-{}", position + 1))),
+                    unavailable: (i == active).then_some("Already the active session"),
+                    target: PaletteTarget::Session(i),
+                }),
+        );
+
+        let order = rank(query, entries.iter().map(|e| e.title.as_str()));
+        order.into_iter().map(|i| entries[i].clone()).collect()
+    }
+
+    pub fn set_palette_query(&mut self, query: String, cx: &mut Context<Self>) {
+        if let ActiveDialog::CommandPalette {
+            query: current,
+            selected,
+        } = &mut self.active_dialog
+        {
+            *current = query;
+            *selected = 0;
+            cx.notify();
+        }
+    }
+
+    pub fn move_palette_selection(&mut self, delta: isize, cx: &mut Context<Self>) {
+        let ActiveDialog::CommandPalette { query, selected } = &self.active_dialog else {
+            return;
+        };
+        let count = self.palette_entries(query, cx).len();
+        if count == 0 {
+            return;
+        }
+        let next = (*selected as isize + delta).rem_euclid(count as isize) as usize;
+        if let ActiveDialog::CommandPalette { selected, .. } = &mut self.active_dialog {
+            *selected = next;
+        }
+        cx.notify();
+    }
+
+    /// Run the palette entry at `index` of the current matches. Disabled
+    /// entries do nothing.
+    pub fn run_palette_entry(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::CommandPalette { query, .. } = &self.active_dialog else {
+            return;
+        };
+        let Some(entry) = self.palette_entries(query, cx).into_iter().nth(index) else {
+            return;
+        };
+        if entry.unavailable.is_some() {
+            return;
+        }
+        self.close_command_palette(window, cx);
+        match entry.target {
+            PaletteTarget::Command(i) => window.dispatch_action(COMMANDS[i].action(), cx),
+            PaletteTarget::Session(i) => self.on_session_selected(i, window, cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_command_ids_and_keys_are_unique() {
+        let mut ids = HashSet::new();
+        let mut titles = HashSet::new();
+        let mut keys = HashSet::new();
+        for command in COMMANDS {
+            assert!(ids.insert(command.id), "duplicate id {}", command.id);
+            assert!(
+                titles.insert(command.title),
+                "duplicate title {}",
+                command.title
+            );
+            if let Some(key) = command.key {
+                assert!(keys.insert(key), "duplicate key {}", key);
+            }
+        }
+        assert_eq!(key_bindings().len(), keys.len());
+    }
+
+    #[test]
+    fn test_menus_come_from_the_registry() {
+        // Separators only between groups, never leading
+        let view = menu_items(MenuId::View);
+        assert!(!matches!(view.first(), Some(MenuItem::Separator)));
+        let app_items = menu_items(MenuId::App);
+        assert_eq!(app_items.len(), 3);
+        assert!(matches!(app_items[1], MenuItem::Separator));
+    }
+
+    #[test]
+    fn test_key_label() {
+        assert_eq!(key_label("ctrl-shift-e"), "Ctrl+Shift+E");
+        assert_eq!(key_label("ctrl-tab"), "Ctrl+Tab");
+        assert_eq!(key_label("escape"), "Escape");
+    }
+
+    #[test]
+    fn test_fuzzy_score_matches_in_order() {
+        assert!(fuzzy_score("tsb", "Toggle Sidebar").is_some());
+        assert!(fuzzy_score("TOGGLE", "Toggle Sidebar").is_some());
+        assert!(fuzzy_score("bst", "Toggle Sidebar").is_none());
+        assert_eq!(fuzzy_score("", "Anything"), Some(0));
+        assert_eq!(fuzzy_score(" ", "Anything"), Some(0));
+    }
+
+    #[test]
+    fn test_ranking_prefers_word_prefixes() {
+        let titles = ["Create Worktree...", "Refresh All"];
+        assert_eq!(rank("rea", titles), vec![1, 0]);
+
+        // "fl" is best read as the initials of "File List", not "Fi(l)e"
+        assert!(
+            fuzzy_score("fl", "Toggle File List").unwrap()
+                > fuzzy_score("fl", "Toggle Files").unwrap_or(0)
+        );
+
+        let titles = ["Command Palette...", "Worktree Maintenance..."];
+        assert_eq!(rank("ma", titles), vec![1, 0]);
+
+        // An empty query keeps the registry order
+        assert_eq!(rank("", ["b", "a"]), vec![0, 1]);
+    }
+}
@@ -0,0 +1,136 @@
+//! Comparing the open file with its copy in another session (see
+//! `crate::compare`): picked from the file view's toolbar or the command,
+//! or straight from a file two sessions share in the conflicts dialog
+
+use super::SashikiApp;
+use super::actions::CompareWithSession;
+use crate::compare::{CompareSide, Comparison};
+use crate::dialog::ActiveDialog;
+use gpui::{Context, Window};
+use std::path::PathBuf;
+
+impl SashikiApp {
+    pub fn on_compare_with_session(
+        &mut self,
+        _: &CompareWithSession,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(path) = self.file_view.read(cx).file_path() {
+            self.open_compare_dialog(path.to_path_buf(), cx);
+        }
+    }
+
+    /// Pick the session to compare `path` (a full path in one session's
+    /// worktree) with
+    pub fn open_compare_dialog(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::CompareWithSession { path };
+        cx.notify();
+    }
+
+    pub fn close_compare_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Compare the file of the dialog with its copy in session
+    /// `other_index`, which goes on the left
+    pub fn compare_with_session(&mut self, other_index: usize, cx: &mut Context<Self>) {
+        let ActiveDialog::CompareWithSession { path } = &self.active_dialog else {
+            return;
+        };
+        let path = path.clone();
+        self.active_dialog = ActiveDialog::None;
+        let comparison = self
+            .repo
+            .session_manager
+            .find_session_containing(&path)
+            .and_then(|index| self.compare_side(index))
+            .zip(self.compare_side(other_index))
+            .and_then(|(right, left)| Comparison::new(&path, left, right));
+        match comparison {
+            Some(comparison) => self.show_comparison(comparison, cx),
+            None => cx.notify(),
+        }
+    }
+
+    /// Compare a file two sessions share (`path`, relative): switch to
+    /// `target_index` and show its copy against that of `other_index`
+    pub fn compare_overlap(
+        &mut self,
+        target_index: usize,
+        other_index: usize,
+        path: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let (Some(right), Some(left)) = (
+            self.compare_side(target_index),
+            self.compare_side(other_index),
+        ) else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::None;
+        if self.repo.session_manager.active_index() != target_index {
+            self.on_session_selected(target_index, window, cx);
+        }
+        let file = right.worktree.join(&path);
+        if let Some(comparison) = Comparison::new(&file, left, right) {
+            self.show_comparison(comparison, cx);
+        }
+    }
+
+    /// Show the comparison on screen with its sides the other way round
+    pub fn swap_comparison(&mut self, comparison: Comparison, cx: &mut Context<Self>) {
+        self.show_comparison(comparison.swapped(), cx);
+    }
+
+    /// Session `index` as a side of a comparison, labelled with its branch
+    fn compare_side(&self, index: usize) -> Option<CompareSide> {
+        let session = self.repo.session_manager.sessions().get(index)?;
+        Some(CompareSide {
+            label: session.branch().unwrap_or(session.name()).to_string(),
+            worktree: session.worktree_path().to_path_buf(),
+        })
+    }
+
+    /// Open the right side's copy with the diff from the left one; the
+    /// copies being the same shows the file as it is
+    fn show_comparison(&mut self, comparison: Comparison, cx: &mut Context<Self>) {
+        let diff = comparison
+            .diff()
+            .and_then(|diff| Ok((comparison.copy(&comparison.right)?, diff)));
+        let (file, diff) = match diff {
+            Ok(shown) => shown,
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Cannot compare the sessions: {}", e));
+                cx.notify();
+                return;
+            }
+        };
+        if file.exists() {
+            self.reveal_in_file_list(&file);
+        }
+        let opened = self.file_view.update(cx, |view, _cx| {
+            let opened = if diff.is_empty() {
+                view.open_file(file.clone())
+            } else if file.exists() {
+                view.open_file_with_diff(file.clone(), diff)
+            } else {
+                view.open_deleted_file_with_diff(file.clone(), diff);
+                Ok(())
+            };
+            if opened.is_ok() {
+                view.set_comparison(Some(comparison));
+            }
+            opened
+        });
+        if let Err(e) = opened {
+            self.active_dialog =
+                ActiveDialog::error(format!("Failed to open {}: {}", file.display(), e));
+        }
+        self.reveal_file_view();
+        cx.notify();
+    }
+}
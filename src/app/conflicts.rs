@@ -0,0 +1,144 @@
+//! Background overlap detection between worktrees
+
+use super::SashikiApp;
+use crate::conflicts;
+use crate::dialog::ActiveDialog;
+use crate::git::GitRepo;
+use gpui::{Context, Window};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two automatic conflict scans
+const CONFLICT_SCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+impl SashikiApp {
+    /// Recompute file overlaps between worktrees in the background.
+    ///
+    /// Automatic refreshes are throttled; `force` bypasses the throttle
+    /// (but never starts a second scan while one is running).
+    pub fn refresh_conflicts_async(&mut self, force: bool, cx: &mut Context<Self>) {
+        if self.conflict_scan_running {
+            return;
+        }
+        if !force
+            && let Some(last) = self.repo.conflict_scan_at
+            && last.elapsed() < CONFLICT_SCAN_INTERVAL
+        {
+            return;
+        }
+
+        let Some(main_workdir) = self
+            .repo
+            .git_repo
+            .as_ref()
+            .map(|r| r.workdir().to_path_buf())
+        else {
+            return;
+        };
+        let worktrees: Vec<PathBuf> = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .filter(|s| !s.is_main())
+            .map(|s| s.worktree_path().to_path_buf())
+            .collect();
+
+        self.conflict_scan_running = true;
+        self.repo.conflict_scan_at = Some(Instant::now());
+
+        cx.spawn(async move |entity, cx| {
+            let scanned = main_workdir.clone();
+            let report = smol::unblock(move || conflicts::scan(&scanned, &worktrees)).await;
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                // Dropped when another repository was switched to meanwhile,
+                // which is then scanned on the next check
+                if app.repo.git_repo.as_ref().map(|r| r.workdir()) == Some(main_workdir.as_path()) {
+                    app.repo.conflict_report = report;
+                } else {
+                    app.repo.conflict_scan_at = None;
+                }
+                app.conflict_scan_running = false;
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    pub fn open_conflicts_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        if index < self.repo.session_manager.len() {
+            self.active_dialog = ActiveDialog::Conflicts {
+                target_index: index,
+            };
+            cx.notify();
+        }
+    }
+
+    pub fn close_conflicts_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Switch to a session and show its diff of `path` against its merge-base with main
+    pub fn open_overlap_diff(
+        &mut self,
+        session_index: usize,
+        path: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(worktree_path) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(session_index)
+            .map(|s| s.worktree_path().to_path_buf())
+        else {
+            return;
+        };
+
+        self.active_dialog = ActiveDialog::None;
+        self.on_session_selected(session_index, window, cx);
+
+        let full_path = worktree_path.join(&path);
+        if !self.ensure_inside_worktree(&full_path, "open the file", cx) {
+            return;
+        }
+        self.reveal_in_file_list(&full_path);
+        let diff = self
+            .repo
+            .conflict_report
+            .base_for(&worktree_path)
+            .map(|base| base.to_string())
+            .and_then(|base| {
+                GitRepo::open(&worktree_path)
+                    .ok()?
+                    .get_file_diff_against(&base, &full_path)
+                    .ok()
+            })
+            .filter(|d| !d.is_empty());
+
+        match diff {
+            Some(diff) if full_path.exists() => {
+                self.file_view.update(cx, |view, _cx| {
+                    let _ = view.open_file_with_diff(full_path, diff);
+                });
+            }
+            Some(diff) => {
+                self.file_view.update(cx, |view, _cx| {
+                    view.open_deleted_file_with_diff(full_path, diff);
+                });
+            }
+            None => {
+                self.file_view.update(cx, |view, _cx| {
+                    let _ = view.open_file(full_path);
+                });
+            }
+        }
+
+        self.reveal_file_view();
+        cx.notify();
+    }
+}
@@ -0,0 +1,247 @@
+//! Requests of the control server (see `crate::control`), answered on the
+//! UI thread with the code the sidebar and dialogs use
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::control::{self, ControlRequest, ControlServer, Pending, Reply};
+use crate::export;
+use crate::git;
+use crate::settings::Settings;
+use gpui::{Context, Window};
+
+impl SashikiApp {
+    /// Start the control server when `sashiki.remote.enabled` is set; its
+    /// requests are handled in `window`
+    pub fn start_control_server(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (settings, _) = Settings::load(None);
+        if !settings.remote_enabled() {
+            return;
+        }
+        let token = match settings.remote_token() {
+            Some(token) => token,
+            None => {
+                let stored = control::generate_token()
+                    .map_err(|e| format!("failed to generate its token: {}", e))
+                    .and_then(|token| {
+                        git::set_global_config_value(git::CONFIG_REMOTE_TOKEN, &token)
+                            .map(|()| token)
+                            .map_err(|e| format!("failed to store its token: {}", e))
+                    });
+                match stored {
+                    Ok(token) => token,
+                    Err(e) => {
+                        app_log::record(LogEntry::new(
+                            LogLevel::Error,
+                            LogSource::App,
+                            format!("Control server not started: {}", e),
+                        ));
+                        return;
+                    }
+                }
+            }
+        };
+
+        let port = settings.remote_port();
+        let (sender, receiver) = smol::channel::unbounded::<Pending>();
+        match ControlServer::start(port, token, control::forward_to(sender)) {
+            Ok(server) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::App,
+                    format!(
+                        "Control server listening on http://{} (token in {})",
+                        server.address(),
+                        git::CONFIG_REMOTE_TOKEN
+                    ),
+                ));
+                self.control_server = Some(server);
+            }
+            Err(e) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Error,
+                    LogSource::App,
+                    format!("Control server could not listen on port {}: {}", port, e),
+                ));
+                return;
+            }
+        }
+
+        cx.spawn_in(window, async move |entity, cx| {
+            while let Ok(pending) = receiver.recv().await {
+                let handled = entity.update_in(cx, |app, window, cx| {
+                    app.handle_control(pending, window, cx)
+                });
+                if handled.is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+
+        // Stop listening (and close the event streams) before the process goes
+        cx.on_app_quit(|app, _cx| {
+            app.control_server = None;
+            async {}
+        })
+        .detach();
+    }
+
+    fn handle_control(&mut self, pending: Pending, window: &mut Window, cx: &mut Context<Self>) {
+        let reply = match pending.request.clone() {
+            ControlRequest::State
+            | ControlRequest::Session { .. }
+            | ControlRequest::ChangedFiles { .. } => {
+                self.answer_with_state(pending, cx);
+                return;
+            }
+            ControlRequest::Activate { name } => match self.session_index(&name) {
+                Some(index) => {
+                    self.on_session_selected(index, window, cx);
+                    Reply::ok()
+                }
+                None => Reply::not_found(&name),
+            },
+            ControlRequest::Send { name, text, enter } => {
+                self.send_to_session(&name, &text, enter, cx)
+            }
+            ControlRequest::CreateWorktree {
+                branch,
+                base,
+                description,
+            } => match self.create_branch_worktree(&branch, &base, &description, cx) {
+                Ok((name, path)) => {
+                    Reply::accepted(&serde_json::json!({ "name": name, "path": path }))
+                }
+                Err(message) => Reply::error(400, message),
+            },
+        };
+        pending.answer(reply);
+    }
+
+    /// Answer with the export document, or the part of it asked for; the
+    /// changes are read off the UI thread
+    fn answer_with_state(&self, pending: Pending, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return pending.answer(Reply::error(409, "No repository is open"));
+        };
+        let only = match &pending.request {
+            ControlRequest::Session { name } | ControlRequest::ChangedFiles { name } => Some(name),
+            _ => None,
+        };
+        let mut sessions = self.session_states(cx);
+        if let Some(name) = only {
+            sessions.retain(|session| &session.name == name);
+            if sessions.is_empty() {
+                return pending.answer(Reply::not_found(name));
+            }
+        }
+        let repository = repo.workdir().to_path_buf();
+        smol::unblock(move || {
+            let state = export::export(repository, sessions);
+            let reply = match &pending.request {
+                ControlRequest::Session { .. } => Reply::json(&state.sessions[0]),
+                ControlRequest::ChangedFiles { .. } => {
+                    Reply::json(&state.sessions[0].changed_files)
+                }
+                _ => Reply::json(&state),
+            };
+            pending.answer(reply);
+        })
+        .detach();
+    }
+
+    /// Paste `text` into the active terminal of session `name`, started if
+    /// it has none, and press Enter after it when `enter` is set
+    fn send_to_session(
+        &mut self,
+        name: &str,
+        text: &str,
+        enter: bool,
+        cx: &mut Context<Self>,
+    ) -> Reply {
+        let Some(index) = self.session_index(name) else {
+            return Reply::not_found(name);
+        };
+        self.repo.session_manager.ensure_session_terminal(index, cx);
+        self.update_activity_watch(cx);
+        let Some(terminal) = self.repo.session_manager.get_session_active_terminal(index) else {
+            return Reply::error(409, format!("Session '{}' has no terminal", name));
+        };
+        terminal.update(cx, |view, _cx| {
+            if enter {
+                view.paste_command(text);
+            } else {
+                view.paste_text(text);
+            }
+        });
+        cx.notify();
+        Reply::ok()
+    }
+
+    fn session_index(&self, name: &str) -> Option<usize> {
+        self.repo
+            .session_manager
+            .sessions()
+            .iter()
+            .position(|session| session.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::StateExport;
+    use crate::test_support::init_repo_with_feature_worktree;
+    use gpui::{AppContext, TestAppContext};
+    use std::time::Duration;
+
+    /// Time the export is given to answer off the UI thread
+    const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[gpui::test]
+    fn test_requests_reach_the_sessions_of_the_open_repository(cx: &mut TestAppContext) {
+        let (dir, _worktree) = init_repo_with_feature_worktree();
+        let cx = cx.add_empty_window();
+        let app = cx.new(SashikiApp::new);
+        app.update(cx, |app, cx| app.open_project(dir.path().to_path_buf(), cx));
+
+        let mut request = |request| {
+            let (pending, answer) = Pending::new(request);
+            cx.update(|window, cx| {
+                app.update(cx, |app, cx| app.handle_control(pending, window, cx))
+            });
+            answer.recv_timeout(ANSWER_TIMEOUT).unwrap()
+        };
+
+        let listed = request(ControlRequest::State);
+        assert_eq!(listed.status, 200, "{}", listed.body);
+        let state: StateExport = serde_json::from_str(&listed.body).unwrap();
+        let feature = state
+            .sessions
+            .iter()
+            .find(|session| session.name == "wt-feature")
+            .unwrap();
+        assert_eq!(feature.branch.as_deref(), Some("feature"));
+        assert_eq!(feature.terminal_count, 0);
+
+        let sent = request(ControlRequest::Send {
+            name: "wt-feature".to_string(),
+            text: "echo hello".to_string(),
+            enter: true,
+        });
+        assert_eq!(sent.status, 200, "{}", sent.body);
+        let missing = request(ControlRequest::Send {
+            name: "no-such-session".to_string(),
+            text: "echo hello".to_string(),
+            enter: true,
+        });
+        assert_eq!(missing.status, 404);
+
+        // The session had no terminal: one was started to paste into
+        let terminals = app.update(cx, |app, _cx| {
+            let index = app.session_index("wt-feature").unwrap();
+            app.repo.session_manager.sessions()[index].terminal_count()
+        });
+        assert_eq!(terminals, 1);
+    }
+}
@@ -1,23 +1,73 @@
 //! Dialog operation methods
 
 use super::SashikiApp;
-use crate::dialog::ActiveDialog;
-use crate::git::{GitRepo, validate_branch_name};
-use crate::template::{self, TemplateConfig};
+use crate::activity::ActivityEvent;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::branch_name::BranchSuggester;
+use crate::dialog::{ActiveDialog, CreationProgress, OpenField, OpenMode};
+use crate::git::{self, GitRepo, validate_branch_name};
+use crate::hooks::{self, HookEvent};
+use crate::settings::Settings;
+use crate::template::{self, NamedTemplate, TemplateConfig, TemplateSet};
 use gpui::{Context, Focusable, PathPromptOptions, Window};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Typing pause before the creation preview is worked out again
+const PREVIEW_DELAY: Duration = Duration::from_millis(200);
+
+/// What a worktree being created checks out
+#[derive(Debug, Clone)]
+struct NewWorktree {
+    /// Directory name under the worktrees folder (also names the session)
+    name: String,
+    /// New or existing branch; None checks out `base` with a detached HEAD
+    branch: Option<String>,
+    /// Commit, tag or branch to start from (see `GitRepo::create_worktree`)
+    base: Option<String>,
+    /// Do not run the repository's git hooks for this checkout
+    skip_hooks: bool,
+}
+
+impl NewWorktree {
+    /// Shown while the worktree is being created
+    fn label(&self) -> &str {
+        self.branch
+            .as_deref()
+            .or(self.base.as_deref())
+            .unwrap_or("HEAD")
+    }
+}
 
 impl SashikiApp {
-    pub fn open_create_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        self.active_dialog = ActiveDialog::CreateWorktree;
-        self.create_branch_input.clear();
-        window.focus(&self.create_dialog_focus, cx);
+    /// Show the Open dialog in the given mode
+    pub fn show_open_dialog(
+        &mut self,
+        mode: OpenMode,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_dialog = ActiveDialog::Open;
+        self.open_mode = mode;
+        self.open_input.clear();
+        self.open_error = None;
+        self.reset_branch_fields();
+        self.refresh_open_preview(cx);
+        window.focus(&self.open_dialog_focus, cx);
         cx.notify();
     }
 
-    pub fn close_create_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    pub fn open_create_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.show_open_dialog(OpenMode::Branch, window, cx);
+    }
+
+    pub fn close_open_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         self.active_dialog = ActiveDialog::None;
-        self.create_branch_input.clear();
+        self.open_input.clear();
+        self.open_description.clear();
+        self.open_error = None;
+        self.open_preview = None;
+        self.open_preview_task = None;
         if let Some(terminal) = self.active_terminal() {
             let focus = terminal.read(cx).focus_handle(cx);
             window.focus(&focus, cx);
@@ -25,69 +75,460 @@ impl SashikiApp {
         cx.notify();
     }
 
-    pub fn submit_create_worktree(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        let branch = self.create_branch_input.trim().to_string();
+    /// Switch the Open dialog mode (Branch mode needs an open repository)
+    pub fn set_open_mode(&mut self, mode: OpenMode, cx: &mut Context<Self>) {
+        if mode == OpenMode::Branch && self.repo.git_repo.is_none() {
+            return;
+        }
+        if self.open_mode != mode {
+            self.open_mode = mode;
+            self.open_input.clear();
+            self.open_error = None;
+            self.reset_branch_fields();
+            self.refresh_open_preview(cx);
+            cx.notify();
+        }
+    }
 
-        if let Err(msg) = validate_branch_name(&branch) {
-            self.active_dialog = ActiveDialog::Error {
-                message: msg.to_string(),
-            };
+    /// Empty the task description and base, and read what branch
+    /// suggestions need (Branch mode only)
+    fn reset_branch_fields(&mut self) {
+        self.open_description.clear();
+        self.open_base.clear();
+        self.open_detach = false;
+        self.open_skip_hooks = false;
+        self.open_field = match self.open_mode {
+            OpenMode::Path => OpenField::Input,
+            OpenMode::Branch => OpenField::Description,
+        };
+        self.branch_suggester = match &self.repo.git_repo {
+            Some(repo) if self.open_mode == OpenMode::Branch => {
+                let (settings, _) = Settings::load(Some(repo));
+                BranchSuggester::new(
+                    settings.branch_prefix(),
+                    repo.local_branches().unwrap_or_default(),
+                )
+            }
+            _ => BranchSuggester::default(),
+        };
+    }
+
+    /// Fields of the Open dialog, top to bottom
+    fn open_fields(&self) -> &'static [OpenField] {
+        match (self.open_mode, self.open_detach) {
+            (OpenMode::Path, _) => &[OpenField::Input],
+            (OpenMode::Branch, false) => {
+                &[OpenField::Description, OpenField::Input, OpenField::Base]
+            }
+            // A detached worktree gets no branch
+            (OpenMode::Branch, true) => &[OpenField::Description, OpenField::Base],
+        }
+    }
+
+    /// Send typing to `field` (if the dialog shows it)
+    pub fn set_open_field(&mut self, field: OpenField, cx: &mut Context<Self>) {
+        if self.open_field != field && self.open_fields().contains(&field) {
+            self.open_field = field;
             cx.notify();
-            return;
         }
+    }
+
+    /// Move typing to the field `delta` rows below the current one
+    pub fn move_open_field(&mut self, delta: isize, cx: &mut Context<Self>) {
+        let fields = self.open_fields();
+        let current = fields
+            .iter()
+            .position(|f| *f == self.open_field)
+            .unwrap_or(0);
+        let next = (current as isize + delta).clamp(0, fields.len() as isize - 1) as usize;
+        self.set_open_field(fields[next], cx);
+    }
 
-        let repo = match self.git_repo.as_ref() {
-            Some(r) => r,
+    /// Append `text` to the path or branch name, or delete its last
+    /// character with None
+    pub fn edit_open_input(&mut self, text: Option<&str>, cx: &mut Context<Self>) {
+        match text {
+            Some(text) => self.open_input.push_str(text),
             None => {
-                self.active_dialog = ActiveDialog::Error {
-                    message: "Git repository not available".to_string(),
-                };
-                cx.notify();
-                return;
+                self.open_input.pop();
             }
-        };
+        }
+        self.open_error = None;
+        self.refresh_open_preview(cx);
+        cx.notify();
+    }
+
+    /// Append `text` to the base, or delete its last character with None
+    pub fn edit_open_base(&mut self, text: Option<&str>, cx: &mut Context<Self>) {
+        match text {
+            Some(text) => self.open_base.push_str(text),
+            None => {
+                self.open_base.pop();
+            }
+        }
+        self.open_error = None;
+        // A detached worktree is named after its base
+        if self.open_detach {
+            self.refresh_open_preview(cx);
+        }
+        cx.notify();
+    }
 
-        let worktree_path = match repo.generate_worktree_path(&branch) {
-            Some(p) => p,
+    /// Run the repository's git hooks for the new worktree, or not
+    pub fn toggle_open_skip_hooks(&mut self, cx: &mut Context<Self>) {
+        self.open_skip_hooks = !self.open_skip_hooks;
+        cx.notify();
+    }
+
+    /// Check out the base without a new branch, or create one again
+    pub fn toggle_open_detach(&mut self, cx: &mut Context<Self>) {
+        self.open_detach = !self.open_detach;
+        if self.open_detach && self.open_field == OpenField::Input {
+            self.open_field = OpenField::Base;
+        }
+        self.open_error = None;
+        self.refresh_open_preview(cx);
+        cx.notify();
+    }
+
+    /// Append `text` to the task description, or delete its last character
+    /// with None. The branch name follows unless it was typed by hand.
+    pub fn edit_open_description(&mut self, text: Option<&str>, cx: &mut Context<Self>) {
+        match text {
+            Some(text) => self.open_description.push_str(text),
             None => {
-                self.active_dialog = ActiveDialog::Error {
-                    message: "Failed to generate worktree path".to_string(),
-                };
+                self.open_description.pop();
+            }
+        }
+        if let Some(branch) = self
+            .branch_suggester
+            .update(&self.open_description, &self.open_input)
+        {
+            self.open_input = branch;
+            self.refresh_open_preview(cx);
+        }
+        self.open_error = None;
+        cx.notify();
+    }
+
+    /// Show or hide what creating the worktree would do
+    pub fn toggle_open_preview(&mut self, cx: &mut Context<Self>) {
+        self.open_preview_expanded = !self.open_preview_expanded;
+        self.refresh_open_preview(cx);
+        cx.notify();
+    }
+
+    /// Branch and worktree directory name the creation preview is for; None
+    /// until a valid branch name is typed. A detached worktree's name is
+    /// only known up front when its base is a branch or tag.
+    fn open_preview_target(&self) -> Option<(Option<String>, Option<String>)> {
+        if self.open_detach {
+            let base = self.open_base.trim();
+            let name = (!base.is_empty() && base != "HEAD" && validate_branch_name(base).is_ok())
+                .then(|| base.replace('/', "-"));
+            return Some((None, name));
+        }
+        let branch = self.open_input.trim();
+        validate_branch_name(branch).ok()?;
+        Some((Some(branch.to_string()), Some(branch.replace('/', "-"))))
+    }
+
+    /// Drop the creation preview and, while it is shown, work it out again
+    /// for the current fields once typing pauses. Templates are read and
+    /// file copy globs resolved off the UI thread; nothing is run.
+    fn refresh_open_preview(&mut self, cx: &mut Context<Self>) {
+        self.open_preview = None;
+        self.open_preview_task = None;
+        if !self.open_preview_expanded || self.open_mode != OpenMode::Branch {
+            return;
+        }
+        let Some(repo) = self.repo.git_repo.clone() else {
+            return;
+        };
+        let Some(target) = self.open_preview_target() else {
+            return;
+        };
+        let (branch, name) = target.clone();
+
+        self.open_preview_task = Some(cx.spawn(async move |entity, cx| {
+            smol::Timer::after(PREVIEW_DELAY).await;
+            let plan = smol::unblock(move || {
+                let worktree_path = name.and_then(|name| repo.generate_worktree_path(&name));
+                TemplateSet::load(&repo).plan(
+                    branch.as_deref(),
+                    repo.workdir(),
+                    worktree_path.as_deref(),
+                )
+            })
+            .await;
+            let _ = entity.update(cx, |app, cx| {
+                // The fields changed since: a newer preview is on its way
+                if app.open_preview_target() != Some(target) {
+                    return;
+                }
+                app.open_preview = Some(plan);
+                app.open_preview_task = None;
                 cx.notify();
-                return;
+            });
+        }));
+    }
+
+    /// Submit the Open dialog; validation errors are shown inline
+    pub fn submit_open_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let input = self.open_input.trim().to_string();
+        let result = match self.open_mode {
+            OpenMode::Path => self.open_path(&input, window, cx),
+            OpenMode::Branch => self
+                .check_new_worktree(
+                    &input,
+                    &self.open_base,
+                    self.open_detach,
+                    self.open_skip_hooks,
+                )
+                .map(|(new, worktree_path)| {
+                    let description = self.open_description.trim().to_string();
+                    self.open_input.clear();
+                    self.open_description.clear();
+                    self.open_base.clear();
+                    self.start_create_worktree(new, &description, worktree_path, cx);
+                }),
+        };
+
+        if let Err(msg) = result {
+            self.open_error = Some(msg);
+            cx.notify();
+        }
+    }
+
+    /// Activate the session containing `input`, or open it as a new project
+    fn open_path(
+        &mut self,
+        input: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<(), String> {
+        if input.is_empty() {
+            return Err("Enter a folder path".to_string());
+        }
+        let path = PathBuf::from(input);
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+
+        if let Some(index) = self.repo.session_manager.find_session_containing(&path) {
+            self.close_open_dialog(window, cx);
+            self.on_session_selected(index, window, cx);
+            return Ok(());
+        }
+
+        GitRepo::open(&path).map_err(|e| format!("Not a git repository: {}", e))?;
+        self.close_open_dialog(window, cx);
+        self.open_project(path, cx);
+        Ok(())
+    }
+
+    /// Pick a folder with the system dialog and open it like a typed path
+    pub fn browse_for_folder(&mut self, cx: &mut Context<Self>) {
+        let paths_receiver = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: None,
+        });
+
+        cx.spawn(async move |entity, cx| {
+            if let Ok(Ok(Some(paths))) = paths_receiver.await
+                && let Some(path) = paths.into_iter().next()
+            {
+                let _ = entity.update(cx, |app, cx| {
+                    app.open_mode = OpenMode::Path;
+                    app.open_input = path.to_string_lossy().to_string();
+                    app.open_error = None;
+                    cx.notify();
+                });
             }
+        })
+        .detach();
+    }
+
+    /// Create a worktree for `branch` from `base` (HEAD when empty) as the
+    /// Open dialog does, for the control server; returns the name of the
+    /// session to be and its worktree
+    pub(crate) fn create_branch_worktree(
+        &mut self,
+        branch: &str,
+        base: &str,
+        description: &str,
+        cx: &mut Context<Self>,
+    ) -> Result<(String, PathBuf), String> {
+        if matches!(self.active_dialog, ActiveDialog::Creating { .. }) {
+            return Err("Another worktree is being created".to_string());
+        }
+        let (new, worktree_path) = self.check_new_worktree(branch, base, false, false)?;
+        let name = new.name.clone();
+        self.start_create_worktree(new, description, worktree_path.clone(), cx);
+        Ok((name, worktree_path))
+    }
+
+    /// Validate the branch and base of a new worktree and return what to
+    /// check out and where; `detach` checks out the base without a branch
+    fn check_new_worktree(
+        &self,
+        branch: &str,
+        base: &str,
+        detach: bool,
+        skip_hooks: bool,
+    ) -> Result<(NewWorktree, PathBuf), String> {
+        let repo = self
+            .repo
+            .git_repo
+            .as_ref()
+            .ok_or_else(|| "Git repository not available".to_string())?;
+
+        let base = Some(base.trim())
+            .filter(|base| !base.is_empty())
+            .map(str::to_string);
+        let commit = match &base {
+            Some(base) => Some(
+                repo.resolve_commit(base)
+                    .map_err(|_| format!("Base '{}' is not a branch, tag or commit", base))?,
+            ),
+            None => None,
         };
 
-        if worktree_path.exists() {
-            self.active_dialog = ActiveDialog::Error {
-                message: format!(
-                    "Worktree directory already exists: {}\nPlease remove it manually or choose a different branch name.",
-                    worktree_path.display()
-                ),
+        let new = if detach {
+            // Named after the tag or branch, else the abbreviated commit
+            let name = match base.as_deref() {
+                Some(base) if base != "HEAD" && validate_branch_name(base).is_ok() => {
+                    base.replace('/', "-")
+                }
+                _ => {
+                    let commit = match commit {
+                        Some(commit) => commit,
+                        None => repo.resolve_commit("HEAD").map_err(|e| e.to_string())?,
+                    };
+                    commit.chars().take(7).collect()
+                }
             };
-            cx.notify();
-            return;
+            NewWorktree {
+                name,
+                branch: None,
+                base,
+                skip_hooks,
+            }
+        } else {
+            validate_branch_name(branch).map_err(|msg| msg.to_string())?;
+            NewWorktree {
+                name: branch.replace('/', "-"),
+                branch: Some(branch.to_string()),
+                base,
+                skip_hooks,
+            }
+        };
+
+        let worktree_path = repo
+            .generate_worktree_path(&new.name)
+            .ok_or_else(|| "Failed to generate worktree path".to_string())?;
+
+        if worktree_path.exists() {
+            return Err(format!(
+                "Worktree directory already exists: {}\nPlease remove it manually or choose a different branch name.",
+                worktree_path.display()
+            ));
         }
 
-        // Load template config
-        let template = TemplateConfig::load(repo);
+        Ok((new, worktree_path))
+    }
+
+    /// Run the creation pipeline (template commands included) with progress
+    fn start_create_worktree(
+        &mut self,
+        new: NewWorktree,
+        description: &str,
+        worktree_path: PathBuf,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+
+        // Pick the template for the branch (first matching glob, else the fallback)
+        let templates = TemplateSet::load(repo);
+        let matched = new.branch.as_deref().and_then(|b| templates.matching(b));
+        let template_name = matched.map(|t| t.name.clone());
+        let template = matched
+            .map(|t| t.config.clone())
+            .unwrap_or_else(|| templates.fallback.clone());
         let steps = template.creation_steps();
 
         // Switch to Creating dialog with progress
         self.active_dialog = ActiveDialog::Creating {
-            branch: branch.clone(),
-            steps: steps.clone(),
-            current_step: 0,
+            branch: new.label().to_string(),
+            template: template_name,
+            progress: CreationProgress::new(steps),
         };
         cx.notify();
 
+        // Lines printed while the worktree is checked out (hooks included)
+        // go to the dialog and the app log as they arrive
+        let (output_tx, output_rx) = smol::channel::unbounded::<String>();
+        cx.spawn(async move |entity, cx| {
+            while let Ok(line) = output_rx.recv().await {
+                let updated = entity.update(cx, |app, cx| {
+                    app_log::record(LogEntry::new(
+                        LogLevel::Info,
+                        LogSource::Git,
+                        format!("worktree add: {}", line),
+                    ));
+                    if let ActiveDialog::Creating { progress, .. } = &mut app.active_dialog {
+                        progress.push_output(line);
+                        cx.notify();
+                    }
+                });
+                if updated.is_err() {
+                    break;
+                }
+            }
+        })
+        .detach();
+
+        // Keep the elapsed time of the running step current
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(Duration::from_secs(1)).await;
+                let creating = entity.update(cx, |app, cx| {
+                    let creating = matches!(app.active_dialog, ActiveDialog::Creating { .. });
+                    if creating {
+                        cx.notify();
+                    }
+                    creating
+                });
+                if !matches!(creating, Ok(true)) {
+                    break;
+                }
+            }
+        })
+        .detach();
+
         // Gather data needed for async pipeline
         let main_workdir = repo.workdir().to_path_buf();
         let git_dir = repo.git_dir().to_path_buf();
-        let worktree_name = branch.replace('/', "-");
 
-        // Close create dialog state (branch input is no longer needed)
-        self.create_branch_input.clear();
+        // Stored up front so the session shows it as soon as it appears; an
+        // empty description clears one left by an earlier worktree
+        let key = git::session_description_key(&new.name);
+        let stored = if description.is_empty() {
+            repo.remove_config_key(&key)
+        } else {
+            repo.set_config_value(&key, description)
+        };
+        if let Err(e) = stored {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::Git,
+                format!("Failed to store the session description: {}", e),
+            ));
+        }
 
         // Spawn async creation pipeline
         cx.spawn(async move |entity, cx| {
@@ -96,16 +537,16 @@ impl SashikiApp {
                 cx,
                 main_workdir,
                 git_dir,
-                branch,
-                worktree_name,
+                new,
                 worktree_path,
                 template,
+                output_tx,
             )
             .await;
 
             if let Err(msg) = result {
                 let _ = entity.update(cx, |app, cx| {
-                    app.active_dialog = ActiveDialog::Error { message: msg };
+                    app.active_dialog = ActiveDialog::error(msg);
                     cx.notify();
                 });
             }
@@ -119,10 +560,10 @@ impl SashikiApp {
         cx: &mut gpui::AsyncApp,
         main_workdir: PathBuf,
         git_dir: PathBuf,
-        branch: String,
-        worktree_name: String,
+        new: NewWorktree,
         worktree_path: PathBuf,
         template: TemplateConfig,
+        output: smol::channel::Sender<String>,
     ) -> Result<(), String> {
         let mut step_index: usize = 0;
 
@@ -140,12 +581,8 @@ impl SashikiApp {
             step_index += 1;
             let step = step_index;
             let _ = entity.update(cx, |app, cx| {
-                if let ActiveDialog::Creating {
-                    ref mut current_step,
-                    ..
-                } = app.active_dialog
-                {
-                    *current_step = step;
+                if let ActiveDialog::Creating { progress, .. } = &mut app.active_dialog {
+                    progress.advance(step);
                 }
                 cx.notify();
             });
@@ -155,34 +592,57 @@ impl SashikiApp {
         {
             let mw = main_workdir.clone();
             let gd = git_dir.clone();
-            let wn = worktree_name.clone();
-            let br = branch.clone();
+            let label = new.label().to_string();
             let wp = worktree_path.clone();
 
-            let worktree = smol::unblock(move || {
-                if let Some(parent) = wp.parent() {
-                    std::fs::create_dir_all(parent)
-                        .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-                }
-                let repo = GitRepo::from_parts(mw, gd);
-                repo.create_worktree(&wn, &br, &wp)
-                    .map_err(|e| format!("Failed to create worktree: {}", e))
-            })
-            .await?;
+            let queue = entity
+                .update(cx, |app, _| app.repo.git_queue.clone())
+                .map_err(|e| e.to_string())?;
+            let worktree = queue
+                .push(
+                    format!("Create worktree {}", label),
+                    Some(&worktree_path),
+                    move || {
+                        if let Some(parent) = wp.parent() {
+                            std::fs::create_dir_all(parent)
+                                .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+                        }
+                        let repo = GitRepo::from_parts(mw.clone(), gd.clone());
+                        repo.create_worktree_with(
+                            &new.name,
+                            new.branch.as_deref(),
+                            &wp,
+                            new.base.as_deref(),
+                            new.skip_hooks,
+                            &mut |line| {
+                                let _ = output.try_send(line.to_string());
+                            },
+                        )
+                        .map_err(|e| format!("Failed to create worktree: {}", e))
+                    },
+                )
+                .wait()
+                .await
+                .unwrap_or_else(|| Err("Worktree creation was cancelled".to_string()))?;
 
             step_index += 1;
             let step = step_index;
             let wt = worktree.clone();
             let _ = entity.update(cx, |app, cx| {
-                if let ActiveDialog::Creating {
-                    ref mut current_step,
-                    ..
-                } = app.active_dialog
-                {
-                    *current_step = step;
+                if let ActiveDialog::Creating { progress, .. } = &mut app.active_dialog {
+                    progress.advance(step);
                 }
                 // Add the session now so it appears in sidebar
-                app.session_manager.add_session(wt);
+                if app.repo.session_manager.add_session(wt)
+                    && let Some(session) = app.repo.session_manager.sessions().last()
+                {
+                    session.events().record(ActivityEvent::WorktreeCreated);
+                }
+                if let Some(repo) = &app.repo.git_repo {
+                    let (settings, _) = Settings::load(Some(repo));
+                    app.repo.session_manager.load_descriptions(&settings);
+                    app.repo.session_manager.load_session_states(repo.git_dir());
+                }
                 cx.notify();
             });
         }
@@ -195,34 +655,24 @@ impl SashikiApp {
 
             let copy_results = smol::unblock(move || tmpl.copy_files(&src, &dst)).await;
 
-            // Check for errors
-            let errors: Vec<_> = copy_results.iter().filter(|r| !r.success).collect();
-
-            if !errors.is_empty() {
-                let msg = errors
-                    .iter()
-                    .map(|r| {
-                        format!(
-                            "{}: {}",
-                            r.path,
-                            r.error.as_deref().unwrap_or("unknown error")
-                        )
-                    })
-                    .collect::<Vec<_>>()
-                    .join("\n");
-                eprintln!("Warning: some file copies failed:\n{}", msg);
-                // Continue despite copy errors (non-fatal)
+            // Copy errors are non-fatal: log them and continue
+            for result in copy_results.iter().filter(|r| !r.success) {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::Template,
+                    format!(
+                        "File copy failed for {}: {}",
+                        result.path,
+                        result.error.as_deref().unwrap_or("unknown error")
+                    ),
+                ));
             }
 
             step_index += 1;
             let step = step_index;
             let _ = entity.update(cx, |app, cx| {
-                if let ActiveDialog::Creating {
-                    ref mut current_step,
-                    ..
-                } = app.active_dialog
-                {
-                    *current_step = step;
+                if let ActiveDialog::Creating { progress, .. } = &mut app.active_dialog {
+                    progress.advance(step);
                 }
                 cx.notify();
             });
@@ -244,12 +694,8 @@ impl SashikiApp {
             step_index += 1;
             let step = step_index;
             let _ = entity.update(cx, |app, cx| {
-                if let ActiveDialog::Creating {
-                    ref mut current_step,
-                    ..
-                } = app.active_dialog
-                {
-                    *current_step = step;
+                if let ActiveDialog::Creating { progress, .. } = &mut app.active_dialog {
+                    progress.advance(step);
                 }
                 cx.notify();
             });
@@ -266,12 +712,15 @@ impl SashikiApp {
 
     /// Called when async creation pipeline completes successfully
     fn finish_create_worktree(&mut self, effective_workdir: PathBuf, cx: &mut Context<Self>) {
-        let new_index = self.session_manager.len() - 1;
-        self.session_manager.switch_to(new_index);
-        self.session_manager
+        let new_index = self.repo.session_manager.len() - 1;
+        self.repo.session_manager.switch_to(new_index);
+        self.repo
+            .session_manager
             .ensure_active_session_terminal_in(effective_workdir, cx);
+        self.update_activity_watch(cx);
 
         self.refresh_file_list();
+        self.refresh_conflicts_async(true, cx);
         self.active_dialog = ActiveDialog::None;
         cx.notify();
     }
@@ -279,23 +728,46 @@ impl SashikiApp {
     // === Delete worktree ===
 
     pub fn open_delete_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
-        let sessions = self.session_manager.sessions();
+        let sessions = self.repo.session_manager.sessions();
         if index < sessions.len() && !sessions[index].is_main() {
             self.active_dialog = ActiveDialog::DeleteConfirm {
                 target_index: index,
+                keep_note: true,
             };
             cx.notify();
         }
     }
 
     pub fn close_delete_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        // Cancelling one worktree of a bulk delete stops the rest
+        if self.maintenance.delete_queue.take().is_some() {
+            self.open_maintenance_dialog(cx);
+        }
+        cx.notify();
+    }
+
+    pub fn toggle_delete_keep_note(&mut self, cx: &mut Context<Self>) {
+        if let ActiveDialog::DeleteConfirm { keep_note, .. } = &mut self.active_dialog {
+            *keep_note = !*keep_note;
+            cx.notify();
+        }
+    }
+
+    pub fn close_quit_dialog(&mut self, cx: &mut Context<Self>) {
         self.active_dialog = ActiveDialog::None;
         cx.notify();
     }
 
+    pub fn confirm_quit(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.quit();
+    }
+
     pub fn confirm_delete_worktree(&mut self, cx: &mut Context<Self>) {
         let ActiveDialog::DeleteConfirm {
             target_index: index,
+            keep_note,
         } = self.active_dialog
         else {
             self.close_delete_dialog(cx);
@@ -303,7 +775,7 @@ impl SashikiApp {
         };
 
         let (worktree_name, worktree_path, is_main) = {
-            let sessions = self.session_manager.sessions();
+            let sessions = self.repo.session_manager.sessions();
             if index >= sessions.len() {
                 self.close_delete_dialog(cx);
                 return;
@@ -323,16 +795,34 @@ impl SashikiApp {
 
         self.prepare_session_for_deletion(index, cx);
         self.cleanup_resources_for_deletion(index, cx);
-
-        if let Some(ref repo) = self.git_repo {
-            // Non-fatal: git worktree prune will clean up orphaned entries.
-            if let Err(e) = repo.remove_worktree(&worktree_name) {
-                eprintln!("Warning: git worktree remove failed: {}", e);
-            }
+        if keep_note {
+            self.close_notes_of(&worktree_name, true);
+        } else {
+            self.discard_note(&worktree_name);
         }
+        self.remove_session_state(&worktree_name);
+
+        // Queued now, so operations queued after the delete (e.g. deleting
+        // the branch) run after it
+        let unregister = self.repo.git_repo.clone().map(|repo| {
+            self.repo.git_queue.push(
+                format!("Remove worktree {}", worktree_name),
+                Some(&worktree_path),
+                move || {
+                    // Non-fatal: git worktree prune will clean up orphaned entries.
+                    if let Err(e) = repo.remove_worktree(&worktree_name) {
+                        eprintln!("Warning: git worktree remove failed: {}", e);
+                    }
+                    repo.remove_session_config(&worktree_name);
+                },
+            )
+        });
 
         self.active_dialog = ActiveDialog::Deleting;
         cx.spawn(async move |entity, cx| {
+            if let Some(op) = unregister {
+                op.wait().await;
+            }
             let result = Self::remove_worktree_directory_async(&worktree_path).await;
             let _ = entity.update(cx, |app, cx| {
                 app.finish_delete_worktree(index, result, cx);
@@ -388,47 +878,54 @@ impl SashikiApp {
         cx: &mut Context<Self>,
     ) {
         if let Err(e) = result {
-            self.active_dialog = ActiveDialog::Error { message: e };
+            self.maintenance.delete_queue = None;
+            self.active_dialog = ActiveDialog::error(e);
             cx.notify();
             return;
         }
 
-        self.session_manager.remove_session(index);
+        if let Some(session) = self.repo.session_manager.sessions().get(index) {
+            hooks::emit(HookEvent::WorktreeRemoved, &session.hook_subject());
+        }
+        self.repo.session_manager.remove_session(index);
+        self.forget_removed_worktrees();
         self.refresh_file_list();
+        self.refresh_conflicts_async(true, cx);
         self.active_dialog = ActiveDialog::None;
+        self.continue_bulk_delete(cx);
         cx.notify();
     }
 
     pub fn prepare_session_for_deletion(&mut self, index: usize, cx: &mut Context<Self>) {
-        let is_active = self.session_manager.active_index() == index;
+        let is_active = self.repo.session_manager.active_index() == index;
         if !is_active {
             return;
         }
 
         let new_index = self
+            .repo
             .session_manager
             .sessions()
             .iter()
             .position(|s| s.is_main())
-            .or_else(|| (0..self.session_manager.len()).find(|&i| i != index));
+            .or_else(|| (0..self.repo.session_manager.len()).find(|&i| i != index));
 
         if let Some(new_idx) = new_index {
-            self.session_manager.switch_to(new_idx);
-            self.session_manager.ensure_active_session_terminal(cx);
+            self.repo.session_manager.switch_to(new_idx);
+            self.repo.session_manager.ensure_active_session_terminal(cx);
+            self.update_activity_watch(cx);
         }
     }
 
     pub fn cleanup_resources_for_deletion(&mut self, index: usize, cx: &mut Context<Self>) {
-        if let Some(terminal) = self.session_manager.get_session_active_terminal(index) {
-            terminal.update(cx, |view, _cx| view.shutdown());
-        }
+        // The worktree goes away, so tmux sessions in it are ended too
+        self.repo.session_manager.kill_session_terminals(index, cx);
+        self.update_activity_watch(cx);
 
         self.invalidate_worktree_repo_cache();
 
         self.file_view.update(cx, |view, _cx| view.close());
         self.show_file_view = false;
-
-        self.session_manager.clear_session_terminals(index);
     }
 
     pub fn close_error_dialog(&mut self, cx: &mut Context<Self>) {
@@ -439,25 +936,16 @@ impl SashikiApp {
     // === Template settings ===
 
     pub fn open_template_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let template = self
+        let templates = self
+            .repo
             .git_repo
             .as_ref()
-            .map(TemplateConfig::load)
+            .map(TemplateSet::load)
             .unwrap_or_default();
-        self.settings_inputs = [
-            template.pre_create_commands.join("\n"),
-            template.file_copies.join("\n"),
-            template.post_create_commands.join("\n"),
-            template.working_directory.clone().unwrap_or_default(),
-        ];
-        self.settings_cursors = [
-            self.settings_inputs[0].chars().count(),
-            self.settings_inputs[1].chars().count(),
-            self.settings_inputs[2].chars().count(),
-            self.settings_inputs[3].chars().count(),
-        ];
-        self.template_edit = Some(template);
-        self.settings_active_section = 0;
+        self.template_edit = Some(templates);
+        self.template_selected = None;
+        self.template_error = None;
+        self.load_template_inputs();
         self.active_dialog = ActiveDialog::TemplateSettings;
         cx.notify();
         // Focus on the next frame so track_focus has registered the handle
@@ -468,19 +956,31 @@ impl SashikiApp {
         });
     }
 
-    pub fn close_template_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        self.template_edit = None;
-        self.settings_inputs = Default::default();
-        self.settings_cursors = Default::default();
-        self.active_dialog = ActiveDialog::None;
-        if let Some(terminal) = self.active_terminal() {
-            let focus = terminal.read(cx).focus_handle(cx);
-            window.focus(&focus, cx);
-        }
-        cx.notify();
+    /// Fill the input fields from the selected template
+    fn load_template_inputs(&mut self) {
+        let Some(templates) = self.template_edit.as_ref() else {
+            return;
+        };
+        let named = self.template_selected.and_then(|i| templates.named.get(i));
+        let template = named.map(|t| &t.config).unwrap_or(&templates.fallback);
+        self.settings_inputs = [
+            template.pre_create_commands.join("\n"),
+            template.file_copies.join("\n"),
+            template.post_create_commands.join("\n"),
+            template.working_directory.clone().unwrap_or_default(),
+            named.map(|t| t.name.clone()).unwrap_or_default(),
+            named.map(|t| t.branch_match.clone()).unwrap_or_default(),
+        ];
+        self.settings_cursors = self
+            .settings_inputs
+            .each_ref()
+            .map(|input| input.chars().count());
+        self.settings_active_section = 0;
     }
 
-    pub fn save_template_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+    /// Write the input fields back into the selected template. A named
+    /// template needs a valid, unique name and a branch glob.
+    fn store_template_inputs(&mut self) -> Result<(), String> {
         let parse_lines = |s: &str| -> Vec<String> {
             s.lines()
                 .map(|l| l.trim().to_string())
@@ -488,32 +988,126 @@ impl SashikiApp {
                 .collect()
         };
 
-        if let Some(ref mut template) = self.template_edit {
-            template.pre_create_commands = parse_lines(&self.settings_inputs[0]);
-            template.file_copies = parse_lines(&self.settings_inputs[1]);
-            template.post_create_commands = parse_lines(&self.settings_inputs[2]);
-            let workdir = self.settings_inputs[3].trim().to_string();
-            template.working_directory = if workdir.is_empty() {
+        let selected = self.template_selected;
+        let inputs = &self.settings_inputs;
+        let Some(templates) = self.template_edit.as_mut() else {
+            return Ok(());
+        };
+
+        let workdir = inputs[3].trim().to_string();
+        // The environment has no input field and is kept as configured
+        let env = selected
+            .and_then(|i| templates.named.get(i))
+            .map(|t| &t.config)
+            .unwrap_or(&templates.fallback)
+            .env
+            .clone();
+        let config = TemplateConfig {
+            pre_create_commands: parse_lines(&inputs[0]),
+            file_copies: parse_lines(&inputs[1]),
+            post_create_commands: parse_lines(&inputs[2]),
+            working_directory: if workdir.is_empty() {
                 None
             } else {
                 Some(workdir)
+            },
+            env,
+        };
+
+        let Some(index) = selected else {
+            templates.fallback = config;
+            return Ok(());
+        };
+        let name = inputs[4].trim().to_string();
+        let branch_match = inputs[5].trim().to_string();
+        if !template::is_valid_template_name(&name) {
+            return Err(format!(
+                "Invalid template name \"{}\": use letters, digits, - and _",
+                name
+            ));
+        }
+        if templates
+            .named
+            .iter()
+            .enumerate()
+            .any(|(i, t)| i != index && t.name == name)
+        {
+            return Err(format!("A template named \"{}\" already exists", name));
+        }
+        if branch_match.is_empty() {
+            return Err(format!("Template \"{}\" needs a branch match", name));
+        }
+        if let Some(template) = templates.named.get_mut(index) {
+            *template = NamedTemplate {
+                name,
+                branch_match,
+                config,
             };
+        }
+        Ok(())
+    }
 
-            if let Some(ref repo) = self.git_repo {
-                if let Err(e) = template.save(repo) {
-                    self.active_dialog = ActiveDialog::Error {
-                        message: format!("Failed to save settings: {}", e),
-                    };
-                    self.template_edit = None;
-                    cx.notify();
-                    return;
-                }
+    /// Show another template in the settings dialog (None = the fallback)
+    pub fn select_template(&mut self, index: Option<usize>, cx: &mut Context<Self>) {
+        if index == self.template_selected {
+            return;
+        }
+        match self.store_template_inputs() {
+            Ok(()) => {
+                self.template_selected = index;
+                self.template_error = None;
+                self.load_template_inputs();
             }
+            Err(e) => self.template_error = Some(e),
         }
+        cx.notify();
+    }
 
-        self.apply_template_working_directory_defaults();
+    /// Add an empty named template and select it with its name field active
+    pub fn add_template(&mut self, cx: &mut Context<Self>) {
+        if let Err(e) = self.store_template_inputs() {
+            self.template_error = Some(e);
+            cx.notify();
+            return;
+        }
+        let Some(templates) = self.template_edit.as_mut() else {
+            return;
+        };
+        let name = (1..)
+            .map(|n| format!("template-{}", n))
+            .find(|name| !templates.named.iter().any(|t| &t.name == name))
+            .unwrap_or_default();
+        templates.named.push(NamedTemplate {
+            name,
+            branch_match: String::new(),
+            config: TemplateConfig::default(),
+        });
+        self.template_selected = Some(templates.named.len() - 1);
+        self.template_error = None;
+        self.load_template_inputs();
+        self.settings_active_section = 5;
+        cx.notify();
+    }
 
+    /// Remove the selected named template and show the fallback
+    pub fn delete_template(&mut self, cx: &mut Context<Self>) {
+        let (Some(templates), Some(index)) = (self.template_edit.as_mut(), self.template_selected)
+        else {
+            return;
+        };
+        if index < templates.named.len() {
+            templates.named.remove(index);
+        }
+        self.template_selected = None;
+        self.template_error = None;
+        self.load_template_inputs();
+        cx.notify();
+    }
+
+    pub fn close_template_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         self.template_edit = None;
+        self.template_selected = None;
+        self.template_error = None;
         self.settings_inputs = Default::default();
         self.settings_cursors = Default::default();
         self.active_dialog = ActiveDialog::None;
@@ -524,32 +1118,37 @@ impl SashikiApp {
         cx.notify();
     }
 
+    pub fn save_template_settings(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Err(e) = self.store_template_inputs() {
+            self.template_error = Some(e);
+            cx.notify();
+            return;
+        }
+
+        let saved = match (&self.template_edit, &self.repo.git_repo) {
+            (Some(templates), Some(repo)) => templates.save(repo),
+            _ => Ok(()),
+        };
+        if let Err(e) = saved {
+            self.active_dialog = ActiveDialog::error(format!("Failed to save settings: {}", e));
+            self.template_edit = None;
+            cx.notify();
+            return;
+        }
+
+        self.apply_template_working_directory_defaults();
+        self.close_template_settings(window, cx);
+    }
+
     // === Open folder ===
 
     pub fn on_open_folder(
         &mut self,
         _: &super::OpenFolder,
-        _window: &mut Window,
+        window: &mut Window,
         cx: &mut Context<Self>,
     ) {
         self.open_menu = None;
-
-        let paths_receiver = cx.prompt_for_paths(PathPromptOptions {
-            files: false,
-            directories: true,
-            multiple: false,
-            prompt: None,
-        });
-
-        cx.spawn(async move |entity, cx| {
-            if let Ok(Ok(Some(paths))) = paths_receiver.await {
-                if let Some(path) = paths.into_iter().next() {
-                    let _ = entity.update(cx, |app, cx| {
-                        app.open_project(path, cx);
-                    });
-                }
-            }
-        })
-        .detach();
+        self.show_open_dialog(OpenMode::Path, window, cx);
     }
 }
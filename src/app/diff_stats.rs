@@ -0,0 +1,95 @@
+//! Top changed files behind the diff stats in the header
+
+use super::SashikiApp;
+use super::actions::ShowDiffStats;
+use crate::git::FileChurn;
+use gpui::{Context, Focusable, Window};
+
+/// Files listed under the diff stats
+pub const TOP_CHURN_FILES: usize = 10;
+
+/// State of the list under the diff stats
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DiffStatsPopover {
+    /// Keyboard highlight
+    pub selected: usize,
+    /// Opened by a click or the keyboard: stays open when the pointer leaves
+    pub pinned: bool,
+}
+
+impl SashikiApp {
+    /// Files with the most lines touched in the active session
+    pub fn top_churn_files(&self) -> Vec<FileChurn> {
+        self.repo
+            .session_manager
+            .active_session()
+            .and_then(|s| self.repo.file_cache.get(s.worktree_path()))
+            .map(|files| {
+                files
+                    .top_churn(TOP_CHURN_FILES, &self.review.exclude)
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Show the top changed files; a pinned list takes the keyboard
+    pub fn open_diff_stats(&mut self, pinned: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let popover = self.diff_stats_popover.get_or_insert_default();
+        popover.pinned |= pinned;
+        if pinned {
+            window.focus(&self.diff_stats_focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn close_diff_stats(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.diff_stats_popover.take().is_none() {
+            return;
+        }
+        if self.diff_stats_focus.is_focused(window)
+            && let Some(terminal) = self.active_terminal()
+        {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn on_show_diff_stats(
+        &mut self,
+        _: &ShowDiffStats,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_diff_stats(true, window, cx);
+    }
+
+    pub fn move_diff_stats_selection(&mut self, delta: isize, cx: &mut Context<Self>) {
+        let count = self.top_churn_files().len();
+        let Some(popover) = self.diff_stats_popover.as_mut() else {
+            return;
+        };
+        if count == 0 {
+            return;
+        }
+        popover.selected = (popover.selected as isize + delta).rem_euclid(count as isize) as usize;
+        cx.notify();
+    }
+
+    /// Open the diff of the listed file at `index`
+    pub fn open_churn_file(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.top_churn_files().into_iter().nth(index) else {
+            return;
+        };
+        let change_type = self
+            .repo
+            .changed_files
+            .iter()
+            .find(|f| f.path == file.path)
+            .map(|f| f.change_type);
+        self.close_diff_stats(window, cx);
+        self.on_file_selected(file.path, change_type, cx);
+    }
+}
@@ -0,0 +1,133 @@
+//! Discarding working tree changes of the active worktree (a hunk or a whole file)
+
+use super::SashikiApp;
+use crate::dialog::{ActiveDialog, DiscardTarget};
+use crate::encoding::TextEncoding;
+use crate::git::ChangeType;
+use gpui::Context;
+use std::path::{Path, PathBuf};
+
+impl SashikiApp {
+    /// Ask before reverting one hunk of the file shown in the file view
+    pub fn open_discard_hunk_dialog(
+        &mut self,
+        path: PathBuf,
+        patch: String,
+        encoding: TextEncoding,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_dialog = ActiveDialog::DiscardConfirm {
+            target: DiscardTarget::Hunk {
+                path,
+                patch,
+                encoding,
+            },
+        };
+        cx.notify();
+    }
+
+    /// Ask before discarding all changes to `path` (relative to the worktree)
+    pub fn open_discard_file_dialog(
+        &mut self,
+        path: PathBuf,
+        change_type: ChangeType,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(worktree_path) = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+        else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::DiscardConfirm {
+            target: DiscardTarget::File {
+                path: worktree_path.join(path),
+                is_new: change_type == ChangeType::Added,
+            },
+        };
+        cx.notify();
+    }
+
+    pub fn close_discard_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Discard the confirmed changes, then refresh the file list and the shown diff
+    pub fn confirm_discard(&mut self, cx: &mut Context<Self>) {
+        let target = match std::mem::take(&mut self.active_dialog) {
+            ActiveDialog::DiscardConfirm { target } => target,
+            other => {
+                self.active_dialog = other;
+                return;
+            }
+        };
+        if !self.ensure_writable(
+            self.repo.session_manager.active_index(),
+            "discard changes",
+            cx,
+        ) {
+            return;
+        }
+
+        let result = match self.worktree_repo() {
+            Some(repo) => match &target {
+                DiscardTarget::Hunk {
+                    patch, encoding, ..
+                } => repo.discard_hunk(patch, *encoding),
+                DiscardTarget::File { path, .. } => repo.discard_file(path),
+            },
+            None => {
+                self.active_dialog = ActiveDialog::error("Git repository not available");
+                cx.notify();
+                return;
+            }
+        };
+
+        let (DiscardTarget::Hunk { path, .. } | DiscardTarget::File { path, .. }) = &target;
+        self.refresh_file_list();
+        self.refresh_file_view_for(path, cx);
+
+        if let Err(e) = result {
+            self.active_dialog = ActiveDialog::error(match target {
+                DiscardTarget::Hunk { .. } => format!(
+                    "Failed to discard hunk: it no longer applies cleanly (the file changed since the diff was shown).\nThe diff has been refreshed; review it and try again.\n\n{}",
+                    e
+                ),
+                DiscardTarget::File { path, .. } => {
+                    format!("Failed to discard changes to {}: {}", path.display(), e)
+                }
+            });
+        }
+        cx.notify();
+    }
+
+    /// Reload the diff if the file view shows `full_path` (closing it if the file is gone)
+    pub(crate) fn refresh_file_view_for(&mut self, full_path: &Path, cx: &mut Context<Self>) {
+        if self.file_view.read(cx).file_path() != Some(full_path) {
+            return;
+        }
+        if !full_path.exists() {
+            self.show_file_view = false;
+            self.file_view.update(cx, |view, _cx| view.close());
+            return;
+        }
+
+        let relative_path = self
+            .repo
+            .session_manager
+            .active_session()
+            .and_then(|s| full_path.strip_prefix(s.worktree_path()).ok())
+            .unwrap_or(full_path)
+            .to_path_buf();
+        let change_type = self
+            .repo
+            .changed_files
+            .iter()
+            .find(|f| f.path == relative_path)
+            .map(|f| f.change_type);
+        self.on_file_selected(relative_path, change_type, cx);
+    }
+}
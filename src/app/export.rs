@@ -0,0 +1,63 @@
+//! Export State...: the sessions and their changes as JSON in a file (see
+//! `crate::export`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::export::{self, SessionState};
+use gpui::{App, Context};
+use std::time::SystemTime;
+
+impl SashikiApp {
+    /// Ask for a file and write the state of every session to it
+    pub fn export_state(&mut self, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        let repository = repo.workdir().to_path_buf();
+        // Terminal activity as of now; the changes are read once a file is chosen
+        let sessions = self.session_states(cx);
+        let path_receiver = cx.prompt_for_new_path(&repository, Some("sashiki-state.json"));
+
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = path_receiver.await else {
+                return;
+            };
+            let result = smol::unblock(move || {
+                let state = export::export(repository, sessions);
+                std::fs::write(&path, export::to_json(&state)).map(|()| path)
+            })
+            .await;
+            match result {
+                Ok(path) => app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::App,
+                    format!("Exported state to {}", path.display()),
+                )),
+                Err(e) => {
+                    let _ = this.update(cx, |app, cx| {
+                        app.active_dialog =
+                            ActiveDialog::error(format!("Failed to export state: {}", e));
+                        cx.notify();
+                    });
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Every session as of now, terminal activity included; `export::export`
+    /// reads their changes
+    pub(crate) fn session_states(&self, cx: &App) -> Vec<SessionState> {
+        let now = SystemTime::now();
+        self.repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|session| {
+                let last_output = session.output_age(cx).and_then(|age| now.checked_sub(age));
+                SessionState::new(session, session.terminal_count(), last_output)
+            })
+            .collect()
+    }
+}
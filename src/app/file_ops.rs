@@ -1,48 +1,152 @@
 //! File operation methods
 
 use super::SashikiApp;
-use crate::git::{ChangeType, GitRepo};
-use crate::ui::{ChangeInfo, FileListMode, FileTreeNode};
-use gpui::Context;
+use crate::dialog::ActiveDialog;
+use crate::diff_cache::{self, DiffStamp};
+use crate::diff_job::{self, DiffOutcome, DiffRequest};
+use crate::dirty::DirtyCounts;
+use crate::encoding::{self, TextEncoding};
+use crate::external_editor;
+use crate::git::{self, ChangeType, ChangedFile, GitRepo, split_nested_repositories};
+use crate::lfs::LfsInfo;
+use crate::review::ReviewState;
+use crate::session::LayoutMode;
+use crate::settings::Settings;
+use crate::ui::file_list::FILE_ROW_HEIGHT;
+use crate::ui::file_nav::{self, FilePosition};
+use crate::ui::file_probe;
+use crate::ui::image_preview;
+use crate::ui::virtual_rows::{self, VisibleWindow};
+use crate::ui::{
+    ChangeInfo, EXCLUDED_GROUP, FileListMode, FileTreeNode, lazy_visible_rows, read_dir_shallow,
+    reveal_row, summarize_dir_changes,
+};
+use crate::worktree_guard;
+use gpui::{App, Context, Window, point, px};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 impl SashikiApp {
     /// Refresh file list and rebuild tree for the active session (sync)
     pub fn refresh_file_list(&mut self) {
         self.invalidate_worktree_repo_cache();
+        if let Some(worktree) = self.active_worktree_path() {
+            self.repo.file_cache.invalidate_listings(&worktree);
+        }
         self.refresh_changed_files_sync();
         if self.file_list_mode == FileListMode::Changes {
             self.build_file_tree();
         }
+        self.refresh_stashes();
     }
 
-    /// Async version of refresh_file_list - spawns background task
+    /// Async version of refresh_file_list - spawns background task.
+    ///
+    /// Sessions visible in parallel mode are refreshed along with the active
+    /// one when their cached files are older than `BACKGROUND_REFRESH_INTERVAL`.
     pub fn refresh_file_list_async(&mut self, cx: &mut Context<Self>) {
         self.invalidate_worktree_repo_cache();
 
-        let worktree_path = self
-            .session_manager
-            .active_session()
-            .map(|s| s.worktree_path().to_path_buf());
-
-        let file_list_mode = self.file_list_mode;
+        let now = Instant::now();
+        let active_path = self.active_worktree_path();
+        let background: Vec<PathBuf> = match self.repo.session_manager.layout_mode() {
+            LayoutMode::Single => Vec::new(),
+            LayoutMode::Parallel => self
+                .repo
+                .session_manager
+                .parallel_sessions()
+                .into_iter()
+                .map(|(_, s)| s.worktree_path().to_path_buf())
+                .filter(|path| {
+                    Some(path) != active_path.as_ref()
+                        && self.repo.file_cache.needs_background_refresh(path, now)
+                })
+                .collect(),
+        };
+        for path in active_path.iter().chain(&background) {
+            self.repo.file_cache.mark_viewed(path, now);
+        }
+        self.repo.file_cache.evict_idle_listings(now);
+        let untracked_limit = self.untracked_limit;
 
         cx.spawn(async move |entity, cx| {
-            let files = if let Some(path) = worktree_path {
-                GitRepo::open(&path)
-                    .ok()
-                    .and_then(|repo| repo.get_changed_files().ok())
-                    .unwrap_or_default()
-            } else {
-                Vec::new()
-            };
+            let (active, stashes, background) = smol::unblock(move || {
+                let repo = active_path
+                    .as_ref()
+                    .and_then(|path| GitRepo::open(path).ok());
+                let listed = repo
+                    .as_ref()
+                    .and_then(|repo| repo.get_changed_files_within(untracked_limit).ok());
+                let (files, unlisted) = match listed {
+                    Some((files, unlisted)) => (Some(files), unlisted),
+                    None => (None, 0),
+                };
+                // Not read when the files were not: marks are only dropped
+                // for files known to be no longer changed
+                let hashes = repo
+                    .as_ref()
+                    .zip(files.as_ref())
+                    .and_then(|(repo, files)| repo.content_hashes(files).ok());
+                let files = files.unwrap_or_default();
+                let lfs = repo
+                    .as_ref()
+                    .and_then(|repo| repo.lfs_paths(files.iter().map(|f| f.path.as_path())).ok())
+                    .unwrap_or_default();
+                let sparse = repo.as_ref().and_then(|repo| repo.sparse_checkout());
+                let churn = repo
+                    .as_ref()
+                    .and_then(|repo| repo.diff_numstat().ok())
+                    .unwrap_or_default();
+                let stashes = repo
+                    .as_ref()
+                    .and_then(|repo| repo.stash_list().ok())
+                    .unwrap_or_default();
+                let background: Vec<(PathBuf, Vec<ChangedFile>, usize)> = background
+                    .into_iter()
+                    .filter_map(|path| {
+                        let (files, unlisted) = GitRepo::open(&path)
+                            .ok()?
+                            .get_changed_files_within(untracked_limit)
+                            .ok()?;
+                        Some((path, files, unlisted))
+                    })
+                    .collect();
+                (
+                    active_path.map(|path| (path, files, unlisted, churn, hashes, sparse, lfs)),
+                    stashes,
+                    background,
+                )
+            })
+            .await;
 
             // Ignore error: only fails if entity was dropped (app closed)
             let _ = entity.update(cx, |app, cx| {
-                app.changed_files = files;
-                if file_list_mode == FileListMode::Changes {
-                    app.build_file_tree();
+                if let Some((path, files, unlisted, churn, hashes, sparse, lfs)) = active {
+                    app.repo
+                        .file_cache
+                        .store_churn(&path, churn, Instant::now());
+                    app.repo
+                        .file_cache
+                        .store_lfs_paths(&path, lfs, Instant::now());
+                    app.repo
+                        .file_cache
+                        .store_sparse_checkout(&path, sparse, Instant::now());
+                    if let Some(hashes) = hashes {
+                        app.store_content_hashes(&path, &files, hashes);
+                    }
+                    app.store_changed_files(&path, files, unlisted);
+                }
+                for (path, files, unlisted) in background {
+                    app.store_changed_files(&path, files, unlisted);
                 }
+                app.repo.stashes = stashes;
+                if std::mem::take(&mut app.open_diff_stale) {
+                    app.reload_open_diff(cx);
+                }
+                app.sync_file_position(false, cx);
+                app.prefetch_diffs(cx);
+                app.refresh_conflicts_async(false, cx);
+                app.refresh_pull_requests_async(false, cx);
                 cx.notify();
             });
         })
@@ -52,69 +156,383 @@ impl SashikiApp {
     /// Synchronous version of refresh_changed_files (for initial load)
     pub fn refresh_changed_files_sync(&mut self) {
         let worktree_path = self
+            .repo
             .session_manager
             .active_session()
             .map(|s| s.worktree_path().to_path_buf());
 
         if let Some(path) = worktree_path
             && let Ok(repo) = GitRepo::open(&path)
-            && let Ok(files) = repo.get_changed_files()
+            && let Ok((files, unlisted)) = repo.get_changed_files_within(self.untracked_limit)
         {
-            self.changed_files = files;
+            let churn = repo.diff_numstat().unwrap_or_default();
+            self.repo
+                .file_cache
+                .store_churn(&path, churn, Instant::now());
+            self.repo.file_cache.store_sparse_checkout(
+                &path,
+                repo.sparse_checkout(),
+                Instant::now(),
+            );
+            if let Ok(hashes) = repo.content_hashes(&files) {
+                self.store_content_hashes(&path, &files, hashes);
+            }
+            let lfs = repo
+                .lfs_paths(files.iter().map(|f| f.path.as_path()))
+                .unwrap_or_default();
+            self.repo
+                .file_cache
+                .store_lfs_paths(&path, lfs, Instant::now());
+            self.store_changed_files(&path, files, unlisted);
             return;
         }
 
-        if let Some(ref repo) = self.git_repo
+        if let Some(ref repo) = self.repo.git_repo
             && let Ok(files) = repo.get_changed_files()
         {
-            self.changed_files = files;
+            self.set_changed_files(files);
+        }
+    }
+
+    /// Store the changed files of the active worktree and aggregate them per
+    /// directory for the file list badges
+    fn set_changed_files(&mut self, files: Vec<ChangedFile>) {
+        let (files, nested) = split_nested_repositories(files);
+        self.repo.nested_repo_paths = nested;
+        self.repo.dir_changes = summarize_dir_changes(&files);
+        self.repo.changed_files = files;
+    }
+
+    /// Cache the changed files of `worktree` and the count of untracked
+    /// files left out of them, showing them if it is the active session's
+    fn store_changed_files(&mut self, worktree: &Path, files: Vec<ChangedFile>, unlisted: usize) {
+        let now = Instant::now();
+        self.repo
+            .file_cache
+            .store_changed_files(worktree, files, now);
+        self.repo
+            .file_cache
+            .store_unlisted_untracked(worktree, unlisted, now);
+        if let Some(index) = self.repo.session_manager.find_session_by_path(worktree)
+            && let Some(cached) = self.repo.file_cache.get(worktree)
+        {
+            let events = self.repo.session_manager.sessions()[index].events();
+            events.changed_files(cached.changed_files.len());
+            events.dirty(
+                DirtyCounts::of(&cached.changed_files, cached.unlisted_untracked).is_dirty(),
+            );
+        }
+        if self.active_worktree_path().as_deref() == Some(worktree) {
+            self.show_cached_files();
+        }
+    }
+
+    /// Show the cached files of the active session right away (empty if it
+    /// was never read); a refresh is expected to follow
+    pub fn show_cached_files(&mut self) {
+        let (files, dir_changes, nested) = self
+            .active_worktree_path()
+            .and_then(|path| self.repo.file_cache.get(&path))
+            .map(|cached| {
+                (
+                    cached.changed_files.clone(),
+                    cached.dir_changes.clone(),
+                    cached.nested_repositories.clone(),
+                )
+            })
+            .unwrap_or_default();
+        self.repo.changed_files = files;
+        self.repo.dir_changes = dir_changes;
+        self.repo.nested_repo_paths = nested;
+        if self.file_list_mode == FileListMode::Changes {
+            self.build_file_tree();
         }
     }
 
+    /// Drop the cached files of worktrees that no longer have a session
+    pub fn forget_removed_worktrees(&mut self) {
+        self.repo.file_cache.retain_worktrees(
+            self.repo
+                .session_manager
+                .sessions()
+                .iter()
+                .map(|s| s.worktree_path()),
+        );
+        self.minimaps.retain_worktrees(
+            self.repo
+                .session_manager
+                .sessions()
+                .iter()
+                .map(|s| s.worktree_path()),
+        );
+    }
+
+    pub(crate) fn active_worktree_path(&self) -> Option<PathBuf> {
+        self.repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+    }
+
     /// Returns a cached GitRepo for the active worktree, creating it if needed.
     pub fn worktree_repo(&mut self) -> Option<&GitRepo> {
         let worktree_path = self
+            .repo
             .session_manager
             .active_session()
             .map(|s| s.worktree_path().to_path_buf())?;
 
-        if let Some((_, cached_path)) = &self.cached_worktree
+        if let Some((_, cached_path)) = &self.repo.cached_worktree
             && cached_path == &worktree_path
         {
-            return self.cached_worktree.as_ref().map(|(repo, _)| repo);
+            return self.repo.cached_worktree.as_ref().map(|(repo, _)| repo);
         }
 
         if let Ok(repo) = GitRepo::open(&worktree_path) {
-            self.cached_worktree = Some((repo, worktree_path));
-            self.cached_worktree.as_ref().map(|(repo, _)| repo)
+            self.repo.cached_worktree = Some((repo, worktree_path));
+            self.repo.cached_worktree.as_ref().map(|(repo, _)| repo)
         } else {
-            self.cached_worktree = None;
+            self.repo.cached_worktree = None;
             None
         }
     }
 
     /// Invalidate worktree repo cache (call when switching sessions)
     pub fn invalidate_worktree_repo_cache(&mut self) {
-        self.cached_worktree = None;
+        self.repo.cached_worktree = None;
     }
 
-    /// Build file tree for Changes mode
+    /// Build file tree for Changes mode, reviewed files last if they sink
+    /// and excluded files folded into one row at the end
     pub fn build_file_tree(&mut self) {
-        let files = self.changed_files.iter().map(|f| {
+        let entry = |f: &ChangedFile| {
             let info = ChangeInfo {
                 change_type: f.change_type,
                 staged: f.staged,
             };
             (f.path.clone(), Some(info))
-        });
-        self.file_tree = Some(FileTreeNode::from_files(files));
+        };
+        let (excluded, included): (Vec<&ChangedFile>, Vec<&ChangedFile>) = self
+            .repo
+            .changed_files
+            .iter()
+            .partition(|f| self.is_excluded(&f.path));
+        // Nested repositories are listed by their roots alone
+        let nested = self
+            .repo
+            .nested_repo_paths
+            .iter()
+            .map(|path| (path.clone(), None));
+        let mut tree = FileTreeNode::from_files(included.into_iter().map(entry).chain(nested));
+        if self.review.sink_reviewed {
+            tree.sink_files(&|path: &Path| self.review_state(path) == ReviewState::Reviewed);
+        }
+        if !excluded.is_empty() {
+            tree.children.push(FileTreeNode::excluded_group(
+                self.review.exclude.group_label(excluded.len()),
+                excluded.into_iter().map(entry),
+            ));
+        }
+        self.repo.file_tree = Some(tree);
+    }
+
+    /// Expand exactly the directories containing changes, collapsing the rest
+    pub fn expand_to_changes(&mut self) {
+        // All mode rows carry absolute paths
+        let base = match self.file_list_mode {
+            FileListMode::Changes => None,
+            FileListMode::AllFiles => self
+                .repo
+                .session_manager
+                .active_session()
+                .map(|s| s.worktree_path().to_path_buf()),
+        };
+        self.repo.expanded_dirs = self
+            .repo
+            .dir_changes
+            .keys()
+            .map(|dir| match &base {
+                Some(base) => base.join(dir),
+                None => dir.clone(),
+            })
+            .collect();
     }
 
     pub fn toggle_dir_expanded(&mut self, path: &Path) {
-        if self.expanded_dirs.contains(path) {
-            self.expanded_dirs.remove(path);
+        if self.repo.expanded_dirs.contains(path) {
+            self.repo.expanded_dirs.remove(path);
         } else {
-            self.expanded_dirs.insert(path.to_path_buf());
+            self.repo.expanded_dirs.insert(path.to_path_buf());
+        }
+    }
+
+    /// Flatten the file list for this frame and work out which rows to build.
+    ///
+    /// The scroll position is re-anchored to the previous top row's path, so a
+    /// refresh that adds or removes files above it does not move the view.
+    pub(crate) fn update_file_rows(&mut self, window: &Window, cx: &App) {
+        if !self.show_file_list || self.repo.session_manager.layout_mode() != LayoutMode::Single {
+            self.file_rows.clear();
+            self.revealed_row = None;
+            return;
+        }
+
+        self.file_rows = match self.file_list_mode {
+            FileListMode::Changes => self
+                .repo
+                .file_tree
+                .as_ref()
+                .map(|tree| {
+                    tree.visible_rows(
+                        &self.repo.expanded_dirs,
+                        &self.repo.submodule_paths,
+                        &self.repo.nested_repo_paths,
+                    )
+                })
+                .unwrap_or_default(),
+            FileListMode::AllFiles => match self.active_worktree_path() {
+                Some(root) => {
+                    let now = Instant::now();
+                    // A sparse worktree lists only what its patterns check out
+                    let sparse = self
+                        .repo
+                        .file_cache
+                        .get(&root)
+                        .and_then(|files| files.sparse.clone());
+                    let cache = &mut self.repo.file_cache;
+                    lazy_visible_rows(
+                        &root,
+                        &self.repo.expanded_dirs,
+                        &self.repo.submodule_paths,
+                        &self.repo.nested_repo_paths,
+                        &mut |dir| {
+                            cache
+                                .listing(&root, dir, now, |dir| {
+                                    read_dir_shallow(dir).unwrap_or_default()
+                                })
+                                .iter()
+                                .filter(|(path, is_dir)| {
+                                    sparse.as_ref().is_none_or(|sparse| {
+                                        path.strip_prefix(&root)
+                                            .is_ok_and(|path| sparse.includes(path, *is_dir))
+                                    })
+                                })
+                                .cloned()
+                                .collect()
+                        },
+                    )
+                }
+                None => Vec::new(),
+            },
+        };
+
+        let revealed_index = self
+            .file_view
+            .read(cx)
+            .file_path()
+            .and_then(|path| self.file_row_path(path))
+            .and_then(|path| reveal_row(&self.file_rows, &path));
+        self.revealed_row = revealed_index.map(|index| self.file_rows[index].path.clone());
+
+        // Before the first layout the list has no bounds yet; assume the window height
+        let mut viewport_height = f32::from(self.file_list_scroll.bounds().size.height);
+        if viewport_height <= 0.0 {
+            viewport_height = f32::from(window.viewport_size().height);
+        }
+
+        let scroll_top = -f32::from(self.file_list_scroll.offset().y);
+        let mut top = self.file_list_anchor.resolve(
+            &self.file_rows,
+            |row| &row.path,
+            scroll_top,
+            FILE_ROW_HEIGHT,
+        );
+        if std::mem::take(&mut self.reveal_pending)
+            && let Some(index) = revealed_index
+        {
+            top = virtual_rows::reveal(index, FILE_ROW_HEIGHT, top, viewport_height);
+        }
+        if top != scroll_top {
+            self.file_list_scroll.set_offset(point(px(0.), px(-top)));
+        }
+
+        self.file_rows_window =
+            VisibleWindow::compute(self.file_rows.len(), FILE_ROW_HEIGHT, top, viewport_height);
+    }
+
+    /// `full_path` as file list rows name it: relative to the worktree in
+    /// Changes mode, absolute in All mode (None outside the active worktree)
+    fn file_row_path(&self, full_path: &Path) -> Option<PathBuf> {
+        let worktree = self.repo.session_manager.active_session()?.worktree_path();
+        let relative = full_path.strip_prefix(worktree).ok()?;
+        Some(match self.file_list_mode {
+            FileListMode::Changes => relative.to_path_buf(),
+            FileListMode::AllFiles => full_path.to_path_buf(),
+        })
+    }
+
+    /// Expand the file list down to `full_path` and scroll it into view on
+    /// the next frame. The row is highlighted while the file is open.
+    pub fn reveal_in_file_list(&mut self, full_path: &Path) {
+        let Some(worktree) = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+        else {
+            return;
+        };
+        let Ok(relative) = full_path.strip_prefix(&worktree) else {
+            return;
+        };
+        for dir in relative.ancestors().skip(1) {
+            if dir.as_os_str().is_empty() {
+                break;
+            }
+            self.repo.expanded_dirs.insert(match self.file_list_mode {
+                FileListMode::Changes => dir.to_path_buf(),
+                FileListMode::AllFiles => worktree.join(dir),
+            });
+        }
+        self.show_file_list = true;
+        self.reveal_pending = true;
+    }
+
+    /// Original path of `path` (relative to the worktree) if it is a renamed file
+    pub fn renamed_from(&self, path: &Path) -> Option<&Path> {
+        self.changed_file(path).and_then(|f| f.old_path.as_deref())
+    }
+
+    /// The changed file at `path` (relative) in the active worktree
+    pub fn changed_file(&self, path: &Path) -> Option<&ChangedFile> {
+        self.repo.changed_files.iter().find(|f| f.path == path)
+    }
+
+    /// False (with the reason in the error dialog) when `path` (full) leads
+    /// outside the worktree of its session, through `..` or a symlink (see
+    /// `worktree_guard`)
+    pub(crate) fn ensure_inside_worktree(
+        &mut self,
+        path: &Path,
+        action: &str,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(worktree) = self
+            .repo
+            .session_manager
+            .find_session_containing(path)
+            .and_then(|index| self.repo.session_manager.sessions().get(index))
+            .map(|session| session.worktree_path().to_path_buf())
+        else {
+            return true;
+        };
+        match worktree_guard::check(&worktree, path) {
+            Ok(_) => true,
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!("Cannot {}: {}", action, e));
+                cx.notify();
+                false
+            }
         }
     }
 
@@ -124,34 +542,644 @@ impl SashikiApp {
         change_type: Option<ChangeType>,
         cx: &mut Context<Self>,
     ) {
-        let full_path = if let Some(session) = self.session_manager.active_session() {
+        let full_path = if let Some(session) = self.repo.session_manager.active_session() {
             session.worktree_path().join(&path)
         } else {
             path.clone()
         };
+        if !self.ensure_inside_worktree(&full_path, "open the file", cx) {
+            return;
+        }
+        self.sync_file_view_read_only(cx);
+        self.sync_file_view_comments(&full_path, cx);
+        self.review_file_opened(&path);
+        // A diff still being computed for the previous file is not shown
+        self.diff_requests.cancel();
+        self.diff_job = None;
+
+        if self.repo.submodule_paths.contains(&path) {
+            self.open_submodule(path, full_path, cx);
+            return;
+        }
+        if self.repo.nested_repo_paths.contains(&path) {
+            self.open_nested_repository_dialog(path, cx);
+            return;
+        }
+
+        let renamed_from = match change_type {
+            Some(ChangeType::Renamed) => self.renamed_from(&path).map(Path::to_path_buf),
+            _ => None,
+        };
+
+        // LFS files are shown from their pointers instead of diffing a
+        // pointer against the object's content
+        if self.is_lfs_file(&path)
+            && let Some(repo) = self.worktree_repo().cloned()
+        {
+            let head_path = renamed_from.clone().unwrap_or_else(|| path.clone());
+            let info = LfsInfo::read(&repo, &full_path, &head_path, change_type);
+            self.file_view.update(cx, |view, _cx| {
+                view.open_lfs(full_path.clone(), info, change_type);
+                view.set_renamed_from(renamed_from);
+            });
+            self.reveal_in_file_list(&full_path);
+            self.reveal_file_view();
+            self.sync_file_position(false, cx);
+            cx.notify();
+            return;
+        }
 
-        let diff = self.worktree_repo().and_then(|repo| match change_type {
-            Some(ChangeType::Added) => repo.generate_added_diff(&full_path).ok(),
-            Some(ChangeType::Deleted) => repo.generate_deleted_diff(&full_path).ok(),
-            _ => repo.get_file_diff(&full_path).ok(),
+        // Binary files and text over the size limit are neither read whole nor diffed
+        let (settings, _) = Settings::load(self.repo.git_repo.as_ref());
+        let max_size = settings.viewer_max_file_size();
+        let max_image_size = settings.viewer_max_image_size();
+        let probe = match change_type {
+            Some(ChangeType::Deleted) => None,
+            _ => file_probe::probe(&full_path, max_size).ok(),
+        };
+        if let Some(probe) = probe.filter(|probe| !probe.is_text()) {
+            self.file_view.update(cx, |view, cx| {
+                view.open_probed(full_path.clone(), probe, max_size, change_type, cx);
+                view.set_renamed_from(renamed_from.clone());
+            });
+            self.preview_image(
+                &path,
+                &full_path,
+                change_type,
+                renamed_from,
+                max_image_size,
+                cx,
+            );
+            self.reveal_in_file_list(&full_path);
+            self.reveal_file_view();
+            self.sync_file_position(false, cx);
+            cx.notify();
+            return;
+        }
+
+        // Conflicted files show their conflict hunks instead of a diff
+        if change_type == Some(ChangeType::Conflicted) {
+            self.file_view.update(cx, |view, _cx| {
+                let _ = view.open_conflicted(full_path.clone());
+            });
+            self.reveal_in_file_list(&full_path);
+            self.reveal_file_view();
+            self.sync_file_position(false, cx);
+            cx.notify();
+            return;
+        }
+
+        // A cached diff is shown right away; otherwise it is computed on a
+        // worker while the view shows a loading state
+        let repo = self.worktree_repo().cloned();
+        let diff = repo.as_ref().and_then(|repo| {
+            let stamp = DiffStamp::read(&full_path, repo.resolve_head().as_deref());
+            self.repo.diff_cache.get(&full_path, &stamp)
         });
+        if diff.is_some() {
+            self.revalidate_diff(full_path.clone(), change_type, renamed_from.clone(), cx);
+        }
+        let pending = diff.is_none() && repo.is_some();
 
-        self.file_view.update(cx, |view, _cx| match change_type {
-            Some(ChangeType::Deleted) => {
-                if let Some(diff_content) = diff {
+        self.file_view.update(cx, |view, _cx| {
+            match (diff, change_type) {
+                (Some(diff_content), Some(ChangeType::Deleted)) => {
                     view.open_deleted_file_with_diff(full_path.clone(), diff_content);
                 }
-            }
-            _ => {
-                if let Some(diff_content) = diff {
+                (Some(diff_content), _) => {
                     let _ = view.open_file_with_diff(full_path.clone(), diff_content);
-                } else {
+                }
+                (None, _) if pending => view.open_diff_pending(full_path.clone()),
+                (None, _) => {
                     let _ = view.open_file(full_path.clone());
                 }
             }
+            view.set_renamed_from(renamed_from.clone());
+        });
+        if let Some(repo) = repo.filter(|_| pending) {
+            let request = DiffRequest {
+                full_path: full_path.clone(),
+                change_type,
+                renamed_from: renamed_from.clone(),
+                max_size: Some(settings.diff_max_size()),
+            };
+            self.start_diff_job(repo, request, cx);
+        }
+        self.preview_image(
+            &path,
+            &full_path,
+            change_type,
+            renamed_from,
+            max_image_size,
+            cx,
+        );
+        self.reveal_in_file_list(&full_path);
+
+        self.reveal_file_view();
+        self.sync_file_position(false, cx);
+        cx.notify();
+    }
+
+    /// Whether the changed file at `path` (relative) in the active worktree
+    /// is tracked by Git LFS
+    fn is_lfs_file(&self, path: &Path) -> bool {
+        self.active_worktree_path()
+            .and_then(|worktree| self.repo.file_cache.get(&worktree))
+            .is_some_and(|cached| cached.lfs_paths.contains(path))
+    }
+
+    /// Changed files in the order of the Changes list. Excluded files come
+    /// last, and only while their row is expanded.
+    fn review_order(&self) -> Vec<&ChangedFile> {
+        let mut order = file_nav::review_order(self.reviewable_files());
+        if self.repo.expanded_dirs.contains(Path::new(EXCLUDED_GROUP)) {
+            let mut excluded: Vec<&ChangedFile> = self
+                .repo
+                .changed_files
+                .iter()
+                .filter(|f| self.is_excluded(&f.path))
+                .collect();
+            excluded.sort_by(|a, b| a.path.cmp(&b.path));
+            order.extend(excluded);
+        }
+        order
+    }
+
+    /// Index of the open file among the changed files in review order
+    fn open_file_review_index(&self, order: &[&ChangedFile], cx: &App) -> Option<usize> {
+        let worktree = self.active_worktree_path()?;
+        let open = self.file_view.read(cx).file_path()?.to_path_buf();
+        let relative = open.strip_prefix(&worktree).ok()?;
+        order.iter().position(|f| f.path == relative)
+    }
+
+    /// Show where the open file is among the changed files ("file 4 of 17")
+    /// in the file view toolbar
+    pub fn sync_file_position(&mut self, wrapped: bool, cx: &mut Context<Self>) {
+        let order = self.review_order();
+        let index = self.open_file_review_index(&order, cx);
+        if index.is_some() {
+            self.repo.review_index = index;
+        }
+        let position = index.map(|index| FilePosition {
+            index,
+            count: order.len(),
+            wrapped,
+        });
+        self.file_view.update(cx, |view, cx| {
+            if view.set_position(position) {
+                cx.notify();
+            }
+        });
+    }
+
+    /// Open the changed file after (or before) the open one, in the order of
+    /// the Changes list, keeping the diff split or inline. Returns false when
+    /// no file is open or nothing changed.
+    pub fn step_changed_file(&mut self, forward: bool, cx: &mut Context<Self>) -> bool {
+        if !self.show_file_view {
+            return false;
+        }
+        let order = self.review_order();
+        let current = self.open_file_review_index(&order, cx);
+        let Some(step) = file_nav::step(order.len(), current, self.repo.review_index, forward)
+        else {
+            return false;
+        };
+        let target = order[step.index];
+        let (path, change_type) = (target.path.clone(), target.change_type);
+        let mode = self.file_view.read(cx).mode();
+        self.on_file_selected(path, Some(change_type), cx);
+        self.file_view
+            .update(cx, |view, _cx| view.restore_diff_mode(mode));
+        self.sync_file_position(step.wrapped, cx);
+        true
+    }
+
+    /// Open the shown diff again after HEAD moved: against the new HEAD while
+    /// the file is still changed, else the file's content
+    fn reload_open_diff(&mut self, cx: &mut Context<Self>) {
+        let view = self.file_view.read(cx);
+        if !self.show_file_view || !view.is_diff_mode() {
+            return;
+        }
+        let mode = view.mode();
+        let Some(path) = self.active_worktree_path().and_then(|worktree| {
+            let open = view.file_path()?;
+            Some(open.strip_prefix(&worktree).ok()?.to_path_buf())
+        }) else {
+            return;
+        };
+        let change_type = self
+            .repo
+            .changed_files
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| f.change_type);
+        self.on_file_selected(path, change_type, cx);
+        self.file_view
+            .update(cx, |view, _cx| view.restore_diff_mode(mode));
+    }
+
+    /// Show an image file as the image; a changed one is compared with its
+    /// HEAD version, which is read in the background
+    fn preview_image(
+        &mut self,
+        path: &Path,
+        full_path: &Path,
+        change_type: Option<ChangeType>,
+        renamed_from: Option<PathBuf>,
+        max_image_size: u32,
+        cx: &mut Context<Self>,
+    ) {
+        if !image_preview::is_previewable(full_path) || change_type == Some(ChangeType::Deleted) {
+            return;
+        }
+        self.file_view
+            .update(cx, |view, cx| view.show_image(max_image_size, cx));
+        if !matches!(
+            change_type,
+            Some(ChangeType::Modified | ChangeType::Renamed)
+        ) {
+            return;
+        }
+        let Some(repo) = self.worktree_repo().cloned() else {
+            return;
+        };
+        let head_path = renamed_from.unwrap_or_else(|| path.to_path_buf());
+        let full_path = full_path.to_path_buf();
+        cx.spawn(async move |entity, cx| {
+            let before = smol::unblock(move || {
+                let id = repo.head_blob_id(&head_path)?;
+                let blob = image_preview::blob_path(&id, &head_path);
+                if !blob.exists() {
+                    let bytes = repo.read_blob(&id).ok()?;
+                    std::fs::create_dir_all(blob.parent()?).ok()?;
+                    std::fs::write(&blob, bytes).ok()?;
+                }
+                image_preview::load(&blob, max_image_size).ok()
+            })
+            .await;
+            let Some(before) = before else {
+                return;
+            };
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.file_view.update(cx, |view, cx| {
+                    view.set_image_before(&full_path, before);
+                    cx.notify();
+                });
+            });
+        })
+        .detach();
+    }
+
+    /// Show what changed in a submodule instead of a text diff
+    fn open_submodule(&mut self, path: PathBuf, full_path: PathBuf, cx: &mut Context<Self>) {
+        let Some(repo) = self.worktree_repo() else {
+            return;
+        };
+        match repo.submodule_status(&path) {
+            Ok(status) => {
+                self.file_view.update(cx, |view, _cx| {
+                    view.open_submodule(full_path.clone(), status)
+                });
+                self.reveal_in_file_list(&full_path);
+                self.reveal_file_view();
+                self.sync_file_position(false, cx);
+            }
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!(
+                    "Failed to read submodule {}: {}",
+                    path.display(),
+                    e
+                ));
+            }
+        }
+        cx.notify();
+    }
+
+    /// Compute the diffs of the first changed files of the active session in
+    /// the background, so opening them is instant.
+    ///
+    /// Replaces (cancelling) any prefetch still running. The number of files
+    /// is `sashiki.diff.prefetch` (default 20, 0 disables).
+    pub fn prefetch_diffs(&mut self, cx: &mut Context<Self>) {
+        self.diff_prefetch = None;
+        let Some(worktree_path) = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+        else {
+            return;
+        };
+        let (settings, _) = Settings::load(self.repo.git_repo.as_ref());
+        let max_size = settings.viewer_max_file_size();
+        let max_diff_size = settings.diff_max_size();
+        let files: Vec<_> = self
+            .repo
+            .changed_files
+            .iter()
+            .filter(|f| !self.repo.submodule_paths.contains(&f.path) && !self.is_lfs_file(&f.path))
+            .take(settings.diff_prefetch())
+            .map(|f| {
+                (
+                    worktree_path.join(&f.path),
+                    f.change_type,
+                    f.old_path.clone(),
+                )
+            })
+            .collect();
+        if files.is_empty() {
+            return;
+        }
+
+        self.diff_prefetch = Some(cx.spawn(async move |entity, cx| {
+            let Some((repo, head)) = smol::unblock(move || {
+                let repo = GitRepo::open(&worktree_path).ok()?;
+                let head = repo.resolve_head();
+                Some((repo, head))
+            })
+            .await
+            else {
+                return;
+            };
+
+            // One file per blocking call, so dropping the task stops between files
+            for (full_path, change_type, old_path) in files {
+                let stamp = DiffStamp::read(&full_path, head.as_deref());
+                let Ok(cached) = entity.update(cx, |app, _| {
+                    app.repo.diff_cache.contains(&full_path, &stamp)
+                }) else {
+                    return;
+                };
+                if cached {
+                    continue;
+                }
+
+                let repo = repo.clone();
+                let path = full_path.clone();
+                let diff = smol::unblock(move || {
+                    // Never shown for binary files and text over the size limit
+                    if file_probe::probe(&path, max_size).is_ok_and(|probe| !probe.is_text()) {
+                        return None;
+                    }
+                    let request = DiffRequest {
+                        full_path: path,
+                        change_type: Some(change_type),
+                        renamed_from: old_path,
+                        max_size: None,
+                    };
+                    // Only counted when opened (see `diff_job`)
+                    if diff_job::is_over_size(&repo, &request, max_diff_size) {
+                        return None;
+                    }
+                    diff_cache::compute_diff(
+                        &repo,
+                        &request.full_path,
+                        request.change_type,
+                        request.renamed_from.as_deref(),
+                    )
+                })
+                .await;
+                if let Some(diff) = diff {
+                    let _ = entity.update(cx, |app, _| {
+                        app.repo.diff_cache.insert(full_path, stamp, diff)
+                    });
+                }
+            }
+        }));
+    }
+
+    /// Compute a diff for the file view on a worker (see `diff_job`),
+    /// replacing any computation still running
+    fn start_diff_job(&mut self, repo: GitRepo, request: DiffRequest, cx: &mut Context<Self>) {
+        let (id, token) = self.diff_requests.start();
+        self.diff_job = Some(cx.spawn(async move |entity, cx| {
+            let full_path = request.full_path.clone();
+            let max_size = request.max_size;
+            let computed = smol::unblock(move || {
+                let stamp = DiffStamp::read(&request.full_path, repo.resolve_head().as_deref());
+                diff_job::compute(&repo, &request, &token).map(|outcome| (stamp, outcome))
+            })
+            .await;
+            let Some((stamp, outcome)) = computed else {
+                return;
+            };
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.show_diff_outcome(id, full_path, stamp, outcome, max_size, cx);
+            });
+        }));
+    }
+
+    /// Show the diff computed by request `id`, unless a newer request or
+    /// another file has taken its place
+    fn show_diff_outcome(
+        &mut self,
+        id: u64,
+        full_path: PathBuf,
+        stamp: DiffStamp,
+        outcome: DiffOutcome,
+        max_size: Option<u64>,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.diff_requests.is_current(id) {
+            return;
+        }
+        if let DiffOutcome::Computed {
+            diff: Some(diff), ..
+        } = &outcome
+        {
+            self.repo
+                .diff_cache
+                .insert(full_path.clone(), stamp, diff.clone());
+        }
+        self.file_view.update(cx, |view, cx| {
+            if view.file_path() != Some(full_path.as_path()) {
+                return;
+            }
+            match outcome {
+                DiffOutcome::Computed { diff, text } => view.show_computed_diff(text, diff),
+                DiffOutcome::TooLarge { added, removed } => {
+                    view.show_diff_summary(added, removed, max_size.unwrap_or_default());
+                }
+            }
+            cx.notify();
+        });
+    }
+
+    /// Compute the diff of the open file even though it is over the size guard
+    pub fn force_full_diff(&mut self, full_path: PathBuf, cx: &mut Context<Self>) {
+        let Some(repo) = self.worktree_repo().cloned() else {
+            return;
+        };
+        let relative = self
+            .active_worktree_path()
+            .and_then(|worktree| full_path.strip_prefix(worktree).ok().map(Path::to_path_buf));
+        let file = relative
+            .and_then(|relative| self.repo.changed_files.iter().find(|f| f.path == relative));
+        let request = DiffRequest {
+            change_type: file.map(|f| f.change_type),
+            renamed_from: file.and_then(|f| f.old_path.clone()),
+            full_path,
+            max_size: None,
+        };
+        self.start_diff_job(repo, request, cx);
+    }
+
+    /// Stop computing the open file's diff; a file view still waiting for
+    /// it is closed
+    pub fn cancel_diff_job(&mut self, cx: &mut Context<Self>) {
+        self.diff_requests.cancel();
+        if self.diff_job.take().is_none() {
+            return;
+        }
+        let closed = self.file_view.update(cx, |view, _cx| {
+            let loading = view.is_diff_loading();
+            if loading {
+                view.close();
+            }
+            loading
         });
+        if closed {
+            self.show_file_view = false;
+        }
+    }
+
+    /// Recompute a diff that was served from the cache and update the file
+    /// view if it changed in a way the cache stamp did not catch (e.g. an edit
+    /// within the file system's mtime resolution)
+    fn revalidate_diff(
+        &mut self,
+        full_path: PathBuf,
+        change_type: Option<ChangeType>,
+        renamed_from: Option<PathBuf>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(repo) = self.worktree_repo().cloned() else {
+            return;
+        };
+        cx.spawn(async move |entity, cx| {
+            let path = full_path.clone();
+            let (stamp, diff) = smol::unblock(move || {
+                let stamp = DiffStamp::read(&path, repo.resolve_head().as_deref());
+                let diff =
+                    diff_cache::compute_diff(&repo, &path, change_type, renamed_from.as_deref());
+                (stamp, diff)
+            })
+            .await;
+            let Some(diff) = diff else {
+                return;
+            };
 
-        self.show_file_view = true;
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                if app.repo.diff_cache.get(&full_path, &stamp).as_ref() == Some(&diff) {
+                    return;
+                }
+                app.repo
+                    .diff_cache
+                    .insert(full_path.clone(), stamp, diff.clone());
+                app.file_view.update(cx, |view, cx| {
+                    if view.file_path() == Some(full_path.as_path()) && view.comparison().is_none()
+                    {
+                        view.replace_diff(diff);
+                        cx.notify();
+                    }
+                });
+            });
+        })
+        .detach();
+    }
+
+    /// Hand a file to the external editor without blocking.
+    ///
+    /// Spawn failures and a missing editor binary are reported in the error
+    /// dialog together with the command line that was tried.
+    pub fn open_externally(&mut self, path: PathBuf, line: Option<usize>, cx: &mut Context<Self>) {
+        if let Some(index) = self.repo.session_manager.find_session_containing(&path)
+            && !self.ensure_writable(index, "open the file for editing", cx)
+        {
+            return;
+        }
+        if !self.ensure_inside_worktree(&path, "open the file for editing", cx) {
+            return;
+        }
+        let (settings, _) = Settings::load(self.repo.git_repo.as_ref());
+        let configured = settings.get(git::CONFIG_EXTERNAL_EDITOR);
+        let Some(template) =
+            external_editor::editor_command(configured, |key| std::env::var(key).ok())
+        else {
+            self.active_dialog = ActiveDialog::error(format!(
+                "No external editor configured.\nSet one with:\n  git config {} \"code --goto {{path}}:{{line}}\"\nor set $VISUAL / $EDITOR.",
+                git::CONFIG_EXTERNAL_EDITOR
+            ));
+            cx.notify();
+            return;
+        };
+
+        let command = external_editor::expand_command(&template, &path, line);
+        let workdir = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+            .or_else(|| path.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        let mut child = match external_editor::spawn_detached(&command, &workdir) {
+            Ok(child) => child,
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to open externally:\n{}\n{}", command, e));
+                cx.notify();
+                return;
+            }
+        };
+
+        // Reap the process; the shell reports a missing editor through its exit code
+        cx.spawn(async move |entity, cx| {
+            let status = smol::unblock(move || child.wait()).await;
+            if let Ok(status) = status
+                && external_editor::is_not_found_exit(status.code())
+            {
+                let _ = entity.update(cx, |app, cx| {
+                    app.active_dialog = ActiveDialog::error(format!(
+                        "Failed to open externally (command not found):\n{}",
+                        command
+                    ));
+                    cx.notify();
+                });
+            }
+        })
+        .detach();
+    }
+
+    /// Rewrite `path` from `encoding` as UTF-8 (from the file view's encoding
+    /// chip). Invalid sequences are written as U+FFFD.
+    pub fn convert_to_utf8(&mut self, path: &Path, encoding: TextEncoding, cx: &mut Context<Self>) {
+        if let Some(index) = self.repo.session_manager.find_session_containing(path)
+            && !self.ensure_writable(index, "convert the file to UTF-8", cx)
+        {
+            return;
+        }
+        if !self.ensure_inside_worktree(path, "convert the file to UTF-8", cx) {
+            return;
+        }
+        let result = std::fs::read(path)
+            .and_then(|bytes| std::fs::write(path, encoding::decode(&bytes, encoding)));
+        if let Err(e) = result {
+            self.active_dialog = ActiveDialog::error(format!(
+                "Failed to convert {} to UTF-8: {}",
+                path.display(),
+                e
+            ));
+        }
+        self.refresh_file_list();
+        self.refresh_file_view_for(path, cx);
         cx.notify();
     }
 }
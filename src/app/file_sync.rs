@@ -0,0 +1,180 @@
+//! Syncing template files from the main worktree (see `crate::file_sync`)
+
+use super::SashikiApp;
+use crate::activity::ActivityEvent;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::file_sync::{self, SyncKind};
+use crate::template::TemplateSet;
+use gpui::Context;
+use std::path::{Path, PathBuf};
+
+impl SashikiApp {
+    /// Main worktree root and the root of the session at `index`
+    fn sync_roots(&self, index: usize) -> Option<(PathBuf, PathBuf)> {
+        let sessions = self.repo.session_manager.sessions();
+        let main = sessions.iter().find(|s| s.is_main())?;
+        let session = sessions.get(index).filter(|s| !s.is_main())?;
+        Some((
+            main.worktree_path().to_path_buf(),
+            session.worktree_path().to_path_buf(),
+        ))
+    }
+
+    /// Compare the template files of the main worktree with the session at
+    /// `index`. Files to create or overwrite start selected, except those
+    /// changed in the session since.
+    pub fn open_sync_files_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(repo), Some((source, dest))) =
+            (self.repo.git_repo.as_ref(), self.sync_roots(index))
+        else {
+            return;
+        };
+        let branch = self.repo.session_manager.sessions()[index].branch();
+        let patterns = TemplateSet::load(repo)
+            .for_branch(branch)
+            .file_copies
+            .clone();
+        if patterns.is_empty() {
+            self.active_dialog = ActiveDialog::error(
+                "No files to sync: add fileCopy patterns in the template settings",
+            );
+            cx.notify();
+            return;
+        }
+
+        let (files, errors) = file_sync::plan(&patterns, &source, &dest);
+        for error in errors {
+            app_log::record(LogEntry::new(LogLevel::Warn, LogSource::Template, error));
+        }
+        let selected = files
+            .iter()
+            .filter(|f| f.kind != SyncKind::Identical && !f.dest_newer)
+            .map(|f| f.path.clone())
+            .collect();
+        self.active_dialog = ActiveDialog::SyncFiles {
+            target_index: index,
+            files,
+            selected,
+            show_identical: false,
+            preview: None,
+        };
+        cx.notify();
+    }
+
+    pub fn toggle_sync_file(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if let ActiveDialog::SyncFiles { selected, .. } = &mut self.active_dialog {
+            if !selected.remove(path) {
+                selected.insert(path.to_path_buf());
+            }
+            cx.notify();
+        }
+    }
+
+    pub fn toggle_sync_show_identical(&mut self, cx: &mut Context<Self>) {
+        if let ActiveDialog::SyncFiles { show_identical, .. } = &mut self.active_dialog {
+            *show_identical = !*show_identical;
+            cx.notify();
+        }
+    }
+
+    /// Show the diff of a file in the dialog, or hide it when already shown
+    pub fn toggle_sync_preview(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let ActiveDialog::SyncFiles {
+            target_index,
+            files,
+            preview,
+            ..
+        } = &self.active_dialog
+        else {
+            return;
+        };
+        let next = if preview.as_ref().is_some_and(|(p, _)| p == path) {
+            None
+        } else {
+            let file = files.iter().find(|f| f.path == path);
+            match (file, self.sync_roots(*target_index)) {
+                (Some(file), Some((source, dest))) => {
+                    let diff = file_sync::diff(&source, &dest, file)
+                        .unwrap_or_else(|e| format!("Failed to diff: {}", e));
+                    Some((path.to_path_buf(), diff))
+                }
+                _ => None,
+            }
+        };
+        if let ActiveDialog::SyncFiles { preview, .. } = &mut self.active_dialog {
+            *preview = next;
+        }
+        cx.notify();
+    }
+
+    pub fn close_sync_files_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Copy the selected files and log a summary
+    pub fn confirm_sync_files(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::SyncFiles {
+            target_index,
+            files,
+            selected,
+            ..
+        } = &self.active_dialog
+        else {
+            return;
+        };
+        let target_index = *target_index;
+        let Some((source, dest)) = self.sync_roots(target_index) else {
+            return;
+        };
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .filter(|f| selected.contains(&f.path))
+            .map(|f| f.path.clone())
+            .collect();
+        let skipped = files.len() - paths.len();
+        if !self.ensure_writable(target_index, "sync files", cx) {
+            return;
+        }
+
+        let summary = file_sync::copy(&source, &dest, &paths, skipped);
+        for (path, error) in &summary.failed {
+            app_log::record(LogEntry::new(
+                LogLevel::Error,
+                LogSource::Template,
+                format!("Failed to sync {}: {}", path.display(), error),
+            ));
+        }
+        app_log::record(LogEntry::new(
+            if summary.failed.is_empty() {
+                LogLevel::Info
+            } else {
+                LogLevel::Warn
+            },
+            LogSource::Template,
+            format!(
+                "Synced files from main into {}: {}",
+                dest.display(),
+                summary.describe()
+            ),
+        ));
+
+        if let Some(session) = self.repo.session_manager.sessions().get(target_index) {
+            session.events().record(ActivityEvent::WorktreeSynced {
+                copied: summary.copied,
+            });
+        }
+
+        self.active_dialog = if summary.failed.is_empty() {
+            ActiveDialog::None
+        } else {
+            ActiveDialog::error(format!(
+                "Some files could not be copied ({}); see the log for details",
+                summary.describe()
+            ))
+        };
+        self.refresh_changed_files_sync();
+        cx.notify();
+    }
+}
@@ -0,0 +1,38 @@
+//! Git operations held because another git process kept the repository
+//! locked past `sashiki.git.lockWait` (see `crate::git_lock`): retried,
+//! cancelled, or retried once the user removed a stale lock
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::git_lock::BusyLock;
+use crate::git_queue::OpId;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Run a held operation again
+    pub fn retry_git_op(&mut self, id: OpId, cx: &mut Context<Self>) {
+        self.repo.git_queue.retry(id);
+        cx.notify();
+    }
+
+    /// Give up a held operation; whoever queued it sees it as cancelled
+    pub fn cancel_git_op(&mut self, id: OpId, cx: &mut Context<Self>) {
+        self.repo.git_queue.cancel(id);
+        cx.notify();
+    }
+
+    /// Remove the stale `lock` the held operation `id` ran into, then run
+    /// it again. Only offered for stale locks, and only on request.
+    pub fn remove_lock_and_retry(&mut self, id: OpId, lock: &BusyLock, cx: &mut Context<Self>) {
+        let entry = match lock.remove() {
+            Ok(()) => LogEntry::new(
+                LogLevel::Info,
+                LogSource::Git,
+                format!("Removed the stale lock {}", lock.path.display()),
+            ),
+            Err(e) => LogEntry::new(LogLevel::Warn, LogSource::Git, e),
+        };
+        app_log::record(entry);
+        self.retry_git_op(id, cx);
+    }
+}
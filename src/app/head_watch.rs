@@ -0,0 +1,118 @@
+//! Refreshing sessions whose HEAD moved outside Sashiki: commits, branch
+//! switches and rebases run in a terminal (see `crate::head_watch`)
+
+use super::SashikiApp;
+use crate::git::GitRepo;
+use crate::head_watch::{HEAD_POLL_INTERVAL, HeadWatcher};
+use gpui::{Context, Task};
+use std::path::PathBuf;
+
+impl SashikiApp {
+    /// Poll HEADs only while the shown repository has sessions
+    pub(crate) fn update_head_watch(&mut self, cx: &mut Context<Self>) {
+        let wanted = !self.repo.session_manager.sessions().is_empty();
+        if wanted != self.head_watch.is_some() {
+            self.head_watch = wanted.then(|| Self::spawn_head_watch(cx));
+        }
+    }
+
+    /// Check the HEADs of the sessions' worktrees, and whether their
+    /// directories are still there, every `HEAD_POLL_INTERVAL` until the
+    /// task is dropped
+    fn spawn_head_watch(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |entity, cx| {
+            let mut watcher = HeadWatcher::default();
+            loop {
+                smol::Timer::after(HEAD_POLL_INTERVAL).await;
+                let Ok(worktrees) = entity.update(cx, |app, _cx| {
+                    app.repo
+                        .session_manager
+                        .sessions()
+                        .iter()
+                        .map(|s| s.worktree_path().to_path_buf())
+                        .collect::<Vec<_>>()
+                }) else {
+                    break;
+                };
+                let (polled, moved, missing) = smol::unblock(move || {
+                    let moved = watcher.poll(&worktrees);
+                    let missing: Vec<PathBuf> = worktrees
+                        .into_iter()
+                        .filter(|path| !path.is_dir())
+                        .collect();
+                    (watcher, moved, missing)
+                })
+                .await;
+                watcher = polled;
+                if entity
+                    .update(cx, |app, cx| app.set_missing_worktrees(&missing, cx))
+                    .is_err()
+                {
+                    break;
+                }
+                if !moved.is_empty()
+                    && entity
+                        .update(cx, |app, cx| app.on_heads_moved(moved, cx))
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Bring what depends on HEAD up to date for the worktrees in `moved`:
+    /// branch labels, the activity timelines, changed files, the open diff,
+    /// overlaps and the maintenance dialog's ahead/behind
+    fn on_heads_moved(&mut self, moved: Vec<PathBuf>, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        let workdir = repo.workdir().to_path_buf();
+        for worktree in &moved {
+            self.repo.file_cache.invalidate(worktree);
+        }
+
+        cx.spawn(async move |entity, cx| {
+            let (worktrees, moved, heads) = smol::unblock(move || {
+                let heads: Vec<_> = moved
+                    .iter()
+                    .filter_map(|path| {
+                        let head = GitRepo::open(path).ok()?.head_subject()?;
+                        Some((path.clone(), head))
+                    })
+                    .collect();
+                let worktrees = GitRepo::open(&workdir).and_then(|repo| repo.list_worktrees());
+                (worktrees, moved, heads)
+            })
+            .await;
+            let _ = entity.update(cx, |app, cx| {
+                for worktree in worktrees.iter().flatten() {
+                    if moved.contains(&worktree.path) {
+                        app.repo.session_manager.update_session_worktree(worktree);
+                    }
+                }
+                for (path, (commit, subject)) in heads {
+                    if let Some(index) = app.repo.session_manager.find_session_by_path(&path) {
+                        app.repo.session_manager.sessions()[index]
+                            .events()
+                            .head_moved(commit, subject);
+                    }
+                }
+                if app
+                    .repo
+                    .session_manager
+                    .active_session()
+                    .is_some_and(|s| moved.iter().any(|path| path == s.worktree_path()))
+                {
+                    app.open_diff_stale = true;
+                }
+                app.refresh_file_list_async(cx);
+                app.refresh_conflicts_async(true, cx);
+                app.reload_maintenance_git_state(cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+}
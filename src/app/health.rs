@@ -0,0 +1,43 @@
+//! Startup health check (see `crate::health`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::health;
+use crate::settings::Settings;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Run the dependency checks in the background and show the problems
+    /// found. Ignored while a run is in progress.
+    pub fn run_health_check(&mut self, cx: &mut Context<Self>) {
+        if self.health_check.is_some() {
+            return;
+        }
+        let shell = Settings::load(self.repo.git_repo.as_ref())
+            .0
+            .terminal_shell();
+        let text_system = cx.text_system().clone();
+
+        self.health_check = Some(cx.spawn(async move |entity, cx| {
+            let problems =
+                smol::unblock(move || health::run(shell.as_deref(), &text_system.all_font_names()))
+                    .await;
+
+            for problem in &problems {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::App,
+                    format!("{}: {}", problem.title, problem.remedy),
+                ));
+            }
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.health_check = None;
+                app.health_problems = problems;
+                app.health_dismissed = false;
+                cx.notify();
+            });
+        }));
+        cx.notify();
+    }
+}
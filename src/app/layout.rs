@@ -0,0 +1,74 @@
+//! Layout keys: resizing the panels and moving the terminal (see
+//! `crate::layout`)
+
+use super::SashikiApp;
+use super::actions::{
+    GrowTerminal, NarrowSidebar, ShrinkTerminal, ToggleMaximizeTerminal, ToggleTerminalDock,
+    WidenSidebar,
+};
+use crate::terminal;
+use gpui::{Context, Window};
+
+impl SashikiApp {
+    /// Show the file view, restoring the central area if the terminal was
+    /// maximized over it
+    pub(crate) fn reveal_file_view(&mut self) {
+        self.show_file_view = true;
+        self.layout.terminal_maximized = false;
+    }
+
+    pub fn on_grow_terminal(&mut self, _: &GrowTerminal, _: &mut Window, cx: &mut Context<Self>) {
+        if self
+            .layout
+            .step_terminal_height(true, terminal::row_height())
+        {
+            cx.notify();
+        }
+    }
+
+    pub fn on_shrink_terminal(
+        &mut self,
+        _: &ShrinkTerminal,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self
+            .layout
+            .step_terminal_height(false, terminal::row_height())
+        {
+            cx.notify();
+        }
+    }
+
+    pub fn on_widen_sidebar(&mut self, _: &WidenSidebar, _: &mut Window, cx: &mut Context<Self>) {
+        if self.layout.step_sidebar_width(true) {
+            cx.notify();
+        }
+    }
+
+    pub fn on_narrow_sidebar(&mut self, _: &NarrowSidebar, _: &mut Window, cx: &mut Context<Self>) {
+        if self.layout.step_sidebar_width(false) {
+            cx.notify();
+        }
+    }
+
+    pub fn on_toggle_terminal_dock(
+        &mut self,
+        _: &ToggleTerminalDock,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.layout.toggle_dock();
+        cx.notify();
+    }
+
+    pub fn on_toggle_maximize_terminal(
+        &mut self,
+        _: &ToggleMaximizeTerminal,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.layout.toggle_terminal_maximized();
+        cx.notify();
+    }
+}
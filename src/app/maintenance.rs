@@ -0,0 +1,308 @@
+//! Worktree maintenance: disk usage, build artifact cleanup and bulk delete
+//! (see `crate::maintenance`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::maintenance::{self, CleanPattern, WorktreeUsage};
+use crate::settings::Settings;
+use gpui::Context;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+impl SashikiApp {
+    /// List the worktrees of the open repository and measure them in the
+    /// background
+    pub fn open_maintenance_dialog(&mut self, cx: &mut Context<Self>) {
+        let rows = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|s| WorktreeUsage::new(s.name(), s.worktree_path(), s.branch(), s.is_main()))
+            .collect();
+        self.maintenance.set_rows(rows);
+        self.maintenance.status = None;
+        self.active_dialog = ActiveDialog::Maintenance;
+        self.start_maintenance_scan(cx);
+        cx.notify();
+    }
+
+    /// Read the git state of the worktrees not read yet, then measure those
+    /// without a cached size. Replaces (and so cancels) a running scan.
+    fn start_maintenance_scan(&mut self, cx: &mut Context<Self>) {
+        let cancel = self.maintenance.start_scan();
+        let main_head = self
+            .repo
+            .git_repo
+            .as_ref()
+            .and_then(|repo| repo.resolve_head());
+        let rows = self.maintenance.rows.clone();
+        self.maintenance.busy = true;
+
+        self.maintenance_scan = Some(cx.spawn(async move |entity, cx| {
+            let mut rows = rows;
+            for measure_size in [false, true] {
+                for row in &mut rows {
+                    let done = if measure_size {
+                        row.size.is_some()
+                    } else {
+                        row.loaded
+                    };
+                    if done {
+                        continue;
+                    }
+                    let mut scanned = row.clone();
+                    let head = main_head.clone();
+                    let flag = cancel.clone();
+                    *row = smol::unblock(move || {
+                        if measure_size {
+                            scanned.size = maintenance::disk_usage(&scanned.path, &flag);
+                        } else {
+                            scanned.load_git_state(head.as_deref());
+                        }
+                        scanned
+                    })
+                    .await;
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let update = row.clone();
+                    if entity
+                        .update(cx, |app, cx| {
+                            app.maintenance.update_row(update);
+                            cx.notify();
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            let _ = entity.update(cx, |app, cx| {
+                app.maintenance.busy = false;
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Read and measure every worktree again, ignoring cached sizes
+    pub fn rescan_maintenance(&mut self, cx: &mut Context<Self>) {
+        self.maintenance.reset_measurements();
+        self.maintenance.status = None;
+        self.start_maintenance_scan(cx);
+        cx.notify();
+    }
+
+    /// Read the git state of the listed worktrees again after a HEAD moved
+    /// (ahead/behind is counted against the main worktree's HEAD, so every
+    /// row may change); sizes are kept
+    pub(crate) fn reload_maintenance_git_state(&mut self, cx: &mut Context<Self>) {
+        if !matches!(self.active_dialog, ActiveDialog::Maintenance) {
+            return;
+        }
+        for row in &mut self.maintenance.rows {
+            if let Some(index) = self.repo.session_manager.find_session_by_path(&row.path) {
+                row.branch = self.repo.session_manager.sessions()[index]
+                    .branch()
+                    .map(str::to_string);
+            }
+            row.loaded = false;
+        }
+        self.start_maintenance_scan(cx);
+    }
+
+    pub fn close_maintenance_dialog(&mut self, cx: &mut Context<Self>) {
+        self.maintenance.cancel_scan();
+        self.maintenance_scan = None;
+        self.maintenance.busy = false;
+        self.maintenance.delete_queue = None;
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    pub fn toggle_maintenance_selection(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.maintenance.toggle_selected(&path);
+        cx.notify();
+    }
+
+    // --- Build artifacts ---
+
+    /// Look for build artifacts in the selected worktrees, then ask before
+    /// removing them
+    pub fn request_clean_artifacts(&mut self, cx: &mut Context<Self>) {
+        let roots: Vec<PathBuf> = self
+            .maintenance
+            .selected_rows()
+            .into_iter()
+            .map(|row| row.path.clone())
+            .collect();
+        if roots.is_empty() {
+            return;
+        }
+        let (settings, _) = Settings::load(self.repo.git_repo.as_ref());
+        let patterns: Vec<CleanPattern> = settings
+            .clean_patterns()
+            .iter()
+            .filter_map(|p| CleanPattern::parse(p))
+            .collect();
+
+        let cancel = self.maintenance.start_scan();
+        self.maintenance.busy = true;
+        self.maintenance.status = Some("Looking for build artifacts…".to_string());
+        self.maintenance_scan = Some(cx.spawn(async move |entity, cx| {
+            let found = smol::unblock(move || {
+                let mut artifacts = Vec::new();
+                let mut bytes = 0;
+                for root in roots {
+                    let paths = maintenance::find_artifacts(&root, &patterns, &cancel)?;
+                    for path in &paths {
+                        bytes += maintenance::disk_usage(path, &cancel)?;
+                    }
+                    if !paths.is_empty() {
+                        artifacts.push((root, paths));
+                    }
+                }
+                Some((artifacts, bytes))
+            })
+            .await;
+            let Some((artifacts, bytes)) = found else {
+                return;
+            };
+            let _ = entity.update(cx, |app, cx| {
+                app.maintenance.busy = false;
+                if artifacts.is_empty() {
+                    // Finish measuring what the search interrupted
+                    app.start_maintenance_scan(cx);
+                    app.maintenance.status =
+                        Some("No build artifacts in the selected worktrees".to_string());
+                } else {
+                    app.maintenance.status = None;
+                    app.active_dialog = ActiveDialog::CleanArtifactsConfirm { artifacts, bytes };
+                }
+                cx.notify();
+            });
+        }));
+        cx.notify();
+    }
+
+    /// Back to the maintenance dialog without removing anything
+    pub fn cancel_clean_artifacts(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::Maintenance;
+        // Sizes left unmeasured by the artifact search are measured now
+        self.start_maintenance_scan(cx);
+        cx.notify();
+    }
+
+    /// Remove the confirmed artifacts in the background, then measure the
+    /// cleaned worktrees again
+    pub fn confirm_clean_artifacts(&mut self, cx: &mut Context<Self>) {
+        let artifacts = match std::mem::take(&mut self.active_dialog) {
+            ActiveDialog::CleanArtifactsConfirm { artifacts, .. } => artifacts,
+            other => {
+                self.active_dialog = other;
+                return;
+            }
+        };
+        self.active_dialog = ActiveDialog::Maintenance;
+        self.maintenance.busy = true;
+        self.maintenance.status = Some("Removing build artifacts…".to_string());
+
+        cx.spawn(async move |entity, cx| {
+            let roots: Vec<PathBuf> = artifacts.iter().map(|(root, _)| root.clone()).collect();
+            let result = smol::unblock(move || {
+                let errors: Vec<String> = artifacts
+                    .iter()
+                    .filter_map(|(root, paths)| maintenance::remove_artifacts(root, paths).err())
+                    .collect();
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(errors.join("\n"))
+                }
+            })
+            .await;
+            let _ = entity.update(cx, |app, cx| {
+                app.finish_clean_artifacts(roots, result, cx);
+            });
+        })
+        .detach();
+        cx.notify();
+    }
+
+    fn finish_clean_artifacts(
+        &mut self,
+        roots: Vec<PathBuf>,
+        result: Result<(), String>,
+        cx: &mut Context<Self>,
+    ) {
+        for root in &roots {
+            self.maintenance.invalidate_size(root);
+        }
+        self.maintenance.busy = false;
+        self.maintenance.status = None;
+        match result {
+            Ok(()) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::App,
+                    format!("Removed build artifacts from {} worktree(s)", roots.len()),
+                ));
+                if matches!(self.active_dialog, ActiveDialog::Maintenance) {
+                    self.maintenance.status = Some("Build artifacts removed".to_string());
+                    self.start_maintenance_scan(cx);
+                }
+            }
+            Err(e) => self.active_dialog = ActiveDialog::error(e),
+        }
+        self.refresh_file_list_async(cx);
+        cx.notify();
+    }
+
+    // --- Bulk delete ---
+
+    /// Delete the selected worktrees through the regular delete
+    /// confirmation, one after the other
+    pub fn request_bulk_delete(&mut self, cx: &mut Context<Self>) {
+        let queue: Vec<PathBuf> = self
+            .maintenance
+            .selected_rows()
+            .into_iter()
+            .filter(|row| !row.is_main)
+            .map(|row| row.path.clone())
+            .collect();
+        if queue.is_empty() {
+            return;
+        }
+        self.maintenance.cancel_scan();
+        self.maintenance_scan = None;
+        self.maintenance.busy = false;
+        self.maintenance.delete_queue = Some(queue);
+        self.continue_bulk_delete(cx);
+    }
+
+    /// Ask about the next queued worktree; back to the maintenance dialog
+    /// once the queue is done. Returns false outside a bulk delete.
+    pub(crate) fn continue_bulk_delete(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(queue) = self.maintenance.delete_queue.as_mut() else {
+            return false;
+        };
+        // Indices shift as sessions are removed, so look each one up by path
+        while !queue.is_empty() {
+            let path = queue.remove(0);
+            if let Some(index) = self
+                .repo
+                .session_manager
+                .sessions()
+                .iter()
+                .position(|s| s.worktree_path() == path)
+            {
+                self.open_delete_dialog(index, cx);
+                return true;
+            }
+        }
+        self.maintenance.delete_queue = None;
+        self.open_maintenance_dialog(cx);
+        true
+    }
+}
@@ -0,0 +1,357 @@
+//! Merging a session's branch into the branch of the main worktree
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::encoding::{self, TextEncoding};
+use crate::git::{MergeMode, MergeOutcome};
+use gpui::{Context, Window};
+use std::path::{Path, PathBuf};
+
+/// What the merge dialog was confirmed with
+struct MergeRequest {
+    worktree_path: PathBuf,
+    session_name: String,
+    branch: String,
+    /// Branch of the main worktree (for messages)
+    target: String,
+    remove_worktree: bool,
+    delete_branch: bool,
+}
+
+impl SashikiApp {
+    /// Ask how to merge the session at `index`. While an earlier merge is
+    /// still waiting on conflicts, show those instead.
+    pub fn open_merge_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        let session = self.repo.session_manager.sessions().get(index);
+        if session.is_none_or(|s| s.is_main()) {
+            return;
+        }
+
+        self.active_dialog = match repo.conflicted_files() {
+            Ok(files) if !files.is_empty() => ActiveDialog::MergeConflicts { files },
+            Ok(_) if session.and_then(|s| s.branch()).is_none() => {
+                ActiveDialog::error("The worktree has no branch checked out (detached HEAD)")
+            }
+            Ok(_) => ActiveDialog::Merge {
+                target_index: index,
+                mode: MergeMode::default(),
+                remove_worktree: false,
+                delete_branch: false,
+            },
+            Err(e) => ActiveDialog::error(format!("Failed to read the main worktree: {}", e)),
+        };
+        cx.notify();
+    }
+
+    pub fn set_merge_mode(&mut self, new_mode: MergeMode, cx: &mut Context<Self>) {
+        if let ActiveDialog::Merge { mode, .. } = &mut self.active_dialog {
+            *mode = new_mode;
+            cx.notify();
+        }
+    }
+
+    pub fn toggle_merge_remove_worktree(&mut self, cx: &mut Context<Self>) {
+        if let ActiveDialog::Merge {
+            remove_worktree,
+            delete_branch,
+            ..
+        } = &mut self.active_dialog
+        {
+            *remove_worktree = !*remove_worktree;
+            // The branch cannot be deleted while a worktree has it checked out
+            *delete_branch &= *remove_worktree;
+            cx.notify();
+        }
+    }
+
+    pub fn toggle_merge_delete_branch(&mut self, cx: &mut Context<Self>) {
+        if let ActiveDialog::Merge {
+            remove_worktree: true,
+            delete_branch,
+            ..
+        } = &mut self.active_dialog
+        {
+            *delete_branch = !*delete_branch;
+            cx.notify();
+        }
+    }
+
+    pub fn close_merge_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Merge in the main worktree on a background thread, refusing when it
+    /// has uncommitted changes
+    pub fn confirm_merge(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::Merge {
+            target_index,
+            mode,
+            remove_worktree,
+            delete_branch,
+        } = self.active_dialog
+        else {
+            return;
+        };
+        let (Some(repo), Some(session)) = (
+            self.repo.git_repo.clone(),
+            self.repo.session_manager.sessions().get(target_index),
+        ) else {
+            return;
+        };
+        let Some(branch) = session.branch().map(str::to_string) else {
+            return;
+        };
+        let target = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .find(|s| s.is_main())
+            .and_then(|s| s.branch())
+            .unwrap_or("main")
+            .to_string();
+        let request = MergeRequest {
+            worktree_path: session.worktree_path().to_path_buf(),
+            session_name: session.name().to_string(),
+            branch,
+            target,
+            remove_worktree,
+            delete_branch,
+        };
+
+        self.active_dialog = ActiveDialog::Merging;
+        let branch = request.branch.clone();
+        let main_workdir = repo.workdir().to_path_buf();
+        let op = self.repo.git_queue.push(
+            format!("Merge {} into {}", request.branch, request.target),
+            Some(&main_workdir),
+            move || {
+                if repo.has_uncommitted_changes().map_err(|e| e.to_string())? {
+                    return Err("The main worktree has uncommitted changes.\nCommit or stash them before merging.".to_string());
+                }
+                repo.merge_branch(&branch, mode).map_err(|e| e.to_string())
+            },
+        );
+        cx.spawn(async move |entity, cx| {
+            let result = op
+                .wait()
+                .await
+                .unwrap_or_else(|| Err("The merge was cancelled".to_string()));
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.finish_merge(request, result, cx);
+            });
+        })
+        .detach();
+        cx.notify();
+    }
+
+    fn finish_merge(
+        &mut self,
+        request: MergeRequest,
+        result: Result<MergeOutcome, String>,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_dialog = ActiveDialog::None;
+        match result {
+            Ok(MergeOutcome::Conflicts(files)) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::Git,
+                    format!(
+                        "Merging {} stopped on {} conflicted file(s)",
+                        request.branch,
+                        files.len()
+                    ),
+                ));
+                self.active_dialog = ActiveDialog::MergeConflicts { files };
+            }
+            Ok(outcome) => {
+                let message = if outcome == MergeOutcome::UpToDate {
+                    format!("{} has nothing to merge", request.branch)
+                } else {
+                    format!("Merged {} into {}", request.branch, request.target)
+                };
+                app_log::record(LogEntry::new(LogLevel::Info, LogSource::Git, message));
+                if request.remove_worktree {
+                    self.remove_merged_worktree(&request, cx);
+                }
+            }
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!(
+                    "Failed to merge {} ({}):\n{}",
+                    request.session_name, request.branch, e
+                ))
+            }
+        }
+        self.refresh_file_list_async(cx);
+        cx.notify();
+    }
+
+    /// Remove the merged worktree through the regular delete flow, then its
+    /// branch (the worktree is unregistered before the directory goes)
+    fn remove_merged_worktree(&mut self, request: &MergeRequest, cx: &mut Context<Self>) {
+        // Sessions may have changed while merging
+        let Some(index) = self
+            .repo
+            .session_manager
+            .find_session_by_path(&request.worktree_path)
+        else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::DeleteConfirm {
+            target_index: index,
+            keep_note: true,
+        };
+        self.confirm_delete_worktree(cx);
+
+        // Queued behind the worktree removal, which still has it checked out
+        if request.delete_branch
+            && let Some(repo) = self.repo.git_repo.clone()
+        {
+            let branch = request.branch.clone();
+            self.repo
+                .git_queue
+                .push(format!("Delete branch {}", branch), None, move || {
+                    if let Err(e) = repo.delete_branch(&branch) {
+                        app_log::record(LogEntry::new(
+                            LogLevel::Warn,
+                            LogSource::Git,
+                            format!("Failed to delete branch {}: {}", branch, e),
+                        ));
+                    }
+                });
+        }
+    }
+
+    // --- Conflicts ---
+
+    /// Give up the conflicted merge in the main worktree
+    pub fn abort_merge(&mut self, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        self.active_dialog = match repo.abort_merge() {
+            Ok(()) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::Git,
+                    "Aborted the merge into main",
+                ));
+                ActiveDialog::None
+            }
+            Err(e) => ActiveDialog::error(format!("Failed to abort the merge: {}", e)),
+        };
+        self.refresh_file_list_async(cx);
+        cx.notify();
+    }
+
+    /// Write a conflicted file after one of its conflicts was resolved in
+    /// the file view. On failure the view reloads what is on disk.
+    pub fn write_conflict_resolution(
+        &mut self,
+        path: &Path,
+        text: &str,
+        encoding: TextEncoding,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.repo.session_manager.find_session_containing(path)
+            && !self.ensure_writable(index, "resolve conflicts", cx)
+        {
+            self.refresh_file_view_for(path, cx);
+            return;
+        }
+        if !self.ensure_inside_worktree(path, "resolve conflicts", cx) {
+            return;
+        }
+        if let Err(e) = std::fs::write(path, encoding::encode(text, encoding)) {
+            self.active_dialog =
+                ActiveDialog::error(format!("Failed to write {}: {}", path.display(), e));
+            self.refresh_file_view_for(path, cx);
+        }
+        cx.notify();
+    }
+
+    /// Stage a file with no conflict markers left, which tells git its
+    /// conflicts are resolved
+    pub fn mark_conflict_resolved(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if let Some(index) = self.repo.session_manager.find_session_containing(path)
+            && !self.ensure_writable(index, "mark conflicts resolved", cx)
+        {
+            return;
+        }
+        let Some(repo) = self.worktree_repo().cloned() else {
+            return;
+        };
+        let relative = path.strip_prefix(repo.workdir()).unwrap_or(path);
+        match repo.mark_resolved(relative) {
+            Ok(()) => app_log::record(LogEntry::new(
+                LogLevel::Info,
+                LogSource::Git,
+                format!("Marked {} as resolved", relative.display()),
+            )),
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!(
+                    "Failed to mark {} as resolved: {}",
+                    relative.display(),
+                    e
+                ));
+            }
+        }
+        self.refresh_file_list();
+        self.refresh_file_view_for(path, cx);
+        cx.notify();
+    }
+
+    /// Switch to the main worktree and show our side of a conflicted file
+    /// against theirs (falls back to the file with its conflict markers)
+    pub fn open_merge_conflict_diff(
+        &mut self,
+        path: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(main_index) = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .position(|s| s.is_main())
+        else {
+            return;
+        };
+        let Some(repo) = self.repo.git_repo.clone() else {
+            return;
+        };
+
+        self.active_dialog = ActiveDialog::None;
+        self.on_session_selected(main_index, window, cx);
+
+        let full_path = repo.workdir().join(&path);
+        if !self.ensure_inside_worktree(&full_path, "open the file", cx) {
+            return;
+        }
+        self.reveal_in_file_list(&full_path);
+        let diff = repo
+            .conflict_sides_diff(&full_path)
+            .ok()
+            .filter(|d| !d.is_empty());
+        self.file_view.update(cx, |view, _cx| match diff {
+            Some(diff) if full_path.exists() => {
+                let _ = view.open_file_with_diff(full_path, diff);
+            }
+            _ => {
+                let _ = view.open_file(full_path);
+            }
+        });
+
+        self.reveal_file_view();
+        cx.notify();
+    }
+}
@@ -0,0 +1,115 @@
+//! Thumbnails of the terminals of sessions that are not shown, drawn under
+//! their sidebar entries (`sashiki.sidebar.minimap`)
+//!
+//! The last rows are read at most every `REFRESH_INTERVAL`, and only for
+//! sessions that printed since their rows were last read; idle sessions
+//! cost one timestamp check per refresh and nothing per frame.
+
+use super::SashikiApp;
+use crate::session::LayoutMode;
+use crate::terminal::TerminalTail;
+use gpui::{Context, Task};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Rows in the preview shown when hovering a thumbnail
+const PREVIEW_ROWS: usize = 20;
+
+/// Shortest time between two reads of a session's rows (2 Hz)
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Last rows of the sessions' active terminals
+#[derive(Debug)]
+pub(crate) struct Minimaps {
+    /// Whether thumbnails are drawn at all
+    pub(crate) enabled: bool,
+    /// By worktree path
+    tails: HashMap<PathBuf, TerminalTail>,
+}
+
+impl Default for Minimaps {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tails: HashMap::new(),
+        }
+    }
+}
+
+impl Minimaps {
+    /// Rows last read for the session of `worktree`
+    pub(crate) fn tail(&self, worktree: &Path) -> Option<&TerminalTail> {
+        self.tails.get(worktree)
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.tails.clear();
+        }
+    }
+
+    /// Drop the rows of sessions whose worktrees are gone
+    pub(crate) fn retain_worktrees<'a>(&mut self, worktrees: impl IntoIterator<Item = &'a Path>) {
+        let keep: Vec<&Path> = worktrees.into_iter().collect();
+        self.tails
+            .retain(|path, _| keep.iter().any(|worktree| worktree == path));
+    }
+}
+
+impl SashikiApp {
+    /// Refresh the thumbnails only while they are drawn
+    pub(crate) fn update_minimap_watch(&mut self, cx: &mut Context<Self>) {
+        let wanted = self.minimaps.enabled;
+        if wanted != self.minimap_watch.is_some() {
+            self.minimap_watch = wanted.then(|| Self::spawn_minimap_watch(cx));
+        }
+    }
+
+    /// Refresh the thumbnails every `REFRESH_INTERVAL` until the task is
+    /// dropped
+    fn spawn_minimap_watch(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(REFRESH_INTERVAL).await;
+                let Ok(()) = entity.update(cx, |app, cx| app.refresh_minimaps(cx)) else {
+                    break;
+                };
+            }
+        })
+    }
+
+    /// Read the rows of the sessions not shown that printed since their
+    /// rows were last read
+    fn refresh_minimaps(&mut self, cx: &mut Context<Self>) {
+        if !self.minimaps.enabled {
+            return;
+        }
+        let layout_mode = self.repo.session_manager.layout_mode();
+        let active_index = self.repo.session_manager.active_index();
+        let mut changed = false;
+        for (i, session) in self.repo.session_manager.sessions().iter().enumerate() {
+            let shown = match layout_mode {
+                LayoutMode::Single => i == active_index,
+                LayoutMode::Parallel => session.is_visible_in_parallel(),
+            };
+            let Some(last_output) = session.last_output(cx).filter(|_| !shown) else {
+                continue;
+            };
+            let read = self.minimaps.tails.get(session.worktree_path());
+            if read.is_some_and(|tail| tail.last_output >= Some(last_output)) {
+                continue;
+            }
+            if let Some(tail) = session.terminal_tail(PREVIEW_ROWS, cx) {
+                self.minimaps
+                    .tails
+                    .insert(session.worktree_path().to_path_buf(), tail);
+                changed = true;
+            }
+        }
+        if changed {
+            cx.notify();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Sessions whose worktree directory was deleted outside Sashiki: found on
+//! opening the repository, on each worktree refresh and on every HEAD poll
+//! (see `head_watch`). No terminal starts in them; the session's right-click
+//! offers to prune the worktree or to recreate its directory.
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::git;
+use crate::git_queue::QueuedOp;
+use gpui::{Context, Window};
+use std::path::{Path, PathBuf};
+
+impl SashikiApp {
+    /// Look for the sessions' worktree directories on disk
+    pub(crate) fn check_missing_worktrees(&mut self, cx: &mut Context<Self>) {
+        self.mark_missing_worktrees(|path| !path.is_dir(), cx);
+    }
+
+    /// Mark the sessions whose worktree is among `missing` (looked for off
+    /// the main thread) and only those
+    pub(crate) fn set_missing_worktrees(&mut self, missing: &[PathBuf], cx: &mut Context<Self>) {
+        self.mark_missing_worktrees(|path| missing.iter().any(|m| m == path), cx);
+    }
+
+    fn mark_missing_worktrees(
+        &mut self,
+        is_missing: impl Fn(&Path) -> bool,
+        cx: &mut Context<Self>,
+    ) {
+        let before: Vec<bool> = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|s| s.is_missing())
+            .collect();
+        for name in self.repo.session_manager.mark_missing_worktrees(is_missing) {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::App,
+                format!(
+                    "The directory of worktree {} is gone; no terminal starts in it",
+                    name
+                ),
+            ));
+        }
+        if self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|s| s.is_missing())
+            .ne(before)
+        {
+            cx.notify();
+        }
+    }
+
+    pub fn open_missing_worktree_dialog(
+        &mut self,
+        index: usize,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(session) = self.repo.session_manager.sessions().get(index) else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::MissingWorktree {
+            worktree: session.worktree().clone(),
+        };
+        cx.notify();
+    }
+
+    pub fn close_missing_worktree_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Forget the dialog's worktree (`git worktree prune`, which forgets
+    /// any other whose directory is gone too) and remove its session
+    pub fn prune_missing_worktree(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::MissingWorktree { worktree } = std::mem::take(&mut self.active_dialog)
+        else {
+            return;
+        };
+        if worktree.locked {
+            self.active_dialog = ActiveDialog::error(format!(
+                "Worktree {} is locked, so it is not pruned.\n\
+                 Unlock it (git worktree unlock) or recreate its directory.",
+                worktree.name
+            ));
+            cx.notify();
+            return;
+        }
+        let Some(repo) = self.repo.git_repo.clone() else {
+            return;
+        };
+        let done = format!("Pruned worktree {}", worktree.name);
+        let failed = format!("Failed to prune worktree {}", worktree.name);
+        let name = worktree.name.clone();
+        let op = self.repo.git_queue.push(
+            format!("Prune worktree {}", worktree.name),
+            None,
+            move || {
+                repo.prune_worktrees()?;
+                repo.remove_session_config(&name);
+                Ok(())
+            },
+        );
+        // Its history, review marks and comments go only once it is pruned
+        let forget = move |app: &mut Self| app.remove_session_state(&worktree.name);
+        self.finish_missing_worktree_op(op, done, failed, forget, cx);
+    }
+
+    /// Check the dialog's worktree out again into a new directory at its path
+    pub fn recreate_missing_worktree(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::MissingWorktree { worktree } = std::mem::take(&mut self.active_dialog)
+        else {
+            return;
+        };
+        let Some(repo) = self.repo.git_repo.clone() else {
+            return;
+        };
+        let done = format!("Recreated {}", worktree.path.display());
+        let failed = format!("Failed to recreate {}", worktree.path.display());
+        let op = self.repo.git_queue.push(
+            format!("Recreate worktree {}", worktree.name),
+            None,
+            move || repo.recreate_worktree(&worktree),
+        );
+        self.finish_missing_worktree_op(op, done, failed, |_| {}, cx);
+    }
+
+    /// Log `done` and run `on_success`, or show `failed` with the error,
+    /// once `op` ran; then sync the sessions with the worktrees
+    fn finish_missing_worktree_op(
+        &mut self,
+        op: QueuedOp<git::Result<()>>,
+        done: String,
+        failed: String,
+        on_success: impl FnOnce(&mut Self) + 'static,
+        cx: &mut Context<Self>,
+    ) {
+        cx.spawn(async move |entity, cx| {
+            let result = op.wait().await;
+            let _ = entity.update(cx, |app, cx| {
+                match result {
+                    Some(Ok(())) => {
+                        app_log::record(LogEntry::new(LogLevel::Info, LogSource::Git, done));
+                        on_success(app);
+                    }
+                    Some(Err(e)) => {
+                        app.active_dialog = ActiveDialog::error(format!("{}:\n{}", failed, e))
+                    }
+                    None => {}
+                }
+                app.refresh_worktrees(cx);
+            });
+        })
+        .detach();
+        cx.notify();
+    }
+}
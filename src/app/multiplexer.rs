@@ -0,0 +1,88 @@
+//! Terminals kept running in tmux across restarts, and closing them by
+//! detaching or killing (see `crate::multiplexer`)
+
+use super::SashikiApp;
+use super::actions::CloseTerminal;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::multiplexer::{self, Multiplexer, TmuxNames};
+use gpui::{Context, Window};
+
+impl SashikiApp {
+    /// Run new terminals in `multiplexer` and attach the sessions to the tmux
+    /// sessions an earlier run left behind. A multiplexer that is not
+    /// installed is logged, and terminals stay bare shells.
+    pub(crate) fn apply_multiplexer(
+        &mut self,
+        multiplexer: Option<Multiplexer>,
+        cx: &mut Context<Self>,
+    ) {
+        let available = multiplexer.filter(|multiplexer| {
+            let available = multiplexer.is_available();
+            if !available {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::Terminal,
+                    "sashiki.terminal.multiplexer is tmux, but tmux could not be run: terminals start without it",
+                ));
+            }
+            available
+        });
+        let names = available.and_then(|_| {
+            let repo = self.repo.git_repo.as_ref()?;
+            Some(TmuxNames::new(repo.workdir()))
+        });
+        let enabled = names.is_some();
+        self.repo.session_manager.set_multiplexer(names);
+        if !enabled {
+            return;
+        }
+        let restored = self
+            .repo
+            .session_manager
+            .restore_terminals(&multiplexer::list_sessions(), cx);
+        self.update_activity_watch(cx);
+        if restored > 0 {
+            app_log::record(LogEntry::new(
+                LogLevel::Info,
+                LogSource::Terminal,
+                format!("Reattached {} session(s) to their tmux sessions", restored),
+            ));
+        }
+    }
+
+    /// Close the active terminal. One running in tmux asks whether to
+    /// detach from it or kill it.
+    pub fn on_close_terminal(&mut self, _: &CloseTerminal, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(terminal) = self.active_terminal() else {
+            return;
+        };
+        match terminal.read(cx).multiplexed() {
+            Some(name) => {
+                self.active_dialog = ActiveDialog::CloseTerminalConfirm {
+                    name: name.to_string(),
+                };
+                cx.notify();
+            }
+            None => self.close_active_terminal(false, cx),
+        }
+    }
+
+    /// Close the active terminal, ending its tmux session when `kill`
+    pub fn close_active_terminal(&mut self, kill: bool, cx: &mut Context<Self>) {
+        if matches!(
+            self.active_dialog,
+            ActiveDialog::CloseTerminalConfirm { .. }
+        ) {
+            self.active_dialog = ActiveDialog::None;
+        }
+        self.repo.session_manager.close_active_terminal(kill, cx);
+        self.update_activity_watch(cx);
+        cx.notify();
+    }
+
+    pub fn close_close_terminal_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+}
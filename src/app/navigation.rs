@@ -0,0 +1,205 @@
+//! Keyboard navigation of the sidebar and file list
+
+use super::{
+    FocusSidebar, SashikiApp, SidebarActivate, SidebarDown, SidebarExit, SidebarToggleDir,
+    SidebarUp,
+};
+use crate::session::LayoutMode;
+use crate::ui::file_list::FILE_ROW_HEIGHT;
+use crate::ui::virtual_rows;
+use crate::ui::{FileListMode, NavRow, NavTarget};
+use gpui::{Context, Focusable, KeyDownEvent, Window, point, px};
+use std::path::PathBuf;
+use std::time::Instant;
+
+impl SashikiApp {
+    /// Rows in on-screen order: sessions, then visible file list entries
+    pub(crate) fn sidebar_nav_rows(&self) -> Vec<NavRow> {
+        let sessions = self
+            .repo
+            .session_manager
+            .ordered_sessions()
+            .map(|(_, s)| NavRow::session(s.worktree_path().to_path_buf(), s.name()));
+        let entries = self.file_rows.iter().map(|row| {
+            // Submodules and nested repositories open like files rather than
+            // expanding
+            NavRow::entry(
+                row.path.clone(),
+                row.is_dir && !row.is_submodule && !row.is_nested_repo,
+                row.name.clone(),
+                row.change_info.map(|i| i.change_type),
+            )
+        });
+        sessions.chain(entries).collect()
+    }
+
+    /// Scroll the file list so the highlighted entry is visible
+    fn reveal_nav_selection(&self) {
+        let Some(NavTarget::Entry { path, .. }) =
+            self.repo.list_nav.selected_row().map(|r| &r.target)
+        else {
+            return;
+        };
+        let Some(index) = self.file_rows.iter().position(|row| &row.path == path) else {
+            return;
+        };
+
+        let scroll_top = -f32::from(self.file_list_scroll.offset().y);
+        let viewport_height = f32::from(self.file_list_scroll.bounds().size.height);
+        let top = virtual_rows::reveal(index, FILE_ROW_HEIGHT, scroll_top, viewport_height);
+        if top != scroll_top {
+            self.file_list_scroll.set_offset(point(px(0.), px(-top)));
+        }
+    }
+
+    /// Rebuild navigable rows, keeping the highlight on the same item
+    pub(crate) fn sync_list_nav(&mut self) {
+        let rows = self.sidebar_nav_rows();
+        self.repo.list_nav.sync(rows);
+    }
+
+    pub fn on_focus_sidebar(
+        &mut self,
+        _: &FocusSidebar,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.show_sidebar = true;
+        window.focus(&self.sidebar_focus, cx);
+
+        self.sync_list_nav();
+        if self.repo.list_nav.selected_index().is_none()
+            && let Some(session) = self.repo.session_manager.active_session()
+        {
+            let target = NavTarget::Session(session.worktree_path().to_path_buf());
+            self.repo.list_nav.select(&target);
+        }
+        cx.notify();
+    }
+
+    pub fn on_sidebar_up(&mut self, _: &SidebarUp, _: &mut Window, cx: &mut Context<Self>) {
+        self.sync_list_nav();
+        self.repo.list_nav.move_by(-1);
+        self.reveal_nav_selection();
+        cx.notify();
+    }
+
+    pub fn on_sidebar_down(&mut self, _: &SidebarDown, _: &mut Window, cx: &mut Context<Self>) {
+        self.sync_list_nav();
+        self.repo.list_nav.move_by(1);
+        self.reveal_nav_selection();
+        cx.notify();
+    }
+
+    /// Enter: switch to a session, open a file's diff, or toggle a directory
+    pub fn on_sidebar_activate(
+        &mut self,
+        _: &SidebarActivate,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.sync_list_nav();
+        let Some(row) = self.repo.list_nav.selected_row().cloned() else {
+            return;
+        };
+
+        match row.target {
+            NavTarget::Session(worktree_path) => {
+                let Some(index) = self
+                    .repo
+                    .session_manager
+                    .sessions()
+                    .iter()
+                    .position(|s| s.worktree_path() == worktree_path)
+                else {
+                    return;
+                };
+                match self.repo.session_manager.layout_mode() {
+                    LayoutMode::Single => {
+                        self.on_session_selected(index, window, cx);
+                        // Stay in the sidebar so the new session's files can be browsed
+                        window.focus(&self.sidebar_focus, cx);
+                    }
+                    LayoutMode::Parallel => self.on_toggle_parallel_visibility(index, cx),
+                }
+            }
+            NavTarget::Entry { path, is_dir: true } => {
+                self.toggle_dir_expanded(&path);
+            }
+            NavTarget::Entry {
+                path,
+                is_dir: false,
+            } => {
+                let relative = match self.file_list_mode {
+                    FileListMode::Changes => path,
+                    FileListMode::AllFiles => self
+                        .repo
+                        .session_manager
+                        .active_session()
+                        .and_then(|s| path.strip_prefix(s.worktree_path()).ok())
+                        .map(PathBuf::from)
+                        .unwrap_or(path),
+                };
+                self.on_file_selected(relative, row.change_type, cx);
+            }
+        }
+        cx.notify();
+    }
+
+    /// Space: expand or collapse the highlighted directory
+    pub fn on_sidebar_toggle_dir(
+        &mut self,
+        _: &SidebarToggleDir,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.sync_list_nav();
+        if let Some(NavRow {
+            target: NavTarget::Entry { path, is_dir: true },
+            ..
+        }) = self.repo.list_nav.selected_row().cloned()
+        {
+            self.toggle_dir_expanded(&path);
+            cx.notify();
+        }
+    }
+
+    /// Esc: hand focus back to the active terminal
+    pub fn on_sidebar_exit(
+        &mut self,
+        _: &SidebarExit,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.repo.list_nav.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        } else {
+            window.focus(&self.focus_handle, cx);
+        }
+        cx.notify();
+    }
+
+    /// Type-ahead: printable keys without modifiers jump to a matching row
+    pub fn on_sidebar_key_down(&mut self, event: &KeyDownEvent, cx: &mut Context<Self>) {
+        let keystroke = &event.keystroke;
+        if keystroke.modifiers.control || keystroke.modifiers.alt || keystroke.modifiers.platform {
+            return;
+        }
+        let Some(c) = keystroke
+            .key_char
+            .as_deref()
+            .and_then(|s| s.chars().next())
+            .filter(|c| !c.is_control() && !c.is_whitespace())
+        else {
+            return;
+        };
+
+        self.sync_list_nav();
+        if self.repo.list_nav.type_ahead(c, Instant::now()) {
+            self.reveal_nav_selection();
+            cx.notify();
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Independent repositories nested in a worktree (see
+//! `git::mark_nested_repositories`): open one on its own or ignore it
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use gpui::Context;
+use std::path::PathBuf;
+
+impl SashikiApp {
+    /// Offer to open or ignore the nested repository at `path` (relative)
+    pub fn open_nested_repository_dialog(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::NestedRepository { path };
+        cx.notify();
+    }
+
+    pub fn close_nested_repository_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Open the nested repository next to the one it is in
+    pub fn open_nested_repository(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::NestedRepository { path } = &self.active_dialog else {
+            return;
+        };
+        let Some(root) = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().join(path))
+        else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::None;
+        self.open_project(root, cx);
+    }
+
+    /// Add the nested repository to `info/exclude`, so it is no longer
+    /// listed, and read the changed files again
+    pub fn ignore_nested_repository(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::NestedRepository { path } = &self.active_dialog else {
+            return;
+        };
+        let path = path.clone();
+        self.active_dialog = ActiveDialog::None;
+        let Some(repo) = self.worktree_repo() else {
+            cx.notify();
+            return;
+        };
+        match repo.exclude_dir(&path) {
+            Ok(()) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::Git,
+                    format!("Ignoring the nested repository {}", path.display()),
+                ));
+                self.repo.nested_repo_paths.remove(&path);
+                self.build_file_tree();
+                self.refresh_file_list_async(cx);
+            }
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to ignore {}: {}", path.display(), e));
+            }
+        }
+        cx.notify();
+    }
+}
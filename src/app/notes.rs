@@ -0,0 +1,204 @@
+//! The notes panel: a markdown scratchpad per session below the terminals
+//! (see `crate::notes`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::notes::{self, AutosaveDebouncer, NOTE_AUTOSAVE_DELAY};
+use gpui::{Context, Focusable, Window};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Note being shown in the notes panel
+#[derive(Debug, Default)]
+pub(crate) struct NotesPanel {
+    /// Worktree name of the session whose note is open (None = closed)
+    pub(crate) session: Option<String>,
+    path: PathBuf,
+    pub(crate) text: String,
+    /// Cursor position (char index)
+    pub(crate) cursor: usize,
+    autosave: AutosaveDebouncer,
+    save_scheduled: bool,
+    /// Worktree names whose note is not empty (the sidebar's note icon)
+    pub(crate) noted: HashSet<String>,
+}
+
+impl SashikiApp {
+    /// Find which sessions have a note
+    pub(crate) fn load_noted_sessions(&mut self) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            self.notes.noted.clear();
+            return;
+        };
+        self.notes.noted = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|session| session.name())
+            .filter(|name| notes::has_note(&notes::note_path(repo.git_dir(), name)))
+            .map(str::to_string)
+            .collect();
+    }
+
+    /// Save and close the open note (another repository is being opened)
+    pub(crate) fn reset_notes(&mut self) {
+        self.save_note();
+        self.notes = NotesPanel::default();
+    }
+
+    /// Show the note of the session at `index`, or close the panel when it
+    /// already shows that note
+    pub fn toggle_session_notes(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(name) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .map(|s| s.name().to_string())
+        else {
+            return;
+        };
+        if self.notes.session.as_deref() == Some(name.as_str()) {
+            self.close_notes(window, cx);
+        } else {
+            self.open_notes(name, window, cx);
+        }
+    }
+
+    fn open_notes(&mut self, name: String, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        let path = notes::note_path(repo.git_dir(), &name);
+        self.save_note();
+        let text = match notes::read_note(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to read the note of {}: {}", name, e));
+                cx.notify();
+                return;
+            }
+        };
+        self.notes.cursor = text.chars().count();
+        self.notes.text = text;
+        self.notes.path = path;
+        self.notes.session = Some(name);
+        window.focus(&self.notes_focus, cx);
+        cx.notify();
+    }
+
+    /// Save the open note and hide the panel
+    pub fn close_notes(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.save_note();
+        self.notes.session = None;
+        self.notes.text.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Close the note of the session named `name` if it is open, saving it
+    /// unless it is being discarded
+    pub(crate) fn close_notes_of(&mut self, name: &str, save: bool) {
+        if self.notes.session.as_deref() != Some(name) {
+            return;
+        }
+        if save {
+            self.save_note();
+        } else {
+            self.notes.autosave.take();
+        }
+        self.notes.session = None;
+        self.notes.text.clear();
+    }
+
+    /// Delete the note of the session named `name` (its worktree is being
+    /// deleted and the note was not kept)
+    pub(crate) fn discard_note(&mut self, name: &str) {
+        self.close_notes_of(name, false);
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        if let Err(e) = notes::remove_note(&notes::note_path(repo.git_dir(), name)) {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::App,
+                format!("Failed to delete the note of {}: {}", name, e),
+            ));
+        }
+        self.notes.noted.remove(name);
+    }
+
+    /// Apply an edit to the open note and schedule saving it
+    pub(crate) fn edit_note(
+        &mut self,
+        edit: impl FnOnce(&mut String, &mut usize),
+        cx: &mut Context<Self>,
+    ) {
+        if self.notes.session.is_none() {
+            return;
+        }
+        edit(&mut self.notes.text, &mut self.notes.cursor);
+        self.notes.autosave.changed(Instant::now());
+        if !self.notes.save_scheduled {
+            self.notes.save_scheduled = true;
+            cx.spawn(async move |this, cx| {
+                loop {
+                    smol::Timer::after(NOTE_AUTOSAVE_DELAY).await;
+                    let pending = this.update(cx, |app, cx| {
+                        if app.notes.autosave.due(Instant::now()) {
+                            app.write_note();
+                            cx.notify();
+                        }
+                        app.notes.save_scheduled = app.notes.autosave.is_pending();
+                        app.notes.save_scheduled
+                    });
+                    // Stop when nothing is pending or the app was dropped
+                    if !matches!(pending, Ok(true)) {
+                        break;
+                    }
+                }
+            })
+            .detach();
+        }
+        cx.notify();
+    }
+
+    /// Save the open note now if it has unsaved edits
+    fn save_note(&mut self) {
+        if self.notes.autosave.take() {
+            self.write_note();
+        }
+    }
+
+    fn write_note(&mut self) {
+        let Some(name) = self.notes.session.clone() else {
+            return;
+        };
+        match notes::write_note(&self.notes.path, &self.notes.text) {
+            Ok(()) => {
+                if self.notes.text.trim().is_empty() {
+                    self.notes.noted.remove(&name);
+                } else {
+                    self.notes.noted.insert(name);
+                }
+            }
+            Err(e) => app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::App,
+                format!("Failed to save the note of {}: {}", name, e),
+            )),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Read-only sessions: an app-level guard against changing a worktree from
+//! Sashiki by accident. The terminal keeps working; nothing is locked on disk.
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::git;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Turn read-only on or off for the session at `index` and store the
+    /// choice in the repository config
+    pub fn toggle_session_read_only(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(repo), Some(session)) = (
+            self.repo.git_repo.as_ref(),
+            self.repo.session_manager.sessions().get(index),
+        ) else {
+            return;
+        };
+        let read_only = !session.is_read_only();
+        let key = git::session_read_only_key(session.name());
+
+        match repo.set_config_value(&key, if read_only { "true" } else { "false" }) {
+            Ok(()) => {
+                self.repo
+                    .session_manager
+                    .set_session_read_only(index, read_only);
+                self.sync_file_view_read_only(cx);
+            }
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to save the read-only setting: {}", e));
+            }
+        }
+        cx.notify();
+    }
+
+    /// False (with the reason in the error dialog) when `action` would change
+    /// the worktree of a read-only session
+    pub(crate) fn ensure_writable(
+        &mut self,
+        index: usize,
+        action: &str,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let result = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .map_or(Ok(()), |session| session.check_writable(action));
+        match result {
+            Ok(()) => true,
+            Err(message) => {
+                self.active_dialog = ActiveDialog::error(message);
+                cx.notify();
+                false
+            }
+        }
+    }
+
+    /// Hide the file view's editing actions while the active session is read-only
+    pub(crate) fn sync_file_view_read_only(&mut self, cx: &mut Context<Self>) {
+        let read_only = self
+            .repo
+            .session_manager
+            .active_session()
+            .is_some_and(|session| session.is_read_only());
+        self.file_view.update(cx, |view, cx| {
+            if view.is_read_only() != read_only {
+                view.set_read_only(read_only);
+                cx.notify();
+            }
+        });
+    }
+}
@@ -0,0 +1,131 @@
+//! Remote links and pull request badges of session branches (see
+//! `crate::remote`)
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::remote::{self, Forge, RemoteRepo};
+use gpui::Context;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two automatic pull request lookups
+const PR_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+impl SashikiApp {
+    /// Read `origin` and its default branch for the opened repository
+    pub(crate) fn load_remote(&mut self, cx: &mut Context<Self>) {
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            return;
+        };
+        self.repo.remote = repo.origin_url().as_deref().and_then(RemoteRepo::parse);
+        self.repo.remote_base_branch = repo.origin_default_branch().or_else(|| {
+            self.repo
+                .session_manager
+                .sessions()
+                .iter()
+                .find(|s| s.is_main())
+                .and_then(|s| s.branch())
+                .map(str::to_string)
+        });
+        self.refresh_pull_requests_async(true, cx);
+    }
+
+    /// Look up the pull requests of all session branches with `gh` in the
+    /// background. Throttled unless `force`d; GitHub remotes only.
+    pub fn refresh_pull_requests_async(&mut self, force: bool, cx: &mut Context<Self>) {
+        if self.pr_scan.is_some() || !self.gh_available {
+            return;
+        }
+        if self.repo.remote.as_ref().map(|r| r.forge) != Some(Forge::GitHub) {
+            return;
+        }
+        if !force
+            && let Some(last) = self.repo.pr_scan_at
+            && last.elapsed() < PR_SCAN_INTERVAL
+        {
+            return;
+        }
+        let Some(workdir) = self
+            .repo
+            .git_repo
+            .as_ref()
+            .map(|r| r.workdir().to_path_buf())
+        else {
+            return;
+        };
+        let branches: Vec<String> = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .filter(|s| !s.is_main())
+            .filter_map(|s| s.branch().map(str::to_string))
+            .collect();
+
+        self.repo.pr_scan_at = Some(Instant::now());
+        self.pr_scan = Some(cx.spawn(async move |entity, cx| {
+            let found = smol::unblock(move || remote::pull_requests(&workdir, &branches)).await;
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.pr_scan = None;
+                match found {
+                    Some(pull_requests) => app.repo.pull_requests = pull_requests,
+                    None => app.gh_available = false,
+                }
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Open the branch of the session at `index` on the forge
+    pub fn open_branch_on_remote(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(remote), Some(branch)) = (
+            self.repo.remote.as_ref(),
+            self.repo
+                .session_manager
+                .sessions()
+                .get(index)
+                .and_then(|s| s.branch()),
+        ) else {
+            return;
+        };
+        let url = remote.branch_url(branch);
+        self.open_url(&url, cx);
+    }
+
+    /// Open the pull request of the session at `index`: the existing one,
+    /// else the page creating one against the default branch
+    pub fn open_pull_request(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(remote), Some(branch)) = (
+            self.repo.remote.as_ref(),
+            self.repo
+                .session_manager
+                .sessions()
+                .get(index)
+                .and_then(|s| s.branch()),
+        ) else {
+            return;
+        };
+        let url = match (
+            self.repo.pull_requests.get(branch),
+            self.repo.remote_base_branch.as_deref(),
+        ) {
+            (Some(pr), _) => pr.url.clone(),
+            (None, Some(base)) => remote.new_pull_request_url(branch, base),
+            (None, None) => {
+                self.active_dialog =
+                    ActiveDialog::error("The default branch of origin is not known");
+                cx.notify();
+                return;
+            }
+        };
+        self.open_url(&url, cx);
+    }
+
+    fn open_url(&mut self, url: &str, cx: &mut Context<Self>) {
+        if let Err(e) = open::that(url) {
+            self.active_dialog = ActiveDialog::error(format!("Failed to open {}: {}", url, e));
+            cx.notify();
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Switching between the repositories open in the window (see
+//! `crate::repositories`): the shown one's `RepositoryState` is the app's
+//! `repo`, the others' are swapped out until they are shown again
+
+use super::SashikiApp;
+use super::actions::{CloseRepository, NextRepository, PreviousRepository};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::conflicts::ConflictReport;
+use crate::dialog::ActiveDialog;
+use crate::diff_cache::DiffCache;
+use crate::file_cache::FileCache;
+use crate::git::{self, ChangedFile, GitRepo, StashEntry};
+use crate::git_queue::GitQueue;
+use crate::hooks;
+use crate::remote::{PullRequest, RemoteRepo};
+use crate::session::SessionManager;
+use crate::session_state::PendingSave;
+use crate::settings::{self, Settings};
+use crate::ui::{DirChangeSummary, FileTreeNode, ListNav};
+use crate::worktree_guard;
+use gpui::{Context, Window};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// What belongs to one repository: the shown one's is `SashikiApp::repo`,
+/// the others' are set aside in `SashikiApp::repositories`
+pub(crate) struct RepositoryState {
+    pub(crate) session_manager: SessionManager,
+    pub(crate) git_repo: Option<GitRepo>,
+    pub(crate) changed_files: Vec<ChangedFile>,
+    /// Changes below each directory (from `changed_files`)
+    pub(crate) dir_changes: HashMap<PathBuf, DirChangeSummary>,
+    /// Changed files and directory listings of every session, so switching
+    /// shows a list at once
+    pub(crate) file_cache: FileCache,
+    pub(crate) expanded_dirs: HashSet<PathBuf>,
+    pub(crate) file_tree: Option<FileTreeNode>,
+    /// Index of the open file among the changed files in review order, kept
+    /// for stepping on when a refresh drops it
+    pub(crate) review_index: Option<usize>,
+    /// Submodule paths of the repository (relative), listed but never walked into
+    pub(crate) submodule_paths: HashSet<PathBuf>,
+    /// Roots of the repositories nested in the active worktree (relative),
+    /// listed but never walked into
+    pub(crate) nested_repo_paths: HashSet<PathBuf>,
+    /// Cached repo for active worktree (avoids repeated Repository::discover() calls)
+    pub(crate) cached_worktree: Option<(GitRepo, PathBuf)>,
+    /// Files changed in more than one worktree (from the last background scan)
+    pub(crate) conflict_report: ConflictReport,
+    pub(crate) conflict_scan_at: Option<Instant>,
+    /// Tag groups folded in the sidebar (None is the untagged group)
+    pub(crate) collapsed_tag_groups: HashSet<Option<String>>,
+    /// Keyboard highlight across sessions and file entries (while the sidebar has focus)
+    pub(crate) list_nav: ListNav,
+    /// Stashes of the repository (shared by all worktrees)
+    pub(crate) stashes: Vec<StashEntry>,
+    /// Diffs of changed files, prefetched in the background
+    pub(crate) diff_cache: DiffCache,
+    /// Git mutations of the repository, run one at a time
+    pub(crate) git_queue: GitQueue,
+    /// Whether the queued git operations are listed below the header
+    pub(crate) show_git_queue: bool,
+    /// `origin` on GitHub or GitLab (None hides the remote actions)
+    pub(crate) remote: Option<RemoteRepo>,
+    /// Branch new pull requests are opened against
+    pub(crate) remote_base_branch: Option<String>,
+    /// Pull requests of session branches, by branch (GitHub with `gh` only)
+    pub(crate) pull_requests: HashMap<String, PullRequest>,
+    pub(crate) pr_scan_at: Option<Instant>,
+}
+
+impl RepositoryState {
+    /// State of a repository just opened (nothing read yet)
+    pub(crate) fn new(cx: &mut Context<SashikiApp>) -> Self {
+        Self {
+            session_manager: SessionManager::new(),
+            git_repo: None,
+            changed_files: Vec::new(),
+            dir_changes: HashMap::new(),
+            file_cache: FileCache::default(),
+            expanded_dirs: HashSet::new(),
+            file_tree: None,
+            review_index: None,
+            submodule_paths: HashSet::new(),
+            nested_repo_paths: HashSet::new(),
+            cached_worktree: None,
+            conflict_report: ConflictReport::default(),
+            conflict_scan_at: None,
+            collapsed_tag_groups: HashSet::new(),
+            list_nav: ListNav::default(),
+            stashes: Vec::new(),
+            diff_cache: DiffCache::default(),
+            git_queue: SashikiApp::spawn_git_queue(cx),
+            show_git_queue: false,
+            remote: None,
+            remote_base_branch: None,
+            pull_requests: HashMap::new(),
+            pr_scan_at: None,
+        }
+    }
+
+    /// Stop the terminals and hooks of all sessions of a repository being
+    /// closed
+    fn tear_down(mut self, cx: &mut Context<SashikiApp>) {
+        for i in 0..self.session_manager.len() {
+            self.session_manager.stop_session_terminals(i, cx);
+        }
+        if let Some(repo) = &self.git_repo {
+            hooks::forget(repo.workdir());
+        }
+        let saves = self.session_manager.take_pending_state_saves();
+        if !saves.is_empty() {
+            smol::unblock(move || saves.into_iter().for_each(PendingSave::write)).detach();
+        }
+    }
+}
+
+impl SashikiApp {
+    pub fn on_next_repository(
+        &mut self,
+        _: &NextRepository,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.repositories.next(true) {
+            self.show_repository(index, cx);
+        }
+    }
+
+    pub fn on_previous_repository(
+        &mut self,
+        _: &PreviousRepository,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.repositories.next(false) {
+            self.show_repository(index, cx);
+        }
+    }
+
+    pub fn on_close_repository(
+        &mut self,
+        _: &CloseRepository,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.repositories.active() {
+            self.close_repository(index, cx);
+        }
+    }
+
+    /// Session managers of all open repositories, the shown one's first
+    pub(crate) fn all_session_managers(&self) -> impl Iterator<Item = &SessionManager> {
+        std::iter::once(&self.repo.session_manager).chain(
+            self.repositories
+                .parked()
+                .map(|state| &state.session_manager),
+        )
+    }
+
+    /// Set the shown repository aside and start afresh for `root`, which
+    /// becomes the shown one
+    pub(crate) fn add_repository(&mut self, root: PathBuf, cx: &mut Context<Self>) {
+        self.leave_repository(cx);
+        let mut repositories = std::mem::take(&mut self.repositories);
+        repositories.add(root, || {
+            std::mem::replace(&mut self.repo, RepositoryState::new(cx))
+        });
+        self.repositories = repositories;
+        settings::record_open_repositories(self.repositories.roots());
+        self.update_watches(cx);
+    }
+
+    /// Show open repository `index` in place of the current one
+    pub fn show_repository(&mut self, index: usize, cx: &mut Context<Self>) {
+        if self.repositories.active() == Some(index)
+            || index >= self.repositories.len()
+            || self.bulk_create_blocks_switch()
+        {
+            return;
+        }
+        self.leave_repository(cx);
+        let mut repositories = std::mem::take(&mut self.repositories);
+        repositories.switch(index, |state| std::mem::swap(&mut self.repo, state));
+        self.repositories = repositories;
+        self.enter_repository(cx);
+    }
+
+    /// Close open repository `index` and stop its terminals; closing the
+    /// shown one shows the next, or leaves the window empty
+    pub fn close_repository(&mut self, index: usize, cx: &mut Context<Self>) {
+        if index >= self.repositories.len()
+            || (self.repositories.active() == Some(index) && self.bulk_create_blocks_switch())
+        {
+            return;
+        }
+        if self.repositories.active() == Some(index)
+            && let Some(next) = self.repositories.next(true)
+        {
+            self.show_repository(next, cx);
+        }
+        let closed = match self.repositories.remove(index) {
+            Some(state) => state,
+            None => {
+                self.leave_repository(cx);
+                std::mem::replace(&mut self.repo, RepositoryState::new(cx))
+            }
+        };
+        closed.tear_down(cx);
+        settings::record_open_repositories(self.repositories.roots());
+        self.update_watches(cx);
+        cx.notify();
+    }
+
+    /// Drop what is shown for the current repository and stop its
+    /// background work; its terminals keep running
+    fn leave_repository(&mut self, cx: &mut Context<Self>) {
+        self.file_view.update(cx, |view, _cx| view.close());
+        self.show_file_view = false;
+        self.show_verify_terminal = false;
+        self.active_dialog = ActiveDialog::None;
+        self.review.open = None;
+        self.open_diff_stale = false;
+        self.diff_prefetch = None;
+        self.cancel_diff_job(cx);
+        self.pr_scan = None;
+        self.reset_notes();
+        self.timeline = Default::default();
+        self.minimaps = Default::default();
+        self.agents = Default::default();
+        self.maintenance.cancel_scan();
+        self.maintenance_scan = None;
+        self.maintenance = Default::default();
+        // Done or never started (see `bulk_create_blocks_switch`)
+        self.bulk_create = Default::default();
+        self.diff_stats_popover = None;
+    }
+
+    /// Take up a repository switched to: its settings apply again and what
+    /// may have changed while it was set aside is read afresh
+    fn enter_repository(&mut self, cx: &mut Context<Self>) {
+        self.update_watches(cx);
+        let Some(repo) = self.repo.git_repo.as_ref() else {
+            cx.notify();
+            return;
+        };
+        let (settings, _) = Settings::load(Some(repo));
+        git::set_limits(settings.git_limits());
+        for warning in worktree_guard::set_allowed_links(&settings) {
+            app_log::record(LogEntry::new(LogLevel::Warn, LogSource::App, warning));
+        }
+        hooks::configure(&settings, repo.workdir());
+        settings::record_last_repository(repo);
+        self.repo.cached_worktree = None;
+        // Worktrees added or removed meanwhile, and the rest of the settings
+        self.refresh_worktrees(cx);
+        let active = self.repo.session_manager.active_index();
+        self.repo
+            .session_manager
+            .ensure_session_terminal(active, cx);
+        self.update_activity_watch(cx);
+        self.refresh_changed_files_sync();
+        self.build_file_tree();
+        self.refresh_stashes();
+        self.refresh_conflicts_async(true, cx);
+        self.refresh_pull_requests_async(true, cx);
+        cx.notify();
+    }
+}
@@ -0,0 +1,90 @@
+//! Memory and CPU of each session's processes, shown next to its sidebar
+//! entry (`sashiki.sidebar.resources`, see `crate::resources`)
+//!
+//! The sampling thread only runs while the readout is turned on; the UI
+//! hands it the shells of the sessions and picks up its figures every
+//! `SAMPLE_INTERVAL`.
+
+use super::SashikiApp;
+use crate::resources::{ResourceMonitor, SAMPLE_INTERVAL, SystemProcesses, TreeUsage};
+use gpui::{Context, Task};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Latest usage of the sessions' process trees
+#[derive(Default)]
+pub(crate) struct Resources {
+    /// Running while the readout is on
+    monitor: Option<ResourceMonitor>,
+    /// By worktree path
+    usage: HashMap<PathBuf, TreeUsage>,
+}
+
+impl Resources {
+    pub(crate) fn enabled(&self) -> bool {
+        self.monitor.is_some()
+    }
+
+    /// Start or stop sampling
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        if enabled == self.enabled() {
+            return;
+        }
+        self.monitor =
+            enabled.then(|| ResourceMonitor::spawn(SystemProcesses::new(), SAMPLE_INTERVAL));
+        self.usage.clear();
+    }
+
+    /// Usage of the session of `worktree`, once sampled
+    pub(crate) fn usage(&self, worktree: &Path) -> Option<&TreeUsage> {
+        self.usage.get(worktree)
+    }
+}
+
+impl SashikiApp {
+    /// Pick up figures only while the readout is on
+    pub(crate) fn update_resource_watch(&mut self, cx: &mut Context<Self>) {
+        let wanted = self.resources.enabled();
+        if wanted != self.resource_watch.is_some() {
+            self.resource_watch = wanted.then(|| Self::spawn_resource_watch(cx));
+        }
+    }
+
+    /// Pick up the figures every `SAMPLE_INTERVAL` until the task is dropped
+    fn spawn_resource_watch(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(SAMPLE_INTERVAL).await;
+                let Ok(()) = entity.update(cx, |app, cx| app.refresh_resources(cx)) else {
+                    break;
+                };
+            }
+        })
+    }
+
+    /// Hand the current shells to the sampler and show its latest figures
+    fn refresh_resources(&mut self, cx: &mut Context<Self>) {
+        let Some(monitor) = &self.resources.monitor else {
+            return;
+        };
+        let roots = self
+            .repo
+            .session_manager
+            .sessions()
+            .iter()
+            .map(|session| {
+                (
+                    session.worktree_path().to_path_buf(),
+                    session.shell_pids(cx),
+                )
+            })
+            .filter(|(_, pids)| !pids.is_empty())
+            .collect();
+        monitor.set_roots(roots);
+        let usage = monitor.usage();
+        if usage != self.resources.usage {
+            self.resources.usage = usage;
+            cx.notify();
+        }
+    }
+}
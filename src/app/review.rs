@@ -0,0 +1,232 @@
+//! Reviewed marks in the Changes list: the checkbox and `r` key, the
+//! "12/30 reviewed" count in the header, marking the file whose diff was
+//! open once another one is opened, and the files left out of review (see
+//! `crate::review`)
+
+use super::{SashikiApp, SidebarToggleReviewed};
+use crate::git::ChangedFile;
+use crate::review::{self, ExcludePatterns, ReviewState};
+use crate::ui::{FileListMode, NavRow, NavTarget};
+use gpui::{Context, Window};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// How the Changes list treats reviewed files
+#[derive(Debug)]
+pub(crate) struct ReviewView {
+    /// List reviewed files after the others of their directory
+    pub(crate) sink_reviewed: bool,
+    /// Mark the file whose diff was open once another file is opened or the
+    /// file view is closed (`sashiki.review.markOnLeave`)
+    pub(crate) mark_on_leave: bool,
+    /// Worktree and relative path of the changed file whose diff is open
+    pub(crate) open: Option<(PathBuf, PathBuf)>,
+    /// Changed files folded away and left out of review
+    /// (`sashiki.review.exclude`)
+    pub(crate) exclude: ExcludePatterns,
+}
+
+impl Default for ReviewView {
+    fn default() -> Self {
+        Self {
+            sink_reviewed: true,
+            mark_on_leave: true,
+            open: None,
+            exclude: ExcludePatterns::default(),
+        }
+    }
+}
+
+impl SashikiApp {
+    /// Store the content hashes of the changed `files` of `worktree` and drop
+    /// the marks of files that are no longer changed
+    pub(crate) fn store_content_hashes(
+        &mut self,
+        worktree: &Path,
+        files: &[ChangedFile],
+        hashes: HashMap<PathBuf, String>,
+    ) {
+        self.repo
+            .file_cache
+            .store_content_hashes(worktree, hashes, Instant::now());
+        if let Some(index) = self.repo.session_manager.find_session_by_path(worktree) {
+            self.repo.session_manager.sessions()[index]
+                .state()
+                .update(|state| {
+                    state
+                        .reviewed
+                        .retain_changed(files.iter().map(|f| f.path.as_path()))
+                });
+        }
+    }
+
+    /// Review state of the changed file at `path` (relative to the worktree)
+    /// in the active session
+    pub fn review_state(&self, path: &Path) -> ReviewState {
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return ReviewState::Unreviewed;
+        };
+        let hash = self
+            .repo
+            .file_cache
+            .get(session.worktree_path())
+            .and_then(|files| files.content_hashes.get(path));
+        session
+            .state()
+            .read(|state| state.reviewed.state(path, hash.map(String::as_str)))
+    }
+
+    /// Whether the changed file at `path` (relative) is left out of review
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        self.review.exclude.matches(path)
+    }
+
+    /// Changed files of the active session that are up for review
+    pub(crate) fn reviewable_files(&self) -> impl Iterator<Item = &ChangedFile> {
+        self.repo
+            .changed_files
+            .iter()
+            .filter(|f| !self.is_excluded(&f.path))
+    }
+
+    /// Reviewed changed files of the active session, and all of them but the
+    /// excluded ones
+    pub fn review_progress(&self) -> (usize, usize) {
+        let total = self.reviewable_files().count();
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return (0, total);
+        };
+        let no_hashes = HashMap::new();
+        let hashes = self
+            .repo
+            .file_cache
+            .get(session.worktree_path())
+            .map_or(&no_hashes, |files| &files.content_hashes);
+        let reviewed = session.state().read(|state| {
+            review::reviewed_count(
+                &state.reviewed,
+                self.reviewable_files().map(|f| f.path.as_path()),
+                hashes,
+            )
+        });
+        (reviewed, total)
+    }
+
+    /// Mark the changed file at `path` (relative) reviewed, or unmark it when
+    /// it is. A file changed since its review is marked at its new content.
+    pub fn toggle_reviewed(&mut self, path: &Path) {
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return;
+        };
+        let worktree = session.worktree_path().to_path_buf();
+        if self.review_state(path) == ReviewState::Reviewed {
+            session.state().update(|state| state.reviewed.unmark(path));
+        } else {
+            self.mark_reviewed(&worktree, path);
+        }
+        self.review_marks_changed();
+    }
+
+    /// Mark `path` in `worktree` reviewed at its content as last read (nothing
+    /// to do before its hash is known)
+    fn mark_reviewed(&self, worktree: &Path, path: &Path) {
+        let Some(hash) = self
+            .repo
+            .file_cache
+            .get(worktree)
+            .and_then(|files| files.content_hashes.get(path))
+            .cloned()
+        else {
+            return;
+        };
+        if let Some(index) = self.repo.session_manager.find_session_by_path(worktree) {
+            self.repo.session_manager.sessions()[index]
+                .state()
+                .update(|state| state.reviewed.mark(path, hash));
+        }
+    }
+
+    /// Reorder the Changes list after marks changed, if reviewed files sink
+    fn review_marks_changed(&mut self) {
+        if self.review.sink_reviewed && self.file_list_mode == FileListMode::Changes {
+            self.build_file_tree();
+        }
+    }
+
+    /// Use new exclusion patterns, regrouping the Changes list when they
+    /// differ
+    pub(crate) fn set_review_exclude(&mut self, exclude: ExcludePatterns) {
+        if self.review.exclude == exclude {
+            return;
+        }
+        self.review.exclude = exclude;
+        if self.file_list_mode == FileListMode::Changes {
+            self.build_file_tree();
+        }
+    }
+
+    pub fn toggle_sink_reviewed(&mut self) {
+        self.review.sink_reviewed = !self.review.sink_reviewed;
+        if self.file_list_mode == FileListMode::Changes {
+            self.build_file_tree();
+        }
+    }
+
+    /// Note that the diff of `path` (relative) is being opened. The changed
+    /// file open until now is marked reviewed unless it is the same one or
+    /// `sashiki.review.markOnLeave` is off.
+    pub(crate) fn review_file_opened(&mut self, path: &Path) {
+        let worktree = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf());
+        let opened = worktree
+            .filter(|_| self.repo.changed_files.iter().any(|f| f.path == path))
+            .map(|worktree| (worktree, path.to_path_buf()));
+        if self.review.open == opened {
+            return;
+        }
+        self.leave_reviewed_file();
+        self.review.open = opened;
+    }
+
+    /// The file view no longer shows the diff of the file noted open: mark
+    /// it reviewed (see `ReviewView::mark_on_leave`)
+    pub(crate) fn leave_reviewed_file(&mut self) {
+        let Some((worktree, path)) = self.review.open.take() else {
+            return;
+        };
+        if self.review.mark_on_leave {
+            self.mark_reviewed(&worktree, &path);
+            self.review_marks_changed();
+        }
+    }
+
+    /// r: mark or unmark the highlighted changed file
+    pub fn on_sidebar_toggle_reviewed(
+        &mut self,
+        _: &SidebarToggleReviewed,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.file_list_mode != FileListMode::Changes {
+            return;
+        }
+        self.sync_list_nav();
+        if let Some(NavRow {
+            target:
+                NavTarget::Entry {
+                    path,
+                    is_dir: false,
+                },
+            change_type: Some(_),
+            ..
+        }) = self.repo.list_nav.selected_row().cloned()
+        {
+            self.toggle_reviewed(&path);
+            cx.notify();
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Review comments from the diff view gutter, shown under their lines, and
+//! the review they make up, copied, saved or sent to the terminal (see
+//! `crate::review_comments`)
+
+use super::SashikiApp;
+use super::actions::{CopyReview, ReviewComments, SaveReview, SendReviewToTerminal};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::review_comments::{self, ReviewComment};
+use gpui::{ClipboardItem, Context, Focusable, Window};
+use std::path::{Path, PathBuf};
+
+impl SashikiApp {
+    /// Show the active session's comments on the file at `full_path` in the
+    /// file view
+    pub(crate) fn sync_file_view_comments(&mut self, full_path: &Path, cx: &mut Context<Self>) {
+        let Some(session) = self.repo.session_manager.active_session() else {
+            return;
+        };
+        let comments = full_path
+            .strip_prefix(session.worktree_path())
+            .map(|relative| {
+                session
+                    .state()
+                    .read(|state| state.comments.for_file(relative))
+            })
+            .unwrap_or_default();
+        let full_path = full_path.to_path_buf();
+        self.file_view.update(cx, |view, cx| {
+            if view.set_comments(full_path, comments) {
+                cx.notify();
+            }
+        });
+    }
+
+    /// Show the comments again after they changed
+    fn comments_changed(&mut self, cx: &mut Context<Self>) {
+        if let Some(path) = self.file_view.read(cx).file_path().map(Path::to_path_buf) {
+            self.sync_file_view_comments(&path, cx);
+        }
+        cx.notify();
+    }
+
+    /// Number of review comments in the active session
+    pub fn review_comment_count(&self) -> usize {
+        self.repo
+            .session_manager
+            .active_session()
+            .map_or(0, |session| {
+                session.state().read(|state| state.comments.len())
+            })
+    }
+
+    /// Ask for a comment on `line` of the file at `full_path`, whose text is
+    /// `line_text`
+    pub fn open_comment_dialog(
+        &mut self,
+        full_path: &Path,
+        line: usize,
+        line_text: String,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(path) = self
+            .active_worktree_path()
+            .and_then(|worktree| full_path.strip_prefix(worktree).ok().map(Path::to_path_buf))
+        else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::ReviewComment {
+            path,
+            line,
+            line_text: Some(line_text),
+            input: String::new(),
+            editing: None,
+        };
+        self.focus_comment_dialog = true;
+        cx.notify();
+    }
+
+    /// Edit the comment at `index` of the active session; `line_text` is
+    /// what its line reads now
+    pub fn edit_comment(
+        &mut self,
+        index: usize,
+        line_text: Option<String>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(comment) = self
+            .repo
+            .session_manager
+            .active_session()
+            .and_then(|session| {
+                session
+                    .state()
+                    .read(|state| state.comments.get(index).cloned())
+            })
+        else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::ReviewComment {
+            path: PathBuf::from(&comment.path),
+            line: comment.line,
+            line_text,
+            input: comment.text,
+            editing: Some(index),
+        };
+        self.focus_comment_dialog = true;
+        cx.notify();
+    }
+
+    pub fn delete_comment(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(session) = self.repo.session_manager.active_session() {
+            session.state().update(|state| state.comments.remove(index));
+        }
+        self.comments_changed(cx);
+    }
+
+    pub fn edit_comment_input(&mut self, edit: impl FnOnce(&mut String), cx: &mut Context<Self>) {
+        if let ActiveDialog::ReviewComment { input, .. } = &mut self.active_dialog {
+            edit(input);
+            cx.notify();
+        }
+    }
+
+    pub fn close_comment_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        let focus = self.file_view.read(cx).focus_handle(cx);
+        window.focus(&focus, cx);
+        cx.notify();
+    }
+
+    /// Save the typed comment; an edited comment is anchored at its line as
+    /// it reads now
+    pub fn submit_comment_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::ReviewComment {
+            path,
+            line,
+            line_text,
+            input,
+            editing,
+        } = &self.active_dialog
+        else {
+            return;
+        };
+        let text = input.trim_end().to_string();
+        if text.trim().is_empty() {
+            return;
+        }
+        if let Some(session) = self.repo.session_manager.active_session() {
+            session.state().update(|state| match editing {
+                Some(index) => state.comments.edit(*index, line_text.as_deref(), text),
+                None => {
+                    let line_text = line_text.as_deref().unwrap_or_default();
+                    state
+                        .comments
+                        .add(ReviewComment::new(path, *line, line_text, text));
+                    true
+                }
+            });
+        }
+        self.close_comment_dialog(window, cx);
+        self.comments_changed(cx);
+    }
+
+    /// The active session's comments as markdown, flagging those whose line
+    /// changed since (None when there are none)
+    fn review_markdown(&self) -> Option<String> {
+        let session = self.repo.session_manager.active_session()?;
+        let worktree = session.worktree_path();
+        session.state().read(|state| {
+            (!state.comments.is_empty()).then(|| {
+                review_comments::to_markdown(&state.comments, |path| {
+                    std::fs::read_to_string(worktree.join(path)).ok()
+                })
+            })
+        })
+    }
+
+    pub fn on_review_comments(
+        &mut self,
+        _: &ReviewComments,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_review_dialog(window, cx);
+    }
+
+    /// Show the review made of the active session's comments
+    pub fn open_review_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(markdown) = self.review_markdown() else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::Review {
+            markdown,
+            count: self.review_comment_count(),
+        };
+        window.focus(&self.review_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_review_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn on_copy_review(&mut self, _: &CopyReview, _: &mut Window, cx: &mut Context<Self>) {
+        self.copy_review(cx);
+    }
+
+    pub fn copy_review(&mut self, cx: &mut Context<Self>) {
+        if let Some(markdown) = self.review_markdown() {
+            cx.write_to_clipboard(ClipboardItem::new_string(markdown));
+        }
+    }
+
+    pub fn on_send_review_to_terminal(
+        &mut self,
+        _: &SendReviewToTerminal,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.send_review_to_terminal(window, cx);
+    }
+
+    /// Paste the review into the active terminal, the way a paste is typed
+    pub fn send_review_to_terminal(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(markdown) = self.review_markdown() else {
+            return;
+        };
+        self.paste_to_terminal(&markdown, cx);
+        self.close_review_dialog(window, cx);
+    }
+
+    pub fn on_save_review(&mut self, _: &SaveReview, _: &mut Window, cx: &mut Context<Self>) {
+        self.save_review(cx);
+    }
+
+    /// Ask for a file (review.md in the worktree by default) and write the
+    /// review to it
+    pub fn save_review(&mut self, cx: &mut Context<Self>) {
+        let (Some(markdown), Some(worktree)) =
+            (self.review_markdown(), self.active_worktree_path())
+        else {
+            return;
+        };
+        let path_receiver = cx.prompt_for_new_path(&worktree, Some("review.md"));
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = path_receiver.await else {
+                return;
+            };
+            let result =
+                smol::unblock(move || std::fs::write(&path, markdown).map(|()| path)).await;
+            match result {
+                Ok(path) => app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::App,
+                    format!("Saved the review to {}", path.display()),
+                )),
+                Err(e) => {
+                    let _ = this.update(cx, |app, cx| {
+                        app.active_dialog =
+                            ActiveDialog::error(format!("Failed to save the review: {}", e));
+                        cx.notify();
+                    });
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Delete all of the active session's comments
+    pub fn clear_review(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(session) = self.repo.session_manager.active_session() {
+            session.state().update(|state| state.comments.clear());
+        }
+        self.close_review_dialog(window, cx);
+        self.comments_changed(cx);
+    }
+}
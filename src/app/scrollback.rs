@@ -0,0 +1,47 @@
+//! Saving the terminals' scrollback when the app quits (see
+//! `crate::scrollback`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::scrollback;
+use gpui::App;
+
+impl SashikiApp {
+    /// Save the scrollback of every terminal that keeps it, in all open
+    /// repositories. The lines are read here; the files are written on a
+    /// thread waited on for at most `SAVE_TIMEOUT`.
+    pub(crate) fn save_scrollback(&self, cx: &App) {
+        let saves: Vec<_> = self
+            .all_session_managers()
+            .flat_map(|manager| manager.sessions())
+            .flat_map(|session| {
+                (0..session.terminal_count()).filter_map(|index| session.get_terminal(index))
+            })
+            .filter_map(|terminal| terminal.read(cx).scrollback_to_save())
+            .collect();
+        if saves.is_empty() {
+            return;
+        }
+
+        let (done, finished) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for (path, lines) in saves {
+                if let Err(e) = scrollback::save(&path, &lines) {
+                    app_log::record(LogEntry::new(
+                        LogLevel::Warn,
+                        LogSource::Terminal,
+                        format!("Failed to save the scrollback in {}: {}", path.display(), e),
+                    ));
+                }
+            }
+            let _ = done.send(());
+        });
+        if finished.recv_timeout(scrollback::SAVE_TIMEOUT).is_err() {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::Terminal,
+                "Quit before all of the scrollback was saved".to_string(),
+            ));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+//! Workspace search dialog: runs searches on a worker as the query changes
+//! and opens or inserts the hits (see `crate::search`)
+
+use super::SashikiApp;
+use super::actions::SearchWorkspace;
+use crate::dialog::ActiveDialog;
+use crate::diff_job::CancelToken;
+use crate::search::{self, ROW_HEIGHT, SearchRow};
+use crate::ui::file_view::format_line_reference;
+use crate::ui::virtual_rows;
+use gpui::{Context, Focusable, Window, point, px};
+use std::time::Duration;
+
+/// Typing pause before a new query is searched
+const QUERY_DELAY: Duration = Duration::from_millis(150);
+
+impl SashikiApp {
+    pub fn on_search_workspace(
+        &mut self,
+        _: &SearchWorkspace,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if matches!(self.active_dialog, ActiveDialog::Search) {
+            self.close_search_dialog(window, cx);
+            return;
+        }
+        let Some(root) = self
+            .repo
+            .session_manager
+            .active_session()
+            .map(|s| s.worktree_path().to_path_buf())
+        else {
+            return;
+        };
+        self.open_menu = None;
+        self.active_dialog = ActiveDialog::Search;
+        // Hits of another worktree do not apply here
+        if self.search.root.as_ref() != Some(&root) {
+            self.search.root = Some(root);
+            self.restart_search(cx);
+        }
+        window.focus(&self.search_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_search_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.search.running {
+            self.search_cancel.cancel();
+            self.search_task = None;
+            self.search.running = false;
+            // An unfinished search is not shown again
+            self.search.root = None;
+        }
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn set_search_query(&mut self, query: String, cx: &mut Context<Self>) {
+        if query != self.search.query {
+            self.search.query = query;
+            self.restart_search(cx);
+        }
+    }
+
+    /// Switch between literal and regex matching
+    pub fn toggle_search_regex(&mut self, cx: &mut Context<Self>) {
+        self.search.options.regex = !self.search.options.regex;
+        self.restart_search(cx);
+    }
+
+    pub fn toggle_search_case(&mut self, cx: &mut Context<Self>) {
+        self.search.options.case_sensitive = !self.search.options.case_sensitive;
+        self.restart_search(cx);
+    }
+
+    /// Cancel the search in flight and search the worktree for the current
+    /// query once typing pauses, streaming hits into the dialog
+    fn restart_search(&mut self, cx: &mut Context<Self>) {
+        self.search_cancel.cancel();
+        self.search_task = None;
+        self.search.start();
+        self.search_scroll.set_offset(point(px(0.), px(0.)));
+        cx.notify();
+
+        if !self.search.running {
+            return;
+        }
+        let Some(repo) = self.worktree_repo().cloned() else {
+            self.search.running = false;
+            return;
+        };
+        let cancel = CancelToken::default();
+        self.search_cancel = cancel.clone();
+        let query = self.search.query.clone();
+        let options = self.search.options;
+        let limit = self.search.limit;
+
+        self.search_task = Some(cx.spawn(async move |entity, cx| {
+            smol::Timer::after(QUERY_DELAY).await;
+            let (hits_tx, hits_rx) = smol::channel::unbounded();
+            let worker_cancel = cancel.clone();
+            let worker = smol::unblock(move || {
+                search::search(&repo, &query, options, limit, &worker_cancel, &mut |hits| {
+                    let _ = hits_tx.send_blocking(hits);
+                })
+            });
+            while let Ok(hits) = hits_rx.recv().await {
+                let updated = entity.update(cx, |app, cx| {
+                    app.search.push_hits(hits);
+                    cx.notify();
+                });
+                if updated.is_err() {
+                    cancel.cancel();
+                    return;
+                }
+            }
+            let result = worker.await;
+            let _ = entity.update(cx, |app, cx| {
+                app.search_task = None;
+                app.search.finish(result);
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Select the next (or previous) hit
+    pub fn move_search_selection(&mut self, delta: isize, cx: &mut Context<Self>) {
+        self.search.move_selection(delta);
+        self.reveal_selected_search_hit();
+        cx.notify();
+    }
+
+    pub fn select_search_hit(&mut self, index: usize, cx: &mut Context<Self>) {
+        self.search.selected = index;
+        cx.notify();
+    }
+
+    fn reveal_selected_search_hit(&self) {
+        let Some(row) = self
+            .search
+            .rows()
+            .iter()
+            .position(|row| *row == SearchRow::Hit(self.search.selected))
+        else {
+            return;
+        };
+        let scroll_top = -f32::from(self.search_scroll.offset().y);
+        let viewport_height = f32::from(self.search_scroll.bounds().size.height);
+        let top = virtual_rows::reveal(row, ROW_HEIGHT, scroll_top, viewport_height);
+        if top != scroll_top {
+            self.search_scroll.set_offset(point(px(0.), px(-top)));
+        }
+    }
+
+    /// Open the selected hit in the file view, scrolled to its line
+    pub fn open_search_hit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(hit) = self.search.selected_hit().cloned() else {
+            return;
+        };
+        self.close_search_dialog(window, cx);
+        let change_type = self.changed_file(&hit.path).map(|f| f.change_type);
+        self.on_file_selected(hit.path, change_type, cx);
+        self.file_view
+            .update(cx, |view, cx| view.reveal_line(hit.line, cx));
+    }
+
+    /// Type `path:line` of the selected hit into the active terminal
+    pub fn insert_search_hit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(hit) = self.search.selected_hit().cloned() else {
+            return;
+        };
+        self.close_search_dialog(window, cx);
+        let path = hit.path.to_string_lossy();
+        self.send_to_terminal(&format_line_reference(&path, &(hit.line..=hit.line)), cx);
+    }
+}
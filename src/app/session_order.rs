@@ -0,0 +1,62 @@
+//! Sidebar order of sessions: drag and drop, Move Up/Down and Ctrl+1..9
+//! (see `SessionManager::move_session`)
+
+use super::SashikiApp;
+use super::actions::{MoveSessionDown, MoveSessionUp};
+use gpui::{Context, Window};
+
+impl SashikiApp {
+    pub fn on_move_session_up(
+        &mut self,
+        _: &MoveSessionUp,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.move_active_session(true, cx);
+    }
+
+    pub fn on_move_session_down(
+        &mut self,
+        _: &MoveSessionDown,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.move_active_session(false, cx);
+    }
+
+    fn move_active_session(&mut self, up: bool, cx: &mut Context<Self>) {
+        let index = self.repo.session_manager.active_index();
+        if self.repo.session_manager.move_session_by_one(index, up) {
+            self.save_session_order(cx);
+        }
+    }
+
+    /// Move the dragged session to where `target` is shown
+    pub fn drop_session_on(&mut self, dragged: usize, target: usize, cx: &mut Context<Self>) {
+        let Some(position) = self.repo.session_manager.display_position(target) else {
+            return;
+        };
+        if self.repo.session_manager.move_session(dragged, position) {
+            self.save_session_order(cx);
+        }
+    }
+
+    /// Switch to the session shown at `position` in the sidebar (Ctrl+1..9)
+    pub fn switch_to_position(
+        &mut self,
+        position: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.repo.session_manager.session_at(position) {
+            self.on_session_selected(index, window, cx);
+        }
+    }
+
+    fn save_session_order(&mut self, cx: &mut Context<Self>) {
+        if let Some(repo) = &self.repo.git_repo {
+            self.repo.session_manager.save_order(repo.git_dir());
+        }
+        cx.notify();
+    }
+}
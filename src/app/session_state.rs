@@ -0,0 +1,39 @@
+//! Writing the sessions' state files (see `crate::session_state`) off the
+//! UI thread: changes are collected every `SAVE_DELAY` and when the app
+//! quits.
+
+use super::SashikiApp;
+use crate::session_state::{PendingSave, SAVE_DELAY};
+use gpui::Context;
+
+impl SashikiApp {
+    /// Write the changed states of all open repositories every
+    /// `SAVE_DELAY` for as long as the app exists
+    pub(crate) fn spawn_session_state_saver(cx: &mut Context<Self>) {
+        cx.spawn(async move |entity, cx| {
+            loop {
+                smol::Timer::after(SAVE_DELAY).await;
+                let Ok(saves) = entity.update(cx, |app, _| app.take_pending_state_saves()) else {
+                    break;
+                };
+                if !saves.is_empty() {
+                    smol::unblock(move || saves.into_iter().for_each(PendingSave::write)).await;
+                }
+            }
+        })
+        .detach();
+    }
+
+    /// Write the changed states now (the app is quitting)
+    pub(crate) fn save_session_states(&self) {
+        self.take_pending_state_saves()
+            .into_iter()
+            .for_each(PendingSave::write);
+    }
+
+    fn take_pending_state_saves(&self) -> Vec<PendingSave> {
+        self.all_session_managers()
+            .flat_map(|manager| manager.take_pending_state_saves())
+            .collect()
+    }
+}
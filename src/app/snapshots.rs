@@ -0,0 +1,182 @@
+//! Working tree snapshots of a session (see `GitRepo::create_snapshot`),
+//! and its automatic checkpoints listed the same way
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::{ActiveDialog, SnapshotKind};
+use crate::git::{GitRepo, Snapshot};
+use crate::settings::Settings;
+use gpui::Context;
+
+impl SashikiApp {
+    /// Repository of a session and the branch its snapshots are filed under
+    /// (the worktree name when HEAD is detached)
+    fn snapshot_target(&self, index: usize) -> Result<(GitRepo, String), String> {
+        let session = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .ok_or_else(|| "Session not found".to_string())?;
+        let repo = GitRepo::open(session.worktree_path())
+            .map_err(|e| format!("Failed to open worktree: {}", e))?;
+        let branch = session.branch().unwrap_or(session.name()).to_string();
+        Ok((repo, branch))
+    }
+
+    /// Snapshot a session's working tree, then prune old snapshots
+    pub fn take_snapshot(&mut self, index: usize, cx: &mut Context<Self>) {
+        let result = self.snapshot_target(index).and_then(|(repo, branch)| {
+            let refname = repo
+                .create_snapshot(&branch)
+                .map_err(|e| format!("Failed to take snapshot: {}", e))?;
+            let (settings, _) = Settings::load(self.repo.git_repo.as_ref());
+            repo.prune_snapshots(&branch, settings.snapshot_keep())
+                .map_err(|e| format!("Failed to prune old snapshots: {}", e))?;
+            Ok(refname)
+        });
+
+        match result {
+            Ok(refname) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::Git,
+                    format!("Snapshot saved as {}", refname),
+                ));
+                if let ActiveDialog::Snapshots {
+                    target_index,
+                    kind: SnapshotKind::Manual,
+                    ..
+                } = self.active_dialog
+                {
+                    self.open_snapshots_dialog(target_index, cx);
+                }
+            }
+            Err(e) => self.active_dialog = ActiveDialog::error(e),
+        }
+        cx.notify();
+    }
+
+    /// List the snapshots of a session
+    pub fn open_snapshots_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        self.open_snapshot_list(index, SnapshotKind::Manual, cx);
+    }
+
+    /// List the automatic checkpoints of a session
+    pub fn open_checkpoints_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        self.open_snapshot_list(index, SnapshotKind::Checkpoint, cx);
+    }
+
+    pub(crate) fn open_snapshot_list(
+        &mut self,
+        index: usize,
+        kind: SnapshotKind,
+        cx: &mut Context<Self>,
+    ) {
+        let result = self
+            .snapshot_target(index)
+            .and_then(|(repo, branch)| match kind {
+                SnapshotKind::Manual => repo
+                    .list_snapshots(&branch)
+                    .map_err(|e| format!("Failed to list snapshots: {}", e)),
+                SnapshotKind::Checkpoint => repo
+                    .list_checkpoints(&branch)
+                    .map_err(|e| format!("Failed to list checkpoints: {}", e)),
+            });
+
+        self.active_dialog = match result {
+            Ok(snapshots) => ActiveDialog::Snapshots {
+                target_index: index,
+                kind,
+                snapshots,
+            },
+            Err(e) => ActiveDialog::error(e),
+        };
+        cx.notify();
+    }
+
+    pub fn request_restore_snapshot(&mut self, snapshot: Snapshot, cx: &mut Context<Self>) {
+        if let ActiveDialog::Snapshots {
+            target_index, kind, ..
+        } = self.active_dialog
+        {
+            self.active_dialog = ActiveDialog::SnapshotRestoreConfirm {
+                target_index,
+                kind,
+                snapshot,
+            };
+            cx.notify();
+        }
+    }
+
+    /// Show what changed in the working tree since `snapshot`
+    pub fn show_snapshot_diff(&mut self, snapshot: Snapshot, cx: &mut Context<Self>) {
+        let ActiveDialog::Snapshots {
+            target_index, kind, ..
+        } = self.active_dialog
+        else {
+            return;
+        };
+        let result = self.snapshot_target(target_index).and_then(|(repo, _)| {
+            repo.diff_snapshot_to_worktree(&snapshot.refname)
+                .map_err(|e| format!("Failed to diff against the snapshot: {}", e))
+        });
+        self.active_dialog = match result {
+            Ok(diff) => ActiveDialog::SnapshotDiff {
+                target_index,
+                kind,
+                snapshot,
+                diff,
+            },
+            Err(e) => ActiveDialog::error(e),
+        };
+        cx.notify();
+    }
+
+    pub fn close_snapshot_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    /// Restore the confirmed snapshot into the session's index and working
+    /// tree. The working tree is snapshotted first when restoring a
+    /// checkpoint, so going back is undoable.
+    pub fn confirm_restore_snapshot(&mut self, cx: &mut Context<Self>) {
+        let (target_index, kind, snapshot) = match std::mem::take(&mut self.active_dialog) {
+            ActiveDialog::SnapshotRestoreConfirm {
+                target_index,
+                kind,
+                snapshot,
+            } => (target_index, kind, snapshot),
+            other => {
+                self.active_dialog = other;
+                return;
+            }
+        };
+        if !self.ensure_writable(target_index, "restore a snapshot", cx) {
+            return;
+        }
+
+        let result = self
+            .snapshot_target(target_index)
+            .and_then(|(repo, branch)| {
+                if kind == SnapshotKind::Checkpoint {
+                    let refname = repo
+                        .create_snapshot(&branch)
+                        .map_err(|e| format!("Failed to snapshot the working tree first: {}", e))?;
+                    app_log::record(LogEntry::new(
+                        LogLevel::Info,
+                        LogSource::Git,
+                        format!("Working tree saved as {} before restoring", refname),
+                    ));
+                }
+                repo.restore_snapshot(&snapshot.refname)
+                    .map_err(|e| format!("Failed to restore snapshot: {}", e))
+            });
+        if let Err(e) = result {
+            self.active_dialog = ActiveDialog::error(e);
+        }
+        self.refresh_file_list();
+        cx.notify();
+    }
+}
@@ -0,0 +1,199 @@
+//! Squashing or dropping commits of a session's branch
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::{ActiveDialog, SquashMode};
+use crate::git::{BranchCommit, GitRepo};
+use gpui::{Context, Focusable, Window};
+use std::collections::HashSet;
+
+impl SashikiApp {
+    /// List the commits a session's branch has on top of main.
+    ///
+    /// Refuses when the worktree has uncommitted changes to tracked files,
+    /// since rewriting history underneath them would mix them in.
+    pub fn open_squash_dialog(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let result = self.squash_candidates(index);
+        self.active_dialog = match result {
+            Ok((base, commits)) => {
+                // Prefill with the subject of the first commit on the branch
+                self.squash_message_input = commits
+                    .last()
+                    .map(|c| c.subject.clone())
+                    .unwrap_or_default();
+                window.focus(&self.squash_dialog_focus, cx);
+                ActiveDialog::Squash {
+                    target_index: index,
+                    base,
+                    commits,
+                    mode: SquashMode::default(),
+                    dropped: HashSet::new(),
+                }
+            }
+            Err(e) => ActiveDialog::error(e),
+        };
+        cx.notify();
+    }
+
+    /// Merge-base with main and the commits since, for the session at `index`
+    fn squash_candidates(&self, index: usize) -> Result<(String, Vec<BranchCommit>), String> {
+        let session = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .ok_or_else(|| "Session not found".to_string())?;
+        let main_commit = self
+            .repo
+            .git_repo
+            .as_ref()
+            .ok_or_else(|| "Git repository not available".to_string())?
+            .head_commit()
+            .map_err(|e| format!("Failed to read main HEAD: {}", e))?;
+        let repo = GitRepo::open(session.worktree_path())
+            .map_err(|e| format!("Failed to open worktree: {}", e))?;
+
+        if repo
+            .has_uncommitted_changes()
+            .map_err(|e| format!("Failed to check for local changes: {}", e))?
+        {
+            return Err(format!(
+                "{} has uncommitted changes.\nCommit or stash them before squashing.",
+                session.name()
+            ));
+        }
+
+        let base = repo
+            .merge_base("HEAD", &main_commit)
+            .map_err(|e| format!("Failed to find the merge-base with main: {}", e))?
+            .ok_or_else(|| "The branch has no common history with main".to_string())?;
+        let commits = repo
+            .commits_since(&base)
+            .map_err(|e| format!("Failed to list commits: {}", e))?;
+        Ok((base, commits))
+    }
+
+    pub fn set_squash_mode(&mut self, new_mode: SquashMode, cx: &mut Context<Self>) {
+        if let ActiveDialog::Squash { mode, .. } = &mut self.active_dialog {
+            *mode = new_mode;
+            cx.notify();
+        }
+    }
+
+    pub fn toggle_squash_drop(&mut self, hash: &str, cx: &mut Context<Self>) {
+        if let ActiveDialog::Squash { dropped, .. } = &mut self.active_dialog {
+            if !dropped.remove(hash) {
+                dropped.insert(hash.to_string());
+            }
+            cx.notify();
+        }
+    }
+
+    pub fn close_squash_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        self.squash_message_input.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Rewrite the branch as chosen in the dialog, in the background.
+    ///
+    /// A drop that conflicts is rolled back by `GitRepo::drop_commits`; the
+    /// error dialog shows git's output.
+    pub fn confirm_squash(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::Squash {
+            target_index,
+            base,
+            commits,
+            mode,
+            dropped,
+        } = &self.active_dialog
+        else {
+            return;
+        };
+        let message = self.squash_message_input.trim().to_string();
+        let to_drop: Vec<_> = commits
+            .iter()
+            .filter(|c| dropped.contains(&c.hash))
+            .cloned()
+            .collect();
+        match mode {
+            SquashMode::SquashAll if message.is_empty() || commits.is_empty() => return,
+            SquashMode::Drop if to_drop.is_empty() => return,
+            _ => {}
+        }
+        let Some(session) = self.repo.session_manager.sessions().get(*target_index) else {
+            return;
+        };
+        let worktree_path = session.worktree_path().to_path_buf();
+        let session_name = session.name().to_string();
+        let (base, mode, commit_count) = (base.clone(), *mode, commits.len());
+        let drop_count = to_drop.len();
+        let target_index = *target_index;
+
+        self.close_squash_dialog(window, cx);
+        if !self.ensure_writable(target_index, "rewrite the branch", cx) {
+            return;
+        }
+        self.active_dialog = ActiveDialog::Squashing;
+
+        let path = worktree_path.clone();
+        let op = self.repo.git_queue.push(
+            format!("Rewrite {}", session_name),
+            Some(&worktree_path),
+            move || {
+                let repo = GitRepo::open(&path).map_err(|e| e.to_string())?;
+                // The worktree may have changed since the dialog was opened
+                if repo.has_uncommitted_changes().map_err(|e| e.to_string())? {
+                    return Err("The worktree has uncommitted changes.\nCommit or stash them before squashing.".to_string());
+                }
+                match mode {
+                    SquashMode::SquashAll => repo.squash_since(&base, &message),
+                    SquashMode::Drop => repo.drop_commits(&to_drop),
+                }
+                .map_err(|e| e.to_string())
+            },
+        );
+        cx.spawn(async move |entity, cx| {
+            let result = op
+                .wait()
+                .await
+                .unwrap_or_else(|| Err("The rewrite was cancelled".to_string()));
+
+            // Ignore error: only fails if entity was dropped (app closed)
+            let _ = entity.update(cx, |app, cx| {
+                app.active_dialog = match result {
+                    Ok(()) => {
+                        let message = match mode {
+                            SquashMode::SquashAll => {
+                                format!("Squashed {} commits of {}", commit_count, session_name)
+                            }
+                            SquashMode::Drop => format!(
+                                "Dropped {} of {} commits of {}",
+                                drop_count, commit_count, session_name
+                            ),
+                        };
+                        app_log::record(LogEntry::new(LogLevel::Info, LogSource::Git, message));
+                        ActiveDialog::None
+                    }
+                    Err(e) => ActiveDialog::error(format!(
+                        "Failed to rewrite {} (the branch was left unchanged):\n{}",
+                        session_name, e
+                    )),
+                };
+                app.refresh_file_list_async(cx);
+                cx.notify();
+            });
+        })
+        .detach();
+        cx.notify();
+    }
+}
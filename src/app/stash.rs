@@ -0,0 +1,123 @@
+//! Stash operations for the active worktree
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use gpui::{Context, Focusable, Window};
+
+impl SashikiApp {
+    /// Reload the stash list (sync)
+    pub fn refresh_stashes(&mut self) {
+        self.repo.stashes = self
+            .worktree_repo()
+            .and_then(|repo| repo.stash_list().ok())
+            .unwrap_or_default();
+    }
+
+    pub fn open_stash_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::StashPush;
+        self.stash_message_input.clear();
+        self.stash_include_untracked = false;
+        window.focus(&self.stash_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_stash_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        self.stash_message_input.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn submit_stash_push(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let message = self.stash_message_input.trim().to_string();
+        let include_untracked = self.stash_include_untracked;
+        if !self.ensure_writable(
+            self.repo.session_manager.active_index(),
+            "stash changes",
+            cx,
+        ) {
+            return;
+        }
+
+        let result = match self.worktree_repo() {
+            Some(repo) => repo.stash_push(&message, include_untracked),
+            None => {
+                self.active_dialog = ActiveDialog::error("Git repository not available");
+                cx.notify();
+                return;
+            }
+        };
+
+        self.close_stash_dialog(window, cx);
+        if let Err(e) = result {
+            self.active_dialog = ActiveDialog::error(format!("Failed to stash changes: {}", e));
+        }
+        self.refresh_file_list();
+        cx.notify();
+    }
+
+    /// Apply (or pop) a stash. Conflicts are reported with git's output and
+    /// the file list is refreshed so the conflicted files show up.
+    pub fn apply_stash(&mut self, index: usize, pop: bool, cx: &mut Context<Self>) {
+        if !self.ensure_writable(
+            self.repo.session_manager.active_index(),
+            "apply a stash",
+            cx,
+        ) {
+            return;
+        }
+        let Some(repo) = self.worktree_repo() else {
+            return;
+        };
+        let result = if pop {
+            repo.stash_pop(index)
+        } else {
+            repo.stash_apply(index)
+        };
+
+        if let Err(e) = result {
+            self.active_dialog = ActiveDialog::error(format!(
+                "Failed to {} stash@{{{}}}:\n{}",
+                if pop { "pop" } else { "apply" },
+                index,
+                e
+            ));
+        }
+        self.refresh_file_list();
+        cx.notify();
+    }
+
+    pub fn open_stash_drop_dialog(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(entry) = self.repo.stashes.iter().find(|s| s.index == index) {
+            self.active_dialog = ActiveDialog::StashDropConfirm {
+                index,
+                message: entry.message.clone(),
+            };
+            cx.notify();
+        }
+    }
+
+    pub fn close_stash_drop_dialog(&mut self, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        cx.notify();
+    }
+
+    pub fn confirm_stash_drop(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::StashDropConfirm { index, .. } = self.active_dialog else {
+            return;
+        };
+        self.active_dialog = ActiveDialog::None;
+
+        if let Some(repo) = self.worktree_repo()
+            && let Err(e) = repo.stash_drop(index)
+        {
+            self.active_dialog =
+                ActiveDialog::error(format!("Failed to drop stash@{{{}}}: {}", index, e));
+        }
+        self.refresh_stashes();
+        cx.notify();
+    }
+}
@@ -0,0 +1,78 @@
+//! Session tags, which group sessions in the sidebar
+
+use super::SashikiApp;
+use crate::dialog::ActiveDialog;
+use crate::git;
+use crate::session::parse_tags;
+use gpui::{Context, Focusable, Window};
+
+impl SashikiApp {
+    pub fn open_tags_dialog(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.repo.session_manager.sessions().get(index) else {
+            return;
+        };
+        self.tags_input = session.tags().join(", ");
+        self.active_dialog = ActiveDialog::SessionTags {
+            target_index: index,
+        };
+        window.focus(&self.tags_dialog_focus, cx);
+        cx.notify();
+    }
+
+    pub fn close_tags_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_dialog = ActiveDialog::None;
+        self.tags_input.clear();
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    /// Store the edited tags in the repository config and regroup the sidebar
+    pub fn save_session_tags(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let ActiveDialog::SessionTags { target_index } = self.active_dialog else {
+            return;
+        };
+        let Some(name) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .map(|s| s.name().to_string())
+        else {
+            return;
+        };
+        let tags = parse_tags(&self.tags_input);
+
+        let key = git::session_tags_key(&name);
+        let result = match &self.repo.git_repo {
+            Some(repo) if tags.is_empty() => repo.remove_config_key(&key),
+            Some(repo) => repo.set_config_value(&key, &tags.join(",")),
+            None => {
+                self.active_dialog = ActiveDialog::error("Git repository not available");
+                cx.notify();
+                return;
+            }
+        };
+
+        self.close_tags_dialog(window, cx);
+        match result {
+            Ok(()) => self
+                .repo
+                .session_manager
+                .set_session_tags(target_index, tags),
+            Err(e) => {
+                self.active_dialog = ActiveDialog::error(format!("Failed to save tags: {}", e));
+            }
+        }
+        cx.notify();
+    }
+
+    pub fn toggle_tag_group(&mut self, tag: Option<String>, cx: &mut Context<Self>) {
+        if !self.repo.collapsed_tag_groups.remove(&tag) {
+            self.repo.collapsed_tag_groups.insert(tag);
+        }
+        cx.notify();
+    }
+}
@@ -0,0 +1,178 @@
+//! Session transcripts: recording switch and the transcript dialog (see
+//! `crate::transcript`)
+
+use super::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::dialog::ActiveDialog;
+use crate::git;
+use crate::transcript::{self, ROW_HEIGHT};
+use crate::ui::virtual_rows;
+use gpui::{ClipboardItem, Context, Focusable, Window, point, px};
+
+impl SashikiApp {
+    /// Turn output recording of the session at `index` on or off and store
+    /// the choice in the repository config
+    pub fn toggle_session_recording(&mut self, index: usize, cx: &mut Context<Self>) {
+        let (Some(repo), Some(session)) = (
+            self.repo.git_repo.as_ref(),
+            self.repo.session_manager.sessions().get(index),
+        ) else {
+            return;
+        };
+        let enabled = !session.is_recording();
+        let key = git::session_transcript_key(session.name());
+        let result = if enabled {
+            repo.set_config_value(&key, "true")
+        } else {
+            repo.remove_config_key(&key)
+        };
+
+        match result {
+            Ok(()) => {
+                session.set_recording(enabled);
+                app_log::record(LogEntry::new(
+                    LogLevel::Info,
+                    LogSource::Terminal,
+                    format!(
+                        "{} recording the transcript of {}",
+                        if enabled { "Started" } else { "Stopped" },
+                        session.name()
+                    ),
+                ));
+            }
+            Err(e) => {
+                self.active_dialog =
+                    ActiveDialog::error(format!("Failed to save the recording setting: {}", e));
+            }
+        }
+        cx.notify();
+    }
+
+    pub fn open_transcript_dialog(
+        &mut self,
+        index: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.repo.session_manager.sessions().get(index).is_none() {
+            return;
+        }
+        self.transcript = Default::default();
+        self.active_dialog = ActiveDialog::Transcript {
+            target_index: index,
+        };
+        self.reload_transcript(cx);
+        window.focus(&self.transcript_dialog_focus, cx);
+        cx.notify();
+    }
+
+    /// Read the session's transcript files in the background, keeping the
+    /// search and scrolling to the end
+    pub fn reload_transcript(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::Transcript { target_index } = self.active_dialog else {
+            return;
+        };
+        let Some(dir) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .and_then(|s| s.transcript_dir())
+        else {
+            return;
+        };
+
+        self.transcript.loading = true;
+        self.transcript_load = Some(cx.spawn(async move |entity, cx| {
+            let result = smol::unblock(move || transcript::read_transcript(&dir)).await;
+            let _ = entity.update(cx, |app, cx| {
+                app.transcript_load = None;
+                match result {
+                    Ok(lines) => {
+                        app.transcript.set_lines(lines);
+                        let bottom = app.transcript.lines.len() as f32 * ROW_HEIGHT;
+                        app.transcript_scroll.set_offset(point(px(0.), px(-bottom)));
+                    }
+                    Err(e) => {
+                        app.transcript.loading = false;
+                        app.active_dialog =
+                            ActiveDialog::error(format!("Failed to read the transcript: {}", e));
+                    }
+                }
+                cx.notify();
+            });
+        }));
+        cx.notify();
+    }
+
+    pub fn close_transcript_dialog(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.transcript_load = None;
+        self.transcript = Default::default();
+        self.active_dialog = ActiveDialog::None;
+        if let Some(terminal) = self.active_terminal() {
+            let focus = terminal.read(cx).focus_handle(cx);
+            window.focus(&focus, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn set_transcript_query(&mut self, query: String, cx: &mut Context<Self>) {
+        self.transcript.set_query(query);
+        if let Some(line) = self.transcript.current_line() {
+            self.reveal_transcript_line(line);
+        }
+        cx.notify();
+    }
+
+    /// Jump to the next (or previous) search match
+    pub fn step_transcript_match(&mut self, forward: bool, cx: &mut Context<Self>) {
+        if let Some(line) = self.transcript.step(forward) {
+            self.reveal_transcript_line(line);
+            cx.notify();
+        }
+    }
+
+    fn reveal_transcript_line(&self, line: usize) {
+        let scroll_top = -f32::from(self.transcript_scroll.offset().y);
+        let viewport_height = f32::from(self.transcript_scroll.bounds().size.height);
+        let top = virtual_rows::reveal(line, ROW_HEIGHT, scroll_top, viewport_height);
+        if top != scroll_top {
+            self.transcript_scroll.set_offset(point(px(0.), px(-top)));
+        }
+    }
+
+    /// Copy the lines currently shown in the dialog
+    pub fn copy_visible_transcript(&mut self, cx: &mut Context<Self>) {
+        let scroll_top = -f32::from(self.transcript_scroll.offset().y);
+        let viewport_height = f32::from(self.transcript_scroll.bounds().size.height);
+        let range = transcript::visible_lines(
+            self.transcript.lines.len(),
+            ROW_HEIGHT,
+            scroll_top,
+            viewport_height,
+        );
+        let text = self.transcript.lines[range].join("\n");
+        cx.write_to_clipboard(ClipboardItem::new_string(text));
+    }
+
+    /// Show the session's transcript directory in the file manager
+    pub fn open_transcript_folder(&mut self, cx: &mut Context<Self>) {
+        let ActiveDialog::Transcript { target_index } = self.active_dialog else {
+            return;
+        };
+        let Some(dir) = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .and_then(|s| s.transcript_dir())
+        else {
+            return;
+        };
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|()| open::that(&dir)) {
+            self.active_dialog =
+                ActiveDialog::error(format!("Failed to open {}: {}", dir.display(), e));
+            cx.notify();
+        }
+    }
+}
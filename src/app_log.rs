@@ -0,0 +1,357 @@
+//! In-app log of operational events
+//!
+//! Failed git and template commands and errors shown to the user are kept in
+//! a bounded, process-wide buffer so they can be reviewed in the log pane after
+//! the error dialog is gone. Only operational events are recorded, never
+//! terminal output.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Oldest entries are dropped beyond this many
+pub const MAX_LOG_ENTRIES: usize = 1000;
+
+static APP_LOG: Mutex<AppLog> = Mutex::new(AppLog::new(MAX_LOG_ENTRIES));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 3] = [LogLevel::Info, LogLevel::Warn, LogLevel::Error];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// Where an entry comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    Git,
+    Terminal,
+    Template,
+    /// User commands run on app events (see `hooks`)
+    Hook,
+    App,
+}
+
+impl LogSource {
+    pub const ALL: [LogSource; 5] = [
+        LogSource::Git,
+        LogSource::Terminal,
+        LogSource::Template,
+        LogSource::Hook,
+        LogSource::App,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LogSource::Git => "git",
+            LogSource::Terminal => "terminal",
+            LogSource::Template => "template",
+            LogSource::Hook => "hook",
+            LogSource::App => "app",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub timestamp: SystemTime,
+    pub level: LogLevel,
+    pub source: LogSource,
+    pub message: String,
+    /// Command line that failed
+    pub command: Option<String>,
+    pub stderr: Option<String>,
+}
+
+impl LogEntry {
+    pub fn new(level: LogLevel, source: LogSource, message: impl Into<String>) -> Self {
+        Self {
+            timestamp: SystemTime::now(),
+            level,
+            source,
+            message: message.into(),
+            command: None,
+            stderr: None,
+        }
+    }
+
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    /// Attach stderr output (ignored when blank)
+    pub fn with_stderr(mut self, stderr: &str) -> Self {
+        let stderr = stderr.trim();
+        if !stderr.is_empty() {
+            self.stderr = Some(stderr.to_string());
+        }
+        self
+    }
+
+    /// Time of day as `HH:MM:SS` (UTC)
+    pub fn time_label(&self) -> String {
+        let secs = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        format!(
+            "{:02}:{:02}:{:02}",
+            (secs / 3600) % 24,
+            (secs / 60) % 60,
+            secs % 60
+        )
+    }
+
+    /// Plain text of the whole entry, for the clipboard
+    pub fn to_clipboard_text(&self) -> String {
+        let mut text = format!(
+            "{} UTC [{}] {}: {}",
+            self.time_label(),
+            self.level.label(),
+            self.source.label(),
+            self.message
+        );
+        if let Some(command) = &self.command {
+            text.push_str("\ncommand: ");
+            text.push_str(command);
+        }
+        if let Some(stderr) = &self.stderr {
+            text.push_str("\nstderr:\n");
+            text.push_str(stderr);
+        }
+        text
+    }
+}
+
+/// Which entries the log pane shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    pub min_level: LogLevel,
+    pub hidden_sources: Vec<LogSource>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            min_level: LogLevel::Info,
+            hidden_sources: Vec::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level >= self.min_level && self.shows_source(entry.source)
+    }
+
+    pub fn shows_source(&self, source: LogSource) -> bool {
+        !self.hidden_sources.contains(&source)
+    }
+
+    pub fn toggle_source(&mut self, source: LogSource) {
+        if let Some(pos) = self.hidden_sources.iter().position(|s| *s == source) {
+            self.hidden_sources.remove(pos);
+        } else {
+            self.hidden_sources.push(source);
+        }
+    }
+}
+
+/// Ring buffer of log entries, oldest first
+#[derive(Debug)]
+pub struct AppLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl AppLog {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Entries matching `filter`, newest first
+    pub fn filtered(&self, filter: &LogFilter) -> Vec<LogEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Add an entry to the process-wide log
+pub fn record(entry: LogEntry) {
+    if let Ok(mut log) = APP_LOG.lock() {
+        log.push(entry);
+    }
+}
+
+/// Entries of the process-wide log matching `filter`, newest first
+pub fn entries(filter: &LogFilter) -> Vec<LogEntry> {
+    APP_LOG
+        .lock()
+        .map(|log| log.filtered(filter))
+        .unwrap_or_default()
+}
+
+/// Number of entries in the process-wide log
+pub fn len() -> usize {
+    APP_LOG.lock().map(|log| log.len()).unwrap_or(0)
+}
+
+pub fn clear() {
+    if let Ok(mut log) = APP_LOG.lock() {
+        log.clear();
+    }
+}
+
+/// Record a failed external command with its full command line and duration
+pub fn record_command_failure(
+    source: LogSource,
+    program: &str,
+    args: &[&str],
+    workdir: &Path,
+    elapsed: Duration,
+    error: &str,
+    stderr: &str,
+) {
+    let name = match args.iter().find(|arg| !arg.starts_with('-')) {
+        Some(subcommand) => format!("{} {}", program, subcommand),
+        None => program.to_string(),
+    };
+    let message = format!(
+        "{} failed after {} ms in {}: {}",
+        name,
+        elapsed.as_millis(),
+        workdir.display(),
+        error
+    );
+    let command = format!("{} {}", program, shell_words(args));
+    record(
+        LogEntry::new(LogLevel::Error, source, message)
+            .with_command(command)
+            .with_stderr(stderr),
+    );
+}
+
+/// Join arguments into one line, quoting those that need it
+fn shell_words(args: &[&str]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=@+,%^~".contains(c))
+            {
+                arg.to_string()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut log = AppLog::new(3);
+        for i in 0..5 {
+            log.push(LogEntry::new(
+                LogLevel::Info,
+                LogSource::App,
+                format!("{}", i),
+            ));
+        }
+        assert_eq!(log.len(), 3);
+        let messages: Vec<_> = log
+            .filtered(&LogFilter::default())
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, vec!["4", "3", "2"]);
+    }
+
+    #[test]
+    fn test_filter_by_level_and_source() {
+        let mut log = AppLog::new(10);
+        log.push(LogEntry::new(LogLevel::Info, LogSource::Terminal, "exit"));
+        log.push(LogEntry::new(LogLevel::Error, LogSource::Git, "fetch"));
+        log.push(LogEntry::new(LogLevel::Warn, LogSource::Template, "copy"));
+        log.push(LogEntry::new(LogLevel::Error, LogSource::App, "dialog"));
+
+        let mut filter = LogFilter {
+            min_level: LogLevel::Warn,
+            ..LogFilter::default()
+        };
+        filter.toggle_source(LogSource::App);
+        let messages: Vec<_> = log
+            .filtered(&filter)
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages, vec!["copy", "fetch"]);
+
+        filter.toggle_source(LogSource::App);
+        assert!(filter.shows_source(LogSource::App));
+        assert_eq!(log.filtered(&filter).len(), 3);
+    }
+
+    #[test]
+    fn test_clipboard_text_includes_command_and_stderr() {
+        let entry = LogEntry::new(LogLevel::Error, LogSource::Git, "failed")
+            .with_command("git fetch origin")
+            .with_stderr("fatal: no remote\n");
+        let text = entry.to_clipboard_text();
+        assert!(text.contains("[ERROR] git: failed"));
+        assert!(text.ends_with("command: git fetch origin\nstderr:\nfatal: no remote"));
+
+        let entry = LogEntry::new(LogLevel::Info, LogSource::App, "ok").with_stderr("  ");
+        assert_eq!(entry.stderr, None);
+    }
+
+    #[test]
+    fn test_shell_words_quotes_when_needed() {
+        assert_eq!(
+            shell_words(&["commit", "-m", "fix it", "it's", ""]),
+            "commit -m 'fix it' 'it'\\''s' ''"
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Attention tracking for sessions in the background
+//!
+//! A session that is not active asks for attention when a terminal rings the
+//! bell, its shell exits with a nonzero code, or output arrives after a
+//! period of silence (e.g. an agent printing a prompt after working quietly).
+//! The sidebar shows a badge until the session is activated. Each reason can
+//! also raise an OS notification, configured with git config
+//! `sashiki.notifications.{output,bell,exit}` (default: bell and exit only).
+//! The bell only counts when its notify response is on (see `bell`).
+
+use crate::activity::SessionEvents;
+use crate::bell::BellSettings;
+use crate::git;
+use crate::settings::Settings;
+use std::cell::{Cell, RefCell};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Output after this much silence counts as the session wanting attention
+pub const SILENCE_BEFORE_OUTPUT: Duration = Duration::from_secs(5);
+
+/// Why a session wants attention, in increasing severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AttentionReason {
+    /// Output after a period of silence
+    Output,
+    Bell,
+    /// The shell exited with this nonzero code
+    Exit(i32),
+}
+
+impl AttentionReason {
+    pub fn describe(&self) -> String {
+        match self {
+            AttentionReason::Output => "New output".to_string(),
+            AttentionReason::Bell => "Bell".to_string(),
+            AttentionReason::Exit(code) => format!("Exited with code {}", code),
+        }
+    }
+}
+
+/// Which reasons also raise an OS notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationSettings {
+    pub output: bool,
+    pub bell: bool,
+    pub exit: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            output: false,
+            bell: true,
+            exit: true,
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let default = Self::default();
+        Self {
+            output: settings
+                .get_bool(git::CONFIG_NOTIFY_OUTPUT)
+                .unwrap_or(default.output),
+            bell: settings
+                .get_bool(git::CONFIG_NOTIFY_BELL)
+                .unwrap_or(default.bell),
+            exit: settings
+                .get_bool(git::CONFIG_NOTIFY_EXIT)
+                .unwrap_or(default.exit),
+        }
+    }
+
+    pub fn wants(&self, reason: AttentionReason) -> bool {
+        match reason {
+            AttentionReason::Output => self.output,
+            AttentionReason::Bell => self.bell,
+            AttentionReason::Exit(_) => self.exit,
+        }
+    }
+}
+
+/// Attention state shared by a session and its terminals
+#[derive(Debug, Default)]
+pub struct SessionAttention {
+    /// Label used in notifications (branch or worktree name)
+    label: RefCell<String>,
+    active: Cell<bool>,
+    reason: Cell<Option<AttentionReason>>,
+    notifications: Cell<NotificationSettings>,
+    bell: Cell<BellSettings>,
+    bell_muted: Cell<bool>,
+    /// Activity timeline the raised reasons are recorded in
+    events: Option<Rc<SessionEvents>>,
+}
+
+impl SessionAttention {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: RefCell::new(label.to_string()),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_events(mut self, events: Rc<SessionEvents>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn reason(&self) -> Option<AttentionReason> {
+        self.reason.get()
+    }
+
+    pub fn label(&self) -> String {
+        self.label.borrow().clone()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        *self.label.borrow_mut() = label.to_string();
+    }
+
+    /// Mark the session (in)active; activating it clears the attention
+    pub fn set_active(&self, active: bool) {
+        self.active.set(active);
+        if active {
+            self.reason.set(None);
+        }
+    }
+
+    pub fn set_notifications(&self, notifications: NotificationSettings) {
+        self.notifications.set(notifications);
+    }
+
+    pub fn set_bell(&self, bell: BellSettings) {
+        self.bell.set(bell);
+    }
+
+    pub fn is_bell_muted(&self) -> bool {
+        self.bell_muted.get()
+    }
+
+    pub fn set_bell_muted(&self, muted: bool) {
+        self.bell_muted.set(muted);
+    }
+
+    /// Responses the session's terminals give the bell (none while muted)
+    pub fn bell(&self) -> BellSettings {
+        if self.bell_muted.get() {
+            BellSettings::SILENT
+        } else {
+            self.bell.get()
+        }
+    }
+
+    /// Record `reason` unless the session is active.
+    ///
+    /// Returns whether the attention is new or more severe than before.
+    pub fn raise(&self, reason: AttentionReason) -> bool {
+        if self.active.get() || self.reason.get().is_some_and(|current| current >= reason) {
+            return false;
+        }
+        self.reason.set(Some(reason));
+        if let Some(events) = &self.events {
+            events.attention(reason);
+        }
+        true
+    }
+
+    /// `raise`, plus an OS notification when configured for the reason
+    pub fn raise_and_notify(&self, reason: AttentionReason) {
+        if self.raise(reason) && self.notifications.get().wants(reason) {
+            let body = format!("{}: {}", self.label.borrow(), reason.describe());
+            send_notification("Sashiki", &body);
+        }
+    }
+}
+
+/// Detects output that follows a period of silence
+#[derive(Debug, Default)]
+pub struct SilenceTracker {
+    last_output: Option<Instant>,
+}
+
+impl SilenceTracker {
+    /// Record output at `now`; true if the previous output is at least
+    /// `SILENCE_BEFORE_OUTPUT` old (the first output never counts)
+    pub fn output(&mut self, now: Instant) -> bool {
+        let after_silence = self
+            .last_output
+            .is_some_and(|last| now.duration_since(last) >= SILENCE_BEFORE_OUTPUT);
+        self.last_output = Some(now);
+        after_silence
+    }
+
+    /// When the last output arrived
+    pub fn last_output(&self) -> Option<Instant> {
+        self.last_output
+    }
+}
+
+/// Show a desktop notification, best effort (`notify-send` on Linux and BSD,
+/// `osascript` on macOS; not supported on Windows)
+pub fn send_notification(title: &str, body: &str) {
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_quote(body),
+            applescript_quote(title)
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut cmd = {
+        let mut cmd = Command::new("notify-send");
+        cmd.args(["--app-name=Sashiki", title, body]);
+        cmd
+    };
+
+    #[cfg(windows)]
+    {
+        let _ = (title, body);
+        return;
+    }
+
+    #[cfg(not(windows))]
+    if let Ok(mut child) = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        // Reap the helper without blocking the UI
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silence_tracker() {
+        let start = Instant::now();
+        let mut tracker = SilenceTracker::default();
+        assert!(!tracker.output(start));
+        assert!(!tracker.output(start + Duration::from_secs(1)));
+        assert!(tracker.output(start + Duration::from_secs(1) + SILENCE_BEFORE_OUTPUT));
+        assert!(!tracker.output(start + Duration::from_secs(2) + SILENCE_BEFORE_OUTPUT));
+    }
+
+    #[test]
+    fn test_raise_only_when_inactive_and_more_severe() {
+        let attention = SessionAttention::new("feature");
+        assert!(attention.raise(AttentionReason::Output));
+        assert!(!attention.raise(AttentionReason::Output));
+        assert!(attention.raise(AttentionReason::Bell));
+        assert!(!attention.raise(AttentionReason::Output));
+        assert!(attention.raise(AttentionReason::Exit(1)));
+        assert_eq!(attention.reason(), Some(AttentionReason::Exit(1)));
+
+        attention.set_active(true);
+        assert_eq!(attention.reason(), None);
+        assert!(!attention.raise(AttentionReason::Bell));
+        assert_eq!(attention.reason(), None);
+
+        attention.set_active(false);
+        assert!(attention.raise(AttentionReason::Bell));
+    }
+
+    #[test]
+    fn test_notification_settings_from_settings() {
+        assert_eq!(
+            NotificationSettings::from_settings(&Settings::from_layers(vec![])),
+            NotificationSettings::default()
+        );
+
+        let settings = Settings::from_layers(vec![vec![
+            (git::CONFIG_NOTIFY_OUTPUT.to_string(), "yes".to_string()),
+            (git::CONFIG_NOTIFY_BELL.to_string(), "false".to_string()),
+            (git::CONFIG_NOTIFY_EXIT.to_string(), "maybe".to_string()),
+        ]]);
+        let notifications = NotificationSettings::from_settings(&settings);
+        assert!(notifications.wants(AttentionReason::Output));
+        assert!(!notifications.wants(AttentionReason::Bell));
+        assert!(notifications.wants(AttentionReason::Exit(2)));
+    }
+
+    #[test]
+    fn test_muted_bell_is_silent() {
+        let attention = SessionAttention::new("feature");
+        assert_eq!(attention.bell(), BellSettings::default());
+        attention.set_bell_muted(true);
+        assert_eq!(attention.bell(), BellSettings::SILENT);
+        attention.set_bell_muted(false);
+        assert!(attention.bell().visual);
+    }
+}
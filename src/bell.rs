@@ -0,0 +1,167 @@
+//! Responses to the terminal bell
+//!
+//! A BEL from the program running in a terminal can flash the pane, play the
+//! system sound, and raise the session's attention (with an OS notification
+//! when `sashiki.notifications.bell` asks for one). Each response is toggled
+//! with git config `sashiki.terminal.{bellVisual,bellAudible,bellNotify}`
+//! (default: visual and notify). A session can be muted from the sidebar,
+//! which silences all three for agents that ring constantly. Bell storms are
+//! limited to one flash or sound per `BELL_INTERVAL`.
+
+use crate::git;
+use crate::settings::Settings;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two visual or audible bell responses
+pub const BELL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the pane flashes
+pub const BELL_FLASH_DURATION: Duration = Duration::from_millis(100);
+
+/// Which responses the bell gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BellSettings {
+    /// Flash the terminal pane
+    pub visual: bool,
+    /// Play the system sound
+    pub audible: bool,
+    /// Raise the session's attention (and notify, see `attention`)
+    pub notify: bool,
+}
+
+impl Default for BellSettings {
+    fn default() -> Self {
+        Self {
+            visual: true,
+            audible: false,
+            notify: true,
+        }
+    }
+}
+
+impl BellSettings {
+    /// No response at all (a muted session)
+    pub const SILENT: Self = Self {
+        visual: false,
+        audible: false,
+        notify: false,
+    };
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        let default = Self::default();
+        Self {
+            visual: settings
+                .get_bool(git::CONFIG_TERMINAL_BELL_VISUAL)
+                .unwrap_or(default.visual),
+            audible: settings
+                .get_bool(git::CONFIG_TERMINAL_BELL_AUDIBLE)
+                .unwrap_or(default.audible),
+            notify: settings
+                .get_bool(git::CONFIG_TERMINAL_BELL_NOTIFY)
+                .unwrap_or(default.notify),
+        }
+    }
+}
+
+/// Lets through at most one bell per `BELL_INTERVAL`
+#[derive(Debug, Default)]
+pub struct BellLimiter {
+    last: Option<Instant>,
+}
+
+impl BellLimiter {
+    /// Record a bell at `now`; true if it gets a response, false while the
+    /// previous response is less than `BELL_INTERVAL` old
+    pub fn ring(&mut self, now: Instant) -> bool {
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < BELL_INTERVAL)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+/// Play the system bell sound, best effort (`canberra-gtk-play` on Linux and
+/// BSD, `afplay` on macOS, the default beep through PowerShell on Windows)
+pub fn play_sound() {
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let mut cmd = Command::new("afplay");
+        cmd.arg("/System/Library/Sounds/Tink.aiff");
+        cmd
+    };
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut cmd = {
+        let mut cmd = Command::new("canberra-gtk-play");
+        cmd.args(["--id=bell", "--description=Sashiki"]);
+        cmd
+    };
+
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-NoProfile",
+            "-Command",
+            "[System.Media.SystemSounds]::Beep.Play()",
+        ]);
+        cmd
+    };
+
+    if let Ok(mut child) = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        // Reap the helper without blocking the UI
+        std::thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bell_storm_is_rate_limited() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut limiter = BellLimiter::default();
+        // 20 bells 50ms apart get a response every 500ms
+        let rung: Vec<u64> = (0..20)
+            .map(|i| i * 50)
+            .filter(|&t| limiter.ring(ms(t)))
+            .collect();
+        assert_eq!(rung, vec![0, 500]);
+        assert!(!limiter.ring(ms(999)));
+        assert!(limiter.ring(ms(1000)));
+    }
+
+    #[test]
+    fn test_bell_settings_from_settings() {
+        assert_eq!(
+            BellSettings::from_settings(&Settings::from_layers(vec![])),
+            BellSettings::default()
+        );
+
+        let entry = |key: &str, value: &str| (key.to_string(), value.to_string());
+        let settings = Settings::from_layers(vec![vec![
+            entry(git::CONFIG_TERMINAL_BELL_VISUAL, "false"),
+            entry(git::CONFIG_TERMINAL_BELL_AUDIBLE, "on"),
+        ]]);
+        assert_eq!(
+            BellSettings::from_settings(&settings),
+            BellSettings {
+                visual: false,
+                audible: true,
+                notify: true,
+            }
+        );
+    }
+}
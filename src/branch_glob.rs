@@ -0,0 +1,94 @@
+//! Branch name globs that select a session template
+//!
+//! `frontend/*` picks the template for `frontend/login` but not for
+//! `frontend/auth/login`: `*` stays within one `/`-separated segment, `**`
+//! spans any number of segments (`**/` also matches none) and `?` is one
+//! character other than `/`. Everything else matches literally.
+
+/// Whether `branch` matches `pattern`
+pub fn matches(pattern: &str, branch: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let branch: Vec<char> = branch.chars().collect();
+    match_from(&pattern, &branch)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            if let ['/', after @ ..] = rest
+                && match_from(after, text)
+            {
+                return true;
+            }
+            (0..=text.len()).any(|i| match_from(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => {
+            for i in 0..=text.len() {
+                if match_from(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => match text {
+            [c, text @ ..] if *c != '/' => match_from(rest, text),
+            _ => false,
+        },
+        [p, rest @ ..] => match text {
+            [c, text @ ..] if c == p => match_from(rest, text),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal() {
+        assert!(matches("main", "main"));
+        assert!(!matches("main", "main2"));
+        assert!(!matches("main", "mai"));
+        assert!(matches("", ""));
+        assert!(!matches("", "main"));
+    }
+
+    #[test]
+    fn test_star_stays_in_segment() {
+        assert!(matches("frontend/*", "frontend/login"));
+        assert!(matches("frontend/*", "frontend/"));
+        assert!(!matches("frontend/*", "frontend/auth/login"));
+        assert!(!matches("frontend/*", "backend/login"));
+        assert!(matches("*-fix", "login-fix"));
+        assert!(!matches("*-fix", "ui/login-fix"));
+        assert!(matches("*/*", "frontend/login"));
+        assert!(matches("*", "main"));
+        assert!(!matches("*", "a/b"));
+    }
+
+    #[test]
+    fn test_double_star_spans_segments() {
+        assert!(matches("frontend/**", "frontend/auth/login"));
+        assert!(matches("frontend/**", "frontend/login"));
+        assert!(!matches("frontend/**", "frontend"));
+        assert!(matches("**", "a/b/c"));
+        assert!(matches("**/fix-*", "fix-login"));
+        assert!(matches("**/fix-*", "team/ui/fix-login"));
+        assert!(!matches("**/fix-*", "team/ui/hotfix-login"));
+        assert!(matches("a/**/z", "a/z"));
+        assert!(matches("a/**/z", "a/b/c/z"));
+    }
+
+    #[test]
+    fn test_question_mark() {
+        assert!(matches("v?", "v1"));
+        assert!(!matches("v?", "v10"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(matches("release-?.?", "release-1.2"));
+    }
+}
@@ -0,0 +1,161 @@
+//! Branch names suggested from a task description
+//!
+//! "Fix flaky session tests" becomes `sashiki/fix-flaky-session-tests`: the
+//! description is lowercased, every run of characters other than letters and
+//! digits turns into a single `-`, and the result is cut to `MAX_SLUG_LEN`
+//! characters. The prefix is `sashiki.worktrees.branchPrefix`. A name that
+//! is already a local branch gets `-2`, `-3`, ... appended.
+
+use crate::git::validate_branch_name;
+
+/// Longest slug taken from a description (in characters, prefix excluded)
+pub const MAX_SLUG_LEN: usize = 40;
+
+/// `description` as a branch name component (empty if it has no letters or
+/// digits). Letters outside ASCII are kept, git allows them.
+pub fn slugify(description: &str) -> String {
+    let mut slug = String::new();
+    let mut separated = false;
+    for c in description.chars() {
+        if c.is_alphanumeric() {
+            if separated && !slug.is_empty() {
+                slug.push('-');
+            }
+            separated = false;
+            slug.extend(c.to_lowercase());
+        } else {
+            separated = true;
+        }
+    }
+    let slug: String = slug.chars().take(MAX_SLUG_LEN).collect();
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Branch for `description` under `prefix` that is not one of `existing`,
+/// or None when the description gives no valid name
+pub fn suggest_branch_name(prefix: &str, description: &str, existing: &[String]) -> Option<String> {
+    let slug = slugify(description);
+    if slug.is_empty() {
+        return None;
+    }
+    let base = format!("{}{}", prefix, slug);
+    validate_branch_name(&base).ok()?;
+    std::iter::once(base.clone())
+        .chain((2..).map(|n| format!("{}-{}", base, n)))
+        .find(|name| !existing.iter().any(|branch| branch == name))
+}
+
+/// Live suggestions for the branch field of the create-worktree dialog
+#[derive(Debug, Default)]
+pub struct BranchSuggester {
+    prefix: String,
+    /// Local branches, read when the dialog opened
+    existing: Vec<String>,
+    /// Last suggested name; the branch field follows the description while
+    /// it still shows this (or nothing)
+    last: Option<String>,
+}
+
+impl BranchSuggester {
+    pub fn new(prefix: String, existing: Vec<String>) -> Self {
+        Self {
+            prefix,
+            existing,
+            last: None,
+        }
+    }
+
+    /// New content for a branch field showing `current` once the description
+    /// became `description`; None leaves a name typed by hand alone
+    pub fn update(&mut self, description: &str, current: &str) -> Option<String> {
+        if !current.is_empty() && self.last.as_deref() != Some(current) {
+            return None;
+        }
+        self.last = suggest_branch_name(&self.prefix, description, &self.existing);
+        Some(self.last.clone().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify_ascii() {
+        assert_eq!(
+            slugify("fix flaky session tests"),
+            "fix-flaky-session-tests"
+        );
+        assert_eq!(
+            slugify("  Add: OAuth2 login (GitHub)!  "),
+            "add-oauth2-login-github"
+        );
+        assert_eq!(slugify("snake_case & dots.v2"), "snake-case-dots-v2");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn test_slugify_unicode_and_emoji() {
+        assert_eq!(slugify("Ünïcödé Straße"), "ünïcödé-straße");
+        assert_eq!(slugify("ログ画面を修正"), "ログ画面を修正");
+        assert_eq!(slugify("🐛 fix 🔥 crash 🚀"), "fix-crash");
+        assert_eq!(slugify("🎉🎉"), "");
+    }
+
+    #[test]
+    fn test_slugify_is_cut_to_max_len() {
+        let slug = slugify(&"word ".repeat(20));
+        assert!(slug.chars().count() <= MAX_SLUG_LEN);
+        assert!(!slug.ends_with('-'));
+        assert_eq!(slugify(&"あ".repeat(50)).chars().count(), MAX_SLUG_LEN);
+    }
+
+    #[test]
+    fn test_suggest_skips_taken_names() {
+        let existing = vec![
+            "main".to_string(),
+            "sashiki/fix-login".to_string(),
+            "sashiki/fix-login-2".to_string(),
+        ];
+        assert_eq!(
+            suggest_branch_name("sashiki/", "Fix login", &existing).as_deref(),
+            Some("sashiki/fix-login-3")
+        );
+        assert_eq!(
+            suggest_branch_name("sashiki/", "Fix logout", &existing).as_deref(),
+            Some("sashiki/fix-logout")
+        );
+        assert_eq!(
+            suggest_branch_name("", "main", &existing).as_deref(),
+            Some("main-2")
+        );
+    }
+
+    #[test]
+    fn test_suggest_needs_a_valid_name() {
+        assert_eq!(suggest_branch_name("sashiki/", "🎉", &[]), None);
+        assert_eq!(suggest_branch_name("my prefix/", "task", &[]), None);
+        assert_eq!(suggest_branch_name("-", "task", &[]), None);
+    }
+
+    #[test]
+    fn test_suggester_follows_description_until_edited() {
+        let mut suggester =
+            BranchSuggester::new("sashiki/".to_string(), vec!["sashiki/fix".to_string()]);
+        let branch = suggester.update("Fix", "").unwrap();
+        assert_eq!(branch, "sashiki/fix-2");
+        let branch = suggester.update("Fix it", &branch).unwrap();
+        assert_eq!(branch, "sashiki/fix-it");
+
+        // A name typed by hand is kept; clearing it resumes the suggestions
+        assert_eq!(suggester.update("Fix it now", "my-branch"), None);
+        assert_eq!(
+            suggester.update("Fix it now", "").as_deref(),
+            Some("sashiki/fix-it-now")
+        );
+        assert_eq!(
+            suggester.update("🎉", "sashiki/fix-it-now").as_deref(),
+            Some("")
+        );
+    }
+}
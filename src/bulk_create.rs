@@ -0,0 +1,325 @@
+//! Creating several worktrees from a task list, one branch per line
+//!
+//! The list is planned first: lines are trimmed, blank lines and `#`
+//! comments dropped, and a branch listed twice is kept once. Every branch
+//! is then checked against git's naming rules, the existing branches, the
+//! worktree directories in use and the other branches of the list (`a/b`
+//! and `a-b` would share a directory). Nothing runs until the whole list is
+//! clean. The worktrees are then created one after the other; a failure is
+//! recorded and the next one runs, and a cancel stops once the worktree
+//! being created is done.
+
+use crate::git::validate_branch_name;
+use std::collections::{HashMap, HashSet};
+
+/// A branch of the list and the worktree it gets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedWorktree {
+    pub branch: String,
+    /// Worktree (and directory) name: the branch with `/` as `-`
+    pub name: String,
+}
+
+/// Why a line of the list cannot be created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanProblem {
+    /// Line of the list, from 1
+    pub line: usize,
+    pub branch: String,
+    pub reason: String,
+}
+
+/// The worktrees a list asks for, or what is wrong with it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub worktrees: Vec<PlannedWorktree>,
+    pub problems: Vec<PlanProblem>,
+}
+
+impl Plan {
+    /// There is something to create and nothing wrong
+    pub fn is_ready(&self) -> bool {
+        !self.worktrees.is_empty() && self.problems.is_empty()
+    }
+}
+
+/// Plan the worktrees of `input`. `branches` are the existing local
+/// branches; `name_taken` tells whether a worktree name is in use (a session
+/// or a directory of that name).
+pub fn plan(input: &str, branches: &[String], name_taken: impl Fn(&str) -> bool) -> Plan {
+    let mut plan = Plan::default();
+    let mut listed = HashSet::new();
+    // Worktree names of the list so far, by the branch that took them
+    let mut names: HashMap<String, String> = HashMap::new();
+    for (i, line) in input.lines().enumerate() {
+        let branch = line.trim();
+        if branch.is_empty() || branch.starts_with('#') || !listed.insert(branch) {
+            continue;
+        }
+        let name = branch.replace('/', "-");
+        let reason = if let Err(reason) = validate_branch_name(branch) {
+            Some(reason.to_string())
+        } else if branches.iter().any(|existing| existing == branch) {
+            Some("Branch already exists".to_string())
+        } else if let Some(other) = names.get(&name) {
+            Some(format!("Same worktree directory as '{}'", other))
+        } else if name_taken(&name) {
+            Some(format!("Worktree '{}' already exists", name))
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => plan.problems.push(PlanProblem {
+                line: i + 1,
+                branch: branch.to_string(),
+                reason,
+            }),
+            None => {
+                names.insert(name.clone(), branch.to_string());
+                plan.worktrees.push(PlannedWorktree {
+                    branch: branch.to_string(),
+                    name,
+                });
+            }
+        }
+    }
+    plan
+}
+
+/// Where a worktree of a run is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemStatus {
+    Pending,
+    Running,
+    Done,
+    Failed(String),
+    /// Not started because the run was cancelled
+    Skipped,
+}
+
+/// A planned worktree and where it is
+#[derive(Debug, Clone)]
+pub struct BulkItem {
+    pub worktree: PlannedWorktree,
+    pub status: ItemStatus,
+}
+
+/// Worktrees of a plan being created one after the other
+#[derive(Debug, Clone)]
+pub struct BulkRun {
+    pub items: Vec<BulkItem>,
+    cancelled: bool,
+}
+
+impl BulkRun {
+    pub fn new(worktrees: Vec<PlannedWorktree>) -> Self {
+        Self {
+            items: worktrees
+                .into_iter()
+                .map(|worktree| BulkItem {
+                    worktree,
+                    status: ItemStatus::Pending,
+                })
+                .collect(),
+            cancelled: false,
+        }
+    }
+
+    /// Mark the next pending worktree running and return it. None once all
+    /// ran, or after a cancel, which skips the ones left.
+    pub fn start_next(&mut self) -> Option<PlannedWorktree> {
+        if self.cancelled {
+            for item in &mut self.items {
+                if item.status == ItemStatus::Pending {
+                    item.status = ItemStatus::Skipped;
+                }
+            }
+            return None;
+        }
+        let item = self
+            .items
+            .iter_mut()
+            .find(|item| item.status == ItemStatus::Pending)?;
+        item.status = ItemStatus::Running;
+        Some(item.worktree.clone())
+    }
+
+    /// The running worktree was created or failed
+    pub fn finish(&mut self, result: Result<(), String>) {
+        if let Some(item) = self
+            .items
+            .iter_mut()
+            .find(|item| item.status == ItemStatus::Running)
+        {
+            item.status = match result {
+                Ok(()) => ItemStatus::Done,
+                Err(e) => ItemStatus::Failed(e),
+            };
+        }
+    }
+
+    /// Stop after the running worktree
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Nothing runs or waits any more
+    pub fn is_finished(&self) -> bool {
+        self.items
+            .iter()
+            .all(|item| !matches!(item.status, ItemStatus::Pending | ItemStatus::Running))
+    }
+
+    /// "3 created, 1 failed, 2 skipped" (counts of zero left out)
+    pub fn summary(&self) -> String {
+        let count = |wanted: fn(&ItemStatus) -> bool| {
+            self.items
+                .iter()
+                .filter(|item| wanted(&item.status))
+                .count()
+        };
+        let parts = [
+            (count(|s| *s == ItemStatus::Done), "created"),
+            (count(|s| matches!(s, ItemStatus::Failed(_))), "failed"),
+            (count(|s| *s == ItemStatus::Skipped), "skipped"),
+        ];
+        let summary: Vec<String> = parts
+            .iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, what)| format!("{} {}", n, what))
+            .collect();
+        if summary.is_empty() {
+            "Nothing created".to_string()
+        } else {
+            summary.join(", ")
+        }
+    }
+}
+
+/// State of the bulk create dialog
+#[derive(Debug, Default)]
+pub struct BulkCreate {
+    /// The task list as typed or pasted
+    pub input: String,
+    /// Local branches when the dialog was opened
+    pub branches: Vec<String>,
+    /// Plan of `input`, kept current while typing
+    pub plan: Plan,
+    /// Show the new sessions in parallel mode with their terminals started
+    pub start_terminals: bool,
+    /// The creation, once started
+    pub run: Option<BulkRun>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::GitRepo;
+    use crate::test_support::init_repo_at;
+
+    fn branches(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn planned(plan: &Plan) -> Vec<&str> {
+        plan.worktrees.iter().map(|w| w.branch.as_str()).collect()
+    }
+
+    #[test]
+    fn test_plan_skips_blanks_comments_and_repeats() {
+        let input = "  feature/login \n\n# later\nfix-typo\nfeature/login\n\tdocs\n";
+        let plan = plan(input, &[], |_| false);
+        assert_eq!(planned(&plan), vec!["feature/login", "fix-typo", "docs"]);
+        assert_eq!(plan.worktrees[0].name, "feature-login");
+        assert!(plan.problems.is_empty());
+        assert!(plan.is_ready());
+    }
+
+    #[test]
+    fn test_plan_reports_every_problem() {
+        let input = "ok\nbad name\nmain\nfeature/x\nfeature-x\ntaken\n";
+        let plan = plan(input, &branches(&["main"]), |name| name == "taken");
+        assert_eq!(planned(&plan), vec!["ok", "feature/x"]);
+        let problems: Vec<(usize, &str)> = plan
+            .problems
+            .iter()
+            .map(|p| (p.line, p.branch.as_str()))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![(2, "bad name"), (3, "main"), (5, "feature-x"), (6, "taken")]
+        );
+        assert_eq!(plan.problems[1].reason, "Branch already exists");
+        assert_eq!(
+            plan.problems[2].reason,
+            "Same worktree directory as 'feature/x'"
+        );
+        assert!(!plan.is_ready());
+    }
+
+    #[test]
+    fn test_empty_plan_is_not_ready() {
+        assert!(!plan("\n# nothing yet\n", &[], |_| false).is_ready());
+    }
+
+    #[test]
+    fn test_cancel_skips_what_has_not_started() {
+        let plan = plan("a\nb\nc", &[], |_| false);
+        let mut run = BulkRun::new(plan.worktrees);
+        assert_eq!(run.start_next().unwrap().branch, "a");
+        run.cancel();
+        assert!(!run.is_finished());
+        run.finish(Ok(()));
+        assert!(run.start_next().is_none());
+        assert!(run.is_finished());
+        assert_eq!(run.summary(), "1 created, 2 skipped");
+    }
+
+    #[test]
+    fn test_run_continues_past_a_failed_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let workdir = dir.path().join("repo");
+        init_repo_at(&workdir);
+
+        let repo = GitRepo::open(&workdir).unwrap();
+        let plan = plan("one\ntwo\nthree", &repo.local_branches().unwrap(), |name| {
+            repo.generate_worktree_path(name)
+                .is_some_and(|path| path.exists())
+        });
+        assert!(plan.is_ready());
+
+        // Something else takes the directory of "two" after planning
+        let taken = repo.generate_worktree_path("two").unwrap();
+        std::fs::create_dir_all(&taken).unwrap();
+        std::fs::write(taken.join("file"), "in the way").unwrap();
+
+        let mut run = BulkRun::new(plan.worktrees);
+        while let Some(next) = run.start_next() {
+            let path = repo.generate_worktree_path(&next.name).unwrap();
+            let result = repo
+                .create_worktree(&next.name, Some(&next.branch), &path, None)
+                .map(|_| ())
+                .map_err(|e| e.to_string());
+            run.finish(result);
+        }
+
+        let statuses: Vec<&ItemStatus> = run.items.iter().map(|item| &item.status).collect();
+        assert_eq!(statuses[0], &ItemStatus::Done);
+        assert!(matches!(statuses[1], ItemStatus::Failed(_)));
+        assert_eq!(statuses[2], &ItemStatus::Done);
+        assert_eq!(run.summary(), "2 created, 1 failed");
+        let worktrees: Vec<String> = repo
+            .list_worktrees()
+            .unwrap()
+            .into_iter()
+            .map(|worktree| worktree.name)
+            .collect();
+        assert!(worktrees.contains(&"one".to_string()));
+        assert!(worktrees.contains(&"three".to_string()));
+        assert!(!worktrees.contains(&"two".to_string()));
+    }
+}
@@ -0,0 +1,101 @@
+//! Sessions that are still at work
+//!
+//! Quitting the app or deleting a session stops its terminals, which would
+//! cut an agent off mid-task. A terminal counts as busy while a program other
+//! than the shell holds its foreground, or when it printed something within
+//! `RECENT_OUTPUT`. Both come from state the terminal already tracks, so the
+//! check is cheap. `sashiki.confirmBusySessions = false` skips the question.
+
+use std::time::Duration;
+
+/// Output this recent makes a terminal busy even at the shell prompt
+pub const RECENT_OUTPUT: Duration = Duration::from_secs(5);
+
+/// What a terminal is doing right now
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalActivity {
+    /// Foreground program other than the shell
+    pub program: Option<String>,
+    /// Time since the terminal last printed anything
+    pub output_age: Option<Duration>,
+}
+
+impl TerminalActivity {
+    fn has_recent_output(&self) -> bool {
+        self.output_age.is_some_and(|age| age < RECENT_OUTPUT)
+    }
+}
+
+/// What keeps the terminals of one session busy, e.g. "claude running,
+/// output 3s ago"; None if none of them is
+pub fn describe(activities: &[TerminalActivity]) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for program in activities.iter().filter_map(|a| a.program.as_deref()) {
+        let part = format!("{} running", program);
+        if !parts.contains(&part) {
+            parts.push(part);
+        }
+    }
+    if let Some(age) = activities
+        .iter()
+        .filter(|a| a.has_recent_output())
+        .filter_map(|a| a.output_age)
+        .min()
+    {
+        parts.push(format!("output {}s ago", age.as_secs()));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(program: Option<&str>, output_secs: Option<u64>) -> TerminalActivity {
+        TerminalActivity {
+            program: program.map(str::to_string),
+            output_age: output_secs.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn test_busy_terminals() {
+        assert_eq!(describe(&[TerminalActivity::default()]), None);
+        assert_eq!(
+            describe(&[activity(Some("claude"), None)]).as_deref(),
+            Some("claude running")
+        );
+        assert_eq!(
+            describe(&[activity(None, Some(2))]).as_deref(),
+            Some("output 2s ago")
+        );
+        assert_eq!(
+            describe(&[activity(None, Some(RECENT_OUTPUT.as_secs()))]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_describe_session() {
+        assert_eq!(describe(&[]), None);
+        assert_eq!(describe(&[activity(None, Some(60))]), None);
+        assert_eq!(
+            describe(&[activity(Some("claude"), Some(3))]).as_deref(),
+            Some("claude running, output 3s ago")
+        );
+        // The freshest output of any terminal; each program once
+        assert_eq!(
+            describe(&[
+                activity(Some("cargo"), Some(4)),
+                activity(Some("cargo"), Some(1)),
+                activity(None, Some(30)),
+            ])
+            .as_deref(),
+            Some("cargo running, output 1s ago")
+        );
+        assert_eq!(
+            describe(&[activity(Some("vim"), Some(120))]).as_deref(),
+            Some("vim running")
+        );
+    }
+}
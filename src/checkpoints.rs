@@ -0,0 +1,145 @@
+//! Automatic checkpoints of a session's working tree
+//!
+//! With `sashiki.session.<name>.autoCheckpoint` set, the session's working
+//! tree is recorded on `refs/sashiki/checkpoints/<branch>/` (see
+//! `GitRepo::create_checkpoint`) every `sashiki.checkpoints.interval`
+//! seconds, and sooner when more files have changed than at the last
+//! checkpoint and the terminals have been quiet for `CHECKPOINT_QUIET` (an
+//! agent finishing a step). HEAD, the index and the working tree are never
+//! touched; nothing is recorded while the working tree stays the same, and
+//! only the newest `sashiki.checkpoints.keep` are kept.
+
+use std::time::{Duration, Instant};
+
+/// How often the sessions are checked for a checkpoint being due
+pub const CHECKPOINT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Quiet time after which more changed files call for a checkpoint
+pub const CHECKPOINT_QUIET: Duration = Duration::from_secs(5);
+
+/// When a session's next checkpoint is due
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointSchedule {
+    /// When the last checkpoint was taken (or the schedule started)
+    last: Option<Instant>,
+    /// Changed files at that time
+    files: usize,
+    /// A checkpoint is being taken
+    running: bool,
+}
+
+impl CheckpointSchedule {
+    /// Whether a checkpoint is due at `now` with `changed_files` files
+    /// changed and the terminals last printing `output_age` ago (None =
+    /// never). The first poll only starts the schedule.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        interval: Duration,
+        changed_files: usize,
+        output_age: Option<Duration>,
+    ) -> bool {
+        let Some(last) = self.last else {
+            self.last = Some(now);
+            self.files = changed_files;
+            return false;
+        };
+        if self.running {
+            return false;
+        }
+        let quiet = output_age.is_none_or(|age| age >= CHECKPOINT_QUIET);
+        now.duration_since(last) >= interval || (changed_files > self.files && quiet)
+    }
+
+    /// A checkpoint was started at `now` with `changed_files` files changed
+    pub fn start(&mut self, now: Instant, changed_files: usize) {
+        self.last = Some(now);
+        self.files = changed_files;
+        self.running = true;
+    }
+
+    pub fn finish(&mut self) {
+        self.running = false;
+    }
+}
+
+/// Message of a checkpoint taken at `at` (unix seconds): "checkpoint 14:32,
+/// 12 files"
+pub fn checkpoint_message(at: i64, changed_files: usize) -> String {
+    format!(
+        "checkpoint {}, {} {}",
+        clock(at),
+        changed_files,
+        if changed_files == 1 { "file" } else { "files" }
+    )
+}
+
+/// "HH:MM" of `at` (unix seconds) in local time, or UTC where the local
+/// offset is not known
+fn clock(at: i64) -> String {
+    let (hour, minute) =
+        local_hour_minute(at).unwrap_or((at.rem_euclid(86_400) / 3600, at.rem_euclid(3600) / 60));
+    format!("{:02}:{:02}", hour, minute)
+}
+
+#[cfg(unix)]
+fn local_hour_minute(at: i64) -> Option<(i64, i64)> {
+    let time = at as libc::time_t;
+    // SAFETY: an all-zero `tm` is a valid value to be overwritten
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid for the call; `localtime_r` does not
+    // share state between threads
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    (!result.is_null()).then_some((tm.tm_hour as i64, tm.tm_min as i64))
+}
+
+#[cfg(not(unix))]
+fn local_hour_minute(_at: i64) -> Option<(i64, i64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(600);
+
+    #[test]
+    fn test_schedule() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let quiet = Some(Duration::from_secs(10));
+        let printing = Some(Duration::from_secs(1));
+        let mut schedule = CheckpointSchedule::default();
+
+        // The first poll starts the schedule
+        assert!(!schedule.poll(start, INTERVAL, 3, quiet));
+        assert!(!schedule.poll(at(10), INTERVAL, 3, quiet));
+        // More files, but the agent is still printing
+        assert!(!schedule.poll(at(20), INTERVAL, 5, printing));
+        assert!(schedule.poll(at(30), INTERVAL, 5, quiet));
+        schedule.start(at(30), 5);
+        // Not again while it runs
+        assert!(!schedule.poll(at(40), INTERVAL, 9, quiet));
+        schedule.finish();
+        assert!(schedule.poll(at(40), INTERVAL, 9, None));
+        schedule.start(at(40), 9);
+        schedule.finish();
+
+        // Fewer files do not count; the interval does, busy or not
+        assert!(!schedule.poll(at(100), INTERVAL, 2, quiet));
+        assert!(schedule.poll(at(640), INTERVAL, 2, printing));
+    }
+
+    #[test]
+    fn test_checkpoint_message() {
+        let message = checkpoint_message(1_700_000_000, 12);
+        let clock = message
+            .strip_prefix("checkpoint ")
+            .and_then(|rest| rest.strip_suffix(", 12 files"))
+            .unwrap();
+        assert_eq!(clock.len(), 5);
+        assert_eq!(clock.as_bytes()[2], b':');
+        assert!(checkpoint_message(0, 1).ends_with(", 1 file"));
+    }
+}
@@ -0,0 +1,670 @@
+//! Fenced code blocks printed in a terminal, and applying one to a file
+//!
+//! Agents print suggestions as Markdown code blocks, often with the file
+//! they belong to in the info string (```` ```rust src/lib.rs ````) or in
+//! the sentence above ("replace the function in src/lib.rs with this").
+//! Rows that wrapped are joined back into the lines printed before the
+//! fences are looked for. A block still being printed (no closing fence
+//! yet) is left out.
+//!
+//! A block is applied as the whole file when it starts and ends like the
+//! file does (or the file does not exist yet). Otherwise it replaces the
+//! one place in the file that starts with its first line and ends with its
+//! last, at the same relative indentation; a block whose first line is not
+//! in the file is appended. A first line found more than once, or no end
+//! for it, is ambiguous and the block can only be copied.
+
+use crate::fs_util;
+use crate::git;
+use crate::worktree_guard;
+use std::path::{Path, PathBuf};
+
+/// Stands in for the file a block creates
+const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+/// Lines above a fence searched for the path of its block
+const PATH_CONTEXT_LINES: usize = 2;
+
+/// A row of terminal text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalRow {
+    /// Grid line
+    pub line: i32,
+    pub text: String,
+    /// The line printed goes on in the next row
+    pub wraps: bool,
+}
+
+/// A complete fenced code block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// What follows the opening fence: a language, a path or both
+    pub info: String,
+    /// Lines between the fences, without the fence's indentation
+    pub text: String,
+    /// Grid lines of the first row of the opening fence and the last row of
+    /// the closing one
+    pub first_line: i32,
+    pub last_line: i32,
+    /// File the info string or the text above names, when it names exactly
+    /// one (as written, relative to the worktree or absolute)
+    pub path: Option<String>,
+}
+
+/// Printed line: its first and last grid line, and its text
+struct PrintedLine {
+    first: i32,
+    last: i32,
+    text: String,
+}
+
+/// Join wrapped rows back into the lines printed
+fn printed_lines(rows: &[TerminalRow]) -> Vec<PrintedLine> {
+    let mut lines = Vec::new();
+    let mut open: Option<PrintedLine> = None;
+    for row in rows {
+        match open.as_mut() {
+            Some(line) => {
+                line.text.push_str(&row.text);
+                line.last = row.line;
+            }
+            None => {
+                open = Some(PrintedLine {
+                    first: row.line,
+                    last: row.line,
+                    text: row.text.clone(),
+                });
+            }
+        }
+        if !row.wraps {
+            lines.extend(open.take());
+        }
+    }
+    lines.extend(open);
+    lines
+}
+
+/// Opening fence of a block
+struct Fence {
+    marker: char,
+    len: usize,
+    /// Leading whitespace of the fence, taken off the lines of the block
+    indent: usize,
+    info: String,
+}
+
+impl Fence {
+    fn open(text: &str) -> Option<Self> {
+        let trimmed = text.trim_start();
+        let marker = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let len = trimmed.chars().take_while(|&c| c == marker).count();
+        let info = trimmed[len..].trim();
+        // A backtick in the info string makes it inline code
+        if len < 3 || (marker == '`' && info.contains('`')) {
+            return None;
+        }
+        Some(Self {
+            marker,
+            len,
+            indent: text.chars().count() - trimmed.chars().count(),
+            info: info.to_string(),
+        })
+    }
+
+    fn closes(&self, text: &str) -> bool {
+        let trimmed = text.trim();
+        trimmed.chars().count() >= self.len && trimmed.chars().all(|c| c == self.marker)
+    }
+
+    /// `text` without up to the fence's indentation
+    fn dedent<'a>(&self, text: &'a str) -> &'a str {
+        let cut: usize = text
+            .chars()
+            .take(self.indent)
+            .take_while(|c| c.is_whitespace())
+            .map(char::len_utf8)
+            .sum();
+        &text[cut..]
+    }
+}
+
+/// The complete code blocks in `rows` (consecutive, top to bottom)
+pub fn extract(rows: &[TerminalRow]) -> Vec<CodeBlock> {
+    let lines = printed_lines(rows);
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(fence) = Fence::open(&lines[i].text) else {
+            i += 1;
+            continue;
+        };
+        let Some(close) = (i + 1..lines.len()).find(|&j| fence.closes(&lines[j].text)) else {
+            break;
+        };
+        let text: String = lines[i + 1..close]
+            .iter()
+            .map(|line| format!("{}\n", fence.dedent(&line.text)))
+            .collect();
+        let above: Vec<&str> = lines[..i]
+            .iter()
+            .rev()
+            .take(PATH_CONTEXT_LINES)
+            .map(|line| line.text.as_str())
+            .collect();
+        blocks.push(CodeBlock {
+            path: named_path(&fence.info, &above),
+            info: fence.info,
+            text,
+            first_line: lines[i].first,
+            last_line: lines[close].last,
+        });
+        i = close + 1;
+    }
+    blocks
+}
+
+/// Path named by the info string, else by the nearest line of `above`
+/// (nearest first) that names any
+fn named_path(info: &str, above: &[&str]) -> Option<String> {
+    let in_info = path_candidates(info, false);
+    if !in_info.is_empty() {
+        return only(in_info);
+    }
+    above
+        .iter()
+        .map(|line| path_candidates(line, true))
+        .find(|candidates| !candidates.is_empty())
+        .and_then(only)
+}
+
+fn only(mut candidates: Vec<String>) -> Option<String> {
+    (candidates.len() == 1).then(|| candidates.remove(0))
+}
+
+/// Distinct words of `text` that look like file paths. In `prose` a word
+/// needs a directory or backticks around it ("Node.js" is not a file).
+fn path_candidates(text: &str, prose: bool) -> Vec<String> {
+    let mut candidates: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        if word.contains("://") {
+            continue;
+        }
+        let quoted = word.starts_with('`');
+        let word = word.trim_matches(|c: char| "`'\"()[]{}<>,;:".contains(c));
+        let word = word.trim_end_matches(['.', ':']);
+        let word = ["path=", "file=", "filename=", "title="]
+            .iter()
+            .find_map(|prefix| word.strip_prefix(prefix))
+            .unwrap_or(word)
+            .trim_matches(['"', '\'']);
+        // "rust:src/lib.rs" and "src/lib.rs:42"
+        let word = match word.split_once(':') {
+            Some((lang, path)) if lang.len() > 1 && looks_like_path(path) => path,
+            Some((path, line)) if line.chars().all(|c| c.is_ascii_digit()) => path,
+            _ => word,
+        };
+        if !looks_like_path(word) || (prose && !quoted && !word.contains('/')) {
+            continue;
+        }
+        if !candidates.iter().any(|c| c == word) {
+            candidates.push(word.to_string());
+        }
+    }
+    candidates
+}
+
+/// A file name with an extension, possibly in directories
+fn looks_like_path(word: &str) -> bool {
+    if word.contains("://")
+        || !word
+            .chars()
+            .all(|c| c.is_alphanumeric() || "._-/\\+@~".contains(c))
+    {
+        return false;
+    }
+    let name = word.rsplit(['/', '\\']).next().unwrap_or(word);
+    let Some((stem, ext)) = name.rsplit_once('.') else {
+        return false;
+    };
+    !ext.is_empty()
+        && ext.len() <= 10
+        && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        && ext.chars().any(|c| c.is_ascii_alphabetic())
+        && (stem.chars().count() >= 2 || (!stem.is_empty() && word.contains('/')))
+}
+
+/// How a block goes into its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    /// The file does not exist yet
+    Create,
+    /// The block is the whole file
+    Replace,
+    /// The block replaces lines `start..=end` (0-based)
+    Substitute {
+        start: usize,
+        end: usize,
+    },
+    Append,
+}
+
+impl Edit {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Create => "Creates the file".to_string(),
+            Self::Replace => "Replaces the whole file".to_string(),
+            Self::Substitute { start, end } if start == end => {
+                format!("Replaces line {}", start + 1)
+            }
+            Self::Substitute { start, end } => {
+                format!("Replaces lines {}–{}", start + 1, end + 1)
+            }
+            Self::Append => "Appends to the end of the file".to_string(),
+        }
+    }
+}
+
+/// A block put into a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub edit: Edit,
+    /// The file with the block in it
+    pub content: String,
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// `current` (None when the file does not exist) with `block` in it; None
+/// when the block has no text or where it goes is ambiguous
+pub fn apply(current: Option<&str>, block: &str) -> Option<Applied> {
+    let block_lines: Vec<&str> = block.lines().collect();
+    let first = block_lines.iter().position(|l| !l.trim().is_empty())?;
+    let last = block_lines.iter().rposition(|l| !l.trim().is_empty())?;
+    let block_lines = &block_lines[first..=last];
+    let whole = |edit| {
+        Some(Applied {
+            edit,
+            content: format!("{}\n", block_lines.join("\n")),
+        })
+    };
+    let Some(current) = current else {
+        return whole(Edit::Create);
+    };
+
+    let newline = if current.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let lines: Vec<&str> = current.lines().collect();
+    let mut non_blank = lines.iter().filter(|l| !l.trim().is_empty());
+    let Some(file_first) = non_blank.next() else {
+        return whole(Edit::Replace);
+    };
+    let file_last = non_blank.next_back().unwrap_or(file_first);
+    let (block_first, block_last) = (block_lines[0], block_lines[block_lines.len() - 1]);
+    if file_first.trim_end() == block_first.trim_end()
+        && file_last.trim_end() == block_last.trim_end()
+    {
+        let mut applied = whole(Edit::Replace)?;
+        applied.content = applied.content.replace('\n', newline);
+        return Some(applied);
+    }
+
+    let mut starts = (0..lines.len()).filter(|&i| lines[i].trim() == block_first.trim());
+    let Some(start) = starts.next() else {
+        let mut content = current.to_string();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push_str(newline);
+        }
+        for line in block_lines {
+            content.push_str(line);
+            content.push_str(newline);
+        }
+        return Some(Applied {
+            edit: Edit::Append,
+            content,
+        });
+    };
+    if starts.next().is_some() {
+        return None;
+    }
+
+    let shift = indent(lines[start]) as isize - indent(block_first) as isize;
+    // The end is the first line back at the indentation the block's last
+    // line has against its first, and must be that line
+    let end = if block_lines.len() == 1 {
+        start
+    } else {
+        let end_indent = indent(block_last) as isize + shift;
+        let end = (start + 1..lines.len())
+            .find(|&i| !lines[i].trim().is_empty() && indent(lines[i]) as isize <= end_indent)?;
+        if lines[end].trim() != block_last.trim() {
+            return None;
+        }
+        end
+    };
+
+    // Indented to where it goes
+    let padding = lines[start].get(..shift.max(0) as usize).unwrap_or("");
+    let replaced = block_lines.iter().map(|line| {
+        if line.trim().is_empty() {
+            String::new()
+        } else if shift >= 0 {
+            format!("{}{}", padding, line)
+        } else {
+            let cut = (-shift as usize).min(indent(line));
+            line.get(cut..).unwrap_or(line).to_string()
+        }
+    });
+    let mut content: Vec<String> = lines[..start].iter().map(|l| l.to_string()).collect();
+    content.extend(replaced);
+    content.extend(lines[end + 1..].iter().map(|l| l.to_string()));
+    let mut content = content.join(newline);
+    if current.ends_with('\n') {
+        content.push_str(newline);
+    }
+    Some(Applied {
+        edit: Edit::Substitute { start, end },
+        content,
+    })
+}
+
+/// A block about to be written to its file, with the diff it makes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    worktree: PathBuf,
+    /// Relative to the worktree
+    pub path: PathBuf,
+    /// Full path of the file
+    pub file: PathBuf,
+    pub edit: Edit,
+    pub content: String,
+    /// The file as it was when the diff was made
+    original: Option<String>,
+    /// Hunks from the file to `content`, without the file headers
+    pub diff: String,
+}
+
+impl Suggestion {
+    /// Put `block` into the file it names in `worktree`. Blocks and runs
+    /// git; call off the UI thread.
+    pub fn prepare(worktree: &Path, block: &CodeBlock) -> Result<Self, String> {
+        let named = block
+            .path
+            .as_deref()
+            .ok_or("The block does not name one file")?;
+        let named = Path::new(named);
+        let path = if named.is_absolute() {
+            named
+                .strip_prefix(worktree)
+                .map_err(|_| format!("{} is outside the worktree", named.display()))?
+        } else {
+            named
+        };
+        let file = worktree_guard::check(worktree, path).map_err(|e| e.to_string())?;
+        if file.is_dir() {
+            return Err(format!("{} is a directory", path.display()));
+        }
+        let original = if file.exists() {
+            let bytes = std::fs::read(&file).map_err(|e| e.to_string())?;
+            let text = String::from_utf8(bytes)
+                .map_err(|_| format!("{} is not a text file", path.display()))?;
+            Some(text)
+        } else if file.parent().is_some_and(Path::is_dir) {
+            None
+        } else {
+            return Err(format!(
+                "No directory for {} in the worktree",
+                path.display()
+            ));
+        };
+        let applied = apply(original.as_deref(), &block.text).ok_or_else(|| {
+            format!(
+                "Where the block goes in {} is ambiguous; copy it instead",
+                path.display()
+            )
+        })?;
+        let diff = diff(&file, original.is_some(), &applied.content)?;
+        Ok(Self {
+            worktree: worktree.to_path_buf(),
+            path: path.to_path_buf(),
+            file,
+            edit: applied.edit,
+            content: applied.content,
+            original,
+            diff,
+        })
+    }
+
+    /// Write the file, unless it changed since the diff was made or its
+    /// path now leads outside the worktree
+    pub fn write(&self) -> Result<(), String> {
+        let file = worktree_guard::check(&self.worktree, &self.path).map_err(|e| e.to_string())?;
+        let now = match std::fs::read(&file) {
+            Ok(bytes) => Some(String::from_utf8_lossy(&bytes).into_owned()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.to_string()),
+        };
+        if now != self.original {
+            return Err(format!(
+                "{} changed since the diff was shown",
+                self.path.display()
+            ));
+        }
+        fs_util::write_atomic(&file, self.content.as_bytes()).map_err(|e| e.to_string())
+    }
+}
+
+/// Hunks from `file` to `content`
+fn diff(file: &Path, exists: bool, content: &str) -> Result<String, String> {
+    let dir = std::env::temp_dir().join("sashiki-suggestions");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let proposed = dir.join(format!("{}-{}", std::process::id(), name));
+    std::fs::write(&proposed, content).map_err(|e| e.to_string())?;
+    let old = if exists { file } else { Path::new(NULL_DEVICE) };
+    let diff = git::diff_files(old, &proposed).map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&proposed);
+    let diff = diff?;
+    Ok(match diff.find("\n@@") {
+        Some(at) => diff[at + 1..].to_string(),
+        None => diff,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rows of a terminal `width` columns wide showing `lines`, from grid
+    /// line 0
+    fn rows(width: usize, lines: &[&str]) -> Vec<TerminalRow> {
+        let mut rows = Vec::new();
+        for line in lines {
+            let chars: Vec<char> = line.chars().collect();
+            let chunks: Vec<String> = if chars.is_empty() {
+                vec![String::new()]
+            } else {
+                chars.chunks(width).map(|c| c.iter().collect()).collect()
+            };
+            let count = chunks.len();
+            for (i, text) in chunks.into_iter().enumerate() {
+                rows.push(TerminalRow {
+                    line: rows.len() as i32,
+                    text,
+                    wraps: i + 1 < count,
+                });
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn test_blocks_are_found_across_wrapped_rows() {
+        let lines = [
+            "Replace the function in src/foo.rs with this:",
+            "",
+            "```rust",
+            "fn long_function_name(argument: usize) -> usize {",
+            "    argument * 2",
+            "}",
+            "```",
+            "$ ",
+        ];
+        let blocks = extract(&rows(20, &lines));
+        assert_eq!(blocks.len(), 1);
+        let block = &blocks[0];
+        assert_eq!(block.info, "rust");
+        assert_eq!(
+            block.text,
+            "fn long_function_name(argument: usize) -> usize {\n    argument * 2\n}\n"
+        );
+        assert_eq!(block.path.as_deref(), Some("src/foo.rs"));
+        // The sentence took 3 rows and the blank line 1; the signature 3
+        assert_eq!((block.first_line, block.last_line), (4, 10));
+    }
+
+    #[test]
+    fn test_fences_and_their_paths() {
+        let lines = [
+            "  ```ts path=web/app.ts",
+            "  const a = 1;",
+            "    nested();",
+            "  ```",
+            "Either `one.rs` or `two.rs`:",
+            "~~~",
+            "x",
+            "~~~",
+            "```python:tools/run.py",
+            "print()",
+            "````",
+            "```",
+            "still printing",
+        ];
+        let blocks = extract(&rows(80, &lines));
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].text, "const a = 1;\n  nested();\n");
+        assert_eq!(blocks[0].path.as_deref(), Some("web/app.ts"));
+        // Two files named: ambiguous
+        assert_eq!(blocks[1].path, None);
+        // A longer closing fence closes the block; the last one is open
+        assert_eq!(blocks[2].path.as_deref(), Some("tools/run.py"));
+        assert_eq!(blocks[2].text, "print()\n");
+
+        assert_eq!(
+            path_candidates("See Node.js, e.g. `lib.rs` and src/x.rs:12.", true),
+            vec!["lib.rs", "src/x.rs"]
+        );
+        assert!(path_candidates("v1.2 https://a.io/x.rs", false).is_empty());
+    }
+
+    const FILE: &str =
+        "use std::fmt;\n\nfn a() {\n    1\n}\n\nimpl X {\n    fn b(&self) {\n        2\n    }\n}\n";
+
+    #[test]
+    fn test_full_file_and_fragments() {
+        // Starts and ends like the file: the whole file
+        let full = "use std::fmt;\n\nfn a() {\n    10\n}\n";
+        let applied = apply(Some(FILE), full).unwrap();
+        assert_eq!(applied.edit, Edit::Replace);
+        assert_eq!(applied.content, full);
+
+        // A method written without its indentation replaces the old one
+        let applied = apply(Some(FILE), "fn b(&self) {\n    20\n}\n").unwrap();
+        assert_eq!(applied.edit, Edit::Substitute { start: 7, end: 9 });
+        assert_eq!(
+            applied.content,
+            "use std::fmt;\n\nfn a() {\n    1\n}\n\nimpl X {\n    fn b(&self) {\n        20\n    }\n}\n"
+        );
+
+        // Not in the file: appended
+        let applied = apply(Some(FILE), "\nfn c() {}\n").unwrap();
+        assert_eq!(applied.edit, Edit::Append);
+        assert_eq!(applied.content, format!("{}fn c() {{}}\n", FILE));
+
+        // No file yet: created
+        assert_eq!(apply(None, "x\n").unwrap().edit, Edit::Create);
+        assert_eq!(apply(Some(""), "x\n").unwrap().edit, Edit::Replace);
+    }
+
+    #[test]
+    fn test_ambiguous_fragments_are_not_applied() {
+        // "}" alone starts in several places
+        assert_eq!(apply(Some(FILE), "}\n"), None);
+        // Starts at `fn a`, but nothing ends it as in the block
+        assert_eq!(apply(Some(FILE), "fn a() {\n    3\n    }\n"), None);
+        assert_eq!(apply(Some(FILE), "\n  \n"), None);
+    }
+
+    #[test]
+    fn test_crlf_files_keep_their_line_endings() {
+        let file = "a\r\nb\r\nc\r\n";
+        let applied = apply(Some(file), "b2\n").unwrap();
+        assert_eq!(applied.content, "a\r\nb\r\nc\r\nb2\r\n");
+        let applied = apply(Some(file), "b\n").unwrap();
+        assert_eq!(applied.content, file);
+    }
+
+    #[test]
+    fn test_suggestion_diff_and_write() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), FILE).unwrap();
+        let block = |path: &str, text: &str| CodeBlock {
+            info: "rust".to_string(),
+            text: text.to_string(),
+            first_line: 0,
+            last_line: 2,
+            path: Some(path.to_string()),
+        };
+
+        let suggestion =
+            Suggestion::prepare(dir.path(), &block("src/lib.rs", "fn a() {\n    5\n}\n")).unwrap();
+        assert_eq!(suggestion.edit, Edit::Substitute { start: 2, end: 4 });
+        assert!(suggestion.diff.starts_with("@@"));
+        assert!(suggestion.diff.contains("-    1\n+    5\n"));
+        suggestion.write().unwrap();
+        assert!(
+            std::fs::read_to_string(dir.path().join("src/lib.rs"))
+                .unwrap()
+                .contains("    5\n")
+        );
+        // Written already: the file is not what the diff was made from
+        assert!(suggestion.write().is_err());
+
+        let created = Suggestion::prepare(dir.path(), &block("src/new.rs", "fn n() {}\n")).unwrap();
+        assert_eq!(created.edit, Edit::Create);
+        created.write().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/new.rs")).unwrap(),
+            "fn n() {}\n"
+        );
+
+        assert!(Suggestion::prepare(dir.path(), &block("../x.rs", "x\n")).is_err());
+        assert!(Suggestion::prepare(dir.path(), &block("no/dir.rs", "x\n")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_refuses_a_path_that_turned_into_a_link_outside() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let block = CodeBlock {
+            info: "rust".to_string(),
+            text: "fn n() {}\n".to_string(),
+            first_line: 0,
+            last_line: 2,
+            path: Some("src/new.rs".to_string()),
+        };
+        let suggestion = Suggestion::prepare(dir.path(), &block).unwrap();
+
+        std::fs::remove_dir(dir.path().join("src")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("src")).unwrap();
+        assert!(suggestion.write().is_err());
+        assert!(!outside.path().join("new.rs").exists());
+    }
+}
@@ -0,0 +1,435 @@
+//! Recent commands of each terminal, for rerunning them
+//!
+//! A terminal's history gets every command line the shell reports through
+//! shell integration (OSC 133 prompt markers, see `Osc133Scanner`) and,
+//! while the shell reports none, every line Sashiki itself writes followed
+//! by Enter. Commands typed straight into a shell without integration are
+//! not seen. A command repeating the previous one is recorded once, and only
+//! the newest `MAX_COMMANDS` are kept.
+//!
+//! The histories of a session's terminals are kept in its state file (see
+//! `session_state`), rewritten whenever a command is recorded.
+
+use crate::session_state::SessionState;
+use crate::transcript;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Commands kept per terminal
+pub const MAX_COMMANDS: usize = 100;
+
+/// Echoed command line kept while waiting for the shell to run it; a longer
+/// one is not recorded
+const MAX_ECHO_BYTES: usize = 8 * 1024;
+
+/// OSC payload kept for parsing (OSC 133 payloads are short)
+const MAX_OSC_BYTES: usize = 4 * 1024;
+
+/// Commands of one terminal, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommandHistory(Vec<String>);
+
+impl CommandHistory {
+    /// Record `command` (trimmed). Blank commands and repeats of the last
+    /// one are skipped; returns whether the history changed.
+    pub fn push(&mut self, command: &str) -> bool {
+        let command = command.trim();
+        if command.is_empty() || self.last() == Some(command) {
+            return false;
+        }
+        self.0.push(command.to_string());
+        if self.0.len() > MAX_COMMANDS {
+            self.0.drain(..self.0.len() - MAX_COMMANDS);
+        }
+        true
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.0.last().map(String::as_str)
+    }
+
+    /// Commands newest first
+    pub fn recent(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().rev().map(String::as_str)
+    }
+}
+
+/// One terminal's history within its session's state
+#[derive(Debug, Clone)]
+pub struct TerminalHistory {
+    session: Rc<SessionState>,
+    /// Position of the terminal in the session
+    terminal: usize,
+}
+
+impl TerminalHistory {
+    pub fn new(session: Rc<SessionState>, terminal: usize) -> Self {
+        Self { session, terminal }
+    }
+
+    /// Record `command` and save the state file when the history changed
+    pub fn record(&self, command: &str) {
+        let terminal = self.terminal;
+        self.session.update(|state| {
+            if state.command_history.len() <= terminal {
+                state
+                    .command_history
+                    .resize_with(terminal + 1, CommandHistory::default);
+            }
+            state.command_history[terminal].push(command)
+        });
+    }
+
+    pub fn get(&self) -> CommandHistory {
+        self.session.read(|state| {
+            state
+                .command_history
+                .get(self.terminal)
+                .cloned()
+                .unwrap_or_default()
+        })
+    }
+}
+
+// --- Shell integration ---
+
+/// OSC 133 markers, in the order a shell sends them for each command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellMark {
+    /// `A`: the prompt starts
+    PromptStart,
+    /// `B`: the prompt ends and the command line starts
+    CommandStart,
+    /// `C`: the command runs and its output starts
+    OutputStart,
+    /// `D`: the command finished
+    CommandEnd,
+}
+
+impl ShellMark {
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "A" => Some(Self::PromptStart),
+            "B" => Some(Self::CommandStart),
+            "C" => Some(Self::OutputStart),
+            "D" => Some(Self::CommandEnd),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the command lines a shell reports with OSC 133 prompt markers.
+///
+/// The shell marks the end of its prompt with `133;B` and the start of the
+/// command's output with `133;C`. The command is the `cmdline_url=` or
+/// `cmdline=` parameter of `133;C` when the shell sends one, otherwise the
+/// last line echoed between the two markers (as `transcript::strip_ansi`
+/// reads it).
+#[derive(Debug, Default)]
+pub struct Osc133Scanner {
+    state: ScanState,
+    /// Payload of the OSC sequence being read
+    osc: Vec<u8>,
+    /// Output since `133;B`, None outside a command line
+    echo: Option<Vec<u8>>,
+    /// Whether any OSC 133 marker was seen
+    integrated: bool,
+    /// Markers that ended in the last `feed`, by the offset just past them
+    marks: Vec<(usize, ShellMark)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    #[default]
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+impl Osc133Scanner {
+    /// Scan more output (sequences may be split anywhere) and return the
+    /// commands that started running in it
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut commands = Vec::new();
+        self.marks.clear();
+        for (offset, &byte) in bytes.iter().enumerate() {
+            self.state = match (self.state, byte) {
+                (ScanState::Ground, 0x1b) => ScanState::Escape,
+                (ScanState::Ground, _) => {
+                    self.echo_byte(byte);
+                    ScanState::Ground
+                }
+                (ScanState::Escape, b']') => {
+                    self.osc.clear();
+                    ScanState::Osc
+                }
+                (ScanState::Escape, _) => {
+                    self.echo_byte(0x1b);
+                    self.echo_byte(byte);
+                    ScanState::Ground
+                }
+                (ScanState::Osc, 0x07) => {
+                    commands.extend(self.end_osc(offset + 1));
+                    ScanState::Ground
+                }
+                (ScanState::Osc, 0x1b) => ScanState::OscEscape,
+                (ScanState::Osc, _) => {
+                    if self.osc.len() < MAX_OSC_BYTES {
+                        self.osc.push(byte);
+                    }
+                    ScanState::Osc
+                }
+                (ScanState::OscEscape, b'\\') => {
+                    commands.extend(self.end_osc(offset + 1));
+                    ScanState::Ground
+                }
+                // Anything but ST after ESC cancels the sequence
+                (ScanState::OscEscape, _) => ScanState::Ground,
+            };
+        }
+        commands
+    }
+
+    /// Whether the shell has shown a marker, i.e. its commands are reported
+    pub fn is_integrated(&self) -> bool {
+        self.integrated
+    }
+
+    /// Markers that ended in the bytes of the last `feed`, with the offset
+    /// just past each
+    pub fn marks(&self) -> &[(usize, ShellMark)] {
+        &self.marks
+    }
+
+    fn echo_byte(&mut self, byte: u8) {
+        if let Some(echo) = &mut self.echo {
+            if echo.len() < MAX_ECHO_BYTES {
+                echo.push(byte);
+            } else {
+                self.echo = None;
+            }
+        }
+    }
+
+    /// Handle the OSC sequence just read, which ends at `end` in the bytes
+    /// being fed; the command if it marks one starting to run
+    fn end_osc(&mut self, end: usize) -> Option<String> {
+        let payload = String::from_utf8_lossy(&self.osc).into_owned();
+        let mut fields = payload.split(';');
+        if fields.next() != Some("133") {
+            return None;
+        }
+        self.integrated = true;
+        let code = fields.next();
+        if let Some(mark) = code.and_then(ShellMark::from_code) {
+            self.marks.push((end, mark));
+        }
+        match code {
+            Some("B") => {
+                self.echo = Some(Vec::new());
+                None
+            }
+            Some("C") => {
+                let echo = self.echo.take();
+                let reported = fields.find_map(|field| {
+                    field
+                        .strip_prefix("cmdline_url=")
+                        .map(percent_decode)
+                        .or_else(|| field.strip_prefix("cmdline=").map(str::to_string))
+                });
+                let command = reported.or_else(|| {
+                    let lines = transcript::strip_ansi(&echo?);
+                    lines.into_iter().rev().find(|line| !line.trim().is_empty())
+                })?;
+                let command = command.trim();
+                (!command.is_empty()).then(|| command.to_string())
+            }
+            _ => {
+                self.echo = None;
+                None
+            }
+        }
+    }
+}
+
+/// `%XX` escapes decoded (invalid escapes are kept as typed)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Commands a terminal's shell reported, handed from the PTY reader thread
+/// to the view
+#[derive(Debug, Default)]
+pub struct ReportedCommands {
+    commands: Mutex<Vec<String>>,
+    integrated: AtomicBool,
+}
+
+impl ReportedCommands {
+    /// Commands reported since the last call
+    pub fn take(&self) -> Vec<String> {
+        self.commands
+            .lock()
+            .map(|mut commands| std::mem::take(&mut *commands))
+            .unwrap_or_default()
+    }
+
+    /// Whether the shell reports its commands (OSC 133)
+    pub fn is_integrated(&self) -> bool {
+        self.integrated.load(Ordering::Acquire)
+    }
+}
+
+/// Reader that scans everything read for OSC 133 command reports
+pub struct CommandScanReader<R> {
+    inner: R,
+    scanner: Osc133Scanner,
+    reported: Arc<ReportedCommands>,
+}
+
+impl<R> CommandScanReader<R> {
+    pub fn new(inner: R, reported: Arc<ReportedCommands>) -> Self {
+        Self {
+            inner,
+            scanner: Osc133Scanner::default(),
+            reported,
+        }
+    }
+
+    /// Markers in the bytes of the last read, with the offset just past each
+    pub fn last_marks(&self) -> &[(usize, ShellMark)] {
+        self.scanner.marks()
+    }
+}
+
+impl<R: Read> Read for CommandScanReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let commands = self.scanner.feed(&buf[..n]);
+        if self.scanner.is_integrated() {
+            self.reported.integrated.store(true, Ordering::Release);
+        }
+        if !commands.is_empty()
+            && let Ok(mut reported) = self.reported.commands.lock()
+        {
+            reported.extend(commands);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_skips_consecutive_repeats() {
+        let mut history = CommandHistory::default();
+        assert!(history.push("cargo build"));
+        assert!(!history.push("cargo build"));
+        assert!(!history.push("  cargo build \n"));
+        assert!(!history.push("   "));
+        assert!(history.push("cargo test"));
+        // Only a repeat of the last command is skipped
+        assert!(history.push("cargo build"));
+        assert_eq!(
+            history.recent().collect::<Vec<_>>(),
+            vec!["cargo build", "cargo test", "cargo build"]
+        );
+        assert_eq!(history.last(), Some("cargo build"));
+
+        for i in 0..MAX_COMMANDS + 5 {
+            history.push(&format!("echo {}", i));
+        }
+        assert_eq!(history.recent().count(), MAX_COMMANDS);
+        assert_eq!(history.recent().last(), Some("echo 5"));
+    }
+
+    #[test]
+    fn test_osc133_reads_the_echoed_command() {
+        let mut scanner = Osc133Scanner::default();
+        assert!(!scanner.is_integrated());
+        let output = b"\x1b]133;A\x07~/repo $ \x1b]133;B\x07cargo tesx\x08t\r\n\x1b]133;C\x07running 3 tests\r\n\x1b]133;D;0\x07";
+        assert_eq!(scanner.feed(output), vec!["cargo test"]);
+        assert!(scanner.is_integrated());
+
+        // Markers split across reads, ended by ST, with colors in the echo
+        let output: &[u8] =
+            b"\x1b]133;A\x1b\\$ \x1b]133;B\x1b\\\x1b[1mgit\x1b[0m status\r\n\x1b]133;C\x1b\\";
+        let mut commands = Vec::new();
+        for chunk in output.chunks(3) {
+            commands.extend(scanner.feed(chunk));
+        }
+        assert_eq!(commands, vec!["git status"]);
+    }
+
+    #[test]
+    fn test_osc133_prefers_the_reported_command_line() {
+        let mut scanner = Osc133Scanner::default();
+        let output = b"\x1b]133;B\x07ls\r\n\x1b]133;C;cmdline_url=echo%20a%3Bb\x07";
+        assert_eq!(scanner.feed(output), vec!["echo a;b"]);
+
+        // A prompt without a command line, and other OSC sequences
+        let output = b"\x1b]0;title\x07\x1b]133;B\x07\r\n\x1b]133;C\x07\x1b]133;D\x07";
+        assert!(scanner.feed(output).is_empty());
+        // Output before a prompt is not taken for a command
+        assert!(scanner.feed(b"hello\r\n\x1b]133;C\x07").is_empty());
+    }
+
+    #[test]
+    fn test_osc133_marks_end_where_the_sequence_ends() {
+        let mut scanner = Osc133Scanner::default();
+        let output = b"\x1b]133;D;0\x07\x1b]133;A\x07$ \x1b]133;B\x1b\\ls";
+        scanner.feed(output);
+        assert_eq!(
+            scanner.marks(),
+            &[
+                (10, ShellMark::CommandEnd),
+                (18, ShellMark::PromptStart),
+                (29, ShellMark::CommandStart),
+            ]
+        );
+        assert_eq!(&output[18..20], b"$ ");
+
+        // A marker split across feeds ends in the feed that completes it;
+        // other OSC 133 codes are not marks
+        scanner.feed(b"\r\n\x1b]13");
+        assert!(scanner.marks().is_empty());
+        scanner.feed(b"3;C\x07out\x1b]133;P;k=i\x07");
+        assert_eq!(scanner.marks(), &[(4, ShellMark::OutputStart)]);
+    }
+
+    #[test]
+    fn test_scan_reader_reports_commands() {
+        let reported = Arc::new(ReportedCommands::default());
+        let output = &b"\x1b]133;B\x07make\r\n\x1b]133;C\x07done\r\n"[..];
+        let mut reader = CommandScanReader::new(output, reported.clone());
+        let mut read = Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        assert_eq!(read, output);
+        assert!(reported.is_integrated());
+        assert_eq!(reported.take(), vec!["make"]);
+        assert!(reported.take().is_empty());
+    }
+}
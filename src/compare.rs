@@ -0,0 +1,207 @@
+//! Comparing a file between two sessions
+//!
+//! Agents working on the same problem in different worktrees often end up
+//! touching the same file. A comparison diffs the copies of one path in two
+//! worktrees: the other session's copy on the left and the active session's
+//! on the right, until the sides are swapped. A copy missing on one side
+//! shows as the whole file added or deleted.
+
+use crate::git;
+use crate::worktree_guard;
+use std::path::{Path, PathBuf};
+
+/// Stands in for the missing copy of a file
+const NULL_DEVICE: &str = if cfg!(windows) { "NUL" } else { "/dev/null" };
+
+/// One session's side of a comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompareSide {
+    /// Branch of the session (its name when detached)
+    pub label: String,
+    pub worktree: PathBuf,
+}
+
+/// The copies of one file in two worktrees
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// Relative to both worktrees
+    pub path: PathBuf,
+    pub left: CompareSide,
+    pub right: CompareSide,
+}
+
+impl Comparison {
+    /// Compare `file` (a full path in the worktree of `right`) with its copy
+    /// in the worktree of `left`; None when `file` is not in `right`'s
+    pub fn new(file: &Path, left: CompareSide, right: CompareSide) -> Option<Self> {
+        let path = file.strip_prefix(&right.worktree).ok()?;
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+        Some(Self {
+            path: path.to_path_buf(),
+            left,
+            right,
+        })
+    }
+
+    pub fn swapped(self) -> Self {
+        Self {
+            path: self.path,
+            left: self.right,
+            right: self.left,
+        }
+    }
+
+    /// Header naming both sides, e.g. "src/lib.rs: feature-a ↔ feature-b"
+    pub fn title(&self) -> String {
+        format!(
+            "{}: {} ↔ {}",
+            self.path.display(),
+            self.left.label,
+            self.right.label
+        )
+    }
+
+    /// Full path of the copy on `side`. Refused when it would lead out of
+    /// that worktree (through a symlink, say) or is a directory.
+    pub fn copy(&self, side: &CompareSide) -> Result<PathBuf, String> {
+        let path = worktree_guard::check(&side.worktree, &self.path).map_err(|e| e.to_string())?;
+        if path.is_dir() {
+            return Err(format!(
+                "{} is a directory in {}",
+                self.path.display(),
+                side.label
+            ));
+        }
+        Ok(path)
+    }
+
+    /// Diff from the left copy to the right one; empty when they are the
+    /// same
+    pub fn diff(&self) -> Result<String, String> {
+        let left = self.copy(&self.left)?;
+        let right = self.copy(&self.right)?;
+        let null = Path::new(NULL_DEVICE);
+        let old = if left.exists() { left.as_path() } else { null };
+        let new = if right.exists() {
+            right.as_path()
+        } else {
+            null
+        };
+        if old == null && new == null {
+            return Err(format!(
+                "{} exists in neither {} nor {}",
+                self.path.display(),
+                self.left.label,
+                self.right.label
+            ));
+        }
+        git::diff_files(old, new).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two worktrees side by side with the files of `left` and `right`
+    fn worktrees(left: &[(&str, &str)], right: &[(&str, &str)]) -> (tempfile::TempDir, Comparison) {
+        let dir = tempfile::tempdir().unwrap();
+        let side = |name: &str, files: &[(&str, &str)]| {
+            let worktree = dir.path().join(name);
+            std::fs::create_dir_all(worktree.join("src")).unwrap();
+            for (path, text) in files {
+                std::fs::write(worktree.join(path), text).unwrap();
+            }
+            CompareSide {
+                label: name.to_string(),
+                worktree,
+            }
+        };
+        let left = side("feature-a", left);
+        let right = side("feature-b", right);
+        let file = right.worktree.join("src/lib.rs");
+        let comparison = Comparison::new(&file, left, right).unwrap();
+        (dir, comparison)
+    }
+
+    /// Added and removed lines of a diff, without the file headers
+    fn changes(diff: &str) -> Vec<&str> {
+        diff.lines()
+            .filter(|line| {
+                (line.starts_with('+') && !line.starts_with("+++"))
+                    || (line.starts_with('-') && !line.starts_with("---"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_path_is_resolved_in_both_worktrees() {
+        let (dir, comparison) = worktrees(&[("src/lib.rs", "a\n")], &[("src/lib.rs", "b\n")]);
+        assert_eq!(comparison.path, PathBuf::from("src/lib.rs"));
+        assert_eq!(
+            comparison.copy(&comparison.left),
+            Ok(dir.path().join("feature-a/src/lib.rs"))
+        );
+        assert_eq!(comparison.title(), "src/lib.rs: feature-a ↔ feature-b");
+        assert_eq!(changes(&comparison.diff().unwrap()), ["-a", "+b"]);
+
+        let swapped = comparison.clone().swapped();
+        assert_eq!(swapped.path, comparison.path);
+        assert_eq!(swapped.title(), "src/lib.rs: feature-b ↔ feature-a");
+        assert_eq!(changes(&swapped.diff().unwrap()), ["-b", "+a"]);
+
+        // Files outside the right worktree, or the worktree itself, have no
+        // counterpart
+        let (left, right) = (comparison.left.clone(), comparison.right.clone());
+        let outside = left.worktree.join("src/lib.rs");
+        assert_eq!(Comparison::new(&outside, left.clone(), right.clone()), None);
+        assert_eq!(Comparison::new(&right.worktree.clone(), left, right), None);
+    }
+
+    #[test]
+    fn test_identical_copies_have_an_empty_diff() {
+        let (_dir, comparison) =
+            worktrees(&[("src/lib.rs", "same\n")], &[("src/lib.rs", "same\n")]);
+        assert_eq!(comparison.diff(), Ok(String::new()));
+    }
+
+    #[test]
+    fn test_a_copy_missing_on_one_side_is_all_added_or_deleted() {
+        let (_dir, comparison) = worktrees(&[], &[("src/lib.rs", "one\ntwo\n")]);
+        assert_eq!(changes(&comparison.diff().unwrap()), ["+one", "+two"]);
+        let swapped = comparison.swapped();
+        assert_eq!(changes(&swapped.diff().unwrap()), ["-one", "-two"]);
+
+        let (_dir, comparison) = worktrees(&[], &[]);
+        let message = comparison.diff().unwrap_err();
+        assert_eq!(
+            message,
+            "src/lib.rs exists in neither feature-a nor feature-b"
+        );
+
+        // A directory where the other side has a file is refused
+        let (_dir, comparison) = worktrees(&[], &[("src/lib.rs", "x\n")]);
+        std::fs::create_dir_all(comparison.left.worktree.join("src/lib.rs")).unwrap();
+        assert!(comparison.diff().unwrap_err().contains("is a directory"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copies_leaving_a_worktree_are_refused() {
+        let (dir, comparison) = worktrees(&[], &[("src/lib.rs", "x\n")]);
+        std::fs::write(dir.path().join("secret"), "key\n").unwrap();
+        std::os::unix::fs::symlink(
+            dir.path().join("secret"),
+            comparison.left.worktree.join("src/lib.rs"),
+        )
+        .unwrap();
+        assert!(
+            comparison
+                .diff()
+                .unwrap_err()
+                .contains("symlink leading outside the worktree")
+        );
+    }
+}
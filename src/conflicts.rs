@@ -0,0 +1,185 @@
+//! Overlap detection between parallel worktrees
+//!
+//! For each linked worktree, collects the files changed since its merge-base
+//! with the main worktree (commits and uncommitted edits to tracked files),
+//! then intersects those sets pairwise. Worktrees whose history is unrelated
+//! to main are skipped with a note instead of being compared.
+
+use crate::git::GitRepo;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Files changed in two worktrees at the same time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    /// Path of the other worktree
+    pub other: PathBuf,
+    /// Relative paths changed on both sides (sorted)
+    pub files: Vec<PathBuf>,
+}
+
+/// Result of a conflict scan, keyed by worktree path
+#[derive(Debug, Clone, Default)]
+pub struct ConflictReport {
+    pub overlaps: HashMap<PathBuf, Vec<Overlap>>,
+    /// Merge-base with main per compared worktree (used to diff each side)
+    pub bases: HashMap<PathBuf, String>,
+    /// Worktrees that could not be compared, with the reason
+    pub skipped: HashMap<PathBuf, String>,
+}
+
+impl ConflictReport {
+    pub fn overlaps_for(&self, worktree: &Path) -> &[Overlap] {
+        self.overlaps
+            .get(worktree)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Number of distinct files this worktree shares with any other worktree
+    pub fn overlap_file_count(&self, worktree: &Path) -> usize {
+        self.overlaps_for(worktree)
+            .iter()
+            .flat_map(|o| o.files.iter())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    pub fn skipped_reason(&self, worktree: &Path) -> Option<&str> {
+        self.skipped.get(worktree).map(|s| s.as_str())
+    }
+
+    pub fn base_for(&self, worktree: &Path) -> Option<&str> {
+        self.bases.get(worktree).map(|s| s.as_str())
+    }
+}
+
+/// Intersect changed-file sets pairwise.
+///
+/// Only worktrees with at least one shared file appear in the result.
+pub fn find_overlaps(changes: &[(PathBuf, BTreeSet<PathBuf>)]) -> HashMap<PathBuf, Vec<Overlap>> {
+    let mut result: HashMap<PathBuf, Vec<Overlap>> = HashMap::new();
+
+    for (i, (path_a, files_a)) in changes.iter().enumerate() {
+        for (path_b, files_b) in changes.iter().skip(i + 1) {
+            let shared: Vec<PathBuf> = files_a.intersection(files_b).cloned().collect();
+            if shared.is_empty() {
+                continue;
+            }
+            result.entry(path_a.clone()).or_default().push(Overlap {
+                other: path_b.clone(),
+                files: shared.clone(),
+            });
+            result.entry(path_b.clone()).or_default().push(Overlap {
+                other: path_a.clone(),
+                files: shared,
+            });
+        }
+    }
+
+    result
+}
+
+/// Compare every linked worktree against the main worktree.
+///
+/// Runs several git commands per worktree; call from a background task.
+pub fn scan(main_workdir: &Path, worktrees: &[PathBuf]) -> ConflictReport {
+    let mut report = ConflictReport::default();
+
+    let main_commit = match GitRepo::open(main_workdir).and_then(|r| r.head_commit()) {
+        Ok(hash) => hash,
+        Err(_) => return report,
+    };
+
+    let mut changes = Vec::new();
+    for path in worktrees {
+        let repo = match GitRepo::open(path) {
+            Ok(r) => r,
+            Err(e) => {
+                report.skipped.insert(path.clone(), e.to_string());
+                continue;
+            }
+        };
+
+        let base = match repo.merge_base("HEAD", &main_commit) {
+            Ok(Some(base)) => base,
+            Ok(None) => {
+                report
+                    .skipped
+                    .insert(path.clone(), "No common history with main".to_string());
+                continue;
+            }
+            Err(e) => {
+                report.skipped.insert(path.clone(), e.to_string());
+                continue;
+            }
+        };
+
+        match repo.diff_names_since(&base) {
+            Ok(files) => {
+                changes.push((path.clone(), files.into_iter().collect()));
+                report.bases.insert(path.clone(), base);
+            }
+            Err(e) => {
+                report.skipped.insert(path.clone(), e.to_string());
+            }
+        }
+    }
+
+    report.overlaps = find_overlaps(&changes);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(paths: &[&str]) -> BTreeSet<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_find_overlaps_none() {
+        let changes = vec![
+            (PathBuf::from("/wt/a"), set(&["src/a.rs"])),
+            (PathBuf::from("/wt/b"), set(&["src/b.rs"])),
+        ];
+        assert!(find_overlaps(&changes).is_empty());
+    }
+
+    #[test]
+    fn test_find_overlaps_is_symmetric() {
+        let changes = vec![
+            (PathBuf::from("/wt/a"), set(&["src/lib.rs", "src/a.rs"])),
+            (PathBuf::from("/wt/b"), set(&["src/lib.rs", "src/b.rs"])),
+        ];
+        let overlaps = find_overlaps(&changes);
+
+        let a = &overlaps[Path::new("/wt/a")];
+        assert_eq!(a.len(), 1);
+        assert_eq!(a[0].other, PathBuf::from("/wt/b"));
+        assert_eq!(a[0].files, vec![PathBuf::from("src/lib.rs")]);
+
+        let b = &overlaps[Path::new("/wt/b")];
+        assert_eq!(b[0].other, PathBuf::from("/wt/a"));
+        assert_eq!(b[0].files, vec![PathBuf::from("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_overlap_file_count_deduplicates_across_worktrees() {
+        let changes = vec![
+            (PathBuf::from("/wt/a"), set(&["Cargo.toml", "src/lib.rs"])),
+            (PathBuf::from("/wt/b"), set(&["Cargo.toml"])),
+            (PathBuf::from("/wt/c"), set(&["Cargo.toml", "src/lib.rs"])),
+        ];
+        let report = ConflictReport {
+            overlaps: find_overlaps(&changes),
+            ..Default::default()
+        };
+
+        assert_eq!(report.overlaps_for(Path::new("/wt/a")).len(), 2);
+        assert_eq!(report.overlap_file_count(Path::new("/wt/a")), 2);
+        assert_eq!(report.overlap_file_count(Path::new("/wt/b")), 1);
+        assert_eq!(report.overlap_file_count(Path::new("/wt/missing")), 0);
+    }
+}
@@ -0,0 +1,974 @@
+//! Local control server for scripts and dashboards
+//!
+//! With `sashiki.remote.enabled` the app listens on 127.0.0.1
+//! (`sashiki.remote.port`) for HTTP/1.1, one request per connection:
+//!
+//! ```text
+//! GET  /state                     the export document (see `export`)
+//! GET  /sessions/{name}           one session of it
+//! GET  /sessions/{name}/files     the session's changed files
+//! POST /sessions/{name}/activate  show the session
+//! POST /sessions/{name}/send      {"text": "...", "enter": false}
+//! POST /worktrees                 {"branch": "...", "base": "...", "description": "..."}
+//! GET  /events                    WebSocket stream of hook events
+//! ```
+//!
+//! Every request must carry `Authorization: Bearer <token>`, the token
+//! being `sashiki.remote.token` (generated into the user's git config the
+//! first time the server starts). A browser cannot set that header on a
+//! WebSocket, so `/events` also takes the token offered as a subprotocol,
+//! along with `sashiki` (which the handshake picks):
+//! `new WebSocket("ws://127.0.0.1:<port>/events", ["sashiki", "bearer.<token>"])`.
+//! Sessions are named as in the sidebar and belong to the repository shown.
+//!
+//! The server only parses requests: the app answers them on its UI thread
+//! (see `Pending`) with the code its sidebar and dialogs use. Text sent to
+//! a terminal is pasted like the clipboard, with control characters other
+//! than tabs and line breaks removed so it cannot act as keys or end a
+//! bracketed paste early; `enter` presses Enter after it.
+//!
+//! Each hook event (see `hooks`) of any open repository goes to the event
+//! streams as a text message such as
+//! `{"event":"session_idle","session":"feature-a","branch":"feature/a","path":"…","repo":"…"}`,
+//! `repo` being the main worktree of the repository it happened in.
+
+use crate::hooks::{self, HookEvent, HookSubject};
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Longest request line or header line taken
+const MAX_LINE: usize = 8 * 1024;
+
+const MAX_HEADERS: usize = 64;
+
+/// Longest request body taken
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Connections served at once beyond which more are refused
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a client may take to send its request (or take a reply)
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the app may take to answer a request
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Quiet time after which an event stream sends a ping (which also finds
+/// clients that went away)
+const PING_AFTER: Duration = Duration::from_secs(30);
+
+/// How often an event stream looks whether the server stopped
+const STOP_POLL: Duration = Duration::from_millis(200);
+
+/// Appended to a WebSocket key before hashing it (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Subprotocol picked for event streams whose client offers it
+const EVENTS_PROTOCOL: &str = "sashiki";
+/// Prefix of the subprotocol carrying the token, for browsers
+const TOKEN_PROTOCOL: &str = "bearer.";
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+
+/// What a client asks the app for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlRequest {
+    /// The whole export document
+    State,
+    Session {
+        name: String,
+    },
+    ChangedFiles {
+        name: String,
+    },
+    /// Show the session, as a click in the sidebar does
+    Activate {
+        name: String,
+    },
+    /// Paste `text` into the session's terminal (started if need be)
+    Send {
+        name: String,
+        text: String,
+        enter: bool,
+    },
+    /// Create a worktree for `branch` as the Open dialog does
+    CreateWorktree {
+        branch: String,
+        /// Commit, tag or branch to start from (HEAD when empty)
+        base: String,
+        description: String,
+    },
+}
+
+/// Status and JSON body of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn json(value: &impl Serialize) -> Self {
+        Self::with_status(200, value)
+    }
+
+    /// Something was started that finishes later
+    pub fn accepted(value: &impl Serialize) -> Self {
+        Self::with_status(202, value)
+    }
+
+    pub fn ok() -> Self {
+        Self::json(&serde_json::json!({ "ok": true }))
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::with_status(status, &serde_json::json!({ "error": message.into() }))
+    }
+
+    pub fn not_found(session: &str) -> Self {
+        Self::error(404, format!("No session named '{}'", session))
+    }
+
+    fn with_status(status: u16, value: &impl Serialize) -> Self {
+        Self {
+            status,
+            // Plain structs with string keys always serialize
+            body: serde_json::to_string(value).expect("control reply serializes"),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "Error",
+        }
+    }
+
+    fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let challenge = if self.status == 401 {
+            "WWW-Authenticate: Bearer\r\n"
+        } else {
+            ""
+        };
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+             {}Connection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            challenge,
+            self.body
+        )?;
+        out.flush()
+    }
+}
+
+/// A request waiting for the app's answer
+pub struct Pending {
+    pub request: ControlRequest,
+    reply: mpsc::Sender<Reply>,
+}
+
+impl Pending {
+    /// `request` with the receiver its reply arrives on
+    pub fn new(request: ControlRequest) -> (Self, mpsc::Receiver<Reply>) {
+        let (reply, answer) = mpsc::channel();
+        (Self { request, reply }, answer)
+    }
+
+    pub fn answer(self, reply: Reply) {
+        // The client may have given up waiting
+        let _ = self.reply.send(reply);
+    }
+}
+
+/// Answers requests: the app (see `forward_to`), or a fake in tests
+pub type Dispatch = Arc<dyn Fn(ControlRequest) -> Reply + Send + Sync>;
+
+/// Hand requests to the app through `app` and wait for its answer
+pub fn forward_to(app: smol::channel::Sender<Pending>) -> Dispatch {
+    Arc::new(move |request| {
+        let (pending, answer) = Pending::new(request);
+        if app.send_blocking(pending).is_err() {
+            return Reply::error(503, "Sashiki is shutting down");
+        }
+        answer
+            .recv_timeout(REPLY_TIMEOUT)
+            .unwrap_or_else(|_| Reply::error(503, "Sashiki did not answer"))
+    })
+}
+
+/// A fresh token of 64 hex digits, read from the system's random source
+pub fn generate_token() -> io::Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// `text` with the control characters that could act as keys removed;
+/// tabs and line breaks stay
+pub fn terminal_text(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+/// The running server; dropping it stops it
+pub struct ControlServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+    accept: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Listen on 127.0.0.1:`port` (0 = any free port) and answer requests
+    /// carrying `token` with `dispatch`
+    pub fn start(port: u16, token: String, dispatch: Dispatch) -> io::Result<Self> {
+        if token.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the token is empty",
+            ));
+        }
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let address = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = Server {
+            token: token.into(),
+            dispatch,
+            stop: stop.clone(),
+            connections: Arc::new(AtomicUsize::new(0)),
+        };
+        let accept = std::thread::Builder::new()
+            .name("control".to_string())
+            .spawn(move || server.accept(listener))?;
+        Ok(Self {
+            address,
+            stop,
+            accept: Some(accept),
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag; event streams see it
+        // within `STOP_POLL` and close
+        let _ = TcpStream::connect_timeout(&self.address, Duration::from_secs(1));
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+    }
+}
+
+/// What the threads serving connections share
+#[derive(Clone)]
+struct Server {
+    token: Arc<str>,
+    dispatch: Dispatch,
+    stop: Arc<AtomicBool>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl Server {
+    fn accept(self, listener: TcpListener) {
+        for stream in listener.incoming() {
+            if self.stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            if self.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                self.connections.fetch_sub(1, Ordering::SeqCst);
+                let _ = Reply::error(503, "Too many connections").write_to(&mut stream);
+                continue;
+            }
+            let server = self.clone();
+            let spawned = std::thread::Builder::new()
+                .name("control connection".to_string())
+                .spawn(move || {
+                    server.serve(stream);
+                    server.connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            if spawned.is_err() {
+                self.connections.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn serve(&self, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+        let request = read_request(&mut BufReader::new(&stream));
+        let reply = match request {
+            Err(reply) => reply,
+            Ok(request) if !authorized(&request, &self.token) => {
+                Reply::error(401, "Missing or wrong bearer token")
+            }
+            Ok(request) => match route(&request) {
+                Err(reply) => reply,
+                Ok(Route::App(request)) => (self.dispatch)(request),
+                Ok(Route::Events) => {
+                    let _ = self.stream_events(stream, &request);
+                    return;
+                }
+            },
+        };
+        let _ = reply.write_to(&mut stream);
+    }
+
+    /// Answer the WebSocket handshake of `request`, then send hook events
+    /// until the client goes away or the server stops
+    fn stream_events(&self, mut stream: TcpStream, request: &HttpRequest) -> io::Result<()> {
+        let upgrade = request
+            .header("upgrade")
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        let key = match request.header("sec-websocket-key") {
+            Some(key) if upgrade => key,
+            _ => {
+                return Reply::error(400, "GET /events takes a WebSocket upgrade")
+                    .write_to(&mut stream);
+            }
+        };
+        let protocol = if offered_protocols(request).any(|p| p == EVENTS_PROTOCOL) {
+            format!("Sec-WebSocket-Protocol: {}\r\n", EVENTS_PROTOCOL)
+        } else {
+            String::new()
+        };
+        let events = hooks::subscribe();
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n{}\r\n",
+            websocket_accept(key),
+            protocol
+        )?;
+        let mut quiet_since = Instant::now();
+        loop {
+            if self.stop.load(Ordering::SeqCst) {
+                // 1001: going away
+                return stream.write_all(&websocket_frame(OPCODE_CLOSE, &1001u16.to_be_bytes()));
+            }
+            match events.recv_timeout(STOP_POLL) {
+                Ok((event, subject)) => {
+                    let message = event_message(event, &subject);
+                    stream.write_all(&websocket_frame(OPCODE_TEXT, message.as_bytes()))?;
+                    quiet_since = Instant::now();
+                }
+                Err(RecvTimeoutError::Timeout) if quiet_since.elapsed() >= PING_AFTER => {
+                    stream.write_all(&websocket_frame(OPCODE_PING, &[]))?;
+                    quiet_since = Instant::now();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// A request as read off the connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HttpRequest {
+    method: String,
+    /// Without the query string
+    path: String,
+    /// Names in lowercase
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// One line without its line break; too long or cut off is a bad request
+fn read_line(reader: &mut impl BufRead) -> Result<String, Reply> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .map_err(|e| Reply::error(400, format!("Failed to read the request: {}", e)))?;
+    if line.last() != Some(&b'\n') {
+        return Err(Reply::error(400, "Incomplete or overlong request line"));
+    }
+    let line = String::from_utf8(line).map_err(|_| Reply::error(400, "Request is not UTF-8"))?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<HttpRequest, Reply> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Reply::error(400, "Malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(Reply::error(400, "Only HTTP/1.x is spoken here"));
+    }
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(Reply::error(400, "Too many headers"));
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| Reply::error(400, "Malformed header"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let mut request = HttpRequest {
+        method: method.to_string(),
+        path,
+        headers,
+        body: Vec::new(),
+    };
+
+    if request.header("transfer-encoding").is_some() {
+        return Err(Reply::error(400, "Send the body with a Content-Length"));
+    }
+    let length: usize = match request.header("content-length") {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Reply::error(400, "Malformed Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(Reply::error(413, "Request body is too large"));
+    }
+    request.body = vec![0; length];
+    reader
+        .read_exact(&mut request.body)
+        .map_err(|_| Reply::error(400, "Request body is shorter than its Content-Length"))?;
+    Ok(request)
+}
+
+/// Whether `request` carries `token` as its bearer token, or offers it as
+/// the WebSocket subprotocol `bearer.<token>`
+fn authorized(request: &HttpRequest, token: &str) -> bool {
+    let bearer = request
+        .header("authorization")
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, given)| given.trim());
+    let offered = offered_protocols(request).filter_map(|p| p.strip_prefix(TOKEN_PROTOCOL));
+    bearer
+        .into_iter()
+        .chain(offered)
+        .any(|given| same_bytes(given.as_bytes(), token.as_bytes()))
+}
+
+/// The WebSocket subprotocols `request` offers
+fn offered_protocols(request: &HttpRequest) -> impl Iterator<Item = &str> {
+    request
+        .header("sec-websocket-protocol")
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+}
+
+/// Equality taking as long wherever the first difference is
+fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Where a request goes
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    App(ControlRequest),
+    Events,
+}
+
+#[derive(Deserialize)]
+struct SendBody {
+    text: String,
+    #[serde(default)]
+    enter: bool,
+}
+
+#[derive(Deserialize)]
+struct WorktreeBody {
+    branch: String,
+    #[serde(default)]
+    base: String,
+    #[serde(default)]
+    description: String,
+}
+
+fn route(request: &HttpRequest) -> Result<Route, Reply> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| Reply::error(400, "Malformed path"))?;
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let session = |name: &str| name.to_string();
+    let request = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["state"]) => ControlRequest::State,
+        ("GET", ["events"]) => return Ok(Route::Events),
+        ("GET", ["sessions", name]) => ControlRequest::Session {
+            name: session(name),
+        },
+        ("GET", ["sessions", name, "files"]) => ControlRequest::ChangedFiles {
+            name: session(name),
+        },
+        ("POST", ["sessions", name, "activate"]) => ControlRequest::Activate {
+            name: session(name),
+        },
+        ("POST", ["sessions", name, "send"]) => {
+            let body: SendBody = parse_body(request)?;
+            ControlRequest::Send {
+                name: session(name),
+                text: terminal_text(&body.text),
+                enter: body.enter,
+            }
+        }
+        ("POST", ["worktrees"]) => {
+            let body: WorktreeBody = parse_body(request)?;
+            ControlRequest::CreateWorktree {
+                branch: body.branch.trim().to_string(),
+                base: body.base.trim().to_string(),
+                description: body.description.trim().to_string(),
+            }
+        }
+        (
+            _,
+            ["state" | "events" | "worktrees"]
+            | ["sessions", _]
+            | ["sessions", _, "files" | "activate" | "send"],
+        ) => {
+            return Err(Reply::error(
+                405,
+                format!("{} is not allowed here", request.method),
+            ));
+        }
+        _ => return Err(Reply::error(404, format!("Nothing at {}", request.path))),
+    };
+    Ok(Route::App(request))
+}
+
+fn parse_body<T: DeserializeOwned>(request: &HttpRequest) -> Result<T, Reply> {
+    serde_json::from_slice(&request.body)
+        .map_err(|e| Reply::error(400, format!("Malformed JSON body: {}", e)))
+}
+
+/// `%XX` escapes of a path segment decoded; None when they do not make UTF-8
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// An event as sent on the event streams
+#[derive(Serialize)]
+struct EventMessage<'a> {
+    event: &'static str,
+    session: &'a str,
+    branch: Option<&'a str>,
+    path: Option<&'a Path>,
+    repo: Option<&'a Path>,
+}
+
+fn event_message(event: HookEvent, subject: &HookSubject) -> String {
+    let message = EventMessage {
+        event: event.name(),
+        session: &subject.session,
+        branch: subject.branch.as_deref(),
+        path: subject.path.as_deref(),
+        repo: subject.repo.as_deref(),
+    };
+    serde_json::to_string(&message).expect("event message serializes")
+}
+
+/// `Sec-WebSocket-Accept` answering `key`
+fn websocket_accept(key: &str) -> String {
+    let mut sha1 = sha1_smol::Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(sha1.digest().bytes())
+}
+
+/// A final, unmasked frame (servers do not mask)
+fn websocket_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    const TOKEN: &str = "s3cret-token";
+
+    fn parse(raw: &str) -> Result<HttpRequest, Reply> {
+        read_request(&mut Cursor::new(raw.as_bytes().to_vec()))
+    }
+
+    fn post(path: &str, body: &str) -> HttpRequest {
+        parse(&format!(
+            "POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        ))
+        .unwrap()
+    }
+
+    /// A server whose app records the requests it is asked and answers
+    /// with a fixed document
+    fn serve() -> (ControlServer, Arc<Mutex<Vec<ControlRequest>>>) {
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let recorded = asked.clone();
+        let dispatch: Dispatch = Arc::new(move |request| {
+            recorded.lock().unwrap().push(request.clone());
+            match request {
+                ControlRequest::State => Reply::json(&serde_json::json!({ "schema_version": 1 })),
+                ControlRequest::Send { name, .. } if name != "feature-a" => Reply::not_found(&name),
+                _ => Reply::ok(),
+            }
+        });
+        let server = ControlServer::start(0, TOKEN.to_string(), dispatch).unwrap();
+        (server, asked)
+    }
+
+    /// Send `raw` and read the whole response: status and body
+    fn exchange(server: &ControlServer, raw: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: &str) -> String {
+        let auth = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_requests_are_routed() {
+        let get = |path: &str| route(&parse(&format!("GET {} HTTP/1.1\r\n\r\n", path)).unwrap());
+        assert_eq!(get("/state"), Ok(Route::App(ControlRequest::State)));
+        assert_eq!(get("/events"), Ok(Route::Events));
+        assert_eq!(
+            get("/sessions/feature%2Da/files?x=1"),
+            Ok(Route::App(ControlRequest::ChangedFiles {
+                name: "feature-a".to_string()
+            }))
+        );
+        assert_eq!(get("/nothing").unwrap_err().status, 404);
+        assert_eq!(get("/sessions/a/send").unwrap_err().status, 405);
+        assert_eq!(get("/sessions/%ZZ").unwrap_err().status, 400);
+
+        assert_eq!(
+            route(&post(
+                "/worktrees",
+                r#"{"branch": " feature/b ", "description": "Try b"}"#
+            )),
+            Ok(Route::App(ControlRequest::CreateWorktree {
+                branch: "feature/b".to_string(),
+                base: String::new(),
+                description: "Try b".to_string(),
+            }))
+        );
+        assert_eq!(route(&post("/worktrees", "{}")).unwrap_err().status, 400);
+        assert_eq!(
+            route(&post("/sessions/main/activate", "")),
+            Ok(Route::App(ControlRequest::Activate {
+                name: "main".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_malformed_requests_are_refused() {
+        assert_eq!(parse("GET /state\r\n\r\n").unwrap_err().status, 400);
+        assert_eq!(parse("GET /state HTTP/2\r\n\r\n").unwrap_err().status, 400);
+        // Cut off before the end of the headers
+        assert_eq!(
+            parse("GET /state HTTP/1.1\r\nHost: x").unwrap_err().status,
+            400
+        );
+        assert_eq!(
+            parse("POST /worktrees HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}")
+                .unwrap_err()
+                .status,
+            400
+        );
+        let huge = format!(
+            "POST /worktrees HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(parse(&huge).unwrap_err().status, 413);
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(parse(&long_line).unwrap_err().status, 400);
+    }
+
+    #[test]
+    fn test_every_request_needs_the_token() {
+        let (server, asked) = serve();
+
+        let (status, body) = exchange(&server, &request("GET", "/state", None, ""));
+        assert_eq!(status, 401);
+        assert!(body.contains("bearer token"));
+        let (status, _) = exchange(&server, &request("GET", "/state", Some("guess"), ""));
+        assert_eq!(status, 401);
+        let wrong_scheme = "GET /state HTTP/1.1\r\nAuthorization: Basic s3cret-token\r\n\r\n";
+        assert_eq!(exchange(&server, wrong_scheme).0, 401);
+        let (status, _) = exchange(
+            &server,
+            &request(
+                "POST",
+                "/sessions/feature-a/send",
+                None,
+                r#"{"text":"rm -rf ~"}"#,
+            ),
+        );
+        assert_eq!(status, 401);
+        let wrong_protocol =
+            "GET /state HTTP/1.1\r\nSec-WebSocket-Protocol: sashiki, guess\r\n\r\n";
+        assert_eq!(exchange(&server, wrong_protocol).0, 401);
+        // Refused before the app hears of them
+        assert!(asked.lock().unwrap().is_empty());
+
+        let (status, body) = exchange(&server, &request("GET", "/state", Some(TOKEN), ""));
+        assert_eq!((status, body.as_str()), (200, r#"{"schema_version":1}"#));
+        assert_eq!(*asked.lock().unwrap(), [ControlRequest::State]);
+    }
+
+    #[test]
+    fn test_sent_text_reaches_the_app_defanged() {
+        let (server, asked) = serve();
+        // ESC [201~ would end a bracketed paste, so what follows would run
+        // as typed; Ctrl-C would interrupt the agent
+        let body = r#"{"text": "fix the tests\u001b[201~\u0003\nthen commit\tnow", "enter": true}"#;
+        let (status, _) = exchange(
+            &server,
+            &request("POST", "/sessions/feature-a/send", Some(TOKEN), body),
+        );
+        assert_eq!(status, 200);
+        assert_eq!(
+            *asked.lock().unwrap(),
+            [ControlRequest::Send {
+                name: "feature-a".to_string(),
+                text: "fix the tests[201~\nthen commit\tnow".to_string(),
+                enter: true,
+            }]
+        );
+
+        // The app's answer is passed on
+        let (status, body) = exchange(
+            &server,
+            &request(
+                "POST",
+                "/sessions/gone/send",
+                Some(TOKEN),
+                r#"{"text":"hi"}"#,
+            ),
+        );
+        assert_eq!(status, 404);
+        assert_eq!(body, r#"{"error":"No session named 'gone'"}"#);
+        let (status, _) = exchange(
+            &server,
+            &request("POST", "/sessions/feature-a/send", Some(TOKEN), "text=hi"),
+        );
+        assert_eq!(status, 400);
+    }
+
+    /// Read one frame the server sent: opcode and payload
+    fn read_frame(stream: &mut impl Read) -> (u8, Vec<u8>) {
+        let mut head = [0; 2];
+        stream.read_exact(&mut head).unwrap();
+        let len = match head[1] & 0x7f {
+            126 => {
+                let mut len = [0; 2];
+                stream.read_exact(&mut len).unwrap();
+                u16::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).unwrap();
+        (head[0] & 0x0f, payload)
+    }
+
+    #[test]
+    fn test_event_stream_carries_hook_events_until_shutdown() {
+        // The example handshake of RFC 6455
+        assert_eq!(
+            websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let (server, _) = serve();
+        let (status, _) = exchange(&server, &request("GET", "/events", Some(TOKEN), ""));
+        assert_eq!(status, 400);
+
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        write!(
+            stream,
+            "GET /events HTTP/1.1\r\nAuthorization: Bearer {}\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            TOKEN
+        )
+        .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut handshake = Vec::new();
+        loop {
+            let line = read_line(&mut reader).unwrap();
+            if line.is_empty() {
+                break;
+            }
+            handshake.push(line);
+        }
+        assert_eq!(handshake[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(
+            handshake.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string())
+        );
+
+        let subject = HookSubject {
+            session: "stream-test".to_string(),
+            branch: Some("feature/stream".to_string()),
+            path: Some(PathBuf::from("/repo/wt")),
+            repo: Some(PathBuf::from("/repo")),
+        };
+        hooks::emit(HookEvent::CommitDetected, &subject);
+        // Events of other tests may come through the same bus first
+        let expected = concat!(
+            r#"{"event":"commit_detected","session":"stream-test","branch":"feature/stream","#,
+            r#""path":"/repo/wt","repo":"/repo"}"#
+        );
+        loop {
+            let (opcode, payload) = read_frame(&mut reader);
+            assert_eq!(opcode, OPCODE_TEXT);
+            if payload == expected.as_bytes() {
+                break;
+            }
+        }
+
+        // Stopping the server closes the stream
+        drop(server);
+        loop {
+            let (opcode, payload) = read_frame(&mut reader);
+            if opcode == OPCODE_CLOSE {
+                assert_eq!(payload, 1001u16.to_be_bytes());
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_browsers_offer_the_token_as_a_subprotocol() {
+        let (server, _) = serve();
+        let mut stream = TcpStream::connect(server.address()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        write!(
+            stream,
+            "GET /events HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Protocol: sashiki, bearer.{}\r\n\r\n",
+            TOKEN
+        )
+        .unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut handshake = Vec::new();
+        loop {
+            let line = read_line(&mut reader).unwrap();
+            if line.is_empty() {
+                break;
+            }
+            handshake.push(line);
+        }
+        assert_eq!(handshake[0], "HTTP/1.1 101 Switching Protocols");
+        assert!(handshake.contains(&"Sec-WebSocket-Protocol: sashiki".to_string()));
+    }
+
+    #[test]
+    fn test_tokens_differ() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token().unwrap());
+        assert!(ControlServer::start(0, String::new(), Arc::new(|_| Reply::ok())).is_err());
+    }
+}
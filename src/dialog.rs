@@ -1,24 +1,316 @@
 //! Dialog components for worktree management
 
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::code_blocks::Suggestion;
+use crate::encoding::TextEncoding;
+use crate::file_sync::SyncFile;
+use crate::git::{BranchCommit, MergeMode, Snapshot, Worktree};
+use std::collections::{HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Output lines of the running creation step kept for the Creating dialog
+pub const MAX_CREATION_OUTPUT: usize = 200;
+
 /// Active dialog state
 #[derive(Default)]
 pub enum ActiveDialog {
     #[default]
     None,
-    CreateWorktree,
+    /// Open a folder or create a worktree for a branch (see `OpenMode`)
+    Open,
     /// Worktree creation in progress with step-by-step progress
     Creating {
         branch: String,
-        steps: Vec<String>,
-        current_step: usize,
+        /// Named template chosen by the branch glob (None = the fallback)
+        template: Option<String>,
+        progress: CreationProgress,
     },
+    /// Create a worktree for each branch of a task list (see
+    /// `crate::bulk_create`)
+    BulkCreate,
     DeleteConfirm {
         target_index: usize,
+        /// Keep the session's note (it lives in the shared `.git`)
+        keep_note: bool,
     },
     Deleting,
+    /// Make a session of a worktree created outside Sashiki
+    AddExistingWorktree {
+        /// Worktrees git lists that have no session
+        unsessioned: Vec<Worktree>,
+        /// Directory typed in
+        input: String,
+        error: Option<String>,
+        /// Repository the typed directory belongs to instead, offered to be
+        /// opened on its own
+        other_repository: Option<PathBuf>,
+    },
+    /// Quit while these sessions are busy ("branch — what it is doing")
+    QuitConfirm {
+        busy: Vec<String>,
+    },
+    /// Close a terminal running in the tmux session `name`: detach from it
+    /// or kill it
+    CloseTerminalConfirm {
+        name: String,
+    },
+    /// A repository nested in the active worktree (`path`, relative) was
+    /// clicked: open it on its own or ignore it
+    NestedRepository {
+        path: PathBuf,
+    },
+    /// Files shared with other worktrees for one session
+    Conflicts {
+        target_index: usize,
+    },
+    /// Stash local changes of the active session
+    StashPush,
+    StashDropConfirm {
+        index: usize,
+        message: String,
+    },
+    /// Confirm discarding working tree changes
+    DiscardConfirm {
+        target: DiscardTarget,
+    },
+    /// Snapshots or checkpoints of a session's working tree
+    Snapshots {
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshots: Vec<Snapshot>,
+    },
+    /// Confirm restoring a session's working tree from a snapshot
+    SnapshotRestoreConfirm {
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshot: Snapshot,
+    },
+    /// Diff from a snapshot to the session's working tree
+    SnapshotDiff {
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshot: Snapshot,
+        diff: String,
+    },
+    /// Squash or drop commits of a session's branch since its merge-base with main
+    Squash {
+        target_index: usize,
+        base: String,
+        /// Newest first
+        commits: Vec<BranchCommit>,
+        mode: SquashMode,
+        /// Hashes of the commits to drop (`SquashMode::Drop`)
+        dropped: HashSet<String>,
+    },
+    /// Branch history is being rewritten in the background
+    Squashing,
+    /// Merge a session's branch into the branch of the main worktree
+    Merge {
+        target_index: usize,
+        mode: MergeMode,
+        /// Remove the session's worktree after a successful merge
+        remove_worktree: bool,
+        /// Delete the session's branch as well (only with `remove_worktree`)
+        delete_branch: bool,
+    },
+    /// A merge into main is running in the background
+    Merging,
+    /// The merge into main stopped on conflicts in these files (relative to
+    /// the main worktree)
+    MergeConflicts {
+        files: Vec<PathBuf>,
+    },
+    /// Fuzzy-searchable list of all commands (see `app::commands`)
+    CommandPalette {
+        query: String,
+        /// Index into the current matches
+        selected: usize,
+    },
+    /// Recent commands of the active terminal, to run again or copy
+    CommandHistory {
+        /// Index into the commands, newest first
+        selected: usize,
+    },
+    /// Edit the tags of a session
+    SessionTags {
+        target_index: usize,
+    },
+    /// Prune or recreate a worktree whose directory is gone (see
+    /// `SessionManager::mark_missing_worktrees`)
+    MissingWorktree {
+        worktree: Worktree,
+    },
+    /// Note a moment in the activity timeline of a session
+    MarkMoment {
+        target_index: usize,
+    },
+    /// Write or edit a review comment on a line of the open file (see
+    /// `crate::review_comments`)
+    ReviewComment {
+        /// Path relative to the active worktree
+        path: PathBuf,
+        line: usize,
+        /// The line's text as it reads now (None = the line is gone)
+        line_text: Option<String>,
+        input: String,
+        /// Index of the comment being edited (None = a new comment)
+        editing: Option<usize>,
+    },
+    /// The review comments of the active session as markdown, to copy,
+    /// save or send to the terminal
+    Review {
+        markdown: String,
+        count: usize,
+    },
+    /// Pick another session to compare the file at `path` (full) with (see
+    /// `crate::compare`)
+    CompareWithSession {
+        path: PathBuf,
+    },
+    /// Diff a code block picked in a terminal makes to its file, to accept
+    /// or cancel (see `crate::code_blocks`)
+    ApplyCodeBlock {
+        suggestion: Suggestion,
+    },
+    /// Recorded terminal output of a session, searchable
+    Transcript {
+        target_index: usize,
+    },
+    /// Text search across the files of the active worktree (see `crate::search`)
+    Search,
+    /// Disk usage of all worktrees with cleanup and bulk delete
+    Maintenance,
+    /// Confirm removing build artifacts found in the selected worktrees
+    CleanArtifactsConfirm {
+        /// Artifacts per worktree root
+        artifacts: Vec<(PathBuf, Vec<PathBuf>)>,
+        /// Their total size in bytes
+        bytes: u64,
+    },
+    /// Re-copy template files from the main worktree into a session
+    SyncFiles {
+        target_index: usize,
+        files: Vec<SyncFile>,
+        /// Paths to copy
+        selected: HashSet<PathBuf>,
+        /// List files that are already the same on both sides
+        show_identical: bool,
+        /// Diff of the file being previewed
+        preview: Option<(PathBuf, String)>,
+    },
     /// Template settings dialog
     TemplateSettings,
     Error {
         message: String,
     },
 }
+
+impl ActiveDialog {
+    /// An error dialog; the message is also kept in the app log
+    pub fn error(message: impl Into<String>) -> Self {
+        let message = message.into();
+        app_log::record(LogEntry::new(
+            LogLevel::Error,
+            LogSource::App,
+            message.clone(),
+        ));
+        ActiveDialog::Error { message }
+    }
+}
+
+/// Steps of a worktree being created, how long each took, and what the
+/// running one prints (e.g. a post-checkout hook installing dependencies)
+#[derive(Debug)]
+pub struct CreationProgress {
+    pub steps: Vec<String>,
+    /// Index of the running step
+    pub current_step: usize,
+    /// How long each finished step took
+    pub step_times: Vec<Duration>,
+    pub step_started: Instant,
+    /// Last `MAX_CREATION_OUTPUT` lines printed by the running step
+    pub output: VecDeque<String>,
+}
+
+impl CreationProgress {
+    pub fn new(steps: Vec<String>) -> Self {
+        Self {
+            steps,
+            current_step: 0,
+            step_times: Vec::new(),
+            step_started: Instant::now(),
+            output: VecDeque::new(),
+        }
+    }
+
+    /// The running step finished; `step` runs next
+    pub fn advance(&mut self, step: usize) {
+        self.step_times.push(self.step_started.elapsed());
+        self.current_step = step;
+        self.step_started = Instant::now();
+        self.output.clear();
+    }
+
+    pub fn push_output(&mut self, line: String) {
+        self.output.push_back(line);
+        if self.output.len() > MAX_CREATION_OUTPUT {
+            self.output.pop_front();
+        }
+    }
+}
+
+/// Which recordings of a working tree a snapshot dialog is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotKind {
+    /// Taken by hand (`GitRepo::create_snapshot`)
+    Manual,
+    /// Taken automatically (see `crate::checkpoints`)
+    Checkpoint,
+}
+
+/// Input mode of the Open dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenMode {
+    /// Open a folder: activates the matching session or opens another repository
+    #[default]
+    Path,
+    /// Create a worktree for a branch of the open repository
+    Branch,
+}
+
+/// Text field of the Open dialog that takes typing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenField {
+    /// Task description (Branch mode)
+    Description,
+    /// Folder path, or the branch name in Branch mode
+    #[default]
+    Input,
+    /// Commit, tag or branch the new worktree starts from (Branch mode)
+    Base,
+}
+
+/// What the squash dialog does with a branch's commits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SquashMode {
+    /// Replace all commits with one
+    #[default]
+    SquashAll,
+    /// Remove the selected commits
+    Drop,
+}
+
+/// Changes to throw away in the active worktree
+#[derive(Debug, Clone)]
+pub enum DiscardTarget {
+    /// One hunk of a file's diff (`patch` holds the file headers and the
+    /// hunk, decoded from the file's `encoding`)
+    Hunk {
+        path: PathBuf,
+        patch: String,
+        encoding: TextEncoding,
+    },
+    /// All changes to a file; `is_new` files are deleted
+    File { path: PathBuf, is_new: bool },
+}
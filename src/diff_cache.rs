@@ -0,0 +1,207 @@
+//! Cache of file diffs shown in the file view
+//!
+//! Diffs of changed files are computed ahead of time in the background so
+//! opening one from the file list does not wait on git. Each entry is stamped
+//! with the file's modification time and the HEAD commit it was computed
+//! against; an entry whose stamp no longer matches is treated as missing. The
+//! cache is bounded by the total number of diff lines it holds, evicting the
+//! least recently used entries first.
+
+use crate::git::{ChangeType, GitRepo};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Upper bound on the diff lines kept across all entries
+pub const MAX_CACHED_DIFF_LINES: usize = 200_000;
+
+/// What a cached diff was computed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffStamp {
+    /// Modification time of the working tree file (None when it is gone)
+    pub mtime: Option<SystemTime>,
+    pub head: Option<String>,
+}
+
+impl DiffStamp {
+    /// Stamp `full_path` against the already resolved `head`
+    pub fn read(full_path: &Path, head: Option<&str>) -> Self {
+        Self {
+            mtime: std::fs::metadata(full_path).and_then(|m| m.modified()).ok(),
+            head: head.map(str::to_string),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CachedDiff {
+    stamp: DiffStamp,
+    diff: String,
+    lines: usize,
+    last_used: u64,
+}
+
+/// Diffs keyed by absolute file path
+#[derive(Debug)]
+pub struct DiffCache {
+    entries: HashMap<PathBuf, CachedDiff>,
+    total_lines: usize,
+    max_lines: usize,
+    /// Use counter for LRU eviction
+    clock: u64,
+}
+
+impl Default for DiffCache {
+    fn default() -> Self {
+        Self::new(MAX_CACHED_DIFF_LINES)
+    }
+}
+
+impl DiffCache {
+    pub fn new(max_lines: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            total_lines: 0,
+            max_lines,
+            clock: 0,
+        }
+    }
+
+    /// Cached diff of `path` if it was computed for `stamp`
+    pub fn get(&mut self, path: &Path, stamp: &DiffStamp) -> Option<String> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(path)?;
+        if entry.stamp != *stamp {
+            return None;
+        }
+        entry.last_used = self.clock;
+        Some(entry.diff.clone())
+    }
+
+    /// Whether a diff for `path` at `stamp` is cached (without touching its LRU position)
+    pub fn contains(&self, path: &Path, stamp: &DiffStamp) -> bool {
+        self.entries
+            .get(path)
+            .is_some_and(|entry| entry.stamp == *stamp)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, stamp: DiffStamp, diff: String) {
+        self.remove(&path);
+        let lines = diff.lines().count();
+        if lines > self.max_lines {
+            return;
+        }
+
+        self.clock += 1;
+        self.total_lines += lines;
+        self.entries.insert(
+            path,
+            CachedDiff {
+                stamp,
+                diff,
+                lines,
+                last_used: self.clock,
+            },
+        );
+        self.evict();
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(entry) = self.entries.remove(path) {
+            self.total_lines -= entry.lines;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.total_lines = 0;
+    }
+
+    fn evict(&mut self) {
+        while self.total_lines > self.max_lines {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+/// Diff of a changed file as shown in the file view (None when git fails)
+pub fn compute_diff(
+    repo: &GitRepo,
+    full_path: &Path,
+    change_type: Option<ChangeType>,
+    renamed_from: Option<&Path>,
+) -> Option<String> {
+    match change_type {
+        Some(ChangeType::Added) => repo.generate_added_diff(full_path).ok(),
+        Some(ChangeType::Deleted) => repo.generate_deleted_diff(full_path).ok(),
+        _ => match renamed_from {
+            Some(old_path) => repo.get_renamed_file_diff(old_path, full_path).ok(),
+            None => repo.get_file_diff(full_path).ok(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn stamp(secs: u64, head: &str) -> DiffStamp {
+        DiffStamp {
+            mtime: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            head: Some(head.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_entry_is_served_only_for_its_stamp() {
+        // As the file view and the prefetch use it: look up, compute on a
+        // miss elsewhere, store
+        let mut cache = DiffCache::new(100);
+        let path = Path::new("/repo/a.txt");
+        assert_eq!(cache.get(path, &stamp(1, "h1")), None);
+        cache.insert(path.to_path_buf(), stamp(1, "h1"), "+a\n+b\n".to_string());
+        assert_eq!(
+            cache.get(path, &stamp(1, "h1")),
+            Some("+a\n+b\n".to_string())
+        );
+
+        // The file or HEAD changing invalidates the entry
+        assert!(!cache.contains(path, &stamp(2, "h1")));
+        assert_eq!(cache.get(path, &stamp(1, "h2")), None);
+
+        // Storing the recomputed diff replaces the stale one
+        cache.insert(path.to_path_buf(), stamp(2, "h2"), "+c\n".to_string());
+        assert!(!cache.contains(path, &stamp(1, "h1")));
+        assert_eq!(cache.get(path, &stamp(2, "h2")), Some("+c\n".to_string()));
+        assert_eq!(cache.total_lines, 1);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_by_line_count() {
+        let mut cache = DiffCache::new(5);
+        let s = stamp(1, "h");
+        cache.insert(PathBuf::from("/a"), s.clone(), "1\n2\n".to_string());
+        cache.insert(PathBuf::from("/b"), s.clone(), "1\n2\n".to_string());
+        assert!(cache.get(Path::new("/a"), &s).is_some());
+
+        cache.insert(PathBuf::from("/c"), s.clone(), "1\n2\n".to_string());
+        assert!(cache.contains(Path::new("/a"), &s));
+        assert!(!cache.contains(Path::new("/b"), &s));
+        assert!(cache.contains(Path::new("/c"), &s));
+        assert_eq!(cache.total_lines, 4);
+
+        // A diff larger than the whole budget is not cached
+        cache.insert(PathBuf::from("/big"), s.clone(), "x\n".repeat(6));
+        assert!(!cache.contains(Path::new("/big"), &s));
+        assert_eq!(cache.total_lines, 4);
+    }
+}
@@ -0,0 +1,310 @@
+//! Diffs for the file view, computed off the UI thread
+//!
+//! Opening a changed file whose diff is not cached shows a loading state
+//! while `compute` runs on a worker: the size check, reading the working tree
+//! file, then `git diff`. Each request is numbered by `DiffRequests`; starting
+//! a new one cancels the one in flight (its `CancelToken` is checked between
+//! phases) and only the latest request's result is applied, so a slow,
+//! superseded computation never overwrites a newer one.
+//!
+//! When either side of the file is over `sashiki.diff.maxSize` (default
+//! 2 MB) the diff is not computed: the view gets a quick line count and can
+//! ask for the full diff anyway.
+
+use crate::diff_cache;
+use crate::git::{ChangeType, GitRepo};
+use crate::ui::file_probe::{self, TextEncoding};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once a computation is no longer wanted
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Numbers diff requests so only the latest one's result is applied
+#[derive(Debug, Default)]
+pub struct DiffRequests {
+    latest: u64,
+    token: CancelToken,
+}
+
+impl DiffRequests {
+    /// Start a request, cancelling the one in flight
+    pub fn start(&mut self) -> (u64, CancelToken) {
+        self.cancel();
+        self.token = CancelToken::default();
+        (self.latest, self.token.clone())
+    }
+
+    /// Cancel the request in flight; its result will not be applied
+    pub fn cancel(&mut self) {
+        self.token.cancel();
+        self.latest += 1;
+    }
+
+    /// Whether the result of request `id` is still wanted
+    pub fn is_current(&self, id: u64) -> bool {
+        id == self.latest && !self.token.is_cancelled()
+    }
+}
+
+/// A file to diff
+#[derive(Debug, Clone)]
+pub struct DiffRequest {
+    pub full_path: PathBuf,
+    pub change_type: Option<ChangeType>,
+    /// Original path of a renamed file
+    pub renamed_from: Option<PathBuf>,
+    /// Size guard per side in bytes (None computes the diff regardless)
+    pub max_size: Option<u64>,
+}
+
+/// Result of a diff computation
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffOutcome {
+    /// The diff (None when git failed) and the working tree text it applies
+    /// to (None for a deleted or unreadable file)
+    Computed {
+        diff: Option<String>,
+        text: Option<(String, TextEncoding)>,
+    },
+    /// A side is over the size guard: lines added and removed by quick count
+    TooLarge { added: usize, removed: usize },
+}
+
+/// Compute the diff for `request`; None when cancelled along the way
+pub fn compute(repo: &GitRepo, request: &DiffRequest, token: &CancelToken) -> Option<DiffOutcome> {
+    let old_blob = old_blob(repo, request);
+    if let Some(max_size) = request.max_size
+        && sides_over(repo, old_blob.as_deref(), request, max_size)
+    {
+        let old = old_blob
+            .as_deref()
+            .and_then(|id| repo.read_blob(id).ok())
+            .unwrap_or_default();
+        if token.is_cancelled() {
+            return None;
+        }
+        let new = match request.change_type {
+            Some(ChangeType::Deleted) => Vec::new(),
+            _ => std::fs::read(&request.full_path).unwrap_or_default(),
+        };
+        if token.is_cancelled() {
+            return None;
+        }
+        let (added, removed) = quick_count(&old, &new);
+        return Some(DiffOutcome::TooLarge { added, removed });
+    }
+    if token.is_cancelled() {
+        return None;
+    }
+
+    let text = match request.change_type {
+        Some(ChangeType::Deleted) => None,
+        _ => file_probe::read_text(&request.full_path).ok(),
+    };
+    if token.is_cancelled() {
+        return None;
+    }
+
+    let diff = diff_cache::compute_diff(
+        repo,
+        &request.full_path,
+        request.change_type,
+        request.renamed_from.as_deref(),
+    );
+    if token.is_cancelled() {
+        return None;
+    }
+    Some(DiffOutcome::Computed { diff, text })
+}
+
+/// Whether the HEAD side (blob `old_blob`) or the working tree side of the
+/// file is larger than `max_size`
+fn sides_over(
+    repo: &GitRepo,
+    old_blob: Option<&str>,
+    request: &DiffRequest,
+    max_size: u64,
+) -> bool {
+    let new_size = match request.change_type {
+        Some(ChangeType::Deleted) => None,
+        _ => std::fs::metadata(&request.full_path).ok().map(|m| m.len()),
+    };
+    let old_size = old_blob.and_then(|id| repo.blob_size(id));
+    new_size.is_some_and(|size| size > max_size) || old_size.is_some_and(|size| size > max_size)
+}
+
+/// Whether the file is too large to diff by `max_size` (for the prefetch,
+/// which skips such files)
+pub fn is_over_size(repo: &GitRepo, request: &DiffRequest, max_size: u64) -> bool {
+    sides_over(repo, old_blob(repo, request).as_deref(), request, max_size)
+}
+
+/// Object id of the HEAD side of the file (None for an added file)
+fn old_blob(repo: &GitRepo, request: &DiffRequest) -> Option<String> {
+    match request.change_type {
+        Some(ChangeType::Added) => None,
+        _ => repo.head_blob_id(
+            request
+                .renamed_from
+                .as_deref()
+                .unwrap_or(&request.full_path),
+        ),
+    }
+}
+
+/// Lines added and removed from `old` to `new`, counting the lines of each
+/// side that have no equal line left on the other. Order is ignored, so a
+/// moved line counts as unchanged.
+pub fn quick_count(old: &[u8], new: &[u8]) -> (usize, usize) {
+    let mut balance: HashMap<&[u8], isize> = HashMap::new();
+    for line in lines(old) {
+        *balance.entry(line).or_default() += 1;
+    }
+    for line in lines(new) {
+        *balance.entry(line).or_default() -= 1;
+    }
+    let added = balance
+        .values()
+        .filter(|&&n| n < 0)
+        .map(|&n| -n)
+        .sum::<isize>();
+    let removed = balance.values().filter(|&&n| n > 0).sum::<isize>();
+    (added as usize, removed as usize)
+}
+
+fn lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes
+        .split(|&b| b == b'\n')
+        .filter(move |_| !bytes.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use std::path::Path;
+
+    /// Repository with `file.txt` committed as `content`
+    fn repo_with(content: &str) -> (tempfile::TempDir, GitRepo) {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        std::fs::write(dir.path().join("file.txt"), content).unwrap();
+        git(dir.path(), &["add", "."]);
+        git(
+            dir.path(),
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-q",
+                "-m",
+                "init",
+            ],
+        );
+        let repo = GitRepo::open(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    fn request(dir: &Path, max_size: Option<u64>) -> DiffRequest {
+        DiffRequest {
+            full_path: dir.join("file.txt"),
+            change_type: Some(ChangeType::Modified),
+            renamed_from: None,
+            max_size,
+        }
+    }
+
+    #[test]
+    fn test_newer_request_supersedes_older() {
+        let mut requests = DiffRequests::default();
+        let (first, first_token) = requests.start();
+        let (second, second_token) = requests.start();
+
+        // The first computation may still finish, but its result is stale
+        assert!(first_token.is_cancelled());
+        assert!(!requests.is_current(first));
+        assert!(!second_token.is_cancelled());
+        assert!(requests.is_current(second));
+
+        // Opening something else without a diff cancels the second as well
+        requests.cancel();
+        assert!(second_token.is_cancelled());
+        assert!(!requests.is_current(second));
+        assert!(!requests.is_current(first));
+    }
+
+    #[test]
+    fn test_cancelled_computation_yields_nothing() {
+        let (dir, repo) = repo_with("a\n");
+        std::fs::write(dir.path().join("file.txt"), "b\n").unwrap();
+
+        let token = CancelToken::default();
+        token.cancel();
+        assert_eq!(compute(&repo, &request(dir.path(), None), &token), None);
+
+        let outcome = compute(&repo, &request(dir.path(), None), &CancelToken::default());
+        let Some(DiffOutcome::Computed {
+            diff: Some(diff),
+            text: Some((text, _)),
+        }) = outcome
+        else {
+            panic!("expected a diff, got {:?}", outcome);
+        };
+        assert!(diff.contains("-a\n+b\n"));
+        assert_eq!(text, "b\n");
+    }
+
+    #[test]
+    fn test_size_guard_counts_lines_instead() {
+        let (dir, repo) = repo_with("one\ntwo\nthree\n");
+        std::fs::write(dir.path().join("file.txt"), "one\nTWO\nthree\nfour\n").unwrap();
+
+        assert_eq!(
+            compute(
+                &repo,
+                &request(dir.path(), Some(8)),
+                &CancelToken::default()
+            ),
+            Some(DiffOutcome::TooLarge {
+                added: 2,
+                removed: 1
+            })
+        );
+        assert!(is_over_size(&repo, &request(dir.path(), None), 8));
+        assert!(!is_over_size(&repo, &request(dir.path(), None), 1024));
+        assert!(matches!(
+            compute(
+                &repo,
+                &request(dir.path(), Some(1024)),
+                &CancelToken::default()
+            ),
+            Some(DiffOutcome::Computed { diff: Some(_), .. })
+        ));
+    }
+
+    #[test]
+    fn test_quick_count() {
+        assert_eq!(quick_count(b"", b""), (0, 0));
+        assert_eq!(quick_count(b"", b"a\nb\n"), (2, 0));
+        assert_eq!(quick_count(b"a\nb\n", b""), (0, 2));
+        assert_eq!(quick_count(b"a\nb\nc\n", b"c\nb\na\n"), (0, 0));
+        assert_eq!(quick_count(b"a\na\nb", b"a\nb\nd\n"), (1, 1));
+    }
+}
@@ -0,0 +1,218 @@
+//! How long each session has sat on uncommitted changes
+//!
+//! A session's worktree turns dirty when a refresh of its changed files
+//! finds some after finding none. That moment is kept in the session state
+//! file (`dirty_since`, unix seconds, see `session_state`), so a restart does
+//! not reset it, and cleared once a refresh finds the worktree clean.
+//!
+//! A commit (HEAD moving, see `head_watch`) restarts the clock: changes left
+//! over after a commit count from the commit, as the agent did get
+//! something in. A commit that leaves nothing behind is followed by a
+//! refresh finding the worktree clean, which clears the moment.
+//!
+//! The sidebar shows the age next to the session ("dirty 2h") once it is
+//! `SHOW_AFTER` old, in yellow and then red from the thresholds set by
+//! `sashiki.sidebar.dirtyWarnMinutes` and `sashiki.sidebar.dirtyAlertMinutes`.
+
+use crate::git::{ChangeType, ChangedFile};
+use std::time::Duration;
+
+/// Age from which the sidebar shows that a session is dirty
+pub const SHOW_AFTER: Duration = Duration::from_secs(60);
+
+/// Ages from which the indicator turns yellow, then red, when not configured
+pub const DEFAULT_WARN_AFTER: Duration = Duration::from_secs(2 * 3600);
+pub const DEFAULT_ALERT_AFTER: Duration = Duration::from_secs(8 * 3600);
+
+/// When the worktree turned dirty, once its files were found `dirty` (or
+/// clean) at `now` (unix seconds); `since` is what was known before
+pub fn observe_files(since: Option<i64>, dirty: bool, now: i64) -> Option<i64> {
+    match (since, dirty) {
+        (_, false) => None,
+        (None, true) => Some(now),
+        (Some(since), true) => Some(since),
+    }
+}
+
+/// When the worktree turned dirty, after a commit at `now`: changes still
+/// there count from the commit (the next refresh clears a clean worktree)
+pub fn observe_commit(since: Option<i64>, now: i64) -> Option<i64> {
+    since.map(|_| now)
+}
+
+/// Changed files of a worktree, split as the sidebar's tooltip shows them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirtyCounts {
+    /// Tracked files modified, added to the index, deleted or renamed
+    pub tracked: usize,
+    pub untracked: usize,
+}
+
+impl DirtyCounts {
+    /// Counts of `files` plus `unlisted` untracked files left out of them
+    pub fn of(files: &[ChangedFile], unlisted: usize) -> Self {
+        let untracked = files
+            .iter()
+            .filter(|f| f.change_type == ChangeType::Added && !f.staged)
+            .count();
+        Self {
+            tracked: files.len() - untracked,
+            untracked: untracked + unlisted,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.tracked + self.untracked > 0
+    }
+
+    /// e.g. "3 modified · 2 untracked"
+    pub fn describe(&self) -> String {
+        format!("{} modified · {} untracked", self.tracked, self.untracked)
+    }
+}
+
+/// How worrying the age of a session's changes is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DirtyLevel {
+    Normal,
+    Warn,
+    Alert,
+}
+
+/// Ages at which the indicator escalates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyThresholds {
+    pub warn_after: Duration,
+    pub alert_after: Duration,
+}
+
+impl Default for DirtyThresholds {
+    fn default() -> Self {
+        Self {
+            warn_after: DEFAULT_WARN_AFTER,
+            alert_after: DEFAULT_ALERT_AFTER,
+        }
+    }
+}
+
+impl DirtyThresholds {
+    pub fn level(&self, age: Duration) -> DirtyLevel {
+        if age >= self.alert_after {
+            DirtyLevel::Alert
+        } else if age >= self.warn_after {
+            DirtyLevel::Warn
+        } else {
+            DirtyLevel::Normal
+        }
+    }
+}
+
+/// Compact age for the sidebar ("dirty 2h"), None while it is younger than
+/// `SHOW_AFTER`
+pub fn summary(age: Duration) -> Option<String> {
+    if age < SHOW_AFTER {
+        return None;
+    }
+    Some(match age.as_secs() {
+        s if s < 3600 => format!("dirty {}m", s / 60),
+        s if s < 86_400 => format!("dirty {}h", s / 3600),
+        s => format!("dirty {}d", s / 86_400),
+    })
+}
+
+/// Exact age for the tooltip ("2h 14m")
+pub fn describe_age(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn file(path: &str, change_type: ChangeType, staged: bool) -> ChangedFile {
+        ChangedFile {
+            path: PathBuf::from(path),
+            change_type,
+            staged,
+            old_path: None,
+            similarity: None,
+            submodule: None,
+            outside_sparse: false,
+            nested_repository: false,
+        }
+    }
+
+    #[test]
+    fn test_dirty_moment_follows_clean_and_dirty_refreshes() {
+        // Clean → dirty at 100, still dirty at 200 → clean at 300
+        let since = observe_files(None, false, 50);
+        assert_eq!(since, None);
+        let since = observe_files(since, true, 100);
+        assert_eq!(since, Some(100));
+        let since = observe_files(since, true, 200);
+        assert_eq!(since, Some(100));
+        let since = observe_files(since, false, 300);
+        assert_eq!(since, None);
+        // Dirty again: counted from the new moment
+        assert_eq!(observe_files(since, true, 400), Some(400));
+    }
+
+    #[test]
+    fn test_commit_restarts_the_clock() {
+        // Committing part of the changes: the rest count from the commit
+        let since = observe_commit(Some(100), 500);
+        assert_eq!(since, Some(500));
+        assert_eq!(observe_files(since, true, 510), Some(500));
+        // Committing everything: the refresh after it clears the moment
+        assert_eq!(
+            observe_files(observe_commit(Some(100), 500), false, 510),
+            None
+        );
+        // A commit on a clean worktree leaves it clean
+        assert_eq!(observe_commit(None, 500), None);
+    }
+
+    #[test]
+    fn test_counts_split_untracked_files() {
+        let files = [
+            file("src/lib.rs", ChangeType::Modified, false),
+            file("src/new.rs", ChangeType::Added, true),
+            file("notes.txt", ChangeType::Added, false),
+            file("build/", ChangeType::Added, false),
+            file("old.rs", ChangeType::Deleted, false),
+        ];
+        let counts = DirtyCounts::of(&files, 10);
+        assert_eq!(
+            counts,
+            DirtyCounts {
+                tracked: 3,
+                untracked: 12
+            }
+        );
+        assert_eq!(counts.describe(), "3 modified · 12 untracked");
+        assert!(counts.is_dirty());
+        assert!(!DirtyCounts::of(&[], 0).is_dirty());
+        assert!(DirtyCounts::of(&[], 1).is_dirty());
+    }
+
+    #[test]
+    fn test_age_readout_and_levels() {
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+        assert_eq!(summary(Duration::from_secs(30)), None);
+        assert_eq!(summary(minutes(5)).as_deref(), Some("dirty 5m"));
+        assert_eq!(summary(minutes(150)).as_deref(), Some("dirty 2h"));
+        assert_eq!(summary(minutes(3 * 24 * 60)).as_deref(), Some("dirty 3d"));
+        assert_eq!(describe_age(minutes(134)), "2h 14m");
+        assert_eq!(describe_age(minutes(7)), "7m");
+
+        let thresholds = DirtyThresholds::default();
+        assert_eq!(thresholds.level(minutes(90)), DirtyLevel::Normal);
+        assert_eq!(thresholds.level(minutes(120)), DirtyLevel::Warn);
+        assert_eq!(thresholds.level(minutes(9 * 60)), DirtyLevel::Alert);
+    }
+}
@@ -0,0 +1,229 @@
+//! Text encodings of files and git output
+//!
+//! Files are read as bytes and decoded for display: a BOM decides between
+//! UTF-8 and UTF-16, otherwise valid UTF-8 is UTF-8. Anything else is tried
+//! as legacy text with `encoding_rs`: Shift_JIS and EUC-JP when the bytes
+//! decode without errors and contain kana, then windows-1252 (Latin-1) when
+//! it looks like text. Invalid sequences decode to U+FFFD instead of failing.
+//! Text written back (e.g. a hunk patch for `git apply`) is encoded in the
+//! file's encoding again, so the bytes git sees match the file.
+
+use encoding_rs::{DecoderResult, EUC_JP, Encoding, SHIFT_JIS, WINDOWS_1252};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Legacy multi-byte encodings, in the order they are tried
+const MULTI_BYTE: [&Encoding; 2] = [SHIFT_JIS, EUC_JP];
+
+/// Encoding of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// A non-Unicode encoding (ASCII-compatible)
+    Legacy(&'static Encoding),
+}
+
+impl TextEncoding {
+    /// Name shown in the file view ("UTF-8", "Shift_JIS")
+    pub fn label(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16 LE",
+            TextEncoding::Utf16Be => "UTF-16 BE",
+            TextEncoding::Legacy(encoding) => encoding.name(),
+        }
+    }
+}
+
+/// Encoding of text starting with `head`, None if it looks binary. A
+/// multi-byte character cut off at the end of `head` is fine.
+pub fn detect(head: &[u8]) -> Option<TextEncoding> {
+    if head.starts_with(UTF8_BOM) {
+        return Some(TextEncoding::Utf8);
+    }
+    if head.starts_with(UTF16_LE_BOM) {
+        return Some(TextEncoding::Utf16Le);
+    }
+    if head.starts_with(UTF16_BE_BOM) {
+        return Some(TextEncoding::Utf16Be);
+    }
+    if head.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => Some(TextEncoding::Utf8),
+        Err(e) if e.error_len().is_none() => Some(TextEncoding::Utf8),
+        Err(_) => detect_legacy(head).map(TextEncoding::Legacy),
+    }
+}
+
+fn detect_legacy(head: &[u8]) -> Option<&'static Encoding> {
+    // Control characters other than whitespace and escape mean binary
+    if head
+        .iter()
+        .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b))
+    {
+        return None;
+    }
+    for encoding in MULTI_BYTE {
+        if decode_strict(encoding, head).is_some_and(|text| text.chars().any(is_kana)) {
+            return Some(encoding);
+        }
+    }
+    // Latin text is mostly ASCII; windows-1252 leaves five bytes undefined
+    let high = head.iter().filter(|&&b| b >= 0x80).count();
+    let undefined = head
+        .iter()
+        .any(|b| matches!(b, 0x81 | 0x8D | 0x8F | 0x90 | 0x9D));
+    (!undefined && high * 3 <= head.len()).then_some(WINDOWS_1252)
+}
+
+/// Hiragana and full-width katakana (half-width katakana share their bytes
+/// with Latin-1 letters in Shift_JIS)
+fn is_kana(c: char) -> bool {
+    ('\u{3040}'..='\u{30FF}').contains(&c)
+}
+
+/// `bytes` decoded without replacements, None on an invalid sequence (an
+/// incomplete one at the end is left out)
+fn decode_strict(encoding: &'static Encoding, bytes: &[u8]) -> Option<String> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text =
+        String::with_capacity(decoder.max_utf8_buffer_length_without_replacement(bytes.len())?);
+    match decoder.decode_to_string_without_replacement(bytes, &mut text, false) {
+        (DecoderResult::InputEmpty, _) => Some(text),
+        _ => None,
+    }
+}
+
+/// `bytes` in `encoding` as text, without a BOM
+pub fn decode(bytes: &[u8], encoding: TextEncoding) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match encoding {
+        TextEncoding::Utf8 => {
+            String::from_utf8_lossy(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)).into_owned()
+        }
+        TextEncoding::Utf16Le => utf16(
+            bytes.strip_prefix(UTF16_LE_BOM).unwrap_or(bytes),
+            u16::from_le_bytes,
+        ),
+        TextEncoding::Utf16Be => utf16(
+            bytes.strip_prefix(UTF16_BE_BOM).unwrap_or(bytes),
+            u16::from_be_bytes,
+        ),
+        TextEncoding::Legacy(encoding) => {
+            encoding.decode_without_bom_handling(bytes).0.into_owned()
+        }
+    }
+}
+
+/// `bytes` decoded in the encoding they are detected to be in (UTF-8 with
+/// replacements when they look binary), with that encoding
+pub fn decode_detected(bytes: &[u8]) -> (String, TextEncoding) {
+    let encoding = detect(bytes).unwrap_or(TextEncoding::Utf8);
+    (decode(bytes, encoding), encoding)
+}
+
+/// `text` as bytes in `encoding` (UTF-16 with a BOM). Characters the
+/// encoding lacks become HTML numeric references, as in `encoding_rs`.
+pub fn encode(text: &str, encoding: TextEncoding) -> Vec<u8> {
+    match encoding {
+        TextEncoding::Utf8 => text.as_bytes().to_vec(),
+        TextEncoding::Utf16Le => UTF16_LE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        TextEncoding::Utf16Be => UTF16_BE_BOM
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+        TextEncoding::Legacy(encoding) => encoding.encode(text).0.into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "日本語のテキスト\n" in Shift_JIS
+    const SHIFT_JIS_TEXT: &[u8] =
+        b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\x83\x67\n";
+
+    #[test]
+    fn test_detect_legacy_encodings() {
+        assert_eq!(
+            detect(SHIFT_JIS_TEXT),
+            Some(TextEncoding::Legacy(SHIFT_JIS))
+        );
+        assert_eq!(
+            decode(SHIFT_JIS_TEXT, TextEncoding::Legacy(SHIFT_JIS)),
+            "日本語のテキスト\n"
+        );
+
+        let (euc_jp, _, _) = EUC_JP.encode("ひらがなとカタカナ\n");
+        assert_eq!(detect(&euc_jp), Some(TextEncoding::Legacy(EUC_JP)));
+
+        // "café crème, été" in Latin-1, including bytes that are Shift_JIS
+        // lead bytes
+        let latin1 = b"caf\xe9 cr\xe8me, \xe9t\xe9\n";
+        assert_eq!(detect(latin1), Some(TextEncoding::Legacy(WINDOWS_1252)));
+        assert_eq!(
+            decode(latin1, TextEncoding::Legacy(WINDOWS_1252)),
+            "café crème, été\n"
+        );
+        assert_eq!(TextEncoding::Legacy(WINDOWS_1252).label(), "windows-1252");
+    }
+
+    #[test]
+    fn test_detect_binary() {
+        assert_eq!(detect(b"\xff\xd8\xff\xe0 JFIF"), None);
+        // Not UTF-8, and control bytes rule out legacy text
+        assert_eq!(detect(b"\x7fELF\x02\x01\x01\x03\x90\xe8"), None);
+        assert_eq!(detect(b"abc\0def"), None);
+    }
+
+    #[test]
+    fn test_detect_with_a_character_cut_off() {
+        let head = &SHIFT_JIS_TEXT[..SHIFT_JIS_TEXT.len() - 2];
+        assert_eq!(detect(head), Some(TextEncoding::Legacy(SHIFT_JIS)));
+    }
+
+    #[test]
+    fn test_invalid_sequences_decode_to_replacements() {
+        // A lead byte followed by a newline is not a character
+        let text = decode(b"\x82\nok\n", TextEncoding::Legacy(SHIFT_JIS));
+        assert_eq!(text, "\u{FFFD}\nok\n");
+        let (text, encoding) = decode_detected(b"\xff\xfeA\0\x00\xd8");
+        assert_eq!(encoding, TextEncoding::Utf16Le);
+        assert!(text.starts_with('A'));
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        for encoding in [
+            TextEncoding::Utf8,
+            TextEncoding::Utf16Le,
+            TextEncoding::Utf16Be,
+            TextEncoding::Legacy(SHIFT_JIS),
+        ] {
+            let bytes = encode("日本語のテキスト\n", encoding);
+            assert_eq!(decode(&bytes, encoding), "日本語のテキスト\n");
+        }
+        assert_eq!(
+            encode("日本語のテキスト\n", TextEncoding::Legacy(SHIFT_JIS)),
+            SHIFT_JIS_TEXT
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Machine-readable export of the sessions and their changes
+//!
+//! `sashiki --export-state [PATH]` prints the state of a repository as JSON
+//! and exits without opening a window or starting a terminal; the Export
+//! State… command writes the same document, with the terminals' activity,
+//! to a file. The document carries `SCHEMA_VERSION`, which is raised when a
+//! field changes meaning or goes away (new fields may appear without it).
+
+use crate::git::{self, ChangeType, ChangedFile, FileChurn, GitRepo};
+use crate::session::{Session, SessionManager, SessionStatus};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of the exported document's layout
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything Sashiki knows about a repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateExport {
+    pub schema_version: u32,
+    /// Milliseconds since the Unix epoch
+    pub exported_at_ms: u64,
+    /// Working directory of the repository
+    pub repository: PathBuf,
+    pub sessions: Vec<SessionState>,
+    /// `diff_stats` of all sessions added up
+    pub diff_stats: DiffStats,
+}
+
+/// One session (worktree)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub name: String,
+    pub branch: Option<String>,
+    pub path: PathBuf,
+    pub status: Status,
+    pub is_main: bool,
+    pub locked: bool,
+    pub terminal_count: usize,
+    /// When a terminal of the session last printed anything, in
+    /// milliseconds since the Unix epoch
+    pub last_output_ms: Option<u64>,
+    pub changed_files: Vec<FileState>,
+    pub diff_stats: DiffStats,
+    /// Why the changes could not be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Terminal state of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Focused,
+    Running,
+    Stopped,
+    /// The worktree's directory is gone
+    Missing,
+}
+
+impl From<SessionStatus> for Status {
+    fn from(status: SessionStatus) -> Self {
+        match status {
+            SessionStatus::Focused => Status::Focused,
+            SessionStatus::Running => Status::Running,
+            SessionStatus::Stopped => Status::Stopped,
+            SessionStatus::Missing => Status::Missing,
+        }
+    }
+}
+
+/// A changed file, relative to the worktree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub path: PathBuf,
+    pub change: Change,
+    pub staged: bool,
+    /// Original path of a renamed file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Conflicted,
+    Unknown,
+}
+
+impl From<ChangeType> for Change {
+    fn from(change_type: ChangeType) -> Self {
+        match change_type {
+            ChangeType::Added => Change::Added,
+            ChangeType::Modified => Change::Modified,
+            ChangeType::Deleted => Change::Deleted,
+            ChangeType::Renamed => Change::Renamed,
+            ChangeType::Conflicted => Change::Conflicted,
+            ChangeType::Unknown => Change::Unknown,
+        }
+    }
+}
+
+impl From<&ChangedFile> for FileState {
+    fn from(file: &ChangedFile) -> Self {
+        Self {
+            path: file.path.clone(),
+            change: file.change_type.into(),
+            staged: file.staged,
+            old_path: file.old_path.clone(),
+        }
+    }
+}
+
+/// Lines added and removed since HEAD (`git diff --numstat`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffStats {
+    pub files: usize,
+    pub added: usize,
+    pub removed: usize,
+    /// Files git counts no lines for
+    pub binary_files: usize,
+}
+
+impl DiffStats {
+    fn from_churn(churn: &[FileChurn]) -> Self {
+        churn.iter().fold(Self::default(), |stats, file| Self {
+            files: stats.files + 1,
+            added: stats.added + file.added,
+            removed: stats.removed + file.removed,
+            binary_files: stats.binary_files + usize::from(file.binary),
+        })
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            files: self.files + other.files,
+            added: self.added + other.added,
+            removed: self.removed + other.removed,
+            binary_files: self.binary_files + other.binary_files,
+        }
+    }
+}
+
+impl SessionState {
+    /// `session` as the app sees it; the changes are read by `export`
+    pub fn new(session: &Session, terminal_count: usize, last_output: Option<SystemTime>) -> Self {
+        let worktree = session.worktree();
+        Self {
+            name: worktree.name.clone(),
+            branch: worktree.branch.clone(),
+            path: worktree.path.clone(),
+            status: session.status().into(),
+            is_main: worktree.is_main,
+            locked: worktree.locked,
+            terminal_count,
+            last_output_ms: last_output.map(unix_ms),
+            changed_files: Vec::new(),
+            diff_stats: DiffStats::default(),
+            error: None,
+        }
+    }
+
+    /// Read the changed files and line counts of the worktree
+    fn read_changes(&mut self) {
+        let result = GitRepo::open(&self.path).and_then(|repo| {
+            let files = repo.get_changed_files()?;
+            let churn = repo.diff_numstat()?;
+            Ok((files, churn))
+        });
+        match result {
+            Ok((files, churn)) => {
+                self.changed_files = files.iter().map(FileState::from).collect();
+                self.diff_stats = DiffStats::from_churn(&churn);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+}
+
+/// Read the changes of every session in `sessions` (runs git, so not on the
+/// UI thread) and put the document together
+pub fn export(repository: PathBuf, mut sessions: Vec<SessionState>) -> StateExport {
+    for session in &mut sessions {
+        session.read_changes();
+    }
+    let diff_stats = sessions
+        .iter()
+        .fold(DiffStats::default(), |total, session| {
+            total.add(session.diff_stats)
+        });
+    StateExport {
+        schema_version: SCHEMA_VERSION,
+        exported_at_ms: unix_ms(SystemTime::now()),
+        repository,
+        sessions,
+        diff_stats,
+    }
+}
+
+/// Export the repository at `path` without a window: the sessions come
+/// from its worktrees, none of which has a terminal
+pub fn export_headless(path: &Path) -> git::Result<StateExport> {
+    let repo = GitRepo::open(path)?;
+    let mut manager = SessionManager::new();
+    manager.init_from_worktrees(repo.list_worktrees()?);
+    let sessions = manager
+        .sessions()
+        .iter()
+        .map(|session| SessionState::new(session, session.terminal_count(), None))
+        .collect();
+    Ok(export(repo.workdir().to_path_buf(), sessions))
+}
+
+/// The document as indented JSON
+pub fn to_json(state: &StateExport) -> String {
+    // Plain structs with string keys always serialize
+    serde_json::to_string_pretty(state).expect("state export serializes")
+}
+
+fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::init_repo_with_feature_worktree;
+
+    /// The feature worktree has `README.md` changed and `new.txt` added
+    fn init_repo() -> tempfile::TempDir {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        std::fs::write(worktree.join("README.md"), "hello\nworld\n").unwrap();
+        std::fs::write(worktree.join("new.txt"), "new\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_headless_export_reads_every_worktree() {
+        let dir = init_repo();
+        let state = export_headless(dir.path()).unwrap();
+
+        assert_eq!(state.schema_version, SCHEMA_VERSION);
+        assert_eq!(state.sessions.len(), 2);
+        assert!(state.sessions[0].is_main);
+
+        let feature = &state.sessions[1];
+        assert_eq!(feature.branch.as_deref(), Some("feature"));
+        let mut files: Vec<(&Path, Change)> = feature
+            .changed_files
+            .iter()
+            .map(|f| (f.path.as_path(), f.change))
+            .collect();
+        files.sort_by_key(|(path, _)| *path);
+        assert_eq!(
+            files,
+            vec![
+                (Path::new("README.md"), Change::Modified),
+                (Path::new("new.txt"), Change::Added),
+            ]
+        );
+        // Untracked files have no line counts in `git diff HEAD`
+        assert_eq!(
+            feature.diff_stats,
+            DiffStats {
+                files: 1,
+                added: 1,
+                removed: 0,
+                binary_files: 0,
+            }
+        );
+        assert_eq!(state.diff_stats, feature.diff_stats);
+    }
+
+    #[test]
+    fn test_headless_export_starts_no_terminal() {
+        let dir = init_repo();
+        let state = export_headless(dir.path()).unwrap();
+        for session in &state.sessions {
+            assert_eq!(session.terminal_count, 0);
+            assert_eq!(session.status, Status::Stopped);
+            assert_eq!(session.last_output_ms, None);
+        }
+    }
+
+    #[test]
+    fn test_json_round_trips() {
+        let dir = init_repo();
+        let state = export_headless(dir.path()).unwrap();
+        let json = to_json(&state);
+        assert!(json.contains("\"schema_version\": 1"));
+        assert!(json.contains("\"change\": \"added\""));
+        assert!(json.contains("\"status\": \"stopped\""));
+        let parsed: StateExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, state);
+    }
+}
@@ -0,0 +1,132 @@
+//! Handing files to an external editor
+//!
+//! The command line comes from git config `sashiki.actions.externalEditor`
+//! (e.g. `code --goto {path}:{line}`) and falls back to `$VISUAL` / `$EDITOR`.
+//! The command runs detached through the shell, without a terminal.
+
+use crate::shell_escape::Shell;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Command template to use: the configured one, else `$VISUAL`, else `$EDITOR`
+pub fn editor_command(
+    configured: Option<String>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    configured
+        .or_else(|| env_var("VISUAL"))
+        .or_else(|| env_var("EDITOR"))
+        .map(|cmd| cmd.trim().to_string())
+        .filter(|cmd| !cmd.is_empty())
+}
+
+/// Expand `{path}` and `{line}` in a command template.
+///
+/// The path is shell-quoted when needed. A template without `{path}` gets the
+/// path appended, so a bare `$EDITOR` value like `gvim` works as is. `{line}`
+/// defaults to 1 when no line is known.
+pub fn expand_command(template: &str, path: &Path, line: Option<usize>) -> String {
+    let path = path.to_string_lossy();
+    let quoted = Shell::platform().escape(&path);
+    let line = line.unwrap_or(1).to_string();
+
+    if template.contains("{path}") {
+        template.replace("{path}", &quoted).replace("{line}", &line)
+    } else {
+        format!("{} {}", template.replace("{line}", &line), quoted)
+    }
+}
+
+/// Start `command` through the shell without waiting for it.
+///
+/// Standard streams are detached so an editor that writes to its terminal
+/// cannot block on a pipe nobody reads.
+pub fn spawn_detached(command: &str, workdir: &Path) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    let mut cmd = Command::new("sh");
+    #[cfg(unix)]
+    cmd.args(["-c", command]);
+
+    #[cfg(windows)]
+    let mut cmd = Command::new("cmd");
+    #[cfg(windows)]
+    cmd.args(["/C", command]);
+
+    cmd.current_dir(workdir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// Whether a shell exit code means the command itself could not be run
+pub fn is_not_found_exit(code: Option<i32>) -> bool {
+    if cfg!(windows) {
+        code == Some(9009)
+    } else {
+        matches!(code, Some(126 | 127))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_editor_command_fallback_order() {
+        let env = |key: &str| match key {
+            "VISUAL" => None,
+            "EDITOR" => Some("nano".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            editor_command(Some("code --goto {path}:{line}".into()), env).as_deref(),
+            Some("code --goto {path}:{line}")
+        );
+        assert_eq!(editor_command(None, env).as_deref(), Some("nano"));
+        assert_eq!(editor_command(Some("  ".into()), |_| None), None);
+        assert_eq!(editor_command(None, |_| None), None);
+    }
+
+    #[test]
+    fn test_expand_command_placeholders() {
+        let path = PathBuf::from("/repo/src/main.rs");
+        assert_eq!(
+            expand_command("code --goto {path}:{line}", &path, Some(42)),
+            "code --goto /repo/src/main.rs:42"
+        );
+        assert_eq!(
+            expand_command("code --goto {path}:{line}", &path, None),
+            "code --goto /repo/src/main.rs:1"
+        );
+        assert_eq!(
+            expand_command("gvim", &path, Some(3)),
+            "gvim /repo/src/main.rs"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_command_quotes_paths() {
+        let path = PathBuf::from("/my repo/it's.rs");
+        assert_eq!(
+            expand_command("subl {path}:{line}", &path, Some(7)),
+            r"subl '/my repo/it'\''s.rs':7"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_detached_reports_missing_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut child = spawn_detached("sashiki-no-such-editor", dir.path()).unwrap();
+        assert!(is_not_found_exit(child.wait().unwrap().code()));
+
+        let marker = dir.path().join("opened file.txt");
+        let command = expand_command("touch", &marker, None);
+        let mut child = spawn_detached(&command, dir.path()).unwrap();
+        assert!(child.wait().unwrap().success());
+        assert!(marker.exists());
+    }
+}
@@ -0,0 +1,493 @@
+//! File list cache, one entry per session
+//!
+//! The changed files and directory listings of every session's worktree are
+//! kept, so switching sessions shows the previous list at once while a
+//! refresh runs in the background. The active session's listings are re-read
+//! once they are `ACTIVE_LISTING_TTL` old; sessions visible in parallel mode
+//! refresh their changed files at most every `BACKGROUND_REFRESH_INTERVAL`.
+//! Listings of sessions not viewed for `LISTING_RETENTION` are dropped to
+//! keep memory bounded (the changed files are small and stay). The active
+//! session's line counts per file (`git diff --numstat`) sit next to its
+//! changed files for the diff stats in the header, as do the content hashes
+//! of those files that tell reviewed files changed since (see `review`).
+//! Deletions git reports only because a file lies outside a sparse
+//! checkout's patterns are counted but kept out of the changed files, as
+//! are the files of new directories past the listing limit; repositories
+//! nested in the worktree are kept apart as their roots. The changed
+//! files tracked by Git LFS are noted too, so opening one needs no git
+//! call to tell (see `lfs`).
+
+use crate::git::{ChangedFile, FileChurn, split_nested_repositories};
+use crate::review::ExcludePatterns;
+use crate::sparse::SparseCheckout;
+use crate::ui::{DirChangeSummary, summarize_dir_changes};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Age at which a directory listing of the active session is read again
+pub const ACTIVE_LISTING_TTL: Duration = Duration::from_secs(2);
+
+/// Minimum time between refreshes of a session that is not active
+pub const BACKGROUND_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long the listings of a session are kept after it was last viewed
+pub const LISTING_RETENTION: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug)]
+struct Listing {
+    entries: Vec<(PathBuf, bool)>,
+    read_at: Instant,
+}
+
+/// Cached files of one worktree
+#[derive(Debug)]
+pub struct SessionFiles {
+    pub changed_files: Vec<ChangedFile>,
+    /// `changed_files` aggregated per directory for the file list badges
+    pub dir_changes: HashMap<PathBuf, DirChangeSummary>,
+    /// When `changed_files` was last stored (None before the first refresh)
+    refreshed_at: Option<Instant>,
+    /// Lines added and removed per file since HEAD
+    pub churn: Vec<FileChurn>,
+    /// Current content hash of each changed file
+    pub content_hashes: HashMap<PathBuf, String>,
+    /// Sparse-checkout patterns of the worktree (None when it is not sparse)
+    pub sparse: Option<SparseCheckout>,
+    /// Deletions outside the sparse patterns left out of `changed_files`
+    pub outside_sparse: usize,
+    /// Roots of the repositories nested in the worktree (relative), left out
+    /// of `changed_files`
+    pub nested_repositories: HashSet<PathBuf>,
+    /// Files of untracked directories past the listing limit, left out of
+    /// `changed_files`
+    pub unlisted_untracked: usize,
+    /// Changed files tracked by Git LFS
+    pub lfs_paths: HashSet<PathBuf>,
+    /// Shallow directory listings for the All files mode, by directory
+    listings: HashMap<PathBuf, Listing>,
+    viewed_at: Instant,
+}
+
+impl SessionFiles {
+    fn new(now: Instant) -> Self {
+        Self {
+            changed_files: Vec::new(),
+            dir_changes: HashMap::new(),
+            refreshed_at: None,
+            churn: Vec::new(),
+            content_hashes: HashMap::new(),
+            sparse: None,
+            outside_sparse: 0,
+            nested_repositories: HashSet::new(),
+            unlisted_untracked: 0,
+            lfs_paths: HashSet::new(),
+            listings: HashMap::new(),
+            viewed_at: now,
+        }
+    }
+
+    /// Lines added and removed in all files but the `excluded` ones
+    pub fn churn_totals(&self, excluded: &ExcludePatterns) -> (usize, usize) {
+        self.churn
+            .iter()
+            .filter(|f| !excluded.matches(&f.path))
+            .fold((0, 0), |(added, removed), f| {
+                (added + f.added, removed + f.removed)
+            })
+    }
+
+    /// The `limit` files with the most lines touched, binary files last,
+    /// leaving out the `excluded` ones
+    pub fn top_churn(&self, limit: usize, excluded: &ExcludePatterns) -> Vec<&FileChurn> {
+        let mut files: Vec<&FileChurn> = self
+            .churn
+            .iter()
+            .filter(|f| !excluded.matches(&f.path))
+            .collect();
+        files.sort_by(|a, b| {
+            a.binary
+                .cmp(&b.binary)
+                .then(b.churn().cmp(&a.churn()))
+                .then(a.path.cmp(&b.path))
+        });
+        files.truncate(limit);
+        files
+    }
+}
+
+/// Per-session file lists, keyed by worktree path
+#[derive(Debug, Default)]
+pub struct FileCache {
+    sessions: HashMap<PathBuf, SessionFiles>,
+}
+
+impl FileCache {
+    pub fn get(&self, worktree: &Path) -> Option<&SessionFiles> {
+        self.sessions.get(worktree)
+    }
+
+    /// Store the changed files of `worktree` (read at `now`), leaving out
+    /// the deletions outside its sparse patterns and the nested repositories
+    pub fn store_changed_files(&mut self, worktree: &Path, files: Vec<ChangedFile>, now: Instant) {
+        let entry = self
+            .sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now));
+        let (outside, files): (Vec<_>, Vec<_>) = files.into_iter().partition(|f| f.outside_sparse);
+        entry.outside_sparse = outside.len();
+        let (files, nested) = split_nested_repositories(files);
+        entry.nested_repositories = nested;
+        entry.dir_changes = summarize_dir_changes(&files);
+        entry.changed_files = files;
+        entry.refreshed_at = Some(now);
+    }
+
+    /// Store the sparse-checkout patterns of `worktree`
+    pub fn store_sparse_checkout(
+        &mut self,
+        worktree: &Path,
+        sparse: Option<SparseCheckout>,
+        now: Instant,
+    ) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .sparse = sparse;
+    }
+
+    /// Store how many untracked files of `worktree` were left out of its
+    /// changed files
+    pub fn store_unlisted_untracked(&mut self, worktree: &Path, count: usize, now: Instant) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .unlisted_untracked = count;
+    }
+
+    /// Store which changed files of `worktree` are tracked by Git LFS
+    pub fn store_lfs_paths(&mut self, worktree: &Path, paths: HashSet<PathBuf>, now: Instant) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .lfs_paths = paths;
+    }
+
+    /// Store the line counts per file of `worktree`
+    pub fn store_churn(&mut self, worktree: &Path, churn: Vec<FileChurn>, now: Instant) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .churn = churn;
+    }
+
+    /// Store the content hashes of the changed files of `worktree`
+    pub fn store_content_hashes(
+        &mut self,
+        worktree: &Path,
+        hashes: HashMap<PathBuf, String>,
+        now: Instant,
+    ) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .content_hashes = hashes;
+    }
+
+    /// Record that `worktree` is on screen, which keeps its listings
+    pub fn mark_viewed(&mut self, worktree: &Path, now: Instant) {
+        self.sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now))
+            .viewed_at = now;
+    }
+
+    /// Whether a session that is not active should have its changed files
+    /// refreshed (the active one always is)
+    pub fn needs_background_refresh(&self, worktree: &Path, now: Instant) -> bool {
+        self.sessions
+            .get(worktree)
+            .and_then(|entry| entry.refreshed_at)
+            .is_none_or(|at| now.duration_since(at) >= BACKGROUND_REFRESH_INTERVAL)
+    }
+
+    /// Children of `dir` in `worktree`, read through `read_dir` when missing
+    /// or older than `ACTIVE_LISTING_TTL`
+    pub fn listing(
+        &mut self,
+        worktree: &Path,
+        dir: &Path,
+        now: Instant,
+        read_dir: impl FnOnce(&Path) -> Vec<(PathBuf, bool)>,
+    ) -> &[(PathBuf, bool)] {
+        let entry = self
+            .sessions
+            .entry(worktree.to_path_buf())
+            .or_insert_with(|| SessionFiles::new(now));
+        entry.viewed_at = now;
+        let fresh = entry
+            .listings
+            .get(dir)
+            .is_some_and(|listing| now.duration_since(listing.read_at) < ACTIVE_LISTING_TTL);
+        if !fresh {
+            let listing = Listing {
+                entries: read_dir(dir),
+                read_at: now,
+            };
+            entry.listings.insert(dir.to_path_buf(), listing);
+        }
+        &entry.listings[dir].entries
+    }
+
+    /// Forget the listings of `worktree`, so they are read again when shown
+    pub fn invalidate_listings(&mut self, worktree: &Path) {
+        if let Some(entry) = self.sessions.get_mut(worktree) {
+            entry.listings.clear();
+        }
+    }
+
+    /// Forget everything read from `worktree` but the changed files shown
+    /// until the next refresh, which is due at once (its HEAD moved)
+    pub fn invalidate(&mut self, worktree: &Path) {
+        if let Some(entry) = self.sessions.get_mut(worktree) {
+            entry.listings.clear();
+            entry.refreshed_at = None;
+        }
+    }
+
+    /// Drop the listings of sessions not viewed within `LISTING_RETENTION`
+    pub fn evict_idle_listings(&mut self, now: Instant) {
+        for entry in self.sessions.values_mut() {
+            if now.duration_since(entry.viewed_at) >= LISTING_RETENTION {
+                entry.listings.clear();
+            }
+        }
+    }
+
+    /// Drop every worktree not in `worktrees` (removed sessions)
+    pub fn retain_worktrees<'a>(&mut self, worktrees: impl IntoIterator<Item = &'a Path>) {
+        let keep: Vec<&Path> = worktrees.into_iter().collect();
+        self.sessions
+            .retain(|path, _| keep.iter().any(|worktree| worktree == path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::ChangeType;
+
+    fn changed(path: &str) -> ChangedFile {
+        ChangedFile {
+            path: PathBuf::from(path),
+            change_type: ChangeType::Modified,
+            staged: false,
+            old_path: None,
+            similarity: None,
+            submodule: None,
+            outside_sparse: false,
+            nested_repository: false,
+        }
+    }
+
+    fn listing_count(cache: &FileCache) -> usize {
+        cache
+            .sessions
+            .values()
+            .map(|entry| entry.listings.len())
+            .sum()
+    }
+
+    fn paths(files: &[ChangedFile]) -> Vec<&Path> {
+        files.iter().map(|f| f.path.as_path()).collect()
+    }
+
+    #[test]
+    fn test_sessions_are_isolated() {
+        let now = Instant::now();
+        let (a, b) = (Path::new("/wt/a"), Path::new("/wt/b"));
+        let mut cache = FileCache::default();
+        cache.store_changed_files(a, vec![changed("src/lib.rs")], now);
+        cache.store_changed_files(b, vec![changed("README.md"), changed("src/main.rs")], now);
+
+        assert_eq!(
+            paths(&cache.get(a).unwrap().changed_files),
+            vec![Path::new("src/lib.rs")]
+        );
+        assert_eq!(cache.get(b).unwrap().changed_files.len(), 2);
+        assert!(
+            cache
+                .get(a)
+                .unwrap()
+                .dir_changes
+                .contains_key(Path::new("src"))
+        );
+        assert!(!cache.get(b).unwrap().dir_changes.is_empty());
+
+        cache.store_changed_files(a, Vec::new(), now);
+        assert!(cache.get(a).unwrap().changed_files.is_empty());
+        assert_eq!(cache.get(b).unwrap().changed_files.len(), 2);
+
+        let a_listing = cache
+            .listing(a, a, now, |_| vec![(a.join("x"), false)])
+            .to_vec();
+        let b_listing = cache
+            .listing(b, b, now, |_| vec![(b.join("y"), true)])
+            .to_vec();
+        assert_ne!(a_listing, b_listing);
+    }
+
+    #[test]
+    fn test_deletions_outside_sparse_patterns_are_counted_apart() {
+        let now = Instant::now();
+        let wt = Path::new("/wt/a");
+        let mut cache = FileCache::default();
+        let mut outside = changed("lib/gone.rs");
+        outside.change_type = ChangeType::Deleted;
+        outside.outside_sparse = true;
+        cache.store_changed_files(wt, vec![changed("src/main.rs"), outside], now);
+
+        let files = cache.get(wt).unwrap();
+        assert_eq!(paths(&files.changed_files), vec![Path::new("src/main.rs")]);
+        assert_eq!(files.outside_sparse, 1);
+        assert!(!files.dir_changes.contains_key(Path::new("lib")));
+
+        cache.store_changed_files(wt, Vec::new(), now);
+        assert_eq!(cache.get(wt).unwrap().outside_sparse, 0);
+    }
+
+    #[test]
+    fn test_nested_repositories_are_kept_apart() {
+        let now = Instant::now();
+        let wt = Path::new("/wt/a");
+        let mut cache = FileCache::default();
+        let mut nested = changed("vendor/inner/");
+        nested.change_type = ChangeType::Added;
+        nested.nested_repository = true;
+        cache.store_changed_files(wt, vec![changed("src/main.rs"), nested], now);
+
+        let files = cache.get(wt).unwrap();
+        assert_eq!(paths(&files.changed_files), vec![Path::new("src/main.rs")]);
+        assert_eq!(
+            files.nested_repositories,
+            HashSet::from([PathBuf::from("vendor/inner")])
+        );
+        assert!(!files.dir_changes.contains_key(Path::new("vendor")));
+    }
+
+    #[test]
+    fn test_listing_is_reused_until_stale() {
+        let start = Instant::now();
+        let root = Path::new("/wt/a");
+        let mut cache = FileCache::default();
+        let reads = std::cell::Cell::new(0);
+        let read = |_: &Path| {
+            reads.set(reads.get() + 1);
+            Vec::new()
+        };
+
+        cache.listing(root, root, start, read);
+        cache.listing(root, root, start + Duration::from_millis(500), read);
+        cache.listing(root, root, start + ACTIVE_LISTING_TTL, read);
+        assert_eq!(reads.get(), 2);
+
+        cache.invalidate_listings(root);
+        cache.listing(root, root, start + ACTIVE_LISTING_TTL, read);
+        assert_eq!(reads.get(), 3);
+    }
+
+    #[test]
+    fn test_background_refresh_interval() {
+        let now = Instant::now();
+        let wt = Path::new("/wt/a");
+        let mut cache = FileCache::default();
+        assert!(cache.needs_background_refresh(wt, now));
+
+        cache.store_changed_files(wt, Vec::new(), now);
+        assert!(!cache.needs_background_refresh(wt, now + Duration::from_secs(1)));
+        assert!(cache.needs_background_refresh(wt, now + BACKGROUND_REFRESH_INTERVAL));
+
+        // A moved HEAD makes the refresh due at once
+        cache.store_changed_files(wt, vec![changed("a.rs")], now);
+        cache.invalidate(wt);
+        assert!(cache.needs_background_refresh(wt, now));
+        assert_eq!(cache.get(wt).unwrap().changed_files.len(), 1);
+    }
+
+    #[test]
+    fn test_idle_listings_are_evicted() {
+        let start = Instant::now();
+        let (a, b) = (Path::new("/wt/a"), Path::new("/wt/b"));
+        let mut cache = FileCache::default();
+        cache.listing(a, a, start, |_| Vec::new());
+        cache.listing(b, b, start, |_| Vec::new());
+        cache.store_changed_files(b, vec![changed("src/lib.rs")], start);
+
+        let later = start + LISTING_RETENTION;
+        cache.mark_viewed(a, later);
+        cache.evict_idle_listings(later);
+
+        assert_eq!(listing_count(&cache), 1);
+        // Changed files outlive the listings
+        assert_eq!(cache.get(b).unwrap().changed_files.len(), 1);
+    }
+
+    #[test]
+    fn test_top_churn() {
+        let now = Instant::now();
+        let wt = Path::new("/wt/a");
+        let file = |path: &str, added, removed, binary| FileChurn {
+            path: PathBuf::from(path),
+            added,
+            removed,
+            binary,
+        };
+        let mut cache = FileCache::default();
+        cache.store_churn(
+            wt,
+            vec![
+                file("logo.png", 0, 0, true),
+                file("b.rs", 1, 1, false),
+                file("big.rs", 40, 2, false),
+                file("a.rs", 2, 0, false),
+            ],
+            now,
+        );
+
+        let files = cache.get(wt).unwrap();
+        let none = ExcludePatterns::default();
+        assert_eq!(files.churn_totals(&none), (43, 3));
+        let top: Vec<&str> = files
+            .top_churn(3, &none)
+            .iter()
+            .map(|f| f.path.to_str().unwrap())
+            .collect();
+        assert_eq!(top, vec!["big.rs", "a.rs", "b.rs"]);
+        assert_eq!(
+            files.top_churn(10, &none).last().unwrap().path,
+            Path::new("logo.png")
+        );
+
+        let excluded = ExcludePatterns::new(["big.*".to_string()]);
+        assert_eq!(files.churn_totals(&excluded), (3, 1));
+        assert_eq!(files.top_churn(10, &excluded).len(), 3);
+    }
+
+    #[test]
+    fn test_removed_worktrees_are_dropped() {
+        let now = Instant::now();
+        let (a, b) = (Path::new("/wt/a"), Path::new("/wt/b"));
+        let mut cache = FileCache::default();
+        cache.store_changed_files(a, vec![changed("a.rs")], now);
+        cache.store_changed_files(b, vec![changed("b.rs")], now);
+        cache.listing(b, b, now, |_| Vec::new());
+
+        cache.retain_worktrees([a]);
+        assert!(cache.get(a).is_some());
+        assert!(cache.get(b).is_none());
+        assert_eq!(listing_count(&cache), 0);
+
+        // A new worktree at the same path starts empty
+        assert!(cache.needs_background_refresh(b, now));
+    }
+}
@@ -0,0 +1,329 @@
+//! Re-syncing template files from the main worktree into a session
+//!
+//! The `sashiki.template.fileCopy` globs (`.env` files and the like) are
+//! copied only when a worktree is created. Syncing evaluates them against the
+//! main worktree again and compares each match with the session's copy, so
+//! the user can pick which files to create or overwrite. Symlinks are copied
+//! as links; regular files keep their permissions.
+
+use crate::git;
+use crate::worktree_guard;
+use std::path::{Path, PathBuf};
+
+/// What copying a file would do to the session's worktree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKind {
+    Create,
+    Overwrite,
+    /// Same content (or link target) on both sides
+    Identical,
+}
+
+/// A file matched by the template globs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncFile {
+    /// Relative to the worktree roots
+    pub path: PathBuf,
+    pub kind: SyncKind,
+    pub symlink: bool,
+    /// The session's copy was modified after the main worktree's
+    pub dest_newer: bool,
+}
+
+/// Outcome of copying the selected files
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+    pub copied: usize,
+    pub skipped: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl SyncSummary {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} copied, {} skipped, {} failed",
+            self.copied,
+            self.skipped,
+            self.failed.len()
+        )
+    }
+}
+
+/// Match `patterns` in `source_root` and compare each file with its
+/// counterpart in `dest_root`, sorted by path. Invalid patterns are
+/// returned as errors next to the files found by the others.
+pub fn plan(
+    patterns: &[String],
+    source_root: &Path,
+    dest_root: &Path,
+) -> (Vec<SyncFile>, Vec<String>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    for pattern in patterns {
+        let full_pattern = source_root.join(pattern).to_string_lossy().to_string();
+        let paths = match glob::glob(&full_pattern) {
+            Ok(paths) => paths,
+            Err(e) => {
+                errors.push(format!("Invalid pattern '{}': {}", pattern, e));
+                continue;
+            }
+        };
+        for src_path in paths.flatten() {
+            let Ok(relative) = src_path.strip_prefix(source_root) else {
+                continue;
+            };
+            if files.iter().any(|f: &SyncFile| f.path == relative) {
+                continue;
+            }
+            if let Some(file) = compare(source_root, dest_root, relative) {
+                files.push(file);
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    (files, errors)
+}
+
+/// None for directories and unreadable entries
+fn compare(source_root: &Path, dest_root: &Path, relative: &Path) -> Option<SyncFile> {
+    let src = source_root.join(relative);
+    let dest = dest_root.join(relative);
+    let src_meta = std::fs::symlink_metadata(&src).ok()?;
+    let symlink = src_meta.file_type().is_symlink();
+    if !symlink && !src_meta.is_file() {
+        return None;
+    }
+
+    let Ok(dest_meta) = std::fs::symlink_metadata(&dest) else {
+        return Some(SyncFile {
+            path: relative.to_path_buf(),
+            kind: SyncKind::Create,
+            symlink,
+            dest_newer: false,
+        });
+    };
+
+    let identical = if symlink {
+        dest_meta.file_type().is_symlink()
+            && std::fs::read_link(&src).ok() == std::fs::read_link(&dest).ok()
+    } else {
+        dest_meta.is_file()
+            && matches!(
+                (std::fs::read(&src), std::fs::read(&dest)),
+                (Ok(a), Ok(b)) if a == b
+            )
+    };
+    let dest_newer = match (src_meta.modified(), dest_meta.modified()) {
+        (Ok(src_time), Ok(dest_time)) => !identical && dest_time > src_time,
+        _ => false,
+    };
+    Some(SyncFile {
+        path: relative.to_path_buf(),
+        kind: if identical {
+            SyncKind::Identical
+        } else {
+            SyncKind::Overwrite
+        },
+        symlink,
+        dest_newer,
+    })
+}
+
+/// Diff from the session's copy to the main worktree's (what syncing
+/// changes). Symlinks are compared by their targets.
+pub fn diff(source_root: &Path, dest_root: &Path, file: &SyncFile) -> Result<String, String> {
+    let src = source_root.join(&file.path);
+    let dest = dest_root.join(&file.path);
+    if file.symlink {
+        let target = |path: &Path| {
+            std::fs::read_link(path)
+                .map(|t| t.display().to_string())
+                .unwrap_or_else(|_| "(none)".to_string())
+        };
+        return Ok(format!("-> {}\n+> {}", target(&dest), target(&src)));
+    }
+    let old = if file.kind == SyncKind::Create {
+        PathBuf::from(if cfg!(windows) { "NUL" } else { "/dev/null" })
+    } else {
+        dest
+    };
+    git::diff_files(&old, &src).map_err(|e| e.to_string())
+}
+
+/// Copy `paths` (relative) from `source_root` to `dest_root`, replacing
+/// what is there. `skipped` is the number of files left unselected.
+pub fn copy(
+    source_root: &Path,
+    dest_root: &Path,
+    paths: &[PathBuf],
+    skipped: usize,
+) -> SyncSummary {
+    let mut summary = SyncSummary {
+        skipped,
+        ..Default::default()
+    };
+    for path in paths {
+        // A link at the destination itself is replaced, not written through;
+        // the directories above it must stay inside the worktree
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let result = worktree_guard::check(dest_root, parent)
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                copy_one(&source_root.join(path), &dest_root.join(path)).map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => summary.copied += 1,
+            Err(e) => summary.failed.push((path.clone(), e)),
+        }
+    }
+    summary
+}
+
+fn copy_one(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Never write through a link at the destination
+    if std::fs::symlink_metadata(dest).is_ok_and(|m| m.file_type().is_symlink()) {
+        std::fs::remove_file(dest)?;
+    }
+
+    if std::fs::symlink_metadata(src)?.file_type().is_symlink() {
+        let target = std::fs::read_link(src)?;
+        if std::fs::symlink_metadata(dest).is_ok() {
+            std::fs::remove_file(dest)?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&target, dest)?;
+        #[cfg(windows)]
+        std::os::windows::fs::symlink_file(&target, dest)?;
+        return Ok(());
+    }
+
+    // Copies the permission bits as well
+    std::fs::copy(src, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn kinds(files: &[SyncFile]) -> Vec<(&str, SyncKind)> {
+        files
+            .iter()
+            .map(|f| (f.path.to_str().unwrap(), f.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_compares_with_destination() {
+        let main = tempfile::tempdir().unwrap();
+        let session = tempfile::tempdir().unwrap();
+        write(main.path(), ".env", "A=1\n");
+        write(main.path(), "config/.env.local", "B=2\n");
+        write(main.path(), "same.env", "C=3\n");
+        write(session.path(), ".env", "A=0\n");
+        write(session.path(), "same.env", "C=3\n");
+
+        let patterns = vec![
+            "*.env".to_string(),
+            ".env".to_string(),
+            "config/*".to_string(),
+        ];
+        let (files, errors) = plan(&patterns, main.path(), session.path());
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            kinds(&files),
+            vec![
+                (".env", SyncKind::Overwrite),
+                ("config/.env.local", SyncKind::Create),
+                ("same.env", SyncKind::Identical),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_reports_invalid_patterns() {
+        let main = tempfile::tempdir().unwrap();
+        let (files, errors) = plan(&["[".to_string()], main.path(), main.path());
+        assert!(files.is_empty());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_copy_overwrites_and_creates() {
+        let main = tempfile::tempdir().unwrap();
+        let session = tempfile::tempdir().unwrap();
+        write(main.path(), ".env", "A=1\n");
+        write(main.path(), "nested/.env", "B=2\n");
+        write(session.path(), ".env", "A=0\n");
+
+        let paths = vec![PathBuf::from(".env"), PathBuf::from("nested/.env")];
+        let summary = copy(main.path(), session.path(), &paths, 1);
+
+        assert_eq!((summary.copied, summary.skipped), (2, 1));
+        assert!(summary.failed.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(session.path().join(".env")).unwrap(),
+            "A=1\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(session.path().join("nested/.env")).unwrap(),
+            "B=2\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let main = tempfile::tempdir().unwrap();
+        let session = tempfile::tempdir().unwrap();
+        write(main.path(), "secrets/dev.env", "A=1\n");
+        std::os::unix::fs::symlink("secrets/dev.env", main.path().join(".env")).unwrap();
+        write(main.path(), "run.sh", "#!/bin/sh\n");
+        std::fs::set_permissions(
+            main.path().join("run.sh"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let (files, _) = plan(
+            &[".env".to_string(), "run.sh".to_string()],
+            main.path(),
+            session.path(),
+        );
+        assert!(
+            files
+                .iter()
+                .any(|f| f.symlink && f.path == Path::new(".env"))
+        );
+
+        let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+        let summary = copy(main.path(), session.path(), &paths, 0);
+        assert_eq!(summary.copied, 2);
+        assert_eq!(
+            std::fs::read_link(session.path().join(".env")).unwrap(),
+            Path::new("secrets/dev.env")
+        );
+        let mode = std::fs::metadata(session.path().join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        let (files, _) = plan(&[".env".to_string()], main.path(), session.path());
+        assert_eq!(files[0].kind, SyncKind::Identical);
+    }
+}
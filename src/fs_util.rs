@@ -0,0 +1,79 @@
+//! Files Sashiki keeps per session under `.git/sashiki` (notes, session
+//! state, scrollback): a file name for each worktree, and writes that never
+//! leave half a file behind
+//!
+//! Like git writing its config, new contents go to a temporary file next to
+//! the target that is flushed to disk and renamed over it, so a crash leaves
+//! either the old file or the new one.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+/// `worktree_name` as a single file name: path separators and `%` are
+/// percent-encoded (so "feat/x" and "feat-x" stay apart), as is a leading
+/// dot (so ".." cannot leave the directory)
+pub fn file_stem(worktree_name: &str) -> String {
+    let mut stem = String::with_capacity(worktree_name.len());
+    for (i, c) in worktree_name.chars().enumerate() {
+        match c {
+            '%' => stem.push_str("%25"),
+            '/' => stem.push_str("%2F"),
+            '\\' => stem.push_str("%5C"),
+            ':' => stem.push_str("%3A"),
+            '.' if i == 0 => stem.push_str("%2E"),
+            c => stem.push(c),
+        }
+    }
+    stem
+}
+
+/// Write `contents` to a temporary file next to `path`, flush it to disk and
+/// rename it over `path`
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_stem_is_one_file_name() {
+        assert_eq!(file_stem("feature/login"), "feature%2Flogin");
+        assert_eq!(file_stem("a%2Fb"), "a%252Fb");
+        assert_eq!(file_stem(r"win\name:1"), "win%5Cname%3A1");
+        assert_eq!(file_stem(".."), "%2E.");
+        assert_eq!(file_stem("v1.2"), "v1.2");
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_without_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/state.json");
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"two");
+        let files: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name())
+            .collect();
+        assert_eq!(files, vec![std::ffi::OsString::from("state.json")]);
+    }
+}
@@ -4,8 +4,31 @@
 //! - Consistent behavior (remove_worktree already used CLI)
 //! - Hook support (post-checkout etc.)
 //! - Simpler build (no C library dependency)
+//!
+//! Every command runs with stdin closed and terminal prompts off, and is
+//! killed once it runs past `sashiki.git.timeout` or prints more than
+//! `sashiki.git.maxOutput` (see `GitLimits`). Commands that run hooks
+//! (`HOOK_COMMANDS`) may run for minutes and are not timed; `worktree add`
+//! streams its output instead, and `grep` runs until the search that
+//! started it stops. Paths are passed as `OsStr`, so
+//! file names that are not UTF-8 reach git unchanged. Commands that change
+//! the repository wait for a lock another git process holds (see
+//! `crate::git_lock`).
 
+use crate::app_log::{self, LogSource};
+use crate::encoding::{self, TextEncoding};
+use crate::git_lock::{self, Backoff, BusyLock};
+use crate::lfs;
+use crate::sparse::SparseCheckout;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, mpsc};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -17,10 +40,34 @@ pub enum GitError {
     #[error("Failed to parse git output: {0}")]
     #[allow(dead_code)]
     Parse(String),
+    #[error("Git command timed out after {} s", .0.as_secs())]
+    Timeout(Duration),
+    #[error("Git output exceeded {0} bytes")]
+    OutputTooLarge(usize),
+    /// Another git process held a lock for longer than `sashiki.git.lockWait`
+    #[error("{0}")]
+    Busy(BusyLock),
 }
 
 pub type Result<T> = std::result::Result<T, GitError>;
 
+/// Why a directory cannot be added as a session of this repository (see
+/// `GitRepo::linked_worktree`)
+#[derive(Error, Debug)]
+pub enum LinkedWorktreeError {
+    #[error("{} is not a directory", .0.display())]
+    NotADirectory(PathBuf),
+    #[error("{} is not inside a git repository", .0.display())]
+    NotARepository(PathBuf),
+    /// The directory belongs to the repository at this path
+    #[error("{} belongs to another repository", .0.display())]
+    OtherRepository(PathBuf),
+    #[error("Git does not list {} as a worktree of this repository", .0.display())]
+    NotListed(PathBuf),
+    #[error(transparent)]
+    Git(#[from] GitError),
+}
+
 /// Represents a git worktree
 #[derive(Debug, Clone)]
 pub struct Worktree {
@@ -36,8 +83,285 @@ pub const CONFIG_PRE_CREATE_CMD: &str = "sashiki.template.preCreateCommand";
 pub const CONFIG_FILE_COPY: &str = "sashiki.template.fileCopy";
 pub const CONFIG_POST_CREATE_CMD: &str = "sashiki.template.postCreateCommand";
 pub const CONFIG_WORKING_DIR: &str = "sashiki.template.workingDirectory";
+/// Environment of the agents launched in a template's worktrees (`KEY=VALUE`,
+/// one per entry; see `agents`)
+pub const CONFIG_TEMPLATE_ENV: &str = "sashiki.template.env";
+
+/// Git config section prefix for named templates (`sashiki.template.<name>.*`,
+/// the keys above under a name plus `match`)
+pub const CONFIG_TEMPLATE_PREFIX: &str = "sashiki.template";
+
+/// Git config key of a template setting: `key` itself for the unnamed
+/// fallback template, else `key` moved under `sashiki.template.<name>`
+pub fn template_key(name: Option<&str>, key: &str) -> String {
+    match name {
+        Some(name) => {
+            let var = key.rsplit('.').next().unwrap_or(key);
+            format!("{}.{}.{}", CONFIG_TEMPLATE_PREFIX, name, var)
+        }
+        None => key.to_string(),
+    }
+}
+
+/// Git config key for the branch glob that selects a named template
+pub fn template_match_key(name: &str) -> String {
+    format!("{}.{}.match", CONFIG_TEMPLATE_PREFIX, name)
+}
+
+/// Git config section prefix for agent profiles (`sashiki.agent.<name>.*`;
+/// see `agents`)
+pub const CONFIG_AGENT_PREFIX: &str = "sashiki.agent";
+
+/// Git config key `var` of the agent profile `name`
+pub fn agent_key(name: &str, var: &str) -> String {
+    format!("{}.{}.{}", CONFIG_AGENT_PREFIX, name, var)
+}
+
+/// Git config section prefix for hooks (`sashiki.hook.<event>.command`; see
+/// `hooks`)
+pub const CONFIG_HOOK_PREFIX: &str = "sashiki.hook";
+
+/// Git config key `var` of the hook for the event `name`
+pub fn hook_key(name: &str, var: &str) -> String {
+    format!("{}.{}.{}", CONFIG_HOOK_PREFIX, name, var)
+}
+
+/// Git config keys for application settings (see `settings`)
+pub const CONFIG_STARTUP: &str = "sashiki.startup";
+pub const CONFIG_LAST_REPOSITORY: &str = "sashiki.lastRepository";
+/// Repositories open in the window, one value each (see `repositories`)
+pub const CONFIG_OPEN_REPOSITORY: &str = "sashiki.openRepository";
+pub const CONFIG_TERMINAL_SHELL: &str = "sashiki.terminal.shell";
+pub const CONFIG_LAYOUT_MODE: &str = "sashiki.layout.mode";
+pub const CONFIG_WINDOW_TITLE: &str = "sashiki.window.titleFormat";
+
+/// Git config keys for the responses to the terminal bell (see `bell`)
+pub const CONFIG_TERMINAL_BELL_VISUAL: &str = "sashiki.terminal.bellVisual";
+pub const CONFIG_TERMINAL_BELL_AUDIBLE: &str = "sashiki.terminal.bellAudible";
+pub const CONFIG_TERMINAL_BELL_NOTIFY: &str = "sashiki.terminal.bellNotify";
+
+/// Git config key for running terminals in tmux, so their programs survive
+/// closing Sashiki (`tmux`; see `multiplexer`)
+pub const CONFIG_TERMINAL_MULTIPLEXER: &str = "sashiki.terminal.multiplexer";
+
+/// Git config keys for keeping the terminals' scrollback across restarts,
+/// and their colors with it (see `scrollback`)
+pub const CONFIG_TERMINAL_SCROLLBACK: &str = "sashiki.terminal.persistScrollback";
+pub const CONFIG_TERMINAL_SCROLLBACK_COLORS: &str = "sashiki.terminal.persistScrollbackColors";
+
+/// Git config keys for OS notifications per attention reason (see `attention`)
+pub const CONFIG_NOTIFY_OUTPUT: &str = "sashiki.notifications.output";
+pub const CONFIG_NOTIFY_BELL: &str = "sashiki.notifications.bell";
+pub const CONFIG_NOTIFY_EXIT: &str = "sashiki.notifications.exit";
+
+/// Git config key for how many snapshots are kept per branch (see `create_snapshot`)
+pub const CONFIG_SNAPSHOT_KEEP: &str = "sashiki.snapshots.keep";
+
+/// Snapshots kept per branch when `sashiki.snapshots.keep` is not set
+pub const DEFAULT_SNAPSHOT_KEEP: usize = 10;
+
+/// Hidden ref namespace for working tree snapshots
+pub const SNAPSHOT_REF_PREFIX: &str = "refs/sashiki/snapshots";
+
+/// Hidden ref namespace for automatic checkpoints (see `checkpoints`)
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/sashiki/checkpoints";
+
+/// Git config key for the seconds between automatic checkpoints of a session
+pub const CONFIG_CHECKPOINT_INTERVAL: &str = "sashiki.checkpoints.interval";
+
+/// Checkpoint interval when `sashiki.checkpoints.interval` is not set
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 600;
+
+/// Git config key for how many checkpoints are kept per branch
+pub const CONFIG_CHECKPOINT_KEEP: &str = "sashiki.checkpoints.keep";
+
+/// Checkpoints kept per branch when `sashiki.checkpoints.keep` is not set
+pub const DEFAULT_CHECKPOINT_KEEP: usize = 20;
+
+/// Git config key for how many changed files get their diff prefetched (0 disables)
+pub const CONFIG_DIFF_PREFETCH: &str = "sashiki.diff.prefetch";
+
+/// Changed files prefetched when `sashiki.diff.prefetch` is not set
+pub const DEFAULT_DIFF_PREFETCH: usize = 20;
+
+/// Git config key for the size of either side of a file above which the file
+/// view shows a quick line count instead of the diff (bytes; `k`/`m`/`g`
+/// suffixes as in git config)
+pub const CONFIG_DIFF_MAX_SIZE: &str = "sashiki.diff.maxSize";
+
+/// Diff size guard when `sashiki.diff.maxSize` is not set (2 MB)
+pub const DEFAULT_DIFF_MAX_SIZE: u64 = 2 * 1024 * 1024;
+
+/// Git config key for the size above which the file view pages text from
+/// disk and shows no diff (bytes; `k`/`m`/`g` suffixes as in git config)
+pub const CONFIG_VIEWER_MAX_FILE_SIZE: &str = "sashiki.viewer.maxFileSize";
+
+/// Size limit when `sashiki.viewer.maxFileSize` is not set (5 MB)
+pub const DEFAULT_VIEWER_MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Git config key for the longest side (pixels) of an image shown as is;
+/// larger images are downscaled for the file view
+pub const CONFIG_VIEWER_MAX_IMAGE_SIZE: &str = "sashiki.viewer.maxImageSize";
+
+/// Longest image side when `sashiki.viewer.maxImageSize` is not set
+pub const DEFAULT_VIEWER_MAX_IMAGE_SIZE: u32 = 4096;
+
+/// Git config key for the columns between tab stops in the file and diff
+/// views
+pub const CONFIG_VIEWER_TAB_WIDTH: &str = "sashiki.viewer.tabWidth";
+
+/// Tab width when `sashiki.viewer.tabWidth` is not set
+pub const DEFAULT_VIEWER_TAB_WIDTH: usize = 4;
+
+/// Git config key for the file names whose long lines wrap in the file view
+/// (multi-valued globs); a file's wrapping can still be toggled
+pub const CONFIG_VIEWER_WRAP: &str = "sashiki.viewer.wrap";
+
+/// Files that wrap when `sashiki.viewer.wrap` is not set: prose
+pub const DEFAULT_VIEWER_WRAP: &[&str] = &["*.md", "*.markdown", "*.txt", "*.rst", "*.adoc"];
+
+/// Git config section prefix for per-session settings (`sashiki.session.<worktree name>.*`)
+pub const CONFIG_SESSION_PREFIX: &str = "sashiki.session";
+
+/// Git config key for a session's tags (a comma-separated list)
+pub fn session_tags_key(worktree_name: &str) -> String {
+    format!("{}.{}.tags", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for whether a session's terminal output is recorded (bool)
+pub fn session_transcript_key(worktree_name: &str) -> String {
+    format!("{}.{}.transcript", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for whether a session's terminal bell is muted (bool)
+pub fn session_bell_muted_key(worktree_name: &str) -> String {
+    format!("{}.{}.bellMuted", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for whether a session is read-only (bool). Unset, locked
+/// worktrees are read-only, and so is the main one with `CONFIG_MAIN_READ_ONLY`.
+pub fn session_read_only_key(worktree_name: &str) -> String {
+    format!("{}.{}.readOnly", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for whether a session takes automatic checkpoints (bool)
+pub fn session_auto_checkpoint_key(worktree_name: &str) -> String {
+    format!("{}.{}.autoCheckpoint", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for the color picked for a session (`#rrggbb`)
+pub fn session_color_key(worktree_name: &str) -> String {
+    format!("{}.{}.color", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for the agent profile last launched in a session
+pub fn session_agent_key(worktree_name: &str) -> String {
+    format!("{}.{}.agent", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for the quoting of paths typed into a session's terminals
+/// (posix, fish, powershell or cmd; see `shell_escape`)
+pub fn session_shell_quoting_key(worktree_name: &str) -> String {
+    format!("{}.{}.shellQuoting", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for making the main worktree's session read-only by default (bool)
+pub const CONFIG_MAIN_READ_ONLY: &str = "sashiki.session.mainReadOnly";
+
+/// Git config key for the task description a session was created for
+pub fn session_description_key(worktree_name: &str) -> String {
+    format!("{}.{}.description", CONFIG_SESSION_PREFIX, worktree_name)
+}
+
+/// Git config key for the prefix of branch names suggested from a task
+/// description (see `branch_name`)
+pub const CONFIG_BRANCH_PREFIX: &str = "sashiki.worktrees.branchPrefix";
+
+/// Branch prefix when `sashiki.worktrees.branchPrefix` is not set
+pub const DEFAULT_BRANCH_PREFIX: &str = "sashiki/";
+
+/// Git config key for the build artifact patterns removed by worktree
+/// maintenance (multi-valued, see `maintenance`)
+pub const CONFIG_CLEAN_PATTERN: &str = "sashiki.maintenance.cleanPattern";
+
+/// Git config key for asking before quitting or deleting a session while
+/// its terminals are busy (see `busy`)
+pub const CONFIG_CONFIRM_BUSY: &str = "sashiki.confirmBusySessions";
+
+/// Git config key for marking a changed file reviewed once its diff was
+/// open and another file is opened (see `review`)
+pub const CONFIG_REVIEW_MARK_ON_LEAVE: &str = "sashiki.review.markOnLeave";
+
+/// Git config key for the patterns of changed files left out of review
+/// (multi-valued, see `review::ExcludePatterns`)
+pub const CONFIG_REVIEW_EXCLUDE: &str = "sashiki.review.exclude";
+
+/// Git config key for the terminal thumbnails under the sidebar entries of
+/// sessions that are not shown (see `app::minimap`)
+pub const CONFIG_SIDEBAR_MINIMAP: &str = "sashiki.sidebar.minimap";
+
+/// Git config key for the memory and CPU readout of each session in the
+/// sidebar (see `resources`)
+pub const CONFIG_SIDEBAR_RESOURCES: &str = "sashiki.sidebar.resources";
+
+/// Git config keys for the minutes a session may sit on uncommitted changes
+/// before its age in the sidebar turns yellow, then red (see `dirty`)
+pub const CONFIG_SIDEBAR_DIRTY_WARN: &str = "sashiki.sidebar.dirtyWarnMinutes";
+pub const CONFIG_SIDEBAR_DIRTY_ALERT: &str = "sashiki.sidebar.dirtyAlertMinutes";
+
+/// Git config key for symlinks allowed to lead outside their worktree
+/// (multi-valued globs of link paths, see `worktree_guard`)
+pub const CONFIG_ALLOW_SYMLINK: &str = "sashiki.files.allowSymlink";
+
+/// Git config key for how many files of untracked directories the Changes
+/// list shows; the rest are counted (see `GitRepo::get_changed_files_within`)
+pub const CONFIG_CHANGES_UNTRACKED_LIMIT: &str = "sashiki.changes.untrackedLimit";
+
+/// Untracked files listed when `sashiki.changes.untrackedLimit` is not set
+pub const DEFAULT_UNTRACKED_LIMIT: usize = 500;
+
+/// Git config key for the "open externally" command (`{path}` and `{line}` are expanded)
+pub const CONFIG_EXTERNAL_EDITOR: &str = "sashiki.actions.externalEditor";
+
+/// Git config key for the hits after which a workspace search stops
+/// (see `crate::search`)
+pub const CONFIG_SEARCH_MAX_RESULTS: &str = "sashiki.search.maxResults";
+
+/// Search hits kept when `sashiki.search.maxResults` is not set
+pub const DEFAULT_SEARCH_MAX_RESULTS: usize = 2000;
+
+/// Git config key for the seconds a git command may run before it is
+/// killed (0 = no limit)
+pub const CONFIG_GIT_TIMEOUT: &str = "sashiki.git.timeout";
+
+/// Git command timeout when `sashiki.git.timeout` is not set
+pub const DEFAULT_GIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Git config key for the stdout size above which a git command is killed
+/// (bytes; `k`/`m`/`g` suffixes as in git config; 0 = no limit)
+pub const CONFIG_GIT_MAX_OUTPUT: &str = "sashiki.git.maxOutput";
+
+/// Output limit when `sashiki.git.maxOutput` is not set (10 MB)
+pub const DEFAULT_GIT_MAX_OUTPUT: usize = 10 * 1024 * 1024;
+
+/// Git config key for the seconds a command that changes the repository
+/// waits for another git process to release its lock (0 = fail at once)
+pub const CONFIG_GIT_LOCK_WAIT: &str = "sashiki.git.lockWait";
+
+/// Lock wait when `sashiki.git.lockWait` is not set
+pub const DEFAULT_GIT_LOCK_WAIT: Duration = Duration::from_secs(5);
+
+/// Git config keys for the local control server (see `control`): whether
+/// it runs, its port on 127.0.0.1 and the bearer token it asks for
+pub const CONFIG_REMOTE_ENABLED: &str = "sashiki.remote.enabled";
+pub const CONFIG_REMOTE_PORT: &str = "sashiki.remote.port";
+pub const CONFIG_REMOTE_TOKEN: &str = "sashiki.remote.token";
+
+/// Port of the control server when `sashiki.remote.port` is not set
+pub const DEFAULT_REMOTE_PORT: u16 = 7423;
 
 /// Git repository wrapper using CLI commands
+#[derive(Clone)]
 pub struct GitRepo {
     /// Working directory of the main worktree
     workdir: PathBuf,
@@ -45,22 +369,629 @@ pub struct GitRepo {
     git_dir: PathBuf,
 }
 
-/// Run a git command and return stdout on success
+/// Run a git command and return stdout on success.
+///
+/// Failures are recorded in the app log with the full command line.
 fn run_git(workdir: &Path, args: &[&str]) -> Result<String> {
-    let output = std::process::Command::new("git")
-        .args(args)
-        .current_dir(workdir)
-        .output()
-        .map_err(GitError::Exec)?;
+    run_git_os(workdir, &os_args(args))
+}
+
+/// `run_git` taking arguments as `OsStr`, so paths that are not valid UTF-8
+/// reach git unchanged
+fn run_git_os(workdir: &Path, args: &[&OsStr]) -> Result<String> {
+    let stdout = run_git_bytes(workdir, args, &[])?;
+    Ok(String::from_utf8_lossy(&stdout).to_string())
+}
+
+/// `run_git` with extra environment variables (e.g. `GIT_INDEX_FILE`)
+fn run_git_with_env(workdir: &Path, args: &[&str], env: &[(&str, &OsStr)]) -> Result<String> {
+    let stdout = run_git_bytes(workdir, &os_args(args), env)?;
+    Ok(String::from_utf8_lossy(&stdout).to_string())
+}
+
+/// `run_git_os` for file contents and diffs: stdout is decoded in the
+/// encoding it is detected to be in (see `crate::encoding`), so a Shift_JIS
+/// file does not come back as replacement characters
+fn run_git_text_os(workdir: &Path, args: &[&OsStr]) -> Result<String> {
+    let stdout = run_git_bytes(workdir, args, &[])?;
+    Ok(encoding::decode_detected(&stdout).0)
+}
+
+/// `run_git_with_env` returning stdout as raw bytes
+fn run_git_bytes(workdir: &Path, args: &[&OsStr], env: &[(&str, &OsStr)]) -> Result<Vec<u8>> {
+    waiting_for_lock(args, || run_git_bytes_once(workdir, args, env))
+}
+
+/// `run_git_bytes` without waiting for a lock
+fn run_git_bytes_once(workdir: &Path, args: &[&OsStr], env: &[(&str, &OsStr)]) -> Result<Vec<u8>> {
+    let started = Instant::now();
+    let output = execute(workdir, args, env, None, limits())
+        .map_err(|e| log_failure(workdir, args, started, e, ""))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(log_failure(
+            workdir,
+            args,
+            started,
+            GitError::Command(stderr.clone()),
+            &stderr,
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Run `attempt`, a git command with `args`, again while it fails because
+/// another git process holds a lock on the repository, for as long as
+/// `sashiki.git.lockWait` allows. Commands that only read are run once.
+fn waiting_for_lock<T>(args: &[&OsStr], mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    if !git_lock::is_mutating(args) {
+        return attempt();
+    }
+    wait_for_lock(limits().lock_wait, attempt)
+}
+
+/// `waiting_for_lock` with a given wait
+fn wait_for_lock<T>(lock_wait: Duration, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = Backoff::new(lock_wait);
+    loop {
+        let lock = match attempt() {
+            Err(GitError::Command(stderr)) => match git_lock::locked_file(&stderr) {
+                Some(lock) => lock,
+                None => return Err(GitError::Command(stderr)),
+            },
+            result => return result,
+        };
+        // The git queue runs the operation again later instead
+        if git_lock::defer(&lock) {
+            return Err(GitError::Busy(BusyLock {
+                path: lock,
+                stale: false,
+            }));
+        }
+        match backoff.next_pause() {
+            Some(pause) => std::thread::sleep(pause),
+            None => return Err(GitError::Busy(BusyLock::inspect(lock))),
+        }
+    }
+}
+
+/// `run_git` for probes whose failure is an expected answer (e.g. whether a
+/// ref exists): failures are not logged
+fn run_git_unlogged(workdir: &Path, args: &[&str]) -> Result<String> {
+    run_git_unlogged_os(workdir, &os_args(args))
+}
+
+/// `run_git_unlogged` taking arguments as `OsStr`
+fn run_git_unlogged_os(workdir: &Path, args: &[&OsStr]) -> Result<String> {
+    let stdout = run_git_unlogged_bytes(workdir, args)?;
+    Ok(String::from_utf8_lossy(&stdout).to_string())
+}
+
+/// `run_git_unlogged_os` returning stdout as raw bytes
+fn run_git_unlogged_bytes(workdir: &Path, args: &[&OsStr]) -> Result<Vec<u8>> {
+    let output = execute(workdir, args, &[], None, limits())?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
         return Err(GitError::Command(stderr));
     }
 
+    Ok(output.stdout)
+}
+
+/// Record a failed git command in the app log and pass the error on
+fn log_failure(
+    workdir: &Path,
+    args: &[&OsStr],
+    started: Instant,
+    error: GitError,
+    stderr: &str,
+) -> GitError {
+    let reason = match &error {
+        GitError::Command(_) => "nonzero exit status".to_string(),
+        other => other.to_string(),
+    };
+    let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_ref()).collect();
+    app_log::record_command_failure(
+        LogSource::Git,
+        "git",
+        &args,
+        workdir,
+        started.elapsed(),
+        &reason,
+        stderr,
+    );
+    error
+}
+
+/// Run a git command and return stdout + stderr, on success and on failure.
+///
+/// Used for commands whose report goes to stdout even when they fail
+/// (e.g. `stash apply` listing merge conflicts).
+fn run_git_combined(workdir: &Path, args: &[&str]) -> Result<String> {
+    let args = os_args(args);
+    waiting_for_lock(&args, || run_git_combined_once(workdir, &args))
+}
+
+fn run_git_combined_once(workdir: &Path, args: &[&OsStr]) -> Result<String> {
+    let started = Instant::now();
+    let output = execute(workdir, args, &[], None, limits())
+        .map_err(|e| log_failure(workdir, args, started, e, ""))?;
+
+    let mut combined = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        if !combined.is_empty() {
+            combined.push('\n');
+        }
+        combined.push_str(stderr.trim());
+    }
+
+    if !output.status.success() {
+        return Err(log_failure(
+            workdir,
+            args,
+            started,
+            GitError::Command(combined.clone()),
+            &combined,
+        ));
+    }
+    Ok(combined)
+}
+
+/// Run a git command with `input` on stdin and return stdout on success
+fn run_git_with_input(workdir: &Path, args: &[&str], input: &[u8]) -> Result<String> {
+    let args = os_args(args);
+    waiting_for_lock(&args, || run_git_with_input_once(workdir, &args, input))
+}
+
+fn run_git_with_input_once(workdir: &Path, args: &[&OsStr], input: &[u8]) -> Result<String> {
+    let started = Instant::now();
+    let output = execute(workdir, args, &[], Some(input), limits())
+        .map_err(|e| log_failure(workdir, args, started, e, ""))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(log_failure(
+            workdir,
+            args,
+            started,
+            GitError::Command(stderr.clone()),
+            &stderr,
+        ));
+    }
+
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
+/// Run a git command that may take long (e.g. `worktree add` running a
+/// post-checkout hook), passing each line it prints to stdout or stderr to
+/// `on_line` as soon as it is printed.
+///
+/// Hooks can legitimately run for minutes, so `sashiki.git.timeout` does not
+/// apply. Failures are logged like `run_git`, with the last lines printed.
+fn run_git_streaming(workdir: &Path, args: &[&OsStr], on_line: &mut dyn FnMut(&str)) -> Result<()> {
+    waiting_for_lock(args, || {
+        run_git_streaming_once(workdir, args, &mut *on_line)
+    })
+}
+
+/// A `git` command that fails rather than prompts for credentials and
+/// speaks English, since `git_lock` recognises a held lock by its message
+fn git_command() -> Command {
+    let mut command = Command::new("git");
+    command.env("GIT_TERMINAL_PROMPT", "0").env("LC_ALL", "C");
+    // Settings the app stores (e.g. the last repository) stay out of the
+    // user's own config
+    #[cfg(test)]
+    command.env("GIT_CONFIG_GLOBAL", crate::test_support::global_config());
+    command
+}
+
+fn run_git_streaming_once(
+    workdir: &Path,
+    args: &[&OsStr],
+    on_line: &mut dyn FnMut(&str),
+) -> Result<()> {
+    let started = Instant::now();
+    let mut child = git_command()
+        .args(args)
+        .current_dir(workdir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| log_failure(workdir, args, started, e.into(), ""))?;
+
+    let (sender, lines) = mpsc::channel();
+    let stdout = child.stdout.take().map(|stdout| {
+        let sender = sender.clone();
+        std::thread::spawn(move || send_lines(stdout, &sender))
+    });
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| std::thread::spawn(move || send_lines(stderr, &sender)));
+
+    // Ends once both pipes are closed
+    let mut tail = VecDeque::new();
+    for line in lines {
+        on_line(&line);
+        tail.push_back(line);
+        if tail.len() > STREAMED_ERROR_LINES {
+            tail.pop_front();
+        }
+    }
+    for reader in [stdout, stderr].into_iter().flatten() {
+        let _ = reader.join();
+    }
+    let status = child
+        .wait()
+        .map_err(|e| log_failure(workdir, args, started, e.into(), ""))?;
+
+    if !status.success() {
+        let output = Vec::from(tail).join("\n");
+        return Err(log_failure(
+            workdir,
+            args,
+            started,
+            GitError::Command(output.clone()),
+            &output,
+        ));
+    }
+    Ok(())
+}
+
+/// Run a git command passing each line of its stdout (without the line
+/// ending) to `on_line` as it is read, until `on_line` returns false; git is
+/// then killed. Exit status 1 with nothing on stderr is not a failure (`git
+/// grep` finding nothing).
+///
+/// The caller decides when to stop, so `sashiki.git.timeout` and
+/// `sashiki.git.maxOutput` do not apply.
+fn run_git_until(
+    workdir: &Path,
+    args: &[&OsStr],
+    on_line: &mut dyn FnMut(&[u8]) -> bool,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut child = git_command()
+        .args(args)
+        .current_dir(workdir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| log_failure(workdir, args, started, e.into(), ""))?;
+
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| std::thread::spawn(move || read_capped(stderr, MAX_STDERR, None)));
+    let mut stopped = false;
+    if let Some(stdout) = child.stdout.take() {
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let text = line.strip_suffix(b"\n").unwrap_or(&line[..]);
+                    if !on_line(text) {
+                        stopped = true;
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+    }
+    if stopped {
+        let _ = child.kill();
+    }
+    let status = child
+        .wait()
+        .map_err(|e| log_failure(workdir, args, started, e.into(), ""))?;
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+
+    if stopped || status.success() || (status.code() == Some(1) && stderr.is_empty()) {
+        return Ok(());
+    }
+    Err(log_failure(
+        workdir,
+        args,
+        started,
+        GitError::Command(stderr.clone()),
+        &stderr,
+    ))
+}
+
+/// Lines printed last that the error of a failed streamed command keeps
+const STREAMED_ERROR_LINES: usize = 20;
+
+/// Send each line of `reader` (without its line ending) until it is closed
+fn send_lines(reader: impl Read, sender: &mpsc::Sender<String>) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                let text = String::from_utf8_lossy(&line);
+                let text = text.trim_end_matches(['\n', '\r']);
+                if sender.send(text.to_string()).is_err() {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+}
+
+fn os_args<'a>(args: &[&'a str]) -> Vec<&'a OsStr> {
+    args.iter().map(|arg| OsStr::new(*arg)).collect()
+}
+
+/// `<rev>:<path>` naming a file in a commit or index stage, with the path
+/// kept as is
+fn rev_path(rev: &str, path: &Path) -> OsString {
+    let mut spec = OsString::from(rev);
+    spec.push(":");
+    spec.push(path);
+    spec
+}
+
+/// A path git printed after `-z` (never quoted), byte for byte
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// Git for Windows prints paths in UTF-8
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// `path` as the bytes git reads and prints
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().replace('\\', "/").into_bytes())
+}
+
+/// `prefix` and `path` as git prints them in a diff header: in double
+/// quotes with C escapes when they hold control characters, quotes,
+/// backslashes or bytes past ASCII (`core.quotePath`)
+fn quote_path(prefix: &str, path: &Path) -> String {
+    let bytes = [prefix.as_bytes(), &path_bytes(path)].concat();
+    let needs_quotes = |b: u8| !(0x20..0x7f).contains(&b) || b == b'"' || b == b'\\';
+    if !bytes.iter().any(|b| needs_quotes(*b)) {
+        return String::from_utf8_lossy(&bytes).into_owned();
+    }
+    let mut quoted = String::from("\"");
+    for b in bytes {
+        match b {
+            b'\x07' => quoted.push_str("\\a"),
+            b'\x08' => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            b'\x0b' => quoted.push_str("\\v"),
+            b'\x0c' => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            b if needs_quotes(b) => quoted.push_str(&format!("\\{:03o}", b)),
+            b => quoted.push(b as char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// How long a git command may run and how much it may print
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GitLimits {
+    /// Time after which the command is killed (zero = no limit)
+    pub timeout: Duration,
+    /// Bytes of stdout after which the command is killed (zero = no limit)
+    pub max_output: usize,
+    /// Time a command that changes the repository waits for another git
+    /// process to release its lock
+    pub lock_wait: Duration,
+}
+
+impl Default for GitLimits {
+    fn default() -> Self {
+        DEFAULT_GIT_LIMITS
+    }
+}
+
+const DEFAULT_GIT_LIMITS: GitLimits = GitLimits {
+    timeout: DEFAULT_GIT_TIMEOUT,
+    max_output: DEFAULT_GIT_MAX_OUTPUT,
+    lock_wait: DEFAULT_GIT_LOCK_WAIT,
+};
+
+/// Limits of every git command run from now on (see `set_limits`)
+static LIMITS: RwLock<GitLimits> = RwLock::new(DEFAULT_GIT_LIMITS);
+
+/// Apply `sashiki.git.timeout`, `sashiki.git.maxOutput` and
+/// `sashiki.git.lockWait` to the git commands run from now on
+pub fn set_limits(limits: GitLimits) {
+    *LIMITS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = limits;
+}
+
+fn limits() -> GitLimits {
+    *LIMITS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Time a command that changes the repository waits for a lock
+pub fn lock_wait() -> Duration {
+    limits().lock_wait
+}
+
+/// Subcommands that run hooks (a pre-commit running the test suite, a
+/// post-checkout installing dependencies), which `sashiki.git.timeout` does
+/// not cut short
+const HOOK_COMMANDS: &[&str] = &[
+    "am",
+    "checkout",
+    "cherry-pick",
+    "commit",
+    "merge",
+    "pull",
+    "push",
+    "rebase",
+    "revert",
+    "switch",
+];
+
+/// stderr kept per command; the rest is read and dropped
+const MAX_STDERR: usize = 64 * 1024;
+
+/// Output of a git command that ran to the end
+struct GitOutput {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Run git in `workdir` within `limits`.
+///
+/// stdin is `input` (closed when None) and credential prompts fail rather
+/// than wait for a terminal. The child is killed when it runs past the
+/// timeout (unless it runs hooks) or prints more than `max_output`; the
+/// pipes are then left to helpers it started (e.g. an askpass) to close on
+/// their own.
+fn execute(
+    workdir: &Path,
+    args: &[&OsStr],
+    env: &[(&str, &OsStr)],
+    input: Option<&[u8]>,
+    limits: GitLimits,
+) -> Result<GitOutput> {
+    let started = Instant::now();
+    let runs_hooks = git_lock::subcommand(args).is_some_and(|c| HOOK_COMMANDS.contains(&c));
+    let timeout = if runs_hooks {
+        Duration::ZERO
+    } else {
+        limits.timeout
+    };
+    let mut child = git_command()
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(workdir)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+    let overflow = Arc::new(AtomicBool::new(false));
+    let stdout = child.stdout.take().map(|stdout| {
+        let overflow = overflow.clone();
+        std::thread::spawn(move || read_capped(stdout, limits.max_output, Some(&overflow)))
+    });
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| std::thread::spawn(move || read_capped(stderr, MAX_STDERR, None)));
+
+    let mut pause = Duration::from_millis(1);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        let error = if overflow.load(Ordering::Relaxed) {
+            Some(GitError::OutputTooLarge(limits.max_output))
+        } else if !timeout.is_zero() && started.elapsed() >= timeout {
+            Some(GitError::Timeout(timeout))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        }
+        std::thread::sleep(pause);
+        pause = (pause * 2).min(Duration::from_millis(20));
+    };
+
+    let stdout = stdout
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    if overflow.load(Ordering::Relaxed) {
+        return Err(GitError::OutputTooLarge(limits.max_output));
+    }
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    Ok(GitOutput {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Read `reader` to the end, keeping at most `max` bytes (zero = all).
+///
+/// With `overflow`, passing `max` sets it and stops reading (the child is
+/// killed); without, the rest is read and dropped so the child never blocks
+/// on a full pipe.
+fn read_capped(mut reader: impl Read, max: usize, overflow: Option<&AtomicBool>) -> Vec<u8> {
+    let mut kept = Vec::new();
+    let mut chunk = [0u8; 64 * 1024];
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        if max > 0 && kept.len() + n > max {
+            if let Some(overflow) = overflow {
+                overflow.store(true, Ordering::Relaxed);
+                break;
+            }
+            kept.extend_from_slice(&chunk[..max - kept.len()]);
+            continue;
+        }
+        kept.extend_from_slice(&chunk[..n]);
+    }
+    kept
+}
+
 impl GitRepo {
     /// Open a repository at the given path
     pub fn open(path: impl AsRef<Path>) -> Result<Self> {
@@ -107,6 +1038,9 @@ impl GitRepo {
         // Each block has: worktree <path>, HEAD <hash>, branch refs/heads/<name>, [locked], [bare]
         let mut current_path: Option<PathBuf> = None;
         let mut current_branch: Option<String> = None;
+        let mut current_head: Option<String> = None;
+        // Worktrees with a detached HEAD, labeled once all are read
+        let mut detached: Vec<(usize, String)> = Vec::new();
         let mut current_locked = false;
         let mut is_bare = false;
 
@@ -116,6 +1050,11 @@ impl GitRepo {
                 if let Some(path) = current_path.take() {
                     let is_main = worktrees.is_empty();
                     let name = self.worktree_name(&path, is_main);
+                    if current_branch.is_none()
+                        && let Some(head) = current_head.take()
+                    {
+                        detached.push((worktrees.len(), head));
+                    }
                     worktrees.push(Worktree {
                         name,
                         path,
@@ -123,6 +1062,7 @@ impl GitRepo {
                         is_main,
                         locked: current_locked,
                     });
+                    current_head = None;
                     current_locked = false;
                     is_bare = false;
                 }
@@ -133,12 +1073,8 @@ impl GitRepo {
                 current_path = Some(PathBuf::from(path_str));
             } else if let Some(branch_ref) = line.strip_prefix("branch refs/heads/") {
                 current_branch = Some(branch_ref.to_string());
-            } else if line.starts_with("HEAD ") && current_branch.is_none() {
-                // Detached HEAD - use short hash
-                let hash = line.strip_prefix("HEAD ").unwrap_or("");
-                if hash.len() >= 7 {
-                    current_branch = Some(hash[..7].to_string());
-                }
+            } else if let Some(hash) = line.strip_prefix("HEAD ") {
+                current_head = Some(hash.to_string());
             } else if line == "bare" {
                 is_bare = true;
             } else if line.starts_with("locked") {
@@ -147,23 +1083,62 @@ impl GitRepo {
         }
 
         // Flush last block (porcelain output may not end with empty line)
-        if let Some(path) = current_path.take() {
-            if !is_bare {
-                let is_main = worktrees.is_empty();
-                let name = self.worktree_name(&path, is_main);
-                worktrees.push(Worktree {
-                    name,
-                    path,
-                    branch: current_branch.take(),
-                    is_main,
-                    locked: current_locked,
-                });
+        if let Some(path) = current_path.take()
+            && !is_bare
+        {
+            let is_main = worktrees.is_empty();
+            let name = self.worktree_name(&path, is_main);
+            if current_branch.is_none()
+                && let Some(head) = current_head.take()
+            {
+                detached.push((worktrees.len(), head));
+            }
+            worktrees.push(Worktree {
+                name,
+                path,
+                branch: current_branch.take(),
+                is_main,
+                locked: current_locked,
+            });
+        }
+
+        // Detached HEAD - use a tag pointing at it, else the short hash
+        if !detached.is_empty() {
+            let tags = self.tags_by_commit();
+            for (index, head) in detached {
+                worktrees[index].branch = Some(detached_label(&tags, &head));
             }
         }
 
         Ok(worktrees)
     }
 
+    /// The worktree of this repository that `path` is in, as `list_worktrees`
+    /// reports it, for a worktree created outside Sashiki. The directory
+    /// belongs here when `git rev-parse --git-common-dir` run in it names
+    /// this repository's `.git`.
+    pub fn linked_worktree(
+        &self,
+        path: &Path,
+    ) -> std::result::Result<Worktree, LinkedWorktreeError> {
+        if !path.is_dir() {
+            return Err(LinkedWorktreeError::NotADirectory(path.to_path_buf()));
+        }
+        let not_a_repository = |_| LinkedWorktreeError::NotARepository(path.to_path_buf());
+        let common_dir =
+            run_git_unlogged(path, &["rev-parse", "--git-common-dir"]).map_err(not_a_repository)?;
+        let toplevel = run_git_unlogged(path, &["rev-parse", "--show-toplevel"])
+            .map(|toplevel| PathBuf::from(toplevel.trim()))
+            .map_err(not_a_repository)?;
+        if !same_dir(&path.join(common_dir.trim()), &self.git_dir) {
+            return Err(LinkedWorktreeError::OtherRepository(toplevel));
+        }
+        self.list_worktrees()?
+            .into_iter()
+            .find(|worktree| same_dir(&worktree.path, &toplevel))
+            .ok_or(LinkedWorktreeError::NotListed(toplevel))
+    }
+
     /// Determine worktree name
     fn worktree_name(&self, path: &Path, is_main: bool) -> String {
         if is_main {
@@ -174,23 +1149,12 @@ impl GitRepo {
                 .to_string();
         }
 
-        // For linked worktrees, check .git/worktrees/<name>/gitdir to find matching name
-        let worktrees_dir = self.git_dir.join("worktrees");
-        if let Ok(entries) = std::fs::read_dir(&worktrees_dir) {
-            for entry in entries.flatten() {
-                let gitdir_file = entry.path().join("gitdir");
-                if let Ok(content) = std::fs::read_to_string(&gitdir_file) {
-                    let referenced = PathBuf::from(content.trim());
-                    // gitdir contains path to the .git file in the worktree
-                    if let Some(parent) = referenced.parent() {
-                        if parent == path {
-                            if let Some(name) = entry.file_name().to_str() {
-                                return name.to_string();
-                            }
-                        }
-                    }
-                }
-            }
+        // For linked worktrees, the name of their directory in .git/worktrees
+        if let Some(name) = self
+            .worktree_admin_dir(path)
+            .and_then(|dir| dir.file_name()?.to_str().map(str::to_string))
+        {
+            return name;
         }
 
         // Fallback: use directory name
@@ -200,20 +1164,107 @@ impl GitRepo {
             .to_string()
     }
 
-    /// Create a new worktree with the specified branch.
+    /// Directory in `.git/worktrees` of the linked worktree at `path`: the
+    /// one whose `gitdir` file points at the worktree's `.git` file
+    fn worktree_admin_dir(&self, path: &Path) -> Option<PathBuf> {
+        let entries = std::fs::read_dir(self.git_dir.join("worktrees")).ok()?;
+        entries.flatten().map(|entry| entry.path()).find(|dir| {
+            std::fs::read_to_string(dir.join("gitdir"))
+                .is_ok_and(|content| PathBuf::from(content.trim()).parent() == Some(path))
+        })
+    }
+
+    /// Forget the worktrees whose directories are gone (`git worktree
+    /// prune`). Git keeps locked ones, whose directory may only be on a
+    /// drive that is not mounted.
+    pub fn prune_worktrees(&self) -> Result<()> {
+        run_git(&self.workdir, &["worktree", "prune"]).map(|_| ())
+    }
+
+    /// Bring back the deleted directory of `worktree`: the `.git` file is
+    /// written again, the links between it and `.git/worktrees` repaired
+    /// (`git worktree repair`) and the files of its HEAD checked out. What
+    /// was not committed is gone with the old directory.
+    pub fn recreate_worktree(&self, worktree: &Worktree) -> Result<()> {
+        let path = &worktree.path;
+        if path.exists() {
+            return Err(GitError::Command(format!(
+                "'{}' already exists",
+                path.display()
+            )));
+        }
+        let admin = self.worktree_admin_dir(path).ok_or_else(|| {
+            GitError::Command(format!(
+                "No entry in {} for '{}'; it may have been pruned",
+                self.git_dir.join("worktrees").display(),
+                path.display()
+            ))
+        })?;
+        std::fs::create_dir_all(path)?;
+        std::fs::write(path.join(".git"), format!("gitdir: {}\n", admin.display()))?;
+        run_git_os(
+            &self.workdir,
+            &[
+                OsStr::new("worktree"),
+                OsStr::new("repair"),
+                path.as_os_str(),
+            ],
+        )?;
+        run_git(path, &["reset", "--hard", "--quiet"]).map(|_| ())
+    }
+
+    /// Create a new worktree.
+    ///
+    /// With a `branch`, branch resolution is delegated to `git worktree add`:
+    /// 1. With a `base`, create the branch from it (the branch must not exist)
+    /// 2. If a local branch with the name exists, use it
+    /// 3. If a remote branch `origin/{branch}` exists, create local branch from it
+    /// 4. Otherwise, create a new branch from HEAD
     ///
-    /// Branch resolution is delegated to `git worktree add`:
-    /// 1. If a local branch with the name exists, use it
-    /// 2. If a remote branch `origin/{branch}` exists, create local branch from it
-    /// 3. Otherwise, create a new branch from HEAD
+    /// Without a `branch`, `base` (default HEAD) is checked out with a
+    /// detached HEAD. A `base` that does not name a commit is rejected before
+    /// anything is created.
     ///
     /// Stale worktree entries are automatically pruned before creation.
-    pub fn create_worktree(&self, name: &str, branch: &str, path: &Path) -> Result<Worktree> {
-        // Prune stale worktree entries
-        let _ = run_git(&self.workdir, &["worktree", "prune"]);
+    pub fn create_worktree(
+        &self,
+        name: &str,
+        branch: Option<&str>,
+        path: &Path,
+        base: Option<&str>,
+    ) -> Result<Worktree> {
+        self.create_worktree_with(name, branch, path, base, false, &mut |_| {})
+    }
+
+    /// `create_worktree` passing each line `git worktree add` and its hooks
+    /// print to `on_output` as it is printed. With `skip_hooks`, the hooks
+    /// are not run for this worktree (`core.hooksPath` points at an empty
+    /// directory for the one command).
+    pub fn create_worktree_with(
+        &self,
+        name: &str,
+        branch: Option<&str>,
+        path: &Path,
+        base: Option<&str>,
+        skip_hooks: bool,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<Worktree> {
+        if let Some(base) = base {
+            self.resolve_commit(base)?;
+        }
+
+        // Prune stale worktree entries
+        let _ = run_git(&self.workdir, &["worktree", "prune"]);
 
-        // Clean up orphaned worktree directory in .git/worktrees/<name>
+        // Clean up orphaned worktree directory in .git/worktrees/<name>,
+        // unless the prune kept it for being locked
         let git_worktrees_dir = self.git_dir.join("worktrees").join(name);
+        if git_worktrees_dir.join("locked").exists() {
+            return Err(GitError::Command(format!(
+                "Worktree '{}' is locked; unlock or remove it first",
+                name
+            )));
+        }
         if git_worktrees_dir.exists() {
             std::fs::remove_dir_all(&git_worktrees_dir).map_err(|e| {
                 GitError::Command(format!(
@@ -224,21 +1275,63 @@ impl GitRepo {
             })?;
         }
 
-        let path_str = path.to_string_lossy();
+        let mut prefix = Vec::new();
+        if skip_hooks {
+            let no_hooks = self.git_dir.join("sashiki").join("no-hooks");
+            std::fs::create_dir_all(&no_hooks)?;
+            let mut setting = OsString::from("core.hooksPath=");
+            setting.push(&no_hooks);
+            prefix = vec![OsString::from("-c"), setting];
+        }
+        let mut add = |args: &[&OsStr]| {
+            let mut all: Vec<&OsStr> = prefix.iter().map(OsString::as_os_str).collect();
+            all.extend_from_slice(&[OsStr::new("worktree"), OsStr::new("add")]);
+            all.extend_from_slice(args);
+            run_git_streaming(&self.workdir, &all, on_output)
+        };
+        let path_arg = path.as_os_str();
+
+        let Some(branch) = branch else {
+            let base = base.unwrap_or("HEAD");
+            add(&[OsStr::new("--detach"), path_arg, OsStr::new(base)])?;
+            return Ok(Worktree {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                branch: Some(detached_label(
+                    &self.tags_by_commit(),
+                    &self.resolve_commit(base)?,
+                )),
+                is_main: false,
+                locked: false,
+            });
+        };
 
         // Check if a local branch exists
-        let local_exists = run_git(
+        let local_exists = run_git_unlogged(
             &self.workdir,
             &["rev-parse", "--verify", &format!("refs/heads/{}", branch)],
         )
         .is_ok();
 
-        if local_exists {
+        if let Some(base) = base {
+            if local_exists {
+                return Err(GitError::Command(format!(
+                    "Branch '{}' already exists; leave the base empty to check it out",
+                    branch
+                )));
+            }
+            add(&[
+                OsStr::new("-b"),
+                OsStr::new(branch),
+                path_arg,
+                OsStr::new(base),
+            ])?;
+        } else if local_exists {
             // Local branch exists - use it directly
-            run_git(&self.workdir, &["worktree", "add", &path_str, branch])?;
+            add(&[path_arg, OsStr::new(branch)])?;
         } else {
             // Check if a remote tracking branch exists
-            let remote_exists = run_git(
+            let remote_exists = run_git_unlogged(
                 &self.workdir,
                 &[
                     "rev-parse",
@@ -250,23 +1343,21 @@ impl GitRepo {
 
             if remote_exists {
                 // Remote branch exists - create local tracking branch
-                run_git(
-                    &self.workdir,
-                    &[
-                        "worktree",
-                        "add",
-                        "-b",
-                        branch,
-                        &path_str,
-                        &format!("origin/{}", branch),
-                    ],
-                )?;
+                let remote = format!("origin/{}", branch);
+                add(&[
+                    OsStr::new("-b"),
+                    OsStr::new(branch),
+                    path_arg,
+                    OsStr::new(&remote),
+                ])?;
             } else {
                 // Create new branch from HEAD
-                run_git(
-                    &self.workdir,
-                    &["worktree", "add", "-b", branch, &path_str, "HEAD"],
-                )?;
+                add(&[
+                    OsStr::new("-b"),
+                    OsStr::new(branch),
+                    path_arg,
+                    OsStr::new("HEAD"),
+                ])?;
             }
         }
 
@@ -279,6 +1370,60 @@ impl GitRepo {
         })
     }
 
+    /// Full hash of the commit `committish` names (a branch, tag, or full or
+    /// abbreviated commit hash)
+    pub fn resolve_commit(&self, committish: &str) -> Result<String> {
+        let invalid = || {
+            GitError::Command(format!(
+                "'{}' is not a branch, tag or commit of this repository",
+                committish
+            ))
+        };
+        // A leading dash would be taken as an option
+        if committish.is_empty() || committish.starts_with('-') {
+            return Err(invalid());
+        }
+        run_git_unlogged(
+            &self.workdir,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", committish),
+            ],
+        )
+        .map(|s| s.trim().to_string())
+        .map_err(|_| invalid())
+    }
+
+    /// First tag (by name) pointing at each tagged commit
+    fn tags_by_commit(&self) -> HashMap<String, String> {
+        let output = run_git_unlogged(
+            &self.workdir,
+            &[
+                "for-each-ref",
+                "--sort=-refname",
+                "--format=%(objectname) %(*objectname) %(refname:short)",
+                "refs/tags",
+            ],
+        )
+        .unwrap_or_default();
+        let mut tags = HashMap::new();
+        for line in output.lines() {
+            let mut fields = line.splitn(3, ' ');
+            let (Some(object), Some(peeled), Some(tag)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            // Annotated tags point at a tag object; the commit is the peeled one
+            let commit = if peeled.is_empty() { object } else { peeled };
+            // Sorted in reverse, so the first name ends up last
+            tags.insert(commit.to_string(), tag.to_string());
+        }
+        tags
+    }
+
     /// Remove a worktree using git command.
     ///
     /// # Safety
@@ -286,11 +1431,8 @@ impl GitRepo {
     /// the name comes from trusted sources (e.g., our own worktree list) or has
     /// been validated with `validate_branch_name`.
     pub fn remove_worktree(&self, name: &str) -> Result<()> {
-        let output = std::process::Command::new("git")
-            .args(["worktree", "remove", "--force", name])
-            .current_dir(&self.workdir)
-            .output()
-            .map_err(GitError::Exec)?;
+        let args = os_args(&["worktree", "remove", "--force", name]);
+        let output = execute(&self.workdir, &args, &[], None, limits())?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -306,52 +1448,309 @@ impl GitRepo {
         Ok(())
     }
 
-    /// Get list of changed files using `git status --porcelain=v1`
+    /// Get list of changed files using `git status --porcelain=v2 -z`.
+    ///
+    /// Renames keep their original path. Git only reports renames once they
+    /// are staged; an unstaged move (a deleted file plus an untracked file with
+    /// the same content) is paired up here as well. In a sparse worktree,
+    /// files missing only because they lie outside the sparse patterns are
+    /// marked `outside_sparse`. New directories are listed file by file (see
+    /// `get_changed_files_within`).
     pub fn get_changed_files(&self) -> Result<Vec<ChangedFile>> {
-        let output = run_git(&self.workdir, &["status", "--porcelain=v1"])?;
-        let mut files = Vec::new();
+        self.get_changed_files_within(DEFAULT_UNTRACKED_LIMIT)
+            .map(|(files, _)| files)
+    }
 
-        for line in output.lines() {
-            if line.len() < 3 {
-                continue;
+    /// Changed files as `get_changed_files` lists them, with the files of
+    /// untracked directories (git reports only `dir/`) listed one by one up
+    /// to `untracked_limit` of them; also returns how many were left out past
+    /// the limit. Ignored files inside the directories are not listed, and
+    /// repositories nested in the worktree are one `nested_repository` entry.
+    pub fn get_changed_files_within(
+        &self,
+        untracked_limit: usize,
+    ) -> Result<(Vec<ChangedFile>, usize)> {
+        let args = os_args(&["status", "--porcelain=v2", "-z"]);
+        let output = run_git_bytes(&self.workdir, &args, &[])?;
+        let files = parse_status_porcelain_v2(&output);
+        let (mut files, unlisted) = self.expand_untracked_dirs(files, untracked_limit)?;
+        mark_nested_repositories(&mut files, |dir| {
+            is_repository_root(&self.workdir.join(dir))
+        });
+        self.pair_unstaged_moves(&mut files)?;
+        if let Some(sparse) = self.sparse_checkout() {
+            mark_outside_sparse(&mut files, &sparse);
+        }
+        Ok((files, unlisted))
+    }
+
+    /// List the untracked files inside the untracked directories of `files`
+    /// (`git ls-files --others --exclude-standard` applies the ignore rules)
+    fn expand_untracked_dirs(
+        &self,
+        files: Vec<ChangedFile>,
+        limit: usize,
+    ) -> Result<(Vec<ChangedFile>, usize)> {
+        let dirs: Vec<&OsStr> = files
+            .iter()
+            .filter(|f| f.is_untracked_dir())
+            .map(|f| f.path.as_os_str())
+            .collect();
+        if dirs.is_empty() {
+            return Ok((files, 0));
+        }
+        let mut args = os_args(&[
+            "--literal-pathspecs",
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "-z",
+            "--",
+        ]);
+        args.extend(dirs);
+        let listed = run_git_unlogged_bytes(&self.workdir, &args)?;
+        Ok(expand_untracked(files, &listed, limit))
+    }
+
+    /// Untracked files of this worktree that are not ignored, relative to it
+    pub fn untracked_files(&self) -> Result<Vec<PathBuf>> {
+        let listed = run_git(
+            &self.workdir,
+            &["ls-files", "--others", "--exclude-standard", "-z"],
+        )?;
+        Ok(listed
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Leave the directory `path` (relative to the working tree) out of the
+    /// untracked files of every worktree by adding it to `info/exclude`
+    pub fn exclude_dir(&self, path: &Path) -> Result<()> {
+        let info = self.git_dir.join("info");
+        std::fs::create_dir_all(&info)?;
+        let exclude = info.join("exclude");
+        let mut content = std::fs::read_to_string(&exclude).unwrap_or_default();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!(
+            "/{}/\n",
+            path.to_string_lossy().trim_end_matches('/')
+        ));
+        std::fs::write(&exclude, content)?;
+        Ok(())
+    }
+
+    /// Search the tracked files of this worktree with `git grep -n --column
+    /// -I -z`, passing each line it prints to `on_line` until it returns
+    /// false. `flags` choose the pattern syntax and case (e.g. `-F`, `-i`);
+    /// finding nothing is not an error. See `crate::search`.
+    pub fn grep(
+        &self,
+        flags: &[&str],
+        pattern: &str,
+        on_line: &mut dyn FnMut(&[u8]) -> bool,
+    ) -> Result<()> {
+        let mut args = vec!["grep", "-n", "--column", "-I", "-z", "--no-color"];
+        args.extend_from_slice(flags);
+        args.extend(["-e", pattern, "--"]);
+        run_git_until(&self.workdir, &os_args(&args), on_line)
+    }
+
+    /// Sparse-checkout patterns of this worktree, None unless
+    /// `core.sparseCheckout` is on (see `sparse`)
+    pub fn sparse_checkout(&self) -> Option<SparseCheckout> {
+        let flag = |key: &str| {
+            run_git_unlogged(&self.workdir, &["config", "--bool", key])
+                .is_ok_and(|value| value.trim() == "true")
+        };
+        if !flag("core.sparseCheckout") {
+            return None;
+        }
+        let cone = flag("core.sparseCheckoutCone");
+        let output = run_git_unlogged(&self.workdir, &["sparse-checkout", "list"]).ok()?;
+        Some(SparseCheckout::parse(&output, cone))
+    }
+
+    /// Lines added and removed per file since HEAD, staged or not
+    /// (`git diff --numstat HEAD`; untracked files are not included)
+    pub fn diff_numstat(&self) -> Result<Vec<FileChurn>> {
+        let output = run_git_unlogged(&self.workdir, &["diff", "--numstat", "HEAD"])?;
+        Ok(parse_numstat(&output))
+    }
+
+    /// Hash of the current content of each of `files` (`git hash-object`),
+    /// so a file can be told to have changed since it was last looked at.
+    /// Deleted files get `DELETED_CONTENT_HASH`; untracked directories and
+    /// submodules have no hash.
+    pub fn content_hashes(&self, files: &[ChangedFile]) -> Result<HashMap<PathBuf, String>> {
+        let mut hashes = HashMap::new();
+        let mut on_disk = Vec::new();
+        for file in files {
+            if file.change_type == ChangeType::Deleted {
+                hashes.insert(file.path.clone(), DELETED_CONTENT_HASH.to_string());
+            } else if self.workdir.join(&file.path).is_file() {
+                on_disk.push(&file.path);
             }
+        }
+        if on_disk.is_empty() {
+            return Ok(hashes);
+        }
 
-            let index_status = line.as_bytes()[0];
-            let wt_status = line.as_bytes()[1];
-            let path_str = &line[3..];
+        let mut args = vec![OsStr::new("hash-object"), OsStr::new("--")];
+        args.extend(on_disk.iter().map(|path| path.as_os_str()));
+        let output = run_git_unlogged_os(&self.workdir, &args)?;
+        for (path, hash) in on_disk.into_iter().zip(output.lines()) {
+            hashes.insert(path.clone(), hash.trim().to_string());
+        }
+        Ok(hashes)
+    }
 
-            // Handle renamed files: "old -> new"
-            let path = if let Some(arrow_pos) = path_str.find(" -> ") {
-                PathBuf::from(&path_str[arrow_pos + 4..])
-            } else {
-                PathBuf::from(path_str)
-            };
+    /// Which of `paths` are Git LFS files (`filter=lfs`), in one
+    /// `git check-attr` for all of them (see `lfs`)
+    pub fn lfs_paths<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<HashSet<PathBuf>> {
+        let mut input = Vec::new();
+        for path in paths {
+            input.extend_from_slice(path.as_os_str().as_encoded_bytes());
+            input.push(0);
+        }
+        if input.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let output = run_git_with_input(
+            &self.workdir,
+            &["check-attr", "-z", "--stdin", "filter"],
+            &input,
+        )?;
+        Ok(lfs::parse_check_attr(&output))
+    }
 
-            let change_type = if matches!(
-                (index_status, wt_status),
-                (b'A', _) | (_, b'A') | (b'?', b'?')
-            ) {
-                ChangeType::Added
-            } else if matches!((index_status, wt_status), (b'M', _) | (_, b'M')) {
-                ChangeType::Modified
-            } else if matches!((index_status, wt_status), (b'D', _) | (_, b'D')) {
-                ChangeType::Deleted
-            } else if matches!((index_status, wt_status), (b'R', _) | (_, b'R')) {
-                ChangeType::Renamed
-            } else {
-                ChangeType::Unknown
-            };
+    /// Turn unstaged deletions whose content reappears unchanged in an
+    /// untracked file into renames of that file
+    fn pair_unstaged_moves(&self, files: &mut Vec<ChangedFile>) -> Result<()> {
+        let deleted: Vec<PathBuf> = files
+            .iter()
+            .filter(|f| f.change_type == ChangeType::Deleted && !f.staged)
+            .map(|f| f.path.clone())
+            .collect();
+        let untracked: Vec<PathBuf> = files
+            .iter()
+            .filter(|f| f.change_type == ChangeType::Added && !f.staged)
+            .filter(|f| !f.is_untracked_dir())
+            .map(|f| f.path.clone())
+            .collect();
+        if deleted.is_empty() || untracked.is_empty() {
+            return Ok(());
+        }
 
-            let staged = matches!(index_status, b'A' | b'M' | b'D' | b'R');
+        // Blob of each deleted file in the index: "<mode> <hash> <stage>\t<path>"
+        let mut args = os_args(&["--literal-pathspecs", "ls-files", "-s", "-z", "--"]);
+        args.extend(deleted.iter().map(|path| path.as_os_str()));
+        let staged_blobs = run_git_bytes(&self.workdir, &args, &[])?;
+        let mut deleted_by_hash: HashMap<&[u8], &[u8]> = HashMap::new();
+        for entry in staged_blobs.split(|b| *b == b'\0') {
+            if let Some(tab) = entry.iter().position(|b| *b == b'\t')
+                && let Some(hash) = entry[..tab].split(|b| *b == b' ').nth(1)
+            {
+                deleted_by_hash.entry(hash).or_insert(&entry[tab + 1..]);
+            }
+        }
 
-            files.push(ChangedFile {
-                path,
-                change_type,
-                staged,
-            });
+        let mut args = os_args(&["hash-object", "--"]);
+        args.extend(untracked.iter().map(|path| path.as_os_str()));
+        let hashes = run_git_os(&self.workdir, &args)?;
+
+        let mut moves = Vec::new();
+        for (new_path, hash) in untracked.into_iter().zip(hashes.lines()) {
+            if let Some(old_path) = deleted_by_hash.remove(hash.trim().as_bytes()) {
+                moves.push((path_from_bytes(old_path), new_path));
+            }
+        }
+
+        for (old_path, new_path) in moves {
+            files.retain(|f| f.path != old_path);
+            if let Some(file) = files.iter_mut().find(|f| f.path == new_path) {
+                file.change_type = ChangeType::Renamed;
+                file.old_path = Some(old_path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the commit hash HEAD points to
+    pub fn head_commit(&self) -> Result<String> {
+        run_git(&self.workdir, &["rev-parse", "HEAD"]).map(|s| s.trim().to_string())
+    }
+
+    /// Commit HEAD points to, or None before the first commit
+    pub fn resolve_head(&self) -> Option<String> {
+        run_git_unlogged(&self.workdir, &["rev-parse", "--verify", "-q", "HEAD"])
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
+    /// Short id and subject of the commit HEAD points to, or None before
+    /// the first commit
+    pub fn head_subject(&self) -> Option<(String, String)> {
+        let output = run_git_unlogged(
+            &self.workdir,
+            &["log", "-1", "--format=%h%x00%s", "HEAD", "--"],
+        )
+        .ok()?;
+        let (commit, subject) = output.trim_end().split_once('\0')?;
+        Some((commit.to_string(), subject.to_string()))
+    }
+
+    /// Find the best common ancestor of two commits using `git merge-base`.
+    ///
+    /// Returns `Ok(None)` when the histories are unrelated.
+    pub fn merge_base(&self, a: &str, b: &str) -> Result<Option<String>> {
+        let args = os_args(&["merge-base", a, b]);
+        let output = execute(&self.workdir, &args, &[], None, limits())?;
+
+        if output.status.success() {
+            let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            return Ok(Some(hash));
+        }
+
+        // Exit code 1 without a message means "no common ancestor"
+        if output.status.code() == Some(1) && output.stderr.is_empty() {
+            return Ok(None);
         }
 
-        Ok(files)
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(GitError::Command(stderr))
+    }
+
+    /// List files that differ between `base` and the working tree.
+    ///
+    /// Covers both commits made since `base` and uncommitted changes to tracked files.
+    pub fn diff_names_since(&self, base: &str) -> Result<Vec<PathBuf>> {
+        let output = run_git(&self.workdir, &["diff", "--name-only", "-z", base, "--"])?;
+        Ok(output
+            .split('\0')
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Get diff for a specific file against an arbitrary base commit
+    pub fn get_file_diff_against(&self, base: &str, file_path: &Path) -> Result<String> {
+        let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
+        run_git_text_os(
+            &self.workdir,
+            &[
+                OsStr::new("diff"),
+                OsStr::new(base),
+                OsStr::new("--"),
+                relative_path.as_os_str(),
+            ],
+        )
     }
 
     /// Get the worktrees directory path ({project}.worktrees/)
@@ -371,29 +1770,109 @@ impl GitRepo {
     /// Get diff for a specific file using `git diff HEAD`
     pub fn get_file_diff(&self, file_path: &Path) -> Result<String> {
         let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
-        let rel_str = relative_path.to_string_lossy();
+        let path = relative_path.as_os_str();
 
         // Try staged + unstaged diff against HEAD
-        match run_git(&self.workdir, &["diff", "HEAD", "--", &rel_str]) {
+        let args = [
+            OsStr::new("diff"),
+            OsStr::new("HEAD"),
+            OsStr::new("--"),
+            path,
+        ];
+        match run_git_text_os(&self.workdir, &args) {
             Ok(diff) if !diff.is_empty() => Ok(diff),
             _ => {
                 // Fallback: unstaged changes only (for initial commits with no HEAD)
-                run_git(&self.workdir, &["diff", "--", &rel_str]).or_else(|_| Ok(String::new()))
+                let args = [OsStr::new("diff"), OsStr::new("--"), path];
+                run_git_text_os(&self.workdir, &args).or_else(|_| Ok(String::new()))
             }
         }
     }
 
-    /// Get file content from HEAD using `git show HEAD:<path>`
+    /// Get the diff of a renamed file against its original path in HEAD.
+    ///
+    /// An unstaged move is not in the index yet, so git has nothing to pair
+    /// it with; those only get a rename header (their content is unchanged).
+    pub fn get_renamed_file_diff(&self, old_path: &Path, file_path: &Path) -> Result<String> {
+        let old_rel = old_path.strip_prefix(&self.workdir).unwrap_or(old_path);
+        let new_rel = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
+        let diff = run_git_text_os(
+            &self.workdir,
+            &[
+                OsStr::new("--literal-pathspecs"),
+                OsStr::new("diff"),
+                OsStr::new("-M"),
+                OsStr::new("HEAD"),
+                OsStr::new("--"),
+                old_rel.as_os_str(),
+                new_rel.as_os_str(),
+            ],
+        )?;
+        if diff.lines().any(|line| line.starts_with("rename from ")) {
+            return Ok(diff);
+        }
+        Ok(format!(
+            "diff --git {} {}\nsimilarity index 100%\nrename from {}\nrename to {}\n",
+            quote_path("a/", old_rel),
+            quote_path("b/", new_rel),
+            quote_path("", old_rel),
+            quote_path("", new_rel)
+        ))
+    }
+
+    /// Get file content from HEAD as it would be checked out, decoded in the
+    /// encoding it is detected to be in. `git cat-file --filters` runs the
+    /// smudge filters and line ending conversion of the path's attributes,
+    /// so the text compares with the working tree file.
     pub fn get_file_content_from_head(&self, file_path: &Path) -> Result<String> {
         let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
-        let spec = format!("HEAD:{}", relative_path.to_string_lossy());
-        run_git(&self.workdir, &["show", &spec])
+        let spec = rev_path("HEAD", relative_path);
+        run_git_text_os(
+            &self.workdir,
+            &[OsStr::new("cat-file"), OsStr::new("--filters"), &spec],
+        )
+    }
+
+    /// Object id of `file_path` in HEAD (None when HEAD does not have it)
+    pub fn head_blob_id(&self, file_path: &Path) -> Option<String> {
+        let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
+        let spec = rev_path("HEAD", relative_path);
+        let args = [
+            OsStr::new("rev-parse"),
+            OsStr::new("--verify"),
+            OsStr::new("-q"),
+            &spec,
+        ];
+        run_git_unlogged_os(&self.workdir, &args)
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+    }
+
+    /// Size in bytes of blob `id` (`git cat-file -s`)
+    pub fn blob_size(&self, id: &str) -> Option<u64> {
+        run_git_unlogged(&self.workdir, &["cat-file", "-s", id])
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Raw bytes of blob `id` (`git cat-file blob`), for files that are not text
+    pub fn read_blob(&self, id: &str) -> Result<Vec<u8>> {
+        let args = os_args(&["cat-file", "blob", id]);
+        let output = execute(&self.workdir, &args, &[], None, limits())?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(GitError::Command(stderr));
+        }
+        Ok(output.stdout)
     }
 
     /// Generate diff for added-only file (all lines as +)
     pub fn generate_added_diff(&self, file_path: &Path) -> Result<String> {
-        let content =
-            std::fs::read_to_string(file_path).map_err(|e| GitError::Command(e.to_string()))?;
+        let bytes = std::fs::read(file_path).map_err(|e| GitError::Command(e.to_string()))?;
+        let (content, _) = encoding::decode_detected(&bytes);
 
         let file_name = file_path
             .file_name()
@@ -443,141 +1922,2180 @@ impl GitRepo {
         Ok(diff)
     }
 
-    // --- Git config access for session templates ---
+    // --- Discarding changes ---
 
-    /// Read all values for a multi-valued git config key
-    pub fn get_config_values(&self, key: &str) -> Vec<String> {
-        match run_git(&self.workdir, &["config", "--get-all", key]) {
-            Ok(output) => output
-                .lines()
-                .filter(|l| !l.is_empty())
-                .map(|l| l.to_string())
-                .collect(),
-            Err(_) => Vec::new(),
-        }
+    /// Revert one hunk in the working tree by applying its patch in reverse.
+    ///
+    /// `patch` is a single-hunk patch from `git diff` (file headers included),
+    /// decoded from the file's `encoding`; it is encoded back so its lines
+    /// match the bytes in the file. Nothing is written when the hunk no
+    /// longer applies, e.g. because the file was edited after the diff was
+    /// taken.
+    pub fn discard_hunk(&self, patch: &str, encoding: TextEncoding) -> Result<()> {
+        run_git_with_input(
+            &self.workdir,
+            &["apply", "-R", "--unidiff-zero", "-"],
+            &encoding::encode(patch, encoding),
+        )?;
+        Ok(())
     }
 
-    /// Read a single git config value
-    pub fn get_config_value(&self, key: &str) -> Option<String> {
-        run_git(&self.workdir, &["config", "--get", key])
-            .ok()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
+    /// Discard all changes to a file, staged and unstaged.
+    ///
+    /// Files not in HEAD are removed from the index and deleted.
+    pub fn discard_file(&self, file_path: &Path) -> Result<()> {
+        let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
+        let path = relative_path.as_os_str();
+        let spec = rev_path("HEAD", relative_path);
+
+        let exists = [OsStr::new("cat-file"), OsStr::new("-e"), &spec];
+        if run_git_unlogged_os(&self.workdir, &exists).is_ok() {
+            let args = [
+                OsStr::new("checkout"),
+                OsStr::new("HEAD"),
+                OsStr::new("--"),
+                path,
+            ];
+            run_git_os(&self.workdir, &args)?;
+        } else {
+            let args = [
+                OsStr::new("rm"),
+                OsStr::new("--cached"),
+                OsStr::new("--quiet"),
+                OsStr::new("--ignore-unmatch"),
+                OsStr::new("--"),
+                path,
+            ];
+            run_git_os(&self.workdir, &args)?;
+            let full_path = self.workdir.join(relative_path);
+            if full_path.exists() {
+                std::fs::remove_file(&full_path).map_err(|e| GitError::Command(e.to_string()))?;
+            }
+        }
+        Ok(())
     }
 
-    /// Set all values for a multi-valued git config key (local scope)
-    pub fn set_config_values(&self, key: &str, values: &[String]) -> Result<()> {
-        // Remove all existing values first (ignore error if key doesn't exist)
-        let _ = run_git(&self.workdir, &["config", "--local", "--unset-all", key]);
+    // --- Stash management ---
 
-        // Add each value
-        for value in values {
-            run_git(&self.workdir, &["config", "--local", "--add", key, value])?;
+    /// List stashes using `git stash list`.
+    ///
+    /// Stashes live in `refs/stash`, which is shared by every worktree of a repository.
+    pub fn stash_list(&self) -> Result<Vec<StashEntry>> {
+        let output = run_git(
+            &self.workdir,
+            &["stash", "list", "--format=%gd%x00%ct%x00%gs"],
+        )?;
+        Ok(parse_stash_list(&output))
+    }
+
+    /// Stash local changes using `git stash push`
+    pub fn stash_push(&self, message: &str, include_untracked: bool) -> Result<()> {
+        let mut args = vec!["stash", "push"];
+        if include_untracked {
+            args.push("--include-untracked");
+        }
+        if !message.is_empty() {
+            args.extend(["--message", message]);
         }
+        run_git(&self.workdir, &args)?;
         Ok(())
     }
 
-    /// Set a single git config value (local scope)
-    pub fn set_config_value(&self, key: &str, value: &str) -> Result<()> {
-        run_git(&self.workdir, &["config", "--local", key, value])?;
-        Ok(())
+    /// Apply a stash, keeping it in the list.
+    ///
+    /// On conflict the error carries git's full output (including the `CONFLICT` lines).
+    pub fn stash_apply(&self, index: usize) -> Result<String> {
+        run_git_combined(&self.workdir, &["stash", "apply", &stash_ref(index)])
     }
 
-    /// Remove a git config key (local scope)
-    pub fn remove_config_key(&self, key: &str) -> Result<()> {
-        let _ = run_git(&self.workdir, &["config", "--local", "--unset-all", key]);
+    /// Apply a stash and drop it. Git keeps the stash if applying it conflicts.
+    pub fn stash_pop(&self, index: usize) -> Result<String> {
+        run_git_combined(&self.workdir, &["stash", "pop", &stash_ref(index)])
+    }
+
+    /// Delete a stash without applying it
+    pub fn stash_drop(&self, index: usize) -> Result<()> {
+        run_git(&self.workdir, &["stash", "drop", &stash_ref(index)])?;
         Ok(())
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ChangeType {
-    Added,
-    Modified,
-    Deleted,
-    Renamed,
-    Unknown,
-}
+    // --- Rewriting branch history ---
 
-#[derive(Debug, Clone)]
-pub struct ChangedFile {
-    pub path: PathBuf,
-    pub change_type: ChangeType,
-    pub staged: bool,
-}
+    /// Commits on HEAD since `base`, newest first
+    pub fn commits_since(&self, base: &str) -> Result<Vec<BranchCommit>> {
+        let range = format!("{}..HEAD", base);
+        let output = run_git(
+            &self.workdir,
+            &["log", "--format=%H%x00%ct%x00%s", &range, "--"],
+        )?;
+        Ok(parse_log(&output))
+    }
 
-/// Validate a branch name according to Git rules
-pub fn validate_branch_name(name: &str) -> std::result::Result<(), &'static str> {
-    if name.is_empty() {
-        return Err("Branch name cannot be empty");
+    /// Whether tracked files have staged or unstaged changes
+    pub fn has_uncommitted_changes(&self) -> Result<bool> {
+        let output = run_git(
+            &self.workdir,
+            &["status", "--porcelain=v1", "--untracked-files=no"],
+        )?;
+        Ok(!output.trim().is_empty())
     }
-    if name.starts_with('/') || name.ends_with('/') {
-        return Err("Branch name cannot start or end with /");
+
+    /// Replace all commits since `base` with one commit carrying `message`
+    /// (`git reset --soft <base>` + `git commit`). HEAD is put back if the
+    /// commit fails.
+    pub fn squash_since(&self, base: &str, message: &str) -> Result<()> {
+        let head = self.head_commit()?;
+        run_git(&self.workdir, &["reset", "--soft", base])?;
+        if let Err(e) = run_git(&self.workdir, &["commit", "-m", message]) {
+            let _ = run_git(&self.workdir, &["reset", "--soft", &head]);
+            return Err(e);
+        }
+        Ok(())
     }
-    if name.starts_with('.') || name.ends_with('.') {
-        return Err("Branch name cannot start or end with .");
+
+    /// Remove `commits` (newest first, as listed by `commits_since`) from the
+    /// current branch with one `git rebase --onto <c>^ <c>` per commit. Going
+    /// newest first keeps the hashes of the remaining ones valid.
+    ///
+    /// If a rebase stops on a conflict it is aborted and the branch is reset
+    /// to where it was, so nothing is half-done.
+    pub fn drop_commits(&self, commits: &[BranchCommit]) -> Result<()> {
+        let head = self.head_commit()?;
+
+        for commit in commits {
+            let parent = format!("{}^", commit.hash);
+            if let Err(e) =
+                run_git_combined(&self.workdir, &["rebase", "--onto", &parent, &commit.hash])
+            {
+                let _ = run_git_unlogged(&self.workdir, &["rebase", "--abort"]);
+                let _ = run_git(&self.workdir, &["reset", "--keep", &head]);
+                return Err(e);
+            }
+        }
+        Ok(())
     }
-    if name.starts_with('-') {
-        return Err("Branch name cannot start with -");
+
+    // --- Working tree snapshots ---
+
+    /// Record the working tree as a commit on a hidden ref
+    /// (`refs/sashiki/snapshots/<branch>/<timestamp>`) without touching the
+    /// index or the working tree. Returns the ref name.
+    ///
+    /// Like `git stash create`, but untracked (not ignored) files are included:
+    /// the tree is written from a temporary copy of the index after `git add -A`.
+    pub fn create_snapshot(&self, branch: &str) -> Result<String> {
+        let tree = self.write_worktree_tree("sashiki-snapshot-index")?;
+        let message = format!("Sashiki snapshot of {}", branch);
+        let commit = self.commit_worktree_tree(&tree, &message)?;
+        self.store_worktree_commit(SNAPSHOT_REF_PREFIX, branch, &commit)
     }
-    if name.contains("..") {
-        return Err("Branch name cannot contain ..");
+
+    /// Record the working tree as a checkpoint
+    /// (`refs/sashiki/checkpoints/<branch>/<timestamp>`), the way
+    /// `create_snapshot` does. Returns None without recording anything when
+    /// the working tree is the same as in the newest checkpoint.
+    pub fn create_checkpoint(&self, branch: &str, message: &str) -> Result<Option<String>> {
+        let tree = self.write_worktree_tree("sashiki-checkpoint-index")?;
+        let prefix = format!("{}/{}/", CHECKPOINT_REF_PREFIX, branch);
+        if let Some((newest, _, _)) = self.list_worktree_refs(&prefix)?.first()
+            && run_git_unlogged(
+                &self.workdir,
+                &["rev-parse", &format!("{}^{{tree}}", newest)],
+            )
+            .is_ok_and(|newest_tree| newest_tree.trim() == tree)
+        {
+            return Ok(None);
+        }
+        let commit = self.commit_worktree_tree(&tree, message)?;
+        self.store_worktree_commit(CHECKPOINT_REF_PREFIX, branch, &commit)
+            .map(Some)
     }
-    if name.contains("//") {
-        return Err("Branch name cannot contain //");
+
+    /// Tree of the working tree, written from a temporary copy of the index
+    /// (`index_name` in the git directory) so the real one is left alone
+    fn write_worktree_tree(&self, index_name: &str) -> Result<String> {
+        let index = self.git_path("index")?;
+        let temp_index = self.git_path(index_name)?;
+        if index.exists() {
+            std::fs::copy(&index, &temp_index).map_err(|e| GitError::Command(e.to_string()))?;
+        }
+
+        let env = [("GIT_INDEX_FILE", temp_index.as_os_str())];
+        let tree = run_git_with_env(&self.workdir, &["add", "-A"], &env)
+            .and_then(|_| run_git_with_env(&self.workdir, &["write-tree"], &env));
+        let _ = std::fs::remove_file(&temp_index);
+        Ok(tree?.trim().to_string())
     }
-    if name.ends_with(".lock") {
-        return Err("Branch name cannot end with .lock");
+
+    /// Commit `tree` on top of HEAD (if any) without moving anything
+    fn commit_worktree_tree(&self, tree: &str, message: &str) -> Result<String> {
+        // Snapshots are internal: a fixed identity avoids requiring user.name/email
+        let mut args = vec![
+            "-c",
+            "user.name=Sashiki",
+            "-c",
+            "user.email=sashiki@localhost",
+            "commit-tree",
+            tree,
+            "-m",
+            message,
+        ];
+        let head = self.resolve_head();
+        if let Some(head) = &head {
+            args.extend(["-p", head.as_str()]);
+        }
+        Ok(run_git(&self.workdir, &args)?.trim().to_string())
     }
-    // Git forbidden characters
-    const FORBIDDEN_CHARS: &[char] = &[' ', '~', '^', ':', '?', '*', '[', '\\', '\x7f'];
-    for c in name.chars() {
-        if c.is_control() || FORBIDDEN_CHARS.contains(&c) {
-            return Err("Branch name contains invalid character");
+
+    /// Point a new ref `<prefix>/<branch>/<timestamp>` at `commit`
+    fn store_worktree_commit(&self, prefix: &str, branch: &str, commit: &str) -> Result<String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let base = format!("{}/{}/{}", prefix, branch, timestamp);
+        let mut refname = base.clone();
+        let mut n = 1;
+        while run_git_unlogged(&self.workdir, &["rev-parse", "--verify", "-q", &refname]).is_ok() {
+            refname = format!("{}-{}", base, n);
+            n += 1;
         }
+        run_git(&self.workdir, &["update-ref", &refname, commit])?;
+        Ok(refname)
     }
-    if name.contains("@{") {
-        return Err("Branch name cannot contain @{");
+
+    /// Snapshots taken for `branch`, newest first
+    pub fn list_snapshots(&self, branch: &str) -> Result<Vec<Snapshot>> {
+        self.list_worktree_commits(&format!("{}/{}/", SNAPSHOT_REF_PREFIX, branch))
     }
-    Ok(())
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Checkpoints recorded for `branch`, newest first
+    pub fn list_checkpoints(&self, branch: &str) -> Result<Vec<Snapshot>> {
+        self.list_worktree_commits(&format!("{}/{}/", CHECKPOINT_REF_PREFIX, branch))
+    }
 
-    #[test]
-    fn test_validate_branch_name_valid() {
-        assert!(validate_branch_name("feature/test").is_ok());
-        assert!(validate_branch_name("bugfix-123").is_ok());
-        assert!(validate_branch_name("release-v1.0.0").is_ok());
-        assert!(validate_branch_name("my_branch").is_ok());
-        assert!(validate_branch_name("a").is_ok());
+    /// Delete all but the `keep` newest snapshots of `branch`
+    pub fn prune_snapshots(&self, branch: &str, keep: usize) -> Result<()> {
+        self.prune_worktree_refs(&format!("{}/{}/", SNAPSHOT_REF_PREFIX, branch), keep)
     }
 
-    #[test]
-    fn test_validate_branch_name_empty() {
-        assert_eq!(validate_branch_name(""), Err("Branch name cannot be empty"));
+    /// Delete all but the `keep` newest checkpoints of `branch`
+    pub fn prune_checkpoints(&self, branch: &str, keep: usize) -> Result<()> {
+        self.prune_worktree_refs(&format!("{}/{}/", CHECKPOINT_REF_PREFIX, branch), keep)
     }
 
-    #[test]
-    fn test_validate_branch_name_slash_rules() {
-        assert_eq!(
-            validate_branch_name("/test"),
-            Err("Branch name cannot start or end with /")
-        );
-        assert_eq!(
-            validate_branch_name("test/"),
-            Err("Branch name cannot start or end with /")
-        );
-        assert_eq!(
-            validate_branch_name("test//foo"),
-            Err("Branch name cannot contain //")
-        );
+    /// Refs directly below `prefix` (name, creation time, subject), newest first
+    fn list_worktree_refs(&self, prefix: &str) -> Result<Vec<(String, i64, String)>> {
+        let output = run_git(
+            &self.workdir,
+            &[
+                "for-each-ref",
+                "--sort=-refname",
+                "--format=%(refname)%00%(creatordate:unix)%00%(contents:subject)",
+                prefix,
+            ],
+        )?;
+        Ok(parse_snapshot_refs(&output, prefix))
     }
 
-    #[test]
-    fn test_validate_branch_name_dot_rules() {
+    fn list_worktree_commits(&self, prefix: &str) -> Result<Vec<Snapshot>> {
+        let mut snapshots = Vec::new();
+        for (refname, timestamp, subject) in self.list_worktree_refs(prefix)? {
+            let stat = run_git(
+                &self.workdir,
+                &["show", "--shortstat", "--format=", &refname],
+            )?
+            .trim()
+            .to_string();
+            snapshots.push(Snapshot {
+                refname,
+                timestamp,
+                subject,
+                stat,
+            });
+        }
+        Ok(snapshots)
+    }
+
+    fn prune_worktree_refs(&self, prefix: &str, keep: usize) -> Result<()> {
+        for (refname, _, _) in self.list_worktree_refs(prefix)?.into_iter().skip(keep) {
+            run_git(&self.workdir, &["update-ref", "-d", &refname])?;
+        }
+        Ok(())
+    }
+
+    /// Make the index and working tree match a snapshot.
+    ///
+    /// Files added since the snapshot was taken are removed if they are
+    /// tracked; untracked files that are not in the snapshot are left alone.
+    pub fn restore_snapshot(&self, refname: &str) -> Result<()> {
+        let source = format!("--source={}", refname);
+        run_git(
+            &self.workdir,
+            &["restore", &source, "--worktree", "--staged", "--", "."],
+        )?;
+        Ok(())
+    }
+
+    /// Diff from a snapshot or checkpoint to the working tree (tracked files)
+    pub fn diff_snapshot_to_worktree(&self, refname: &str) -> Result<String> {
+        run_git_text_os(&self.workdir, &os_args(&["diff", refname, "--"]))
+    }
+
+    /// Absolute path of a file in this worktree's git directory
+    fn git_path(&self, name: &str) -> Result<PathBuf> {
+        let path =
+            PathBuf::from(run_git(&self.workdir, &["rev-parse", "--git-path", name])?.trim());
+        Ok(if path.is_relative() {
+            self.workdir.join(path)
+        } else {
+            path
+        })
+    }
+
+    // --- Submodules ---
+
+    /// Paths of the submodules declared in `.gitmodules`, relative to the
+    /// working tree (empty without submodules)
+    pub fn submodule_paths(&self) -> Vec<PathBuf> {
+        run_git_unlogged(
+            &self.workdir,
+            &[
+                "config",
+                "--file",
+                ".gitmodules",
+                "-z",
+                "--get-regexp",
+                r"^submodule\..*\.path$",
+            ],
+        )
+        .map(|output| parse_gitmodules_paths(&output))
+        .unwrap_or_default()
+    }
+
+    /// Commit recorded for the submodule at `path` versus the one checked out
+    /// in it, and whether its working tree has changes
+    pub fn submodule_status(&self, path: &Path) -> Result<SubmoduleStatus> {
+        let path_str = path.to_string_lossy();
+        let recorded = run_git_unlogged(
+            &self.workdir,
+            &["rev-parse", "--verify", "-q", &format!("HEAD:{}", path_str)],
+        )
+        .ok()
+        .map(|s| s.trim().to_string());
+
+        // Without its own .git the submodule is not initialized, and git run
+        // inside it would answer for this repository instead
+        let sub_dir = self.workdir.join(path);
+        if !sub_dir.join(".git").exists() {
+            return Ok(SubmoduleStatus {
+                path: path.to_path_buf(),
+                recorded,
+                checked_out: None,
+                dirty: false,
+                commits: Vec::new(),
+            });
+        }
+
+        let sub = GitRepo::open(&sub_dir)?;
+        let checked_out = sub.resolve_head();
+        let dirty = !run_git(&sub_dir, &["status", "--porcelain=v1"])?
+            .trim()
+            .is_empty();
+        let commits = match (&recorded, &checked_out) {
+            (Some(old), Some(new)) if old != new => {
+                let range = format!("{}..{}", old, new);
+                run_git_unlogged(&sub_dir, &["log", "--format=%H%x00%ct%x00%s", &range, "--"])
+                    .map(|output| parse_log(&output))
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(SubmoduleStatus {
+            path: path.to_path_buf(),
+            recorded,
+            checked_out,
+            dirty,
+            commits,
+        })
+    }
+
+    // --- Worktree maintenance ---
+
+    /// Commit time of HEAD (unix seconds), or None before the first commit
+    pub fn last_commit_time(&self) -> Option<i64> {
+        run_git_unlogged(&self.workdir, &["log", "-1", "--format=%ct", "HEAD", "--"])
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    /// Commits HEAD is (ahead of, behind) `base`
+    pub fn ahead_behind(&self, base: &str) -> Result<(usize, usize)> {
+        let range = format!("{}...HEAD", base);
+        let output = run_git_unlogged(
+            &self.workdir,
+            &["rev-list", "--left-right", "--count", &range, "--"],
+        )?;
+        parse_left_right_count(&output)
+            .map(|(behind, ahead)| (ahead, behind))
+            .ok_or_else(|| GitError::Command(format!("Unexpected rev-list output: {}", output)))
+    }
+
+    // --- Remote ---
+
+    /// URL of the `origin` remote, or None without one
+    pub fn origin_url(&self) -> Option<String> {
+        run_git_unlogged(&self.workdir, &["remote", "get-url", "origin"])
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// Default branch of `origin` as last fetched (`refs/remotes/origin/HEAD`)
+    pub fn origin_default_branch(&self) -> Option<String> {
+        run_git_unlogged(
+            &self.workdir,
+            &[
+                "symbolic-ref",
+                "--quiet",
+                "--short",
+                "refs/remotes/origin/HEAD",
+            ],
+        )
+        .ok()
+        .and_then(|name| name.trim().strip_prefix("origin/").map(str::to_string))
+    }
+
+    // --- Merging session branches ---
+
+    /// Merge `branch` into the checked out branch.
+    ///
+    /// Conflicts are not an error: the merge is left in progress and the
+    /// conflicted paths are returned, to be resolved or `abort_merge`d. A
+    /// squash merge is committed as "Squash merge branch '<branch>'".
+    pub fn merge_branch(&self, branch: &str, mode: MergeMode) -> Result<MergeOutcome> {
+        let head = self.head_commit()?;
+        let args: &[&str] = match mode {
+            MergeMode::NoFastForward => &["merge", "--no-ff", "--no-edit", branch],
+            MergeMode::FastForward => &["merge", "--ff-only", branch],
+            MergeMode::Squash => &["merge", "--squash", branch],
+        };
+        if let Err(e) = run_git_combined(&self.workdir, args) {
+            let conflicts = self.conflicted_files()?;
+            if conflicts.is_empty() {
+                return Err(e);
+            }
+            return Ok(MergeOutcome::Conflicts(conflicts));
+        }
+
+        if mode == MergeMode::Squash {
+            // `--squash` only stages the result
+            if !self.has_uncommitted_changes()? {
+                return Ok(MergeOutcome::UpToDate);
+            }
+            let message = format!("Squash merge branch '{}'", branch);
+            run_git(&self.workdir, &["commit", "-q", "-m", &message])?;
+        }
+        if self.head_commit()? == head {
+            return Ok(MergeOutcome::UpToDate);
+        }
+        Ok(MergeOutcome::Merged)
+    }
+
+    /// Paths (relative to the worktree) with unresolved conflicts
+    pub fn conflicted_files(&self) -> Result<Vec<PathBuf>> {
+        let output = run_git(
+            &self.workdir,
+            &["diff", "--name-only", "--diff-filter=U", "-z"],
+        )?;
+        Ok(output
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Mark the conflicts of `path` (relative to the worktree) as resolved by
+    /// staging the file as it is now
+    pub fn mark_resolved(&self, path: &Path) -> Result<()> {
+        run_git_os(
+            &self.workdir,
+            &[OsStr::new("add"), OsStr::new("--"), path.as_os_str()],
+        )?;
+        Ok(())
+    }
+
+    /// Give up a conflicted merge and go back to the state before it. A squash
+    /// merge leaves no `MERGE_HEAD`, so it is undone with `reset --merge`.
+    pub fn abort_merge(&self) -> Result<()> {
+        let merging = run_git_unlogged(
+            &self.workdir,
+            &["rev-parse", "-q", "--verify", "MERGE_HEAD"],
+        )
+        .is_ok();
+        if merging {
+            run_git(&self.workdir, &["merge", "--abort"])?;
+        } else {
+            run_git(&self.workdir, &["reset", "--merge"])?;
+        }
+        Ok(())
+    }
+
+    /// Diff of a conflicted file from our side (index stage 2) to theirs
+    /// (stage 3), without file headers
+    pub fn conflict_sides_diff(&self, file_path: &Path) -> Result<String> {
+        let relative_path = file_path.strip_prefix(&self.workdir).unwrap_or(file_path);
+        let ours = rev_path(":2", relative_path);
+        let theirs = rev_path(":3", relative_path);
+        let output = run_git_os(&self.workdir, &[OsStr::new("diff"), &ours, &theirs])?;
+        Ok(strip_diff_headers(&output))
+    }
+
+    /// Names of all local branches
+    pub fn local_branches(&self) -> Result<Vec<String>> {
+        let output = run_git(
+            &self.workdir,
+            &["for-each-ref", "--format=%(refname:short)", "refs/heads"],
+        )?;
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    /// Delete a local branch whether or not git considers it merged (a
+    /// squash merge leaves the branch unmerged as far as git can tell)
+    pub fn delete_branch(&self, branch: &str) -> Result<()> {
+        run_git(&self.workdir, &["branch", "-D", branch])?;
+        Ok(())
+    }
+
+    // --- Git config access for session templates ---
+
+    /// Set all values for a multi-valued git config key (local scope)
+    pub fn set_config_values(&self, key: &str, values: &[String]) -> Result<()> {
+        // Remove all existing values first (ignore error if key doesn't exist)
+        let _ = run_git_unlogged(&self.workdir, &["config", "--local", "--unset-all", key]);
+
+        // Add each value
+        for value in values {
+            run_git(&self.workdir, &["config", "--local", "--add", key, value])?;
+        }
+        Ok(())
+    }
+
+    /// Set a single git config value (local scope)
+    pub fn set_config_value(&self, key: &str, value: &str) -> Result<()> {
+        run_git(&self.workdir, &["config", "--local", key, value])?;
+        Ok(())
+    }
+
+    /// Remove a git config key (local scope)
+    pub fn remove_config_key(&self, key: &str) -> Result<()> {
+        let _ = run_git_unlogged(&self.workdir, &["config", "--local", "--unset-all", key]);
+        Ok(())
+    }
+
+    /// Remove the per-session settings of a worktree (no-op if there are none)
+    pub fn remove_session_config(&self, worktree_name: &str) {
+        self.remove_config_section(&format!("{}.{}", CONFIG_SESSION_PREFIX, worktree_name));
+    }
+
+    /// Remove a whole config section such as `sashiki.template.<name>`
+    /// (local scope; no-op if it does not exist)
+    pub fn remove_config_section(&self, section: &str) {
+        let _ = run_git_unlogged(
+            &self.workdir,
+            &["config", "--local", "--remove-section", section],
+        );
+    }
+}
+
+/// One layer of git config, read on its own (no merging across scopes)
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigSource<'a> {
+    /// `.git/config` of the repository at the given workdir
+    Local(&'a Path),
+    /// A standalone file in git config syntax
+    File(&'a Path),
+    /// The user's `~/.gitconfig`
+    Global,
+    /// The system-wide git config
+    System,
+}
+
+/// Read all entries of one config source, in file order
+pub fn read_config_entries(source: ConfigSource) -> Result<Vec<(String, String)>> {
+    let output = match source {
+        ConfigSource::Local(workdir) => run_git(workdir, &["config", "--local", "--list", "-z"])?,
+        ConfigSource::File(path) => {
+            let path = path.to_string_lossy();
+            run_git(Path::new("."), &["config", "--file", &path, "--list", "-z"])?
+        }
+        // Missing user or system config files are common: not worth logging
+        ConfigSource::Global => {
+            run_git_unlogged(Path::new("."), &["config", "--global", "--list", "-z"])?
+        }
+        ConfigSource::System => {
+            run_git_unlogged(Path::new("."), &["config", "--system", "--list", "-z"])?
+        }
+    };
+    Ok(parse_config_list(&output))
+}
+
+/// Set a value in the user's global git config
+pub fn set_global_config_value(key: &str, value: &str) -> Result<()> {
+    run_git(Path::new("."), &["config", "--global", key, value])?;
+    Ok(())
+}
+
+/// Replace all values of a multi-valued git config key (global scope)
+pub fn set_global_config_values(key: &str, values: &[String]) -> Result<()> {
+    // Remove all existing values first (ignore error if key doesn't exist)
+    let _ = run_git_unlogged(Path::new("."), &["config", "--global", "--unset-all", key]);
+    for value in values {
+        run_git(Path::new("."), &["config", "--global", "--add", key, value])?;
+    }
+    Ok(())
+}
+
+/// Unified diff between two files outside any repository
+/// (`git diff --no-index`); `old` may be a null device path for a new file.
+///
+/// Empty when the files are identical.
+pub fn diff_files(old: &Path, new: &Path) -> Result<String> {
+    let args = [
+        OsStr::new("diff"),
+        OsStr::new("--no-index"),
+        OsStr::new("--no-color"),
+        OsStr::new("--"),
+        old.as_os_str(),
+        new.as_os_str(),
+    ];
+    let output = execute(Path::new("."), &args, &[], None, limits())?;
+    // Exit status 1 means "differences found"
+    match output.status.code() {
+        Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        _ => Err(GitError::Command(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+/// Parse `git config --list -z` output (`key\nvalue\0` per entry).
+///
+/// A key without a value (`[section] flag`) means true, as in git.
+fn parse_config_list(output: &str) -> Vec<(String, String)> {
+    output
+        .split('\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('\n') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (entry.to_string(), "true".to_string()),
+        })
+        .collect()
+}
+
+/// Content hash of a deleted file (see `GitRepo::content_hashes`)
+pub const DELETED_CONTENT_HASH: &str = "deleted";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    /// Unmerged: left with conflicts by a merge, rebase, pull or stash pop
+    Conflicted,
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    pub path: PathBuf,
+    pub change_type: ChangeType,
+    pub staged: bool,
+    /// Original path of a renamed file
+    pub old_path: Option<PathBuf>,
+    /// Similarity of a rename git detected, in percent
+    pub similarity: Option<u8>,
+    /// What changed in a submodule
+    pub submodule: Option<SubmoduleChange>,
+    /// Deleted only because it lies outside the sparse-checkout patterns
+    pub outside_sparse: bool,
+    /// The root of an independent repository inside the worktree (`dir/`),
+    /// not a submodule; nothing below it is listed
+    pub nested_repository: bool,
+}
+
+impl ChangedFile {
+    /// An untracked directory git did not look into (`dir/`)
+    fn is_untracked_dir(&self) -> bool {
+        self.change_type == ChangeType::Added
+            && !self.staged
+            && self.path.to_string_lossy().ends_with('/')
+    }
+}
+
+/// How a changed submodule differs (the `S<c><m><u>` field of porcelain v2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmoduleChange {
+    /// It has another commit checked out than the one recorded
+    pub new_commits: bool,
+    /// Its tracked files have changes
+    pub modified: bool,
+    /// It has untracked files
+    pub untracked: bool,
+}
+
+impl SubmoduleChange {
+    /// `S<c><m><u>`, None for the `N...` of other entries
+    fn parse(field: &str) -> Option<Self> {
+        let flags = field.strip_prefix('S')?.as_bytes();
+        Some(Self {
+            new_commits: flags.first() == Some(&b'C'),
+            modified: flags.get(1) == Some(&b'M'),
+            untracked: flags.get(2) == Some(&b'U'),
+        })
+    }
+
+    /// In the words of `git status`: "new commits, modified content"
+    pub fn describe(&self) -> String {
+        let parts: Vec<&str> = [
+            (self.new_commits, "new commits"),
+            (self.modified, "modified content"),
+            (self.untracked, "untracked content"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, part)| part)
+        .collect();
+        parts.join(", ")
+    }
+}
+
+/// Parse `git status --porcelain=v2 -z` output.
+///
+/// Entries are separated by NUL: `1 <XY> <sub> <modes> <hashes> <path>` for
+/// ordinary changes, `2 <XY> <sub> <modes> <hashes> <X><score> <path>`
+/// followed by an entry with the original path for renames and copies,
+/// `u <XY> <sub> ...` for unmerged paths and `? <path>` for untracked ones.
+/// `.` stands for an unchanged side of XY. Paths are never quoted with -z, so
+/// names containing spaces or " -> " survive intact.
+fn parse_status_porcelain_v2(output: &[u8]) -> Vec<ChangedFile> {
+    let mut files = Vec::new();
+    let mut entries = output.split(|b| *b == b'\0');
+
+    while let Some(entry) = entries.next() {
+        // Fields before the path: kind, XY, sub, then modes and hashes
+        let (fields, path) = match entry {
+            [b'1', b' ', ..] => split_fields(entry, 8),
+            [b'2', b' ', ..] => split_fields(entry, 9),
+            [b'u', b' ', ..] => split_fields(entry, 10),
+            [b'?', b' ', path @ ..] => (vec!["?", "??"], Some(path)),
+            _ => continue,
+        };
+        let (Some(xy), Some(path)) = (fields.get(1), path) else {
+            continue;
+        };
+        let [index_status, wt_status] = match xy.as_bytes() {
+            [x, y] => [*x, *y],
+            _ => continue,
+        };
+        let path = path_from_bytes(path);
+
+        let (old_path, similarity) = if fields[0] == "2" {
+            let score = fields.get(8).and_then(|field| field.get(1..)?.parse().ok());
+            (entries.next().map(path_from_bytes), score)
+        } else {
+            (None, None)
+        };
+        let submodule = fields
+            .get(2)
+            .and_then(|field| SubmoduleChange::parse(field));
+
+        // Unmerged entries (UU, AA, DU, ...) have their own kind
+        let change_type = if fields[0] == "u" {
+            ChangeType::Conflicted
+        } else if matches!((index_status, wt_status), (b'R', _) | (_, b'R')) {
+            ChangeType::Renamed
+        } else if matches!(
+            (index_status, wt_status),
+            (b'A', _) | (_, b'A') | (b'?', b'?')
+        ) {
+            ChangeType::Added
+        } else if matches!((index_status, wt_status), (b'M', _) | (_, b'M')) {
+            ChangeType::Modified
+        } else if matches!((index_status, wt_status), (b'D', _) | (_, b'D')) {
+            ChangeType::Deleted
+        } else {
+            ChangeType::Unknown
+        };
+
+        let staged = change_type != ChangeType::Conflicted
+            && matches!(index_status, b'A' | b'M' | b'D' | b'R');
+        let renamed = change_type == ChangeType::Renamed;
+
+        files.push(ChangedFile {
+            path,
+            change_type,
+            staged,
+            old_path: old_path.filter(|_| renamed),
+            similarity: similarity.filter(|_| renamed),
+            submodule,
+            outside_sparse: false,
+            nested_repository: false,
+        });
+    }
+
+    files
+}
+
+/// Replace the untracked directories among `files` with the untracked files
+/// in them (`listed` as `git ls-files -z` prints them), at most `limit` files
+/// in all. Returns the files and how many were left out past the limit. A
+/// directory listed as itself (a nested repository) is kept as it is.
+fn expand_untracked(
+    files: Vec<ChangedFile>,
+    listed: &[u8],
+    limit: usize,
+) -> (Vec<ChangedFile>, usize) {
+    let listed: Vec<PathBuf> = listed
+        .split(|b| *b == b'\0')
+        .filter(|p| !p.is_empty())
+        .map(path_from_bytes)
+        .collect();
+    let mut remaining = limit;
+    let mut unlisted = 0;
+    let mut expanded = Vec::with_capacity(files.len());
+    for file in files {
+        if !file.is_untracked_dir() {
+            expanded.push(file);
+            continue;
+        }
+        let inside: Vec<&PathBuf> = listed
+            .iter()
+            .filter(|path| path.starts_with(&file.path) && **path != file.path)
+            .collect();
+        if inside.is_empty() {
+            expanded.push(file);
+            continue;
+        }
+        for path in inside {
+            if remaining == 0 {
+                unlisted += 1;
+                continue;
+            }
+            remaining -= 1;
+            expanded.push(ChangedFile {
+                path: path.clone(),
+                ..file.clone()
+            });
+        }
+    }
+    (expanded, unlisted)
+}
+
+/// Mark the untracked directories `is_root` holds for repositories of their
+/// own (git lists a nested clone as `dir/` and never looks inside) and drop
+/// any other entry below them, which belongs to the inner repository
+fn mark_nested_repositories(files: &mut Vec<ChangedFile>, is_root: impl Fn(&Path) -> bool) {
+    let mut roots = Vec::new();
+    for file in files.iter_mut() {
+        if file.is_untracked_dir() && is_root(&file.path) {
+            file.nested_repository = true;
+            roots.push(file.path.clone());
+        }
+    }
+    files.retain(|f| f.nested_repository || !roots.iter().any(|root| f.path.starts_with(root)));
+}
+
+/// Split the nested repository entries off `files`, returning the other
+/// files and the roots of the nested repositories (relative, no trailing
+/// slash)
+pub fn split_nested_repositories(files: Vec<ChangedFile>) -> (Vec<ChangedFile>, HashSet<PathBuf>) {
+    let (nested, files): (Vec<_>, Vec<_>) = files.into_iter().partition(|f| f.nested_repository);
+    let roots = nested
+        .into_iter()
+        .map(|f| f.path.components().collect())
+        .collect();
+    (files, roots)
+}
+
+/// Whether `dir` is the top of a repository: it has a `.git` directory, or
+/// the `.git` file of a linked worktree or submodule
+pub fn is_repository_root(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// Mark the unstaged deletions of files outside the sparse patterns
+fn mark_outside_sparse(files: &mut [ChangedFile], sparse: &SparseCheckout) {
+    for file in files {
+        file.outside_sparse = file.change_type == ChangeType::Deleted
+            && !file.staged
+            && !sparse.includes(&file.path, false);
+    }
+}
+
+/// The first `count` space-separated fields of a porcelain v2 entry and the
+/// path after them (None when the entry is cut short). The fields are
+/// ASCII; the path is left as the bytes git printed.
+fn split_fields(entry: &[u8], count: usize) -> (Vec<&str>, Option<&[u8]>) {
+    let mut fields: Vec<&[u8]> = entry.splitn(count + 1, |b| *b == b' ').collect();
+    let path = (fields.len() > count).then(|| fields.remove(count));
+    let fields = fields
+        .into_iter()
+        .map(|field| std::str::from_utf8(field).unwrap_or_default())
+        .collect();
+    (fields, path)
+}
+
+/// Label shown instead of a branch for a worktree with a detached HEAD at
+/// `commit`: a tag pointing at it, else the abbreviated hash
+fn detached_label(tags: &HashMap<String, String>, commit: &str) -> String {
+    tags.get(commit)
+        .cloned()
+        .unwrap_or_else(|| commit.chars().take(7).collect())
+}
+
+/// Lines added and removed in one file (see `diff_numstat`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChurn {
+    /// Path after a rename, relative to the worktree
+    pub path: PathBuf,
+    pub added: usize,
+    pub removed: usize,
+    /// Git prints no line counts for binary files
+    pub binary: bool,
+}
+
+impl FileChurn {
+    /// Lines touched, for ranking files
+    pub fn churn(&self) -> usize {
+        self.added + self.removed
+    }
+}
+
+/// Parse `git diff --numstat` output.
+///
+/// Lines are `<added>\t<removed>\t<path>`, with `-` for both counts of a
+/// binary file. A rename is shown as `old => new`, or with the common parts
+/// outside braces (`src/{old => new}/lib.rs`); the new path is kept.
+fn parse_numstat(output: &str) -> Vec<FileChurn> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let (added, removed, path) = (fields.next()?, fields.next()?, fields.next()?);
+            let binary = added == "-" && removed == "-";
+            let (added, removed) = if binary {
+                (0, 0)
+            } else {
+                (added.parse().ok()?, removed.parse().ok()?)
+            };
+            Some(FileChurn {
+                path: PathBuf::from(renamed_to(path)),
+                added,
+                removed,
+                binary,
+            })
+        })
+        .collect()
+}
+
+/// New path of a numstat path that may describe a rename
+fn renamed_to(path: &str) -> String {
+    if let (Some(open), Some(close)) = (path.find('{'), path.find('}'))
+        && open < close
+        && let Some((_, new)) = path[open + 1..close].split_once(" => ")
+    {
+        let joined = format!("{}{}{}", &path[..open], new, &path[close + 1..]);
+        // "src/{old => }/lib.rs" leaves an empty component behind
+        return joined.replace("//", "/");
+    }
+    match path.split_once(" => ") {
+        Some((_, new)) => new.to_string(),
+        None => path.to_string(),
+    }
+}
+
+/// A stash entry from `git stash list`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StashEntry {
+    /// N in `stash@{N}`
+    pub index: usize,
+    /// Branch the stash was created on (None for unrecognized subjects)
+    pub branch: Option<String>,
+    pub message: String,
+    /// Creation time (unix seconds)
+    pub timestamp: i64,
+}
+
+/// A commit listed by `GitRepo::commits_since`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchCommit {
+    pub hash: String,
+    /// Commit time (unix seconds)
+    pub timestamp: i64,
+    pub subject: String,
+}
+
+impl BranchCommit {
+    pub fn short_hash(&self) -> &str {
+        short_sha(&self.hash)
+    }
+}
+
+/// How `GitRepo::merge_branch` merges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// Always create a merge commit
+    #[default]
+    NoFastForward,
+    /// Only move the branch forward; fails when it has diverged
+    FastForward,
+    /// Commit the branch's changes as one new commit
+    Squash,
+}
+
+/// Result of `GitRepo::merge_branch`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeOutcome {
+    Merged,
+    /// Nothing to merge
+    UpToDate,
+    /// The merge stopped; these paths need resolving
+    Conflicts(Vec<PathBuf>),
+}
+
+/// A working tree snapshot or checkpoint (see `GitRepo::create_snapshot`
+/// and `GitRepo::create_checkpoint`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Full ref name, `refs/sashiki/{snapshots,checkpoints}/<branch>/<timestamp>`
+    pub refname: String,
+    /// Creation time (unix seconds)
+    pub timestamp: i64,
+    /// Commit message (e.g. "checkpoint 14:32, 12 files")
+    pub subject: String,
+    /// `--shortstat` of the snapshot against the commit it was taken on
+    pub stat: String,
+}
+
+/// State of a submodule (see `GitRepo::submodule_status`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmoduleStatus {
+    /// Relative to the superproject's working tree
+    pub path: PathBuf,
+    /// Commit recorded in the superproject's HEAD (None for a new submodule)
+    pub recorded: Option<String>,
+    /// Commit checked out in the submodule (None when not initialized)
+    pub checked_out: Option<String>,
+    /// Whether the submodule's working tree has changes
+    pub dirty: bool,
+    /// Commits from `recorded` to `checked_out`, newest first (empty when the
+    /// checked out commit is not ahead of the recorded one)
+    pub commits: Vec<BranchCommit>,
+}
+
+/// `diff` from its first hunk on, dropping the `diff --git`, `index` and
+/// `---`/`+++` lines
+fn strip_diff_headers(diff: &str) -> String {
+    diff.find("\n@@")
+        .map(|i| diff[i + 1..].to_string())
+        .unwrap_or_else(|| {
+            if diff.starts_with("@@") {
+                diff.to_string()
+            } else {
+                String::new()
+            }
+        })
+}
+
+/// Abbreviated commit hash for display
+pub fn short_sha(sha: &str) -> &str {
+    &sha[..sha.len().min(7)]
+}
+
+/// Parse `rev-list --left-right --count` output (`<left>\t<right>`)
+fn parse_left_right_count(output: &str) -> Option<(usize, usize)> {
+    let (left, right) = output.trim().split_once('\t')?;
+    Some((left.parse().ok()?, right.parse().ok()?))
+}
+
+/// Parse `git config --file .gitmodules -z --get-regexp` output: entries of
+/// `<key>\n<value>` separated by NUL
+fn parse_gitmodules_paths(output: &str) -> Vec<PathBuf> {
+    output
+        .split('\0')
+        .filter_map(|entry| entry.split_once('\n'))
+        .map(|(_, path)| PathBuf::from(path.trim_end_matches('/')))
+        .filter(|path| !path.as_os_str().is_empty())
+        .collect()
+}
+
+/// Parse `for-each-ref --format=%(refname)%00%(creatordate:unix)%00%(contents:subject)`
+/// output, keeping only refs directly below `prefix` (not those of branches
+/// nested below it, e.g. `feature/x` for `feature`)
+fn parse_snapshot_refs(output: &str, prefix: &str) -> Vec<(String, i64, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let refname = fields.next()?;
+            let timestamp = fields.next()?.parse().ok()?;
+            let subject = fields.next().unwrap_or_default().to_string();
+            let name = refname.strip_prefix(prefix)?;
+            if name.contains('/') {
+                return None;
+            }
+            Some((refname.to_string(), timestamp, subject))
+        })
+        .collect()
+}
+
+/// Whether `a` and `b` name the same directory (symlinks and `..` resolved)
+fn same_dir(a: &Path, b: &Path) -> bool {
+    a == b
+        || a.canonicalize()
+            .is_ok_and(|a| b.canonicalize().is_ok_and(|b| a == b))
+}
+
+/// Parse `git log --format=%H%x00%ct%x00%s` output
+fn parse_log(output: &str) -> Vec<BranchCommit> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let hash = fields.next()?.to_string();
+            let timestamp = fields.next()?.parse().ok()?;
+            let subject = fields.next().unwrap_or_default().to_string();
+            Some(BranchCommit {
+                hash,
+                timestamp,
+                subject,
+            })
+        })
+        .collect()
+}
+
+fn stash_ref(index: usize) -> String {
+    format!("stash@{{{}}}", index)
+}
+
+/// Parse `git stash list --format=%gd%x00%ct%x00%gs` output.
+///
+/// Fields are NUL-separated so the message may contain anything. The subject
+/// is `On <branch>: <message>` or `WIP on <branch>: <hash> <subject>`; branch
+/// names cannot contain `:`, so the first `": "` ends the branch.
+fn parse_stash_list(output: &str) -> Vec<StashEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let selector = fields.next()?;
+            let timestamp = fields.next()?.parse().ok()?;
+            let subject = fields.next()?;
+
+            let index = selector
+                .strip_prefix("stash@{")?
+                .strip_suffix('}')?
+                .parse()
+                .ok()?;
+
+            let (branch, message) = subject
+                .strip_prefix("WIP on ")
+                .or_else(|| subject.strip_prefix("On "))
+                .and_then(|rest| rest.split_once(": "))
+                .map(|(branch, message)| (Some(branch.to_string()), message.to_string()))
+                .unwrap_or_else(|| (None, subject.to_string()));
+
+            Some(StashEntry {
+                index,
+                branch,
+                message,
+                timestamp,
+            })
+        })
+        .collect()
+}
+
+/// Validate a branch name according to Git rules
+pub fn validate_branch_name(name: &str) -> std::result::Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("Branch name cannot be empty");
+    }
+    if name.starts_with('/') || name.ends_with('/') {
+        return Err("Branch name cannot start or end with /");
+    }
+    if name.starts_with('.') || name.ends_with('.') {
+        return Err("Branch name cannot start or end with .");
+    }
+    if name.starts_with('-') {
+        return Err("Branch name cannot start with -");
+    }
+    if name.contains("..") {
+        return Err("Branch name cannot contain ..");
+    }
+    if name.contains("//") {
+        return Err("Branch name cannot contain //");
+    }
+    if name.ends_with(".lock") {
+        return Err("Branch name cannot end with .lock");
+    }
+    // Git forbidden characters
+    const FORBIDDEN_CHARS: &[char] = &[' ', '~', '^', ':', '?', '*', '[', '\\', '\x7f'];
+    for c in name.chars() {
+        if c.is_control() || FORBIDDEN_CHARS.contains(&c) {
+            return Err("Branch name contains invalid character");
+        }
+    }
+    if name.contains("@{") {
+        return Err("Branch name cannot contain @{");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, init_repo, init_repo_with_feature_worktree};
+
+    fn churn(path: &str, added: usize, removed: usize, binary: bool) -> FileChurn {
+        FileChurn {
+            path: PathBuf::from(path),
+            added,
+            removed,
+            binary,
+        }
+    }
+
+    #[test]
+    fn test_merge_base_and_diff_names_since() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let base = repo.head_commit().unwrap();
+
+        git(dir.path(), &["checkout", "-q", "-b", "feature"]);
+        std::fs::write(dir.path().join("committed.txt"), "a\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "feature work"]);
+        std::fs::write(dir.path().join("README.md"), "changed\n").unwrap();
+
+        assert_eq!(repo.merge_base("HEAD", "main").unwrap(), Some(base.clone()));
+
+        let mut names = repo.diff_names_since(&base).unwrap();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![PathBuf::from("README.md"), PathBuf::from("committed.txt")]
+        );
+    }
+
+    #[test]
+    fn test_merge_base_unrelated_histories() {
+        let dir = init_repo();
+        git(dir.path(), &["checkout", "-q", "--orphan", "unrelated"]);
+        git(dir.path(), &["commit", "-q", "-m", "orphan"]);
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.merge_base("HEAD", "main").unwrap(), None);
+    }
+
+    #[test]
+    fn test_renamed_file_with_edits_diffs_against_old_path() {
+        let dir = init_repo();
+        let content: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(dir.path().join("old.txt"), &content).unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "add old"]);
+
+        git(dir.path(), &["mv", "old.txt", "new.txt"]);
+        std::fs::write(
+            dir.path().join("new.txt"),
+            content.replace("line 5\n", "line five\n"),
+        )
+        .unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let files = repo.get_changed_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("new.txt"));
+        assert_eq!(files[0].old_path, Some(PathBuf::from("old.txt")));
+        assert_eq!(files[0].change_type, ChangeType::Renamed);
+        assert!(files[0].staged);
+
+        let diff = repo
+            .get_renamed_file_diff(Path::new("old.txt"), &dir.path().join("new.txt"))
+            .unwrap();
+        assert!(diff.contains("rename from old.txt\nrename to new.txt"));
+        assert!(diff.contains("-line 5\n+line five\n"));
+        assert!(!diff.contains("+line 1\n"));
+    }
+
+    #[test]
+    fn test_rename_of_path_containing_arrow() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("a -> b.txt"), "arrow\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "add arrow"]);
+        git(dir.path(), &["mv", "a -> b.txt", "plain.txt"]);
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let files = repo.get_changed_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("plain.txt"));
+        assert_eq!(files[0].old_path, Some(PathBuf::from("a -> b.txt")));
+    }
+
+    #[test]
+    fn test_untracked_directory_is_listed_file_by_file() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "ignore logs"]);
+        let feature = dir.path().join("feature");
+        std::fs::create_dir_all(feature.join("nested")).unwrap();
+        std::fs::write(feature.join("mod.rs"), "mod nested;\n").unwrap();
+        std::fs::write(feature.join("nested/lib.rs"), "fn f() {}\n").unwrap();
+        std::fs::write(feature.join("build.log"), "noise\n").unwrap();
+        std::fs::write(feature.join("nested/debug.log"), "noise\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let mut files = repo.get_changed_files().unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("feature/mod.rs"),
+                Path::new("feature/nested/lib.rs")
+            ]
+        );
+        assert!(
+            files
+                .iter()
+                .all(|f| f.change_type == ChangeType::Added && !f.staged)
+        );
+    }
+
+    #[test]
+    fn test_untracked_files_past_the_limit_are_counted() {
+        let dir = init_repo();
+        for d in ["a", "b"] {
+            std::fs::create_dir(dir.path().join(d)).unwrap();
+            for i in 0..3 {
+                std::fs::write(dir.path().join(d).join(format!("{}.txt", i)), "x\n").unwrap();
+            }
+        }
+        std::fs::write(dir.path().join("top.txt"), "x\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let (files, unlisted) = repo.get_changed_files_within(4).unwrap();
+        assert_eq!(unlisted, 2);
+        // Loose untracked files are listed as before, past the limit or not
+        assert_eq!(files.len(), 5);
+        assert!(files.iter().any(|f| f.path == Path::new("top.txt")));
+        assert!(files.iter().all(|f| !f.is_untracked_dir()));
+
+        let (files, unlisted) = repo.get_changed_files_within(100).unwrap();
+        assert_eq!((files.len(), unlisted), (7, 0));
+    }
+
+    #[test]
+    fn test_expand_untracked_keeps_nested_repositories() {
+        let untracked = |path: &str| ChangedFile {
+            path: PathBuf::from(path),
+            change_type: ChangeType::Added,
+            staged: false,
+            old_path: None,
+            similarity: None,
+            submodule: None,
+            outside_sparse: false,
+            nested_repository: false,
+        };
+        let files = vec![untracked("new/"), untracked("repo/")];
+        let (files, unlisted) = expand_untracked(files, b"new/a.rs\0new/b.rs\0repo/\0", 10);
+        let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Path::new("new/a.rs"),
+                Path::new("new/b.rs"),
+                Path::new("repo/")
+            ]
+        );
+        assert_eq!(unlisted, 0);
+    }
+
+    #[test]
+    fn test_nested_repositories_are_left_out_of_changes() {
+        let dir = init_repo();
+        let inner = dir.path().join("vendor").join("inner");
+        std::fs::create_dir_all(&inner).unwrap();
+        git(&inner, &["init", "-q"]);
+        std::fs::write(inner.join("lib.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(dir.path().join("vendor").join("notes.txt"), "x\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let files = repo.get_changed_files().unwrap();
+        assert!(!files.iter().any(|f| f.path.ends_with("lib.rs")));
+        let nested: Vec<_> = files.iter().filter(|f| f.nested_repository).collect();
+        assert_eq!(nested.len(), 1);
+        assert!(nested[0].path.starts_with("vendor/inner"));
+
+        let (files, roots) = split_nested_repositories(files);
+        assert_eq!(roots, HashSet::from([PathBuf::from("vendor/inner")]));
+        let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(paths, vec![Path::new("vendor/notes.txt")]);
+        assert!(is_repository_root(&inner));
+        assert!(!is_repository_root(&dir.path().join("vendor")));
+
+        // Ignoring the nested repository drops it from the changes
+        repo.exclude_dir(Path::new("vendor/inner")).unwrap();
+        let files = repo.get_changed_files().unwrap();
+        assert!(files.iter().all(|f| !f.nested_repository));
+    }
+
+    #[test]
+    fn test_unstaged_move_is_reported_as_rename() {
+        let dir = init_repo();
+        std::fs::rename(dir.path().join("README.md"), dir.path().join("INTRO.md")).unwrap();
+        std::fs::write(dir.path().join("other.txt"), "unrelated\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let mut files = repo.get_changed_files().unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, PathBuf::from("INTRO.md"));
+        assert_eq!(files[0].change_type, ChangeType::Renamed);
+        assert_eq!(files[0].old_path, Some(PathBuf::from("README.md")));
+        assert!(!files[0].staged);
+        assert_eq!(files[1].change_type, ChangeType::Added);
+
+        let diff = repo
+            .get_renamed_file_diff(Path::new("README.md"), Path::new("INTRO.md"))
+            .unwrap();
+        assert!(diff.contains("rename from README.md\nrename to INTRO.md"));
+    }
+
+    /// `git status --porcelain=v2 -z`: a staged rename with edits, an
+    /// unstaged deletion and edit, a submodule with a new commit and
+    /// untracked files, and an untracked file with " -> " in its name
+    const STATUS_V2: &str = "2 R. N... 100644 100644 100644 71ac1b5791204c80666ab1a4f9886b79e982739c b236ae5a2def91fb7a10f08da8e215fad70a4fe3 R88 docs/new name.md\0docs/old name.md\0\
+        1 .D N... 100644 100644 000000 975fbec8256d3e8a3797e7a3611380f27c49f4ac 975fbec8256d3e8a3797e7a3611380f27c49f4ac lib/gone.rs\0\
+        1 .M N... 100644 100644 100644 587be6b4c3f93f93c489c0111bba5596147a26cb 587be6b4c3f93f93c489c0111bba5596147a26cb src/main.rs\0\
+        1 .M SC.U 160000 160000 160000 c3abcb47a416b43fcf945ec7c319989bca90d9b4 c3abcb47a416b43fcf945ec7c319989bca90d9b4 vendor/sub\0\
+        ? notes -> later.txt\0";
+
+    /// A merge conflict on both sides
+    const STATUS_V2_UNMERGED: &str = "u UU N... 100644 100644 100644 100644 78981922613b2afb6025042ff6bd878ac1994e85 f2ad6c76f0115a6ba5b00456a849810e7ec0af20 61780798228d17af2d34fce4cfbdf35556832472 c.rs\0";
+
+    #[test]
+    fn test_parse_status_porcelain_v2() {
+        let files = parse_status_porcelain_v2(STATUS_V2.as_bytes());
+        let paths: Vec<_> = files.iter().map(|f| f.path.to_str().unwrap()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "docs/new name.md",
+                "lib/gone.rs",
+                "src/main.rs",
+                "vendor/sub",
+                "notes -> later.txt"
+            ]
+        );
+
+        let renamed = &files[0];
+        assert_eq!(renamed.change_type, ChangeType::Renamed);
+        assert!(renamed.staged);
+        assert_eq!(renamed.old_path, Some(PathBuf::from("docs/old name.md")));
+        assert_eq!(renamed.similarity, Some(88));
+        assert_eq!(renamed.submodule, None);
+
+        assert_eq!(files[1].change_type, ChangeType::Deleted);
+        assert!(!files[1].staged);
+        assert_eq!(files[2].change_type, ChangeType::Modified);
+        assert_eq!(files[2].similarity, None);
+
+        let submodule = files[3].submodule.unwrap();
+        assert!(submodule.new_commits && !submodule.modified && submodule.untracked);
+        assert_eq!(submodule.describe(), "new commits, untracked content");
+
+        assert_eq!(files[4].change_type, ChangeType::Added);
+        assert!(!files[4].staged);
+        assert!(files.iter().all(|f| !f.outside_sparse));
+    }
+
+    #[test]
+    fn test_parse_status_porcelain_v2_unmerged_and_malformed() {
+        let files = parse_status_porcelain_v2(STATUS_V2_UNMERGED.as_bytes());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("c.rs"));
+        assert_eq!(files[0].change_type, ChangeType::Conflicted);
+        assert!(!files[0].staged);
+
+        assert!(parse_status_porcelain_v2(b"").is_empty());
+        // Headers and cut-short entries are skipped
+        assert!(parse_status_porcelain_v2(b"# branch.oid abc\x001 .M N...\0").is_empty());
+    }
+
+    #[test]
+    fn test_deletions_outside_sparse_cone_are_marked() {
+        let mut files = parse_status_porcelain_v2(STATUS_V2.as_bytes());
+        mark_outside_sparse(&mut files, &SparseCheckout::parse("src\n", true));
+        let outside: Vec<_> = files
+            .iter()
+            .filter(|f| f.outside_sparse)
+            .map(|f| f.path.to_str().unwrap())
+            .collect();
+        assert_eq!(outside, vec!["lib/gone.rs"]);
+
+        mark_outside_sparse(&mut files, &SparseCheckout::parse("lib\n", true));
+        assert!(files.iter().all(|f| !f.outside_sparse));
+    }
+
+    #[test]
+    fn test_sparse_checkout_read_from_worktree() {
+        let dir = init_repo();
+        std::fs::create_dir_all(dir.path().join("src/app")).unwrap();
+        std::fs::create_dir_all(dir.path().join("lib")).unwrap();
+        std::fs::write(dir.path().join("src/app/view.rs"), "view\n").unwrap();
+        std::fs::write(dir.path().join("lib/util.rs"), "util\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "tree"]);
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.sparse_checkout(), None);
+
+        git(dir.path(), &["sparse-checkout", "set", "--cone", "src/app"]);
+        let sparse = repo.sparse_checkout().unwrap();
+        assert!(sparse.cone);
+        assert_eq!(sparse.patterns, vec!["src/app"]);
+        assert!(!dir.path().join("lib/util.rs").exists());
+        // Files outside the cone are not reported as deleted
+        assert!(repo.get_changed_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_numstat() {
+        let output = "3\t1\tsrc/main.rs\n\
+                      -\t-\tassets/logo.png\n\
+                      0\t0\told name.txt => new name.txt\n\
+                      2\t2\tsrc/{ui => view}/list.rs\n\
+                      1\t0\tsrc/{ => nested}/mod.rs\n\
+                      4\t0\tsrc/{nested => }/lib.rs\n";
+        assert_eq!(
+            parse_numstat(output),
+            vec![
+                churn("src/main.rs", 3, 1, false),
+                churn("assets/logo.png", 0, 0, true),
+                churn("new name.txt", 0, 0, false),
+                churn("src/view/list.rs", 2, 2, false),
+                churn("src/nested/mod.rs", 1, 0, false),
+                churn("src/lib.rs", 4, 0, false),
+            ]
+        );
+        assert!(parse_numstat("").is_empty());
+        assert!(parse_numstat("x\t1\tbad.rs\n").is_empty());
+    }
+
+    #[test]
+    fn test_diff_numstat_counts_staged_and_unstaged() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("README.md"), "hello\nworld\n").unwrap();
+        std::fs::write(dir.path().join("data.bin"), [0u8, 1, 2, 0]).unwrap();
+        git(dir.path(), &["add", "data.bin"]);
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let mut files = repo.diff_numstat().unwrap();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            files,
+            vec![
+                churn("README.md", 1, 0, false),
+                churn("data.bin", 0, 0, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_content_hashes_follow_edits() {
+        let dir = init_repo();
+        git(dir.path(), &["rm", "-q", "README.md"]);
+        std::fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+        std::fs::write(dir.path().join("other.txt"), "other\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let files = repo.get_changed_files().unwrap();
+        let hashes = repo.content_hashes(&files).unwrap();
+        assert_eq!(hashes[Path::new("README.md")], DELETED_CONTENT_HASH);
+        let before = hashes[Path::new("new.txt")].clone();
+        assert_eq!(before.len(), 40);
+        assert_ne!(before, hashes[Path::new("other.txt")]);
+
+        std::fs::write(dir.path().join("new.txt"), "edited\n").unwrap();
+        let hashes = repo.content_hashes(&files).unwrap();
+        assert_ne!(hashes[Path::new("new.txt")], before);
+    }
+
+    #[test]
+    fn test_read_head_blob() {
+        let dir = init_repo();
+        let bytes = [0x89u8, b'P', b'N', b'G', 0, 1, 2];
+        std::fs::write(dir.path().join("logo.png"), bytes).unwrap();
+        git(dir.path(), &["add", "logo.png"]);
+        git(dir.path(), &["commit", "-q", "-m", "logo"]);
+        std::fs::write(dir.path().join("logo.png"), b"changed").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let id = repo.head_blob_id(&dir.path().join("logo.png")).unwrap();
+        assert_eq!(repo.read_blob(&id).unwrap(), bytes);
+        assert_eq!(repo.head_blob_id(Path::new("missing.png")), None);
+        assert!(repo.read_blob("0123456789abcdef").is_err());
+    }
+
+    /// `init_repo` with a second commit, tagged `v1` (lightweight) on the
+    /// first and `v2` (annotated) on the second; returns the two hashes
+    fn init_tagged_repo() -> (tempfile::TempDir, String, String) {
+        let dir = init_repo();
+        let first = git(dir.path(), &["rev-parse", "HEAD"]).trim().to_string();
+        git(dir.path(), &["tag", "v1"]);
+        std::fs::write(dir.path().join("README.md"), "hello\nagain\n").unwrap();
+        git(dir.path(), &["commit", "-q", "-am", "second"]);
+        git(dir.path(), &["tag", "-a", "v2", "-m", "release 2"]);
+        let second = git(dir.path(), &["rev-parse", "HEAD"]).trim().to_string();
+        (dir, first, second)
+    }
+
+    #[test]
+    fn test_resolve_commit() {
+        let (dir, first, second) = init_tagged_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.resolve_commit("v1").unwrap(), first);
+        assert_eq!(repo.resolve_commit("v2").unwrap(), second);
+        assert_eq!(repo.resolve_commit(&first[..8]).unwrap(), first);
+        assert_eq!(repo.resolve_commit("main~1").unwrap(), first);
+        for invalid in ["v3", "deadbeef", "", "--all", "main:README.md"] {
+            assert!(repo.resolve_commit(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_create_worktree_from_base() {
+        let (dir, first, _) = init_tagged_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+
+        let path = worktrees.path().join("feature");
+        let worktree = repo
+            .create_worktree("feature", Some("feature"), &path, Some("v1"))
+            .unwrap();
+        assert_eq!(worktree.branch.as_deref(), Some("feature"));
+        assert_eq!(git(&path, &["rev-parse", "HEAD"]).trim(), first);
+
+        let path = worktrees.path().join("from-hash");
+        repo.create_worktree("from-hash", Some("from-hash"), &path, Some(&first[..7]))
+            .unwrap();
+        assert_eq!(git(&path, &["rev-parse", "HEAD"]).trim(), first);
+
+        // An existing branch cannot be moved to another base
+        let path = worktrees.path().join("again");
+        let err = repo
+            .create_worktree("again", Some("feature"), &path, Some("v2"))
+            .unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+
+        // Nothing is created for a base that does not resolve
+        let path = worktrees.path().join("invalid");
+        let err = repo
+            .create_worktree("invalid", Some("invalid"), &path, Some("no-such-tag"))
+            .unwrap_err();
+        assert!(err.to_string().contains("no-such-tag"), "{}", err);
+        assert!(!path.exists());
+        assert!(git(dir.path(), &["branch", "--list", "invalid"]).is_empty());
+    }
+
+    #[test]
+    fn test_detached_worktree_shows_tag_or_hash() {
+        let (dir, _, _) = init_tagged_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let worktrees = tempfile::tempdir().unwrap();
+
+        let tagged = repo
+            .create_worktree("v2", None, &worktrees.path().join("v2"), Some("v2"))
+            .unwrap();
+        assert_eq!(tagged.branch.as_deref(), Some("v2"));
+
+        std::fs::write(dir.path().join("README.md"), "third\n").unwrap();
+        git(dir.path(), &["commit", "-q", "-am", "third"]);
+        let head = git(dir.path(), &["rev-parse", "HEAD"]).trim().to_string();
+        let untagged = repo
+            .create_worktree("head", None, &worktrees.path().join("head"), None)
+            .unwrap();
+        assert_eq!(untagged.branch.as_deref(), Some(&head[..7]));
+
+        let mut labels: Vec<Option<String>> = repo
+            .list_worktrees()
+            .unwrap()
+            .into_iter()
+            .map(|w| w.branch)
+            .collect();
+        labels[1..].sort();
+        assert_eq!(
+            labels,
+            vec![
+                Some("main".to_string()),
+                Some(head[..7].to_string()),
+                Some("v2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_stash_list() {
+        let output = "stash@{0}\x001700000100\x00On main: fix: handle {a: b} case\n\
+                      stash@{1}\x001700000000\x00WIP on feature/x: abc1234 Add parser\n\
+                      stash@{2}\x001690000000\x00autostash\n";
+        let entries = parse_stash_list(output);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].index, 0);
+        assert_eq!(entries[0].branch.as_deref(), Some("main"));
+        assert_eq!(entries[0].message, "fix: handle {a: b} case");
+        assert_eq!(entries[0].timestamp, 1700000100);
+
+        assert_eq!(entries[1].index, 1);
+        assert_eq!(entries[1].branch.as_deref(), Some("feature/x"));
+        assert_eq!(entries[1].message, "abc1234 Add parser");
+
+        assert_eq!(entries[2].branch, None);
+        assert_eq!(entries[2].message, "autostash");
+    }
+
+    #[test]
+    fn test_stash_push_list_pop() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("README.md"), "changed\n").unwrap();
+        std::fs::write(dir.path().join("new.txt"), "untracked\n").unwrap();
+        repo.stash_push("wip: {braces} stay", true).unwrap();
+
+        assert!(!dir.path().join("new.txt").exists());
+        let stashes = repo.stash_list().unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert_eq!(stashes[0].message, "wip: {braces} stay");
+        assert_eq!(stashes[0].branch.as_deref(), Some("main"));
+
+        repo.stash_pop(0).unwrap();
+        assert!(dir.path().join("new.txt").exists());
+        assert!(repo.stash_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stash_apply_conflict_reports_output() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("README.md"), "stashed\n").unwrap();
+        repo.stash_push("", false).unwrap();
+        std::fs::write(dir.path().join("README.md"), "committed\n").unwrap();
+        git(dir.path(), &["commit", "-q", "-am", "conflicting"]);
+
+        let err = repo.stash_pop(0).unwrap_err().to_string();
+        assert!(err.contains("CONFLICT"), "unexpected output: {}", err);
+        // A conflicting pop keeps the stash
+        assert_eq!(repo.stash_list().unwrap().len(), 1);
+
+        git(dir.path(), &["reset", "-q", "--hard"]);
+        repo.stash_drop(0).unwrap();
+        assert!(repo.stash_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_discard_hunk_keeps_other_hunks() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let path = dir.path().join("lines.txt");
+        let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(&path, &original).unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "lines"]);
+
+        let edited = original
+            .replace("line 2\n", "line two\n")
+            .replace("line 19\n", "line nineteen\n");
+        std::fs::write(&path, &edited).unwrap();
+
+        let diff = repo.get_file_diff(&path).unwrap();
+        let header: String = diff
+            .lines()
+            .take_while(|l| !l.starts_with("@@"))
+            .map(|l| format!("{}\n", l))
+            .collect();
+        let second_hunk = &diff[diff.rfind("@@ -").unwrap()..];
+        let patch = format!("{}{}", header, second_hunk);
+
+        repo.discard_hunk(&patch, TextEncoding::Utf8).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            original.replace("line 2\n", "line two\n")
+        );
+
+        // Already discarded: no longer applies and leaves the file alone
+        assert!(repo.discard_hunk(&patch, TextEncoding::Utf8).is_err());
+        assert!(std::fs::read_to_string(&path).unwrap().contains("line two"));
+    }
+
+    #[test]
+    fn test_head_content_goes_through_smudge_filters() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join(".gitattributes"), "*.up filter=upper\n").unwrap();
+        let path = dir.path().join("shout.up");
+        std::fs::write(&path, "quiet\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "filtered"]);
+        git(dir.path(), &["config", "filter.upper.smudge", "tr a-z A-Z"]);
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.get_file_content_from_head(&path).unwrap(), "QUIET\n");
+    }
+
+    #[test]
+    fn test_shift_jis_file_diff() {
+        use encoding_rs::SHIFT_JIS;
+        let sjis = |text: &str| SHIFT_JIS.encode(text).0.into_owned();
+
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let path = dir.path().join("sjis.txt");
+        let original = sjis("こんにちは\n日本語のテキスト\nさようなら\n");
+        std::fs::write(&path, &original).unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "sjis"]);
+
+        assert_eq!(
+            repo.get_file_content_from_head(&path).unwrap(),
+            "こんにちは\n日本語のテキスト\nさようなら\n"
+        );
+
+        std::fs::write(&path, sjis("こんにちは\n変更したテキスト\nさようなら\n")).unwrap();
+        let diff = repo.get_file_diff(&path).unwrap();
+        let changed: Vec<&str> = diff
+            .lines()
+            .filter(|l| {
+                (l.starts_with('-') || l.starts_with('+'))
+                    && !l.starts_with("---")
+                    && !l.starts_with("+++")
+            })
+            .collect();
+        assert_eq!(changed, vec!["-日本語のテキスト", "+変更したテキスト"]);
+
+        // The decoded hunk is encoded back to apply to the file's bytes
+        repo.discard_hunk(&diff, TextEncoding::Legacy(SHIFT_JIS))
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_discard_file_tracked_and_new() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        std::fs::write(dir.path().join("README.md"), "changed\n").unwrap();
+        git(dir.path(), &["add", "README.md"]);
+        repo.discard_file(&dir.path().join("README.md")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("README.md")).unwrap(),
+            "hello\n"
+        );
+
+        let new_file = dir.path().join("new.txt");
+        std::fs::write(&new_file, "x\n").unwrap();
+        git(dir.path(), &["add", "new.txt"]);
+        repo.discard_file(Path::new("new.txt")).unwrap();
+        assert!(!new_file.exists());
+        assert!(repo.get_changed_files().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_includes_untracked() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        std::fs::write(dir.path().join("README.md"), "edited\n").unwrap();
+        std::fs::write(dir.path().join("staged.txt"), "staged\n").unwrap();
+        git(dir.path(), &["add", "staged.txt"]);
+        std::fs::write(dir.path().join("untracked.txt"), "untracked\n").unwrap();
+        let status_before = git(dir.path(), &["status", "--porcelain"]);
+
+        let refname = repo.create_snapshot("main").unwrap();
+        assert!(refname.starts_with("refs/sashiki/snapshots/main/"));
+        // Taking a snapshot leaves the index and working tree alone
+        assert_eq!(git(dir.path(), &["status", "--porcelain"]), status_before);
+
+        std::fs::write(dir.path().join("README.md"), "agent rewrote this\n").unwrap();
+        std::fs::remove_file(dir.path().join("untracked.txt")).unwrap();
+        git(dir.path(), &["rm", "-q", "--cached", "staged.txt"]);
+        std::fs::write(dir.path().join("staged.txt"), "changed\n").unwrap();
+
+        repo.restore_snapshot(&refname).unwrap();
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("README.md"), "edited\n");
+        assert_eq!(read("staged.txt"), "staged\n");
+        assert_eq!(read("untracked.txt"), "untracked\n");
+
+        let snapshots = repo.list_snapshots("main").unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].refname, refname);
+        assert!(snapshots[0].stat.contains("3 files changed"));
+    }
+
+    #[test]
+    fn test_prune_snapshots_keeps_newest() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let first = repo.create_snapshot("feature/x").unwrap();
+        let second = repo.create_snapshot("feature/x").unwrap();
+        let third = repo.create_snapshot("feature/x").unwrap();
+        let other = repo.create_snapshot("feature").unwrap();
+        assert_ne!(first, second);
+
+        repo.prune_snapshots("feature/x", 2).unwrap();
+        let names: Vec<_> = repo
+            .list_snapshots("feature/x")
+            .unwrap()
+            .into_iter()
+            .map(|s| s.refname)
+            .collect();
+        assert_eq!(names, vec![third, second]);
+        assert_eq!(repo.list_snapshots("feature").unwrap()[0].refname, other);
+    }
+
+    #[test]
+    fn test_checkpoints_leave_head_index_and_worktree_alone() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        std::fs::write(dir.path().join("README.md"), "edited\n").unwrap();
+        std::fs::write(dir.path().join("staged.txt"), "staged\n").unwrap();
+        git(dir.path(), &["add", "staged.txt"]);
+        std::fs::write(dir.path().join("untracked.txt"), "untracked\n").unwrap();
+        let head = repo.head_commit().unwrap();
+        let status = git(dir.path(), &["status", "--porcelain"]);
+        let index = std::fs::read(dir.path().join(".git/index")).unwrap();
+        let staged = git(dir.path(), &["diff", "--cached"]);
+
+        let first = repo
+            .create_checkpoint("main", "checkpoint 14:32, 3 files")
+            .unwrap()
+            .unwrap();
+        assert!(first.starts_with("refs/sashiki/checkpoints/main/"));
+        assert_eq!(repo.head_commit().unwrap(), head);
+        assert_eq!(git(dir.path(), &["status", "--porcelain"]), status);
+        assert_eq!(git(dir.path(), &["diff", "--cached"]), staged);
+        assert_eq!(std::fs::read(dir.path().join(".git/index")).unwrap(), index);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("untracked.txt")).unwrap(),
+            "untracked\n"
+        );
+
+        // Nothing changed since: no new checkpoint
+        assert_eq!(repo.create_checkpoint("main", "again").unwrap(), None);
+
+        std::fs::write(dir.path().join("README.md"), "edited again\n").unwrap();
+        let second = repo.create_checkpoint("main", "later").unwrap().unwrap();
+        assert_eq!(repo.head_commit().unwrap(), head);
+        let diff = repo.diff_snapshot_to_worktree(&first).unwrap();
+        assert!(diff.contains("-edited\n+edited again\n"), "{}", diff);
+
+        let checkpoints = repo.list_checkpoints("main").unwrap();
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[0].refname, second);
+        assert_eq!(checkpoints[1].subject, "checkpoint 14:32, 3 files");
+        // Checkpoints and snapshots are kept apart
+        assert!(repo.list_snapshots("main").unwrap().is_empty());
+
+        repo.restore_snapshot(&first).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("README.md")).unwrap(),
+            "edited\n"
+        );
+        assert_eq!(repo.head_commit().unwrap(), head);
+
+        repo.prune_checkpoints("main", 1).unwrap();
+        let names: Vec<_> = repo
+            .list_checkpoints("main")
+            .unwrap()
+            .into_iter()
+            .map(|c| c.refname)
+            .collect();
+        assert_eq!(names, vec![second]);
+    }
+
+    /// Commit `content` to `file` with `subject` as the message
+    fn commit_file(dir: &Path, file: &str, content: &str, subject: &str) {
+        std::fs::write(dir.join(file), content).unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "-q", "-m", subject]);
+    }
+
+    #[test]
+    fn test_squash_since_collapses_branch() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let base = repo.head_commit().unwrap();
+        for i in 0..15 {
+            commit_file(dir.path(), "work.txt", &format!("{}\n", i), "wip");
+        }
+
+        let commits = repo.commits_since(&base).unwrap();
+        assert_eq!(commits.len(), 15);
+        assert_eq!(commits[0].subject, "wip");
+
+        std::fs::write(dir.path().join("README.md"), "dirty\n").unwrap();
+        assert!(repo.has_uncommitted_changes().unwrap());
+        git(dir.path(), &["checkout", "--", "README.md"]);
+        assert!(!repo.has_uncommitted_changes().unwrap());
+
+        repo.squash_since(&base, "Add work").unwrap();
+        let commits = repo.commits_since(&base).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].subject, "Add work");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("work.txt")).unwrap(),
+            "14\n"
+        );
+    }
+
+    #[test]
+    fn test_drop_commits_and_abort_on_conflict() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let base = repo.head_commit().unwrap();
+        commit_file(dir.path(), "a.txt", "a\n", "add a");
+        commit_file(dir.path(), "b.txt", "b\n", "add b");
+        commit_file(dir.path(), "c.txt", "c\n", "add c");
+
+        let commits = repo.commits_since(&base).unwrap();
+        let dropped: Vec<_> = commits
+            .iter()
+            .filter(|c| c.subject != "add b")
+            .cloned()
+            .collect();
+        repo.drop_commits(&dropped).unwrap();
+        let subjects: Vec<_> = repo
+            .commits_since(&base)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.subject)
+            .collect();
+        assert_eq!(subjects, vec!["add b"]);
+        assert!(!dir.path().join("a.txt").exists());
+
+        // Dropping a commit a later one builds on conflicts: nothing changes
+        commit_file(dir.path(), "b.txt", "b2\n", "edit b");
+        let head = repo.head_commit().unwrap();
+        let commits = repo.commits_since(&base).unwrap();
+        assert!(repo.drop_commits(&commits[1..]).is_err());
+        assert_eq!(repo.head_commit().unwrap(), head);
+        assert!(!dir.path().join(".git/rebase-merge").exists());
+        assert!(!repo.has_uncommitted_changes().unwrap());
+    }
+
+    #[test]
+    fn test_ahead_behind_and_last_commit_time() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let base = repo.head_commit().unwrap();
+        assert!(repo.last_commit_time().is_some_and(|t| t > 0));
+
+        git(dir.path(), &["checkout", "-q", "-b", "feature"]);
+        commit_file(dir.path(), "a.txt", "a\n", "one");
+        commit_file(dir.path(), "b.txt", "b\n", "two");
+        git(dir.path(), &["checkout", "-q", "main"]);
+        commit_file(dir.path(), "c.txt", "c\n", "three");
+        let main = repo.head_commit().unwrap();
+        git(dir.path(), &["checkout", "-q", "feature"]);
+
+        assert_eq!(repo.ahead_behind(&base).unwrap(), (2, 0));
+        assert_eq!(repo.ahead_behind(&main).unwrap(), (2, 1));
+    }
+
+    #[test]
+    fn test_submodule_paths_and_status() {
+        let upstream = init_repo();
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert!(repo.submodule_paths().is_empty());
+
+        let url = upstream.path().to_string_lossy().to_string();
+        git(
+            dir.path(),
+            &[
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "-q",
+                &url,
+                "vendor/lib",
+            ],
+        );
+        git(dir.path(), &["commit", "-q", "-m", "add submodule"]);
+        assert_eq!(repo.submodule_paths(), vec![PathBuf::from("vendor/lib")]);
+
+        let lib = Path::new("vendor/lib");
+        let status = repo.submodule_status(lib).unwrap();
+        assert_eq!(status.recorded, status.checked_out);
+        assert!(!status.dirty);
+
+        let sub = dir.path().join("vendor/lib");
+        git(&sub, &["config", "user.name", "Test"]);
+        git(&sub, &["config", "user.email", "test@example.com"]);
+        commit_file(&sub, "new.txt", "x\n", "advance lib");
+        std::fs::write(sub.join("scratch.txt"), "y\n").unwrap();
+
+        let status = repo.submodule_status(lib).unwrap();
+        assert_ne!(status.recorded, status.checked_out);
+        assert!(status.dirty);
+        let subjects: Vec<_> = status.commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["advance lib"]);
+
+        let changed = repo.get_changed_files().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].path, PathBuf::from("vendor/lib"));
+    }
+
+    #[test]
+    fn test_validate_branch_name_valid() {
+        assert!(validate_branch_name("feature/test").is_ok());
+        assert!(validate_branch_name("bugfix-123").is_ok());
+        assert!(validate_branch_name("release-v1.0.0").is_ok());
+        assert!(validate_branch_name("my_branch").is_ok());
+        assert!(validate_branch_name("a").is_ok());
+    }
+
+    #[test]
+    fn test_validate_branch_name_empty() {
+        assert_eq!(validate_branch_name(""), Err("Branch name cannot be empty"));
+    }
+
+    #[test]
+    fn test_validate_branch_name_slash_rules() {
+        assert_eq!(
+            validate_branch_name("/test"),
+            Err("Branch name cannot start or end with /")
+        );
+        assert_eq!(
+            validate_branch_name("test/"),
+            Err("Branch name cannot start or end with /")
+        );
+        assert_eq!(
+            validate_branch_name("test//foo"),
+            Err("Branch name cannot contain //")
+        );
+    }
+
+    #[test]
+    fn test_validate_branch_name_dot_rules() {
         assert_eq!(
             validate_branch_name(".test"),
             Err("Branch name cannot start or end with .")
@@ -649,4 +4167,516 @@ mod tests {
             Err("Branch name cannot contain @{")
         );
     }
+
+    #[test]
+    fn test_read_config_entries_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("settings");
+        std::fs::write(
+            &file,
+            "[sashiki \"terminal\"]\n\tshell = /bin/zsh -l\n[sashiki]\n\tflag\n\tnote = a=b\n",
+        )
+        .unwrap();
+
+        let entries = read_config_entries(ConfigSource::File(&file)).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "sashiki.terminal.shell".to_string(),
+                    "/bin/zsh -l".to_string()
+                ),
+                ("sashiki.flag".to_string(), "true".to_string()),
+                ("sashiki.note".to_string(), "a=b".to_string()),
+            ]
+        );
+
+        std::fs::write(&file, "[sashiki\n\tbroken = \n").unwrap();
+        assert!(read_config_entries(ConfigSource::File(&file)).is_err());
+    }
+
+    #[test]
+    fn test_linked_worktree_in_sibling_directory() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let sibling = tempfile::tempdir().unwrap();
+        let worktree = sibling.path().join("outside");
+        git(
+            dir.path(),
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "outside",
+                worktree.to_str().unwrap(),
+            ],
+        );
+        std::fs::create_dir(worktree.join("sub")).unwrap();
+
+        // Found from the worktree or any directory in it
+        for path in [worktree.clone(), worktree.join("sub")] {
+            let found = repo.linked_worktree(&path).unwrap();
+            assert!(same_dir(&found.path, &worktree));
+            assert_eq!(found.branch.as_deref(), Some("outside"));
+            assert!(!found.is_main);
+        }
+
+        // The path as git lists it, so the session matches on the next sync
+        let found = repo.linked_worktree(&worktree).unwrap();
+        assert!(
+            repo.list_worktrees()
+                .unwrap()
+                .iter()
+                .any(|listed| listed.path == found.path)
+        );
+    }
+
+    #[test]
+    fn test_linked_worktree_rejects_other_directories() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        let other = init_repo();
+        match repo.linked_worktree(other.path()) {
+            Err(LinkedWorktreeError::OtherRepository(path)) => {
+                assert!(same_dir(&path, other.path()))
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let plain = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            repo.linked_worktree(plain.path()),
+            Err(LinkedWorktreeError::NotARepository(_))
+        ));
+        assert!(matches!(
+            repo.linked_worktree(&plain.path().join("missing")),
+            Err(LinkedWorktreeError::NotADirectory(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge_branch_fast_forward_and_no_ff() {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        commit_file(&worktree, "a.txt", "a\n", "add a");
+
+        assert_eq!(
+            repo.merge_branch("feature", MergeMode::FastForward)
+                .unwrap(),
+            MergeOutcome::Merged
+        );
+        assert_eq!(
+            git(dir.path(), &["log", "-1", "--format=%s"]).trim(),
+            "add a"
+        );
+        assert_eq!(
+            repo.merge_branch("feature", MergeMode::NoFastForward)
+                .unwrap(),
+            MergeOutcome::UpToDate
+        );
+
+        // Once main has moved on, only a real merge works
+        commit_file(dir.path(), "main.txt", "main\n", "main work");
+        commit_file(&worktree, "b.txt", "b\n", "add b");
+        assert!(
+            repo.merge_branch("feature", MergeMode::FastForward)
+                .is_err()
+        );
+        assert_eq!(
+            repo.merge_branch("feature", MergeMode::NoFastForward)
+                .unwrap(),
+            MergeOutcome::Merged
+        );
+        let parents = git(dir.path(), &["log", "-1", "--format=%P"]);
+        assert_eq!(parents.split_whitespace().count(), 2);
+        assert!(dir.path().join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_merge_branch_squash_commits_once() {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        commit_file(&worktree, "a.txt", "a\n", "add a");
+        commit_file(&worktree, "b.txt", "b\n", "add b");
+
+        assert_eq!(
+            repo.merge_branch("feature", MergeMode::Squash).unwrap(),
+            MergeOutcome::Merged
+        );
+        assert_eq!(
+            git(dir.path(), &["log", "-1", "--format=%s"]).trim(),
+            "Squash merge branch 'feature'"
+        );
+        assert_eq!(
+            git(dir.path(), &["rev-list", "--count", "HEAD"]).trim(),
+            "2"
+        );
+        assert!(!repo.has_uncommitted_changes().unwrap());
+
+        // The squashed branch is not merged as far as git can tell
+        git(
+            dir.path(),
+            &["worktree", "remove", worktree.to_str().unwrap()],
+        );
+        repo.delete_branch("feature").unwrap();
+        assert!(git(dir.path(), &["branch", "--list", "feature"]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_branch_conflicts_and_abort() {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        commit_file(&worktree, "README.md", "theirs\n", "feature edit");
+        commit_file(dir.path(), "README.md", "ours\n", "main edit");
+        let head = repo.head_commit().unwrap();
+
+        assert_eq!(
+            repo.merge_branch("feature", MergeMode::NoFastForward)
+                .unwrap(),
+            MergeOutcome::Conflicts(vec![PathBuf::from("README.md")])
+        );
+        let diff = repo
+            .conflict_sides_diff(&dir.path().join("README.md"))
+            .unwrap();
+        assert!(diff.starts_with("@@"));
+        assert!(diff.contains("-ours") && diff.contains("+theirs"));
+
+        repo.abort_merge().unwrap();
+        assert!(repo.conflicted_files().unwrap().is_empty());
+        assert_eq!(repo.head_commit().unwrap(), head);
+        assert!(!repo.has_uncommitted_changes().unwrap());
+
+        // A conflicting squash merge leaves no MERGE_HEAD and is aborted too
+        assert!(matches!(
+            repo.merge_branch("feature", MergeMode::Squash).unwrap(),
+            MergeOutcome::Conflicts(_)
+        ));
+        repo.abort_merge().unwrap();
+        assert!(!repo.has_uncommitted_changes().unwrap());
+    }
+
+    #[test]
+    fn test_commands_wait_for_a_lock_to_be_released() {
+        let dir = init_repo();
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let readme = dir.path().join("README.md");
+        std::fs::write(&readme, "changed\n").unwrap();
+
+        // Another git process holds the index for a moment
+        let lock = dir.path().join(".git/index.lock");
+        std::fs::write(&lock, "").unwrap();
+        let release = std::thread::spawn({
+            let lock = lock.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(300));
+                std::fs::remove_file(lock).unwrap();
+            }
+        });
+        repo.discard_file(&readme).unwrap();
+        release.join().unwrap();
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "hello\n");
+    }
+
+    #[test]
+    fn test_lock_held_past_the_wait_fails_as_busy() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("README.md"), "changed\n").unwrap();
+        std::fs::write(dir.path().join(".git/index.lock"), "").unwrap();
+
+        let args = os_args(&["checkout", "HEAD", "--", "README.md"]);
+        let started = Instant::now();
+        let result = wait_for_lock(Duration::from_millis(200), || {
+            run_git_bytes_once(dir.path(), &args, &[])
+        });
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        let Err(GitError::Busy(lock)) = result else {
+            panic!("expected a busy repository, got {:?}", result);
+        };
+        assert!(lock.path.ends_with(".git/index.lock"));
+        // Just taken: not offered for removal
+        assert!(!lock.stale);
+        assert!(
+            GitError::Busy(lock)
+                .to_string()
+                .starts_with("The repository is busy (another git process is running)")
+        );
+
+        // Other failures are not retried
+        std::fs::remove_file(dir.path().join(".git/index.lock")).unwrap();
+        let args = os_args(&["checkout", "no-such-branch"]);
+        let started = Instant::now();
+        let result = wait_for_lock(Duration::from_secs(5), || {
+            run_git_bytes_once(dir.path(), &args, &[])
+        });
+        assert!(matches!(result, Err(GitError::Command(_))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_slow_command_times_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = GitLimits {
+            timeout: Duration::from_millis(200),
+            ..GitLimits::default()
+        };
+        let args = os_args(&["-c", "alias.slow=!sleep 5", "slow"]);
+        let started = Instant::now();
+        let result = execute(dir.path(), &args, &[], None, limits);
+        assert!(matches!(result, Err(GitError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commands_running_hooks_are_not_timed() {
+        let dir = init_repo();
+        write_hook(dir.path(), "pre-commit", "sleep 1");
+        let limits = GitLimits {
+            timeout: Duration::from_millis(200),
+            ..GitLimits::default()
+        };
+        let args = os_args(&["commit", "-q", "--allow-empty", "-m", "slow hook"]);
+        let output = execute(dir.path(), &args, &[], None, limits).unwrap();
+        assert!(output.status.success());
+    }
+
+    #[cfg(unix)]
+    fn write_hook(repo: &Path, name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        let hook = repo.join(".git").join("hooks").join(name);
+        std::fs::write(&hook, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_worktree_hook_output_streams() {
+        let dir = init_repo();
+        write_hook(
+            dir.path(),
+            "post-checkout",
+            "echo installing; sleep 1; echo done >&2",
+        );
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let path = dir.path().join("wt-hook");
+
+        let started = Instant::now();
+        let mut lines = Vec::new();
+        repo.create_worktree_with("wt-hook", Some("hook"), &path, None, false, &mut |line| {
+            lines.push((line.to_string(), started.elapsed()));
+        })
+        .unwrap();
+
+        let arrived = |text: &str| {
+            lines
+                .iter()
+                .find(|(line, _)| line == text)
+                .map(|(_, at)| *at)
+                .unwrap_or_else(|| panic!("{:?} not in {:?}", text, lines))
+        };
+        // The first line arrived while the hook was still sleeping
+        assert!(arrived("done") - arrived("installing") >= Duration::from_millis(500));
+        assert!(path.join("README.md").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_worktree_hooks_can_be_skipped() {
+        let dir = init_repo();
+        write_hook(dir.path(), "post-checkout", "echo ran > hook-ran");
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        let skipped = dir.path().join("wt-skipped");
+        let mut lines = Vec::new();
+        repo.create_worktree_with("wt-skipped", None, &skipped, None, true, &mut |line| {
+            lines.push(line.to_string())
+        })
+        .unwrap();
+        assert!(skipped.join("README.md").exists());
+        assert!(!skipped.join("hook-ran").exists());
+        // Only this worktree: the repository's hooks path is unchanged
+        let hooks_path = Command::new("git")
+            .args(["config", "--get", "core.hooksPath"])
+            .current_dir(dir.path())
+            .output()
+            .unwrap();
+        assert!(!hooks_path.status.success());
+
+        let hooked = dir.path().join("wt-hooked");
+        repo.create_worktree("wt-hooked", None, &hooked, None)
+            .unwrap();
+        assert!(hooked.join("hook-ran").exists());
+    }
+
+    #[test]
+    fn test_prune_worktrees_forgets_deleted_directories_but_not_locked_ones() {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        let locked = dir.path().join("wt-locked");
+        git(
+            dir.path(),
+            &[
+                "worktree",
+                "add",
+                "-q",
+                "-b",
+                "locked",
+                locked.to_str().unwrap(),
+            ],
+        );
+        git(dir.path(), &["worktree", "lock", locked.to_str().unwrap()]);
+        std::fs::remove_dir_all(&worktree).unwrap();
+        std::fs::remove_dir_all(&locked).unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        repo.prune_worktrees().unwrap();
+        let paths: Vec<PathBuf> = repo
+            .list_worktrees()
+            .unwrap()
+            .into_iter()
+            .map(|wt| wt.path)
+            .collect();
+        assert!(!paths.contains(&worktree));
+        assert!(paths.contains(&locked));
+
+        // Nor is the locked one's entry removed to make room for a new one
+        let elsewhere = dir.path().join("wt-other");
+        assert!(
+            repo.create_worktree("wt-locked", Some("other"), &elsewhere, None)
+                .is_err()
+        );
+        assert!(repo.git_dir.join("worktrees").join("wt-locked").exists());
+    }
+
+    #[test]
+    fn test_recreate_worktree_checks_out_its_branch_again() {
+        let (dir, worktree) = init_repo_with_feature_worktree();
+        std::fs::write(worktree.join("feature.txt"), "feature\n").unwrap();
+        git(&worktree, &["add", "feature.txt"]);
+        git(&worktree, &["commit", "-q", "-m", "feature"]);
+        std::fs::remove_dir_all(&worktree).unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let missing = repo
+            .list_worktrees()
+            .unwrap()
+            .into_iter()
+            .find(|wt| wt.path == worktree)
+            .unwrap();
+        repo.recreate_worktree(&missing).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(worktree.join("feature.txt")).unwrap(),
+            "feature\n"
+        );
+        assert_eq!(
+            git(&worktree, &["rev-parse", "--abbrev-ref", "HEAD"]).trim(),
+            "feature"
+        );
+        assert!(git(&worktree, &["status", "--porcelain"]).trim().is_empty());
+        // Not twice over an existing directory
+        assert!(repo.recreate_worktree(&missing).is_err());
+    }
+
+    #[test]
+    fn test_output_over_the_limit_fails() {
+        let dir = init_repo();
+        std::fs::write(dir.path().join("big.txt"), "x".repeat(100_000)).unwrap();
+        git(dir.path(), &["add", "big.txt"]);
+        git(dir.path(), &["commit", "-q", "-m", "big"]);
+
+        let args = os_args(&["show", "HEAD:big.txt"]);
+        let capped = GitLimits {
+            max_output: 10_000,
+            ..GitLimits::default()
+        };
+        assert!(matches!(
+            execute(dir.path(), &args, &[], None, capped),
+            Err(GitError::OutputTooLarge(10_000))
+        ));
+        let output = execute(dir.path(), &args, &[], None, GitLimits::default()).unwrap();
+        assert_eq!(output.stdout.len(), 100_000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path_round_trips() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = init_repo();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        let path = dir.path().join(name);
+        std::fs::write(&path, "before\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "latin-1 name"]);
+        std::fs::write(&path, "after\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.get_file_content_from_head(&path).unwrap(), "before\n");
+        assert!(repo.head_blob_id(&path).is_some());
+        let diff = repo.get_file_diff(&path).unwrap();
+        assert!(diff.contains("-before\n+after\n"), "{}", diff);
+
+        repo.discard_file(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "before\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_changed_files_keep_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = init_repo();
+        let name = |bytes: &[u8]| PathBuf::from(OsStr::from_bytes(bytes));
+        std::fs::write(dir.path().join(name(b"caf\xe9.txt")), "before\n").unwrap();
+        std::fs::write(dir.path().join(name(b"old\xe9.txt")), "moved\n").unwrap();
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "latin-1 names"]);
+        std::fs::write(dir.path().join(name(b"caf\xe9.txt")), "after\n").unwrap();
+        std::fs::rename(
+            dir.path().join(name(b"old\xe9.txt")),
+            dir.path().join(name(b"new\xe9.txt")),
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join(name(b"d\xe9"))).unwrap();
+        std::fs::write(dir.path().join(name(b"d\xe9/f\xe9.txt")), "new\n").unwrap();
+
+        let repo = GitRepo::open(dir.path()).unwrap();
+        let (files, unlisted) = repo.get_changed_files_within(100).unwrap();
+        assert_eq!(unlisted, 0);
+        let mut paths: Vec<_> = files.iter().map(|f| f.path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                name(b"caf\xe9.txt"),
+                name(b"d\xe9/f\xe9.txt"),
+                name(b"new\xe9.txt")
+            ]
+        );
+        let moved = files
+            .iter()
+            .find(|f| f.path == name(b"new\xe9.txt"))
+            .unwrap();
+        assert_eq!(moved.change_type, ChangeType::Renamed);
+        assert_eq!(moved.old_path, Some(name(b"old\xe9.txt")));
+
+        let diff = repo
+            .get_renamed_file_diff(&name(b"old\xe9.txt"), &name(b"new\xe9.txt"))
+            .unwrap();
+        assert!(
+            diff.contains("rename from \"old\\351.txt\"\nrename to \"new\\351.txt\""),
+            "{}",
+            diff
+        );
+
+        repo.mark_resolved(&name(b"d\xe9/f\xe9.txt")).unwrap();
+        let (files, _) = repo.get_changed_files_within(100).unwrap();
+        assert!(
+            files
+                .iter()
+                .any(|f| f.path == name(b"d\xe9/f\xe9.txt") && f.staged)
+        );
+    }
 }
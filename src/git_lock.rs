@@ -0,0 +1,329 @@
+//! Waiting for another git process to release the repository
+//!
+//! Git takes `index.lock` (or `shallow.lock`) for every command that changes
+//! the index, so an agent committing while Sashiki discards a file or creates
+//! a worktree makes one of the two fail with "Unable to create
+//! '.../index.lock': File exists". Mutating commands that fail this way are
+//! run again after a growing pause (`Backoff`) until `sashiki.git.lockWait`
+//! has passed, and then fail with `GitError::Busy`.
+//!
+//! On the git queue's worker nothing waits: the command fails at once and the
+//! queue runs the operation again later (see `deferring`), so the operations
+//! behind it are not held up.
+//!
+//! A lock that has been there for `STALE_AFTER` while no git process runs was
+//! most likely left by a git that crashed. It is offered for removal, never
+//! removed on its own.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Lock files whose "File exists" error means another git process is busy
+const LOCK_FILES: &[&str] = &["index.lock", "shallow.lock"];
+
+/// Age from which a lock no git process holds is offered for removal
+pub const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// First pause before running a command again, doubled after each attempt
+const FIRST_PAUSE: Duration = Duration::from_millis(50);
+const MAX_PAUSE: Duration = Duration::from_secs(1);
+
+/// Subcommands that change the repository and may run into a lock
+const MUTATING: &[&str] = &[
+    "add",
+    "am",
+    "apply",
+    "branch",
+    "checkout",
+    "cherry-pick",
+    "commit",
+    "fetch",
+    "merge",
+    "mv",
+    "pull",
+    "read-tree",
+    "rebase",
+    "reset",
+    "restore",
+    "revert",
+    "rm",
+    "stash",
+    "switch",
+    "update-index",
+    "worktree",
+];
+
+/// Whether git run with `args` changes the repository
+pub fn is_mutating(args: &[&OsStr]) -> bool {
+    subcommand(args).is_some_and(|command| MUTATING.contains(&command))
+}
+
+/// The git subcommand `args` run, past the global options before it
+pub fn subcommand<'a>(args: &[&'a OsStr]) -> Option<&'a str> {
+    let mut args = args.iter().copied().filter_map(OsStr::to_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            // Global options taking a value
+            "-c" | "-C" => {
+                args.next();
+            }
+            option if option.starts_with('-') => {}
+            command => return Some(command),
+        }
+    }
+    None
+}
+
+/// The lock file git could not create, when `stderr` says another git
+/// process holds it. Relies on git's untranslated message, so git must run
+/// in the C locale
+pub fn locked_file(stderr: &str) -> Option<PathBuf> {
+    const PREFIX: &str = "Unable to create '";
+    let start = stderr.find(PREFIX)? + PREFIX.len();
+    let rest = &stderr[start..];
+    let (path, after) = rest.split_once('\'')?;
+    if !after.starts_with(": File exists") {
+        return None;
+    }
+    let path = PathBuf::from(path);
+    let name = path.file_name()?.to_str()?;
+    LOCK_FILES.contains(&name).then_some(path)
+}
+
+/// Growing pauses between attempts that add up to at most a budget
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    next: Duration,
+    remaining: Duration,
+}
+
+impl Backoff {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            next: FIRST_PAUSE,
+            remaining: budget,
+        }
+    }
+
+    /// Pause before the next attempt; None once the budget is spent
+    pub fn next_pause(&mut self) -> Option<Duration> {
+        if self.remaining.is_zero() {
+            return None;
+        }
+        let pause = self.next.min(self.remaining);
+        self.remaining -= pause;
+        self.next = (self.next * 2).min(MAX_PAUSE);
+        Some(pause)
+    }
+}
+
+/// A lock that kept a command from running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusyLock {
+    pub path: PathBuf,
+    /// Old enough with no git process running that it can be removed
+    pub stale: bool,
+}
+
+impl BusyLock {
+    /// The lock at `path`, checked for being stale
+    pub fn inspect(path: PathBuf) -> Self {
+        Self::inspect_with(path, git_running)
+    }
+
+    fn inspect_with(path: PathBuf, git_running: impl FnOnce() -> bool) -> Self {
+        let stale = lock_age(&path).is_some_and(|age| age >= STALE_AFTER) && !git_running();
+        Self { path, stale }
+    }
+
+    /// Remove a stale lock. Refused when the lock is not stale or was taken
+    /// again since it was inspected.
+    pub fn remove(&self) -> Result<(), String> {
+        if !self.stale {
+            return Err(format!("{} is still in use", self.path.display()));
+        }
+        match lock_age(&self.path) {
+            None => Ok(()),
+            Some(age) if age < STALE_AFTER => Err(format!(
+                "{} was taken again by another git process",
+                self.path.display()
+            )),
+            Some(_) => std::fs::remove_file(&self.path)
+                .map_err(|e| format!("Failed to remove {}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+impl fmt::Display for BusyLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The repository is busy (another git process is running): {} exists",
+            self.path.display()
+        )?;
+        if self.stale {
+            write!(
+                f,
+                ". It is over {} minutes old and no git process is running, so it was \
+                 probably left by a git that crashed",
+                STALE_AFTER.as_secs() / 60
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Time since the lock at `path` was taken (None when it is gone)
+fn lock_age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(
+        SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default(),
+    )
+}
+
+/// Whether a git process runs on this machine
+fn git_running() -> bool {
+    use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessesToUpdate::All, ProcessRefreshKind::new());
+    system
+        .processes()
+        .values()
+        .any(|process| matches!(process.name().to_str(), Some("git" | "git.exe")))
+}
+
+thread_local! {
+    /// Set while `deferring` runs on this thread
+    static DEFERRING: Cell<bool> = const { Cell::new(false) };
+    /// First lock a command ran into during `deferring`
+    static DEFERRED: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with commands failing at once on a lock instead of waiting for
+/// it. Returns the lock the first of them ran into, so the caller can run
+/// `f` again later.
+pub fn deferring<T>(f: impl FnOnce() -> T) -> (T, Option<PathBuf>) {
+    let outer = DEFERRING.with(|deferring| deferring.replace(true));
+    let outer_lock = DEFERRED.with(|deferred| deferred.take());
+    let result = f();
+    DEFERRING.with(|deferring| deferring.set(outer));
+    let lock = DEFERRED.with(|deferred| deferred.replace(outer_lock));
+    (result, lock)
+}
+
+/// When a command on this thread runs inside `deferring`, note `lock` for it
+/// and return true: the command should fail without waiting
+pub fn defer(lock: &Path) -> bool {
+    if !DEFERRING.with(Cell::get) {
+        return false;
+    }
+    DEFERRED.with(|deferred| {
+        deferred
+            .borrow_mut()
+            .get_or_insert_with(|| lock.to_path_buf());
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&'static str]) -> Vec<&'static OsStr> {
+        args.iter().map(|arg| OsStr::new(*arg)).collect()
+    }
+
+    #[test]
+    fn test_lock_errors_are_recognized() {
+        let stderr = "fatal: Unable to create '/repo/.git/index.lock': File exists.\n\n\
+                      Another git process seems to be running in this repository";
+        assert_eq!(
+            locked_file(stderr),
+            Some(PathBuf::from("/repo/.git/index.lock"))
+        );
+        let shallow = "error: Unable to create '/repo/.git/shallow.lock': File exists.";
+        assert_eq!(
+            locked_file(shallow),
+            Some(PathBuf::from("/repo/.git/shallow.lock"))
+        );
+        // Other locks and failures are not waited for
+        assert_eq!(
+            locked_file("error: Unable to create '/repo/.git/config.lock': File exists."),
+            None
+        );
+        assert_eq!(
+            locked_file("fatal: Unable to create '/repo/.git/index.lock': Permission denied"),
+            None
+        );
+        assert_eq!(locked_file("fatal: not a git repository"), None);
+
+        assert!(is_mutating(&args(&["checkout", "--", "a.txt"])));
+        assert!(is_mutating(&args(&[
+            "-c",
+            "core.quotepath=off",
+            "worktree",
+            "add"
+        ])));
+        assert!(!is_mutating(&args(&["-C", "add", "status"])));
+        assert!(!is_mutating(&args(&["diff", "--", "add"])));
+    }
+
+    #[test]
+    fn test_backoff_grows_within_the_budget() {
+        let mut backoff = Backoff::new(Duration::from_millis(500));
+        let pauses: Vec<_> = std::iter::from_fn(|| backoff.next_pause())
+            .map(|pause| pause.as_millis())
+            .collect();
+        assert_eq!(pauses, [50, 100, 200, 150]);
+        assert_eq!(Backoff::new(Duration::ZERO).next_pause(), None);
+
+        let mut backoff = Backoff::new(Duration::from_secs(5));
+        let pauses: Vec<_> = std::iter::from_fn(|| backoff.next_pause()).collect();
+        assert_eq!(pauses.iter().sum::<Duration>(), Duration::from_secs(5));
+        assert!(pauses.iter().all(|&pause| pause <= MAX_PAUSE));
+    }
+
+    #[test]
+    fn test_deferring_notes_the_first_lock() {
+        assert!(!defer(Path::new("/repo/.git/index.lock")));
+        let ((), lock) = deferring(|| {
+            assert!(defer(Path::new("/a/index.lock")));
+            assert!(defer(Path::new("/b/index.lock")));
+        });
+        assert_eq!(lock, Some(PathBuf::from("/a/index.lock")));
+        assert_eq!(deferring(|| ()).1, None);
+        assert!(!defer(Path::new("/repo/.git/index.lock")));
+    }
+
+    #[test]
+    fn test_only_old_locks_without_git_running_are_offered_for_removal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.lock");
+        let lock = std::fs::File::create(&path).unwrap();
+        let fresh = BusyLock::inspect_with(path.clone(), || false);
+        assert!(!fresh.stale);
+        assert!(fresh.remove().is_err());
+
+        lock.set_modified(SystemTime::now() - STALE_AFTER - Duration::from_secs(60))
+            .unwrap();
+        assert!(!BusyLock::inspect_with(path.clone(), || true).stale);
+        let stale = BusyLock::inspect_with(path.clone(), || false);
+        assert!(stale.stale);
+        assert!(stale.to_string().contains("left by a git that crashed"));
+
+        // Taken again since: kept
+        lock.set_modified(SystemTime::now()).unwrap();
+        assert!(stale.remove().is_err());
+        assert!(path.exists());
+        lock.set_modified(SystemTime::now() - STALE_AFTER - Duration::from_secs(60))
+            .unwrap();
+        stale.remove().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,506 @@
+//! Queue of git mutations for the open repository
+//!
+//! Interleaved `git worktree` commands can corrupt the admin files under
+//! `.git/worktrees`, so long-running mutations (creating and removing
+//! worktrees, merging, rewriting a branch) run one after the other on a
+//! single worker thread. Read-only commands (status, diff, log) are run
+//! directly and never wait here.
+//!
+//! Each operation has a name and, optionally, the worktree it touches, so the
+//! UI can show what is running and keep conflicting actions disabled. An
+//! operation that has not started yet can be cancelled.
+//!
+//! An operation whose git command runs into another git process's lock does
+//! not wait on the worker: it is run again from the start after a growing
+//! pause (see `crate::git_lock`), and operations on other worktrees run in
+//! the meantime. Once `sashiki.git.lockWait` has passed it is held until the
+//! user retries or cancels it.
+
+use crate::git;
+use crate::git_lock::{self, Backoff, BusyLock};
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Identifies a queued operation (for cancelling it)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpId(u64);
+
+/// A queued or running operation as shown in the UI
+#[derive(Debug, Clone)]
+pub struct GitOp {
+    pub id: OpId,
+    pub name: String,
+    /// Worktree the operation changes, if it is about one
+    pub worktree: Option<PathBuf>,
+    /// Lock another git process holds that the operation waits for
+    pub lock: Option<PathBuf>,
+}
+
+struct Job {
+    op: GitOp,
+    /// Runs the operation and sends its result; returns the lock it ran into
+    /// instead, without sending anything
+    run: Box<dyn FnMut() -> Option<PathBuf> + Send>,
+    wait: Wait,
+    /// Pauses left while a lock is held
+    backoff: Option<Backoff>,
+}
+
+/// When a queued job may run
+enum Wait {
+    Ready,
+    /// Waiting for a lock to be released
+    Until(Instant),
+    /// The lock was held for too long: waiting for the user to retry
+    Held(BusyLock),
+}
+
+#[derive(Default)]
+struct State {
+    pending: VecDeque<Job>,
+    current: Option<GitOp>,
+    next_id: u64,
+    closed: bool,
+}
+
+impl State {
+    /// Take the first job that may run now. A job only goes ahead of jobs
+    /// waiting for a lock when it is about another worktree than all of
+    /// them; otherwise it may depend on one of them (deleting a branch
+    /// after its worktree). Err with when to look again, if ever.
+    fn take_ready(&mut self, now: Instant) -> Result<Job, Option<Instant>> {
+        let mut wake: Option<Instant> = None;
+        for index in 0..self.pending.len() {
+            let job = &self.pending[index];
+            let ready = match &job.wait {
+                Wait::Ready => true,
+                Wait::Until(at) => *at <= now,
+                Wait::Held(_) => false,
+            };
+            let waiting = || self.pending.range(..index);
+            let may_pass = index == 0
+                || job.op.worktree.as_deref().is_some_and(|worktree| {
+                    waiting().all(|ahead| {
+                        ahead
+                            .op
+                            .worktree
+                            .as_deref()
+                            .is_some_and(|other| other != worktree)
+                    })
+                });
+            if ready && may_pass {
+                return Ok(self.pending.remove(index).expect("index is in range"));
+            }
+            if let Wait::Until(at) = job.wait {
+                wake = Some(wake.map_or(at, |wake| wake.min(at)));
+            }
+        }
+        Err(wake)
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    ready: Condvar,
+    /// Called (from any thread) whenever the queue changes
+    on_change: Box<dyn Fn() + Send + Sync>,
+    /// How long an operation may wait for a lock (`git::lock_wait`)
+    lock_wait: fn() -> Duration,
+}
+
+/// Closes the queue once the last `GitQueue` clone is gone
+struct Handle {
+    shared: Arc<Shared>,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.closed = true;
+            state.pending.clear();
+        }
+        self.shared.ready.notify_one();
+    }
+}
+
+/// Handle to the worker thread. Dropping the last clone drops the pending
+/// operations; the running one still finishes.
+#[derive(Clone)]
+pub struct GitQueue {
+    handle: Arc<Handle>,
+}
+
+/// Result of a queued operation
+pub struct QueuedOp<T> {
+    result: smol::channel::Receiver<T>,
+}
+
+impl<T> QueuedOp<T> {
+    /// Wait for the operation to finish. None if it was cancelled (or the
+    /// queue closed) before it started.
+    pub async fn wait(self) -> Option<T> {
+        self.result.recv().await.ok()
+    }
+}
+
+impl GitQueue {
+    /// Start the worker thread
+    pub fn spawn(on_change: impl Fn() + Send + Sync + 'static) -> Self {
+        Self::spawn_with(on_change, git::lock_wait)
+    }
+
+    fn spawn_with(
+        on_change: impl Fn() + Send + Sync + 'static,
+        lock_wait: fn() -> Duration,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            ready: Condvar::new(),
+            on_change: Box::new(on_change),
+            lock_wait,
+        });
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .name("git-queue".to_string())
+            .spawn(move || run(&thread_shared))
+            .expect("failed to spawn git queue thread");
+        Self {
+            handle: Arc::new(Handle { shared }),
+        }
+    }
+
+    fn shared(&self) -> &Shared {
+        &self.handle.shared
+    }
+
+    /// Queue `f` behind every operation queued before it. `f` is run again
+    /// from the start when a git command in it ran into a lock.
+    pub fn push<T, F>(
+        &self,
+        name: impl Into<String>,
+        worktree: Option<&Path>,
+        mut f: F,
+    ) -> QueuedOp<T>
+    where
+        T: Send + 'static,
+        F: FnMut() -> T + Send + 'static,
+    {
+        let (tx, rx) = smol::channel::bounded(1);
+        let mut state = self
+            .shared()
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let id = OpId(state.next_id);
+        state.next_id += 1;
+        state.pending.push_back(Job {
+            op: GitOp {
+                id,
+                name: name.into(),
+                worktree: worktree.map(Path::to_path_buf),
+                lock: None,
+            },
+            run: Box::new(move || {
+                let (result, lock) = git_lock::deferring(&mut f);
+                if lock.is_none() {
+                    let _ = tx.try_send(result);
+                }
+                lock
+            }),
+            wait: Wait::Ready,
+            backoff: None,
+        });
+        drop(state);
+
+        self.shared().ready.notify_one();
+        (self.shared().on_change)();
+        QueuedOp { result: rx }
+    }
+
+    /// Remove an operation that has not started. Returns false when it is
+    /// already running or done.
+    pub fn cancel(&self, id: OpId) -> bool {
+        let Ok(mut state) = self.shared().state.lock() else {
+            return false;
+        };
+        let Some(index) = state.pending.iter().position(|job| job.op.id == id) else {
+            return false;
+        };
+        // Dropping the job drops its result sender, which ends `wait`
+        state.pending.remove(index);
+        drop(state);
+        (self.shared().on_change)();
+        true
+    }
+
+    /// The running operation and the ones waiting behind it
+    pub fn status(&self) -> (Option<GitOp>, Vec<GitOp>) {
+        match self.shared().state.lock() {
+            Ok(state) => (
+                state.current.clone(),
+                state.pending.iter().map(|job| job.op.clone()).collect(),
+            ),
+            Err(_) => (None, Vec::new()),
+        }
+    }
+
+    /// Operations held because another git process kept its lock for too
+    /// long, with that lock
+    pub fn held(&self) -> Vec<(GitOp, BusyLock)> {
+        match self.shared().state.lock() {
+            Ok(state) => state
+                .pending
+                .iter()
+                .filter_map(|job| match &job.wait {
+                    Wait::Held(lock) => Some((job.op.clone(), lock.clone())),
+                    _ => None,
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Run a held operation again, waiting for the lock as long as the
+    /// first time. Returns false when it is not held.
+    pub fn retry(&self, id: OpId) -> bool {
+        let Ok(mut state) = self.shared().state.lock() else {
+            return false;
+        };
+        let Some(job) = state
+            .pending
+            .iter_mut()
+            .find(|job| job.op.id == id && matches!(job.wait, Wait::Held(_)))
+        else {
+            return false;
+        };
+        job.wait = Wait::Ready;
+        job.backoff = None;
+        drop(state);
+        self.shared().ready.notify_one();
+        (self.shared().on_change)();
+        true
+    }
+
+    /// Whether a running or queued operation changes `worktree`
+    pub fn involves(&self, worktree: &Path) -> bool {
+        self.shared().state.lock().is_ok_and(|state| {
+            state
+                .current
+                .iter()
+                .chain(state.pending.iter().map(|job| &job.op))
+                .any(|op| op.worktree.as_deref() == Some(worktree))
+        })
+    }
+}
+
+/// Worker thread: run queued jobs in order until closed
+fn run(shared: &Shared) {
+    loop {
+        let mut job = {
+            let Ok(mut state) = shared.state.lock() else {
+                return;
+            };
+            let job = loop {
+                if state.closed {
+                    return;
+                }
+                let wake = match state.take_ready(Instant::now()) {
+                    Ok(job) => break job,
+                    Err(wake) => wake,
+                };
+                state = match wake {
+                    Some(at) => {
+                        let timeout = at.saturating_duration_since(Instant::now());
+                        match shared.ready.wait_timeout(state, timeout) {
+                            Ok((state, _)) => state,
+                            Err(_) => return,
+                        }
+                    }
+                    None => match shared.ready.wait(state) {
+                        Ok(state) => state,
+                        Err(_) => return,
+                    },
+                };
+            };
+            state.current = Some(job.op.clone());
+            job
+        };
+        (shared.on_change)();
+
+        // A panicking operation must not take the queue down with it; its
+        // waiter sees the dropped sender as a cancellation
+        let lock = panic::catch_unwind(AssertUnwindSafe(|| (job.run)()));
+        let requeued = match lock {
+            Ok(Some(lock)) => {
+                let backoff = job
+                    .backoff
+                    .get_or_insert_with(|| Backoff::new((shared.lock_wait)()));
+                job.wait = match backoff.next_pause() {
+                    Some(pause) => Wait::Until(Instant::now() + pause),
+                    None => {
+                        job.backoff = None;
+                        Wait::Held(BusyLock::inspect(lock.clone()))
+                    }
+                };
+                job.op.lock = Some(lock);
+                Some(job)
+            }
+            _ => None,
+        };
+
+        if let Ok(mut state) = shared.state.lock() {
+            state.current = None;
+            // Back in its place, ahead of the jobs queued after it
+            if let Some(job) = requeued
+                && !state.closed
+            {
+                state.pending.push_front(job);
+            }
+        }
+        (shared.on_change)();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::GitRepo;
+    use crate::test_support::init_repo;
+    use std::sync::mpsc;
+
+    /// Queue discarding the change to `README.md` in `dir`
+    fn push_discard(queue: &GitQueue, dir: &Path) -> QueuedOp<git::Result<()>> {
+        let repo = GitRepo::open(dir).unwrap();
+        let readme = dir.join("README.md");
+        std::fs::write(&readme, "changed\n").unwrap();
+        queue.push("Discard", Some(dir), move || repo.discard_file(&readme))
+    }
+
+    #[test]
+    fn test_create_worktree_requests_run_one_after_the_other() {
+        let dir = init_repo();
+
+        let queue = GitQueue::spawn(|| {});
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let ops: Vec<_> = ["one", "two"]
+            .into_iter()
+            .map(|name| {
+                let repo = GitRepo::open(dir.path()).unwrap();
+                let path = dir.path().join(format!("wt-{}", name));
+                let events = events.clone();
+                queue.push(format!("Create {}", name), Some(&path), {
+                    let path = path.clone();
+                    move || {
+                        events.lock().unwrap().push(format!("start {}", name));
+                        let result = repo.create_worktree(name, Some(name), &path, None);
+                        events.lock().unwrap().push(format!("end {}", name));
+                        result.map(|worktree| worktree.path)
+                    }
+                })
+            })
+            .collect();
+
+        let paths: Vec<_> = smol::block_on(async {
+            let mut paths = Vec::new();
+            for op in ops {
+                paths.push(op.wait().await.unwrap().unwrap());
+            }
+            paths
+        });
+        assert!(paths.iter().all(|path| path.join(".git").exists()));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["start one", "end one", "start two", "end two"]
+        );
+        let repo = GitRepo::open(dir.path()).unwrap();
+        assert_eq!(repo.list_worktrees().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_cancel_removes_only_operations_not_started() {
+        let queue = GitQueue::spawn(|| {});
+        let (release, blocked) = mpsc::channel::<()>();
+        let running = queue.push("running", Some(Path::new("/wt/a")), move || {
+            let _ = blocked.recv();
+            1
+        });
+        let queued = queue.push("queued", Some(Path::new("/wt/b")), || 2);
+
+        // Wait for the worker to pick up the first operation
+        while queue.status().0.is_none() {
+            std::thread::yield_now();
+        }
+        assert!(queue.involves(Path::new("/wt/a")));
+        assert!(queue.involves(Path::new("/wt/b")));
+        let (current, pending) = queue.status();
+        assert!(!queue.cancel(current.unwrap().id));
+        assert!(queue.cancel(pending[0].id));
+        assert!(!queue.involves(Path::new("/wt/b")));
+
+        drop(release);
+        assert_eq!(smol::block_on(running.wait()), Some(1));
+        assert_eq!(smol::block_on(queued.wait()), None);
+    }
+
+    #[test]
+    fn test_operations_waiting_for_a_lock_let_other_worktrees_pass() {
+        let dir = init_repo();
+        let lock = dir.path().join(".git/index.lock");
+        std::fs::write(&lock, "").unwrap();
+
+        let queue = GitQueue::spawn_with(|| {}, || Duration::from_secs(5));
+        let discard = push_discard(&queue, dir.path());
+        let other = queue.push("Other", Some(Path::new("/wt/other")), || 2);
+
+        // The worker is not blocked while the discard waits for the lock
+        assert_eq!(smol::block_on(other.wait()), Some(2));
+        let (current, pending) = queue.status();
+        let waiting = current
+            .into_iter()
+            .chain(pending)
+            .find(|op| op.name == "Discard")
+            .unwrap();
+        assert!(waiting.lock.unwrap().ends_with(".git/index.lock"));
+
+        std::fs::remove_file(&lock).unwrap();
+        assert!(matches!(smol::block_on(discard.wait()), Some(Ok(()))));
+        let readme = std::fs::read_to_string(dir.path().join("README.md")).unwrap();
+        assert_eq!(readme, "hello\n");
+    }
+
+    #[test]
+    fn test_operations_held_past_the_lock_wait_are_retried_on_request() {
+        let dir = init_repo();
+        let lock = dir.path().join(".git/index.lock");
+        std::fs::write(&lock, "").unwrap();
+
+        let queue = GitQueue::spawn_with(|| {}, || Duration::from_millis(200));
+        let discard = push_discard(&queue, dir.path());
+        // Not about a worktree: it may depend on the discard and waits too
+        let after = queue.push("After", None, || 3);
+
+        let started = Instant::now();
+        let held = loop {
+            let held = queue.held();
+            if !held.is_empty() {
+                break held;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        let (op, busy) = &held[0];
+        assert_eq!(op.name, "Discard");
+        assert!(busy.path.ends_with(".git/index.lock"));
+        assert!(!busy.stale);
+        assert_eq!(queue.status().1.len(), 2);
+
+        std::fs::remove_file(&lock).unwrap();
+        assert!(queue.retry(op.id));
+        assert!(!queue.retry(op.id));
+        assert!(matches!(smol::block_on(discard.wait()), Some(Ok(()))));
+        assert_eq!(smol::block_on(after.wait()), Some(3));
+    }
+}
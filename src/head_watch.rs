@@ -0,0 +1,195 @@
+//! Noticing commits and branch switches made outside Sashiki
+//!
+//! A `git commit`, `git switch` or rebase step run in a session's terminal
+//! moves that worktree's HEAD. Every `HEAD_POLL_INTERVAL` the files git
+//! writes when HEAD moves are stat'ed (no git process is started): the
+//! worktree's `HEAD`, its reflog `logs/HEAD`, and the loose ref of the branch
+//! HEAD points at. A worktree whose files changed gets its branch label,
+//! changed files and open diff refreshed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the HEAD files of every worktree are checked
+pub const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Modification time and size of each file that moves with HEAD (None for a
+/// file that does not exist, e.g. a packed ref)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadStamp(Vec<Option<(SystemTime, u64)>>);
+
+impl HeadStamp {
+    /// Stamp the HEAD files of the worktree checked out at `worktree`
+    pub fn read(worktree: &Path) -> Self {
+        let Some(git_dir) = worktree_git_dir(worktree) else {
+            return Self::default();
+        };
+        let mut files = vec![git_dir.join("HEAD"), git_dir.join("logs").join("HEAD")];
+        if let Some(branch_ref) = symbolic_ref(&git_dir) {
+            files.push(common_dir(&git_dir).join(branch_ref));
+        }
+        Self(files.iter().map(|path| stamp(path)).collect())
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Git directory of the worktree at `worktree`: its `.git` directory, or
+/// where the `.git` file of a linked worktree points
+fn worktree_git_dir(worktree: &Path) -> Option<PathBuf> {
+    let dot_git = worktree.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let dir = content.strip_prefix("gitdir:")?.trim();
+    Some(worktree.join(dir))
+}
+
+/// Directory holding the refs shared by all worktrees (`commondir` of a
+/// linked worktree's git directory)
+fn common_dir(git_dir: &Path) -> PathBuf {
+    std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+/// Ref HEAD points at ("refs/heads/main"), None when detached
+fn symbolic_ref(git_dir: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    Some(head.strip_prefix("ref:")?.trim().to_string())
+}
+
+/// The last stamp of each worktree, to tell which HEADs moved
+#[derive(Debug, Default)]
+pub struct HeadWatcher {
+    stamps: HashMap<PathBuf, HeadStamp>,
+}
+
+impl HeadWatcher {
+    /// Stamp `worktrees` again and return those whose HEAD moved since the
+    /// last poll. Worktrees seen for the first time are only recorded;
+    /// worktrees no longer listed are forgotten.
+    pub fn poll(&mut self, worktrees: &[PathBuf]) -> Vec<PathBuf> {
+        let mut moved = Vec::new();
+        let mut stamps = HashMap::with_capacity(worktrees.len());
+        for worktree in worktrees {
+            let stamp = HeadStamp::read(worktree);
+            if self
+                .stamps
+                .get(worktree)
+                .is_some_and(|previous| *previous != stamp)
+            {
+                moved.push(worktree.clone());
+            }
+            stamps.insert(worktree.clone(), stamp);
+        }
+        self.stamps = stamps;
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::GitRepo;
+    use crate::session::SessionManager;
+    use crate::test_support::{add_worktree, git, init_repo_at};
+
+    /// Repository with a `feature` branch checked out in a linked worktree
+    /// next to it
+    fn init_repo() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        // Compared with the paths git lists
+        let root = dir.path().canonicalize().unwrap();
+        let main = root.join("repo");
+        let feature = root.join("repo-feature");
+        init_repo_at(&main);
+        add_worktree(&main, "feature", &feature);
+        (dir, main, feature)
+    }
+
+    fn commit(worktree: &Path, file: &str) {
+        std::fs::write(worktree.join(file), file).unwrap();
+        git(worktree, &["add", "."]);
+        git(worktree, &["commit", "-q", "-m", file]);
+    }
+
+    /// Sessions for the repository's worktrees, refreshed the way the app
+    /// does when a HEAD moved
+    fn refresh(manager: &mut SessionManager, repo: &GitRepo, moved: &[PathBuf]) {
+        for worktree in repo.list_worktrees().unwrap() {
+            if moved.contains(&worktree.path) {
+                manager.update_session_worktree(&worktree);
+            }
+        }
+    }
+
+    fn branch(manager: &SessionManager, worktree: &Path) -> Option<String> {
+        let index = manager.find_session_by_path(worktree)?;
+        manager.sessions()[index].branch().map(str::to_string)
+    }
+
+    #[test]
+    fn test_commit_moves_head() {
+        let (_dir, main, feature) = init_repo();
+        let worktrees = vec![main.clone(), feature.clone()];
+        let mut watcher = HeadWatcher::default();
+        assert!(watcher.poll(&worktrees).is_empty());
+        assert!(watcher.poll(&worktrees).is_empty());
+
+        commit(&feature, "a.txt");
+        assert_eq!(watcher.poll(&worktrees), vec![feature.clone()]);
+        assert!(watcher.poll(&worktrees).is_empty());
+
+        // Working tree edits do not move HEAD
+        std::fs::write(feature.join("a.txt"), "edited").unwrap();
+        assert!(watcher.poll(&worktrees).is_empty());
+
+        commit(&main, "b.txt");
+        assert_eq!(watcher.poll(&worktrees), vec![main]);
+    }
+
+    #[test]
+    fn test_branch_switch_updates_the_session() {
+        let (_dir, main, feature) = init_repo();
+        let repo = GitRepo::open(&main).unwrap();
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(repo.list_worktrees().unwrap());
+        let worktrees = vec![main.clone(), feature.clone()];
+        let mut watcher = HeadWatcher::default();
+        watcher.poll(&worktrees);
+        assert_eq!(branch(&manager, &feature).as_deref(), Some("feature"));
+
+        git(&feature, &["switch", "-q", "-c", "other"]);
+        let moved = watcher.poll(&worktrees);
+        assert_eq!(moved, vec![feature.clone()]);
+        refresh(&mut manager, &repo, &moved);
+        assert_eq!(branch(&manager, &feature).as_deref(), Some("other"));
+        assert_eq!(branch(&manager, &main).as_deref(), Some("main"));
+
+        // A detached HEAD (e.g. during a rebase) shows the short hash
+        commit(&feature, "c.txt");
+        git(&feature, &["checkout", "-q", "--detach", "HEAD~1"]);
+        let moved = watcher.poll(&worktrees);
+        assert_eq!(moved, vec![feature.clone()]);
+        refresh(&mut manager, &repo, &moved);
+        let head = GitRepo::open(&feature).unwrap().resolve_head().unwrap();
+        assert_eq!(branch(&manager, &feature), Some(head[..7].to_string()));
+    }
+
+    #[test]
+    fn test_removed_worktrees_are_forgotten() {
+        let (_dir, main, feature) = init_repo();
+        let mut watcher = HeadWatcher::default();
+        watcher.poll(&[main.clone(), feature.clone()]);
+        watcher.poll(std::slice::from_ref(&main));
+        commit(&feature, "a.txt");
+        // Seen again after being dropped: recorded, not reported
+        assert!(watcher.poll(&[main, feature]).is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+//! Startup diagnostics for missing or outdated dependencies
+//!
+//! Checks that `git` runs and is recent enough for the worktree commands in
+//! use, that the configured terminal shell exists and that the fonts the
+//! views rely on are installed. Each problem comes with a concrete fix and is
+//! listed in the health panel until dismissed. The checks run in the
+//! background; results are kept until the user re-runs them.
+
+use crate::theme::MONOSPACE_FONT;
+use std::path::Path;
+use std::process::Command;
+
+/// Oldest git with the `worktree list --porcelain` output and worktree
+/// commands relied on
+pub const MIN_GIT_VERSION: (u32, u32) = (2, 31);
+
+/// Font families that cover Japanese / Chinese / Korean text
+const CJK_FONTS: &[&str] = &[
+    "Noto Sans CJK JP",
+    "Noto Sans Mono CJK JP",
+    "Noto Sans JP",
+    "Source Han Sans",
+    "Yu Gothic",
+    "MS Gothic",
+    "Meiryo",
+    "Hiragino Sans",
+    "Hiragino Kaku Gothic ProN",
+    "Microsoft YaHei",
+    "WenQuanYi Zen Hei",
+];
+
+/// A failed check and what to do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub title: String,
+    pub remedy: String,
+}
+
+impl Problem {
+    fn new(title: impl Into<String>, remedy: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            remedy: remedy.into(),
+        }
+    }
+}
+
+/// Run all checks. `shell` is the configured terminal shell (None = the
+/// login shell); `font_names` are the installed font families.
+pub fn run(shell: Option<&str>, font_names: &[String]) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    problems.extend(check_git());
+    problems.extend(check_shell(shell));
+    problems.extend(check_fonts(font_names));
+    problems
+}
+
+/// Parse the output of `git --version` into (major, minor, patch).
+///
+/// Accepts vendor suffixes such as `2.43.0.windows.1` or
+/// `2.39.3 (Apple Git-146)`; a missing patch number reads as 0.
+pub fn parse_git_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let version = version.split_whitespace().next()?;
+    let mut parts = version.split('.').map(|part| {
+        let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse::<u32>().ok()
+    });
+    let major = parts.next()??;
+    let minor = parts.next()??;
+    let patch = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+fn check_git() -> Option<Problem> {
+    let output = match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        Ok(_) | Err(_) => {
+            return Some(Problem::new(
+                "git was not found",
+                "Install git (https://git-scm.com/downloads) and make sure it is on PATH, \
+                 then restart Sashiki.",
+            ));
+        }
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    let Some((major, minor, patch)) = parse_git_version(&text) else {
+        return Some(Problem::new(
+            format!("Unrecognized git version: {}", text.trim()),
+            "Worktree commands may not work. Check that `git --version` runs normally.",
+        ));
+    };
+    if (major, minor) < MIN_GIT_VERSION {
+        return Some(Problem::new(
+            format!("git {}.{}.{} is too old", major, minor, patch),
+            format!(
+                "Worktree features need git {}.{} or newer. Update git and restart Sashiki.",
+                MIN_GIT_VERSION.0, MIN_GIT_VERSION.1
+            ),
+        ));
+    }
+    None
+}
+
+fn check_shell(shell: Option<&str>) -> Option<Problem> {
+    let (program, configured) = match shell {
+        Some(command) => (command.split_whitespace().next()?.to_string(), true),
+        // The login shell: only checkable through $SHELL
+        None if cfg!(unix) => (std::env::var("SHELL").ok()?, false),
+        None => return None,
+    };
+    if find_program(&program) {
+        return None;
+    }
+    let remedy = if configured {
+        "Point `sashiki.terminal.shell` (git config) to an installed shell, or \
+         unset it to use the login shell."
+    } else {
+        "The SHELL environment variable points to a missing program. Fix it or set \
+         `sashiki.terminal.shell` with git config."
+    };
+    Some(Problem::new(
+        format!("Shell not found: {}", program),
+        remedy,
+    ))
+}
+
+/// Whether `program` is a file, or a name found on PATH
+fn find_program(program: &str) -> bool {
+    if program.contains(['/', '\\']) {
+        return Path::new(program).is_file();
+    }
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    std::env::split_paths(&path).any(|dir| {
+        extensions
+            .iter()
+            .any(|ext| dir.join(format!("{}{}", program, ext)).is_file())
+    })
+}
+
+fn check_fonts(font_names: &[String]) -> Vec<Problem> {
+    let installed = |name: &str| font_names.iter().any(|f| f.eq_ignore_ascii_case(name));
+    let mut problems = Vec::new();
+    if !installed(MONOSPACE_FONT) {
+        problems.push(Problem::new(
+            format!("Font not found: {}", MONOSPACE_FONT),
+            format!(
+                "Terminals and file views fall back to another font and may misalign. \
+                 Install {} for the intended layout.",
+                MONOSPACE_FONT
+            ),
+        ));
+    }
+    if !CJK_FONTS.iter().any(|name| installed(name)) {
+        problems.push(Problem::new(
+            "No CJK font found",
+            "Japanese, Chinese and Korean text may show as boxes. Install a CJK font \
+             such as Noto Sans CJK.",
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_git_version() {
+        assert_eq!(parse_git_version("git version 2.43.0\n"), Some((2, 43, 0)));
+        assert_eq!(
+            parse_git_version("git version 2.43.0.windows.1"),
+            Some((2, 43, 0))
+        );
+        assert_eq!(
+            parse_git_version("git version 2.39.3 (Apple Git-146)"),
+            Some((2, 39, 3))
+        );
+        assert_eq!(parse_git_version("git version 2.31"), Some((2, 31, 0)));
+        assert_eq!(
+            parse_git_version("git version 2.45.0-rc1"),
+            Some((2, 45, 0))
+        );
+        assert_eq!(parse_git_version("git: command not found"), None);
+        assert_eq!(parse_git_version("git version unknown"), None);
+    }
+
+    #[test]
+    fn test_minimum_version_comparison() {
+        let too_old = parse_git_version("git version 2.30.9").unwrap();
+        assert!((too_old.0, too_old.1) < MIN_GIT_VERSION);
+        let new_enough = parse_git_version("git version 2.31.0.windows.1").unwrap();
+        assert!((new_enough.0, new_enough.1) >= MIN_GIT_VERSION);
+    }
+
+    #[test]
+    fn test_font_checks() {
+        let fonts = vec![
+            MONOSPACE_FONT.to_lowercase(),
+            "Noto Sans CJK JP".to_string(),
+        ];
+        assert!(check_fonts(&fonts).is_empty());
+
+        let problems = check_fonts(&["Arial".to_string()]);
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_shell_is_reported() {
+        assert!(check_shell(Some("/nonexistent/sashiki-shell -l")).is_some());
+        assert!(check_shell(Some("sashiki-no-such-shell")).is_some());
+    }
+}
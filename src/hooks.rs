@@ -0,0 +1,534 @@
+//! User commands run on app events
+//!
+//! A hook lives under `[sashiki "hook.<event>"]`, one `command` entry per
+//! shell command to run when the event happens:
+//!
+//! ```text
+//! [sashiki "hook.session_idle"]
+//!     command = notify-send "Idle:" {session}
+//! ```
+//!
+//! Events are `session_idle`, `session_attention`, `worktree_created`,
+//! `worktree_removed`, `commit_detected` and `repository_opened`. In a
+//! command, `{repo}` is replaced by the main worktree, `{session}` by the
+//! session's worktree name and `{branch}` by its branch (both empty for
+//! `repository_opened`), and `{path}` by its worktree (the main worktree
+//! when the event has none). Each value is quoted as one word for the shell
+//! (see `crate::shell_escape`), so a branch named `x;rm -rf ~` stays a
+//! name; placeholders go outside quotes in the command.
+//!
+//! Hooks are read from the user's own configuration only, never from a
+//! repository's `.sashikiconfig` (see `settings::USER_ONLY_KEYS`).
+//!
+//! Commands run detached on a background thread in the session's worktree,
+//! or the main worktree when there is none or it was removed. The same
+//! event of the same session fires at most once per `DEBOUNCE`, and at most
+//! `MAX_RUNNING` commands run at a time; the rest are skipped. The exit status and
+//! output of each run are recorded in the in-app log. A hook never affects
+//! the app: failures are only logged.
+//!
+//! Each open repository has its own `HookBus`, configured from its settings
+//! when it is opened or shown again (see `configure`). An event carries the
+//! repository it happened in (`HookSubject::repo`), and `emit` runs the hooks
+//! of that repository only, so a session left running in a repository set
+//! aside fires its own hooks rather than those of the one shown. Whatever
+//! else wants to hear of events (the control server's event stream) can
+//! `subscribe`, hooks or not.
+
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::git;
+use crate::settings::Settings;
+use crate::shell_escape::Shell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+/// An event fires its commands at most once per this long (per session)
+pub const DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// Hook commands running at once beyond which more are skipped
+pub const MAX_RUNNING: usize = 4;
+
+/// Output kept in the log per run
+const MAX_OUTPUT: usize = 4000;
+
+/// The bus of each open repository, by main worktree
+static HOOK_BUSES: Mutex<Option<HashMap<PathBuf, HookBus>>> = Mutex::new(None);
+
+/// Where every emitted event is also sent (see `subscribe`)
+static SUBSCRIBERS: Mutex<Vec<mpsc::Sender<(HookEvent, HookSubject)>>> = Mutex::new(Vec::new());
+
+/// Something hooks can run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    /// A working session went quiet (see `activity::IDLE_AFTER`)
+    SessionIdle,
+    /// A session in the background asked for attention
+    SessionAttention,
+    WorktreeCreated,
+    WorktreeRemoved,
+    /// HEAD of a worktree moved to another commit
+    CommitDetected,
+    RepositoryOpened,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 6] = [
+        HookEvent::SessionIdle,
+        HookEvent::SessionAttention,
+        HookEvent::WorktreeCreated,
+        HookEvent::WorktreeRemoved,
+        HookEvent::CommitDetected,
+        HookEvent::RepositoryOpened,
+    ];
+
+    /// Name used in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::SessionIdle => "session_idle",
+            HookEvent::SessionAttention => "session_attention",
+            HookEvent::WorktreeCreated => "worktree_created",
+            HookEvent::WorktreeRemoved => "worktree_removed",
+            HookEvent::CommitDetected => "commit_detected",
+            HookEvent::RepositoryOpened => "repository_opened",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// The session an event is about
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookSubject {
+    /// Worktree name (empty for repository events)
+    pub session: String,
+    pub branch: Option<String>,
+    /// Worktree the event is about (None = the main worktree)
+    pub path: Option<PathBuf>,
+    /// Main worktree of the repository the event happened in (None = not
+    /// known yet, so no hooks run)
+    pub repo: Option<PathBuf>,
+}
+
+impl HookSubject {
+    pub fn new(session: &str, branch: Option<&str>, path: &Path) -> Self {
+        Self {
+            session: session.to_string(),
+            branch: branch.map(str::to_string),
+            path: Some(path.to_path_buf()),
+            repo: None,
+        }
+    }
+
+    /// The repository whose main worktree is `repo` itself
+    pub fn repository(repo: &Path) -> Self {
+        Self::default().in_repo(Some(repo.to_path_buf()))
+    }
+
+    /// This subject, as happening in the repository at `repo`
+    pub fn in_repo(mut self, repo: Option<PathBuf>) -> Self {
+        self.repo = repo;
+        self
+    }
+}
+
+/// How a hook command ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+    /// Exit code (None = killed by a signal)
+    pub code: Option<i32>,
+    /// stdout followed by stderr
+    pub output: String,
+}
+
+/// Runs a hook command to completion (the shell, or a fake in tests)
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, command: &str, workdir: &Path) -> std::io::Result<HookOutput>;
+}
+
+/// Runs commands with `sh -c` (`cmd /C` on Windows)
+#[derive(Debug, Default)]
+pub struct ShellRunner;
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &str, workdir: &Path) -> std::io::Result<HookOutput> {
+        #[cfg(unix)]
+        let (program, args) = ("sh", ["-c", command]);
+
+        #[cfg(windows)]
+        let (program, args) = ("cmd", ["/C", command]);
+
+        let output = std::process::Command::new(program)
+            .args(args)
+            .current_dir(workdir)
+            .stdin(std::process::Stdio::null())
+            .output()?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(HookOutput {
+            code: output.status.code(),
+            output: text,
+        })
+    }
+}
+
+/// `template` with the placeholders filled in from `repo` and `subject`,
+/// each quoted for the shell the command runs in
+pub fn expand(template: &str, repo: &Path, subject: &HookSubject) -> String {
+    let shell = Shell::platform();
+    let path = subject.path.as_deref().unwrap_or(repo);
+    template
+        .replace("{repo}", &shell.escape(&repo.to_string_lossy()))
+        .replace("{session}", &shell.escape(&subject.session))
+        .replace(
+            "{branch}",
+            &shell.escape(subject.branch.as_deref().unwrap_or_default()),
+        )
+        .replace("{path}", &shell.escape(&path.to_string_lossy()))
+}
+
+/// Configured hooks of a repository and the runs they started
+pub struct HookBus {
+    /// Main worktree (`{repo}`)
+    repo: PathBuf,
+    commands: HashMap<HookEvent, Vec<String>>,
+    runner: Arc<dyn CommandRunner>,
+    /// When each event last fired, per session worktree
+    last_fired: HashMap<(HookEvent, Option<PathBuf>), Instant>,
+    running: Arc<AtomicUsize>,
+}
+
+impl HookBus {
+    /// Hooks configured in `settings`, with a warning for each section that
+    /// names no known event
+    pub fn from_settings(
+        settings: &Settings,
+        repo: &Path,
+        runner: Arc<dyn CommandRunner>,
+    ) -> (Self, Vec<String>) {
+        let mut commands = HashMap::new();
+        let mut warnings = Vec::new();
+        for name in settings.subsection_names(git::CONFIG_HOOK_PREFIX) {
+            let Some(event) = HookEvent::from_name(&name) else {
+                warnings.push(format!(
+                    "Unknown hook event '{}' in {}.{}; known events: {}",
+                    name,
+                    git::CONFIG_HOOK_PREFIX,
+                    name,
+                    HookEvent::ALL.map(|event| event.name()).join(", ")
+                ));
+                continue;
+            };
+            let entries = settings.get_all(&git::hook_key(&name, "command"));
+            if !entries.is_empty() {
+                commands.insert(event, entries);
+            }
+        }
+        let bus = Self {
+            repo: repo.to_path_buf(),
+            commands,
+            runner,
+            last_fired: HashMap::new(),
+            running: Arc::new(AtomicUsize::new(0)),
+        };
+        (bus, warnings)
+    }
+
+    /// Start the commands of `event` unless it fired for the same session
+    /// within `DEBOUNCE`; returns the command lines started
+    pub fn emit(&mut self, event: HookEvent, subject: &HookSubject, now: Instant) -> Vec<String> {
+        let Some(templates) = self.commands.get(&event) else {
+            return Vec::new();
+        };
+        let key = (event, subject.path.clone());
+        if self
+            .last_fired
+            .get(&key)
+            .is_some_and(|last| now.duration_since(*last) < DEBOUNCE)
+        {
+            return Vec::new();
+        }
+        self.last_fired.insert(key, now);
+
+        // A removed worktree is gone by the time its hooks run
+        let workdir = subject
+            .path
+            .clone()
+            .filter(|path| path.is_dir())
+            .unwrap_or_else(|| self.repo.clone());
+        let mut started = Vec::new();
+        for template in templates {
+            let command = expand(template, &self.repo, subject);
+            if self.running.fetch_add(1, Ordering::SeqCst) >= MAX_RUNNING {
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                app_log::record(
+                    LogEntry::new(
+                        LogLevel::Warn,
+                        LogSource::Hook,
+                        format!(
+                            "Skipped {} hook: {} hooks are still running",
+                            event.name(),
+                            MAX_RUNNING
+                        ),
+                    )
+                    .with_command(&command),
+                );
+                continue;
+            }
+            let runner = self.runner.clone();
+            let running = self.running.clone();
+            let workdir = workdir.clone();
+            let line = command.clone();
+            let spawned = std::thread::Builder::new()
+                .name("hook".to_string())
+                .spawn(move || {
+                    let result = runner.run(&line, &workdir);
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    log_run(event, &line, result);
+                });
+            if let Err(e) = spawned {
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                log_run(event, &command, Err(e));
+                continue;
+            }
+            started.push(command);
+        }
+        started
+    }
+}
+
+fn log_run(event: HookEvent, command: &str, result: std::io::Result<HookOutput>) {
+    let entry = match result {
+        Ok(HookOutput { code, output }) => {
+            let (level, status) = match code {
+                Some(0) => (LogLevel::Info, "exited with 0".to_string()),
+                Some(code) => (LogLevel::Warn, format!("exited with {}", code)),
+                None => (LogLevel::Warn, "was killed".to_string()),
+            };
+            let output = output.trim();
+            let output = match output.char_indices().nth(MAX_OUTPUT) {
+                Some((end, _)) => &output[..end],
+                None => output,
+            };
+            LogEntry::new(
+                level,
+                LogSource::Hook,
+                format!("{} hook {}", event.name(), status),
+            )
+            .with_stderr(output)
+        }
+        Err(e) => LogEntry::new(
+            LogLevel::Error,
+            LogSource::Hook,
+            format!("{} hook could not be started: {}", event.name(), e),
+        ),
+    };
+    app_log::record(entry.with_command(command));
+}
+
+/// Use the hooks in `settings` for the events of the repository at `repo`
+/// from now on; unknown events in the config are logged as warnings
+pub fn configure(settings: &Settings, repo: &Path) {
+    let (bus, warnings) = HookBus::from_settings(settings, repo, Arc::new(ShellRunner));
+    for warning in warnings {
+        app_log::record(LogEntry::new(LogLevel::Warn, LogSource::Hook, warning));
+    }
+    install(bus);
+}
+
+/// Make `bus` the one of its repository
+fn install(bus: HookBus) {
+    if let Ok(mut buses) = HOOK_BUSES.lock() {
+        buses
+            .get_or_insert_with(HashMap::new)
+            .insert(bus.repo.clone(), bus);
+    }
+}
+
+/// Stop running hooks for the repository at `repo` (closed)
+pub fn forget(repo: &Path) {
+    if let Ok(mut buses) = HOOK_BUSES.lock()
+        && let Some(buses) = buses.as_mut()
+    {
+        buses.remove(repo);
+    }
+}
+
+/// Run the hooks `subject`'s repository has for `event`, if any, and tell
+/// the subscribers
+pub fn emit(event: HookEvent, subject: &HookSubject) {
+    if let Ok(mut buses) = HOOK_BUSES.lock()
+        && let Some(repo) = &subject.repo
+        && let Some(bus) = buses.as_mut().and_then(|buses| buses.get_mut(repo))
+    {
+        bus.emit(event, subject, Instant::now());
+    }
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.retain(|subscriber| subscriber.send((event, subject.clone())).is_ok());
+    }
+}
+
+/// Every event emitted from now on, undebounced; dropping the receiver
+/// unsubscribes (at the next event)
+pub fn subscribe() -> mpsc::Receiver<(HookEvent, HookSubject)> {
+    let (sender, receiver) = mpsc::channel();
+    if let Ok(mut subscribers) = SUBSCRIBERS.lock() {
+        subscribers.push(sender);
+    }
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    /// Sends the commands it is asked to run instead of running them
+    struct FakeRunner(Mutex<mpsc::Sender<(String, PathBuf)>>);
+
+    impl CommandRunner for FakeRunner {
+        fn run(&self, command: &str, workdir: &Path) -> std::io::Result<HookOutput> {
+            let _ = self
+                .0
+                .lock()
+                .unwrap()
+                .send((command.to_string(), workdir.to_path_buf()));
+            Ok(HookOutput {
+                code: Some(0),
+                output: String::new(),
+            })
+        }
+    }
+
+    fn bus(entries: &[(&str, &str)]) -> (HookBus, Vec<String>, mpsc::Receiver<(String, PathBuf)>) {
+        let (tx, rx) = mpsc::channel();
+        let settings = Settings::from_layers(vec![
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ]);
+        let (bus, warnings) = HookBus::from_settings(
+            &settings,
+            Path::new("/repo"),
+            Arc::new(FakeRunner(Mutex::new(tx))),
+        );
+        (bus, warnings, rx)
+    }
+
+    fn subject() -> HookSubject {
+        HookSubject::new("feature", Some("feature/x"), Path::new("/wt/feature"))
+    }
+
+    #[test]
+    fn test_placeholders_are_expanded() {
+        let (mut bus, warnings, rx) = bus(&[(
+            "sashiki.hook.session_idle.command",
+            "say {session} {branch} {path} {repo} {other}",
+        )]);
+        assert!(warnings.is_empty());
+
+        let worktree = tempfile::tempdir().unwrap();
+        let session = HookSubject::new("feature", Some("feature/x"), worktree.path());
+        let started = bus.emit(HookEvent::SessionIdle, &session, Instant::now());
+        let expected = format!(
+            "say feature feature/x {} /repo {{other}}",
+            worktree.path().display()
+        );
+        assert_eq!(started, vec![expected.clone()]);
+        let ran = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(ran, (expected, worktree.path().to_path_buf()));
+
+        // Repository events have no session; a removed worktree is still
+        // named but the command runs in the main worktree
+        assert_eq!(
+            expand(
+                "{session}|{branch}|{path}",
+                Path::new("/repo"),
+                &HookSubject::default()
+            ),
+            format!("{0}|{0}|/repo", Shell::platform().escape(""))
+        );
+        bus.emit(HookEvent::SessionIdle, &subject(), Instant::now());
+        let ran = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(ran.1, PathBuf::from("/repo"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_placeholders_cannot_inject_commands() {
+        let branch = "x;`touch injected`$(touch injected) $HOME'\"";
+        let worktree = tempfile::tempdir().unwrap();
+        let subject = HookSubject::new("my session", Some(branch), worktree.path());
+        let command = expand("printf '%s|' {branch} {session}", worktree.path(), &subject);
+        let ran = ShellRunner.run(&command, worktree.path()).unwrap();
+        assert_eq!(ran.code, Some(0));
+        assert_eq!(ran.output, format!("{}|my session|", branch));
+        assert!(!worktree.path().join("injected").exists());
+    }
+
+    #[test]
+    fn test_events_are_debounced_per_session() {
+        let (mut bus, _, _rx) = bus(&[("sashiki.hook.commit_detected.command", "a")]);
+        let start = Instant::now();
+        let other = HookSubject::new("other", None, Path::new("/wt/other"));
+        let mut emit = |subject: &HookSubject, at| bus.emit(HookEvent::CommitDetected, subject, at);
+
+        assert_eq!(emit(&subject(), start).len(), 1);
+        assert!(emit(&subject(), start + DEBOUNCE / 2).is_empty());
+        assert_eq!(emit(&other, start + DEBOUNCE / 2).len(), 1);
+        assert_eq!(emit(&subject(), start + DEBOUNCE).len(), 1);
+        // Events without hooks start nothing
+        assert!(
+            bus.emit(HookEvent::SessionIdle, &subject(), start)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_unknown_events_are_warned_about() {
+        let (bus, warnings, _rx) = bus(&[
+            ("sashiki.hook.session_idel.command", "a"),
+            ("sashiki.hook.Worktree_Created.command", "b"),
+        ]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'session_idel'"));
+        assert!(bus.commands.contains_key(&HookEvent::WorktreeCreated));
+    }
+
+    #[test]
+    fn test_events_run_the_hooks_of_their_repository() {
+        let (tx, rx) = mpsc::channel();
+        let runner: Arc<dyn CommandRunner> = Arc::new(FakeRunner(Mutex::new(tx)));
+        for (repo, command) in [("/hooks-test/a", "echo a"), ("/hooks-test/b", "echo b")] {
+            let settings = Settings::from_layers(vec![vec![(
+                "sashiki.hook.worktree_created.command".to_string(),
+                command.to_string(),
+            )]]);
+            install(HookBus::from_settings(&settings, Path::new(repo), runner.clone()).0);
+        }
+        let ran = || rx.recv_timeout(Duration::from_secs(5)).ok().map(|(c, _)| c);
+
+        let in_b = HookSubject::new("x", None, Path::new("/hooks-test/b-wt/x"))
+            .in_repo(Some(PathBuf::from("/hooks-test/b")));
+        emit(HookEvent::WorktreeCreated, &in_b);
+        assert_eq!(ran().as_deref(), Some("echo b"));
+
+        // Not known to be in any repository: no hooks
+        let unknown = HookSubject::new("y", None, Path::new("/hooks-test/a-wt/y"));
+        emit(HookEvent::WorktreeCreated, &unknown);
+        forget(Path::new("/hooks-test/a"));
+        emit(
+            HookEvent::WorktreeCreated,
+            &HookSubject::repository(Path::new("/hooks-test/a")),
+        );
+        assert_eq!(rx.recv_timeout(Duration::from_millis(200)).ok(), None);
+        forget(Path::new("/hooks-test/b"));
+    }
+}
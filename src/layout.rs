@@ -0,0 +1,193 @@
+//! Sizes of the panels around the terminal
+//!
+//! The drag handles and the layout keys both go through `PanelLayout`, so a
+//! size is clamped the same way however it is changed. The terminal grid
+//! follows its element on its own (see `terminal::resize`); the keys move
+//! the file view edge by whole terminal rows so the program sees its rows
+//! change by whole lines. In single mode the terminal is docked below the
+//! file view or to its right, and can be maximized over the central area;
+//! neither touches the sizes, so toggling back restores them exactly.
+
+use std::ops::RangeInclusive;
+
+pub const SIDEBAR_WIDTH: RangeInclusive<f32> = 120.0..=500.0;
+pub const FILE_LIST_WIDTH: RangeInclusive<f32> = 120.0..=500.0;
+/// Height of the file view above a bottom-docked terminal
+pub const FILE_VIEW_HEIGHT: RangeInclusive<f32> = 100.0..=800.0;
+/// Width of the file view left of a right-docked terminal
+pub const FILE_VIEW_WIDTH: RangeInclusive<f32> = 200.0..=1400.0;
+/// Share of the main terminal next to the verify terminal
+pub const TERMINAL_SPLIT_RATIO: RangeInclusive<f32> = 0.2..=0.8;
+
+/// Terminal rows a layout key grows or shrinks the terminal by
+pub const TERMINAL_ROW_STEP: usize = 2;
+/// Pixels a layout key widens or narrows the sidebar by
+pub const SIDEBAR_STEP: f32 = 16.0;
+
+/// Where the terminal sits next to the file view in single mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TerminalDock {
+    #[default]
+    Bottom,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanelLayout {
+    pub sidebar_width: f32,
+    pub file_list_width: f32,
+    pub file_view_height: f32,
+    pub file_view_width: f32,
+    pub terminal_split_ratio: f32,
+    pub dock: TerminalDock,
+    /// The terminal has the whole central area; the file view and the
+    /// panels below the terminal are hidden, not closed
+    pub terminal_maximized: bool,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            sidebar_width: 224.0,
+            file_list_width: 256.0,
+            file_view_height: 384.0,
+            file_view_width: 640.0,
+            terminal_split_ratio: 0.5,
+            dock: TerminalDock::Bottom,
+            terminal_maximized: false,
+        }
+    }
+}
+
+fn clamp(value: f32, range: &RangeInclusive<f32>) -> f32 {
+    value.clamp(*range.start(), *range.end())
+}
+
+impl PanelLayout {
+    pub fn set_sidebar_width(&mut self, width: f32) {
+        self.sidebar_width = clamp(width, &SIDEBAR_WIDTH);
+    }
+
+    pub fn set_file_list_width(&mut self, width: f32) {
+        self.file_list_width = clamp(width, &FILE_LIST_WIDTH);
+    }
+
+    pub fn set_file_view_height(&mut self, height: f32) {
+        self.file_view_height = clamp(height, &FILE_VIEW_HEIGHT);
+    }
+
+    pub fn set_file_view_width(&mut self, width: f32) {
+        self.file_view_width = clamp(width, &FILE_VIEW_WIDTH);
+    }
+
+    pub fn set_terminal_split_ratio(&mut self, ratio: f32) {
+        self.terminal_split_ratio = clamp(ratio, &TERMINAL_SPLIT_RATIO);
+    }
+
+    /// Grow (or with `grow` false, shrink) a bottom-docked terminal by
+    /// `TERMINAL_ROW_STEP` rows of `row_height`, taking the height from the
+    /// file view. Near a limit it moves by the whole rows that still fit.
+    /// Returns whether anything changed.
+    pub fn step_terminal_height(&mut self, grow: bool, row_height: f32) -> bool {
+        if self.dock != TerminalDock::Bottom || row_height <= 0.0 {
+            return false;
+        }
+        let room = if grow {
+            self.file_view_height - FILE_VIEW_HEIGHT.start()
+        } else {
+            FILE_VIEW_HEIGHT.end() - self.file_view_height
+        };
+        let rows = ((room / row_height).floor().max(0.0) as usize).min(TERMINAL_ROW_STEP);
+        if rows == 0 {
+            return false;
+        }
+        let delta = rows as f32 * row_height;
+        self.file_view_height += if grow { -delta } else { delta };
+        true
+    }
+
+    /// Widen (or narrow) the sidebar by `SIDEBAR_STEP`. Returns whether
+    /// anything changed.
+    pub fn step_sidebar_width(&mut self, widen: bool) -> bool {
+        let before = self.sidebar_width;
+        let step = if widen { SIDEBAR_STEP } else { -SIDEBAR_STEP };
+        self.set_sidebar_width(before + step);
+        self.sidebar_width != before
+    }
+
+    pub fn toggle_dock(&mut self) {
+        self.dock = match self.dock {
+            TerminalDock::Bottom => TerminalDock::Right,
+            TerminalDock::Right => TerminalDock::Bottom,
+        };
+    }
+
+    pub fn toggle_terminal_maximized(&mut self) {
+        self.terminal_maximized = !self.terminal_maximized;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_steps_by_whole_rows_up_to_the_limits() {
+        let row = 19.6;
+        let mut layout = PanelLayout::default();
+        assert!(layout.step_terminal_height(true, row));
+        assert_eq!(layout.file_view_height, 384.0 - 2.0 * row);
+
+        // 30 px of room left: one whole row fits, then none
+        layout.file_view_height = 130.0;
+        assert!(layout.step_terminal_height(true, row));
+        assert_eq!(layout.file_view_height, 130.0 - row);
+        assert!(!layout.step_terminal_height(true, row));
+        assert_eq!(layout.file_view_height, 130.0 - row);
+
+        layout.file_view_height = 790.0;
+        assert!(!layout.step_terminal_height(false, row));
+        layout.file_view_height = 770.0;
+        assert!(layout.step_terminal_height(false, row));
+        assert_eq!(layout.file_view_height, 770.0 + row);
+
+        // A right-docked terminal is as tall as the central area
+        layout.toggle_dock();
+        assert!(!layout.step_terminal_height(true, row));
+    }
+
+    #[test]
+    fn test_sidebar_steps_stop_at_the_limits() {
+        let mut layout = PanelLayout::default();
+        assert!(layout.step_sidebar_width(true));
+        assert_eq!(layout.sidebar_width, 224.0 + SIDEBAR_STEP);
+
+        layout.set_sidebar_width(125.0);
+        assert!(layout.step_sidebar_width(false));
+        assert_eq!(layout.sidebar_width, 120.0);
+        assert!(!layout.step_sidebar_width(false));
+
+        layout.set_sidebar_width(1000.0);
+        assert_eq!(layout.sidebar_width, 500.0);
+        assert!(!layout.step_sidebar_width(true));
+    }
+
+    #[test]
+    fn test_dock_and_maximize_round_trips_keep_the_sizes() {
+        let mut layout = PanelLayout::default();
+        layout.set_file_view_height(250.0);
+        layout.set_file_view_width(700.0);
+        layout.set_sidebar_width(300.0);
+        let before = layout;
+
+        layout.toggle_dock();
+        assert_eq!(layout.dock, TerminalDock::Right);
+        layout.toggle_dock();
+        assert_eq!(layout, before);
+
+        layout.toggle_terminal_maximized();
+        assert!(layout.terminal_maximized);
+        layout.toggle_terminal_maximized();
+        assert_eq!(layout, before);
+    }
+}
@@ -0,0 +1,270 @@
+//! Git LFS files in the file view
+//!
+//! A file tracked by Git LFS (`filter=lfs` in `.gitattributes`) is stored in
+//! the repository as a small pointer naming its object by SHA-256 and size;
+//! the smudge filter puts the object in its place on checkout. Diffing the
+//! pointer in HEAD against the content in the working tree is noise, so such
+//! files get an info panel built from their pointers instead of a diff.
+//! Which changed files are LFS files is asked of `git check-attr` once per
+//! refresh for all of them, and kept with the worktree's changed files.
+
+use crate::git::{ChangeType, GitRepo};
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Pointers are small; a larger file is content, not a pointer
+pub const MAX_POINTER_SIZE: usize = 1024;
+
+/// First line of a pointer (the pre-release URL is still accepted by git-lfs)
+const VERSIONS: [&str; 2] = [
+    "version https://git-lfs.github.com/spec/v1",
+    "version https://hawser.github.com/spec/v1",
+];
+
+/// The object a pointer file names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// SHA-256 of the object, in hex
+    pub oid: String,
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// Parse pointer file content; None for anything else
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let text = std::str::from_utf8(bytes).ok()?;
+        let mut lines = text.lines();
+        if !VERSIONS.contains(&lines.next()?) {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => {
+                    let hex = value.strip_prefix("sha256:")?;
+                    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                        return None;
+                    }
+                    oid = Some(hex.to_ascii_lowercase());
+                }
+                "size" => size = Some(value.parse().ok()?),
+                // Extension keys (`ext-0-...`) and later additions
+                _ => {}
+            }
+        }
+        Some(Self {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// Read the pointer at `path`; None when the file holds content
+    pub fn read(path: &Path) -> Option<Self> {
+        let mut bytes = Vec::with_capacity(MAX_POINTER_SIZE);
+        std::fs::File::open(path)
+            .ok()?
+            .take(MAX_POINTER_SIZE as u64 + 1)
+            .read_to_end(&mut bytes)
+            .ok()?;
+        Self::parse(&bytes)
+    }
+
+    /// Where git-lfs keeps the object under the shared `git_dir`
+    pub fn object_path(&self, git_dir: &Path) -> PathBuf {
+        git_dir
+            .join("lfs")
+            .join("objects")
+            .join(&self.oid[..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+
+    /// "sha256:0123abcd…", short enough for the panel
+    pub fn short_oid(&self) -> String {
+        format!("sha256:{}…", &self.oid[..12])
+    }
+}
+
+/// A pointer and whether its object is in the local LFS store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsSide {
+    pub pointer: LfsPointer,
+    pub local: bool,
+}
+
+impl LfsSide {
+    fn new(pointer: LfsPointer, git_dir: &Path) -> Self {
+        let local = pointer.object_path(git_dir).is_file();
+        Self { pointer, local }
+    }
+}
+
+/// What the info panel shows of an LFS file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsInfo {
+    /// Pointer in HEAD (None for a file added since)
+    pub head: Option<LfsSide>,
+    /// Pointer in the working tree, None when the file holds the object's
+    /// content (or was deleted)
+    pub worktree: Option<LfsSide>,
+    /// Size of the working tree file (None when deleted)
+    pub worktree_size: Option<u64>,
+}
+
+impl LfsInfo {
+    /// Read the pointers of `full_path` (in HEAD at `head_path`, which
+    /// differs for a rename)
+    pub fn read(
+        repo: &GitRepo,
+        full_path: &Path,
+        head_path: &Path,
+        change_type: Option<ChangeType>,
+    ) -> Self {
+        let git_dir = repo.git_dir();
+        let head = match change_type {
+            Some(ChangeType::Added) => None,
+            _ => repo
+                .head_blob_id(head_path)
+                .filter(|id| {
+                    repo.blob_size(id)
+                        .is_some_and(|size| size <= MAX_POINTER_SIZE as u64)
+                })
+                .and_then(|id| repo.read_blob(&id).ok())
+                .and_then(|bytes| LfsPointer::parse(&bytes))
+                .map(|pointer| LfsSide::new(pointer, git_dir)),
+        };
+        let (worktree, worktree_size) = match change_type {
+            Some(ChangeType::Deleted) => (None, None),
+            _ => (
+                LfsPointer::read(full_path).map(|pointer| LfsSide::new(pointer, git_dir)),
+                std::fs::metadata(full_path).ok().map(|m| m.len()),
+            ),
+        };
+        Self {
+            head,
+            worktree,
+            worktree_size,
+        }
+    }
+}
+
+/// Paths `git check-attr -z filter` reports as `filter=lfs`
+pub fn parse_check_attr(output: &str) -> HashSet<PathBuf> {
+    let fields: Vec<&str> = output.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .filter(|entry| entry[1] == "filter" && entry[2] == "lfs")
+        .map(|entry| PathBuf::from(entry[0]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    fn pointer_text(size: u64) -> String {
+        format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n",
+            OID, size
+        )
+    }
+
+    #[test]
+    fn test_parse_pointer() {
+        let pointer = LfsPointer::parse(pointer_text(12345).as_bytes()).unwrap();
+        assert_eq!(pointer.oid, OID);
+        assert_eq!(pointer.size, 12345);
+        assert_eq!(pointer.short_oid(), "sha256:4d7a214614ab…");
+        assert_eq!(
+            pointer.object_path(Path::new("/repo/.git")),
+            Path::new("/repo/.git/lfs/objects/4d/7a").join(OID)
+        );
+
+        // Extension lines are skipped
+        let extended = pointer_text(1).replace("oid", "ext-0-foo sha256:00\noid");
+        assert!(LfsPointer::parse(extended.as_bytes()).is_some());
+
+        for bad in [
+            "just some text\n".to_string(),
+            pointer_text(1).replace("sha256:", "md5:"),
+            pointer_text(1).replace("size 1", "size many"),
+            pointer_text(1).replace(&OID[..4], "zz"),
+            pointer_text(1).replace("size 1\n", ""),
+            format!("{}{}", pointer_text(1), "x".repeat(MAX_POINTER_SIZE)),
+        ] {
+            assert_eq!(LfsPointer::parse(bad.as_bytes()), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_check_attr() {
+        let output = "a.psd\0filter\0lfs\0b.txt\0filter\0unspecified\0c d.bin\0filter\0lfs\0";
+        let paths = parse_check_attr(output);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(Path::new("a.psd")));
+        assert!(paths.contains(Path::new("c d.bin")));
+    }
+
+    #[test]
+    fn test_lfs_files_are_classified_from_attributes_and_pointers() {
+        let dir = tempfile::tempdir().unwrap();
+        let workdir = dir.path();
+        git(workdir, &["init", "-q", "-b", "main"]);
+        git(workdir, &["config", "user.name", "Test"]);
+        git(workdir, &["config", "user.email", "test@example.com"]);
+        // No git-lfs needed: the attribute alone marks the files, and
+        // without a configured filter driver the pointers are committed as is
+        std::fs::write(
+            workdir.join(".gitattributes"),
+            "*.psd filter=lfs diff=lfs merge=lfs -text\n",
+        )
+        .unwrap();
+        std::fs::write(workdir.join("art.psd"), pointer_text(2048)).unwrap();
+        std::fs::write(workdir.join("notes.txt"), "plain\n").unwrap();
+        git(workdir, &["add", "."]);
+        git(workdir, &["commit", "-q", "-m", "initial"]);
+
+        let repo = GitRepo::open(workdir).unwrap();
+        let lfs = repo
+            .lfs_paths([Path::new("art.psd"), Path::new("notes.txt")])
+            .unwrap();
+        assert_eq!(lfs, HashSet::from([PathBuf::from("art.psd")]));
+
+        // The working tree gets the real content; HEAD keeps the pointer
+        let art = workdir.join("art.psd");
+        std::fs::write(&art, vec![0u8; 2048]).unwrap();
+        let info = LfsInfo::read(
+            &repo,
+            &art,
+            Path::new("art.psd"),
+            Some(ChangeType::Modified),
+        );
+        let head = info.head.unwrap();
+        assert_eq!(head.pointer.size, 2048);
+        assert!(!head.local);
+        assert_eq!(info.worktree, None);
+        assert_eq!(info.worktree_size, Some(2048));
+
+        // Once the object is in the store it counts as local
+        let object = head.pointer.object_path(repo.git_dir());
+        std::fs::create_dir_all(object.parent().unwrap()).unwrap();
+        std::fs::write(&object, vec![0u8; 2048]).unwrap();
+        std::fs::write(&art, pointer_text(2048)).unwrap();
+        let info = LfsInfo::read(
+            &repo,
+            &art,
+            Path::new("art.psd"),
+            Some(ChangeType::Modified),
+        );
+        assert!(info.head.unwrap().local);
+        assert!(info.worktree.unwrap().local);
+    }
+}
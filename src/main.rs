@@ -3,37 +3,115 @@
 //! Each git worktree gets its own terminal session, making it easy to work on
 //! multiple branches simultaneously.
 
+mod activity;
+mod agents;
 mod app;
+mod app_log;
+mod attention;
+mod bell;
+mod branch_glob;
+mod branch_name;
+mod bulk_create;
+mod busy;
+mod checkpoints;
+mod code_blocks;
+mod command_history;
+mod compare;
+mod conflicts;
+mod control;
 mod dialog;
+mod diff_cache;
+mod diff_job;
+mod dirty;
+mod encoding;
+mod export;
+mod external_editor;
+mod file_cache;
+mod file_sync;
+mod fs_util;
 mod git;
+mod git_lock;
+mod git_queue;
+mod head_watch;
+mod health;
+mod hooks;
+mod layout;
+mod lfs;
+mod maintenance;
+mod merge_markers;
+mod multiplexer;
+mod notes;
+mod remote;
+mod repositories;
+mod resources;
+mod review;
+mod review_comments;
+mod scrollback;
+mod search;
 mod session;
+mod session_state;
+mod settings;
+mod shell_escape;
+mod sparse;
+mod stable_hash;
 mod template;
 mod terminal;
+#[cfg(test)]
+pub(crate) mod test_support;
 mod theme;
+mod transcript;
 mod ui;
+mod window_title;
+mod worktree_guard;
 
 use app::{
-    CloseFileView, NextSession, OpenFolder, PrevSession, Quit, RefreshAll, SashikiApp,
-    ToggleFileList, ToggleParallelMode, ToggleSidebar, ToggleVerifyTerminal,
+    MenuId, Quit, SashikiApp, SidebarActivate, SidebarDown, SidebarExit, SidebarToggleDir,
+    SidebarToggleReviewed, SidebarUp, SwitchToSession1, SwitchToSession2, SwitchToSession3,
+    SwitchToSession4, SwitchToSession5, SwitchToSession6, SwitchToSession7, SwitchToSession8,
+    SwitchToSession9,
 };
-use gpui::{App, AppContext, Application, Focusable, KeyBinding, Menu, MenuItem, WindowOptions};
+use dialog::ActiveDialog;
+use gpui::{App, AppContext, Application, Focusable, KeyBinding, Menu, WindowOptions};
+use std::path::Path;
 use terminal::TerminalView;
 
 fn main() {
+    match settings::CliArgs::parse(std::env::args().skip(1)) {
+        Ok(args) if args.export_state => {
+            std::process::exit(export_state(args.path.as_deref()));
+        }
+        Ok(args) => settings::init_cli_args(args),
+        Err(e) => {
+            eprintln!("sashiki: {}", e);
+            eprintln!("usage: sashiki [-c key=value]... [--export-state] [PATH]");
+            std::process::exit(2);
+        }
+    }
+
     Application::new().run(|app: &mut App| {
         // Global bindings must be registered BEFORE terminal bindings.
         // GPUI resolves ties (same context depth) by LIFO, so terminal-specific
         // bindings registered later will correctly override these when focused.
+        app.bind_keys(app::commands::key_bindings());
         app.bind_keys([
-            KeyBinding::new("ctrl-o", OpenFolder, None),
-            KeyBinding::new("ctrl-p", ToggleParallelMode, None),
-            KeyBinding::new("ctrl-tab", NextSession, None),
-            KeyBinding::new("ctrl-shift-tab", PrevSession, None),
-            KeyBinding::new("ctrl-b", ToggleSidebar, None),
-            KeyBinding::new("ctrl-t", ToggleVerifyTerminal, None),
-            KeyBinding::new("ctrl-e", ToggleFileList, None),
-            KeyBinding::new("ctrl-r", RefreshAll, None),
-            KeyBinding::new("escape", CloseFileView, None),
+            // Sidebar keyboard navigation (only while the sidebar has focus)
+            KeyBinding::new("up", SidebarUp, Some("Sidebar")),
+            KeyBinding::new("down", SidebarDown, Some("Sidebar")),
+            KeyBinding::new("enter", SidebarActivate, Some("Sidebar")),
+            KeyBinding::new("space", SidebarToggleDir, Some("Sidebar")),
+            // Takes "r" from type-ahead: marks the highlighted file reviewed
+            KeyBinding::new("r", SidebarToggleReviewed, Some("Sidebar")),
+            KeyBinding::new("escape", SidebarExit, Some("Sidebar")),
+            // The n-th session in sidebar order
+            KeyBinding::new("ctrl-1", SwitchToSession1, None),
+            KeyBinding::new("ctrl-2", SwitchToSession2, None),
+            KeyBinding::new("ctrl-3", SwitchToSession3, None),
+            KeyBinding::new("ctrl-4", SwitchToSession4, None),
+            KeyBinding::new("ctrl-5", SwitchToSession5, None),
+            KeyBinding::new("ctrl-6", SwitchToSession6, None),
+            KeyBinding::new("ctrl-7", SwitchToSession7, None),
+            KeyBinding::new("ctrl-8", SwitchToSession8, None),
+            KeyBinding::new("ctrl-9", SwitchToSession9, None),
         ]);
 
         app.on_action(|_: &Quit, cx: &mut App| {
@@ -43,25 +121,15 @@ fn main() {
         app.set_menus(vec![
             Menu {
                 name: "Sashiki".into(),
-                items: vec![
-                    MenuItem::action("Quit", Quit),
-                ],
+                items: app::commands::menu_items(MenuId::App),
             },
             Menu {
                 name: "File".into(),
-                items: vec![
-                    MenuItem::action("Open Folder", OpenFolder),
-                ],
+                items: app::commands::menu_items(MenuId::File),
             },
             Menu {
                 name: "View".into(),
-                items: vec![
-                    MenuItem::action("Toggle Sidebar", ToggleSidebar),
-                    MenuItem::action("Toggle File List", ToggleFileList),
-                    MenuItem::action("Toggle Parallel", ToggleParallelMode),
-                    MenuItem::separator(),
-                    MenuItem::action("Refresh All", RefreshAll),
-                ],
+                items: app::commands::menu_items(MenuId::View),
             },
         ]);
 
@@ -80,7 +148,26 @@ fn main() {
             if let Some(terminal) = view.active_terminal() {
                 let focus = terminal.read(cx).focus_handle(cx);
                 window.focus(&focus, cx);
+            } else if matches!(view.active_dialog, ActiveDialog::Open) {
+                window.focus(&view.open_dialog_focus, cx);
             }
+            view.start_control_server(window, cx);
         });
     });
 }
+
+/// `--export-state`: print the state of the repository at `path` (default:
+/// the current directory) as JSON, without a window or terminals. Returns
+/// the exit code.
+fn export_state(path: Option<&Path>) -> i32 {
+    match export::export_headless(path.unwrap_or(Path::new("."))) {
+        Ok(state) => {
+            println!("{}", export::to_json(&state));
+            0
+        }
+        Err(e) => {
+            eprintln!("sashiki: {}", e);
+            1
+        }
+    }
+}
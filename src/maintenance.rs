@@ -0,0 +1,379 @@
+//! Disk usage of worktrees and removal of build artifacts
+//!
+//! Worktrees pile up dependencies and build output (`node_modules/`,
+//! `target/`, ...) that take far more space than the sources. The maintenance
+//! dialog lists each worktree's size next to its git state and removes the
+//! artifacts matching the configured patterns (git config
+//! `sashiki.maintenance.cleanPattern`, multi-valued). Walks never follow
+//! symlinks and stop as soon as they are cancelled.
+
+use crate::git::GitRepo;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Artifact patterns used when `sashiki.maintenance.cleanPattern` is not set
+pub const DEFAULT_CLEAN_PATTERNS: &[&str] = &["node_modules/", "target/", ".venv/"];
+
+/// A build artifact pattern: a file or directory name, glob wildcards
+/// allowed; a trailing `/` matches directories only
+#[derive(Debug, Clone)]
+pub struct CleanPattern {
+    name: glob::Pattern,
+    dir_only: bool,
+}
+
+impl CleanPattern {
+    /// None for patterns that are not a single name (or name `.git`)
+    pub fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim();
+        let dir_only = pattern.ends_with('/');
+        let name = pattern.trim_end_matches('/');
+        if name.is_empty() || name.contains('/') || matches!(name, "." | ".." | ".git") {
+            return None;
+        }
+        Some(Self {
+            name: glob::Pattern::new(name).ok()?,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, name: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.name.matches(name)
+    }
+}
+
+/// Size in bytes of a file, or of all files below a directory, without
+/// following symlinks (None when cancelled). Unreadable entries are skipped.
+pub fn disk_usage(root: &Path, cancel: &AtomicBool) -> Option<u64> {
+    let Ok(metadata) = std::fs::symlink_metadata(root) else {
+        return Some(0);
+    };
+    if !metadata.is_dir() {
+        return Some(metadata.len());
+    }
+    let mut total = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            // DirEntry::metadata does not traverse symlinks
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += metadata.len();
+            }
+        }
+    }
+    Some(total)
+}
+
+/// Files and directories below `root` matching `patterns`, outermost only
+/// (a match is not searched further) and never inside `.git` or through a
+/// symlink. None when cancelled.
+pub fn find_artifacts(
+    root: &Path,
+    patterns: &[CleanPattern],
+    cancel: &AtomicBool,
+) -> Option<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if file_type.is_symlink() || name == ".git" {
+                continue;
+            }
+            let is_dir = file_type.is_dir();
+            if patterns.iter().any(|p| p.matches(&name, is_dir)) {
+                found.push(entry.path());
+            } else if is_dir {
+                pending.push(entry.path());
+            }
+        }
+    }
+    found.sort();
+    Some(found)
+}
+
+/// Delete artifacts found by `find_artifacts` for `root`.
+///
+/// Paths outside `root` or turned into symlinks since are refused. Keeps
+/// going after a failure; the error lists every path that could not be
+/// removed.
+pub fn remove_artifacts(root: &Path, paths: &[PathBuf]) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for path in paths {
+        let result = match std::fs::symlink_metadata(path) {
+            _ if path == root || !path.starts_with(root) => Err("outside the worktree".to_string()),
+            Ok(metadata) if metadata.file_type().is_symlink() => Err("is a symlink".to_string()),
+            Ok(metadata) if metadata.is_dir() => {
+                std::fs::remove_dir_all(path).map_err(|e| e.to_string())
+            }
+            Ok(_) => std::fs::remove_file(path).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            failures.push(format!("{}: {}", path.display(), e));
+        }
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to remove some build artifacts:\n{}",
+            failures.join("\n")
+        ))
+    }
+}
+
+/// Human-readable byte count ("1.5 GB")
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// A worktree as listed in the maintenance dialog
+#[derive(Debug, Clone)]
+pub struct WorktreeUsage {
+    pub name: String,
+    pub path: PathBuf,
+    pub branch: Option<String>,
+    pub is_main: bool,
+    /// Bytes on disk (None while measuring)
+    pub size: Option<u64>,
+    /// Commit time of HEAD (unix seconds)
+    pub last_commit: Option<i64>,
+    /// Commits (ahead of, behind) the main worktree's HEAD
+    pub ahead_behind: Option<(usize, usize)>,
+    /// Changed files in the working tree
+    pub changed: Option<usize>,
+    /// Whether the git state has been read
+    pub loaded: bool,
+}
+
+impl WorktreeUsage {
+    pub fn new(name: &str, path: &Path, branch: Option<&str>, is_main: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            branch: branch.map(str::to_string),
+            is_main,
+            size: None,
+            last_commit: None,
+            ahead_behind: None,
+            changed: None,
+            loaded: false,
+        }
+    }
+
+    /// Read the last commit, ahead/behind counts against `main_head` and the
+    /// number of changed files (blocking; fields stay None when git fails)
+    pub fn load_git_state(&mut self, main_head: Option<&str>) {
+        self.loaded = true;
+        let Ok(repo) = GitRepo::open(&self.path) else {
+            return;
+        };
+        self.last_commit = repo.last_commit_time();
+        self.ahead_behind = match main_head {
+            Some(head) if !self.is_main => repo.ahead_behind(head).ok(),
+            _ => None,
+        };
+        self.changed = repo.get_changed_files().ok().map(|files| files.len());
+    }
+}
+
+/// State of the maintenance dialog. Sizes are cached across openings and
+/// dropped for a worktree once it is cleaned.
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    pub rows: Vec<WorktreeUsage>,
+    pub selected: HashSet<PathBuf>,
+    /// Worktrees still to delete, one confirmation after the other (None
+    /// outside a bulk delete)
+    pub delete_queue: Option<Vec<PathBuf>>,
+    /// A scan or cleanup is running in the background
+    pub busy: bool,
+    /// Progress or outcome of the last action, shown in the footer
+    pub status: Option<String>,
+    sizes: HashMap<PathBuf, u64>,
+    /// Cancels the running scan when set
+    cancel: Arc<AtomicBool>,
+}
+
+impl Maintenance {
+    /// List `rows` with their cached sizes, keeping the selection of those
+    /// still present
+    pub fn set_rows(&mut self, mut rows: Vec<WorktreeUsage>) {
+        for row in &mut rows {
+            row.size = self.sizes.get(&row.path).copied();
+        }
+        self.selected
+            .retain(|path| rows.iter().any(|row| &row.path == path));
+        self.rows = rows;
+    }
+
+    /// Take over a row measured in the background
+    pub fn update_row(&mut self, row: WorktreeUsage) {
+        if let Some(size) = row.size {
+            self.sizes.insert(row.path.clone(), size);
+        }
+        if let Some(existing) = self.rows.iter_mut().find(|r| r.path == row.path) {
+            *existing = row;
+        }
+    }
+
+    /// Forget the size of `path` so the next scan measures it again
+    pub fn invalidate_size(&mut self, path: &Path) {
+        self.sizes.remove(path);
+        if let Some(row) = self.rows.iter_mut().find(|r| r.path == path) {
+            row.size = None;
+        }
+    }
+
+    /// Forget all sizes and git state so the next scan reads them again
+    pub fn reset_measurements(&mut self) {
+        self.sizes.clear();
+        for row in &mut self.rows {
+            row.size = None;
+            row.loaded = false;
+        }
+    }
+
+    /// Cancel the running scan and hand out the flag for a new one
+    pub fn start_scan(&mut self) -> Arc<AtomicBool> {
+        self.cancel_scan();
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.cancel.clone()
+    }
+
+    pub fn cancel_scan(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn toggle_selected(&mut self, path: &Path) {
+        if !self.selected.remove(path) {
+            self.selected.insert(path.to_path_buf());
+        }
+    }
+
+    /// Selected worktrees in list order
+    pub fn selected_rows(&self) -> Vec<&WorktreeUsage> {
+        self.rows
+            .iter()
+            .filter(|r| self.selected.contains(&r.path))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(list: &[&str]) -> Vec<CleanPattern> {
+        list.iter().filter_map(|p| CleanPattern::parse(p)).collect()
+    }
+
+    #[test]
+    fn test_clean_pattern_parse_and_match() {
+        let dir = CleanPattern::parse("target/").unwrap();
+        assert!(dir.matches("target", true));
+        assert!(!dir.matches("target", false));
+        let any = CleanPattern::parse("*.pyc").unwrap();
+        assert!(any.matches("mod.pyc", false));
+        assert!(!any.matches("mod.py", false));
+
+        assert!(CleanPattern::parse("").is_none());
+        assert!(CleanPattern::parse(".git/").is_none());
+        assert!(CleanPattern::parse("build/out").is_none());
+    }
+
+    #[test]
+    fn test_find_artifacts_stops_at_matches_and_skips_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for sub in [
+            "node_modules/pkg/node_modules",
+            "web/node_modules",
+            ".git/node_modules",
+            "src/target.rs.d",
+        ] {
+            std::fs::create_dir_all(root.join(sub)).unwrap();
+        }
+        std::fs::write(root.join("src/target"), "a file, not a directory").unwrap();
+        let cancel = AtomicBool::new(false);
+
+        let found = find_artifacts(root, &patterns(DEFAULT_CLEAN_PATTERNS), &cancel).unwrap();
+        assert_eq!(
+            found,
+            vec![root.join("node_modules"), root.join("web/node_modules")]
+        );
+
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(find_artifacts(root, &patterns(&["target/"]), &cancel), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_size_and_removal_do_not_follow_symlinks() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::create_dir(outside.path().join("node_modules")).unwrap();
+        std::fs::write(outside.path().join("node_modules/big"), vec![0u8; 4096]).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("target")).unwrap();
+        std::fs::write(root.join("target/out"), vec![0u8; 100]).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("node_modules"),
+            root.join("node_modules"),
+        )
+        .unwrap();
+        let cancel = AtomicBool::new(false);
+
+        assert!(disk_usage(root, &cancel).unwrap() < 4096);
+        let found = find_artifacts(root, &patterns(DEFAULT_CLEAN_PATTERNS), &cancel).unwrap();
+        assert_eq!(found, vec![root.join("target")]);
+
+        remove_artifacts(root, &found).unwrap();
+        assert!(!root.join("target").exists());
+        assert!(remove_artifacts(root, &[outside.path().join("node_modules")]).is_err());
+        assert!(outside.path().join("node_modules/big").exists());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
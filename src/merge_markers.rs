@@ -0,0 +1,375 @@
+//! Conflict markers left in a file by a merge, rebase, pull or stash pop
+//!
+//! A conflicted file holds hunks of the form
+//!
+//! ```text
+//! <<<<<<< ours
+//! our lines
+//! ||||||| base          (diff3 and zdiff3 styles only)
+//! the common ancestor's lines
+//! =======
+//! their lines
+//! >>>>>>> theirs
+//! ```
+//!
+//! between runs of plain text. A file is parsed into those segments, hunks
+//! are resolved one at a time, and the text is put back together with the
+//! lines (and line endings) outside the resolved hunks untouched. Markers
+//! that do not pair up are an error, so the file is never rewritten from a
+//! guess.
+
+/// Length of git's conflict markers (`conflict-marker-size` is not read)
+const MARKER_SIZE: usize = 7;
+
+/// Markers that do not form well-formed conflict hunks (lines are 1-based)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MarkerError {
+    #[error("line {line} opens a conflict inside the one opened at line {outer}")]
+    Nested { line: usize, outer: usize },
+    #[error("line {line} has a conflict marker out of place")]
+    Unexpected { line: usize },
+    #[error("the conflict opened at line {line} never ends")]
+    Unterminated { line: usize },
+}
+
+/// One conflict: both sides (and the base, in diff3 style), each line with
+/// its line ending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictHunk {
+    /// Text after `<<<<<<<` (usually `HEAD` or a branch)
+    pub ours_label: String,
+    pub ours: Vec<String>,
+    /// Text after `|||||||` and the common ancestor's lines (diff3 style)
+    pub base: Option<(String, Vec<String>)>,
+    pub theirs: Vec<String>,
+    /// Text after `>>>>>>>`
+    pub theirs_label: String,
+    /// The hunk's marker lines, as found (kept for writing it back)
+    markers: Vec<String>,
+}
+
+impl ConflictHunk {
+    /// The hunk's lines as they stand in the file, markers included
+    fn lines(&self) -> Vec<&str> {
+        let mut lines = vec![self.markers[0].as_str()];
+        lines.extend(self.ours.iter().map(String::as_str));
+        let mut marker = 1;
+        if let Some((_, base)) = &self.base {
+            lines.push(&self.markers[marker]);
+            lines.extend(base.iter().map(String::as_str));
+            marker += 1;
+        }
+        lines.push(&self.markers[marker]);
+        lines.extend(self.theirs.iter().map(String::as_str));
+        lines.push(&self.markers[marker + 1]);
+        lines
+    }
+
+    /// Lines the hunk spans in the file, markers included
+    pub fn line_count(&self) -> usize {
+        self.lines().len()
+    }
+}
+
+/// How a conflict is resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    /// Our lines followed by theirs
+    Both,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Lines outside any conflict, with their line endings
+    Text(Vec<String>),
+    Conflict(ConflictHunk),
+}
+
+/// A file split into plain text and conflict hunks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictFile {
+    segments: Vec<Segment>,
+}
+
+/// Where a parser is inside a hunk
+enum Part {
+    Ours,
+    Base,
+    Theirs,
+}
+
+/// The label after a marker of `marker` characters, if `line` is one. The
+/// run must be exactly the marker size and be followed by a space or the
+/// end of the line.
+fn marker_label(line: &str, marker: char) -> Option<&str> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let rest = line.strip_prefix(&marker.to_string().repeat(MARKER_SIZE))?;
+    if rest.is_empty() {
+        Some("")
+    } else {
+        rest.strip_prefix(' ').map(str::trim)
+    }
+}
+
+/// Whether `line` is the `=======` between the sides of a hunk
+fn is_separator(line: &str) -> bool {
+    let line = line.trim_end_matches(['\n', '\r']);
+    line.len() == MARKER_SIZE && line.chars().all(|c| c == '=')
+}
+
+impl ConflictFile {
+    /// Split `text` into plain text and conflict hunks. Separator and closing
+    /// markers outside a hunk are plain text (a Markdown heading underline
+    /// is `=======` too); markers that cannot pair up inside one are an error.
+    pub fn parse(text: &str) -> Result<Self, MarkerError> {
+        let mut segments = Vec::new();
+        let mut plain = Vec::new();
+        // The hunk being read, the line that opened it and where in it we are
+        let mut open: Option<(ConflictHunk, usize, Part)> = None;
+
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let number = index + 1;
+            let Some((hunk, start, part)) = &mut open else {
+                if let Some(label) = marker_label(line, '<') {
+                    if !plain.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut plain)));
+                    }
+                    let hunk = ConflictHunk {
+                        ours_label: label.to_string(),
+                        ours: Vec::new(),
+                        base: None,
+                        theirs: Vec::new(),
+                        theirs_label: String::new(),
+                        markers: vec![line.to_string()],
+                    };
+                    open = Some((hunk, number, Part::Ours));
+                } else {
+                    plain.push(line.to_string());
+                }
+                continue;
+            };
+
+            if marker_label(line, '<').is_some() {
+                return Err(MarkerError::Nested {
+                    line: number,
+                    outer: *start,
+                });
+            }
+            match part {
+                Part::Ours | Part::Base => {
+                    if let Some(label) = marker_label(line, '|') {
+                        if hunk.base.is_some() {
+                            return Err(MarkerError::Unexpected { line: number });
+                        }
+                        hunk.base = Some((label.to_string(), Vec::new()));
+                        hunk.markers.push(line.to_string());
+                        *part = Part::Base;
+                    } else if is_separator(line) {
+                        hunk.markers.push(line.to_string());
+                        *part = Part::Theirs;
+                    } else if marker_label(line, '>').is_some() {
+                        return Err(MarkerError::Unexpected { line: number });
+                    } else if let Some((_, base)) = &mut hunk.base {
+                        // Past `|||||||`, lines belong to the base
+                        base.push(line.to_string());
+                    } else {
+                        hunk.ours.push(line.to_string());
+                    }
+                }
+                Part::Theirs => {
+                    if let Some(label) = marker_label(line, '>') {
+                        hunk.theirs_label = label.to_string();
+                        hunk.markers.push(line.to_string());
+                        if let Some((hunk, _, _)) = open.take() {
+                            segments.push(Segment::Conflict(hunk));
+                        }
+                    } else if marker_label(line, '|').is_some() || is_separator(line) {
+                        return Err(MarkerError::Unexpected { line: number });
+                    } else {
+                        hunk.theirs.push(line.to_string());
+                    }
+                }
+            }
+        }
+
+        if let Some((_, start, _)) = open {
+            return Err(MarkerError::Unterminated { line: start });
+        }
+        if !plain.is_empty() {
+            segments.push(Segment::Text(plain));
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Number of conflicts left
+    pub fn conflict_count(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Conflict(_)))
+            .count()
+    }
+
+    /// Replace conflict `index` (among those left) with the lines
+    /// `resolution` keeps. Returns false when there is no such conflict.
+    pub fn resolve(&mut self, index: usize, resolution: Resolution) -> bool {
+        let Some(position) = self
+            .segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| matches!(segment, Segment::Conflict(_)))
+            .nth(index)
+            .map(|(position, _)| position)
+        else {
+            return false;
+        };
+        let Segment::Conflict(hunk) = &mut self.segments[position] else {
+            return false;
+        };
+        let kept = match resolution {
+            Resolution::Ours => std::mem::take(&mut hunk.ours),
+            Resolution::Theirs => std::mem::take(&mut hunk.theirs),
+            Resolution::Both => {
+                let mut both = std::mem::take(&mut hunk.ours);
+                both.append(&mut hunk.theirs);
+                both
+            }
+        };
+        self.segments[position] = Segment::Text(kept);
+        true
+    }
+
+    /// The file's text with the conflicts left still marked
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(lines) => lines.iter().for_each(|line| text.push_str(line)),
+                Segment::Conflict(hunk) => hunk.lines().into_iter().for_each(|l| text.push_str(l)),
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREE_CONFLICTS: &str = "\
+fn main() {
+<<<<<<< HEAD
+    let a = 1;
+=======
+    let a = 2;
+>>>>>>> feature
+    let b = 0;
+<<<<<<< HEAD
+    log(\"ours\");
+=======
+    log(\"theirs\");
+>>>>>>> feature
+    run();
+<<<<<<< HEAD
+    done();
+=======
+    finish();
+>>>>>>> feature
+}
+";
+
+    #[test]
+    fn test_parse_keeps_text_around_conflicts() {
+        let file = ConflictFile::parse(THREE_CONFLICTS).unwrap();
+        assert_eq!(file.conflict_count(), 3);
+        assert_eq!(file.segments().len(), 7);
+        let Segment::Conflict(first) = &file.segments()[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(first.ours_label, "HEAD");
+        assert_eq!(first.theirs_label, "feature");
+        assert_eq!(first.ours, vec!["    let a = 1;\n"]);
+        assert_eq!(first.theirs, vec!["    let a = 2;\n"]);
+        assert_eq!(first.base, None);
+        assert_eq!(first.line_count(), 5);
+        // Unresolved, the file is written back as it was read
+        assert_eq!(file.text(), THREE_CONFLICTS);
+    }
+
+    #[test]
+    fn test_parse_diff3_hunk_with_base() {
+        let text = "a\r\n<<<<<<< ours\r\nx = 1\r\n||||||| merged common ancestors\r\n\
+                    x = 0\r\n=======\r\nx = 2\r\ny = 3\r\n>>>>>>> theirs\r\nz";
+        let mut file = ConflictFile::parse(text).unwrap();
+        let Segment::Conflict(hunk) = &file.segments()[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(
+            hunk.base,
+            Some((
+                "merged common ancestors".to_string(),
+                vec!["x = 0\r\n".to_string()]
+            ))
+        );
+        assert_eq!(hunk.theirs, vec!["x = 2\r\n", "y = 3\r\n"]);
+        assert_eq!(file.text(), text);
+
+        // The base is dropped whichever side is kept
+        assert!(file.resolve(0, Resolution::Theirs));
+        assert_eq!(file.text(), "a\r\nx = 2\r\ny = 3\r\nz");
+    }
+
+    #[test]
+    fn test_resolve_three_conflicts_in_mixed_directions() {
+        let mut file = ConflictFile::parse(THREE_CONFLICTS).unwrap();
+        // Indices count the conflicts still left
+        assert!(file.resolve(1, Resolution::Theirs));
+        assert!(file.resolve(1, Resolution::Both));
+        assert!(file.resolve(0, Resolution::Ours));
+        assert!(!file.resolve(0, Resolution::Ours));
+        assert_eq!(file.conflict_count(), 0);
+        assert_eq!(
+            file.text(),
+            "fn main() {\n    let a = 1;\n    let b = 0;\n    log(\"theirs\");\n    run();\n\
+             \x20   done();\n    finish();\n}\n"
+        );
+        // Nothing left to parse as a conflict
+        assert_eq!(
+            ConflictFile::parse(&file.text()).unwrap().conflict_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_stray_markers_outside_conflicts_are_text() {
+        let text = "Title\n=======\n>>>>>>> not a conflict\n<<<<<<<< eight\n";
+        let file = ConflictFile::parse(text).unwrap();
+        assert_eq!(file.conflict_count(), 0);
+        assert_eq!(file.text(), text);
+    }
+
+    #[test]
+    fn test_malformed_markers_are_errors() {
+        assert_eq!(
+            ConflictFile::parse("<<<<<<< a\nx\n<<<<<<< b\ny\n=======\nz\n>>>>>>> c\n"),
+            Err(MarkerError::Nested { line: 3, outer: 1 })
+        );
+        assert_eq!(
+            ConflictFile::parse("<<<<<<< a\nx\n>>>>>>> b\n"),
+            Err(MarkerError::Unexpected { line: 3 })
+        );
+        assert_eq!(
+            ConflictFile::parse("<<<<<<< a\nx\n=======\ny\n=======\n>>>>>>> b\n"),
+            Err(MarkerError::Unexpected { line: 5 })
+        );
+        assert_eq!(
+            ConflictFile::parse("ok\n<<<<<<< a\nx\n=======\ny\n"),
+            Err(MarkerError::Unterminated { line: 2 })
+        );
+    }
+}
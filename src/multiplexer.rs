@@ -0,0 +1,287 @@
+//! Terminals run inside tmux so their programs outlive Sashiki
+//!
+//! With `sashiki.terminal.multiplexer` set to `tmux`, terminal `n` of a
+//! session runs `tmux new-session -A -s sashiki-<repo>-<hash>-<worktree>-<n>`,
+//! where `<hash>` tells apart repositories of the same name in different
+//! directories (it hashes the canonical path of the working tree). The
+//! shell belongs to the tmux server, so an agent keeps running when Sashiki
+//! is closed or crashes: the PTY going away only detaches the client. When
+//! the repository is opened again, the tmux sessions named after it are
+//! listed and attached to the sessions of their worktrees (`-A` attaches to
+//! a session that exists). tmux follows the size of the PTY its client runs
+//! in, so a resize reaches it the way it reaches a bare shell.
+//!
+//! Without tmux installed, terminals are bare shells as before.
+
+use crate::shell_escape::Shell;
+use crate::stable_hash;
+use std::path::Path;
+use std::process::Command;
+
+/// Start of the names of the tmux sessions Sashiki runs terminals in
+const PREFIX: &str = "sashiki";
+
+/// What keeps terminals running (`sashiki.terminal.multiplexer`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexer {
+    Tmux,
+}
+
+impl Multiplexer {
+    /// Parse a setting value; anything else (such as `none`) is no multiplexer
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "tmux" => Some(Self::Tmux),
+            _ => None,
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Tmux => "tmux",
+        }
+    }
+
+    /// Whether the multiplexer can be run
+    pub fn is_available(self) -> bool {
+        Command::new(self.program())
+            .arg("-V")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+}
+
+/// Names of the tmux sessions of one repository's terminals
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxNames {
+    /// Repository name, sanitized, and the hash of its working tree's path
+    repo: String,
+}
+
+impl TmuxNames {
+    /// Names for the repository whose working tree is `workdir`
+    pub fn new(workdir: &Path) -> Self {
+        let workdir = workdir
+            .canonicalize()
+            .unwrap_or_else(|_| workdir.to_path_buf());
+        let name = workdir
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let hash = stable_hash::fnv1a_32(workdir.to_string_lossy().as_bytes());
+        Self {
+            repo: format!("{}-{:08x}", sanitize(&name), hash),
+        }
+    }
+
+    /// tmux session of terminal `number` of the worktree `worktree_name`
+    pub fn session(&self, worktree_name: &str, number: usize) -> String {
+        format!("{}{}", self.worktree_prefix(worktree_name), number)
+    }
+
+    /// Numbers of the terminals of `worktree_name` among the tmux `sessions`
+    /// listed, ascending
+    pub fn terminals_of(&self, sessions: &[String], worktree_name: &str) -> Vec<usize> {
+        let prefix = self.worktree_prefix(worktree_name);
+        let mut numbers: Vec<usize> = sessions
+            .iter()
+            .filter_map(|name| name.strip_prefix(&prefix)?.parse().ok())
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+
+    fn worktree_prefix(&self, worktree_name: &str) -> String {
+        format!("{}-{}-{}-", PREFIX, self.repo, sanitize(worktree_name))
+    }
+}
+
+/// `name` as part of a tmux session name: tmux rejects `.` and `:`, and
+/// slashes of branch-like names would read as paths, so everything but
+/// letters, digits, `-` and `_` becomes `_`
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Command line of a terminal kept in the tmux session `name`, running
+/// `shell` (None = tmux's default shell) with `env` set when the session is
+/// new. The tmux server starts the program, so the environment goes through
+/// `-e` rather than the client's, each `KEY=value` quoted as one word (see
+/// `shell_escape::split_words`).
+pub fn command(name: &str, shell: Option<&str>, env: &[(String, String)]) -> String {
+    let mut command = format!("tmux new-session -A -s {}", name);
+    for (key, value) in env {
+        let assignment = format!("{}={}", key, value);
+        command.push_str(&format!(" -e {}", Shell::Posix.escape(&assignment)));
+    }
+    if let Some(shell) = shell {
+        command.push(' ');
+        command.push_str(shell);
+    }
+    command
+}
+
+/// Names in `tmux ls -F '#{session_name}'` output that Sashiki started
+pub fn parse_session_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|name| {
+            name.strip_prefix(PREFIX)
+                .is_some_and(|rest| rest.starts_with('-'))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// tmux sessions Sashiki started that are still running (none when no tmux
+/// server runs)
+pub fn list_sessions() -> Vec<String> {
+    Command::new("tmux")
+        .args(["ls", "-F", "#{session_name}"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| parse_session_list(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+}
+
+/// Detach the clients of the tmux session `name`; its programs keep running
+pub fn detach(name: &str) {
+    let _ = Command::new("tmux")
+        .args(["detach-client", "-s", &format!("={}", name)])
+        .output();
+}
+
+/// End the tmux session `name` along with the programs running in it
+pub fn kill(name: &str) {
+    let _ = Command::new("tmux")
+        .args(["kill-session", "-t", &format!("={}", name)])
+        .output();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_setting() {
+        assert_eq!(Multiplexer::parse("tmux"), Some(Multiplexer::Tmux));
+        assert_eq!(Multiplexer::parse(" TMUX\n"), Some(Multiplexer::Tmux));
+        assert_eq!(Multiplexer::parse("none"), None);
+        assert_eq!(Multiplexer::parse(""), None);
+    }
+
+    #[test]
+    fn test_session_names_are_sanitized() {
+        let names = TmuxNames::new(Path::new("/nonexistent/my.repo"));
+        let hash = stable_hash::fnv1a_32(b"/nonexistent/my.repo");
+        assert_eq!(
+            names.session("feature/login", 0),
+            format!("sashiki-my_repo-{:08x}-feature_login-0", hash)
+        );
+        assert_eq!(
+            names.session("fix: a b", 2),
+            format!("sashiki-my_repo-{:08x}-fix__a_b-2", hash)
+        );
+    }
+
+    #[test]
+    fn test_same_named_repositories_get_their_own_sessions() {
+        let a = TmuxNames::new(Path::new("/nonexistent/a/repo"));
+        let b = TmuxNames::new(Path::new("/nonexistent/b/repo"));
+        assert_ne!(a.session("main", 0), b.session("main", 0));
+        assert!(a.session("main", 0).starts_with("sashiki-repo-"));
+
+        // The same directory reached through another path is the same one
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        assert_eq!(
+            TmuxNames::new(&repo),
+            TmuxNames::new(&dir.path().join("repo/../repo"))
+        );
+    }
+
+    #[test]
+    fn test_command_attaches_or_creates() {
+        assert_eq!(
+            command("sashiki-r-main-0", None, &[]),
+            "tmux new-session -A -s sashiki-r-main-0"
+        );
+        assert_eq!(
+            command("sashiki-r-main-0", Some("fish -l"), &[]),
+            "tmux new-session -A -s sashiki-r-main-0 fish -l"
+        );
+        let env = [("EDITOR".to_string(), "vi".to_string())];
+        assert_eq!(
+            command("sashiki-r-main-1", Some("aider"), &env),
+            "tmux new-session -A -s sashiki-r-main-1 -e EDITOR=vi aider"
+        );
+
+        let env = [
+            ("EDITOR".to_string(), "code --wait".to_string()),
+            ("PROMPT".to_string(), "it's $(done)".to_string()),
+        ];
+        let line = command("sashiki-r-main-2", Some("aider"), &env);
+        assert_eq!(
+            line,
+            concat!(
+                "tmux new-session -A -s sashiki-r-main-2",
+                " -e 'EDITOR=code --wait'",
+                r" -e 'PROMPT=it'\''s $(done)' aider",
+            )
+        );
+        assert_eq!(
+            crate::shell_escape::split_words(&line),
+            [
+                "tmux",
+                "new-session",
+                "-A",
+                "-s",
+                "sashiki-r-main-2",
+                "-e",
+                "EDITOR=code --wait",
+                "-e",
+                "PROMPT=it's $(done)",
+                "aider",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_session_list() {
+        let output = "sashiki-repo-main-0\n0\nwork\nsashiki-repo-feature_x-1\nsashikix-1\n\n";
+        assert_eq!(
+            parse_session_list(output),
+            vec!["sashiki-repo-main-0", "sashiki-repo-feature_x-1"]
+        );
+        assert!(parse_session_list("").is_empty());
+    }
+
+    #[test]
+    fn test_terminals_of_a_worktree() {
+        let names = TmuxNames::new(Path::new("/nonexistent/repo"));
+        let other = TmuxNames::new(Path::new("/nonexistent/other/repo"));
+        let listed = vec![
+            names.session("feat", 2),
+            names.session("feat", 0),
+            names.session("feat_x", 0),
+            other.session("feat", 1),
+            names.session("main", 0),
+        ];
+        assert_eq!(names.terminals_of(&listed, "feat"), vec![0, 2]);
+        assert_eq!(names.terminals_of(&listed, "feat/x"), vec![0]);
+        assert_eq!(names.terminals_of(&listed, "main"), vec![0]);
+        assert!(names.terminals_of(&listed, "docs").is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+//! Per-session scratchpad notes
+//!
+//! Each session has a markdown note kept in
+//! `.git/sashiki/notes/<worktree-name>.md`, so it never shows up as a change
+//! in any working tree. The notes panel saves it `NOTE_AUTOSAVE_DELAY` after
+//! the last keystroke (at least every `NOTE_AUTOSAVE_MAX_DELAY` while typing
+//! goes on), replacing it whole (see `fs_util::write_atomic`) so a crash
+//! never leaves half a note. A note emptied to whitespace is deleted.
+//!
+//! While the panel is not focused the note is shown as a simple markdown
+//! preview (see `preview_lines`).
+
+use crate::fs_util::{file_stem, write_atomic};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Quiet time after the last edit before the note is saved
+pub const NOTE_AUTOSAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest an edit waits to be saved while typing never pauses
+pub const NOTE_AUTOSAVE_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Directory of all notes inside the shared `.git` directory
+pub fn notes_dir(git_dir: &Path) -> PathBuf {
+    git_dir.join("sashiki").join("notes")
+}
+
+/// File of the note of the session named `worktree_name`
+pub fn note_path(git_dir: &Path, worktree_name: &str) -> PathBuf {
+    notes_dir(git_dir).join(format!("{}.md", file_stem(worktree_name)))
+}
+
+/// Text of the note at `path` (empty when there is none)
+pub fn read_note(path: &Path) -> io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
+
+/// Whether a note with more than whitespace exists at `path`
+pub fn has_note(path: &Path) -> bool {
+    read_note(path).is_ok_and(|text| !text.trim().is_empty())
+}
+
+/// Save `text` as the note at `path`, or delete the note when `text` is only
+/// whitespace
+pub fn write_note(path: &Path, text: &str) -> io::Result<()> {
+    if text.trim().is_empty() {
+        return remove_note(path);
+    }
+    write_atomic(path, text.as_bytes())
+}
+
+/// Delete the note at `path` (nothing to do when there is none)
+pub fn remove_note(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Decides when an edited note is saved
+#[derive(Debug, Default)]
+pub struct AutosaveDebouncer {
+    /// First and last edit since the last save
+    pending: Option<(Instant, Instant)>,
+}
+
+impl AutosaveDebouncer {
+    /// Record an edit made at `now`
+    pub fn changed(&mut self, now: Instant) {
+        let first = self.pending.map_or(now, |(first, _)| first);
+        self.pending = Some((first, now));
+    }
+
+    /// Whether the edits should be saved at `now`: `NOTE_AUTOSAVE_DELAY`
+    /// after the last one, or `NOTE_AUTOSAVE_MAX_DELAY` after the first.
+    /// Returning true counts as saving.
+    pub fn due(&mut self, now: Instant) -> bool {
+        let Some((first, last)) = self.pending else {
+            return false;
+        };
+        if now.duration_since(last) < NOTE_AUTOSAVE_DELAY
+            && now.duration_since(first) < NOTE_AUTOSAVE_MAX_DELAY
+        {
+            return false;
+        }
+        self.pending = None;
+        true
+    }
+
+    /// Whether edits are waiting to be saved
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Forget the waiting edits (they were saved another way, or discarded);
+    /// true if there were any
+    pub fn take(&mut self) -> bool {
+        self.pending.take().is_some()
+    }
+}
+
+/// A line of the note as the preview shows it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewLine {
+    /// `#` to `######`
+    Heading {
+        level: usize,
+        text: String,
+    },
+    /// `-`, `*` or `+` item, indented by `depth` levels
+    Bullet {
+        depth: usize,
+        text: String,
+    },
+    /// `- [ ]` or `- [x]` item
+    Task {
+        depth: usize,
+        done: bool,
+        text: String,
+    },
+    /// `>` line
+    Quote(String),
+    /// Line inside a ``` fence (the fences themselves are not shown)
+    Code(String),
+    /// `---`, `***` or `___`
+    Rule,
+    Text(String),
+    Blank,
+}
+
+/// Classify each line of `text` for the preview. Inline markup (emphasis,
+/// links) is left as typed.
+pub fn preview_lines(text: &str) -> Vec<PreviewLine> {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(PreviewLine::Code(line.to_string()));
+            continue;
+        }
+        lines.push(preview_line(line));
+    }
+    lines
+}
+
+fn preview_line(line: &str) -> PreviewLine {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+        return PreviewLine::Blank;
+    }
+    let marks: Vec<char> = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+    if marks.len() >= 3
+        && matches!(marks[0], '-' | '*' | '_')
+        && marks.iter().all(|&c| c == marks[0])
+    {
+        return PreviewLine::Rule;
+    }
+
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes)
+        && let Some(text) = trimmed[hashes..].strip_prefix(' ')
+    {
+        return PreviewLine::Heading {
+            level: hashes,
+            text: text.trim().to_string(),
+        };
+    }
+
+    if let Some(text) = trimmed.strip_prefix('>') {
+        return PreviewLine::Quote(text.trim_start().to_string());
+    }
+
+    let indent = line.len() - trimmed.len();
+    let depth = indent / 2;
+    if let Some(item) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| trimmed.strip_prefix(marker))
+    {
+        for (marker, done) in [("[ ] ", false), ("[x] ", true), ("[X] ", true)] {
+            if let Some(text) = item.strip_prefix(marker) {
+                return PreviewLine::Task {
+                    depth,
+                    done,
+                    text: text.to_string(),
+                };
+            }
+        }
+        return PreviewLine::Bullet {
+            depth,
+            text: item.to_string(),
+        };
+    }
+
+    PreviewLine::Text(line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_path_of_names_with_slashes() {
+        let git_dir = Path::new("/repo/.git");
+        let dir = notes_dir(git_dir);
+        assert_eq!(dir, Path::new("/repo/.git/sashiki/notes"));
+
+        let nested = note_path(git_dir, "feature/login/form");
+        assert_eq!(nested, dir.join("feature%2Flogin%2Fform.md"));
+        assert_eq!(nested.parent(), Some(dir.as_path()));
+        // Names that only differ in the separator keep their own notes
+        assert_ne!(nested, note_path(git_dir, "feature-login-form"));
+        assert_ne!(
+            note_path(git_dir, "a%2Fb"),
+            note_path(git_dir, "a/b"),
+            "a literal %2F is not a slash"
+        );
+        assert_eq!(note_path(git_dir, "win\\name"), dir.join("win%5Cname.md"));
+
+        // Nothing escapes the notes directory
+        for name in ["..", "../x", "/abs", "./x"] {
+            assert_eq!(note_path(git_dir, name).parent(), Some(dir.as_path()));
+        }
+    }
+
+    #[test]
+    fn test_autosave_waits_for_a_pause() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut autosave = AutosaveDebouncer::default();
+        assert!(!autosave.due(ms(0)));
+
+        // Keystrokes 200ms apart keep pushing the save back
+        for t in [0, 200, 400, 600] {
+            autosave.changed(ms(t));
+            assert!(!autosave.due(ms(t + 100)));
+        }
+        assert!(autosave.is_pending());
+        assert!(!autosave.due(ms(1099)));
+        assert!(autosave.due(ms(1100)));
+        assert!(!autosave.is_pending());
+        assert!(!autosave.due(ms(5000)));
+    }
+
+    #[test]
+    fn test_autosave_during_continuous_typing() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut autosave = AutosaveDebouncer::default();
+        // A keystroke every 100ms for 12s is saved every 5s
+        let saves: Vec<u64> = (0..120)
+            .map(|i| i * 100)
+            .filter(|&t| {
+                autosave.changed(ms(t));
+                autosave.due(ms(t))
+            })
+            .collect();
+        assert_eq!(saves, vec![5000, 10100]);
+
+        autosave.changed(ms(12000));
+        assert!(autosave.take());
+        assert!(!autosave.due(ms(20000)));
+    }
+
+    #[test]
+    fn test_write_note_replaces_and_removes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = note_path(dir.path(), "feature/x");
+        assert_eq!(read_note(&path).unwrap(), "");
+        assert!(!has_note(&path));
+
+        write_note(&path, "# Todo\n- [ ] tests\n").unwrap();
+        assert_eq!(read_note(&path).unwrap(), "# Todo\n- [ ] tests\n");
+        assert!(has_note(&path));
+        write_note(&path, "done").unwrap();
+        assert_eq!(read_note(&path).unwrap(), "done");
+        // No temporary file is left behind
+        let files: Vec<_> = std::fs::read_dir(notes_dir(dir.path()))
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name())
+            .collect();
+        assert_eq!(files, vec![std::ffi::OsString::from("feature%2Fx.md")]);
+
+        write_note(&path, " \n\n").unwrap();
+        assert!(!path.exists());
+        remove_note(&path).unwrap();
+    }
+
+    #[test]
+    fn test_preview_lines() {
+        let text = "# Plan\n\n- step\n  - [x] done\n* [ ] open\n> quote\n---\n```\n# not a heading\n```\n#tag\nplain";
+        assert_eq!(
+            preview_lines(text),
+            vec![
+                PreviewLine::Heading {
+                    level: 1,
+                    text: "Plan".into()
+                },
+                PreviewLine::Blank,
+                PreviewLine::Bullet {
+                    depth: 0,
+                    text: "step".into()
+                },
+                PreviewLine::Task {
+                    depth: 1,
+                    done: true,
+                    text: "done".into()
+                },
+                PreviewLine::Task {
+                    depth: 0,
+                    done: false,
+                    text: "open".into()
+                },
+                PreviewLine::Quote("quote".into()),
+                PreviewLine::Rule,
+                PreviewLine::Code("# not a heading".into()),
+                PreviewLine::Text("#tag".into()),
+                PreviewLine::Text("plain".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Several repositories open in one window
+//!
+//! One repository is shown at a time. Its sessions, caches and git queue
+//! are the app's `repo`, so everything that works on "the" repository keeps
+//! doing so; the state of the others is set aside here until they are
+//! switched to. The background watchers only ever look at the shown
+//! repository, so one set aside costs nothing but its running terminals.
+//!
+//! The roots of the open repositories are kept in the user's git config
+//! (`sashiki.openRepository`, one value each) so startup can reopen them all.
+
+use std::path::{Path, PathBuf};
+
+/// Open repositories and the set-aside state `S` of those not shown
+#[derive(Debug)]
+pub struct OpenRepositories<S> {
+    /// Main worktree roots, in the order they were opened
+    roots: Vec<PathBuf>,
+    /// State of each repository while it is not shown (None for the shown one)
+    parked: Vec<Option<S>>,
+    active: Option<usize>,
+}
+
+impl<S> Default for OpenRepositories<S> {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            parked: Vec::new(),
+            active: None,
+        }
+    }
+}
+
+impl<S> OpenRepositories<S> {
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    /// Index of the shown repository
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+
+    /// State of the repositories not shown
+    pub fn parked(&self) -> impl Iterator<Item = &S> {
+        self.parked.iter().flatten()
+    }
+
+    /// Index of the repository whose main worktree is at `root`
+    pub fn position(&self, root: &Path) -> Option<usize> {
+        self.roots.iter().position(|r| r == root)
+    }
+
+    /// Add `root` as the shown repository. `set_aside` gives the state of the
+    /// repository shown so far (not called when none was).
+    pub fn add(&mut self, root: PathBuf, set_aside: impl FnOnce() -> S) -> usize {
+        if let Some(active) = self.active {
+            self.parked[active] = Some(set_aside());
+        }
+        self.roots.push(root);
+        self.parked.push(None);
+        let index = self.roots.len() - 1;
+        self.active = Some(index);
+        index
+    }
+
+    /// Show repository `to` instead of the current one: `swap` exchanges the
+    /// state shown with the one set aside for `to`. False (and nothing
+    /// swapped) when `to` is already shown or not open.
+    pub fn switch(&mut self, to: usize, swap: impl FnOnce(&mut S)) -> bool {
+        let Some(from) = self.active else {
+            return false;
+        };
+        let Some(state) = self.parked.get_mut(to).and_then(Option::as_mut) else {
+            return false;
+        };
+        swap(state);
+        // The slot of `to` now holds the state of `from`
+        self.parked.swap(from, to);
+        self.active = Some(to);
+        true
+    }
+
+    /// Forget repository `index`. Returns its set-aside state; the shown
+    /// repository has none (its state is in the app) and leaves nothing shown.
+    pub fn remove(&mut self, index: usize) -> Option<S> {
+        if index >= self.roots.len() {
+            return None;
+        }
+        self.roots.remove(index);
+        let state = self.parked.remove(index);
+        self.active = match self.active {
+            Some(active) if active == index => None,
+            Some(active) if active > index => Some(active - 1),
+            active => active,
+        };
+        state
+    }
+
+    /// Repository after (or before) the shown one, wrapping around; None
+    /// when there is no other
+    pub fn next(&self, forward: bool) -> Option<usize> {
+        let active = self.active?;
+        let len = self.roots.len();
+        if len < 2 {
+            return None;
+        }
+        Some(if forward {
+            (active + 1) % len
+        } else {
+            (active + len - 1) % len
+        })
+    }
+}
+
+/// Name of a repository in the switcher (the directory name of its root)
+pub fn display_name(root: &Path) -> String {
+    root.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| root.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for the app's per-repository state
+    #[derive(Debug, Default, PartialEq)]
+    struct Workspace {
+        sessions: Vec<&'static str>,
+        active_session: usize,
+        /// Stands in for the changed files and diffs cached for the worktree
+        cache: Option<&'static str>,
+    }
+
+    fn workspace(sessions: &[&'static str], active_session: usize) -> Workspace {
+        Workspace {
+            sessions: sessions.to_vec(),
+            active_session,
+            cache: Some(sessions[active_session]),
+        }
+    }
+
+    #[test]
+    fn test_switching_keeps_each_repository_to_itself() {
+        let mut repos = OpenRepositories::default();
+        let mut shown = workspace(&["main", "feature-a", "feature-b"], 2);
+        assert_eq!(repos.add(PathBuf::from("/repos/app"), || unreachable!()), 0);
+
+        // Opening another sets the first aside; the app starts afresh
+        let fresh = Workspace::default();
+        let previous = std::mem::replace(&mut shown, fresh);
+        assert_eq!(repos.add(PathBuf::from("/repos/lib"), || previous), 1);
+        shown = workspace(&["main", "fix"], 0);
+
+        // Back to the first: its session index and cache come back with it,
+        // none of the second's carry over
+        assert!(repos.switch(0, |parked| std::mem::swap(parked, &mut shown)));
+        assert_eq!(repos.active(), Some(0));
+        assert_eq!(shown, workspace(&["main", "feature-a", "feature-b"], 2));
+
+        // Selecting a session here does not touch the one set aside
+        shown.active_session = 1;
+        shown.cache = None;
+        assert!(repos.switch(1, |parked| std::mem::swap(parked, &mut shown)));
+        assert_eq!(shown, workspace(&["main", "fix"], 0));
+        assert!(repos.switch(0, |parked| std::mem::swap(parked, &mut shown)));
+        assert_eq!(shown.active_session, 1);
+        assert_eq!(shown.cache, None);
+
+        // Nothing to swap with the shown repository or one not open
+        assert!(!repos.switch(0, |_| unreachable!()));
+        assert!(!repos.switch(5, |_| unreachable!()));
+    }
+
+    #[test]
+    fn test_closing_and_cycling() {
+        let mut repos = OpenRepositories::default();
+        assert_eq!(repos.next(true), None);
+        repos.add(PathBuf::from("/repos/a"), || unreachable!());
+        assert_eq!(repos.next(true), None);
+        repos.add(PathBuf::from("/repos/b"), || "state of a");
+        repos.add(PathBuf::from("/repos/c"), || "state of b");
+        assert_eq!(repos.position(Path::new("/repos/b")), Some(1));
+        assert_eq!(repos.next(true), Some(0));
+        assert_eq!(repos.next(false), Some(1));
+        assert_eq!(
+            repos.parked().collect::<Vec<_>>(),
+            [&"state of a", &"state of b"]
+        );
+
+        // Closing one set aside hands back its state and keeps c shown
+        assert_eq!(repos.remove(0), Some("state of a"));
+        assert_eq!(
+            repos.roots(),
+            [PathBuf::from("/repos/b"), PathBuf::from("/repos/c")]
+        );
+        assert_eq!(repos.active(), Some(1));
+
+        // Closing the shown one leaves nothing shown
+        assert_eq!(repos.remove(1), None);
+        assert_eq!(repos.active(), None);
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos.remove(3), None);
+        assert_eq!(display_name(Path::new("/repos/b")), "b");
+    }
+}
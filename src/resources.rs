@@ -0,0 +1,339 @@
+//! Memory and CPU used by the terminals of each session
+//!
+//! Off unless `sashiki.sidebar.resources` is set. While on, a thread takes a
+//! snapshot of all processes every `SAMPLE_INTERVAL` and adds up the CPU
+//! and resident memory of the process tree under each session's shells
+//! (`tree_usage`). The UI reads the latest figures on its own tick; nothing
+//! is sampled while the feature is off or no session has a shell.
+//!
+//! Processes come and go between two snapshots and while one is being read.
+//! A snapshot is only ever looked up by pid, so a process that is gone is
+//! simply not counted, and a shell that exited gives no figures at all.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time between two snapshots
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Processes listed in the details of a session
+const TOP_PROCESSES: usize = 5;
+
+/// Memory from which a session is flagged (warning, then critical)
+const MEMORY_HIGH: u64 = 2 << 30;
+const MEMORY_CRITICAL: u64 = 4 << 30;
+
+/// CPU from which a session is flagged, in percent of one core
+const CPU_HIGH: f32 = 80.;
+const CPU_CRITICAL: f32 = 200.;
+
+/// One process in a snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessSample {
+    pub pid: u32,
+    pub parent: Option<u32>,
+    pub name: String,
+    /// Percent of one core since the previous snapshot
+    pub cpu: f32,
+    /// Resident memory in bytes
+    pub memory: u64,
+}
+
+/// What a process tree uses, and its largest processes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeUsage {
+    pub cpu: f32,
+    pub memory: u64,
+    /// Largest by memory first, at most `TOP_PROCESSES`
+    pub top: Vec<ProcessSample>,
+}
+
+/// How worrying a session's usage is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UsageLevel {
+    Normal,
+    High,
+    Critical,
+}
+
+impl TreeUsage {
+    pub fn level(&self) -> UsageLevel {
+        if self.memory >= MEMORY_CRITICAL || self.cpu >= CPU_CRITICAL {
+            UsageLevel::Critical
+        } else if self.memory >= MEMORY_HIGH || self.cpu >= CPU_HIGH {
+            UsageLevel::High
+        } else {
+            UsageLevel::Normal
+        }
+    }
+
+    /// Compact readout, e.g. "1.2G · 87%"
+    pub fn summary(&self) -> String {
+        format!("{} · {:.0}%", format_memory(self.memory), self.cpu)
+    }
+
+    /// One line per process of `top`
+    pub fn details(&self) -> String {
+        self.top
+            .iter()
+            .map(|p| {
+                format!(
+                    "{} ({}) · {} · {:.0}%",
+                    p.name,
+                    p.pid,
+                    format_memory(p.memory),
+                    p.cpu
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Bytes in the largest fitting unit, one decimal below 10 ("1.2G", "340M")
+pub fn format_memory(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    let mut value = bytes as f64 / 1024.;
+    let mut unit = 0;
+    while value >= 1024. && unit + 1 < UNITS.len() {
+        value /= 1024.;
+        unit += 1;
+    }
+    if value < 10. {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+/// Usage of the processes under `roots` (the roots included) in
+/// `snapshot`, None when none of the roots is in it any more
+pub fn tree_usage(snapshot: &[ProcessSample], roots: &[u32]) -> Option<TreeUsage> {
+    let by_pid: HashMap<u32, &ProcessSample> = snapshot.iter().map(|p| (p.pid, p)).collect();
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for process in snapshot {
+        if let Some(parent) = process.parent {
+            children.entry(parent).or_default().push(process.pid);
+        }
+    }
+
+    // Reused pids can make the parent links loop; each process counts once
+    let mut seen = HashSet::new();
+    let mut pending: Vec<u32> = roots
+        .iter()
+        .copied()
+        .filter(|pid| by_pid.contains_key(pid))
+        .collect();
+    if pending.is_empty() {
+        return None;
+    }
+    let mut tree = Vec::new();
+    while let Some(pid) = pending.pop() {
+        if !seen.insert(pid) {
+            continue;
+        }
+        if let Some(process) = by_pid.get(&pid) {
+            tree.push(*process);
+        }
+        pending.extend(children.get(&pid).into_iter().flatten());
+    }
+
+    let mut usage = TreeUsage {
+        cpu: tree.iter().map(|p| p.cpu).sum(),
+        memory: tree.iter().map(|p| p.memory).sum(),
+        top: Vec::new(),
+    };
+    tree.sort_by(|a, b| b.memory.cmp(&a.memory).then(a.pid.cmp(&b.pid)));
+    usage.top = tree.into_iter().take(TOP_PROCESSES).cloned().collect();
+    Some(usage)
+}
+
+/// Source of process snapshots
+pub trait ProcessSource: Send + 'static {
+    fn snapshot(&mut self) -> Vec<ProcessSample>;
+}
+
+/// Snapshots read through `sysinfo`
+pub struct SystemProcesses(sysinfo::System);
+
+impl SystemProcesses {
+    pub fn new() -> Self {
+        Self(sysinfo::System::new())
+    }
+}
+
+impl ProcessSource for SystemProcesses {
+    fn snapshot(&mut self) -> Vec<ProcessSample> {
+        use sysinfo::{ProcessRefreshKind, ProcessesToUpdate};
+
+        self.0.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            ProcessRefreshKind::new().with_cpu().with_memory(),
+        );
+        self.0
+            .processes()
+            .iter()
+            .map(|(pid, process)| ProcessSample {
+                pid: pid.as_u32(),
+                parent: process.parent().map(|parent| parent.as_u32()),
+                name: process.name().to_string_lossy().into_owned(),
+                cpu: process.cpu_usage(),
+                memory: process.memory(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    /// Shell pids by worktree, set by the UI
+    roots: HashMap<PathBuf, Vec<u32>>,
+    /// Latest usage by worktree
+    usage: HashMap<PathBuf, TreeUsage>,
+}
+
+/// Sampling thread; it stops when this is dropped
+pub struct ResourceMonitor {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ResourceMonitor {
+    /// Start sampling `source` every `interval`
+    pub fn spawn(mut source: impl ProcessSource, interval: Duration) -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let monitor = Self {
+            shared: Arc::clone(&shared),
+            stop: Arc::clone(&stop),
+        };
+        std::thread::spawn(move || {
+            while !stop.load(Ordering::Acquire) {
+                let roots = match shared.lock() {
+                    Ok(shared) => shared.roots.clone(),
+                    Err(_) => break,
+                };
+                if !roots.is_empty() {
+                    let snapshot = source.snapshot();
+                    let usage = roots
+                        .into_iter()
+                        .filter_map(|(worktree, pids)| {
+                            tree_usage(&snapshot, &pids).map(|usage| (worktree, usage))
+                        })
+                        .collect();
+                    match shared.lock() {
+                        Ok(mut shared) => shared.usage = usage,
+                        Err(_) => break,
+                    }
+                }
+                std::thread::sleep(interval);
+            }
+        });
+        monitor
+    }
+
+    /// Set which shells belong to which session
+    pub fn set_roots(&self, roots: HashMap<PathBuf, Vec<u32>>) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared
+                .usage
+                .retain(|worktree, _| roots.contains_key(worktree));
+            shared.roots = roots;
+        }
+    }
+
+    /// Latest usage by worktree
+    pub fn usage(&self) -> HashMap<PathBuf, TreeUsage> {
+        self.shared
+            .lock()
+            .map(|shared| shared.usage.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, parent: Option<u32>, cpu: f32, memory_mb: u64) -> ProcessSample {
+        ProcessSample {
+            pid,
+            parent,
+            name: format!("p{}", pid),
+            cpu,
+            memory: memory_mb << 20,
+        }
+    }
+
+    #[test]
+    fn test_tree_usage_adds_up_descendants_only() {
+        let snapshot = [
+            process(1, None, 1., 10),
+            // Shell with an agent that runs a build
+            process(100, Some(1), 0.5, 5),
+            process(101, Some(100), 40., 300),
+            process(102, Some(101), 60., 700),
+            // Another session's shell
+            process(200, Some(1), 90., 900),
+        ];
+        let usage = tree_usage(&snapshot, &[100]).unwrap();
+        assert_eq!(usage.memory, 1005 << 20);
+        assert!((usage.cpu - 100.5).abs() < 0.01);
+        let top: Vec<u32> = usage.top.iter().map(|p| p.pid).collect();
+        assert_eq!(top, [102, 101, 100]);
+
+        // Several shells of one session
+        let both = tree_usage(&snapshot, &[100, 200]).unwrap();
+        assert_eq!(both.memory, 1905 << 20);
+    }
+
+    #[test]
+    fn test_tree_usage_skips_exited_processes() {
+        // The shell exited between the UI reading its pid and the snapshot
+        let snapshot = [process(1, None, 1., 10), process(101, Some(100), 5., 50)];
+        assert_eq!(tree_usage(&snapshot, &[100]), None);
+        assert_eq!(tree_usage(&[], &[100]), None);
+
+        // A child whose parent is gone is no longer in the tree; one shell
+        // of two still running is counted alone
+        let snapshot = [process(100, None, 1., 10), process(102, Some(101), 9., 90)];
+        let usage = tree_usage(&snapshot, &[100, 300]).unwrap();
+        assert_eq!(usage.memory, 10 << 20);
+    }
+
+    #[test]
+    fn test_tree_usage_counts_each_process_once() {
+        // Reused pids linking back to the root
+        let snapshot = [
+            process(100, Some(102), 1., 10),
+            process(101, Some(100), 1., 10),
+            process(102, Some(101), 1., 10),
+        ];
+        let usage = tree_usage(&snapshot, &[100, 100]).unwrap();
+        assert_eq!(usage.memory, 30 << 20);
+        assert_eq!(usage.top.len(), 3);
+    }
+
+    #[test]
+    fn test_readout_and_levels() {
+        let usage = tree_usage(&[process(1, None, 87.4, 1229)], &[1]).unwrap();
+        assert_eq!(usage.summary(), "1.2G · 87%");
+        assert_eq!(usage.level(), UsageLevel::High);
+        assert_eq!(format_memory(340 << 20), "340M");
+        assert_eq!(format_memory(512), "0.5K");
+
+        let quiet = tree_usage(&[process(1, None, 2., 100)], &[1]).unwrap();
+        assert_eq!(quiet.level(), UsageLevel::Normal);
+        let runaway = tree_usage(&[process(1, None, 2., 5000)], &[1]).unwrap();
+        assert_eq!(runaway.level(), UsageLevel::Critical);
+    }
+}
@@ -0,0 +1,257 @@
+//! Reviewed marks of changed files
+//!
+//! A changed file can be marked reviewed in the file list. The mark keeps
+//! the file's content hash at that moment (see `GitRepo::content_hashes`),
+//! so a file that changes again afterwards reads "changed since review"
+//! until it is marked again. Marks are kept per session in the session
+//! state file (see `session_state`) and dropped once the file is no longer
+//! changed.
+//!
+//! Files matching `sashiki.review.exclude` (lockfiles, snapshots and the
+//! like that agents regenerate all the time) are folded into one row at the
+//! end of the Changes list, left out of the reviewed count and the diff
+//! stats, and skipped by next/previous file while that row is collapsed.
+
+use crate::branch_glob;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Review state of a changed file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewState {
+    Unreviewed,
+    Reviewed,
+    /// Marked reviewed, but its content changed since
+    ChangedSinceReview,
+}
+
+/// Content hash of each reviewed file at review time, by path relative to
+/// the worktree
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReviewMarks(BTreeMap<String, String>);
+
+impl ReviewMarks {
+    /// State of `path` whose content hash is now `hash` (None = not known
+    /// yet, which leaves a mark standing)
+    pub fn state(&self, path: &Path, hash: Option<&str>) -> ReviewState {
+        match (self.0.get(path.to_string_lossy().as_ref()), hash) {
+            (None, _) => ReviewState::Unreviewed,
+            (Some(marked), Some(hash)) if marked != hash => ReviewState::ChangedSinceReview,
+            (Some(_), _) => ReviewState::Reviewed,
+        }
+    }
+
+    /// Mark `path` reviewed at content `hash`; false if it already was
+    pub fn mark(&mut self, path: &Path, hash: String) -> bool {
+        self.0
+            .insert(path.to_string_lossy().into_owned(), hash.clone())
+            != Some(hash)
+    }
+
+    /// Remove the mark of `path`; false if there was none
+    pub fn unmark(&mut self, path: &Path) -> bool {
+        self.0.remove(path.to_string_lossy().as_ref()).is_some()
+    }
+
+    /// Drop the marks of files that are no longer changed (committed or
+    /// reverted); false if none were
+    pub fn retain_changed<'a>(&mut self, changed: impl IntoIterator<Item = &'a Path>) -> bool {
+        let changed: Vec<_> = changed.into_iter().map(Path::to_string_lossy).collect();
+        let before = self.0.len();
+        self.0
+            .retain(|path, _| changed.iter().any(|c| c.as_ref() == path.as_str()));
+        self.0.len() != before
+    }
+}
+
+/// Patterns of changed files left out of review (`sashiki.review.exclude`,
+/// multi-valued). A pattern with a `/` matches the whole path relative to
+/// the worktree, one without matches file names at any depth; the globs are
+/// those of `branch_glob` (`*.lock`, `**/__snapshots__/**`, `dist/**`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExcludePatterns(Vec<String>);
+
+impl ExcludePatterns {
+    pub fn new(patterns: impl IntoIterator<Item = String>) -> Self {
+        Self(
+            patterns
+                .into_iter()
+                .map(|p| p.trim().trim_start_matches('/').to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `path` (relative, `/` or `\` separated) is excluded
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.0.is_empty() {
+            return false;
+        }
+        let path = path.to_string_lossy().replace('\\', "/");
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        self.0.iter().any(|pattern| {
+            if pattern.contains('/') {
+                branch_glob::matches(pattern, &path)
+            } else {
+                branch_glob::matches(pattern, name)
+            }
+        })
+    }
+
+    /// Label of the row the `count` excluded files are folded into, e.g.
+    /// "3 excluded files (*.lock, dist/**…)"
+    pub fn group_label(&self, count: usize) -> String {
+        let shown = self
+            .0
+            .iter()
+            .take(2)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        let more = if self.0.len() > 2 { "…" } else { "" };
+        format!(
+            "{} excluded file{} ({}{})",
+            count,
+            if count == 1 { "" } else { "s" },
+            shown,
+            more
+        )
+    }
+}
+
+/// How many of `files` are reviewed and unchanged since
+pub fn reviewed_count<'a>(
+    marks: &ReviewMarks,
+    files: impl IntoIterator<Item = &'a Path>,
+    hashes: &HashMap<PathBuf, String>,
+) -> usize {
+    files
+        .into_iter()
+        .filter(|path| {
+            marks.state(path, hashes.get(*path).map(String::as_str)) == ReviewState::Reviewed
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(files: &[PathBuf]) -> impl Iterator<Item = &Path> {
+        files.iter().map(PathBuf::as_path)
+    }
+
+    #[test]
+    fn test_change_after_review_resets_the_mark() {
+        let path = Path::new("src/lib.rs");
+        let mut marks = ReviewMarks::default();
+        assert_eq!(marks.state(path, Some("aaa")), ReviewState::Unreviewed);
+
+        assert!(marks.mark(path, "aaa".into()));
+        assert!(!marks.mark(path, "aaa".into()));
+        assert_eq!(marks.state(path, Some("aaa")), ReviewState::Reviewed);
+        // Hashes not computed yet leave the mark standing
+        assert_eq!(marks.state(path, None), ReviewState::Reviewed);
+
+        assert_eq!(
+            marks.state(path, Some("bbb")),
+            ReviewState::ChangedSinceReview
+        );
+        // Changed back to what was reviewed
+        assert_eq!(marks.state(path, Some("aaa")), ReviewState::Reviewed);
+
+        assert!(marks.mark(path, "bbb".into()));
+        assert_eq!(marks.state(path, Some("bbb")), ReviewState::Reviewed);
+        assert!(marks.unmark(path));
+        assert!(!marks.unmark(path));
+        assert_eq!(marks.state(path, Some("bbb")), ReviewState::Unreviewed);
+    }
+
+    #[test]
+    fn test_reviewed_count_and_retain() {
+        let files = vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")];
+        let mut hashes = HashMap::from([
+            (PathBuf::from("a.rs"), "1".to_string()),
+            (PathBuf::from("b.rs"), "2".to_string()),
+        ]);
+        let mut marks = ReviewMarks::default();
+        marks.mark(Path::new("a.rs"), "1".into());
+        marks.mark(Path::new("b.rs"), "2".into());
+        marks.mark(Path::new("gone.rs"), "3".into());
+        assert_eq!(reviewed_count(&marks, paths(&files), &hashes), 2);
+
+        hashes.insert(PathBuf::from("b.rs"), "changed".into());
+        assert_eq!(reviewed_count(&marks, paths(&files), &hashes), 1);
+
+        assert!(marks.retain_changed(paths(&files)));
+        assert!(!marks.retain_changed(paths(&files)));
+        assert_eq!(
+            marks.state(Path::new("gone.rs"), Some("3")),
+            ReviewState::Unreviewed
+        );
+    }
+
+    #[test]
+    fn test_marks_serialize_as_a_map() {
+        let mut marks = ReviewMarks::default();
+        marks.mark(Path::new("src/main.rs"), "abc".into());
+        let json = serde_json::to_string(&marks).unwrap();
+        assert_eq!(json, r#"{"src/main.rs":"abc"}"#);
+        assert_eq!(serde_json::from_str::<ReviewMarks>(&json).unwrap(), marks);
+    }
+
+    fn excluded(patterns: &[&str]) -> ExcludePatterns {
+        ExcludePatterns::new(patterns.iter().map(|p| p.to_string()))
+    }
+
+    #[test]
+    fn test_exclude_patterns_match_relative_paths() {
+        let patterns = excluded(&["*.lock", "**/__snapshots__/**", "/dist/**", " "]);
+        assert!(patterns.matches(Path::new("Cargo.lock")));
+        assert!(patterns.matches(Path::new("web/yarn.lock")));
+        assert!(patterns.matches(Path::new("src/__snapshots__/app.test.ts.snap")));
+        assert!(patterns.matches(Path::new("__snapshots__/a.snap")));
+        assert!(patterns.matches(Path::new("dist/app.js")));
+        assert!(patterns.matches(Path::new("dist/assets/logo.svg")));
+        // Anchored at the root
+        assert!(!patterns.matches(Path::new("web/dist/app.js")));
+        assert!(!patterns.matches(Path::new("src/lock.rs")));
+        assert!(!patterns.matches(Path::new("src/main.rs")));
+    }
+
+    #[test]
+    fn test_exclude_patterns_accept_backslashes() {
+        let patterns = excluded(&["*.lock", "**/__snapshots__/**", "dist/**"]);
+        assert!(patterns.matches(Path::new("web\\yarn.lock")));
+        assert!(patterns.matches(Path::new("src\\__snapshots__\\app.snap")));
+        assert!(patterns.matches(Path::new("dist\\app.js")));
+        assert!(!patterns.matches(Path::new("src\\main.rs")));
+    }
+
+    #[test]
+    fn test_no_patterns_exclude_nothing() {
+        let patterns = ExcludePatterns::default();
+        assert!(patterns.is_empty());
+        assert!(!patterns.matches(Path::new("Cargo.lock")));
+        assert!(excluded(&["", "  "]).is_empty());
+    }
+
+    #[test]
+    fn test_group_label_counts() {
+        let patterns = excluded(&["*.lock", "dist/**", "*.snap"]);
+        assert_eq!(
+            patterns.group_label(3),
+            "3 excluded files (*.lock, dist/**…)"
+        );
+        assert_eq!(
+            excluded(&["*.lock"]).group_label(1),
+            "1 excluded file (*.lock)"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Review comments on lines of changed files
+//!
+//! A comment is written from the gutter of the diff view and tied to a line
+//! of the working tree file: its path relative to the worktree, the line
+//! number and a hash of the line's text at that moment. A comment whose
+//! line no longer reads the same (edited, moved or gone) is shown as stale
+//! until it is edited or deleted. Comments are kept per session in the
+//! session state file (see `session_state`).
+//!
+//! The whole set is handed back as markdown grouped by file, each comment
+//! under its `path:line` reference and the commented line quoted (see
+//! `to_markdown`), ready to paste into an agent's terminal.
+
+use crate::stable_hash;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A comment on one line of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewComment {
+    /// Path relative to the worktree
+    pub path: String,
+    /// Line of the working tree file (1-based)
+    pub line: usize,
+    /// `line_hash` of the line when it was commented
+    pub line_hash: String,
+    /// The line as it read then, quoted in the export
+    pub quoted: String,
+    pub text: String,
+}
+
+impl ReviewComment {
+    pub fn new(path: &Path, line: usize, line_text: &str, text: String) -> Self {
+        let line_text = line_text.trim_end_matches('\r');
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            line,
+            line_hash: line_hash(line_text),
+            quoted: line_text.to_string(),
+            text,
+        }
+    }
+
+    /// Whether the commented line no longer reads as it did, in the file's
+    /// current text (None = the file is gone)
+    pub fn is_stale(&self, file: Option<&str>) -> bool {
+        let current = file.and_then(|text| text.lines().nth(self.line.checked_sub(1)?));
+        current.is_none_or(|line| line_hash(line.trim_end_matches('\r')) != self.line_hash)
+    }
+}
+
+/// Hash of a line's text, fixed across releases as it is saved
+pub fn line_hash(line: &str) -> String {
+    format!("{:016x}", stable_hash::fnv1a_64(line.as_bytes()))
+}
+
+/// The comments of a session, ordered by path and line
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReviewComments(Vec<ReviewComment>);
+
+impl ReviewComments {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ReviewComment> {
+        self.0.get(index)
+    }
+
+    /// Add `comment` after the others on the same line
+    pub fn add(&mut self, comment: ReviewComment) {
+        let at = self
+            .0
+            .partition_point(|c| (&c.path, c.line) <= (&comment.path, comment.line));
+        self.0.insert(at, comment);
+    }
+
+    /// Replace the text of the comment at `index`. Editing also re-anchors
+    /// it at the line as it reads now (`line_text`), which clears it being
+    /// stale.
+    pub fn edit(&mut self, index: usize, line_text: Option<&str>, text: String) -> bool {
+        let Some(comment) = self.0.get_mut(index) else {
+            return false;
+        };
+        if let Some(line_text) = line_text {
+            let path = comment.path.clone();
+            *comment = ReviewComment::new(Path::new(&path), comment.line, line_text, text);
+        } else {
+            comment.text = text;
+        }
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.0.len() {
+            return false;
+        }
+        self.0.remove(index);
+        true
+    }
+
+    pub fn clear(&mut self) -> bool {
+        let had_any = !self.0.is_empty();
+        self.0.clear();
+        had_any
+    }
+
+    /// Comments on the file at `path` (relative), with their indices
+    pub fn for_file(&self, path: &Path) -> Vec<(usize, ReviewComment)> {
+        let path = path.to_string_lossy();
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.path == path)
+            .map(|(i, c)| (i, c.clone()))
+            .collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ReviewComment> {
+        self.0.iter()
+    }
+}
+
+/// The comments as markdown, a section per file:
+///
+/// ````text
+/// ## src/app.rs
+///
+/// `src/app.rs:12`
+/// > let x = 1;
+///
+/// Use the constant.
+/// ````
+///
+/// `read_file` gives the current text of a file (by relative path); comments
+/// whose line changed since are flagged.
+pub fn to_markdown(
+    comments: &ReviewComments,
+    mut read_file: impl FnMut(&str) -> Option<String>,
+) -> String {
+    let mut out = String::from("# Review\n");
+    let mut current: Option<(&str, Option<String>)> = None;
+    for comment in comments.iter() {
+        if current
+            .as_ref()
+            .is_none_or(|(path, _)| *path != comment.path)
+        {
+            out.push_str(&format!("\n## {}\n", comment.path));
+            current = Some((&comment.path, read_file(&comment.path)));
+        }
+        let file = current.as_ref().and_then(|(_, text)| text.as_deref());
+        out.push_str(&format!("\n`{}:{}`", comment.path, comment.line));
+        if comment.is_stale(file) {
+            out.push_str(" (the line has changed since)");
+        }
+        if comment.quoted.is_empty() {
+            out.push_str("\n>\n\n");
+        } else {
+            out.push_str(&format!("\n> {}\n\n", comment.quoted));
+        }
+        out.push_str(comment.text.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(path: &str, line: usize, line_text: &str, text: &str) -> ReviewComment {
+        ReviewComment::new(Path::new(path), line, line_text, text.to_string())
+    }
+
+    #[test]
+    fn test_comment_goes_stale_when_its_line_changes() {
+        let c = comment("src/lib.rs", 2, "let x = 1;", "Use a constant");
+        assert!(!c.is_stale(Some("fn f() {\nlet x = 1;\n}\n")));
+        // Line endings do not count as a change
+        assert!(!c.is_stale(Some("fn f() {\r\nlet x = 1;\r\n}\r\n")));
+        assert!(c.is_stale(Some("fn f() {\nlet x = 2;\n}\n")));
+        // A line inserted above moves the text off the commented line
+        assert!(c.is_stale(Some("// new\nfn f() {\nlet x = 1;\n}\n")));
+        assert!(c.is_stale(Some("fn f() {\n")));
+        assert!(c.is_stale(None));
+    }
+
+    #[test]
+    fn test_edit_re_anchors_at_the_current_line() {
+        let mut comments = ReviewComments::default();
+        comments.add(comment("a.rs", 1, "old", "note"));
+        assert!(comments.get(0).unwrap().is_stale(Some("new\n")));
+
+        comments.edit(0, None, "note, still".to_string());
+        assert!(comments.get(0).unwrap().is_stale(Some("new\n")));
+        comments.edit(0, Some("new"), "note again".to_string());
+        let edited = comments.get(0).unwrap();
+        assert!(!edited.is_stale(Some("new\n")));
+        assert_eq!(
+            (edited.quoted.as_str(), edited.text.as_str()),
+            ("new", "note again")
+        );
+        assert!(!comments.edit(1, None, String::new()));
+    }
+
+    #[test]
+    fn test_comments_are_ordered_by_file_and_line() {
+        let mut comments = ReviewComments::default();
+        comments.add(comment("b.rs", 3, "", "b3"));
+        comments.add(comment("a.rs", 9, "", "a9"));
+        comments.add(comment("a.rs", 2, "", "a2"));
+        comments.add(comment("a.rs", 9, "", "a9 again"));
+        let texts: Vec<_> = comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["a2", "a9", "a9 again", "b3"]);
+
+        let on_a: Vec<_> = comments
+            .for_file(Path::new("a.rs"))
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(on_a, [0, 1, 2]);
+        assert!(comments.remove(1));
+        assert_eq!(comments.get(1).unwrap().text, "a9 again");
+        assert!(comments.clear());
+        assert!(!comments.clear());
+    }
+
+    #[test]
+    fn test_markdown_groups_comments_by_file() {
+        let mut comments = ReviewComments::default();
+        comments.add(comment("src/app.rs", 12, "let x = 1;", "Use the constant."));
+        comments.add(comment("src/app.rs", 40, "", "Why blank?\nRemove it.\n"));
+        comments.add(comment("README.md", 1, "# Title", "Typo"));
+
+        let markdown = to_markdown(&comments, |path| match path {
+            "src/app.rs" => Some(format!("{}let x = 1;\n", "\n".repeat(11))),
+            _ => None,
+        });
+        assert_eq!(
+            markdown,
+            "# Review\n\
+             \n\
+             ## README.md\n\
+             \n\
+             `README.md:1` (the line has changed since)\n\
+             > # Title\n\
+             \n\
+             Typo\n\
+             \n\
+             ## src/app.rs\n\
+             \n\
+             `src/app.rs:12`\n\
+             > let x = 1;\n\
+             \n\
+             Use the constant.\n\
+             \n\
+             `src/app.rs:40` (the line has changed since)\n\
+             >\n\
+             \n\
+             Why blank?\n\
+             Remove it.\n"
+        );
+    }
+}
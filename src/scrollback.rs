@@ -0,0 +1,217 @@
+//! Terminal scrollback kept across restarts
+//!
+//! Off unless `sashiki.terminal.persistScrollback` is set. When the app
+//! quits, the logical lines of each terminal are written gzip-compressed to
+//! `.git/sashiki/scrollback/<worktree-name>/<terminal>`, where `<terminal>`
+//! is the terminal's position in its session. The terminal started at that
+//! position after the next launch begins with them as its history, under a
+//! divider (`DIVIDER`), and the file is removed once read. Lines are plain
+//! text unless `sashiki.terminal.persistScrollbackColors` is set too, in
+//! which case they carry SGR sequences for their colors.
+//!
+//! Terminals running in tmux are left out: tmux keeps their scrollback.
+//! Writing the files happens on a thread the app waits on for at most
+//! `SAVE_TIMEOUT`, so a slow disk cannot hold up quitting. A file that is
+//! too large or does not decode is skipped with a warning in the app log.
+
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::fs_util;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Line put between the restored lines and the new shell's output
+pub const DIVIDER: &str = "── restored session ──";
+
+/// Longest the app waits for the files to be written when it quits
+pub const SAVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A saved file larger than this is not read
+const MAX_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Text a saved file may decompress to
+const MAX_TEXT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// First line of a saved file
+const HEADER: &str = "sashiki-scrollback 1";
+
+/// Directory of a session's saved scrollback inside the shared `.git` directory
+pub fn scrollback_dir(git_dir: &Path, worktree_name: &str) -> PathBuf {
+    git_dir
+        .join("sashiki")
+        .join("scrollback")
+        .join(fs_util::file_stem(worktree_name))
+}
+
+/// `lines` as the contents of a saved file
+pub fn encode(lines: &[String]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(HEADER.as_bytes())?;
+    for line in lines {
+        encoder.write_all(b"\n")?;
+        encoder.write_all(line.as_bytes())?;
+    }
+    encoder.finish()
+}
+
+/// Lines of a saved file
+pub fn decode(bytes: &[u8]) -> io::Result<Vec<String>> {
+    let mut text = Vec::new();
+    GzDecoder::new(bytes)
+        .take(MAX_TEXT_BYTES + 1)
+        .read_to_end(&mut text)?;
+    if text.len() as u64 > MAX_TEXT_BYTES {
+        return Err(io::Error::other("more text than a scrollback holds"));
+    }
+    let text = String::from_utf8(text).map_err(|_| io::Error::other("not UTF-8 text"))?;
+    let mut lines = text.split('\n');
+    if lines.next() != Some(HEADER) {
+        return Err(io::Error::other("not a saved scrollback"));
+    }
+    Ok(lines.map(str::to_string).collect())
+}
+
+pub fn save(path: &Path, lines: &[String]) -> io::Result<()> {
+    fs_util::write_atomic(path, &encode(lines)?)
+}
+
+/// Read and remove the file at `path` (None when there is none). A file too
+/// large or not decoding is removed as well.
+pub fn take(path: &Path) -> io::Result<Option<Vec<String>>> {
+    let size = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let read = if size > MAX_FILE_BYTES {
+        Err(io::Error::other(format!("{} bytes is too large", size)))
+    } else {
+        std::fs::read(path).and_then(|bytes| decode(&bytes))
+    };
+    std::fs::remove_file(path)?;
+    read.map(Some)
+}
+
+/// Output that puts `lines` on a fresh screen, each on its own line, and
+/// leaves the cursor at the start of the line after the divider
+pub fn restored_output(lines: &[String]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for line in lines {
+        output.extend_from_slice(line.as_bytes());
+        // Colors do not run on into the next line
+        output.extend_from_slice(b"\x1b[0m\r\n");
+    }
+    output.extend_from_slice(format!("\x1b[2m{}\x1b[0m\r\n", DIVIDER).as_bytes());
+    output
+}
+
+/// Whether and where a session's scrollback is kept, shared with its terminals
+#[derive(Debug, Default)]
+pub struct SessionScrollback {
+    /// None until the repository is known
+    dir: RefCell<Option<PathBuf>>,
+    enabled: Cell<bool>,
+    colors: Cell<bool>,
+}
+
+impl SessionScrollback {
+    /// Keep the scrollback in `dir` when `enabled` (the git config settings)
+    pub fn configure(&self, dir: PathBuf, enabled: bool, colors: bool) {
+        *self.dir.borrow_mut() = Some(dir);
+        self.enabled.set(enabled);
+        self.colors.set(colors);
+    }
+}
+
+/// One terminal's saved scrollback within its session's
+#[derive(Debug, Clone)]
+pub struct TerminalScrollback {
+    session: Rc<SessionScrollback>,
+    /// Position of the terminal in the session
+    terminal: usize,
+}
+
+impl TerminalScrollback {
+    pub fn new(session: Rc<SessionScrollback>, terminal: usize) -> Self {
+        Self { session, terminal }
+    }
+
+    /// File of the terminal, None while the scrollback is not kept
+    pub fn path(&self) -> Option<PathBuf> {
+        if !self.session.enabled.get() {
+            return None;
+        }
+        let dir = self.session.dir.borrow();
+        Some(dir.as_ref()?.join(self.terminal.to_string()))
+    }
+
+    /// Whether lines are saved with their colors
+    pub fn colors(&self) -> bool {
+        self.session.colors.get()
+    }
+
+    /// Lines saved for the terminal by the last run, if any
+    pub fn restore(&self) -> Option<Vec<String>> {
+        let path = self.path()?;
+        match take(&path) {
+            Ok(lines) => lines,
+            Err(e) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Warn,
+                    LogSource::Terminal,
+                    format!("Skipped the saved scrollback in {}: {}", path.display(), e),
+                ));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_lines_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = scrollback_dir(dir.path(), "feat/x").join("0");
+        let lines = vec![
+            "$ cargo test".to_string(),
+            String::new(),
+            "日本語 \x1b[31mfailed\x1b[0m".to_string(),
+        ];
+        save(&path, &lines).unwrap();
+        assert_eq!(take(&path).unwrap(), Some(lines));
+        // Read once only
+        assert!(!path.exists());
+        assert_eq!(take(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unreadable_files_are_skipped_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0");
+        std::fs::write(&path, b"not gzip").unwrap();
+        assert!(take(&path).is_err());
+        assert!(!path.exists());
+
+        // Compressed, but not something Sashiki wrote
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello\nworld").unwrap();
+        assert!(decode(&encoder.finish().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_restored_output_ends_with_the_divider() {
+        let output = restored_output(&["one".to_string(), "two".to_string()]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "one\x1b[0m\r\ntwo\x1b[0m\r\n\x1b[2m── restored session ──\x1b[0m\r\n"
+        );
+    }
+}
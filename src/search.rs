@@ -0,0 +1,569 @@
+//! Workspace search across the active worktree
+//!
+//! Tracked files are searched with `git grep` (binary files are skipped), then
+//! the untracked files that are not ignored are read here, at most
+//! `MAX_UNTRACKED_FILES` files of up to `MAX_UNTRACKED_FILE_SIZE` each. Hits
+//! reach the search dialog in batches while the search runs on a worker; a
+//! new query cancels the search in flight (see `CancelToken`). A search stops
+//! after `sashiki.search.maxResults` hits (default 2000) and the dialog asks
+//! for a narrower query.
+//!
+//! Regex queries use git's extended syntax for tracked files and the `regex`
+//! crate for untracked ones; the two agree on everything but rare constructs.
+
+use crate::diff_job::CancelToken;
+use crate::git::GitRepo;
+use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Height of a row in the search dialog
+pub const ROW_HEIGHT: f32 = 22.0;
+
+/// Untracked files read per search; the rest are not searched
+pub const MAX_UNTRACKED_FILES: usize = 2000;
+
+/// Untracked files larger than this are not searched
+pub const MAX_UNTRACKED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Bytes checked for a NUL to tell an untracked binary file (as git does)
+const BINARY_PROBE: usize = 8000;
+
+/// Hits sent to the dialog at once
+const BATCH_SIZE: usize = 64;
+
+/// Longest a batch waits for more hits, so a slow search still shows progress
+const BATCH_DELAY: Duration = Duration::from_millis(100);
+
+/// Characters of a line shown in the dialog
+const PREVIEW_CHARS: usize = 200;
+
+/// Bytes kept before the match when a long line is cut
+const PREVIEW_CONTEXT: usize = 40;
+
+/// How the query is matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SearchOptions {
+    /// The query is a regular expression rather than literal text
+    pub regex: bool,
+    pub case_sensitive: bool,
+}
+
+impl SearchOptions {
+    /// `git grep` flags selecting the pattern syntax and case
+    fn grep_flags(self) -> Vec<&'static str> {
+        let mut flags = vec![if self.regex { "-E" } else { "-F" }];
+        if !self.case_sensitive {
+            flags.push("-i");
+        }
+        flags
+    }
+
+    /// Matcher for the files git does not search (also rejects an invalid
+    /// regex before git runs)
+    fn matcher(self, query: &str) -> Result<Regex, regex::Error> {
+        let pattern = if self.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+    }
+}
+
+/// A line matching the query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// Relative to the worktree root
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based byte offset of the first match in the line
+    pub column: usize,
+    /// The line, cut around the match when it is long
+    pub preview: String,
+}
+
+/// Parse a line of `git grep -n --column -z` output (`path\0line\0column\0text`).
+/// None for anything else, such as a "Binary file … matches" notice.
+pub fn parse_grep_line(line: &[u8]) -> Option<SearchHit> {
+    let mut fields = line.splitn(4, |&b| b == 0);
+    let path = fields.next()?;
+    let number = |field: &[u8]| std::str::from_utf8(field).ok()?.parse().ok();
+    let line_number = number(fields.next()?)?;
+    let column = number(fields.next()?)?;
+    let text = String::from_utf8_lossy(fields.next()?);
+    Some(SearchHit {
+        path: PathBuf::from(String::from_utf8_lossy(path).into_owned()),
+        line: line_number,
+        column,
+        preview: preview(&text, column),
+    })
+}
+
+/// `text` without its line ending, cut to `PREVIEW_CHARS` starting a little
+/// before the match at `column` when it is longer
+fn preview(text: &str, column: usize) -> String {
+    let text = text.trim_end_matches(['\n', '\r']);
+    if text.chars().count() <= PREVIEW_CHARS {
+        return text.to_string();
+    }
+    let mut start = column.saturating_sub(1 + PREVIEW_CONTEXT).min(text.len());
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let cut: String = text[start..].chars().take(PREVIEW_CHARS).collect();
+    if start > 0 {
+        format!("…{}", cut)
+    } else {
+        cut
+    }
+}
+
+/// How a search ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchEnd {
+    /// Every file was searched
+    Complete,
+    /// The hit limit was reached before every file was searched
+    Truncated,
+    Cancelled,
+}
+
+/// Collects hits into batches and counts them against the limit
+struct HitSink<'a> {
+    limit: usize,
+    found: usize,
+    batch: Vec<SearchHit>,
+    batch_started: Instant,
+    on_hits: &'a mut dyn FnMut(Vec<SearchHit>),
+}
+
+impl HitSink<'_> {
+    /// Add a hit; false once the limit is reached
+    fn push(&mut self, hit: SearchHit) -> bool {
+        if self.batch.is_empty() {
+            self.batch_started = Instant::now();
+        }
+        self.batch.push(hit);
+        self.found += 1;
+        if self.batch.len() >= BATCH_SIZE || self.batch_started.elapsed() >= BATCH_DELAY {
+            self.flush();
+        }
+        self.found < self.limit
+    }
+
+    fn flush(&mut self) {
+        if !self.batch.is_empty() {
+            (self.on_hits)(std::mem::take(&mut self.batch));
+        }
+    }
+
+    /// How the search ended, if it should stop here
+    fn end(&self, cancel: &CancelToken) -> Option<SearchEnd> {
+        if cancel.is_cancelled() {
+            Some(SearchEnd::Cancelled)
+        } else if self.found >= self.limit {
+            Some(SearchEnd::Truncated)
+        } else {
+            None
+        }
+    }
+}
+
+/// Search the worktree of `repo` for `query`, passing hits to `on_hits` in
+/// batches until `limit` hits are found or `cancel` is set. Hits of a
+/// cancelled search that were not passed on yet are dropped.
+pub fn search(
+    repo: &GitRepo,
+    query: &str,
+    options: SearchOptions,
+    limit: usize,
+    cancel: &CancelToken,
+    on_hits: &mut dyn FnMut(Vec<SearchHit>),
+) -> Result<SearchEnd, String> {
+    let matcher = options.matcher(query).map_err(|e| e.to_string())?;
+    let mut sink = HitSink {
+        limit: limit.max(1),
+        found: 0,
+        batch: Vec::new(),
+        batch_started: Instant::now(),
+        on_hits,
+    };
+
+    repo.grep(&options.grep_flags(), query, &mut |line| {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        match parse_grep_line(line) {
+            Some(hit) => sink.push(hit),
+            None => true,
+        }
+    })
+    .map_err(|e| e.to_string())?;
+
+    if sink.end(cancel).is_none() {
+        let untracked = repo.untracked_files().map_err(|e| e.to_string())?;
+        for path in untracked.into_iter().take(MAX_UNTRACKED_FILES) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let full_path = repo.workdir().join(&path);
+            if !search_file(&full_path, &path, &matcher, &mut |hit| sink.push(hit)) {
+                break;
+            }
+        }
+    }
+
+    let end = sink.end(cancel).unwrap_or(SearchEnd::Complete);
+    if end != SearchEnd::Cancelled {
+        sink.flush();
+    }
+    Ok(end)
+}
+
+/// Search a file git does not track, unless it is too large or binary.
+/// Returns false once `push` asks to stop.
+fn search_file(
+    full_path: &Path,
+    path: &Path,
+    matcher: &Regex,
+    push: &mut dyn FnMut(SearchHit) -> bool,
+) -> bool {
+    let readable = std::fs::metadata(full_path)
+        .is_ok_and(|meta| meta.is_file() && meta.len() <= MAX_UNTRACKED_FILE_SIZE);
+    let Some(bytes) = readable.then(|| std::fs::read(full_path).ok()).flatten() else {
+        return true;
+    };
+    if bytes[..bytes.len().min(BINARY_PROBE)].contains(&0) {
+        return true;
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    for (index, line) in text.lines().enumerate() {
+        if let Some(found) = matcher.find(line) {
+            let column = found.start() + 1;
+            let hit = SearchHit {
+                path: path.to_path_buf(),
+                line: index + 1,
+                column,
+                preview: preview(line, column),
+            };
+            if !push(hit) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// A row of the results: a file heading or one of its hits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchRow<'a> {
+    File {
+        path: &'a Path,
+        hits: usize,
+    },
+    /// Index into the hits
+    Hit(usize),
+}
+
+/// State of the search dialog
+#[derive(Debug)]
+pub struct WorkspaceSearch {
+    pub query: String,
+    pub options: SearchOptions,
+    /// Worktree the hits are in
+    pub root: Option<PathBuf>,
+    /// Hits in the order they were found, so those of a file are adjacent
+    pub hits: Vec<SearchHit>,
+    /// Index into `hits`
+    pub selected: usize,
+    pub running: bool,
+    /// The search stopped at `limit` hits
+    pub truncated: bool,
+    pub error: Option<String>,
+    /// Hits after which a search stops (`sashiki.search.maxResults`)
+    pub limit: usize,
+}
+
+impl Default for WorkspaceSearch {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            options: SearchOptions::default(),
+            root: None,
+            hits: Vec::new(),
+            selected: 0,
+            running: false,
+            truncated: false,
+            error: None,
+            limit: crate::git::DEFAULT_SEARCH_MAX_RESULTS,
+        }
+    }
+}
+
+impl WorkspaceSearch {
+    /// Drop the hits of the previous query; a search runs unless the query
+    /// is empty
+    pub fn start(&mut self) {
+        self.hits.clear();
+        self.selected = 0;
+        self.running = !self.query.is_empty();
+        self.truncated = false;
+        self.error = None;
+    }
+
+    pub fn push_hits(&mut self, hits: Vec<SearchHit>) {
+        self.hits.extend(hits);
+    }
+
+    pub fn finish(&mut self, result: Result<SearchEnd, String>) {
+        self.running = false;
+        match result {
+            Ok(end) => self.truncated = end == SearchEnd::Truncated,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Hits grouped under a heading per file
+    pub fn rows(&self) -> Vec<SearchRow<'_>> {
+        let mut rows = Vec::with_capacity(self.hits.len() * 2);
+        let mut start = 0;
+        while start < self.hits.len() {
+            let path = self.hits[start].path.as_path();
+            let end = self.hits[start..]
+                .iter()
+                .position(|hit| hit.path != path)
+                .map_or(self.hits.len(), |len| start + len);
+            rows.push(SearchRow::File {
+                path,
+                hits: end - start,
+            });
+            rows.extend((start..end).map(SearchRow::Hit));
+            start = end;
+        }
+        rows
+    }
+
+    /// Files with hits
+    pub fn file_count(&self) -> usize {
+        self.rows()
+            .iter()
+            .filter(|row| matches!(row, SearchRow::File { .. }))
+            .count()
+    }
+
+    pub fn selected_hit(&self) -> Option<&SearchHit> {
+        self.hits.get(self.selected)
+    }
+
+    /// Move the selection by `delta` hits, wrapping around
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.hits.is_empty() {
+            return;
+        }
+        let len = self.hits.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+
+    /// Repository with `files` tracked (added to the index)
+    fn repo_with(files: &[(&str, &[u8])]) -> (tempfile::TempDir, GitRepo) {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q", "-b", "main"]);
+        for (path, content) in files {
+            std::fs::write(dir.path().join(path), content).unwrap();
+        }
+        git(dir.path(), &["add", "."]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    fn run(
+        repo: &GitRepo,
+        query: &str,
+        options: SearchOptions,
+        limit: usize,
+    ) -> (Vec<SearchHit>, SearchEnd) {
+        let mut hits = Vec::new();
+        let end = search(
+            repo,
+            query,
+            options,
+            limit,
+            &CancelToken::default(),
+            &mut |batch| hits.extend(batch),
+        )
+        .unwrap();
+        (hits, end)
+    }
+
+    #[test]
+    fn test_parse_grep_line_fields() {
+        let hit = parse_grep_line(b"src/main.rs\x0012\x005\x00let x = 1;").unwrap();
+        assert_eq!(hit.path, PathBuf::from("src/main.rs"));
+        assert_eq!(hit.line, 12);
+        assert_eq!(hit.column, 5);
+        assert_eq!(hit.preview, "let x = 1;");
+    }
+
+    #[test]
+    fn test_parse_grep_line_keeps_colons_and_nuls_in_text() {
+        let hit = parse_grep_line(b"a:b.txt\x003\x001\x00key: value\x00tail\r").unwrap();
+        assert_eq!(hit.path, PathBuf::from("a:b.txt"));
+        assert_eq!(hit.line, 3);
+        assert_eq!(hit.preview, "key: value\0tail");
+    }
+
+    #[test]
+    fn test_parse_grep_line_skips_binary_notices() {
+        assert_eq!(parse_grep_line(b"Binary file image.png matches"), None);
+        assert_eq!(parse_grep_line(b"file\x00x\x001\x00text"), None);
+        assert_eq!(parse_grep_line(b""), None);
+    }
+
+    #[test]
+    fn test_preview_cuts_long_lines_around_the_match() {
+        let line = format!("{}needle{}", "a".repeat(500), "b".repeat(500));
+        let cut = preview(&line, 501);
+        assert!(cut.starts_with('…'));
+        assert!(cut.contains("needle"));
+        assert_eq!(cut.chars().count(), PREVIEW_CHARS + 1);
+        assert_eq!(preview("short\n", 1), "short");
+    }
+
+    #[test]
+    fn test_search_tracked_and_untracked_files() {
+        let (dir, repo) = repo_with(&[
+            ("tracked.txt", b"one\nTODO: two\n"),
+            ("image.bin", b"TODO\0\x01\x02"),
+            (".gitignore", b"ignored.txt\n"),
+        ]);
+        std::fs::write(dir.path().join("new.txt"), "x\ny todo\n").unwrap();
+        std::fs::write(dir.path().join("ignored.txt"), "todo\n").unwrap();
+        std::fs::write(dir.path().join("blob.dat"), b"todo\0").unwrap();
+
+        let (hits, end) = run(&repo, "todo", SearchOptions::default(), 100);
+        assert_eq!(end, SearchEnd::Complete);
+        let found: Vec<(String, usize, usize)> = hits
+            .iter()
+            .map(|h| (h.path.to_string_lossy().into_owned(), h.line, h.column))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("tracked.txt".to_string(), 2, 1),
+                ("new.txt".to_string(), 2, 3),
+            ]
+        );
+
+        let case_sensitive = SearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let (hits, _) = run(&repo, "todo", case_sensitive, 100);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, PathBuf::from("new.txt"));
+    }
+
+    #[test]
+    fn test_search_regex_and_invalid_regex() {
+        let (_dir, repo) = repo_with(&[("a.txt", b"foo1\nfoo\nbar22\n")]);
+        let regex = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let (hits, _) = run(&repo, "[a-z]+[0-9]+", regex, 100);
+        let lines: Vec<usize> = hits.iter().map(|h| h.line).collect();
+        assert_eq!(lines, vec![1, 3]);
+
+        let result = search(&repo, "(", regex, 100, &CancelToken::default(), &mut |_| {});
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_search_stops_at_the_limit() {
+        let content = "match\n".repeat(50);
+        let (_dir, repo) = repo_with(&[("a.txt", content.as_bytes())]);
+        let (hits, end) = run(&repo, "match", SearchOptions::default(), 10);
+        assert_eq!(end, SearchEnd::Truncated);
+        assert_eq!(hits.len(), 10);
+    }
+
+    #[test]
+    fn test_cancelled_search_stops_and_drops_pending_hits() {
+        let content = "match\n".repeat(1000);
+        let (_dir, repo) = repo_with(&[("a.txt", content.as_bytes())]);
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let mut delivered = 0;
+        let end = search(
+            &repo,
+            "match",
+            SearchOptions::default(),
+            5000,
+            &cancel,
+            &mut |batch| delivered += batch.len(),
+        )
+        .unwrap();
+        assert_eq!(end, SearchEnd::Cancelled);
+        assert_eq!(delivered, 0);
+
+        // Cancelled from the first batch on: nothing more arrives
+        let cancel = CancelToken::default();
+        let mut batches = 0;
+        let end = search(
+            &repo,
+            "match",
+            SearchOptions::default(),
+            5000,
+            &cancel,
+            &mut |_| {
+                batches += 1;
+                cancel.cancel();
+            },
+        )
+        .unwrap();
+        assert_eq!(end, SearchEnd::Cancelled);
+        assert_eq!(batches, 1);
+    }
+
+    #[test]
+    fn test_rows_group_hits_by_file() {
+        let hit = |path: &str, line| SearchHit {
+            path: PathBuf::from(path),
+            line,
+            column: 1,
+            preview: String::new(),
+        };
+        let mut search = WorkspaceSearch::default();
+        search.push_hits(vec![hit("a", 1), hit("a", 4), hit("b", 2)]);
+        assert_eq!(
+            search.rows(),
+            vec![
+                SearchRow::File {
+                    path: Path::new("a"),
+                    hits: 2
+                },
+                SearchRow::Hit(0),
+                SearchRow::Hit(1),
+                SearchRow::File {
+                    path: Path::new("b"),
+                    hits: 1
+                },
+                SearchRow::Hit(2),
+            ]
+        );
+        assert_eq!(search.file_count(), 2);
+        search.move_selection(-1);
+        assert_eq!(search.selected, 2);
+    }
+}
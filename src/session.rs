@@ -1,12 +1,35 @@
 //! Session management - each worktree has its own session with terminal
 
+use crate::activity::{ActivityEvent, SessionEvents};
+use crate::attention::{AttentionReason, NotificationSettings, SessionAttention};
+use crate::bell::BellSettings;
+use crate::busy;
+use crate::command_history::TerminalHistory;
 use crate::git::Worktree;
-use crate::terminal::TerminalView;
+use crate::hooks::HookSubject;
+use crate::multiplexer::{self, TmuxNames};
+use crate::scrollback::{self, SessionScrollback, TerminalScrollback};
+use crate::session_state::{self, PendingSave, SessionState};
+use crate::settings::Settings;
+use crate::shell_escape::{self, Shell};
+use crate::stable_hash;
+use crate::template::TemplateSet;
+use crate::terminal::ViewTerminals;
+use crate::terminal::{TerminalTail, TerminalView};
 use crate::theme;
-use gpui::{AppContext, Context, Entity};
+use crate::transcript::{self, SessionTranscript};
+use gpui::{App, Entity};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod terminals;
+
+pub use terminals::{TerminalFactory, TerminalHandle, TerminalSpec};
 
 /// Color for visual identification of sessions
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct SessionColor {
     pub primary: u32,
 }
@@ -40,11 +63,59 @@ impl SessionColor {
         },
     ];
 
-    pub fn for_index(index: usize) -> Self {
-        Self::COLORS[index % Self::COLORS.len()]
+    /// Default color of the session for `worktree_name`. Picked from a hash
+    /// of the name, so it stays the same across restarts and whatever order
+    /// worktrees are listed in.
+    pub fn for_name(worktree_name: &str) -> Self {
+        let hash = stable_hash::fnv1a_32(worktree_name.as_bytes());
+        Self::COLORS[hash as usize % Self::COLORS.len()]
+    }
+
+    /// Parse a `#rrggbb` (or `rrggbb`) hex color, as stored in git config
+    pub fn parse_hex(value: &str) -> Option<Self> {
+        let digits = value.trim().trim_start_matches('#');
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .map(|primary| Self { primary })
+    }
+
+    /// `#rrggbb`, as stored in git config
+    pub fn to_hex(self) -> String {
+        format!("#{:06x}", self.primary)
+    }
+
+    /// The color as drawn on `background`: darkened on a light one, where
+    /// the palette's pastel tones would lack contrast
+    pub fn on(self, background: u32) -> u32 {
+        if luminance(background) < 0.5 {
+            return self.primary;
+        }
+        let darken = |shift: u32| (((self.primary >> shift) & 0xff) * 55 / 100) << shift;
+        darken(16) | darken(8) | darken(0)
+    }
+
+    /// `background` with `percent` of the color mixed in, for tinted areas
+    /// and the borders of panes that are not focused
+    pub fn tint(self, background: u32, percent: u32) -> u32 {
+        let color = self.on(background);
+        let mix = |shift: u32| {
+            let over = (color >> shift) & 0xff;
+            let under = (background >> shift) & 0xff;
+            ((over * percent + under * (100 - percent)) / 100) << shift
+        };
+        mix(16) | mix(8) | mix(0)
     }
 }
 
+/// Relative brightness of an `0xrrggbb` color, 0.0 (black) to 1.0 (white)
+fn luminance(color: u32) -> f32 {
+    let channel = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.0;
+    0.2126 * channel(16) + 0.7152 * channel(8) + 0.0722 * channel(0)
+}
+
 /// Session status
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SessionStatus {
@@ -54,6 +125,8 @@ pub enum SessionStatus {
     Running,
     /// Terminal is closed/not started
     Stopped,
+    /// The worktree's directory is gone; no terminal can start
+    Missing,
 }
 
 impl SessionStatus {
@@ -62,6 +135,7 @@ impl SessionStatus {
             SessionStatus::Focused => "●",
             SessionStatus::Running => "○",
             SessionStatus::Stopped => "◌",
+            SessionStatus::Missing => "✕",
         }
     }
 }
@@ -70,61 +144,206 @@ impl SessionStatus {
 /// Each session can have multiple terminals (1:N relationship).
 /// Session : Worktree = 1:1 (immutable after creation)
 /// Session : Terminal = 1:N
-pub struct Session {
+pub struct Session<T: TerminalHandle = Entity<TerminalView>> {
     worktree: Worktree,
-    terminals: Vec<Entity<TerminalView>>,
+    terminals: Vec<T>,
     active_terminal_index: usize,
     terminal_default_directory: Option<std::path::PathBuf>,
-    color: SessionColor,
+    /// Shell command for new terminals (None = the user's default shell)
+    terminal_shell: Option<String>,
+    /// Quoting for paths typed into the terminals (git config
+    /// `sashiki.session.<name>.shellQuoting`); None follows the shell running
+    shell_quoting: Option<Shell>,
+    /// Color picked by the user (git config `sashiki.session.<name>.color`);
+    /// None uses the one derived from the worktree name
+    color: Option<SessionColor>,
     status: SessionStatus,
     /// Whether to show in parallel mode
     visible_in_parallel: bool,
+    /// Shared with the session's terminals, which raise it
+    attention: Rc<SessionAttention>,
+    /// Sidebar groups (git config `sashiki.session.<name>.tags`)
+    tags: Vec<String>,
+    /// Explicit read-only choice (git config `sashiki.session.<name>.readOnly`);
+    /// None follows the worktree's lock
+    read_only: Option<bool>,
+    /// Task description from the create-worktree dialog
+    /// (git config `sashiki.session.<name>.description`)
+    description: Option<String>,
+    /// Output recording, shared with the terminals' reader threads
+    transcript: Arc<SessionTranscript>,
+    /// Where the terminals' scrollback is kept across restarts
+    scrollback: Rc<SessionScrollback>,
+    /// Command histories and activity, shared with the terminals
+    state: Rc<SessionState>,
+    /// Activity timeline, shared with the attention
+    events: Rc<SessionEvents>,
+    /// Whether automatic checkpoints are taken (see `checkpoints`)
+    auto_checkpoint: bool,
+    /// Names of the tmux sessions terminals run in, None for bare shells
+    /// (see `multiplexer`)
+    multiplexer: Option<TmuxNames>,
+    /// Main worktree of the repository, whose hooks the session's events run
+    repo: Option<PathBuf>,
+    /// Whether the terminals were attached to tmux sessions left running
+    /// by an earlier run
+    restored: bool,
+    /// Whether the worktree's directory was found missing (see
+    /// `SessionManager::mark_missing_worktrees`)
+    missing: bool,
 }
 
-impl Session {
+impl<T: TerminalHandle> Session<T> {
     /// Create a new session for a worktree (no terminals yet)
-    pub fn new(worktree: Worktree, color_index: usize) -> Self {
-        Self {
+    pub fn new(worktree: Worktree) -> Self {
+        let state = Rc::new(SessionState::default());
+        let events = Rc::new(SessionEvents::new(state.clone()));
+        let attention = SessionAttention::new(worktree.branch.as_deref().unwrap_or(&worktree.name))
+            .with_events(events.clone());
+        let transcript = SessionTranscript::new(&worktree.name);
+        let session = Self {
             worktree,
             terminals: Vec::new(),
             active_terminal_index: 0,
             terminal_default_directory: None,
-            color: SessionColor::for_index(color_index),
+            terminal_shell: None,
+            shell_quoting: None,
+            color: None,
             status: SessionStatus::Stopped,
             visible_in_parallel: false,
-        }
+            attention: Rc::new(attention),
+            tags: Vec::new(),
+            read_only: None,
+            description: None,
+            transcript: Arc::new(transcript),
+            scrollback: Rc::default(),
+            state,
+            events,
+            auto_checkpoint: false,
+            multiplexer: None,
+            repo: None,
+            restored: false,
+            missing: false,
+        };
+        session.events.set_subject(session.hook_subject());
+        session
     }
 
-    /// Add a new terminal to this session and make it active
-    pub fn add_terminal<V: 'static>(&mut self, cx: &mut Context<V>) {
-        let path = self
-            .terminal_default_directory
+    /// Where new terminals start: the default directory or the worktree
+    pub fn terminal_directory(&self) -> std::path::PathBuf {
+        self.terminal_default_directory
             .clone()
-            .unwrap_or_else(|| self.worktree.path.clone());
-        self.add_terminal_in_directory(path, cx);
+            .unwrap_or_else(|| self.worktree.path.clone())
+    }
+
+    /// Start a shell in `path` after the existing terminals
+    fn start_terminal(
+        &mut self,
+        path: std::path::PathBuf,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) {
+        self.start_terminal_running(path, None, Vec::new(), factory, cx);
+    }
+
+    /// Start a terminal in `path` running `program` (None = the session's
+    /// shell) with `env` set, after the existing ones. In tmux it gets the
+    /// lowest number no other terminal of the session uses.
+    fn start_terminal_running(
+        &mut self,
+        path: std::path::PathBuf,
+        program: Option<String>,
+        env: Vec<(String, String)>,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) {
+        let used: Vec<String> = self
+            .terminals
+            .iter()
+            .filter_map(|terminal| terminal.multiplexed(cx))
+            .collect();
+        let multiplexed = self.multiplexer.as_ref().map(|names| {
+            (0..)
+                .map(|number| names.session(&self.worktree.name, number))
+                .find(|name| !used.contains(name))
+                .unwrap_or_default()
+        });
+        self.start_terminal_in(path, multiplexed, program, env, factory, cx);
+    }
+
+    /// Start a terminal in `path`, in the tmux session `multiplexed` if given
+    fn start_terminal_in(
+        &mut self,
+        path: std::path::PathBuf,
+        multiplexed: Option<String>,
+        program: Option<String>,
+        env: Vec<(String, String)>,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) {
+        let program = program.or_else(|| self.terminal_shell.clone());
+        let (shell, env) = match &multiplexed {
+            Some(name) => (
+                Some(multiplexer::command(name, program.as_deref(), &env)),
+                Vec::new(),
+            ),
+            None => (program, env),
+        };
+        let spec = TerminalSpec {
+            working_dir: path,
+            shell,
+            env,
+            attention: self.attention.clone(),
+            transcript: self.transcript.clone(),
+            history: TerminalHistory::new(self.state.clone(), self.terminals.len()),
+            scrollback: TerminalScrollback::new(self.scrollback.clone(), self.terminals.len()),
+            multiplexed,
+        };
+        self.terminals.push(factory.create(spec, cx));
+        self.events.record(ActivityEvent::TerminalStarted {
+            count: self.terminals.len(),
+        });
+    }
+
+    /// Add a new terminal to this session and make it active
+    fn add_terminal(&mut self, factory: &dyn TerminalFactory<T>, cx: &mut T::Cx) {
+        self.add_terminal_in_directory(self.terminal_directory(), factory, cx);
     }
 
     /// Add a new terminal with a custom working directory
-    pub fn add_terminal_in_directory<V: 'static>(
+    fn add_terminal_in_directory(
         &mut self,
         path: std::path::PathBuf,
-        cx: &mut Context<V>,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
     ) {
-        let terminal = cx.new(|cx| TerminalView::new_with_directory(path, cx));
-        self.terminals.push(terminal);
+        self.add_terminal_running(path, None, Vec::new(), factory, cx);
+    }
+
+    /// Add a terminal running `program` (None = the shell) with `env` set
+    /// and make it active
+    fn add_terminal_running(
+        &mut self,
+        path: std::path::PathBuf,
+        program: Option<String>,
+        env: Vec<(String, String)>,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) -> T {
+        self.start_terminal_running(path, program, env, factory, cx);
         self.active_terminal_index = self.terminals.len() - 1;
         self.status = SessionStatus::Running;
+        self.terminals[self.active_terminal_index].clone()
     }
 
     /// Start a terminal if none exists (convenience method for initial terminal)
-    pub fn ensure_terminal<V: 'static>(&mut self, cx: &mut Context<V>) {
+    fn ensure_terminal(&mut self, factory: &dyn TerminalFactory<T>, cx: &mut T::Cx) {
         if self.terminals.is_empty() {
-            self.add_terminal(cx);
+            self.add_terminal(factory, cx);
         }
     }
 
     /// Remove a terminal by index
-    #[allow(dead_code)]
     pub fn remove_terminal(&mut self, index: usize) {
         if index >= self.terminals.len() {
             return;
@@ -150,58 +369,107 @@ impl Session {
 
     /// Remove all terminals
     pub fn clear_terminals(&mut self) {
+        if !self.terminals.is_empty() {
+            self.events.record(ActivityEvent::TerminalsStopped);
+        }
         self.terminals.clear();
         self.active_terminal_index = 0;
         self.status = SessionStatus::Stopped;
+        self.restored = false;
     }
 
-    /// Get the active terminal
-    pub fn active_terminal(&self) -> Option<&Entity<TerminalView>> {
-        self.terminals.get(self.active_terminal_index)
+    /// Ask the shells still running to exit, then remove all terminals
+    fn stop_terminals(&mut self, cx: &mut T::Cx) {
+        for terminal in &self.terminals {
+            if terminal.is_running(cx) {
+                terminal.shutdown(cx);
+            }
+        }
+        self.clear_terminals();
     }
 
-    /// Switch to a specific terminal by index
-    #[allow(dead_code)]
-    pub fn switch_terminal(&mut self, index: usize) {
-        if index < self.terminals.len() {
-            self.active_terminal_index = index;
+    /// End the programs of all terminals, tmux sessions included, then
+    /// remove the terminals
+    fn kill_terminals(&mut self, cx: &mut T::Cx) {
+        for terminal in &self.terminals {
+            terminal.kill(cx);
         }
+        self.clear_terminals();
     }
 
-    /// Switch to next terminal within this session
-    #[allow(dead_code)]
-    pub fn next_terminal(&mut self) {
-        if !self.terminals.is_empty() {
-            self.active_terminal_index = (self.active_terminal_index + 1) % self.terminals.len();
+    /// Close terminal `index`: detach it from its tmux session, or end its
+    /// programs when `kill` (a bare shell is always asked to exit)
+    pub fn close_terminal(&mut self, index: usize, kill: bool, cx: &mut T::Cx) {
+        let Some(terminal) = self.terminals.get(index) else {
+            return;
+        };
+        if kill {
+            terminal.kill(cx);
+        } else if terminal.is_running(cx) {
+            terminal.shutdown(cx);
         }
+        self.remove_terminal(index);
     }
 
-    /// Switch to previous terminal within this session
-    #[allow(dead_code)]
-    pub fn prev_terminal(&mut self) {
-        if !self.terminals.is_empty() {
-            self.active_terminal_index = if self.active_terminal_index == 0 {
-                self.terminals.len() - 1
-            } else {
-                self.active_terminal_index - 1
-            };
+    /// Attach terminals to the tmux sessions `numbers` left running for this
+    /// session's worktree
+    fn restore_terminals(
+        &mut self,
+        numbers: &[usize],
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) {
+        let Some(names) = self.multiplexer.clone() else {
+            return;
+        };
+        if numbers.is_empty() || !self.terminals.is_empty() {
+            return;
         }
+        let path = self.terminal_directory();
+        for &number in numbers {
+            let name = names.session(&self.worktree.name, number);
+            self.start_terminal_in(path.clone(), Some(name), None, Vec::new(), factory, cx);
+        }
+        self.active_terminal_index = 0;
+        self.status = SessionStatus::Running;
+        self.restored = true;
+    }
+
+    /// Whether the terminals were attached to tmux sessions that outlived
+    /// the last run of Sashiki
+    pub fn is_restored(&self) -> bool {
+        self.restored
+    }
+
+    /// Get the active terminal
+    pub fn active_terminal(&self) -> Option<&T> {
+        self.terminals.get(self.active_terminal_index)
+    }
+
+    /// Where the systems noticing what happens in this session report it
+    pub fn events(&self) -> &SessionEvents {
+        &self.events
+    }
+
+    /// What is kept of this session across restarts (see `session_state`)
+    pub fn state(&self) -> &SessionState {
+        &self.state
     }
 
     /// Get a terminal by index
-    pub fn get_terminal(&self, index: usize) -> Option<&Entity<TerminalView>> {
+    pub fn get_terminal(&self, index: usize) -> Option<&T> {
         self.terminals.get(index)
     }
 
     /// Ensure the session has at least `count` terminals (without changing active_terminal_index)
-    pub fn ensure_terminal_count<V: 'static>(&mut self, count: usize, cx: &mut Context<V>) {
+    fn ensure_terminal_count(
+        &mut self,
+        count: usize,
+        factory: &dyn TerminalFactory<T>,
+        cx: &mut T::Cx,
+    ) {
         while self.terminals.len() < count {
-            let path = self
-                .terminal_default_directory
-                .clone()
-                .unwrap_or_else(|| self.worktree.path.clone());
-            let terminal = cx.new(|cx| TerminalView::new_with_directory(path, cx));
-            self.terminals.push(terminal);
+            self.start_terminal(self.terminal_directory(), factory, cx);
             if self.status == SessionStatus::Stopped {
                 self.status = SessionStatus::Running;
             }
@@ -214,13 +482,11 @@ impl Session {
     }
 
     /// Get the number of terminals in this session
-    #[allow(dead_code)]
     pub fn terminal_count(&self) -> usize {
         self.terminals.len()
     }
 
     /// Get reference to the worktree (read-only)
-    #[allow(dead_code)]
     pub fn worktree(&self) -> &Worktree {
         &self.worktree
     }
@@ -230,18 +496,64 @@ impl Session {
         &self.worktree.path
     }
 
+    /// The session as its events tell hooks about it
+    pub fn hook_subject(&self) -> HookSubject {
+        HookSubject::new(
+            &self.worktree.name,
+            self.worktree.branch.as_deref(),
+            &self.worktree.path,
+        )
+        .in_repo(self.repo.clone())
+    }
+
     /// Update worktree information (branch, locked status)
     /// Note: path and is_main cannot be changed as they are immutable identifiers
     pub fn update_worktree_info(&mut self, updated: &Worktree) {
         debug_assert_eq!(self.worktree.path, updated.path, "Worktree path mismatch");
+        if updated.branch != self.worktree.branch
+            && let Some(branch) = &updated.branch
+        {
+            self.events.record(ActivityEvent::BranchSwitched {
+                branch: branch.clone(),
+            });
+        }
         self.worktree.branch = updated.branch.clone();
         self.worktree.locked = updated.locked;
+        self.events.set_subject(self.hook_subject());
+        self.attention
+            .set_label(updated.branch.as_deref().unwrap_or(&self.worktree.name));
     }
 
     pub fn set_terminal_default_directory(&mut self, path: Option<std::path::PathBuf>) {
         self.terminal_default_directory = path;
     }
 
+    pub fn set_terminal_shell(&mut self, shell: Option<String>) {
+        self.terminal_shell = shell;
+    }
+
+    pub fn set_notification_settings(&self, notifications: NotificationSettings) {
+        self.attention.set_notifications(notifications);
+    }
+
+    pub fn set_bell_settings(&self, bell: BellSettings) {
+        self.attention.set_bell(bell);
+    }
+
+    /// Whether the terminal bell gets no response in this session
+    pub fn is_bell_muted(&self) -> bool {
+        self.attention.is_bell_muted()
+    }
+
+    pub fn set_bell_muted(&self, muted: bool) {
+        self.attention.set_bell_muted(muted);
+    }
+
+    /// Why the session wants attention (cleared when it becomes active)
+    pub fn attention(&self) -> Option<AttentionReason> {
+        self.attention.reason()
+    }
+
     /// Get display name (worktree name)
     pub fn name(&self) -> &str {
         &self.worktree.name
@@ -265,11 +577,79 @@ impl Session {
     /// Get session color
     pub fn color(&self) -> SessionColor {
         self.color
+            .unwrap_or_else(|| SessionColor::for_name(&self.worktree.name))
+    }
+
+    /// Whether the color was picked by the user rather than derived
+    pub fn has_custom_color(&self) -> bool {
+        self.color.is_some()
     }
 
     /// Get session status
     pub fn status(&self) -> SessionStatus {
-        self.status
+        if self.missing {
+            SessionStatus::Missing
+        } else {
+            self.status
+        }
+    }
+
+    /// Whether the worktree's directory was gone when last looked for
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags;
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Whether Sashiki refuses to change the worktree (discarding, restoring,
+    /// syncing, opening files for editing). The terminal is not affected.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.unwrap_or(self.worktree.locked)
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = Some(read_only);
+    }
+
+    pub fn auto_checkpoint(&self) -> bool {
+        self.auto_checkpoint
+    }
+
+    /// Err with a message for the error dialog when `action` would change a
+    /// read-only session's worktree
+    pub fn check_writable(&self, action: &str) -> Result<(), String> {
+        if self.is_read_only() {
+            return Err(format!(
+                "Cannot {}: the session \"{}\" is read-only.\nTurn read-only off with the lock in the sidebar first.",
+                action,
+                self.name()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the terminals' output is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.transcript.is_enabled()
+    }
+
+    pub fn set_recording(&self, enabled: bool) {
+        self.transcript.set_enabled(enabled);
+    }
+
+    /// Where the transcript is recorded (set by `SessionManager::load_transcripts`)
+    pub fn transcript_dir(&self) -> Option<std::path::PathBuf> {
+        self.transcript.dir()
     }
 
     /// Check if visible in parallel mode
@@ -293,6 +673,77 @@ impl Session {
     }
 }
 
+/// What only the terminal views can tell
+impl Session {
+    /// Shell at the prompt of the active terminal, whose quoting paths typed
+    /// into it need; None while another program (an agent, say) has the
+    /// foreground
+    pub fn prompt_shell(&self, cx: &App) -> Option<Shell> {
+        let view = self.active_terminal()?.read(cx);
+        let running = view
+            .shell_program()
+            .and_then(|name| Shell::from_program(&name))
+            .or_else(|| self.terminal_shell.as_deref().and_then(Shell::from_program));
+        shell_escape::prompt_shell(
+            self.shell_quoting,
+            view.foreground_program().as_deref(),
+            running,
+        )
+    }
+
+    /// Window title reported by the program in a terminal (OSC 0/2), if any
+    pub fn terminal_title(&self, index: usize, cx: &App) -> Option<String> {
+        self.terminals
+            .get(index)
+            .and_then(|t| t.read(cx).title().map(|s| s.to_string()))
+    }
+
+    /// Window title of the active terminal
+    pub fn active_terminal_title(&self, cx: &App) -> Option<String> {
+        self.terminal_title(self.active_terminal_index, cx)
+    }
+
+    /// When the active terminal last printed anything
+    pub fn last_output(&self, cx: &App) -> Option<Instant> {
+        self.active_terminal()
+            .and_then(|t| t.read(cx).last_output())
+    }
+
+    /// Last `rows` rows of the active terminal (see `TerminalView::tail`)
+    pub fn terminal_tail(&self, rows: usize, cx: &App) -> Option<TerminalTail> {
+        self.active_terminal().map(|t| t.read(cx).tail(rows))
+    }
+
+    /// What keeps the terminals of this session busy, or None if they are
+    /// idle (see `busy`)
+    pub fn activity_summary(&self, cx: &App) -> Option<String> {
+        let now = Instant::now();
+        let activities: Vec<_> = self
+            .terminals
+            .iter()
+            .map(|t| t.read(cx).activity(now))
+            .collect();
+        busy::describe(&activities)
+    }
+
+    /// Process ids of the shells of this session's terminals
+    pub fn shell_pids(&self, cx: &App) -> Vec<u32> {
+        self.terminals
+            .iter()
+            .filter_map(|t| t.read(cx).shell_pid())
+            .collect()
+    }
+
+    /// Time since a terminal of this session last printed anything
+    pub fn output_age(&self, cx: &App) -> Option<Duration> {
+        let now = Instant::now();
+        self.terminals
+            .iter()
+            .filter_map(|t| t.read(cx).activity(now).output_age)
+            .min()
+    }
+}
+
 /// Layout mode for terminal operation
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum LayoutMode {
@@ -303,58 +754,262 @@ pub enum LayoutMode {
     Parallel,
 }
 
+/// Sessions sharing a tag (see `SessionManager::grouped_sessions`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionGroup {
+    /// None for the sessions without tags
+    pub tag: Option<String>,
+    /// Indices into `SessionManager::sessions`
+    pub indices: Vec<usize>,
+}
+
+/// Split a comma-separated tag list, trimming blanks and a leading `#` and
+/// dropping duplicates
+pub fn parse_tags(value: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in value.split(',') {
+        let tag = tag.trim().trim_start_matches('#').trim();
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
 /// Manages all sessions (one per worktree)
-#[derive(Default)]
-pub struct SessionManager {
-    sessions: Vec<Session>,
+pub struct SessionManager<T: TerminalHandle = Entity<TerminalView>> {
+    sessions: Vec<Session<T>>,
+    /// Indices into `sessions` in the order the sidebar shows them (see
+    /// `move_session`). Every other method takes and returns indices into
+    /// `sessions`, which a reorder leaves as they are.
+    order: Vec<usize>,
     active_index: usize,
     layout_mode: LayoutMode,
+    /// Shell command given to every session (see `set_terminal_shell`)
+    terminal_shell: Option<String>,
+    /// tmux naming given to every session (see `set_multiplexer`)
+    multiplexer: Option<TmuxNames>,
+    /// Repository given to every session (see `set_repository`)
+    repo: Option<PathBuf>,
+    notifications: NotificationSettings,
+    bell: BellSettings,
+    /// Starts the terminals of all sessions
+    factory: Rc<dyn TerminalFactory<T>>,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_factory(Rc::new(ViewTerminals))
+    }
+
+    /// Busy sessions as "branch — what it is doing", for the quit guard
+    pub fn busy_sessions(&self, cx: &App) -> Vec<String> {
+        self.sessions
+            .iter()
+            .filter_map(|s| {
+                let summary = s.activity_summary(cx)?;
+                let label = s.branch().unwrap_or(s.name());
+                Some(format!("{} — {}", label, summary))
+            })
+            .collect()
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TerminalHandle> SessionManager<T> {
+    /// Sessions whose terminals `factory` starts
+    pub fn with_factory(factory: Rc<dyn TerminalFactory<T>>) -> Self {
+        Self {
+            sessions: Vec::new(),
+            order: Vec::new(),
+            active_index: 0,
+            layout_mode: LayoutMode::default(),
+            terminal_shell: None,
+            multiplexer: None,
+            repo: None,
+            notifications: NotificationSettings::default(),
+            bell: BellSettings::default(),
+            factory,
+        }
     }
 
     /// Initialize sessions from worktrees (no terminals yet)
     pub fn init_from_worktrees(&mut self, worktrees: Vec<Worktree>) {
         self.sessions.clear();
-        for (i, worktree) in worktrees.into_iter().enumerate() {
-            let session = Session::new(worktree, i);
+        for worktree in worktrees {
+            let session = self.new_session(worktree);
             self.sessions.push(session);
         }
+        self.order = (0..self.sessions.len()).collect();
         self.active_index = 0;
+        self.sync_attention_active();
+    }
+
+    /// Create a session with the manager-wide terminal settings applied
+    fn new_session(&self, worktree: Worktree) -> Session<T> {
+        let mut session = Session::new(worktree);
+        session.set_terminal_shell(self.terminal_shell.clone());
+        session.multiplexer = self.multiplexer.clone();
+        session.repo = self.repo.clone();
+        session.events.set_subject(session.hook_subject());
+        session.set_notification_settings(self.notifications);
+        session.set_bell_settings(self.bell);
+        session
+    }
+
+    /// Only the active session is exempt from (and cleared of) attention
+    fn sync_attention_active(&self) {
+        for (i, session) in self.sessions.iter().enumerate() {
+            session.attention.set_active(i == self.active_index);
+        }
+    }
+
+    /// Session `index` if terminals can start in it (its worktree's
+    /// directory is there)
+    fn startable_session(&mut self, index: usize) -> Option<&mut Session<T>> {
+        self.sessions.get_mut(index).filter(|s| !s.missing)
+    }
+
+    /// Record which sessions' worktree directories are gone, as told by
+    /// `is_missing` (stat calls, so the manager itself never touches the
+    /// disk). Returns the names of the sessions newly found missing.
+    pub fn mark_missing_worktrees(
+        &mut self,
+        is_missing: impl Fn(&std::path::Path) -> bool,
+    ) -> Vec<String> {
+        let mut newly_missing = Vec::new();
+        for session in &mut self.sessions {
+            let missing = is_missing(session.worktree_path());
+            if missing && !session.missing {
+                newly_missing.push(session.worktree.name.clone());
+            }
+            session.missing = missing;
+        }
+        newly_missing
     }
 
     /// Ensure the session has at least one terminal (starts one if none exist)
-    pub fn ensure_session_terminal<V: 'static>(&mut self, index: usize, cx: &mut Context<V>) {
-        if let Some(session) = self.sessions.get_mut(index) {
-            session.ensure_terminal(cx);
+    pub fn ensure_session_terminal(&mut self, index: usize, cx: &mut T::Cx) {
+        if let Some(session) = self.startable_session(index) {
+            session.ensure_terminal(&*self.factory, cx);
         }
     }
 
     /// Ensure the active session has at least one terminal
-    pub fn ensure_active_session_terminal<V: 'static>(&mut self, cx: &mut Context<V>) {
+    pub fn ensure_active_session_terminal(&mut self, cx: &mut T::Cx) {
         self.ensure_session_terminal(self.active_index, cx);
     }
 
     /// Ensure the active session has a terminal, using a custom working directory
-    pub fn ensure_active_session_terminal_in<V: 'static>(
+    pub fn ensure_active_session_terminal_in(
         &mut self,
         directory: std::path::PathBuf,
-        cx: &mut Context<V>,
+        cx: &mut T::Cx,
     ) {
-        if let Some(session) = self.sessions.get_mut(self.active_index) {
+        self.ensure_session_terminal_in(self.active_index, directory, cx);
+    }
+
+    /// Ensure a session has a terminal, using a custom working directory
+    pub fn ensure_session_terminal_in(
+        &mut self,
+        index: usize,
+        directory: std::path::PathBuf,
+        cx: &mut T::Cx,
+    ) {
+        if let Some(session) = self.startable_session(index) {
             session.set_terminal_default_directory(Some(directory.clone()));
             if session.terminals.is_empty() {
-                session.add_terminal_in_directory(directory, cx);
+                session.add_terminal_in_directory(directory, &*self.factory, cx);
             }
         }
     }
 
-    pub fn apply_terminal_default_directory_to_all(&mut self, relative_path: Option<&str>) {
+    /// Start a terminal in session `index` running `program` (None = the
+    /// shell) in `directory` with `env` set, and make it the session's
+    /// active one
+    pub fn add_session_terminal_running(
+        &mut self,
+        index: usize,
+        directory: std::path::PathBuf,
+        program: Option<String>,
+        env: Vec<(String, String)>,
+        cx: &mut T::Cx,
+    ) -> Option<T> {
+        let session = self.startable_session(index)?;
+        Some(session.add_terminal_running(directory, program, env, &*self.factory, cx))
+    }
+
+    /// Shell command for terminals started from now on, in all current and future sessions
+    pub fn set_terminal_shell(&mut self, shell: Option<String>) {
+        for session in &mut self.sessions {
+            session.set_terminal_shell(shell.clone());
+        }
+        self.terminal_shell = shell;
+    }
+
+    /// Run terminals started from now on in tmux sessions named by `names`
+    /// (None = bare shells), in all current and future sessions
+    pub fn set_multiplexer(&mut self, names: Option<TmuxNames>) {
+        for session in &mut self.sessions {
+            session.multiplexer = names.clone();
+        }
+        self.multiplexer = names;
+    }
+
+    /// Tag the events of all current and future sessions with the
+    /// repository whose main worktree is `repo`, so they run its hooks
+    pub fn set_repository(&mut self, repo: PathBuf) {
+        for session in &mut self.sessions {
+            session.repo = Some(repo.clone());
+            session.events.set_subject(session.hook_subject());
+        }
+        self.repo = Some(repo);
+    }
+
+    /// Attach the sessions without terminals to the tmux sessions `listed`
+    /// that were started for their worktrees. Returns how many sessions got
+    /// terminals back.
+    pub fn restore_terminals(&mut self, listed: &[String], cx: &mut T::Cx) -> usize {
+        let Some(names) = self.multiplexer.clone() else {
+            return 0;
+        };
+        let mut restored = 0;
+        for session in self.sessions.iter_mut().filter(|s| !s.missing) {
+            let numbers = names.terminals_of(listed, &session.worktree.name);
+            session.restore_terminals(&numbers, &*self.factory, cx);
+            restored += usize::from(session.is_restored());
+        }
+        restored
+    }
+
+    /// Which attention reasons raise OS notifications, for all current and future sessions
+    pub fn set_notification_settings(&mut self, notifications: NotificationSettings) {
+        for session in &self.sessions {
+            session.set_notification_settings(notifications);
+        }
+        self.notifications = notifications;
+    }
+
+    /// Responses to the terminal bell, for all current and future sessions
+    pub fn set_bell_settings(&mut self, bell: BellSettings) {
+        for session in &self.sessions {
+            session.set_bell_settings(bell);
+        }
+        self.bell = bell;
+    }
+
+    /// Default terminal directory of every session, from the template of its branch
+    pub fn apply_terminal_default_directory_to_all(&mut self, templates: &TemplateSet) {
         for session in &mut self.sessions {
-            let path = relative_path
+            let path = templates
+                .for_branch(session.branch())
+                .working_directory
+                .as_deref()
                 .filter(|s| !s.trim().is_empty())
                 .map(|s| session.worktree_path().join(s));
             session.set_terminal_default_directory(path);
@@ -362,34 +1017,40 @@ impl SessionManager {
     }
 
     /// Ensure the active session has at least `count` terminals
-    pub fn ensure_active_session_terminal_count<V: 'static>(
-        &mut self,
-        count: usize,
-        cx: &mut Context<V>,
-    ) {
-        if let Some(session) = self.sessions.get_mut(self.active_index) {
-            session.ensure_terminal_count(count, cx);
+    pub fn ensure_active_session_terminal_count(&mut self, count: usize, cx: &mut T::Cx) {
+        if let Some(session) = self.startable_session(self.active_index) {
+            session.ensure_terminal_count(count, &*self.factory, cx);
+        }
+    }
+
+    /// Ask the shells of a session to exit and clear its terminals. Shells
+    /// in tmux are detached and keep running.
+    pub fn stop_session_terminals(&mut self, index: usize, cx: &mut T::Cx) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.stop_terminals(cx);
         }
     }
 
-    /// Add a new terminal to a session
-    #[allow(dead_code)]
-    pub fn add_terminal_to_session<V: 'static>(&mut self, index: usize, cx: &mut Context<V>) {
+    /// End the programs of a session's terminals, tmux sessions included,
+    /// and clear them, before its worktree goes away
+    pub fn kill_session_terminals(&mut self, index: usize, cx: &mut T::Cx) {
         if let Some(session) = self.sessions.get_mut(index) {
-            session.add_terminal(cx);
+            session.kill_terminals(cx);
         }
     }
 
-    /// Add a new terminal to the active session
-    #[allow(dead_code)]
-    pub fn add_terminal_to_active_session<V: 'static>(&mut self, cx: &mut Context<V>) {
-        self.add_terminal_to_session(self.active_index, cx);
+    /// Close the active terminal of the active session (see
+    /// `Session::close_terminal`)
+    pub fn close_active_terminal(&mut self, kill: bool, cx: &mut T::Cx) {
+        if let Some(session) = self.sessions.get_mut(self.active_index) {
+            session.close_terminal(session.active_terminal_index, kill, cx);
+        }
     }
 
-    /// Clear all terminals for a session (releases file handles)
-    pub fn clear_session_terminals(&mut self, index: usize) {
-        if let Some(session) = self.sessions.get_mut(index) {
-            session.clear_terminals();
+    /// Type `text` into the active terminal of the active session
+    pub fn write_to_active_terminal(&self, text: &str, cx: &mut T::Cx) {
+        if let Some(terminal) = self.active_session().and_then(|s| s.active_terminal()) {
+            terminal.write(text, cx);
         }
     }
 
@@ -400,19 +1061,34 @@ impl SessionManager {
         if self.find_session_by_path(&worktree.path).is_some() {
             return false;
         }
-        let index = self.sessions.len();
-        let session = Session::new(worktree, index);
-        self.sessions.push(session);
+        let session = self.new_session(worktree);
+        self.push_session(session);
         true
     }
 
+    /// Store `session` and show it last
+    fn push_session(&mut self, session: Session<T>) {
+        self.order.push(self.sessions.len());
+        self.sessions.push(session);
+    }
+
+    /// Drop the session at `index` from storage and from the display order
+    fn take_session(&mut self, index: usize) {
+        self.sessions.remove(index);
+        self.order.retain(|&i| i != index);
+        for i in &mut self.order {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+    }
+
     /// Find a session by worktree path
     pub fn find_session_by_path(&self, path: &std::path::Path) -> Option<usize> {
         self.sessions.iter().position(|s| s.worktree_path() == path)
     }
 
     /// Update worktree information for a session identified by path
-    #[allow(dead_code)]
     pub fn update_session_worktree(&mut self, updated: &Worktree) -> bool {
         if let Some(index) = self.find_session_by_path(&updated.path)
             && let Some(session) = self.sessions.get_mut(index)
@@ -445,7 +1121,7 @@ impl SessionManager {
             i -= 1;
             let session_path = self.sessions[i].worktree_path().to_path_buf();
             if !current_paths.contains(&session_path) {
-                self.sessions.remove(i);
+                self.take_session(i);
                 removed += 1;
                 // Adjust active_index after removal
                 if i < self.active_index {
@@ -465,13 +1141,13 @@ impl SessionManager {
                     updated += 1;
                 }
             } else {
-                // Add new session
-                let index = self.sessions.len();
-                let session = Session::new(worktree, index);
-                self.sessions.push(session);
+                // Add new session (shown last)
+                let session = self.new_session(worktree);
+                self.push_session(session);
                 added += 1;
             }
         }
+        self.sync_attention_active();
 
         (added, removed, updated)
     }
@@ -479,7 +1155,7 @@ impl SessionManager {
     /// Remove a session by index
     pub fn remove_session(&mut self, index: usize) {
         if index < self.sessions.len() && self.sessions.len() > 1 {
-            self.sessions.remove(index);
+            self.take_session(index);
             // Adjust active_index after removal
             if index < self.active_index {
                 // Removed session was before active - shift index down
@@ -491,28 +1167,39 @@ impl SessionManager {
                 }
             }
             // If index > active_index, no adjustment needed
+            self.sync_attention_active();
         }
     }
 
     /// Get all sessions
-    pub fn sessions(&self) -> &[Session] {
+    pub fn sessions(&self) -> &[Session<T>] {
         &self.sessions
     }
 
+    /// Find the session whose worktree contains `path` (the deepest one if nested)
+    pub fn find_session_containing(&self, path: &std::path::Path) -> Option<usize> {
+        self.sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| path.starts_with(s.worktree_path()))
+            .max_by_key(|(_, s)| s.worktree_path().components().count())
+            .map(|(i, _)| i)
+    }
+
     /// Get active session
-    pub fn active_session(&self) -> Option<&Session> {
+    pub fn active_session(&self) -> Option<&Session<T>> {
         self.sessions.get(self.active_index)
     }
 
     /// Get active terminal (the active terminal of the active session)
-    pub fn active_terminal(&self) -> Option<Entity<TerminalView>> {
+    pub fn active_terminal(&self) -> Option<T> {
         self.active_session()
             .and_then(|s| s.active_terminal())
             .cloned()
     }
 
     /// Get the active terminal for a specific session
-    pub fn get_session_active_terminal(&self, index: usize) -> Option<Entity<TerminalView>> {
+    pub fn get_session_active_terminal(&self, index: usize) -> Option<T> {
         self.sessions
             .get(index)
             .and_then(|s| s.active_terminal())
@@ -538,29 +1225,89 @@ impl SessionManager {
                 // Active session should always be visible in parallel mode
                 new_session.set_visible_in_parallel(true);
             }
+            self.sync_attention_active();
         }
     }
 
-    /// Switch to next session
+    /// Switch to the session shown after the active one (wrapping around)
     pub fn next_session(&mut self) {
-        if !self.sessions.is_empty() {
-            let next = (self.active_index + 1) % self.sessions.len();
+        if let Some(position) = self.display_position(self.active_index) {
+            let next = self.order[(position + 1) % self.order.len()];
             self.switch_to(next);
         }
     }
 
-    /// Switch to previous session
+    /// Switch to the session shown before the active one (wrapping around)
     pub fn prev_session(&mut self) {
-        if !self.sessions.is_empty() {
-            let prev = if self.active_index == 0 {
-                self.sessions.len() - 1
-            } else {
-                self.active_index - 1
-            };
+        if let Some(position) = self.display_position(self.active_index) {
+            let prev = self.order[(position + self.order.len() - 1) % self.order.len()];
             self.switch_to(prev);
         }
     }
 
+    /// Sessions in the order they are shown, with their indices
+    pub fn ordered_sessions(&self) -> impl Iterator<Item = (usize, &Session<T>)> {
+        self.order.iter().map(|&i| (i, &self.sessions[i]))
+    }
+
+    /// Index of the session shown at `position` (0 = top; Ctrl+1)
+    pub fn session_at(&self, position: usize) -> Option<usize> {
+        self.order.get(position).copied()
+    }
+
+    /// Where the session at `index` is shown (0 = top)
+    pub fn display_position(&self, index: usize) -> Option<usize> {
+        self.order.iter().position(|&i| i == index)
+    }
+
+    /// Show the session at `index` at `position` (clamped to the list),
+    /// shifting the ones between. Returns whether the order changed.
+    pub fn move_session(&mut self, index: usize, position: usize) -> bool {
+        let Some(from) = self.display_position(index) else {
+            return false;
+        };
+        let to = position.min(self.order.len() - 1);
+        if from == to {
+            return false;
+        }
+        let moved = self.order.remove(from);
+        self.order.insert(to, moved);
+        true
+    }
+
+    /// Move the session at `index` one place up (or down) in the list.
+    /// Returns whether it moved.
+    pub fn move_session_by_one(&mut self, index: usize, up: bool) -> bool {
+        match self.display_position(index) {
+            Some(position) if up => position > 0 && self.move_session(index, position - 1),
+            Some(position) => self.move_session(index, position + 1),
+            None => false,
+        }
+    }
+
+    /// Worktree paths in display order, as saved across restarts
+    pub fn ordered_paths(&self) -> Vec<std::path::PathBuf> {
+        self.ordered_sessions()
+            .map(|(_, s)| s.worktree_path().to_path_buf())
+            .collect()
+    }
+
+    /// Show sessions in the order of `paths` (as `ordered_paths` returned
+    /// them). Sessions not listed follow in their current order; listed
+    /// paths without a session are skipped.
+    pub fn apply_order(&mut self, paths: &[std::path::PathBuf]) {
+        let mut order: Vec<usize> = Vec::with_capacity(self.order.len());
+        let listed = paths
+            .iter()
+            .filter_map(|path| self.find_session_by_path(path));
+        for i in listed.chain(self.order.iter().copied()) {
+            if !order.contains(&i) {
+                order.push(i);
+            }
+        }
+        self.order = order;
+    }
+
     /// Get active session index
     pub fn active_index(&self) -> usize {
         self.active_index
@@ -571,6 +1318,10 @@ impl SessionManager {
         self.layout_mode
     }
 
+    pub fn set_layout_mode(&mut self, mode: LayoutMode) {
+        self.layout_mode = mode;
+    }
+
     /// Toggle between Single and Parallel mode
     pub fn toggle_layout_mode(&mut self) {
         self.layout_mode = match self.layout_mode {
@@ -586,16 +1337,192 @@ impl SessionManager {
         }
     }
 
-    /// Get sessions that should be shown in parallel mode
-    /// Note: Caller should ensure terminals exist for these sessions before rendering
-    pub fn parallel_sessions(&self) -> Vec<(usize, &Session)> {
+    /// Show a session in parallel mode
+    pub fn show_in_parallel(&mut self, index: usize) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.set_visible_in_parallel(true);
+        }
+    }
+
+    /// Get sessions that should be shown in parallel mode
+    /// Note: Caller should ensure terminals exist for these sessions before rendering
+    pub fn parallel_sessions(&self) -> Vec<(usize, &Session<T>)> {
+        self.ordered_sessions()
+            .filter(|(_, s)| s.is_visible_in_parallel())
+            .collect()
+    }
+
+    /// Load every session's tags from `settings`
+    pub fn load_tags(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.tags = settings.session_tags(session.name());
+        }
+    }
+
+    /// Load every session's task description from `settings`
+    pub fn load_descriptions(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.description = settings.session_description(session.name());
+        }
+    }
+
+    /// Load every session's read-only choice from `settings`; sessions without
+    /// one follow their lock, or `sashiki.session.mainReadOnly` for the main one
+    pub fn load_read_only(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.read_only = settings
+                .session_read_only(session.name())
+                .or((session.is_main() && settings.main_read_only()).then_some(true));
+        }
+    }
+
+    /// Load every session's quoting override from `settings`
+    pub fn load_shell_quoting(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.shell_quoting = settings.session_shell_quoting(session.name());
+        }
+    }
+
+    /// Load every session's picked color from `settings`
+    pub fn load_colors(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.color = settings.session_color(session.name());
+        }
+    }
+
+    /// Use `color` for the session at `index` (None = derived from its name)
+    pub fn set_session_color(&mut self, index: usize, color: Option<SessionColor>) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.color = color;
+        }
+    }
+
+    /// Load which sessions take automatic checkpoints from `settings`
+    pub fn load_auto_checkpoints(&mut self, settings: &Settings) {
+        for session in &mut self.sessions {
+            session.auto_checkpoint = settings.session_auto_checkpoint(session.name());
+        }
+    }
+
+    pub fn set_session_auto_checkpoint(&mut self, index: usize, enabled: bool) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.auto_checkpoint = enabled;
+        }
+    }
+
+    /// Load which sessions have their terminal bell muted from `settings`
+    pub fn load_bell_mutes(&self, settings: &Settings) {
+        for session in &self.sessions {
+            session.set_bell_muted(settings.session_bell_muted(session.name()));
+        }
+    }
+
+    /// Point each session's transcript at `<git_dir>/sashiki/transcripts` and
+    /// turn recording on where the setting asks for it
+    pub fn load_transcripts(&mut self, settings: &Settings, git_dir: &Path) {
+        for session in &self.sessions {
+            session.transcript.configure(
+                transcript::transcript_dir(git_dir, session.name()),
+                settings.session_transcript(session.name()),
+            );
+        }
+    }
+
+    /// Point each session's saved scrollback at `<git_dir>/sashiki/scrollback`
+    /// and keep it when the settings ask for it
+    pub fn load_scrollback(&self, settings: &Settings, git_dir: &Path) {
+        for session in &self.sessions {
+            session.scrollback.configure(
+                scrollback::scrollback_dir(git_dir, session.name()),
+                settings.terminal_persist_scrollback(),
+                settings.terminal_persist_scrollback_colors(),
+            );
+        }
+    }
+
+    /// Read each session's command histories and activity from its state
+    /// file in `<git_dir>/sashiki/sessions` (once per session)
+    pub fn load_session_states(&self, git_dir: &Path) {
+        for session in &self.sessions {
+            session
+                .state
+                .open(session_state::state_path(git_dir, session.name()));
+        }
+    }
+
+    /// The state files to write for the sessions whose state changed
+    pub fn take_pending_state_saves(&self) -> Vec<PendingSave> {
         self.sessions
             .iter()
-            .enumerate()
-            .filter(|(_, s)| s.is_visible_in_parallel())
+            .filter_map(|session| session.state.take_pending())
             .collect()
     }
 
+    /// Show sessions in the order saved in `<git_dir>/sashiki/sessions.json`
+    pub fn load_order(&mut self, git_dir: &Path) {
+        self.apply_order(&session_state::read_session_order(git_dir));
+    }
+
+    /// Save the display order to `<git_dir>/sashiki/sessions.json`
+    pub fn save_order(&self, git_dir: &Path) {
+        session_state::save_session_order(git_dir, self.ordered_paths());
+    }
+
+    pub fn set_session_read_only(&mut self, index: usize, read_only: bool) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.set_read_only(read_only);
+        }
+    }
+
+    pub fn set_session_tags(&mut self, index: usize, tags: Vec<String>) {
+        if let Some(session) = self.sessions.get_mut(index) {
+            session.set_tags(tags);
+        }
+    }
+
+    /// Whether any session has a tag (the sidebar is grouped only then)
+    pub fn has_tags(&self) -> bool {
+        self.sessions.iter().any(|s| !s.tags.is_empty())
+    }
+
+    /// Sessions grouped by tag for the sidebar: tags in alphabetical order,
+    /// then untagged sessions. A session with several tags is listed under
+    /// each; within a group sessions keep their display order.
+    pub fn grouped_sessions(&self) -> Vec<SessionGroup> {
+        let mut tags: Vec<&str> = self
+            .sessions
+            .iter()
+            .flat_map(|s| s.tags.iter().map(String::as_str))
+            .collect();
+        tags.sort_by_key(|t| t.to_lowercase());
+        tags.dedup();
+
+        let mut groups: Vec<SessionGroup> = tags
+            .into_iter()
+            .map(|tag| SessionGroup {
+                tag: Some(tag.to_string()),
+                indices: self
+                    .ordered_sessions()
+                    .filter(|(_, s)| s.tags.iter().any(|t| t == tag))
+                    .map(|(i, _)| i)
+                    .collect(),
+            })
+            .collect();
+
+        let untagged: Vec<usize> = self
+            .ordered_sessions()
+            .filter(|(_, s)| s.tags.is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        if !untagged.is_empty() {
+            groups.push(SessionGroup {
+                tag: None,
+                indices: untagged,
+            });
+        }
+        groups
+    }
+
     /// Check if there are any sessions
     pub fn is_empty(&self) -> bool {
         self.sessions.is_empty()
@@ -610,17 +1537,12 @@ impl SessionManager {
     pub fn running_session_count(&self) -> usize {
         self.sessions.iter().filter(|s| s.has_terminals()).count()
     }
-
-    /// Get total terminal count across all sessions
-    #[allow(dead_code)]
-    pub fn total_terminal_count(&self) -> usize {
-        self.sessions.iter().map(|s| s.terminal_count()).sum()
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::{Cell, RefCell};
     use std::path::PathBuf;
 
     fn make_worktree(name: &str, is_main: bool) -> Worktree {
@@ -636,15 +1558,34 @@ mod tests {
     // ===== SessionColor tests =====
 
     #[test]
-    fn test_session_color_for_index_cycles() {
-        let color0 = SessionColor::for_index(0);
-        let color8 = SessionColor::for_index(8);
-        assert_eq!(color0, color8);
+    fn test_session_color_from_name_is_stable() {
+        assert_eq!(
+            SessionColor::for_name("feature-login"),
+            SessionColor::for_name("feature-login")
+        );
+        // Pinned, so a change of hash shows up as a recolored session
+        assert_eq!(SessionColor::for_name(""), SessionColor::COLORS[5]);
+        assert_eq!(SessionColor::for_name("main"), SessionColor::COLORS[0]);
+
+        // Not the position in the worktree list
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![make_worktree("a", true), make_worktree("b", false)]);
+        let before = manager.sessions()[1].color();
+        manager.init_from_worktrees(vec![make_worktree("b", false), make_worktree("a", true)]);
+        assert_eq!(manager.sessions()[0].color(), before);
+    }
+
+    #[test]
+    fn test_session_color_names_spread_over_palette() {
+        let used: std::collections::HashSet<_> = (0..64)
+            .map(|i| SessionColor::for_name(&format!("worktree-{}", i)))
+            .collect();
+        assert_eq!(used.len(), SessionColor::COLORS.len());
     }
 
     #[test]
     fn test_session_color_all_unique() {
-        let colors: Vec<_> = (0..8).map(SessionColor::for_index).collect();
+        let colors = SessionColor::COLORS;
         for i in 0..8 {
             for j in (i + 1)..8 {
                 assert_ne!(
@@ -656,6 +1597,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_session_color_hex() {
+        let color = SessionColor::parse_hex("#5A92d6").unwrap();
+        assert_eq!(color.primary, 0x5a92d6);
+        assert_eq!(color.to_hex(), "#5a92d6");
+        assert_eq!(
+            SessionColor::parse_hex(" 00ff0a "),
+            Some(SessionColor { primary: 0x00ff0a })
+        );
+        assert_eq!(
+            SessionColor::parse_hex(&SessionColor::COLORS[3].to_hex()),
+            Some(SessionColor::COLORS[3])
+        );
+        assert_eq!(SessionColor::parse_hex("#fff"), None);
+        assert_eq!(SessionColor::parse_hex("#12345g"), None);
+        assert_eq!(SessionColor::parse_hex("+12345"), None);
+        assert_eq!(SessionColor::parse_hex(""), None);
+    }
+
+    #[test]
+    fn test_session_color_darkened_on_light_background() {
+        let color = SessionColor { primary: 0x5a92d6 };
+        assert_eq!(color.on(theme::BG_BASE), 0x5a92d6);
+        let darkened = color.on(0xffffff);
+        assert!(luminance(darkened) < luminance(color.primary));
+        assert_eq!(darkened, 0x315075);
+    }
+
+    #[test]
+    fn test_session_color_tint() {
+        let color = SessionColor { primary: 0xff0000 };
+        assert_eq!(color.tint(0x000000, 0), 0x000000);
+        assert_eq!(color.tint(0x000000, 100), 0xff0000);
+        assert_eq!(color.tint(0x0000ff, 50), 0x7f007f);
+    }
+
     // ===== SessionStatus tests =====
 
     #[test]
@@ -663,6 +1640,7 @@ mod tests {
         assert_eq!(SessionStatus::Focused.symbol(), "●");
         assert_eq!(SessionStatus::Running.symbol(), "○");
         assert_eq!(SessionStatus::Stopped.symbol(), "◌");
+        assert_eq!(SessionStatus::Missing.symbol(), "✕");
     }
 
     // ===== Session tests (without terminal operations) =====
@@ -670,7 +1648,7 @@ mod tests {
     #[test]
     fn test_session_new() {
         let worktree = make_worktree("test", false);
-        let session = Session::new(worktree, 0);
+        let session: Session = Session::new(worktree);
 
         assert_eq!(session.name(), "test");
         assert_eq!(session.branch(), Some("feature/test"));
@@ -685,7 +1663,7 @@ mod tests {
     #[test]
     fn test_session_main_worktree() {
         let worktree = make_worktree("main", true);
-        let session = Session::new(worktree, 0);
+        let session: Session = Session::new(worktree);
 
         assert!(session.is_main());
     }
@@ -693,7 +1671,7 @@ mod tests {
     #[test]
     fn test_session_update_worktree_info() {
         let worktree = make_worktree("test", false);
-        let mut session = Session::new(worktree, 0);
+        let mut session: Session = Session::new(worktree);
 
         let updated = Worktree {
             name: "test".to_string(),
@@ -835,6 +1813,72 @@ mod tests {
         assert!(manager.sessions()[1].is_visible_in_parallel());
     }
 
+    #[test]
+    fn test_session_manager_find_session_containing() {
+        let mut manager = SessionManager::new();
+        let mut nested = make_worktree("nested", false);
+        nested.path = PathBuf::from("/worktrees/main/vendor/nested");
+        manager.init_from_worktrees(vec![
+            make_worktree("main", true),
+            make_worktree("main-2", false),
+            nested,
+        ]);
+
+        let find = |p: &str| manager.find_session_containing(std::path::Path::new(p));
+        assert_eq!(find("/worktrees/main"), Some(0));
+        assert_eq!(find("/worktrees/main/src/lib.rs"), Some(0));
+        // Component-wise match: "main-2" is not inside "main"
+        assert_eq!(find("/worktrees/main-2/src"), Some(1));
+        assert_eq!(find("/worktrees/main/vendor/nested/src"), Some(2));
+        assert_eq!(find("/elsewhere"), None);
+    }
+
+    #[test]
+    fn test_session_manager_terminal_shell_applies_to_new_sessions() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![make_worktree("main", true)]);
+        manager.set_terminal_shell(Some("zsh -l".to_string()));
+        manager.add_session(make_worktree("feature", false));
+
+        assert_eq!(manager.len(), 2);
+        assert!(
+            manager
+                .sessions()
+                .iter()
+                .all(|s| s.terminal_shell.as_deref() == Some("zsh -l"))
+        );
+    }
+
+    #[test]
+    fn test_session_attention_clears_on_activation() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![
+            make_worktree("main", true),
+            make_worktree("feature", false),
+        ]);
+
+        // The active session never collects attention
+        manager.sessions()[0].attention.raise(AttentionReason::Bell);
+        manager.sessions()[1]
+            .attention
+            .raise(AttentionReason::Exit(1));
+        assert_eq!(manager.sessions()[0].attention(), None);
+        assert_eq!(
+            manager.sessions()[1].attention(),
+            Some(AttentionReason::Exit(1))
+        );
+
+        manager.switch_to(1);
+        assert_eq!(manager.sessions()[1].attention(), None);
+        manager.sessions()[0]
+            .attention
+            .raise(AttentionReason::Output);
+        assert_eq!(
+            manager.sessions()[0].attention(),
+            Some(AttentionReason::Output)
+        );
+    }
+
     #[test]
     fn test_session_manager_next_session() {
         let mut manager = SessionManager::new();
@@ -952,6 +1996,29 @@ mod tests {
         assert_eq!(manager.len(), 1);
     }
 
+    #[test]
+    fn test_session_manager_sync_keeps_added_worktree_outside_layout() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![make_worktree("main", true)]);
+        let outside = Worktree {
+            name: "outside".to_string(),
+            path: PathBuf::from("/elsewhere/outside"),
+            branch: Some("outside".to_string()),
+            is_main: false,
+            locked: false,
+        };
+
+        assert!(manager.add_session(outside.clone()));
+        let (added, removed, _updated) =
+            manager.sync_with_worktrees(vec![make_worktree("main", true), outside.clone()]);
+        assert_eq!((added, removed), (0, 0));
+        assert!(manager.find_session_by_path(&outside.path).is_some());
+
+        // Gone once git no longer lists it
+        manager.sync_with_worktrees(vec![make_worktree("main", true)]);
+        assert_eq!(manager.find_session_by_path(&outside.path), None);
+    }
+
     #[test]
     fn test_session_manager_sync_with_worktrees_update() {
         let mut manager = SessionManager::new();
@@ -967,6 +2034,100 @@ mod tests {
         assert!(manager.sessions()[0].is_locked());
     }
 
+    fn ordered_names<T: TerminalHandle>(manager: &SessionManager<T>) -> Vec<&str> {
+        manager.ordered_sessions().map(|(_, s)| s.name()).collect()
+    }
+
+    #[test]
+    fn test_session_manager_reorder_keeps_active_session() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![
+            make_worktree("main", true),
+            make_worktree("a", false),
+            make_worktree("b", false),
+            make_worktree("c", false),
+        ]);
+        manager.switch_to(2);
+
+        // Reordering leaves indices (and so the active session) as they are
+        assert!(manager.move_session(3, 0));
+        assert_eq!(ordered_names(&manager), vec!["c", "main", "a", "b"]);
+        assert_eq!(manager.active_index(), 2);
+        assert_eq!(manager.display_position(2), Some(3));
+        assert_eq!(manager.session_at(0), Some(3));
+        assert!(!manager.move_session(3, 0));
+
+        // Next/previous follow the display order, wrapping around
+        manager.next_session();
+        assert_eq!(manager.active_session().unwrap().name(), "c");
+        manager.prev_session();
+        assert_eq!(manager.active_session().unwrap().name(), "b");
+
+        assert!(manager.move_session_by_one(2, true));
+        assert_eq!(ordered_names(&manager), vec!["c", "main", "b", "a"]);
+        // Nothing moves past either end
+        assert!(!manager.move_session_by_one(3, true));
+        assert!(!manager.move_session_by_one(1, false));
+        assert_eq!(manager.active_session().unwrap().name(), "b");
+    }
+
+    #[test]
+    fn test_session_manager_sync_around_custom_order() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![
+            make_worktree("main", true),
+            make_worktree("a", false),
+            make_worktree("b", false),
+            make_worktree("c", false),
+        ]);
+        manager.move_session(3, 0);
+        manager.switch_to(2);
+
+        // "a" is removed, "d" is new: it goes last, the rest keep their places
+        manager.sync_with_worktrees(vec![
+            make_worktree("main", true),
+            make_worktree("b", false),
+            make_worktree("c", false),
+            make_worktree("d", false),
+        ]);
+        assert_eq!(ordered_names(&manager), vec!["c", "main", "b", "d"]);
+        assert_eq!(manager.active_session().unwrap().name(), "b");
+
+        manager.remove_session(
+            manager
+                .find_session_by_path(Path::new("/worktrees/c"))
+                .unwrap(),
+        );
+        assert_eq!(ordered_names(&manager), vec!["main", "b", "d"]);
+        assert_eq!(manager.session_at(0), Some(0));
+        assert_eq!(manager.active_session().unwrap().name(), "b");
+    }
+
+    #[test]
+    fn test_session_manager_order_round_trips() {
+        let git_dir = tempfile::tempdir().unwrap();
+        let worktrees = vec![
+            make_worktree("main", true),
+            make_worktree("a", false),
+            make_worktree("b", false),
+        ];
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(worktrees.clone());
+        manager.move_session(2, 0);
+        manager.save_order(git_dir.path());
+
+        // Restarted with a worktree gone and a new one: saved paths come
+        // first, the new session last
+        let mut restarted = SessionManager::new();
+        restarted.init_from_worktrees(vec![
+            worktrees[0].clone(),
+            make_worktree("new", false),
+            worktrees[2].clone(),
+        ]);
+        restarted.load_order(git_dir.path());
+        assert_eq!(ordered_names(&restarted), vec!["b", "main", "new"]);
+    }
+
     #[test]
     fn test_session_manager_find_session_by_path() {
         let mut manager = SessionManager::new();
@@ -979,9 +2140,751 @@ mod tests {
         assert_eq!(not_found, None);
     }
 
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags(" agents, #review ,,agents, レビュー "),
+            vec!["agents", "review", "レビュー"]
+        );
+        assert!(parse_tags(" , # ").is_empty());
+    }
+
+    #[test]
+    fn test_grouped_sessions() {
+        let mut manager = SessionManager::new();
+        manager.init_from_worktrees(vec![
+            make_worktree("s0", false),
+            make_worktree("s1", false),
+            make_worktree("s2", false),
+            make_worktree("s3", false),
+        ]);
+        assert!(!manager.has_tags());
+
+        manager.set_session_tags(0, parse_tags("review, agents"));
+        manager.set_session_tags(2, parse_tags("agents"));
+        manager.set_session_tags(3, parse_tags("Ärger, Build"));
+        assert!(manager.has_tags());
+
+        let groups: Vec<_> = manager
+            .grouped_sessions()
+            .into_iter()
+            .map(|g| (g.tag, g.indices))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                (Some("agents".to_string()), vec![0, 2]),
+                (Some("Build".to_string()), vec![3]),
+                (Some("review".to_string()), vec![0]),
+                (Some("Ärger".to_string()), vec![3]),
+                (None, vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tags_survive_reopen() {
+        use crate::git::{self, GitRepo};
+
+        let dir = tempfile::tempdir().unwrap();
+        crate::test_support::git(dir.path(), &["init", "-q"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+        repo.set_config_value(&git::session_tags_key("s0"), "agents,review")
+            .unwrap();
+        repo.set_config_value(&git::session_tags_key("s1"), "agents")
+            .unwrap();
+
+        let worktrees = || vec![make_worktree("s0", false), make_worktree("s1", false)];
+        for _ in 0..2 {
+            let repo = GitRepo::open(dir.path()).unwrap();
+            let (settings, _) = Settings::load(Some(&repo));
+            let mut manager = SessionManager::new();
+            manager.init_from_worktrees(worktrees());
+            manager.load_tags(&settings);
+            let groups = manager.grouped_sessions();
+            assert_eq!(groups[0].tag.as_deref(), Some("agents"));
+            assert_eq!(groups[0].indices, vec![0, 1]);
+            assert_eq!(groups[1].indices, vec![0]);
+        }
+
+        repo.remove_session_config("s0");
+        let (settings, _) = Settings::load(Some(&repo));
+        assert!(settings.session_tags("s0").is_empty());
+        assert_eq!(settings.session_tags("s1"), vec!["agents"]);
+    }
+
+    #[test]
+    fn test_read_only_follows_lock_until_set() {
+        let mut worktree = make_worktree("locked", false);
+        worktree.locked = true;
+        let mut session: Session = Session::new(worktree);
+        assert!(session.is_read_only());
+
+        session.set_read_only(false);
+        assert!(!session.is_read_only());
+        assert!(session.check_writable("discard changes").is_ok());
+
+        let mut session: Session = Session::new(make_worktree("feature", false));
+        assert!(!session.is_read_only());
+        session.set_read_only(true);
+        assert!(session.is_read_only());
+    }
+
+    #[test]
+    fn test_read_only_rejects_writes() {
+        let mut session: Session = Session::new(make_worktree("feature", false));
+        session.set_read_only(true);
+
+        for action in ["discard changes", "sync files", "open the file for editing"] {
+            let error = session.check_writable(action).unwrap_err();
+            assert!(error.contains(action), "{}", error);
+            assert!(error.contains("\"feature\" is read-only"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_read_only_loaded_from_config() {
+        use crate::git::{self, GitRepo};
+
+        let dir = tempfile::tempdir().unwrap();
+        crate::test_support::git(dir.path(), &["init", "-q"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        let worktrees = || {
+            let mut locked = make_worktree("locked", false);
+            locked.locked = true;
+            vec![
+                make_worktree("main", true),
+                make_worktree("s1", false),
+                locked,
+            ]
+        };
+        let load = || {
+            let (settings, _) = Settings::load(Some(&repo));
+            let mut manager = SessionManager::new();
+            manager.init_from_worktrees(worktrees());
+            manager.load_read_only(&settings);
+            manager
+                .sessions()
+                .iter()
+                .map(|s| s.is_read_only())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(load(), vec![false, false, true]);
+
+        repo.set_config_value(git::CONFIG_MAIN_READ_ONLY, "true")
+            .unwrap();
+        repo.set_config_value(&git::session_read_only_key("s1"), "true")
+            .unwrap();
+        repo.set_config_value(&git::session_read_only_key("locked"), "false")
+            .unwrap();
+        assert_eq!(load(), vec![true, true, false]);
+
+        repo.set_config_value(&git::session_read_only_key("main"), "false")
+            .unwrap();
+        assert_eq!(load(), vec![false, true, false]);
+    }
+
     #[test]
     fn test_session_manager_running_session_count() {
         let manager = SessionManager::new();
         assert_eq!(manager.running_session_count(), 0);
     }
+
+    // ===== Terminal lifecycle (recording terminals, no GUI) =====
+
+    /// What a recording terminal was started with and asked to do
+    #[derive(Default)]
+    struct FakeTerminalLog {
+        working_dir: PathBuf,
+        shell: Option<String>,
+        env: Vec<(String, String)>,
+        written: RefCell<Vec<String>>,
+        shutdowns: Cell<usize>,
+        kills: Cell<usize>,
+        exited: Cell<bool>,
+        multiplexed: Option<String>,
+    }
+
+    #[derive(Clone)]
+    struct FakeTerminal(Rc<FakeTerminalLog>);
+
+    impl FakeTerminal {
+        fn is(&self, other: &FakeTerminal) -> bool {
+            Rc::ptr_eq(&self.0, &other.0)
+        }
+    }
+
+    impl TerminalHandle for FakeTerminal {
+        type Cx = ();
+
+        fn write(&self, text: &str, _: &mut ()) {
+            self.0.written.borrow_mut().push(text.to_string());
+        }
+
+        fn shutdown(&self, _: &mut ()) {
+            self.0.shutdowns.set(self.0.shutdowns.get() + 1);
+            self.0.exited.set(true);
+        }
+
+        fn kill(&self, _: &mut ()) {
+            self.0.kills.set(self.0.kills.get() + 1);
+            self.0.exited.set(true);
+        }
+
+        fn multiplexed(&self, _: &()) -> Option<String> {
+            self.0.multiplexed.clone()
+        }
+
+        fn is_running(&self, _: &()) -> bool {
+            !self.0.exited.get()
+        }
+    }
+
+    /// Records every terminal it starts
+    #[derive(Default)]
+    struct FakeTerminals {
+        started: RefCell<Vec<FakeTerminal>>,
+    }
+
+    impl FakeTerminals {
+        fn started(&self) -> Vec<FakeTerminal> {
+            self.started.borrow().clone()
+        }
+    }
+
+    impl TerminalFactory<FakeTerminal> for FakeTerminals {
+        fn create(&self, spec: TerminalSpec, _: &mut ()) -> FakeTerminal {
+            let terminal = FakeTerminal(Rc::new(FakeTerminalLog {
+                working_dir: spec.working_dir,
+                shell: spec.shell,
+                env: spec.env,
+                multiplexed: spec.multiplexed,
+                ..Default::default()
+            }));
+            self.started.borrow_mut().push(terminal.clone());
+            terminal
+        }
+    }
+
+    /// Terminals across all sessions
+    fn total_terminal_count(manager: &SessionManager<FakeTerminal>) -> usize {
+        manager.sessions().iter().map(|s| s.terminal_count()).sum()
+    }
+
+    fn fake_manager(names: &[&str]) -> (SessionManager<FakeTerminal>, Rc<FakeTerminals>) {
+        let terminals = Rc::new(FakeTerminals::default());
+        let mut manager = SessionManager::<FakeTerminal>::with_factory(terminals.clone());
+        manager.init_from_worktrees(
+            names
+                .iter()
+                .map(|name| make_worktree(name, *name == "main"))
+                .collect(),
+        );
+        (manager, terminals)
+    }
+
+    fn tmux_names() -> TmuxNames {
+        TmuxNames::new(Path::new("/nonexistent/repo"))
+    }
+
+    #[test]
+    fn test_ensure_terminal_starts_one_in_the_worktree() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        assert_eq!(
+            manager.active_session().unwrap().status(),
+            SessionStatus::Stopped
+        );
+
+        manager.ensure_active_session_terminal(&mut ());
+        manager.ensure_active_session_terminal(&mut ());
+
+        let started = terminals.started();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].0.working_dir, PathBuf::from("/worktrees/main"));
+        assert_eq!(started[0].0.shell, None);
+        let session = manager.active_session().unwrap();
+        assert_eq!(session.terminal_count(), 1);
+        assert_eq!(session.status(), SessionStatus::Running);
+        assert!(manager.active_terminal().unwrap().is(&started[0]));
+        assert!(!manager.sessions()[1].has_terminals());
+    }
+
+    #[test]
+    fn test_new_terminals_get_shell_and_directory() {
+        let (mut manager, terminals) = fake_manager(&["main"]);
+        manager.set_terminal_shell(Some("zsh".to_string()));
+
+        manager.ensure_active_session_terminal_in(PathBuf::from("/worktrees/main/app"), &mut ());
+        // Already has a terminal: nothing started
+        manager.ensure_active_session_terminal_in(PathBuf::from("/elsewhere"), &mut ());
+        manager.ensure_active_session_terminal_count(2, &mut ());
+
+        let started = terminals.started();
+        assert_eq!(started.len(), 2);
+        for terminal in &started {
+            assert_eq!(terminal.0.shell.as_deref(), Some("zsh"));
+        }
+        assert_eq!(
+            started[0].0.working_dir,
+            PathBuf::from("/worktrees/main/app")
+        );
+        // The directory asked for last became the default
+        assert_eq!(started[1].0.working_dir, PathBuf::from("/elsewhere"));
+    }
+
+    #[test]
+    fn test_ensure_terminal_count_keeps_active_terminal() {
+        let (mut manager, terminals) = fake_manager(&["main"]);
+        manager.ensure_active_session_terminal(&mut ());
+        manager.ensure_active_session_terminal_count(3, &mut ());
+        manager.ensure_active_session_terminal_count(2, &mut ());
+
+        let started = terminals.started();
+        assert_eq!(started.len(), 3);
+        assert_eq!(manager.active_session().unwrap().terminal_count(), 3);
+        assert!(manager.active_terminal().unwrap().is(&started[0]));
+        assert_eq!(total_terminal_count(&manager), 3);
+    }
+
+    #[test]
+    fn test_missing_worktree_starts_no_terminals() {
+        let (mut manager, terminals) = fake_manager(&["main", "gone"]);
+        let gone = PathBuf::from("/worktrees/gone");
+        assert_eq!(
+            manager.mark_missing_worktrees(|path| path == gone),
+            vec!["gone"]
+        );
+        // Reported once
+        assert!(
+            manager
+                .mark_missing_worktrees(|path| path == gone)
+                .is_empty()
+        );
+
+        manager.switch_to(1);
+        manager.ensure_active_session_terminal(&mut ());
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        assert!(
+            manager
+                .add_session_terminal_running(1, gone.clone(), None, Vec::new(), &mut ())
+                .is_none()
+        );
+        assert!(terminals.started().is_empty());
+        let session = manager.active_session().unwrap();
+        assert!(session.is_missing());
+        assert_eq!(session.status(), SessionStatus::Missing);
+
+        // Back (recreated): terminals start again
+        manager.mark_missing_worktrees(|_| false);
+        manager.ensure_active_session_terminal(&mut ());
+        assert_eq!(terminals.started().len(), 1);
+        assert_eq!(
+            manager.active_session().unwrap().status(),
+            SessionStatus::Running
+        );
+    }
+
+    #[test]
+    fn test_ensure_terminal_count_starts_a_stopped_session() {
+        let (mut manager, terminals) = fake_manager(&["main"]);
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        let session = manager.active_session().unwrap();
+        assert_eq!(session.terminal_count(), 2);
+        assert_eq!(session.status(), SessionStatus::Running);
+        assert!(
+            manager
+                .active_terminal()
+                .unwrap()
+                .is(&terminals.started()[0])
+        );
+    }
+
+    #[test]
+    fn test_add_terminal_makes_it_active() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        let mut add = |index: usize, directory: &str| {
+            manager.add_session_terminal_running(
+                index,
+                PathBuf::from(directory),
+                None,
+                Vec::new(),
+                &mut (),
+            );
+        };
+        add(0, "/worktrees/main");
+        add(0, "/worktrees/main");
+        add(1, "/worktrees/feature");
+
+        let started = terminals.started();
+        assert_eq!(started.len(), 3);
+        assert!(manager.active_terminal().unwrap().is(&started[1]));
+        assert!(
+            manager
+                .get_session_active_terminal(1)
+                .unwrap()
+                .is(&started[2])
+        );
+        assert_eq!(
+            started[2].0.working_dir,
+            PathBuf::from("/worktrees/feature")
+        );
+        assert!(manager.get_session_active_terminal(2).is_none());
+    }
+
+    /// A session of the manager with `count` terminals, the one at `active`
+    /// active
+    fn session_with_terminals(
+        count: usize,
+        active: usize,
+    ) -> (SessionManager<FakeTerminal>, Vec<FakeTerminal>) {
+        let (mut manager, terminals) = fake_manager(&["main"]);
+        manager.ensure_active_session_terminal_count(count, &mut ());
+        manager.sessions[0].active_terminal_index = active;
+        (manager, terminals.started())
+    }
+
+    #[test]
+    fn test_remove_terminal_before_active_shifts_index() {
+        let (mut manager, started) = session_with_terminals(3, 2);
+        manager.sessions[0].remove_terminal(0);
+        let session = &manager.sessions()[0];
+        assert_eq!(session.terminal_count(), 2);
+        assert!(session.active_terminal().unwrap().is(&started[2]));
+    }
+
+    #[test]
+    fn test_remove_terminal_after_active_keeps_index() {
+        let (mut manager, started) = session_with_terminals(3, 0);
+        manager.sessions[0].remove_terminal(2);
+        let session = &manager.sessions()[0];
+        assert_eq!(session.terminal_count(), 2);
+        assert!(session.active_terminal().unwrap().is(&started[0]));
+    }
+
+    #[test]
+    fn test_remove_active_terminal_moves_to_next() {
+        let (mut manager, started) = session_with_terminals(3, 1);
+        manager.sessions[0].remove_terminal(1);
+        assert!(
+            manager.sessions()[0]
+                .active_terminal()
+                .unwrap()
+                .is(&started[2])
+        );
+    }
+
+    #[test]
+    fn test_remove_last_active_terminal_clamps_index() {
+        let (mut manager, started) = session_with_terminals(3, 2);
+        manager.sessions[0].remove_terminal(2);
+        assert!(
+            manager.sessions()[0]
+                .active_terminal()
+                .unwrap()
+                .is(&started[1])
+        );
+    }
+
+    #[test]
+    fn test_remove_terminal_out_of_range_is_ignored() {
+        let (mut manager, started) = session_with_terminals(2, 1);
+        manager.sessions[0].remove_terminal(5);
+        let session = &manager.sessions()[0];
+        assert_eq!(session.terminal_count(), 2);
+        assert!(session.active_terminal().unwrap().is(&started[1]));
+    }
+
+    #[test]
+    fn test_remove_every_terminal_stops_session() {
+        let (mut manager, _started) = session_with_terminals(2, 1);
+        manager.sessions[0].remove_terminal(1);
+        assert_eq!(manager.sessions()[0].status(), SessionStatus::Running);
+        manager.sessions[0].remove_terminal(0);
+        let session = &manager.sessions()[0];
+        assert!(!session.has_terminals());
+        assert!(session.active_terminal().is_none());
+        assert_eq!(session.status(), SessionStatus::Stopped);
+        assert_eq!(manager.running_session_count(), 0);
+    }
+
+    #[test]
+    fn test_switch_to_moves_focus_between_running_sessions() {
+        let (mut manager, _terminals) = fake_manager(&["main", "feature", "idle"]);
+        manager.ensure_session_terminal(0, &mut ());
+        manager.ensure_session_terminal(1, &mut ());
+        let statuses = |manager: &SessionManager<FakeTerminal>| {
+            manager
+                .sessions()
+                .iter()
+                .map(|s| s.status())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(&manager),
+            vec![
+                SessionStatus::Running,
+                SessionStatus::Running,
+                SessionStatus::Stopped
+            ]
+        );
+
+        manager.switch_to(1);
+        assert_eq!(
+            statuses(&manager),
+            vec![
+                SessionStatus::Running,
+                SessionStatus::Focused,
+                SessionStatus::Stopped
+            ]
+        );
+
+        // A session without terminals stays stopped when switched to
+        manager.switch_to(2);
+        assert_eq!(
+            statuses(&manager),
+            vec![
+                SessionStatus::Running,
+                SessionStatus::Running,
+                SessionStatus::Stopped
+            ]
+        );
+
+        manager.switch_to(0);
+        assert_eq!(statuses(&manager)[0], SessionStatus::Focused);
+    }
+
+    #[test]
+    fn test_stop_session_terminals_shuts_down_running_shells() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        manager.ensure_session_terminal(1, &mut ());
+        let started = terminals.started();
+        // The second shell already exited on its own
+        started[1].0.exited.set(true);
+        assert_eq!(manager.running_session_count(), 2);
+
+        manager.stop_session_terminals(0, &mut ());
+
+        assert_eq!(started[0].0.shutdowns.get(), 1);
+        assert_eq!(started[1].0.shutdowns.get(), 0);
+        assert_eq!(started[2].0.shutdowns.get(), 0);
+        let session = &manager.sessions()[0];
+        assert!(!session.has_terminals());
+        assert_eq!(session.status(), SessionStatus::Stopped);
+        assert_eq!(manager.running_session_count(), 1);
+        assert_eq!(total_terminal_count(&manager), 1);
+    }
+
+    #[test]
+    fn test_hook_subjects_carry_the_repository() {
+        let (mut manager, _) = fake_manager(&["main"]);
+        assert_eq!(manager.sessions()[0].hook_subject().repo, None);
+        manager.set_repository(PathBuf::from("/repo"));
+        manager.add_session(make_worktree("feature", false));
+        for session in manager.sessions() {
+            assert_eq!(session.hook_subject().repo, Some(PathBuf::from("/repo")));
+        }
+    }
+
+    #[test]
+    fn test_terminals_run_in_tmux_sessions_when_set() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature/x"]);
+        manager.set_terminal_shell(Some("fish".into()));
+        let names = tmux_names();
+        manager.set_multiplexer(Some(names.clone()));
+        manager.ensure_active_session_terminal_count(2, &mut ());
+
+        let started = terminals.started();
+        assert_eq!(
+            started[0].0.shell,
+            Some(format!(
+                "tmux new-session -A -s {} fish",
+                names.session("main", 0)
+            ))
+        );
+        assert_eq!(started[1].0.multiplexed, Some(names.session("main", 1)));
+
+        // A closed terminal's number is taken again by the next one
+        manager.close_active_terminal(false, &mut ());
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        assert_eq!(
+            terminals.started()[2].0.multiplexed,
+            Some(names.session("main", 0))
+        );
+
+        manager.set_multiplexer(None);
+        manager.ensure_session_terminal(1, &mut ());
+        assert_eq!(terminals.started()[3].0.shell.as_deref(), Some("fish"));
+        assert_eq!(terminals.started()[3].0.multiplexed, None);
+    }
+
+    #[test]
+    fn test_program_terminal_becomes_active_with_its_environment() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        manager.set_terminal_shell(Some("fish".into()));
+        manager.ensure_session_terminal(1, &mut ());
+        let env = vec![("OPENAI_MODEL".to_string(), "o3".to_string())];
+        let agent = manager
+            .add_session_terminal_running(
+                1,
+                PathBuf::from("/worktrees/feature/app"),
+                Some("codex --full-auto".into()),
+                env.clone(),
+                &mut (),
+            )
+            .unwrap();
+
+        let started = terminals.started();
+        assert!(agent.is(&started[1]));
+        assert_eq!(started[0].0.env, Vec::new());
+        assert_eq!(started[1].0.shell.as_deref(), Some("codex --full-auto"));
+        assert_eq!(started[1].0.env, env);
+        assert_eq!(
+            started[1].0.working_dir,
+            PathBuf::from("/worktrees/feature/app")
+        );
+        let feature = &manager.sessions()[1];
+        assert!(feature.active_terminal().unwrap().is(&agent));
+
+        // In tmux the program and its environment go to the tmux session
+        let names = tmux_names();
+        manager.set_multiplexer(Some(names.clone()));
+        manager.add_session_terminal_running(0, PathBuf::from("/main"), None, env, &mut ());
+        let tmux = &terminals.started()[2];
+        assert_eq!(
+            tmux.0.shell,
+            Some(format!(
+                "tmux new-session -A -s {} -e OPENAI_MODEL=o3 fish",
+                names.session("main", 0)
+            ))
+        );
+        assert!(tmux.0.env.is_empty());
+        assert!(
+            manager
+                .add_session_terminal_running(5, PathBuf::from("/"), None, Vec::new(), &mut ())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_restore_attaches_listed_tmux_sessions() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature", "docs"]);
+        let names = tmux_names();
+        let other = TmuxNames::new(Path::new("/nonexistent/other/repo"));
+        let listed = vec![
+            names.session("feature", 2),
+            names.session("feature", 0),
+            other.session("docs", 0),
+        ];
+        // Without a multiplexer nothing is attached
+        assert_eq!(manager.restore_terminals(&listed, &mut ()), 0);
+
+        manager.set_multiplexer(Some(names.clone()));
+        assert_eq!(manager.restore_terminals(&listed, &mut ()), 1);
+
+        let started: Vec<_> = terminals
+            .started()
+            .iter()
+            .map(|t| t.0.multiplexed.clone().unwrap())
+            .collect();
+        assert_eq!(
+            started,
+            vec![names.session("feature", 0), names.session("feature", 2)]
+        );
+        let feature = &manager.sessions()[1];
+        assert!(feature.is_restored());
+        assert_eq!(feature.terminal_count(), 2);
+        assert_eq!(feature.status(), SessionStatus::Running);
+        assert!(!manager.sessions()[0].is_restored());
+        assert!(!manager.sessions()[2].has_terminals());
+
+        // A new terminal of the session skips the numbers in use
+        manager.switch_to(1);
+        manager.ensure_active_session_terminal_count(3, &mut ());
+        assert_eq!(
+            terminals.started()[2].0.multiplexed,
+            Some(names.session("feature", 1))
+        );
+    }
+
+    #[test]
+    fn test_close_terminal_detaches_or_kills() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        manager.set_multiplexer(Some(tmux_names()));
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        let started = terminals.started();
+
+        manager.close_active_terminal(true, &mut ());
+        assert_eq!(started[0].0.kills.get(), 1);
+        assert_eq!(started[0].0.shutdowns.get(), 0);
+        manager.close_active_terminal(false, &mut ());
+        assert_eq!(started[1].0.shutdowns.get(), 1);
+        assert_eq!(started[1].0.kills.get(), 0);
+        assert_eq!(
+            manager.active_session().unwrap().status(),
+            SessionStatus::Stopped
+        );
+
+        manager.ensure_session_terminal(1, &mut ());
+        manager.kill_session_terminals(1, &mut ());
+        assert_eq!(terminals.started()[2].0.kills.get(), 1);
+        assert!(!manager.sessions()[1].has_terminals());
+    }
+
+    #[test]
+    fn test_write_goes_to_active_terminal_of_active_session() {
+        let (mut manager, terminals) = fake_manager(&["main", "feature"]);
+        // No terminal yet: nothing to write to
+        manager.write_to_active_terminal("ls\r", &mut ());
+
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        manager.ensure_session_terminal(1, &mut ());
+        manager.write_to_active_terminal("ls\r", &mut ());
+        manager.switch_to(1);
+        manager.write_to_active_terminal("make\r", &mut ());
+
+        let started = terminals.started();
+        assert_eq!(*started[0].0.written.borrow(), vec!["ls\r"]);
+        assert!(started[1].0.written.borrow().is_empty());
+        assert_eq!(*started[2].0.written.borrow(), vec!["make\r"]);
+    }
+
+    #[test]
+    fn test_terminal_counts_across_sessions() {
+        let (mut manager, _terminals) = fake_manager(&["main", "a", "b"]);
+        assert_eq!(manager.running_session_count(), 0);
+        assert_eq!(total_terminal_count(&manager), 0);
+
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        manager.ensure_session_terminal(2, &mut ());
+        assert_eq!(manager.running_session_count(), 2);
+        assert_eq!(total_terminal_count(&manager), 3);
+
+        // Removing a session takes its terminals along
+        manager.remove_session(2);
+        assert_eq!(manager.running_session_count(), 1);
+        assert_eq!(total_terminal_count(&manager), 2);
+    }
+
+    #[test]
+    fn test_terminal_lifecycle_is_recorded_in_timeline() {
+        let (mut manager, _terminals) = fake_manager(&["main"]);
+        manager.ensure_active_session_terminal_count(2, &mut ());
+        manager.stop_session_terminals(0, &mut ());
+        let events: Vec<_> = manager.sessions()[0]
+            .events()
+            .entries()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ActivityEvent::TerminalsStopped,
+                ActivityEvent::TerminalStarted { count: 2 },
+                ActivityEvent::TerminalStarted { count: 1 },
+            ]
+        );
+    }
 }
@@ -0,0 +1,58 @@
+//! The terminals of a session as the session sees them: started by a
+//! factory and driven through a handle, so sessions can be run without a
+//! GUI (see `terminal::ViewTerminals` for the real ones)
+
+use crate::attention::SessionAttention;
+use crate::command_history::TerminalHistory;
+use crate::scrollback::TerminalScrollback;
+use crate::transcript::SessionTranscript;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// What a session hands a new terminal
+pub struct TerminalSpec {
+    pub working_dir: PathBuf,
+    /// Shell command (None = the user's default shell)
+    pub shell: Option<String>,
+    /// Variables set for the shell on top of Sashiki's environment
+    pub env: Vec<(String, String)>,
+    /// Attention state of the session, raised by the terminal
+    pub attention: Rc<SessionAttention>,
+    /// Output recording of the session
+    pub transcript: Arc<SessionTranscript>,
+    /// Where the terminal records the commands run in it
+    pub history: TerminalHistory,
+    /// Where the terminal's scrollback is kept across restarts
+    pub scrollback: TerminalScrollback,
+    /// tmux session the terminal runs in, which `shell` attaches to (see
+    /// `crate::multiplexer`)
+    pub multiplexed: Option<String>,
+}
+
+/// A running terminal of a session
+pub trait TerminalHandle: Clone + 'static {
+    /// What the terminal is driven through (the gpui `App` for real ones)
+    type Cx: ?Sized;
+
+    /// Type `text` into the terminal
+    fn write(&self, text: &str, cx: &mut Self::Cx);
+
+    /// Ask the shell to exit; one running in tmux is detached and keeps
+    /// running
+    fn shutdown(&self, cx: &mut Self::Cx);
+
+    /// End the terminal's programs, tmux session and all
+    fn kill(&self, cx: &mut Self::Cx);
+
+    /// tmux session the terminal runs in
+    fn multiplexed(&self, cx: &Self::Cx) -> Option<String>;
+
+    /// Whether the shell was started and has not exited yet
+    fn is_running(&self, cx: &Self::Cx) -> bool;
+}
+
+/// Starts the terminals of sessions
+pub trait TerminalFactory<T: TerminalHandle> {
+    fn create(&self, spec: TerminalSpec, cx: &mut T::Cx) -> T;
+}
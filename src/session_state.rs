@@ -0,0 +1,361 @@
+//! State of a session kept across restarts
+//!
+//! The command histories of a session's terminals (see `command_history`),
+//! its activity timeline (see `activity`), the reviewed marks of its
+//! changed files (see `review`), its review comments (see
+//! `review_comments`) and when its worktree turned dirty (see `dirty`) are
+//! kept in the session state file
+//! `.git/sashiki/sessions/<worktree-name>.json`. A change only marks the
+//! state as unsaved: the app collects the unsaved states every `SAVE_DELAY`
+//! (see `SessionState::take_pending`) and writes them off the UI thread, and
+//! flushes them when it quits. Sessions read theirs once the repository is
+//! known; activity recorded before that is added to what the file holds.
+//!
+//! What concerns all sessions, the order the sidebar shows them in, is kept
+//! in `.git/sashiki/sessions.json`, keyed by worktree path.
+
+use crate::activity::ActivityLog;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::command_history::CommandHistory;
+use crate::fs_util;
+use crate::review::ReviewMarks;
+use crate::review_comments::ReviewComments;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Longest a change waits to be written to the state file
+pub const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Held while a state file is written or removed, so a removal never
+/// races a write of the same file
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Contents of a session state file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionStateFile {
+    /// History of each terminal, by its position in the session
+    #[serde(default)]
+    pub command_history: Vec<CommandHistory>,
+    #[serde(default)]
+    pub activity: ActivityLog,
+    #[serde(default)]
+    pub reviewed: ReviewMarks,
+    #[serde(default)]
+    pub comments: ReviewComments,
+    /// When the worktree last turned dirty, in unix seconds (see `dirty`)
+    #[serde(default)]
+    pub dirty_since: Option<i64>,
+}
+
+/// Contents of the repository's state file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoStateFile {
+    /// Worktree paths of the sessions, top to bottom
+    #[serde(default)]
+    pub session_order: Vec<PathBuf>,
+}
+
+/// State file shared by all sessions of the repository
+pub fn repo_state_path(git_dir: &Path) -> PathBuf {
+    git_dir.join("sashiki").join("sessions.json")
+}
+
+/// The repository state file at `path` (empty when there is none)
+pub fn read_repo_state(path: &Path) -> io::Result<RepoStateFile> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(RepoStateFile::default()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn write_repo_state(path: &Path, state: &RepoStateFile) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(state).map_err(io::Error::other)?;
+    fs_util::write_atomic(path, &json)
+}
+
+/// Saved session order of the repository in `git_dir` (empty when none was
+/// saved or the file cannot be read)
+pub fn read_session_order(git_dir: &Path) -> Vec<PathBuf> {
+    let path = repo_state_path(git_dir);
+    match read_repo_state(&path) {
+        Ok(state) => state.session_order,
+        Err(e) => {
+            log_warning(format!(
+                "Failed to read the session order in {}: {}",
+                path.display(),
+                e
+            ));
+            Vec::new()
+        }
+    }
+}
+
+/// Save `order` (worktree paths, top to bottom) for the repository in `git_dir`
+pub fn save_session_order(git_dir: &Path, order: Vec<PathBuf>) {
+    let path = repo_state_path(git_dir);
+    let mut state = read_repo_state(&path).unwrap_or_default();
+    state.session_order = order;
+    if let Err(e) = write_repo_state(&path, &state) {
+        log_warning(format!(
+            "Failed to save the session order in {}: {}",
+            path.display(),
+            e
+        ));
+    }
+}
+
+/// State file of the session named `worktree_name`
+pub fn state_path(git_dir: &Path, worktree_name: &str) -> PathBuf {
+    git_dir
+        .join("sashiki")
+        .join("sessions")
+        .join(format!("{}.json", fs_util::file_stem(worktree_name)))
+}
+
+/// The state file at `path` (empty when there is none)
+pub fn read_state(path: &Path) -> io::Result<SessionStateFile> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SessionStateFile::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Delete the state file at `path` (nothing to do when there is none)
+pub fn remove_state(path: &Path) -> io::Result<()> {
+    let _lock = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// A state file to write, taken off a `SessionState`
+#[derive(Debug)]
+pub struct PendingSave {
+    path: PathBuf,
+    contents: Vec<u8>,
+    /// Set when the session's state is discarded before this is written
+    discarded: Arc<AtomicBool>,
+}
+
+impl PendingSave {
+    /// Write the file (on any thread), logging a failure; nothing happens
+    /// when the session's state was discarded meanwhile
+    pub fn write(self) {
+        let _lock = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if self.discarded.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = fs_util::write_atomic(&self.path, &self.contents) {
+            log_warning(format!(
+                "Failed to save the session state in {}: {}",
+                self.path.display(),
+                e
+            ));
+        }
+    }
+}
+
+/// State of one session, shared with its terminal views (which record
+/// commands) and its activity sink
+#[derive(Debug, Default)]
+pub struct SessionState {
+    /// State file (None until the repository is known, or once discarded)
+    path: RefCell<Option<PathBuf>>,
+    state: RefCell<SessionStateFile>,
+    /// Whether `state` changed since it was last taken for saving
+    unsaved: Cell<bool>,
+    /// Shared with the saves taken, which skip writing once it is set
+    discarded: Arc<AtomicBool>,
+}
+
+impl SessionState {
+    /// Read the state file at `path`, unless it was already read from there
+    pub fn open(&self, path: PathBuf) {
+        if self.path.borrow().as_ref() == Some(&path) {
+            return;
+        }
+        let mut state = read_state(&path).unwrap_or_else(|e| {
+            log_warning(format!(
+                "Failed to read the session state in {}: {}",
+                path.display(),
+                e
+            ));
+            SessionStateFile::default()
+        });
+        let unsaved = std::mem::take(&mut self.state.borrow_mut().activity);
+        let merge = !unsaved.is_empty();
+        state.activity.extend(unsaved);
+        *self.state.borrow_mut() = state;
+        *self.path.borrow_mut() = Some(path);
+        self.discarded.store(false, Ordering::SeqCst);
+        if merge {
+            self.unsaved.set(true);
+        }
+    }
+
+    /// Apply `change`, and mark the state for saving if it returns true
+    pub fn update(&self, change: impl FnOnce(&mut SessionStateFile) -> bool) {
+        if change(&mut self.state.borrow_mut()) {
+            self.unsaved.set(true);
+        }
+    }
+
+    pub fn read<R>(&self, read: impl FnOnce(&SessionStateFile) -> R) -> R {
+        read(&self.state.borrow())
+    }
+
+    /// The state file to write if the state changed since the last call
+    /// (None while the file is not known yet)
+    pub fn take_pending(&self) -> Option<PendingSave> {
+        if !self.unsaved.get() {
+            return None;
+        }
+        let path = self.path.borrow().clone()?;
+        self.unsaved.set(false);
+        match serde_json::to_vec_pretty(&*self.state.borrow()) {
+            Ok(contents) => Some(PendingSave {
+                path,
+                contents,
+                discarded: self.discarded.clone(),
+            }),
+            Err(e) => {
+                log_warning(format!(
+                    "Failed to save the session state in {}: {}",
+                    path.display(),
+                    e
+                ));
+                None
+            }
+        }
+    }
+
+    /// Write the state file now if the state changed
+    pub fn flush(&self) {
+        if let Some(save) = self.take_pending() {
+            save.write();
+        }
+    }
+
+    /// Never write the state file again, saves already taken included (it
+    /// is being removed)
+    pub fn discard(&self) {
+        self.discarded.store(true, Ordering::SeqCst);
+        self.unsaved.set(false);
+        *self.path.borrow_mut() = None;
+    }
+}
+
+fn log_warning(message: String) {
+    app_log::record(LogEntry::new(LogLevel::Warn, LogSource::App, message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activity::ActivityEvent;
+    use crate::command_history::TerminalHistory;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_state_file_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = state_path(dir.path(), "feature/x");
+        assert_eq!(read_state(&path).unwrap(), SessionStateFile::default());
+
+        let state = Rc::new(SessionState::default());
+        // Activity from before the file is known is kept once it is
+        state.update(|file| {
+            file.activity.push_at(100, ActivityEvent::WorktreeCreated);
+            true
+        });
+        state.open(path.clone());
+        let history = TerminalHistory::new(state.clone(), 1);
+        history.record("cargo build");
+        history.record("cargo build");
+        assert_eq!(
+            TerminalHistory::new(state.clone(), 0).get(),
+            CommandHistory::default()
+        );
+        // Nothing is written until the state is flushed
+        assert!(!path.exists());
+        state.flush();
+
+        let reopened = Rc::new(SessionState::default());
+        reopened.open(path.clone());
+        let history = TerminalHistory::new(reopened.clone(), 1).get();
+        assert_eq!(history.last(), Some("cargo build"));
+        assert_eq!(history.recent().count(), 1);
+        assert_eq!(
+            reopened.read(|file| file.activity.clone()),
+            state.read(|file| file.activity.clone())
+        );
+        assert_eq!(
+            reopened.read(|file| file.activity.newest_first().count()),
+            1
+        );
+
+        remove_state(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_discarded_state_is_not_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = state_path(dir.path(), "feature");
+        let state = SessionState::default();
+        state.open(path.clone());
+        assert!(state.take_pending().is_none());
+
+        state.update(|file| {
+            file.activity.push_at(100, ActivityEvent::WorktreeCreated);
+            true
+        });
+        let save = state.take_pending().unwrap();
+        assert!(state.take_pending().is_none());
+        // The worktree is deleted before the save taken is written
+        state.discard();
+        save.write();
+        state.update(|file| {
+            file.activity.push_at(200, ActivityEvent::WorktreeCreated);
+            true
+        });
+        state.flush();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_session_order_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_session_order(dir.path()).is_empty());
+
+        let order = vec![PathBuf::from("/wt/b"), PathBuf::from("/wt/a")];
+        save_session_order(dir.path(), order.clone());
+        assert_eq!(read_session_order(dir.path()), order);
+        assert_eq!(
+            read_repo_state(&repo_state_path(dir.path())).unwrap(),
+            RepoStateFile {
+                session_order: order
+            }
+        );
+    }
+
+    #[test]
+    fn test_state_file_without_activity_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = state_path(dir.path(), "main");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, r#"{"command_history": [["ls"]]}"#).unwrap();
+
+        let state = read_state(&path).unwrap();
+        assert_eq!(state.command_history[0].last(), Some("ls"));
+        assert!(state.activity.is_empty());
+    }
+}
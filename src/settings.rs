@@ -0,0 +1,991 @@
+//! Layered application settings
+//!
+//! Settings use the same `sashiki.*` keys as the rest of the git config
+//! (e.g. `sashiki.terminal.shell`). Each key is looked up in these layers,
+//! highest precedence first; the first layer that has the key wins:
+//!
+//! 1. Command line: `sashiki -c key=value`
+//! 2. Repository git config (`.git/config`, where the settings dialog saves)
+//! 3. Repository settings file: `.sashikiconfig` in the main worktree root,
+//!    in git config syntax, meant to be committed and shared
+//! 4. User git config (`~/.gitconfig`, then the system config)
+//! 5. Built-in defaults
+//!
+//! Multi-valued keys (template commands) are not merged across layers: the
+//! values of the highest layer that has any replace the lower ones.
+//!
+//! A malformed `.sashikiconfig` is skipped with a warning so it never blocks
+//! opening the repository. Keys that name a program Sashiki runs (hooks, the
+//! terminal shell and multiplexer, the external editor, agent profiles) or
+//! lift a guard (`sashiki.files.allowSymlink`) are not read from it at all
+//! (see `USER_ONLY_KEYS`): a cloned repository could otherwise run anything
+//! just by being opened, or let its own links out of the worktree.
+
+use crate::dirty::{self, DirtyThresholds};
+use crate::git::{self, ConfigSource, GitLimits, GitRepo};
+use crate::maintenance::DEFAULT_CLEAN_PATTERNS;
+use crate::multiplexer::Multiplexer;
+use crate::review::ExcludePatterns;
+use crate::session::{LayoutMode, SessionColor, parse_tags};
+use crate::shell_escape::Shell;
+use crate::window_title::DEFAULT_TITLE_FORMAT;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Name of the repository settings file (git config syntax)
+pub const REPO_SETTINGS_FILE: &str = ".sashikiconfig";
+
+/// Keys (and prefixes of keys) only the user's own configuration sets: the
+/// command line, `.git/config` and the user and system git config
+pub const USER_ONLY_KEYS: &[&str] = &[
+    git::CONFIG_HOOK_PREFIX,
+    git::CONFIG_ALLOW_SYMLINK,
+    git::CONFIG_TERMINAL_SHELL,
+    git::CONFIG_TERMINAL_MULTIPLEXER,
+    git::CONFIG_EXTERNAL_EDITOR,
+    git::CONFIG_AGENT_PREFIX,
+];
+
+/// Whether `key` is or starts with one of `USER_ONLY_KEYS`
+fn is_user_only(key: &str) -> bool {
+    USER_ONLY_KEYS.iter().any(|prefix| {
+        key.get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+            && matches!(key.as_bytes().get(prefix.len()), None | Some(&b'.'))
+    })
+}
+
+/// Command line of this process (set once at startup)
+static CLI_ARGS: OnceLock<CliArgs> = OnceLock::new();
+
+/// Parsed command line: `sashiki [-c key=value]... [--export-state] [PATH]`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    /// Repository to open instead of the current directory
+    pub path: Option<PathBuf>,
+    pub overrides: Vec<(String, String)>,
+    /// Print the repository's state as JSON and exit (see `export`)
+    pub export_state: bool,
+}
+
+impl CliArgs {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "-c" {
+                let pair = args
+                    .next()
+                    .ok_or_else(|| "-c requires key=value".to_string())?;
+                let (key, value) = pair
+                    .split_once('=')
+                    .filter(|(key, _)| !key.trim().is_empty())
+                    .ok_or_else(|| format!("Invalid -c argument (expected key=value): {}", pair))?;
+                parsed
+                    .overrides
+                    .push((key.trim().to_string(), value.to_string()));
+            } else if arg == "--export-state" {
+                parsed.export_state = true;
+            } else if arg.starts_with('-') {
+                return Err(format!("Unknown option: {}", arg));
+            } else if parsed.path.is_none() {
+                parsed.path = Some(PathBuf::from(arg));
+            } else {
+                return Err(format!("Unexpected argument: {}", arg));
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Install the parsed command line (first call wins)
+pub fn init_cli_args(args: CliArgs) {
+    let _ = CLI_ARGS.set(args);
+}
+
+pub fn cli_args() -> &'static CliArgs {
+    CLI_ARGS.get_or_init(CliArgs::default)
+}
+
+/// What to open when started without a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StartupMode {
+    /// Reopen the last repository
+    #[default]
+    Last,
+    /// Open nothing
+    None,
+    /// Show the Open dialog
+    Ask,
+}
+
+/// Startup decision (see `resolve_startup`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Startup {
+    Open(PathBuf),
+    /// Reopen the repositories left open, showing the last one (at the end)
+    Reopen(Vec<PathBuf>),
+    Ask,
+    Nothing,
+}
+
+/// Decide what to open at startup.
+///
+/// An explicit path wins, then a repository in the current directory, then
+/// the startup mode (`last_repository` and `open_repositories` must already
+/// be checked to exist). Reopening the last repository brings back the
+/// others that were open with it.
+pub fn resolve_startup(
+    cli_path: Option<&Path>,
+    cwd_is_repo: bool,
+    mode: StartupMode,
+    last_repository: Option<&Path>,
+    open_repositories: &[PathBuf],
+) -> Startup {
+    if let Some(path) = cli_path {
+        return Startup::Open(path.to_path_buf());
+    }
+    if cwd_is_repo {
+        return Startup::Open(PathBuf::from("."));
+    }
+    match (mode, last_repository) {
+        (StartupMode::Last, Some(path)) => {
+            let mut paths: Vec<PathBuf> = open_repositories
+                .iter()
+                .filter(|p| p.as_path() != path)
+                .cloned()
+                .collect();
+            if paths.is_empty() {
+                return Startup::Open(path.to_path_buf());
+            }
+            paths.push(path.to_path_buf());
+            Startup::Reopen(paths)
+        }
+        (StartupMode::Ask, _) => Startup::Ask,
+        _ => Startup::Nothing,
+    }
+}
+
+/// Remember `repo` as the repository to reopen next time
+pub fn record_last_repository(repo: &GitRepo) {
+    let path = main_worktree_root(repo);
+    // Best effort: failing to write the user config must not affect opening
+    let _ = git::set_global_config_value(git::CONFIG_LAST_REPOSITORY, &path.to_string_lossy());
+}
+
+/// Remember the repositories open in the window (main worktree roots) to
+/// reopen them next time
+pub fn record_open_repositories(roots: &[PathBuf]) {
+    let values: Vec<String> = roots
+        .iter()
+        .map(|root| root.to_string_lossy().into_owned())
+        .collect();
+    // Best effort, as for the last repository
+    let _ = git::set_global_config_values(git::CONFIG_OPEN_REPOSITORY, &values);
+}
+
+/// Root of the main worktree (parent of the shared `.git` directory)
+pub fn main_worktree_root(repo: &GitRepo) -> PathBuf {
+    let git_dir = repo.git_dir();
+    match git_dir.parent() {
+        Some(parent) if git_dir.file_name().is_some_and(|n| n == ".git") => parent.to_path_buf(),
+        _ => repo.workdir().to_path_buf(),
+    }
+}
+
+/// Settings resolved from all layers
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// Entries per layer, highest precedence first
+    layers: Vec<Vec<(String, String)>>,
+}
+
+impl Settings {
+    /// Build from explicit layers (highest precedence first)
+    #[cfg(test)]
+    pub fn from_layers(layers: Vec<Vec<(String, String)>>) -> Self {
+        Self { layers }
+    }
+
+    /// Load all layers for `repo` (user-level layers only when `None`).
+    ///
+    /// Returns a warning if the repository settings file had to be ignored.
+    pub fn load(repo: Option<&GitRepo>) -> (Self, Option<String>) {
+        let mut layers = vec![cli_args().overrides.clone()];
+        let mut warning = None;
+
+        if let Some(repo) = repo {
+            layers.push(
+                git::read_config_entries(ConfigSource::Local(repo.workdir())).unwrap_or_default(),
+            );
+
+            let file = main_worktree_root(repo).join(REPO_SETTINGS_FILE);
+            if file.is_file() {
+                match git::read_config_entries(ConfigSource::File(&file)) {
+                    Ok(mut entries) => {
+                        let mut ignored: Vec<String> = Vec::new();
+                        entries.retain(|(key, _)| {
+                            let user_only = is_user_only(key);
+                            if user_only && !ignored.contains(key) {
+                                ignored.push(key.clone());
+                            }
+                            !user_only
+                        });
+                        if !ignored.is_empty() {
+                            warning = Some(format!(
+                                "Ignoring {} in {}: only read from your own git config",
+                                ignored.join(", "),
+                                file.display()
+                            ));
+                        }
+                        layers.push(entries);
+                    }
+                    Err(e) => {
+                        warning = Some(format!("Ignoring {}: {}", file.display(), e));
+                    }
+                }
+            }
+        }
+
+        // A missing ~/.gitconfig is reported as an error by git; treat as empty
+        layers.push(git::read_config_entries(ConfigSource::Global).unwrap_or_default());
+        layers.push(git::read_config_entries(ConfigSource::System).unwrap_or_default());
+
+        (Self { layers }, warning)
+    }
+
+    /// Entries of the highest layer that has `key` (git keys are case-insensitive)
+    fn values(&self, key: &str) -> Vec<&str> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                layer
+                    .iter()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+                    .map(|(_, v)| v.as_str())
+                    .collect::<Vec<_>>()
+            })
+            .find(|values| !values.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// Single value (the last one in its layer, as `git config --get` does)
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.values(key)
+            .last()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Boolean value in git's spelling (`true`/`yes`/`on`/`1` and the opposites)
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)?.to_ascii_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(true),
+            "false" | "no" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        self.values(key)
+            .into_iter()
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Names of the `<prefix>.<name>.*` subsections in all layers (see
+    /// `subsection_names`)
+    pub fn subsection_names(&self, prefix: &str) -> Vec<String> {
+        subsection_names(
+            self.layers.iter().flatten().map(|(key, _)| key.as_str()),
+            prefix,
+        )
+    }
+
+    pub fn startup_mode(&self) -> StartupMode {
+        match self.get(git::CONFIG_STARTUP).as_deref() {
+            Some("none") => StartupMode::None,
+            Some("ask") => StartupMode::Ask,
+            _ => StartupMode::Last,
+        }
+    }
+
+    /// Last opened repository, if it still exists
+    pub fn last_repository(&self) -> Option<PathBuf> {
+        self.get(git::CONFIG_LAST_REPOSITORY)
+            .map(PathBuf::from)
+            .filter(|p| p.is_dir())
+    }
+
+    /// Repositories open in the window when it was last used, those that
+    /// still exist
+    pub fn open_repositories(&self) -> Vec<PathBuf> {
+        self.get_all(git::CONFIG_OPEN_REPOSITORY)
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|p| p.is_dir())
+            .collect()
+    }
+
+    /// Format of the OS window title (see `window_title`)
+    pub fn window_title_format(&self) -> String {
+        self.get(git::CONFIG_WINDOW_TITLE)
+            .unwrap_or_else(|| DEFAULT_TITLE_FORMAT.to_string())
+    }
+
+    /// Shell command line for new terminals (None = the user's login shell)
+    pub fn terminal_shell(&self) -> Option<String> {
+        self.get(git::CONFIG_TERMINAL_SHELL)
+    }
+
+    /// What keeps terminals running across restarts (None = bare shells)
+    pub fn terminal_multiplexer(&self) -> Option<Multiplexer> {
+        self.get(git::CONFIG_TERMINAL_MULTIPLEXER)
+            .and_then(|v| Multiplexer::parse(&v))
+    }
+
+    /// Whether the terminals' scrollback is saved on quit and restored on
+    /// the next launch (off unless turned on)
+    pub fn terminal_persist_scrollback(&self) -> bool {
+        self.get_bool(git::CONFIG_TERMINAL_SCROLLBACK)
+            .unwrap_or(false)
+    }
+
+    /// Whether the saved scrollback keeps its colors (off unless turned on)
+    pub fn terminal_persist_scrollback_colors(&self) -> bool {
+        self.get_bool(git::CONFIG_TERMINAL_SCROLLBACK_COLORS)
+            .unwrap_or(false)
+    }
+
+    /// How many snapshots to keep per branch (older ones are pruned)
+    pub fn snapshot_keep(&self) -> usize {
+        self.get(git::CONFIG_SNAPSHOT_KEEP)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(git::DEFAULT_SNAPSHOT_KEEP)
+    }
+
+    /// Time between automatic checkpoints of a session
+    pub fn checkpoint_interval(&self) -> Duration {
+        self.get(git::CONFIG_CHECKPOINT_INTERVAL)
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|&secs| secs > 0)
+            .map_or(
+                Duration::from_secs(git::DEFAULT_CHECKPOINT_INTERVAL),
+                Duration::from_secs,
+            )
+    }
+
+    /// How many checkpoints to keep per branch (older ones are pruned)
+    pub fn checkpoint_keep(&self) -> usize {
+        self.get(git::CONFIG_CHECKPOINT_KEEP)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(git::DEFAULT_CHECKPOINT_KEEP)
+    }
+
+    /// How many changed files get their diff computed ahead of time
+    pub fn diff_prefetch(&self) -> usize {
+        self.get(git::CONFIG_DIFF_PREFETCH)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(git::DEFAULT_DIFF_PREFETCH)
+    }
+
+    /// Size in bytes of either side above which a diff is only counted (see
+    /// `diff_job`)
+    pub fn diff_max_size(&self) -> u64 {
+        self.get(git::CONFIG_DIFF_MAX_SIZE)
+            .and_then(|v| parse_size(&v))
+            .unwrap_or(git::DEFAULT_DIFF_MAX_SIZE)
+    }
+
+    /// Size in bytes above which the file view pages text and skips the diff
+    pub fn viewer_max_file_size(&self) -> u64 {
+        self.get(git::CONFIG_VIEWER_MAX_FILE_SIZE)
+            .and_then(|v| parse_size(&v))
+            .unwrap_or(git::DEFAULT_VIEWER_MAX_FILE_SIZE)
+    }
+
+    /// Longest side in pixels above which the file view shows a downscaled image
+    pub fn viewer_max_image_size(&self) -> u32 {
+        self.get(git::CONFIG_VIEWER_MAX_IMAGE_SIZE)
+            .and_then(|v| v.parse().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(git::DEFAULT_VIEWER_MAX_IMAGE_SIZE)
+    }
+
+    /// Columns between tab stops in the file and diff views
+    pub fn viewer_tab_width(&self) -> usize {
+        self.get(git::CONFIG_VIEWER_TAB_WIDTH)
+            .and_then(|v| v.parse().ok())
+            .filter(|&width| width > 0)
+            .unwrap_or(git::DEFAULT_VIEWER_TAB_WIDTH)
+    }
+
+    /// File name globs whose long lines wrap in the file view
+    pub fn viewer_wrap(&self) -> Vec<String> {
+        let patterns = self.get_all(git::CONFIG_VIEWER_WRAP);
+        if patterns.is_empty() {
+            git::DEFAULT_VIEWER_WRAP
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        } else {
+            patterns
+        }
+    }
+
+    /// Files of untracked directories listed in the Changes list before the
+    /// rest are only counted
+    pub fn changes_untracked_limit(&self) -> usize {
+        self.get(git::CONFIG_CHANGES_UNTRACKED_LIMIT)
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|&limit| limit > 0)
+            .unwrap_or(git::DEFAULT_UNTRACKED_LIMIT)
+    }
+
+    /// Hits after which a workspace search stops
+    pub fn search_max_results(&self) -> usize {
+        self.get(git::CONFIG_SEARCH_MAX_RESULTS)
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|&limit| limit > 0)
+            .unwrap_or(git::DEFAULT_SEARCH_MAX_RESULTS)
+    }
+
+    /// Timeout, output limit and lock wait of git commands
+    pub fn git_limits(&self) -> GitLimits {
+        GitLimits {
+            timeout: self
+                .get(git::CONFIG_GIT_TIMEOUT)
+                .and_then(|v| v.trim().parse().ok())
+                .map_or(git::DEFAULT_GIT_TIMEOUT, Duration::from_secs),
+            max_output: self
+                .get(git::CONFIG_GIT_MAX_OUTPUT)
+                .and_then(|v| parse_size(&v))
+                .and_then(|size| usize::try_from(size).ok())
+                .unwrap_or(git::DEFAULT_GIT_MAX_OUTPUT),
+            lock_wait: self
+                .get(git::CONFIG_GIT_LOCK_WAIT)
+                .and_then(|v| v.trim().parse().ok())
+                .map_or(git::DEFAULT_GIT_LOCK_WAIT, Duration::from_secs),
+        }
+    }
+
+    /// Tags of the session for `worktree_name`
+    pub fn session_tags(&self, worktree_name: &str) -> Vec<String> {
+        self.get(&git::session_tags_key(worktree_name))
+            .map(|v| parse_tags(&v))
+            .unwrap_or_default()
+    }
+
+    /// Whether the session for `worktree_name` records a transcript
+    pub fn session_transcript(&self, worktree_name: &str) -> bool {
+        self.get_bool(&git::session_transcript_key(worktree_name))
+            .unwrap_or(false)
+    }
+
+    /// Whether the terminal bell of the session for `worktree_name` is muted
+    pub fn session_bell_muted(&self, worktree_name: &str) -> bool {
+        self.get_bool(&git::session_bell_muted_key(worktree_name))
+            .unwrap_or(false)
+    }
+
+    /// Whether the session for `worktree_name` takes automatic checkpoints
+    pub fn session_auto_checkpoint(&self, worktree_name: &str) -> bool {
+        self.get_bool(&git::session_auto_checkpoint_key(worktree_name))
+            .unwrap_or(false)
+    }
+
+    /// Read-only choice stored for the session of `worktree_name` (None = unset)
+    pub fn session_read_only(&self, worktree_name: &str) -> Option<bool> {
+        self.get_bool(&git::session_read_only_key(worktree_name))
+    }
+
+    /// Color picked for the session of `worktree_name` (None = unset or not
+    /// a `#rrggbb` color)
+    pub fn session_color(&self, worktree_name: &str) -> Option<SessionColor> {
+        self.get(&git::session_color_key(worktree_name))
+            .and_then(|v| SessionColor::parse_hex(&v))
+    }
+
+    /// Shell whose quoting paths typed into the session of `worktree_name`
+    /// get (None = the one found running)
+    pub fn session_shell_quoting(&self, worktree_name: &str) -> Option<Shell> {
+        self.get(&git::session_shell_quoting_key(worktree_name))
+            .and_then(|v| Shell::parse(&v))
+    }
+
+    /// Whether the main worktree's session is read-only unless turned off
+    pub fn main_read_only(&self) -> bool {
+        self.get_bool(git::CONFIG_MAIN_READ_ONLY).unwrap_or(false)
+    }
+
+    /// Task description the session of `worktree_name` was created for
+    pub fn session_description(&self, worktree_name: &str) -> Option<String> {
+        self.get(&git::session_description_key(worktree_name))
+            .filter(|v| !v.trim().is_empty())
+    }
+
+    /// Agent profile last launched in the session of `worktree_name`
+    pub fn session_agent(&self, worktree_name: &str) -> Option<String> {
+        self.get(&git::session_agent_key(worktree_name))
+    }
+
+    /// Whether quitting or deleting a busy session asks first (on unless
+    /// turned off)
+    pub fn confirm_busy_sessions(&self) -> bool {
+        self.get_bool(git::CONFIG_CONFIRM_BUSY).unwrap_or(true)
+    }
+
+    /// Whether a changed file whose diff was open is marked reviewed when
+    /// another file is opened (on unless turned off)
+    pub fn review_mark_on_leave(&self) -> bool {
+        self.get_bool(git::CONFIG_REVIEW_MARK_ON_LEAVE)
+            .unwrap_or(true)
+    }
+
+    /// Changed files left out of review. The patterns of the highest layer
+    /// replace the others, so an empty value in the repository config turns
+    /// off the patterns of the user config for that repository.
+    pub fn review_exclude(&self) -> ExcludePatterns {
+        ExcludePatterns::new(self.get_all(git::CONFIG_REVIEW_EXCLUDE))
+    }
+
+    /// Whether the sidebar shows terminal thumbnails (on unless turned off)
+    pub fn sidebar_minimap(&self) -> bool {
+        self.get_bool(git::CONFIG_SIDEBAR_MINIMAP).unwrap_or(true)
+    }
+
+    /// Whether the sidebar shows what each session's processes use (off
+    /// unless turned on)
+    pub fn sidebar_resources(&self) -> bool {
+        self.get_bool(git::CONFIG_SIDEBAR_RESOURCES)
+            .unwrap_or(false)
+    }
+
+    /// Whether the local control server runs (off unless turned on)
+    pub fn remote_enabled(&self) -> bool {
+        self.get_bool(git::CONFIG_REMOTE_ENABLED).unwrap_or(false)
+    }
+
+    pub fn remote_port(&self) -> u16 {
+        self.get(git::CONFIG_REMOTE_PORT)
+            .and_then(|v| v.parse().ok())
+            .filter(|&port| port > 0)
+            .unwrap_or(git::DEFAULT_REMOTE_PORT)
+    }
+
+    /// Bearer token of the control server (None until one is generated)
+    pub fn remote_token(&self) -> Option<String> {
+        self.get(git::CONFIG_REMOTE_TOKEN)
+    }
+
+    /// Ages of uncommitted changes at which a session's indicator in the
+    /// sidebar turns yellow, then red
+    pub fn dirty_thresholds(&self) -> DirtyThresholds {
+        let minutes = |key| {
+            self.get(key)
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|minutes| Duration::from_secs(minutes * 60))
+        };
+        DirtyThresholds {
+            warn_after: minutes(git::CONFIG_SIDEBAR_DIRTY_WARN)
+                .unwrap_or(dirty::DEFAULT_WARN_AFTER),
+            alert_after: minutes(git::CONFIG_SIDEBAR_DIRTY_ALERT)
+                .unwrap_or(dirty::DEFAULT_ALERT_AFTER),
+        }
+    }
+
+    /// Prefix of branch names suggested from a task description
+    pub fn branch_prefix(&self) -> String {
+        self.get(git::CONFIG_BRANCH_PREFIX)
+            .unwrap_or_else(|| git::DEFAULT_BRANCH_PREFIX.to_string())
+    }
+
+    /// Build artifact patterns removed by worktree maintenance
+    pub fn clean_patterns(&self) -> Vec<String> {
+        let patterns = self.get_all(git::CONFIG_CLEAN_PATTERN);
+        if patterns.is_empty() {
+            DEFAULT_CLEAN_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        } else {
+            patterns
+        }
+    }
+
+    pub fn layout_mode(&self) -> LayoutMode {
+        match self.get(git::CONFIG_LAYOUT_MODE).as_deref() {
+            Some("parallel") => LayoutMode::Parallel,
+            _ => LayoutMode::Single,
+        }
+    }
+}
+
+/// Names `<name>` of keys `<prefix>.<name>.<variable>`, in order of first
+/// appearance. The prefix is compared without case; names keep theirs, as
+/// git subsections do.
+pub fn subsection_names<'a>(keys: impl IntoIterator<Item = &'a str>, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for key in keys {
+        let Some(rest) = key
+            .get(..prefix.len())
+            .filter(|head| head.eq_ignore_ascii_case(prefix))
+            .and_then(|_| key[prefix.len()..].strip_prefix('.'))
+        else {
+            continue;
+        };
+        if let Some((name, _)) = rest.rsplit_once('.')
+            && !name.is_empty()
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Parse a byte count the way `git config --type=int` does: an integer with
+/// an optional `k`, `m` or `g` suffix (powers of 1024)
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, multiplier) = match value.chars().last()?.to_ascii_lowercase() {
+        'k' => (&value[..value.len() - 1], 1024),
+        'm' => (&value[..value.len() - 1], 1024 * 1024),
+        'g' => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn args(list: &[&str]) -> Result<CliArgs, String> {
+        CliArgs::parse(list.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_precedence_cli_over_repo_over_user_over_defaults() {
+        let shell = git::CONFIG_TERMINAL_SHELL;
+        let layout = git::CONFIG_LAYOUT_MODE;
+        let cli = layer(&[(shell, "fish")]);
+        let repo_local = layer(&[("sashiki.terminal.SHELL", "bash")]);
+        let repo_file = layer(&[(shell, "zsh"), (layout, "parallel")]);
+        let user = layer(&[
+            (shell, "sh"),
+            (layout, "single"),
+            (git::CONFIG_STARTUP, "ask"),
+        ]);
+
+        let all = Settings::from_layers(vec![
+            cli.clone(),
+            repo_local.clone(),
+            repo_file.clone(),
+            user.clone(),
+        ]);
+        assert_eq!(all.terminal_shell().as_deref(), Some("fish"));
+        assert_eq!(all.layout_mode(), LayoutMode::Parallel);
+        assert_eq!(all.startup_mode(), StartupMode::Ask);
+
+        let no_cli = Settings::from_layers(vec![repo_local, repo_file.clone(), user.clone()]);
+        assert_eq!(no_cli.terminal_shell().as_deref(), Some("bash"));
+
+        let file_over_user = Settings::from_layers(vec![repo_file, user.clone()]);
+        assert_eq!(file_over_user.terminal_shell().as_deref(), Some("zsh"));
+
+        let user_only = Settings::from_layers(vec![user]);
+        assert_eq!(user_only.terminal_shell().as_deref(), Some("sh"));
+        assert_eq!(user_only.layout_mode(), LayoutMode::Single);
+
+        let defaults = Settings::default();
+        assert_eq!(defaults.terminal_shell(), None);
+        assert_eq!(defaults.layout_mode(), LayoutMode::Single);
+        assert_eq!(defaults.startup_mode(), StartupMode::Last);
+    }
+
+    #[test]
+    fn test_multi_values_come_from_one_layer() {
+        let key = git::CONFIG_POST_CREATE_CMD;
+        let settings = Settings::from_layers(vec![
+            layer(&[]),
+            layer(&[(key, "npm ci"), (key, "npm run build")]),
+            layer(&[(key, "make")]),
+        ]);
+        assert_eq!(settings.get_all(key), vec!["npm ci", "npm run build"]);
+        assert_eq!(settings.get(key).as_deref(), Some("npm run build"));
+    }
+
+    #[test]
+    fn test_repo_config_turns_off_review_exclusions() {
+        let key = git::CONFIG_REVIEW_EXCLUDE;
+        let user = layer(&[(key, "*.lock"), (key, "dist/**")]);
+        let settings = Settings::from_layers(vec![layer(&[]), user.clone()]);
+        assert!(settings.review_exclude().matches(Path::new("Cargo.lock")));
+
+        let settings = Settings::from_layers(vec![layer(&[(key, "")]), user]);
+        assert!(settings.review_exclude().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_repo_file_is_ignored_with_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = |args: &[&str]| crate::test_support::git(dir.path(), args);
+        run(&["init", "-q", "-b", "main"]);
+        run(&["config", git::CONFIG_LAYOUT_MODE, "parallel"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        std::fs::write(
+            dir.path().join(REPO_SETTINGS_FILE),
+            "[sashiki \"viewer\"]\n\ttabWidth = 2\n",
+        )
+        .unwrap();
+        let (settings, warning) = Settings::load(Some(&repo));
+        assert_eq!(warning, None);
+        assert_eq!(settings.viewer_tab_width(), 2);
+        assert_eq!(settings.layout_mode(), LayoutMode::Parallel);
+
+        std::fs::write(
+            dir.path().join(REPO_SETTINGS_FILE),
+            "[sashiki \"viewer\"\ntabWidth",
+        )
+        .unwrap();
+        let (settings, warning) = Settings::load(Some(&repo));
+        assert!(warning.is_some_and(|w| w.contains(REPO_SETTINGS_FILE)));
+        assert_ne!(settings.viewer_tab_width(), 2);
+        // The rest of the configuration still applies
+        assert_eq!(settings.layout_mode(), LayoutMode::Parallel);
+    }
+
+    #[test]
+    fn test_user_only_keys_in_repo_file_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = |args: &[&str]| crate::test_support::git(dir.path(), args);
+        run(&["init", "-q", "-b", "main"]);
+        let idle = git::hook_key("session_idle", "command");
+        run(&["config", &idle, "notify-send idle"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        std::fs::write(
+            dir.path().join(REPO_SETTINGS_FILE),
+            "[sashiki \"hook.repository_opened\"]\n\tcommand = touch pwned\n\
+             [sashiki \"HOOK.session_idle\"]\n\tcommand = touch pwned\n\
+             [sashiki \"files\"]\n\tallowSymlink = *\n\
+             [sashiki \"terminal\"]\n\tshell = ./pwn.sh\n\tmultiplexer = tmux\n\
+             [sashiki \"actions\"]\n\texternalEditor = ./pwn.sh\n\
+             [sashiki \"agent.claude\"]\n\tcommand = ./pwn.sh\n\
+             [sashiki \"viewer\"]\n\ttabWidth = 2\n",
+        )
+        .unwrap();
+        let (settings, warning) = Settings::load(Some(&repo));
+        let opened = git::hook_key("repository_opened", "command");
+        assert!(settings.get_all(&opened).is_empty());
+        assert!(
+            !settings
+                .subsection_names(git::CONFIG_HOOK_PREFIX)
+                .iter()
+                .any(|name| name == "repository_opened")
+        );
+        // Not the repository's hook in place of the user's own either
+        assert_eq!(settings.get_all(&idle), vec!["notify-send idle"]);
+        assert!(settings.get_all(git::CONFIG_ALLOW_SYMLINK).is_empty());
+        // Nor a program of its own in place of the user's shell or tools
+        let pwn = Some("./pwn.sh".to_string());
+        assert_ne!(settings.terminal_shell(), pwn);
+        assert_ne!(settings.get(git::CONFIG_EXTERNAL_EDITOR), pwn);
+        assert_ne!(settings.get(&git::agent_key("claude", "command")), pwn);
+        let warning = warning.unwrap().to_lowercase();
+        for key in [
+            "terminal.shell",
+            "terminal.multiplexer",
+            "actions.externaleditor",
+            "agent.claude.command",
+        ] {
+            assert!(warning.contains(key), "{}", warning);
+        }
+        assert!(warning.contains("repository_opened"));
+        // The rest of the file still applies
+        assert_eq!(settings.viewer_tab_width(), 2);
+    }
+
+    #[test]
+    fn test_subsection_names() {
+        let prefix = git::CONFIG_TEMPLATE_PREFIX;
+        let settings = Settings::from_layers(vec![
+            layer(&[
+                ("sashiki.template.Frontend.match", "frontend/*"),
+                (git::CONFIG_POST_CREATE_CMD, "make"),
+            ]),
+            layer(&[
+                ("sashiki.template.backend.match", "backend/*"),
+                ("sashiki.template.Frontend.postcreatecommand", "npm ci"),
+                ("sashiki.template.release.v2.match", "release/**"),
+                ("sashiki.session.foo.tags", "x"),
+            ]),
+        ]);
+        assert_eq!(
+            settings.subsection_names(prefix),
+            vec!["Frontend", "backend", "release.v2"]
+        );
+        assert!(settings.subsection_names("sashiki.other").is_empty());
+    }
+
+    #[test]
+    fn test_cli_args() {
+        assert_eq!(args(&[]), Ok(CliArgs::default()));
+        assert_eq!(
+            args(&[
+                "-c",
+                "sashiki.layout.mode=parallel",
+                "-c",
+                "a.b= x=y",
+                "../repo"
+            ]),
+            Ok(CliArgs {
+                path: Some(PathBuf::from("../repo")),
+                overrides: vec![
+                    ("sashiki.layout.mode".to_string(), "parallel".to_string()),
+                    ("a.b".to_string(), " x=y".to_string()),
+                ],
+                export_state: false,
+            })
+        );
+        assert_eq!(
+            args(&["--export-state", "../repo"]),
+            Ok(CliArgs {
+                path: Some(PathBuf::from("../repo")),
+                export_state: true,
+                ..CliArgs::default()
+            })
+        );
+        assert!(args(&["-c"]).is_err());
+        assert!(args(&["-c", "novalue"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+        assert!(args(&["one", "two"]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_startup() {
+        let last = PathBuf::from("/repos/last");
+        let cli = PathBuf::from("/repos/cli");
+
+        assert_eq!(
+            resolve_startup(Some(&cli), true, StartupMode::None, Some(&last), &[]),
+            Startup::Open(cli.clone())
+        );
+        assert_eq!(
+            resolve_startup(None, true, StartupMode::Last, Some(&last), &[]),
+            Startup::Open(PathBuf::from("."))
+        );
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::Last, Some(&last), &[]),
+            Startup::Open(last.clone())
+        );
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::Last, None, &[]),
+            Startup::Nothing
+        );
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::Ask, Some(&last), &[]),
+            Startup::Ask
+        );
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::None, Some(&last), &[]),
+            Startup::Nothing
+        );
+
+        // The others left open come back with the last one, which is shown
+        let other = PathBuf::from("/repos/other");
+        let open = [last.clone(), other.clone()];
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::Last, Some(&last), &open),
+            Startup::Reopen(vec![other, last.clone()])
+        );
+        assert_eq!(
+            resolve_startup(None, false, StartupMode::Last, Some(&last), &[last.clone()]),
+            Startup::Open(last.clone())
+        );
+        assert_eq!(
+            resolve_startup(Some(&cli), false, StartupMode::Last, Some(&last), &open),
+            Startup::Open(cli)
+        );
+    }
+
+    #[test]
+    fn test_viewer_max_file_size() {
+        let key = git::CONFIG_VIEWER_MAX_FILE_SIZE;
+        let size = |value: &str| {
+            Settings::from_layers(vec![layer(&[(key, value)])]).viewer_max_file_size()
+        };
+
+        assert_eq!(
+            Settings::from_layers(vec![]).viewer_max_file_size(),
+            git::DEFAULT_VIEWER_MAX_FILE_SIZE
+        );
+        assert_eq!(size("1000"), 1000);
+        assert_eq!(size("512k"), 512 * 1024);
+        assert_eq!(size("20M"), 20 * 1024 * 1024);
+        assert_eq!(size("1g"), 1024 * 1024 * 1024);
+        assert_eq!(size("big"), git::DEFAULT_VIEWER_MAX_FILE_SIZE);
+    }
+
+    #[test]
+    fn test_git_limits() {
+        assert_eq!(
+            Settings::from_layers(vec![]).git_limits(),
+            GitLimits::default()
+        );
+
+        let settings = Settings::from_layers(vec![layer(&[
+            (git::CONFIG_GIT_TIMEOUT, "90"),
+            (git::CONFIG_GIT_MAX_OUTPUT, "64m"),
+            (git::CONFIG_GIT_LOCK_WAIT, "0"),
+        ])]);
+        assert_eq!(
+            settings.git_limits(),
+            GitLimits {
+                timeout: Duration::from_secs(90),
+                max_output: 64 * 1024 * 1024,
+                lock_wait: Duration::ZERO,
+            }
+        );
+
+        let settings = Settings::from_layers(vec![layer(&[
+            (git::CONFIG_GIT_TIMEOUT, "soon"),
+            (git::CONFIG_GIT_MAX_OUTPUT, "0"),
+        ])]);
+        assert_eq!(settings.git_limits().timeout, git::DEFAULT_GIT_TIMEOUT);
+        assert_eq!(settings.git_limits().max_output, 0);
+        assert_eq!(settings.git_limits().lock_wait, git::DEFAULT_GIT_LOCK_WAIT);
+    }
+
+    #[test]
+    fn test_dirty_thresholds() {
+        let settings = Settings::from_layers(vec![layer(&[
+            (git::CONFIG_SIDEBAR_DIRTY_WARN, "30"),
+            (git::CONFIG_SIDEBAR_DIRTY_ALERT, "later"),
+        ])]);
+        assert_eq!(
+            settings.dirty_thresholds(),
+            DirtyThresholds {
+                warn_after: Duration::from_secs(30 * 60),
+                alert_after: dirty::DEFAULT_ALERT_AFTER,
+            }
+        );
+        assert_eq!(
+            Settings::from_layers(vec![]).dirty_thresholds(),
+            DirtyThresholds::default()
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Escaping paths typed into a terminal for the shell at its prompt
+//!
+//! A path inserted from the file list goes through the shell's parser before
+//! the program it is meant for sees it, and each shell family quotes
+//! differently: POSIX shells and fish take single quotes (with their own
+//! escapes inside), PowerShell doubles single quotes, and cmd.exe only has
+//! double quotes, which leave `%` expanding. Paths made of plain characters
+//! are passed through unchanged.
+//!
+//! The same rules quote what Sashiki itself hands to a shell: commands run
+//! through `sh -c` (`cmd /C` on Windows, see `Shell::platform`) and command
+//! lines typed into a terminal. A terminal's own command line is not run by
+//! a shell; `split_words` splits it, taking back the POSIX quoting.
+//!
+//! The shell is the one in the foreground of the terminal when that is a
+//! shell, else the one the terminal was started with, else the login shell.
+//! `sashiki.session.<name>.shellQuoting` (posix, fish, powershell or cmd)
+//! overrides the guess for a session.
+
+use std::borrow::Cow;
+
+/// Quoting rules of a shell family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// sh, bash, zsh, dash, ksh and the like
+    Posix,
+    Fish,
+    /// PowerShell (`pwsh`, `powershell.exe`)
+    Pwsh,
+    Cmd,
+}
+
+impl Shell {
+    /// Family of `program`: a command line, a path to the shell or a process
+    /// name (a login shell's leading `-` is ignored); None for other programs
+    pub fn from_program(program: &str) -> Option<Self> {
+        let word = program.split_whitespace().next()?;
+        let name = word.rsplit(['/', '\\']).next()?.trim_start_matches('-');
+        let name = name.to_ascii_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        match name {
+            "sh" | "bash" | "zsh" | "dash" | "ksh" | "mksh" | "ash" | "yash" | "busybox" => {
+                Some(Self::Posix)
+            }
+            "fish" => Some(Self::Fish),
+            "pwsh" | "powershell" => Some(Self::Pwsh),
+            "cmd" => Some(Self::Cmd),
+            _ => None,
+        }
+    }
+
+    /// Value of `sashiki.session.<name>.shellQuoting` (a family or a shell)
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.trim().eq_ignore_ascii_case("posix") {
+            return Some(Self::Posix);
+        }
+        Self::from_program(value)
+    }
+
+    /// Shell commands are run through when Sashiki spawns them: `sh -c`, or
+    /// `cmd /C` on Windows
+    pub fn platform() -> Self {
+        if cfg!(windows) {
+            Self::Cmd
+        } else {
+            Self::Posix
+        }
+    }
+
+    /// Shell new terminals start when none is configured
+    pub fn login() -> Self {
+        if cfg!(windows) {
+            return Self::Pwsh;
+        }
+        std::env::var("SHELL")
+            .ok()
+            .and_then(|shell| Self::from_program(&shell))
+            .unwrap_or(Self::Posix)
+    }
+
+    /// `arg` as one word for this shell
+    pub fn escape(self, arg: &str) -> Cow<'_, str> {
+        if !arg.is_empty() && arg.chars().all(|c| self.is_plain(c)) {
+            return Cow::Borrowed(arg);
+        }
+        Cow::Owned(match self {
+            Self::Posix => format!("'{}'", arg.replace('\'', r"'\''")),
+            // Inside single quotes fish only knows `\'` and `\\`
+            Self::Fish => format!("'{}'", arg.replace('\\', r"\\").replace('\'', r"\'")),
+            Self::Pwsh => format!("'{}'", escape_powershell_quotes(arg)),
+            Self::Cmd => escape_cmd(arg),
+        })
+    }
+
+    /// Characters that mean nothing to the shell anywhere in a word
+    fn is_plain(self, c: char) -> bool {
+        if c.is_alphanumeric() || "-_./:".contains(c) {
+            return true;
+        }
+        match self {
+            Self::Posix => "=,@%+".contains(c),
+            Self::Fish => "=,@+".contains(c),
+            Self::Pwsh => c == '\\',
+            Self::Cmd => "\\@+".contains(c),
+        }
+    }
+}
+
+/// Single quotes doubled, the typographic ones PowerShell also takes for
+/// quotes included
+fn escape_powershell_quotes(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len());
+    for c in arg.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            out.push(c);
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Double quotes around each run between `%` signs, which go outside as
+/// `^%` so no `%name%` can form. Backslashes ending a run are doubled, as
+/// programs would otherwise read `\"` as a literal quote.
+fn escape_cmd(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len() + 2);
+    for (i, run) in arg.split('%').enumerate() {
+        if i > 0 {
+            out.push_str("^%");
+        }
+        if run.is_empty() {
+            continue;
+        }
+        let trailing = run.len() - run.trim_end_matches('\\').len();
+        out.push('"');
+        out.push_str(run);
+        out.push_str(&"\\".repeat(trailing));
+        out.push('"');
+    }
+    if out.is_empty() {
+        out.push_str("\"\"");
+    }
+    out
+}
+
+/// Words of the command line a terminal starts: split on whitespace, except
+/// inside single quotes, with `\'` outside them as a quote (which is how
+/// `Shell::Posix` escapes one). Other backslashes are kept, so Windows paths
+/// need no quoting.
+pub fn split_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '\\' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                word.get_or_insert_with(String::new).push('\'');
+            }
+            c if c.is_whitespace() => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Shell to escape a path typed into a terminal for. `foreground` is the
+/// program holding the terminal when it is not the terminal's own shell,
+/// `running` the family of that shell as far as it is known and
+/// `configured` the session's override. None while a program that is not
+/// a shell (an agent, say) has the foreground.
+pub fn prompt_shell(
+    configured: Option<Shell>,
+    foreground: Option<&str>,
+    running: Option<Shell>,
+) -> Option<Shell> {
+    let detected = match foreground {
+        Some(program) => Some(Shell::from_program(program)?),
+        None => running,
+    };
+    Some(configured.or(detected).unwrap_or_else(Shell::login))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paths every shell should take as they are
+    const PLAIN: &[&str] = &[
+        "src/main.rs",
+        "a-b_c.d/e:f",
+        "日本語/ファイル.txt",
+        "Ünïcödé",
+    ];
+
+    fn escaped(shell: Shell, paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| shell.escape(p).into_owned()).collect()
+    }
+
+    #[test]
+    fn test_shells_are_recognized() {
+        assert_eq!(Shell::from_program("/bin/bash"), Some(Shell::Posix));
+        assert_eq!(Shell::from_program("-zsh"), Some(Shell::Posix));
+        assert_eq!(Shell::from_program("zsh -l"), Some(Shell::Posix));
+        assert_eq!(
+            Shell::from_program("/usr/local/bin/fish"),
+            Some(Shell::Fish)
+        );
+        // Split on whitespace, as the terminal splits its command
+        assert_eq!(
+            Shell::from_program(r"C:\Program Files\PowerShell\7\pwsh.exe"),
+            None
+        );
+        assert_eq!(Shell::from_program("pwsh.exe -NoLogo"), Some(Shell::Pwsh));
+        assert_eq!(Shell::from_program("PowerShell"), Some(Shell::Pwsh));
+        assert_eq!(
+            Shell::from_program(r"C:\Windows\System32\cmd.exe"),
+            Some(Shell::Cmd)
+        );
+        assert_eq!(Shell::from_program("claude"), None);
+        assert_eq!(Shell::from_program(""), None);
+
+        assert_eq!(Shell::parse("posix"), Some(Shell::Posix));
+        assert_eq!(Shell::parse("PowerShell"), Some(Shell::Pwsh));
+        assert_eq!(Shell::parse("fish"), Some(Shell::Fish));
+        assert_eq!(Shell::parse("cmd"), Some(Shell::Cmd));
+        assert_eq!(Shell::parse("nushell"), None);
+    }
+
+    #[test]
+    fn test_posix_quoting() {
+        let shell = Shell::Posix;
+        assert_eq!(escaped(shell, PLAIN), PLAIN);
+        assert!(matches!(shell.escape("src/lib.rs"), Cow::Borrowed(_)));
+        assert_eq!(
+            escaped(
+                shell,
+                &[
+                    "my file.rs",
+                    "it's.txt",
+                    "$(rm -rf ~).txt",
+                    "`date`",
+                    "wow!.md",
+                    "say \"hi\"",
+                    r"back\slash",
+                    "",
+                ]
+            ),
+            [
+                "'my file.rs'",
+                r"'it'\''s.txt'",
+                "'$(rm -rf ~).txt'",
+                "'`date`'",
+                "'wow!.md'",
+                "'say \"hi\"'",
+                r"'back\slash'",
+                "''",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fish_quoting() {
+        let shell = Shell::Fish;
+        assert_eq!(escaped(shell, PLAIN), PLAIN);
+        assert_eq!(
+            escaped(
+                shell,
+                &[
+                    "my file.rs",
+                    "it's.txt",
+                    "$(rm -rf ~).txt",
+                    r"back\slash\",
+                    "100%.txt",
+                    "",
+                ]
+            ),
+            [
+                "'my file.rs'",
+                r"'it\'s.txt'",
+                "'$(rm -rf ~).txt'",
+                r"'back\\slash\\'",
+                "'100%.txt'",
+                "''",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_powershell_quoting() {
+        let shell = Shell::Pwsh;
+        assert_eq!(escaped(shell, PLAIN), PLAIN);
+        assert_eq!(shell.escape(r"C:\src\main.rs"), r"C:\src\main.rs");
+        assert_eq!(
+            escaped(
+                shell,
+                &[
+                    r"C:\My Files\a.rs",
+                    "it's.txt",
+                    "it\u{2019}s.txt",
+                    "$(Get-Date).txt",
+                    "`n.txt",
+                    "a,b.txt",
+                    r"C:\dir with space\",
+                    "",
+                ]
+            ),
+            [
+                r"'C:\My Files\a.rs'",
+                "'it''s.txt'",
+                "'it\u{2019}\u{2019}s.txt'",
+                "'$(Get-Date).txt'",
+                "'`n.txt'",
+                "'a,b.txt'",
+                r"'C:\dir with space\'",
+                "''",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmd_quoting() {
+        let shell = Shell::Cmd;
+        assert_eq!(escaped(shell, PLAIN), PLAIN);
+        assert_eq!(shell.escape(r"C:\src\main.rs"), r"C:\src\main.rs");
+        assert_eq!(
+            escaped(
+                shell,
+                &[
+                    r"C:\My Files\a.rs",
+                    "a&b.txt",
+                    "x^y (1).txt",
+                    "%PATH%.txt",
+                    "100%",
+                    r"C:\dir with space\",
+                    r"C:\trail\\",
+                    "",
+                ]
+            ),
+            [
+                r#""C:\My Files\a.rs""#,
+                r#""a&b.txt""#,
+                r#""x^y (1).txt""#,
+                r#"^%"PATH"^%".txt""#,
+                r#""100"^%"#,
+                r#""C:\dir with space\\""#,
+                // Not quoted, so nothing to protect
+                r"C:\trail\\",
+                r#""""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_words_undoes_posix_quoting() {
+        assert_eq!(split_words("  fish   -l "), ["fish", "-l"]);
+        assert!(split_words("").is_empty());
+        assert_eq!(
+            split_words(r"C:\tools\agent.exe --dir C:\src"),
+            [r"C:\tools\agent.exe", "--dir", r"C:\src"]
+        );
+        let args = ["my file.rs", "it's", "$(x) `y`", r"back\slash", "", "plain"];
+        let line = args
+            .iter()
+            .map(|arg| Shell::Posix.escape(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_words(&line), args);
+    }
+
+    #[test]
+    fn test_prompt_shell() {
+        // An agent in the foreground takes the path as it is
+        assert_eq!(prompt_shell(None, Some("claude"), Some(Shell::Posix)), None);
+        assert_eq!(prompt_shell(Some(Shell::Cmd), Some("node"), None), None);
+        // A shell started from the terminal's shell wins over it
+        assert_eq!(
+            prompt_shell(None, Some("fish"), Some(Shell::Posix)),
+            Some(Shell::Fish)
+        );
+        assert_eq!(
+            prompt_shell(None, None, Some(Shell::Fish)),
+            Some(Shell::Fish)
+        );
+        // The session's setting wins over what was detected
+        assert_eq!(
+            prompt_shell(Some(Shell::Pwsh), Some("bash"), Some(Shell::Fish)),
+            Some(Shell::Pwsh)
+        );
+        assert_eq!(
+            prompt_shell(Some(Shell::Cmd), None, Some(Shell::Posix)),
+            Some(Shell::Cmd)
+        );
+        // Nothing known: the login shell
+        assert_eq!(prompt_shell(None, None, None), Some(Shell::login()));
+    }
+}
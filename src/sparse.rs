@@ -0,0 +1,162 @@
+//! Sparse-checkout patterns of a worktree
+//!
+//! A sparse worktree only materializes part of the tree. In cone mode
+//! `git sparse-checkout list` prints the directories in the cone: their
+//! files are checked out recursively, along with the files directly inside
+//! the root and every directory leading to them. Without cone mode it prints
+//! gitignore-style patterns (`/*`, `!/*/`, `/docs/`), of which the last one
+//! matching a path or one of its directories decides.
+
+use crate::branch_glob;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseCheckout {
+    /// Whether the patterns are cone directories
+    pub cone: bool,
+    /// Directories (cone mode) or patterns, as listed
+    pub patterns: Vec<String>,
+}
+
+impl SparseCheckout {
+    /// Parse `git sparse-checkout list` output
+    pub fn parse(output: &str, cone: bool) -> Self {
+        let patterns = output
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                if cone {
+                    line.trim_matches('/').to_string()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        Self { cone, patterns }
+    }
+
+    /// Whether `path` (relative to the worktree) is inside the checkout
+    pub fn includes(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        if self.cone {
+            if is_dir {
+                self.cone_dir_included(&path)
+            } else {
+                let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+                self.cone_dir_included(parent)
+            }
+        } else {
+            self.pattern_included(&path, is_dir)
+        }
+    }
+
+    /// The root, a directory in the cone or below, or one leading to it
+    fn cone_dir_included(&self, dir: &str) -> bool {
+        dir.is_empty()
+            || self
+                .patterns
+                .iter()
+                .any(|cone| is_within(dir, cone) || is_within(cone, dir))
+    }
+
+    /// The last pattern matching `path` or one of its directories decides
+    fn pattern_included(&self, path: &str, is_dir: bool) -> bool {
+        let mut included = false;
+        for pattern in &self.patterns {
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern.as_str()),
+            };
+            let matched = ancestors(path).any(|dir| pattern_matches(pattern, dir, true))
+                || pattern_matches(pattern, path, is_dir);
+            if matched {
+                included = !negated;
+            }
+        }
+        included
+    }
+}
+
+/// Whether `path` is `dir` or below it
+fn is_within(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Directories leading to `path`, outermost first ("a", "a/b" for "a/b/c")
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(move |(i, _)| &path[..i])
+}
+
+/// Whether one gitignore-style pattern matches `path`. A trailing `/` only
+/// matches directories; a pattern with a `/` anywhere else is anchored at
+/// the root, one without matches names at any depth.
+fn pattern_matches(pattern: &str, path: &str, is_dir: bool) -> bool {
+    let (pattern, dir_only) = match pattern.strip_suffix('/') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    if dir_only && !is_dir {
+        return false;
+    }
+    if pattern.contains('/') {
+        branch_glob::matches(pattern.trim_start_matches('/'), path)
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        branch_glob::matches(pattern, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn includes(sparse: &SparseCheckout, path: &str) -> bool {
+        sparse.includes(Path::new(path), false)
+    }
+
+    #[test]
+    fn test_parse_cone_list() {
+        let sparse = SparseCheckout::parse("src/app\ndocs/\n\n", true);
+        assert_eq!(sparse.patterns, vec!["src/app", "docs"]);
+        assert!(sparse.cone);
+    }
+
+    #[test]
+    fn test_cone_includes_root_files_and_leading_directories() {
+        let sparse = SparseCheckout::parse("src/app\n", true);
+        assert!(includes(&sparse, "README.md"));
+        // Files directly inside a directory leading to the cone
+        assert!(includes(&sparse, "src/main.rs"));
+        assert!(includes(&sparse, "src/app/view.rs"));
+        assert!(includes(&sparse, "src/app/ui/list.rs"));
+        assert!(!includes(&sparse, "src/lib/mod.rs"));
+        assert!(!includes(&sparse, "docs/guide.md"));
+        // A sibling sharing the cone's name as a prefix is outside
+        assert!(!includes(&sparse, "src/apps/main.rs"));
+
+        assert!(sparse.includes(Path::new("src"), true));
+        assert!(sparse.includes(Path::new("src/app/ui"), true));
+        assert!(!sparse.includes(Path::new("src/lib"), true));
+        assert!(!sparse.includes(Path::new("vendor"), true));
+    }
+
+    #[test]
+    fn test_non_cone_last_matching_pattern_wins() {
+        let sparse = SparseCheckout::parse("/*\n!/*/\n/docs/\n*.md\n", false);
+        assert!(includes(&sparse, "Cargo.toml"));
+        assert!(!includes(&sparse, "src/main.rs"));
+        assert!(includes(&sparse, "docs/guide/intro.txt"));
+        // Unanchored: a name at any depth
+        assert!(includes(&sparse, "src/nested/NOTES.md"));
+        assert!(sparse.includes(Path::new("docs"), true));
+        assert!(!sparse.includes(Path::new("src"), true));
+    }
+
+    #[test]
+    fn test_non_cone_without_patterns_includes_nothing() {
+        let sparse = SparseCheckout::parse("", false);
+        assert!(!includes(&sparse, "README.md"));
+    }
+}
@@ -5,11 +5,26 @@
 //! - File copies (glob patterns copied from main worktree to new)
 //! - Post-create commands (run in the new worktree after creation)
 //! - Working directory (relative to worktree root)
+//! - Environment of the agents launched in the worktree (see `agents`)
 //!
-//! Configuration is stored in git config under `[sashiki "template"]`.
+//! Configuration is stored in git config under `[sashiki "template"]`; a
+//! committed `.sashikiconfig` can provide defaults (see `settings`).
+//!
+//! Named templates live under `[sashiki "template.<name>"]` with the same
+//! keys plus `match`, a branch glob (see `branch_glob`). A new worktree gets
+//! the first named template, in definition order, whose glob matches its
+//! branch; the unnamed template is the fallback.
+//!
+//! `TemplateSet::plan` works out what creating a worktree would do without
+//! running anything, for the preview in the create dialog.
 
-use crate::git::{self, GitRepo};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::branch_glob;
+use crate::git::{self, ConfigSource, GitRepo};
+use crate::settings::{self, Settings};
+use crate::worktree_guard;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 /// Session template configuration loaded from git config
 #[derive(Debug, Clone, Default)]
@@ -22,33 +37,48 @@ pub struct TemplateConfig {
     pub post_create_commands: Vec<String>,
     /// Working directory relative to worktree root (for terminal and post-create commands)
     pub working_directory: Option<String>,
+    /// `KEY=VALUE` entries set for agents launched in the worktree
+    pub env: Vec<String>,
 }
 
 impl TemplateConfig {
-    /// Load template config from git config
-    pub fn load(repo: &GitRepo) -> Self {
+    /// The unnamed (fallback) template
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::from_settings_named(settings, None)
+    }
+
+    /// The template called `name`, or the unnamed one for `None`
+    fn from_settings_named(settings: &Settings, name: Option<&str>) -> Self {
+        let key = |key: &str| git::template_key(name, key);
         Self {
-            pre_create_commands: repo.get_config_values(git::CONFIG_PRE_CREATE_CMD),
-            file_copies: repo.get_config_values(git::CONFIG_FILE_COPY),
-            post_create_commands: repo.get_config_values(git::CONFIG_POST_CREATE_CMD),
-            working_directory: repo.get_config_value(git::CONFIG_WORKING_DIR),
+            pre_create_commands: settings.get_all(&key(git::CONFIG_PRE_CREATE_CMD)),
+            file_copies: settings.get_all(&key(git::CONFIG_FILE_COPY)),
+            post_create_commands: settings.get_all(&key(git::CONFIG_POST_CREATE_CMD)),
+            working_directory: settings.get(&key(git::CONFIG_WORKING_DIR)),
+            env: settings.get_all(&key(git::CONFIG_TEMPLATE_ENV)),
         }
     }
 
-    /// Save template config to local git config
-    pub fn save(&self, repo: &GitRepo) -> git::Result<()> {
-        repo.set_config_values(git::CONFIG_PRE_CREATE_CMD, &self.pre_create_commands)?;
-        repo.set_config_values(git::CONFIG_FILE_COPY, &self.file_copies)?;
-        repo.set_config_values(git::CONFIG_POST_CREATE_CMD, &self.post_create_commands)?;
+    /// Save template config to local git config (under `name` if given)
+    fn save_named(&self, repo: &GitRepo, name: Option<&str>) -> git::Result<()> {
+        let key = |key: &str| git::template_key(name, key);
+        repo.set_config_values(&key(git::CONFIG_PRE_CREATE_CMD), &self.pre_create_commands)?;
+        repo.set_config_values(&key(git::CONFIG_FILE_COPY), &self.file_copies)?;
+        repo.set_config_values(
+            &key(git::CONFIG_POST_CREATE_CMD),
+            &self.post_create_commands,
+        )?;
+        repo.set_config_values(&key(git::CONFIG_TEMPLATE_ENV), &self.env)?;
 
+        let working_dir_key = key(git::CONFIG_WORKING_DIR);
         if let Some(ref dir) = self.working_directory {
             if !dir.is_empty() {
-                repo.set_config_value(git::CONFIG_WORKING_DIR, dir)?;
+                repo.set_config_value(&working_dir_key, dir)?;
             } else {
-                repo.remove_config_key(git::CONFIG_WORKING_DIR)?;
+                repo.remove_config_key(&working_dir_key)?;
             }
         } else {
-            repo.remove_config_key(git::CONFIG_WORKING_DIR)?;
+            repo.remove_config_key(&working_dir_key)?;
         }
 
         Ok(())
@@ -136,6 +166,180 @@ impl TemplateConfig {
     }
 }
 
+/// A template used for branches matching a glob
+#[derive(Debug, Clone, Default)]
+pub struct NamedTemplate {
+    pub name: String,
+    /// Branch glob (`sashiki.template.<name>.match`)
+    pub branch_match: String,
+    pub config: TemplateConfig,
+}
+
+/// All templates of a repository: the named ones in definition order and the
+/// unnamed fallback
+#[derive(Debug, Clone, Default)]
+pub struct TemplateSet {
+    pub named: Vec<NamedTemplate>,
+    pub fallback: TemplateConfig,
+}
+
+impl TemplateSet {
+    /// Load all templates from the layered settings (see `settings`)
+    pub fn load(repo: &GitRepo) -> Self {
+        let (settings, _) = Settings::load(Some(repo));
+        Self::from_settings(&settings)
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        let named = settings
+            .subsection_names(git::CONFIG_TEMPLATE_PREFIX)
+            .into_iter()
+            .map(|name| NamedTemplate {
+                branch_match: settings
+                    .get(&git::template_match_key(&name))
+                    .unwrap_or_default(),
+                config: TemplateConfig::from_settings_named(settings, Some(&name)),
+                name,
+            })
+            .collect();
+        Self {
+            named,
+            fallback: TemplateConfig::from_settings(settings),
+        }
+    }
+
+    /// Save all templates to local git config. Named templates no longer in
+    /// the set (deleted or renamed) are removed.
+    pub fn save(&self, repo: &GitRepo) -> git::Result<()> {
+        let local = git::read_config_entries(ConfigSource::Local(repo.workdir()))?;
+        let stored = settings::subsection_names(
+            local.iter().map(|(key, _)| key.as_str()),
+            git::CONFIG_TEMPLATE_PREFIX,
+        );
+        for name in stored {
+            repo.remove_config_section(&format!("{}.{}", git::CONFIG_TEMPLATE_PREFIX, name));
+        }
+
+        self.fallback.save_named(repo, None)?;
+        for template in &self.named {
+            repo.set_config_value(
+                &git::template_match_key(&template.name),
+                &template.branch_match,
+            )?;
+            template.config.save_named(repo, Some(&template.name))?;
+        }
+        Ok(())
+    }
+
+    /// First named template whose glob matches `branch`
+    pub fn matching(&self, branch: &str) -> Option<&NamedTemplate> {
+        self.named
+            .iter()
+            .find(|t| !t.branch_match.is_empty() && branch_glob::matches(&t.branch_match, branch))
+    }
+
+    /// Template for a worktree on `branch` (the fallback when no glob
+    /// matches or the worktree has no branch)
+    pub fn for_branch(&self, branch: Option<&str>) -> &TemplateConfig {
+        branch
+            .and_then(|b| self.matching(b))
+            .map(|t| &t.config)
+            .unwrap_or(&self.fallback)
+    }
+
+    /// What creating a worktree on `branch` at `worktree_path` would do with
+    /// the template that applies, resolving the file copies against
+    /// `main_workdir` as they match now. Nothing is run or copied.
+    pub fn plan(
+        &self,
+        branch: Option<&str>,
+        main_workdir: &Path,
+        worktree_path: Option<&Path>,
+    ) -> CreationPlan {
+        let matched = branch.and_then(|b| self.matching(b));
+        let template = matched.map(|t| &t.config).unwrap_or(&self.fallback);
+        CreationPlan {
+            template: matched.map(|t| t.name.clone()),
+            worktree_path: worktree_path.map(Path::to_path_buf),
+            pre_create_commands: template.pre_create_commands.clone(),
+            file_copies: template
+                .file_copies
+                .iter()
+                .map(|pattern| preview_copy(main_workdir, pattern))
+                .collect(),
+            post_create_commands: template.post_create_commands.clone(),
+            working_directory: worktree_path.map(|path| template.resolve_working_directory(path)),
+        }
+    }
+}
+
+/// Files listed per pattern in a creation plan; the rest are counted
+pub const PLAN_FILE_LIMIT: usize = 10;
+
+/// What creating a worktree would do (see `TemplateSet::plan`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreationPlan {
+    /// Named template that applies (None = the unnamed fallback)
+    pub template: Option<String>,
+    /// Where the worktree goes (None when it cannot be told yet)
+    pub worktree_path: Option<PathBuf>,
+    /// Run in the main worktree, in order
+    pub pre_create_commands: Vec<String>,
+    pub file_copies: Vec<PlannedCopy>,
+    /// Run in `working_directory`, in order
+    pub post_create_commands: Vec<String>,
+    /// Where the post-create commands and the first terminal run
+    pub working_directory: Option<PathBuf>,
+}
+
+/// A `fileCopy` pattern and the files it matches in the main worktree now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCopy {
+    pub pattern: String,
+    /// Up to `PLAN_FILE_LIMIT` matching files, relative to the main worktree
+    pub files: Vec<PathBuf>,
+    /// Matching files past the listed ones
+    pub more: usize,
+    /// Why the pattern cannot be used
+    pub error: Option<String>,
+}
+
+/// Resolve `pattern` against `source_root` the way `copy_files` does
+fn preview_copy(source_root: &Path, pattern: &str) -> PlannedCopy {
+    let mut planned = PlannedCopy {
+        pattern: pattern.to_string(),
+        files: Vec::new(),
+        more: 0,
+        error: None,
+    };
+    let full_pattern = source_root.join(pattern).to_string_lossy().to_string();
+    let paths = match glob::glob(&full_pattern) {
+        Ok(paths) => paths,
+        Err(e) => {
+            planned.error = Some(format!("Invalid pattern '{}': {}", pattern, e));
+            return planned;
+        }
+    };
+    // Unreadable entries and directories are skipped when copying, too
+    for path in paths.flatten().filter(|path| path.is_file()) {
+        if planned.files.len() == PLAN_FILE_LIMIT {
+            planned.more += 1;
+        } else if let Ok(relative) = path.strip_prefix(source_root) {
+            planned.files.push(relative.to_path_buf());
+        }
+    }
+    planned
+}
+
+/// Whether `name` can name a template: letters, digits, `-` and `_` (it
+/// becomes part of a git config key)
+pub fn is_valid_template_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 /// Result of a single file copy operation
 #[derive(Debug, Clone)]
 pub struct FileCopyResult {
@@ -157,8 +361,18 @@ fn copy_single_file(source_root: &Path, dest_root: &Path, src_path: &Path) -> Fi
         }
     };
 
-    let dest_path = dest_root.join(relative);
     let rel_str = relative.to_string_lossy().to_string();
+    // A link in the new worktree must not carry the copy outside it
+    let dest_path = match worktree_guard::check(dest_root, relative) {
+        Ok(path) => path,
+        Err(e) => {
+            return FileCopyResult {
+                path: rel_str,
+                success: false,
+                error: Some(e.to_string()),
+            };
+        }
+    };
 
     // Don't overwrite existing files
     if dest_path.exists() {
@@ -194,34 +408,237 @@ fn copy_single_file(source_root: &Path, dest_root: &Path, src_path: &Path) -> Fi
     }
 }
 
-/// Run a shell command synchronously in the given working directory
+/// Run a shell command synchronously in the given working directory.
+///
+/// Failures are recorded in the app log with the command and its stderr.
 pub fn run_shell_command(cmd: &str, workdir: &Path) -> std::result::Result<(), String> {
+    let started = Instant::now();
+
     #[cfg(unix)]
-    let output = std::process::Command::new("sh")
-        .args(["-c", cmd])
-        .current_dir(workdir)
-        .output();
+    let (program, args) = ("sh", ["-c", cmd]);
 
     #[cfg(windows)]
-    let output = std::process::Command::new("cmd")
-        .args(["/C", cmd])
+    let (program, args) = ("cmd", ["/C", cmd]);
+
+    let output = std::process::Command::new(program)
+        .args(args)
         .current_dir(workdir)
         .output();
 
-    match output {
-        Ok(o) if o.status.success() => Ok(()),
+    let (msg, stderr) = match output {
+        Ok(o) if o.status.success() => return Ok(()),
         Ok(o) => {
             let stderr = String::from_utf8_lossy(&o.stderr).trim().to_string();
             let stdout = String::from_utf8_lossy(&o.stdout).trim().to_string();
             let msg = if !stderr.is_empty() {
-                stderr
+                stderr.clone()
             } else if !stdout.is_empty() {
                 stdout
             } else {
                 format!("Command exited with status: {}", o.status)
             };
-            Err(msg)
+            (msg, stderr)
         }
-        Err(e) => Err(e.to_string()),
+        Err(e) => (e.to_string(), String::new()),
+    };
+
+    app_log::record(
+        LogEntry::new(
+            LogLevel::Error,
+            LogSource::Template,
+            format!(
+                "Template command failed after {} ms in {}: {}",
+                started.elapsed().as_millis(),
+                workdir.display(),
+                msg.lines().next().unwrap_or_default()
+            ),
+        )
+        .with_command(cmd)
+        .with_stderr(&stderr),
+    );
+    Err(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(entries: &[(&str, &str)]) -> Settings {
+        Settings::from_layers(vec![
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ])
+    }
+
+    #[test]
+    fn test_named_templates_first_match_wins() {
+        let set = TemplateSet::from_settings(&settings(&[
+            (git::CONFIG_POST_CREATE_CMD, "make"),
+            ("sashiki.template.frontend.match", "frontend/**"),
+            ("sashiki.template.frontend.postCreateCommand", "npm ci"),
+            ("sashiki.template.frontend.workingDirectory", "web"),
+            ("sashiki.template.login.match", "frontend/login"),
+            ("sashiki.template.backend.match", "backend/*"),
+            ("sashiki.template.backend.postCreateCommand", "cargo fetch"),
+        ]));
+
+        let names: Vec<&str> = set.named.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["frontend", "login", "backend"]);
+        assert_eq!(set.fallback.post_create_commands, vec!["make"]);
+
+        let frontend = set.matching("frontend/login").unwrap();
+        assert_eq!(frontend.name, "frontend");
+        assert_eq!(frontend.config.post_create_commands, vec!["npm ci"]);
+        assert_eq!(frontend.config.working_directory.as_deref(), Some("web"));
+        assert_eq!(
+            set.for_branch(Some("backend/api")).post_create_commands,
+            vec!["cargo fetch"]
+        );
+        assert!(set.matching("backend/api/v2").is_none());
+        assert_eq!(
+            set.for_branch(Some("docs/readme")).post_create_commands,
+            vec!["make"]
+        );
+        assert_eq!(set.for_branch(None).post_create_commands, vec!["make"]);
+    }
+
+    #[test]
+    fn test_legacy_flat_keys_are_the_fallback() {
+        let set = TemplateSet::from_settings(&settings(&[
+            (git::CONFIG_PRE_CREATE_CMD, "git pull --ff-only"),
+            (git::CONFIG_FILE_COPY, ".env"),
+            (git::CONFIG_WORKING_DIR, "app"),
+        ]));
+        assert!(set.named.is_empty());
+        let template = set.for_branch(Some("feature/x"));
+        assert_eq!(template.pre_create_commands, vec!["git pull --ff-only"]);
+        assert_eq!(template.file_copies, vec![".env"]);
+        assert_eq!(template.working_directory.as_deref(), Some("app"));
+    }
+
+    #[test]
+    fn test_template_without_match_is_never_selected() {
+        let set = TemplateSet::from_settings(&settings(&[(
+            "sashiki.template.draft.postCreateCommand",
+            "true",
+        )]));
+        assert_eq!(set.named.len(), 1);
+        assert!(set.matching("draft").is_none());
+    }
+
+    #[test]
+    fn test_save_replaces_renamed_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        crate::test_support::git(dir.path(), &["init", "-q", "-b", "main"]);
+        let repo = GitRepo::open(dir.path()).unwrap();
+
+        let mut set = TemplateSet {
+            named: vec![NamedTemplate {
+                name: "web".to_string(),
+                branch_match: "frontend/*".to_string(),
+                config: TemplateConfig {
+                    post_create_commands: vec!["npm ci".to_string()],
+                    ..Default::default()
+                },
+            }],
+            fallback: TemplateConfig {
+                post_create_commands: vec!["make".to_string()],
+                env: vec!["CI=1".to_string(), "NODE_ENV=test".to_string()],
+                ..Default::default()
+            },
+        };
+        set.save(&repo).unwrap();
+
+        set.named[0].name = "frontend".to_string();
+        set.save(&repo).unwrap();
+
+        let loaded = TemplateSet::load(&repo);
+        let names: Vec<&str> = loaded.named.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["frontend"]);
+        assert_eq!(loaded.named[0].branch_match, "frontend/*");
+        assert_eq!(loaded.named[0].config.post_create_commands, vec!["npm ci"]);
+        assert_eq!(loaded.fallback.post_create_commands, vec!["make"]);
+        assert_eq!(loaded.fallback.env, vec!["CI=1", "NODE_ENV=test"]);
+    }
+
+    #[test]
+    fn test_plan_resolves_template_and_copies() {
+        let main = tempfile::tempdir().unwrap();
+        std::fs::create_dir(main.path().join("config")).unwrap();
+        for name in ["a.toml", "b.toml"] {
+            std::fs::write(main.path().join("config").join(name), "").unwrap();
+        }
+        std::fs::write(main.path().join(".env"), "").unwrap();
+        let set = TemplateSet::from_settings(&settings(&[
+            (git::CONFIG_POST_CREATE_CMD, "make"),
+            ("sashiki.template.web.match", "frontend/*"),
+            ("sashiki.template.web.preCreateCommand", "git fetch"),
+            ("sashiki.template.web.fileCopy", ".env"),
+            ("sashiki.template.web.fileCopy", "config/*.toml"),
+            ("sashiki.template.web.fileCopy", "missing/*"),
+            ("sashiki.template.web.postCreateCommand", "npm ci"),
+            ("sashiki.template.web.workingDirectory", "web"),
+        ]));
+        let worktree = Path::new("/repo.worktrees/frontend-login");
+
+        let plan = set.plan(Some("frontend/login"), main.path(), Some(worktree));
+        assert_eq!(plan.template.as_deref(), Some("web"));
+        assert_eq!(plan.worktree_path.as_deref(), Some(worktree));
+        assert_eq!(plan.pre_create_commands, vec!["git fetch"]);
+        let copies: Vec<(&str, Vec<&Path>)> = plan
+            .file_copies
+            .iter()
+            .map(|c| {
+                (
+                    c.pattern.as_str(),
+                    c.files.iter().map(PathBuf::as_path).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            copies,
+            vec![
+                (".env", vec![Path::new(".env")]),
+                (
+                    "config/*.toml",
+                    vec![Path::new("config/a.toml"), Path::new("config/b.toml")]
+                ),
+                ("missing/*", vec![]),
+            ]
+        );
+        assert_eq!(plan.post_create_commands, vec!["npm ci"]);
+        assert_eq!(plan.working_directory, Some(worktree.join("web")));
+
+        // Other branches get the fallback; an unknown path leaves no directory
+        let plan = set.plan(Some("docs/readme"), main.path(), None);
+        assert_eq!(plan.template, None);
+        assert!(plan.file_copies.is_empty());
+        assert_eq!(plan.post_create_commands, vec!["make"]);
+        assert_eq!(plan.working_directory, None);
+    }
+
+    #[test]
+    fn test_plan_caps_listed_files() {
+        let main = tempfile::tempdir().unwrap();
+        for i in 0..PLAN_FILE_LIMIT + 3 {
+            std::fs::write(main.path().join(format!("{:02}.txt", i)), "").unwrap();
+        }
+        let set = TemplateSet::from_settings(&settings(&[(git::CONFIG_FILE_COPY, "*.txt")]));
+        let plan = set.plan(None, main.path(), None);
+        let copy = &plan.file_copies[0];
+        assert_eq!(copy.files.len(), PLAN_FILE_LIMIT);
+        assert_eq!(copy.more, 3);
+        assert_eq!(copy.error, None);
+    }
+
+    #[test]
+    fn test_template_names() {
+        assert!(is_valid_template_name("frontend"));
+        assert!(is_valid_template_name("api_v2-beta"));
+        assert!(!is_valid_template_name(""));
+        assert!(!is_valid_template_name("front end"));
+        assert!(!is_valid_template_name("a.b"));
     }
 }
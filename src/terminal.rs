@@ -5,33 +5,119 @@
 //! ## Module structure
 //! - `view`: Main TerminalView struct, initialization, mouse/IME handling, Render
 //! - `keybindings`: Action definitions, key bindings, action handlers
+//! - `code_scan`: Code blocks found in the grid on demand, kept on their
+//!   rows as output arrives
+//! - `copy_mode`: Cursor movement and selection of the keyboard copy mode
 //! - `element`: TerminalElement for custom GPUI rendering
+//! - `filter`: Showing only the rows that match a query, matched a budget of
+//!   rows per frame
+//! - `follow`: Whether a view scrolls along with new output, and what
+//!   arrived below it while scrolled back
+//! - `foreground`: Name of the program in the foreground (unix only)
+//! - `glance`: Last rows of the grid as text, for the sidebar thumbnails
+//! - `handle`: TerminalView as the terminal of a session (`ViewTerminals`)
+//! - `prompt_marks`: Prompt and command output lines reported by shell
+//!   integration, kept in step with scrolling and reflow
+//! - `pty_writer`: Input queue written to the PTY on a background thread
+//! - `reflow`: Logical lines of the grid, and points carried across the
+//!   reflow of a resize
+//! - `resize`: Grid size of a terminal element, debounced PTY resizes
+//! - `saved_lines`: The scrollback as the logical lines saved across
+//!   restarts, and saved lines put into a new grid
+//! - `scroll`: Turning wheel and touchpad deltas into whole-line scrolls
+//! - `tee_pty`: PTY wrapper recording output into the session transcript,
+//!   scanning it for the commands the shell reports and placing its markers
 
+mod code_scan;
+mod copy_mode;
 mod element;
+mod filter;
+mod follow;
+#[cfg(unix)]
+mod foreground;
+mod glance;
+mod handle;
 mod keybindings;
+mod prompt_marks;
+mod pty_writer;
+mod reflow;
+mod resize;
+mod saved_lines;
+mod scroll;
+#[cfg(unix)]
+mod tee_pty;
 mod view;
 
+pub use glance::TerminalTail;
+pub use handle::ViewTerminals;
+pub use keybindings::ApplyCodeBlock;
+pub use resize::row_height;
 pub use view::TerminalView;
 
-use alacritty_terminal::event::{Event as AlacEvent, EventListener, Notify, WindowSize};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::command_history::ReportedCommands;
+use crate::shell_escape;
+use crate::transcript::SessionTranscript;
+use alacritty_terminal::event::{Event as AlacEvent, EventListener, WindowSize};
 use alacritty_terminal::event_loop::{EventLoop, Msg, Notifier};
 use alacritty_terminal::grid::Scroll;
 use alacritty_terminal::sync::FairMutex;
 use alacritty_terminal::term::test::TermSize;
 use alacritty_terminal::term::{Config as TermConfig, Term};
 use alacritty_terminal::tty;
-use std::sync::Arc;
+use prompt_marks::PromptMarks;
+use pty_writer::PtyWriter;
+use reflow::CarriedPoints;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Longest window title kept (in chars); longer titles are cut
+const MAX_TITLE_CHARS: usize = 256;
 
 pub struct Terminal {
     term: Arc<FairMutex<Term<TerminalEventListener>>>,
     pty_tx: Notifier,
+    /// Keyboard and paste input, written on its own thread
+    input: PtyWriter,
     /// Current terminal size (cols, lines) for deduplication
     current_size: std::sync::Mutex<(u16, u16)>,
+    title: Arc<TitleState>,
+    /// Commands reported through shell integration (unix only)
+    commands: Arc<ReportedCommands>,
+    /// Prompt marks placed through shell integration (unix only)
+    marks: Arc<Mutex<PromptMarks>>,
+    /// Duplicate of the PTY master, for asking who has the foreground
+    #[cfg(unix)]
+    master: std::fs::File,
+    #[cfg(unix)]
+    shell_pid: u32,
+}
+
+/// Latest window title set via OSC 0/2, shared with the event listener
+#[derive(Default)]
+struct TitleState {
+    title: std::sync::Mutex<Option<String>>,
+    /// Set when a title change has not been announced to the view yet
+    pending: AtomicBool,
 }
 
 #[derive(Clone)]
 pub struct TerminalEventListener {
     sender: smol::channel::Sender<TerminalEvent>,
+    title: Arc<TitleState>,
+}
+
+impl TerminalEventListener {
+    /// Store the new title and announce it once until the view picks it up,
+    /// so a program retitling in a tight loop cannot flood the event channel.
+    fn set_title(&self, title: Option<String>) {
+        if let Ok(mut current) = self.title.title.lock() {
+            *current = title;
+        }
+        if !self.title.pending.swap(true, Ordering::AcqRel) {
+            let _ = self.sender.try_send(TerminalEvent::Title);
+        }
+    }
 }
 
 impl EventListener for TerminalEventListener {
@@ -40,7 +126,9 @@ impl EventListener for TerminalEventListener {
             AlacEvent::Wakeup => TerminalEvent::Wakeup,
             AlacEvent::Bell => TerminalEvent::Bell,
             AlacEvent::Exit => TerminalEvent::Exit,
-            AlacEvent::Title(_) => TerminalEvent::Title,
+            AlacEvent::ChildExit(code) => TerminalEvent::ChildExit(code),
+            AlacEvent::Title(title) => return self.set_title(sanitize_title(&title)),
+            AlacEvent::ResetTitle => return self.set_title(None),
             _ => return,
         };
         // Ignore send failure - channel full or receiver dropped is non-fatal
@@ -48,32 +136,73 @@ impl EventListener for TerminalEventListener {
     }
 }
 
+/// Clean up a title from an OSC 0/2 sequence for display.
+///
+/// The parser already drops payload segments that are not valid UTF-8; this
+/// strips control characters, trims, caps the length and maps an empty
+/// result to "no title".
+fn sanitize_title(raw: &str) -> Option<String> {
+    let title: String = raw
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_TITLE_CHARS)
+        .collect();
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
 #[derive(Debug, Clone)]
 pub enum TerminalEvent {
     Wakeup,
     Bell,
     Exit,
+    /// The shell exited with this code
+    ChildExit(i32),
     Title,
+    /// Input started or stopped backing up (see `Terminal::input_backed_up`)
+    InputBacklog,
 }
 
 impl Terminal {
+    /// Start a PTY running `shell` (program and whitespace-separated arguments),
+    /// or the user's default shell when `None`, with `env` set on top of
+    /// Sashiki's environment. Output is recorded into `transcript` while its
+    /// recording is on (unix only).
     pub fn new(
         working_directory: Option<std::path::PathBuf>,
+        shell: Option<&str>,
+        env: Vec<(String, String)>,
+        transcript: Arc<SessionTranscript>,
+        restored: Option<Vec<String>>,
     ) -> anyhow::Result<(Self, smol::channel::Receiver<TerminalEvent>)> {
         // Buffer size 100 allows burst of terminal events without blocking PTY thread
         let (event_tx, event_rx) = smol::channel::bounded(100);
-        let listener = TerminalEventListener { sender: event_tx };
+        let title = Arc::new(TitleState::default());
+        let backlog_tx = event_tx.clone();
+        let listener = TerminalEventListener {
+            sender: event_tx,
+            title: title.clone(),
+        };
 
         let config = TermConfig::default();
         // 80x24 is the VT100 standard terminal size, used as initial default
         let term_size = TermSize::new(80, 24);
-        let term = Term::new(config, &term_size, listener.clone());
+        let mut term = Term::new(config, &term_size, listener.clone());
+        // Output saved by the last run goes in before the shell prints anything
+        if let Some(lines) = restored {
+            saved_lines::preload(&mut term, &lines);
+        }
         let term = Arc::new(FairMutex::new(term));
 
+        let shell = shell.and_then(|cmd| {
+            let mut parts = shell_escape::split_words(cmd).into_iter();
+            let program = parts.next()?;
+            Some(tty::Shell::new(program, parts.collect()))
+        });
         let pty_config = tty::Options {
-            shell: None,
+            shell,
             working_directory,
-            env: std::collections::HashMap::new(),
+            env: env.into_iter().collect(),
             ..Default::default()
         };
 
@@ -88,12 +217,44 @@ impl Terminal {
         };
 
         // window_id parameter (0) is unused on Windows
-        let pty = tty::new(&pty_config, window_size, 0)?;
+        #[cfg_attr(not(unix), allow(unused_mut))]
+        let mut pty = tty::new(&pty_config, window_size, 0)?;
+
+        // Input is written straight to a duplicate of the PTY master, so a
+        // program that stops reading backs up our queue instead of the event loop
+        #[cfg(unix)]
+        let input_writer = {
+            use alacritty_terminal::tty::EventedReadWrite;
+            pty.writer().try_clone()?
+        };
+        #[cfg(unix)]
+        let (master, shell_pid) = (pty.file().try_clone()?, pty.child().id());
+        let commands = Arc::new(ReportedCommands::default());
+        let marks = Arc::new(Mutex::new(PromptMarks::default()));
+        #[cfg(unix)]
+        let pty = tee_pty::TeePty::new(
+            pty,
+            transcript,
+            commands.clone(),
+            tee_pty::MarkTarget {
+                term: term.clone(),
+                marks: marks.clone(),
+                listener: listener.clone(),
+            },
+        )?;
+        #[cfg(not(unix))]
+        let _ = transcript;
 
         let event_loop =
             EventLoop::new(term.clone(), listener, pty, pty_config.drain_on_exit, false)?;
 
         let pty_tx = Notifier(event_loop.channel());
+        // ConPTY input is already written from a thread of its own
+        #[cfg(not(unix))]
+        let input_writer = NotifierWriter(Notifier(event_loop.channel()));
+        let input = PtyWriter::spawn(input_writer, move || {
+            let _ = backlog_tx.try_send(TerminalEvent::InputBacklog);
+        });
         // Thread handle intentionally dropped - PTY thread runs until Terminal is dropped
         // and channel closes, at which point it exits naturally
         let _pty_thread = event_loop.spawn();
@@ -102,25 +263,108 @@ impl Terminal {
             Self {
                 term,
                 pty_tx,
+                input,
                 current_size: std::sync::Mutex::new((80, 24)),
+                title,
+                commands,
+                marks,
+                #[cfg(unix)]
+                master,
+                #[cfg(unix)]
+                shell_pid,
             },
             event_rx,
         ))
     }
 
+    /// Queue input for the shell without waiting on the PTY
     pub fn write(&self, input: &[u8]) {
-        self.pty_tx.notify(input.to_vec());
+        if !self.input.write(input) {
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::Terminal,
+                format!(
+                    "Dropped {} bytes of input: the terminal is not accepting input",
+                    input.len()
+                ),
+            ));
+        }
+    }
+
+    /// Whether input is piling up because the program stopped reading it
+    pub fn input_backed_up(&self) -> bool {
+        self.input.is_backed_up()
+    }
+
+    /// Name of the program running in the foreground when it is not the
+    /// shell (always None on Windows)
+    pub fn foreground_program(&self) -> Option<String> {
+        #[cfg(unix)]
+        {
+            foreground::program(&self.master, self.shell_pid)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Process name of the shell (None on Windows or when it cannot be read)
+    pub fn shell_program(&self) -> Option<String> {
+        #[cfg(unix)]
+        {
+            foreground::process_name(self.shell_pid as libc::pid_t)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Process id of the shell (None on Windows)
+    pub fn shell_pid(&self) -> Option<u32> {
+        #[cfg(unix)]
+        {
+            Some(self.shell_pid)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Directory the shell is in (Linux only; None elsewhere or when it
+    /// cannot be read)
+    pub fn shell_directory(&self) -> Option<std::path::PathBuf> {
+        #[cfg(target_os = "linux")]
+        {
+            std::fs::read_link(format!("/proc/{}/cwd", self.shell_pid)).ok()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
     }
 
     /// Send exit command to the shell to terminate the PTY process
     pub fn shutdown(&self) {
+        // Input still queued would only delay the exit
+        self.input.clear();
         // Send "exit" command to terminate the shell
         // This works for cmd.exe, powershell, bash, etc.
-        self.pty_tx.notify(b"exit\r".to_vec());
+        self.input.write(b"exit\r");
     }
 
-    /// Resize the terminal to new dimensions
-    pub fn resize(&self, cols: u16, lines: u16, cell_width: u16, cell_height: u16) {
+    /// Resize the terminal, moving `points` (grid line, column) to where their
+    /// text went in the reflow; a point whose line left the grid becomes None
+    pub fn resize_carrying(
+        &self,
+        cols: u16,
+        lines: u16,
+        cell_width: u16,
+        cell_height: u16,
+        points: &mut [Option<(i32, usize)>],
+    ) {
         // Check if size actually changed
         {
             let Ok(mut current) = self.current_size.lock() else {
@@ -140,10 +384,16 @@ impl Terminal {
             cell_height,
         };
 
-        // Resize the terminal grid
+        // Resize the terminal grid, which rewraps lines at the new width;
+        // prompt marks and `points` are carried across the reflow
         {
             let mut term = self.term.lock();
-            term.resize(TermSize::new(cols as usize, lines as usize));
+            let carried = CarriedPoints::take(&term, points);
+            self.lock_marks()
+                .resize(&mut *term, TermSize::new(cols as usize, lines as usize));
+            for (point, carried) in points.iter_mut().zip(carried.place(&term)) {
+                *point = carried;
+            }
         }
 
         // Notify PTY of size change
@@ -156,6 +406,32 @@ impl Terminal {
         term.scroll_display(scroll);
     }
 
+    /// Latest window title, marking the change as seen
+    pub fn take_title(&self) -> Option<String> {
+        self.title.pending.store(false, Ordering::Release);
+        self.title.title.lock().ok().and_then(|t| t.clone())
+    }
+
+    /// Commands the shell reported since the last call
+    pub fn take_commands(&self) -> Vec<String> {
+        self.commands.take()
+    }
+
+    /// Whether the shell reports the commands it runs (OSC 133)
+    pub fn has_shell_integration(&self) -> bool {
+        self.commands.is_integrated()
+    }
+
+    /// Last `count` rows with text, read under the lock (see `glance`)
+    pub fn tail_rows(&self, count: usize) -> Vec<String> {
+        self.with_term(|term| glance::tail_rows(term, count))
+    }
+
+    /// Logical lines of the scrollback and screen, to keep across a restart
+    pub fn saved_lines(&self, colors: bool) -> Vec<String> {
+        self.with_term(|term| saved_lines::capture(term.grid(), colors))
+    }
+
     pub fn with_term<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&Term<TerminalEventListener>) -> R,
@@ -163,4 +439,151 @@ impl Terminal {
         let term = self.term.lock();
         f(&term)
     }
+
+    /// Like `with_term`, with the prompt marks brought up to date (None
+    /// while the alternate screen is shown)
+    pub fn with_marks<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Term<TerminalEventListener>, Option<&PromptMarks>) -> R,
+    {
+        let term = self.term.lock();
+        let mut marks = self.lock_marks();
+        let visible = marks.sync(&term);
+        f(&term, visible.then_some(&*marks))
+    }
+
+    /// The terminal is locked first whenever both are (as the PTY reader does)
+    fn lock_marks(&self) -> std::sync::MutexGuard<'_, PromptMarks> {
+        self.marks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hands input to the event loop, which writes it to ConPTY
+#[cfg(not(unix))]
+struct NotifierWriter(Notifier);
+
+#[cfg(not(unix))]
+impl std::io::Write for NotifierWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        use alacritty_terminal::event::Notify;
+        self.0.notify(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn test_term() -> (Term<TerminalEventListener>, Arc<TitleState>) {
+        let (sender, _receiver) = smol::channel::bounded(100);
+        let title = Arc::new(TitleState::default());
+        let listener = TerminalEventListener {
+            sender,
+            title: title.clone(),
+        };
+        let term = Term::new(TermConfig::default(), &TermSize::new(80, 24), listener);
+        (term, title)
+    }
+
+    fn current_title(state: &TitleState) -> Option<String> {
+        state.title.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_sanitize_title() {
+        assert_eq!(
+            sanitize_title("  claude — planning "),
+            Some("claude — planning".into())
+        );
+        assert_eq!(sanitize_title("a\u{7}b\u{9b}c"), Some("abc".into()));
+        assert_eq!(sanitize_title("   "), None);
+        assert_eq!(
+            sanitize_title(&"x".repeat(1000)).map(|t| t.len()),
+            Some(MAX_TITLE_CHARS)
+        );
+    }
+
+    #[test]
+    fn test_osc_title_sequences() {
+        let (mut term, state) = test_term();
+        let mut parser: Processor = Processor::new();
+
+        parser.advance(&mut term, b"\x1b]2;build\x07");
+        assert_eq!(current_title(&state), Some("build".into()));
+
+        // Invalid UTF-8 segments are dropped by the parser
+        parser.advance(&mut term, b"\x1b]0;ok;\xff\xfe\x07");
+        assert_eq!(current_title(&state), Some("ok".into()));
+
+        parser.advance(&mut term, b"\x1b]0;\xff\x07");
+        assert_eq!(current_title(&state), None);
+    }
+
+    #[test]
+    fn test_title_updates_are_coalesced() {
+        let (sender, receiver) = smol::channel::bounded(100);
+        let listener = TerminalEventListener {
+            sender,
+            title: Arc::new(TitleState::default()),
+        };
+
+        for i in 0..500 {
+            listener.send_event(AlacEvent::Title(format!("step {}", i)));
+        }
+        assert_eq!(receiver.len(), 1);
+        assert_eq!(current_title(&listener.title), Some("step 499".into()));
+    }
+
+    #[test]
+    fn test_output_does_not_move_a_scrolled_back_view() {
+        let (mut term, _) = test_term();
+        let mut parser: Processor = Processor::new();
+        for i in 0..100 {
+            parser.advance(&mut term, format!("line {}\r\n", i).as_bytes());
+        }
+        term.scroll_display(Scroll::Delta(10));
+        let top = term.grid().display_offset();
+
+        for i in 100..105 {
+            parser.advance(&mut term, format!("line {}\r\n", i).as_bytes());
+        }
+        // The same lines stay on screen; the offset grew by the new lines
+        assert_eq!(term.grid().display_offset(), top + 5);
+
+        term.scroll_display(Scroll::Bottom);
+        parser.advance(&mut term, b"line 105\r\n");
+        assert_eq!(term.grid().display_offset(), 0);
+    }
+
+    #[test]
+    fn test_bell_and_child_exit_are_forwarded() {
+        let (sender, receiver) = smol::channel::bounded(100);
+        let listener = TerminalEventListener {
+            sender,
+            title: Arc::new(TitleState::default()),
+        };
+        let mut term = Term::new(
+            TermConfig::default(),
+            &TermSize::new(80, 24),
+            listener.clone(),
+        );
+        let mut parser: Processor = Processor::new();
+
+        parser.advance(&mut term, b"done\x07");
+        assert!(matches!(receiver.try_recv(), Ok(TerminalEvent::Bell)));
+
+        listener.send_event(AlacEvent::ChildExit(3));
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(TerminalEvent::ChildExit(3))
+        ));
+    }
 }
@@ -0,0 +1,141 @@
+//! Code blocks found in the grid on demand (see `crate::code_blocks`)
+//!
+//! The view and up to `SCROLLBACK_ROWS` rows above it are scanned when the
+//! blocks are asked for, never as output arrives. Found blocks keep their
+//! place while output pushes them up the scrollback; one whose opening row
+//! no longer reads the same (rows dropped off the scrollback, a redraw or a
+//! reflow) is forgotten.
+
+use super::glance::row_text;
+use crate::code_blocks::{self, CodeBlock, TerminalRow};
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags};
+
+/// Rows above the view scanned for blocks
+const SCROLLBACK_ROWS: i32 = 1000;
+
+/// Blocks found by the last scan that are still in place
+pub(super) struct FoundBlocks {
+    /// Each block with the text of its opening row
+    blocks: Vec<(CodeBlock, String)>,
+    history_size: usize,
+}
+
+impl FoundBlocks {
+    /// Scan the view at `display_offset` and the scrollback above it
+    pub(super) fn scan(grid: &Grid<Cell>, display_offset: i32) -> Self {
+        let top = (-display_offset - SCROLLBACK_ROWS).max(grid.topmost_line().0);
+        let bottom = grid.screen_lines() as i32 - 1 - display_offset;
+        let last_column = Column(grid.columns() - 1);
+        let rows: Vec<TerminalRow> = (top..=bottom)
+            .map(|line| TerminalRow {
+                line,
+                text: row_text(grid, line),
+                wraps: grid[Line(line)][last_column]
+                    .flags
+                    .contains(Flags::WRAPLINE),
+            })
+            .collect();
+        let blocks = code_blocks::extract(&rows)
+            .into_iter()
+            .map(|block| {
+                let anchor = row_text(grid, block.first_line);
+                (block, anchor)
+            })
+            .collect();
+        Self {
+            blocks,
+            history_size: grid.history_size(),
+        }
+    }
+
+    /// Keep the blocks on their rows after `grid` changed
+    pub(super) fn follow(&mut self, grid: &Grid<Cell>) {
+        let pushed = grid.history_size() as i32 - self.history_size as i32;
+        self.history_size = grid.history_size();
+        let (top, lines) = (grid.topmost_line().0, grid.screen_lines() as i32);
+        self.blocks.retain_mut(|(block, anchor)| {
+            block.first_line -= pushed;
+            block.last_line -= pushed;
+            (top..lines).contains(&block.first_line) && row_text(grid, block.first_line) == *anchor
+        });
+    }
+
+    pub(super) fn get(&self, index: usize) -> Option<&CodeBlock> {
+        self.blocks.get(index).map(|(block, _)| block)
+    }
+
+    /// Blocks with a row among the `lines` shown at `display_offset`: their
+    /// index and the display line their overlay goes on (their top, or the
+    /// top of the view when they start above it)
+    pub(super) fn visible(&self, display_offset: i32, lines: usize) -> Vec<(usize, usize)> {
+        let top = -display_offset;
+        let bottom = top + lines as i32 - 1;
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, (block, _))| block.last_line >= top && block.first_line <= bottom)
+            .map(|(index, (block, _))| (index, (block.first_line.max(top) - top) as usize))
+            .collect()
+    }
+
+    /// Opening row of the newest block above the view
+    pub(super) fn last_above(&self, display_offset: i32) -> Option<i32> {
+        self.blocks
+            .iter()
+            .rev()
+            .map(|(block, _)| block)
+            .find(|block| block.last_line < -display_offset)
+            .map(|block| block.first_line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::Term;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn write(term: &mut Term<VoidListener>, text: &str) {
+        let mut parser: Processor = Processor::new();
+        parser.advance(term, text.as_bytes());
+    }
+
+    #[test]
+    fn test_blocks_follow_output_into_the_scrollback() {
+        let mut term = Term::new(TermConfig::default(), &TermSize::new(16, 6), VoidListener);
+        // The code line is wider than the terminal and wraps onto 2 rows
+        write(
+            &mut term,
+            "In src/a.rs:\r\n```\r\nlet total = first + second;\r\n```\r\n$ ",
+        );
+        let mut found = FoundBlocks::scan(term.grid(), 0);
+        let block = found.get(0).unwrap();
+        assert_eq!(block.text, "let total = first + second;\n");
+        assert_eq!(block.path.as_deref(), Some("src/a.rs"));
+        assert_eq!((block.first_line, block.last_line), (1, 4));
+        assert_eq!(found.visible(0, 6), vec![(0, 1)]);
+
+        // Three more lines push everything up by three rows
+        write(&mut term, "\r\n1\r\n2\r\n3");
+        found.follow(term.grid());
+        assert_eq!(found.get(0).map(|b| b.first_line), Some(-2));
+        // Its top is above the view; the overlay sits on the first row
+        assert_eq!(found.visible(0, 6), vec![(0, 0)]);
+        assert_eq!(found.last_above(0), None);
+
+        write(&mut term, "\r\n4\r\n5\r\n6\r\n7\r\n8");
+        found.follow(term.grid());
+        assert!(found.visible(0, 6).is_empty());
+        assert_eq!(found.last_above(0), Some(-7));
+
+        // Cleared: nothing where the block was
+        write(&mut term, "\x1b[3J\x1b[H\x1b[2J");
+        found.follow(term.grid());
+        assert!(found.get(0).is_none());
+    }
+}
@@ -0,0 +1,496 @@
+//! Keyboard copy mode for the terminal
+//!
+//! While copy mode is on, keys no longer reach the program: a block cursor
+//! moves over the grid with vi keys (h/j/k/l, w/b, 0/$, PageUp/PageDown),
+//! `v` and `V` start a character- or line-wise selection at the cursor and
+//! `y` copies it. Positions are grid points as in `TerminalSelection`, so the
+//! cursor can walk up into the scrollback (negative lines).
+
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags as CellFlags};
+
+/// The cells copy mode moves over
+pub(super) trait CopyGrid {
+    /// Topmost line (the oldest history line, so <= 0)
+    fn top_line(&self) -> i32;
+    /// Bottom line of the screen
+    fn bottom_line(&self) -> i32;
+    fn columns(&self) -> usize;
+    fn char_at(&self, line: i32, col: usize) -> char;
+    /// Whether the cell only pads a wide character to its left (or one
+    /// wrapped to the next line)
+    fn is_spacer(&self, line: i32, col: usize) -> bool;
+}
+
+impl CopyGrid for Grid<Cell> {
+    fn top_line(&self) -> i32 {
+        -(self.history_size() as i32)
+    }
+
+    fn bottom_line(&self) -> i32 {
+        self.screen_lines() as i32 - 1
+    }
+
+    fn columns(&self) -> usize {
+        Dimensions::columns(self)
+    }
+
+    fn char_at(&self, line: i32, col: usize) -> char {
+        self[Line(line)][Column(col)].c
+    }
+
+    fn is_spacer(&self, line: i32, col: usize) -> bool {
+        self[Line(line)][Column(col)]
+            .flags
+            .intersects(CellFlags::WIDE_CHAR_SPACER | CellFlags::LEADING_WIDE_CHAR_SPACER)
+    }
+}
+
+/// A cursor movement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Motion {
+    Left,
+    Right,
+    /// Lines down (negative = up), for j/k and PageUp/PageDown
+    Lines(i32),
+    /// Start of the next word (w)
+    WordForward,
+    /// Start of the current or previous word (b)
+    WordBackward,
+    /// First column (0)
+    LineStart,
+    /// Last non-blank cell (`$`)
+    LineEnd,
+}
+
+/// What a key does in copy mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CopyCommand {
+    Move(Motion),
+    /// Start (or stop) a selection; true for line-wise (`V`)
+    Select {
+        line_wise: bool,
+    },
+    Yank,
+    Exit,
+}
+
+/// Command for a key press (`key` as gpui names it, `key_char` the text it
+/// types); PageUp/PageDown move by `page` lines
+pub(super) fn command_for_key(key: &str, key_char: Option<&str>, page: i32) -> Option<CopyCommand> {
+    let command = match key {
+        "escape" => CopyCommand::Exit,
+        "left" => CopyCommand::Move(Motion::Left),
+        "right" => CopyCommand::Move(Motion::Right),
+        "up" => CopyCommand::Move(Motion::Lines(-1)),
+        "down" => CopyCommand::Move(Motion::Lines(1)),
+        "home" => CopyCommand::Move(Motion::LineStart),
+        "end" => CopyCommand::Move(Motion::LineEnd),
+        "pageup" => CopyCommand::Move(Motion::Lines(-page)),
+        "pagedown" => CopyCommand::Move(Motion::Lines(page)),
+        _ => match key_char? {
+            "h" => CopyCommand::Move(Motion::Left),
+            "l" => CopyCommand::Move(Motion::Right),
+            "k" => CopyCommand::Move(Motion::Lines(-1)),
+            "j" => CopyCommand::Move(Motion::Lines(1)),
+            "w" => CopyCommand::Move(Motion::WordForward),
+            "b" => CopyCommand::Move(Motion::WordBackward),
+            "0" => CopyCommand::Move(Motion::LineStart),
+            "$" => CopyCommand::Move(Motion::LineEnd),
+            "v" => CopyCommand::Select { line_wise: false },
+            "V" => CopyCommand::Select { line_wise: true },
+            "y" => CopyCommand::Yank,
+            _ => return None,
+        },
+    };
+    Some(command)
+}
+
+/// Copy mode state of a terminal view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct CopyMode {
+    /// Block cursor (grid line, column)
+    pub(super) cursor: (i32, usize),
+    /// Where the selection started, None until `v` or `V`
+    anchor: Option<(i32, usize)>,
+    line_wise: bool,
+}
+
+impl CopyMode {
+    pub(super) fn new(cursor: (i32, usize)) -> Self {
+        Self {
+            cursor,
+            anchor: None,
+            line_wise: false,
+        }
+    }
+
+    /// `v`/`V`: start a selection at the cursor. The key of the running
+    /// selection ends it, the other one switches its kind.
+    pub(super) fn toggle_selection(&mut self, line_wise: bool) {
+        match self.anchor {
+            Some(_) if self.line_wise == line_wise => self.anchor = None,
+            Some(_) => self.line_wise = line_wise,
+            None => {
+                self.anchor = Some(self.cursor);
+                self.line_wise = line_wise;
+            }
+        }
+    }
+
+    /// Cursor and anchor, to be carried across a resize
+    pub(super) fn points(&self) -> [Option<(i32, usize)>; 2] {
+        [Some(self.cursor), self.anchor]
+    }
+
+    /// Take the carried cursor and anchor. False when the cursor's line left
+    /// the grid; the anchor is dropped when its own did.
+    pub(super) fn carry(&mut self, [cursor, anchor]: [Option<(i32, usize)>; 2]) -> bool {
+        let Some(cursor) = cursor else {
+            return false;
+        };
+        self.cursor = cursor;
+        self.anchor = anchor;
+        true
+    }
+
+    /// Selected (start, end) grid points; line-wise selections cover whole
+    /// lines of a grid `cols` wide
+    pub(super) fn selection(&self, cols: usize) -> Option<((i32, usize), (i32, usize))> {
+        let anchor = self.anchor?;
+        if !self.line_wise {
+            return Some((anchor, self.cursor));
+        }
+        let (top, bottom) = if anchor.0 <= self.cursor.0 {
+            (anchor.0, self.cursor.0)
+        } else {
+            (self.cursor.0, anchor.0)
+        };
+        Some(((top, 0), (bottom, cols.saturating_sub(1))))
+    }
+}
+
+/// Where `motion` takes the cursor at `from`. The result is always a cell
+/// that starts a character: wide characters are one step wide.
+pub(super) fn move_cursor(
+    grid: &impl CopyGrid,
+    from: (i32, usize),
+    motion: Motion,
+) -> (i32, usize) {
+    let last_col = grid.columns().saturating_sub(1);
+    // The grid may have shrunk since the cursor was placed
+    let from = snap_to_char(
+        grid,
+        (
+            from.0.clamp(grid.top_line(), grid.bottom_line()),
+            from.1.min(last_col),
+        ),
+    );
+    let (line, col) = from;
+    let target = match motion {
+        Motion::Left => return step_back_in_line(grid, from).unwrap_or(from),
+        Motion::Right => return step_forward_in_line(grid, from).unwrap_or(from),
+        Motion::Lines(delta) => (
+            line.saturating_add(delta)
+                .clamp(grid.top_line(), grid.bottom_line()),
+            col.min(last_col),
+        ),
+        Motion::WordForward => return word_forward(grid, from),
+        Motion::WordBackward => return word_backward(grid, from),
+        Motion::LineStart => (line, 0),
+        Motion::LineEnd => (
+            line,
+            (0..=last_col)
+                .rev()
+                .find(|&c| !is_blank(grid.char_at(line, c)))
+                .unwrap_or(0),
+        ),
+    };
+    snap_to_char(grid, target)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\0' || c == '\t'
+}
+
+fn class_at(grid: &impl CopyGrid, (line, col): (i32, usize)) -> CharClass {
+    let c = grid.char_at(line, col);
+    if is_blank(c) {
+        CharClass::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Move off a spacer onto the wide character it belongs to
+fn snap_to_char(grid: &impl CopyGrid, (line, mut col): (i32, usize)) -> (i32, usize) {
+    while col > 0 && grid.is_spacer(line, col) {
+        col -= 1;
+    }
+    (line, col)
+}
+
+fn step_forward_in_line(grid: &impl CopyGrid, (line, col): (i32, usize)) -> Option<(i32, usize)> {
+    ((col + 1)..grid.columns())
+        .find(|&c| !grid.is_spacer(line, c))
+        .map(|c| (line, c))
+}
+
+fn step_back_in_line(grid: &impl CopyGrid, (line, col): (i32, usize)) -> Option<(i32, usize)> {
+    (0..col)
+        .rev()
+        .find(|&c| !grid.is_spacer(line, c))
+        .map(|c| (line, c))
+}
+
+/// Next character, continuing on the following line
+fn step_forward(grid: &impl CopyGrid, pos: (i32, usize)) -> Option<(i32, usize)> {
+    step_forward_in_line(grid, pos)
+        .or_else(|| (pos.0 < grid.bottom_line()).then_some((pos.0 + 1, 0)))
+}
+
+/// Previous character, continuing on the line above
+fn step_back(grid: &impl CopyGrid, pos: (i32, usize)) -> Option<(i32, usize)> {
+    step_back_in_line(grid, pos).or_else(|| {
+        (pos.0 > grid.top_line())
+            .then(|| snap_to_char(grid, (pos.0 - 1, grid.columns().saturating_sub(1))))
+    })
+}
+
+/// `w`: skip the rest of the current word, then blanks. A line break ends a
+/// word. Stays put when no word follows.
+fn word_forward(grid: &impl CopyGrid, from: (i32, usize)) -> (i32, usize) {
+    let class = class_at(grid, from);
+    let mut pos = from;
+    loop {
+        let Some(next) = step_forward(grid, pos) else {
+            return from;
+        };
+        let new_line = next.0 != pos.0;
+        pos = next;
+        if new_line || class == CharClass::Blank || class_at(grid, pos) != class {
+            break;
+        }
+    }
+    while class_at(grid, pos) == CharClass::Blank {
+        match step_forward(grid, pos) {
+            Some(next) => pos = next,
+            None => return from,
+        }
+    }
+    pos
+}
+
+/// `b`: back over blanks, then to the first character of that word
+fn word_backward(grid: &impl CopyGrid, from: (i32, usize)) -> (i32, usize) {
+    let mut pos = from;
+    loop {
+        match step_back(grid, pos) {
+            Some(prev) => pos = prev,
+            None => return from,
+        }
+        if class_at(grid, pos) != CharClass::Blank {
+            break;
+        }
+    }
+    let class = class_at(grid, pos);
+    while let Some(prev) = step_back(grid, pos) {
+        if prev.0 != pos.0 || class_at(grid, prev) != class {
+            break;
+        }
+        pos = prev;
+    }
+    pos
+}
+
+/// Display offset that brings grid line `line` into a view of `screen_lines`
+/// lines currently scrolled back by `display_offset`
+pub(super) fn offset_showing(line: i32, display_offset: i32, screen_lines: usize) -> i32 {
+    let display_line = line + display_offset;
+    if display_line < 0 {
+        -line
+    } else if display_line >= screen_lines as i32 {
+        screen_lines as i32 - 1 - line
+    } else {
+        display_offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use unicode_width::UnicodeWidthChar;
+
+    /// Grid from text rows, the first `history` of them scrollback. Wide
+    /// characters take two cells like in alacritty.
+    struct FakeGrid {
+        cells: Vec<Vec<(char, bool)>>,
+        history: usize,
+        cols: usize,
+    }
+
+    impl FakeGrid {
+        fn new(history: usize, cols: usize, rows: &[&str]) -> Self {
+            let cells = rows
+                .iter()
+                .map(|row| {
+                    let mut cells = Vec::new();
+                    for c in row.chars() {
+                        cells.push((c, false));
+                        if c.width() == Some(2) {
+                            cells.push((' ', true));
+                        }
+                    }
+                    cells.resize(cols, (' ', false));
+                    cells
+                })
+                .collect();
+            Self {
+                cells,
+                history,
+                cols,
+            }
+        }
+
+        fn cell(&self, line: i32, col: usize) -> (char, bool) {
+            self.cells[(line + self.history as i32) as usize][col]
+        }
+    }
+
+    impl CopyGrid for FakeGrid {
+        fn top_line(&self) -> i32 {
+            -(self.history as i32)
+        }
+
+        fn bottom_line(&self) -> i32 {
+            (self.cells.len() - self.history) as i32 - 1
+        }
+
+        fn columns(&self) -> usize {
+            self.cols
+        }
+
+        fn char_at(&self, line: i32, col: usize) -> char {
+            self.cell(line, col).0
+        }
+
+        fn is_spacer(&self, line: i32, col: usize) -> bool {
+            self.cell(line, col).1
+        }
+    }
+
+    #[test]
+    fn test_hjkl_stay_inside_the_grid() {
+        let grid = FakeGrid::new(1, 4, &["old", "ab", "cd"]);
+        assert_eq!(move_cursor(&grid, (0, 0), Motion::Left), (0, 0));
+        assert_eq!(move_cursor(&grid, (0, 3), Motion::Right), (0, 3));
+        assert_eq!(move_cursor(&grid, (0, 1), Motion::Right), (0, 2));
+        assert_eq!(move_cursor(&grid, (1, 2), Motion::Lines(1)), (1, 2));
+        // Up into the scrollback, and no further
+        assert_eq!(move_cursor(&grid, (0, 2), Motion::Lines(-1)), (-1, 2));
+        assert_eq!(move_cursor(&grid, (1, 2), Motion::Lines(-10)), (-1, 2));
+        assert_eq!(move_cursor(&grid, (-1, 0), Motion::Lines(10)), (1, 0));
+    }
+
+    #[test]
+    fn test_wide_characters_are_one_step() {
+        let grid = FakeGrid::new(0, 8, &["a日本b", "xxxxxxx"]);
+        // Cells: a 日 _ 本 _ b
+        assert_eq!(move_cursor(&grid, (0, 0), Motion::Right), (0, 1));
+        assert_eq!(move_cursor(&grid, (0, 1), Motion::Right), (0, 3));
+        assert_eq!(move_cursor(&grid, (0, 3), Motion::Right), (0, 5));
+        assert_eq!(move_cursor(&grid, (0, 5), Motion::Left), (0, 3));
+        assert_eq!(move_cursor(&grid, (0, 3), Motion::Left), (0, 1));
+        // Moving vertically onto a spacer lands on its character
+        assert_eq!(move_cursor(&grid, (1, 4), Motion::Lines(-1)), (0, 3));
+        assert_eq!(move_cursor(&grid, (0, 5), Motion::LineEnd), (0, 5));
+    }
+
+    #[test]
+    fn test_line_start_and_end() {
+        let grid = FakeGrid::new(0, 10, &["  ls -la", "", "日本"]);
+        assert_eq!(move_cursor(&grid, (0, 4), Motion::LineStart), (0, 0));
+        assert_eq!(move_cursor(&grid, (0, 0), Motion::LineEnd), (0, 7));
+        assert_eq!(move_cursor(&grid, (1, 5), Motion::LineEnd), (1, 0));
+        // The last cell of a wide character is its spacer
+        assert_eq!(move_cursor(&grid, (2, 0), Motion::LineEnd), (2, 2));
+    }
+
+    #[test]
+    fn test_word_motions_cross_lines_and_history() {
+        let grid = FakeGrid::new(1, 12, &["cargo build", "foo.bar  baz", "日本 語"]);
+        let w = |pos| move_cursor(&grid, pos, Motion::WordForward);
+        let b = |pos| move_cursor(&grid, pos, Motion::WordBackward);
+
+        assert_eq!(w((-1, 0)), (-1, 6));
+        assert_eq!(w((-1, 6)), (0, 0));
+        assert_eq!(w((0, 0)), (0, 3));
+        assert_eq!(w((0, 3)), (0, 4));
+        assert_eq!(w((0, 4)), (0, 9));
+        assert_eq!(w((0, 9)), (1, 0));
+        assert_eq!(w((1, 0)), (1, 5));
+        // No word after the last one
+        assert_eq!(w((1, 5)), (1, 5));
+
+        assert_eq!(b((1, 5)), (1, 0));
+        assert_eq!(b((1, 0)), (0, 9));
+        assert_eq!(b((0, 10)), (0, 9));
+        assert_eq!(b((0, 9)), (0, 4));
+        assert_eq!(b((0, 0)), (-1, 6));
+        assert_eq!(b((-1, 6)), (-1, 0));
+        assert_eq!(b((-1, 0)), (-1, 0));
+    }
+
+    #[test]
+    fn test_selections() {
+        let mut mode = CopyMode::new((2, 5));
+        assert_eq!(mode.selection(10), None);
+
+        mode.toggle_selection(false);
+        mode.cursor = (0, 3);
+        assert_eq!(mode.selection(10), Some(((2, 5), (0, 3))));
+
+        // V switches to whole lines, pressing it again ends the selection
+        mode.toggle_selection(true);
+        assert_eq!(mode.selection(10), Some(((0, 0), (2, 9))));
+        mode.toggle_selection(true);
+        assert_eq!(mode.selection(10), None);
+    }
+
+    #[test]
+    fn test_keys() {
+        let key = |key: &str, c: Option<&str>| command_for_key(key, c, 20);
+        assert_eq!(
+            key("4", Some("$")),
+            Some(CopyCommand::Move(Motion::LineEnd))
+        );
+        assert_eq!(
+            key("v", Some("V")),
+            Some(CopyCommand::Select { line_wise: true })
+        );
+        assert_eq!(
+            key("pageup", None),
+            Some(CopyCommand::Move(Motion::Lines(-20)))
+        );
+        assert_eq!(key("escape", None), Some(CopyCommand::Exit));
+        assert_eq!(key("x", Some("x")), None);
+    }
+
+    #[test]
+    fn test_offset_keeps_cursor_on_screen() {
+        assert_eq!(offset_showing(3, 0, 10), 0);
+        assert_eq!(offset_showing(-4, 0, 10), 4);
+        assert_eq!(offset_showing(-4, 6, 10), 6);
+        assert_eq!(offset_showing(8, 5, 10), 1);
+    }
+}
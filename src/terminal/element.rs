@@ -3,12 +3,15 @@
 //! This module implements the custom GPUI Element for rendering terminal content.
 
 use super::TerminalView;
+use super::resize::{self, grid_size};
 use crate::theme::*;
 use gpui::{
-    App, Bounds, Element, ElementId, ElementInputHandler, Entity, GlobalElementId, Hsla,
-    InspectorElementId, IntoElement, LayoutId, Pixels, Point, SharedString, Size, TextRun,
+    App, Bounds, ContentMask, Element, ElementId, ElementInputHandler, Entity, GlobalElementId,
+    Hsla, InspectorElementId, IntoElement, LayoutId, Pixels, Point, SharedString, Size, TextRun,
     TextStyle, UnderlineStyle, Window, fill, px, relative, rgb,
 };
+use std::rc::Rc;
+use unicode_width::UnicodeWidthChar;
 
 /// Padding around terminal content in pixels
 pub(super) const TERMINAL_PADDING: f32 = 8.0;
@@ -20,22 +23,14 @@ pub(super) const DEFAULT_CELL_HEIGHT: f32 = 16.0;
 pub(super) const SCROLL_LINES_WHEEL: i32 = 3;
 /// Maximum milliseconds between clicks for multi-click detection
 pub(super) const MULTI_CLICK_THRESHOLD_MS: u128 = 500;
-/// Line height as a multiple of font size (1.4 is standard for terminal readability)
-const LINE_HEIGHT_MULTIPLIER: f32 = 1.4;
-/// Minimum element width in pixels to perform layout (avoids freezing on tiny resize)
-const MIN_ELEMENT_WIDTH: f32 = 50.0;
-/// Minimum element height in pixels to perform layout
-const MIN_ELEMENT_HEIGHT: f32 = 40.0;
-/// Minimum terminal columns (prevents degenerate grid)
-const MIN_TERMINAL_COLS: u16 = 2;
-/// Minimum terminal lines (prevents degenerate grid)
-const MIN_TERMINAL_LINES: u16 = 2;
 
 /// Terminal cell data for paint phase rendering
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub(super) struct CellData {
     /// Character to display
     pub c: char,
+    /// Combining marks and joiners drawn together with `c`
+    pub zerowidth: Vec<char>,
     /// Foreground color
     pub fg: Hsla,
     /// Background color (None = transparent)
@@ -56,13 +51,41 @@ pub(super) struct CellData {
 
 /// Cached terminal layout for paint phase
 pub(super) struct TerminalLayout {
-    /// Grid of cells (rows x cols)
-    pub cells: Vec<Vec<CellData>>,
+    /// Grid of cells (rows x cols); rows are shared with the view's row cache
+    pub cells: Vec<Rc<Vec<CellData>>>,
     /// Cell dimensions
     pub cell_width: Pixels,
     pub line_height: Pixels,
-    /// Preedit text if any
-    pub preedit_text: String,
+    /// IME composition in progress, if any
+    pub preedit: Option<PreeditLayout>,
+    /// Display rows where a marked prompt starts
+    pub prompt_rows: Vec<usize>,
+}
+
+/// IME composition text, drawn inline at the cursor
+pub(super) struct PreeditLayout {
+    pub text: String,
+    /// Display line of the cursor
+    pub line: usize,
+    /// Column of the cursor; cells from here on are pushed right
+    pub col: usize,
+}
+
+/// Column offset and width in cells of each character of `text`, and the
+/// total width. Wide CJK characters take two cells; combining marks take
+/// none and stay with the character before them.
+pub(super) fn preedit_columns(text: &str) -> (Vec<(String, usize, usize)>, usize) {
+    let mut columns: Vec<(String, usize, usize)> = Vec::new();
+    let mut offset = 0;
+    for c in text.chars() {
+        let width = c.width().unwrap_or(0);
+        match columns.last_mut() {
+            Some((prev, _, _)) if width == 0 => prev.push(c),
+            _ => columns.push((c.to_string(), offset, width)),
+        }
+        offset += width;
+    }
+    (columns, offset)
 }
 
 /// Custom element that renders terminal directly in paint phase
@@ -131,7 +154,7 @@ impl Element for TerminalElement {
         window: &mut Window,
         cx: &mut App,
     ) -> Self::PrepaintState {
-        let font_size = px(14.0);
+        let font_size = px(resize::FONT_SIZE);
 
         // Build text style for measuring and rendering
         let text_style = TextStyle {
@@ -148,44 +171,48 @@ impl Element for TerminalElement {
             .advance(font_id, font_size, 'M')
             .map(|s| s.width)
             .unwrap_or(px(DEFAULT_CELL_WIDTH));
-        let line_height = font_size * LINE_HEIGHT_MULTIPLIER;
+        let line_height = px(resize::line_height(font_size.into()));
+        let cell_width_f32: f32 = cell_width.into();
+        let line_height_f32: f32 = line_height.into();
 
         // Minimum bounds check - skip resize if too small to avoid freezing
-        let min_width = px(MIN_ELEMENT_WIDTH);
-        let min_height = px(MIN_ELEMENT_HEIGHT);
-        if bounds.size.width < min_width || bounds.size.height < min_height {
+        let Some(grid) = grid_size(
+            bounds.size.width.into(),
+            bounds.size.height.into(),
+            TERMINAL_PADDING,
+            cell_width_f32,
+            line_height_f32,
+        ) else {
             return TerminalPrepaintState {
                 layout: None,
                 text_style,
                 font_size,
             };
-        }
-
-        // Calculate terminal size from bounds
-        let padding = px(TERMINAL_PADDING);
-        let available_width = (bounds.size.width - padding * 2.0).max(cell_width);
-        let available_height = (bounds.size.height - padding * 2.0).max(line_height);
-
-        let cols = ((available_width / cell_width).floor() as u16).max(MIN_TERMINAL_COLS);
-        let lines = ((available_height / line_height).floor() as u16).max(MIN_TERMINAL_LINES);
+        };
 
         // Resize terminal if needed and update view state for mouse handling
-        let cell_width_f32: f32 = cell_width.into();
-        let line_height_f32: f32 = line_height.into();
         let origin_x: f32 = bounds.origin.x.into();
         let origin_y: f32 = bounds.origin.y.into();
-        self.view.update(cx, |view, _cx| {
-            if let Some(ref terminal) = view.terminal {
-                terminal.resize(cols, lines, cell_width_f32 as u16, line_height_f32 as u16);
-            }
+        self.view.update(cx, |view, cx| {
             // Update cell dimensions and content origin for mouse handling
             view.cell_width = cell_width_f32;
             view.cell_height = line_height_f32;
             view.content_origin = (origin_x, origin_y);
+            view.content_height = bounds.size.height.into();
+            view.measure_grid(grid, cx);
         });
 
         // Build layout data from terminal grid
-        let layout = self.view.read(cx).build_layout(cell_width, line_height);
+        let (layout, filter_pending) = self.view.update(cx, |view, _cx| {
+            (
+                view.build_layout(cell_width, line_height),
+                view.is_filter_pending(),
+            )
+        });
+        // The filter matches a budget of rows per frame until it has all
+        if filter_pending {
+            window.request_animation_frame();
+        }
 
         TerminalPrepaintState {
             layout,
@@ -210,16 +237,20 @@ impl Element for TerminalElement {
         // Paint background
         window.paint_quad(fill(bounds, Hsla::from(rgb(BG_BASE))));
 
-        // Paint terminal content
+        // Paint terminal content, clipped: while a resize is held back the
+        // grid can be wider than the element
         if let Some(ref layout) = prepaint.layout {
-            self.paint_cells(
-                origin,
-                layout,
-                &prepaint.text_style,
-                prepaint.font_size,
-                window,
-                cx,
-            );
+            window.with_content_mask(Some(ContentMask { bounds }), |window| {
+                paint_prompt_ticks(origin, layout, window);
+                self.paint_cells(
+                    origin,
+                    layout,
+                    &prepaint.text_style,
+                    prepaint.font_size,
+                    window,
+                    cx,
+                );
+            });
         }
 
         // Set up input handler
@@ -234,6 +265,25 @@ impl Element for TerminalElement {
     }
 }
 
+/// Width of the gutter tick beside a marked prompt
+const PROMPT_TICK_WIDTH: f32 = 3.0;
+
+/// Paint a tick in the left padding beside each marked prompt
+fn paint_prompt_ticks(origin: Point<Pixels>, layout: &TerminalLayout, window: &mut Window) {
+    // Centered in the padding
+    let x = origin.x - px(TERMINAL_PADDING + PROMPT_TICK_WIDTH) / 2.0;
+    for &row in &layout.prompt_rows {
+        let tick = Bounds::new(
+            Point::new(x, origin.y + layout.line_height * row),
+            Size {
+                width: px(PROMPT_TICK_WIDTH),
+                height: layout.line_height,
+            },
+        );
+        window.paint_quad(fill(tick, Hsla::from(rgb(BG_SURFACE2))));
+    }
+}
+
 impl TerminalElement {
     /// Paint all terminal cells
     fn paint_cells(
@@ -247,9 +297,20 @@ impl TerminalElement {
     ) {
         let cell_width = layout.cell_width;
         let line_height = layout.line_height;
+        let preedit_width = layout
+            .preedit
+            .as_ref()
+            .map(|preedit| preedit_columns(&preedit.text).1)
+            .unwrap_or(0);
 
         for (line_idx, row) in layout.cells.iter().enumerate() {
             let y = origin.y + line_height * line_idx;
+            // Cells from the cursor on make room for the preedit on its line
+            let shift_from = layout
+                .preedit
+                .as_ref()
+                .filter(|preedit| preedit.line == line_idx)
+                .map(|preedit| preedit.col);
 
             for (col_idx, cell) in row.iter().enumerate() {
                 // Skip wide character spacers - they're handled by the previous cell
@@ -257,14 +318,19 @@ impl TerminalElement {
                     continue;
                 }
 
-                let x = origin.x + cell_width * col_idx;
-
                 // Wide characters occupy 2 cells in terminal grid
-                let render_width = if cell.is_wide_char {
-                    cell_width * 2.0
-                } else {
-                    cell_width
+                let cells = if cell.is_wide_char { 2 } else { 1 };
+                let render_width = cell_width * cells as f32;
+
+                let display_col = match shift_from {
+                    Some(from) if col_idx >= from => col_idx + preedit_width,
+                    _ => col_idx,
                 };
+                // Pushed past the right edge
+                if display_col + cells > row.len() {
+                    continue;
+                }
+                let x = origin.x + cell_width * display_col;
 
                 let cell_bounds = Bounds::new(
                     Point::new(x, y),
@@ -288,7 +354,7 @@ impl TerminalElement {
                 }
 
                 // Paint character
-                if cell.c != ' ' {
+                if cell.c != ' ' || !cell.zerowidth.is_empty() {
                     let fg_color = if cell.is_cursor || cell.is_selected {
                         Hsla::from(rgb(BG_BASE))
                     } else if cell.is_url_hovered {
@@ -302,14 +368,16 @@ impl TerminalElement {
                     // Block elements (U+2580-U+259F): draw as filled rectangles
                     // instead of font glyphs to ensure gap-free rendering
                     // (same approach as Alacritty's builtin_font)
-                    if self.paint_block_element(
-                        cell.c,
-                        Point::new(x, y),
-                        render_width,
-                        line_height,
-                        fg_color,
-                        window,
-                    ) {
+                    if cell.zerowidth.is_empty()
+                        && self.paint_block_element(
+                            cell.c,
+                            Point::new(x, y),
+                            render_width,
+                            line_height,
+                            fg_color,
+                            window,
+                        )
+                    {
                         continue;
                     }
 
@@ -326,7 +394,12 @@ impl TerminalElement {
                         None
                     };
 
-                    let text: SharedString = cell.c.to_string().into();
+                    // The whole grapheme in one run, so the font shapes
+                    // combining marks and ZWJ sequences onto the base
+                    let text: SharedString = std::iter::once(cell.c)
+                        .chain(cell.zerowidth.iter().copied())
+                        .collect::<String>()
+                        .into();
                     let runs = [TextRun {
                         len: text.len(),
                         font: style.font(),
@@ -355,19 +428,7 @@ impl TerminalElement {
             }
         }
 
-        // Paint preedit overlay if present
-        if !layout.preedit_text.is_empty() {
-            self.paint_preedit(
-                origin,
-                &layout.preedit_text,
-                cell_width,
-                line_height,
-                text_style,
-                font_size,
-                window,
-                cx,
-            );
-        }
+        self.paint_preedit(origin, layout, text_style, font_size, window, cx);
     }
 
     /// Paint a block element character (U+2580-U+259F) as filled rectangles.
@@ -500,61 +561,66 @@ impl TerminalElement {
         true
     }
 
-    /// Paint IME preedit overlay
+    /// Paint the IME preedit (if any) inline at the cursor, underlined, one
+    /// character per grid position so it lines up with the cells around it
     fn paint_preedit(
         &self,
         origin: Point<Pixels>,
-        preedit_text: &str,
-        cell_width: Pixels,
-        line_height: Pixels,
+        layout: &TerminalLayout,
         text_style: &TextStyle,
         font_size: Pixels,
         window: &mut Window,
         cx: &mut App,
     ) {
-        let cell_width_f32: f32 = cell_width.into();
-        let preedit_padding = px(TERMINAL_PADDING);
-
-        let mut style = text_style.clone();
-        style.color = Hsla::from(rgb(YELLOW));
-
-        let display_text = format!("IME: {}", preedit_text);
-        // Use character count (not byte length) to handle multibyte characters.
-        // Note: fullwidth CJK characters occupy 2 cells but are counted as 1 here;
-        // a unicode-width crate would be needed for exact width.
-        let char_count = display_text.chars().count();
-        let preedit_width = px(char_count as f32 * cell_width_f32) + preedit_padding * 2.0;
+        let Some(ref preedit) = layout.preedit else {
+            return;
+        };
+        let cell_width = layout.cell_width;
+        let line_height = layout.line_height;
+        let (columns, width) = preedit_columns(&preedit.text);
+        let x = origin.x + cell_width * preedit.col;
+        let y = origin.y + line_height * preedit.line;
 
-        let preedit_bg = Bounds::new(
-            Point::new(origin.x, origin.y),
+        let background = Bounds::new(
+            Point::new(x, y),
             Size {
-                width: preedit_width,
-                height: line_height + preedit_padding,
+                width: cell_width * width as f32,
+                height: line_height,
             },
         );
-        window.paint_quad(fill(preedit_bg, Hsla::from(rgb(BG_SURFACE0))));
-
-        let text: SharedString = display_text.into();
-        let runs = [TextRun {
-            len: text.len(),
-            font: style.font(),
-            color: style.color,
-            background_color: None,
-            underline: None,
-            strikethrough: None,
-        }];
-
-        let shaped = window
-            .text_system()
-            .shape_line(text, font_size, &runs, None);
-        let text_origin = Point::new(origin.x + preedit_padding, origin.y + preedit_padding / 2.0);
-        let _ = shaped.paint(
-            text_origin,
-            line_height,
-            gpui::TextAlign::Left,
-            None,
-            window,
-            cx,
-        );
+        window.paint_quad(fill(background, Hsla::from(rgb(BG_SURFACE0))));
+
+        let color = Hsla::from(rgb(YELLOW));
+        let font = text_style.font();
+        for (chars, offset, cells) in columns {
+            let text: SharedString = chars.into();
+            let runs = [TextRun {
+                len: text.len(),
+                font: font.clone(),
+                color,
+                background_color: None,
+                underline: Some(UnderlineStyle {
+                    thickness: px(1.0),
+                    color: Some(color),
+                    wavy: false,
+                }),
+                strikethrough: None,
+            }];
+            let render_width = cell_width * cells.max(1) as f32;
+            let shaped =
+                window
+                    .text_system()
+                    .shape_line(text, font_size, &runs, Some(render_width));
+            let text_origin =
+                Point::new(x + cell_width * offset, y + (line_height - font_size) / 2.0);
+            let _ = shaped.paint(
+                text_origin,
+                line_height,
+                gpui::TextAlign::Left,
+                None,
+                window,
+                cx,
+            );
+        }
     }
 }
@@ -0,0 +1,425 @@
+//! Filtering a terminal view down to the rows that match a query
+//!
+//! The filter only changes what the view draws: the grid, its scrollback and
+//! the PTY carry on underneath, keys go to the program once the query is
+//! entered, and clearing the filter shows the grid where it was. Rows are
+//! numbered from the top of the scrollback. History rows keep their number
+//! while output is added below, so only the screen rows are matched again;
+//! once the scrollback is full it drops its oldest rows, which renumbers
+//! them all and starts the matching over. Rows are matched at most
+//! `SCAN_BUDGET` per frame, newest first, so a long history is filtered over
+//! a few frames instead of stalling one.
+
+use regex::Regex;
+
+/// Rows matched per frame at most
+pub(super) const SCAN_BUDGET: usize = 4000;
+
+/// What a row must contain to be shown
+#[derive(Debug, Clone)]
+pub(super) enum FilterQuery {
+    /// Substring, matched ignoring case (kept lowercased)
+    Literal(String),
+    /// `/pattern/`
+    Regex(Regex),
+}
+
+impl FilterQuery {
+    /// Parse the filter input: `/pattern/` is a regex, anything else a
+    /// substring. None for an empty query.
+    pub(super) fn parse(input: &str) -> Result<Option<Self>, regex::Error> {
+        if let Some(pattern) = input
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            if pattern.is_empty() {
+                return Ok(None);
+            }
+            return Regex::new(pattern).map(|regex| Some(Self::Regex(regex)));
+        }
+        Ok((!input.is_empty()).then(|| Self::Literal(input.to_lowercase())))
+    }
+
+    pub(super) fn matches(&self, text: &str) -> bool {
+        match self {
+            Self::Literal(needle) => text.to_lowercase().contains(needle.as_str()),
+            Self::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// A line of the filtered view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum FilterEntry {
+    /// A matching row, numbered from the top of the scrollback
+    Row(usize),
+    /// A run of rows that do not match
+    Hidden(usize),
+    /// A run of rows not matched yet
+    Pending(usize),
+}
+
+/// Which rows match a query, filled in a budget at a time
+#[derive(Debug, Clone)]
+pub(super) struct FilterScan {
+    query: FilterQuery,
+    /// Whether each row matches (meaningful for scanned rows only)
+    matches: Vec<bool>,
+    /// Rows above this one are not scanned yet; they are scanned upward
+    top_pending: usize,
+    /// Rows from this one down are not scanned yet (output since the last
+    /// scan)
+    bottom_pending: usize,
+}
+
+impl FilterScan {
+    pub(super) fn new(query: FilterQuery, rows: usize) -> Self {
+        Self {
+            query,
+            matches: vec![false; rows],
+            top_pending: rows,
+            bottom_pending: rows,
+        }
+    }
+
+    /// The rows from `stable` down may have changed, and there are now
+    /// `rows` of them. Scanned rows above `stable` keep their result; if
+    /// there are none the scan starts over from the bottom.
+    pub(super) fn invalidate_from(&mut self, stable: usize, rows: usize) {
+        self.matches.resize(rows, false);
+        if stable <= self.top_pending {
+            self.top_pending = rows;
+            self.bottom_pending = rows;
+        } else {
+            self.bottom_pending = self.bottom_pending.min(stable).min(rows);
+        }
+    }
+
+    /// Match up to `budget` pending rows, given the text of a row: first the
+    /// rows below the scanned ones, then those above, bottom up. Returns the
+    /// number of rows matched.
+    pub(super) fn scan(
+        &mut self,
+        budget: usize,
+        mut row_text: impl FnMut(usize) -> String,
+    ) -> usize {
+        let mut scanned = 0;
+        while scanned < budget && self.bottom_pending < self.matches.len() {
+            let row = self.bottom_pending;
+            self.matches[row] = self.query.matches(&row_text(row));
+            self.bottom_pending += 1;
+            scanned += 1;
+        }
+        while scanned < budget && self.top_pending > 0 {
+            self.top_pending -= 1;
+            let row = self.top_pending;
+            self.matches[row] = self.query.matches(&row_text(row));
+            scanned += 1;
+        }
+        scanned
+    }
+
+    pub(super) fn is_complete(&self) -> bool {
+        self.top_pending == 0 && self.bottom_pending == self.matches.len()
+    }
+
+    /// The filtered view, top to bottom: matching rows, with the runs
+    /// between them collapsed
+    pub(super) fn entries(&self) -> Vec<FilterEntry> {
+        let mut entries = Vec::new();
+        let mut run = None;
+        for (row, &matched) in self.matches.iter().enumerate() {
+            let pending = row < self.top_pending || row >= self.bottom_pending;
+            let entry = if pending {
+                FilterEntry::Pending(1)
+            } else if matched {
+                FilterEntry::Row(row)
+            } else {
+                FilterEntry::Hidden(1)
+            };
+            run = match (run, entry) {
+                (Some(FilterEntry::Hidden(n)), FilterEntry::Hidden(_)) => {
+                    Some(FilterEntry::Hidden(n + 1))
+                }
+                (Some(FilterEntry::Pending(n)), FilterEntry::Pending(_)) => {
+                    Some(FilterEntry::Pending(n + 1))
+                }
+                (previous, FilterEntry::Row(_)) => {
+                    entries.extend(previous);
+                    entries.push(entry);
+                    None
+                }
+                (previous, _) => {
+                    entries.extend(previous);
+                    Some(entry)
+                }
+            };
+        }
+        entries.extend(run);
+        entries
+    }
+}
+
+/// The filter of one terminal view
+#[derive(Debug, Default)]
+pub(super) struct TerminalFilter {
+    /// Text typed into the filter input
+    pub(super) input: String,
+    /// Keys edit the input instead of going to the program
+    pub(super) editing: bool,
+    /// The input is not a valid regex (the last valid query stays applied)
+    pub(super) invalid: bool,
+    /// Rows matching the applied query; None while it is empty, which
+    /// shows the grid as it is
+    scan: Option<FilterScan>,
+    /// Lines of the filtered view scrolled back from its bottom
+    offset: usize,
+    /// Scrollback size at the last sync
+    history_size: usize,
+}
+
+impl TerminalFilter {
+    /// An open filter input over a grid with `history_size` rows of
+    /// scrollback
+    pub(super) fn open(history_size: usize) -> Self {
+        Self {
+            editing: true,
+            history_size,
+            ..Self::default()
+        }
+    }
+
+    /// Apply the input as it is now to a grid of `rows` rows
+    pub(super) fn set_input(&mut self, input: String, rows: usize) {
+        match FilterQuery::parse(&input) {
+            Ok(query) => {
+                self.scan = query.map(|query| FilterScan::new(query, rows));
+                self.invalid = false;
+                self.offset = 0;
+            }
+            Err(_) => self.invalid = true,
+        }
+        self.input = input;
+    }
+
+    /// The grid has `history_size` rows of scrollback and `rows` in all
+    /// after an update. `full` says the scrollback is at its limit, so rows
+    /// may have been dropped from its top.
+    pub(super) fn sync(&mut self, history_size: usize, rows: usize, full: bool) {
+        let stable = if full || history_size < self.history_size {
+            0
+        } else {
+            self.history_size
+        };
+        if let Some(scan) = self.scan.as_mut() {
+            scan.invalidate_from(stable, rows);
+        }
+        self.history_size = history_size;
+    }
+
+    /// Match the next budget of rows
+    pub(super) fn scan(&mut self, row_text: impl FnMut(usize) -> String) {
+        if let Some(scan) = self.scan.as_mut() {
+            scan.scan(SCAN_BUDGET, row_text);
+        }
+    }
+
+    /// Whether a query is applied (the view shows the filtered rows)
+    pub(super) fn is_filtering(&self) -> bool {
+        self.scan.is_some()
+    }
+
+    /// Whether rows are still to be matched
+    pub(super) fn is_pending(&self) -> bool {
+        self.scan.as_ref().is_some_and(|scan| !scan.is_complete())
+    }
+
+    /// Rows found to match so far
+    pub(super) fn matched_rows(&self) -> usize {
+        self.scan.as_ref().map_or(0, |scan| {
+            scan.matches[scan.top_pending..scan.bottom_pending]
+                .iter()
+                .filter(|&&matched| matched)
+                .count()
+        })
+    }
+
+    /// Entries of `lines` display lines, top to bottom, at the current
+    /// scroll position; None while nothing is filtered
+    pub(super) fn visible(&mut self, lines: usize) -> Option<Vec<FilterEntry>> {
+        let entries = self.scan.as_ref()?.entries();
+        self.offset = self.offset.min(entries.len().saturating_sub(lines));
+        let end = entries.len() - self.offset;
+        Some(entries[end.saturating_sub(lines)..end].to_vec())
+    }
+
+    /// Scroll the filtered view `delta` lines back (negative = forward)
+    pub(super) fn scroll(&mut self, delta: i32) {
+        self.offset = self.offset.saturating_add_signed(delta as isize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan_all(query: &str, rows: &[&str]) -> FilterScan {
+        let query = FilterQuery::parse(query).unwrap().unwrap();
+        let mut scan = FilterScan::new(query, rows.len());
+        scan.scan(usize::MAX, |row| rows[row].to_string());
+        assert!(scan.is_complete());
+        scan
+    }
+
+    #[test]
+    fn test_literal_and_regex_queries() {
+        let literal = FilterQuery::parse("Error").unwrap().unwrap();
+        assert!(literal.matches("build ERROR: missing crate"));
+        assert!(!literal.matches("warning: unused variable"));
+        // A regex metacharacter in a literal is just a character
+        let dots = FilterQuery::parse("a.b").unwrap().unwrap();
+        assert!(dots.matches("a.b"));
+        assert!(!dots.matches("axb"));
+
+        let regex = FilterQuery::parse(r"/^\s*(warning|error)\b/")
+            .unwrap()
+            .unwrap();
+        assert!(regex.matches("  warning: unused variable"));
+        assert!(!regex.matches("no warnings"));
+        // Regexes are case-sensitive unless they say otherwise
+        assert!(!regex.matches("ERROR"));
+        assert!(
+            FilterQuery::parse("/(?i)error/")
+                .unwrap()
+                .unwrap()
+                .matches("ERROR")
+        );
+
+        assert!(FilterQuery::parse("/[unclosed/").is_err());
+        assert!(FilterQuery::parse("").unwrap().is_none());
+        assert!(FilterQuery::parse("//").unwrap().is_none());
+        // A lone slash is a literal
+        assert!(
+            FilterQuery::parse("/")
+                .unwrap()
+                .unwrap()
+                .matches("src/main.rs")
+        );
+    }
+
+    #[test]
+    fn test_non_matching_runs_are_collapsed() {
+        let rows = [
+            "Compiling a",
+            "[####    ]",
+            "[#####   ]",
+            "error: one",
+            "[######  ]",
+            "error: two",
+            "error: three",
+            "[########]",
+            "done",
+        ];
+        let scan = scan_all("error", &rows);
+        let entries = scan.entries();
+        assert_eq!(
+            entries,
+            vec![
+                FilterEntry::Hidden(3),
+                FilterEntry::Row(3),
+                FilterEntry::Hidden(1),
+                FilterEntry::Row(5),
+                FilterEntry::Row(6),
+                FilterEntry::Hidden(2),
+            ]
+        );
+        // Every row is accounted for once
+        let total: usize = entries
+            .iter()
+            .map(|entry| match entry {
+                FilterEntry::Row(_) => 1,
+                FilterEntry::Hidden(n) | FilterEntry::Pending(n) => *n,
+            })
+            .sum();
+        assert_eq!(total, rows.len());
+
+        assert_eq!(
+            scan_all("zzz", &rows).entries(),
+            vec![FilterEntry::Hidden(9)]
+        );
+    }
+
+    #[test]
+    fn test_scan_is_chunked_newest_first_and_resumes_after_output() {
+        let mut rows: Vec<String> = (0..10).map(|i| format!("line {}", i)).collect();
+        let query = FilterQuery::parse("line").unwrap().unwrap();
+        let mut scan = FilterScan::new(query, rows.len());
+
+        let mut read = Vec::new();
+        assert_eq!(
+            scan.scan(4, |row| {
+                read.push(row);
+                rows[row].clone()
+            }),
+            4
+        );
+        assert_eq!(read, vec![9, 8, 7, 6]);
+        assert_eq!(
+            scan.entries(),
+            vec![
+                FilterEntry::Pending(6),
+                FilterEntry::Row(6),
+                FilterEntry::Row(7),
+                FilterEntry::Row(8),
+                FilterEntry::Row(9),
+            ]
+        );
+
+        // Output replaces the last two rows and adds three; only those are
+        // matched again, before the rows still pending above
+        rows.truncate(8);
+        rows.extend((0..5).map(|i| format!("new {}", i)));
+        scan.invalidate_from(8, rows.len());
+        read.clear();
+        scan.scan(6, |row| {
+            read.push(row);
+            rows[row].clone()
+        });
+        assert_eq!(read, vec![8, 9, 10, 11, 12, 5]);
+        assert!(!scan.is_complete());
+        scan.scan(5, |row| rows[row].clone());
+        assert!(scan.is_complete());
+        let mut expected: Vec<_> = (0..8).map(FilterEntry::Row).collect();
+        expected.push(FilterEntry::Hidden(5));
+        assert_eq!(scan.entries(), expected);
+
+        // Nothing scanned survives a change from the top: start over
+        scan.invalidate_from(0, rows.len());
+        assert_eq!(scan.entries(), vec![FilterEntry::Pending(13)]);
+    }
+
+    #[test]
+    fn test_filter_view_scrolls_within_its_entries() {
+        let mut filter = TerminalFilter::open(0);
+        filter.set_input("/[/".to_string(), 20);
+        assert!(filter.invalid);
+        assert_eq!(filter.visible(5), None);
+
+        filter.set_input("even".to_string(), 20);
+        filter.scan(|row| (if row % 2 == 0 { "even" } else { "odd" }).to_string());
+        assert!(!filter.is_pending());
+        assert_eq!(filter.matched_rows(), 10);
+        let bottom = filter.visible(4).unwrap();
+        assert_eq!(bottom.last(), Some(&FilterEntry::Hidden(1)));
+        assert_eq!(bottom.len(), 4);
+
+        filter.scroll(3);
+        assert_eq!(filter.visible(4).unwrap()[3], FilterEntry::Row(16));
+        assert_eq!(filter.visible(4).unwrap()[2], FilterEntry::Hidden(1));
+        // Scrolling stops at either end
+        filter.scroll(100);
+        assert_eq!(filter.visible(4).unwrap()[0], FilterEntry::Row(0));
+        filter.scroll(-200);
+        assert_eq!(filter.visible(4).unwrap(), bottom);
+    }
+}
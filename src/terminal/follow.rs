@@ -0,0 +1,129 @@
+//! Whether a terminal view follows its output
+//!
+//! A view at the bottom follows: new output scrolls it along. Scrolled back
+//! it stays on the lines it shows. Alacritty keeps those lines in place by
+//! raising the display offset by every line pushed into history, so the
+//! growth of the offset across a batch of output is what arrived below the
+//! view. Each view keeps its own state, so panes side by side in parallel
+//! mode follow or stay put independently.
+
+/// Follow state of one terminal view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Follow {
+    following: bool,
+    /// Lines of output that arrived below the view while scrolled back
+    unseen_lines: usize,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self {
+            following: true,
+            unseen_lines: 0,
+        }
+    }
+}
+
+impl Follow {
+    pub(super) fn is_following(&self) -> bool {
+        self.following
+    }
+
+    pub(super) fn unseen_lines(&self) -> usize {
+        self.unseen_lines
+    }
+
+    /// The view is now `offset` lines back from the bottom, whoever moved
+    /// it. At the bottom it follows again and has nothing unseen.
+    pub(super) fn settle(&mut self, offset: usize) {
+        self.following = offset == 0;
+        if self.following {
+            self.unseen_lines = 0;
+        }
+    }
+
+    /// Output moved the offset from `previous` to `offset`
+    pub(super) fn output(&mut self, previous: usize, offset: usize) {
+        if !self.following && offset > previous {
+            self.unseen_lines += offset - previous;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::grid::{Dimensions, Scroll};
+    use alacritty_terminal::index::{Column, Line};
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::term::{Config, Term};
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn feed(term: &mut Term<VoidListener>, parser: &mut Processor, lines: std::ops::Range<usize>) {
+        for i in lines {
+            parser.advance(term, format!("line {}\r\n", i).as_bytes());
+        }
+    }
+
+    /// Text of the top line of the view
+    fn top_line(term: &Term<VoidListener>) -> String {
+        let grid = term.grid();
+        let line = Line(-(grid.display_offset() as i32));
+        (0..grid.columns())
+            .map(|column| grid[line][Column(column)].c)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn test_output_while_scrolled_back_keeps_the_view_and_counts() {
+        let mut term = Term::new(Config::default(), &TermSize::new(40, 10), VoidListener);
+        let mut parser: Processor = Processor::new();
+        let mut follow = Follow::default();
+        feed(&mut term, &mut parser, 0..50);
+        assert!(follow.is_following());
+
+        term.scroll_display(Scroll::Delta(15));
+        follow.settle(term.grid().display_offset());
+        assert!(!follow.is_following());
+        let top = top_line(&term);
+
+        for batch in [50..53, 53..60] {
+            let previous = term.grid().display_offset();
+            feed(&mut term, &mut parser, batch);
+            follow.settle(term.grid().display_offset());
+            follow.output(previous, term.grid().display_offset());
+            assert_eq!(top_line(&term), top);
+        }
+        assert_eq!(follow.unseen_lines(), 10);
+        assert!(!follow.is_following());
+
+        // Jumping to the bottom follows again
+        term.scroll_display(Scroll::Bottom);
+        follow.settle(term.grid().display_offset());
+        assert!(follow.is_following());
+        assert_eq!(follow.unseen_lines(), 0);
+        let previous = term.grid().display_offset();
+        feed(&mut term, &mut parser, 60..65);
+        follow.output(previous, term.grid().display_offset());
+        assert_eq!(term.grid().display_offset(), 0);
+        assert_eq!(follow.unseen_lines(), 0);
+    }
+
+    #[test]
+    fn test_scrolling_is_not_output() {
+        let mut follow = Follow::default();
+        follow.settle(5);
+        follow.settle(8);
+        follow.output(8, 8);
+        assert_eq!(follow.unseen_lines(), 0);
+
+        follow.output(8, 11);
+        follow.settle(4);
+        assert_eq!(follow.unseen_lines(), 3);
+        follow.settle(0);
+        assert_eq!(follow, Follow::default());
+    }
+}
@@ -0,0 +1,41 @@
+//! Which program holds the terminal
+//!
+//! The foreground process group of the PTY is read from the master with
+//! `tcgetpgrp`; while the shell waits at its prompt, that is the shell
+//! itself. The group leader's name comes from `/proc` on Linux and
+//! `proc_name` on macOS, as does the shell's own name. Nothing is spawned,
+//! so this is cheap enough to ask from the UI thread.
+
+use std::fs::File;
+use std::os::fd::AsRawFd;
+
+/// Name of the foreground program of the PTY behind `master`, None while the
+/// shell (`shell_pid`) has the foreground
+pub fn program(master: &File, shell_pid: u32) -> Option<String> {
+    // SAFETY: tcgetpgrp only queries the terminal behind a valid open fd
+    let pgrp = unsafe { libc::tcgetpgrp(master.as_raw_fd()) };
+    if pgrp <= 0 || pgrp as u32 == shell_pid {
+        return None;
+    }
+    Some(process_name(pgrp).unwrap_or_else(|| format!("process {}", pgrp)))
+}
+
+#[cfg(target_os = "linux")]
+pub fn process_name(pid: libc::pid_t) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(target_os = "macos")]
+pub fn process_name(pid: libc::pid_t) -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: proc_name writes at most `buf.len()` bytes into `buf`
+    let len = unsafe { libc::proc_name(pid, buf.as_mut_ptr().cast(), buf.len() as u32) };
+    (len > 0).then(|| String::from_utf8_lossy(&buf[..len as usize]).into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn process_name(_pid: libc::pid_t) -> Option<String> {
+    None
+}
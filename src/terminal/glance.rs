@@ -0,0 +1,123 @@
+//! The last rows of a terminal as plain text, for glancing at a session
+//! without switching to it
+//!
+//! Only the rows asked for are read, straight out of the grid under the
+//! terminal lock; nothing else of the grid is copied.
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::Term;
+use alacritty_terminal::term::cell::{Cell, Flags};
+use std::time::Instant;
+
+/// Last rows of a terminal and when it last printed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalTail {
+    /// Rows, oldest first, without trailing blanks
+    pub rows: Vec<String>,
+    /// When the terminal last printed anything
+    pub last_output: Option<Instant>,
+}
+
+/// Up to `count` rows ending at the last row on screen that has text. Blank
+/// rows below it are left out (a fresh shell only has text at the top);
+/// rows above the screen come from the scrollback. An empty screen has no
+/// rows.
+pub(super) fn tail_rows<T: EventListener>(term: &Term<T>, count: usize) -> Vec<String> {
+    let grid = term.grid();
+    let Some(bottom) = (0..grid.screen_lines() as i32)
+        .rev()
+        .find(|&line| !is_blank(grid, line))
+    else {
+        return Vec::new();
+    };
+    let top = (bottom - count as i32 + 1).max(grid.topmost_line().0);
+    (top..=bottom).map(|line| row_text(grid, line)).collect()
+}
+
+fn is_blank(grid: &Grid<Cell>, line: i32) -> bool {
+    let row = &grid[Line(line)];
+    (0..grid.columns()).all(|column| row[Column(column)].c.is_whitespace())
+}
+
+/// Text of a row: wide characters once, tabs as spaces, no trailing blanks
+pub(super) fn row_text(grid: &Grid<Cell>, line: i32) -> String {
+    let row = &grid[Line(line)];
+    let mut text = String::new();
+    for column in 0..grid.columns() {
+        let cell = &row[Column(column)];
+        if cell
+            .flags
+            .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER)
+        {
+            continue;
+        }
+        text.push(if cell.c == '\t' { ' ' } else { cell.c });
+        if let Some(zerowidth) = cell.zerowidth() {
+            text.extend(zerowidth);
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn term(columns: usize, lines: usize) -> Term<VoidListener> {
+        Term::new(
+            TermConfig::default(),
+            &TermSize::new(columns, lines),
+            VoidListener,
+        )
+    }
+
+    fn write(term: &mut Term<VoidListener>, text: &str) {
+        let mut parser: Processor = Processor::new();
+        parser.advance(term, text.as_bytes());
+    }
+
+    #[test]
+    fn test_empty_grid_has_no_rows() {
+        let term = term(20, 5);
+        assert!(tail_rows(&term, 6).is_empty());
+    }
+
+    #[test]
+    fn test_rows_end_at_the_last_text() {
+        let mut term = term(20, 10);
+        write(&mut term, "one\r\ntwo  \r\n\r\nthree\r\n");
+        assert_eq!(tail_rows(&term, 6), vec!["one", "two", "", "three"]);
+        assert_eq!(tail_rows(&term, 2), vec!["", "three"]);
+        assert!(tail_rows(&term, 0).is_empty());
+    }
+
+    #[test]
+    fn test_rows_reach_into_the_scrollback() {
+        let mut term = term(20, 3);
+        for i in 0..10 {
+            write(&mut term, &format!("line {}\r\n", i));
+        }
+        // The screen shows lines 8 and 9 above the empty cursor row
+        assert_eq!(
+            tail_rows(&term, 4),
+            vec!["line 6", "line 7", "line 8", "line 9"]
+        );
+    }
+
+    #[test]
+    fn test_wide_characters_are_read_once() {
+        let mut term = term(10, 4);
+        write(&mut term, "日本語\tx\r\n");
+        // A wide character that does not fit moves to the next row, leaving
+        // a spacer at the end of this one
+        write(&mut term, "abcdefghi漢\r\n");
+        assert_eq!(tail_rows(&term, 3), vec!["日本語  x", "abcdefghi", "漢"]);
+    }
+}
@@ -0,0 +1,50 @@
+//! Terminal views as the terminals of sessions (see `session::TerminalHandle`)
+
+use super::TerminalView;
+use crate::session::{TerminalFactory, TerminalHandle, TerminalSpec};
+use gpui::{App, AppContext, Entity};
+
+/// Starts a `TerminalView` for every terminal of a session
+pub struct ViewTerminals;
+
+impl TerminalFactory<Entity<TerminalView>> for ViewTerminals {
+    fn create(&self, spec: TerminalSpec, cx: &mut App) -> Entity<TerminalView> {
+        cx.new(|cx| {
+            TerminalView::new_with_directory(
+                spec.working_dir,
+                spec.shell.as_deref(),
+                spec.env,
+                spec.attention,
+                spec.transcript,
+                spec.history,
+                spec.scrollback,
+                spec.multiplexed,
+                cx,
+            )
+        })
+    }
+}
+
+impl TerminalHandle for Entity<TerminalView> {
+    type Cx = App;
+
+    fn write(&self, text: &str, cx: &mut App) {
+        self.read(cx).write_text(text);
+    }
+
+    fn shutdown(&self, cx: &mut App) {
+        self.read(cx).shutdown();
+    }
+
+    fn kill(&self, cx: &mut App) {
+        self.read(cx).kill();
+    }
+
+    fn multiplexed(&self, cx: &App) -> Option<String> {
+        self.read(cx).multiplexed().map(str::to_string)
+    }
+
+    fn is_running(&self, cx: &App) -> bool {
+        self.read(cx).is_running()
+    }
+}
@@ -91,16 +91,24 @@ actions!(
         CtrlDown,
         CtrlLeft,
         CtrlRight,
+        CtrlEnd,
         CtrlShiftUp,
         CtrlShiftDown,
         CtrlShiftLeft,
         CtrlShiftRight,
+        CtrlShiftA,
         CtrlShiftC,
         CtrlShiftV,
-        CtrlAltUp,
-        CtrlAltDown,
-        CtrlAltLeft,
-        CtrlAltRight,
+        ToggleCopyMode,
+        ToggleFilter,
+        // Code block overlay; the app handles ApplyCodeBlock (see
+        // `TerminalView::take_chosen_code_block`)
+        ToggleCodeBlocks,
+        ApplyCodeBlock,
+        // Shell integration prompts
+        PreviousPrompt,
+        NextPrompt,
+        CopyLastOutput,
     ]
 );
 
@@ -193,18 +201,27 @@ impl TerminalView {
             KeyBinding::new("ctrl-down", CtrlDown, Some("Terminal")),
             KeyBinding::new("ctrl-left", CtrlLeft, Some("Terminal")),
             KeyBinding::new("ctrl-right", CtrlRight, Some("Terminal")),
+            KeyBinding::new("ctrl-end", CtrlEnd, Some("Terminal")),
             // Ctrl+Shift keys
             KeyBinding::new("ctrl-shift-up", CtrlShiftUp, Some("Terminal")),
             KeyBinding::new("ctrl-shift-down", CtrlShiftDown, Some("Terminal")),
             KeyBinding::new("ctrl-shift-left", CtrlShiftLeft, Some("Terminal")),
             KeyBinding::new("ctrl-shift-right", CtrlShiftRight, Some("Terminal")),
+            KeyBinding::new("ctrl-shift-a", CtrlShiftA, Some("Terminal")),
             KeyBinding::new("ctrl-shift-c", CtrlShiftC, Some("Terminal")),
             KeyBinding::new("ctrl-shift-v", CtrlShiftV, Some("Terminal")),
-            // Ctrl+Alt+arrow keys
-            KeyBinding::new("ctrl-alt-up", CtrlAltUp, Some("Terminal")),
-            KeyBinding::new("ctrl-alt-down", CtrlAltDown, Some("Terminal")),
-            KeyBinding::new("ctrl-alt-left", CtrlAltLeft, Some("Terminal")),
-            KeyBinding::new("ctrl-alt-right", CtrlAltRight, Some("Terminal")),
+            // Copy mode (its other keys are handled in `handle_copy_mode_key`)
+            KeyBinding::new("ctrl-shift-space", ToggleCopyMode, Some("Terminal")),
+            KeyBinding::new("ctrl-shift-space", ToggleCopyMode, Some("TerminalCopyMode")),
+            // Output filter (its input's keys are handled in `handle_filter_key`)
+            KeyBinding::new("ctrl-shift-f", ToggleFilter, Some("Terminal")),
+            KeyBinding::new("ctrl-shift-f", ToggleFilter, Some("TerminalFilter")),
+            // Code blocks printed in the view, to apply to a file or copy
+            KeyBinding::new("ctrl-shift-b", ToggleCodeBlocks, Some("Terminal")),
+            // Shell integration prompts (no-ops without integration)
+            KeyBinding::new("alt-shift-up", PreviousPrompt, Some("Terminal")),
+            KeyBinding::new("alt-shift-down", NextPrompt, Some("Terminal")),
+            KeyBinding::new("ctrl-shift-o", CopyLastOutput, Some("Terminal")),
         ]);
     }
 
@@ -248,8 +265,13 @@ impl TerminalView {
         self.write_to_terminal(b"\x1b[H");
     }
 
-    pub(super) fn on_end(&mut self, _: &End, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[F");
+    pub(super) fn on_end(&mut self, _: &End, _: &mut Window, cx: &mut Context<Self>) {
+        // Jumps back to the newest output while scrolled back
+        if self.is_scrolled_back() {
+            self.scroll_to_bottom(cx);
+        } else {
+            self.write_to_terminal(b"\x1b[F");
+        }
     }
 
     pub(super) fn on_delete(&mut self, _: &Delete, _: &mut Window, _: &mut Context<Self>) {
@@ -494,28 +516,46 @@ impl TerminalView {
     }
 
     // Shift+arrow handlers (xterm sequences with modifier 2)
-    pub(super) fn on_shift_up(&mut self, _: &ShiftUp, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;2A");
+    pub(super) fn on_shift_up(&mut self, _: &ShiftUp, _: &mut Window, cx: &mut Context<Self>) {
+        if !self.extend_selection_by(-1, 0, cx) {
+            self.write_to_terminal(b"\x1b[1;2A");
+        }
     }
 
-    pub(super) fn on_shift_down(&mut self, _: &ShiftDown, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;2B");
+    pub(super) fn on_shift_down(&mut self, _: &ShiftDown, _: &mut Window, cx: &mut Context<Self>) {
+        if !self.extend_selection_by(1, 0, cx) {
+            self.write_to_terminal(b"\x1b[1;2B");
+        }
     }
 
-    pub(super) fn on_shift_left(&mut self, _: &ShiftLeft, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;2D");
+    pub(super) fn on_shift_left(&mut self, _: &ShiftLeft, _: &mut Window, cx: &mut Context<Self>) {
+        if !self.extend_selection_by(0, -1, cx) {
+            self.write_to_terminal(b"\x1b[1;2D");
+        }
     }
 
-    pub(super) fn on_shift_right(&mut self, _: &ShiftRight, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;2C");
+    pub(super) fn on_shift_right(
+        &mut self,
+        _: &ShiftRight,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.extend_selection_by(0, 1, cx) {
+            self.write_to_terminal(b"\x1b[1;2C");
+        }
     }
 
     pub(super) fn on_shift_home(&mut self, _: &ShiftHome, _: &mut Window, _: &mut Context<Self>) {
         self.write_to_terminal(b"\x1b[1;2H");
     }
 
-    pub(super) fn on_shift_end(&mut self, _: &ShiftEnd, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;2F");
+    pub(super) fn on_shift_end(&mut self, _: &ShiftEnd, _: &mut Window, cx: &mut Context<Self>) {
+        // Jumps back to the newest output while scrolled back
+        if self.is_scrolled_back() {
+            self.scroll_to_bottom(cx);
+        } else {
+            self.write_to_terminal(b"\x1b[1;2F");
+        }
     }
 
     pub(super) fn on_shift_insert(
@@ -526,7 +566,7 @@ impl TerminalView {
     ) {
         // Paste from clipboard
         if let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) {
-            self.write_to_terminal(text.as_bytes());
+            self.paste_text(&text);
         }
     }
 
@@ -581,6 +621,15 @@ impl TerminalView {
         self.write_to_terminal(b"\x1b[1;5C");
     }
 
+    pub(super) fn on_ctrl_end(&mut self, _: &CtrlEnd, _: &mut Window, cx: &mut Context<Self>) {
+        // Jumps back to the newest output while scrolled back
+        if self.is_scrolled_back() {
+            self.scroll_to_bottom(cx);
+        } else {
+            self.write_to_terminal(b"\x1b[1;5F");
+        }
+    }
+
     // Ctrl+Shift+arrow handlers (xterm sequences with modifier 6)
     pub(super) fn on_ctrl_shift_up(
         &mut self,
@@ -618,6 +667,15 @@ impl TerminalView {
         self.write_to_terminal(b"\x1b[1;6C");
     }
 
+    pub(super) fn on_ctrl_shift_a(
+        &mut self,
+        _: &CtrlShiftA,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.select_all(cx);
+    }
+
     pub(super) fn on_ctrl_shift_c(
         &mut self,
         _: &CtrlShiftC,
@@ -638,39 +696,61 @@ impl TerminalView {
     ) {
         // Paste from clipboard
         if let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) {
-            self.write_to_terminal(text.as_bytes());
+            self.paste_text(&text);
         }
     }
 
-    // Ctrl+Alt+arrow handlers (xterm sequences with modifier 7)
-    pub(super) fn on_ctrl_alt_up(&mut self, _: &CtrlAltUp, _: &mut Window, _: &mut Context<Self>) {
-        self.write_to_terminal(b"\x1b[1;7A");
+    pub(super) fn on_toggle_filter(
+        &mut self,
+        _: &ToggleFilter,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.toggle_filter(cx);
     }
 
-    pub(super) fn on_ctrl_alt_down(
+    pub(super) fn on_toggle_code_blocks(
         &mut self,
-        _: &CtrlAltDown,
+        _: &ToggleCodeBlocks,
         _: &mut Window,
-        _: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) {
-        self.write_to_terminal(b"\x1b[1;7B");
+        self.toggle_code_blocks(cx);
     }
 
-    pub(super) fn on_ctrl_alt_left(
+    pub(super) fn on_toggle_copy_mode(
         &mut self,
-        _: &CtrlAltLeft,
+        _: &ToggleCopyMode,
         _: &mut Window,
-        _: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) {
-        self.write_to_terminal(b"\x1b[1;7D");
+        self.toggle_copy_mode(cx);
     }
 
-    pub(super) fn on_ctrl_alt_right(
+    pub(super) fn on_previous_prompt(
         &mut self,
-        _: &CtrlAltRight,
+        _: &PreviousPrompt,
         _: &mut Window,
-        _: &mut Context<Self>,
+        cx: &mut Context<Self>,
+    ) {
+        self.jump_to_prompt(true, cx);
+    }
+
+    pub(super) fn on_next_prompt(
+        &mut self,
+        _: &NextPrompt,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.jump_to_prompt(false, cx);
+    }
+
+    pub(super) fn on_copy_last_output(
+        &mut self,
+        _: &CopyLastOutput,
+        _: &mut Window,
+        cx: &mut Context<Self>,
     ) {
-        self.write_to_terminal(b"\x1b[1;7C");
+        self.copy_last_output(cx);
     }
 }
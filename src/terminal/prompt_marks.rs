@@ -0,0 +1,382 @@
+//! Prompt and command output lines reported by shell integration
+//!
+//! A shell with OSC 133 integration marks where its prompt starts, where the
+//! command line starts, where the command's output starts and where the
+//! command finished (see `ShellMark`). The PTY reader ends each read at a
+//! marker and places it on the cursor line once the event loop has parsed
+//! up to it (see `tee_pty`), so a mark sits on the line the shell meant.
+//!
+//! Marks are grid lines (negative in the scrollback) and move up as lines
+//! scroll into history. Alacritty does not count scrolled lines, so the
+//! newest history row is remembered by the address of its cells, which a
+//! row keeps while it scrolls, and found again on the next look. A flood
+//! that wraps the whole scrollback in between brings the row back with other
+//! text, and the marks are dropped. A resize reflows lines, so marks are
+//! carried across it as logical lines counted from the cursor's.
+
+use super::reflow::LineStarts;
+use crate::command_history::ShellMark;
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::Cell;
+use alacritty_terminal::term::{Term, TermMode};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A marker the shell sent, on the grid line the cursor was on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PromptMark {
+    pub kind: ShellMark,
+    pub line: i32,
+}
+
+/// Marks on a terminal's primary screen
+#[derive(Debug, Default)]
+pub struct PromptMarks {
+    /// Top to bottom
+    marks: Vec<PromptMark>,
+    /// Row looked for on the next sync to tell how far the grid scrolled
+    anchor: Option<Anchor>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Anchor {
+    line: i32,
+    address: usize,
+    text: u64,
+}
+
+impl Anchor {
+    /// The newest history row, or the top screen row while there is no
+    /// history
+    fn of(grid: &Grid<Cell>) -> Self {
+        let line = if grid.history_size() > 0 { -1 } else { 0 };
+        Self {
+            line,
+            address: row_address(grid, line),
+            text: row_text(grid, line),
+        }
+    }
+}
+
+impl PromptMarks {
+    /// Place `kind` on the cursor line. Marks below it are dropped, as is a
+    /// mark of the same kind on that line, which a shell redrawing its
+    /// prompt sends again.
+    pub fn place<T: EventListener>(&mut self, kind: ShellMark, term: &Term<T>) {
+        if !self.sync(term) {
+            return;
+        }
+        let line = term.grid().cursor.point.line.0;
+        self.marks
+            .retain(|mark| mark.line < line || (mark.line == line && mark.kind != kind));
+        self.marks.push(PromptMark { kind, line });
+    }
+
+    /// Catch up with the lines `term` scrolled into history since the last
+    /// sync, dropping the marks that left the scrollback. False while the
+    /// alternate screen is shown: the marks belong to the primary one.
+    pub fn sync<T: EventListener>(&mut self, term: &Term<T>) -> bool {
+        if term.mode().contains(TermMode::ALT_SCREEN) {
+            return false;
+        }
+        let grid = term.grid();
+        if let Some(anchor) = self.anchor {
+            let top = -(grid.history_size() as i32);
+            let found = (top..=anchor.line)
+                .rev()
+                .find(|&line| row_address(grid, line) == anchor.address)
+                // History rows keep their text; the same row coming around
+                // again after the scrollback wrapped has other text
+                .filter(|&line| anchor.line == 0 || row_text(grid, line) == anchor.text);
+            match found {
+                Some(line) => {
+                    let scrolled = anchor.line - line;
+                    self.marks.retain_mut(|mark| {
+                        mark.line -= scrolled;
+                        mark.line >= top
+                    });
+                }
+                None => self.marks.clear(),
+            }
+        }
+        self.anchor = Some(Anchor::of(grid));
+        true
+    }
+
+    /// Resize `term` to `size`, carrying the marks across the reflow
+    pub fn resize<T: EventListener, S: Dimensions>(&mut self, term: &mut Term<T>, size: S) {
+        if !self.sync(term) {
+            // The primary screen reflows out of sight
+            term.resize(size);
+            self.marks.clear();
+            self.anchor = None;
+            return;
+        }
+        let before = LineStarts::of(term.grid());
+        let cursor = before.index_of(term.grid().cursor.point.line.0) as isize;
+        let carried: Vec<_> = self
+            .marks
+            .iter()
+            .map(|mark| {
+                let index = before.index_of(mark.line);
+                (
+                    mark.kind,
+                    cursor - index as isize,
+                    mark.line - before.start(index),
+                )
+            })
+            .collect();
+
+        term.resize(size);
+
+        let grid = term.grid();
+        let after = LineStarts::of(grid);
+        let cursor = after.index_of(grid.cursor.point.line.0) as isize;
+        self.marks = carried
+            .into_iter()
+            .filter_map(|(kind, back, row)| {
+                let index = usize::try_from(cursor - back).ok()?;
+                let line = after.row(index, row, grid)?;
+                Some(PromptMark { kind, line })
+            })
+            .collect();
+        self.anchor = Some(Anchor::of(grid));
+    }
+
+    /// Marks top to bottom
+    #[cfg(test)]
+    pub fn marks(&self) -> &[PromptMark] {
+        &self.marks
+    }
+
+    /// Lines where prompts start, top to bottom
+    pub fn prompt_lines(&self) -> impl DoubleEndedIterator<Item = i32> + '_ {
+        self.marks
+            .iter()
+            .filter(|mark| mark.kind == ShellMark::PromptStart)
+            .map(|mark| mark.line)
+    }
+
+    /// Nearest prompt line above `line`
+    pub fn prompt_above(&self, line: i32) -> Option<i32> {
+        self.prompt_lines().rfind(|&prompt| prompt < line)
+    }
+
+    /// Nearest prompt line below `line`
+    pub fn prompt_below(&self, line: i32) -> Option<i32> {
+        self.prompt_lines().find(|&prompt| prompt > line)
+    }
+
+    /// First and last line of the output of the last command run: from where
+    /// its output started to the line before the next prompt, or to
+    /// `cursor_line` while it still runs. None when it printed nothing.
+    pub fn last_output(&self, cursor_line: i32) -> Option<(i32, i32)> {
+        let start = self
+            .marks
+            .iter()
+            .rposition(|mark| mark.kind == ShellMark::OutputStart)?;
+        let first = self.marks[start].line;
+        let last = self.marks[start + 1..]
+            .iter()
+            .find(|mark| mark.kind == ShellMark::PromptStart)
+            .map_or(cursor_line, |prompt| prompt.line - 1);
+        (last >= first).then_some((first, last))
+    }
+}
+
+/// Address of the cells of the row at `line`, which stay with the row as it
+/// scrolls
+fn row_address(grid: &Grid<Cell>, line: i32) -> usize {
+    std::ptr::from_ref(&grid[Line(line)][Column(0)]).addr()
+}
+
+fn row_text(grid: &Grid<Cell>, line: i32) -> u64 {
+    let row = &grid[Line(line)];
+    let mut hasher = DefaultHasher::new();
+    for column in 0..grid.columns() {
+        row[Column(column)].c.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn term(columns: usize, lines: usize, history: usize) -> Term<VoidListener> {
+        let config = TermConfig {
+            scrolling_history: history,
+            ..TermConfig::default()
+        };
+        Term::new(config, &TermSize::new(columns, lines), VoidListener)
+    }
+
+    fn write(term: &mut Term<VoidListener>, text: &str) {
+        let mut parser: Processor = Processor::new();
+        parser.advance(term, text.as_bytes());
+    }
+
+    /// Text of the row at `line`, trailing blanks trimmed
+    fn row(term: &Term<VoidListener>, line: i32) -> String {
+        let grid = term.grid();
+        let text: String = (0..grid.columns())
+            .map(|column| grid[Line(line)][Column(column)].c)
+            .collect();
+        text.trim_end().to_string()
+    }
+
+    fn mark(kind: ShellMark, line: i32) -> PromptMark {
+        PromptMark { kind, line }
+    }
+
+    #[test]
+    fn test_marks_follow_lines_into_history_until_evicted() {
+        // 5 screen lines and at most 10 lines of history
+        let mut term = term(20, 5, 10);
+        let mut marks = PromptMarks::default();
+
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "1$ ls\r\n");
+        for i in 0..12 {
+            write(&mut term, &format!("l{}\r\n", i));
+        }
+        assert!(marks.sync(&term));
+        assert_eq!(marks.marks(), &[mark(ShellMark::PromptStart, -9)]);
+        assert_eq!(row(&term, -9), "1$ ls");
+
+        // Filling the scrollback evicts the first prompt
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "2$ ls\r\nm0\r\nm1\r\nm2\r\n");
+        marks.sync(&term);
+        assert_eq!(term.grid().history_size(), 10);
+        assert_eq!(marks.marks(), &[mark(ShellMark::PromptStart, 0)]);
+        assert_eq!(row(&term, 0), "2$ ls");
+
+        for i in 0..30 {
+            write(&mut term, &format!("n{}\r\n", i));
+        }
+        marks.sync(&term);
+        assert!(marks.marks().is_empty());
+    }
+
+    #[test]
+    fn test_marks_survive_reflow() {
+        let mut term = term(20, 10, 100);
+        let mut marks = PromptMarks::default();
+
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "1$ make\r\n");
+        marks.place(ShellMark::OutputStart, &term);
+        write(&mut term, &format!("{}\r\nyy\r\n", "x".repeat(30)));
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "2$ ");
+        let texts = |marks: &PromptMarks, term: &Term<VoidListener>| -> Vec<String> {
+            marks.marks().iter().map(|m| row(term, m.line)).collect()
+        };
+        assert_eq!(texts(&marks, &term), vec!["1$ make", &"x".repeat(20), "2$"]);
+
+        // Narrower: the long line splits in three, the marks keep their lines
+        marks.resize(&mut term, TermSize::new(10, 10));
+        assert_eq!(texts(&marks, &term), vec!["1$ make", &"x".repeat(10), "2$"]);
+        let lines: Vec<i32> = marks.marks().iter().map(|m| m.line).collect();
+        assert_eq!(lines[2] - lines[1], 4);
+
+        // Wider than ever: it merges back into one row
+        marks.resize(&mut term, TermSize::new(40, 10));
+        assert_eq!(texts(&marks, &term), vec!["1$ make", &"x".repeat(30), "2$"]);
+        let lines: Vec<i32> = marks.marks().iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![lines[0], lines[0] + 1, lines[0] + 3]);
+
+        // Fewer lines push the top into history, where the marks follow
+        marks.resize(&mut term, TermSize::new(40, 2));
+        assert_eq!(texts(&marks, &term), vec!["1$ make", &"x".repeat(30), "2$"]);
+        assert!(marks.marks()[0].line < 0);
+    }
+
+    #[test]
+    fn test_redrawn_prompt_replaces_its_mark() {
+        let mut term = term(20, 5, 10);
+        let mut marks = PromptMarks::default();
+        marks.place(ShellMark::PromptStart, &term);
+        marks.place(ShellMark::CommandStart, &term);
+        marks.place(ShellMark::PromptStart, &term);
+        assert_eq!(
+            marks.marks(),
+            &[
+                mark(ShellMark::CommandStart, 0),
+                mark(ShellMark::PromptStart, 0)
+            ]
+        );
+
+        // A prompt drawn above newer marks (the screen was redrawn) drops them
+        write(&mut term, "a\r\nb\r\n");
+        marks.place(ShellMark::OutputStart, &term);
+        write(&mut term, "\x1b[H");
+        marks.place(ShellMark::PromptStart, &term);
+        assert_eq!(
+            marks.marks(),
+            &[
+                mark(ShellMark::CommandStart, 0),
+                mark(ShellMark::PromptStart, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alternate_screen_has_no_marks() {
+        let mut term = term(20, 5, 10);
+        let mut marks = PromptMarks::default();
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "\x1b[?1049h");
+        assert!(!marks.sync(&term));
+        marks.place(ShellMark::PromptStart, &term);
+        write(&mut term, "\x1b[?1049l");
+        assert!(marks.sync(&term));
+        assert_eq!(marks.marks(), &[mark(ShellMark::PromptStart, 0)]);
+    }
+
+    #[test]
+    fn test_prompt_navigation_and_last_output() {
+        let marks = PromptMarks {
+            marks: vec![
+                mark(ShellMark::PromptStart, -20),
+                mark(ShellMark::CommandStart, -20),
+                mark(ShellMark::OutputStart, -19),
+                mark(ShellMark::CommandEnd, -5),
+                mark(ShellMark::PromptStart, -5),
+                mark(ShellMark::OutputStart, -4),
+                mark(ShellMark::PromptStart, -4),
+            ],
+            anchor: None,
+        };
+        assert_eq!(marks.prompt_above(0), Some(-4));
+        assert_eq!(marks.prompt_above(-5), Some(-20));
+        assert_eq!(marks.prompt_above(-20), None);
+        assert_eq!(marks.prompt_below(-20), Some(-5));
+        assert_eq!(marks.prompt_below(-4), None);
+
+        // The last command printed nothing
+        assert_eq!(marks.last_output(0), None);
+
+        let marks = PromptMarks {
+            marks: marks.marks[..5].to_vec(),
+            anchor: None,
+        };
+        assert_eq!(marks.last_output(0), Some((-19, -6)));
+
+        // A command still running: its output so far
+        let mut running = marks.marks.clone();
+        running.push(mark(ShellMark::OutputStart, -3));
+        let marks = PromptMarks {
+            marks: running,
+            anchor: None,
+        };
+        assert_eq!(marks.last_output(2), Some((-3, 2)));
+        assert_eq!(PromptMarks::default().last_output(2), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! Input queue for a PTY, drained by a dedicated writer thread
+//!
+//! A write to a PTY blocks once the program on the other side stops reading
+//! (XOFF flow control, a stuck agent). Input is queued here and written by a
+//! background thread instead, so the UI never waits on the PTY. Whatever is
+//! queued when the writer gets to it goes out in one write and one flush. A
+//! backlog above `BACKLOG_THRESHOLD` is reported so the view can show that the
+//! terminal is not accepting input.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Unwritten input above this many bytes counts as a backlog
+pub const BACKLOG_THRESHOLD: usize = 64 * 1024;
+
+/// Input arriving while this many bytes are unwritten is dropped
+pub const MAX_QUEUED_BYTES: usize = 16 * 1024 * 1024;
+
+/// How long to wait before retrying a write the PTY would block on
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Default)]
+struct Queue {
+    chunks: VecDeque<Vec<u8>>,
+    /// Bytes not written yet, including the batch being written
+    unwritten: usize,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    backed_up: AtomicBool,
+    /// Called (from any thread) when `backed_up` changes
+    on_backlog_change: Box<dyn Fn() + Send + Sync>,
+}
+
+impl Shared {
+    fn update_backlog(&self, queue: &Queue) {
+        let backed_up = queue.unwritten > BACKLOG_THRESHOLD;
+        if self.backed_up.swap(backed_up, Ordering::AcqRel) != backed_up {
+            (self.on_backlog_change)();
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue.lock().map(|q| q.closed).unwrap_or(true)
+    }
+}
+
+/// Non-blocking handle to the writer thread; dropping it drops pending input
+/// and stops the thread
+pub struct PtyWriter {
+    shared: Arc<Shared>,
+}
+
+impl PtyWriter {
+    /// Start the writer thread for `writer`
+    pub fn spawn<W>(writer: W, on_backlog_change: impl Fn() + Send + Sync + 'static) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            backed_up: AtomicBool::new(false),
+            on_backlog_change: Box::new(on_backlog_change),
+        });
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .name("pty-writer".to_string())
+            .spawn(move || run(writer, &thread_shared))
+            .expect("failed to spawn PTY writer thread");
+        Self { shared }
+    }
+
+    /// Queue `data` without waiting. Returns false when it was dropped
+    /// because the backlog is full or the PTY is gone.
+    pub fn write(&self, data: &[u8]) -> bool {
+        if data.is_empty() {
+            return true;
+        }
+        let Ok(mut queue) = self.shared.queue.lock() else {
+            return false;
+        };
+        if queue.closed || queue.unwritten + data.len() > MAX_QUEUED_BYTES {
+            return false;
+        }
+        queue.chunks.push_back(data.to_vec());
+        queue.unwritten += data.len();
+        self.shared.update_backlog(&queue);
+        self.shared.ready.notify_one();
+        true
+    }
+
+    /// Drop input that has not been handed to the PTY yet
+    pub fn clear(&self) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            let pending: usize = queue.chunks.drain(..).map(|chunk| chunk.len()).sum();
+            queue.unwritten -= pending;
+            self.shared.update_backlog(&queue);
+        }
+    }
+
+    /// Whether more than `BACKLOG_THRESHOLD` bytes are waiting on the PTY
+    pub fn is_backed_up(&self) -> bool {
+        self.shared.backed_up.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for PtyWriter {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            queue.closed = true;
+            queue.chunks.clear();
+        }
+        self.shared.ready.notify_one();
+    }
+}
+
+/// Writer thread: write everything queued as one batch until closed or the
+/// PTY fails
+fn run<W: Write>(mut writer: W, shared: &Shared) {
+    loop {
+        let batch = {
+            let Ok(mut queue) = shared.queue.lock() else {
+                return;
+            };
+            while queue.chunks.is_empty() && !queue.closed {
+                queue = match shared.ready.wait(queue) {
+                    Ok(queue) => queue,
+                    Err(_) => return,
+                };
+            }
+            if queue.closed {
+                return;
+            }
+            queue.chunks.drain(..).flatten().collect::<Vec<u8>>()
+        };
+
+        let result = write_batch(&mut writer, &batch, shared).and_then(|()| writer.flush());
+
+        let Ok(mut queue) = shared.queue.lock() else {
+            return;
+        };
+        queue.unwritten -= batch.len();
+        if result.is_err() {
+            // The PTY is gone; nothing queued can be delivered
+            queue.closed = true;
+            queue.chunks.clear();
+            queue.unwritten = 0;
+        }
+        shared.update_backlog(&queue);
+        if queue.closed {
+            return;
+        }
+    }
+}
+
+/// `write_all` that waits out a PTY that would block, giving up once the
+/// writer is closed
+fn write_batch<W: Write>(writer: &mut W, mut data: &[u8], shared: &Shared) -> io::Result<()> {
+    while !data.is_empty() {
+        match writer.write(data) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => data = &data[n..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if shared.is_closed() {
+                    return Err(e);
+                }
+                std::thread::sleep(RETRY_INTERVAL);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    /// Writer that blocks until released, recording what it was given
+    struct BlockingWriter {
+        release: mpsc::Receiver<()>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for BlockingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            // Blocks until the sender is dropped
+            let _ = self.release.recv();
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "timed out");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_write_returns_while_pty_blocks() {
+        let (release, blocked) = mpsc::channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+        let writer = PtyWriter::spawn(
+            BlockingWriter {
+                release: blocked,
+                written: written.clone(),
+            },
+            move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        let paste = vec![b'x'; BACKLOG_THRESHOLD + 1];
+        let started = Instant::now();
+        assert!(writer.write(&paste));
+        assert!(writer.write(b"\r"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(writer.is_backed_up());
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+
+        // Over the hard limit input is refused rather than queued
+        assert!(!writer.write(&vec![b'y'; MAX_QUEUED_BYTES]));
+
+        drop(release);
+        wait_until(|| !writer.is_backed_up());
+        wait_until(|| written.lock().unwrap().len() == paste.len() + 1);
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+        assert_eq!(written.lock().unwrap().last(), Some(&b'\r'));
+    }
+
+    #[test]
+    fn test_clear_drops_pending_input() {
+        let (release, blocked) = mpsc::channel();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let writer = PtyWriter::spawn(
+            BlockingWriter {
+                release: blocked,
+                written: written.clone(),
+            },
+            || {},
+        );
+
+        assert!(writer.write(b"first"));
+        // Let the thread pick up the first batch before queueing more
+        wait_until(|| writer.shared.queue.lock().unwrap().chunks.is_empty());
+        assert!(writer.write(&vec![b'x'; BACKLOG_THRESHOLD]));
+        assert!(writer.is_backed_up());
+
+        writer.clear();
+        assert!(!writer.is_backed_up());
+        assert!(writer.write(b"exit\r"));
+
+        drop(release);
+        wait_until(|| written.lock().unwrap().as_slice() == b"firstexit\r");
+    }
+}
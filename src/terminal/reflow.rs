@@ -0,0 +1,245 @@
+//! Grid points carried across a resize
+//!
+//! Alacritty reflows the primary screen when its width changes: a line that
+//! wrapped at the old width is rewrapped at the new one instead of being
+//! cut, so its rows move. A point is carried across as its logical line,
+//! counted back from the cursor's, and its cell within that line. The
+//! alternate screen is resized without reflow; its points are only kept
+//! within the new size.
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::term::{Term, TermMode};
+
+/// First rows of the logical lines of a grid, top to bottom; a row that
+/// ends in a wrap goes on in the next
+pub(super) struct LineStarts(Vec<i32>);
+
+impl LineStarts {
+    pub(super) fn of(grid: &Grid<Cell>) -> Self {
+        let top = -(grid.history_size() as i32);
+        let bottom = grid.screen_lines() as i32 - 1;
+        let last_column = Column(grid.columns() - 1);
+        let mut starts = vec![top];
+        starts.extend(
+            (top..bottom)
+                .filter(|&line| {
+                    !grid[Line(line)][last_column]
+                        .flags
+                        .contains(Flags::WRAPLINE)
+                })
+                .map(|line| line + 1),
+        );
+        Self(starts)
+    }
+
+    /// Logical line that `line` is part of
+    pub(super) fn index_of(&self, line: i32) -> usize {
+        self.0
+            .partition_point(|&start| start <= line)
+            .saturating_sub(1)
+    }
+
+    /// First row of logical line `index`
+    pub(super) fn start(&self, index: usize) -> i32 {
+        self.0[index]
+    }
+
+    /// Row `row` of logical line `index`, kept within the line
+    pub(super) fn row(&self, index: usize, row: i32, grid: &Grid<Cell>) -> Option<i32> {
+        let start = *self.0.get(index)?;
+        let end = self
+            .0
+            .get(index + 1)
+            .map_or(grid.screen_lines() as i32 - 1, |next| next - 1);
+        Some((start + row).min(end))
+    }
+}
+
+/// A point as its logical line, counted back from the cursor's, and its
+/// cell within the line
+#[derive(Debug, Clone, Copy)]
+struct LogicalPoint {
+    back: isize,
+    cell: usize,
+}
+
+/// Points of a grid about to be resized
+pub(super) struct CarriedPoints {
+    points: Vec<Option<LogicalPoint>>,
+    reflow: bool,
+}
+
+impl CarriedPoints {
+    /// Note where `points` (grid line, column) are in the text of `term`
+    pub(super) fn take<T: EventListener>(term: &Term<T>, points: &[Option<(i32, usize)>]) -> Self {
+        let reflow = !term.mode().contains(TermMode::ALT_SCREEN);
+        let grid = term.grid();
+        let starts = LineStarts::of(grid);
+        let cursor = starts.index_of(grid.cursor.point.line.0) as isize;
+        let columns = grid.columns();
+        let points = points
+            .iter()
+            .map(|point| {
+                point.map(|(line, column)| {
+                    if !reflow {
+                        // Kept as it is, the line in place of the count
+                        return LogicalPoint {
+                            back: line as isize,
+                            cell: column,
+                        };
+                    }
+                    let index = starts.index_of(line);
+                    let row = (line - starts.start(index)) as usize;
+                    LogicalPoint {
+                        back: cursor - index as isize,
+                        cell: row * columns + column,
+                    }
+                })
+            })
+            .collect();
+        Self { points, reflow }
+    }
+
+    /// Where the points are in the resized `term`. A point whose line went
+    /// out of the grid is None; one past the end of its shortened line is
+    /// put on the line's last cell.
+    pub(super) fn place<T: EventListener>(self, term: &Term<T>) -> Vec<Option<(i32, usize)>> {
+        let grid = term.grid();
+        let columns = grid.columns();
+        if !self.reflow {
+            let lines = grid.screen_lines() as isize;
+            return self
+                .points
+                .into_iter()
+                .map(|point| {
+                    let point = point?;
+                    (point.back < lines).then_some((point.back as i32, point.cell.min(columns - 1)))
+                })
+                .collect();
+        }
+        let starts = LineStarts::of(grid);
+        let cursor = starts.index_of(grid.cursor.point.line.0) as isize;
+        self.points
+            .into_iter()
+            .map(|point| {
+                let point = point?;
+                let index = usize::try_from(cursor - point.back).ok()?;
+                let row = (point.cell / columns) as i32;
+                let line = starts.row(index, row, grid)?;
+                if line - starts.start(index) < row {
+                    Some((line, columns - 1))
+                } else {
+                    Some((line, point.cell % columns))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::prompt_marks::PromptMarks;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn term(columns: usize, lines: usize) -> Term<VoidListener> {
+        Term::new(
+            TermConfig::default(),
+            &TermSize::new(columns, lines),
+            VoidListener,
+        )
+    }
+
+    fn write(term: &mut Term<VoidListener>, text: &str) {
+        let mut parser: Processor = Processor::new();
+        parser.advance(term, text.as_bytes());
+    }
+
+    /// Resize the way `Terminal::resize` does
+    fn resize(
+        term: &mut Term<VoidListener>,
+        columns: usize,
+        points: &[Option<(i32, usize)>],
+    ) -> Vec<Option<(i32, usize)>> {
+        let carried = CarriedPoints::take(term, points);
+        let lines = term.grid().screen_lines();
+        PromptMarks::default().resize(term, TermSize::new(columns, lines));
+        carried.place(term)
+    }
+
+    /// Text of the logical line the cursor is on, and the cursor's cell in it
+    fn cursor_line(term: &Term<VoidListener>) -> (String, usize) {
+        let grid = term.grid();
+        let starts = LineStarts::of(grid);
+        let cursor = grid.cursor.point;
+        let index = starts.index_of(cursor.line.0);
+        let start = starts.start(index);
+        let end = starts.row(index, grid.total_lines() as i32, grid).unwrap();
+        let text: String = (start..=end)
+            .flat_map(|line| (0..grid.columns()).map(move |column| (line, column)))
+            .map(|(line, column)| grid[Line(line)][Column(column)].c)
+            .collect();
+        let cell = (cursor.line.0 - start) as usize * grid.columns() + cursor.column.0;
+        (text.trim_end().to_string(), cell)
+    }
+
+    fn char_at(term: &Term<VoidListener>, (line, column): (i32, usize)) -> char {
+        term.grid()[Line(line)][Column(column)].c
+    }
+
+    fn long_line() -> String {
+        (0..300)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn test_long_line_survives_widening_and_narrowing() {
+        let mut term = term(80, 24);
+        let text = long_line();
+        write(&mut term, &text);
+        assert_eq!(cursor_line(&term), (text.clone(), 300));
+
+        resize(&mut term, 120, &[]);
+        assert_eq!(term.grid().columns(), 120);
+        assert_eq!(cursor_line(&term), (text.clone(), 300));
+
+        resize(&mut term, 80, &[]);
+        assert_eq!(cursor_line(&term), (text, 300));
+    }
+
+    #[test]
+    fn test_points_follow_their_text() {
+        let mut term = term(80, 24);
+        write(&mut term, "first\r\n");
+        write(&mut term, &long_line());
+        // Row 3 of the long line at 80 columns: its 250th cell
+        let start = (4, 10);
+        let end = (1, 2);
+        let expected = (char_at(&term, start), char_at(&term, end));
+
+        let carried = resize(&mut term, 120, &[Some(start), Some(end), None]);
+        let (Some(start), Some(end)) = (carried[0], carried[1]) else {
+            panic!("points dropped: {carried:?}");
+        };
+        assert_eq!(carried[2], None);
+        assert_eq!((char_at(&term, start), char_at(&term, end)), expected);
+        assert_eq!(start.1, 10);
+        assert_eq!(start.0 - end.0, 2);
+        assert_eq!(char_at(&term, (end.0 - 1, 0)), 'f');
+    }
+
+    #[test]
+    fn test_point_past_shortened_line_moves_to_its_end() {
+        let mut term = term(80, 24);
+        write(&mut term, "short");
+        let carried = resize(&mut term, 40, &[Some((0, 70))]);
+        assert_eq!(carried, vec![Some((0, 39))]);
+    }
+}
@@ -0,0 +1,187 @@
+//! Sizing the terminal grid to its element
+//!
+//! Every terminal view measures its own element with the font's cell
+//! metrics, so panes side by side in parallel mode each get the columns that
+//! fit them. The first size is applied at once; after that a new size is
+//! applied once it has held for `RESIZE_DEBOUNCE`, so dragging a splitter or
+//! an animating panel sends the program one SIGWINCH for the size it ends at
+//! instead of one per frame.
+
+use std::time::{Duration, Instant};
+
+/// Font size of the terminal text, in pixels
+pub(super) const FONT_SIZE: f32 = 14.0;
+/// How long a new size must hold before the PTY is resized
+pub(super) const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+/// Line height as a multiple of font size (1.4 is standard for terminal readability)
+pub(super) const LINE_HEIGHT_MULTIPLIER: f32 = 1.4;
+/// Minimum element width in pixels to perform layout (avoids freezing on tiny resize)
+const MIN_ELEMENT_WIDTH: f32 = 50.0;
+/// Minimum element height in pixels to perform layout
+const MIN_ELEMENT_HEIGHT: f32 = 40.0;
+/// Minimum terminal columns (prevents degenerate grid)
+const MIN_TERMINAL_COLS: u16 = 2;
+/// Minimum terminal lines (prevents degenerate grid)
+const MIN_TERMINAL_LINES: u16 = 2;
+
+/// Columns and lines of the terminal grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct GridSize {
+    pub cols: u16,
+    pub lines: u16,
+}
+
+/// Height of a terminal line at `font_size`
+pub(super) fn line_height(font_size: f32) -> f32 {
+    font_size * LINE_HEIGHT_MULTIPLIER
+}
+
+/// Height of a terminal row, which the layout keys step the terminal by
+pub fn row_height() -> f32 {
+    line_height(FONT_SIZE)
+}
+
+/// Grid that fits an element of `width` x `height` pixels, `padding` on each
+/// side, with cells of `cell_width` x `line_height`. None when the element
+/// is too small to lay out.
+pub(super) fn grid_size(
+    width: f32,
+    height: f32,
+    padding: f32,
+    cell_width: f32,
+    line_height: f32,
+) -> Option<GridSize> {
+    if width < MIN_ELEMENT_WIDTH || height < MIN_ELEMENT_HEIGHT {
+        return None;
+    }
+    if cell_width <= 0.0 || line_height <= 0.0 {
+        return None;
+    }
+    let available_width = (width - padding * 2.0).max(cell_width);
+    let available_height = (height - padding * 2.0).max(line_height);
+    Some(GridSize {
+        cols: ((available_width / cell_width).floor() as u16).max(MIN_TERMINAL_COLS),
+        lines: ((available_height / line_height).floor() as u16).max(MIN_TERMINAL_LINES),
+    })
+}
+
+/// Holds back grid sizes measured while the element keeps changing size
+#[derive(Debug, Default)]
+pub(super) struct ResizeDebouncer {
+    /// Size last handed to the PTY
+    applied: Option<GridSize>,
+    /// Size measured since, and when it was first measured
+    pending: Option<(GridSize, Instant)>,
+}
+
+impl ResizeDebouncer {
+    /// Record the size measured at `now`. Returns the size to apply right
+    /// away, which is only the first one; later sizes wait in `due`.
+    pub(super) fn measure(&mut self, size: GridSize, now: Instant) -> Option<GridSize> {
+        if self.applied.is_none() {
+            self.applied = Some(size);
+            return Some(size);
+        }
+        if self.applied == Some(size) {
+            self.pending = None;
+        } else if self.pending.is_none_or(|(pending, _)| pending != size) {
+            self.pending = Some((size, now));
+        }
+        None
+    }
+
+    /// The pending size, once it has held for `RESIZE_DEBOUNCE` at `now`
+    pub(super) fn due(&mut self, now: Instant) -> Option<GridSize> {
+        let (size, since) = self.pending?;
+        if now.duration_since(since) < RESIZE_DEBOUNCE {
+            return None;
+        }
+        self.pending = None;
+        self.applied = Some(size);
+        Some(size)
+    }
+
+    /// Whether a size is waiting to be applied
+    pub(super) fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cell width of a monospace font, about 0.6 of its size
+    fn cell_width(font_size: f32) -> f32 {
+        font_size * 0.6
+    }
+
+    fn grid(width: f32, height: f32, font_size: f32) -> GridSize {
+        grid_size(
+            width,
+            height,
+            8.0,
+            cell_width(font_size),
+            line_height(font_size),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_grid_size_at_font_sizes() {
+        // 800 - 16 = 784 wide, 600 - 16 = 584 high
+        let sizes: Vec<(u16, u16)> = [10.0, 14.0, 20.0]
+            .into_iter()
+            .map(|font| grid(800.0, 600.0, font))
+            .map(|g| (g.cols, g.lines))
+            .collect();
+        assert_eq!(sizes, vec![(130, 41), (93, 29), (65, 20)]);
+    }
+
+    #[test]
+    fn test_grid_size_limits() {
+        assert_eq!(grid_size(40.0, 600.0, 8.0, 8.0, 16.0), None);
+        assert_eq!(grid_size(800.0, 30.0, 8.0, 8.0, 16.0), None);
+        assert_eq!(grid_size(800.0, 600.0, 8.0, 0.0, 16.0), None);
+        assert_eq!(
+            grid_size(50.0, 40.0, 8.0, 30.0, 30.0),
+            Some(GridSize { cols: 2, lines: 2 })
+        );
+    }
+
+    #[test]
+    fn test_parallel_panes_get_their_own_cols() {
+        let now = Instant::now();
+        let (mut left, mut right) = (ResizeDebouncer::default(), ResizeDebouncer::default());
+        let left_size = left.measure(grid(900.0, 600.0, 14.0), now).unwrap();
+        let right_size = right.measure(grid(500.0, 600.0, 14.0), now).unwrap();
+        assert_eq!(left_size.lines, right_size.lines);
+        assert!(left_size.cols > right_size.cols);
+    }
+
+    #[test]
+    fn test_resizes_wait_until_the_size_holds() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let size = |cols| GridSize { cols, lines: 24 };
+        let mut debouncer = ResizeDebouncer::default();
+        assert_eq!(debouncer.measure(size(80), start), Some(size(80)));
+
+        // A drag through several widths
+        for (t, cols) in [(16, 79), (32, 75), (48, 70), (64, 66)] {
+            assert_eq!(debouncer.measure(size(cols), ms(t)), None);
+            assert_eq!(debouncer.due(ms(t)), None);
+        }
+        assert!(debouncer.is_pending());
+        assert_eq!(debouncer.due(ms(150)), None);
+        // The size the drag ended at is applied once, exactly
+        assert_eq!(debouncer.due(ms(164)), Some(size(66)));
+        assert_eq!(debouncer.due(ms(400)), None);
+        assert!(!debouncer.is_pending());
+
+        // Going back to the applied size cancels the pending one
+        debouncer.measure(size(70), ms(500));
+        debouncer.measure(size(66), ms(510));
+        assert_eq!(debouncer.due(ms(700)), None);
+    }
+}
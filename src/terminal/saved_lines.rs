@@ -0,0 +1,237 @@
+//! A grid's scrollback as the logical lines `crate::scrollback` saves, and
+//! saved lines put back into the grid of a new terminal
+//!
+//! A line that wrapped is saved whole, so it is rewrapped at whatever width
+//! the new terminal has. With colors, each change of style within a line is
+//! written as an SGR sequence that starts from a reset.
+
+use super::glance::row_text;
+use crate::scrollback;
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::{Dimensions, Grid};
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::term::Term;
+use alacritty_terminal::term::cell::{Cell, Flags};
+use alacritty_terminal::vte::ansi::{Color, NamedColor, Processor};
+use std::fmt::Write;
+
+/// Attributes written as SGR parameters, with theirs
+const SGR_FLAGS: [(Flags, u8); 7] = [
+    (Flags::BOLD, 1),
+    (Flags::DIM, 2),
+    (Flags::ITALIC, 3),
+    (Flags::ALL_UNDERLINES, 4),
+    (Flags::INVERSE, 7),
+    (Flags::HIDDEN, 8),
+    (Flags::STRIKEOUT, 9),
+];
+
+/// Logical lines of `grid`, scrollback first, up to the last row with text
+pub(super) fn capture(grid: &Grid<Cell>, colors: bool) -> Vec<String> {
+    let top = grid.topmost_line().0;
+    let Some(bottom) = (top..grid.screen_lines() as i32)
+        .rev()
+        .find(|&line| !row_text(grid, line).is_empty())
+    else {
+        return Vec::new();
+    };
+    let last_column = Column(grid.columns() - 1);
+    let mut lines = Vec::new();
+    let mut line = LineText::new(colors);
+    for row in top..=bottom {
+        let cells = &grid[Line(row)];
+        for column in 0..grid.columns() {
+            line.push(&cells[Column(column)]);
+        }
+        if row == bottom || !cells[last_column].flags.contains(Flags::WRAPLINE) {
+            lines.push(std::mem::replace(&mut line, LineText::new(colors)).finish());
+        }
+    }
+    lines
+}
+
+/// Put `lines` into the new `term` as output it printed before its shell
+/// started, followed by the divider
+pub(super) fn preload<T: EventListener>(term: &mut Term<T>, lines: &[String]) {
+    let mut parser: Processor = Processor::new();
+    parser.advance(term, &scrollback::restored_output(lines));
+}
+
+/// Style of a cell as far as it is saved
+#[derive(Clone, Copy, PartialEq)]
+struct Style {
+    fg: Color,
+    bg: Color,
+    flags: Flags,
+}
+
+impl Style {
+    const DEFAULT: Self = Self {
+        fg: Color::Named(NamedColor::Foreground),
+        bg: Color::Named(NamedColor::Background),
+        flags: Flags::empty(),
+    };
+
+    fn of(cell: &Cell) -> Self {
+        let mut flags = Flags::empty();
+        for (flag, _) in SGR_FLAGS {
+            flags |= cell.flags & flag;
+        }
+        Self {
+            fg: cell.fg,
+            bg: cell.bg,
+            flags,
+        }
+    }
+
+    fn write_sgr(&self, text: &mut String) {
+        text.push_str("\x1b[0");
+        for (flag, parameter) in SGR_FLAGS {
+            if self.flags.intersects(flag) {
+                let _ = write!(text, ";{}", parameter);
+            }
+        }
+        write_color(text, self.fg, 30);
+        write_color(text, self.bg, 40);
+        text.push('m');
+    }
+}
+
+/// SGR parameters of `color` as a foreground (`base` 30) or background (40)
+fn write_color(text: &mut String, color: Color, base: u8) {
+    let _ = match color {
+        Color::Named(named) if (named as usize) < 8 => write!(text, ";{}", base + named as u8),
+        Color::Named(named) if (named as usize) < 16 => {
+            write!(text, ";{}", base + 60 + named as u8 - 8)
+        }
+        // The theme's own colors
+        Color::Named(_) => Ok(()),
+        Color::Indexed(index) => write!(text, ";{};5;{}", base + 8, index),
+        Color::Spec(rgb) => write!(text, ";{};2;{};{};{}", base + 8, rgb.r, rgb.g, rgb.b),
+    };
+}
+
+/// A logical line being read off the grid
+struct LineText {
+    text: String,
+    /// Style last written (None without colors)
+    style: Option<Style>,
+    /// Length of `text` up to the last cell that shows something
+    end: usize,
+}
+
+impl LineText {
+    fn new(colors: bool) -> Self {
+        Self {
+            text: String::new(),
+            style: colors.then_some(Style::DEFAULT),
+            end: 0,
+        }
+    }
+
+    fn push(&mut self, cell: &Cell) {
+        if cell
+            .flags
+            .intersects(Flags::WIDE_CHAR_SPACER | Flags::LEADING_WIDE_CHAR_SPACER)
+        {
+            return;
+        }
+        let c = if cell.c == '\t' { ' ' } else { cell.c };
+        let mut shows = !c.is_whitespace();
+        if let Some(style) = self.style {
+            let current = Style::of(cell);
+            if current != style {
+                current.write_sgr(&mut self.text);
+                self.style = Some(current);
+            }
+            shows |= current.bg != Style::DEFAULT.bg || current.flags.contains(Flags::INVERSE);
+        }
+        self.text.push(c);
+        if let Some(zerowidth) = cell.zerowidth() {
+            self.text.extend(zerowidth);
+        }
+        if shows {
+            self.end = self.text.len();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.text.truncate(self.end);
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+
+    fn term(columns: usize, lines: usize) -> Term<VoidListener> {
+        Term::new(
+            TermConfig::default(),
+            &TermSize::new(columns, lines),
+            VoidListener,
+        )
+    }
+
+    fn write(term: &mut Term<VoidListener>, text: &str) {
+        let mut parser: Processor = Processor::new();
+        parser.advance(term, text.as_bytes());
+    }
+
+    fn rows(term: &Term<VoidListener>) -> Vec<String> {
+        let grid = term.grid();
+        (grid.topmost_line().0..grid.screen_lines() as i32)
+            .map(|line| row_text(grid, line))
+            .collect()
+    }
+
+    #[test]
+    fn test_lines_come_back_above_the_divider() {
+        // The wide text wraps after 4 characters, with a spacer at the end
+        // of its first row
+        let mut before = term(9, 3);
+        write(&mut before, "one\r\n日本語のテキスト\r\nthree");
+        let lines = capture(before.grid(), false);
+        assert_eq!(lines, vec!["one", "日本語のテキスト", "three"]);
+
+        let mut after = term(30, 4);
+        preload(&mut after, &lines);
+        assert_eq!(after.grid().cursor.point.line.0, 3);
+        assert_eq!(after.grid().cursor.point.column.0, 0);
+        write(&mut after, "$ ");
+        assert_eq!(after.grid().history_size(), 1);
+        assert_eq!(
+            rows(&after),
+            vec!["one", "日本語のテキスト", "three", scrollback::DIVIDER, "$"]
+        );
+
+        // Saved again on the next quit, the divider is just another line
+        assert_eq!(
+            capture(after.grid(), false),
+            vec!["one", "日本語のテキスト", "three", scrollback::DIVIDER, "$"]
+        );
+    }
+
+    #[test]
+    fn test_colors_are_saved_as_sgr() {
+        let mut before = term(20, 3);
+        write(
+            &mut before,
+            "\x1b[1;31mred\x1b[0m 日本\x1b[48;5;22m  \x1b[0m  ",
+        );
+        let lines = capture(before.grid(), true);
+        // Trailing blanks go unless they have a background
+        assert_eq!(lines, vec!["\x1b[0;1;31mred\x1b[0m 日本\x1b[0;48;5;22m  "]);
+
+        let mut after = term(30, 3);
+        preload(&mut after, &lines);
+        let cell = &after.grid()[Line(0)][Column(0)];
+        assert_eq!(cell.fg, Color::Named(NamedColor::Red));
+        assert!(cell.flags.contains(Flags::BOLD));
+        assert_eq!(after.grid()[Line(0)][Column(8)].bg, Color::Indexed(22));
+        assert_eq!(capture(after.grid(), true)[0], lines[0]);
+    }
+}
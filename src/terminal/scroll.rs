@@ -0,0 +1,65 @@
+//! Pixel-precise scrolling for the terminal
+//!
+//! Wheels report whole notches, touchpads a stream of small pixel deltas.
+//! The terminal can only scroll whole lines, so deltas are accumulated in
+//! pixels and the part of a line left over is carried into the next event.
+//! Delta signs already follow the platform's "natural scrolling" setting.
+
+/// Pixels scrolled but not yet turned into whole lines
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(super) struct ScrollAccumulator {
+    remainder: f32,
+}
+
+impl ScrollAccumulator {
+    /// Add `pixels` and return the whole lines of `line_height` they complete
+    /// (positive = up). Reversing direction drops the remainder, so a flick
+    /// back never has to undo a partial line first.
+    pub(super) fn add(&mut self, pixels: f32, line_height: f32) -> i32 {
+        if pixels == 0.0 || line_height <= 0.0 {
+            return 0;
+        }
+        if self.remainder != 0.0 && self.remainder.signum() != pixels.signum() {
+            self.remainder = 0.0;
+        }
+        self.remainder += pixels;
+        let lines = (self.remainder / line_height).trunc();
+        self.remainder -= lines * line_height;
+        lines as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_deltas_sum_to_a_line() {
+        let mut acc = ScrollAccumulator::default();
+        let lines: i32 = (0..8).map(|_| acc.add(2.5, 20.0)).sum();
+        assert_eq!(lines, 1);
+
+        // The fraction is carried, not dropped
+        let mut acc = ScrollAccumulator::default();
+        assert_eq!(acc.add(30.0, 20.0), 1);
+        assert_eq!(acc.add(10.0, 20.0), 1);
+        assert_eq!(acc.add(-45.0, 20.0), -2);
+    }
+
+    #[test]
+    fn test_direction_reversal_resets_remainder() {
+        let mut acc = ScrollAccumulator::default();
+        assert_eq!(acc.add(15.0, 20.0), 0);
+        assert_eq!(acc.add(-15.0, 20.0), 0);
+        assert_eq!(acc.add(-5.0, 20.0), -1);
+        assert_eq!(acc.add(19.0, 20.0), 0);
+    }
+
+    #[test]
+    fn test_wheel_notches_scroll_whole_lines() {
+        let mut acc = ScrollAccumulator::default();
+        assert_eq!(acc.add(3.0 * 16.0, 16.0), 3);
+        assert_eq!(acc.add(-3.0 * 16.0, 16.0), -3);
+        assert_eq!(acc.add(50.0, 0.0), 0);
+    }
+}
@@ -0,0 +1,307 @@
+//! PTY wrapper that tees the shell's output into a session transcript
+//!
+//! The event loop reads the PTY on its own thread; reading through a
+//! duplicate of the master fd wrapped in a `TeeReader` hands every chunk to
+//! the `TranscriptRecorder` on that same thread, and a `CommandScanReader`
+//! picks the commands the shell reports out of it. Polling, resizing and
+//! child events go to the wrapped `Pty` unchanged.
+//!
+//! Shell integration markers are placed by a `MarkReader` on top. The event
+//! loop parses whatever a read returned before it reads again, but keeps
+//! the terminal locked while it goes on reading, so a read ends at each
+//! marker, the output after it is held back, and the marker is placed at
+//! the start of a later read, once the loop has parsed up to it and let go
+//! of the terminal. A socket registered next to the PTY wakes the loop
+//! while output is held back.
+
+use super::TerminalEventListener;
+use super::prompt_marks::PromptMarks;
+use crate::command_history::{CommandScanReader, ReportedCommands, ShellMark};
+use crate::transcript::{SessionTranscript, TeeReader, TranscriptRecorder};
+use alacritty_terminal::event::{Event as AlacEvent, EventListener, OnResize, WindowSize};
+use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::term::Term;
+use alacritty_terminal::tty::{ChildEvent, EventedPty, EventedReadWrite, Pty};
+use polling::{Event, PollMode, Poller};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+
+/// Where the PTY reader places the shell's markers
+pub struct MarkTarget {
+    pub term: Arc<FairMutex<Term<TerminalEventListener>>>,
+    pub marks: Arc<Mutex<PromptMarks>>,
+    /// Told when a mark was placed, so the view redraws its gutter
+    pub listener: TerminalEventListener,
+}
+
+pub struct TeePty {
+    pty: Pty,
+    reader: MarkReader<TeeReader<File>>,
+}
+
+impl TeePty {
+    pub fn new(
+        mut pty: Pty,
+        transcript: Arc<SessionTranscript>,
+        commands: Arc<ReportedCommands>,
+        target: MarkTarget,
+    ) -> io::Result<Self> {
+        // The duplicate shares the non-blocking file description with the Pty
+        let reader = pty.reader().try_clone()?;
+        let tee = TeeReader::new(reader, TranscriptRecorder::new(transcript));
+        let scan = CommandScanReader::new(tee, commands);
+        Ok(Self {
+            pty,
+            reader: MarkReader::new(scan, target)?,
+        })
+    }
+}
+
+impl EventedReadWrite for TeePty {
+    type Reader = MarkReader<TeeReader<File>>;
+    type Writer = File;
+
+    unsafe fn register(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        // SAFETY: forwarded as is; the Pty owns the registered fds
+        unsafe { self.pty.register(poll, interest, mode)? };
+        // SAFETY: the wake socket lives in the reader as long as the Pty and
+        // is deleted from the poller in `deregister`. It is registered under
+        // the key the event loop reads the PTY by.
+        unsafe { poll.add_with_mode(&self.reader.wake_rx, Event::readable(interest.key), mode) }
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &Arc<Poller>,
+        interest: Event,
+        mode: PollMode,
+    ) -> io::Result<()> {
+        self.pty.reregister(poll, interest, mode)
+    }
+
+    fn deregister(&mut self, poll: &Arc<Poller>) -> io::Result<()> {
+        let _ = poll.delete(&self.reader.wake_rx);
+        self.pty.deregister(poll)
+    }
+
+    fn reader(&mut self) -> &mut Self::Reader {
+        &mut self.reader
+    }
+
+    fn writer(&mut self) -> &mut Self::Writer {
+        self.pty.writer()
+    }
+}
+
+impl EventedPty for TeePty {
+    fn next_child_event(&mut self) -> Option<ChildEvent> {
+        self.pty.next_child_event()
+    }
+}
+
+impl OnResize for TeePty {
+    fn on_resize(&mut self, window_size: WindowSize) {
+        self.pty.on_resize(window_size);
+    }
+}
+
+/// Reader that ends each read at a shell integration marker and places the
+/// marker once the event loop parsed up to it
+pub struct MarkReader<R> {
+    inner: CommandScanReader<R>,
+    target: MarkTarget,
+    /// Output read past a marker, handed out after the marker is placed
+    held: Vec<u8>,
+    /// Markers in `held`, by the offset just past each
+    held_marks: VecDeque<(usize, ShellMark)>,
+    /// Marker that ended the last read and the room the event loop had left
+    /// in its buffer after it
+    unplaced: Option<(ShellMark, usize)>,
+    /// Readable while output is held back, so the event loop comes back
+    wake_rx: UnixStream,
+    wake_tx: UnixStream,
+}
+
+impl<R: Read> MarkReader<R> {
+    fn new(inner: CommandScanReader<R>, target: MarkTarget) -> io::Result<Self> {
+        let (wake_rx, wake_tx) = UnixStream::pair()?;
+        wake_rx.set_nonblocking(true)?;
+        wake_tx.set_nonblocking(true)?;
+        Ok(Self {
+            inner,
+            target,
+            held: Vec::new(),
+            held_marks: VecDeque::new(),
+            unplaced: None,
+            wake_rx,
+            wake_tx,
+        })
+    }
+
+    /// Place the marker that ended the last read if the event loop has
+    /// parsed it; false while it has to wait
+    fn place_unplaced(&mut self, room: usize) -> bool {
+        let Some((kind, room_left)) = self.unplaced else {
+            return true;
+        };
+        // The event loop reads into the rest of its buffer until it parses
+        // what it has, so being offered more room means the marker is parsed
+        if room <= room_left {
+            return false;
+        }
+        // After parsing, the loop keeps the terminal locked while it reads on;
+        // the wake socket brings it back once it let go
+        let Some(term) = self.target.term.try_lock_unfair() else {
+            self.wake();
+            return false;
+        };
+        self.target
+            .marks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .place(kind, &term);
+        drop(term);
+        self.unplaced = None;
+        self.target.listener.send_event(AlacEvent::Wakeup);
+        true
+    }
+
+    /// Have the event loop read again even if the PTY has nothing new
+    fn wake(&self) {
+        let _ = (&self.wake_tx).write(&[0]);
+    }
+
+    /// Hand out held output up to the next marker
+    fn read_held(&mut self, buf: &mut [u8]) -> usize {
+        let until = self
+            .held_marks
+            .front()
+            .map_or(self.held.len(), |&(end, _)| end);
+        let n = until.min(buf.len());
+        buf[..n].copy_from_slice(&self.held[..n]);
+        self.held.drain(..n);
+        for (end, _) in &mut self.held_marks {
+            *end -= n;
+        }
+        if let Some(&(0, kind)) = self.held_marks.front() {
+            self.held_marks.pop_front();
+            self.unplaced = Some((kind, buf.len() - n));
+            self.wake();
+        }
+        n
+    }
+}
+
+impl<R: Read> Read for MarkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut sink = [0u8; 64];
+        while matches!((&self.wake_rx).read(&mut sink), Ok(n) if n > 0) {}
+
+        if !self.place_unplaced(buf.len()) {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        if !self.held.is_empty() {
+            return Ok(self.read_held(buf));
+        }
+
+        let n = self.inner.read(buf)?;
+        let Some((&(first, kind), rest)) = self.inner.last_marks().split_first() else {
+            return Ok(n);
+        };
+        self.held.extend_from_slice(&buf[first..n]);
+        self.held_marks
+            .extend(rest.iter().map(|&(end, kind)| (end - first, kind)));
+        self.unplaced = Some((kind, buf.len() - first));
+        // The loop may stop reading once it parsed this (it caps how much it
+        // parses in one go), with the PTY already drained
+        self.wake();
+        Ok(first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::TitleState;
+    use crate::terminal::prompt_marks::PromptMark;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::Processor;
+
+    fn would_block(result: io::Result<usize>) -> bool {
+        matches!(result, Err(e) if e.kind() == io::ErrorKind::WouldBlock)
+    }
+
+    #[test]
+    fn test_marks_are_placed_where_the_loop_parsed_them() {
+        let (sender, _receiver) = smol::channel::bounded(100);
+        let listener = TerminalEventListener {
+            sender,
+            title: Arc::new(TitleState::default()),
+        };
+        let term = Term::new(
+            TermConfig::default(),
+            &TermSize::new(20, 5),
+            listener.clone(),
+        );
+        let term = Arc::new(FairMutex::new(term));
+        let marks = Arc::new(Mutex::new(PromptMarks::default()));
+        let output = &b"out\r\n\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07"[..];
+        let scan = CommandScanReader::new(output, Arc::new(ReportedCommands::default()));
+        let target = MarkTarget {
+            term: term.clone(),
+            marks: marks.clone(),
+            listener,
+        };
+        let mut reader = MarkReader::new(scan, target).unwrap();
+        let mut parser: Processor = Processor::new();
+        let mut buf = [0u8; 256];
+
+        // The read ends at the prompt marker
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"out\r\n\x1b]133;A\x07");
+        // Nothing more until the loop parsed it (it offers the rest of its
+        // buffer until then)
+        assert!(would_block(reader.read(&mut buf[n..])));
+        parser.advance(&mut *term.lock(), &buf[..n]);
+
+        // Still locked by the loop after parsing: wait for it to let go
+        let locked = term.lock();
+        assert!(would_block(reader.read(&mut buf)));
+        drop(locked);
+
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"$ \x1b]133;B\x07");
+        parser.advance(&mut *term.lock(), &buf[..n]);
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"ls\r\n\x1b]133;C\x07");
+        parser.advance(&mut *term.lock(), &buf[..n]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        assert_eq!(
+            marks.lock().unwrap().marks(),
+            &[
+                PromptMark {
+                    kind: ShellMark::PromptStart,
+                    line: 1
+                },
+                PromptMark {
+                    kind: ShellMark::CommandStart,
+                    line: 1
+                },
+                PromptMark {
+                    kind: ShellMark::OutputStart,
+                    line: 2
+                },
+            ]
+        );
+    }
+}
@@ -2,24 +2,45 @@
 //!
 //! This module provides the main TerminalView struct and its implementation.
 
-use super::Terminal;
+use super::code_scan::FoundBlocks;
+use super::copy_mode::{self, CopyCommand, CopyMode};
+use super::filter::{FilterEntry, TerminalFilter};
+use super::follow::Follow;
+use super::glance;
+use super::keybindings::ApplyCodeBlock;
+use super::resize::{GridSize, RESIZE_DEBOUNCE, ResizeDebouncer};
+use super::scroll::ScrollAccumulator;
+use super::{Terminal, TerminalEvent, TerminalTail};
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::attention::{AttentionReason, SessionAttention, SilenceTracker};
+use crate::bell::{self, BELL_FLASH_DURATION, BellLimiter};
+use crate::busy::TerminalActivity;
+use crate::code_blocks::CodeBlock;
+use crate::command_history::TerminalHistory;
+use crate::multiplexer;
+use crate::scrollback::TerminalScrollback;
 use crate::terminal::element::{
-    CellData, DEFAULT_CELL_HEIGHT, DEFAULT_CELL_WIDTH, MULTI_CLICK_THRESHOLD_MS,
-    SCROLL_LINES_WHEEL, TERMINAL_PADDING, TerminalElement, TerminalLayout,
+    CellData, DEFAULT_CELL_HEIGHT, DEFAULT_CELL_WIDTH, MULTI_CLICK_THRESHOLD_MS, PreeditLayout,
+    SCROLL_LINES_WHEEL, TERMINAL_PADDING, TerminalElement, TerminalLayout, preedit_columns,
 };
 use crate::theme::{self, *};
+use crate::transcript::SessionTranscript;
+use alacritty_terminal::event::EventListener;
 use alacritty_terminal::grid::Dimensions;
 use alacritty_terminal::index::{Column, Line, Point as AlacPoint};
-use alacritty_terminal::term::cell::Flags as CellFlags;
+use alacritty_terminal::term::cell::{Cell, Flags as CellFlags};
+use alacritty_terminal::term::{Config as TermConfig, Term, TermMode};
 use alacritty_terminal::vte::ansi::{Color as AnsiColor, NamedColor};
 use gpui::prelude::FluentBuilder;
 use gpui::{
-    App, AsyncApp, Bounds, Context, EntityInputHandler, FocusHandle, Focusable, Hsla,
-    InteractiveElement, IntoElement, MouseButton, MouseMoveEvent, ParentElement, Pixels, Render,
-    ScrollWheelEvent, Styled, UTF16Selection, WeakEntity, Window, div, rgb,
+    App, AsyncApp, Bounds, ClipboardItem, Context, EntityInputHandler, FocusHandle, Focusable,
+    Hsla, InteractiveElement, IntoElement, KeyDownEvent, Keystroke, MouseButton, MouseMoveEvent,
+    ParentElement, Pixels, Render, ScrollWheelEvent, Styled, UTF16Selection, WeakEntity, Window,
+    div, point, px, relative, rgb, size,
 };
 use regex::Regex;
-use std::ops::Range;
+use std::ops::{Range, RangeInclusive};
+use std::rc::Rc;
 use std::sync::{Arc, LazyLock};
 use std::time::Instant;
 
@@ -56,14 +77,72 @@ impl DetectedUrl {
 
 /// Cached cell data from terminal grid.
 /// Copied from alacritty_terminal to ensure consistent state during rendering.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 struct CachedCell {
     c: char,
+    /// Combining marks and joiners drawn with `c` (empty for nearly all cells,
+    /// so nothing is allocated)
+    zerowidth: Vec<char>,
     fg: AnsiColor,
     bg: AnsiColor,
     flags: CellFlags,
 }
 
+impl CachedCell {
+    /// Whether this is still a copy of `cell` (compared without copying it)
+    fn matches(&self, cell: &Cell) -> bool {
+        self.c == cell.c
+            && self.fg == cell.fg
+            && self.bg == cell.bg
+            && self.flags == cell.flags
+            && self.zerowidth.as_slice() == cell.zerowidth().unwrap_or_default()
+    }
+}
+
+impl From<&Cell> for CachedCell {
+    fn from(cell: &Cell) -> Self {
+        Self {
+            c: cell.c,
+            zerowidth: cell.zerowidth().map(<[char]>::to_vec).unwrap_or_default(),
+            fg: cell.fg,
+            bg: cell.bg,
+            flags: cell.flags,
+        }
+    }
+}
+
+/// A grid cell as a selection reads it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct CellText<'a> {
+    /// None for the spacer right of a wide character, and for the one left
+    /// at the end of a line when a wide character did not fit
+    c: Option<char>,
+    zerowidth: &'a [char],
+    /// Left half of a wide character; its spacer follows
+    wide: bool,
+}
+
+impl<'a> CellText<'a> {
+    fn of(cell: &'a Cell) -> Self {
+        if cell
+            .flags
+            .intersects(CellFlags::WIDE_CHAR_SPACER | CellFlags::LEADING_WIDE_CHAR_SPACER)
+        {
+            return Self::default();
+        }
+        Self {
+            c: Some(if cell.c == '\0' { ' ' } else { cell.c }),
+            zerowidth: cell.zerowidth().unwrap_or_default(),
+            wide: cell.flags.contains(CellFlags::WIDE_CHAR),
+        }
+    }
+
+    fn push_to(&self, text: &mut String) {
+        text.extend(self.c);
+        text.extend(self.zerowidth);
+    }
+}
+
 /// Cached terminal content snapshot.
 /// Similar to Zed's TerminalContent, this captures the entire terminal state
 /// at a specific point in time to prevent rendering intermediate states.
@@ -71,14 +150,318 @@ struct CachedCell {
 struct CachedContent {
     /// Grid of cells (rows x cols)
     cells: Vec<Vec<CachedCell>>,
+    /// Revision of each row, bumped whenever the row is recopied
+    revisions: Vec<u64>,
+    next_revision: u64,
     /// Cursor position (line, column)
     cursor: (i32, usize),
     /// Whether cursor should be visible (SHOW_CURSOR mode)
     cursor_visible: bool,
     /// Display offset for scrollback
     display_offset: i32,
+    /// Lines of scrollback above the screen
+    history_size: usize,
     /// Number of lines
     lines: usize,
+    /// Number of columns
+    cols: usize,
+}
+
+impl CachedContent {
+    /// Snapshot of the visible screen of `term`
+    fn capture<T: EventListener>(term: &Term<T>) -> Self {
+        let mut content = Self {
+            cells: Vec::new(),
+            revisions: Vec::new(),
+            next_revision: 0,
+            cursor: (0, 0),
+            cursor_visible: false,
+            display_offset: 0,
+            history_size: 0,
+            lines: 0,
+            cols: 0,
+        };
+        content.update(term);
+        content
+    }
+
+    /// Bring the snapshot up to date with `term`, recopying only the rows
+    /// whose cells changed. A resize or scroll recopies every row. Returns
+    /// the number of rows copied.
+    fn update<T: EventListener>(&mut self, term: &Term<T>) -> usize {
+        let render_content = term.renderable_content();
+        let cursor_point = render_content.cursor.point;
+        let display_offset = render_content.display_offset as i32;
+
+        let grid = term.grid();
+        let cols = grid.columns();
+        let lines = grid.screen_lines();
+        let invalidate =
+            lines != self.lines || cols != self.cols || display_offset != self.display_offset;
+        if invalidate {
+            self.cells.resize_with(lines, Vec::new);
+            self.revisions.resize(lines, 0);
+        }
+
+        let mut copied = 0;
+        for (line_idx, cached_row) in self.cells.iter_mut().enumerate() {
+            let row = &grid[Line(line_idx as i32 - display_offset)];
+            let unchanged =
+                !invalidate && (0..cols).all(|col| cached_row[col].matches(&row[Column(col)]));
+            if unchanged {
+                continue;
+            }
+            cached_row.clear();
+            cached_row.extend((0..cols).map(|col| CachedCell::from(&row[Column(col)])));
+            self.revisions[line_idx] = self.next_revision;
+            self.next_revision += 1;
+            copied += 1;
+        }
+
+        self.cursor = (cursor_point.line.0, cursor_point.column.0);
+        self.cursor_visible = term.mode().contains(TermMode::SHOW_CURSOR);
+        self.display_offset = display_offset;
+        self.history_size = grid.history_size();
+        self.lines = lines;
+        self.cols = cols;
+        copied
+    }
+
+    /// Column of the character shown at display `line`, `col`: the right
+    /// half of a wide character counts as the character
+    fn char_column(&self, line: i32, col: usize) -> usize {
+        let on_spacer = usize::try_from(line)
+            .ok()
+            .and_then(|line| self.cells.get(line))
+            .and_then(|row| row.get(col))
+            .is_some_and(|cell| cell.flags.contains(CellFlags::WIDE_CHAR_SPACER));
+        if on_spacer {
+            col.saturating_sub(1)
+        } else {
+            col
+        }
+    }
+}
+
+/// Everything besides its cells that the layout of a row depends on; the
+/// row is rebuilt when any of it changes
+#[derive(Clone, Copy, PartialEq)]
+struct RowKey {
+    revision: u64,
+    actual_line: i32,
+    cursor_col: Option<usize>,
+    /// The selection, when it covers part of the row
+    selection: Option<TerminalSelection>,
+    /// Start and end of the hovered URL, when it is on the row
+    hovered_url: Option<((usize, usize), (usize, usize))>,
+}
+
+/// State outside the grid that row layouts are built from
+struct RowContext<'a> {
+    display_offset: i32,
+    /// Display line and column of the cursor, when it is shown
+    cursor: Option<(i32, usize)>,
+    /// Non-empty selection
+    selection: Option<TerminalSelection>,
+    urls: &'a [DetectedUrl],
+    hovered_url_index: Option<usize>,
+}
+
+impl RowContext<'_> {
+    fn key(&self, cached: &CachedContent, line_idx: usize) -> RowKey {
+        let actual_line = line_idx as i32 - self.display_offset;
+        RowKey {
+            revision: cached.revisions[line_idx],
+            actual_line,
+            cursor_col: self
+                .cursor
+                .filter(|(line, _)| *line == line_idx as i32)
+                .map(|(_, col)| col),
+            selection: self.selection.filter(|sel| {
+                let (start_line, _, end_line, _) = sel.normalized();
+                (start_line..=end_line).contains(&actual_line)
+            }),
+            hovered_url: self
+                .hovered_url_index
+                .and_then(|i| self.urls.get(i))
+                .filter(|url| (url.start.0..=url.end.0).contains(&line_idx))
+                .map(|url| (url.start, url.end)),
+        }
+    }
+
+    /// Layout of one display line
+    fn build_row(&self, cached_row: &[CachedCell], line_idx: usize) -> Vec<CellData> {
+        let actual_line = line_idx as i32 - self.display_offset;
+        let cursor_col = self
+            .cursor
+            .filter(|(line, _)| *line == line_idx as i32)
+            .map(|(_, col)| col);
+
+        let mut row_cells: Vec<CellData> = Vec::with_capacity(cached_row.len());
+
+        for (col_idx, cached_cell) in cached_row.iter().enumerate() {
+            let is_inverse = cached_cell.flags.contains(CellFlags::INVERSE);
+
+            // Swap fg/bg when INVERSE flag is set (used by TUI apps for software cursors)
+            let (fg, bg) = if is_inverse {
+                let fg = if cached_cell.bg == AnsiColor::Named(NamedColor::Background) {
+                    TerminalView::named_color_to_hsla(NamedColor::Background)
+                } else {
+                    TerminalView::ansi_color_to_hsla(cached_cell.bg)
+                };
+                let bg = Some(TerminalView::ansi_color_to_hsla(cached_cell.fg));
+                (fg, bg)
+            } else {
+                let fg = TerminalView::ansi_color_to_hsla(cached_cell.fg);
+                let bg = if cached_cell.bg == AnsiColor::Named(NamedColor::Background) {
+                    None
+                } else {
+                    Some(TerminalView::ansi_color_to_hsla(cached_cell.bg))
+                };
+                (fg, bg)
+            };
+
+            let is_wide_char = cached_cell.flags.contains(CellFlags::WIDE_CHAR);
+            let is_wide_spacer = cached_cell.flags.contains(CellFlags::WIDE_CHAR_SPACER);
+            // The other half of a wide character: both halves are drawn as
+            // one, so the cursor or selection on either shows on both
+            let partner = if is_wide_char {
+                Some(col_idx + 1)
+            } else if is_wide_spacer {
+                col_idx.checked_sub(1)
+            } else {
+                None
+            };
+            let on_either =
+                |col: Option<usize>| col == Some(col_idx) || (col.is_some() && col == partner);
+
+            let is_cursor = on_either(cursor_col);
+            let is_selected = self.selection.is_some_and(|sel| {
+                sel.contains(actual_line, col_idx)
+                    || partner.is_some_and(|col| sel.contains(actual_line, col))
+            });
+
+            // A spacer left where a wide character did not fit shows blank
+            let c = if cached_cell.c == '\0'
+                || cached_cell
+                    .flags
+                    .contains(CellFlags::LEADING_WIDE_CHAR_SPACER)
+            {
+                ' '
+            } else {
+                cached_cell.c
+            };
+
+            // Check if this cell is part of a detected URL
+            let mut is_url = false;
+            let mut is_url_hovered = false;
+            for (url_idx, url) in self.urls.iter().enumerate() {
+                if url.contains_point(line_idx, col_idx) {
+                    is_url = true;
+                    is_url_hovered = self.hovered_url_index == Some(url_idx);
+                    break;
+                }
+            }
+
+            row_cells.push(CellData {
+                c,
+                zerowidth: cached_cell.zerowidth.clone(),
+                fg,
+                bg,
+                is_cursor,
+                is_selected,
+                is_wide_char,
+                is_wide_spacer,
+                is_url,
+                is_url_hovered,
+            });
+        }
+
+        row_cells
+    }
+}
+
+/// Row layouts of the last frame, reused while their `RowKey` is unchanged
+#[derive(Default)]
+struct RowLayoutCache {
+    rows: Vec<Option<(RowKey, Rc<Vec<CellData>>)>>,
+}
+
+impl RowLayoutCache {
+    /// Layout of every display line; also returns how many rows were built
+    fn rows(
+        &mut self,
+        cached: &CachedContent,
+        context: &RowContext,
+    ) -> (Vec<Rc<Vec<CellData>>>, usize) {
+        self.rows.resize(cached.lines, None);
+        let mut built = 0;
+        let rows = cached
+            .cells
+            .iter()
+            .zip(self.rows.iter_mut())
+            .enumerate()
+            .map(|(line_idx, (cached_row, slot))| {
+                let key = context.key(cached, line_idx);
+                match slot {
+                    Some((slot_key, row)) if *slot_key == key => row.clone(),
+                    _ => {
+                        built += 1;
+                        let row = Rc::new(context.build_row(cached_row, line_idx));
+                        *slot = Some((key, row.clone()));
+                        row
+                    }
+                }
+            })
+            .collect();
+        (rows, built)
+    }
+}
+
+/// Shape of a terminal selection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SelectionKind {
+    /// Text flow from start to end, wrapping at line ends
+    #[default]
+    Linear,
+    /// Rectangle spanned by start and end (Alt+drag)
+    Block,
+}
+
+/// URLs in cached content, with their screen positions
+fn detect_urls(cached: &CachedContent) -> Vec<DetectedUrl> {
+    let mut urls = Vec::new();
+    for (line_idx, row) in cached.cells.iter().enumerate() {
+        let line_text: String = row
+            .iter()
+            .map(|cell| if cell.c == '\0' { ' ' } else { cell.c })
+            .collect();
+
+        for mat in URL_REGEX.find_iter(&line_text) {
+            // Strip trailing punctuation that is commonly not part of URLs
+            // (e.g. "Visit https://example.com." should not include the period)
+            let url_str = mat
+                .as_str()
+                .trim_end_matches(|c: char| matches!(c, '.' | ',' | ';' | ':' | '!' | '?'));
+            if url_str.len() <= "https://".len() {
+                continue;
+            }
+
+            // Convert byte offsets to column indices.
+            // Because the line is built char-by-char from the grid, each char
+            // maps 1:1 to a column only when all characters are single-byte.
+            // Use char_indices for correct mapping.
+            let start_col = line_text[..mat.start()].chars().count();
+            let end_col = start_col + url_str.chars().count() - 1;
+
+            urls.push(DetectedUrl {
+                url: url_str.to_string(),
+                start: (line_idx, start_col),
+                end: (line_idx, end_col),
+            });
+        }
+    }
+    urls
 }
 
 /// Selection state for text selection in the terminal
@@ -88,9 +471,18 @@ struct TerminalSelection {
     start: (i32, usize),
     /// End point (line, column)
     end: (i32, usize),
+    kind: SelectionKind,
 }
 
 impl TerminalSelection {
+    fn new(start: (i32, usize), end: (i32, usize)) -> Self {
+        Self {
+            start,
+            end,
+            kind: SelectionKind::Linear,
+        }
+    }
+
     /// Returns the selection normalized so start <= end
     fn normalized(&self) -> (i32, usize, i32, usize) {
         let (start_line, start_col) = self.start;
@@ -102,12 +494,21 @@ impl TerminalSelection {
         }
     }
 
+    /// Column range of a block selection (inclusive)
+    fn block_columns(&self) -> (usize, usize) {
+        (self.start.1.min(self.end.1), self.start.1.max(self.end.1))
+    }
+
     /// Check if a position is within the selection
     fn contains(&self, line: i32, col: usize) -> bool {
         let (start_line, start_col, end_line, end_col) = self.normalized();
         if line < start_line || line > end_line {
             return false;
         }
+        if self.kind == SelectionKind::Block {
+            let (left, right) = self.block_columns();
+            return col >= left && col <= right;
+        }
         if line == start_line && line == end_line {
             col >= start_col && col <= end_col
         } else if line == start_line {
@@ -118,6 +519,80 @@ impl TerminalSelection {
             true
         }
     }
+
+    /// Selected text from a grid `cols` wide whose existing lines are `lines`.
+    ///
+    /// Linear selections drop trailing whitespace per line. Block selections
+    /// keep their columns: short lines are padded with spaces to the block width.
+    /// A wide character half inside the selection is taken whole, once.
+    fn text<'a>(
+        &self,
+        cols: usize,
+        lines: RangeInclusive<i32>,
+        cell: impl Fn(i32, usize) -> CellText<'a>,
+    ) -> String {
+        let (start_line, start_col, end_line, end_col) = self.normalized();
+        let mut result = String::new();
+        // Columns `from..=to` of a line, widened to whole wide characters
+        let widen = |line: i32, from: usize, to: usize| {
+            let from = if from > 0 && from < cols && cell(line, from - 1).wide {
+                from - 1
+            } else {
+                from
+            };
+            let to = if to + 1 < cols && cell(line, to).wide {
+                to + 1
+            } else {
+                to
+            };
+            (from, to)
+        };
+
+        for line_idx in start_line..=end_line {
+            if !lines.contains(&line_idx) {
+                continue;
+            }
+
+            if self.kind == SelectionKind::Block {
+                let (left, right) = self.block_columns();
+                let (left, right) = widen(line_idx, left, right);
+                for col_idx in left..=right {
+                    if col_idx < cols {
+                        cell(line_idx, col_idx).push_to(&mut result);
+                    } else {
+                        result.push(' ');
+                    }
+                }
+            } else {
+                let col_start = if line_idx == start_line { start_col } else { 0 };
+                let col_end = if line_idx == end_line {
+                    end_col.min(cols - 1)
+                } else {
+                    cols - 1
+                };
+                let (col_start, col_end) = widen(line_idx, col_start, col_end);
+                for col_idx in col_start..=col_end {
+                    cell(line_idx, col_idx).push_to(&mut result);
+                }
+            }
+
+            // Add newline between lines (but not after the last line)
+            if line_idx < end_line {
+                result.push('\n');
+            }
+        }
+
+        if self.kind == SelectionKind::Block {
+            return result;
+        }
+
+        // Trim trailing whitespace from each line
+        result
+            .lines()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 pub struct TerminalView {
@@ -137,30 +612,109 @@ pub struct TerminalView {
     /// Cell dimensions for mouse position to cell conversion
     pub(super) cell_width: f32,
     pub(super) cell_height: f32,
+    /// Scrolled pixels short of a whole line
+    scroll_accumulator: ScrollAccumulator,
+    /// Grid sizes measured by the element, applied once they hold
+    resize: ResizeDebouncer,
+    /// A task applies the pending grid size
+    resize_scheduled: bool,
     /// Terminal content origin for mouse coordinate conversion
     pub(super) content_origin: (f32, f32),
     /// Cached terminal content to ensure consistent state during rendering.
     /// Updated after all events are processed, used by build_layout().
     cached_content: Option<CachedContent>,
+    /// Row layouts reused across frames (see `RowLayoutCache`)
+    row_layouts: RowLayoutCache,
     /// URLs detected in the current terminal content
     pub(super) detected_urls: Vec<DetectedUrl>,
     /// Index of the URL currently hovered with Ctrl held
     pub(super) hovered_url_index: Option<usize>,
+    /// Window title set by the running program (OSC 0/2)
+    title: Option<String>,
+    /// Attention state of the owning session
+    attention: Rc<SessionAttention>,
+    silence: SilenceTracker,
+    /// Rate limit of the visual and audible bell
+    bell_limiter: BellLimiter,
+    /// Whether the pane is flashing for a bell
+    bell_flash: bool,
+    /// Whether new output scrolls the view along
+    follow: Follow,
+    /// Whether the scrollbar thumb is being dragged
+    scrollbar_dragging: bool,
+    /// Height of the terminal element, for mapping scrollbar clicks
+    pub(super) content_height: f32,
+    /// Keyboard copy mode, None while keys go to the program
+    copy_mode: Option<CopyMode>,
+    /// Output filter (see `filter`), None while the grid is shown as it is
+    filter: Option<TerminalFilter>,
+    /// Code blocks shown with their apply and copy buttons (see
+    /// `code_scan`), None while the overlay is closed
+    code_blocks: Option<FoundBlocks>,
+    /// Block picked to apply to a file, until the app takes it
+    chosen_code_block: Option<CodeBlock>,
+    /// Commands run in this terminal (see `command_history`)
+    history: TerminalHistory,
+    /// Where the scrollback is kept across restarts (see `crate::scrollback`)
+    scrollback: TerminalScrollback,
+    /// Grid lines of the prompts shell integration marked, top to bottom
+    prompt_lines: Vec<i32>,
+    /// Whether the shell has exited
+    shell_exited: bool,
+    /// tmux session the shell runs in (see `crate::multiplexer`)
+    multiplexed: Option<String>,
 }
 
 impl TerminalView {
-    /// Create a new terminal with a specific working directory
+    /// Create a new terminal with a specific working directory, optional
+    /// shell command and extra environment variables
     pub fn new_with_directory(
         working_directory: std::path::PathBuf,
+        shell: Option<&str>,
+        env: Vec<(String, String)>,
+        attention: Rc<SessionAttention>,
+        transcript: Arc<SessionTranscript>,
+        history: TerminalHistory,
+        scrollback: TerminalScrollback,
+        multiplexed: Option<String>,
         cx: &mut Context<Self>,
     ) -> Self {
-        Self::new_internal(Some(working_directory), cx)
+        Self::new_internal(
+            Some(working_directory),
+            shell,
+            env,
+            attention,
+            transcript,
+            history,
+            scrollback,
+            multiplexed,
+            cx,
+        )
     }
 
-    fn new_internal(working_directory: Option<std::path::PathBuf>, cx: &mut Context<Self>) -> Self {
+    fn new_internal(
+        working_directory: Option<std::path::PathBuf>,
+        shell: Option<&str>,
+        env: Vec<(String, String)>,
+        attention: Rc<SessionAttention>,
+        transcript: Arc<SessionTranscript>,
+        history: TerminalHistory,
+        scrollback: TerminalScrollback,
+        multiplexed: Option<String>,
+        cx: &mut Context<Self>,
+    ) -> Self {
         let focus_handle = cx.focus_handle();
-
-        match Terminal::new(working_directory) {
+        let directory = working_directory
+            .as_ref()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+
+        // tmux keeps the scrollback of the terminals that run in it
+        let restored = multiplexed
+            .is_none()
+            .then(|| scrollback.restore())
+            .flatten();
+        match Terminal::new(working_directory, shell, env, transcript, restored) {
             Ok((terminal, event_rx)) => {
                 let terminal = Arc::new(terminal);
 
@@ -168,16 +722,27 @@ impl TerminalView {
                 // This prevents catching intermediate states during rapid event sequences
                 cx.spawn(
                     async move |this: WeakEntity<TerminalView>, cx: &mut AsyncApp| {
-                        while let Ok(_event) = event_rx.recv().await {
+                        while let Ok(event) = event_rx.recv().await {
                             // Drain any additional pending events before updating
                             // This ensures we process all events in a batch
-                            while event_rx.try_recv().is_ok() {}
+                            let mut events = vec![event];
+                            while let Ok(event) = event_rx.try_recv() {
+                                events.push(event);
+                            }
 
                             let should_break = cx.update(|cx| {
                                 if let Some(this) = this.upgrade() {
                                     this.update(cx, |view, cx: &mut Context<TerminalView>| {
+                                        let previous_offset = view.display_offset();
                                         // Update content cache after all events processed
                                         view.update_content_cache();
+                                        view.follow.output(previous_offset, view.display_offset());
+                                        // Picks up at most one title change per batch
+                                        view.refresh_title();
+                                        view.record_reported_commands();
+                                        view.track_attention(&events);
+                                        view.ring_bell(&events, cx);
+                                        view.note_child_exit(&events);
                                         cx.notify();
                                     });
                                     false
@@ -204,44 +769,330 @@ impl TerminalView {
                     click_count: 0,
                     cell_width: DEFAULT_CELL_WIDTH,
                     cell_height: DEFAULT_CELL_HEIGHT,
+                    scroll_accumulator: ScrollAccumulator::default(),
+                    resize: ResizeDebouncer::default(),
+                    resize_scheduled: false,
                     content_origin: (0.0, 0.0),
                     cached_content: None,
+                    row_layouts: RowLayoutCache::default(),
                     detected_urls: Vec::new(),
                     hovered_url_index: None,
+                    title: None,
+                    attention,
+                    silence: SilenceTracker::default(),
+                    bell_limiter: BellLimiter::default(),
+                    bell_flash: false,
+                    follow: Follow::default(),
+                    scrollbar_dragging: false,
+                    content_height: 0.0,
+                    copy_mode: None,
+                    filter: None,
+                    code_blocks: None,
+                    chosen_code_block: None,
+                    history,
+                    scrollback,
+                    prompt_lines: Vec::new(),
+                    shell_exited: false,
+                    multiplexed,
                 };
                 // Capture initial terminal state so build_layout always has cached data
                 view.update_content_cache();
                 view
             }
-            Err(e) => Self {
-                terminal: None,
-                focus_handle,
-                preedit_text: String::new(),
-                error_message: Some(format!("Failed to create terminal: {}", e)),
-                selection: None,
-                is_dragging: false,
-                last_click_time: None,
-                click_count: 0,
-                cell_width: DEFAULT_CELL_WIDTH,
-                cell_height: DEFAULT_CELL_HEIGHT,
-                content_origin: (0.0, 0.0),
-                cached_content: None,
-                detected_urls: Vec::new(),
-                hovered_url_index: None,
-            },
+            Err(e) => {
+                app_log::record(LogEntry::new(
+                    LogLevel::Error,
+                    LogSource::Terminal,
+                    format!("Failed to create terminal in {}: {}", directory, e),
+                ));
+                Self {
+                    terminal: None,
+                    focus_handle,
+                    preedit_text: String::new(),
+                    error_message: Some(format!("Failed to create terminal: {}", e)),
+                    selection: None,
+                    is_dragging: false,
+                    last_click_time: None,
+                    click_count: 0,
+                    cell_width: DEFAULT_CELL_WIDTH,
+                    cell_height: DEFAULT_CELL_HEIGHT,
+                    scroll_accumulator: ScrollAccumulator::default(),
+                    resize: ResizeDebouncer::default(),
+                    resize_scheduled: false,
+                    content_origin: (0.0, 0.0),
+                    cached_content: None,
+                    row_layouts: RowLayoutCache::default(),
+                    detected_urls: Vec::new(),
+                    hovered_url_index: None,
+                    title: None,
+                    attention,
+                    silence: SilenceTracker::default(),
+                    bell_limiter: BellLimiter::default(),
+                    bell_flash: false,
+                    follow: Follow::default(),
+                    scrollbar_dragging: false,
+                    content_height: 0.0,
+                    copy_mode: None,
+                    filter: None,
+                    code_blocks: None,
+                    chosen_code_block: None,
+                    history,
+                    scrollback,
+                    prompt_lines: Vec::new(),
+                    shell_exited: false,
+                    multiplexed,
+                }
+            }
         }
     }
 
-    /// Shutdown the terminal by sending exit command to the shell
-    pub fn shutdown(&self) {
+    /// Foreground program and age of the last output, for telling whether
+    /// stopping the terminal would interrupt something
+    pub fn activity(&self, now: Instant) -> TerminalActivity {
+        TerminalActivity {
+            program: self
+                .terminal
+                .as_ref()
+                .and_then(|terminal| terminal.foreground_program()),
+            output_age: self
+                .silence
+                .last_output()
+                .map(|last| now.duration_since(last)),
+        }
+    }
+
+    /// Name of the program in the foreground when it is not the shell
+    pub fn foreground_program(&self) -> Option<String> {
+        self.terminal
+            .as_ref()
+            .and_then(|terminal| terminal.foreground_program())
+    }
+
+    /// Process name of the shell, while it runs
+    pub fn shell_program(&self) -> Option<String> {
+        self.terminal
+            .as_ref()
+            .filter(|_| !self.shell_exited)
+            .and_then(|terminal| terminal.shell_program())
+    }
+
+    /// Process id of the shell, while it runs
+    pub fn shell_pid(&self) -> Option<u32> {
+        self.terminal
+            .as_ref()
+            .filter(|_| !self.shell_exited)
+            .and_then(|terminal| terminal.shell_pid())
+    }
+
+    /// When the terminal last printed anything
+    pub fn last_output(&self) -> Option<Instant> {
+        self.silence.last_output()
+    }
+
+    /// Last `count` rows with text and the time of the last output, cheap
+    /// enough to call for a thumbnail
+    pub fn tail(&self, count: usize) -> TerminalTail {
+        TerminalTail {
+            rows: self
+                .terminal
+                .as_ref()
+                .map(|terminal| terminal.tail_rows(count))
+                .unwrap_or_default(),
+            last_output: self.last_output(),
+        }
+    }
+
+    /// Window title set by the running program, if any
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn refresh_title(&mut self) {
         if let Some(ref terminal) = self.terminal {
+            self.title = terminal.take_title();
+        }
+    }
+
+    /// Move the commands the shell reported into the history
+    fn record_reported_commands(&self) {
+        if let Some(ref terminal) = self.terminal {
+            for command in terminal.take_commands() {
+                self.history.record(&command);
+            }
+        }
+    }
+
+    /// Commands run in this terminal, newest first
+    pub fn recent_commands(&self) -> Vec<String> {
+        self.history.get().recent().map(str::to_string).collect()
+    }
+
+    /// Whether the shell reports the commands it runs (OSC 133); without it
+    /// only commands sent by Sashiki are in the history
+    pub fn has_shell_integration(&self) -> bool {
+        self.terminal
+            .as_ref()
+            .is_some_and(|terminal| terminal.has_shell_integration())
+    }
+
+    /// Run `command` as if it was typed and entered
+    pub fn run_command(&self, command: &str) {
+        self.write_text(&format!("{}\r", command));
+    }
+
+    /// Paste `command` and enter it: pasted like `paste_text`, so control
+    /// characters in it cannot act as keys, and recorded like `run_command`
+    pub fn paste_command(&self, command: &str) {
+        self.paste_text(command);
+        self.write_to_terminal(b"\r");
+        if !self.has_shell_integration() {
+            self.history.record(command);
+        }
+    }
+
+    /// Directory the shell is in, where the platform tells
+    pub fn shell_directory(&self) -> Option<std::path::PathBuf> {
+        self.terminal
+            .as_ref()
+            .and_then(|terminal| terminal.shell_directory())
+    }
+
+    /// Raise session attention for a batch of terminal events
+    fn track_attention(&mut self, events: &[TerminalEvent]) {
+        let mut reason = None;
+        for event in events {
+            let raised = match event {
+                TerminalEvent::Wakeup => self
+                    .silence
+                    .output(Instant::now())
+                    .then_some(AttentionReason::Output),
+                TerminalEvent::Bell => self
+                    .attention
+                    .bell()
+                    .notify
+                    .then_some(AttentionReason::Bell),
+                TerminalEvent::ChildExit(code) if *code != 0 => Some(AttentionReason::Exit(*code)),
+                _ => None,
+            };
+            reason = reason.max(raised);
+        }
+        if let Some(reason) = reason {
+            self.attention.raise_and_notify(reason);
+        }
+    }
+
+    /// Flash the pane and play the bell sound for a batch of terminal
+    /// events, as configured and at most once per `bell::BELL_INTERVAL`
+    fn ring_bell(&mut self, events: &[TerminalEvent], cx: &mut Context<Self>) {
+        let settings = self.attention.bell();
+        if !(settings.visual || settings.audible)
+            || !events.iter().any(|e| matches!(e, TerminalEvent::Bell))
+            || !self.bell_limiter.ring(Instant::now())
+        {
+            return;
+        }
+        if settings.audible {
+            bell::play_sound();
+        }
+        if settings.visual {
+            self.bell_flash = true;
+            cx.spawn(
+                async move |this: WeakEntity<TerminalView>, cx: &mut AsyncApp| {
+                    smol::Timer::after(BELL_FLASH_DURATION).await;
+                    let _ = this.update(cx, |view, cx| {
+                        view.bell_flash = false;
+                        cx.notify();
+                    });
+                },
+            )
+            .detach();
+        }
+    }
+
+    /// Remember shell exits and record them in the app log (never the
+    /// terminal output itself)
+    fn note_child_exit(&mut self, events: &[TerminalEvent]) {
+        for event in events {
+            if let TerminalEvent::ChildExit(code) = event {
+                self.shell_exited = true;
+                let level = if *code == 0 {
+                    LogLevel::Info
+                } else {
+                    LogLevel::Warn
+                };
+                app_log::record(LogEntry::new(
+                    level,
+                    LogSource::Terminal,
+                    format!(
+                        "Shell in {} exited with code {}",
+                        self.attention.label(),
+                        code
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Whether the shell was started and has not exited yet
+    pub fn is_running(&self) -> bool {
+        self.terminal.is_some() && !self.shell_exited
+    }
+
+    /// Shutdown the terminal by sending exit command to the shell. A shell
+    /// in tmux is left running: the tmux client is detached instead.
+    pub fn shutdown(&self) {
+        if let Some(name) = &self.multiplexed {
+            multiplexer::detach(name);
+        } else if let Some(ref terminal) = self.terminal {
             terminal.shutdown();
         }
     }
 
-    /// Write text to the terminal (for pasting from file view)
+    /// Like `shutdown`, ending the tmux session of a shell that runs in one
+    pub fn kill(&self) {
+        match &self.multiplexed {
+            Some(name) => multiplexer::kill(name),
+            None => self.shutdown(),
+        }
+    }
+
+    /// tmux session the shell runs in
+    pub fn multiplexed(&self) -> Option<&str> {
+        self.multiplexed.as_deref()
+    }
+
+    /// File to save the scrollback in and its lines, when it is kept for
+    /// this terminal
+    pub fn scrollback_to_save(&self) -> Option<(std::path::PathBuf, Vec<String>)> {
+        if self.multiplexed.is_some() {
+            return None;
+        }
+        let terminal = self.terminal.as_ref()?;
+        let path = self.scrollback.path()?;
+        Some((path, terminal.saved_lines(self.scrollback.colors())))
+    }
+
+    /// Write text to the terminal (for pasting from file view). A line
+    /// ended with Enter goes into the history unless the shell reports its
+    /// commands itself.
     pub fn write_text(&self, text: &str) {
         self.write_to_terminal(text.as_bytes());
+        if let Some(line) = text.strip_suffix(['\r', '\n'])
+            && !self.has_shell_integration()
+        {
+            let command = line.rsplit(['\r', '\n']).next().unwrap_or(line);
+            self.history.record(command);
+        }
+    }
+
+    /// Paste text into the terminal, bracketed when the program asked for it
+    /// so multi-line input is not run line by line
+    pub fn paste_text(&self, text: &str) {
+        let Some(ref terminal) = self.terminal else {
+            return;
+        };
+        let bracketed = terminal.with_term(|term| term.mode().contains(TermMode::BRACKETED_PASTE));
+        terminal.write(&paste_bytes(text, bracketed));
     }
 
     /// Write bytes to the terminal (used by action handlers)
@@ -261,150 +1112,510 @@ impl TerminalView {
             .unwrap_or(10)
     }
 
+    fn display_offset(&self) -> usize {
+        self.cached_content
+            .as_ref()
+            .map_or(0, |c| c.display_offset as usize)
+    }
+
+    /// Scroll back to the newest output
+    pub(super) fn scroll_to_bottom(&mut self, cx: &mut Context<Self>) {
+        if let Some(ref terminal) = self.terminal {
+            terminal.scroll(alacritty_terminal::grid::Scroll::Bottom);
+        } else {
+            return;
+        }
+        self.update_content_cache();
+        cx.notify();
+    }
+
+    /// Whether the view is scrolled back into history
+    pub(super) fn is_scrolled_back(&self) -> bool {
+        self.display_offset() > 0
+    }
+
+    /// Whether new output scrolls the view along (it is at the bottom)
+    pub fn is_following(&self) -> bool {
+        self.follow.is_following()
+    }
+
+    /// Scroll so the previous (or next) marked prompt is at the top of the
+    /// view; past the last prompt, back to the newest output
+    pub(super) fn jump_to_prompt(&mut self, previous: bool, cx: &mut Context<Self>) {
+        let (Some(terminal), Some(cached)) = (&self.terminal, &self.cached_content) else {
+            return;
+        };
+        let top = -cached.display_offset;
+        let target = terminal.with_marks(|_, marks| {
+            let marks = marks?;
+            if previous {
+                marks.prompt_above(top)
+            } else {
+                marks.prompt_below(top)
+            }
+        });
+        let offset = match target {
+            Some(line) => (-line).clamp(0, cached.history_size as i32),
+            None if previous => return,
+            None => 0,
+        };
+        if offset == cached.display_offset {
+            return;
+        }
+        terminal.scroll(alacritty_terminal::grid::Scroll::Delta(
+            offset - cached.display_offset,
+        ));
+        self.update_content_cache();
+        cx.notify();
+    }
+
+    /// Select and copy the output of the last command shell integration
+    /// marked
+    pub(super) fn copy_last_output(&mut self, cx: &mut Context<Self>) {
+        let (Some(terminal), Some(cached)) = (&self.terminal, &self.cached_content) else {
+            return;
+        };
+        let cursor_line = cached.cursor.0;
+        let Some((start, end)) =
+            terminal.with_marks(|_, marks| marks.and_then(|marks| marks.last_output(cursor_line)))
+        else {
+            return;
+        };
+        self.selection = Some(TerminalSelection::new(
+            (start, 0),
+            (end, cached.cols.saturating_sub(1)),
+        ));
+        if let Some(text) = self.get_selected_text() {
+            cx.write_to_clipboard(ClipboardItem::new_string(text));
+        }
+        cx.notify();
+    }
+
+    /// Scroll so the view is centered on the scrollbar position at window
+    /// coordinate `y`
+    fn scroll_to_scrollbar_position(&mut self, y: f32, cx: &mut Context<Self>) {
+        let (Some(terminal), Some(cached)) = (&self.terminal, &self.cached_content) else {
+            return;
+        };
+        if self.content_height <= 0.0 {
+            return;
+        }
+        let fraction = (y - self.content_origin.1) / self.content_height;
+        let target = offset_at_fraction(fraction, cached.history_size, cached.lines);
+        let delta = target as i32 - cached.display_offset;
+        if delta == 0 {
+            return;
+        }
+        terminal.scroll(alacritty_terminal::grid::Scroll::Delta(delta));
+        self.update_content_cache();
+        cx.notify();
+    }
+
+    /// Take the grid size the element measured: the first one is applied at
+    /// once, later ones after they held for `RESIZE_DEBOUNCE`
+    pub(super) fn measure_grid(&mut self, size: GridSize, cx: &mut Context<Self>) {
+        if let Some(size) = self.resize.measure(size, Instant::now()) {
+            self.apply_grid_size(size);
+        }
+        if !self.resize.is_pending() || self.resize_scheduled {
+            return;
+        }
+        self.resize_scheduled = true;
+        cx.spawn(
+            async move |this: WeakEntity<TerminalView>, cx: &mut AsyncApp| {
+                loop {
+                    smol::Timer::after(RESIZE_DEBOUNCE).await;
+                    let pending = this.update(cx, |view, cx| {
+                        if let Some(size) = view.resize.due(Instant::now()) {
+                            view.apply_grid_size(size);
+                            view.update_content_cache();
+                            cx.notify();
+                        }
+                        view.resize_scheduled = view.resize.is_pending();
+                        view.resize_scheduled
+                    });
+                    // Stop when nothing is pending or the view was dropped
+                    if !matches!(pending, Ok(true)) {
+                        break;
+                    }
+                }
+            },
+        )
+        .detach();
+    }
+
+    /// Resize the terminal. Lines are rewrapped at the new width, so the
+    /// selection and the copy mode points move with their text; those whose
+    /// lines left the grid are dropped. A view scrolled back keeps the same
+    /// text at its top, one that follows stays at the bottom.
+    fn apply_grid_size(&mut self, size: GridSize) {
+        let Some(terminal) = self.terminal.clone() else {
+            return;
+        };
+        let [start, end] = self.selection.map_or([None; 2], |selection| {
+            [Some(selection.start), Some(selection.end)]
+        });
+        let [cursor, anchor] = self.copy_mode.map_or([None; 2], |mode| mode.points());
+        let following = self.follow.is_following();
+        let top = (!following).then(|| (-(self.display_offset() as i32), 0));
+        let mut points = [start, end, cursor, anchor, top];
+        terminal.resize_carrying(
+            size.cols,
+            size.lines,
+            self.cell_width as u16,
+            self.cell_height as u16,
+            &mut points,
+        );
+        let [start, end, cursor, anchor, top] = points;
+        self.update_content_cache();
+        let offset = self.display_offset() as i32;
+        let delta = match top {
+            Some((line, _)) => -line.min(0) - offset,
+            None if following => -offset,
+            // The top line left the grid
+            None => 0,
+        };
+        if delta != 0 {
+            terminal.scroll(alacritty_terminal::grid::Scroll::Delta(delta));
+            self.update_content_cache();
+        }
+        self.selection = self
+            .selection
+            .zip(start.zip(end))
+            .map(|(selection, (start, end))| TerminalSelection {
+                start,
+                end,
+                ..selection
+            });
+        self.copy_mode = self
+            .copy_mode
+            .and_then(|mut mode| mode.carry([cursor, anchor]).then_some(mode));
+    }
+
     /// Update cached content from terminal.
     /// Called after event processing to capture the complete terminal state.
-    /// Similar to Zed's make_content() - captures all cells, cursor, and display state.
+    /// Similar to Zed's make_content(), but only rows that changed since the
+    /// last snapshot are copied.
     pub(super) fn update_content_cache(&mut self) {
         let Some(ref terminal) = self.terminal else {
             return;
         };
 
-        terminal.with_term(|term| {
-            let render_content = term.renderable_content();
-            let cursor_point = render_content.cursor.point;
-            let display_offset = render_content.display_offset as i32;
-
-            let grid = term.grid();
-            let cols = grid.columns();
-            let lines = grid.screen_lines();
-
-            // Copy all cell data from the grid
-            let mut cells = Vec::with_capacity(lines);
-            for line_idx in 0..lines {
-                let actual_line = line_idx as i32 - display_offset;
-                let mut row = Vec::with_capacity(cols);
-                for col_idx in 0..cols {
-                    let point = AlacPoint::new(Line(actual_line), Column(col_idx));
-                    let cell = &grid[point];
-                    row.push(CachedCell {
-                        c: cell.c,
-                        fg: cell.fg,
-                        bg: cell.bg,
-                        flags: cell.flags,
-                    });
+        terminal.with_marks(|term, marks| {
+            match self.cached_content.as_mut() {
+                Some(cached) => {
+                    cached.update(term);
                 }
-                cells.push(row);
+                None => self.cached_content = Some(CachedContent::capture(term)),
+            }
+            if let Some(found) = self.code_blocks.as_mut() {
+                found.follow(term.grid());
             }
+            self.prompt_lines.clear();
+            self.prompt_lines
+                .extend(marks.into_iter().flat_map(|marks| marks.prompt_lines()));
+        });
 
-            let cursor_visible = term
-                .mode()
-                .contains(alacritty_terminal::term::TermMode::SHOW_CURSOR);
+        self.follow.settle(self.display_offset());
+        let cached = self.cached_content.as_ref();
+        if let (Some(filter), Some(cached)) = (self.filter.as_mut(), cached) {
+            let history_size = cached.history_size;
+            let full = history_size >= TermConfig::default().scrolling_history;
+            filter.sync(history_size, history_size + cached.lines, full);
+        }
 
-            self.cached_content = Some(CachedContent {
-                cells,
-                cursor: (cursor_point.line.0, cursor_point.column.0),
-                cursor_visible,
-                display_offset,
-                lines,
-            });
+        self.detect_urls_from_cache();
+    }
+
+    /// Scan cached content for URLs using regex and record their screen positions.
+    fn detect_urls_from_cache(&mut self) {
+        self.detected_urls = self
+            .cached_content
+            .as_ref()
+            .map(detect_urls)
+            .unwrap_or_default();
+    }
+
+    /// Get the text content of the current selection
+    pub(super) fn get_selected_text(&self) -> Option<String> {
+        let selection = self.selection?;
+        let terminal = self.terminal.as_ref()?;
+
+        let result = terminal.with_term(|term| {
+            let content = term.grid();
+            let cols = content.columns();
+            let total_lines = content.screen_lines() as i32;
+            let history = content.history_size() as i32;
+
+            // Selection is in grid coordinates: valid range is -history..screen_lines
+            selection.text(cols, -history..=total_lines - 1, |line, col| {
+                CellText::of(&content[AlacPoint::new(Line(line), Column(col))])
+            })
         });
 
-        self.detect_urls_from_cache();
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Select the whole scrollback and screen
+    pub(super) fn select_all(&mut self, cx: &mut Context<Self>) {
+        let Some(ref terminal) = self.terminal else {
+            return;
+        };
+        let (history, screen_lines, cols) = terminal.with_term(|term| {
+            let grid = term.grid();
+            (
+                grid.history_size() as i32,
+                grid.screen_lines() as i32,
+                grid.columns(),
+            )
+        });
+        self.selection = Some(TerminalSelection::new(
+            (-history, 0),
+            (screen_lines - 1, cols.saturating_sub(1)),
+        ));
+        cx.notify();
+    }
+
+    /// Move the end of the selection with the keyboard, starting a selection
+    /// at the cursor if there is none.
+    ///
+    /// Returns false (leaving the selection alone) while a full-screen program
+    /// owns the alternate screen, so it still receives Shift+arrow keys.
+    pub(super) fn extend_selection_by(
+        &mut self,
+        lines: i32,
+        cols: i32,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(ref terminal) = self.terminal else {
+            return false;
+        };
+        let Some(bounds) = terminal.with_term(|term| {
+            if term.mode().contains(TermMode::ALT_SCREEN) {
+                return None;
+            }
+            let grid = term.grid();
+            Some((
+                -(grid.history_size() as i32),
+                grid.screen_lines() as i32 - 1,
+                grid.columns().saturating_sub(1),
+            ))
+        }) else {
+            return false;
+        };
+        let (top, bottom, last_col) = bounds;
+
+        let cursor = self
+            .cached_content
+            .as_ref()
+            .map(|c| c.cursor)
+            .unwrap_or((0, 0));
+        let selection = self
+            .selection
+            .get_or_insert_with(|| TerminalSelection::new(cursor, cursor));
+        let (line, col) = selection.end;
+        selection.end = (
+            (line + lines).clamp(top, bottom),
+            (col as i32 + cols).clamp(0, last_col as i32) as usize,
+        );
+        cx.notify();
+        true
+    }
+
+    /// Whether keys drive the copy mode cursor instead of the program
+    pub fn is_copy_mode(&self) -> bool {
+        self.copy_mode.is_some()
+    }
+
+    /// Enter or leave copy mode. The block cursor starts at the terminal
+    /// cursor, or at the top of the view while that is scrolled out of it.
+    pub(super) fn toggle_copy_mode(&mut self, cx: &mut Context<Self>) {
+        if self.copy_mode.take().is_some() {
+            self.selection = None;
+        } else {
+            let Some(cached) = self.cached_content.as_ref() else {
+                return;
+            };
+            let start = if self.cursor_display_position().is_some() {
+                cached.cursor
+            } else {
+                (-cached.display_offset, 0)
+            };
+            self.copy_mode = Some(CopyMode::new(start));
+        }
+        cx.notify();
+    }
+
+    /// Whether the view shows filtered rows instead of the grid
+    pub fn is_filtered(&self) -> bool {
+        self.filter
+            .as_ref()
+            .is_some_and(TerminalFilter::is_filtering)
+    }
+
+    /// Open the filter input, or clear the filter and show the grid where
+    /// it was
+    pub(super) fn toggle_filter(&mut self, cx: &mut Context<Self>) {
+        if self.filter.take().is_none() {
+            let history_size = self.cached_content.as_ref().map_or(0, |c| c.history_size);
+            self.filter = Some(TerminalFilter::open(history_size));
+            self.hovered_url_index = None;
+        }
+        cx.notify();
     }
 
-    /// Scan cached content for URLs using regex and record their screen positions.
-    fn detect_urls_from_cache(&mut self) {
-        self.detected_urls.clear();
-
-        let Some(ref cached) = self.cached_content else {
+    /// Show the code blocks in the view and the scrollback above it with
+    /// their buttons, or hide them. With none in the view the newest one
+    /// above it is scrolled to.
+    pub(super) fn toggle_code_blocks(&mut self, cx: &mut Context<Self>) {
+        if self.code_blocks.take().is_some() {
+            cx.notify();
+            return;
+        }
+        let Some(terminal) = self.terminal.clone() else {
             return;
         };
-
-        for (line_idx, row) in cached.cells.iter().enumerate() {
-            let line_text: String = row
-                .iter()
-                .map(|cell| if cell.c == '\0' { ' ' } else { cell.c })
-                .collect();
-
-            for mat in URL_REGEX.find_iter(&line_text) {
-                // Strip trailing punctuation that is commonly not part of URLs
-                // (e.g. "Visit https://example.com." should not include the period)
-                let url_str = mat
-                    .as_str()
-                    .trim_end_matches(|c: char| matches!(c, '.' | ',' | ';' | ':' | '!' | '?'));
-                if url_str.len() <= "https://".len() {
-                    continue;
-                }
-
-                // Convert byte offsets to column indices.
-                // Because the line is built char-by-char from the grid, each char
-                // maps 1:1 to a column only when all characters are single-byte.
-                // Use char_indices for correct mapping.
-                let start_col = line_text[..mat.start()].chars().count();
-                let end_col = start_col + url_str.chars().count() - 1;
-
-                self.detected_urls.push(DetectedUrl {
-                    url: url_str.to_string(),
-                    start: (line_idx, start_col),
-                    end: (line_idx, end_col),
-                });
-            }
+        let offset = self.display_offset() as i32;
+        let found = terminal.with_term(|term| FoundBlocks::scan(term.grid(), offset));
+        let lines = self.cached_content.as_ref().map_or(0, |c| c.lines);
+        if found.visible(offset, lines).is_empty()
+            && let Some(line) = found.last_above(offset)
+        {
+            terminal.scroll(alacritty_terminal::grid::Scroll::Delta(-line - offset));
         }
+        self.code_blocks = Some(found);
+        self.update_content_cache();
+        cx.notify();
     }
 
-    /// Get the text content of the current selection
-    pub(super) fn get_selected_text(&self) -> Option<String> {
-        let selection = self.selection?;
-        let terminal = self.terminal.as_ref()?;
-
-        let (start_line, start_col, end_line, end_col) = selection.normalized();
-        let mut result = String::new();
+    /// Copy code block `index` of the overlay and close it
+    fn copy_code_block(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(block) = self
+            .code_blocks
+            .take()
+            .and_then(|found| found.get(index).cloned())
+        {
+            cx.write_to_clipboard(ClipboardItem::new_string(block.text));
+        }
+        cx.notify();
+    }
 
-        terminal.with_term(|term| {
-            let content = term.grid();
-            let cols = content.columns();
-            let total_lines = content.screen_lines() as i32;
-            let history = content.history_size() as i32;
+    /// Hand code block `index` of the overlay to the app to apply
+    fn apply_code_block(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        self.chosen_code_block = self
+            .code_blocks
+            .take()
+            .and_then(|found| found.get(index).cloned());
+        window.dispatch_action(Box::new(ApplyCodeBlock), cx);
+        cx.notify();
+    }
 
-            for line_idx in start_line..=end_line {
-                // Selection is in grid coordinates: valid range is -history..screen_lines
-                if line_idx < -history || line_idx >= total_lines {
-                    continue;
-                }
+    /// The block picked with "Apply to file…", once
+    pub fn take_chosen_code_block(&mut self) -> Option<CodeBlock> {
+        self.chosen_code_block.take()
+    }
 
-                let col_start = if line_idx == start_line { start_col } else { 0 };
-                let col_end = if line_idx == end_line {
-                    end_col.min(cols - 1)
+    /// Handle a key press in the filter input: typing edits the query, which
+    /// applies as it changes; Enter gives the keys back to the program and
+    /// Escape clears the filter
+    fn handle_filter_key(&mut self, keystroke: &Keystroke, cx: &mut Context<Self>) {
+        let modifiers = keystroke.modifiers;
+        if modifiers.control || modifiers.alt || modifiers.platform {
+            return;
+        }
+        let Some(filter) = self.filter.as_mut() else {
+            return;
+        };
+        let mut input = filter.input.clone();
+        match keystroke.key.as_str() {
+            "escape" => {
+                self.filter = None;
+                cx.notify();
+                return;
+            }
+            "enter" => {
+                if input.is_empty() {
+                    self.filter = None;
                 } else {
-                    cols - 1
-                };
-
-                for col_idx in col_start..=col_end {
-                    let point = AlacPoint::new(Line(line_idx), Column(col_idx));
-                    let cell = &content[point];
-                    let c = if cell.c == '\0' { ' ' } else { cell.c };
-                    result.push(c);
+                    filter.editing = false;
                 }
+                cx.notify();
+                return;
+            }
+            "backspace" => {
+                input.pop();
+            }
+            _ => match keystroke.key_char.as_deref() {
+                Some(text) if !text.chars().any(char::is_control) => input.push_str(text),
+                _ => return,
+            },
+        }
+        let rows = self
+            .cached_content
+            .as_ref()
+            .map_or(0, |c| c.history_size + c.lines);
+        filter.set_input(input, rows);
+        cx.notify();
+    }
+
+    /// Handle a key press in copy mode (see `copy_mode`)
+    fn handle_copy_mode_key(&mut self, keystroke: &Keystroke, cx: &mut Context<Self>) {
+        let modifiers = keystroke.modifiers;
+        if modifiers.control || modifiers.alt || modifiers.platform {
+            return;
+        }
+        let (Some(mut mode), Some(terminal)) = (self.copy_mode, self.terminal.clone()) else {
+            return;
+        };
+        let Some(command) = copy_mode::command_for_key(
+            &keystroke.key,
+            keystroke.key_char.as_deref(),
+            self.page_scroll_lines(),
+        ) else {
+            return;
+        };
 
-                // Add newline between lines (but not after the last line)
-                if line_idx < end_line {
-                    result.push('\n');
+        match command {
+            CopyCommand::Exit => {
+                self.toggle_copy_mode(cx);
+                return;
+            }
+            CopyCommand::Yank => {
+                if let Some(text) = self.get_selected_text() {
+                    cx.write_to_clipboard(ClipboardItem::new_string(text));
+                    self.toggle_copy_mode(cx);
                 }
+                return;
             }
-        });
-
-        // Trim trailing whitespace from each line
-        let result: String = result
-            .lines()
-            .map(|line| line.trim_end())
-            .collect::<Vec<_>>()
-            .join("\n");
+            CopyCommand::Select { line_wise } => mode.toggle_selection(line_wise),
+            CopyCommand::Move(motion) => {
+                mode.cursor = terminal
+                    .with_term(|term| copy_mode::move_cursor(term.grid(), mode.cursor, motion));
+            }
+        }
 
-        if result.is_empty() {
-            None
-        } else {
-            Some(result)
+        let cols = self.cached_content.as_ref().map_or(0, |c| c.cols);
+        self.selection = mode
+            .selection(cols)
+            .map(|(start, end)| TerminalSelection::new(start, end));
+        self.copy_mode = Some(mode);
+
+        // Scroll the cursor into view
+        if let Some(cached) = self.cached_content.as_ref() {
+            let offset =
+                copy_mode::offset_showing(mode.cursor.0, cached.display_offset, cached.lines);
+            if offset != cached.display_offset {
+                terminal.scroll(alacritty_terminal::grid::Scroll::Delta(
+                    offset - cached.display_offset,
+                ));
+                self.update_content_cache();
+            }
         }
+        cx.notify();
     }
 
     // ========================================================================
@@ -418,6 +1629,10 @@ impl TerminalView {
         let y = (y - self.content_origin.1 - TERMINAL_PADDING).max(0.0);
         let col = (x / self.cell_width) as usize;
         let line = (y / self.cell_height) as i32;
+        let col = match &self.cached_content {
+            Some(cached) => cached.char_column(line, col),
+            None => col,
+        };
         (line, col)
     }
 
@@ -435,7 +1650,7 @@ impl TerminalView {
 
     /// Update hovered URL index based on current mouse position and Ctrl state.
     fn update_hovered_url(&mut self, screen_line: usize, col: usize, ctrl: bool) {
-        if ctrl {
+        if ctrl && !self.is_filtered() {
             self.hovered_url_index = self
                 .detected_urls
                 .iter()
@@ -446,7 +1661,11 @@ impl TerminalView {
     }
 
     /// Handle mouse down event for selection
-    fn handle_mouse_down(&mut self, x: f32, y: f32, ctrl: bool, cx: &mut Context<Self>) {
+    fn handle_mouse_down(&mut self, x: f32, y: f32, ctrl: bool, alt: bool, cx: &mut Context<Self>) {
+        // Filtered rows are not where the grid has them
+        if self.is_filtered() {
+            return;
+        }
         let (screen_line, col) = self.position_to_cell(x, y);
 
         // Ctrl+click opens the URL under the cursor
@@ -478,10 +1697,14 @@ impl TerminalView {
 
         match self.click_count {
             1 => {
-                // Single click - start new selection
+                // Single click - start new selection (Alt selects a block)
                 self.selection = Some(TerminalSelection {
-                    start: (line, col),
-                    end: (line, col),
+                    kind: if alt {
+                        SelectionKind::Block
+                    } else {
+                        SelectionKind::Linear
+                    },
+                    ..TerminalSelection::new((line, col), (line, col))
                 });
                 self.is_dragging = true;
             }
@@ -489,20 +1712,18 @@ impl TerminalView {
                 // Double click - select word
                 if let Some(ref terminal) = self.terminal {
                     let (word_start, word_end) = self.find_word_boundaries(terminal, line, col);
-                    self.selection = Some(TerminalSelection {
-                        start: (line, word_start),
-                        end: (line, word_end),
-                    });
+                    self.selection =
+                        Some(TerminalSelection::new((line, word_start), (line, word_end)));
                 }
             }
             3 => {
                 // Triple click - select line
                 if let Some(ref terminal) = self.terminal {
                     let cols = terminal.with_term(|term| term.grid().columns());
-                    self.selection = Some(TerminalSelection {
-                        start: (line, 0),
-                        end: (line, cols.saturating_sub(1)),
-                    });
+                    self.selection = Some(TerminalSelection::new(
+                        (line, 0),
+                        (line, cols.saturating_sub(1)),
+                    ));
                 }
             }
             _ => {}
@@ -598,15 +1819,25 @@ impl TerminalView {
     }
 
     /// Handle scroll wheel event
-    fn handle_scroll(&mut self, delta_y: f32, cx: &mut Context<Self>) {
+    fn handle_scroll(&mut self, event: &ScrollWheelEvent, cx: &mut Context<Self>) {
         if let Some(ref terminal) = self.terminal {
+            // A wheel notch scrolls SCROLL_LINES_WHEEL lines; touchpads
+            // report pixels, which scroll in proportion to the cell height
+            let notch = Pixels::from(self.cell_height * SCROLL_LINES_WHEEL as f32);
+            let delta_y = f32::from(event.delta.pixel_delta(notch).y);
             // GPUI scroll: positive delta_y = wheel up = scroll back in history
             // alacritty Scroll::Delta: positive = scroll up (show older content)
-            let lines = if delta_y > 0.0 {
-                SCROLL_LINES_WHEEL
-            } else {
-                -SCROLL_LINES_WHEEL
-            };
+            let lines = self.scroll_accumulator.add(delta_y, self.cell_height);
+            if lines == 0 {
+                return;
+            }
+            // A filtered view scrolls through its own lines, leaving the
+            // grid where clearing the filter shows it again
+            if let Some(filter) = self.filter.as_mut().filter(|f| f.is_filtering()) {
+                filter.scroll(lines);
+                cx.notify();
+                return;
+            }
             terminal.scroll(alacritty_terminal::grid::Scroll::Delta(lines));
         } else {
             return;
@@ -715,109 +1946,126 @@ impl TerminalView {
     /// Always uses cached content for consistent state (like Zed's approach).
     /// Cache is initialized at terminal creation and updated on every event.
     pub(super) fn build_layout(
-        &self,
+        &mut self,
         cell_width: Pixels,
         line_height: Pixels,
     ) -> Option<TerminalLayout> {
+        if let Some(layout) = self.build_filtered_layout(cell_width, line_height) {
+            return Some(layout);
+        }
         let cached = self.cached_content.as_ref()?;
-        Some(self.build_layout_from_cache(cached, cell_width, line_height))
-    }
-
-    /// Build layout from cached content (consistent state)
-    fn build_layout_from_cache(
-        &self,
-        cached: &CachedContent,
-        cell_width: Pixels,
-        line_height: Pixels,
-    ) -> TerminalLayout {
-        let selection = self.selection;
         let (cursor_line, cursor_col) = cached.cursor;
-        let cursor_visible = cached.cursor_visible;
-        let display_offset = cached.display_offset;
-        let hovered_url_index = self.hovered_url_index;
-
-        // Convert cursor to display coordinates
-        let display_cursor_line = cursor_line + display_offset;
-
-        let mut cells: Vec<Vec<CellData>> = Vec::with_capacity(cached.lines);
-
-        for (line_idx, cached_row) in cached.cells.iter().enumerate() {
-            let actual_line = line_idx as i32 - display_offset;
-            let is_cursor_line = line_idx as i32 == display_cursor_line;
-
-            let mut row_cells: Vec<CellData> = Vec::with_capacity(cached_row.len());
-
-            for (col_idx, cached_cell) in cached_row.iter().enumerate() {
-                let is_inverse = cached_cell.flags.contains(CellFlags::INVERSE);
-
-                // Swap fg/bg when INVERSE flag is set (used by TUI apps for software cursors)
-                let (fg, bg) = if is_inverse {
-                    let fg = if cached_cell.bg == AnsiColor::Named(NamedColor::Background) {
-                        Self::named_color_to_hsla(NamedColor::Background)
-                    } else {
-                        Self::ansi_color_to_hsla(cached_cell.bg)
-                    };
-                    let bg = Some(Self::ansi_color_to_hsla(cached_cell.fg));
-                    (fg, bg)
-                } else {
-                    let fg = Self::ansi_color_to_hsla(cached_cell.fg);
-                    let bg = if cached_cell.bg == AnsiColor::Named(NamedColor::Background) {
-                        None
-                    } else {
-                        Some(Self::ansi_color_to_hsla(cached_cell.bg))
-                    };
-                    (fg, bg)
-                };
-
-                // Only show cursor if SHOW_CURSOR mode is enabled
-                let is_cursor = cursor_visible && is_cursor_line && col_idx == cursor_col;
-                let is_selected = selection
-                    .filter(|sel| sel.start != sel.end)
-                    .map(|sel| sel.contains(actual_line, col_idx))
-                    .unwrap_or(false);
-
-                let c = if cached_cell.c == ' ' || cached_cell.c == '\0' {
-                    ' '
-                } else {
-                    cached_cell.c
-                };
-
-                let is_wide_char = cached_cell.flags.contains(CellFlags::WIDE_CHAR);
-                let is_wide_spacer = cached_cell.flags.contains(CellFlags::WIDE_CHAR_SPACER);
-
-                // Check if this cell is part of a detected URL
-                let mut is_url = false;
-                let mut is_url_hovered = false;
-                for (url_idx, url) in self.detected_urls.iter().enumerate() {
-                    if url.contains_point(line_idx, col_idx) {
-                        is_url = true;
-                        is_url_hovered = hovered_url_index == Some(url_idx);
-                        break;
-                    }
-                }
+        let cursor = match self.copy_mode {
+            Some(mode) => Some((mode.cursor.0 + cached.display_offset, mode.cursor.1)),
+            None => cached
+                .cursor_visible
+                .then_some((cursor_line + cached.display_offset, cursor_col)),
+        };
+        let context = RowContext {
+            display_offset: cached.display_offset,
+            cursor,
+            selection: self.selection.filter(|sel| sel.start != sel.end),
+            urls: &self.detected_urls,
+            hovered_url_index: self.hovered_url_index,
+        };
+        let (cells, _) = self.row_layouts.rows(cached, &context);
+
+        // The preedit goes at the cursor even when a program hides it
+        let preedit = self
+            .cursor_display_position()
+            .filter(|_| !self.preedit_text.is_empty())
+            .map(|(line, col)| PreeditLayout {
+                text: self.preedit_text.clone(),
+                line,
+                col,
+            });
 
-                row_cells.push(CellData {
-                    c,
-                    fg,
-                    bg,
-                    is_cursor,
-                    is_selected,
-                    is_wide_char,
-                    is_wide_spacer,
-                    is_url,
-                    is_url_hovered,
-                });
-            }
+        let prompt_rows = self
+            .prompt_lines
+            .iter()
+            .filter_map(|&line| usize::try_from(line + cached.display_offset).ok())
+            .filter(|&row| row < cached.lines)
+            .collect();
 
-            cells.push(row_cells);
-        }
+        Some(TerminalLayout {
+            cells,
+            cell_width,
+            line_height,
+            preedit,
+            prompt_rows,
+        })
+    }
 
-        TerminalLayout {
+    /// Layout of the filtered view: matching rows read straight from the
+    /// grid, with separators for the runs between them. Matches the next
+    /// budget of rows first. None while nothing is filtered.
+    fn build_filtered_layout(
+        &mut self,
+        cell_width: Pixels,
+        line_height: Pixels,
+    ) -> Option<TerminalLayout> {
+        let (Some(filter), Some(terminal), Some(cached)) = (
+            self.filter.as_mut(),
+            self.terminal.as_ref(),
+            self.cached_content.as_ref(),
+        ) else {
+            return None;
+        };
+        let history_size = cached.history_size as i32;
+        let context = RowContext {
+            display_offset: 0,
+            cursor: None,
+            selection: None,
+            urls: &[],
+            hovered_url_index: None,
+        };
+        let cells = terminal.with_term(|term| {
+            let grid = term.grid();
+            filter.scan(|row| glance::row_text(grid, row as i32 - history_size));
+            let entries = filter.visible(cached.lines)?;
+            Some(
+                entries
+                    .into_iter()
+                    .enumerate()
+                    .map(|(line_idx, entry)| {
+                        Rc::new(match entry {
+                            FilterEntry::Row(row) => {
+                                let row = &grid[Line(row as i32 - history_size)];
+                                let cells: Vec<CachedCell> = (0..cached.cols)
+                                    .map(|col| CachedCell::from(&row[Column(col)]))
+                                    .collect();
+                                context.build_row(&cells, line_idx)
+                            }
+                            FilterEntry::Hidden(count) => {
+                                separator_row(&lines_label(count, "hidden"), cached.cols)
+                            }
+                            FilterEntry::Pending(count) => {
+                                separator_row(&lines_label(count, "not filtered yet"), cached.cols)
+                            }
+                        })
+                    })
+                    .collect(),
+            )
+        })?;
+        Some(TerminalLayout {
             cells,
             cell_width,
             line_height,
-            preedit_text: self.preedit_text.clone(),
-        }
+            preedit: None,
+            prompt_rows: Vec::new(),
+        })
+    }
+
+    /// Whether the filter still has rows to match (another frame is needed)
+    pub(super) fn is_filter_pending(&self) -> bool {
+        self.filter.as_ref().is_some_and(TerminalFilter::is_pending)
+    }
+
+    /// Display (line, column) of the cursor, if it is on screen
+    fn cursor_display_position(&self) -> Option<(usize, usize)> {
+        let cached = self.cached_content.as_ref()?;
+        let line = usize::try_from(cached.cursor.0 + cached.display_offset).ok()?;
+        (line < cached.lines).then_some((line, cached.cursor.1))
     }
 }
 
@@ -867,8 +2115,9 @@ impl EntityInputHandler for TerminalView {
         }
     }
 
-    fn unmark_text(&mut self, _window: &mut Window, _cx: &mut Context<Self>) {
+    fn unmark_text(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
         self.preedit_text.clear();
+        cx.notify();
     }
 
     fn replace_text_in_range(
@@ -880,7 +2129,7 @@ impl EntityInputHandler for TerminalView {
     ) {
         // Clear preedit and send committed text to terminal
         self.preedit_text.clear();
-        if !text.is_empty() {
+        if !text.is_empty() && self.copy_mode.is_none() {
             self.write_to_terminal(text.as_bytes());
         }
         cx.notify();
@@ -901,13 +2150,24 @@ impl EntityInputHandler for TerminalView {
 
     fn bounds_for_range(
         &mut self,
-        _range_utf16: Range<usize>,
+        range_utf16: Range<usize>,
         bounds: Bounds<Pixels>,
         _window: &mut Window,
         _cx: &mut Context<Self>,
     ) -> Option<Bounds<Pixels>> {
-        // Return bounds for IME candidate window positioning
-        Some(bounds)
+        // The cell the range starts at within the preedit drawn at the
+        // cursor, so the candidate window opens next to the caret
+        let Some((line, col)) = self.cursor_display_position() else {
+            return Some(bounds);
+        };
+        let prefix = utf16_prefix(&self.preedit_text, range_utf16.start);
+        let col = col + preedit_columns(prefix).1;
+        let x = bounds.origin.x + px(TERMINAL_PADDING + col as f32 * self.cell_width);
+        let y = bounds.origin.y + px(TERMINAL_PADDING + line as f32 * self.cell_height);
+        Some(Bounds::new(
+            point(x, y),
+            size(px(self.cell_width), px(self.cell_height)),
+        ))
     }
 
     fn character_index_for_point(
@@ -934,11 +2194,39 @@ impl Render for TerminalView {
                 .into_any_element();
         }
 
+        let input_backed_up = self
+            .terminal
+            .as_ref()
+            .is_some_and(|terminal| terminal.input_backed_up());
+        let thumb = self
+            .cached_content
+            .as_ref()
+            .and_then(|c| scrollbar_thumb(c.history_size, c.lines, c.display_offset as usize));
+        let filtered = self.is_filtered();
+        let thumb = thumb.filter(|_| !filtered);
+        let scrolled_back = self.is_scrolled_back();
+        let unseen_lines = self.follow.unseen_lines();
+        let copy_mode = self.is_copy_mode();
+        let filter_editing = self.filter.as_ref().is_some_and(|f| f.editing);
+        let code_blocks = self.code_blocks.as_ref().map(|found| {
+            let lines = self.cached_content.as_ref().map_or(0, |c| c.lines);
+            found.visible(self.display_offset() as i32, lines)
+        });
+
         // Outer div handles focus, key context, and events
-        // Uses flex_col layout so children can use flex_1 to fill
+        // Uses flex_col layout so children can use flex_1 to fill.
+        // Copy mode and the filter input switch the key context so no
+        // terminal binding writes to the PTY, and take every key press
+        // themselves.
         div()
             .id("terminal-view")
-            .key_context("Terminal")
+            .key_context(if copy_mode {
+                "TerminalCopyMode"
+            } else if filter_editing {
+                "TerminalFilter"
+            } else {
+                "Terminal"
+            })
             .track_focus(&self.focus_handle)
             .flex_1()
             .w_full()
@@ -1035,31 +2323,53 @@ impl Render for TerminalView {
             .on_action(cx.listener(Self::on_ctrl_down))
             .on_action(cx.listener(Self::on_ctrl_left))
             .on_action(cx.listener(Self::on_ctrl_right))
+            .on_action(cx.listener(Self::on_ctrl_end))
             // Ctrl+Shift keys
             .on_action(cx.listener(Self::on_ctrl_shift_up))
             .on_action(cx.listener(Self::on_ctrl_shift_down))
             .on_action(cx.listener(Self::on_ctrl_shift_left))
             .on_action(cx.listener(Self::on_ctrl_shift_right))
+            .on_action(cx.listener(Self::on_ctrl_shift_a))
             .on_action(cx.listener(Self::on_ctrl_shift_c))
             .on_action(cx.listener(Self::on_ctrl_shift_v))
-            // Ctrl+Alt+arrow keys
-            .on_action(cx.listener(Self::on_ctrl_alt_up))
-            .on_action(cx.listener(Self::on_ctrl_alt_down))
-            .on_action(cx.listener(Self::on_ctrl_alt_left))
-            .on_action(cx.listener(Self::on_ctrl_alt_right))
+            .on_action(cx.listener(Self::on_toggle_copy_mode))
+            .on_action(cx.listener(Self::on_toggle_filter))
+            .on_action(cx.listener(Self::on_toggle_code_blocks))
+            .on_action(cx.listener(Self::on_previous_prompt))
+            .on_action(cx.listener(Self::on_next_prompt))
+            .on_action(cx.listener(Self::on_copy_last_output))
+            .when(copy_mode, |el| {
+                el.on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                    cx.stop_propagation();
+                    this.handle_copy_mode_key(&event.keystroke, cx);
+                }))
+            })
+            .when(filter_editing && !copy_mode, |el| {
+                el.on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                    cx.stop_propagation();
+                    this.handle_filter_key(&event.keystroke, cx);
+                }))
+            })
             .on_mouse_down(
                 MouseButton::Left,
                 cx.listener(move |this, event: &gpui::MouseDownEvent, window, cx| {
                     window.focus(&this.focus_handle, cx);
                     let x: f32 = event.position.x.into();
                     let y: f32 = event.position.y.into();
-                    let ctrl = event.modifiers.control;
-                    this.handle_mouse_down(x, y, ctrl, cx);
+                    let modifiers = event.modifiers;
+                    this.handle_mouse_down(x, y, modifiers.control, modifiers.alt, cx);
                 }),
             )
             .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _window, cx| {
                 let x: f32 = event.position.x.into();
                 let y: f32 = event.position.y.into();
+                if this.scrollbar_dragging {
+                    if event.pressed_button == Some(MouseButton::Left) {
+                        this.scroll_to_scrollbar_position(y, cx);
+                        return;
+                    }
+                    this.scrollbar_dragging = false;
+                }
                 if this.is_dragging {
                     this.handle_mouse_drag(x, y, cx);
                 }
@@ -1073,13 +2383,12 @@ impl Render for TerminalView {
             .on_mouse_up(
                 MouseButton::Left,
                 cx.listener(|this, _event: &gpui::MouseUpEvent, _window, cx| {
+                    this.scrollbar_dragging = false;
                     this.handle_mouse_up(cx);
                 }),
             )
             .on_scroll_wheel(cx.listener(|this, event: &ScrollWheelEvent, _window, cx| {
-                let delta = event.delta.pixel_delta(Pixels::from(16.0));
-                let y: f32 = delta.y.into();
-                this.handle_scroll(y, cx);
+                this.handle_scroll(event, cx);
             }))
             .child(
                 // Wrapper div as flex container for proper layout propagation
@@ -1088,9 +2397,643 @@ impl Render for TerminalView {
                     .w_full()
                     .flex()
                     .flex_col()
+                    .relative()
                     .bg(rgb(BG_BASE))
-                    .child(TerminalElement::new(cx.entity())),
+                    .child(TerminalElement::new(cx.entity()))
+                    .when(self.bell_flash, |el| {
+                        el.child(
+                            div()
+                                .absolute()
+                                .inset_0()
+                                .border_2()
+                                .border_color(rgb(PEACH)),
+                        )
+                    })
+                    .child(
+                        div()
+                            .absolute()
+                            .top_1()
+                            .right_2()
+                            .flex()
+                            .flex_col()
+                            .items_end()
+                            .gap_1()
+                            .when(input_backed_up, |el| {
+                                el.child(
+                                    div()
+                                        .px_2()
+                                        .py_0p5()
+                                        .rounded_sm()
+                                        .bg(rgb(BG_SURFACE1))
+                                        .text_xs()
+                                        .text_color(rgb(YELLOW))
+                                        .child("Terminal not accepting input"),
+                                )
+                            })
+                            .when_some(self.filter.as_ref(), |el, filter| {
+                                el.child(self.render_filter_bar(filter, cx))
+                            })
+                            .when(code_blocks.as_ref().is_some_and(Vec::is_empty), |el| {
+                                el.child(
+                                    div()
+                                        .px_2()
+                                        .py_0p5()
+                                        .rounded_sm()
+                                        .bg(rgb(BG_SURFACE1))
+                                        .text_xs()
+                                        .text_color(rgb(TEXT_MUTED))
+                                        .child("No complete code blocks in view"),
+                                )
+                            }),
+                    )
+                    .children(
+                        code_blocks
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|(index, line)| {
+                                let block = self.code_blocks.as_ref()?.get(index)?;
+                                Some(self.render_code_block_bar(index, line, block, cx))
+                            }),
+                    )
+                    .when_some(thumb, |el, (top, height)| {
+                        el.child(
+                            div()
+                                .id("terminal-scrollbar")
+                                .absolute()
+                                .top_0()
+                                .bottom_0()
+                                .right_0()
+                                .w(px(SCROLLBAR_WIDTH))
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, event: &gpui::MouseDownEvent, _, cx| {
+                                        cx.stop_propagation();
+                                        this.scrollbar_dragging = true;
+                                        this.scroll_to_scrollbar_position(
+                                            event.position.y.into(),
+                                            cx,
+                                        );
+                                    }),
+                                )
+                                .child(
+                                    div()
+                                        .absolute()
+                                        .right(px(1.))
+                                        .w(px(SCROLLBAR_WIDTH - 2.0))
+                                        .top(relative(top))
+                                        .h(relative(height))
+                                        .min_h(px(12.))
+                                        .rounded_sm()
+                                        .bg(rgb(if scrolled_back {
+                                            TEXT_MUTED
+                                        } else {
+                                            BG_SURFACE2
+                                        })),
+                                ),
+                        )
+                    })
+                    .when(scrolled_back && unseen_lines > 0 && !filtered, |el| {
+                        el.child(
+                            div()
+                                .id("terminal-new-output")
+                                .absolute()
+                                .bottom_2()
+                                .right_4()
+                                .px_2()
+                                .py_0p5()
+                                .rounded_full()
+                                .cursor_pointer()
+                                .bg(rgb(BG_SURFACE1))
+                                .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                .text_xs()
+                                .text_color(rgb(BLUE))
+                                .on_mouse_down(
+                                    MouseButton::Left,
+                                    cx.listener(|this, _event: &gpui::MouseDownEvent, _, cx| {
+                                        cx.stop_propagation();
+                                        this.scroll_to_bottom(cx);
+                                    }),
+                                )
+                                .child(format!(
+                                    "{} new line{} ↓",
+                                    unseen_lines,
+                                    if unseen_lines == 1 { "" } else { "s" }
+                                )),
+                        )
+                    }),
             )
             .into_any_element()
     }
 }
+
+/// "· · · 42 lines hidden"
+fn lines_label(count: usize, what: &str) -> String {
+    format!(
+        "· · · {} line{} {}",
+        count,
+        if count == 1 { "" } else { "s" },
+        what
+    )
+}
+
+/// A display line showing `text` in muted color instead of grid cells
+fn separator_row(text: &str, cols: usize) -> Vec<CellData> {
+    let fg = Hsla::from(rgb(TEXT_MUTED));
+    let mut chars = text.chars();
+    (0..cols)
+        .map(|_| CellData {
+            c: chars.next().unwrap_or(' '),
+            zerowidth: Vec::new(),
+            fg,
+            bg: None,
+            is_cursor: false,
+            is_selected: false,
+            is_wide_char: false,
+            is_wide_spacer: false,
+            is_url: false,
+            is_url_hovered: false,
+        })
+        .collect()
+}
+
+impl TerminalView {
+    /// Buttons of code block `index` on display line `line`: apply it to the
+    /// file it names, or copy it
+    fn render_code_block_bar(
+        &self,
+        index: usize,
+        line: usize,
+        block: &CodeBlock,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id((id, index))
+                .px_2()
+                .rounded_sm()
+                .cursor_pointer()
+                .bg(rgb(BG_SURFACE2))
+                .hover(|el| el.text_color(rgb(BLUE)))
+                .text_color(rgb(TEXT))
+                .child(label)
+        };
+        let label = match (&block.path, block.info.is_empty()) {
+            (Some(path), _) => path.clone(),
+            (None, false) => block.info.clone(),
+            (None, true) => "code".to_string(),
+        };
+        div()
+            .absolute()
+            .top(px(TERMINAL_PADDING + line as f32 * self.cell_height))
+            .right_4()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px_1()
+            .py_0p5()
+            .rounded_sm()
+            .border_1()
+            .border_color(rgb(BLUE))
+            .bg(rgb(BG_SURFACE1))
+            .text_xs()
+            .child(div().px_1().text_color(rgb(TEXT_MUTED)).child(label))
+            .when(block.path.is_some(), |el| {
+                el.child(button("code-block-apply", "Apply to file…").on_mouse_down(
+                    MouseButton::Left,
+                    cx.listener(move |this, _event: &gpui::MouseDownEvent, window, cx| {
+                        cx.stop_propagation();
+                        this.apply_code_block(index, window, cx);
+                    }),
+                ))
+            })
+            .child(button("code-block-copy", "Copy").on_mouse_down(
+                MouseButton::Left,
+                cx.listener(move |this, _event: &gpui::MouseDownEvent, _, cx| {
+                    cx.stop_propagation();
+                    this.copy_code_block(index, cx);
+                }),
+            ))
+    }
+
+    /// The filter input with the query and what it found; clicking it while
+    /// the keys go to the program edits the query again
+    fn render_filter_bar(&self, filter: &TerminalFilter, cx: &Context<Self>) -> impl IntoElement {
+        let status = if filter.invalid {
+            Some(("invalid regex".to_string(), RED))
+        } else if filter.is_pending() {
+            Some(("filtering…".to_string(), TEXT_MUTED))
+        } else if filter.is_filtering() {
+            let matched = filter.matched_rows();
+            Some((
+                format!("{} row{}", matched, if matched == 1 { "" } else { "s" }),
+                TEXT_MUTED,
+            ))
+        } else {
+            None
+        };
+        div()
+            .id("terminal-filter")
+            .flex()
+            .items_center()
+            .gap_2()
+            .px_2()
+            .py_0p5()
+            .rounded_sm()
+            .border_1()
+            .border_color(rgb(if filter.editing { BLUE } else { BG_SURFACE2 }))
+            .bg(rgb(BG_SURFACE1))
+            .text_xs()
+            .cursor_pointer()
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, _event: &gpui::MouseDownEvent, window, cx| {
+                    cx.stop_propagation();
+                    window.focus(&this.focus_handle, cx);
+                    if let Some(filter) = this.filter.as_mut() {
+                        filter.editing = true;
+                    }
+                    cx.notify();
+                }),
+            )
+            .child(div().text_color(rgb(TEXT_MUTED)).child("Filter"))
+            .child(if filter.input.is_empty() {
+                div().text_color(rgb(TEXT_MUTED)).child("text or /regex/")
+            } else {
+                div().text_color(rgb(TEXT)).child(format!(
+                    "{}{}",
+                    filter.input,
+                    if filter.editing { "▏" } else { "" }
+                ))
+            })
+            .when_some(status, |el, (status, color)| {
+                el.child(div().text_color(rgb(color)).child(status))
+            })
+    }
+}
+
+/// Width of the scrollback scrollbar (inside the terminal padding)
+const SCROLLBAR_WIDTH: f32 = 6.0;
+
+/// Scrollbar thumb as (top, height) fractions of the track, or None while
+/// there is no scrollback
+fn scrollbar_thumb(history: usize, lines: usize, display_offset: usize) -> Option<(f32, f32)> {
+    if history == 0 || lines == 0 {
+        return None;
+    }
+    let total = (history + lines) as f32;
+    let top = history.saturating_sub(display_offset) as f32 / total;
+    Some((top, lines as f32 / total))
+}
+
+/// Display offset that centers the view on `fraction` of the scrollbar track
+fn offset_at_fraction(fraction: f32, history: usize, lines: usize) -> usize {
+    let total = (history + lines) as f32;
+    let top = fraction.clamp(0.0, 1.0) * total - lines as f32 / 2.0;
+    history - top.round().clamp(0.0, history as f32) as usize
+}
+
+/// The start of `text` spanning `utf16_len` UTF-16 code units (IME ranges
+/// are in UTF-16)
+fn utf16_prefix(text: &str, utf16_len: usize) -> &str {
+    let mut units = 0;
+    for (index, c) in text.char_indices() {
+        if units >= utf16_len {
+            return &text[..index];
+        }
+        units += c.len_utf16();
+    }
+    text
+}
+
+/// Bytes written for a paste of `text`. Bracketed pastes drop ESC so the
+/// text cannot end the paste early; plain pastes send line breaks as Enter.
+fn paste_bytes(text: &str, bracketed: bool) -> Vec<u8> {
+    if bracketed {
+        let mut bytes = b"\x1b[200~".to_vec();
+        bytes.extend(text.replace('\x1b', "").bytes());
+        bytes.extend_from_slice(b"\x1b[201~");
+        bytes
+    } else {
+        text.replace("\r\n", "\r").replace('\n', "\r").into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::Config as TermConfig;
+    use alacritty_terminal::term::test::TermSize;
+    use alacritty_terminal::vte::ansi::{Processor, StdSyncHandler};
+
+    fn grid_cell<'a>(rows: &[&str]) -> impl Fn(i32, usize) -> CellText<'a> {
+        move |line, col| CellText {
+            c: Some(rows[line as usize].chars().nth(col).unwrap_or(' ')),
+            ..CellText::default()
+        }
+    }
+
+    #[test]
+    fn test_block_selection_contains_column_range_only() {
+        let selection = TerminalSelection {
+            kind: SelectionKind::Block,
+            ..TerminalSelection::new((3, 6), (1, 2))
+        };
+        assert!(selection.contains(2, 2));
+        assert!(selection.contains(1, 6));
+        assert!(!selection.contains(2, 0));
+        assert!(!selection.contains(2, 7));
+        assert!(!selection.contains(0, 4));
+
+        let linear = TerminalSelection::new((1, 2), (3, 6));
+        assert!(linear.contains(2, 0));
+        assert!(linear.contains(2, 9));
+    }
+
+    #[test]
+    fn test_block_selection_text_pads_short_lines() {
+        let rows = ["abcdef", "ab    ", "abcd  "];
+        let selection = TerminalSelection {
+            kind: SelectionKind::Block,
+            ..TerminalSelection::new((0, 1), (2, 3))
+        };
+        assert_eq!(selection.text(6, 0..=2, grid_cell(&rows)), "bcd\nb  \nbcd");
+
+        let selection = TerminalSelection::new((0, 4), (1, 5));
+        assert_eq!(selection.text(6, 0..=2, grid_cell(&rows)), "ef\nab");
+    }
+
+    #[test]
+    fn test_preedit_advances_by_display_width() {
+        let (columns, width) = preedit_columns("aかe\u{301}😀");
+        let offsets: Vec<(&str, usize, usize)> = columns
+            .iter()
+            .map(|(chars, offset, cells)| (chars.as_str(), *offset, *cells))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![("a", 0, 1), ("か", 1, 2), ("e\u{301}", 3, 1), ("😀", 4, 2)]
+        );
+        assert_eq!(width, 6);
+
+        // IME ranges count UTF-16 units; the emoji is two of them
+        assert_eq!(utf16_prefix("aか😀b", 2), "aか");
+        assert_eq!(utf16_prefix("aか😀b", 4), "aか😀");
+        assert_eq!(utf16_prefix("aか", 10), "aか");
+    }
+
+    #[test]
+    fn test_scrollbar_thumb_follows_display_offset() {
+        assert_eq!(scrollbar_thumb(0, 24, 0), None);
+        // At the bottom the thumb sits at the end of the track
+        assert_eq!(scrollbar_thumb(76, 24, 0), Some((0.76, 0.24)));
+        assert_eq!(scrollbar_thumb(76, 24, 76), Some((0.0, 0.24)));
+
+        assert_eq!(offset_at_fraction(0.0, 76, 24), 76);
+        assert_eq!(offset_at_fraction(1.0, 76, 24), 0);
+        assert_eq!(offset_at_fraction(0.5, 76, 24), 38);
+        assert_eq!(offset_at_fraction(2.0, 76, 24), 0);
+    }
+
+    #[test]
+    fn test_paste_bytes_brackets_and_strips_escape() {
+        assert_eq!(
+            paste_bytes("a\n\x1b[201~b", true),
+            b"\x1b[200~a\n[201~b\x1b[201~".to_vec()
+        );
+        assert_eq!(paste_bytes("a\r\nb\nc", false), b"a\rb\rc".to_vec());
+    }
+
+    /// Layout of every row built from scratch, as before row caching
+    fn full_layout(cached: &CachedContent, context: &RowContext) -> Vec<Vec<CellData>> {
+        cached
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(line_idx, row)| context.build_row(row, line_idx))
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_layout_matches_full_rebuild() {
+        let mut term = Term::new(TermConfig::default(), &TermSize::new(40, 8), VoidListener);
+        let mut parser: Processor<StdSyncHandler> = Processor::new();
+        let script: &[&[u8]] = &[
+            b"hello\r\nworld\r\n",
+            b"\x1b[2;1Hchanged",
+            b"\x1b[8;1H\x1b[31mred https://example.com/x\x1b[0m",
+            b"\x1b[5;10Hx",
+            b"\x1b[?25l\x1b[5;11Hy",
+            b"\x1b[?25h\x1b[8;1H\r\nscrolled\r\nagain\r\n",
+            b"\x1b[7m inverse \x1b[0m\xe3\x81\x82wide",
+            b"\x1b[H\x1b[2J",
+            b"after clear https://example.org",
+        ];
+
+        let mut cached = CachedContent::capture(&term);
+        let mut layouts = RowLayoutCache::default();
+        let (mut copied, mut full_copies) = (0, 0);
+
+        for (step, chunk) in script.iter().enumerate() {
+            parser.advance(&mut term, chunk);
+            if step == 6 {
+                term.scroll_display(alacritty_terminal::grid::Scroll::Delta(2));
+            }
+            copied += cached.update(&term);
+
+            let fresh = CachedContent::capture(&term);
+            full_copies += fresh.lines;
+            assert_eq!(
+                cached.cells, fresh.cells,
+                "cells differ after step {}",
+                step
+            );
+            assert_eq!(cached.cursor, fresh.cursor);
+
+            let urls = detect_urls(&fresh);
+            let context = RowContext {
+                display_offset: fresh.display_offset,
+                cursor: fresh
+                    .cursor_visible
+                    .then_some((fresh.cursor.0 + fresh.display_offset, fresh.cursor.1)),
+                selection: (step % 3 == 1).then(|| TerminalSelection::new((1, 3), (2, 5))),
+                urls: &urls,
+                hovered_url_index: (step % 2 == 0 && !urls.is_empty()).then_some(0),
+            };
+            let (rows, _) = layouts.rows(&cached, &context);
+            let rows: Vec<Vec<CellData>> = rows.iter().map(|row| row.as_ref().clone()).collect();
+            assert_eq!(
+                rows,
+                full_layout(&fresh, &context),
+                "layout differs after step {}",
+                step
+            );
+        }
+
+        assert!(
+            copied < full_copies,
+            "copied {} rows, a full copy per step is {}",
+            copied,
+            full_copies
+        );
+    }
+
+    #[test]
+    fn test_only_changed_rows_are_copied_and_rebuilt() {
+        let mut term = Term::new(TermConfig::default(), &TermSize::new(20, 6), VoidListener);
+        let mut parser: Processor<StdSyncHandler> = Processor::new();
+        parser.advance(&mut term, b"one\r\ntwo\r\nthree");
+
+        let mut cached = CachedContent::capture(&term);
+        let mut layouts = RowLayoutCache::default();
+        let urls = Vec::new();
+        let context = |cached: &CachedContent| RowContext {
+            display_offset: cached.display_offset,
+            cursor: None,
+            selection: None,
+            urls: &urls,
+            hovered_url_index: None,
+        };
+        let (_, built) = layouts.rows(&cached, &context(&cached));
+        assert_eq!(built, 6);
+
+        parser.advance(&mut term, b"\x1b[5;1Hfive");
+        assert_eq!(cached.update(&term), 1);
+        let (_, built) = layouts.rows(&cached, &context(&cached));
+        assert_eq!(built, 1);
+
+        // Nothing changed: nothing is copied or rebuilt
+        assert_eq!(cached.update(&term), 0);
+        assert_eq!(layouts.rows(&cached, &context(&cached)).1, 0);
+
+        // A resize recopies everything
+        term.resize(TermSize::new(24, 6));
+        assert_eq!(cached.update(&term), 6);
+    }
+
+    /// A 10 column terminal after `input`
+    fn narrow_term(input: &str) -> Term<VoidListener> {
+        let mut term = Term::new(TermConfig::default(), &TermSize::new(10, 2), VoidListener);
+        let mut parser: Processor<StdSyncHandler> = Processor::new();
+        parser.advance(&mut term, input.as_bytes());
+        term
+    }
+
+    fn line_text(term: &Term<VoidListener>, selection: TerminalSelection) -> String {
+        selection.text(10, 0..=1, |line, col| {
+            CellText::of(&term.grid()[AlacPoint::new(Line(line), Column(col))])
+        })
+    }
+
+    fn plain_context(
+        cursor: Option<(i32, usize)>,
+        selection: Option<TerminalSelection>,
+    ) -> RowContext<'static> {
+        RowContext {
+            display_offset: 0,
+            cursor,
+            selection,
+            urls: &[],
+            hovered_url_index: None,
+        }
+    }
+
+    #[test]
+    fn test_cell_widths_of_wide_and_combining_text() {
+        // What each cell draws and how many columns it takes; the spacer after
+        // a wide character draws nothing
+        let cases: &[(&str, &[(&str, usize)], usize)] = &[
+            (
+                "👩\u{200d}👩\u{200d}👧\u{200d}👦",
+                &[
+                    ("👩\u{200d}", 2),
+                    ("", 0),
+                    ("👩\u{200d}", 2),
+                    ("", 0),
+                    ("👧\u{200d}", 2),
+                    ("", 0),
+                    ("👦", 2),
+                    ("", 0),
+                ],
+                8,
+            ),
+            ("ｱｲｳ", &[("ｱ", 1), ("ｲ", 1), ("ｳ", 1)], 3),
+            ("e\u{301}x", &[("e\u{301}", 1), ("x", 1)], 2),
+            ("┌─┐│", &[("┌", 1), ("─", 1), ("┐", 1), ("│", 1)], 4),
+        ];
+
+        for (input, cells, cursor_col) in cases {
+            let term = narrow_term(input);
+            let cached = CachedContent::capture(&term);
+            assert_eq!(cached.cursor, (0, *cursor_col), "cursor after {:?}", input);
+
+            let row = plain_context(None, None).build_row(&cached.cells[0], 0);
+            let layout: Vec<(String, usize)> = row
+                .iter()
+                .map(|cell| match (cell.is_wide_char, cell.is_wide_spacer) {
+                    (_, true) => (String::new(), 0),
+                    (wide, false) => (
+                        std::iter::once(cell.c)
+                            .chain(cell.zerowidth.iter().copied())
+                            .collect(),
+                        if wide { 2 } else { 1 },
+                    ),
+                })
+                .take(cells.len())
+                .collect();
+            let expected: Vec<(String, usize)> = cells
+                .iter()
+                .map(|(text, width)| (text.to_string(), *width))
+                .collect();
+            assert_eq!(layout, expected, "cells of {:?}", input);
+            assert!(row[cells.len()..].iter().all(|cell| cell.c == ' '));
+
+            // Copying the line gives back what was printed
+            let whole = TerminalSelection::new((0, 0), (0, 9));
+            assert_eq!(line_text(&term, whole), *input);
+        }
+    }
+
+    #[test]
+    fn test_selection_never_splits_a_wide_character() {
+        let term = narrow_term("a😀b");
+        let cached = CachedContent::capture(&term);
+
+        // Either half alone selects the emoji
+        assert_eq!(
+            line_text(&term, TerminalSelection::new((0, 1), (0, 1))),
+            "😀"
+        );
+        assert_eq!(
+            line_text(&term, TerminalSelection::new((0, 2), (0, 2))),
+            "😀"
+        );
+        assert_eq!(
+            line_text(&term, TerminalSelection::new((0, 2), (0, 3))),
+            "😀b"
+        );
+        let block = TerminalSelection {
+            kind: SelectionKind::Block,
+            ..TerminalSelection::new((0, 0), (1, 1))
+        };
+        assert_eq!(line_text(&term, block), "a😀\n  ");
+
+        // Highlighted as one, and so is the cursor on its right half
+        let selected = Some(TerminalSelection::new((0, 2), (0, 2)));
+        let row = plain_context(Some((0, 2)), selected).build_row(&cached.cells[0], 0);
+        let marked: Vec<(bool, bool)> = row[..4]
+            .iter()
+            .map(|cell| (cell.is_selected, cell.is_cursor))
+            .collect();
+        assert_eq!(
+            marked,
+            [(false, false), (true, true), (true, true), (false, false)]
+        );
+
+        // Pointing at the right half points at the emoji
+        assert_eq!(cached.char_column(0, 2), 1);
+        assert_eq!(cached.char_column(0, 1), 1);
+        assert_eq!(cached.char_column(0, 3), 3);
+        assert_eq!(cached.char_column(5, 2), 2);
+    }
+}
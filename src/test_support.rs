@@ -0,0 +1,68 @@
+//! Git repositories for tests
+
+use std::path::{Path, PathBuf};
+
+/// Config file `git config --global` reads and writes in tests (see
+/// `git::git_command`)
+pub fn global_config() -> PathBuf {
+    std::env::temp_dir().join(format!("sashiki-test-{}.gitconfig", std::process::id()))
+}
+
+/// Run git in `dir` and return its output, panicking on failure
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Create a repository at `dir` (made if needed) on `main`, with one
+/// commit containing `README.md`
+pub fn init_repo_at(dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q", "-b", "main"]);
+    git(dir, &["config", "user.name", "Test"]);
+    git(dir, &["config", "user.email", "test@example.com"]);
+    std::fs::write(dir.join("README.md"), "hello\n").unwrap();
+    git(dir, &["add", "."]);
+    git(dir, &["commit", "-q", "-m", "initial"]);
+}
+
+/// A repository with one commit containing `README.md`
+pub fn init_repo() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    init_repo_at(dir.path());
+    dir
+}
+
+/// Check a new branch `branch` out in a linked worktree at `path`
+pub fn add_worktree(repo: &Path, branch: &str, path: &Path) {
+    git(
+        repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            branch,
+            path.to_str().unwrap(),
+        ],
+    );
+}
+
+/// Repository with a `feature` branch checked out in the linked worktree
+/// `wt-feature` inside it
+pub fn init_repo_with_feature_worktree() -> (tempfile::TempDir, PathBuf) {
+    let dir = init_repo();
+    let worktree = dir.path().join("wt-feature");
+    add_worktree(dir.path(), "feature", &worktree);
+    (dir, worktree)
+}
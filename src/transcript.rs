@@ -0,0 +1,497 @@
+//! Opt-in transcripts of terminal output
+//!
+//! While recording is on for a session, everything its terminals print is
+//! appended as raw bytes to `.git/sashiki/transcripts/<session>/<millis>.log`.
+//! The bytes are teed on the PTY reader thread into a buffered file, so the
+//! terminal never waits on the disk. Each terminal writes its own file; a file
+//! is rotated at `MAX_FILE_BYTES` and only the newest `MAX_FILES` of a session
+//! are kept. A write error (e.g. a full disk) turns recording off for the
+//! session with a warning in the app log.
+//!
+//! The transcript dialog shows all files of a session oldest first, with
+//! escape sequences stripped (see `strip_ansi`).
+
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::ui::virtual_rows::VisibleWindow;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Whether terminal output can be teed on this platform (the ConPTY reader
+/// cannot be shared with the event loop)
+pub const SUPPORTED: bool = cfg!(unix);
+
+/// A transcript file is rotated once it reaches this size
+pub const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Transcript files kept per session; older ones are deleted
+pub const MAX_FILES: usize = 8;
+
+/// Buffered output is flushed at least this often, so the dialog sees it
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Directory of a session's transcripts inside the shared `.git` directory
+pub fn transcript_dir(git_dir: &Path, session_name: &str) -> PathBuf {
+    git_dir
+        .join("sashiki")
+        .join("transcripts")
+        .join(session_name)
+}
+
+/// Recording switch and directory of one session, shared with the reader
+/// threads of its terminals
+#[derive(Debug, Default)]
+pub struct SessionTranscript {
+    name: String,
+    dir: Mutex<Option<PathBuf>>,
+    enabled: AtomicBool,
+}
+
+impl SessionTranscript {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Record into `dir` when `enabled` (the git config setting)
+    pub fn configure(&self, dir: PathBuf, enabled: bool) {
+        if let Ok(mut current) = self.dir.lock() {
+            *current = Some(dir);
+        }
+        self.set_enabled(enabled);
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.lock().ok().and_then(|dir| dir.clone())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        SUPPORTED && self.enabled.load(Ordering::Acquire)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Release);
+    }
+}
+
+/// Transcript file being written
+struct OpenTranscript {
+    writer: BufWriter<File>,
+    written: u64,
+    last_flush: Instant,
+}
+
+/// Writes one terminal's output to the session's transcript directory
+pub struct TranscriptRecorder {
+    session: Arc<SessionTranscript>,
+    file: Option<OpenTranscript>,
+}
+
+impl TranscriptRecorder {
+    pub fn new(session: Arc<SessionTranscript>) -> Self {
+        Self {
+            session,
+            file: None,
+        }
+    }
+
+    /// Append `data` if recording is on. A failed write turns recording off.
+    pub fn record(&mut self, data: &[u8]) {
+        if !self.session.is_enabled() {
+            // Close the file so turning recording back on starts a new one
+            self.close();
+            return;
+        }
+        if data.is_empty() {
+            return;
+        }
+        if let Err(e) = self.write(data) {
+            self.file = None;
+            self.session.set_enabled(false);
+            app_log::record(LogEntry::new(
+                LogLevel::Warn,
+                LogSource::Terminal,
+                format!(
+                    "Stopped recording the transcript of {}: {}",
+                    self.session.name, e
+                ),
+            ));
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let dir = self
+                    .session
+                    .dir()
+                    .ok_or_else(|| io::Error::other("no transcript directory"))?;
+                self.file.insert(open_transcript(&dir)?)
+            }
+        };
+
+        file.writer.write_all(data)?;
+        file.written += data.len() as u64;
+        if file.written >= MAX_FILE_BYTES {
+            file.writer.flush()?;
+            self.file = None;
+        } else if file.last_flush.elapsed() >= FLUSH_INTERVAL {
+            file.writer.flush()?;
+            file.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let _ = file.writer.flush();
+        }
+    }
+}
+
+/// Start a new transcript file in `dir`, deleting the oldest files over
+/// `MAX_FILES`
+fn open_transcript(dir: &Path) -> io::Result<OpenTranscript> {
+    std::fs::create_dir_all(dir)?;
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    // Terminals started in the same millisecond take the next free name
+    let mut stamp = millis;
+    let file = loop {
+        match OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(dir.join(format!("{}.log", stamp)))
+        {
+            Ok(file) => break file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => stamp += 1,
+            Err(e) => return Err(e),
+        }
+    };
+
+    let files = transcript_files(dir)?;
+    for old in files.iter().take(files.len().saturating_sub(MAX_FILES)) {
+        let _ = std::fs::remove_file(old);
+    }
+
+    Ok(OpenTranscript {
+        writer: BufWriter::new(file),
+        written: 0,
+        last_flush: Instant::now(),
+    })
+}
+
+/// Transcript files in `dir`, oldest first
+pub fn transcript_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<(u128, PathBuf)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .filter_map(|path| {
+            let stamp = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((stamp, path))
+        })
+        .collect();
+    files.sort();
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Reader that hands everything read to a `TranscriptRecorder`
+pub struct TeeReader<R> {
+    inner: R,
+    recorder: TranscriptRecorder,
+}
+
+impl<R> TeeReader<R> {
+    pub fn new(inner: R, recorder: TranscriptRecorder) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.recorder.record(&buf[..n]);
+        Ok(n)
+    }
+}
+
+// --- Reading transcripts ---
+
+/// Height of a line in the transcript dialog
+pub const ROW_HEIGHT: f32 = 18.0;
+
+/// Marks where the output of another terminal (or a rotated file) starts
+pub const FILE_SEPARATOR: &str = "────────";
+
+/// All transcript files in `dir` as plain text lines, oldest first
+pub fn read_transcript(dir: &Path) -> io::Result<Vec<String>> {
+    let files = match transcript_files(dir) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines = Vec::new();
+    for (i, path) in files.iter().enumerate() {
+        if i > 0 {
+            lines.push(FILE_SEPARATOR.to_string());
+        }
+        let bytes = std::fs::read(path)?;
+        lines.extend(strip_ansi(&bytes));
+    }
+    Ok(lines)
+}
+
+/// Terminal output as plain text lines.
+///
+/// Escape sequences (CSI, OSC, DCS and two-byte escapes) are dropped. A
+/// carriage return starts the line over, so progress bars keep their last
+/// state; backspace removes the previous character.
+pub fn strip_ansi(bytes: &[u8]) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum State {
+        Text,
+        Escape,
+        Csi,
+        /// Charset designation, one more character
+        Charset,
+        /// OSC/DCS/APC/PM/SOS payload, ended by BEL or ST
+        String,
+        StringEscape,
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    // A carriage return only starts the line over once more text follows,
+    // so CRLF is a plain line break
+    let mut carriage_return = false;
+    let mut state = State::Text;
+
+    for c in text.chars() {
+        state = match state {
+            State::Text => match c {
+                '\x1b' => State::Escape,
+                '\n' => {
+                    lines.push(std::mem::take(&mut line));
+                    carriage_return = false;
+                    State::Text
+                }
+                '\r' => {
+                    carriage_return = true;
+                    State::Text
+                }
+                '\x08' => {
+                    line.pop();
+                    State::Text
+                }
+                c if c.is_control() && c != '\t' => State::Text,
+                c => {
+                    if std::mem::take(&mut carriage_return) {
+                        line.clear();
+                    }
+                    line.push(c);
+                    State::Text
+                }
+            },
+            State::Escape => match c {
+                '[' => State::Csi,
+                ']' | 'P' | '_' | '^' | 'X' => State::String,
+                '(' | ')' | '*' | '+' => State::Charset,
+                _ => State::Text,
+            },
+            State::Csi if ('\x40'..='\x7e').contains(&c) => State::Text,
+            State::Csi => State::Csi,
+            State::Charset => State::Text,
+            State::String => match c {
+                '\x07' => State::Text,
+                '\x1b' => State::StringEscape,
+                _ => State::String,
+            },
+            State::StringEscape if c == '\\' => State::Text,
+            State::StringEscape => State::String,
+        };
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Indices of the lines containing `query` (ignoring case)
+pub fn find_matches(lines: &[String], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query = query.to_lowercase();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(&query))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Lines at least partly inside the viewport (no overscan)
+pub fn visible_lines(
+    len: usize,
+    row_height: f32,
+    scroll_top: f32,
+    viewport_height: f32,
+) -> Range<usize> {
+    let window = VisibleWindow::compute(len, row_height, scroll_top, viewport_height);
+    let first = (scroll_top.max(0.0) / row_height).floor() as usize;
+    let last = ((scroll_top.max(0.0) + viewport_height.max(0.0)) / row_height).ceil() as usize;
+    first.clamp(window.range.start, window.range.end)..last.clamp(first, window.range.end)
+}
+
+/// State of the transcript dialog
+#[derive(Debug, Default)]
+pub struct TranscriptBrowser {
+    pub lines: Vec<String>,
+    pub loading: bool,
+    pub query: String,
+    /// Line indices matching `query`
+    pub matches: Vec<usize>,
+    /// Index into `matches` of the current match
+    pub current: usize,
+}
+
+impl TranscriptBrowser {
+    pub fn set_lines(&mut self, lines: Vec<String>) {
+        self.lines = lines;
+        self.loading = false;
+        self.update_matches();
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+        self.update_matches();
+    }
+
+    fn update_matches(&mut self) {
+        self.matches = find_matches(&self.lines, &self.query);
+        self.current = 0;
+    }
+
+    /// Line of the current match
+    pub fn current_line(&self) -> Option<usize> {
+        self.matches.get(self.current).copied()
+    }
+
+    /// Move to the next (or previous) match, wrapping around
+    pub fn step(&mut self, forward: bool) -> Option<usize> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let len = self.matches.len();
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+        self.current_line()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording_session(dir: &Path) -> Arc<SessionTranscript> {
+        let session = Arc::new(SessionTranscript::new("s0"));
+        session.configure(dir.to_path_buf(), true);
+        session
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_recorder_writes_rotates_and_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = recording_session(dir.path());
+
+        let mut recorder = TranscriptRecorder::new(session.clone());
+        recorder.record(b"hello\r\n");
+        drop(recorder);
+        assert_eq!(read_transcript(dir.path()).unwrap(), vec!["hello"]);
+
+        // Nothing is written while recording is off
+        session.set_enabled(false);
+        let mut recorder = TranscriptRecorder::new(session.clone());
+        recorder.record(b"secret\n");
+        drop(recorder);
+        assert_eq!(transcript_files(dir.path()).unwrap().len(), 1);
+
+        // Every full file starts a new one; only MAX_FILES are kept
+        session.set_enabled(true);
+        let mut recorder = TranscriptRecorder::new(session.clone());
+        let chunk = vec![b'x'; MAX_FILE_BYTES as usize];
+        for _ in 0..MAX_FILES + 2 {
+            recorder.record(&chunk);
+        }
+        recorder.record(b"last\n");
+        drop(recorder);
+        let files = transcript_files(dir.path()).unwrap();
+        assert_eq!(files.len(), MAX_FILES);
+        assert_eq!(std::fs::read(files.last().unwrap()).unwrap(), b"last\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_error_turns_recording_off() {
+        let dir = tempfile::tempdir().unwrap();
+        // A file where the directory should be makes every open fail
+        let blocked = dir.path().join("blocked");
+        std::fs::write(&blocked, "").unwrap();
+        let session = recording_session(&blocked);
+
+        let mut reader = TeeReader::new(&b"output"[..], TranscriptRecorder::new(session.clone()));
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!(out, "output");
+        assert!(!session.is_enabled());
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let raw = b"\x1b[1;32mok\x1b[0m done\r\n\x1b]0;title\x0750%\r100%\nab\x08c\x1b(Bd\n";
+        assert_eq!(strip_ansi(raw), vec!["ok done", "100%", "acd"]);
+        assert_eq!(strip_ansi(b"\x1bP+q\x1b\\tail"), vec!["tail"]);
+    }
+
+    #[test]
+    fn test_browser_search_wraps_around() {
+        let mut browser = TranscriptBrowser::default();
+        browser.set_lines(
+            ["Error: a", "fine", "another error", "ok"]
+                .map(String::from)
+                .to_vec(),
+        );
+        browser.set_query("error".to_string());
+        assert_eq!(browser.matches, vec![0, 2]);
+        assert_eq!(browser.current_line(), Some(0));
+        assert_eq!(browser.step(true), Some(2));
+        assert_eq!(browser.step(true), Some(0));
+        assert_eq!(browser.step(false), Some(2));
+
+        browser.set_query(String::new());
+        assert_eq!(browser.step(true), None);
+    }
+
+    #[test]
+    fn test_visible_lines_excludes_overscan() {
+        assert_eq!(visible_lines(1000, 20.0, 400.0, 200.0), 20..30);
+        assert_eq!(visible_lines(25, 20.0, 400.0, 200.0), 20..25);
+        assert_eq!(visible_lines(0, 20.0, 0.0, 200.0), 0..0);
+    }
+}
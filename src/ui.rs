@@ -1,18 +1,58 @@
 //! UI components
 
+pub mod adopt;
+pub mod agents;
+pub mod bulk_create;
+pub mod code_blocks;
+pub mod command_history;
+pub mod conflicts;
 pub mod dialogs;
+pub mod diff_whitespace;
+pub mod file_follow;
 pub mod file_list;
+pub mod file_nav;
+pub mod file_probe;
+pub mod file_sync;
 pub mod file_tree;
 pub mod file_view;
+pub mod image_preview;
+pub mod list_nav;
+pub mod log_pane;
+pub mod maintenance;
+pub mod merge;
+pub mod notes;
 pub mod render;
+pub mod review_comments;
+pub mod search;
 pub mod sidebar;
+pub mod snapshots;
+pub mod soft_wrap;
+pub mod squash;
+pub mod stash;
+pub mod tab_stops;
 pub mod terminal;
+pub mod timeline;
+pub mod transcript;
+pub mod virtual_rows;
 
-pub use file_tree::{ChangeInfo, FileListMode, FileTreeNode, read_dir_shallow};
-pub use file_view::{FileView, SendToTerminalEvent};
+pub use file_tree::{
+    ChangeInfo, DirChangeSummary, EXCLUDED_GROUP, FileListMode, FileRow, FileTreeNode,
+    lazy_visible_rows, read_dir_shallow, reveal_row, summarize_dir_changes,
+};
+pub use file_view::{
+    CommentLineEvent, CompareWithSessionEvent, ConvertToUtf8Event, DeleteCommentEvent,
+    DiscardHunkEvent, EditCommentEvent, FileView, ForceFullDiffEvent, InsertReferenceEvent,
+    InsertSnippetEvent, MarkResolvedEvent, NavigateFileEvent, OpenExternalEvent,
+    OpenRepositoryEvent, ResolveConflictEvent, RevealInSidebarEvent, SendToTerminalEvent,
+    SwapComparisonEvent,
+};
+pub use list_nav::{ListNav, NavRow, NavTarget};
 
 use crate::theme::*;
-use gpui::{IntoElement, ParentElement, Styled, div, rgb};
+use gpui::{
+    AnyView, App, AppContext, Context, IntoElement, ParentElement, Render, SharedString, Styled,
+    Window, div, rgb,
+};
 
 /// Renders the "main" badge for main worktree indicator
 pub fn render_main_badge() -> impl IntoElement {
@@ -35,3 +75,41 @@ pub fn render_locked_badge() -> impl IntoElement {
         .rounded_sm()
         .child("locked")
 }
+
+/// Renders the "restored" badge for sessions whose terminals were reattached
+/// to tmux sessions left running
+pub fn render_restored_badge() -> impl IntoElement {
+    div()
+        .px_1()
+        .bg(rgb(TEAL))
+        .text_color(rgb(BG_BASE))
+        .text_xs()
+        .rounded_sm()
+        .child("restored")
+}
+
+/// Plain text tooltip
+pub struct TextTooltip(SharedString);
+
+impl TextTooltip {
+    /// Tooltip builder for `.tooltip(...)`
+    pub fn build(text: impl Into<SharedString>) -> impl Fn(&mut Window, &mut App) -> AnyView {
+        let text = text.into();
+        move |_, cx| cx.new(|_| TextTooltip(text.clone())).into()
+    }
+}
+
+impl Render for TextTooltip {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_2()
+            .py_1()
+            .bg(rgb(BG_SURFACE0))
+            .border_1()
+            .border_color(rgb(BG_SURFACE1))
+            .rounded_sm()
+            .text_xs()
+            .text_color(rgb(TEXT))
+            .child(self.0.clone())
+    }
+}
@@ -0,0 +1,240 @@
+//! Adopt dialog rendering: adding worktrees created outside Sashiki as sessions
+//! (see `crate::app::adopt`)
+
+use crate::app::SashikiApp;
+use crate::git::Worktree;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    pub fn render_adopt_dialog(
+        &self,
+        unsessioned: &[Worktree],
+        input: &str,
+        error: Option<&str>,
+        other_repository: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let button = |id: &'static str, label: &'static str, primary: bool| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(if primary { GREEN } else { BG_SURFACE1 }))
+                .hover(move |el| el.bg(rgb(if primary { TEAL } else { BG_SURFACE2 })))
+                .text_xs()
+                .text_color(rgb(if primary { BG_BASE } else { TEXT }))
+                .child(label)
+        };
+
+        div()
+            .id("adopt-dialog-container")
+            .track_focus(&self.adopt_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_adopt_dialog(window, cx);
+                } else if key == "enter" {
+                    this.submit_adopt_dialog(window, cx);
+                } else if key == "backspace" {
+                    this.edit_adopt_input(
+                        |input| {
+                            input.pop();
+                        },
+                        cx,
+                    );
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.edit_adopt_input(|input| input.push_str(text), cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("adopt-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_adopt_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("adopt-dialog")
+                            .occlude()
+                            .w(px(520.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Add Existing Worktree"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .when(!unsessioned.is_empty(), |el| {
+                                        el.child(
+                                            div()
+                                                .text_color(rgb(TEXT_SECONDARY))
+                                                .text_sm()
+                                                .child("Worktrees without a session:"),
+                                        )
+                                        .children(
+                                            unsessioned.iter().enumerate().map(|(i, worktree)| {
+                                                let adopted = worktree.clone();
+                                                div()
+                                                    .id(("adopt-worktree", i))
+                                                    .px_3()
+                                                    .py_2()
+                                                    .flex()
+                                                    .flex_col()
+                                                    .cursor_pointer()
+                                                    .rounded_sm()
+                                                    .bg(rgb(BG_SURFACE0))
+                                                    .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                                    .on_click(cx.listener(
+                                                        move |this, _, window, cx| {
+                                                            this.adopt_worktree(
+                                                                adopted.clone(),
+                                                                window,
+                                                                cx,
+                                                            );
+                                                        },
+                                                    ))
+                                                    .child(
+                                                        div()
+                                                            .text_sm()
+                                                            .text_color(rgb(TEXT))
+                                                            .child(
+                                                                worktree
+                                                                    .branch
+                                                                    .clone()
+                                                                    .unwrap_or_else(|| {
+                                                                        worktree.name.clone()
+                                                                    }),
+                                                            ),
+                                                    )
+                                                    .child(
+                                                        div()
+                                                            .text_xs()
+                                                            .text_color(rgb(TEXT_MUTED))
+                                                            .child(
+                                                                worktree.path.display().to_string(),
+                                                            ),
+                                                    )
+                                            }),
+                                        )
+                                    })
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child("Worktree directory:"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("adopt-path-input")
+                                            .w_full()
+                                            .px_3()
+                                            .py_2()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .cursor_text()
+                                            .text_color(if input.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .text_sm()
+                                            .child(if input.is_empty() {
+                                                "/path/to/worktree".to_string()
+                                            } else {
+                                                format!("{}_", input)
+                                            }),
+                                    )
+                                    .child(div().text_xs().text_color(rgb(TEXT_MUTED)).child(
+                                        "A worktree of this repository made with \
+                                                 `git worktree add`, anywhere on disk. \
+                                                 Git is not changed.",
+                                    ))
+                                    .when_some(error, |el, error| {
+                                        el.child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(rgb(RED))
+                                                .child(error.to_string()),
+                                        )
+                                    }),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(button("cancel-adopt", "Cancel", false).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.close_adopt_dialog(window, cx);
+                                        }),
+                                    ))
+                                    .when(other_repository, |el| {
+                                        el.child(
+                                            button(
+                                                "open-other-repository",
+                                                "Open Repository",
+                                                false,
+                                            )
+                                            .on_click(
+                                                cx.listener(|this, _, window, cx| {
+                                                    this.open_other_repository(window, cx);
+                                                }),
+                                            ),
+                                        )
+                                    })
+                                    .child(button("submit-adopt", "Add", true).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.submit_adopt_dialog(window, cx);
+                                        }),
+                                    )),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,134 @@
+//! Agent launcher menu rendering (see `crate::agents`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use crate::ui::TextTooltip;
+use gpui::{
+    Context, IntoElement, MouseButton, MouseDownEvent, ParentElement, Pixels, Point, Styled, div,
+    prelude::*, px, rgb,
+};
+
+/// Width of the launcher menu, which opens to the left of the click
+const MENU_WIDTH: f32 = 280.;
+
+impl SashikiApp {
+    /// Button opening the launcher menu of session `index`
+    pub fn render_agent_button(
+        &self,
+        id: impl Into<gpui::ElementId>,
+        index: usize,
+        label: &'static str,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let open = self.agents.menu.is_some_and(|(open, _)| open == index);
+        div()
+            .id(id)
+            .px_1()
+            .cursor_pointer()
+            .rounded_sm()
+            .text_xs()
+            .text_color(if open { rgb(MAUVE) } else { rgb(TEXT_MUTED) })
+            .hover(|el| el.text_color(rgb(MAUVE)))
+            .tooltip(TextTooltip::build("Launch agent…"))
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(move |this, event: &MouseDownEvent, _, cx| {
+                    cx.stop_propagation();
+                    this.toggle_agent_menu(index, event.position, cx);
+                }),
+            )
+            .on_click(|_, _, cx| cx.stop_propagation())
+            .child(label)
+    }
+
+    /// The profiles of the open launcher menu, under the point it was opened at
+    pub fn render_agent_menu(
+        &self,
+        index: usize,
+        position: Point<Pixels>,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let last = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(index)
+            .and_then(|session| self.agents.last_used(session.name()));
+        let left = (f32::from(position.x) - MENU_WIDTH).max(8.);
+
+        let items = self
+            .agents
+            .profiles
+            .iter()
+            .enumerate()
+            .map(|(i, (profile, unavailable))| {
+                let name = profile.name.clone();
+                let is_last = last == Some(profile.name.as_str());
+                div()
+                    .id(("agent-profile", i))
+                    .px_3()
+                    .py_1()
+                    .flex()
+                    .flex_col()
+                    .text_xs()
+                    .when_some(unavailable.clone(), |el, reason| {
+                        el.opacity(0.5).tooltip(TextTooltip::build(reason))
+                    })
+                    .when(unavailable.is_none(), |el| {
+                        el.cursor_pointer()
+                            .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.launch_agent(index, &name, window, cx);
+                            }))
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .justify_between()
+                            .child(div().text_color(rgb(TEXT)).child(profile.name.clone()))
+                            .when(is_last, |el| {
+                                el.child(div().text_color(rgb(TEXT_MUTED)).child("default"))
+                            }),
+                    )
+                    .child(
+                        div()
+                            .font_family(MONOSPACE_FONT)
+                            .text_color(rgb(TEXT_MUTED))
+                            .truncate()
+                            .child(profile.command_line()),
+                    )
+            });
+
+        div()
+            .id("agent-menu-overlay")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("agent-menu-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .on_mouse_down(
+                        MouseButton::Left,
+                        cx.listener(|this, _, _, cx| this.close_agent_menu(cx)),
+                    ),
+            )
+            .child(
+                div()
+                    .id("agent-menu")
+                    .occlude()
+                    .absolute()
+                    .top(position.y + px(8.))
+                    .left(px(left))
+                    .w(px(MENU_WIDTH))
+                    .bg(rgb(BG_BASE))
+                    .border_1()
+                    .border_color(rgb(BG_SURFACE1))
+                    .rounded_sm()
+                    .shadow_lg()
+                    .py_1()
+                    .children(items),
+            )
+    }
+}
@@ -0,0 +1,277 @@
+//! Bulk worktree creation dialog rendering (see `crate::bulk_create`)
+
+use crate::app::SashikiApp;
+use crate::bulk_create::ItemStatus;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    /// Task list of the bulk create dialog, then the progress of each
+    /// worktree
+    pub fn render_bulk_create_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let state = &self.bulk_create;
+        let editing = state.run.is_none();
+        let ready = state.plan.is_ready();
+        let running = state.run.as_ref().is_some_and(|run| !run.is_finished());
+        let cancelled = state.run.as_ref().is_some_and(|run| run.is_cancelled());
+
+        let mut body = div().p_4().flex().flex_col().gap_3();
+        if let Some(run) = &state.run {
+            let mut list = div()
+                .id("bulk-create-progress")
+                .max_h_96()
+                .overflow_y_scroll()
+                .flex()
+                .flex_col()
+                .gap_1();
+            for item in &run.items {
+                let (icon, color) = match &item.status {
+                    ItemStatus::Pending => ("   ", TEXT_MUTED),
+                    ItemStatus::Running => (">> ", YELLOW),
+                    ItemStatus::Done => ("OK ", GREEN),
+                    ItemStatus::Failed(_) => ("NG ", RED),
+                    ItemStatus::Skipped => ("-- ", TEXT_MUTED),
+                };
+                list = list.child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .text_xs()
+                        .child(
+                            div()
+                                .flex()
+                                .gap_2()
+                                .child(div().text_color(rgb(color)).child(icon))
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .overflow_hidden()
+                                        .text_color(rgb(color))
+                                        .child(item.worktree.branch.clone()),
+                                ),
+                        )
+                        .when_some(
+                            match &item.status {
+                                ItemStatus::Failed(error) => Some(error.clone()),
+                                _ => None,
+                            },
+                            |el, error| {
+                                el.child(div().pl_6().text_color(rgb(TEXT_MUTED)).child(error))
+                            },
+                        ),
+                );
+            }
+            body = body.child(list).when(!running, |el| {
+                el.child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(run.summary()),
+                )
+            });
+        } else {
+            let input = state.input.clone();
+            body = body
+                .child(
+                    div()
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .text_sm()
+                        .child("Branches, one per line (# starts a comment):"),
+                )
+                .child(
+                    div()
+                        .id("bulk-create-input")
+                        .w_full()
+                        .min_h_24()
+                        .max_h_64()
+                        .overflow_y_scroll()
+                        .px_3()
+                        .py_2()
+                        .bg(rgb(BG_SURFACE0))
+                        .border_1()
+                        .border_color(rgb(BLUE))
+                        .rounded_sm()
+                        .cursor_text()
+                        .font_family(MONOSPACE_FONT)
+                        .text_xs()
+                        .when(input.is_empty(), |el| {
+                            el.text_color(rgb(TEXT_MUTED))
+                                .child("feature/login")
+                                .child(div().child("fix/typo"))
+                        })
+                        .when(!input.is_empty(), |el| {
+                            let shown = format!("{}_", input);
+                            el.text_color(rgb(TEXT)).children(
+                                shown
+                                    .split('\n')
+                                    .map(|line| div().min_h_4().child(line.to_string()))
+                                    .collect::<Vec<_>>(),
+                            )
+                        }),
+                )
+                .children(state.plan.problems.iter().map(|problem| {
+                    div().text_xs().text_color(rgb(RED)).child(format!(
+                        "Line {}: {}: {}",
+                        problem.line, problem.branch, problem.reason
+                    ))
+                }))
+                .when(ready, |el| {
+                    el.child(div().text_xs().text_color(rgb(TEXT_MUTED)).child(
+                        match state.plan.worktrees.len() {
+                            1 => "1 worktree to create".to_string(),
+                            n => format!("{} worktrees to create", n),
+                        },
+                    ))
+                })
+                .child(
+                    div()
+                        .id("bulk-create-terminals")
+                        .flex()
+                        .items_center()
+                        .gap_2()
+                        .text_xs()
+                        .text_color(rgb(TEXT))
+                        .cursor_pointer()
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.toggle_bulk_create_terminals(cx);
+                        }))
+                        .child(
+                            div()
+                                .text_color(rgb(if state.start_terminals {
+                                    GREEN
+                                } else {
+                                    TEXT_MUTED
+                                }))
+                                .child(if state.start_terminals { "☑" } else { "☐" }),
+                        )
+                        .child("Show in parallel mode and start terminals"),
+                );
+        }
+
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .rounded_sm()
+                .text_xs()
+                .child(label)
+        };
+        let footer = div()
+            .px_4()
+            .py_3()
+            .border_t_1()
+            .border_color(rgb(BG_SURFACE0))
+            .flex()
+            .justify_end()
+            .gap_2()
+            .child(
+                button(
+                    "bulk-create-close",
+                    match (editing, running, cancelled) {
+                        (true, _, _) => "Cancel",
+                        (false, true, true) => "Cancelling...",
+                        (false, true, false) => "Cancel",
+                        (false, false, _) => "Close",
+                    },
+                )
+                .cursor_pointer()
+                .bg(rgb(BG_SURFACE1))
+                .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                .text_color(rgb(TEXT))
+                .on_click(cx.listener(|this, _, window, cx| {
+                    this.close_bulk_create_dialog(window, cx);
+                })),
+            )
+            .when(editing, |el| {
+                el.child(
+                    button("bulk-create-start", "Create")
+                        .when(ready, |el| {
+                            el.cursor_pointer()
+                                .bg(rgb(GREEN))
+                                .hover(|el| el.bg(rgb(TEAL)))
+                                .text_color(rgb(BG_BASE))
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.start_bulk_create(cx);
+                                }))
+                        })
+                        .when(!ready, |el| {
+                            el.bg(rgb(BG_SURFACE0)).text_color(rgb(TEXT_MUTED))
+                        }),
+                )
+            });
+
+        div()
+            .id("bulk-create-container")
+            .track_focus(&self.bulk_create_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let keystroke = &event.keystroke;
+                let key = keystroke.key.as_str();
+                let command = keystroke.modifiers.control || keystroke.modifiers.platform;
+                match key {
+                    "escape" => this.close_bulk_create_dialog(window, cx),
+                    "enter" if command => this.start_bulk_create(cx),
+                    "enter" => this.insert_bulk_create_text("\n", cx),
+                    "backspace" => this.delete_bulk_create_char(cx),
+                    "v" if command => {
+                        if let Some(pasted) = cx.read_from_clipboard().and_then(|item| item.text())
+                        {
+                            this.insert_bulk_create_text(&pasted.replace("\r\n", "\n"), cx);
+                        }
+                    }
+                    _ => {
+                        if !command
+                            && let Some(typed) = keystroke.key_char.as_deref()
+                            && !typed.chars().any(char::is_control)
+                        {
+                            this.insert_bulk_create_text(typed, cx);
+                        }
+                    }
+                }
+            }))
+            .child(
+                div()
+                    .id("bulk-create-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("bulk-create-dialog")
+                            .occlude()
+                            .w(px(480.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Create Worktrees"),
+                            )
+                            .child(body)
+                            .child(footer),
+                    ),
+            )
+            .into_any_element()
+    }
+}
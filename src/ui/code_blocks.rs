@@ -0,0 +1,162 @@
+//! Code block apply dialog rendering (see `crate::app::code_blocks`)
+
+use crate::app::SashikiApp;
+use crate::code_blocks::Suggestion;
+use crate::theme::*;
+use crate::ui::snapshots::{MAX_SNAPSHOT_DIFF_LINES, diff_line_color};
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    /// Diff a code block picked in a terminal makes to its file
+    pub fn render_apply_code_block_dialog(
+        &self,
+        suggestion: &Suggestion,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let diff = suggestion.diff.as_str();
+        let total = diff.lines().count();
+
+        let body = div()
+            .id("code-block-diff-body")
+            .max_h_96()
+            .overflow_y_scroll()
+            .px_4()
+            .py_2()
+            .font_family(MONOSPACE_FONT)
+            .text_xs()
+            .when(diff.is_empty(), |el| {
+                el.text_color(rgb(TEXT_MUTED))
+                    .child("The file already reads like this")
+            })
+            .children(diff.lines().take(MAX_SNAPSHOT_DIFF_LINES).map(|line| {
+                div()
+                    .whitespace_nowrap()
+                    .text_color(rgb(diff_line_color(line)))
+                    .child(line.to_string())
+            }))
+            .when(total > MAX_SNAPSHOT_DIFF_LINES, |el| {
+                el.child(
+                    div()
+                        .pt_1()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child(format!("… {} more lines", total - MAX_SNAPSHOT_DIFF_LINES)),
+                )
+            });
+
+        div()
+            .id("code-block-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                match event.keystroke.key.as_str() {
+                    "escape" => this.close_code_block_dialog(cx),
+                    "enter" => this.confirm_apply_code_block(cx),
+                    _ => {}
+                }
+            }))
+            .child(
+                div()
+                    .id("code-block-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_code_block_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("code-block-dialog")
+                            .occlude()
+                            .w(px(720.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .flex_col()
+                                    .gap_1()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child(format!(
+                                                "Apply code block to {}",
+                                                suggestion.path.display()
+                                            )),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child(suggestion.edit.describe()),
+                                    ),
+                            )
+                            .child(body)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-code-block")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_code_block_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-code-block")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_apply_code_block(cx);
+                                            }))
+                                            .child("Apply"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,155 @@
+//! Recent commands overlay rendering (see `crate::command_history`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    /// Commands of the active terminal, newest first: Enter runs the
+    /// selected one again, Ctrl+C copies it
+    pub fn render_command_history(&self, selected: usize, cx: &Context<Self>) -> AnyElement {
+        let commands = self.recent_commands(cx);
+        let integrated = self
+            .active_terminal()
+            .is_some_and(|terminal| terminal.read(cx).has_shell_integration());
+        let source_note = if integrated {
+            "Shell integration (OSC 133): commands typed in the terminal are recorded"
+        } else {
+            "No shell integration: only commands sent by Sashiki are recorded"
+        };
+
+        div()
+            .id("command-history-container")
+            .track_focus(&self.command_history_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                let control = event.keystroke.modifiers.control;
+                if key == "escape" {
+                    this.close_command_history(window, cx);
+                } else if key == "enter" {
+                    this.run_history_command(selected, window, cx);
+                } else if key == "up" {
+                    this.move_command_history_selection(-1, cx);
+                } else if key == "down" {
+                    this.move_command_history_selection(1, cx);
+                } else if key == "c" && control {
+                    this.copy_history_command(selected, window, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("command-history-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_command_history(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .justify_center()
+                    .pt_16()
+                    .child(
+                        div()
+                            .id("command-history")
+                            .occlude()
+                            .w(px(520.))
+                            .max_h(px(480.))
+                            .flex()
+                            .flex_col()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_1()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(rgb(TEXT))
+                                            .child("Recent Commands"),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(if integrated {
+                                                TEXT_SECONDARY
+                                            } else {
+                                                PEACH
+                                            }))
+                                            .child(source_note),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .id("command-history-entries")
+                                    .flex_1()
+                                    .overflow_y_scroll()
+                                    .py_1()
+                                    .when(commands.is_empty(), |el| {
+                                        el.child(
+                                            div()
+                                                .px_4()
+                                                .py_2()
+                                                .text_xs()
+                                                .text_color(rgb(TEXT_MUTED))
+                                                .child("No commands yet"),
+                                        )
+                                    })
+                                    .children(commands.into_iter().enumerate().map(
+                                        |(i, command)| {
+                                            div()
+                                                .id(("command-history-entry", i))
+                                                .px_4()
+                                                .py_1()
+                                                .text_xs()
+                                                .font_family(MONOSPACE_FONT)
+                                                .text_color(rgb(TEXT))
+                                                .truncate()
+                                                .cursor_pointer()
+                                                .when(i == selected, |el| el.bg(rgb(BG_SURFACE1)))
+                                                .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                                                .on_click(cx.listener(
+                                                    move |this, _, window, cx| {
+                                                        this.run_history_command(i, window, cx);
+                                                    },
+                                                ))
+                                                .child(command)
+                                        },
+                                    )),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_2()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_xs()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .child("Enter to run again · Ctrl+C to copy · Esc to close"),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,222 @@
+//! Overlap dialog rendering: files changed in more than one worktree (see
+//! `crate::conflicts`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use gpui::{AnyElement, Context, IntoElement, ParentElement, Styled, div, prelude::*, rgb, rgba};
+
+impl SashikiApp {
+    pub fn render_conflicts_dialog(&self, target_index: usize, cx: &Context<Self>) -> AnyElement {
+        let sessions = self.repo.session_manager.sessions();
+        let Some(target) = sessions.get(target_index) else {
+            return div().into_any_element();
+        };
+        let target_name = target.name().to_string();
+        let target_path = target.worktree_path().to_path_buf();
+
+        let mut body = div()
+            .id("conflicts-list")
+            .p_4()
+            .max_h_96()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .gap_3();
+
+        if let Some(reason) = self.repo.conflict_report.skipped_reason(&target_path) {
+            body = body.child(
+                div()
+                    .text_color(rgb(TEXT_MUTED))
+                    .text_xs()
+                    .child(format!("Skipped: {}", reason)),
+            );
+        }
+
+        for (group, overlap) in self
+            .repo
+            .conflict_report
+            .overlaps_for(&target_path)
+            .iter()
+            .enumerate()
+        {
+            let other_index = self
+                .repo
+                .session_manager
+                .find_session_by_path(&overlap.other);
+            let other = other_index.and_then(|idx| sessions.get(idx));
+            let other_name = other
+                .map(|s| s.name().to_string())
+                .unwrap_or_else(|| overlap.other.display().to_string());
+            let other_branch = other.and_then(|s| s.branch()).map(|b| b.to_string());
+
+            let mut section = div().flex().flex_col().gap_1().child(
+                div()
+                    .flex()
+                    .gap_2()
+                    .text_sm()
+                    .child(
+                        div()
+                            .text_color(rgb(TEXT))
+                            .child(format!("Shared with {}", other_name)),
+                    )
+                    .when_some(other_branch, |el, b| {
+                        el.child(div().text_color(rgb(TEXT_MUTED)).child(format!("⎇ {}", b)))
+                    }),
+            );
+
+            for (row, file) in overlap.files.iter().enumerate() {
+                let this_path = file.clone();
+                let other_path = file.clone();
+                let compare_path = file.clone();
+
+                section = section.child(
+                    div()
+                        .pl_2()
+                        .flex()
+                        .items_center()
+                        .gap_2()
+                        .text_xs()
+                        .child(
+                            div()
+                                .flex_1()
+                                .truncate()
+                                .text_color(rgb(YELLOW))
+                                .child(file.to_string_lossy().to_string()),
+                        )
+                        .child(
+                            div()
+                                .id(("conflict-this", group * 10_000 + row))
+                                .px_1()
+                                .cursor_pointer()
+                                .text_color(rgb(BLUE))
+                                .hover(|el| el.text_color(rgb(TEXT)))
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    this.open_overlap_diff(
+                                        target_index,
+                                        this_path.clone(),
+                                        window,
+                                        cx,
+                                    );
+                                }))
+                                .child(target_name.clone()),
+                        )
+                        .when_some(other_index, |el, other_index| {
+                            el.child(
+                                div()
+                                    .id(("conflict-other", group * 10_000 + row))
+                                    .px_1()
+                                    .cursor_pointer()
+                                    .text_color(rgb(BLUE))
+                                    .hover(|el| el.text_color(rgb(TEXT)))
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        this.open_overlap_diff(
+                                            other_index,
+                                            other_path.clone(),
+                                            window,
+                                            cx,
+                                        );
+                                    }))
+                                    .child(other_name.clone()),
+                            )
+                        })
+                        .when_some(other_index, |el, other_index| {
+                            el.child(
+                                div()
+                                    .id(("conflict-compare", group * 10_000 + row))
+                                    .px_1()
+                                    .cursor_pointer()
+                                    .text_color(rgb(MAUVE))
+                                    .hover(|el| el.text_color(rgb(TEXT)))
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        this.compare_overlap(
+                                            target_index,
+                                            other_index,
+                                            compare_path.clone(),
+                                            window,
+                                            cx,
+                                        );
+                                    }))
+                                    .child("compare"),
+                            )
+                        }),
+                );
+            }
+
+            body = body.child(section);
+        }
+
+        div()
+            .id("conflicts-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("conflicts-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_conflicts_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("conflicts-dialog")
+                            .occlude()
+                            .w_128()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(YELLOW))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!("Overlapping Changes: {}", target_name)),
+                            )
+                            .child(body)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .child(
+                                        div()
+                                            .id("close-conflicts")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_conflicts_dialog(cx);
+                                            }))
+                                            .child("Close"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
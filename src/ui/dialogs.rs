@@ -1,48 +1,217 @@
 //! Dialog rendering
 
 use crate::app::SashikiApp;
+use crate::dialog::{ActiveDialog, CreationProgress, DiscardTarget, OpenField, OpenMode};
+use crate::git::Worktree;
+use crate::git_lock::BusyLock;
+use crate::git_queue::GitOp;
+use crate::session::SessionColor;
+use crate::template::CreationPlan;
 use crate::theme::*;
+use crate::ui::TextTooltip;
 use gpui::{
-    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, rgb,
-    rgba,
+    AnyElement, Context, Div, IntoElement, KeyDownEvent, ParentElement, SharedString, Styled, div,
+    prelude::*, px, rgb, rgba,
 };
+use std::path::{Path, PathBuf};
+
+/// Muted line in the creation preview
+fn preview_note(text: impl Into<SharedString>) -> Div {
+    div()
+        .text_xs()
+        .text_color(rgb(TEXT_MUTED))
+        .child(text.into())
+}
+
+/// Steps of a creation plan in the order they run
+fn render_creation_plan(plan: &CreationPlan) -> impl IntoElement {
+    let heading = |text: &'static str| {
+        div()
+            .mt_1()
+            .text_xs()
+            .text_color(rgb(TEXT_SECONDARY))
+            .child(text)
+    };
+    let line = |text: String| {
+        div()
+            .pl_3()
+            .text_xs()
+            .font_family(MONOSPACE_FONT)
+            .text_color(rgb(TEXT))
+            .child(text)
+    };
+    let path = |path: &Option<PathBuf>, unknown: &'static str| match path {
+        Some(path) => line(path.display().to_string()),
+        None => preview_note(unknown).pl_3(),
+    };
+    let commands = |commands: &[String]| -> Vec<Div> {
+        if commands.is_empty() {
+            vec![preview_note("none").pl_3()]
+        } else {
+            commands
+                .iter()
+                .map(|cmd| line(format!("$ {}", cmd)))
+                .collect()
+        }
+    };
+
+    let mut copies = Vec::new();
+    for copy in &plan.file_copies {
+        copies.push(line(copy.pattern.clone()).text_color(rgb(TEXT_SECONDARY)));
+        if let Some(error) = &copy.error {
+            copies.push(line(error.clone()).pl_6().text_color(rgb(RED)));
+        } else if copy.files.is_empty() {
+            copies.push(preview_note("matches nothing now").pl_6());
+        }
+        for file in &copy.files {
+            copies.push(line(file.display().to_string()).pl_6());
+        }
+        if copy.more > 0 {
+            copies.push(preview_note(format!("… and {} more", copy.more)).pl_6());
+        }
+    }
+    if copies.is_empty() {
+        copies.push(preview_note("none").pl_3());
+    }
+
+    div()
+        .flex()
+        .flex_col()
+        .child(preview_note(match &plan.template {
+            Some(name) => format!("Template \"{}\" matches the branch", name),
+            None => "Default template".to_string(),
+        }))
+        .child(heading("Worktree"))
+        .child(path(&plan.worktree_path, "named after the base commit"))
+        .child(heading("Before creating, in the main worktree"))
+        .children(commands(&plan.pre_create_commands))
+        .child(heading("Files copied from the main worktree"))
+        .children(copies)
+        .child(heading("After creating, in the working directory"))
+        .children(commands(&plan.post_create_commands))
+        .child(heading("Working directory (first terminal)"))
+        .child(path(&plan.working_directory, "in the new worktree"))
+}
+
+/// How long a creation step ran ("8s", "2m 05s")
+fn format_elapsed(elapsed: std::time::Duration) -> String {
+    let seconds = elapsed.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
 
 impl SashikiApp {
-    pub fn render_create_dialog(&self, cx: &Context<Self>) -> AnyElement {
-        let input_value = self.create_branch_input.clone();
+    pub fn render_open_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let input_value = self.open_input.clone();
+        let mode = self.open_mode;
+        let has_repo = self.repo.git_repo.is_some();
+
+        let (prompt, placeholder, hint, submit_label) = match mode {
+            OpenMode::Path => (
+                "Enter folder path:",
+                "/path/to/repository",
+                "A path inside an open worktree switches to that session.",
+                "Open",
+            ),
+            OpenMode::Branch => (
+                "Enter branch name:",
+                "feature/my-branch",
+                "The branch name is suggested from the task (↑/↓ switch fields). \
+                 If the branch doesn't exist, it will be created from the base.",
+                "Create",
+            ),
+        };
+        let field = self.open_field;
+        let description_active = mode == OpenMode::Branch && field == OpenField::Description;
+        let description_value = self.open_description.clone();
+        let show_input = mode == OpenMode::Path || !self.open_detach;
+        let base_active = mode == OpenMode::Branch && field == OpenField::Base;
+        let base_value = self.open_base.clone();
+        let detach = self.open_detach;
+        let skip_hooks = self.open_skip_hooks;
+
+        let mode_tab = |id: &'static str, label: &'static str, tab_mode: OpenMode| {
+            let selected = mode == tab_mode;
+            div()
+                .id(id)
+                .px_3()
+                .py_1()
+                .rounded_sm()
+                .cursor_pointer()
+                .text_xs()
+                .when(selected, |el| el.bg(rgb(BG_SURFACE1)).text_color(rgb(TEXT)))
+                .when(!selected, |el| {
+                    el.text_color(rgb(TEXT_MUTED))
+                        .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                })
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.set_open_mode(tab_mode, cx);
+                }))
+                .child(label)
+        };
 
         div()
-            .id("create-dialog-container")
-            .track_focus(&self.create_dialog_focus)
+            .id("open-dialog-container")
+            .track_focus(&self.open_dialog_focus)
             .absolute()
             .inset_0()
             .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
                 let key = &event.keystroke.key;
                 if key == "escape" {
-                    this.close_create_dialog(window, cx);
+                    this.close_open_dialog(window, cx);
                 } else if key == "enter" {
-                    this.submit_create_worktree(window, cx);
+                    this.submit_open_dialog(window, cx);
+                } else if key == "tab" {
+                    let next = match this.open_mode {
+                        OpenMode::Path => OpenMode::Branch,
+                        OpenMode::Branch => OpenMode::Path,
+                    };
+                    this.set_open_mode(next, cx);
+                } else if key == "up" || key == "down" {
+                    this.move_open_field(if key == "up" { -1 } else { 1 }, cx);
                 } else if key == "backspace" {
-                    this.create_branch_input.pop();
-                    cx.notify();
-                } else if let Some(c) = key.chars().next()
-                    && key.chars().count() == 1
-                    && (c.is_alphanumeric() || matches!(c, '-' | '_' | '/' | '.' | '@'))
+                    match this.open_field {
+                        OpenField::Description => this.edit_open_description(None, cx),
+                        OpenField::Base => this.edit_open_base(None, cx),
+                        OpenField::Input => this.edit_open_input(None, cx),
+                    }
+                } else if this.open_field == OpenField::Description
+                    && !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
                 {
-                    this.create_branch_input.push(c);
-                    cx.notify();
+                    this.edit_open_description(Some(text), cx);
+                } else if this.open_field == OpenField::Base
+                    && !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(|c| c.is_control() || c.is_whitespace())
+                {
+                    this.edit_open_base(Some(text), cx);
+                } else if this.open_field == OpenField::Input
+                    && !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                    && (this.open_mode == OpenMode::Path
+                        || text.chars().all(|c| {
+                            c.is_alphanumeric() || matches!(c, '-' | '_' | '/' | '.' | '@')
+                        }))
+                {
+                    this.edit_open_input(Some(text), cx);
                 }
             }))
             .child(
                 div()
-                    .id("create-dialog-backdrop")
+                    .id("open-dialog-backdrop")
                     .absolute()
                     .inset_0()
                     .bg(rgba(OVERLAY))
                     .on_mouse_down(
                         gpui::MouseButton::Left,
                         cx.listener(|this, _, window, cx| {
-                            this.close_create_dialog(window, cx);
+                            this.close_open_dialog(window, cx);
                         }),
                     ),
             )
@@ -55,9 +224,9 @@ impl SashikiApp {
                     .justify_center()
                     .child(
                         div()
-                            .id("create-dialog")
+                            .id("open-dialog")
                             .occlude()
-                            .w_80()
+                            .w_96()
                             .bg(rgb(BG_BASE))
                             .border_1()
                             .border_color(rgb(BG_SURFACE1))
@@ -69,9 +238,32 @@ impl SashikiApp {
                                     .py_3()
                                     .border_b_1()
                                     .border_color(rgb(BG_SURFACE0))
-                                    .text_color(rgb(TEXT))
-                                    .font_weight(gpui::FontWeight::BOLD)
-                                    .child("Create Worktree"),
+                                    .flex()
+                                    .items_center()
+                                    .justify_between()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child("Open"),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap_1()
+                                            .child(mode_tab(
+                                                "open-mode-path",
+                                                "Folder",
+                                                OpenMode::Path,
+                                            ))
+                                            .when(has_repo, |el| {
+                                                el.child(mode_tab(
+                                                    "open-mode-branch",
+                                                    "Branch as worktree",
+                                                    OpenMode::Branch,
+                                                ))
+                                            }),
+                                    ),
                             )
                             .child(
                                 div()
@@ -79,41 +271,1907 @@ impl SashikiApp {
                                     .flex()
                                     .flex_col()
                                     .gap_3()
+                                    .when(mode == OpenMode::Branch, |el| {
+                                        el.child(
+                                            div()
+                                                .text_color(rgb(TEXT_SECONDARY))
+                                                .text_sm()
+                                                .child("Task description (optional):"),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("open-description")
+                                                .px_3()
+                                                .py_2()
+                                                .bg(rgb(BG_SURFACE0))
+                                                .border_1()
+                                                .border_color(if description_active {
+                                                    rgb(BLUE)
+                                                } else {
+                                                    rgb(BG_SURFACE1)
+                                                })
+                                                .rounded_sm()
+                                                .cursor_text()
+                                                .overflow_hidden()
+                                                .text_color(if description_value.is_empty() {
+                                                    rgb(TEXT_MUTED)
+                                                } else {
+                                                    rgb(TEXT)
+                                                })
+                                                .text_sm()
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.set_open_field(OpenField::Description, cx);
+                                                }))
+                                                .child(
+                                                    match (
+                                                        description_value.is_empty(),
+                                                        description_active,
+                                                    ) {
+                                                        (true, _) => {
+                                                            "fix flaky session tests".to_string()
+                                                        }
+                                                        (false, true) => {
+                                                            format!("{}_", description_value)
+                                                        }
+                                                        (false, false) => description_value.clone(),
+                                                    },
+                                                ),
+                                        )
+                                    })
+                                    .when(show_input, |el| {
+                                        el.child(
+                                            div()
+                                                .text_color(rgb(TEXT_SECONDARY))
+                                                .text_sm()
+                                                .child(prompt),
+                                        )
+                                    })
+                                    .when(show_input, |el| {
+                                        el.child(
+                                            div()
+                                                .flex()
+                                                .gap_2()
+                                                .child(
+                                                    div()
+                                                        .id("open-input")
+                                                        .flex_1()
+                                                        .min_w_0()
+                                                        .px_3()
+                                                        .py_2()
+                                                        .bg(rgb(BG_SURFACE0))
+                                                        .border_1()
+                                                        .border_color(
+                                                            if self.open_error.is_some() {
+                                                                rgb(RED)
+                                                            } else if field == OpenField::Input {
+                                                                rgb(BLUE)
+                                                            } else {
+                                                                rgb(BG_SURFACE1)
+                                                            },
+                                                        )
+                                                        .rounded_sm()
+                                                        .cursor_text()
+                                                        .overflow_hidden()
+                                                        .text_color(if input_value.is_empty() {
+                                                            rgb(TEXT_MUTED)
+                                                        } else {
+                                                            rgb(TEXT)
+                                                        })
+                                                        .text_sm()
+                                                        .on_click(cx.listener(|this, _, _, cx| {
+                                                            this.set_open_field(
+                                                                OpenField::Input,
+                                                                cx,
+                                                            );
+                                                        }))
+                                                        .child(if input_value.is_empty() {
+                                                            placeholder.to_string()
+                                                        } else if field == OpenField::Input {
+                                                            format!("{}_", input_value)
+                                                        } else {
+                                                            input_value
+                                                        }),
+                                                )
+                                                .when(mode == OpenMode::Path, |el| {
+                                                    el.child(
+                                                        div()
+                                                            .id("open-browse")
+                                                            .px_3()
+                                                            .py_2()
+                                                            .cursor_pointer()
+                                                            .rounded_sm()
+                                                            .bg(rgb(BG_SURFACE1))
+                                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                                            .text_xs()
+                                                            .text_color(rgb(TEXT))
+                                                            .on_click(cx.listener(
+                                                                |this, _, _, cx| {
+                                                                    this.browse_for_folder(cx);
+                                                                },
+                                                            ))
+                                                            .child("Browse…"),
+                                                    )
+                                                }),
+                                        )
+                                    })
+                                    .when(mode == OpenMode::Branch, |el| {
+                                        el.child(
+                                            div()
+                                                .text_color(rgb(TEXT_SECONDARY))
+                                                .text_sm()
+                                                .child("Start from (branch, tag or commit):"),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("open-base")
+                                                .px_3()
+                                                .py_2()
+                                                .bg(rgb(BG_SURFACE0))
+                                                .border_1()
+                                                .border_color(if base_active {
+                                                    rgb(BLUE)
+                                                } else {
+                                                    rgb(BG_SURFACE1)
+                                                })
+                                                .rounded_sm()
+                                                .cursor_text()
+                                                .overflow_hidden()
+                                                .text_color(if base_value.is_empty() {
+                                                    rgb(TEXT_MUTED)
+                                                } else {
+                                                    rgb(TEXT)
+                                                })
+                                                .text_sm()
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.set_open_field(OpenField::Base, cx);
+                                                }))
+                                                .child(
+                                                    match (base_value.is_empty(), base_active) {
+                                                        (true, _) => "HEAD".to_string(),
+                                                        (false, true) => format!("{}_", base_value),
+                                                        (false, false) => base_value.clone(),
+                                                    },
+                                                ),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("open-detach")
+                                                .flex()
+                                                .items_center()
+                                                .gap_2()
+                                                .cursor_pointer()
+                                                .text_xs()
+                                                .text_color(rgb(TEXT))
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.toggle_open_detach(cx);
+                                                }))
+                                                .child(
+                                                    div()
+                                                        .text_color(if detach {
+                                                            rgb(BLUE)
+                                                        } else {
+                                                            rgb(TEXT_MUTED)
+                                                        })
+                                                        .child(if detach { "☑" } else { "☐" }),
+                                                )
+                                                .child(
+                                                    "Detached: check out the base without a branch",
+                                                ),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("open-skip-hooks")
+                                                .flex()
+                                                .items_center()
+                                                .gap_2()
+                                                .cursor_pointer()
+                                                .text_xs()
+                                                .text_color(rgb(TEXT))
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.toggle_open_skip_hooks(cx);
+                                                }))
+                                                .child(
+                                                    div()
+                                                        .text_color(if skip_hooks {
+                                                            rgb(BLUE)
+                                                        } else {
+                                                            rgb(TEXT_MUTED)
+                                                        })
+                                                        .child(if skip_hooks {
+                                                            "☑"
+                                                        } else {
+                                                            "☐"
+                                                        }),
+                                                )
+                                                .child("Skip git hooks for this checkout"),
+                                        )
+                                        .child(self.render_open_preview(cx))
+                                    })
+                                    .when_some(self.open_error.clone(), |el, error| {
+                                        el.child(div().text_color(rgb(RED)).text_xs().child(error))
+                                    })
+                                    .child(div().text_color(rgb(TEXT_MUTED)).text_xs().child(hint)),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
                                     .child(
                                         div()
-                                            .text_color(rgb(TEXT_SECONDARY))
-                                            .text_sm()
-                                            .child("Enter branch name:"),
+                                            .id("cancel-open")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.close_open_dialog(window, cx);
+                                            }))
+                                            .child("Cancel"),
                                     )
                                     .child(
                                         div()
-                                            .id("branch-input")
-                                            .w_full()
-                                            .px_3()
+                                            .id("submit-open")
+                                            .px_4()
                                             .py_2()
-                                            .bg(rgb(BG_SURFACE0))
-                                            .border_1()
-                                            .border_color(rgb(BLUE))
+                                            .cursor_pointer()
                                             .rounded_sm()
-                                            .cursor_text()
-                                            .text_color(if input_value.is_empty() {
-                                                rgb(TEXT_MUTED)
-                                            } else {
-                                                rgb(TEXT)
-                                            })
-                                            .text_sm()
-                                            .child(if input_value.is_empty() {
-                                                "feature/my-branch".to_string()
-                                            } else {
-                                                format!("{}_", input_value)
-                                            }),
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.submit_open_dialog(window, cx);
+                                            }))
+                                            .child(submit_label),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    /// "Preview" expander of Branch mode: what creating the worktree would
+    /// run and copy (see `TemplateSet::plan`)
+    fn render_open_preview(&self, cx: &Context<Self>) -> impl IntoElement {
+        let expanded = self.open_preview_expanded;
+        let header = div()
+            .id("open-preview-toggle")
+            .flex()
+            .items_center()
+            .gap_2()
+            .cursor_pointer()
+            .text_xs()
+            .text_color(rgb(TEXT_SECONDARY))
+            .hover(|el| el.text_color(rgb(TEXT)))
+            .on_click(cx.listener(|this, _, _, cx| {
+                this.toggle_open_preview(cx);
+            }))
+            .child(div().w_3().child(if expanded { "▼" } else { "▶" }))
+            .child("Preview");
+        let body = match (&self.open_preview, &self.open_preview_task) {
+            (Some(plan), _) => render_creation_plan(plan).into_any_element(),
+            (None, Some(_)) => preview_note("Resolving the template…").into_any_element(),
+            (None, None) => preview_note("Type a branch name to preview").into_any_element(),
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .child(header)
+            .when(expanded, |el| {
+                el.child(
+                    div()
+                        .id("open-preview")
+                        .max_h(px(200.0))
+                        .overflow_y_scroll()
+                        .px_3()
+                        .py_2()
+                        .bg(rgb(BG_MANTLE))
+                        .rounded_sm()
+                        .child(body),
+                )
+            })
+    }
+
+    pub fn render_delete_dialog(
+        &self,
+        target_index: usize,
+        keep_note: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let target = self.repo.session_manager.sessions().get(target_index);
+        let target_name = target.map(|s| s.name().to_string()).unwrap_or_default();
+        let has_note = self.notes.noted.contains(&target_name);
+        let busy = target
+            .filter(|_| self.confirm_busy_sessions)
+            .and_then(|s| s.activity_summary(cx));
+
+        div()
+            .id("delete-confirm-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_delete_dialog(cx);
+                } else if key == "enter" {
+                    this.confirm_delete_worktree(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("delete-confirm-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_delete_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("delete-confirm-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(RED))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(RED))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Delete Worktree"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(format!(
+                                        "Are you sure you want to delete \"{}\"?",
+                                        target_name
+                                    )))
+                                    .when_some(busy, |this, busy| {
+                                        this.child(
+                                            div()
+                                                .text_color(rgb(PEACH))
+                                                .text_xs()
+                                                .child(format!("⚠ {} — deleting stops it", busy)),
+                                        )
+                                    })
+                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(
+                                        "This will remove the worktree directory and its contents.",
+                                    ))
+                                    .when(has_note, |this| {
+                                        this.child(
+                                            div()
+                                                .id("delete-keep-note")
+                                                .flex()
+                                                .items_center()
+                                                .gap_2()
+                                                .cursor_pointer()
+                                                .text_xs()
+                                                .text_color(rgb(TEXT))
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.toggle_delete_keep_note(cx);
+                                                }))
+                                                .child(
+                                                    div()
+                                                        .text_color(rgb(if keep_note {
+                                                            GREEN
+                                                        } else {
+                                                            TEXT_MUTED
+                                                        }))
+                                                        .child(if keep_note {
+                                                            "☑"
+                                                        } else {
+                                                            "☐"
+                                                        }),
+                                                )
+                                                .child(
+                                                    "Keep the session's note (reopens with a \
+                                                     worktree of the same name)",
+                                                ),
+                                        )
+                                    }),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-delete")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_delete_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
                                     )
                                     .child(
                                         div()
-                                            .text_color(rgb(TEXT_MUTED))
+                                            .id("confirm-delete")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(RED))
+                                            .hover(|el| el.bg(rgb(MAROON)))
                                             .text_xs()
-                                            .child("If the branch doesn't exist, it will be created from HEAD."),
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_delete_worktree(cx);
+                                            }))
+                                            .child("Delete"),
                                     ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_quit_dialog(&self, busy: &[String], cx: &Context<Self>) -> AnyElement {
+        div()
+            .id("quit-confirm-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_quit_dialog(cx);
+                } else if key == "enter" {
+                    this.confirm_quit(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("quit-confirm-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_quit_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("quit-confirm-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(RED))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(RED))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Quit Sashiki"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .text_sm()
+                                            .child("These sessions are still busy:"),
+                                    )
+                                    .child(div().flex().flex_col().gap_1().children(
+                                        busy.iter().map(|line| {
+                                            div()
+                                                .text_color(rgb(PEACH))
+                                                .text_xs()
+                                                .child(line.clone())
+                                        }),
+                                    ))
+                                    .child(
+                                        div()
+                                            .text_color(rgb(YELLOW))
+                                            .text_xs()
+                                            .child("Quitting stops every terminal."),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-quit")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_quit_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-quit")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(RED))
+                                            .hover(|el| el.bg(rgb(MAROON)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_quit(cx);
+                                            }))
+                                            .child("Quit Anyway"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_error_dialog(&self, message: &str, cx: &Context<Self>) -> AnyElement {
+        let message = message.to_string();
+
+        div()
+            .id("error-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("error-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_error_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("error-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(RED))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(RED))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Error"),
+                            )
+                            .child(div().p_4().text_color(rgb(TEXT)).text_sm().child(message))
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .child(
+                                        div()
+                                            .id("dismiss-error")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_error_dialog(cx);
+                                            }))
+                                            .child("OK"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    /// A queued git operation gave up waiting for another git process's
+    /// lock: retry it or cancel it, and remove the lock when it is stale
+    pub fn render_repository_busy_dialog(
+        &self,
+        op: &GitOp,
+        lock: &BusyLock,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let id = op.id;
+        let button = |id: &'static str, label: &'static str, bg: u32, hover: u32, fg: u32| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(bg))
+                .hover(move |el| el.bg(rgb(hover)))
+                .text_xs()
+                .text_color(rgb(fg))
+                .child(label)
+        };
+
+        div()
+            .id("repository-busy-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("repository-busy-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("repository-busy-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(YELLOW))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Repository Busy"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .text_sm()
+                                            .child(format!("{} is waiting.", op.name)),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_xs()
+                                            .child(lock.to_string()),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        button(
+                                            "cancel-busy-op",
+                                            "Cancel",
+                                            BG_SURFACE1,
+                                            BG_SURFACE2,
+                                            TEXT,
+                                        )
+                                        .on_click(
+                                            cx.listener(move |this, _, _, cx| {
+                                                this.cancel_git_op(id, cx);
+                                            }),
+                                        ),
+                                    )
+                                    .when(lock.stale, |el| {
+                                        let lock = lock.clone();
+                                        el.child(
+                                            button(
+                                                "remove-stale-lock",
+                                                "Remove Lock and Retry",
+                                                BG_SURFACE1,
+                                                BG_SURFACE2,
+                                                RED,
+                                            )
+                                            .on_click(
+                                                cx.listener(move |this, _, _, cx| {
+                                                    this.remove_lock_and_retry(id, &lock, cx);
+                                                }),
+                                            ),
+                                        )
+                                    })
+                                    .child(
+                                        button("retry-busy-op", "Retry", BLUE, TEAL, BG_BASE)
+                                            .on_click(cx.listener(move |this, _, _, cx| {
+                                                this.retry_git_op(id, cx);
+                                            })),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_deleting_dialog(&self) -> AnyElement {
+        div()
+            .id("deleting-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("deleting-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("deleting-dialog")
+                            .occlude()
+                            .w_64()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .p_4()
+                            .flex()
+                            .flex_col()
+                            .items_center()
+                            .gap_3()
+                            .child(
+                                div()
+                                    .text_color(rgb(YELLOW))
+                                    .text_sm()
+                                    .child("Deleting worktree..."),
+                            )
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .text_xs()
+                                    .child("Please wait"),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_creating_dialog(
+        &self,
+        branch: &str,
+        template: Option<&str>,
+        progress: &CreationProgress,
+    ) -> AnyElement {
+        let branch = branch.to_string();
+        let template_label = match template {
+            Some(name) => format!("Template: {}", name),
+            None => "Template: default".to_string(),
+        };
+
+        let mut body = div().p_4().flex().flex_col().gap_2().child(
+            div()
+                .text_xs()
+                .text_color(rgb(TEXT_MUTED))
+                .child(template_label),
+        );
+
+        let current_step = progress.current_step;
+        for (i, step) in progress.steps.iter().enumerate() {
+            let elapsed = if i < current_step {
+                progress.step_times.get(i).copied()
+            } else if i == current_step {
+                Some(progress.step_started.elapsed())
+            } else {
+                None
+            };
+            let (icon, color) = if i < current_step {
+                // Completed
+                ("OK ", GREEN)
+            } else if i == current_step {
+                // Running
+                (">> ", YELLOW)
+            } else {
+                // Pending
+                ("   ", TEXT_MUTED)
+            };
+
+            body = body.child(
+                div()
+                    .flex()
+                    .gap_2()
+                    .text_xs()
+                    .child(div().text_color(rgb(color)).child(icon))
+                    .child(
+                        div()
+                            .flex_1()
+                            .overflow_hidden()
+                            .text_color(rgb(color))
+                            .child(step.clone()),
+                    )
+                    .when_some(elapsed, |el, elapsed| {
+                        el.child(
+                            div()
+                                .flex_shrink_0()
+                                .text_color(rgb(TEXT_MUTED))
+                                .child(format_elapsed(elapsed)),
+                        )
+                    }),
+            );
+        }
+
+        if !progress.output.is_empty() {
+            body = body.child(
+                div()
+                    .id("creating-output")
+                    .max_h(px(160.))
+                    .overflow_y_scroll()
+                    .p_2()
+                    .rounded_sm()
+                    .bg(rgb(BG_MANTLE))
+                    .font_family(MONOSPACE_FONT)
+                    .text_xs()
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .children(
+                        progress
+                            .output
+                            .iter()
+                            .map(|line| div().whitespace_nowrap().child(line.clone())),
+                    ),
+            );
+        }
+
+        div()
+            .id("creating-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("creating-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("creating-dialog")
+                            .occlude()
+                            .w(px(480.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(GREEN))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(GREEN))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .text_sm()
+                                    .child(format!("Creating \"{}\"", branch)),
+                            )
+                            .child(body),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_command_palette(
+        &self,
+        query: &str,
+        selected: usize,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let entries = self.palette_entries(query, cx);
+        let query_label = if query.is_empty() {
+            "Type a command".to_string()
+        } else {
+            format!("{}_", query)
+        };
+
+        div()
+            .id("command-palette-container")
+            .track_focus(&self.palette_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, window, cx| {
+                let ActiveDialog::CommandPalette { query, selected } = &this.active_dialog else {
+                    return;
+                };
+                let (mut query, selected) = (query.clone(), *selected);
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_command_palette(window, cx);
+                } else if key == "enter" {
+                    this.run_palette_entry(selected, window, cx);
+                } else if key == "up" {
+                    this.move_palette_selection(-1, cx);
+                } else if key == "down" {
+                    this.move_palette_selection(1, cx);
+                } else if key == "backspace" {
+                    query.pop();
+                    this.set_palette_query(query, cx);
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    query.push_str(text);
+                    this.set_palette_query(query, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("command-palette-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_command_palette(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .justify_center()
+                    .pt_16()
+                    .child(
+                        div()
+                            .id("command-palette")
+                            .occlude()
+                            .w(px(520.))
+                            .max_h(px(480.))
+                            .flex()
+                            .flex_col()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_sm()
+                                    .text_color(if query.is_empty() {
+                                        rgb(TEXT_MUTED)
+                                    } else {
+                                        rgb(TEXT)
+                                    })
+                                    .child(query_label),
+                            )
+                            .child(
+                                div()
+                                    .id("command-palette-entries")
+                                    .flex_1()
+                                    .overflow_y_scroll()
+                                    .py_1()
+                                    .when(entries.is_empty(), |el| {
+                                        el.child(
+                                            div()
+                                                .px_4()
+                                                .py_2()
+                                                .text_xs()
+                                                .text_color(rgb(TEXT_MUTED))
+                                                .child("No matching commands"),
+                                        )
+                                    })
+                                    .children(entries.into_iter().enumerate().map(|(i, entry)| {
+                                        let disabled = entry.unavailable.is_some();
+                                        div()
+                                            .id(gpui::ElementId::Name(entry.id.clone()))
+                                            .px_4()
+                                            .py_1()
+                                            .flex()
+                                            .items_center()
+                                            .gap_2()
+                                            .text_xs()
+                                            .when(i == selected, |el| el.bg(rgb(BG_SURFACE1)))
+                                            .when(!disabled, |el| {
+                                                el.cursor_pointer()
+                                                    .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                                                    .on_click(cx.listener(
+                                                        move |this, _, window, cx| {
+                                                            this.run_palette_entry(i, window, cx);
+                                                        },
+                                                    ))
+                                            })
+                                            .child(
+                                                div()
+                                                    .w_20()
+                                                    .flex_shrink_0()
+                                                    .text_color(rgb(TEXT_MUTED))
+                                                    .child(entry.category),
+                                            )
+                                            .child(
+                                                div()
+                                                    .flex_1()
+                                                    .min_w_0()
+                                                    .truncate()
+                                                    .text_color(if disabled {
+                                                        rgb(TEXT_MUTED)
+                                                    } else {
+                                                        rgb(TEXT)
+                                                    })
+                                                    .child(entry.title),
+                                            )
+                                            .when_some(entry.unavailable, |el, reason| {
+                                                el.child(
+                                                    div().text_color(rgb(TEXT_MUTED)).child(reason),
+                                                )
+                                            })
+                                            .when_some(entry.key, |el, key| {
+                                                el.child(
+                                                    div()
+                                                        .text_color(rgb(TEXT_SECONDARY))
+                                                        .child(key),
+                                                )
+                                            })
+                                    })),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_tags_dialog(&self, target_index: usize, cx: &Context<Self>) -> AnyElement {
+        let input_value = self.tags_input.clone();
+        let session = self.repo.session_manager.sessions().get(target_index);
+        let session_name = session
+            .map(|s| s.branch().unwrap_or(s.name()).to_string())
+            .unwrap_or_default();
+        // The picked color, None while it is derived from the name
+        let custom_color = session.filter(|s| s.has_custom_color()).map(|s| s.color());
+        let color_swatch = |id: usize, color: SessionColor, picked: bool| {
+            div()
+                .id(("session-color", id))
+                .w_5()
+                .h_5()
+                .rounded_full()
+                .cursor_pointer()
+                .bg(rgb(color.on(BG_BASE)))
+                .border_2()
+                .border_color(if picked { rgb(TEXT) } else { rgb(BG_BASE) })
+                .hover(|el| el.border_color(rgb(TEXT_SECONDARY)))
+        };
+
+        div()
+            .id("tags-dialog-container")
+            .track_focus(&self.tags_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_tags_dialog(window, cx);
+                } else if key == "enter" {
+                    this.save_session_tags(window, cx);
+                } else if key == "backspace" {
+                    this.tags_input.pop();
+                    cx.notify();
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.tags_input.push_str(text);
+                    cx.notify();
+                }
+            }))
+            .child(
+                div()
+                    .id("tags-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_tags_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("tags-dialog")
+                            .occlude()
+                            .w_80()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!("Tags and color of {}", session_name)),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child("Tags (comma-separated):"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("tags-input")
+                                            .w_full()
+                                            .px_3()
+                                            .py_2()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .cursor_text()
+                                            .text_color(if input_value.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .text_sm()
+                                            .child(if input_value.is_empty() {
+                                                "agents, review".to_string()
+                                            } else {
+                                                format!("{}_", input_value)
+                                            }),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child("Leave empty to remove all tags"),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child("Color:"),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .gap_2()
+                                            .children(
+                                                SessionColor::COLORS.into_iter().enumerate().map(
+                                                    |(i, color)| {
+                                                        color_swatch(
+                                                            i,
+                                                            color,
+                                                            custom_color == Some(color),
+                                                        )
+                                                        .on_click(cx.listener(
+                                                            move |this, _, _, cx| {
+                                                                this.set_session_color(
+                                                                    target_index,
+                                                                    Some(color),
+                                                                    cx,
+                                                                );
+                                                            },
+                                                        ))
+                                                    },
+                                                ),
+                                            )
+                                            .child(
+                                                div()
+                                                    .id("session-color-auto")
+                                                    .px_2()
+                                                    .py_1()
+                                                    .cursor_pointer()
+                                                    .rounded_sm()
+                                                    .bg(if custom_color.is_none() {
+                                                        rgb(BG_SURFACE2)
+                                                    } else {
+                                                        rgb(BG_SURFACE0)
+                                                    })
+                                                    .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                                    .text_xs()
+                                                    .text_color(rgb(TEXT))
+                                                    .tooltip(TextTooltip::build(
+                                                        "Pick the color from the worktree name",
+                                                    ))
+                                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                                        this.set_session_color(
+                                                            target_index,
+                                                            None,
+                                                            cx,
+                                                        );
+                                                    }))
+                                                    .child("Auto"),
+                                            ),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-tags")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.close_tags_dialog(window, cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("save-tags")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.save_session_tags(window, cx);
+                                            }))
+                                            .child("Save"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_mark_moment_dialog(&self, target_index: usize, cx: &Context<Self>) -> AnyElement {
+        let input_value = self.moment_input.clone();
+        let session_name = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .map(|s| s.branch().unwrap_or(s.name()).to_string())
+            .unwrap_or_default();
+
+        div()
+            .id("moment-dialog-container")
+            .track_focus(&self.moment_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_mark_moment_dialog(window, cx);
+                } else if key == "enter" {
+                    this.submit_mark_moment(window, cx);
+                } else if key == "backspace" {
+                    this.moment_input.pop();
+                    cx.notify();
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.moment_input.push_str(text);
+                    cx.notify();
+                }
+            }))
+            .child(
+                div()
+                    .id("moment-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_mark_moment_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("moment-dialog")
+                            .occlude()
+                            .w_80()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!("Mark moment in {}", session_name)),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child("Note for the activity timeline:"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("moment-input")
+                                            .w_full()
+                                            .px_3()
+                                            .py_2()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .cursor_text()
+                                            .text_color(if input_value.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .text_sm()
+                                            .child(if input_value.is_empty() {
+                                                "tests pass, ready for review".to_string()
+                                            } else {
+                                                format!("{}_", input_value)
+                                            }),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child("Recorded with the current time"),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-moment")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.close_mark_moment_dialog(window, cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("save-moment")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.submit_mark_moment(window, cx);
+                                            }))
+                                            .child("Mark"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_close_terminal_dialog(&self, name: &str, cx: &Context<Self>) -> AnyElement {
+        let name = name.to_string();
+        let button = |id: &'static str, label: &'static str, bg: u32, hover: u32, fg: u32| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(bg))
+                .hover(move |el| el.bg(rgb(hover)))
+                .text_xs()
+                .text_color(rgb(fg))
+                .child(label)
+        };
+
+        div()
+            .id("close-terminal-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_close_terminal_dialog(cx);
+                } else if key == "enter" {
+                    this.close_active_terminal(false, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("close-terminal-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_close_terminal_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("close-terminal-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Close Terminal"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(format!(
+                                        "This terminal runs in the tmux session \"{}\".",
+                                        name
+                                    )))
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_xs()
+                                            .child("Detach keeps its programs running; the next start attaches to it again. Kill ends them."),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        button("cancel-close-terminal", "Cancel", BG_SURFACE1, BG_SURFACE2, TEXT)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_close_terminal_dialog(cx);
+                                            })),
+                                    )
+                                    .child(
+                                        button("kill-terminal", "Kill", RED, MAROON, BG_BASE)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_active_terminal(true, cx);
+                                            })),
+                                    )
+                                    .child(
+                                        button("detach-terminal", "Detach", GREEN, TEAL, BG_BASE)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_active_terminal(false, cx);
+                                            })),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_nested_repository_dialog(&self, path: &Path, cx: &Context<Self>) -> AnyElement {
+        let path = path.display().to_string();
+        let button = |id: &'static str, label: &'static str, bg: u32, hover: u32, fg: u32| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(bg))
+                .hover(move |el| el.bg(rgb(hover)))
+                .text_xs()
+                .text_color(rgb(fg))
+                .child(label)
+        };
+
+        div()
+            .id("nested-repository-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_nested_repository_dialog(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("nested-repository-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_nested_repository_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("nested-repository-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Nested Repository"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(format!(
+                                        "{} is a git repository of its own, not a submodule.",
+                                        path
+                                    )))
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_xs()
+                                            .child("Its files are not compared against this worktree. Open it as a separate repository, or ignore it to stop listing it (adds it to .git/info/exclude)."),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        button("cancel-nested-repository", "Cancel", BG_SURFACE1, BG_SURFACE2, TEXT)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_nested_repository_dialog(cx);
+                                            })),
+                                    )
+                                    .child(
+                                        button("ignore-nested-repository", "Ignore", BG_SURFACE1, BG_SURFACE2, TEXT)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.ignore_nested_repository(cx);
+                                            })),
+                                    )
+                                    .child(
+                                        button("open-nested-repository", "Open as Repository", BLUE, TEAL, BG_BASE)
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.open_nested_repository(cx);
+                                            })),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_compare_dialog(&self, path: &Path, cx: &Context<Self>) -> AnyElement {
+        let own_index = self.repo.session_manager.find_session_containing(path);
+        let sessions = self.repo.session_manager.sessions();
+        let relative = own_index
+            .and_then(|index| sessions.get(index))
+            .and_then(|session| path.strip_prefix(session.worktree_path()).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string();
+
+        let mut list = div().flex().flex_col().gap_1();
+        let mut others = 0;
+        for (index, session) in sessions.iter().enumerate() {
+            if Some(index) == own_index {
+                continue;
+            }
+            others += 1;
+            let branch = session.branch().map(|b| b.to_string());
+            list = list.child(
+                div()
+                    .id(("compare-session", index))
+                    .px_2()
+                    .py_1()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .cursor_pointer()
+                    .rounded_sm()
+                    .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                    .text_sm()
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        this.compare_with_session(index, cx);
+                    }))
+                    .child(
+                        div()
+                            .text_color(rgb(TEXT))
+                            .child(session.name().to_string()),
+                    )
+                    .when_some(branch, |el, b| {
+                        el.child(div().text_color(rgb(TEXT_MUTED)).child(format!("⎇ {}", b)))
+                    }),
+            );
+        }
+        if others == 0 {
+            list = list.child(
+                div()
+                    .text_color(rgb(TEXT_MUTED))
+                    .text_xs()
+                    .child("There is no other session to compare with."),
+            );
+        }
+
+        div()
+            .id("compare-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_compare_dialog(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("compare-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_compare_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("compare-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Compare with Session"),
+                            )
+                            .child(
+                                div()
+                                    .id("compare-sessions")
+                                    .p_4()
+                                    .max_h_96()
+                                    .overflow_y_scroll()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT_SECONDARY)).text_xs().child(
+                                        format!(
+                                            "Diff {} from the session picked to this one.",
+                                            relative
+                                        ),
+                                    ))
+                                    .child(list),
                             )
                             .child(
                                 div()
@@ -123,10 +2181,9 @@ impl SashikiApp {
                                     .border_color(rgb(BG_SURFACE0))
                                     .flex()
                                     .justify_end()
-                                    .gap_2()
                                     .child(
                                         div()
-                                            .id("cancel-create")
+                                            .id("cancel-compare")
                                             .px_4()
                                             .py_2()
                                             .cursor_pointer()
@@ -135,26 +2192,10 @@ impl SashikiApp {
                                             .hover(|el| el.bg(rgb(BG_SURFACE2)))
                                             .text_xs()
                                             .text_color(rgb(TEXT))
-                                            .on_click(cx.listener(|this, _, window, cx| {
-                                                this.close_create_dialog(window, cx);
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_compare_dialog(cx);
                                             }))
                                             .child("Cancel"),
-                                    )
-                                    .child(
-                                        div()
-                                            .id("submit-create")
-                                            .px_4()
-                                            .py_2()
-                                            .cursor_pointer()
-                                            .rounded_sm()
-                                            .bg(rgb(GREEN))
-                                            .hover(|el| el.bg(rgb(TEAL)))
-                                            .text_xs()
-                                            .text_color(rgb(BG_BASE))
-                                            .on_click(cx.listener(|this, _, window, cx| {
-                                                this.submit_create_worktree(window, cx);
-                                            }))
-                                            .child("Create"),
                                     ),
                             ),
                     ),
@@ -162,36 +2203,61 @@ impl SashikiApp {
             .into_any_element()
     }
 
-    pub fn render_delete_dialog(&self, target_index: usize, cx: &Context<Self>) -> AnyElement {
-        let target_name = self
+    pub fn render_discard_dialog(&self, target: &DiscardTarget, cx: &Context<Self>) -> AnyElement {
+        let (DiscardTarget::Hunk { path, .. } | DiscardTarget::File { path, .. }) = target;
+        let display_path = self
+            .repo
             .session_manager
-            .sessions()
-            .get(target_index)
-            .map(|s| s.name().to_string())
-            .unwrap_or_default();
+            .active_session()
+            .and_then(|s| path.strip_prefix(s.worktree_path()).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+
+        let (title, question, detail, warning) = match target {
+            DiscardTarget::Hunk { patch, .. } => (
+                "Discard Hunk",
+                format!("Discard this hunk of {}?", display_path),
+                patch
+                    .lines()
+                    .find(|l| l.starts_with("@@"))
+                    .map(str::to_string),
+                "The change is reverted in the working tree. This cannot be undone.",
+            ),
+            DiscardTarget::File { is_new, .. } => (
+                "Discard Changes",
+                format!("Discard all changes to {}?", display_path),
+                None,
+                if *is_new {
+                    "The new file will be deleted. This cannot be undone."
+                } else {
+                    "The file is restored to HEAD, including staged changes. This cannot be undone."
+                },
+            ),
+        };
 
         div()
-            .id("delete-confirm-container")
+            .id("discard-container")
             .absolute()
             .inset_0()
             .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
                 let key = &event.keystroke.key;
                 if key == "escape" {
-                    this.close_delete_dialog(cx);
+                    this.close_discard_dialog(cx);
                 } else if key == "enter" {
-                    this.confirm_delete_worktree(cx);
+                    this.confirm_discard(cx);
                 }
             }))
             .child(
                 div()
-                    .id("delete-confirm-backdrop")
+                    .id("discard-backdrop")
                     .absolute()
                     .inset_0()
                     .bg(rgba(OVERLAY))
                     .on_mouse_down(
                         gpui::MouseButton::Left,
                         cx.listener(|this, _, _, cx| {
-                            this.close_delete_dialog(cx);
+                            this.close_discard_dialog(cx);
                         }),
                     ),
             )
@@ -204,7 +2270,7 @@ impl SashikiApp {
                     .justify_center()
                     .child(
                         div()
-                            .id("delete-confirm-dialog")
+                            .id("discard-dialog")
                             .occlude()
                             .w_96()
                             .bg(rgb(BG_BASE))
@@ -220,7 +2286,7 @@ impl SashikiApp {
                                     .border_color(rgb(BG_SURFACE0))
                                     .text_color(rgb(RED))
                                     .font_weight(gpui::FontWeight::BOLD)
-                                    .child("Delete Worktree"),
+                                    .child(title),
                             )
                             .child(
                                 div()
@@ -228,13 +2294,17 @@ impl SashikiApp {
                                     .flex()
                                     .flex_col()
                                     .gap_3()
-                                    .child(div().text_color(rgb(TEXT)).text_sm().child(format!(
-                                        "Are you sure you want to delete \"{}\"?",
-                                        target_name
-                                    )))
-                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(
-                                        "This will remove the worktree directory and its contents.",
-                                    )),
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(question))
+                                    .when_some(detail, |el, detail| {
+                                        el.child(
+                                            div()
+                                                .font_family(MONOSPACE_FONT)
+                                                .text_xs()
+                                                .text_color(rgb(TEXT_MUTED))
+                                                .child(detail),
+                                        )
+                                    })
+                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(warning)),
                             )
                             .child(
                                 div()
@@ -247,7 +2317,7 @@ impl SashikiApp {
                                     .gap_2()
                                     .child(
                                         div()
-                                            .id("cancel-delete")
+                                            .id("cancel-discard")
                                             .px_4()
                                             .py_2()
                                             .cursor_pointer()
@@ -257,13 +2327,13 @@ impl SashikiApp {
                                             .text_xs()
                                             .text_color(rgb(TEXT))
                                             .on_click(cx.listener(|this, _, _, cx| {
-                                                this.close_delete_dialog(cx);
+                                                this.close_discard_dialog(cx);
                                             }))
                                             .child("Cancel"),
                                     )
                                     .child(
                                         div()
-                                            .id("confirm-delete")
+                                            .id("confirm-discard")
                                             .px_4()
                                             .py_2()
                                             .cursor_pointer()
@@ -273,9 +2343,9 @@ impl SashikiApp {
                                             .text_xs()
                                             .text_color(rgb(BG_BASE))
                                             .on_click(cx.listener(|this, _, _, cx| {
-                                                this.confirm_delete_worktree(cx);
+                                                this.confirm_discard(cx);
                                             }))
-                                            .child("Delete"),
+                                            .child("Discard"),
                                     ),
                             ),
                     ),
@@ -283,23 +2353,39 @@ impl SashikiApp {
             .into_any_element()
     }
 
-    pub fn render_error_dialog(&self, message: &str, cx: &Context<Self>) -> AnyElement {
-        let message = message.to_string();
+    pub fn render_missing_worktree_dialog(
+        &self,
+        worktree: &Worktree,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let explanation = if worktree.locked {
+            "The worktree is locked, so it is not pruned. Recreating the directory \
+             checks out its branch there again."
+        } else {
+            "Pruning forgets every worktree whose directory is gone and removes \
+             its session. Recreating the directory checks out its branch there \
+             again; changes that were not committed are lost either way."
+        };
 
         div()
-            .id("error-dialog-container")
+            .id("missing-worktree-container")
             .absolute()
             .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_missing_worktree_dialog(cx);
+                }
+            }))
             .child(
                 div()
-                    .id("error-dialog-backdrop")
+                    .id("missing-worktree-backdrop")
                     .absolute()
                     .inset_0()
                     .bg(rgba(OVERLAY))
                     .on_mouse_down(
                         gpui::MouseButton::Left,
                         cx.listener(|this, _, _, cx| {
-                            this.close_error_dialog(cx);
+                            this.close_missing_worktree_dialog(cx);
                         }),
                     ),
             )
@@ -312,12 +2398,12 @@ impl SashikiApp {
                     .justify_center()
                     .child(
                         div()
-                            .id("error-dialog")
+                            .id("missing-worktree-dialog")
                             .occlude()
                             .w_96()
                             .bg(rgb(BG_BASE))
                             .border_1()
-                            .border_color(rgb(RED))
+                            .border_color(rgb(BG_SURFACE1))
                             .rounded_md()
                             .shadow_lg()
                             .child(
@@ -326,11 +2412,36 @@ impl SashikiApp {
                                     .py_3()
                                     .border_b_1()
                                     .border_color(rgb(BG_SURFACE0))
-                                    .text_color(rgb(RED))
+                                    .text_color(rgb(TEXT))
                                     .font_weight(gpui::FontWeight::BOLD)
-                                    .child("Error"),
+                                    .child(format!("Worktree {} is missing", worktree.name)),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_2()
+                                    .text_sm()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .child("Its directory was not found:"),
+                                    )
+                                    .child(
+                                        div()
+                                            .font_family(MONOSPACE_FONT)
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .child(worktree.path.display().to_string()),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child(explanation),
+                                    ),
                             )
-                            .child(div().p_4().text_color(rgb(TEXT)).text_sm().child(message))
                             .child(
                                 div()
                                     .px_4()
@@ -339,9 +2450,10 @@ impl SashikiApp {
                                     .border_color(rgb(BG_SURFACE0))
                                     .flex()
                                     .justify_end()
+                                    .gap_2()
                                     .child(
                                         div()
-                                            .id("dismiss-error")
+                                            .id("cancel-missing-worktree")
                                             .px_4()
                                             .py_2()
                                             .cursor_pointer()
@@ -351,9 +2463,43 @@ impl SashikiApp {
                                             .text_xs()
                                             .text_color(rgb(TEXT))
                                             .on_click(cx.listener(|this, _, _, cx| {
-                                                this.close_error_dialog(cx);
+                                                this.close_missing_worktree_dialog(cx);
                                             }))
-                                            .child("OK"),
+                                            .child("Cancel"),
+                                    )
+                                    .when(!worktree.locked, |el| {
+                                        el.child(
+                                            div()
+                                                .id("prune-missing-worktree")
+                                                .px_4()
+                                                .py_2()
+                                                .cursor_pointer()
+                                                .rounded_sm()
+                                                .bg(rgb(RED))
+                                                .hover(|el| el.bg(rgb(MAROON)))
+                                                .text_xs()
+                                                .text_color(rgb(BG_BASE))
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.prune_missing_worktree(cx);
+                                                }))
+                                                .child("Prune"),
+                                        )
+                                    })
+                                    .child(
+                                        div()
+                                            .id("recreate-missing-worktree")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.recreate_missing_worktree(cx);
+                                            }))
+                                            .child("Recreate directory"),
                                     ),
                             ),
                     ),
@@ -361,138 +2507,88 @@ impl SashikiApp {
             .into_any_element()
     }
 
-    pub fn render_deleting_dialog(&self) -> AnyElement {
-        div()
-            .id("deleting-dialog-container")
-            .absolute()
-            .inset_0()
-            .child(
-                div()
-                    .id("deleting-dialog-backdrop")
-                    .absolute()
-                    .inset_0()
-                    .bg(rgba(OVERLAY)),
-            )
-            .child(
-                div()
-                    .absolute()
-                    .inset_0()
-                    .flex()
-                    .items_center()
-                    .justify_center()
-                    .child(
-                        div()
-                            .id("deleting-dialog")
-                            .occlude()
-                            .w_64()
-                            .bg(rgb(BG_BASE))
-                            .border_1()
-                            .border_color(rgb(YELLOW))
-                            .rounded_md()
-                            .shadow_lg()
-                            .p_4()
-                            .flex()
-                            .flex_col()
-                            .items_center()
-                            .gap_3()
-                            .child(
-                                div()
-                                    .text_color(rgb(YELLOW))
-                                    .text_sm()
-                                    .child("Deleting worktree..."),
-                            )
-                            .child(
-                                div()
-                                    .text_color(rgb(TEXT_MUTED))
-                                    .text_xs()
-                                    .child("Please wait"),
-                            ),
-                    ),
-            )
-            .into_any_element()
-    }
-
-    pub fn render_creating_dialog(
-        &self,
-        branch: &str,
-        steps: &[String],
-        current_step: usize,
-    ) -> AnyElement {
-        let branch = branch.to_string();
-
-        let mut body = div().p_4().flex().flex_col().gap_2();
-
-        for (i, step) in steps.iter().enumerate() {
-            let (icon, color) = if i < current_step {
-                // Completed
-                ("OK ", GREEN)
-            } else if i == current_step {
-                // Running
-                (">> ", YELLOW)
-            } else {
-                // Pending
-                ("   ", TEXT_MUTED)
-            };
+    pub fn render_template_settings_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let active_section = self.settings_active_section;
+        let inputs: Vec<String> = self.settings_inputs.iter().cloned().collect();
+        let cursors = self.settings_cursors;
+        let selected = self.template_selected;
+        let names: Vec<String> = self
+            .template_edit
+            .as_ref()
+            .map(|t| t.named.iter().map(|n| n.name.clone()).collect())
+            .unwrap_or_default();
 
-            body = body.child(
-                div()
-                    .flex()
-                    .gap_2()
-                    .text_xs()
-                    .child(div().text_color(rgb(color)).child(icon))
-                    .child(div().text_color(rgb(color)).child(step.clone())),
-            );
+        let template_tab = |id: gpui::ElementId, label: String, index: Option<usize>| {
+            let is_selected = selected == index;
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .rounded_sm()
+                .cursor_pointer()
+                .text_xs()
+                .when(is_selected, |el| {
+                    el.bg(rgb(BG_SURFACE1)).text_color(rgb(TEXT))
+                })
+                .when(!is_selected, |el| {
+                    el.text_color(rgb(TEXT_MUTED))
+                        .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                })
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.select_template(index, cx);
+                }))
+                .child(label)
+        };
+        let mut selector = div()
+            .flex()
+            .flex_wrap()
+            .items_center()
+            .gap_1()
+            .child(template_tab(
+                "template-tab-default".into(),
+                "Default".to_string(),
+                None,
+            ));
+        for (i, name) in names.iter().enumerate() {
+            selector = selector.child(template_tab(
+                ("template-tab", i).into(),
+                name.clone(),
+                Some(i),
+            ));
         }
-
-        div()
-            .id("creating-dialog-container")
-            .absolute()
-            .inset_0()
-            .child(
-                div()
-                    .id("creating-dialog-backdrop")
-                    .absolute()
-                    .inset_0()
-                    .bg(rgba(OVERLAY)),
-            )
+        selector = selector
             .child(
                 div()
-                    .absolute()
-                    .inset_0()
-                    .flex()
-                    .items_center()
-                    .justify_center()
-                    .child(
-                        div()
-                            .id("creating-dialog")
-                            .occlude()
-                            .w_80()
-                            .bg(rgb(BG_BASE))
-                            .border_1()
-                            .border_color(rgb(GREEN))
-                            .rounded_md()
-                            .shadow_lg()
-                            .child(
-                                div()
-                                    .px_4()
-                                    .py_3()
-                                    .border_b_1()
-                                    .border_color(rgb(BG_SURFACE0))
-                                    .text_color(rgb(GREEN))
-                                    .font_weight(gpui::FontWeight::BOLD)
-                                    .text_sm()
-                                    .child(format!("Creating \"{}\"", branch)),
-                            )
-                            .child(body),
-                    ),
+                    .id("template-add")
+                    .px_2()
+                    .py_1()
+                    .rounded_sm()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(rgb(GREEN))
+                    .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.add_template(cx);
+                    }))
+                    .child("+ Add"),
             )
-            .into_any_element()
-    }
-
-    pub fn render_template_settings_dialog(&self, cx: &Context<Self>) -> AnyElement {
-        let active_section = self.settings_active_section;
-        let inputs: Vec<String> = self.settings_inputs.iter().cloned().collect();
-        let cursors = self.settings_cursors;
+            .when(selected.is_some(), |el| {
+                el.child(
+                    div()
+                        .id("template-delete")
+                        .px_2()
+                        .py_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(RED))
+                        .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.delete_template(cx);
+                        }))
+                        .child("Delete"),
+                )
+            });
 
         div()
             .id("template-settings-container")
@@ -502,6 +2598,12 @@ impl SashikiApp {
             .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
                 let key = &event.keystroke.key;
                 let sec = this.settings_active_section;
+                // Name and branch match exist only for named templates
+                let section_count = if this.template_selected.is_some() {
+                    6
+                } else {
+                    4
+                };
 
                 if key == "escape" {
                     this.close_template_settings(window, cx);
@@ -509,13 +2611,14 @@ impl SashikiApp {
                     this.save_template_settings(window, cx);
                 } else if key == "tab" {
                     if event.keystroke.modifiers.shift {
-                        this.settings_active_section = if sec == 0 { 3 } else { sec - 1 };
+                        this.settings_active_section =
+                            if sec == 0 { section_count - 1 } else { sec - 1 };
                     } else {
-                        this.settings_active_section = (sec + 1) % 4;
+                        this.settings_active_section = (sec + 1) % section_count;
                     }
                     cx.notify();
                 } else if key == "enter" {
-                    if sec == 3 {
+                    if sec >= 3 {
                         this.save_template_settings(window, cx);
                         return;
                     }
@@ -639,6 +2742,36 @@ impl SashikiApp {
                                     .flex()
                                     .flex_col()
                                     .gap_3()
+                                    .child(selector)
+                                    .when(selected.is_some(), |el| {
+                                        el.child(Self::render_textarea_section(
+                                            "Name",
+                                            "e.g. frontend",
+                                            &inputs[4],
+                                            cursors[4],
+                                            4,
+                                            active_section,
+                                            false,
+                                            cx,
+                                        ))
+                                        .child(Self::render_textarea_section(
+                                            "Branch Match (glob)",
+                                            "e.g. frontend/**",
+                                            &inputs[5],
+                                            cursors[5],
+                                            5,
+                                            active_section,
+                                            false,
+                                            cx,
+                                        ))
+                                        .child(
+                                            div().text_color(rgb(TEXT_MUTED)).text_xs().child(
+                                                "* stays within a path segment, ** spans segments, \
+                                             ? is one character. The first matching template \
+                                             is used; Default applies otherwise.",
+                                            ),
+                                        )
+                                    })
                                     .child(Self::render_template_group_header(
                                         "Create-time Actions",
                                     ))
@@ -697,7 +2830,10 @@ impl SashikiApp {
                                             .text_color(rgb(TEXT_MUTED))
                                             .text_xs()
                                             .child("Relative path from worktree root."),
-                                    ),
+                                    )
+                                    .when_some(self.template_error.clone(), |el, error| {
+                                        el.child(div().text_color(rgb(RED)).text_xs().child(error))
+                                    }),
                             )
                             // Footer
                             .child(
@@ -865,7 +3001,7 @@ impl SashikiApp {
 }
 
 /// Get (line, col) from a char-based cursor position in text.
-fn cursor_to_line_col(text: &str, cursor: usize) -> (usize, usize) {
+pub(crate) fn cursor_to_line_col(text: &str, cursor: usize) -> (usize, usize) {
     let mut line = 0;
     let mut col = 0;
     for (i, c) in text.chars().enumerate() {
@@ -884,7 +3020,7 @@ fn cursor_to_line_col(text: &str, cursor: usize) -> (usize, usize) {
 
 /// Get char-based cursor position from (line, col).
 /// Clamps col to the end of the target line if it exceeds the line length.
-fn line_col_to_cursor(text: &str, target_line: usize, target_col: usize) -> usize {
+pub(crate) fn line_col_to_cursor(text: &str, target_line: usize, target_col: usize) -> usize {
     let mut line = 0;
     let mut col = 0;
     for (i, c) in text.chars().enumerate() {
@@ -910,7 +3046,7 @@ fn line_col_to_cursor(text: &str, target_line: usize, target_col: usize) -> usiz
 }
 
 /// Convert a char offset to a byte offset in a string.
-fn char_to_byte_offset(text: &str, char_offset: usize) -> usize {
+pub(crate) fn char_to_byte_offset(text: &str, char_offset: usize) -> usize {
     text.char_indices()
         .nth(char_offset)
         .map(|(i, _)| i)
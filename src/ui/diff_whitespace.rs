@@ -0,0 +1,364 @@
+//! Line-ending and trailing whitespace awareness for unified diffs
+//!
+//! Edits that only flip CRLF/LF or add trailing blanks make `git diff` report
+//! lines that look identical. `diff_stats` tells when a diff is nothing but
+//! line-ending changes, and `ignore_whitespace` rewrites a diff for display
+//! so such lines become context again (the diff given to `git apply` is never
+//! touched). Diff lines are split on `\n` only, so a `\r` stays visible.
+
+use super::tab_stops;
+use std::collections::HashSet;
+
+/// Lines of `text`, keeping a trailing `\r` (unlike `str::lines`)
+pub fn raw_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split_terminator('\n')
+}
+
+/// Summary of a unified diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub added: usize,
+    pub removed: usize,
+    /// Every changed line differs only by a `\r` at its end
+    pub line_ending_only: bool,
+    /// Line endings of the changed lines went from CRLF to LF (false: LF to
+    /// CRLF, or both ways). Only meaningful with `line_ending_only`.
+    pub crlf_to_lf: bool,
+}
+
+fn strip_cr(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+fn strip_trailing_whitespace(line: &str) -> &str {
+    line.trim_end_matches([' ', '\t', '\r'])
+}
+
+/// Consecutive removed and added lines of a hunk
+#[derive(Default)]
+struct ChangeBlock<'a> {
+    removed: Vec<&'a str>,
+    added: Vec<&'a str>,
+}
+
+/// One line of a unified diff
+enum DiffLine<'a> {
+    /// `@@` header, with the start line in the new file
+    Hunk(usize),
+    Removed(&'a str),
+    Added(&'a str),
+    /// Context, file headers and `\ No newline` markers
+    Other(&'a str),
+}
+
+fn classify(line: &str) -> DiffLine<'_> {
+    if line.starts_with("@@") {
+        let new_start = super::FileView::parse_hunk_header(line).map_or(1, |(_, new)| new);
+        DiffLine::Hunk(new_start)
+    } else if line.starts_with("---") || line.starts_with("+++") {
+        DiffLine::Other(line)
+    } else if let Some(text) = line.strip_prefix('-') {
+        DiffLine::Removed(text)
+    } else if let Some(text) = line.strip_prefix('+') {
+        DiffLine::Added(text)
+    } else {
+        DiffLine::Other(line)
+    }
+}
+
+/// Change blocks of `diff`, in order
+fn change_blocks(diff: &str) -> Vec<ChangeBlock<'_>> {
+    let mut blocks = Vec::new();
+    let mut block = ChangeBlock::default();
+    for line in raw_lines(diff) {
+        match classify(line) {
+            DiffLine::Removed(text) if block.added.is_empty() => block.removed.push(text),
+            DiffLine::Removed(text) => {
+                blocks.push(std::mem::take(&mut block));
+                block.removed.push(text);
+            }
+            DiffLine::Added(text) => block.added.push(text),
+            DiffLine::Hunk(_) | DiffLine::Other(_) => {
+                if !block.removed.is_empty() || !block.added.is_empty() {
+                    blocks.push(std::mem::take(&mut block));
+                }
+            }
+        }
+    }
+    if !block.removed.is_empty() || !block.added.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+pub fn diff_stats(diff: &str) -> DiffStats {
+    let blocks = change_blocks(diff);
+    let mut stats = DiffStats {
+        added: blocks.iter().map(|b| b.added.len()).sum(),
+        removed: blocks.iter().map(|b| b.removed.len()).sum(),
+        ..DiffStats::default()
+    };
+
+    let pairs = || {
+        blocks
+            .iter()
+            .flat_map(|b| b.removed.iter().zip(b.added.iter()))
+    };
+    stats.line_ending_only = !blocks.is_empty()
+        && blocks.iter().all(|b| b.removed.len() == b.added.len())
+        && pairs().all(|(old, new)| old != new && strip_cr(old) == strip_cr(new));
+    stats.crlf_to_lf = stats.line_ending_only && pairs().all(|(old, _)| old.ends_with('\r'));
+    stats
+}
+
+/// Diff for display with whitespace-only line changes turned into context
+#[derive(Debug, Clone, Default)]
+pub struct NormalizedDiff {
+    pub diff: String,
+    /// New-file line numbers of the lines that changed only in trailing
+    /// whitespace or line ending
+    pub whitespace_lines: HashSet<usize>,
+}
+
+/// Pair removed and added lines that are equal once trailing whitespace
+/// (including `\r`) is dropped, keeping their order (longest common
+/// subsequence). Returns (removed index, added index) pairs.
+fn whitespace_pairs(removed: &[&str], added: &[&str]) -> Vec<(usize, usize)> {
+    let same = |i: usize, j: usize| {
+        strip_trailing_whitespace(removed[i]) == strip_trailing_whitespace(added[j])
+    };
+    // lengths[i][j]: LCS length of removed[i..] and added[j..]
+    let mut lengths = vec![vec![0usize; added.len() + 1]; removed.len() + 1];
+    for i in (0..removed.len()).rev() {
+        for j in (0..added.len()).rev() {
+            lengths[i][j] = if same(i, j) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < removed.len() && j < added.len() {
+        if same(i, j) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Rewrite `diff` so lines that differ only in trailing whitespace or line
+/// ending show as context (with their new text). Hunk headers keep their
+/// line counts, since such a line counts once on each side either way.
+pub fn ignore_whitespace(diff: &str) -> NormalizedDiff {
+    let mut result = NormalizedDiff::default();
+    let mut block = ChangeBlock::default();
+    let mut new_line = 1usize;
+
+    for line in raw_lines(diff) {
+        match classify(line) {
+            DiffLine::Removed(text) => {
+                if !block.added.is_empty() {
+                    flush_block(&mut block, &mut new_line, &mut result);
+                }
+                block.removed.push(text);
+                continue;
+            }
+            DiffLine::Added(text) => {
+                block.added.push(text);
+                continue;
+            }
+            DiffLine::Hunk(start) => {
+                flush_block(&mut block, &mut new_line, &mut result);
+                new_line = start;
+            }
+            DiffLine::Other(text) => {
+                flush_block(&mut block, &mut new_line, &mut result);
+                if text.starts_with(' ') || text.is_empty() {
+                    new_line += 1;
+                }
+            }
+        }
+        result.diff.push_str(line);
+        result.diff.push('\n');
+    }
+    flush_block(&mut block, &mut new_line, &mut result);
+    result
+}
+
+/// Write a change block, turning whitespace-only pairs into context lines
+fn flush_block(block: &mut ChangeBlock, new_line: &mut usize, out: &mut NormalizedDiff) {
+    let (mut i, mut j) = (0, 0);
+    for (pair_i, pair_j) in whitespace_pairs(&block.removed, &block.added) {
+        write_changes(
+            &block.removed[i..pair_i],
+            &block.added[j..pair_j],
+            new_line,
+            out,
+        );
+        out.diff.push_str(&format!(" {}\n", block.added[pair_j]));
+        out.whitespace_lines.insert(*new_line);
+        *new_line += 1;
+        i = pair_i + 1;
+        j = pair_j + 1;
+    }
+    write_changes(&block.removed[i..], &block.added[j..], new_line, out);
+    *block = ChangeBlock::default();
+}
+
+fn write_changes(removed: &[&str], added: &[&str], new_line: &mut usize, out: &mut NormalizedDiff) {
+    for text in removed {
+        out.diff.push_str(&format!("-{}\n", text));
+    }
+    for text in added {
+        out.diff.push_str(&format!("+{}\n", text));
+        *new_line += 1;
+    }
+}
+
+/// Make trailing blanks and a line-ending `\r` visible, with tabs expanded
+/// to `tab_width` columns (a trailing tab is an arrow padded to its stop)
+pub fn show_whitespace(text: &str, tab_width: usize) -> String {
+    let (text, cr) = match text.strip_suffix('\r') {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let body = text.trim_end_matches([' ', '\t']);
+    let mut shown = String::with_capacity(text.len());
+    let mut column = tab_stops::push_expanded(&mut shown, body, 0, tab_width);
+    for c in text[body.len()..].chars() {
+        if c == '\t' {
+            let span = tab_stops::tab_span(column, tab_width);
+            shown.push('→');
+            shown.extend(std::iter::repeat_n(' ', span - 1));
+            column += span;
+        } else {
+            shown.push('·');
+            column += 1;
+        }
+    }
+    if cr {
+        shown.push('␍');
+    }
+    shown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const CRLF_TO_LF: &str = "diff --git a/a.txt b/a.txt\n\
+        --- a/a.txt\n\
+        +++ b/a.txt\n\
+        @@ -1,3 +1,3 @@\n\
+        -one\r\n\
+        -two\r\n\
+        -three\r\n\
+        +one\n\
+        +two\n\
+        +three\n";
+
+    #[test]
+    fn test_crlf_only_change() {
+        let stats = diff_stats(CRLF_TO_LF);
+        assert_eq!(stats.added, 3);
+        assert_eq!(stats.removed, 3);
+        assert!(stats.line_ending_only);
+        assert!(stats.crlf_to_lf);
+
+        let normalized = ignore_whitespace(CRLF_TO_LF);
+        assert_eq!(diff_stats(&normalized.diff).added, 0);
+        assert_eq!(normalized.whitespace_lines, HashSet::from([1, 2, 3]));
+        assert!(
+            normalized
+                .diff
+                .ends_with("@@ -1,3 +1,3 @@\n one\n two\n three\n")
+        );
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        // Only the CRLF lines were converted; the LF line is context
+        let converted = "@@ -1,3 +1,3 @@\n-a\r\n+a\n b\n-c\r\n+c\n";
+        let stats = diff_stats(converted);
+        assert!(stats.line_ending_only);
+        assert!(stats.crlf_to_lf);
+
+        // One line went each way
+        let both_ways = "@@ -1,2 +1,2 @@\n-a\r\n-b\n+a\n+b\r\n";
+        let stats = diff_stats(both_ways);
+        assert!(stats.line_ending_only);
+        assert!(!stats.crlf_to_lf);
+
+        // A real edit next to a line-ending change
+        let edited = "@@ -1,2 +1,2 @@\n-a\r\n-b\r\n+a\n+x\n";
+        assert!(!diff_stats(edited).line_ending_only);
+        let normalized = ignore_whitespace(edited);
+        assert_eq!(normalized.diff, "@@ -1,2 +1,2 @@\n a\n-b\r\n+x\n");
+        assert_eq!(normalized.whitespace_lines, HashSet::from([1]));
+    }
+
+    #[test]
+    fn test_trailing_whitespace_becomes_context() {
+        let diff = "@@ -4,3 +4,4 @@\n ctx\n-x = 1  \n+new\n+x = 1\n end\n";
+        let normalized = ignore_whitespace(diff);
+        assert_eq!(
+            normalized.diff,
+            "@@ -4,3 +4,4 @@\n ctx\n+new\n x = 1\n end\n"
+        );
+        assert_eq!(normalized.whitespace_lines, HashSet::from([6]));
+        assert!(!diff_stats(diff).line_ending_only);
+    }
+
+    #[test]
+    fn test_ignoring_whitespace_leaves_the_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| crate::test_support::git(dir.path(), args);
+        git(&["init", "-q"]);
+        git(&["config", "core.autocrlf", "false"]);
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        git(&["add", "a.txt"]);
+        git(&[
+            "-c",
+            "user.name=T",
+            "-c",
+            "user.email=t@e",
+            "commit",
+            "-qm",
+            "init",
+        ]);
+        std::fs::write(&path, "one\ntwo  \n").unwrap();
+
+        let repo = crate::git::GitRepo::open(dir.path()).unwrap();
+        let diff = repo.get_file_diff(Path::new("a.txt")).unwrap();
+        let stats = diff_stats(&diff);
+        assert_eq!(stats.removed, 2);
+        assert!(!stats.line_ending_only);
+        let normalized = ignore_whitespace(&diff);
+        assert_eq!(normalized.whitespace_lines.len(), 2);
+
+        // Display only: the file and git's view of it are unchanged
+        assert_eq!(std::fs::read(&path).unwrap(), b"one\ntwo  \n");
+        assert_eq!(repo.get_file_diff(Path::new("a.txt")).unwrap(), diff);
+    }
+
+    #[test]
+    fn test_show_whitespace_markers() {
+        assert_eq!(show_whitespace("let a = 1;  \r", 4), "let a = 1;··␍");
+        assert_eq!(show_whitespace("x\t", 4), "x→  ");
+        assert_eq!(show_whitespace("plain", 4), "plain");
+        // Tabs inside the line are expanded, the trailing one keeps its stop
+        assert_eq!(show_whitespace("\tif x {\t", 4), "    if x {→ ");
+        assert_eq!(show_whitespace("a\t \t", 4), "a→  ·→  ");
+    }
+}
@@ -0,0 +1,206 @@
+//! Following a file that is still being written
+//!
+//! The file is polled by size and modification time. Growth is read from the
+//! last known end of the file and appended; anything else (truncation, a
+//! rewrite in place) falls back to reading the whole file again.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often a followed file is checked for changes
+pub const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Bytes before the read position compared on growth to detect a rewrite
+const TAIL_CHECK_BYTES: u64 = 64;
+
+/// Result of one poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FollowUpdate {
+    Unchanged,
+    /// Text written after the previous end of the file
+    Appended(String),
+    /// The file was truncated or rewritten: full new content
+    Reloaded(String),
+}
+
+/// Read position in a followed file
+#[derive(Debug, Clone)]
+pub struct FileFollower {
+    path: PathBuf,
+    /// Bytes already turned into text (never inside a UTF-8 sequence)
+    offset: u64,
+    /// Last bytes before `offset`, as read
+    tail: Vec<u8>,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileFollower {
+    /// Start following `path` whose current content is `content`
+    pub fn new(path: &Path, content: &str) -> Self {
+        let mut follower = Self {
+            path: path.to_path_buf(),
+            offset: 0,
+            tail: Vec::new(),
+            len: 0,
+            modified: None,
+        };
+        follower.reset(content.as_bytes());
+        follower
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn reset(&mut self, bytes: &[u8]) {
+        self.offset = bytes.len() as u64;
+        self.tail = tail_of(bytes);
+        self.stat();
+    }
+
+    fn stat(&mut self) {
+        if let Ok(metadata) = std::fs::metadata(&self.path) {
+            self.len = metadata.len();
+            self.modified = metadata.modified().ok();
+        }
+    }
+
+    /// Check the file once and read what changed
+    pub fn poll(&mut self) -> io::Result<FollowUpdate> {
+        let metadata = std::fs::metadata(&self.path)?;
+        let len = metadata.len();
+        let modified = metadata.modified().ok();
+
+        if len == self.len && modified == self.modified {
+            return Ok(FollowUpdate::Unchanged);
+        }
+        if len <= self.offset {
+            // Shrunk, or same size with a new mtime: rewritten in place
+            return self.reload();
+        }
+
+        let mut file = File::open(&self.path)?;
+        let tail_len = self.tail.len() as u64;
+        file.seek(SeekFrom::Start(self.offset - tail_len))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        if !bytes.starts_with(&self.tail) {
+            return self.reload();
+        }
+        let new_bytes = &bytes[self.tail.len()..];
+
+        // Keep an incomplete trailing UTF-8 sequence for the next poll
+        let complete = match std::str::from_utf8(new_bytes) {
+            Ok(_) => new_bytes.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => new_bytes.len(),
+        };
+        let text = String::from_utf8_lossy(&new_bytes[..complete]).into_owned();
+
+        self.offset += complete as u64;
+        self.tail = tail_of(&bytes[..self.tail.len() + complete]);
+        self.len = len;
+        self.modified = modified;
+
+        if text.is_empty() {
+            Ok(FollowUpdate::Unchanged)
+        } else {
+            Ok(FollowUpdate::Appended(text))
+        }
+    }
+
+    fn reload(&mut self) -> io::Result<FollowUpdate> {
+        let content = std::fs::read_to_string(&self.path)?;
+        self.reset(content.as_bytes());
+        Ok(FollowUpdate::Reloaded(content))
+    }
+}
+
+fn tail_of(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.len().saturating_sub(TAIL_CHECK_BYTES as usize);
+    bytes[start..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, bytes: &[u8]) {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn test_poll_appends_only_new_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.log");
+        std::fs::write(&path, "step 1\n").unwrap();
+        let mut follower = FileFollower::new(&path, "step 1\n");
+
+        assert_eq!(follower.poll().unwrap(), FollowUpdate::Unchanged);
+
+        append(&path, b"step 2\n");
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Appended("step 2\n".to_string())
+        );
+        append(&path, b"step 3");
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Appended("step 3".to_string())
+        );
+        assert_eq!(follower.poll().unwrap(), FollowUpdate::Unchanged);
+    }
+
+    #[test]
+    fn test_poll_waits_for_complete_utf8_sequence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plan.md");
+        std::fs::write(&path, "").unwrap();
+        let mut follower = FileFollower::new(&path, "");
+
+        let check = "✓".as_bytes();
+        append(&path, &[b"a", &check[..1]].concat());
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Appended("a".to_string())
+        );
+        append(&path, &check[1..]);
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Appended("✓".to_string())
+        );
+    }
+
+    #[test]
+    fn test_poll_reloads_on_truncate_or_rewrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        std::fs::write(&path, "long original content\n").unwrap();
+        let mut follower = FileFollower::new(&path, "long original content\n");
+
+        std::fs::write(&path, "short\n").unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Reloaded("short\n".to_string())
+        );
+
+        // Rewritten with more content: the old tail no longer matches
+        std::fs::write(&path, "shorT\nand more\n").unwrap();
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Reloaded("shorT\nand more\n".to_string())
+        );
+
+        append(&path, b"tail\n");
+        assert_eq!(
+            follower.poll().unwrap(),
+            FollowUpdate::Appended("tail\n".to_string())
+        );
+    }
+}
@@ -2,12 +2,16 @@
 
 use crate::app::SashikiApp;
 use crate::git::ChangeType;
+use crate::review::ReviewState;
 use crate::theme::*;
-use crate::ui::{FileListMode, FileTreeNode, read_dir_shallow};
+use crate::ui::{EXCLUDED_GROUP, FileListMode, FileRow, TextTooltip};
 use gpui::{
     AnyElement, Context, Div, IntoElement, ParentElement, Styled, div, prelude::*, px, rgb,
 };
-use std::path::{Path, PathBuf};
+use std::path::Path;
+
+/// Fixed height of a file list row, which row virtualization relies on
+pub const FILE_ROW_HEIGHT: f32 = 26.0;
 
 /// Render directory expand/collapse arrow and folder icon
 fn render_dir_icons(is_expanded: bool) -> (Div, Div) {
@@ -26,25 +30,35 @@ fn render_dir_icons(is_expanded: bool) -> (Div, Div) {
     (arrow, folder)
 }
 
+/// Color and symbol of a change
+fn change_style(change_type: ChangeType) -> (u32, &'static str) {
+    match change_type {
+        ChangeType::Added => (GREEN, "+"),
+        ChangeType::Modified => (YELLOW, "~"),
+        ChangeType::Deleted => (RED, "-"),
+        ChangeType::Renamed => (BLUE, "→"),
+        ChangeType::Conflicted => (MAROON, "!"),
+        ChangeType::Unknown => (TEXT_MUTED, "?"),
+    }
+}
+
 impl SashikiApp {
     pub fn render_file_list(&self, cx: &Context<Self>) -> AnyElement {
         let mode = self.file_list_mode;
 
         div()
-            .w(px(self.file_list_width))
+            .w(px(self.layout.file_list_width))
             .h_full()
             .bg(rgb(BG_MANTLE))
             .flex()
             .flex_col()
             .child(self.render_file_list_header(mode, cx))
-            .child(match mode {
-                FileListMode::Changes => self.render_changes_tree(cx),
-                FileListMode::AllFiles => self.render_all_files_tree(cx),
-            })
+            .child(self.render_file_rows(cx))
             .into_any_element()
     }
 
     fn render_file_list_header(&self, mode: FileListMode, cx: &Context<Self>) -> impl IntoElement {
+        let comments = self.review_comment_count();
         div()
             .h_8()
             .px_2()
@@ -70,7 +84,7 @@ impl SashikiApp {
                             .text_color(rgb(YELLOW))
                             .on_click(cx.listener(|this, _, _, cx| {
                                 this.file_list_mode = FileListMode::Changes;
-                                this.expanded_dirs.clear();
+                                this.repo.expanded_dirs.clear();
                                 this.build_file_tree();
                                 cx.notify();
                             }))
@@ -89,27 +103,125 @@ impl SashikiApp {
                             .text_color(rgb(BLUE))
                             .on_click(cx.listener(|this, _, _, cx| {
                                 this.file_list_mode = FileListMode::AllFiles;
-                                this.expanded_dirs.clear();
+                                this.repo.expanded_dirs.clear();
                                 cx.notify();
                             }))
                             .child("All"),
                     ),
             )
+            .child(div().flex_1())
+            .when_some(self.render_sparse_badge(), |el, badge| el.child(badge))
+            .when(
+                mode == FileListMode::Changes && !self.repo.changed_files.is_empty(),
+                |el| el.child(self.render_review_progress(cx)),
+            )
+            .when(comments > 0, |el| {
+                el.child(
+                    div()
+                        .id("review-comments")
+                        .px_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                        .text_xs()
+                        .text_color(rgb(MAUVE))
+                        .tooltip(TextTooltip::build("Review comments"))
+                        .on_click(cx.listener(|this, _, window, cx| {
+                            this.open_review_dialog(window, cx);
+                        }))
+                        .child(format!("● {}", comments)),
+                )
+            })
+            .when(!self.repo.dir_changes.is_empty(), |el| {
+                el.child(
+                    div()
+                        .id("expand-to-changes")
+                        .px_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .tooltip(TextTooltip::build("Expand to changes"))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.expand_to_changes();
+                            cx.notify();
+                        }))
+                        .child("⤢"),
+                )
+            })
     }
 
-    fn render_changes_tree(&self, cx: &Context<Self>) -> AnyElement {
-        if let Some(ref tree) = self.file_tree {
-            div()
-                .flex_1()
-                .overflow_hidden()
-                .children(
-                    tree.children
-                        .iter()
-                        .map(|node| self.render_tree_node(node, 0, cx)),
-                )
-                .into_any_element()
-        } else {
+    /// Marks a sparse worktree, telling how many deletions outside its
+    /// patterns are left out of the Changes list
+    fn render_sparse_badge(&self) -> Option<impl IntoElement> {
+        let files = self
+            .repo
+            .session_manager
+            .active_session()
+            .and_then(|s| self.repo.file_cache.get(s.worktree_path()))?;
+        let sparse = files.sparse.as_ref()?;
+        let mut tooltip = format!(
+            "Sparse checkout active ({} mode): only files its patterns check out are listed",
+            if sparse.cone { "cone" } else { "pattern" }
+        );
+        if files.outside_sparse > 0 {
+            tooltip.push_str(&format!(
+                "\n{} deleted file(s) outside the patterns hidden",
+                files.outside_sparse
+            ));
+        }
+        Some(
             div()
+                .id("sparse-checkout")
+                .px_1()
+                .text_xs()
+                .text_color(rgb(TEXT_MUTED))
+                .tooltip(TextTooltip::build(tooltip))
+                .child("sparse checkout active"),
+        )
+    }
+
+    /// "12/30 reviewed" and the toggle that sinks reviewed files to the bottom
+    fn render_review_progress(&self, cx: &Context<Self>) -> impl IntoElement {
+        let (reviewed, total) = self.review_progress();
+        let sink = self.review.sink_reviewed;
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(if reviewed == total { GREEN } else { TEXT_MUTED }))
+                    .child(format!("{}/{} reviewed", reviewed, total)),
+            )
+            .child(
+                div()
+                    .id("sink-reviewed")
+                    .px_1()
+                    .cursor_pointer()
+                    .rounded_sm()
+                    .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                    .text_xs()
+                    .text_color(rgb(if sink { BLUE } else { TEXT_MUTED }))
+                    .tooltip(TextTooltip::build(if sink {
+                        "Keep reviewed files in place"
+                    } else {
+                        "Move reviewed files to the bottom"
+                    }))
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.toggle_sink_reviewed();
+                        cx.notify();
+                    }))
+                    .child("⇣"),
+            )
+    }
+
+    /// Only rows inside the scroll viewport get elements (see `update_file_rows`)
+    fn render_file_rows(&self, cx: &Context<Self>) -> AnyElement {
+        if self.file_rows.is_empty() {
+            return div()
                 .flex_1()
                 .flex()
                 .items_center()
@@ -117,243 +229,336 @@ impl SashikiApp {
                 .text_color(rgb(TEXT_MUTED))
                 .text_sm()
                 .child("No files")
-                .into_any_element()
+                .into_any_element();
         }
+
+        // All mode rows carry absolute paths; selection and terminal paths are relative
+        let base_path = match self.file_list_mode {
+            FileListMode::Changes => None,
+            FileListMode::AllFiles => self
+                .repo
+                .session_manager
+                .active_session()
+                .map(|s| s.worktree_path().to_path_buf()),
+        };
+        let window = &self.file_rows_window;
+
+        div()
+            .id("file-list-scroll")
+            .flex_1()
+            .overflow_y_scroll()
+            .track_scroll(&self.file_list_scroll)
+            .child(div().h(px(window.top_padding)))
+            .children(
+                window
+                    .slice(&self.file_rows)
+                    .iter()
+                    .map(|row| self.render_file_row(row, base_path.as_deref(), cx)),
+            )
+            .child(div().h(px(window.bottom_padding)))
+            .when_some(self.unlisted_untracked(), |el, count| {
+                el.child(
+                    div()
+                        .id("unlisted-untracked")
+                        .h(px(FILE_ROW_HEIGHT))
+                        .px_3()
+                        .flex()
+                        .items_center()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .tooltip(TextTooltip::build(format!(
+                            "Only the first {} files of new directories are listed \
+                             (sashiki.changes.untrackedLimit)",
+                            self.untracked_limit
+                        )))
+                        .child(format!("…and {} more untracked files", count)),
+                )
+            })
+            .into_any_element()
     }
 
-    fn render_tree_node(
+    /// Untracked files of the active session left out of the Changes list
+    fn unlisted_untracked(&self) -> Option<usize> {
+        if self.file_list_mode != FileListMode::Changes {
+            return None;
+        }
+        let session = self.repo.session_manager.active_session()?;
+        let files = self.repo.file_cache.get(session.worktree_path())?;
+        Some(files.unlisted_untracked).filter(|&count| count > 0)
+    }
+
+    fn render_file_row(
         &self,
-        node: &FileTreeNode,
-        depth: usize,
+        row: &FileRow,
+        base_path: Option<&Path>,
         cx: &Context<Self>,
     ) -> AnyElement {
-        let indent = depth * 16;
-        let is_expanded = self.expanded_dirs.contains(&node.path);
-        let node_path = node.path.clone();
-        let node_name = node.name.clone();
-
-        let mut result = div().flex().flex_col();
-
-        if node.is_dir {
-            let click_path = node_path.clone();
-            let node_element = div()
-                .id(format!("tree-dir-{}", node.path.to_string_lossy()))
-                .pl(px(indent as f32))
-                .pr_3()
-                .py_1()
-                .cursor_pointer()
-                .hover(|el| el.bg(rgb(BG_SURFACE0)))
-                .on_click(cx.listener(move |this, _, _, cx| {
-                    this.toggle_dir_expanded(&click_path);
-                    cx.notify();
-                }))
-                .flex()
-                .items_center()
-                .gap_2();
-            let (arrow, folder) = render_dir_icons(is_expanded);
-            let node_element = node_element
-                .child(arrow)
-                .child(folder)
-                .child(div().text_color(rgb(TEXT)).text_sm().child(node_name));
-
-            result = result.child(node_element);
+        let indent = row.depth * 16;
+        let is_nav_cursor = self.repo.list_nav.is_entry_selected(&row.path);
+        let is_revealed = self.revealed_row.as_ref() == Some(&row.path);
 
-            if is_expanded {
-                for child in &node.children {
-                    result = result.child(self.render_tree_node(child, depth + 1, cx));
-                }
-            }
-        } else {
-            let click_path = node_path.clone();
-            let right_click_path = node_path.clone();
-            let change_info = node.change_info;
-            let (color, symbol) = if let Some(info) = change_info {
-                match info.change_type {
-                    ChangeType::Added => (GREEN, "+"),
-                    ChangeType::Modified => (YELLOW, "~"),
-                    ChangeType::Deleted => (RED, "-"),
-                    ChangeType::Renamed => (BLUE, "→"),
-                    ChangeType::Unknown => (TEXT_MUTED, "?"),
-                }
-            } else {
-                (TEXT_MUTED, "")
-            };
+        let element = div()
+            .id(format!("file-row-{}", row.path.to_string_lossy()))
+            .h(px(FILE_ROW_HEIGHT))
+            .flex_shrink_0()
+            .pl(px(indent as f32))
+            .pr_3()
+            .cursor_pointer()
+            .when(is_revealed, |el| el.bg(rgb(BG_SURFACE1)))
+            .when(is_nav_cursor, |el| el.bg(rgb(BG_SURFACE2)))
+            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+            .flex()
+            .items_center()
+            .gap_2();
 
-            let node_element = div()
-                .id(format!("tree-file-{}", node.path.to_string_lossy()))
-                .pl(px(indent as f32))
-                .pr_3()
-                .py_1()
-                .cursor_pointer()
-                .hover(|el| el.bg(rgb(BG_SURFACE0)))
+        if row.is_submodule {
+            let relative_path = base_path
+                .and_then(|base| row.path.strip_prefix(base).ok())
+                .unwrap_or(&row.path)
+                .to_path_buf();
+            let change_type = row.change_info.map(|info| info.change_type);
+            return element
                 .on_click(cx.listener(move |this, _, _, cx| {
-                    this.on_file_selected(
-                        click_path.clone(),
-                        change_info.map(|i| i.change_type),
-                        cx,
-                    );
+                    this.on_file_selected(relative_path.clone(), change_type, cx);
                 }))
-                .on_mouse_down(
-                    gpui::MouseButton::Right,
-                    cx.listener(move |this, _, _, cx| {
-                        let path_str = format!("`{}`", right_click_path.to_string_lossy());
-                        this.send_to_terminal(&path_str, cx);
-                    }),
-                )
-                .flex()
-                .items_center()
-                .gap_2()
                 .child(
                     div()
                         .w_4()
                         .text_center()
-                        .text_color(rgb(color))
+                        .text_color(rgb(YELLOW))
                         .text_xs()
                         .font_weight(gpui::FontWeight::BOLD)
-                        .child(symbol),
+                        .child(if change_type.is_some() { "~" } else { "" }),
                 )
                 .child(
                     div()
                         .w_4()
                         .text_center()
-                        .text_color(rgb(TEXT_MUTED))
+                        .text_color(rgb(TEAL))
                         .text_sm()
-                        .child("📄"),
+                        .child("📦"),
                 )
-                .child(div().text_color(rgb(TEXT)).text_sm().child(node_name));
-
-            result = result.child(node_element);
+                .child(
+                    div()
+                        .flex_1()
+                        .text_color(rgb(TEXT))
+                        .text_sm()
+                        .child(row.name.clone()),
+                )
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child("submodule"),
+                )
+                .into_any_element();
         }
 
-        result.into_any_element()
-    }
-
-    fn render_all_files_tree(&self, cx: &Context<Self>) -> AnyElement {
-        let base_path = if let Some(session) = self.session_manager.active_session() {
-            session.worktree_path().to_path_buf()
-        } else {
-            PathBuf::from(".")
-        };
-
-        let entries = read_dir_shallow(&base_path).unwrap_or_default();
-
-        if entries.is_empty() {
-            return div()
-                .flex_1()
-                .flex()
-                .items_center()
-                .justify_center()
-                .text_color(rgb(TEXT_MUTED))
-                .text_sm()
-                .child("No files")
+        if row.is_nested_repo {
+            let relative_path = base_path
+                .and_then(|base| row.path.strip_prefix(base).ok())
+                .unwrap_or(&row.path)
+                .to_path_buf();
+            return element
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.on_file_selected(relative_path.clone(), None, cx);
+                }))
+                .child(div().w_4())
+                .child(
+                    div()
+                        .w_4()
+                        .text_center()
+                        .text_color(rgb(MAUVE))
+                        .text_sm()
+                        .child("⎇"),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .text_color(rgb(TEXT))
+                        .text_sm()
+                        .child(row.name.clone()),
+                )
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child("nested repository"),
+                )
                 .into_any_element();
         }
 
-        div()
-            .flex_1()
-            .overflow_hidden()
-            .children(
-                entries.iter().map(|(path, is_dir)| {
-                    self.render_lazy_tree_node(path, *is_dir, 0, &base_path, cx)
-                }),
-            )
-            .into_any_element()
-    }
-
-    fn render_lazy_tree_node(
-        &self,
-        path: &Path,
-        is_dir: bool,
-        depth: usize,
-        base_path: &Path,
-        cx: &Context<Self>,
-    ) -> AnyElement {
-        let indent = depth * 16;
-        let is_expanded = self.expanded_dirs.contains(path);
-        let node_path = path.to_path_buf();
-        let node_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("")
-            .to_string();
-
-        let mut result = div().flex().flex_col();
-
-        if is_dir {
-            let click_path = node_path.clone();
-            let node_element = div()
-                .id(format!("lazy-dir-{}", path.to_string_lossy()))
-                .pl(px(indent as f32))
-                .pr_3()
-                .py_1()
-                .cursor_pointer()
-                .hover(|el| el.bg(rgb(BG_SURFACE0)))
+        if row.is_dir {
+            let is_expanded = self.repo.expanded_dirs.contains(&row.path);
+            let is_excluded_group = row.path == Path::new(EXCLUDED_GROUP);
+            let click_path = row.path.clone();
+            let (arrow, folder) = render_dir_icons(is_expanded);
+            let changes = (!is_expanded)
+                .then(|| {
+                    let relative = base_path
+                        .and_then(|base| row.path.strip_prefix(base).ok())
+                        .unwrap_or(&row.path);
+                    self.repo.dir_changes.get(relative)
+                })
+                .flatten();
+            return element
                 .on_click(cx.listener(move |this, _, _, cx| {
                     this.toggle_dir_expanded(&click_path);
                     cx.notify();
                 }))
-                .flex()
-                .items_center()
-                .gap_2();
-            let (arrow, folder) = render_dir_icons(is_expanded);
-            let node_element = node_element
                 .child(arrow)
                 .child(folder)
-                .child(div().text_color(rgb(TEXT)).text_sm().child(node_name));
-
-            result = result.child(node_element);
-
-            if is_expanded && let Ok(children) = read_dir_shallow(&node_path) {
-                for (child_path, child_is_dir) in children {
-                    result = result.child(self.render_lazy_tree_node(
-                        &child_path,
-                        child_is_dir,
-                        depth + 1,
-                        base_path,
-                        cx,
-                    ));
-                }
-            }
-        } else {
-            let relative_path = path.strip_prefix(base_path).unwrap_or(path).to_path_buf();
-            let click_path = relative_path.clone();
-            let right_click_path = relative_path.clone();
-
-            let node_element = div()
-                .id(format!("lazy-file-{}", path.to_string_lossy()))
-                .pl(px(indent as f32))
-                .pr_3()
-                .py_1()
-                .cursor_pointer()
-                .hover(|el| el.bg(rgb(BG_SURFACE0)))
-                .on_click(cx.listener(move |this, _, _, cx| {
-                    this.on_file_selected(click_path.clone(), None, cx);
-                }))
-                .on_mouse_down(
-                    gpui::MouseButton::Right,
-                    cx.listener(move |this, _, _, cx| {
-                        let path_str = format!("`{}`", right_click_path.to_string_lossy());
-                        this.send_to_terminal(&path_str, cx);
-                    }),
-                )
-                .flex()
-                .items_center()
-                .gap_2()
-                // Spacer for alignment with Changes mode (which shows change symbols here)
-                .child(div().w_4())
                 .child(
                     div()
-                        .w_4()
-                        .text_center()
-                        .text_color(rgb(TEXT_MUTED))
+                        .flex_1()
+                        .text_color(rgb(if is_excluded_group { TEXT_MUTED } else { TEXT }))
                         .text_sm()
-                        .child("📄"),
+                        .child(row.name.clone()),
                 )
-                .child(div().text_color(rgb(TEXT)).text_sm().child(node_name));
+                .when_some(changes, |el, summary| {
+                    let (color, _) = change_style(summary.change_type);
+                    el.child(
+                        div()
+                            .px_1()
+                            .rounded_sm()
+                            .bg(rgb(color))
+                            .text_color(rgb(BG_BASE))
+                            .text_xs()
+                            .child(summary.count.to_string()),
+                    )
+                })
+                .into_any_element();
+        }
 
-            result = result.child(node_element);
+        let relative_path = base_path
+            .and_then(|base| row.path.strip_prefix(base).ok())
+            .unwrap_or(&row.path)
+            .to_path_buf();
+        let changed = self.changed_file(&relative_path);
+        let mut name = match changed.and_then(|f| f.old_path.as_deref()) {
+            Some(old_path) => format!("{} → {}", old_path.display(), row.name),
+            None => row.name.clone(),
+        };
+        // A rename git detected with edits: how much of the file it kept
+        if let Some(similarity) = changed.and_then(|f| f.similarity).filter(|s| *s < 100) {
+            name.push_str(&format!(" ({}%)", similarity));
+        }
+        if let Some(submodule) = changed.and_then(|f| f.submodule) {
+            name.push_str(&format!(" ({})", submodule.describe()));
         }
+        let click_path = relative_path.clone();
+        let review_path = relative_path.clone();
+        let discard_path = relative_path.clone();
+        let right_click_path = relative_path;
+        let change_info = row.change_info;
+        let can_discard = !self
+            .repo
+            .session_manager
+            .active_session()
+            .is_some_and(|s| s.is_read_only());
+        let (color, symbol) = change_info
+            .map(|info| change_style(info.change_type))
+            .unwrap_or((TEXT_MUTED, ""));
+        // Reviewed marks belong to the Changes list, excluded files aside
+        let review_state = change_info
+            .filter(|_| self.file_list_mode == FileListMode::Changes)
+            .filter(|_| !self.is_excluded(&review_path))
+            .map(|_| self.review_state(&review_path));
 
-        result.into_any_element()
+        element
+            .when(review_state == Some(ReviewState::Reviewed), |el| {
+                el.opacity(0.5)
+            })
+            .on_click(cx.listener(move |this, _, _, cx| {
+                this.on_file_selected(click_path.clone(), change_info.map(|i| i.change_type), cx);
+            }))
+            .on_mouse_down(
+                gpui::MouseButton::Right,
+                cx.listener(move |this, _, _, cx| {
+                    this.insert_path(&right_click_path, cx);
+                }),
+            )
+            // Change symbol (blank in All mode, keeping names aligned)
+            .child(
+                div()
+                    .w_4()
+                    .text_center()
+                    .text_color(rgb(color))
+                    .text_xs()
+                    .font_weight(gpui::FontWeight::BOLD)
+                    .child(symbol),
+            )
+            .child(
+                div()
+                    .w_4()
+                    .text_center()
+                    .text_color(rgb(TEXT_MUTED))
+                    .text_sm()
+                    .child("📄"),
+            )
+            .child(div().flex_1().text_color(rgb(TEXT)).text_sm().child(name))
+            .when(
+                review_state == Some(ReviewState::ChangedSinceReview),
+                |el| {
+                    el.child(
+                        div()
+                            .id(format!("review-changed-{}", review_path.to_string_lossy()))
+                            .px_1()
+                            .rounded_sm()
+                            .bg(rgb(PEACH))
+                            .text_color(rgb(BG_BASE))
+                            .text_xs()
+                            .tooltip(TextTooltip::build("Changed since review"))
+                            .child("changed"),
+                    )
+                },
+            )
+            .when_some(review_state, |el, state| {
+                let reviewed = state == ReviewState::Reviewed;
+                el.child(
+                    div()
+                        .id(format!("review-{}", review_path.to_string_lossy()))
+                        .w_4()
+                        .text_center()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(if reviewed { GREEN } else { TEXT_MUTED }))
+                        .hover(|el| el.text_color(rgb(TEXT)))
+                        .tooltip(TextTooltip::build(if reviewed {
+                            "Mark as not reviewed (r)"
+                        } else {
+                            "Mark as reviewed (r)"
+                        }))
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.toggle_reviewed(&review_path);
+                            cx.notify();
+                        }))
+                        .child(if reviewed { "☑" } else { "☐" }),
+                )
+            })
+            .when_some(change_info.filter(|_| can_discard), |el, info| {
+                el.child(
+                    div()
+                        .id(format!("discard-{}", discard_path.to_string_lossy()))
+                        .px_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .hover(|el| el.text_color(rgb(RED)).bg(rgb(BG_SURFACE1)))
+                        .tooltip(TextTooltip::build("Discard changes"))
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.open_discard_file_dialog(
+                                discard_path.clone(),
+                                info.change_type,
+                                cx,
+                            );
+                        }))
+                        .child("↺"),
+                )
+            })
+            .into_any_element()
     }
 }
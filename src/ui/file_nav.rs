@@ -0,0 +1,173 @@
+//! Stepping through the changed files from the file view
+//!
+//! Next and previous file follow the Changes list as it is displayed
+//! (directories first, then by name), files in collapsed directories
+//! included, and wrap at the ends. When a refresh drops the open file (an
+//! agent reverted it), the step starts from where the file was instead.
+
+use super::FileTreeNode;
+use crate::git::ChangedFile;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The open file's place in the review order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilePosition {
+    pub index: usize,
+    pub count: usize,
+    /// Reached by stepping past either end
+    pub wrapped: bool,
+}
+
+impl FilePosition {
+    /// "file 4 of 17"
+    pub fn label(&self) -> String {
+        format!("file {} of {}", self.index + 1, self.count)
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.count
+    }
+}
+
+/// `files` in the order the Changes list shows them
+pub fn review_order<'a>(files: impl IntoIterator<Item = &'a ChangedFile>) -> Vec<&'a ChangedFile> {
+    let by_path: HashMap<&Path, &ChangedFile> =
+        files.into_iter().map(|f| (f.path.as_path(), f)).collect();
+    let tree = FileTreeNode::from_files(by_path.keys().map(|path| (path.to_path_buf(), None)));
+    tree.file_paths()
+        .into_iter()
+        .filter_map(|path| by_path.get(path).copied())
+        .collect()
+}
+
+/// Position of the file after (`forward`) or before the open one in a list
+/// of `count` files. `current` is the open file's index, None when it is not
+/// in the list; then `last_index`, where it was, stands in: forward lands on
+/// the file that took its place. With neither, the first or last file.
+pub fn step(
+    count: usize,
+    current: Option<usize>,
+    last_index: Option<usize>,
+    forward: bool,
+) -> Option<FilePosition> {
+    if count == 0 {
+        return None;
+    }
+    // Where the next file would be, and the index the previous one is below
+    let (next, below) = match (current, last_index) {
+        (Some(current), _) => (current + 1, current),
+        (None, Some(last)) => (last, last),
+        (None, None) => (0, count),
+    };
+    let (index, wrapped) = if forward {
+        if next < count {
+            (next, false)
+        } else {
+            (0, true)
+        }
+    } else {
+        match below.checked_sub(1) {
+            Some(previous) => (previous.min(count - 1), false),
+            None => (count - 1, true),
+        }
+    };
+    Some(FilePosition {
+        index,
+        count,
+        wrapped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::ChangeType;
+    use std::path::PathBuf;
+
+    fn changed(path: &str) -> ChangedFile {
+        ChangedFile {
+            path: PathBuf::from(path),
+            change_type: ChangeType::Modified,
+            staged: false,
+            old_path: None,
+            similarity: None,
+            submodule: None,
+            outside_sparse: false,
+            nested_repository: false,
+        }
+    }
+
+    fn index(position: Option<FilePosition>) -> Option<(usize, bool)> {
+        position.map(|p| (p.index, p.wrapped))
+    }
+
+    #[test]
+    fn test_review_order_matches_changes_list() {
+        let files = vec![
+            changed("z.rs"),
+            changed("src/main.rs"),
+            changed("README.md"),
+            changed("src/app/a.rs"),
+            changed("docs/guide.md"),
+        ];
+        let order: Vec<&str> = review_order(&files)
+            .iter()
+            .map(|f| f.path.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "docs/guide.md",
+                "src/app/a.rs",
+                "src/main.rs",
+                "README.md",
+                "z.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step_wraps_at_the_ends() {
+        assert_eq!(index(step(3, Some(0), None, true)), Some((1, false)));
+        assert_eq!(index(step(3, Some(2), None, true)), Some((0, true)));
+        assert_eq!(index(step(3, Some(1), None, false)), Some((0, false)));
+        assert_eq!(index(step(3, Some(0), None, false)), Some((2, true)));
+        assert_eq!(index(step(1, Some(0), None, true)), Some((0, true)));
+        assert_eq!(index(step(0, Some(0), None, true)), None);
+        assert_eq!(index(step(3, None, None, true)), Some((0, false)));
+        assert_eq!(index(step(3, None, None, false)), Some((2, false)));
+    }
+
+    #[test]
+    fn test_step_from_a_file_that_disappeared() {
+        // The open file was at index 1 of 4; now 3 files are left
+        assert_eq!(index(step(3, None, Some(1), true)), Some((1, false)));
+        assert_eq!(index(step(3, None, Some(1), false)), Some((0, false)));
+        // It was the last one
+        assert_eq!(index(step(3, None, Some(3), true)), Some((0, true)));
+        assert_eq!(index(step(3, None, Some(3), false)), Some((2, false)));
+        // It was the first one
+        assert_eq!(index(step(3, None, Some(0), false)), Some((2, true)));
+        // The list shrank by more than one
+        assert_eq!(index(step(2, None, Some(7), false)), Some((1, false)));
+    }
+
+    #[test]
+    fn test_position_label() {
+        let position = FilePosition {
+            index: 3,
+            count: 17,
+            wrapped: false,
+        };
+        assert_eq!(position.label(), "file 4 of 17");
+        assert!(!position.is_last());
+        assert!(
+            FilePosition {
+                index: 16,
+                ..position
+            }
+            .is_last()
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Classifying files before the file view opens them
+//!
+//! Reading a PNG as text shows garbage and reading a log of hundreds of
+//! megabytes blocks the UI, so a file is stat'ed and its first `SNIFF_LEN`
+//! bytes sniffed before anything else. Binary files get an info panel; text
+//! files over the size limit (`sashiki.viewer.maxFileSize`) are paged from
+//! disk through a line index instead of being read whole.
+
+pub use crate::encoding::TextEncoding;
+use crate::encoding::{self, decode};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// Bytes read to tell text from binary
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+/// Bytes of a single line shown in paged mode (minified files can be one
+/// line of hundreds of megabytes)
+pub const MAX_LINE_BYTES: usize = 4 * 1024;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// How the file view opens a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// Read whole, diffed as usual
+    Text(TextEncoding),
+    /// Text over the size limit: paged, no diff
+    Large(TextEncoding),
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileProbe {
+    pub kind: FileKind,
+    pub size: u64,
+}
+
+impl FileProbe {
+    pub fn is_text(&self) -> bool {
+        matches!(self.kind, FileKind::Text(_))
+    }
+}
+
+/// Encoding of a file starting with `head`, None if it looks binary (see
+/// `crate::encoding` for how it is told)
+pub fn sniff(head: &[u8]) -> Option<TextEncoding> {
+    encoding::detect(head)
+}
+
+/// Stat and sniff `path`. Text larger than `max_size` bytes is `Large`.
+pub fn probe(path: &Path, max_size: u64) -> io::Result<FileProbe> {
+    let size = std::fs::metadata(path)?.len();
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+
+    let kind = match sniff(&head) {
+        None => FileKind::Binary,
+        Some(encoding) if size > max_size => FileKind::Large(encoding),
+        Some(encoding) => FileKind::Text(encoding),
+    };
+    Ok(FileProbe { kind, size })
+}
+
+/// Read a whole text file in its sniffed encoding, without the BOM, along
+/// with that encoding. Binary files are an `InvalidData` error.
+pub fn read_text(path: &Path) -> io::Result<(String, TextEncoding)> {
+    let bytes = std::fs::read(path)?;
+    match sniff(&bytes[..bytes.len().min(SNIFF_LEN)]) {
+        Some(encoding) => Ok((decode(&bytes, encoding), encoding)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "binary file")),
+    }
+}
+
+/// Whether the file view can show `path` as an image
+pub fn is_image(path: &Path) -> bool {
+    const EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "bmp", "svg", "ico"];
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// Byte offsets of the lines of a UTF-8 file, for reading any range of
+/// lines without loading the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Start of each line; a trailing newline does not start another line
+    starts: Vec<u64>,
+    len: u64,
+}
+
+impl LineIndex {
+    /// Scan `path` once (meant for a background thread)
+    pub fn build(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::with_capacity(64 * 1024, File::open(path)?);
+        let mut starts = vec![0];
+        let mut offset = 0u64;
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            starts.extend(
+                chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, byte)| **byte == b'\n')
+                    .map(|(i, _)| offset + i as u64 + 1),
+            );
+            let read = chunk.len();
+            offset += read as u64;
+            reader.consume(read);
+        }
+        if starts.last() == Some(&offset) && offset > 0 {
+            starts.pop();
+        }
+        Ok(Self {
+            starts,
+            len: offset,
+        })
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Lines in `range` (clamped) decoded from `encoding` (UTF-8 or a legacy
+    /// one, whose newlines are single bytes too), without line endings.
+    /// Lines longer than `MAX_LINE_BYTES` are cut off with "…".
+    pub fn read_lines(
+        &self,
+        path: &Path,
+        range: Range<usize>,
+        encoding: TextEncoding,
+    ) -> io::Result<Vec<String>> {
+        let end = range.end.min(self.starts.len());
+        let start = range.start.min(end);
+        let mut file = File::open(path)?;
+        let mut lines = Vec::with_capacity(end - start);
+        for i in start..end {
+            let line_start = self.starts[i];
+            let line_end = self.starts.get(i + 1).copied().unwrap_or(self.len);
+            let full_len = (line_end - line_start) as usize;
+            let mut bytes = vec![0; full_len.min(MAX_LINE_BYTES)];
+            file.seek(SeekFrom::Start(line_start))?;
+            file.read_exact(&mut bytes)?;
+
+            let truncated = full_len > MAX_LINE_BYTES;
+            if i == 0 {
+                bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(&bytes).to_vec();
+            }
+            let mut line = decode(&bytes, encoding);
+            if truncated {
+                line.push('…');
+            } else {
+                let trimmed = line.trim_end_matches(['\n', '\r']).len();
+                line.truncate(trimmed);
+            }
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+    const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+    fn utf16(text: &str, bom: &[u8], to: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut bytes = bom.to_vec();
+        bytes.extend(text.encode_utf16().flat_map(to));
+        bytes
+    }
+
+    #[test]
+    fn test_sniff_text_and_binary() {
+        assert_eq!(sniff(b""), Some(TextEncoding::Utf8));
+        assert_eq!(sniff(b"fn main() {}\n"), Some(TextEncoding::Utf8));
+        assert_eq!(sniff("日本語\n".as_bytes()), Some(TextEncoding::Utf8));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), None);
+        assert_eq!(sniff(b"abc\0def"), None);
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0 JFIF"), None);
+    }
+
+    #[test]
+    fn test_sniff_multibyte_char_cut_at_the_end() {
+        let text = "あ".repeat(10);
+        let head = &text.as_bytes()[..text.len() - 1];
+        assert_eq!(sniff(head), Some(TextEncoding::Utf8));
+    }
+
+    #[test]
+    fn test_sniff_and_decode_boms() {
+        let utf8 = [UTF8_BOM, b"a = 1\n"].concat();
+        assert_eq!(sniff(&utf8), Some(TextEncoding::Utf8));
+        assert_eq!(decode(&utf8, TextEncoding::Utf8), "a = 1\n");
+
+        let le = utf16("Hello, 世界\n", UTF16_LE_BOM, u16::to_le_bytes);
+        assert_eq!(sniff(&le), Some(TextEncoding::Utf16Le));
+        assert_eq!(decode(&le, TextEncoding::Utf16Le), "Hello, 世界\n");
+
+        let be = utf16("Hello\n", UTF16_BE_BOM, u16::to_be_bytes);
+        assert_eq!(sniff(&be), Some(TextEncoding::Utf16Be));
+        assert_eq!(decode(&be, TextEncoding::Utf16Be), "Hello\n");
+
+        // Without a BOM the NUL bytes of UTF-16 read as binary
+        let bare = utf16("Hello\n", &[], u16::to_le_bytes);
+        assert_eq!(sniff(&bare), None);
+    }
+
+    #[test]
+    fn test_probe_classifies_by_content_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+
+        let empty = probe(&write("empty.txt", b""), 10).unwrap();
+        assert_eq!(empty.kind, FileKind::Text(TextEncoding::Utf8));
+        assert_eq!(empty.size, 0);
+
+        let small = probe(&write("small.txt", b"0123456789"), 10).unwrap();
+        assert_eq!(small.kind, FileKind::Text(TextEncoding::Utf8));
+
+        let large = probe(&write("large.log", b"0123456789\n"), 10).unwrap();
+        assert_eq!(large.kind, FileKind::Large(TextEncoding::Utf8));
+
+        let binary = probe(&write("image.png", b"\x89PNG\0\0"), 10).unwrap();
+        assert_eq!(binary.kind, FileKind::Binary);
+    }
+
+    #[test]
+    fn test_legacy_text_is_not_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("readme.txt");
+        // "こんにちは\n" and "さようなら\n" in Shift_JIS
+        let shift_jis =
+            b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd\n\x82\xb3\x82\xe6\x82\xa4\x82\xc8\x82\xe7\n";
+        std::fs::write(&path, shift_jis).unwrap();
+
+        let legacy = TextEncoding::Legacy(encoding_rs::SHIFT_JIS);
+        assert_eq!(probe(&path, 1024).unwrap().kind, FileKind::Text(legacy));
+        assert_eq!(
+            read_text(&path).unwrap(),
+            ("こんにちは\nさようなら\n".to_string(), legacy)
+        );
+
+        // Paged when large, line by line in the same encoding
+        assert_eq!(probe(&path, 4).unwrap().kind, FileKind::Large(legacy));
+        let index = LineIndex::build(&path).unwrap();
+        assert_eq!(
+            index.read_lines(&path, 1..2, legacy).unwrap(),
+            vec!["さようなら"]
+        );
+    }
+
+    #[test]
+    fn test_line_index_reads_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        let long = "x".repeat(MAX_LINE_BYTES + 10);
+        std::fs::write(&path, format!("\u{feff}first\r\nsecond\n\n{}\nlast", long)).unwrap();
+
+        let index = LineIndex::build(&path).unwrap();
+        assert_eq!(index.line_count(), 5);
+        assert_eq!(
+            index.read_lines(&path, 0..3, TextEncoding::Utf8).unwrap(),
+            vec!["first", "second", ""]
+        );
+        let tail = index.read_lines(&path, 3..100, TextEncoding::Utf8).unwrap();
+        assert_eq!(tail.len(), 2);
+        assert!(tail[0].ends_with('…'));
+        assert_eq!(tail[1], "last");
+
+        std::fs::write(&path, "a\nb\n").unwrap();
+        let index = LineIndex::build(&path).unwrap();
+        assert_eq!(index.line_count(), 2);
+
+        std::fs::write(&path, "").unwrap();
+        let index = LineIndex::build(&path).unwrap();
+        assert_eq!(
+            index.read_lines(&path, 0..10, TextEncoding::Utf8).unwrap(),
+            vec![""]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Template file sync dialog rendering (see `crate::file_sync`)
+
+use crate::app::SashikiApp;
+use crate::file_sync::{SyncFile, SyncKind};
+use crate::theme::*;
+use crate::ui::TextTooltip;
+use gpui::{
+    AnyElement, Context, IntoElement, ParentElement, Styled, div, prelude::*, px, rgb, rgba,
+};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+impl SashikiApp {
+    pub fn render_sync_files_dialog(
+        &self,
+        target_index: usize,
+        files: &[SyncFile],
+        selected: &HashSet<PathBuf>,
+        show_identical: bool,
+        preview: Option<&(PathBuf, String)>,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let session_name = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .map(|s| s.name().to_string())
+            .unwrap_or_default();
+        let identical_count = files
+            .iter()
+            .filter(|f| f.kind == SyncKind::Identical)
+            .count();
+        let can_confirm = !selected.is_empty();
+        let visible: Vec<&SyncFile> = files
+            .iter()
+            .filter(|f| show_identical || f.kind != SyncKind::Identical)
+            .collect();
+
+        let list = div()
+            .id("sync-file-list")
+            .max_h_48()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .when(visible.is_empty(), |el| {
+                el.child(div().py_2().text_xs().text_color(rgb(TEXT_MUTED)).child(
+                    if files.is_empty() {
+                        "No files match the fileCopy patterns"
+                    } else {
+                        "All files are up to date"
+                    },
+                ))
+            })
+            .children(visible.into_iter().enumerate().map(|(i, file)| {
+                let is_selected = selected.contains(&file.path);
+                let is_previewed = preview.is_some_and(|(path, _)| *path == file.path);
+                let toggle_path = file.path.clone();
+                let preview_path = file.path.clone();
+                let (label, color) = match file.kind {
+                    SyncKind::Create => ("new", GREEN),
+                    SyncKind::Overwrite => ("overwrite", YELLOW),
+                    SyncKind::Identical => ("same", TEXT_MUTED),
+                };
+
+                div()
+                    .py_0p5()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .text_xs()
+                    .when(is_previewed, |el| el.bg(rgb(BG_SURFACE0)))
+                    .child(
+                        div()
+                            .id(("sync-file-check", i))
+                            .cursor_pointer()
+                            .text_color(rgb(if is_selected { GREEN } else { TEXT_MUTED }))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.toggle_sync_file(&toggle_path, cx);
+                            }))
+                            .child(if is_selected { "☑" } else { "☐" }),
+                    )
+                    .child(
+                        div()
+                            .id(("sync-file-name", i))
+                            .flex_1()
+                            .truncate()
+                            .cursor_pointer()
+                            .font_family(MONOSPACE_FONT)
+                            .text_color(rgb(TEXT))
+                            .hover(|el| el.text_color(rgb(BLUE)))
+                            .tooltip(TextTooltip::build("Show diff"))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.toggle_sync_preview(&preview_path, cx);
+                            }))
+                            .child(format!(
+                                "{}{}",
+                                file.path.display(),
+                                if file.symlink { " ⇢" } else { "" }
+                            )),
+                    )
+                    .when(file.dest_newer, |el| {
+                        el.child(
+                            div()
+                                .id(("sync-file-newer", i))
+                                .text_color(rgb(YELLOW))
+                                .tooltip(TextTooltip::build(
+                                    "The session's copy is newer than the main worktree's",
+                                ))
+                                .child("⚠"),
+                        )
+                    })
+                    .child(div().flex_shrink_0().text_color(rgb(color)).child(label))
+            }));
+
+        let preview_view = preview.map(|(path, diff)| {
+            div()
+                .flex()
+                .flex_col()
+                .gap_1()
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(format!("Changes to {}:", path.display())),
+                )
+                .child(
+                    div()
+                        .id("sync-file-diff")
+                        .max_h_48()
+                        .overflow_y_scroll()
+                        .p_2()
+                        .bg(rgb(BG_MANTLE))
+                        .rounded_sm()
+                        .font_family(MONOSPACE_FONT)
+                        .text_xs()
+                        .children(
+                            diff.lines()
+                                .filter(|line| {
+                                    !line.starts_with("diff --git")
+                                        && !line.starts_with("index ")
+                                        && !line.starts_with("--- ")
+                                        && !line.starts_with("+++ ")
+                                        && !line.starts_with("new file mode")
+                                })
+                                .map(|line| {
+                                    let color = if line.starts_with('+') {
+                                        GREEN
+                                    } else if line.starts_with('-') {
+                                        RED
+                                    } else if line.starts_with("@@") {
+                                        BLUE
+                                    } else {
+                                        TEXT
+                                    };
+                                    div()
+                                        .text_color(rgb(color))
+                                        .child(if line.is_empty() { " " } else { line }.to_string())
+                                }),
+                        )
+                        .when(diff.is_empty(), |el| {
+                            el.text_color(rgb(TEXT_MUTED)).child("No differences")
+                        }),
+                )
+        });
+
+        div()
+            .id("sync-files-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("sync-files-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_sync_files_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("sync-files-dialog")
+                            .occlude()
+                            .w(px(560.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!("Sync Files from Main: {}", session_name)),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .justify_between()
+                                            .child(
+                                                div()
+                                                    .text_sm()
+                                                    .text_color(rgb(TEXT_SECONDARY))
+                                                    .child(format!(
+                                                        "Copy {} of {} files:",
+                                                        selected.len(),
+                                                        files.len()
+                                                    )),
+                                            )
+                                            .when(identical_count > 0, |el| {
+                                                el.child(
+                                                    div()
+                                                        .id("sync-show-identical")
+                                                        .cursor_pointer()
+                                                        .text_xs()
+                                                        .text_color(rgb(TEXT_MUTED))
+                                                        .hover(|el| el.text_color(rgb(TEXT)))
+                                                        .on_click(cx.listener(|this, _, _, cx| {
+                                                            this.toggle_sync_show_identical(cx);
+                                                        }))
+                                                        .child(format!(
+                                                            "{} {} unchanged",
+                                                            if show_identical {
+                                                                "Hide"
+                                                            } else {
+                                                                "Show"
+                                                            },
+                                                            identical_count
+                                                        )),
+                                                )
+                                            }),
+                                    )
+                                    .child(list)
+                                    .children(preview_view),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-sync-files")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_sync_files_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-sync-files")
+                                            .px_4()
+                                            .py_2()
+                                            .rounded_sm()
+                                            .text_xs()
+                                            .when(can_confirm, |el| {
+                                                el.cursor_pointer()
+                                                    .bg(rgb(GREEN))
+                                                    .hover(|el| el.bg(rgb(TEAL)))
+                                                    .text_color(rgb(BG_BASE))
+                                                    .on_click(cx.listener(|this, _, _, cx| {
+                                                        this.confirm_sync_files(cx);
+                                                    }))
+                                            })
+                                            .when(!can_confirm, |el| {
+                                                el.bg(rgb(BG_SURFACE0)).text_color(rgb(TEXT_MUTED))
+                                            })
+                                            .child("Copy"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -1,7 +1,8 @@
 //! File tree types and utilities for file list display
 
-use crate::git::ChangeType;
+use crate::git::{ChangeType, ChangedFile};
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 /// Compare two items with directory-first ordering, then by name
@@ -30,6 +31,78 @@ pub struct ChangeInfo {
     pub staged: bool,
 }
 
+/// Changes below a directory, shown as a badge on its collapsed row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirChangeSummary {
+    /// Changed files anywhere below the directory
+    pub count: usize,
+    /// The most severe change among them (see `change_severity`)
+    pub change_type: ChangeType,
+}
+
+/// Rank of a change for directory badges: conflicts stand out most, then
+/// deletions
+fn change_severity(change_type: ChangeType) -> u8 {
+    match change_type {
+        ChangeType::Conflicted => 5,
+        ChangeType::Deleted => 4,
+        ChangeType::Modified => 3,
+        ChangeType::Renamed => 2,
+        ChangeType::Added => 1,
+        ChangeType::Unknown => 0,
+    }
+}
+
+/// Directories with changes below them, keyed by relative path.
+///
+/// A renamed file counts toward the directories of both its old and new
+/// path. Files at the repository root have no directory to count toward.
+pub fn summarize_dir_changes(files: &[ChangedFile]) -> HashMap<PathBuf, DirChangeSummary> {
+    let mut summaries: HashMap<PathBuf, DirChangeSummary> = HashMap::new();
+    for file in files {
+        let dirs: HashSet<&Path> = std::iter::once(file.path.as_path())
+            .chain(file.old_path.as_deref())
+            .flat_map(|path| path.ancestors().skip(1))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect();
+        for dir in dirs {
+            summaries
+                .entry(dir.to_path_buf())
+                .and_modify(|summary| {
+                    summary.count += 1;
+                    if change_severity(file.change_type) > change_severity(summary.change_type) {
+                        summary.change_type = file.change_type;
+                    }
+                })
+                .or_insert(DirChangeSummary {
+                    count: 1,
+                    change_type: file.change_type,
+                });
+        }
+    }
+    summaries
+}
+
+/// One row of the flattened file list, in display order
+#[derive(Debug, Clone)]
+pub struct FileRow {
+    pub name: String,
+    /// Relative path in Changes mode, absolute in All mode
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// A submodule, listed as one entry and never expanded
+    pub is_submodule: bool,
+    /// The root of an independent repository nested in the worktree, listed
+    /// as one entry and never expanded
+    pub is_nested_repo: bool,
+    pub depth: usize,
+    pub change_info: Option<ChangeInfo>,
+}
+
+/// Path of the Changes list row that holds the files left out of review
+/// (see `review::ExcludePatterns`); no file can be named like it
+pub const EXCLUDED_GROUP: &str = "\0excluded";
+
 /// File tree node for tree view
 #[derive(Debug, Clone)]
 pub struct FileTreeNode {
@@ -62,6 +135,32 @@ impl FileTreeNode {
         root
     }
 
+    /// Directory-like node at `EXCLUDED_GROUP` named `label`, listing
+    /// `files` by their whole path
+    pub fn excluded_group(
+        label: String,
+        files: impl IntoIterator<Item = (PathBuf, Option<ChangeInfo>)>,
+    ) -> Self {
+        let mut children: Vec<FileTreeNode> = files
+            .into_iter()
+            .map(|(path, change_info)| FileTreeNode {
+                name: path.to_string_lossy().into_owned(),
+                path,
+                is_dir: false,
+                children: Vec::new(),
+                change_info,
+            })
+            .collect();
+        children.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            name: label,
+            path: PathBuf::from(EXCLUDED_GROUP),
+            is_dir: true,
+            children,
+            change_info: None,
+        }
+    }
+
     /// Insert a path into the tree
     pub fn insert(&mut self, path: &Path, change_info: Option<ChangeInfo>) {
         self.insert_with_full_path(path, path, change_info);
@@ -129,6 +228,142 @@ impl FileTreeNode {
             child.sort();
         }
     }
+
+    /// Move the files `sunk` holds for after the other files of their
+    /// directory (e.g. reviewed ones), keeping the order otherwise
+    pub fn sink_files(&mut self, sunk: &impl Fn(&Path) -> bool) {
+        self.children
+            .sort_by_key(|child| !child.is_dir && sunk(&child.path));
+        for child in &mut self.children {
+            if child.is_dir {
+                child.sink_files(sunk);
+            }
+        }
+    }
+
+    /// Paths of all files below this node in display order, collapsed
+    /// directories included
+    pub fn file_paths(&self) -> Vec<&Path> {
+        let mut paths = Vec::new();
+        self.push_file_paths(&mut paths);
+        paths
+    }
+
+    fn push_file_paths<'a>(&'a self, paths: &mut Vec<&'a Path>) {
+        for child in &self.children {
+            if child.is_dir {
+                child.push_file_paths(paths);
+            } else {
+                paths.push(&child.path);
+            }
+        }
+    }
+
+    /// Descendants in display order, skipping children of collapsed directories.
+    /// Paths in `submodules` are marked as submodules, those in `nested_repos`
+    /// as nested repositories.
+    pub fn visible_rows(
+        &self,
+        expanded_dirs: &HashSet<PathBuf>,
+        submodules: &HashSet<PathBuf>,
+        nested_repos: &HashSet<PathBuf>,
+    ) -> Vec<FileRow> {
+        let mut rows = Vec::new();
+        self.push_visible_rows(expanded_dirs, submodules, nested_repos, 0, &mut rows);
+        rows
+    }
+
+    fn push_visible_rows(
+        &self,
+        expanded_dirs: &HashSet<PathBuf>,
+        submodules: &HashSet<PathBuf>,
+        nested_repos: &HashSet<PathBuf>,
+        depth: usize,
+        rows: &mut Vec<FileRow>,
+    ) {
+        for child in &self.children {
+            rows.push(FileRow {
+                name: child.name.clone(),
+                path: child.path.clone(),
+                is_dir: child.is_dir,
+                is_submodule: submodules.contains(&child.path),
+                is_nested_repo: nested_repos.contains(&child.path),
+                depth,
+                change_info: child.change_info,
+            });
+            if child.is_dir && expanded_dirs.contains(&child.path) {
+                child.push_visible_rows(expanded_dirs, submodules, nested_repos, depth + 1, rows);
+            }
+        }
+    }
+}
+
+/// Index of the row for `path`, or of its nearest ancestor in `rows` when the
+/// path itself is not listed (e.g. an ignored file, or a collapsed directory)
+pub fn reveal_row(rows: &[FileRow], path: &Path) -> Option<usize> {
+    path.ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .find_map(|p| rows.iter().position(|row| row.path == p))
+}
+
+/// Rows of a directory listing where only expanded directories are read.
+///
+/// `submodules` and `nested_repos` are relative to `root`; their contents
+/// belong to another repository and are never read. `read_dir` lists one
+/// directory like `read_dir_shallow` (the app answers from its file cache).
+pub fn lazy_visible_rows(
+    root: &Path,
+    expanded_dirs: &HashSet<PathBuf>,
+    submodules: &HashSet<PathBuf>,
+    nested_repos: &HashSet<PathBuf>,
+    read_dir: &mut dyn FnMut(&Path) -> Vec<(PathBuf, bool)>,
+) -> Vec<FileRow> {
+    // Whether a directory is a submodule or a nested repository
+    let boundary = |path: &Path| match path.strip_prefix(root) {
+        Ok(relative) => (
+            submodules.contains(relative),
+            nested_repos.contains(relative),
+        ),
+        Err(_) => (false, false),
+    };
+    let mut rows = Vec::new();
+    push_lazy_rows(root, expanded_dirs, &boundary, read_dir, 0, &mut rows);
+    rows
+}
+
+fn push_lazy_rows(
+    dir: &Path,
+    expanded_dirs: &HashSet<PathBuf>,
+    boundary: &dyn Fn(&Path) -> (bool, bool),
+    read_dir: &mut dyn FnMut(&Path) -> Vec<(PathBuf, bool)>,
+    depth: usize,
+    rows: &mut Vec<FileRow>,
+) {
+    for (path, is_dir) in read_dir(dir) {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_string();
+        let (is_submodule, is_nested_repo) = if is_dir {
+            boundary(&path)
+        } else {
+            (false, false)
+        };
+        let expanded = is_dir && !is_submodule && !is_nested_repo && expanded_dirs.contains(&path);
+        rows.push(FileRow {
+            name,
+            path: path.clone(),
+            is_dir,
+            is_submodule,
+            is_nested_repo,
+            depth,
+            change_info: None,
+        });
+        if expanded {
+            push_lazy_rows(&path, expanded_dirs, boundary, read_dir, depth + 1, rows);
+        }
+    }
 }
 
 /// Read only immediate children of a directory (for lazy loading tree view)
@@ -231,6 +466,22 @@ mod tests {
         assert_eq!(tree.children[2].name, "z.txt"); // then z.txt
     }
 
+    #[test]
+    fn test_sink_files_keeps_dirs_first() {
+        let files = vec![
+            (PathBuf::from("a.txt"), None),
+            (PathBuf::from("b.txt"), None),
+            (PathBuf::from("dir/x.txt"), None),
+            (PathBuf::from("dir/y.txt"), None),
+        ];
+        let mut tree = FileTreeNode::from_files(files);
+        tree.sink_files(&|path: &Path| path.ends_with("a.txt") || path.ends_with("x.txt"));
+
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["dir", "b.txt", "a.txt"]);
+        assert_eq!(tree.children[0].children[0].name, "y.txt");
+    }
+
     #[test]
     fn test_file_tree_node_with_change_info() {
         let change_info = ChangeInfo {
@@ -274,4 +525,248 @@ mod tests {
         assert!(!file.is_dir);
         assert_eq!(file.path, PathBuf::from("a/b/c/d/file.txt"));
     }
+
+    #[test]
+    fn test_visible_rows_respects_expanded_dirs() {
+        let files = vec![
+            (PathBuf::from("src/ui/view.rs"), None),
+            (PathBuf::from("src/main.rs"), None),
+            (PathBuf::from("README.md"), None),
+        ];
+        let tree = FileTreeNode::from_files(files);
+
+        let rows = |expanded: &HashSet<PathBuf>| -> Vec<(String, usize)> {
+            tree.visible_rows(expanded, &HashSet::new(), &HashSet::new())
+                .into_iter()
+                .map(|row| (row.name, row.depth))
+                .collect()
+        };
+        let expect = |rows: &[(&str, usize)]| -> Vec<(String, usize)> {
+            rows.iter().map(|(n, d)| (n.to_string(), *d)).collect()
+        };
+
+        assert_eq!(
+            rows(&HashSet::new()),
+            expect(&[("src", 0), ("README.md", 0)])
+        );
+
+        let mut expanded = HashSet::from([PathBuf::from("src")]);
+        assert_eq!(
+            rows(&expanded),
+            expect(&[("src", 0), ("ui", 1), ("main.rs", 1), ("README.md", 0)])
+        );
+
+        expanded.insert(PathBuf::from("src/ui"));
+        assert_eq!(
+            rows(&expanded),
+            expect(&[
+                ("src", 0),
+                ("ui", 1),
+                ("view.rs", 2),
+                ("main.rs", 1),
+                ("README.md", 0)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_excluded_group_expands_to_its_files() {
+        let mut tree = FileTreeNode::from_files(vec![(PathBuf::from("src/main.rs"), None)]);
+        tree.children.push(FileTreeNode::excluded_group(
+            "2 excluded files (*.lock)".into(),
+            vec![
+                (PathBuf::from("web/yarn.lock"), None),
+                (PathBuf::from("Cargo.lock"), None),
+            ],
+        ));
+        let rows = |expanded: &HashSet<PathBuf>| -> Vec<(String, usize)> {
+            tree.visible_rows(expanded, &HashSet::new(), &HashSet::new())
+                .into_iter()
+                .map(|row| (row.name, row.depth))
+                .collect()
+        };
+
+        let collapsed = rows(&HashSet::new());
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[1], ("2 excluded files (*.lock)".to_string(), 0));
+
+        let expanded = rows(&HashSet::from([PathBuf::from(EXCLUDED_GROUP)]));
+        assert_eq!(
+            expanded[2..],
+            [
+                ("Cargo.lock".to_string(), 1),
+                ("web/yarn.lock".to_string(), 1)
+            ]
+        );
+        // The files keep their paths, so they open like any other
+        assert_eq!(
+            tree.file_paths(),
+            vec![
+                Path::new("src/main.rs"),
+                Path::new("Cargo.lock"),
+                Path::new("web/yarn.lock")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reveal_row_falls_back_to_nearest_ancestor() {
+        let tree = FileTreeNode::from_files(vec![
+            (PathBuf::from("src/ui/view.rs"), None),
+            (PathBuf::from("README.md"), None),
+        ]);
+        let expanded = HashSet::from([PathBuf::from("src")]);
+        let rows = tree.visible_rows(&expanded, &HashSet::new(), &HashSet::new());
+
+        assert_eq!(reveal_row(&rows, Path::new("README.md")), Some(2));
+        // src/ui is listed but collapsed, so its child highlights the directory
+        assert_eq!(reveal_row(&rows, Path::new("src/ui/view.rs")), Some(1));
+        assert_eq!(reveal_row(&rows, Path::new("src/ignored.log")), Some(0));
+        assert_eq!(reveal_row(&rows, Path::new("target/debug/app")), None);
+    }
+
+    fn changed(path: &str, change_type: ChangeType, old_path: Option<&str>) -> ChangedFile {
+        ChangedFile {
+            path: PathBuf::from(path),
+            change_type,
+            staged: false,
+            old_path: old_path.map(PathBuf::from),
+            similarity: None,
+            submodule: None,
+            outside_sparse: false,
+            nested_repository: false,
+        }
+    }
+
+    #[test]
+    fn test_summarize_dir_changes_nested_under_collapsed_dir() {
+        let summaries = summarize_dir_changes(&[
+            changed("src/ui/view.rs", ChangeType::Added, None),
+            changed("src/ui/list.rs", ChangeType::Modified, None),
+            changed("src/main.rs", ChangeType::Added, None),
+        ]);
+
+        // Nothing is expanded, yet `src` reports everything below it
+        let tree = FileTreeNode::from_files(vec![
+            (PathBuf::from("src/ui/view.rs"), None),
+            (PathBuf::from("src/ui/list.rs"), None),
+            (PathBuf::from("src/main.rs"), None),
+        ]);
+        let rows = tree.visible_rows(&HashSet::new(), &HashSet::new(), &HashSet::new());
+        assert_eq!(rows.len(), 1);
+        assert_eq!(
+            summaries.get(&rows[0].path),
+            Some(&DirChangeSummary {
+                count: 3,
+                change_type: ChangeType::Modified,
+            })
+        );
+        assert_eq!(summaries[Path::new("src/ui")].count, 2);
+        assert_eq!(summaries.len(), 2);
+    }
+
+    #[test]
+    fn test_summarize_dir_changes_root_renames_and_deletes() {
+        let summaries = summarize_dir_changes(&[
+            changed("README.md", ChangeType::Modified, None),
+            changed("docs/old/guide.md", ChangeType::Deleted, None),
+            changed("src/new.rs", ChangeType::Renamed, Some("lib/old.rs")),
+            changed("src/a/b.rs", ChangeType::Renamed, Some("src/b.rs")),
+        ]);
+
+        // A change at the root belongs to no directory
+        assert!(!summaries.contains_key(Path::new("")));
+        assert_eq!(
+            summaries[Path::new("docs")].change_type,
+            ChangeType::Deleted
+        );
+        assert_eq!(summaries[Path::new("docs/old")].count, 1);
+        // Both sides of a rename count, a shared parent only once per file
+        assert_eq!(summaries[Path::new("lib")].count, 1);
+        assert_eq!(summaries[Path::new("src")].count, 2);
+        assert_eq!(summaries[Path::new("src/a")].count, 1);
+        assert_eq!(summaries.len(), 5);
+    }
+
+    #[test]
+    fn test_lazy_rows_do_not_descend_into_submodules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::create_dir_all(root.join("vendor/lib")).unwrap();
+        std::fs::write(root.join("vendor/lib/foreign.rs"), "").unwrap();
+
+        let expanded = HashSet::from([root.join("src"), root.join("vendor/lib")]);
+        let submodules = HashSet::from([PathBuf::from("vendor/lib")]);
+        let mut read = |dir: &Path| read_dir_shallow(dir).unwrap_or_default();
+        let rows: Vec<_> =
+            lazy_visible_rows(root, &expanded, &submodules, &HashSet::new(), &mut read)
+                .into_iter()
+                .map(|row| (row.name, row.is_submodule))
+                .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("src".to_string(), false),
+                ("main.rs".to_string(), false),
+                ("vendor".to_string(), false),
+            ]
+        );
+
+        let expanded = HashSet::from([root.join("vendor"), root.join("vendor/lib")]);
+        let rows: Vec<_> =
+            lazy_visible_rows(root, &expanded, &submodules, &HashSet::new(), &mut read)
+                .into_iter()
+                .map(|row| (row.name, row.is_submodule))
+                .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("src".to_string(), false),
+                ("vendor".to_string(), false),
+                ("lib".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_repositories_are_listed_but_not_walked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("vendor/inner/src")).unwrap();
+        std::fs::write(root.join("vendor/inner/src/lib.rs"), "").unwrap();
+
+        let expanded = HashSet::from([root.join("vendor"), root.join("vendor/inner")]);
+        let nested = HashSet::from([PathBuf::from("vendor/inner")]);
+        let mut read = |dir: &Path| read_dir_shallow(dir).unwrap_or_default();
+        let rows: Vec<_> = lazy_visible_rows(root, &expanded, &HashSet::new(), &nested, &mut read)
+            .into_iter()
+            .map(|row| (row.name, row.is_nested_repo))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("vendor".to_string(), false), ("inner".to_string(), true)]
+        );
+
+        // In the Changes list the root is a leaf next to the changed files
+        let tree = FileTreeNode::from_files([
+            (PathBuf::from("vendor/inner"), None),
+            (PathBuf::from("vendor/notes.txt"), None),
+        ]);
+        let expanded = HashSet::from([PathBuf::from("vendor")]);
+        let rows: Vec<_> = tree
+            .visible_rows(&expanded, &HashSet::new(), &nested)
+            .into_iter()
+            .map(|row| (row.name, row.is_nested_repo))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("vendor".to_string(), false),
+                ("inner".to_string(), true),
+                ("notes.txt".to_string(), false),
+            ]
+        );
+    }
 }
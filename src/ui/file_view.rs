@@ -1,17 +1,179 @@
 //! File view component for viewing files and diffs
 
+use super::diff_whitespace::{self, DiffStats, NormalizedDiff, raw_lines, show_whitespace};
+use super::file_follow::{FOLLOW_POLL_INTERVAL, FileFollower, FollowUpdate};
+use super::file_nav::FilePosition;
+use super::file_probe::{self, FileKind, FileProbe, LineIndex, TextEncoding};
+use super::image_preview::{self, ImagePreview, LoadedImage, SliderDrag, Zoom};
+use super::soft_wrap::{self, WrapLayout};
+use super::tab_stops::expand_tabs;
+use super::virtual_rows::VisibleWindow;
+use crate::compare::Comparison;
+use crate::git::{self, ChangeType, SubmoduleStatus};
+use crate::lfs::{LfsInfo, LfsSide};
+use crate::maintenance::format_size;
+use crate::merge_markers::{ConflictFile, ConflictHunk, Resolution, Segment};
+use crate::review_comments::ReviewComment;
 use crate::theme::*;
 use gpui::{
-    App, Context, DefiniteLength, EventEmitter, FocusHandle, Focusable, IntoElement, MouseButton,
-    ParentElement, Render, ScrollHandle, Styled, Window, div, prelude::*, px, rgb,
+    AnyElement, App, ClickEvent, Context, DefiniteLength, EventEmitter, FocusHandle, Focusable,
+    IntoElement, MouseButton, ObjectFit, ParentElement, Pixels, Render, ScrollHandle,
+    ScrollWheelEvent, Styled, StyledImage, Task, Window, div, img, point, prelude::*, px, rgb,
 };
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 /// Event to send text to terminal
 #[derive(Debug, Clone)]
 pub struct SendToTerminalEvent(pub String);
 
+/// Event to open a file (optionally at a line) in the external editor
+#[derive(Debug, Clone)]
+pub struct OpenExternalEvent(pub PathBuf, pub Option<usize>);
+
+/// Event to discard one hunk: the file, a patch holding only that hunk and
+/// the encoding the patch is written back in
+#[derive(Debug, Clone)]
+pub struct DiscardHunkEvent(pub PathBuf, pub String, pub TextEncoding);
+
+/// Event to rewrite a file from its legacy encoding as UTF-8
+#[derive(Debug, Clone)]
+pub struct ConvertToUtf8Event(pub PathBuf, pub TextEncoding);
+
+/// Event to open a submodule as the project
+#[derive(Debug, Clone)]
+pub struct OpenRepositoryEvent(pub PathBuf);
+
+/// Event to show the open file in the file list
+#[derive(Debug, Clone)]
+pub struct RevealInSidebarEvent(pub PathBuf);
+
+/// Event to open the next (true) or previous changed file
+#[derive(Debug, Clone)]
+pub struct NavigateFileEvent(pub bool);
+
+/// Event to insert a reference to lines of a file into the terminal
+#[derive(Debug, Clone)]
+pub struct InsertReferenceEvent(pub PathBuf, pub RangeInclusive<usize>);
+
+/// Event to paste lines of a file into the terminal as a code block: the file
+/// and the lines themselves
+#[derive(Debug, Clone)]
+pub struct InsertSnippetEvent(pub PathBuf, pub String);
+
+/// Event to compute the diff of a file that is over the diff size guard
+#[derive(Debug, Clone)]
+pub struct ForceFullDiffEvent(pub PathBuf);
+
+/// Event to write a conflicted file after one of its conflicts was
+/// resolved: the file, its new text and the encoding to write it in
+#[derive(Debug, Clone)]
+pub struct ResolveConflictEvent(pub PathBuf, pub String, pub TextEncoding);
+
+/// Event to stage a file with no conflict markers left, marking it resolved
+#[derive(Debug, Clone)]
+pub struct MarkResolvedEvent(pub PathBuf);
+
+/// Event to write a review comment on a line: the file, the line and its text
+#[derive(Debug, Clone)]
+pub struct CommentLineEvent(pub PathBuf, pub usize, pub String);
+
+/// Event to edit the review comment at an index of the session's comments,
+/// with the text its line has now (None = the line is gone)
+#[derive(Debug, Clone)]
+pub struct EditCommentEvent(pub usize, pub Option<String>);
+
+/// Event to delete the review comment at an index of the session's comments
+#[derive(Debug, Clone)]
+pub struct DeleteCommentEvent(pub usize);
+
+/// Event to compare a file with its copy in another session
+#[derive(Debug, Clone)]
+pub struct CompareWithSessionEvent(pub PathBuf);
+
+/// Event to show a comparison between sessions again with its sides swapped
+#[derive(Debug, Clone)]
+pub struct SwapComparisonEvent(pub Comparison);
+
+/// Diff of the open file that is not shown yet (see `diff_job`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingDiff {
+    /// Being computed in the background
+    Loading,
+    /// Over the size guard (`max_size` bytes): lines added and removed by
+    /// quick count
+    TooLarge {
+        added: usize,
+        removed: usize,
+        max_size: u64,
+    },
+}
+
+/// Selected line numbers (of the working tree file) in the file view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LineSelection {
+    /// Line clicked first
+    anchor: usize,
+    /// Line Shift+clicked last (the anchor when only one line is selected)
+    head: usize,
+}
+
+impl LineSelection {
+    /// Selection after a click on `line`: Shift extends the current selection,
+    /// a plain click selects the line or clears it when it is all that is selected
+    fn click(current: Option<Self>, line: usize, extend: bool) -> Option<Self> {
+        match current {
+            Some(selection) if extend => Some(Self {
+                anchor: selection.anchor,
+                head: line,
+            }),
+            Some(selection) if selection.anchor == line && selection.head == line => None,
+            _ => Some(Self {
+                anchor: line,
+                head: line,
+            }),
+        }
+    }
+
+    fn range(&self) -> RangeInclusive<usize> {
+        self.anchor.min(self.head)..=self.anchor.max(self.head)
+    }
+}
+
+/// `path:line` or `path:start-end` for a line range
+pub fn format_line_reference(path: &str, lines: &RangeInclusive<usize>) -> String {
+    if lines.start() == lines.end() {
+        format!("{}:{}", path, lines.start())
+    } else {
+        format!("{}:{}-{}", path, lines.start(), lines.end())
+    }
+}
+
+/// `text` as a fenced code block with `path` as the info string. The fence is
+/// longer than any backtick run in `text` so the block cannot end early.
+pub fn format_snippet(path: &str, text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    let body = text.strip_suffix('\n').unwrap_or(text);
+    format!("{fence}{path}\n{body}\n{fence}\n")
+}
+
+/// "sha256:0123abcd… · 12.0 MB · not fetched" for a side of an LFS file
+fn lfs_side(side: &LfsSide) -> String {
+    format!(
+        "{} · {} · {}",
+        side.pointer.short_oid(),
+        format_size(side.pointer.size),
+        if side.local {
+            "object stored locally"
+        } else {
+            "object not fetched"
+        }
+    )
+}
+
 /// View mode for the file view
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum FileViewMode {
@@ -22,6 +184,8 @@ pub enum FileViewMode {
     DiffSplit,
     /// Display diff inline (additions/deletions marked in content)
     DiffInline,
+    /// Display the conflict hunks of a conflicted file with their actions
+    Conflicts,
 }
 
 /// Diff line for split view (side-by-side display)
@@ -31,6 +195,10 @@ struct SplitDiffLine {
     new_line_num: Option<usize>,
     content: String,
     line_type: DiffLineType,
+    /// Hunk index (hunk header rows only)
+    hunk: Option<usize>,
+    /// Context line that changed only in trailing whitespace or line ending
+    whitespace: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,6 +206,8 @@ enum DiffLineType {
     Context,
     Added,
     Removed,
+    /// `@@` line, shown when hunks can be discarded
+    HunkHeader,
 }
 
 /// Line info for inline diff view
@@ -46,6 +216,10 @@ struct InlineDiffLine {
     line_num: Option<usize>,
     content: String,
     change_type: InlineChangeType,
+    /// Hunk index (hunk header rows only)
+    hunk: Option<usize>,
+    /// Unchanged line that changed only in trailing whitespace or line ending
+    whitespace: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -53,6 +227,7 @@ enum InlineChangeType {
     Unchanged,
     Added,
     Deleted,
+    HunkHeader,
 }
 
 struct DiffResizeDrag {
@@ -60,11 +235,133 @@ struct DiffResizeDrag {
     initial_ratio: f32,
 }
 
+/// Row height of paged large files (rows are virtualized)
+const LARGE_ROW_HEIGHT: f32 = 20.0;
+
+/// Lines kept above a line scrolled to in a large file
+const REVEAL_CONTEXT_LINES: usize = 3;
+
+/// Row height of wrapped text (rows are virtualized)
+const WRAPPED_ROW_HEIGHT: f32 = 20.0;
+
+/// Width of the line numbers beside wrapped text (`w_12`)
+const WRAPPED_GUTTER_WIDTH: f32 = 48.0;
+
+/// Lines read ahead of and behind the viewport of a large file
+const LARGE_PAGE_LINES: usize = 200;
+
+/// Space around a previewed image
+const IMAGE_PADDING: f32 = 16.0;
+
+/// A file shown as an info panel instead of its text
+#[derive(Debug, Clone)]
+struct BinaryInfo {
+    size: u64,
+    change_type: Option<ChangeType>,
+    /// Why a text file is not shown (None = binary)
+    reason: Option<String>,
+    image: bool,
+    show_preview: bool,
+    /// Pointers of a Git LFS file, shown instead of the size
+    lfs: Option<LfsInfo>,
+}
+
+/// Row of each conflict's header among the rows of the conflicts view (text
+/// lines and marker lines are a row each, plus a header above each conflict)
+fn conflict_rows(conflicts: &ConflictFile) -> Vec<usize> {
+    let mut rows = Vec::new();
+    let mut row = 0;
+    for segment in conflicts.segments() {
+        match segment {
+            Segment::Text(lines) => row += lines.len(),
+            Segment::Conflict(hunk) => {
+                rows.push(row);
+                row += 1 + hunk.line_count();
+            }
+        }
+    }
+    rows
+}
+
+/// The open text wrapped to the view's width
+struct WrappedText {
+    /// Width in columns, tab width, and the address and length of the text
+    /// it was made for: the text is only ever replaced or appended to, so a
+    /// new text never has both the same address and length
+    key: (usize, usize, usize, usize),
+    layout: Rc<WrapLayout>,
+    /// Byte range of each line in the text
+    lines: Vec<Range<usize>>,
+}
+
+/// Text over the size limit, read from disk a page of lines at a time
+struct LargeText {
+    size: u64,
+    max_size: u64,
+    encoding: TextEncoding,
+    /// None while the line index is built in the background
+    index: Option<Result<Rc<LineIndex>, String>>,
+    /// First line of `lines`
+    page_start: usize,
+    lines: Vec<String>,
+    _index_task: Task<()>,
+}
+
+impl LargeText {
+    /// Read the page around `range` unless it is already loaded
+    fn load(&mut self, path: &Path, index: &LineIndex, range: &Range<usize>) {
+        let loaded = self.page_start..self.page_start + self.lines.len();
+        if range.is_empty() || (loaded.start <= range.start && range.end <= loaded.end) {
+            return;
+        }
+        let start = range.start.saturating_sub(LARGE_PAGE_LINES);
+        let end = range.end + LARGE_PAGE_LINES;
+        // A file shrunk or removed since indexing shows empty rows
+        self.lines = index
+            .read_lines(path, start..end, self.encoding)
+            .unwrap_or_default();
+        self.page_start = start;
+    }
+
+    fn line(&self, line: usize) -> &str {
+        line.checked_sub(self.page_start)
+            .and_then(|i| self.lines.get(i))
+            .map_or("", String::as_str)
+    }
+}
+
 /// File view component - read-only viewer
 pub struct FileView {
     file_path: Option<PathBuf>,
+    /// Original path (relative to the worktree) when showing a renamed file
+    renamed_from: Option<PathBuf>,
+    /// Set when showing the diff between the file's copies in two sessions
+    comparison: Option<Comparison>,
+    /// Set when showing a submodule, which has an info panel instead of text
+    submodule: Option<SubmoduleStatus>,
+    /// Set for a binary file, which has an info panel instead of text
+    binary: Option<BinaryInfo>,
+    /// Set for text over the size limit, which is paged and not diffed
+    large: Option<LargeText>,
+    /// Set for an image file, which is shown as the image
+    image: Option<ImagePreview>,
+    image_scroll_handle: ScrollHandle,
+    _image_task: Task<()>,
     content: String,
+    /// Encoding `content` was decoded from (tagged in the toolbar unless UTF-8)
+    encoding: TextEncoding,
+    /// The encoding chip's menu is open
+    encoding_menu: bool,
     diff_content: Option<String>,
+    /// Set while the diff is computed or was skipped as too large
+    pending_diff: Option<PendingDiff>,
+    /// Counts and line-ending check of `diff_content`
+    diff_stats: DiffStats,
+    /// Show lines that changed only in trailing whitespace as context
+    ignore_whitespace: bool,
+    /// `diff_content` with whitespace-only changes as context, while those
+    /// are ignored (display only; hunks are discarded from `diff_content`)
+    normalized_diff: Option<NormalizedDiff>,
     mode: FileViewMode,
     focus_handle: FocusHandle,
     /// Rc-wrapped for cheap clones during render
@@ -77,14 +374,66 @@ pub struct FileView {
     diff_scroll_handle: ScrollHandle,
     diff_split_ratio: f32,
     diff_resize_drag: Option<DiffResizeDrag>,
+    content_scroll_handle: ScrollHandle,
+    /// Read position in the open file (content mode only)
+    follower: Option<FileFollower>,
+    /// Re-read the file as it changes and stick to the bottom
+    follow: bool,
+    /// Turn `follow` on when the file grows (until the user turns it off)
+    auto_follow: bool,
+    /// Bumped whenever the open file changes, so stale polls are dropped
+    follow_generation: u64,
+    _follow_task: Task<()>,
+    /// Lines picked from the line numbers for the quick actions
+    line_selection: Option<LineSelection>,
+    /// File the review comments are on, and the comments with their index
+    /// among the session's (see `set_comments`)
+    comments: Option<(PathBuf, Rc<Vec<(usize, ReviewComment)>>)>,
+    /// Line of this file to scroll to once its text is shown (see `reveal_line`)
+    pending_reveal: Option<(PathBuf, usize)>,
+    /// The file belongs to a read-only session: no discarding or opening
+    /// externally
+    read_only: bool,
+    /// Place of the file among the changed files (None if it is not one)
+    position: Option<FilePosition>,
+    /// Columns between tab stops when showing text (`sashiki.viewer.tabWidth`)
+    tab_width: usize,
+    /// Names of files whose long lines wrap unless toggled
+    /// (`sashiki.viewer.wrap`)
+    wrap_patterns: Vec<glob::Pattern>,
+    /// Wrapping toggled for a file, kept while the app runs
+    wrap_by_file: HashMap<PathBuf, bool>,
+    wrapped: Option<WrappedText>,
+    /// Line of wrapped text to scroll to once its rows are known
+    wrapped_reveal: Option<usize>,
+    /// Conflict hunks of a conflicted file (conflicts mode)
+    conflicts: Option<ConflictFile>,
+    /// Conflict last moved to with the previous/next arrows
+    current_conflict: usize,
+    /// Why the markers of a conflicted file are shown as plain text
+    conflict_warning: Option<String>,
 }
 
 impl FileView {
     pub fn new(cx: &mut Context<Self>) -> Self {
         Self {
             file_path: None,
+            renamed_from: None,
+            comparison: None,
+            submodule: None,
+            binary: None,
+            large: None,
+            image: None,
+            image_scroll_handle: ScrollHandle::new(),
+            _image_task: Task::ready(()),
             content: String::new(),
+            encoding: TextEncoding::Utf8,
+            encoding_menu: false,
             diff_content: None,
+            pending_diff: None,
+            diff_stats: DiffStats::default(),
+            ignore_whitespace: false,
+            normalized_diff: None,
             mode: FileViewMode::Content,
             focus_handle: cx.focus_handle(),
             cached_added_lines: Rc::new(std::collections::HashSet::new()),
@@ -93,46 +442,414 @@ impl FileView {
             diff_scroll_handle: ScrollHandle::new(),
             diff_split_ratio: 0.5,
             diff_resize_drag: None,
+            content_scroll_handle: ScrollHandle::new(),
+            follower: None,
+            follow: false,
+            auto_follow: false,
+            follow_generation: 0,
+            _follow_task: Self::spawn_follow_task(cx),
+            line_selection: None,
+            comments: None,
+            pending_reveal: None,
+            read_only: false,
+            position: None,
+            tab_width: git::DEFAULT_VIEWER_TAB_WIDTH,
+            wrap_patterns: Vec::new(),
+            wrap_by_file: HashMap::new(),
+            wrapped: None,
+            wrapped_reveal: None,
+            conflicts: None,
+            current_conflict: 0,
+            conflict_warning: None,
         }
     }
 
     pub fn open_file(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
-        self.content = std::fs::read_to_string(&path)?;
+        (self.content, self.encoding) = file_probe::read_text(&path)?;
+        self.encoding_menu = false;
+        self.stop_following();
+        self.follower = Some(FileFollower::new(&path, &self.content));
+        self.auto_follow = true;
+        self.line_selection = None;
+        self.content_scroll_handle.set_offset(point(px(0.), px(0.)));
         self.file_path = Some(path);
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = None;
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
         self.diff_content = None;
+        self.pending_diff = None;
         self.mode = FileViewMode::Content;
         self.clear_diff_cache();
         Ok(())
     }
 
+    /// Show a conflicted file as its conflict hunks, each with the actions
+    /// that resolve it. Markers that do not pair up leave the text as it is,
+    /// with a warning, so nothing is rewritten from a guess.
+    pub fn open_conflicted(&mut self, path: PathBuf) -> Result<(), std::io::Error> {
+        self.open_file(path)?;
+        // Resolving rewrites the file; following it would only reload it
+        self.stop_following();
+        match ConflictFile::parse(&self.content) {
+            Ok(conflicts) => {
+                self.conflicts = Some(conflicts);
+                self.mode = FileViewMode::Conflicts;
+            }
+            Err(e) => {
+                self.conflict_warning = Some(format!(
+                    "Conflict markers are shown as plain text because they could not be \
+                     read: {}",
+                    e
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_conflicts(&mut self) {
+        self.conflicts = None;
+        self.current_conflict = 0;
+        self.conflict_warning = None;
+    }
+
+    /// Resolve conflict `index` (among those left) and have the file written
+    fn resolve_conflict(&mut self, index: usize, resolution: Resolution, cx: &mut Context<Self>) {
+        if self.read_only {
+            return;
+        }
+        let (Some(path), Some(conflicts)) = (self.file_path.clone(), self.conflicts.as_mut())
+        else {
+            return;
+        };
+        if !conflicts.resolve(index, resolution) {
+            return;
+        }
+        self.content = conflicts.text();
+        self.current_conflict = index.min(conflicts.conflict_count().saturating_sub(1));
+        cx.emit(ResolveConflictEvent(
+            path,
+            self.content.clone(),
+            self.encoding,
+        ));
+        cx.notify();
+    }
+
+    /// Scroll to the next (or previous) conflict, wrapping around
+    fn step_conflict(&mut self, forward: bool, cx: &mut Context<Self>) {
+        let Some(conflicts) = &self.conflicts else {
+            return;
+        };
+        let rows = conflict_rows(conflicts);
+        if rows.is_empty() {
+            return;
+        }
+        self.current_conflict = if forward {
+            (self.current_conflict + 1) % rows.len()
+        } else {
+            (self.current_conflict + rows.len() - 1) % rows.len()
+        };
+        self.content_scroll_handle
+            .scroll_to_item(rows[self.current_conflict]);
+        cx.notify();
+    }
+
     pub fn open_file_with_diff(
         &mut self,
         path: PathBuf,
         diff: String,
     ) -> Result<(), std::io::Error> {
-        self.content = std::fs::read_to_string(&path)?;
+        (self.content, self.encoding) = file_probe::read_text(&path)?;
+        self.encoding_menu = false;
+        self.stop_following();
+        self.line_selection = None;
         self.file_path = Some(path);
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = None;
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
         self.diff_content = Some(diff);
+        self.pending_diff = None;
         self.mode = FileViewMode::DiffSplit;
         self.update_diff_cache();
         Ok(())
     }
 
     pub fn open_deleted_file_with_diff(&mut self, path: PathBuf, diff: String) {
+        self.stop_following();
+        self.line_selection = None;
         self.file_path = Some(path);
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = None;
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
         self.content = String::new();
+        self.encoding = TextEncoding::Utf8;
         self.diff_content = Some(diff);
+        self.pending_diff = None;
         self.mode = FileViewMode::DiffSplit;
         self.update_diff_cache();
     }
 
+    /// Show `path` in diff mode while its diff is computed in the background
+    /// (`show_computed_diff` or `show_diff_summary` follow)
+    pub fn open_diff_pending(&mut self, path: PathBuf) {
+        self.stop_following();
+        self.line_selection = None;
+        self.file_path = Some(path);
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = None;
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
+        self.content = String::new();
+        self.encoding = TextEncoding::Utf8;
+        self.encoding_menu = false;
+        self.diff_content = None;
+        self.pending_diff = Some(PendingDiff::Loading);
+        self.mode = FileViewMode::DiffSplit;
+        self.clear_diff_cache();
+    }
+
+    /// Show the diff computed for the open file, keeping a split or inline
+    /// mode chosen meanwhile. Without a diff (git failed) the text is shown.
+    pub fn show_computed_diff(
+        &mut self,
+        text: Option<(String, TextEncoding)>,
+        diff: Option<String>,
+    ) {
+        (self.content, self.encoding) = text.unwrap_or_else(|| (String::new(), TextEncoding::Utf8));
+        self.pending_diff = None;
+        self.content_scroll_handle.set_offset(point(px(0.), px(0.)));
+        match diff {
+            Some(diff) => {
+                self.diff_content = Some(diff);
+                if !self.is_diff_mode() {
+                    self.mode = FileViewMode::DiffSplit;
+                }
+                self.update_diff_cache();
+            }
+            None => {
+                self.diff_content = None;
+                self.mode = FileViewMode::Content;
+                self.clear_diff_cache();
+            }
+        }
+    }
+
+    /// Whether the open file's diff is still being computed
+    pub fn is_diff_loading(&self) -> bool {
+        self.pending_diff == Some(PendingDiff::Loading)
+    }
+
+    /// Show a quick line count instead of the diff of a file over the size guard
+    pub fn show_diff_summary(&mut self, added: usize, removed: usize, max_size: u64) {
+        self.pending_diff = Some(PendingDiff::TooLarge {
+            added,
+            removed,
+            max_size,
+        });
+    }
+
+    /// Show the info panel of the submodule at `path`
+    pub fn open_submodule(&mut self, path: PathBuf, status: SubmoduleStatus) {
+        self.stop_following();
+        self.line_selection = None;
+        self.file_path = Some(path);
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = Some(status);
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
+        self.content = String::new();
+        self.encoding = TextEncoding::Utf8;
+        self.diff_content = None;
+        self.pending_diff = None;
+        self.mode = FileViewMode::Content;
+        self.clear_diff_cache();
+    }
+
+    /// Open a file the probe did not find to be regular text: binary files
+    /// get an info panel, large text is paged from disk without a diff
+    pub fn open_probed(
+        &mut self,
+        path: PathBuf,
+        probe: FileProbe,
+        max_size: u64,
+        change_type: Option<ChangeType>,
+        cx: &mut Context<Self>,
+    ) {
+        self.close();
+        let binary = |reason| BinaryInfo {
+            size: probe.size,
+            change_type,
+            reason,
+            image: file_probe::is_image(&path),
+            show_preview: false,
+            lfs: None,
+        };
+        match probe.kind {
+            FileKind::Text(_) => {
+                let _ = self.open_file(path);
+                return;
+            }
+            FileKind::Binary => self.binary = Some(binary(None)),
+            // Pages are found by scanning for newlines, which UTF-16 does not have
+            FileKind::Large(TextEncoding::Utf16Le | TextEncoding::Utf16Be) => {
+                self.binary = Some(binary(Some(format!(
+                    "UTF-16 text larger than {} is not shown.",
+                    format_size(max_size)
+                ))));
+            }
+            FileKind::Large(encoding @ (TextEncoding::Utf8 | TextEncoding::Legacy(_))) => {
+                let index_path = path.clone();
+                let task = cx.spawn(async move |view, cx| {
+                    let index = smol::unblock(move || LineIndex::build(&index_path)).await;
+                    // Ignore error: only fails if the view was dropped
+                    let _ = view.update(cx, |view, cx| {
+                        if let Some(large) = &mut view.large {
+                            large.index = Some(index.map(Rc::new).map_err(|e| e.to_string()));
+                            cx.notify();
+                        }
+                    });
+                });
+                self.encoding = encoding;
+                self.large = Some(LargeText {
+                    size: probe.size,
+                    max_size,
+                    encoding,
+                    index: None,
+                    page_start: 0,
+                    lines: Vec::new(),
+                    _index_task: task,
+                });
+            }
+        }
+        self.content_scroll_handle.set_offset(point(px(0.), px(0.)));
+        self.file_path = Some(path);
+    }
+
+    /// Open a Git LFS file: an info panel from its pointers, no text or diff
+    pub fn open_lfs(&mut self, path: PathBuf, info: LfsInfo, change_type: Option<ChangeType>) {
+        self.close();
+        self.binary = Some(BinaryInfo {
+            size: info.worktree_size.unwrap_or(0),
+            change_type,
+            reason: None,
+            image: false,
+            show_preview: false,
+            lfs: Some(info),
+        });
+        self.content_scroll_handle.set_offset(point(px(0.), px(0.)));
+        self.file_path = Some(path);
+    }
+
+    /// Show the open file as an image once it is decoded; a file that cannot
+    /// be keeps its binary info panel (or its text, for SVG)
+    pub fn show_image(&mut self, max_side: u32, cx: &mut Context<Self>) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        self.image = Some(ImagePreview::new(path.clone()));
+        self.image_scroll_handle.set_offset(point(px(0.), px(0.)));
+        let load_path = path.clone();
+        self._image_task = cx.spawn(async move |view, cx| {
+            let loaded = smol::unblock(move || image_preview::load(&load_path, max_side)).await;
+            // Ignore error: only fails if the view was dropped
+            let _ = view.update(cx, |view, cx| {
+                let Some(image) = view.image.as_mut().filter(|image| image.path == path) else {
+                    return;
+                };
+                match loaded {
+                    Ok(loaded) => image.current = Some(loaded),
+                    Err(e) => {
+                        view.image = None;
+                        if let Some(binary) = &mut view.binary {
+                            binary.reason = Some(format!("The image could not be decoded: {}", e));
+                        }
+                    }
+                }
+                cx.notify();
+            });
+        });
+    }
+
+    /// Compare the image at `path`, if still open, with `before` (its HEAD version)
+    pub fn set_image_before(&mut self, path: &Path, before: LoadedImage) {
+        if let Some(image) = self.image.as_mut().filter(|image| image.path == path) {
+            image.before = Some(before);
+        }
+    }
+
+    /// Scale the open image is shown at (None while it is loading)
+    fn image_scale(&self) -> Option<f32> {
+        let image = self.image.as_ref()?;
+        let size = image.size()?;
+        let bounds = self.image_scroll_handle.bounds().size;
+        let viewport = (
+            f32::from(bounds.width) - 2.0 * IMAGE_PADDING,
+            f32::from(bounds.height) - 2.0 * IMAGE_PADDING,
+        );
+        Some(image.zoom.scale(size, viewport))
+    }
+
+    fn set_image_zoom(&mut self, zoom: Zoom) {
+        if let Some(image) = &mut self.image {
+            image.zoom = zoom;
+        }
+    }
+
+    fn toggle_image_source(&mut self) {
+        if let Some(image) = &mut self.image {
+            image.show_source = !image.show_source;
+        }
+    }
+
+    fn toggle_image_preview(&mut self) {
+        if let Some(binary) = &mut self.binary {
+            binary.show_preview = !binary.show_preview;
+        }
+    }
+
+    /// Swap in a newer diff of the open file, keeping the view mode and scroll position
+    pub fn replace_diff(&mut self, diff: String) {
+        if let Some(path) = &self.file_path
+            && let Ok((content, encoding)) = file_probe::read_text(path)
+        {
+            self.content = content;
+            self.encoding = encoding;
+        }
+        self.diff_content = Some(diff);
+        self.update_diff_cache();
+    }
+
     fn clear_diff_cache(&mut self) {
+        self.diff_stats = DiffStats::default();
+        self.normalized_diff = None;
         self.cached_added_lines = Rc::new(std::collections::HashSet::new());
         self.cached_left_lines = Rc::new(Vec::new());
         self.cached_right_lines = Rc::new(Vec::new());
     }
 
     fn update_diff_cache(&mut self) {
+        let diff = self.diff_content.as_deref().unwrap_or("");
+        self.diff_stats = diff_whitespace::diff_stats(diff);
+        self.normalized_diff = self
+            .whitespace_ignored()
+            .then(|| diff_whitespace::ignore_whitespace(diff));
         self.cached_added_lines = Rc::new(self.compute_added_line_numbers());
         let (left, right) = self.compute_split_diff();
         self.cached_left_lines = Rc::new(left);
@@ -144,10 +861,36 @@ impl FileView {
         self.mode = match self.mode {
             FileViewMode::DiffSplit => FileViewMode::DiffInline,
             FileViewMode::DiffInline => FileViewMode::DiffSplit,
-            FileViewMode::Content => FileViewMode::Content,
+            mode @ (FileViewMode::Content | FileViewMode::Conflicts) => mode,
         };
     }
 
+    /// Whether whitespace-only changes are shown as context: when asked
+    /// for, and always for a diff of nothing but line endings
+    fn whitespace_ignored(&self) -> bool {
+        self.ignore_whitespace || self.diff_stats.line_ending_only
+    }
+
+    pub fn toggle_ignore_whitespace(&mut self) {
+        self.ignore_whitespace = !self.ignore_whitespace;
+        self.update_diff_cache();
+    }
+
+    /// The diff as displayed
+    fn shown_diff(&self) -> &str {
+        match &self.normalized_diff {
+            Some(normalized) => &normalized.diff,
+            None => self.diff_content.as_deref().unwrap_or(""),
+        }
+    }
+
+    /// Whether new-file line `line` changed only in trailing whitespace
+    fn is_whitespace_line(&self, line: usize) -> bool {
+        self.normalized_diff
+            .as_ref()
+            .is_some_and(|n| n.whitespace_lines.contains(&line))
+    }
+
     /// Check if currently in a diff mode
     pub fn is_diff_mode(&self) -> bool {
         matches!(
@@ -156,107 +899,411 @@ impl FileView {
         )
     }
 
+    /// Show the file as renamed from `old_path` in the toolbar
+    pub fn set_renamed_from(&mut self, old_path: Option<PathBuf>) {
+        self.renamed_from = old_path;
+    }
+
+    /// Show the open file as the comparison of its copies in two sessions
+    pub fn set_comparison(&mut self, comparison: Option<Comparison>) {
+        self.comparison = comparison;
+    }
+
+    pub fn comparison(&self) -> Option<&Comparison> {
+        self.comparison.as_ref()
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    pub fn mode(&self) -> FileViewMode {
+        self.mode
+    }
+
+    /// Show a diff split or inline as `mode` does (for a file that has one)
+    pub fn restore_diff_mode(&mut self, mode: FileViewMode) {
+        if self.is_diff_mode() && mode != FileViewMode::Content {
+            self.mode = mode;
+        }
+    }
+
+    /// Show the file's place among the changed files in the toolbar; returns
+    /// whether it changed
+    pub fn set_position(&mut self, position: Option<FilePosition>) -> bool {
+        let changed = self.position != position;
+        self.position = position;
+        changed
+    }
+
     pub fn close(&mut self) {
+        self.stop_following();
+        self.line_selection = None;
+        self.pending_reveal = None;
         self.file_path = None;
+        self.renamed_from = None;
+        self.comparison = None;
+        self.submodule = None;
+        self.binary = None;
+        self.large = None;
+        self.image = None;
+        self.clear_conflicts();
+        self.position = None;
         self.content.clear();
+        self.encoding = TextEncoding::Utf8;
+        self.encoding_menu = false;
         self.diff_content = None;
+        self.pending_diff = None;
         self.mode = FileViewMode::Content;
         self.clear_diff_cache();
     }
 
-    /// Parse diff to create inline view lines.
-    ///
-    /// Algorithm:
-    /// 1. First pass: scan diff to identify added lines and their positions,
-    ///    and collect deleted lines with their insertion points
-    /// 2. Second pass: iterate through file content, inserting deleted lines
-    ///    at their original positions and marking added lines
-    fn parse_diff_for_inline_view(&self) -> Vec<InlineDiffLine> {
-        let diff = self.diff_content.as_deref().unwrap_or("");
-        let content_lines: Vec<&str> = self.content.lines().collect();
-        let mut result: Vec<InlineDiffLine> = Vec::new();
-
-        let mut added_lines: std::collections::HashSet<usize> = std::collections::HashSet::new();
-        let mut deleted_at: Vec<(usize, String)> = Vec::new();
-        let mut new_line_num = 1usize;
+    /// Select `line` (1-based) of the open file and scroll to it in the
+    /// content view, as soon as the text is shown if it is still loading
+    pub fn reveal_line(&mut self, line: usize, cx: &mut Context<Self>) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+        self.line_selection = Some(LineSelection {
+            anchor: line,
+            head: line,
+        });
+        self.pending_reveal = Some((path, line));
+        cx.notify();
+    }
 
-        for line in diff.lines() {
-            if line.starts_with("@@") {
-                if let Some((_, new_start)) = Self::parse_hunk_header(line) {
-                    new_line_num = new_start;
+    /// Scroll to the line asked for by `reveal_line` once the text is there;
+    /// a diff shown meanwhile gives way to the content view
+    fn apply_pending_reveal(&mut self) {
+        let Some((path, line)) = self.pending_reveal.clone() else {
+            return;
+        };
+        if self.file_path.as_ref() != Some(&path) {
+            self.pending_reveal = None;
+            return;
+        }
+        let index = line.saturating_sub(1);
+        if let Some(large) = &self.large {
+            match &large.index {
+                None => return,
+                Some(Ok(_)) => {
+                    let top = index.saturating_sub(REVEAL_CONTEXT_LINES) as f32 * LARGE_ROW_HEIGHT;
+                    self.content_scroll_handle
+                        .set_offset(point(px(0.), px(-top)));
                 }
-            } else if line.starts_with("---")
-                || line.starts_with("+++")
-                || line.starts_with("diff ")
-            {
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                added_lines.insert(new_line_num);
-                new_line_num += 1;
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                let content = line.strip_prefix('-').unwrap_or(line);
-                deleted_at.push((new_line_num, content.to_string()));
-            } else if line.starts_with(' ') || (!line.starts_with('@') && !line.is_empty()) {
-                new_line_num += 1;
+                Some(Err(_)) => {}
+            }
+        } else if self.pending_diff.is_some() {
+            return;
+        } else if self.binary.is_none() && self.submodule.is_none() && self.image.is_none() {
+            self.mode = FileViewMode::Content;
+            if self.wraps() {
+                self.wrapped_reveal = Some(index);
+            } else {
+                self.content_scroll_handle.scroll_to_item(index);
             }
         }
+        self.pending_reveal = None;
+    }
 
-        let mut deleted_idx = 0;
-        for (i, content_line) in content_lines.iter().enumerate() {
-            let line_num = i + 1;
+    fn stop_following(&mut self) {
+        self.follower = None;
+        self.follow = false;
+        self.auto_follow = false;
+        self.follow_generation += 1;
+    }
 
-            while deleted_idx < deleted_at.len() && deleted_at[deleted_idx].0 == line_num {
-                result.push(InlineDiffLine {
-                    line_num: None,
-                    content: deleted_at[deleted_idx].1.clone(),
-                    change_type: InlineChangeType::Deleted,
-                });
-                deleted_idx += 1;
-            }
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
 
-            let change_type = if added_lines.contains(&line_num) {
-                InlineChangeType::Added
-            } else {
-                InlineChangeType::Unchanged
-            };
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
 
-            result.push(InlineDiffLine {
-                line_num: Some(line_num),
-                content: content_line.to_string(),
-                change_type,
-            });
-        }
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+    }
+
+    /// Wrap the long lines of files whose names match `patterns` unless
+    /// wrapping was toggled for them
+    pub fn set_wrap_patterns(&mut self, patterns: Vec<String>) {
+        self.wrap_patterns = patterns
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .collect();
+    }
+
+    /// Whether the long lines of the open file wrap
+    fn wraps(&self) -> bool {
+        let Some(path) = &self.file_path else {
+            return false;
+        };
+        if let Some(&wrap) = self.wrap_by_file.get(path) {
+            return wrap;
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        self.wrap_patterns
+            .iter()
+            .any(|pattern| pattern.matches(&name))
+    }
+
+    /// Turn wrapping of the open file on or off, staying at the selected
+    /// line if there is one
+    pub fn toggle_wrap(&mut self) {
+        let Some(path) = self.file_path.clone().filter(|_| self.can_wrap()) else {
+            return;
+        };
+        let wrap = !self.wraps();
+        self.wrap_by_file.insert(path, wrap);
+        self.content_scroll_handle.set_offset(point(px(0.), px(0.)));
+        if let Some(selection) = self.line_selection {
+            let index = selection.range().start().saturating_sub(1);
+            if wrap {
+                self.wrapped_reveal = Some(index);
+            } else {
+                self.content_scroll_handle.scroll_to_item(index);
+            }
+        }
+    }
+
+    /// Whether the wrap toggle applies (text shown as is)
+    pub fn can_wrap(&self) -> bool {
+        self.file_path.is_some()
+            && self.mode == FileViewMode::Content
+            && self.large.is_none()
+            && self.binary.is_none()
+            && self.submodule.is_none()
+            && self.pending_diff.is_none()
+            && self.image.as_ref().is_none_or(|image| image.show_source)
+    }
+
+    /// Show the review `comments` (with their index among the session's)
+    /// under their lines of the file at `path`. Returns whether they differ
+    /// from those shown.
+    pub fn set_comments(&mut self, path: PathBuf, comments: Vec<(usize, ReviewComment)>) -> bool {
+        let changed = self.comments.as_ref().is_none_or(|(shown_path, shown)| {
+            *shown_path != path || shown.as_slice() != comments.as_slice()
+        });
+        if changed {
+            self.comments = Some((path, Rc::new(comments)));
+        }
+        changed
+    }
+
+    /// Turn follow mode on (catching up with the file) or off
+    pub fn set_follow(&mut self, follow: bool) {
+        let Some(path) = self.follower.as_ref().map(|f| f.path().to_path_buf()) else {
+            return;
+        };
+        self.auto_follow = false;
+        self.follow_generation += 1;
+        if follow && let Ok(content) = std::fs::read_to_string(&path) {
+            self.follower = Some(FileFollower::new(&path, &content));
+            self.content = content;
+            self.content_scroll_handle.scroll_to_bottom();
+        }
+        self.follow = follow;
+    }
+
+    /// Poll the open file for as long as the view exists
+    fn spawn_follow_task(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |view, cx| {
+            loop {
+                smol::Timer::after(FOLLOW_POLL_INTERVAL).await;
+                let Ok(polled) = view.update(cx, |view, _cx| {
+                    view.follower
+                        .clone()
+                        .filter(|_| view.follow || view.auto_follow)
+                        .map(|follower| (view.follow_generation, follower))
+                }) else {
+                    break;
+                };
+                let Some((generation, mut follower)) = polled else {
+                    continue;
+                };
+
+                let result =
+                    smol::unblock(move || follower.poll().map(|update| (follower, update))).await;
+
+                // A deleted file keeps its last content; polling resumes if it returns
+                if let Ok((follower, update)) = result {
+                    let _ = view.update(cx, |view, cx| {
+                        view.apply_follow_update(generation, follower, update, cx);
+                    });
+                }
+            }
+        })
+    }
+
+    fn apply_follow_update(
+        &mut self,
+        generation: u64,
+        follower: FileFollower,
+        update: FollowUpdate,
+        cx: &mut Context<Self>,
+    ) {
+        if generation != self.follow_generation || update == FollowUpdate::Unchanged {
+            return;
+        }
+
+        if !self.follow {
+            match update {
+                FollowUpdate::Appended(_) => self.follow = true,
+                // Rewritten rather than grown: not a log, leave the snapshot alone
+                _ => {
+                    self.auto_follow = false;
+                    return;
+                }
+            }
+            self.content_scroll_handle.scroll_to_bottom();
+        } else if self.is_content_scrolled_to_bottom() {
+            self.content_scroll_handle.scroll_to_bottom();
+        }
+
+        match update {
+            FollowUpdate::Appended(text) => self.content.push_str(&text),
+            FollowUpdate::Reloaded(content) => self.content = content,
+            FollowUpdate::Unchanged => {}
+        }
+        self.follower = Some(follower);
+        cx.notify();
+    }
+
+    /// Whether the content view shows its last line (sticky scrolling)
+    fn is_content_scrolled_to_bottom(&self) -> bool {
+        let max = f32::from(self.content_scroll_handle.max_offset().height);
+        let top = -f32::from(self.content_scroll_handle.offset().y);
+        top >= max - 4.0
+    }
+
+    /// Parse diff to create inline view lines.
+    ///
+    /// Algorithm:
+    /// 1. First pass: scan diff to identify added lines and their positions,
+    ///    and collect deleted lines with their insertion points
+    /// 2. Second pass: iterate through file content, inserting deleted lines
+    ///    at their original positions and marking added lines
+    fn parse_diff_for_inline_view(&self) -> Vec<InlineDiffLine> {
+        let diff = self.shown_diff();
+        let content_lines: Vec<&str> = raw_lines(&self.content).collect();
+        let mut result: Vec<InlineDiffLine> = Vec::new();
+
+        let mut added_lines: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        let mut deleted_at: Vec<(usize, String)> = Vec::new();
+        let mut hunks_at: Vec<(usize, usize, String)> = Vec::new();
+        let mut new_line_num = 1usize;
+        let show_hunks = self.can_discard_hunks();
+
+        for line in raw_lines(diff) {
+            if line.starts_with("@@") {
+                if let Some((_, new_start)) = Self::parse_hunk_header(line) {
+                    new_line_num = new_start;
+                }
+                if show_hunks {
+                    // A hunk removing the first lines starts at line 0
+                    hunks_at.push((new_line_num.max(1), hunks_at.len(), line.to_string()));
+                }
+            } else if line.starts_with("---")
+                || line.starts_with("+++")
+                || line.starts_with("diff ")
+            {
+            } else if line.starts_with('+') && !line.starts_with("+++") {
+                added_lines.insert(new_line_num);
+                new_line_num += 1;
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                let content = line.strip_prefix('-').unwrap_or(line);
+                deleted_at.push((new_line_num, content.to_string()));
+            } else if line.starts_with(' ') || (!line.starts_with('@') && !line.is_empty()) {
+                new_line_num += 1;
+            }
+        }
+
+        let mut deleted_idx = 0;
+        let mut hunk_idx = 0;
+        let push_hunks_until = |result: &mut Vec<InlineDiffLine>, hunk_idx: &mut usize, line| {
+            while *hunk_idx < hunks_at.len() && hunks_at[*hunk_idx].0 <= line {
+                result.push(InlineDiffLine {
+                    line_num: None,
+                    content: hunks_at[*hunk_idx].2.clone(),
+                    change_type: InlineChangeType::HunkHeader,
+                    hunk: Some(hunks_at[*hunk_idx].1),
+                    whitespace: false,
+                });
+                *hunk_idx += 1;
+            }
+        };
+
+        for (i, content_line) in content_lines.iter().enumerate() {
+            let line_num = i + 1;
+
+            push_hunks_until(&mut result, &mut hunk_idx, line_num);
+            while deleted_idx < deleted_at.len() && deleted_at[deleted_idx].0 == line_num {
+                result.push(InlineDiffLine {
+                    line_num: None,
+                    content: deleted_at[deleted_idx].1.clone(),
+                    change_type: InlineChangeType::Deleted,
+                    hunk: None,
+                    whitespace: false,
+                });
+                deleted_idx += 1;
+            }
+
+            let change_type = if added_lines.contains(&line_num) {
+                InlineChangeType::Added
+            } else {
+                InlineChangeType::Unchanged
+            };
+
+            result.push(InlineDiffLine {
+                line_num: Some(line_num),
+                content: content_line.to_string(),
+                change_type,
+                hunk: None,
+                whitespace: self.is_whitespace_line(line_num),
+            });
+        }
 
         while deleted_idx < deleted_at.len() {
+            push_hunks_until(&mut result, &mut hunk_idx, deleted_at[deleted_idx].0);
             result.push(InlineDiffLine {
                 line_num: None,
                 content: deleted_at[deleted_idx].1.clone(),
                 change_type: InlineChangeType::Deleted,
+                hunk: None,
+                whitespace: false,
             });
             deleted_idx += 1;
         }
+        push_hunks_until(&mut result, &mut hunk_idx, usize::MAX);
 
         result
     }
 
     fn compute_split_diff(&self) -> (Vec<SplitDiffLine>, Vec<SplitDiffLine>) {
-        let diff = self.diff_content.as_deref().unwrap_or("");
+        let diff = self.shown_diff();
         let mut left_lines: Vec<SplitDiffLine> = Vec::new();
         let mut right_lines: Vec<SplitDiffLine> = Vec::new();
 
         // If diff is empty or has no actual changes, show file content as context
-        let has_changes = diff.lines().any(|line| {
+        let has_changes = raw_lines(diff).any(|line| {
             line.starts_with('+') && !line.starts_with("+++")
                 || line.starts_with('-') && !line.starts_with("---")
         });
 
         if !has_changes {
-            for (i, line) in self.content.lines().enumerate() {
+            for (i, line) in raw_lines(&self.content).enumerate() {
                 let line_num = i + 1;
                 let parsed = SplitDiffLine {
                     old_line_num: Some(line_num),
                     new_line_num: Some(line_num),
                     content: line.to_string(),
                     line_type: DiffLineType::Context,
+                    hunk: None,
+                    whitespace: self.is_whitespace_line(line_num),
                 };
                 left_lines.push(parsed.clone());
                 right_lines.push(parsed);
@@ -266,14 +1313,30 @@ impl FileView {
 
         let mut old_line_num = 1usize;
         let mut new_line_num = 1usize;
+        let show_hunks = self.can_discard_hunks();
+        let mut hunk_count = 0;
 
-        for line in diff.lines() {
+        for line in raw_lines(diff) {
             if line.starts_with("@@") {
-                // Parse hunk header to update line numbers, but don't display it
+                // Parse hunk header to update line numbers; it is only shown
+                // (on both sides) when it carries the discard action
                 if let Some((old_start, new_start)) = Self::parse_hunk_header(line) {
                     old_line_num = old_start;
                     new_line_num = new_start;
                 }
+                if show_hunks {
+                    let header = SplitDiffLine {
+                        old_line_num: None,
+                        new_line_num: None,
+                        content: line.to_string(),
+                        line_type: DiffLineType::HunkHeader,
+                        hunk: Some(hunk_count),
+                        whitespace: false,
+                    };
+                    left_lines.push(header.clone());
+                    right_lines.push(header);
+                }
+                hunk_count += 1;
             } else if line.starts_with("---")
                 || line.starts_with("+++")
                 || line.starts_with("diff ")
@@ -285,12 +1348,16 @@ impl FileView {
                     new_line_num: None,
                     content: String::new(),
                     line_type: DiffLineType::Added,
+                    hunk: None,
+                    whitespace: false,
                 });
                 right_lines.push(SplitDiffLine {
                     old_line_num: None,
                     new_line_num: Some(new_line_num),
                     content: stripped.to_string(),
                     line_type: DiffLineType::Added,
+                    hunk: None,
+                    whitespace: false,
                 });
                 new_line_num += 1;
             } else if let Some(stripped) = line.strip_prefix('-') {
@@ -299,207 +1366,1865 @@ impl FileView {
                     new_line_num: None,
                     content: stripped.to_string(),
                     line_type: DiffLineType::Removed,
+                    hunk: None,
+                    whitespace: false,
                 });
                 right_lines.push(SplitDiffLine {
                     old_line_num: None,
                     new_line_num: None,
                     content: String::new(),
                     line_type: DiffLineType::Removed,
+                    hunk: None,
+                    whitespace: false,
                 });
                 old_line_num += 1;
             } else if line.starts_with(' ') || line.is_empty() {
                 let content = if line.is_empty() { "" } else { &line[1..] };
+                let whitespace = self.is_whitespace_line(new_line_num);
                 left_lines.push(SplitDiffLine {
                     old_line_num: Some(old_line_num),
                     new_line_num: None,
                     content: content.to_string(),
                     line_type: DiffLineType::Context,
+                    hunk: None,
+                    whitespace,
                 });
                 right_lines.push(SplitDiffLine {
                     old_line_num: None,
                     new_line_num: Some(new_line_num),
                     content: content.to_string(),
                     line_type: DiffLineType::Context,
+                    hunk: None,
+                    whitespace,
                 });
                 old_line_num += 1;
                 new_line_num += 1;
             }
-        }
+        }
+
+        (left_lines, right_lines)
+    }
+
+    pub(crate) fn parse_hunk_header(line: &str) -> Option<(usize, usize)> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        if parts.len() >= 3 {
+            let old_part = parts[1].trim_start_matches('-');
+            let new_part = parts[2].trim_start_matches('+');
+            let old_start = old_part.split(',').next()?.parse().ok()?;
+            let new_start = new_part.split(',').next()?.parse().ok()?;
+            Some((old_start, new_start))
+        } else {
+            None
+        }
+    }
+
+    /// Hunks can be discarded for real `git diff` output of an existing file
+    /// (the diffs generated for added and deleted files have no usable headers),
+    /// unless the session is read-only or the diff is against another session
+    fn can_discard_hunks(&self) -> bool {
+        !self.read_only
+            && self.comparison.is_none()
+            && self
+                .diff_content
+                .as_deref()
+                .is_some_and(|diff| diff.starts_with("diff --git"))
+            && self.file_path.as_ref().is_some_and(|p| p.exists())
+    }
+
+    /// Patch for hunk `index` of `diff`: the file headers plus that hunk
+    pub(crate) fn hunk_patch(diff: &str, index: usize) -> Option<String> {
+        let mut header = String::new();
+        let mut hunk = String::new();
+        let mut current: Option<usize> = None;
+
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("@@") {
+                let next = current.map_or(0, |i| i + 1);
+                if next > index {
+                    break;
+                }
+                current = Some(next);
+            }
+            match current {
+                None => header.push_str(line),
+                Some(i) if i == index => hunk.push_str(line),
+                Some(_) => {}
+            }
+        }
+
+        if hunk.is_empty() {
+            return None;
+        }
+        if !hunk.ends_with('\n') {
+            hunk.push('\n');
+        }
+        Some(header + &hunk)
+    }
+
+    fn request_discard_hunk(&mut self, hunk: usize, cx: &mut Context<Self>) {
+        if let (Some(path), Some(patch)) = (
+            self.file_path.clone(),
+            self.diff_content
+                .as_deref()
+                .and_then(|diff| Self::hunk_patch(diff, hunk)),
+        ) {
+            cx.emit(DiscardHunkEvent(path, patch, self.encoding));
+        }
+    }
+
+    fn click_line_number(&mut self, line: usize, event: &ClickEvent, cx: &mut Context<Self>) {
+        self.line_selection =
+            LineSelection::click(self.line_selection, line, event.modifiers().shift);
+        cx.notify();
+    }
+
+    fn is_line_selected(&self, line: Option<usize>) -> bool {
+        line.zip(self.line_selection)
+            .is_some_and(|(line, selection)| selection.range().contains(&line))
+    }
+
+    fn insert_line_reference(&mut self, cx: &mut Context<Self>) {
+        if let (Some(path), Some(selection)) = (self.file_path.clone(), self.line_selection) {
+            cx.emit(InsertReferenceEvent(path, selection.range()));
+        }
+    }
+
+    fn insert_line_snippet(&mut self, cx: &mut Context<Self>) {
+        let (Some(path), Some(selection)) = (self.file_path.clone(), self.line_selection) else {
+            return;
+        };
+        let range = selection.range();
+        let text: String = self
+            .content
+            .lines()
+            .skip(range.start() - 1)
+            .take(range.end() - range.start() + 1)
+            .map(|line| format!("{}\n", expand_tabs(line, self.tab_width)))
+            .collect();
+        cx.emit(InsertSnippetEvent(path, text));
+    }
+
+    /// Line number cell; clicks select lines, a right click sends a reference
+    /// to the terminal right away
+    fn render_line_number(
+        &self,
+        id: impl Into<gpui::ElementId>,
+        line_num: Option<usize>,
+        label: String,
+        cx: &Context<Self>,
+    ) -> gpui::Stateful<gpui::Div> {
+        let selected = self.is_line_selected(line_num);
+        let path_for_click = self.file_path.clone();
+
+        div()
+            .id(id)
+            .flex_shrink_0()
+            .text_right()
+            .pr_2()
+            .text_color(rgb(if selected { BLUE } else { TEXT_MUTED }))
+            .when(selected, |el| el.bg(rgb(BG_SURFACE1)))
+            .when_some(line_num, |el, num| {
+                el.cursor_pointer()
+                    .hover(|el| el.text_color(rgb(BLUE)))
+                    .on_click(cx.listener(move |this, event: &ClickEvent, _, cx| {
+                        this.click_line_number(num, event, cx);
+                    }))
+                    .on_mouse_down(
+                        MouseButton::Right,
+                        cx.listener(move |_this, _, _, cx| {
+                            if let Some(ref path) = path_for_click {
+                                let text = format!("`{}:{}`", path.to_string_lossy(), num);
+                                cx.emit(SendToTerminalEvent(text));
+                            }
+                        }),
+                    )
+            })
+            .child(label)
+    }
+
+    /// Floating actions for the selected lines
+    fn render_line_actions(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let range = self.line_selection?.range();
+        let label = if range.start() == range.end() {
+            format!("L{}", range.start())
+        } else {
+            format!("L{}–{}", range.start(), range.end())
+        };
+        // Deleted files have nothing to open
+        let external_path = self
+            .file_path
+            .clone()
+            .filter(|p| !self.read_only && p.exists());
+        let start = *range.start();
+        let button = |id: &'static str, label: &'static str, color: u32| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_xs()
+                .text_color(rgb(color))
+                .child(label)
+        };
+
+        Some(
+            div()
+                .absolute()
+                .top_2()
+                .right_4()
+                .flex()
+                .items_center()
+                .gap_1()
+                .p_1()
+                .rounded_md()
+                .bg(rgb(BG_SURFACE0))
+                .border_1()
+                .border_color(rgb(BG_SURFACE2))
+                .shadow_md()
+                .child(
+                    div()
+                        .px_1()
+                        .text_xs()
+                        .font_family(MONOSPACE_FONT)
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(label),
+                )
+                .child(
+                    button("insert-line-reference", "Insert reference", BLUE)
+                        .on_click(cx.listener(|this, _, _, cx| this.insert_line_reference(cx))),
+                )
+                .child(
+                    button("insert-line-snippet", "Insert snippet", GREEN)
+                        .on_click(cx.listener(|this, _, _, cx| this.insert_line_snippet(cx))),
+                )
+                .when_some(external_path, |el, path| {
+                    el.child(
+                        button("open-line-externally", "Open", TEXT_SECONDARY).on_click(
+                            cx.listener(move |_this, _, _, cx| {
+                                cx.emit(OpenExternalEvent(path.clone(), Some(start)));
+                            }),
+                        ),
+                    )
+                })
+                .child(
+                    button("clear-line-selection", "×", TEXT_MUTED).on_click(cx.listener(
+                        |this, _, _, cx| {
+                            this.line_selection = None;
+                            cx.notify();
+                        },
+                    )),
+                )
+                .into_any_element(),
+        )
+    }
+
+    /// Text of `line` (1-based) of the open file as it reads now
+    fn line_text(&self, line: usize) -> Option<String> {
+        let text = self.content.lines().nth(line.checked_sub(1)?)?;
+        Some(text.trim_end_matches('\r').to_string())
+    }
+
+    /// Review comments on `line` of the open file
+    fn comments_on(&self, line: Option<usize>) -> Vec<(usize, ReviewComment)> {
+        let (Some(line), Some((path, comments))) = (line, &self.comments) else {
+            return Vec::new();
+        };
+        if self.file_path.as_ref() != Some(path) {
+            return Vec::new();
+        }
+        comments
+            .iter()
+            .filter(|(_, comment)| comment.line == line)
+            .cloned()
+            .collect()
+    }
+
+    fn comment_line(&mut self, line: usize, cx: &mut Context<Self>) {
+        if let Some(path) = self.file_path.clone() {
+            let text = self.line_text(line).unwrap_or_default();
+            cx.emit(CommentLineEvent(path, line, text));
+        }
+    }
+
+    /// Gutter cell left of a line number of the working tree file: a click
+    /// writes a review comment on the line, which is marked once it has one
+    fn render_comment_gutter(
+        &self,
+        id: impl Into<gpui::ElementId>,
+        line_num: Option<usize>,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let commented = !self.comments_on(line_num).is_empty();
+        div()
+            .id(id)
+            .w_4()
+            .flex_shrink_0()
+            .text_center()
+            .when_some(line_num, |el, line| {
+                el.cursor_pointer()
+                    .text_color(rgb(if commented { MAUVE } else { BLUE }))
+                    .when(!commented, |el| el.opacity(0.).hover(|el| el.opacity(1.)))
+                    .on_click(cx.listener(move |this, _, _, cx| this.comment_line(line, cx)))
+                    .child(if commented { "●" } else { "+" })
+            })
+            .into_any_element()
+    }
+
+    /// Review comments shown under `line` of the working tree file. The
+    /// "Before" side of the split diff gets the same blocks as an invisible
+    /// `spacer`, so its rows stay level with the "After" side; lines do not
+    /// wrap, so the blocks are as tall on both sides.
+    fn render_comment_blocks(
+        &self,
+        line: Option<usize>,
+        spacer: bool,
+        cx: &Context<Self>,
+    ) -> Option<AnyElement> {
+        let comments = self.comments_on(line);
+        if comments.is_empty() {
+            return None;
+        }
+        let button = |id: (&'static str, usize), label: &'static str, color: u32| {
+            div()
+                .id(id)
+                .px_1()
+                .rounded_sm()
+                .when(!spacer, |el| {
+                    el.cursor_pointer().hover(|el| el.bg(rgb(BG_SURFACE1)))
+                })
+                .text_color(rgb(color))
+                .child(label)
+        };
+
+        Some(
+            div()
+                .flex()
+                .flex_col()
+                .gap_1()
+                .py_1()
+                .pl_14()
+                .pr_2()
+                .when(spacer, |el| el.opacity(0.))
+                .children(comments.into_iter().map(|(index, comment)| {
+                    let stale = comment.is_stale(Some(&self.content));
+                    let line_text = self.line_text(comment.line);
+                    div()
+                        .flex()
+                        .flex_col()
+                        .px_2()
+                        .py_1()
+                        .rounded_sm()
+                        .bg(rgb(BG_SURFACE0))
+                        .border_l_2()
+                        .border_color(rgb(if stale { YELLOW } else { MAUVE }))
+                        .text_xs()
+                        .whitespace_nowrap()
+                        .child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .gap_2()
+                                .text_color(rgb(TEXT_MUTED))
+                                .child(format!("L{}", comment.line))
+                                .when(stale, |el| {
+                                    el.child(
+                                        div()
+                                            .text_color(rgb(YELLOW))
+                                            .child("the line has changed since"),
+                                    )
+                                })
+                                .child(div().flex_1())
+                                .child(button(("edit-comment", index), "Edit", BLUE).when(
+                                    !spacer,
+                                    |el| {
+                                        el.on_click(cx.listener(move |_this, _, _, cx| {
+                                            cx.emit(EditCommentEvent(index, line_text.clone()));
+                                        }))
+                                    },
+                                ))
+                                .child(button(("delete-comment", index), "Delete", RED).when(
+                                    !spacer,
+                                    |el| {
+                                        el.on_click(cx.listener(move |_this, _, _, cx| {
+                                            cx.emit(DeleteCommentEvent(index));
+                                        }))
+                                    },
+                                )),
+                        )
+                        .children(
+                            comment
+                                .text
+                                .lines()
+                                .map(|text| {
+                                    div()
+                                        .text_color(rgb(TEXT))
+                                        .child(if text.is_empty() { " " } else { text }.to_string())
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                }))
+                .into_any_element(),
+        )
+    }
+
+    /// Hunk separator row; `with_action` adds the "Discard hunk" button
+    fn render_hunk_header(
+        hunk: usize,
+        header: &str,
+        with_action: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        div()
+            .flex()
+            .items_center()
+            .justify_between()
+            .gap_2()
+            .mt_1()
+            .px_2()
+            .bg(rgb(BG_SURFACE0))
+            .text_xs()
+            .text_color(rgb(TEXT_MUTED))
+            .child(
+                div()
+                    .min_w_0()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .child(header.to_string()),
+            )
+            .when(with_action, |el| {
+                el.child(
+                    div()
+                        .id(("discard-hunk", hunk))
+                        .flex_shrink_0()
+                        .px_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .text_color(rgb(RED))
+                        .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.request_discard_hunk(hunk, cx);
+                        }))
+                        .child("Discard hunk"),
+                )
+            })
+            .into_any_element()
+    }
+
+    fn compute_added_line_numbers(&self) -> std::collections::HashSet<usize> {
+        let diff = self.shown_diff();
+        let mut added_lines = std::collections::HashSet::new();
+        let mut new_line_num = 1usize;
+
+        for line in raw_lines(diff) {
+            if line.starts_with("@@") {
+                if let Some((_, new_start)) = Self::parse_hunk_header(line) {
+                    new_line_num = new_start;
+                }
+            } else if line.starts_with('+') && !line.starts_with("+++") {
+                added_lines.insert(new_line_num);
+                new_line_num += 1;
+            } else if line.starts_with('-') && !line.starts_with("---") {
+            } else if line.starts_with(' ') || (!line.starts_with('@') && !line.is_empty()) {
+                new_line_num += 1;
+            }
+        }
+
+        added_lines
+    }
+
+    fn render_toolbar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let file_name = self
+            .file_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("No file");
+        let title = match (&self.comparison, &self.renamed_from) {
+            (Some(comparison), _) if self.diff_content.is_none() => {
+                format!("{} (identical)", comparison.title())
+            }
+            (Some(comparison), _) => comparison.title(),
+            (None, Some(old_path)) => format!("{} → {}", old_path.display(), file_name),
+            (None, None) => file_name.to_string(),
+        };
+
+        let mode = self.mode;
+        let has_diff = self.diff_content.is_some();
+        let can_follow = mode == FileViewMode::Content && self.follower.is_some();
+        let can_wrap = self.can_wrap();
+        let wraps = self.wraps();
+        let follow = self.follow;
+        // Deleted files have nothing to open
+        let external_path = self
+            .file_path
+            .clone()
+            .filter(|p| !self.read_only && p.exists());
+        let reveal_path = self.file_path.clone();
+        let comparison = self.comparison.clone();
+        let compare_path = self
+            .file_path
+            .clone()
+            .filter(|_| self.comparison.is_none() && self.submodule.is_none());
+        let repository_path = self
+            .submodule
+            .as_ref()
+            .filter(|status| status.checked_out.is_some())
+            .and(self.file_path.clone());
+        let image_controls = self.render_image_controls(cx);
+        let encoding_chip = self.render_encoding_chip(cx);
+        let file_nav = self
+            .position
+            .map(|position| Self::render_file_nav(position, cx));
+
+        div()
+            .h_8()
+            .px_3()
+            .flex()
+            .items_center()
+            .justify_between()
+            .bg(rgb(BG_BASE))
+            .border_b_1()
+            .border_color(rgb(BG_SURFACE0))
+            .child(div().text_sm().text_color(rgb(TEXT)).child(title))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .when(has_diff && self.is_diff_mode(), |el| {
+                        el.child(
+                            div()
+                                .id("toggle-diff-display")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(MAUVE))
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.toggle_diff_display_mode();
+                                    cx.notify();
+                                }))
+                                .child(if mode == FileViewMode::DiffSplit {
+                                    "Inline"
+                                } else {
+                                    "Split"
+                                }),
+                        )
+                    })
+                    .when(
+                        has_diff && self.is_diff_mode() && !self.diff_stats.line_ending_only,
+                        |el| {
+                            let ignoring = self.ignore_whitespace;
+                            el.child(
+                                div()
+                                    .id("toggle-ignore-whitespace")
+                                    .px_2()
+                                    .py_1()
+                                    .cursor_pointer()
+                                    .rounded_sm()
+                                    .bg(rgb(BG_SURFACE0))
+                                    .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                    .text_xs()
+                                    .text_color(rgb(if ignoring { YELLOW } else { TEXT_MUTED }))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.toggle_ignore_whitespace();
+                                        cx.notify();
+                                    }))
+                                    .child("Ignore whitespace"),
+                            )
+                        },
+                    )
+                    .children(file_nav)
+                    .children(encoding_chip)
+                    .children(image_controls)
+                    .when(can_wrap, |el| {
+                        el.child(
+                            div()
+                                .id("toggle-wrap")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(if wraps { GREEN } else { TEXT_MUTED }))
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.toggle_wrap();
+                                    cx.notify();
+                                }))
+                                .child("Wrap"),
+                        )
+                    })
+                    .when(can_follow, |el| {
+                        el.child(
+                            div()
+                                .id("toggle-follow")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(if follow { GREEN } else { TEXT_MUTED }))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.set_follow(!follow);
+                                    cx.notify();
+                                }))
+                                .child(if follow { "Following" } else { "Follow" }),
+                        )
+                    })
+                    .when_some(comparison, |el, comparison| {
+                        el.child(
+                            div()
+                                .id("swap-comparison")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(MAUVE))
+                                .on_click(cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(SwapComparisonEvent(comparison.clone()));
+                                }))
+                                .child("Swap sides"),
+                        )
+                    })
+                    .when_some(compare_path, |el, path| {
+                        el.child(
+                            div()
+                                .id("compare-with-session")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(TEXT_SECONDARY))
+                                .on_click(cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(CompareWithSessionEvent(path.clone()));
+                                }))
+                                .child("Compare…"),
+                        )
+                    })
+                    .when_some(reveal_path, |el, path| {
+                        el.child(
+                            div()
+                                .id("reveal-in-sidebar")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(TEXT_SECONDARY))
+                                .on_click(cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(RevealInSidebarEvent(path.clone()));
+                                }))
+                                .child("Reveal"),
+                        )
+                    })
+                    .when_some(repository_path, |el, path| {
+                        el.child(
+                            div()
+                                .id("open-as-repository")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(TEAL))
+                                .on_click(cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(OpenRepositoryEvent(path.clone()));
+                                }))
+                                .child("Open as repository"),
+                        )
+                    })
+                    .when_some(external_path, |el, path| {
+                        el.child(
+                            div()
+                                .id("open-externally")
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .bg(rgb(BG_SURFACE0))
+                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(BLUE))
+                                .on_click(cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(OpenExternalEvent(path.clone(), None));
+                                }))
+                                .child("Open externally"),
+                        )
+                    })
+                    .child(
+                        div()
+                            .id("close-file")
+                            .px_2()
+                            .py_1()
+                            .cursor_pointer()
+                            .text_xs()
+                            .text_color(rgb(TEXT_MUTED))
+                            .hover(|el| el.text_color(rgb(RED)))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.close();
+                                cx.notify();
+                            }))
+                            .child("Close"),
+                    ),
+            )
+    }
+
+    /// Previous/next changed file arrows around "file 4 of 17". The label
+    /// turns peach after wrapping around and notes the last file.
+    fn render_file_nav(position: FilePosition, cx: &mut Context<Self>) -> AnyElement {
+        let arrow = |id: &'static str, label: &'static str, forward: bool| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE0))
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_xs()
+                .text_color(rgb(TEXT_SECONDARY))
+                .on_click(cx.listener(move |_this, _, _, cx| {
+                    cx.emit(NavigateFileEvent(forward));
+                }))
+                .child(label)
+        };
+        let mut label = position.label();
+        if position.is_last() {
+            label.push_str(" · last");
+        }
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(arrow("previous-changed-file", "‹", false))
+            .child(
+                div()
+                    .px_1()
+                    .text_xs()
+                    .text_color(rgb(if position.wrapped { PEACH } else { TEXT_MUTED }))
+                    .child(label),
+            )
+            .child(arrow("next-changed-file", "›", true))
+            .into_any_element()
+    }
+
+    /// Encoding of a file that is not UTF-8, for the toolbar. Clicking it
+    /// offers to rewrite the file as UTF-8 (when it may be written).
+    fn render_encoding_chip(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        if self.encoding == TextEncoding::Utf8 {
+            return None;
+        }
+        let encoding = self.encoding;
+        let convert_path = self
+            .file_path
+            .clone()
+            .filter(|p| self.encoding_menu && !self.read_only && p.exists());
+        Some(
+            div()
+                .flex()
+                .items_center()
+                .gap_1()
+                .child(
+                    div()
+                        .id("file-encoding")
+                        .px_2()
+                        .py_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .bg(rgb(BG_SURFACE0))
+                        .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                        .text_xs()
+                        .text_color(rgb(PEACH))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.encoding_menu = !this.encoding_menu;
+                            cx.notify();
+                        }))
+                        .child(encoding.label()),
+                )
+                .when_some(convert_path, |el, path| {
+                    el.child(
+                        div()
+                            .id("convert-to-utf8")
+                            .px_2()
+                            .py_1()
+                            .cursor_pointer()
+                            .rounded_sm()
+                            .bg(rgb(BG_SURFACE0))
+                            .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                            .text_xs()
+                            .text_color(rgb(TEXT_SECONDARY))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.encoding_menu = false;
+                                cx.emit(ConvertToUtf8Event(path.clone(), encoding));
+                                cx.notify();
+                            }))
+                            .child("Convert to UTF-8"),
+                    )
+                })
+                .into_any_element(),
+        )
+    }
+
+    /// Size, zoom and SVG source toggle of an open image, for the toolbar
+    fn render_image_controls(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let image = self.image.as_ref()?;
+        let button = |id: &'static str, label: String, color: u32| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE0))
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_xs()
+                .text_color(rgb(color))
+                .child(label)
+        };
+        let zoom_color = |zoom| {
+            if image.zoom == zoom {
+                BLUE
+            } else {
+                TEXT_SECONDARY
+            }
+        };
+        let source_toggle = image_preview::is_svg(&image.path).then(|| {
+            button(
+                "toggle-image-source",
+                if image.show_source { "Image" } else { "Source" }.to_string(),
+                MAUVE,
+            )
+            .on_click(cx.listener(|this, _, _, cx| {
+                this.toggle_image_source();
+                cx.notify();
+            }))
+        });
+        let zoom = self
+            .image_scale()
+            .zip(image.current.as_ref())
+            .filter(|_| !image.show_source)
+            .map(|(scale, current)| {
+                let mut info = format!(
+                    "{}×{} · {}",
+                    current.width,
+                    current.height,
+                    format_size(current.file_size)
+                );
+                if current.downscaled {
+                    info.push_str(" · downscaled");
+                }
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .child(
+                        div()
+                            .px_1()
+                            .font_family(MONOSPACE_FONT)
+                            .text_xs()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(info),
+                    )
+                    .child(
+                        button("image-zoom-fit", "Fit".to_string(), zoom_color(Zoom::Fit))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.set_image_zoom(Zoom::Fit);
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        button(
+                            "image-zoom-actual",
+                            "1:1".to_string(),
+                            zoom_color(Zoom::Scale(1.0)),
+                        )
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.set_image_zoom(Zoom::Scale(1.0));
+                            cx.notify();
+                        })),
+                    )
+                    .child(
+                        div()
+                            .w_10()
+                            .text_xs()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(format!("{:.0}%", scale * 100.0)),
+                    )
+            });
+        Some(
+            div()
+                .flex()
+                .items_center()
+                .gap_1()
+                .children(zoom)
+                .children(source_toggle)
+                .into_any_element(),
+        )
+    }
+
+    /// The open image, over its HEAD version with a slider when it changed
+    fn render_image_preview(&self, window: &Window, cx: &mut Context<Self>) -> AnyElement {
+        let Some(image) = &self.image else {
+            return div().into_any_element();
+        };
+        let (Some(current), Some(size), Some(scale)) =
+            (&image.current, image.size(), self.image_scale())
+        else {
+            return div()
+                .flex_1()
+                .flex()
+                .items_center()
+                .justify_center()
+                .bg(rgb(BG_BASE))
+                .text_color(rgb(TEXT_MUTED))
+                .child("Loading image…")
+                .into_any_element();
+        };
+        // Fitting needs the viewport, which is known after the first layout
+        if image.zoom == Zoom::Fit && self.image_scroll_handle.bounds().size.width == Pixels::ZERO {
+            window.request_animation_frame();
+        }
+        let (width, height) = (size.0 as f32 * scale, size.1 as f32 * scale);
+        let shown = |loaded: &LoadedImage| {
+            img(loaded.display.clone())
+                .absolute()
+                .top_0()
+                .left_0()
+                .w(px(loaded.width as f32 * scale))
+                .h(px(loaded.height as f32 * scale))
+        };
+        let label = |text: &'static str| {
+            div()
+                .absolute()
+                .top_1()
+                .px_1()
+                .rounded_sm()
+                .bg(rgb(BG_BASE))
+                .text_xs()
+                .text_color(rgb(TEXT_SECONDARY))
+                .child(text)
+        };
+        let comparison = image.before.as_ref().map(|before| {
+            let split_x = width * image.split;
+            div()
+                .absolute()
+                .top_0()
+                .left_0()
+                .size_full()
+                .child(
+                    div()
+                        .absolute()
+                        .top_0()
+                        .left_0()
+                        .h_full()
+                        .w(px(split_x))
+                        .overflow_hidden()
+                        .bg(rgb(BG_SURFACE0))
+                        .child(shown(before)),
+                )
+                .child(
+                    div()
+                        .id("image-slider")
+                        .absolute()
+                        .top_0()
+                        .left(px(split_x - 2.0))
+                        .h_full()
+                        .w(px(4.0))
+                        .cursor_col_resize()
+                        .bg(rgb(TEXT))
+                        .hover(|el| el.bg(rgb(BLUE)))
+                        .on_mouse_down(
+                            MouseButton::Left,
+                            cx.listener(move |this, event: &gpui::MouseDownEvent, _, cx| {
+                                if let Some(image) = &mut this.image {
+                                    image.slider_drag = Some(SliderDrag {
+                                        start_x: f32::from(event.position.x),
+                                        initial_split: image.split,
+                                        width,
+                                    });
+                                }
+                                cx.stop_propagation();
+                                cx.notify();
+                            }),
+                        ),
+                )
+                .child(label("HEAD").left_1())
+                .child(label("Working copy").right_1())
+        });
+
+        div()
+            .id("image-preview")
+            .flex_1()
+            .overflow_scroll()
+            .track_scroll(&self.image_scroll_handle)
+            .bg(rgb(BG_BASE))
+            .on_mouse_move(cx.listener(|this, event: &gpui::MouseMoveEvent, _, cx| {
+                if let Some(image) = &mut this.image
+                    && let Some(drag) = image.slider_drag
+                {
+                    image.split = drag.split_at(f32::from(event.position.x));
+                    cx.notify();
+                }
+            }))
+            .on_mouse_up(
+                MouseButton::Left,
+                cx.listener(|this, _, _, cx| {
+                    if let Some(image) = &mut this.image
+                        && image.slider_drag.take().is_some()
+                    {
+                        cx.notify();
+                    }
+                }),
+            )
+            .child(
+                // Fills the viewport so Ctrl+wheel zooms anywhere in it, and
+                // runs before the scroll container's own wheel handling
+                div()
+                    .min_w_full()
+                    .min_h_full()
+                    .p(px(IMAGE_PADDING))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .on_scroll_wheel(cx.listener(Self::handle_image_zoom_scroll))
+                    .child(
+                        div()
+                            .relative()
+                            .flex_shrink_0()
+                            .w(px(width))
+                            .h(px(height))
+                            .bg(rgb(BG_SURFACE0))
+                            .child(shown(current))
+                            .children(comparison),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    /// Ctrl+wheel (Cmd+wheel on macOS) zooms the image instead of scrolling
+    fn handle_image_zoom_scroll(
+        &mut self,
+        event: &ScrollWheelEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !event.modifiers.control && !event.modifiers.platform {
+            return;
+        }
+        let Some(scale) = self.image_scale() else {
+            return;
+        };
+        let line_height = window.line_height();
+        let lines = f32::from(event.delta.pixel_delta(line_height).y) / f32::from(line_height);
+        self.set_image_zoom(Zoom::Scale(image_preview::zoom_by(scale, lines)));
+        cx.stop_propagation();
+        cx.notify();
+    }
+
+    fn render_content(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let lines: Vec<String> = self.content.lines().map(|s| s.to_string()).collect();
+
+        // Lines are direct children of the scrolled element, so
+        // `reveal_line` can scroll to one by its index
+        div()
+            .id("file-content-scroll")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_y_scroll()
+            .track_scroll(&self.content_scroll_handle)
+            .bg(rgb(BG_BASE))
+            .p_2()
+            .font_family(MONOSPACE_FONT)
+            .text_sm()
+            .children(lines.into_iter().enumerate().map(|(num, line)| {
+                let line_num = num + 1;
+
+                div()
+                    .flex()
+                    .flex_shrink_0()
+                    .child(
+                        self.render_line_number(
+                            ("content-line", line_num),
+                            Some(line_num),
+                            format!("{}", line_num),
+                            cx,
+                        )
+                        .w_12(),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_color(rgb(TEXT))
+                            .child(if line.is_empty() {
+                                " ".to_string()
+                            } else {
+                                expand_tabs(&line, self.tab_width)
+                            }),
+                    )
+            }))
+    }
+
+    /// The text with its long lines wrapped to the view's width. Only the
+    /// rows in view get elements, so long files scroll smoothly.
+    fn render_wrapped_content(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let bounds = self.content_scroll_handle.bounds();
+        let mut width = f32::from(bounds.size.width);
+        let mut viewport_height = f32::from(bounds.size.height);
+        if width <= 0.0 || viewport_height <= 0.0 {
+            // Not laid out yet: guess from the window, then wrap again
+            width = f32::from(window.viewport_size().width);
+            viewport_height = f32::from(window.viewport_size().height);
+            window.request_animation_frame();
+        }
+        let scroll_top = -f32::from(self.content_scroll_handle.offset().y);
+
+        // Columns from the monospace advance at the text size, which
+        // follows the zoom through the rem size
+        let font_size = window.rem_size() * 0.875;
+        let font_id = window
+            .text_system()
+            .resolve_font(&gpui::font(MONOSPACE_FONT));
+        let advance = window
+            .text_system()
+            .advance(font_id, font_size, 'M')
+            .map_or(8.0, |size| f32::from(size.width));
+        // The padding of the scrolled element on both sides
+        let text_width = width - WRAPPED_GUTTER_WIDTH - 16.0;
+        let columns = (text_width / advance.max(1.0)).floor().max(1.0) as usize;
+
+        let tab_width = self.tab_width;
+        let key = (
+            columns,
+            tab_width,
+            self.content.as_ptr() as usize,
+            self.content.len(),
+        );
+        if self
+            .wrapped
+            .as_ref()
+            .is_none_or(|wrapped| wrapped.key != key)
+        {
+            let mut lines = Vec::new();
+            let mut start = 0;
+            for line in self.content.split_inclusive('\n') {
+                let text = line.trim_end_matches('\n').trim_end_matches('\r');
+                lines.push(start..start + text.len());
+                start += line.len();
+            }
+            let layout = WrapLayout::compute(
+                lines
+                    .iter()
+                    .map(|range| expand_tabs(&self.content[range.clone()], tab_width))
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .map(String::as_str),
+                columns,
+            );
+            self.wrapped = Some(WrappedText {
+                key,
+                layout: Rc::new(layout),
+                lines,
+            });
+        }
+        let Some(wrapped) = &self.wrapped else {
+            return div().into_any_element();
+        };
+        let layout = wrapped.layout.clone();
+        let mut scroll_top = scroll_top;
+        if let Some(line) = self.wrapped_reveal.take() {
+            let row = layout.first_row(line).saturating_sub(REVEAL_CONTEXT_LINES);
+            scroll_top = row as f32 * WRAPPED_ROW_HEIGHT;
+            self.content_scroll_handle
+                .set_offset(point(px(0.), px(-scroll_top)));
+        }
+
+        let rows = VisibleWindow::compute(
+            layout.row_count(),
+            WRAPPED_ROW_HEIGHT,
+            scroll_top,
+            viewport_height,
+        );
+        // Each line in view is wrapped once for all of its rows
+        let mut current: Option<(usize, String, Vec<Range<usize>>)> = None;
+        let mut row_elements = Vec::with_capacity(rows.range.len());
+        for row in rows.range.clone() {
+            let (line, part) = layout.line_at(row);
+            if current.as_ref().is_none_or(|(shown, _, _)| *shown != line) {
+                let text = wrapped
+                    .lines
+                    .get(line)
+                    .map(|range| expand_tabs(&self.content[range.clone()], tab_width))
+                    .unwrap_or_default();
+                let parts = soft_wrap::wrap_line(&text, columns);
+                current = Some((line, text, parts));
+            }
+            let Some((_, text, parts)) = &current else {
+                continue;
+            };
+            let shown = parts.get(part).map_or("", |range| &text[range.clone()]);
+            let shown = if part == 0 {
+                shown.to_string()
+            } else {
+                let indent = soft_wrap::hanging_indent(text, columns);
+                format!("{}{}", " ".repeat(indent), shown)
+            };
+            let line_num = line + 1;
+            let gutter = if part == 0 {
+                self.render_line_number(
+                    ("content-line", line_num),
+                    Some(line_num),
+                    format!("{}", line_num),
+                    cx,
+                )
+                .w_12()
+                .into_any_element()
+            } else {
+                div()
+                    .w_12()
+                    .flex_shrink_0()
+                    .text_right()
+                    .pr_2()
+                    .text_color(rgb(TEXT_MUTED))
+                    .child("↪")
+                    .into_any_element()
+            };
+            row_elements.push(
+                div()
+                    .h(px(WRAPPED_ROW_HEIGHT))
+                    .flex_shrink_0()
+                    .flex()
+                    .whitespace_nowrap()
+                    .overflow_hidden()
+                    .child(gutter)
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_color(rgb(TEXT))
+                            .child(if shown.is_empty() {
+                                " ".to_string()
+                            } else {
+                                shown
+                            }),
+                    ),
+            );
+        }
+
+        div()
+            .id("file-content-scroll")
+            .flex_1()
+            .overflow_y_scroll()
+            .track_scroll(&self.content_scroll_handle)
+            .bg(rgb(BG_BASE))
+            .px_2()
+            .font_family(MONOSPACE_FONT)
+            .text_sm()
+            .child(div().h(px(rows.top_padding)))
+            .children(row_elements)
+            .child(div().h(px(rows.bottom_padding)))
+            .into_any_element()
+    }
+
+    fn render_submodule_info(status: &SubmoduleStatus) -> impl IntoElement {
+        let sha = |sha: &Option<String>| {
+            sha.as_deref()
+                .map(|sha| git::short_sha(sha).to_string())
+                .unwrap_or_else(|| "none".to_string())
+        };
+        let delta = match (&status.recorded, &status.checked_out) {
+            (_, None) => "Not initialized".to_string(),
+            (recorded, checked_out) if recorded == checked_out => {
+                format!("At {}", sha(checked_out))
+            }
+            (recorded, checked_out) => format!("{} → {}", sha(recorded), sha(checked_out)),
+        };
+
+        div()
+            .id("submodule-info")
+            .flex_1()
+            .overflow_y_scroll()
+            .bg(rgb(BG_BASE))
+            .p_4()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .text_sm()
+            .child(
+                div()
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(format!("Submodule {}", status.path.display())),
+            )
+            .child(
+                div()
+                    .font_family(MONOSPACE_FONT)
+                    .text_color(rgb(TEXT))
+                    .child(delta),
+            )
+            .when(status.dirty, |el| {
+                el.child(
+                    div()
+                        .text_color(rgb(YELLOW))
+                        .child("The submodule has uncommitted changes"),
+                )
+            })
+            .when(!status.commits.is_empty(), |el| {
+                el.child(
+                    div()
+                        .pt_2()
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(format!("{} new commit(s):", status.commits.len())),
+                )
+                .children(status.commits.iter().map(|commit| {
+                    div()
+                        .flex()
+                        .gap_2()
+                        .child(
+                            div()
+                                .font_family(MONOSPACE_FONT)
+                                .text_color(rgb(TEXT_MUTED))
+                                .child(commit.short_hash().to_string()),
+                        )
+                        .child(div().text_color(rgb(TEXT)).child(commit.subject.clone()))
+                }))
+            })
+    }
+
+    fn render_binary_info(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let Some(info) = &self.binary else {
+            return div().id("binary-info");
+        };
+        let change = match info.change_type {
+            Some(ChangeType::Added) => "Added",
+            Some(ChangeType::Modified) => "Modified",
+            Some(ChangeType::Deleted) => "Deleted",
+            Some(ChangeType::Renamed) => "Renamed",
+            Some(ChangeType::Conflicted) => "Conflicted",
+            Some(ChangeType::Unknown) => "Changed",
+            None => "Unchanged",
+        };
+        let preview_path = self
+            .file_path
+            .clone()
+            .filter(|_| info.image && info.show_preview);
+        let external_path = self
+            .file_path
+            .clone()
+            .filter(|p| info.lfs.is_some() && !self.read_only && p.exists());
+
+        div()
+            .id("binary-info")
+            .flex_1()
+            .overflow_y_scroll()
+            .bg(rgb(BG_BASE))
+            .p_4()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .text_sm()
+            .child(
+                div()
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(if info.lfs.is_some() {
+                        "Git LFS file"
+                    } else if info.reason.is_some() {
+                        "File not shown"
+                    } else {
+                        "Binary file"
+                    }),
+            )
+            .child(
+                div()
+                    .font_family(MONOSPACE_FONT)
+                    .text_color(rgb(TEXT))
+                    .child(match &info.lfs {
+                        Some(_) => change.to_string(),
+                        None => format!("{} · {}", format_size(info.size), change),
+                    }),
+            )
+            .when_some(info.lfs.as_ref(), |el, lfs| {
+                el.child(
+                    div()
+                        .font_family(MONOSPACE_FONT)
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(match &lfs.head {
+                            Some(side) => format!("HEAD: {}", lfs_side(side)),
+                            None => "HEAD: no LFS pointer".to_string(),
+                        }),
+                )
+                .child(
+                    div()
+                        .font_family(MONOSPACE_FONT)
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(match (&lfs.worktree, lfs.worktree_size) {
+                            (Some(side), _) => format!("Working tree: pointer, {}", lfs_side(side)),
+                            (None, Some(size)) => {
+                                format!("Working tree: {} of content", format_size(size))
+                            }
+                            (None, None) => "Working tree: deleted".to_string(),
+                        }),
+                )
+            })
+            .when_some(external_path, |el, path| {
+                el.child(
+                    div()
+                        .id("lfs-open-externally")
+                        .px_2()
+                        .py_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .bg(rgb(BG_SURFACE0))
+                        .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                        .text_xs()
+                        .text_color(rgb(BLUE))
+                        .on_click(cx.listener(move |_this, _, _, cx| {
+                            cx.emit(OpenExternalEvent(path.clone(), None));
+                        }))
+                        .child("Open externally"),
+                )
+            })
+            .when_some(info.reason.clone(), |el, reason| {
+                el.child(div().text_color(rgb(YELLOW)).child(reason))
+            })
+            .when(info.image, |el| {
+                el.child(
+                    div()
+                        .id("toggle-image-preview")
+                        .px_2()
+                        .py_1()
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .bg(rgb(BG_SURFACE0))
+                        .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                        .text_xs()
+                        .text_color(rgb(BLUE))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.toggle_image_preview();
+                            cx.notify();
+                        }))
+                        .child(if info.show_preview {
+                            "Hide preview"
+                        } else {
+                            "Show preview"
+                        }),
+                )
+            })
+            .when_some(preview_path, |el, path| {
+                el.child(
+                    img(path)
+                        .max_w(px(480.))
+                        .max_h(px(480.))
+                        .object_fit(ObjectFit::Contain),
+                )
+            })
+    }
+
+    /// Note shown above a file too large to read whole
+    /// Placeholder for a diff being computed, or the quick count of one
+    /// skipped as too large with a button to compute it anyway
+    fn render_pending_diff(
+        &self,
+        pending: PendingDiff,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let panel = div()
+            .id("pending-diff")
+            .flex_1()
+            .bg(rgb(BG_BASE))
+            .p_4()
+            .flex()
+            .flex_col()
+            .gap_2()
+            .text_sm();
+        let (added, removed, max_size) = match pending {
+            PendingDiff::Loading => {
+                return panel.child(div().text_color(rgb(TEXT_MUTED)).child("Computing diff…"));
+            }
+            PendingDiff::TooLarge {
+                added,
+                removed,
+                max_size,
+            } => (added, removed, max_size),
+        };
+        let path = self.file_path.clone();
+
+        panel
+            .child(div().text_color(rgb(TEXT_SECONDARY)).child(format!(
+                "Files differ, +{} −{} lines by quick count",
+                added, removed
+            )))
+            .child(div().text_color(rgb(TEXT_MUTED)).child(format!(
+                "The diff is not computed for files over {} ({}).",
+                format_size(max_size),
+                git::CONFIG_DIFF_MAX_SIZE
+            )))
+            .child(
+                div()
+                    .id("force-full-diff")
+                    .px_2()
+                    .py_1()
+                    .cursor_pointer()
+                    .rounded_sm()
+                    .bg(rgb(BG_SURFACE0))
+                    .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                    .text_xs()
+                    .text_color(rgb(BLUE))
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        if let Some(path) = path.clone() {
+                            this.pending_diff = Some(PendingDiff::Loading);
+                            cx.emit(ForceFullDiffEvent(path));
+                            cx.notify();
+                        }
+                    }))
+                    .child("Show full diff"),
+            )
+    }
 
-        (left_lines, right_lines)
+    fn render_large_file_banner(large: &LargeText) -> impl IntoElement {
+        let lines = match &large.index {
+            Some(Ok(index)) => format!(", {} lines", index.line_count()),
+            _ => String::new(),
+        };
+        div()
+            .px_3()
+            .py_1()
+            .flex_shrink_0()
+            .bg(rgb(BG_SURFACE0))
+            .text_xs()
+            .text_color(rgb(YELLOW))
+            .child(format!(
+                "Read-only: {}{} is over the {} limit ({}), so lines are read from disk \
+                 as you scroll and no diff is shown",
+                format_size(large.size),
+                lines,
+                format_size(large.max_size),
+                git::CONFIG_VIEWER_MAX_FILE_SIZE
+            ))
     }
 
-    pub(crate) fn parse_hunk_header(line: &str) -> Option<(usize, usize)> {
-        let parts: Vec<&str> = line.split(' ').collect();
-        if parts.len() >= 3 {
-            let old_part = parts[1].trim_start_matches('-');
-            let new_part = parts[2].trim_start_matches('+');
-            let old_start = old_part.split(',').next()?.parse().ok()?;
-            let new_start = new_part.split(',').next()?.parse().ok()?;
-            Some((old_start, new_start))
-        } else {
-            None
+    /// Conflicts left with previous/next arrows, or once none are left an
+    /// offer to stage the file; a warning when the markers could not be read
+    fn render_conflict_banner(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let banner = || {
+            div()
+                .px_3()
+                .py_1()
+                .flex_shrink_0()
+                .flex()
+                .items_center()
+                .gap_2()
+                .bg(rgb(BG_SURFACE0))
+                .text_xs()
+        };
+        let button = |id: &'static str, label: &'static str, color: u32| {
+            div()
+                .id(id)
+                .px_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_color(rgb(color))
+                .child(label)
+        };
+
+        if let Some(warning) = &self.conflict_warning {
+            return Some(
+                banner()
+                    .text_color(rgb(YELLOW))
+                    .child(warning.clone())
+                    .into_any_element(),
+            );
+        }
+        let count = self.conflicts.as_ref()?.conflict_count();
+        if count == 0 {
+            let path = self.file_path.clone()?;
+            return Some(
+                banner()
+                    .child(
+                        div()
+                            .text_color(rgb(GREEN))
+                            .child("No conflict markers left"),
+                    )
+                    .when(!self.read_only, |el| {
+                        el.child(
+                            button("mark-resolved", "Mark resolved (git add)", TEAL).on_click(
+                                cx.listener(move |_this, _, _, cx| {
+                                    cx.emit(MarkResolvedEvent(path.clone()));
+                                }),
+                            ),
+                        )
+                    })
+                    .into_any_element(),
+            );
         }
+        Some(
+            banner()
+                .child(div().text_color(rgb(MAROON)).child(format!(
+                    "Conflict {} of {}",
+                    self.current_conflict.min(count - 1) + 1,
+                    count
+                )))
+                .child(
+                    button("previous-conflict", "◀ Previous", TEXT_SECONDARY)
+                        .on_click(cx.listener(|this, _, _, cx| this.step_conflict(false, cx))),
+                )
+                .child(
+                    button("next-conflict", "Next ▶", TEXT_SECONDARY)
+                        .on_click(cx.listener(|this, _, _, cx| this.step_conflict(true, cx))),
+                )
+                .into_any_element(),
+        )
     }
 
-    fn compute_added_line_numbers(&self) -> std::collections::HashSet<usize> {
-        let diff = self.diff_content.as_deref().unwrap_or("");
-        let mut added_lines = std::collections::HashSet::new();
-        let mut new_line_num = 1usize;
+    /// A conflicted file: plain text as it is, each conflict as its marker
+    /// lines and sides (ours in the added colors, theirs in the removed
+    /// ones) under a header with the actions that resolve it
+    fn render_conflicts(&self, conflicts: &ConflictFile, cx: &mut Context<Self>) -> AnyElement {
+        let tab_width = self.tab_width;
+        let row = |number: usize, line: &str, bg: Option<u32>, color: u32| {
+            let line = line.trim_end_matches(['\n', '\r']);
+            div()
+                .flex()
+                .flex_shrink_0()
+                .when_some(bg, |el, bg| el.bg(rgb(bg)))
+                .child(
+                    div()
+                        .w_12()
+                        .flex_shrink_0()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child(number.to_string()),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .text_color(rgb(color))
+                        .child(if line.is_empty() {
+                            " ".to_string()
+                        } else {
+                            expand_tabs(line, tab_width)
+                        }),
+                )
+                .into_any_element()
+        };
 
-        for line in diff.lines() {
-            if line.starts_with("@@") {
-                if let Some((_, new_start)) = Self::parse_hunk_header(line) {
-                    new_line_num = new_start;
+        let count = conflicts.conflict_count();
+        let mut rows = Vec::new();
+        // Line numbers are those of the file on disk, markers included
+        let mut number = 0;
+        let mut conflict = 0;
+        for segment in conflicts.segments() {
+            let hunk = match segment {
+                Segment::Text(lines) => {
+                    for line in lines {
+                        number += 1;
+                        rows.push(row(number, line.as_str(), None, TEXT));
+                    }
+                    continue;
                 }
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                added_lines.insert(new_line_num);
-                new_line_num += 1;
-            } else if line.starts_with('-') && !line.starts_with("---") {
-            } else if line.starts_with(' ') || (!line.starts_with('@') && !line.is_empty()) {
-                new_line_num += 1;
+                Segment::Conflict(hunk) => hunk,
+            };
+            rows.push(self.render_conflict_header(conflict, count, hunk, cx));
+            let marker = |text: String| (text.trim_end().to_string(), BG_SURFACE0, TEXT_SECONDARY);
+            let mut lines = vec![marker(format!("<<<<<<< {}", hunk.ours_label))];
+            lines.extend(hunk.ours.iter().map(|l| (l.clone(), DIFF_ADDED_BG, GREEN)));
+            if let Some((label, base)) = &hunk.base {
+                lines.push(marker(format!("||||||| {}", label)));
+                lines.extend(base.iter().map(|l| (l.clone(), BG_MANTLE, TEXT_MUTED)));
+            }
+            lines.push(marker("=======".to_string()));
+            lines.extend(
+                hunk.theirs
+                    .iter()
+                    .map(|l| (l.clone(), DIFF_REMOVED_BG, RED)),
+            );
+            lines.push(marker(format!(">>>>>>> {}", hunk.theirs_label)));
+            for (line, bg, color) in lines {
+                number += 1;
+                rows.push(row(number, &line, Some(bg), color));
             }
+            conflict += 1;
         }
 
-        added_lines
+        // Rows are direct children of the scrolled element, so the arrows
+        // can scroll to a conflict's header by its index
+        div()
+            .id("file-conflicts-scroll")
+            .flex_1()
+            .flex()
+            .flex_col()
+            .overflow_y_scroll()
+            .track_scroll(&self.content_scroll_handle)
+            .bg(rgb(BG_BASE))
+            .p_2()
+            .font_family(MONOSPACE_FONT)
+            .text_sm()
+            .children(rows)
+            .into_any_element()
     }
 
-    fn render_toolbar(&self, cx: &mut Context<Self>) -> impl IntoElement {
-        let file_name = self
-            .file_path
-            .as_ref()
-            .and_then(|p| p.file_name())
-            .and_then(|n| n.to_str())
-            .unwrap_or("No file");
-
-        let mode = self.mode;
-        let has_diff = self.diff_content.is_some();
+    /// "Conflict 2 of 3" above a conflict, with its actions unless the file
+    /// is read-only
+    fn render_conflict_header(
+        &self,
+        index: usize,
+        count: usize,
+        hunk: &ConflictHunk,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let action = |id: &'static str, label: String, resolution: Resolution, color: u32| {
+            div()
+                .id((id, index))
+                .px_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE0))
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_color(rgb(color))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.resolve_conflict(index, resolution, cx);
+                }))
+                .child(label)
+        };
+        let side_label = |side: &str, label: &str| {
+            if label.is_empty() {
+                format!("Take {}", side)
+            } else {
+                format!("Take {} ({})", side, label)
+            }
+        };
 
         div()
-            .h_8()
-            .px_3()
+            .mt_2()
+            .py_1()
             .flex()
+            .flex_shrink_0()
             .items_center()
-            .justify_between()
-            .bg(rgb(BG_BASE))
-            .border_b_1()
-            .border_color(rgb(BG_SURFACE0))
-            .child(
-                div()
-                    .text_sm()
-                    .text_color(rgb(TEXT))
-                    .child(file_name.to_string()),
-            )
-            .child(
-                div()
+            .gap_2()
+            .text_xs()
+            .child(div().text_color(rgb(MAROON)).child(format!(
+                "Conflict {} of {}",
+                index + 1,
+                count
+            )))
+            .when(!self.read_only, |el| {
+                el.child(action(
+                    "take-ours",
+                    side_label("ours", &hunk.ours_label),
+                    Resolution::Ours,
+                    GREEN,
+                ))
+                .child(action(
+                    "take-theirs",
+                    side_label("theirs", &hunk.theirs_label),
+                    Resolution::Theirs,
+                    RED,
+                ))
+                .child(action(
+                    "keep-both",
+                    "Keep both".to_string(),
+                    Resolution::Both,
+                    BLUE,
+                ))
+            })
+            .into_any_element()
+    }
+
+    /// Text over the size limit: only the lines around the viewport are read
+    fn render_large_text(&mut self, window: &Window) -> AnyElement {
+        let mut viewport_height = f32::from(self.content_scroll_handle.bounds().size.height);
+        if viewport_height <= 0.0 {
+            viewport_height = f32::from(window.viewport_size().height);
+        }
+        let scroll_top = -f32::from(self.content_scroll_handle.offset().y);
+        let tab_width = self.tab_width;
+        let (Some(path), Some(large)) = (&self.file_path, &mut self.large) else {
+            return div().into_any_element();
+        };
+        let index = match &large.index {
+            Some(Ok(index)) => index.clone(),
+            status => {
+                let message = match status {
+                    Some(Err(e)) => format!("Failed to index lines: {}", e),
+                    _ => "Indexing lines…".to_string(),
+                };
+                return div()
+                    .flex_1()
                     .flex()
                     .items_center()
-                    .gap_1()
-                    .when(has_diff && self.is_diff_mode(), |el| {
-                        el.child(
-                            div()
-                                .id("toggle-diff-display")
-                                .px_2()
-                                .py_1()
-                                .cursor_pointer()
-                                .rounded_sm()
-                                .bg(rgb(BG_SURFACE0))
-                                .hover(|d| d.bg(rgb(BG_SURFACE1)))
-                                .text_xs()
-                                .text_color(rgb(MAUVE))
-                                .on_click(cx.listener(|this, _, _, cx| {
-                                    this.toggle_diff_display_mode();
-                                    cx.notify();
-                                }))
-                                .child(if mode == FileViewMode::DiffSplit {
-                                    "Inline"
-                                } else {
-                                    "Split"
-                                }),
-                        )
-                    })
-                    .child(
-                        div()
-                            .id("close-file")
-                            .px_2()
-                            .py_1()
-                            .cursor_pointer()
-                            .text_xs()
-                            .text_color(rgb(TEXT_MUTED))
-                            .hover(|el| el.text_color(rgb(RED)))
-                            .on_click(cx.listener(|this, _, _, cx| {
-                                this.close();
-                                cx.notify();
-                            }))
-                            .child("Close"),
-                    ),
-            )
-    }
+                    .justify_center()
+                    .text_color(rgb(TEXT_MUTED))
+                    .child(message)
+                    .into_any_element();
+            }
+        };
 
-    fn render_content(&self, cx: &mut Context<Self>) -> impl IntoElement {
-        let lines: Vec<String> = self.content.lines().map(|s| s.to_string()).collect();
-        let file_path = self.file_path.clone();
+        let rows = VisibleWindow::compute(
+            index.line_count(),
+            LARGE_ROW_HEIGHT,
+            scroll_top,
+            viewport_height,
+        );
+        large.load(path, &index, &rows.range);
 
         div()
-            .id("file-content-scroll")
+            .id("large-file-scroll")
             .flex_1()
             .overflow_y_scroll()
+            .track_scroll(&self.content_scroll_handle)
             .bg(rgb(BG_BASE))
-            .p_2()
-            .child(
+            .px_2()
+            .font_family(MONOSPACE_FONT)
+            .text_sm()
+            .child(div().h(px(rows.top_padding)))
+            .children(rows.range.clone().map(|line| {
                 div()
+                    .h(px(LARGE_ROW_HEIGHT))
+                    .flex_shrink_0()
                     .flex()
-                    .flex_col()
-                    .font_family(MONOSPACE_FONT)
-                    .text_sm()
-                    .children(lines.into_iter().enumerate().map(|(num, line)| {
-                        let line_num = num + 1;
-                        let path_for_click = file_path.clone();
-
+                    .whitespace_nowrap()
+                    .overflow_hidden()
+                    .child(
                         div()
-                            .flex()
-                            .child(
-                                div()
-                                    .id(("content-line", line_num))
-                                    .w_12()
-                                    .flex_shrink_0()
-                                    .text_right()
-                                    .pr_2()
-                                    .text_color(rgb(TEXT_MUTED))
-                                    .cursor_pointer()
-                                    .hover(|el| el.text_color(rgb(BLUE)))
-                                    .on_mouse_down(
-                                        MouseButton::Right,
-                                        cx.listener(move |_this, _, _, cx| {
-                                            if let Some(ref path) = path_for_click {
-                                                let text = format!(
-                                                    "`{}:{}`",
-                                                    path.to_string_lossy(),
-                                                    line_num
-                                                );
-                                                cx.emit(SendToTerminalEvent(text));
-                                            }
-                                        }),
-                                    )
-                                    .child(format!("{}", line_num)),
-                            )
-                            .child(
-                                div()
-                                    .flex_1()
-                                    .text_color(rgb(TEXT))
-                                    .child(if line.is_empty() {
-                                        " ".to_string()
-                                    } else {
-                                        line
-                                    }),
-                            )
-                    })),
-            )
+                            .w_16()
+                            .flex_shrink_0()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(format!("{}", line + 1)),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_color(rgb(TEXT))
+                            .child(expand_tabs(large.line(line), tab_width)),
+                    )
+            }))
+            .child(div().h(px(rows.bottom_padding)))
+            .into_any_element()
     }
 
     fn render_inline_diff(&self, cx: &mut Context<Self>) -> impl IntoElement {
         let lines = self.parse_diff_for_inline_view();
-        let file_path = self.file_path.clone();
 
         div()
             .id("inline-diff-scroll")
@@ -514,12 +3239,20 @@ impl FileView {
                     .font_family(MONOSPACE_FONT)
                     .text_sm()
                     .children(lines.into_iter().enumerate().map(|(idx, line)| {
+                        if let (InlineChangeType::HunkHeader, Some(hunk)) =
+                            (line.change_type, line.hunk)
+                        {
+                            return Self::render_hunk_header(hunk, &line.content, true, cx);
+                        }
+
                         let (bg_color, text_color, opacity) = match line.change_type {
                             InlineChangeType::Added => (Some(rgb(DIFF_ADDED_BG)), rgb(GREEN), 1.0),
                             InlineChangeType::Deleted => {
                                 (Some(rgb(DIFF_REMOVED_BG)), rgb(RED), 0.6)
                             }
-                            InlineChangeType::Unchanged => (None, rgb(TEXT), 1.0),
+                            InlineChangeType::Unchanged | InlineChangeType::HunkHeader => {
+                                (None, rgb(TEXT), 1.0)
+                            }
                         };
 
                         let line_num_str = line
@@ -530,40 +3263,38 @@ impl FileView {
                         let prefix = match line.change_type {
                             InlineChangeType::Added => "+",
                             InlineChangeType::Deleted => "-",
-                            InlineChangeType::Unchanged => " ",
+                            InlineChangeType::Unchanged | InlineChangeType::HunkHeader => " ",
                         };
 
-                        let path_for_click = file_path.clone();
-                        let line_num_for_click = line.line_num;
+                        let content = match line.change_type {
+                            InlineChangeType::Added | InlineChangeType::Deleted => {
+                                show_whitespace(&line.content, self.tab_width)
+                            }
+                            InlineChangeType::Unchanged | InlineChangeType::HunkHeader => {
+                                expand_tabs(line.content.trim_end_matches('\r'), self.tab_width)
+                            }
+                        };
 
-                        div()
+                        let row = div()
                             .flex()
                             .when_some(bg_color, |el, color| el.bg(color))
+                            .when(line.whitespace, |el| {
+                                el.border_l_2().border_color(rgb(BG_SURFACE2))
+                            })
                             .opacity(opacity)
+                            .child(self.render_comment_gutter(
+                                ("inline-diff-comment", idx),
+                                line.line_num,
+                                cx,
+                            ))
                             .child(
-                                div()
-                                    .id(("inline-diff-line", idx))
-                                    .w_12()
-                                    .flex_shrink_0()
-                                    .text_right()
-                                    .pr_2()
-                                    .text_color(rgb(TEXT_MUTED))
-                                    .when(line_num_for_click.is_some(), |el| {
-                                        el.cursor_pointer().hover(|el| el.text_color(rgb(BLUE)))
-                                    })
-                                    .on_mouse_down(
-                                        MouseButton::Right,
-                                        cx.listener(move |_this, _, _, cx| {
-                                            if let (Some(path), Some(num)) =
-                                                (&path_for_click, line_num_for_click)
-                                            {
-                                                let text =
-                                                    format!("`{}:{}`", path.to_string_lossy(), num);
-                                                cx.emit(SendToTerminalEvent(text));
-                                            }
-                                        }),
-                                    )
-                                    .child(line_num_str),
+                                self.render_line_number(
+                                    ("inline-diff-line", idx),
+                                    line.line_num,
+                                    line_num_str,
+                                    cx,
+                                )
+                                .w_12(),
                             )
                             .child(
                                 div()
@@ -573,12 +3304,21 @@ impl FileView {
                                     .child(prefix),
                             )
                             .child(div().flex_1().text_color(text_color).child(
-                                if line.content.is_empty() {
+                                if content.is_empty() {
                                     " ".to_string()
                                 } else {
-                                    line.content
+                                    content
                                 },
-                            ))
+                            ));
+                        match self.render_comment_blocks(line.line_num, false, cx) {
+                            Some(blocks) => div()
+                                .flex()
+                                .flex_col()
+                                .child(row)
+                                .child(blocks)
+                                .into_any_element(),
+                            None => row.into_any_element(),
+                        }
                     })),
             )
     }
@@ -635,8 +3375,9 @@ impl FileView {
                         div()
                             .id("diff-scroll-left")
                             .flex_1()
-                            .overflow_y_scroll()
+                            .overflow_scroll()
                             .track_scroll(&scroll_handle)
+                            .on_scroll_wheel(cx.listener(Self::handle_diff_shift_scroll))
                             .pl_2()
                             .py_2()
                             .font_family(MONOSPACE_FONT)
@@ -644,7 +3385,8 @@ impl FileView {
                             .children(
                                 left_lines
                                     .iter()
-                                    .map(|line| Self::render_diff_line(line, true)),
+                                    .enumerate()
+                                    .map(|(idx, line)| self.render_diff_line(idx, line, true, cx)),
                             ),
                     ),
             )
@@ -674,8 +3416,9 @@ impl FileView {
                         div()
                             .id("diff-scroll-right")
                             .flex_1()
-                            .overflow_y_scroll()
+                            .overflow_scroll()
                             .track_scroll(&scroll_handle)
+                            .on_scroll_wheel(cx.listener(Self::handle_diff_shift_scroll))
                             .pr_2()
                             .py_2()
                             .font_family(MONOSPACE_FONT)
@@ -683,17 +3426,53 @@ impl FileView {
                             .children(
                                 right_lines
                                     .iter()
-                                    .map(|line| Self::render_diff_line(line, false)),
+                                    .enumerate()
+                                    .map(|(idx, line)| self.render_diff_line(idx, line, false, cx)),
                             ),
                     ),
             )
     }
 
-    fn render_diff_line(line: &SplitDiffLine, is_left: bool) -> impl IntoElement {
+    /// Note shown above a diff in which only line endings changed
+    fn render_line_ending_banner(&self) -> impl IntoElement {
+        let stats = &self.diff_stats;
+        let direction = if stats.crlf_to_lf {
+            "CRLF → LF"
+        } else {
+            "LF → CRLF or mixed"
+        };
+        let lines = stats.added.max(stats.removed);
+        div()
+            .px_3()
+            .py_1()
+            .flex_shrink_0()
+            .bg(rgb(BG_SURFACE0))
+            .text_xs()
+            .text_color(rgb(YELLOW))
+            .child(format!(
+                "Only line endings changed ({}) on {} line{}",
+                direction,
+                lines,
+                if lines == 1 { "" } else { "s" }
+            ))
+    }
+
+    fn render_diff_line(
+        &self,
+        idx: usize,
+        line: &SplitDiffLine,
+        is_left: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        if let (DiffLineType::HunkHeader, Some(hunk)) = (line.line_type, line.hunk) {
+            // The action sits on the "After" side, which is what gets reverted
+            return Self::render_hunk_header(hunk, &line.content, !is_left, cx);
+        }
+
         let (bg_color, text_color) = match line.line_type {
             DiffLineType::Added => (Some(rgb(DIFF_ADDED_BG)), rgb(GREEN)),
             DiffLineType::Removed => (Some(rgb(DIFF_REMOVED_BG)), rgb(RED)),
-            DiffLineType::Context => (None, rgb(TEXT)),
+            DiffLineType::Context | DiffLineType::HunkHeader => (None, rgb(TEXT)),
         };
 
         let line_num = if is_left {
@@ -701,33 +3480,91 @@ impl FileView {
         } else {
             line.new_line_num
         };
+        let label = line_num.map(|n| n.to_string()).unwrap_or_default();
 
-        let content = if line.content.is_empty() {
+        let content = match line.line_type {
+            DiffLineType::Added | DiffLineType::Removed => {
+                show_whitespace(&line.content, self.tab_width)
+            }
+            DiffLineType::Context | DiffLineType::HunkHeader => {
+                expand_tabs(line.content.trim_end_matches('\r'), self.tab_width)
+            }
+        };
+        let content = if content.is_empty() {
             " ".to_string()
         } else {
-            line.content.clone()
+            content
         };
 
-        div()
+        let row = div()
             .flex()
             .flex_row()
             .when_some(bg_color, |el, color| el.bg(color))
-            .child(
+            .when(line.whitespace, |el| {
+                el.border_l_2().border_color(rgb(BG_SURFACE2))
+            })
+            // Selections and comments are on lines of the working tree file,
+            // so only the "After" side takes part
+            .child(if is_left {
                 div()
                     .w_10()
                     .flex_shrink_0()
                     .text_right()
                     .pr_2()
                     .text_color(rgb(TEXT_MUTED))
-                    .child(line_num.map(|n| n.to_string()).unwrap_or_default()),
-            )
+                    .child(label)
+                    .into_any_element()
+            } else {
+                div()
+                    .flex()
+                    .flex_shrink_0()
+                    .child(self.render_comment_gutter(("diff-comment-right", idx), line_num, cx))
+                    .child(
+                        self.render_line_number(("diff-line-right", idx), line_num, label, cx)
+                            .w_10(),
+                    )
+                    .into_any_element()
+            })
             .child(
                 div()
                     .flex_1()
-                    .min_w_0()
+                    .whitespace_nowrap()
                     .text_color(text_color)
                     .child(content),
-            )
+            );
+
+        let new_line_num = self
+            .cached_right_lines
+            .get(idx)
+            .and_then(|right| right.new_line_num);
+        match self.render_comment_blocks(new_line_num, is_left, cx) {
+            Some(blocks) => div()
+                .flex()
+                .flex_col()
+                .child(row)
+                .child(blocks)
+                .into_any_element(),
+            None => row.into_any_element(),
+        }
+    }
+
+    /// Shift+wheel scrolls the split diff sideways. Both columns share one
+    /// scroll handle, so they move together; the column's own scroll listener
+    /// runs first and has already moved it vertically, which is undone here.
+    fn handle_diff_shift_scroll(
+        &mut self,
+        event: &ScrollWheelEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let delta = event.delta.pixel_delta(window.line_height());
+        if !event.modifiers.shift || delta.x != Pixels::ZERO {
+            return;
+        }
+        let offset = self.diff_scroll_handle.offset();
+        self.diff_scroll_handle
+            .set_offset(point(offset.x + delta.y, offset.y - delta.y));
+        cx.notify();
     }
 
     fn render_diff_resize_handle(&self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -776,16 +3613,48 @@ impl Focusable for FileView {
 }
 
 impl EventEmitter<SendToTerminalEvent> for FileView {}
+impl EventEmitter<OpenExternalEvent> for FileView {}
+impl EventEmitter<DiscardHunkEvent> for FileView {}
+impl EventEmitter<ConvertToUtf8Event> for FileView {}
+impl EventEmitter<OpenRepositoryEvent> for FileView {}
+impl EventEmitter<RevealInSidebarEvent> for FileView {}
+impl EventEmitter<InsertReferenceEvent> for FileView {}
+impl EventEmitter<NavigateFileEvent> for FileView {}
+impl EventEmitter<InsertSnippetEvent> for FileView {}
+impl EventEmitter<ForceFullDiffEvent> for FileView {}
+impl EventEmitter<ResolveConflictEvent> for FileView {}
+impl EventEmitter<MarkResolvedEvent> for FileView {}
+impl EventEmitter<CommentLineEvent> for FileView {}
+impl EventEmitter<EditCommentEvent> for FileView {}
+impl EventEmitter<DeleteCommentEvent> for FileView {}
+impl EventEmitter<CompareWithSessionEvent> for FileView {}
+impl EventEmitter<SwapComparisonEvent> for FileView {}
 
 impl Render for FileView {
-    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.apply_pending_reveal();
         let has_file = self.file_path.is_some();
 
-        let content_element = if has_file {
+        let content_element = if self.image.as_ref().is_some_and(|image| !image.show_source) {
+            self.render_image_preview(window, cx)
+        } else if let Some(status) = &self.submodule {
+            Self::render_submodule_info(status).into_any_element()
+        } else if self.binary.is_some() {
+            self.render_binary_info(cx).into_any_element()
+        } else if self.large.is_some() {
+            self.render_large_text(window)
+        } else if let Some(pending) = self.pending_diff {
+            self.render_pending_diff(pending, cx).into_any_element()
+        } else if has_file {
             match self.mode {
+                FileViewMode::Content if self.wraps() => self.render_wrapped_content(window, cx),
                 FileViewMode::Content => self.render_content(cx).into_any_element(),
                 FileViewMode::DiffSplit => self.render_diff(cx).into_any_element(),
                 FileViewMode::DiffInline => self.render_inline_diff(cx).into_any_element(),
+                FileViewMode::Conflicts => match &self.conflicts {
+                    Some(conflicts) => self.render_conflicts(conflicts, cx).into_any_element(),
+                    None => self.render_content(cx).into_any_element(),
+                },
             }
         } else {
             div()
@@ -798,6 +3667,13 @@ impl Render for FileView {
                 .into_any_element()
         };
 
+        let line_actions = self.render_line_actions(cx);
+        let line_ending_banner =
+            (has_file && self.is_diff_mode() && self.diff_stats.line_ending_only)
+                .then(|| self.render_line_ending_banner());
+        let large_file_banner = self.large.as_ref().map(Self::render_large_file_banner);
+        let conflict_banner = self.render_conflict_banner(cx);
+
         div()
             .id("file-view")
             .flex()
@@ -805,7 +3681,19 @@ impl Render for FileView {
             .size_full()
             .bg(rgb(BG_BASE))
             .when(has_file, |el| el.child(self.render_toolbar(cx)))
-            .child(content_element)
+            .child(
+                div()
+                    .relative()
+                    .flex_1()
+                    .min_h_0()
+                    .flex()
+                    .flex_col()
+                    .children(line_ending_banner)
+                    .children(large_file_banner)
+                    .children(conflict_banner)
+                    .child(content_element)
+                    .children(line_actions),
+            )
     }
 }
 
@@ -859,6 +3747,28 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    // ===== hunk_patch tests =====
+
+    const TWO_HUNK_DIFF: &str = "diff --git a/f.txt b/f.txt\nindex 1..2 100644\n--- a/f.txt\n+++ b/f.txt\n@@ -1,2 +1,2 @@\n-a\n+A\n b\n@@ -9,2 +9,2 @@\n y\n-z\n+Z\n\\ No newline at end of file";
+
+    #[test]
+    fn test_hunk_patch_keeps_headers_and_one_hunk() {
+        let header = "diff --git a/f.txt b/f.txt\nindex 1..2 100644\n--- a/f.txt\n+++ b/f.txt\n";
+        assert_eq!(
+            FileView::hunk_patch(TWO_HUNK_DIFF, 0),
+            Some(format!("{}@@ -1,2 +1,2 @@\n-a\n+A\n b\n", header))
+        );
+        assert_eq!(
+            FileView::hunk_patch(TWO_HUNK_DIFF, 1),
+            Some(format!(
+                "{}@@ -9,2 +9,2 @@\n y\n-z\n+Z\n\\ No newline at end of file\n",
+                header
+            ))
+        );
+        assert_eq!(FileView::hunk_patch(TWO_HUNK_DIFF, 2), None);
+        assert_eq!(FileView::hunk_patch("", 0), None);
+    }
+
     // ===== DiffLineType tests =====
 
     #[test]
@@ -984,4 +3894,47 @@ mod tests {
         assert!(added.contains(&2));
         assert!(added.contains(&6));
     }
+
+    // ===== Line quick actions =====
+
+    #[test]
+    fn test_line_selection_click_and_shift_click() {
+        let selected = LineSelection::click(None, 12, false);
+        assert_eq!(selected.map(|s| s.range()), Some(12..=12));
+
+        // Shift+click extends from the anchor in either direction
+        let extended = LineSelection::click(selected, 15, true);
+        assert_eq!(extended.map(|s| s.range()), Some(12..=15));
+        let extended = LineSelection::click(extended, 9, true);
+        assert_eq!(extended.map(|s| s.range()), Some(9..=12));
+
+        // A plain click starts over; clicking the only selected line clears it
+        let single = LineSelection::click(extended, 20, false);
+        assert_eq!(single.map(|s| s.range()), Some(20..=20));
+        assert_eq!(LineSelection::click(single, 20, false), None);
+    }
+
+    #[test]
+    fn test_format_line_reference() {
+        assert_eq!(
+            format_line_reference("src/foo.rs", &(87..=87)),
+            "src/foo.rs:87"
+        );
+        assert_eq!(
+            format_line_reference("src/foo.rs", &(3..=9)),
+            "src/foo.rs:3-9"
+        );
+    }
+
+    #[test]
+    fn test_format_snippet_fence_outlasts_backticks() {
+        assert_eq!(
+            format_snippet("src/lib.rs", "fn a() {}\n"),
+            "```src/lib.rs\nfn a() {}\n```\n"
+        );
+        assert_eq!(
+            format_snippet("README.md", "````\nx\n"),
+            "`````README.md\n````\nx\n`````\n"
+        );
+    }
 }
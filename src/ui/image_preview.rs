@@ -0,0 +1,416 @@
+//! Image preview in the file view
+//!
+//! PNG, JPEG, GIF and WebP files open as the image instead of the binary
+//! info panel; SVG files, which are text, open rendered with a toggle back to
+//! the source. The image is fit into the view unless zoomed (Ctrl+wheel, or
+//! 1:1 in the toolbar). A changed image is laid over its HEAD version with a
+//! slider between the two.
+//!
+//! gpui caches images by path, so the file is shown from a copy in the temp
+//! directory named after its size and mtime; a changed file gets a new copy.
+//! Images with a side longer than `sashiki.viewer.maxImageSize` are copied
+//! downscaled. A file that cannot be decoded keeps the binary info panel.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Extensions shown as images (SVG is text and opens as source first)
+const EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg"];
+
+/// Size of an SVG that declares neither width, height nor viewBox (the
+/// browser default for replaced elements)
+const SVG_DEFAULT_SIZE: (u32, u32) = (300, 150);
+
+/// Zoom change per wheel line
+pub const ZOOM_STEP: f32 = 1.1;
+
+pub const MIN_ZOOM: f32 = 0.05;
+pub const MAX_ZOOM: f32 = 32.0;
+
+/// Whether the file view previews `path` as an image
+pub fn is_previewable(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| EXTENSIONS.contains(&ext.as_str()))
+}
+
+pub fn is_svg(path: &Path) -> bool {
+    extension(path).as_deref() == Some("svg")
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// How the image is scaled
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Zoom {
+    /// Shrunk to fit the view, never enlarged
+    #[default]
+    Fit,
+    /// Fixed scale, 1.0 = one image pixel per logical pixel
+    Scale(f32),
+}
+
+impl Zoom {
+    /// Scale of an image of `size` shown in a view of `viewport`
+    pub fn scale(self, size: (u32, u32), viewport: (f32, f32)) -> f32 {
+        match self {
+            Zoom::Fit => fit_scale(size, viewport),
+            Zoom::Scale(scale) => scale,
+        }
+    }
+}
+
+/// Scale that fits `size` into `viewport` (1.0 for images that fit already,
+/// or before the viewport is known)
+pub fn fit_scale(size: (u32, u32), viewport: (f32, f32)) -> f32 {
+    let (width, height) = (size.0 as f32, size.1 as f32);
+    if width <= 0.0 || height <= 0.0 || viewport.0 <= 0.0 || viewport.1 <= 0.0 {
+        return 1.0;
+    }
+    (viewport.0 / width).min(viewport.1 / height).min(1.0)
+}
+
+/// `scale` zoomed in by `lines` wheel lines (out when negative)
+pub fn zoom_by(scale: f32, lines: f32) -> f32 {
+    (scale * ZOOM_STEP.powf(lines)).clamp(MIN_ZOOM, MAX_ZOOM)
+}
+
+/// Size of an image of `size` shrunk to `max_side` pixels on its longest
+/// side, or None when it is not larger than that
+pub fn downscaled_size(size: (u32, u32), max_side: u32) -> Option<(u32, u32)> {
+    let longest = size.0.max(size.1);
+    if longest <= max_side {
+        return None;
+    }
+    let scale = max_side as f64 / longest as f64;
+    let side = |n: u32| ((n as f64 * scale).round() as u32).max(1);
+    Some((side(size.0), side(size.1)))
+}
+
+/// Size an SVG declares through `width`/`height` (in pixels) or its
+/// `viewBox`; None when it declares neither
+pub fn svg_dimensions(source: &str) -> Option<(u32, u32)> {
+    let start = source.find("<svg")?;
+    let tag = &source[start..start + source[start..].find('>')?];
+    let length = |name| attribute(tag, name).and_then(parse_length);
+    let view_box = attribute(tag, "viewBox").and_then(|value| {
+        let numbers: Vec<f32> = value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+            .filter_map(|n| n.parse().ok())
+            .collect();
+        match numbers[..] {
+            [_, _, width, height] if width > 0.0 && height > 0.0 => Some((width, height)),
+            _ => None,
+        }
+    });
+    let (width, height) = match (length("width"), length("height"), view_box) {
+        (Some(width), Some(height), _) => (width, height),
+        (Some(width), None, Some((vw, vh))) => (width, width * vh / vw),
+        (None, Some(height), Some((vw, vh))) => (height * vw / vh, height),
+        (None, None, view_box) => view_box?,
+        _ => return None,
+    };
+    Some((
+        (width.round() as u32).max(1),
+        (height.round() as u32).max(1),
+    ))
+}
+
+/// Value of attribute `name` in the start tag `tag`
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(i) = rest.find(name) {
+        let preceded_by_space = rest[..i].ends_with(char::is_whitespace);
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+        if !preceded_by_space {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+/// A length in pixels (`px` or no unit); other units are not resolved
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .parse()
+        .ok()
+        .filter(|n: &f32| *n > 0.0)
+}
+
+/// An image ready to show
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedImage {
+    /// Copy of the file to hand to gpui
+    pub display: PathBuf,
+    /// Size of the file's image (not of a downscaled copy)
+    pub width: u32,
+    pub height: u32,
+    /// `display` was shrunk to the size limit
+    pub downscaled: bool,
+    /// Bytes of the file
+    pub file_size: u64,
+}
+
+impl LoadedImage {
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+/// Directory of the copies shown by the file view
+fn cache_dir() -> PathBuf {
+    std::env::temp_dir().join("sashiki-images")
+}
+
+/// Where the HEAD version (blob `id`) of `path` is written for comparing
+pub fn blob_path(id: &str, path: &Path) -> PathBuf {
+    let ext = extension(path).unwrap_or_default();
+    cache_dir().join(format!("blob-{}.{}", id, ext))
+}
+
+/// Decode `path` and copy it (downscaled beyond `max_side`) for display.
+/// Blocks; run off the UI thread.
+pub fn load(path: &Path, max_side: u32) -> Result<LoadedImage, String> {
+    let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    max_side.hash(&mut hasher);
+    let stem = format!("{:016x}", hasher.finish());
+    std::fs::create_dir_all(cache_dir()).map_err(|e| e.to_string())?;
+
+    if is_svg(path) {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        if !source.contains("<svg") {
+            return Err("not an SVG document".to_string());
+        }
+        let (width, height) = svg_dimensions(&source).unwrap_or(SVG_DEFAULT_SIZE);
+        let display = cache_dir().join(format!("{}.svg", stem));
+        copy_once(path, &display)?;
+        return Ok(LoadedImage {
+            display,
+            width,
+            height,
+            downscaled: false,
+            file_size: metadata.len(),
+        });
+    }
+
+    let image = image::ImageReader::open(path)
+        .map_err(|e| e.to_string())?
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| e.to_string())?;
+    let (width, height) = (image.width(), image.height());
+    let downscaled = downscaled_size((width, height), max_side);
+    let display = match downscaled {
+        Some((w, h)) => {
+            let display = cache_dir().join(format!("{}.png", stem));
+            if !display.exists() {
+                image
+                    .thumbnail(w, h)
+                    .save(&display)
+                    .map_err(|e| e.to_string())?;
+            }
+            display
+        }
+        // Blobs written for comparing are already named after their content
+        None if path.starts_with(cache_dir()) => path.to_path_buf(),
+        None => {
+            let ext = extension(path).unwrap_or_default();
+            let display = cache_dir().join(format!("{}.{}", stem, ext));
+            copy_once(path, &display)?;
+            display
+        }
+    };
+    Ok(LoadedImage {
+        display,
+        width,
+        height,
+        downscaled: downscaled.is_some(),
+        file_size: metadata.len(),
+    })
+}
+
+fn copy_once(from: &Path, to: &Path) -> Result<(), String> {
+    if to.exists() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).map(drop).map_err(|e| e.to_string())
+}
+
+/// Dragging the before/after slider
+#[derive(Debug, Clone, Copy)]
+pub struct SliderDrag {
+    pub start_x: f32,
+    pub initial_split: f32,
+    /// Width of the shown image, which the slider spans
+    pub width: f32,
+}
+
+impl SliderDrag {
+    /// Slider position with the pointer at `x`
+    pub fn split_at(&self, x: f32) -> f32 {
+        if self.width <= 0.0 {
+            return self.initial_split;
+        }
+        (self.initial_split + (x - self.start_x) / self.width).clamp(0.0, 1.0)
+    }
+}
+
+/// Image shown by the file view
+#[derive(Debug)]
+pub struct ImagePreview {
+    /// The open file
+    pub path: PathBuf,
+    /// None while decoding
+    pub current: Option<LoadedImage>,
+    /// HEAD version of a changed image
+    pub before: Option<LoadedImage>,
+    pub zoom: Zoom,
+    /// Share of the width showing `before` (from the left)
+    pub split: f32,
+    pub slider_drag: Option<SliderDrag>,
+    /// Show the text of an SVG instead of the image
+    pub show_source: bool,
+}
+
+impl ImagePreview {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            current: None,
+            before: None,
+            zoom: Zoom::Fit,
+            split: 0.5,
+            slider_drag: None,
+            show_source: false,
+        }
+    }
+
+    /// Size the images are laid out at: the larger of both versions
+    pub fn size(&self) -> Option<(u32, u32)> {
+        let current = self.current.as_ref()?.size();
+        Some(match &self.before {
+            Some(before) => (current.0.max(before.width), current.1.max(before.height)),
+            None => current,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_previewable() {
+        assert!(is_previewable(Path::new("assets/logo.PNG")));
+        assert!(is_previewable(Path::new("a/b.webp")));
+        assert!(is_previewable(Path::new("icon.svg")));
+        assert!(!is_previewable(Path::new("favicon.ico")));
+        assert!(!is_previewable(Path::new("png")));
+        assert!(is_svg(Path::new("x.SVG")));
+        assert!(!is_svg(Path::new("x.png")));
+    }
+
+    #[test]
+    fn test_fit_and_zoom() {
+        assert_eq!(fit_scale((2000, 1000), (1000.0, 1000.0)), 0.5);
+        assert_eq!(fit_scale((1000, 4000), (1000.0, 1000.0)), 0.25);
+        // Small images are not enlarged
+        assert_eq!(fit_scale((16, 16), (1000.0, 1000.0)), 1.0);
+        assert_eq!(fit_scale((2000, 1000), (0.0, 0.0)), 1.0);
+        assert_eq!(Zoom::Scale(2.0).scale((2000, 1000), (10.0, 10.0)), 2.0);
+
+        assert!((zoom_by(1.0, 1.0) - ZOOM_STEP).abs() < 1e-6);
+        assert!((zoom_by(zoom_by(1.0, 3.0), -3.0) - 1.0).abs() < 1e-5);
+        assert_eq!(zoom_by(1.0, 1000.0), MAX_ZOOM);
+        assert_eq!(zoom_by(1.0, -1000.0), MIN_ZOOM);
+    }
+
+    #[test]
+    fn test_downscaled_size() {
+        assert_eq!(downscaled_size((4096, 2048), 4096), None);
+        assert_eq!(downscaled_size((8000, 4000), 4000), Some((4000, 2000)));
+        assert_eq!(downscaled_size((3000, 9000), 900), Some((300, 900)));
+        assert_eq!(downscaled_size((10000, 1), 100), Some((100, 1)));
+    }
+
+    #[test]
+    fn test_svg_dimensions() {
+        let svg = |attrs: &str| format!("<?xml version=\"1.0\"?>\n<svg {}><rect/></svg>", attrs);
+        assert_eq!(
+            svg_dimensions(&svg("width=\"64\" height='32px'")),
+            Some((64, 32))
+        );
+        assert_eq!(
+            svg_dimensions(&svg("viewBox=\"0 0 24 12\"")),
+            Some((24, 12))
+        );
+        assert_eq!(
+            svg_dimensions(&svg("width=\"48\" viewBox=\"0,0,24,12\"")),
+            Some((48, 24))
+        );
+        // Relative units fall back to the viewBox
+        assert_eq!(
+            svg_dimensions(&svg("width=\"100%\" height=\"100%\" viewBox=\"0 0 10 20\"")),
+            Some((10, 20))
+        );
+        // stroke-width is not width
+        assert_eq!(
+            svg_dimensions(&svg("stroke-width=\"2\" viewBox=\"0 0 5 5\"")),
+            Some((5, 5))
+        );
+        assert_eq!(svg_dimensions(&svg("fill=\"red\"")), None);
+        assert_eq!(svg_dimensions("not svg"), None);
+    }
+
+    #[test]
+    fn test_load_downscales_large_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small.png");
+        let large = dir.path().join("large.png");
+        image::RgbImage::new(20, 10).save(&small).unwrap();
+        image::RgbImage::new(400, 100).save(&large).unwrap();
+
+        let loaded = load(&small, 100).unwrap();
+        assert_eq!((loaded.size(), loaded.downscaled), ((20, 10), false));
+        assert_ne!(loaded.display, small);
+        assert!(loaded.display.exists());
+
+        let loaded = load(&large, 100).unwrap();
+        assert_eq!((loaded.size(), loaded.downscaled), ((400, 100), true));
+        assert_eq!(image::image_dimensions(&loaded.display).unwrap(), (100, 25));
+
+        let broken = dir.path().join("broken.png");
+        std::fs::write(&broken, b"\x89PNG not really").unwrap();
+        assert!(load(&broken, 100).is_err());
+    }
+
+    #[test]
+    fn test_slider_drag() {
+        let drag = SliderDrag {
+            start_x: 100.0,
+            initial_split: 0.5,
+            width: 200.0,
+        };
+        assert_eq!(drag.split_at(150.0), 0.75);
+        assert_eq!(drag.split_at(-500.0), 0.0);
+        assert_eq!(drag.split_at(1000.0), 1.0);
+    }
+}
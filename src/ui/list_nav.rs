@@ -0,0 +1,270 @@
+//! Keyboard selection model for the sidebar and file list
+//!
+//! The sidebar and the file list are flattened into one list of rows
+//! (sessions first, then visible file entries). The highlighted row is
+//! anchored by identity rather than by index, so it survives the list being
+//! rebuilt underneath it.
+
+use crate::git::ChangeType;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Time after the last keystroke before type-ahead starts a new prefix
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// What a navigable row refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NavTarget {
+    /// Session, identified by its worktree path
+    Session(PathBuf),
+    /// File list entry (relative path in Changes mode, absolute in All mode)
+    Entry { path: PathBuf, is_dir: bool },
+}
+
+/// One row of the flattened sidebar + file list
+#[derive(Debug, Clone)]
+pub struct NavRow {
+    pub target: NavTarget,
+    /// Text matched by type-ahead
+    pub label: String,
+    pub change_type: Option<ChangeType>,
+}
+
+impl NavRow {
+    pub fn session(worktree_path: PathBuf, label: impl Into<String>) -> Self {
+        Self {
+            target: NavTarget::Session(worktree_path),
+            label: label.into(),
+            change_type: None,
+        }
+    }
+
+    pub fn entry(
+        path: PathBuf,
+        is_dir: bool,
+        label: impl Into<String>,
+        change_type: Option<ChangeType>,
+    ) -> Self {
+        Self {
+            target: NavTarget::Entry { path, is_dir },
+            label: label.into(),
+            change_type,
+        }
+    }
+}
+
+/// Highlighted row and type-ahead state
+#[derive(Debug, Default)]
+pub struct ListNav {
+    rows: Vec<NavRow>,
+    selected: Option<usize>,
+    typed: String,
+    typed_at: Option<Instant>,
+}
+
+impl ListNav {
+    pub fn selected_index(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn selected_row(&self) -> Option<&NavRow> {
+        self.selected.and_then(|i| self.rows.get(i))
+    }
+
+    pub fn is_session_selected(&self, worktree_path: &Path) -> bool {
+        self.selected_row()
+            .is_some_and(|row| matches!(&row.target, NavTarget::Session(p) if p == worktree_path))
+    }
+
+    pub fn is_entry_selected(&self, path: &Path) -> bool {
+        self.selected_row()
+            .is_some_and(|row| matches!(&row.target, NavTarget::Entry { path: p, .. } if p == path))
+    }
+
+    /// Replace the rows, keeping the highlight on the same target.
+    ///
+    /// If the highlighted target disappeared, the highlight stays at the same
+    /// position (clamped to the new length) so the cursor does not jump.
+    pub fn sync(&mut self, rows: Vec<NavRow>) {
+        let previous = self.selected_row().map(|row| row.target.clone());
+        let previous_index = self.selected;
+        self.rows = rows;
+
+        self.selected = match (previous, previous_index) {
+            (Some(target), Some(index)) => self
+                .rows
+                .iter()
+                .position(|row| row.target == target)
+                .or_else(|| (!self.rows.is_empty()).then(|| index.min(self.rows.len() - 1))),
+            _ => None,
+        };
+    }
+
+    /// Highlight the row for `target`, if present
+    pub fn select(&mut self, target: &NavTarget) {
+        if let Some(index) = self.rows.iter().position(|row| &row.target == target) {
+            self.selected = Some(index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.selected = None;
+        self.typed.clear();
+        self.typed_at = None;
+    }
+
+    /// Move the highlight by `delta` rows, stopping at either end
+    pub fn move_by(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            self.selected = None;
+            return;
+        }
+        let last = self.rows.len() - 1;
+        self.selected = Some(match self.selected {
+            Some(index) => index.saturating_add_signed(delta).min(last),
+            None if delta < 0 => last,
+            None => 0,
+        });
+    }
+
+    /// Extend the type-ahead prefix and jump to the next row starting with it.
+    ///
+    /// Matching is case-insensitive. A single character searches from the row
+    /// after the highlight (so repeated presses cycle); a longer prefix first
+    /// re-checks the highlighted row. Returns whether the highlight moved.
+    pub fn type_ahead(&mut self, c: char, now: Instant) -> bool {
+        let expired = self
+            .typed_at
+            .is_none_or(|at| now.duration_since(at) > TYPE_AHEAD_TIMEOUT);
+        if expired {
+            self.typed.clear();
+        }
+        self.typed.extend(c.to_lowercase());
+        self.typed_at = Some(now);
+
+        if self.rows.is_empty() {
+            return false;
+        }
+
+        let len = self.rows.len();
+        let start = match self.selected {
+            Some(index) if self.typed.chars().count() > 1 => index,
+            Some(index) => index + 1,
+            None => 0,
+        };
+
+        let found = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&i| self.rows[i].label.to_lowercase().starts_with(&self.typed));
+
+        match found {
+            Some(index) => {
+                let moved = self.selected != Some(index);
+                self.selected = Some(index);
+                moved
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> NavRow {
+        let label = path.rsplit('/').next().unwrap_or(path);
+        NavRow::entry(PathBuf::from(path), false, label, None)
+    }
+
+    fn rows(paths: &[&str]) -> Vec<NavRow> {
+        let mut rows = vec![NavRow::session(PathBuf::from("/wt/main"), "main")];
+        rows.extend(paths.iter().map(|p| entry(p)));
+        rows
+    }
+
+    fn target(path: &str) -> NavTarget {
+        NavTarget::Entry {
+            path: PathBuf::from(path),
+            is_dir: false,
+        }
+    }
+
+    fn selected_label(nav: &ListNav) -> Option<&str> {
+        nav.selected_row().map(|row| row.label.as_str())
+    }
+
+    #[test]
+    fn test_move_by_clamps_at_ends() {
+        let mut nav = ListNav::default();
+        nav.sync(rows(&["a.rs", "b.rs"]));
+
+        nav.move_by(1);
+        assert_eq!(nav.selected_index(), Some(0));
+        nav.move_by(5);
+        assert_eq!(nav.selected_index(), Some(2));
+        nav.move_by(-10);
+        assert_eq!(nav.selected_index(), Some(0));
+    }
+
+    #[test]
+    fn test_sync_keeps_selection_on_same_target() {
+        let mut nav = ListNav::default();
+        nav.sync(rows(&["b.rs", "c.rs"]));
+        nav.select(&target("c.rs"));
+        assert_eq!(selected_label(&nav), Some("c.rs"));
+
+        // A new file appears above the cursor
+        nav.sync(rows(&["a.rs", "b.rs", "c.rs"]));
+        assert_eq!(nav.selected_index(), Some(3));
+        assert_eq!(selected_label(&nav), Some("c.rs"));
+    }
+
+    #[test]
+    fn test_sync_clamps_when_selected_row_disappears() {
+        let mut nav = ListNav::default();
+        nav.sync(rows(&["a.rs", "b.rs", "c.rs"]));
+        nav.select(&target("b.rs"));
+        assert_eq!(selected_label(&nav), Some("b.rs"));
+
+        // b.rs was committed: cursor stays at the same position
+        nav.sync(rows(&["a.rs", "c.rs"]));
+        assert_eq!(selected_label(&nav), Some("c.rs"));
+
+        // The tail of the list disappears: cursor moves to the last row
+        nav.sync(rows(&[]));
+        assert_eq!(selected_label(&nav), Some("main"));
+
+        nav.sync(Vec::new());
+        assert_eq!(nav.selected_index(), None);
+    }
+
+    #[test]
+    fn test_sync_without_selection_stays_unselected() {
+        let mut nav = ListNav::default();
+        nav.sync(rows(&["a.rs"]));
+        assert_eq!(nav.selected_index(), None);
+    }
+
+    #[test]
+    fn test_type_ahead_prefix_and_cycle() {
+        let mut nav = ListNav::default();
+        nav.sync(rows(&["app.rs", "git.rs", "glob.rs"]));
+        let now = Instant::now();
+
+        assert!(nav.type_ahead('g', now));
+        assert_eq!(selected_label(&nav), Some("git.rs"));
+
+        // Same letter again after the timeout cycles to the next match
+        let later = now + TYPE_AHEAD_TIMEOUT * 2;
+        assert!(nav.type_ahead('G', later));
+        assert_eq!(selected_label(&nav), Some("glob.rs"));
+
+        // Continuing the prefix keeps the current row if it still matches
+        assert!(!nav.type_ahead('l', later));
+        assert_eq!(selected_label(&nav), Some("glob.rs"));
+
+        assert!(!nav.type_ahead('x', later));
+        assert_eq!(selected_label(&nav), Some("glob.rs"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Log pane rendering (see `app_log`)
+
+use crate::app::SashikiApp;
+use crate::app_log::{self, LogEntry, LogLevel, LogSource};
+use crate::theme::*;
+use crate::ui::TextTooltip;
+use gpui::{
+    AnyElement, ClipboardItem, Context, IntoElement, ParentElement, Styled, div, prelude::*, px,
+    rgb,
+};
+
+/// Height of the log pane
+const LOG_PANE_HEIGHT: f32 = 220.0;
+
+fn level_color(level: LogLevel) -> u32 {
+    match level {
+        LogLevel::Info => TEXT_SECONDARY,
+        LogLevel::Warn => YELLOW,
+        LogLevel::Error => RED,
+    }
+}
+
+impl SashikiApp {
+    pub fn render_log_pane(&self, cx: &Context<Self>) -> AnyElement {
+        let entries = app_log::entries(&self.log_filter);
+
+        div()
+            .h(px(LOG_PANE_HEIGHT))
+            .flex_shrink_0()
+            .flex()
+            .flex_col()
+            .bg(rgb(BG_MANTLE))
+            .border_t_1()
+            .border_color(rgb(BG_SURFACE0))
+            .child(self.render_log_pane_header(entries.len(), cx))
+            .child(
+                div()
+                    .id("log-entries")
+                    .flex_1()
+                    .overflow_y_scroll()
+                    .when(entries.is_empty(), |el| {
+                        el.child(
+                            div()
+                                .p_2()
+                                .text_xs()
+                                .text_color(rgb(TEXT_MUTED))
+                                .child("No log entries"),
+                        )
+                    })
+                    .children(
+                        entries
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, entry)| Self::render_log_entry(index, entry, cx)),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    fn render_log_pane_header(&self, shown: usize, cx: &Context<Self>) -> impl IntoElement {
+        let min_level = self.log_filter.min_level;
+
+        let level_buttons = LogLevel::ALL.into_iter().map(|level| {
+            let label = match level {
+                LogLevel::Info => "All",
+                LogLevel::Warn => "Warn+",
+                LogLevel::Error => "Errors",
+            };
+            div()
+                .id(("log-level", level as u32))
+                .px_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .when(min_level == level, |el| el.bg(rgb(BG_SURFACE1)))
+                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.log_filter.min_level = level;
+                    cx.notify();
+                }))
+                .child(label)
+        });
+
+        let source_buttons = LogSource::ALL.into_iter().map(|source| {
+            let shown = self.log_filter.shows_source(source);
+            div()
+                .id(("log-source", source as u32))
+                .px_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .text_color(rgb(if shown { TEXT } else { TEXT_MUTED }))
+                .when(shown, |el| el.bg(rgb(BG_SURFACE0)))
+                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.log_filter.toggle_source(source);
+                    cx.notify();
+                }))
+                .child(source.label())
+        });
+
+        div()
+            .h_7()
+            .px_2()
+            .flex()
+            .items_center()
+            .gap_2()
+            .bg(rgb(BG_BASE))
+            .border_b_1()
+            .border_color(rgb(BG_SURFACE0))
+            .text_xs()
+            .text_color(rgb(TEXT_SECONDARY))
+            .child(
+                div()
+                    .text_color(rgb(TEXT))
+                    .child(format!("Log ({}/{})", shown, app_log::len())),
+            )
+            .child(div().flex().gap_1().children(level_buttons))
+            .child(div().w(px(1.0)).h_4().bg(rgb(BG_SURFACE1)))
+            .child(div().flex().gap_1().children(source_buttons))
+            .child(div().flex_1())
+            .child(
+                div()
+                    .id("log-clear")
+                    .px_2()
+                    .cursor_pointer()
+                    .rounded_sm()
+                    .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                    .on_click(cx.listener(|_, _, _, cx| {
+                        app_log::clear();
+                        cx.notify();
+                    }))
+                    .child("Clear"),
+            )
+            .child(
+                div()
+                    .id("log-close")
+                    .px_1()
+                    .cursor_pointer()
+                    .rounded_sm()
+                    .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                    .on_click(cx.listener(|this, _, _, cx| {
+                        this.show_log_pane = false;
+                        cx.notify();
+                    }))
+                    .child("×"),
+            )
+    }
+
+    fn render_log_entry(index: usize, entry: LogEntry, cx: &Context<Self>) -> impl IntoElement {
+        let clipboard_text = entry.to_clipboard_text();
+
+        div()
+            .id(("log-entry", index))
+            .px_2()
+            .py_0p5()
+            .flex()
+            .flex_col()
+            .border_b_1()
+            .border_color(rgb(BG_SURFACE0))
+            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+            .text_xs()
+            .child(
+                div()
+                    .flex()
+                    .items_start()
+                    .gap_2()
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(entry.time_label()),
+                    )
+                    .child(
+                        div()
+                            .w(px(40.0))
+                            .flex_shrink_0()
+                            .text_color(rgb(level_color(entry.level)))
+                            .child(entry.level.label()),
+                    )
+                    .child(
+                        div()
+                            .w(px(56.0))
+                            .flex_shrink_0()
+                            .text_color(rgb(TEXT_SECONDARY))
+                            .child(entry.source.label()),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_color(rgb(TEXT))
+                            .child(entry.message.clone()),
+                    )
+                    .child(
+                        div()
+                            .id(("log-copy", index))
+                            .flex_shrink_0()
+                            .px_1()
+                            .cursor_pointer()
+                            .rounded_sm()
+                            .text_color(rgb(TEXT_MUTED))
+                            .hover(|el| el.bg(rgb(BG_SURFACE1)).text_color(rgb(TEXT)))
+                            .tooltip(TextTooltip::build("Copy entry to clipboard"))
+                            .on_click(cx.listener(move |_, _, _, cx| {
+                                cx.write_to_clipboard(ClipboardItem::new_string(
+                                    clipboard_text.clone(),
+                                ));
+                            }))
+                            .child("Copy"),
+                    ),
+            )
+            .when_some(entry.command, |el, command| {
+                el.child(
+                    div()
+                        .pl(px(16.0))
+                        .font_family(MONOSPACE_FONT)
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(format!("$ {}", command)),
+                )
+            })
+            .when_some(entry.stderr, |el, stderr| {
+                el.child(
+                    div()
+                        .pl(px(16.0))
+                        .font_family(MONOSPACE_FONT)
+                        .text_color(rgb(MAROON))
+                        .children(
+                            stderr
+                                .lines()
+                                .map(|line| line.to_string())
+                                .collect::<Vec<_>>(),
+                        ),
+                )
+            })
+    }
+}
@@ -0,0 +1,474 @@
+//! Worktree maintenance dialog rendering (see `crate::maintenance`)
+
+use crate::app::SashikiApp;
+use crate::maintenance::{self, WorktreeUsage};
+use crate::theme::*;
+use crate::ui::sidebar::format_age;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, rgb,
+    rgba,
+};
+use std::path::PathBuf;
+
+impl SashikiApp {
+    pub fn render_maintenance_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let state = &self.maintenance;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let selected = state.selected_rows();
+        let can_clean = !state.busy && !selected.is_empty();
+        let deletable = selected.iter().filter(|row| !row.is_main).count();
+        let can_delete = !state.busy && deletable > 0;
+        let total: u64 = state.rows.iter().filter_map(|row| row.size).sum();
+
+        let header = div()
+            .px_4()
+            .py_1()
+            .flex()
+            .items_center()
+            .gap_2()
+            .text_xs()
+            .text_color(rgb(TEXT_MUTED))
+            .border_b_1()
+            .border_color(rgb(BG_SURFACE0))
+            .child(div().w_4().flex_shrink_0())
+            .child(div().flex_1().child("Worktree"))
+            .child(maintenance_column(72.0).child("Size"))
+            .child(maintenance_column(72.0).child("Last commit"))
+            .child(maintenance_column(64.0).child("Ahead/behind"))
+            .child(maintenance_column(56.0).child("Changed"));
+
+        let list = div()
+            .id("maintenance-list")
+            .max_h_96()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .children(
+                state
+                    .rows
+                    .iter()
+                    .enumerate()
+                    .map(|(i, row)| self.render_maintenance_row(i, row, now, cx)),
+            );
+
+        let footer_button = |id: &'static str, label: String, enabled: bool, color: u32| {
+            div()
+                .id(id)
+                .px_3()
+                .py_2()
+                .rounded_sm()
+                .text_xs()
+                .bg(rgb(BG_SURFACE1))
+                .when(enabled, |el| {
+                    el.cursor_pointer()
+                        .text_color(rgb(color))
+                        .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                })
+                .when(!enabled, |el| el.text_color(rgb(TEXT_MUTED)))
+                .child(label)
+        };
+
+        div()
+            .id("maintenance-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_maintenance_dialog(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("maintenance-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_maintenance_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("maintenance-dialog")
+                            .occlude()
+                            .w(gpui::px(640.0))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .justify_between()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child("Worktree Maintenance"),
+                                    )
+                                    .child(div().text_xs().text_color(rgb(TEXT_SECONDARY)).child(
+                                        format!(
+                                            "{} worktrees · {}",
+                                            state.rows.len(),
+                                            maintenance::format_size(total)
+                                        ),
+                                    )),
+                            )
+                            .child(header)
+                            .child(list)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .flex_1()
+                                            .truncate()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .children(state.status.clone().or_else(|| {
+                                                state.busy.then(|| "Measuring…".to_string())
+                                            })),
+                                    )
+                                    .child(
+                                        footer_button(
+                                            "maintenance-rescan",
+                                            "Rescan".to_string(),
+                                            !state.busy,
+                                            TEXT,
+                                        )
+                                        .when(
+                                            !state.busy,
+                                            |el| {
+                                                el.on_click(cx.listener(|this, _, _, cx| {
+                                                    this.rescan_maintenance(cx);
+                                                }))
+                                            },
+                                        ),
+                                    )
+                                    .child(
+                                        footer_button(
+                                            "maintenance-clean",
+                                            "Clean build artifacts…".to_string(),
+                                            can_clean,
+                                            YELLOW,
+                                        )
+                                        .when(
+                                            can_clean,
+                                            |el| {
+                                                el.on_click(cx.listener(|this, _, _, cx| {
+                                                    this.request_clean_artifacts(cx);
+                                                }))
+                                            },
+                                        ),
+                                    )
+                                    .child(
+                                        footer_button(
+                                            "maintenance-delete",
+                                            format!("Delete {} worktrees…", deletable),
+                                            can_delete,
+                                            RED,
+                                        )
+                                        .when(
+                                            can_delete,
+                                            |el| {
+                                                el.on_click(cx.listener(|this, _, _, cx| {
+                                                    this.request_bulk_delete(cx);
+                                                }))
+                                            },
+                                        ),
+                                    )
+                                    .child(
+                                        footer_button(
+                                            "close-maintenance",
+                                            "Close".to_string(),
+                                            true,
+                                            TEXT,
+                                        )
+                                        .on_click(
+                                            cx.listener(|this, _, _, cx| {
+                                                this.close_maintenance_dialog(cx);
+                                            }),
+                                        ),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    fn render_maintenance_row(
+        &self,
+        i: usize,
+        row: &WorktreeUsage,
+        now: i64,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let is_selected = self.maintenance.selected.contains(&row.path);
+        let path = row.path.clone();
+        let size = row
+            .size
+            .map(maintenance::format_size)
+            .unwrap_or_else(|| "…".to_string());
+        let last_commit = maintenance_cell(
+            row.loaded,
+            row.last_commit.map(|time| format_age(now - time)),
+        );
+        let ahead_behind = maintenance_cell(
+            row.loaded,
+            row.ahead_behind
+                .map(|(ahead, behind)| format!("↑{} ↓{}", ahead, behind)),
+        );
+        let changed = maintenance_cell(row.loaded, row.changed.map(|n| n.to_string()));
+
+        div()
+            .id(("maintenance-row", i))
+            .px_4()
+            .py_1()
+            .flex()
+            .items_center()
+            .gap_2()
+            .text_xs()
+            .cursor_pointer()
+            .when(is_selected, |el| el.bg(rgb(BG_SURFACE0)))
+            .hover(|el| el.bg(rgb(BG_SURFACE1)))
+            .on_click(cx.listener(move |this, _, _, cx| {
+                this.toggle_maintenance_selection(path.clone(), cx);
+            }))
+            .child(
+                div()
+                    .w_4()
+                    .flex_shrink_0()
+                    .text_color(if is_selected {
+                        rgb(BLUE)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .child(if is_selected { "☑" } else { "☐" }),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .flex()
+                    .flex_col()
+                    .overflow_hidden()
+                    .child(
+                        div()
+                            .truncate()
+                            .text_color(rgb(TEXT))
+                            .child(if row.is_main {
+                                format!("{} (main)", row.name)
+                            } else {
+                                row.name.clone()
+                            }),
+                    )
+                    .when_some(row.branch.clone(), |el, branch| {
+                        el.child(
+                            div()
+                                .truncate()
+                                .text_color(rgb(TEXT_MUTED))
+                                .child(format!("⎇ {}", branch)),
+                        )
+                    }),
+            )
+            .child(maintenance_column(72.0).text_color(rgb(TEXT)).child(size))
+            .child(
+                maintenance_column(72.0)
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(last_commit),
+            )
+            .child(
+                maintenance_column(64.0)
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(ahead_behind),
+            )
+            .child(
+                maintenance_column(56.0)
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(changed),
+            )
+    }
+
+    pub fn render_clean_artifacts_dialog(
+        &self,
+        artifacts: &[(PathBuf, Vec<PathBuf>)],
+        bytes: u64,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let count: usize = artifacts.iter().map(|(_, paths)| paths.len()).sum();
+        let question = format!(
+            "Remove {} build artifacts ({}) from {} worktrees?",
+            count,
+            maintenance::format_size(bytes),
+            artifacts.len()
+        );
+        let names = self.maintenance.rows.iter();
+        let lines: Vec<String> = artifacts
+            .iter()
+            .flat_map(|(root, paths)| {
+                let name = names
+                    .clone()
+                    .find(|row| &row.path == root)
+                    .map(|row| row.name.clone())
+                    .unwrap_or_else(|| root.display().to_string());
+                paths.iter().map(move |path| {
+                    let relative = path.strip_prefix(root).unwrap_or(path);
+                    format!("{}/{}", name, relative.display())
+                })
+            })
+            .collect();
+
+        div()
+            .id("clean-artifacts-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.cancel_clean_artifacts(cx);
+                } else if key == "enter" {
+                    this.confirm_clean_artifacts(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("clean-artifacts-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.cancel_clean_artifacts(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("clean-artifacts-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(RED))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(RED))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Clean Build Artifacts"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(question))
+                                    .child(
+                                        div()
+                                            .id("clean-artifacts-list")
+                                            .max_h_48()
+                                            .overflow_y_scroll()
+                                            .font_family(MONOSPACE_FONT)
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .children(lines),
+                                    )
+                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(
+                                        "These are deleted permanently. Reinstall or rebuild to get them back.",
+                                    )),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-clean-artifacts")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.cancel_clean_artifacts(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-clean-artifacts")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(RED))
+                                            .hover(|el| el.bg(rgb(MAROON)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_clean_artifacts(cx);
+                                            }))
+                                            .child("Remove"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
+
+/// Right-aligned column of the maintenance dialog
+fn maintenance_column(width: f32) -> gpui::Div {
+    div().w(gpui::px(width)).flex_shrink_0().text_right()
+}
+
+/// Text of a git state cell in the maintenance dialog ("…" until read)
+fn maintenance_cell(loaded: bool, value: Option<String>) -> String {
+    value.unwrap_or_else(|| if loaded { "-" } else { "…" }.to_string())
+}
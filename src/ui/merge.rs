@@ -0,0 +1,414 @@
+//! Merge-into-main dialogs rendering: options, progress and conflicts (see
+//! `crate::app::merge`)
+
+use crate::app::SashikiApp;
+use crate::git::MergeMode;
+use crate::theme::*;
+use gpui::{AnyElement, Context, IntoElement, ParentElement, Styled, div, prelude::*, rgb, rgba};
+use std::path::PathBuf;
+
+impl SashikiApp {
+    pub fn render_merge_dialog(
+        &self,
+        target_index: usize,
+        mode: MergeMode,
+        remove_worktree: bool,
+        delete_branch: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let sessions = self.repo.session_manager.sessions();
+        let Some(session) = sessions.get(target_index) else {
+            return div().into_any_element();
+        };
+        let session_name = session.name().to_string();
+        let branch = session.branch().unwrap_or_default().to_string();
+        let target = sessions
+            .iter()
+            .find(|s| s.is_main())
+            .and_then(|s| s.branch())
+            .unwrap_or("main")
+            .to_string();
+        let description = match mode {
+            MergeMode::NoFastForward => "Always record a merge commit.",
+            MergeMode::FastForward => "Only move main forward; fails if main has moved on.",
+            MergeMode::Squash => "Commit all changes of the branch as one commit.",
+        };
+
+        let mode_tab = |id: &'static str, tab: MergeMode, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .text_xs()
+                .text_color(rgb(TEXT))
+                .when(mode == tab, |el| el.bg(rgb(BG_SURFACE1)))
+                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.set_merge_mode(tab, cx);
+                }))
+                .child(label)
+        };
+        let checkbox = |id: &'static str, checked: bool, enabled: bool, label: &'static str| {
+            div()
+                .id(id)
+                .flex()
+                .items_center()
+                .gap_2()
+                .text_xs()
+                .text_color(rgb(if enabled { TEXT } else { TEXT_MUTED }))
+                .when(enabled, |el| el.cursor_pointer())
+                .child(
+                    div()
+                        .text_color(rgb(if checked { GREEN } else { TEXT_MUTED }))
+                        .child(if checked { "☑" } else { "☐" }),
+                )
+                .child(label)
+        };
+
+        div()
+            .id("merge-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("merge-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_merge_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("merge-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!("Merge Branch: {}", session_name)),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .child(format!("⎇ {} → {}", branch, target)),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap_1()
+                                            .child(mode_tab(
+                                                "merge-mode-no-ff",
+                                                MergeMode::NoFastForward,
+                                                "Merge commit",
+                                            ))
+                                            .child(mode_tab(
+                                                "merge-mode-ff",
+                                                MergeMode::FastForward,
+                                                "Fast-forward",
+                                            ))
+                                            .child(mode_tab(
+                                                "merge-mode-squash",
+                                                MergeMode::Squash,
+                                                "Squash",
+                                            )),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child(description),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .flex_col()
+                                            .gap_1()
+                                            .child(
+                                                checkbox(
+                                                    "merge-remove-worktree",
+                                                    remove_worktree,
+                                                    true,
+                                                    "Remove the worktree after merging",
+                                                )
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.toggle_merge_remove_worktree(cx);
+                                                })),
+                                            )
+                                            .child(
+                                                checkbox(
+                                                    "merge-delete-branch",
+                                                    delete_branch,
+                                                    remove_worktree,
+                                                    "Delete the branch as well",
+                                                )
+                                                .on_click(cx.listener(|this, _, _, cx| {
+                                                    this.toggle_merge_delete_branch(cx);
+                                                })),
+                                            ),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-merge")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_merge_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-merge")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_merge(cx);
+                                            }))
+                                            .child("Merge"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_merging_dialog(&self) -> AnyElement {
+        div()
+            .id("merging-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("merging-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("merging-dialog")
+                            .occlude()
+                            .w_64()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(GREEN))
+                            .rounded_md()
+                            .shadow_lg()
+                            .p_4()
+                            .flex()
+                            .flex_col()
+                            .items_center()
+                            .gap_3()
+                            .child(div().text_color(rgb(GREEN)).text_sm().child("Merging..."))
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .text_xs()
+                                    .child("Please wait"),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_merge_conflicts_dialog(
+        &self,
+        files: &[PathBuf],
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let list = div()
+            .id("merge-conflict-list")
+            .max_h_64()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .children(files.iter().enumerate().map(|(i, file)| {
+                let path = file.clone();
+                div()
+                    .id(("merge-conflict", i))
+                    .py_0p5()
+                    .cursor_pointer()
+                    .truncate()
+                    .text_xs()
+                    .text_color(rgb(BLUE))
+                    .hover(|el| el.text_color(rgb(TEXT)))
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.open_merge_conflict_diff(path.clone(), window, cx);
+                    }))
+                    .child(file.to_string_lossy().to_string())
+            }));
+
+        div()
+            .id("merge-conflicts-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("merge-conflicts-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_merge_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("merge-conflicts-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(YELLOW))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Merge Conflicts"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .child(format!(
+                                                "The merge stopped on {} conflicted file(s) in the main worktree. Resolve and commit them there, or abort the merge.",
+                                                files.len()
+                                            )),
+                                    )
+                                    .child(list)
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child("Click a file to compare main's side (Before) with the branch's side (After)."),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("close-merge-conflicts")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_merge_dialog(cx);
+                                            }))
+                                            .child("Close"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("abort-merge")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(RED))
+                                            .hover(|el| el.bg(rgb(MAROON)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.abort_merge(cx);
+                                            }))
+                                            .child("Abort merge"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,260 @@
+//! Notes panel rendering (see `crate::notes`)
+
+use crate::app::SashikiApp;
+use crate::notes::{self, PreviewLine};
+use crate::theme::*;
+use crate::ui::dialogs::{char_to_byte_offset, cursor_to_line_col, line_col_to_cursor};
+use gpui::{
+    AnyElement, Context, Focusable, IntoElement, KeyDownEvent, ParentElement, Styled, Window, div,
+    prelude::*, px, rgb,
+};
+
+/// Height of the notes panel
+const NOTES_PANEL_HEIGHT: f32 = 200.0;
+
+/// Indentation of one list level in the preview
+const LIST_INDENT: f32 = 12.0;
+
+impl SashikiApp {
+    /// The open note below the terminals: the editor while `focused`, the
+    /// markdown preview otherwise
+    pub fn render_notes_panel(&self, name: &str, focused: bool, cx: &Context<Self>) -> AnyElement {
+        let body = if focused {
+            self.render_note_editor()
+        } else {
+            Self::render_note_preview(&self.notes.text)
+        };
+
+        div()
+            .id("notes-panel")
+            .track_focus(&self.notes_focus)
+            .h(px(NOTES_PANEL_HEIGHT))
+            .flex_shrink_0()
+            .flex()
+            .flex_col()
+            .bg(rgb(BG_MANTLE))
+            .border_t_1()
+            .border_color(rgb(if focused { BLUE } else { BG_SURFACE0 }))
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                this.handle_note_key(event, window, cx);
+            }))
+            .child(
+                div()
+                    .h_7()
+                    .px_2()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .bg(rgb(BG_BASE))
+                    .border_b_1()
+                    .border_color(rgb(BG_SURFACE0))
+                    .text_xs()
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(
+                        div()
+                            .text_color(rgb(TEXT))
+                            .child(format!("Notes · {}", name)),
+                    )
+                    .child(div().flex_1())
+                    .child(if focused {
+                        "Esc to preview · saved automatically"
+                    } else {
+                        "Click to edit"
+                    })
+                    .child(
+                        div()
+                            .id("notes-close")
+                            .px_1()
+                            .cursor_pointer()
+                            .rounded_sm()
+                            .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.close_notes(window, cx);
+                            }))
+                            .child("×"),
+                    ),
+            )
+            .child(
+                div()
+                    .id("notes-body")
+                    .flex_1()
+                    .px_2()
+                    .py_1()
+                    .overflow_y_scroll()
+                    .cursor_text()
+                    .text_xs()
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        window.focus(&this.notes_focus, cx);
+                        cx.notify();
+                    }))
+                    .child(body),
+            )
+            .into_any_element()
+    }
+
+    /// The note's text with the cursor shown as `|`
+    fn render_note_editor(&self) -> AnyElement {
+        let text = &self.notes.text;
+        let (cursor_line, cursor_col) = cursor_to_line_col(text, self.notes.cursor);
+        div()
+            .flex()
+            .flex_col()
+            .font_family(MONOSPACE_FONT)
+            .text_color(rgb(TEXT))
+            .children(text.split('\n').enumerate().map(|(index, line)| {
+                let display = if index == cursor_line {
+                    let (before, after) = line.split_at(char_to_byte_offset(line, cursor_col));
+                    format!("{}|{}", before, after)
+                } else if line.is_empty() {
+                    " ".to_string()
+                } else {
+                    line.to_string()
+                };
+                div().child(display)
+            }))
+            .into_any_element()
+    }
+
+    fn render_note_preview(text: &str) -> AnyElement {
+        if text.trim().is_empty() {
+            return div()
+                .text_color(rgb(TEXT_MUTED))
+                .child("No notes for this session yet")
+                .into_any_element();
+        }
+        div()
+            .flex()
+            .flex_col()
+            .text_color(rgb(TEXT))
+            .children(notes::preview_lines(text).into_iter().map(|line| {
+                match line {
+                    PreviewLine::Heading { level, text } => div()
+                        .pt_1()
+                        .font_weight(gpui::FontWeight::BOLD)
+                        .text_color(rgb(if level == 1 { BLUE } else { MAUVE }))
+                        .when(level <= 2, |el| el.text_sm())
+                        .child(text),
+                    PreviewLine::Bullet { depth, text } => div()
+                        .pl(px(depth as f32 * LIST_INDENT))
+                        .flex()
+                        .gap_1()
+                        .child(div().text_color(rgb(TEXT_MUTED)).child("•"))
+                        .child(text),
+                    PreviewLine::Task { depth, done, text } => div()
+                        .pl(px(depth as f32 * LIST_INDENT))
+                        .flex()
+                        .gap_1()
+                        .child(
+                            div()
+                                .text_color(rgb(if done { GREEN } else { TEXT_MUTED }))
+                                .child(if done { "☑" } else { "☐" }),
+                        )
+                        .child(
+                            div()
+                                .when(done, |el| el.text_color(rgb(TEXT_MUTED)).line_through())
+                                .child(text),
+                        ),
+                    PreviewLine::Quote(text) => div()
+                        .pl_2()
+                        .border_l_2()
+                        .border_color(rgb(BG_SURFACE2))
+                        .text_color(rgb(TEXT_SECONDARY))
+                        .child(text),
+                    PreviewLine::Code(text) => div()
+                        .px_1()
+                        .bg(rgb(BG_SURFACE0))
+                        .font_family(MONOSPACE_FONT)
+                        .child(if text.is_empty() {
+                            " ".to_string()
+                        } else {
+                            text
+                        }),
+                    PreviewLine::Rule => div().my_1().h(px(1.0)).bg(rgb(BG_SURFACE1)),
+                    PreviewLine::Text(text) => div().child(text),
+                    PreviewLine::Blank => div().h_2(),
+                }
+            }))
+            .into_any_element()
+    }
+
+    /// Typing in the focused notes panel
+    fn handle_note_key(
+        &mut self,
+        event: &KeyDownEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let keystroke = &event.keystroke;
+        let key = keystroke.key.as_str();
+        let command = keystroke.modifiers.control || keystroke.modifiers.platform;
+
+        match key {
+            "escape" => {
+                // Back to the terminal; the panel shows the preview
+                let focus = match self.active_terminal() {
+                    Some(terminal) => terminal.read(cx).focus_handle(cx),
+                    None => self.focus_handle.clone(),
+                };
+                window.focus(&focus, cx);
+                cx.notify();
+            }
+            "enter" => self.insert_note_text("\n", cx),
+            "tab" => self.insert_note_text("  ", cx),
+            "backspace" => self.edit_note(
+                |text, cursor| {
+                    if *cursor > 0 {
+                        *cursor -= 1;
+                        text.remove(char_to_byte_offset(text, *cursor));
+                    }
+                },
+                cx,
+            ),
+            "delete" => self.edit_note(
+                |text, cursor| {
+                    if *cursor < text.chars().count() {
+                        text.remove(char_to_byte_offset(text, *cursor));
+                    }
+                },
+                cx,
+            ),
+            "left" | "right" | "up" | "down" | "home" | "end" => {
+                let text = &self.notes.text;
+                let cursor = self.notes.cursor;
+                let (line, col) = cursor_to_line_col(text, cursor);
+                self.notes.cursor = match key {
+                    "left" => cursor.saturating_sub(1),
+                    "right" => (cursor + 1).min(text.chars().count()),
+                    "up" if line > 0 => line_col_to_cursor(text, line - 1, col),
+                    "up" => 0,
+                    "down" => line_col_to_cursor(text, line + 1, col),
+                    "home" => line_col_to_cursor(text, line, 0),
+                    _ => line_col_to_cursor(text, line, usize::MAX),
+                };
+                cx.notify();
+            }
+            "v" if command => {
+                if let Some(pasted) = cx.read_from_clipboard().and_then(|item| item.text()) {
+                    self.insert_note_text(&pasted.replace("\r\n", "\n"), cx);
+                }
+            }
+            _ => {
+                if !command
+                    && let Some(typed) = keystroke.key_char.as_deref()
+                    && !typed.chars().any(char::is_control)
+                {
+                    self.insert_note_text(typed, cx);
+                }
+            }
+        }
+    }
+
+    fn insert_note_text(&mut self, inserted: &str, cx: &mut Context<Self>) {
+        self.edit_note(
+            |text, cursor| {
+                text.insert_str(char_to_byte_offset(text, *cursor), inserted);
+                *cursor += inserted.chars().count();
+            },
+            cx,
+        );
+    }
+}
@@ -1,12 +1,19 @@
 //! Render trait implementation for SashikiApp
 
-use crate::app::{MenuId, ResizeDrag, SashikiApp};
+use crate::app::{
+    MenuId, ResizeDrag, SashikiApp, SwitchToSession1, SwitchToSession2, SwitchToSession3,
+    SwitchToSession4, SwitchToSession5, SwitchToSession6, SwitchToSession7, SwitchToSession8,
+    SwitchToSession9, commands,
+};
 use crate::dialog::ActiveDialog;
+use crate::layout::{FILE_VIEW_HEIGHT, FILE_VIEW_WIDTH, TerminalDock};
 use crate::session::LayoutMode;
 use crate::theme::*;
+use crate::ui::TextTooltip;
+use crate::window_title::{self, TitleParts};
 use gpui::{
-    App, Context, FocusHandle, Focusable, IntoElement, MouseButton, Render, Styled, Window, div,
-    prelude::*, px, rgb,
+    App, Context, FocusHandle, Focusable, IntoElement, KeyDownEvent, MouseButton, Render, Styled,
+    Window, div, prelude::*, px, rgb,
 };
 
 impl Focusable for SashikiApp {
@@ -16,10 +23,30 @@ impl Focusable for SashikiApp {
 }
 
 impl Render for SashikiApp {
-    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let layout_mode = self.session_manager.layout_mode();
-        let session_count = self.session_manager.len();
-        let running_session_count = self.session_manager.running_session_count();
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.update_file_rows(window, cx);
+
+        // Keyboard highlight follows list changes while the sidebar has focus
+        if self.sidebar_focus.is_focused(window) {
+            self.sync_list_nav();
+        } else if self.repo.list_nav.selected_index().is_some() {
+            self.repo.list_nav.clear();
+        }
+
+        if std::mem::take(&mut self.focus_comment_dialog) {
+            window.focus(&self.comment_dialog_focus, cx);
+        }
+
+        let title = self.window_title();
+        if self.shown_window_title.as_deref() != Some(title.as_str()) {
+            window.set_window_title(&title);
+            self.shown_window_title = Some(title);
+        }
+
+        let layout_mode = self.repo.session_manager.layout_mode();
+        let notes_focused = self.notes_focus.is_focused(window);
+        let session_count = self.repo.session_manager.len();
+        let running_session_count = self.repo.session_manager.running_session_count();
 
         div()
             .size_full()
@@ -29,49 +56,429 @@ impl Render for SashikiApp {
             .on_action(cx.listener(Self::on_toggle_parallel))
             .on_action(cx.listener(Self::on_next_session))
             .on_action(cx.listener(Self::on_prev_session))
+            .on_action(cx.listener(Self::on_move_session_up))
+            .on_action(cx.listener(Self::on_move_session_down))
+            .on_action(cx.listener(|this, _: &SwitchToSession1, window, cx| {
+                this.switch_to_position(0, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession2, window, cx| {
+                this.switch_to_position(1, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession3, window, cx| {
+                this.switch_to_position(2, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession4, window, cx| {
+                this.switch_to_position(3, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession5, window, cx| {
+                this.switch_to_position(4, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession6, window, cx| {
+                this.switch_to_position(5, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession7, window, cx| {
+                this.switch_to_position(6, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession8, window, cx| {
+                this.switch_to_position(7, window, cx)
+            }))
+            .on_action(cx.listener(|this, _: &SwitchToSession9, window, cx| {
+                this.switch_to_position(8, window, cx)
+            }))
             .on_action(cx.listener(Self::on_toggle_sidebar))
             .on_action(cx.listener(Self::on_toggle_file_list))
+            .on_action(cx.listener(Self::on_grow_terminal))
+            .on_action(cx.listener(Self::on_shrink_terminal))
+            .on_action(cx.listener(Self::on_widen_sidebar))
+            .on_action(cx.listener(Self::on_narrow_sidebar))
+            .on_action(cx.listener(Self::on_toggle_terminal_dock))
+            .on_action(cx.listener(Self::on_toggle_maximize_terminal))
             .on_action(cx.listener(Self::on_refresh_all))
             .on_action(cx.listener(Self::on_close_file_view))
             .on_action(cx.listener(Self::on_open_folder))
             .on_action(cx.listener(Self::on_toggle_verify_terminal))
+            .on_action(cx.listener(Self::on_focus_sidebar))
+            .on_action(cx.listener(Self::on_command_palette))
+            .on_action(cx.listener(Self::on_search_workspace))
+            .on_action(cx.listener(Self::on_rerun_last_command))
+            .on_action(cx.listener(Self::on_recent_commands))
+            .on_action(cx.listener(Self::on_create_worktree))
+            .on_action(cx.listener(Self::on_create_worktrees))
+            .on_action(cx.listener(Self::on_add_existing_worktree))
+            .on_action(cx.listener(Self::on_delete_worktree))
+            .on_action(cx.listener(Self::on_quit))
+            .on_action(cx.listener(Self::on_show_diff_stats))
+            .on_action(cx.listener(Self::on_toggle_diff_mode))
+            .on_action(cx.listener(Self::on_toggle_word_wrap))
+            .on_action(cx.listener(Self::on_compare_with_session))
+            .on_action(cx.listener(Self::on_apply_code_block))
+            .on_action(cx.listener(Self::on_next_repository))
+            .on_action(cx.listener(Self::on_previous_repository))
+            .on_action(cx.listener(Self::on_close_repository))
+            .on_action(cx.listener(Self::on_next_changed_file))
+            .on_action(cx.listener(Self::on_prev_changed_file))
+            .on_action(cx.listener(Self::on_toggle_log))
+            .on_action(cx.listener(Self::on_toggle_read_only))
+            .on_action(cx.listener(Self::on_toggle_bell_mute))
+            .on_action(cx.listener(Self::on_close_terminal))
+            .on_action(cx.listener(Self::on_launch_default_agent))
+            .on_action(cx.listener(Self::on_toggle_auto_checkpoint))
+            .on_action(cx.listener(Self::on_checkpoints))
+            .on_action(cx.listener(Self::on_toggle_notes))
+            .on_action(cx.listener(Self::on_toggle_timeline))
+            .on_action(cx.listener(Self::on_mark_moment))
+            .on_action(cx.listener(Self::on_review_comments))
+            .on_action(cx.listener(Self::on_copy_review))
+            .on_action(cx.listener(Self::on_save_review))
+            .on_action(cx.listener(Self::on_send_review_to_terminal))
+            .on_action(cx.listener(Self::on_template_settings))
+            .on_action(cx.listener(Self::on_worktree_maintenance))
+            .on_action(cx.listener(Self::on_export_state))
             .child(self.render_header(layout_mode, session_count, running_session_count, cx))
-            .child(self.render_main_content(layout_mode, cx))
+            .child(self.render_main_content(layout_mode, notes_focused, cx))
             .when(self.open_menu.is_some(), |this| {
                 this.child(self.render_menu_overlay(cx))
             })
-            .when(
-                matches!(self.active_dialog, ActiveDialog::CreateWorktree),
-                |this| this.child(self.render_create_dialog(cx)),
-            )
+            .when_some(self.agents.menu, |this, (index, position)| {
+                this.child(self.render_agent_menu(index, position, cx))
+            })
+            .when(self.repo.show_git_queue, |this| {
+                this.child(self.render_git_queue_overlay(cx))
+            })
+            .when_some(self.diff_stats_popover, |this, popover| {
+                this.child(self.render_diff_stats_popover(popover.selected, popover.pinned, cx))
+            })
+            .when(matches!(self.active_dialog, ActiveDialog::Open), |this| {
+                this.child(self.render_open_dialog(cx))
+            })
             .when_some(
                 match &self.active_dialog {
-                    ActiveDialog::DeleteConfirm { target_index } => Some(*target_index),
+                    ActiveDialog::DeleteConfirm {
+                        target_index,
+                        keep_note,
+                    } => Some((*target_index, *keep_note)),
                     _ => None,
                 },
-                |this, idx| this.child(self.render_delete_dialog(idx, cx)),
+                |this, (idx, keep_note)| this.child(self.render_delete_dialog(idx, keep_note, cx)),
             )
             .when(
                 matches!(self.active_dialog, ActiveDialog::Deleting),
                 |this| this.child(self.render_deleting_dialog()),
             )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::QuitConfirm { busy } => Some(busy.as_slice()),
+                    _ => None,
+                },
+                |this, busy| this.child(self.render_quit_dialog(busy, cx)),
+            )
             .when_some(
                 match &self.active_dialog {
                     ActiveDialog::Creating {
                         branch,
-                        steps,
-                        current_step,
-                    } => Some((branch.as_str(), steps.as_slice(), *current_step)),
+                        template,
+                        progress,
+                    } => Some((branch.as_str(), template.as_deref(), progress)),
+                    _ => None,
+                },
+                |this, (branch, template, progress)| {
+                    this.child(self.render_creating_dialog(branch, template, progress))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::AddExistingWorktree {
+                        unsessioned,
+                        input,
+                        error,
+                        other_repository,
+                    } => Some((
+                        unsessioned.as_slice(),
+                        input.as_str(),
+                        error.as_deref(),
+                        other_repository.is_some(),
+                    )),
                     _ => None,
                 },
-                |this, (branch, steps, current_step)| {
-                    this.child(self.render_creating_dialog(branch, steps, current_step))
+                |this, (unsessioned, input, error, other)| {
+                    this.child(self.render_adopt_dialog(unsessioned, input, error, other, cx))
+                },
+            )
+            .when(
+                matches!(self.active_dialog, ActiveDialog::BulkCreate),
+                |this| this.child(self.render_bulk_create_dialog(cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Conflicts { target_index } => Some(*target_index),
+                    _ => None,
+                },
+                |this, idx| this.child(self.render_conflicts_dialog(idx, cx)),
+            )
+            .when(
+                matches!(self.active_dialog, ActiveDialog::StashPush),
+                |this| this.child(self.render_stash_dialog(cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Transcript { target_index } => Some(*target_index),
+                    _ => None,
                 },
+                |this, idx| this.child(self.render_transcript_dialog(idx, cx)),
+            )
+            .when(matches!(self.active_dialog, ActiveDialog::Search), |this| {
+                this.child(self.render_search_dialog(cx))
+            })
+            .when(
+                matches!(self.active_dialog, ActiveDialog::Maintenance),
+                |this| this.child(self.render_maintenance_dialog(cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::CleanArtifactsConfirm { artifacts, bytes } => {
+                        Some((artifacts.as_slice(), *bytes))
+                    }
+                    _ => None,
+                },
+                |this, (artifacts, bytes)| {
+                    this.child(self.render_clean_artifacts_dialog(artifacts, bytes, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::SessionTags { target_index } => Some(*target_index),
+                    _ => None,
+                },
+                |this, idx| this.child(self.render_tags_dialog(idx, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::MissingWorktree { worktree } => Some(worktree),
+                    _ => None,
+                },
+                |this, worktree| this.child(self.render_missing_worktree_dialog(worktree, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::MarkMoment { target_index } => Some(*target_index),
+                    _ => None,
+                },
+                |this, idx| this.child(self.render_mark_moment_dialog(idx, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::ReviewComment {
+                        path,
+                        line,
+                        input,
+                        editing,
+                        ..
+                    } => Some((path.as_path(), *line, input.as_str(), editing.is_some())),
+                    _ => None,
+                },
+                |this, (path, line, input, editing)| {
+                    this.child(self.render_comment_dialog(path, line, input, editing, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Review { markdown, count } => Some((markdown.as_str(), *count)),
+                    _ => None,
+                },
+                |this, (markdown, count)| {
+                    this.child(self.render_review_dialog(markdown, count, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::CommandPalette { query, selected } => {
+                        Some((query.clone(), *selected))
+                    }
+                    _ => None,
+                },
+                |this, (query, selected)| {
+                    this.child(self.render_command_palette(&query, selected, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::CommandHistory { selected } => Some(*selected),
+                    _ => None,
+                },
+                |this, selected| this.child(self.render_command_history(selected, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::CloseTerminalConfirm { name } => Some(name.as_str()),
+                    _ => None,
+                },
+                |this, name| this.child(self.render_close_terminal_dialog(name, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::NestedRepository { path } => Some(path.as_path()),
+                    _ => None,
+                },
+                |this, path| this.child(self.render_nested_repository_dialog(path, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::CompareWithSession { path } => Some(path.as_path()),
+                    _ => None,
+                },
+                |this, path| this.child(self.render_compare_dialog(path, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::StashDropConfirm { index, message } => {
+                        Some((*index, message.as_str()))
+                    }
+                    _ => None,
+                },
+                |this, (index, message)| {
+                    this.child(self.render_stash_drop_dialog(index, message, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::DiscardConfirm { target } => Some(target),
+                    _ => None,
+                },
+                |this, target| this.child(self.render_discard_dialog(target, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Snapshots {
+                        target_index,
+                        kind,
+                        snapshots,
+                    } => Some((*target_index, *kind, snapshots.as_slice())),
+                    _ => None,
+                },
+                |this, (idx, kind, snapshots)| {
+                    this.child(self.render_snapshots_dialog(idx, kind, snapshots, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::SnapshotRestoreConfirm {
+                        target_index,
+                        kind,
+                        snapshot,
+                    } => Some((*target_index, *kind, snapshot)),
+                    _ => None,
+                },
+                |this, (idx, kind, snapshot)| {
+                    this.child(self.render_snapshot_restore_dialog(idx, kind, snapshot, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::SnapshotDiff {
+                        target_index,
+                        kind,
+                        snapshot,
+                        diff,
+                    } => Some((*target_index, *kind, snapshot, diff.as_str())),
+                    _ => None,
+                },
+                |this, (idx, kind, snapshot, diff)| {
+                    this.child(self.render_snapshot_diff_dialog(idx, kind, snapshot, diff, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::ApplyCodeBlock { suggestion } => Some(suggestion),
+                    _ => None,
+                },
+                |this, suggestion| this.child(self.render_apply_code_block_dialog(suggestion, cx)),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Squash {
+                        target_index,
+                        commits,
+                        mode,
+                        dropped,
+                        ..
+                    } => Some((*target_index, commits.as_slice(), *mode, dropped)),
+                    _ => None,
+                },
+                |this, (idx, commits, mode, dropped)| {
+                    this.child(self.render_squash_dialog(idx, commits, mode, dropped, cx))
+                },
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::SyncFiles {
+                        target_index,
+                        files,
+                        selected,
+                        show_identical,
+                        preview,
+                    } => Some((
+                        *target_index,
+                        files.as_slice(),
+                        selected,
+                        *show_identical,
+                        preview.as_ref(),
+                    )),
+                    _ => None,
+                },
+                |this, (idx, files, selected, show_identical, preview)| {
+                    this.child(self.render_sync_files_dialog(
+                        idx,
+                        files,
+                        selected,
+                        show_identical,
+                        preview,
+                        cx,
+                    ))
+                },
+            )
+            .when(
+                matches!(self.active_dialog, ActiveDialog::Squashing),
+                |this| this.child(self.render_squashing_dialog()),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::Merge {
+                        target_index,
+                        mode,
+                        remove_worktree,
+                        delete_branch,
+                    } => Some((*target_index, *mode, *remove_worktree, *delete_branch)),
+                    _ => None,
+                },
+                |this, (idx, mode, remove_worktree, delete_branch)| {
+                    this.child(self.render_merge_dialog(
+                        idx,
+                        mode,
+                        remove_worktree,
+                        delete_branch,
+                        cx,
+                    ))
+                },
+            )
+            .when(
+                matches!(self.active_dialog, ActiveDialog::Merging),
+                |this| this.child(self.render_merging_dialog()),
+            )
+            .when_some(
+                match &self.active_dialog {
+                    ActiveDialog::MergeConflicts { files } => Some(files.as_slice()),
+                    _ => None,
+                },
+                |this, files| this.child(self.render_merge_conflicts_dialog(files, cx)),
             )
             .when(
                 matches!(self.active_dialog, ActiveDialog::TemplateSettings),
                 |this| this.child(self.render_template_settings_dialog(cx)),
             )
+            .when_some(
+                self.repo.git_queue.held().into_iter().next(),
+                |this, (op, lock)| this.child(self.render_repository_busy_dialog(&op, &lock, cx)),
+            )
             .when_some(
                 match &self.active_dialog {
                     ActiveDialog::Error { message } => Some(message.as_str()),
@@ -83,6 +490,33 @@ impl Render for SashikiApp {
 }
 
 impl SashikiApp {
+    /// OS window title for the open repository and active session
+    fn window_title(&self) -> String {
+        let (Some(repo), Some(session)) = (
+            &self.repo.git_repo,
+            self.repo.session_manager.active_session(),
+        ) else {
+            return window_title::APP_TITLE.to_string();
+        };
+        let repo_name = repo
+            .workdir()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let parts = TitleParts {
+            repo: &repo_name,
+            session: session.name(),
+            branch: session.branch(),
+            attention: self
+                .repo
+                .session_manager
+                .sessions()
+                .iter()
+                .any(|s| s.attention().is_some()),
+        };
+        window_title::expand(&self.window_title_format, &parts)
+    }
+
     fn render_header(
         &self,
         layout_mode: LayoutMode,
@@ -134,7 +568,7 @@ impl SashikiApp {
                             .hover(|this| this.bg(rgb(BG_SURFACE2)))
                             .text_xs()
                             .on_click(cx.listener(|this, _, _, cx| {
-                                this.session_manager.toggle_layout_mode();
+                                this.repo.session_manager.toggle_layout_mode();
                                 cx.notify();
                             }))
                             .child(if layout_mode == LayoutMode::Parallel {
@@ -146,7 +580,324 @@ impl SashikiApp {
                     .child(div().text_xs().text_color(rgb(TEXT_MUTED)).child(format!(
                         "{}/{} running",
                         running_session_count, session_count
-                    ))),
+                    )))
+                    .when_some(self.repo.session_manager.active_session(), |el, session| {
+                        let color = session.color().on(BG_SURFACE0);
+                        el.child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .gap_1()
+                                .text_xs()
+                                .text_color(rgb(color))
+                                .child(div().w_2().h_2().rounded_full().bg(rgb(color)))
+                                .child(session.branch().unwrap_or(session.name()).to_string()),
+                        )
+                    }),
+            )
+            .child(
+                // Right: changes of the active session, git operation in progress
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .child(self.render_diff_stats(cx))
+                    .child(self.render_git_queue_indicator(cx)),
+            )
+    }
+
+    // === Diff stats ===
+
+    /// Lines added and removed in the active session since HEAD, excluded
+    /// files left out; hovering or clicking lists the files with the most
+    /// changes
+    fn render_diff_stats(&self, cx: &Context<Self>) -> impl IntoElement {
+        let files = self
+            .repo
+            .session_manager
+            .active_session()
+            .and_then(|s| self.repo.file_cache.get(s.worktree_path()))
+            .filter(|files| !files.churn.is_empty());
+        let totals = files.map(|files| files.churn_totals(&self.review.exclude));
+        let excluded = files.map_or(0, |files| {
+            files
+                .churn
+                .iter()
+                .filter(|f| self.is_excluded(&f.path))
+                .count()
+        });
+
+        div()
+            .id("diff-stats")
+            .when_some(totals, |el, (added, removed)| {
+                el.child(
+                    div()
+                        .id("diff-stats-button")
+                        .px_2()
+                        .py_1()
+                        .flex()
+                        .gap_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .hover(|this| this.bg(rgb(BG_SURFACE2)))
+                        .text_xs()
+                        .on_hover(cx.listener(|this, hovered: &bool, window, cx| {
+                            if *hovered {
+                                this.open_diff_stats(false, window, cx);
+                            }
+                        }))
+                        .on_click(cx.listener(|this, _, window, cx| {
+                            this.open_diff_stats(true, window, cx);
+                        }))
+                        .when(excluded > 0, |el| {
+                            el.tooltip(TextTooltip::build(format!(
+                                "{} excluded file{} not counted (sashiki.review.exclude)",
+                                excluded,
+                                if excluded == 1 { "" } else { "s" }
+                            )))
+                        })
+                        .child(div().text_color(rgb(GREEN)).child(format!("+{}", added)))
+                        .child(div().text_color(rgb(RED)).child(format!("−{}", removed))),
+                )
+            })
+    }
+
+    fn render_diff_stats_popover(
+        &self,
+        selected: usize,
+        pinned: bool,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let files = self.top_churn_files();
+
+        div()
+            .id("diff-stats-overlay")
+            .track_focus(&self.diff_stats_focus)
+            .absolute()
+            .top(px(32.))
+            .left_0()
+            .right_0()
+            .bottom_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_diff_stats(window, cx);
+                } else if key == "enter" {
+                    this.open_churn_file(selected, window, cx);
+                } else if key == "up" {
+                    this.move_diff_stats_selection(-1, cx);
+                } else if key == "down" {
+                    this.move_diff_stats_selection(1, cx);
+                }
+            }))
+            .child(
+                // Below the header, so the stats button stays hoverable
+                div()
+                    .id("diff-stats-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .on_hover(cx.listener(move |this, hovered: &bool, window, cx| {
+                        if *hovered && !pinned {
+                            this.close_diff_stats(window, cx);
+                        }
+                    }))
+                    .on_mouse_down(
+                        MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_diff_stats(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .id("diff-stats-list")
+                    .occlude()
+                    .absolute()
+                    .top_0()
+                    .right(px(8.))
+                    .w(px(360.))
+                    .bg(rgb(BG_BASE))
+                    .border_1()
+                    .border_color(rgb(BG_SURFACE1))
+                    .rounded_sm()
+                    .shadow_lg()
+                    .py_1()
+                    .child(
+                        div()
+                            .px_3()
+                            .py_1()
+                            .text_xs()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child("Most changed files"),
+                    )
+                    .when(files.is_empty(), |el| {
+                        el.child(
+                            div()
+                                .px_3()
+                                .py_1()
+                                .text_xs()
+                                .text_color(rgb(TEXT_MUTED))
+                                .child("No changes since HEAD"),
+                        )
+                    })
+                    .children(files.into_iter().enumerate().map(|(i, file)| {
+                        let counts = if file.binary {
+                            div().text_color(rgb(TEXT_MUTED)).child("binary")
+                        } else {
+                            div()
+                                .flex()
+                                .gap_1()
+                                .child(
+                                    div()
+                                        .text_color(rgb(GREEN))
+                                        .child(format!("+{}", file.added)),
+                                )
+                                .child(
+                                    div()
+                                        .text_color(rgb(RED))
+                                        .child(format!("−{}", file.removed)),
+                                )
+                        };
+                        div()
+                            .id(("diff-stats-file", i))
+                            .px_3()
+                            .py_1()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .cursor_pointer()
+                            .text_xs()
+                            .when(i == selected, |el| el.bg(rgb(BG_SURFACE1)))
+                            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.open_churn_file(i, window, cx);
+                            }))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .whitespace_nowrap()
+                                    .text_ellipsis()
+                                    .text_color(rgb(TEXT))
+                                    .child(file.path.display().to_string()),
+                            )
+                            .child(counts)
+                    })),
+            )
+    }
+
+    // === Git operation queue ===
+
+    fn render_git_queue_indicator(&self, cx: &Context<Self>) -> impl IntoElement {
+        let (current, pending) = self.repo.git_queue.status();
+        let label = match (&current, pending.len()) {
+            (None, 0) => None,
+            (Some(op), 0) => Some(format!("⟳ {}", op.name)),
+            (Some(op), queued) => Some(format!("⟳ {} (+{} queued)", op.name, queued)),
+            (None, queued) => Some(format!("{} queued", queued)),
+        };
+
+        div()
+            .id("git-queue")
+            .min_w(gpui::px(120.))
+            .flex()
+            .justify_end()
+            .when_some(label, |el, label| {
+                el.child(
+                    div()
+                        .id("git-queue-button")
+                        .px_2()
+                        .py_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .hover(|this| this.bg(rgb(BG_SURFACE2)))
+                        .text_xs()
+                        .text_color(rgb(YELLOW))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.repo.show_git_queue = !this.repo.show_git_queue;
+                            cx.notify();
+                        }))
+                        .child(label),
+                )
+            })
+    }
+
+    fn render_git_queue_overlay(&self, cx: &Context<Self>) -> impl IntoElement {
+        let (current, pending) = self.repo.git_queue.status();
+
+        let row = |name: String| {
+            div()
+                .px_3()
+                .py_1()
+                .flex()
+                .items_center()
+                .gap_2()
+                .text_xs()
+                .child(div().flex_1().text_color(rgb(TEXT)).child(name))
+        };
+
+        div()
+            .id("git-queue-overlay")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("git-queue-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.repo.show_git_queue = false;
+                            cx.notify();
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .top(gpui::px(32.))
+                    .right(gpui::px(8.))
+                    .min_w(gpui::px(240.))
+                    .bg(rgb(BG_BASE))
+                    .border_1()
+                    .border_color(rgb(BG_SURFACE1))
+                    .rounded_sm()
+                    .shadow_lg()
+                    .py_1()
+                    .when(current.is_none() && pending.is_empty(), |el| {
+                        el.child(row("No git operations".to_string()))
+                    })
+                    .when_some(current, |el, op| {
+                        el.child(row(op.name).child(div().text_color(rgb(YELLOW)).child("running")))
+                    })
+                    .children(pending.into_iter().map(|op| {
+                        let id = op.id;
+                        // Waiting for another git process to release a lock
+                        let lock = op
+                            .lock
+                            .as_ref()
+                            .and_then(|lock| lock.file_name())
+                            .map(|name| format!("waiting for {}", name.to_string_lossy()));
+                        row(op.name)
+                            .when_some(lock, |el, lock| {
+                                el.child(div().text_color(rgb(TEXT_MUTED)).child(lock))
+                            })
+                            .child(
+                                div()
+                                    .id(format!("git-queue-cancel-{:?}", id))
+                                    .px_1()
+                                    .rounded_sm()
+                                    .cursor_pointer()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .hover(|this| this.text_color(rgb(RED)).bg(rgb(BG_SURFACE1)))
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        this.repo.git_queue.cancel(id);
+                                        cx.notify();
+                                    }))
+                                    .child("×"),
+                            )
+                    })),
             )
     }
 
@@ -203,75 +954,23 @@ impl SashikiApp {
             .shadow_lg()
             .py_1();
 
-        match menu_id {
-            MenuId::App => {
-                dropdown = dropdown
-                    .child(Self::render_menu_item("Template Settings...", None, cx, |this, window, cx| {
-                        this.open_menu = None;
-                        this.open_template_settings(window, cx);
-                    }))
-                    .child(Self::render_menu_separator())
-                    .child(Self::render_menu_item("Quit", Some("Alt+F4"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        cx.quit();
-                    }));
-            }
-            MenuId::File => {
-                dropdown = dropdown
-                    .child(Self::render_menu_item("Open Folder...", Some("Ctrl+O"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        cx.notify();
-                        let paths_receiver = cx.prompt_for_paths(gpui::PathPromptOptions {
-                            files: false,
-                            directories: true,
-                            multiple: false,
-                            prompt: None,
-                        });
-                        cx.spawn(async move |entity, cx| {
-                            if let Ok(Ok(Some(paths))) = paths_receiver.await {
-                                if let Some(path) = paths.into_iter().next() {
-                                    let _ = entity.update(cx, |app, cx| {
-                                        app.open_project(path, cx);
-                                    });
-                                }
-                            }
-                        })
-                        .detach();
-                    }));
-            }
-            MenuId::View => {
-                dropdown = dropdown
-                    .child(Self::render_menu_item("Toggle Sidebar", Some("Ctrl+B"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        this.show_sidebar = !this.show_sidebar;
-                        cx.notify();
-                    }))
-                    .child(Self::render_menu_item("Toggle File List", Some("Ctrl+E"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        this.show_file_list = !this.show_file_list;
-                        cx.notify();
-                    }))
-                    .child(Self::render_menu_item("Toggle Parallel", Some("Ctrl+P"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        this.session_manager.toggle_layout_mode();
-                        cx.notify();
-                    }))
-                    .child(Self::render_menu_item("Toggle Verify Terminal", Some("Ctrl+T"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        this.show_verify_terminal = !this.show_verify_terminal;
-                        if this.show_verify_terminal {
-                            this.session_manager.ensure_active_session_terminal_count(2, cx);
-                        }
-                        cx.notify();
-                    }))
-                    .child(Self::render_menu_separator())
-                    .child(Self::render_menu_item("Refresh All", Some("Ctrl+R"), cx, |this, _, cx| {
-                        this.open_menu = None;
-                        this.refresh_worktrees(cx);
-                        this.refresh_file_list_async(cx);
-                        cx.notify();
-                    }));
+        for command in commands::COMMANDS
+            .iter()
+            .filter(|command| command.menu == Some(menu_id))
+        {
+            if command.separated {
+                dropdown = dropdown.child(Self::render_menu_separator());
             }
+            dropdown = dropdown.child(Self::render_menu_item(
+                command.title,
+                command.key_label().as_deref(),
+                cx,
+                move |this, window, cx| {
+                    this.open_menu = None;
+                    window.dispatch_action(command.action(), cx);
+                    cx.notify();
+                },
+            ));
         }
 
         dropdown
@@ -302,21 +1001,12 @@ impl SashikiApp {
             }))
             .child(div().text_color(rgb(TEXT)).child(label_owned))
             .when_some(shortcut_owned, |this, sc| {
-                this.child(
-                    div()
-                        .ml_4()
-                        .text_color(rgb(TEXT_MUTED))
-                        .child(sc),
-                )
+                this.child(div().ml_4().text_color(rgb(TEXT_MUTED)).child(sc))
             })
     }
 
     fn render_menu_separator() -> impl IntoElement {
-        div()
-            .my_1()
-            .mx_2()
-            .h_px()
-            .bg(rgb(BG_SURFACE1))
+        div().my_1().mx_2().h_px().bg(rgb(BG_SURFACE1))
     }
 
     /// Full-screen overlay with backdrop + positioned dropdown.
@@ -355,7 +1045,13 @@ impl SashikiApp {
             )
     }
 
-    fn render_main_content(&mut self, layout_mode: LayoutMode, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render_main_content(
+        &mut self,
+        layout_mode: LayoutMode,
+        notes_focused: bool,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let maximized = self.layout.terminal_maximized;
         div()
             .id("main-content")
             .flex_1()
@@ -364,7 +1060,10 @@ impl SashikiApp {
             .overflow_hidden()
             .on_mouse_move(cx.listener(|this, event: &gpui::MouseMoveEvent, _, cx| {
                 if this.resize_drag.is_some() {
-                    this.handle_resize_drag_move(f32::from(event.position.x), f32::from(event.position.y));
+                    this.handle_resize_drag_move(
+                        f32::from(event.position.x),
+                        f32::from(event.position.y),
+                    );
                     cx.notify();
                 }
             }))
@@ -379,10 +1078,13 @@ impl SashikiApp {
             )
             .when(self.show_sidebar, |this| {
                 this.child(self.render_sidebar(cx))
-                    .child(self.render_resize_handle_v(ResizeDrag::Sidebar {
-                        start_x: 0.0,
-                        initial_width: self.sidebar_width,
-                    }, cx))
+                    .child(self.render_resize_handle_v(
+                        ResizeDrag::Sidebar {
+                            start_x: 0.0,
+                            initial_width: self.layout.sidebar_width,
+                        },
+                        cx,
+                    ))
             })
             .child(
                 div()
@@ -391,46 +1093,178 @@ impl SashikiApp {
                     .flex_col()
                     .overflow_hidden()
                     .when(
-                        self.show_file_view && layout_mode == LayoutMode::Single,
-                        |this| {
-                            this.child(
-                                div()
-                                    .h(px(self.file_view_height))
-                                    .min_h(px(100.0))
-                                    .flex_shrink_0()
-                                    .child(self.file_view.clone()),
-                            )
-                            .child(self.render_resize_handle_h(cx))
-                        },
+                        !self.health_problems.is_empty() && !self.health_dismissed,
+                        |this| this.child(self.render_health_panel(cx)),
                     )
-                    .child(
-                        div()
-                            .flex_1()
-                            .flex()
-                            .flex_col()
-                            .overflow_hidden()
-                            .child(self.render_terminal_area(cx)),
-                    ),
+                    .child(self.render_docked_terminal(layout_mode, cx))
+                    .when(!maximized, |this| {
+                        this.when_some(self.notes.session.as_deref(), |this, name| {
+                            this.child(self.render_notes_panel(name, notes_focused, cx))
+                        })
+                        .when_some(self.timeline.session.as_deref(), |this, name| {
+                            this.child(self.render_timeline_panel(name, cx))
+                        })
+                        .when(self.show_log_pane, |this| {
+                            this.child(self.render_log_pane(cx))
+                        })
+                    }),
             )
             .when(
                 self.show_file_list && layout_mode == LayoutMode::Single,
                 |this| {
-                    this.child(self.render_resize_handle_v(ResizeDrag::FileList {
-                        start_x: 0.0,
-                        initial_width: self.file_list_width,
-                    }, cx))
+                    this.child(self.render_resize_handle_v(
+                        ResizeDrag::FileList {
+                            start_x: 0.0,
+                            initial_width: self.layout.file_list_width,
+                        },
+                        cx,
+                    ))
                     .child(self.render_file_list(cx))
                 },
             )
     }
 
-    pub(crate) fn render_resize_handle_v(&self, drag_variant: ResizeDrag, cx: &Context<Self>) -> impl IntoElement {
+    /// The terminal area with the file view above it or, docked right,
+    /// beside it (single mode only; hidden while the terminal is maximized)
+    fn render_docked_terminal(
+        &self,
+        layout_mode: LayoutMode,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
+        let show_file_view = self.show_file_view
+            && layout_mode == LayoutMode::Single
+            && !self.layout.terminal_maximized;
+        let docked_right = self.layout.dock == TerminalDock::Right;
+        div()
+            .flex_1()
+            .flex()
+            .overflow_hidden()
+            .when(docked_right, |this| this.flex_row())
+            .when(!docked_right, |this| this.flex_col())
+            .when(show_file_view, |this| {
+                let file_view = div().flex_shrink_0().child(self.file_view.clone());
+                if docked_right {
+                    this.child(
+                        file_view
+                            .w(px(self.layout.file_view_width))
+                            .min_w(px(*FILE_VIEW_WIDTH.start())),
+                    )
+                    .child(self.render_resize_handle_v(
+                        ResizeDrag::FileViewTerminalSide {
+                            start_x: 0.0,
+                            initial_width: self.layout.file_view_width,
+                        },
+                        cx,
+                    ))
+                } else {
+                    this.child(
+                        file_view
+                            .h(px(self.layout.file_view_height))
+                            .min_h(px(*FILE_VIEW_HEIGHT.start())),
+                    )
+                    .child(self.render_resize_handle_h(cx))
+                }
+            })
+            .child(
+                div()
+                    .flex_1()
+                    .flex()
+                    .flex_col()
+                    .overflow_hidden()
+                    .child(self.render_terminal_area(cx)),
+            )
+    }
+
+    /// Problems found by the startup health check, with their fixes
+    fn render_health_panel(&self, cx: &Context<Self>) -> impl IntoElement {
+        let checking = self.health_check.is_some();
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE0))
+                .hover(|d| d.bg(rgb(BG_SURFACE1)))
+                .text_xs()
+                .text_color(rgb(TEXT))
+                .child(label)
+        };
+
+        div()
+            .flex_shrink_0()
+            .px_3()
+            .py_2()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .bg(rgb(BG_MANTLE))
+            .border_b_1()
+            .border_color(rgb(YELLOW))
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_sm()
+                            .text_color(rgb(YELLOW))
+                            .child("Setup problems found"),
+                    )
+                    .child(
+                        button(
+                            "health-rerun",
+                            if checking {
+                                "Checking..."
+                            } else {
+                                "Re-run checks"
+                            },
+                        )
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.run_health_check(cx);
+                        })),
+                    )
+                    .child(button("health-dismiss", "Dismiss").on_click(cx.listener(
+                        |this, _, _, cx| {
+                            this.health_dismissed = true;
+                            cx.notify();
+                        },
+                    ))),
+            )
+            .children(self.health_problems.iter().map(|problem| {
+                div()
+                    .flex()
+                    .flex_col()
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(rgb(TEXT))
+                            .child(problem.title.clone()),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(problem.remedy.clone()),
+                    )
+            }))
+    }
+
+    pub(crate) fn render_resize_handle_v(
+        &self,
+        drag_variant: ResizeDrag,
+        cx: &Context<Self>,
+    ) -> impl IntoElement {
         let initial = drag_variant;
         div()
             .id(match initial {
                 ResizeDrag::Sidebar { .. } => "resize-sidebar",
                 ResizeDrag::FileList { .. } => "resize-filelist",
                 ResizeDrag::TerminalSplit { .. } => "resize-terminal-split",
+                ResizeDrag::FileViewTerminalSide { .. } => "resize-fileview-terminal-side",
                 _ => "resize-v",
             })
             .h_full()
@@ -445,16 +1279,22 @@ impl SashikiApp {
                     this.resize_drag = Some(match initial {
                         ResizeDrag::Sidebar { .. } => ResizeDrag::Sidebar {
                             start_x: x,
-                            initial_width: this.sidebar_width,
+                            initial_width: this.layout.sidebar_width,
                         },
                         ResizeDrag::FileList { .. } => ResizeDrag::FileList {
                             start_x: x,
-                            initial_width: this.file_list_width,
+                            initial_width: this.layout.file_list_width,
                         },
                         ResizeDrag::TerminalSplit { .. } => ResizeDrag::TerminalSplit {
                             start_x: x,
-                            initial_ratio: this.terminal_split_ratio,
+                            initial_ratio: this.layout.terminal_split_ratio,
                         },
+                        ResizeDrag::FileViewTerminalSide { .. } => {
+                            ResizeDrag::FileViewTerminalSide {
+                                start_x: x,
+                                initial_width: this.layout.file_view_width,
+                            }
+                        }
                         other => other,
                     });
                     cx.notify();
@@ -475,7 +1315,7 @@ impl SashikiApp {
                 cx.listener(move |this, event: &gpui::MouseDownEvent, _, cx| {
                     this.resize_drag = Some(ResizeDrag::FileViewTerminal {
                         start_y: f32::from(event.position.y),
-                        initial_height: this.file_view_height,
+                        initial_height: this.layout.file_view_height,
                     });
                     cx.notify();
                 }),
@@ -488,15 +1328,31 @@ impl SashikiApp {
             None => return,
         };
         match drag {
-            ResizeDrag::Sidebar { start_x, initial_width } => {
-                let new_width = (initial_width + (current_x - start_x)).clamp(120.0, 500.0);
-                self.sidebar_width = new_width;
+            ResizeDrag::Sidebar {
+                start_x,
+                initial_width,
+            } => {
+                self.layout
+                    .set_sidebar_width(initial_width + (current_x - start_x));
+            }
+            ResizeDrag::FileViewTerminal {
+                start_y,
+                initial_height,
+            } => {
+                self.layout
+                    .set_file_view_height(initial_height + (current_y - start_y));
             }
-            ResizeDrag::FileViewTerminal { start_y, initial_height } => {
-                let new_height = (initial_height + (current_y - start_y)).clamp(100.0, 800.0);
-                self.file_view_height = new_height;
+            ResizeDrag::FileViewTerminalSide {
+                start_x,
+                initial_width,
+            } => {
+                self.layout
+                    .set_file_view_width(initial_width + (current_x - start_x));
             }
-            ResizeDrag::TerminalSplit { start_x, initial_ratio } => {
+            ResizeDrag::TerminalSplit {
+                start_x,
+                initial_ratio,
+            } => {
                 let container_width = if initial_ratio > 0.0 {
                     (start_x - 0.0) / initial_ratio
                 } else {
@@ -504,12 +1360,16 @@ impl SashikiApp {
                 };
                 if container_width > 0.0 {
                     let ratio_delta = (current_x - start_x) / container_width;
-                    self.terminal_split_ratio = (initial_ratio + ratio_delta).clamp(0.2, 0.8);
+                    self.layout
+                        .set_terminal_split_ratio(initial_ratio + ratio_delta);
                 }
             }
-            ResizeDrag::FileList { start_x, initial_width } => {
-                let new_width = (initial_width - (current_x - start_x)).clamp(120.0, 500.0);
-                self.file_list_width = new_width;
+            ResizeDrag::FileList {
+                start_x,
+                initial_width,
+            } => {
+                self.layout
+                    .set_file_list_width(initial_width - (current_x - start_x));
             }
         }
     }
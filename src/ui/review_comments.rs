@@ -0,0 +1,316 @@
+//! Review comment dialogs rendering (see `crate::review_comments`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+use std::path::Path;
+
+impl SashikiApp {
+    pub fn render_comment_dialog(
+        &self,
+        path: &Path,
+        line: usize,
+        input: &str,
+        editing: bool,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let button = |id: &'static str, label: &'static str, primary: bool| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(if primary { GREEN } else { BG_SURFACE1 }))
+                .hover(move |el| el.bg(rgb(if primary { TEAL } else { BG_SURFACE2 })))
+                .text_xs()
+                .text_color(rgb(if primary { BG_BASE } else { TEXT }))
+                .child(label)
+        };
+        let reference = format!("{}:{}", path.to_string_lossy(), line);
+        let input_lines: Vec<String> = if input.is_empty() {
+            vec!["Ask for a change or a question about this line".to_string()]
+        } else {
+            format!("{}_", input).lines().map(str::to_string).collect()
+        };
+
+        div()
+            .id("comment-dialog-container")
+            .track_focus(&self.comment_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_comment_dialog(window, cx);
+                } else if key == "enter" && event.keystroke.modifiers.shift {
+                    this.edit_comment_input(|input| input.push('\n'), cx);
+                } else if key == "enter" {
+                    this.submit_comment_dialog(window, cx);
+                } else if key == "backspace" {
+                    this.edit_comment_input(
+                        |input| {
+                            input.pop();
+                        },
+                        cx,
+                    );
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.edit_comment_input(|input| input.push_str(text), cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("comment-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_comment_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("comment-dialog")
+                            .occlude()
+                            .w(px(520.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(if editing { "Edit Comment" } else { "Comment" }),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .font_family(MONOSPACE_FONT)
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .child(reference),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("comment-input")
+                                            .w_full()
+                                            .px_3()
+                                            .py_2()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .cursor_text()
+                                            .text_color(rgb(if input.is_empty() {
+                                                TEXT_MUTED
+                                            } else {
+                                                TEXT
+                                            }))
+                                            .text_sm()
+                                            .children(
+                                                input_lines
+                                                    .into_iter()
+                                                    .map(|line| div().child(line)),
+                                            ),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child("Enter saves, Shift+Enter starts a new line"),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(button("cancel-comment", "Cancel", false).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.close_comment_dialog(window, cx);
+                                        }),
+                                    ))
+                                    .child(button("save-comment", "Save", true).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.submit_comment_dialog(window, cx);
+                                        }),
+                                    )),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    /// The review as it will be copied, saved or sent
+    pub fn render_review_dialog(
+        &self,
+        markdown: &str,
+        count: usize,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let button = |id: &'static str, label: &'static str, primary: bool| {
+            div()
+                .id(id)
+                .px_4()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(if primary { GREEN } else { BG_SURFACE1 }))
+                .hover(move |el| el.bg(rgb(if primary { TEAL } else { BG_SURFACE2 })))
+                .text_xs()
+                .text_color(rgb(if primary { BG_BASE } else { TEXT }))
+                .child(label)
+        };
+        let lines: Vec<String> = markdown.lines().map(str::to_string).collect();
+
+        div()
+            .id("review-dialog-container")
+            .track_focus(&self.review_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_review_dialog(window, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("review-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_review_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("review-dialog")
+                            .occlude()
+                            .w(px(640.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(format!(
+                                        "Review ({} comment{})",
+                                        count,
+                                        if count == 1 { "" } else { "s" }
+                                    )),
+                            )
+                            .child(
+                                div()
+                                    .id("review-preview")
+                                    .m_4()
+                                    .p_3()
+                                    .max_h(px(360.))
+                                    .overflow_y_scroll()
+                                    .bg(rgb(BG_SURFACE0))
+                                    .rounded_sm()
+                                    .font_family(MONOSPACE_FONT)
+                                    .text_xs()
+                                    .text_color(rgb(TEXT))
+                                    .children(lines.into_iter().map(|line| {
+                                        div().child(if line.is_empty() {
+                                            " ".to_string()
+                                        } else {
+                                            line
+                                        })
+                                    })),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .gap_2()
+                                    .child(button("clear-review", "Delete All", false).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.clear_review(window, cx);
+                                        }),
+                                    ))
+                                    .child(div().flex_1())
+                                    .child(button("close-review", "Close", false).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.close_review_dialog(window, cx);
+                                        }),
+                                    ))
+                                    .child(button("copy-review", "Copy", false).on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.copy_review(cx);
+                                            this.close_review_dialog(window, cx);
+                                        }),
+                                    ))
+                                    .child(
+                                        button("save-review", "Save review.md...", false).on_click(
+                                            cx.listener(|this, _, window, cx| {
+                                                this.save_review(cx);
+                                                this.close_review_dialog(window, cx);
+                                            }),
+                                        ),
+                                    )
+                                    .child(
+                                        button("send-review", "Send to Terminal", true).on_click(
+                                            cx.listener(|this, _, window, cx| {
+                                                this.send_review_to_terminal(window, cx);
+                                            }),
+                                        ),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
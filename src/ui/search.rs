@@ -0,0 +1,328 @@
+//! Workspace search dialog rendering (see `crate::search`)
+
+use crate::app::SashikiApp;
+use crate::search::{self, SearchRow};
+use crate::theme::*;
+use crate::ui::TextTooltip;
+use crate::ui::virtual_rows::VisibleWindow;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    pub fn render_search_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let state = &self.search;
+        let rows = state.rows();
+        let status = if let Some(error) = &state.error {
+            error.clone()
+        } else if state.query.is_empty() {
+            String::new()
+        } else if state.running {
+            format!("Searching… {} results", state.hits.len())
+        } else if state.hits.is_empty() {
+            "No results".to_string()
+        } else if state.truncated {
+            format!(
+                "Showing the first {} results; refine your search to see the rest",
+                state.hits.len()
+            )
+        } else {
+            format!(
+                "{} results in {} files",
+                state.hits.len(),
+                state.file_count()
+            )
+        };
+
+        // Rows are virtualized; the list has a fixed height, so it is known
+        // before the first layout
+        let list_height = 420.0;
+        let scroll_top = -f32::from(self.search_scroll.offset().y);
+        let window =
+            VisibleWindow::compute(rows.len(), search::ROW_HEIGHT, scroll_top, list_height);
+
+        let list = div()
+            .id("search-results")
+            .h(px(list_height))
+            .overflow_y_scroll()
+            .track_scroll(&self.search_scroll)
+            .text_xs()
+            .child(div().h(px(window.top_padding)))
+            .children(
+                window
+                    .slice(&rows)
+                    .iter()
+                    .zip(window.range.clone())
+                    .map(|(row, i)| match *row {
+                        SearchRow::File { path, hits } => div()
+                            .id(("search-file", i))
+                            .h(px(search::ROW_HEIGHT))
+                            .flex_shrink_0()
+                            .px_4()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .whitespace_nowrap()
+                            .overflow_hidden()
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(path.to_string_lossy().into_owned()),
+                            )
+                            .child(div().text_color(rgb(TEXT_MUTED)).child(format!("{}", hits))),
+                        SearchRow::Hit(index) => {
+                            let hit = &state.hits[index];
+                            let selected = index == state.selected;
+                            div()
+                                .id(("search-hit", i))
+                                .h(px(search::ROW_HEIGHT))
+                                .flex_shrink_0()
+                                .pl_8()
+                                .pr_4()
+                                .flex()
+                                .items_center()
+                                .gap_2()
+                                .whitespace_nowrap()
+                                .overflow_hidden()
+                                .cursor_pointer()
+                                .font_family(MONOSPACE_FONT)
+                                .when(selected, |el| el.bg(rgb(BG_SURFACE1)))
+                                .when(!selected, |el| el.hover(|el| el.bg(rgb(BG_SURFACE0))))
+                                .on_click(cx.listener(
+                                    move |this, event: &gpui::ClickEvent, window, cx| {
+                                        this.select_search_hit(index, cx);
+                                        if event.click_count() > 1 {
+                                            this.open_search_hit(window, cx);
+                                        }
+                                    },
+                                ))
+                                .child(
+                                    div()
+                                        .min_w(px(40.))
+                                        .text_right()
+                                        .text_color(rgb(TEXT_MUTED))
+                                        .child(format!("{}", hit.line)),
+                                )
+                                .child(
+                                    div()
+                                        .text_color(rgb(TEXT))
+                                        .child(hit.preview.trim().replace('\t', " ")),
+                                )
+                        }
+                    }),
+            )
+            .child(div().h(px(window.bottom_padding)))
+            .when(rows.is_empty(), |el| {
+                el.flex()
+                    .items_center()
+                    .justify_center()
+                    .text_color(rgb(TEXT_MUTED))
+                    .child(if state.query.is_empty() {
+                        "Type to search the files of this worktree"
+                    } else if state.running {
+                        "Searching…"
+                    } else {
+                        "No results"
+                    })
+            });
+
+        let toggle = |id: &'static str, label: &'static str, on: bool| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .text_xs()
+                .font_family(MONOSPACE_FONT)
+                .bg(rgb(if on { BLUE } else { BG_SURFACE1 }))
+                .text_color(rgb(if on { BG_BASE } else { TEXT_SECONDARY }))
+                .hover(|el| el.opacity(0.85))
+                .child(label)
+        };
+        let footer_button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_3()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE1))
+                .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                .text_xs()
+                .text_color(rgb(TEXT))
+                .child(label)
+        };
+
+        div()
+            .id("search-container")
+            .track_focus(&self.search_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = event.keystroke.key.as_str();
+                let modifiers = &event.keystroke.modifiers;
+                if key == "escape" {
+                    this.close_search_dialog(window, cx);
+                } else if key == "enter" && modifiers.control {
+                    this.insert_search_hit(window, cx);
+                } else if key == "enter" {
+                    this.open_search_hit(window, cx);
+                } else if key == "up" || key == "down" {
+                    this.move_search_selection(if key == "up" { -1 } else { 1 }, cx);
+                } else if modifiers.alt && key == "r" {
+                    this.toggle_search_regex(cx);
+                } else if modifiers.alt && key == "c" {
+                    this.toggle_search_case(cx);
+                } else if key == "backspace" {
+                    let mut query = this.search.query.clone();
+                    query.pop();
+                    this.set_search_query(query, cx);
+                } else if !modifiers.control
+                    && !modifiers.alt
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    let query = format!("{}{}", this.search.query, text);
+                    this.set_search_query(query, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("search-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_search_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("search-dialog")
+                            .occlude()
+                            .w(px(760.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Search in Worktree"),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_2()
+                                    .flex()
+                                    .items_center()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .flex_1()
+                                            .px_3()
+                                            .py_1()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .text_sm()
+                                            .text_color(if state.query.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .child(if state.query.is_empty() {
+                                                "Type to search".to_string()
+                                            } else {
+                                                format!("{}_", state.query)
+                                            }),
+                                    )
+                                    .child(
+                                        toggle("search-case", "Aa", state.options.case_sensitive)
+                                            .tooltip(TextTooltip::build("Match case (Alt+C)"))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.toggle_search_case(cx);
+                                            })),
+                                    )
+                                    .child(
+                                        toggle("search-regex", ".*", state.options.regex)
+                                            .tooltip(TextTooltip::build(
+                                                "Regular expression (Alt+R)",
+                                            ))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.toggle_search_regex(cx);
+                                            })),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .pb_2()
+                                    .text_xs()
+                                    .text_color(if state.error.is_some() {
+                                        rgb(RED)
+                                    } else if state.truncated {
+                                        rgb(YELLOW)
+                                    } else {
+                                        rgb(TEXT_MUTED)
+                                    })
+                                    .child(status),
+                            )
+                            .child(list)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child("Enter: open · Ctrl+Enter: insert path:line"),
+                                    )
+                                    .child(div().flex_1())
+                                    .child(footer_button("search-insert", "Insert").on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.insert_search_hit(window, cx);
+                                        }),
+                                    ))
+                                    .child(footer_button("search-open", "Open").on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.open_search_hit(window, cx);
+                                        }),
+                                    ))
+                                    .child(footer_button("search-close", "Close").on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.close_search_dialog(window, cx);
+                                        }),
+                                    )),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
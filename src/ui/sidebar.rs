@@ -1,23 +1,48 @@
 //! Sidebar rendering for session list
 
+use crate::activity::unix_now;
 use crate::app::SashikiApp;
+use crate::attention::AttentionReason;
+use crate::busy;
+use crate::dirty::{self, DirtyCounts, DirtyLevel};
+use crate::remote::PullRequestState;
+use crate::repositories;
+use crate::resources::UsageLevel;
 use crate::session::{LayoutMode, SessionStatus};
+use crate::terminal::TerminalTail;
 use crate::theme::*;
-use crate::ui::{render_locked_badge, render_main_badge};
-use gpui::{AnyElement, Context, IntoElement, ParentElement, Styled, div, prelude::*, px, rgb};
+use crate::transcript;
+use crate::ui::{TextTooltip, render_locked_badge, render_main_badge, render_restored_badge};
+use gpui::{
+    AnyElement, Context, Div, IntoElement, ParentElement, Styled, div, prelude::*, px, rgb,
+};
 
 impl SashikiApp {
     pub fn render_sidebar(&self, cx: &Context<Self>) -> AnyElement {
-        let sessions = self.session_manager.sessions();
-        let active_index = self.session_manager.active_index();
-        let layout_mode = self.session_manager.layout_mode();
+        let sessions = self.repo.session_manager.sessions();
+        let active_index = self.repo.session_manager.active_index();
+        let layout_mode = self.repo.session_manager.layout_mode();
 
         div()
-            .w(px(self.sidebar_width))
+            .w(px(self.layout.sidebar_width))
             .h_full()
             .bg(rgb(BG_MANTLE))
             .flex()
             .flex_col()
+            .track_focus(&self.sidebar_focus)
+            .key_context("Sidebar")
+            .on_action(cx.listener(Self::on_sidebar_up))
+            .on_action(cx.listener(Self::on_sidebar_down))
+            .on_action(cx.listener(Self::on_sidebar_activate))
+            .on_action(cx.listener(Self::on_sidebar_toggle_dir))
+            .on_action(cx.listener(Self::on_sidebar_toggle_reviewed))
+            .on_action(cx.listener(Self::on_sidebar_exit))
+            .on_key_down(cx.listener(|this, event: &gpui::KeyDownEvent, _, cx| {
+                this.on_sidebar_key_down(event, cx);
+            }))
+            .when(self.repositories.len() > 1, |el| {
+                el.child(self.render_repository_switcher(cx))
+            })
             .child(self.render_sidebar_header(layout_mode, cx))
             .child(self.render_session_list(sessions, active_index, layout_mode, cx))
             .when(sessions.is_empty(), |this: gpui::Div| {
@@ -32,10 +57,69 @@ impl SashikiApp {
                         .child("No worktrees"),
                 )
             })
+            .child(self.render_stash_section(cx))
             .child(self.render_create_button(cx))
             .into_any_element()
     }
 
+    /// Tabs of the open repositories, shown once there is more than one
+    fn render_repository_switcher(&self, cx: &Context<Self>) -> impl IntoElement {
+        let active = self.repositories.active();
+        div()
+            .id("repository-switcher")
+            .flex()
+            .flex_wrap()
+            .gap_1()
+            .px_2()
+            .py_1()
+            .bg(rgb(BG_BASE))
+            .border_b_1()
+            .border_color(rgb(BG_SURFACE0))
+            .children(
+                self.repositories
+                    .roots()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, root)| {
+                        let shown = active == Some(index);
+                        div()
+                            .id(("repository", index))
+                            .flex()
+                            .items_center()
+                            .gap_1()
+                            .px_2()
+                            .rounded_sm()
+                            .text_xs()
+                            .cursor_pointer()
+                            .when(shown, |el| {
+                                el.bg(rgb(BG_SURFACE0))
+                                    .text_color(rgb(BLUE))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                            })
+                            .when(!shown, |el| {
+                                el.text_color(rgb(TEXT_MUTED))
+                                    .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                            })
+                            .tooltip(TextTooltip::build(root.display().to_string()))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.show_repository(index, cx);
+                            }))
+                            .child(repositories::display_name(root))
+                            .child(
+                                div()
+                                    .id(("repository-close", index))
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .hover(|el| el.text_color(rgb(RED)))
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        cx.stop_propagation();
+                                        this.close_repository(index, cx);
+                                    }))
+                                    .child("×"),
+                            )
+                    }),
+            )
+    }
+
     fn render_sidebar_header(
         &self,
         layout_mode: LayoutMode,
@@ -65,13 +149,13 @@ impl SashikiApp {
                 if layout_mode == LayoutMode::Parallel {
                     format!(
                         "{} selected",
-                        self.session_manager.parallel_sessions().len()
+                        self.repo.session_manager.parallel_sessions().len()
                     )
                 } else {
                     format!(
                         "{}/{}",
-                        self.session_manager.running_session_count(),
-                        self.session_manager.sessions().len()
+                        self.repo.session_manager.running_session_count(),
+                        self.repo.session_manager.sessions().len()
                     )
                 },
             ))
@@ -84,11 +168,70 @@ impl SashikiApp {
         layout_mode: LayoutMode,
         cx: &Context<Self>,
     ) -> impl IntoElement {
+        if !self.repo.session_manager.has_tags() {
+            return div().flex_1().overflow_hidden().children(
+                self.repo
+                    .session_manager
+                    .ordered_sessions()
+                    .map(|(i, session)| {
+                        self.render_session_item(i, session, active_index, layout_mode, cx)
+                    }),
+            );
+        }
+
+        let groups = self.repo.session_manager.grouped_sessions();
         div()
             .flex_1()
             .overflow_hidden()
-            .children(sessions.iter().enumerate().map(|(i, session)| {
-                self.render_session_item(i, session, active_index, layout_mode, cx)
+            .children(groups.into_iter().enumerate().map(|(gi, group)| {
+                let collapsed = self.repo.collapsed_tag_groups.contains(&group.tag);
+                let label = match &group.tag {
+                    Some(tag) => format!("#{}", tag),
+                    None => "Untagged".to_string(),
+                };
+                let toggle_tag = group.tag.clone();
+
+                div()
+                    .id(("tag-group", gi))
+                    .child(
+                        div()
+                            .id(("tag-group-header", gi))
+                            .px_3()
+                            .py_1()
+                            .flex()
+                            .items_center()
+                            .gap_1()
+                            .cursor_pointer()
+                            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                            .text_xs()
+                            .text_color(rgb(TEXT_SECONDARY))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.toggle_tag_group(toggle_tag.clone(), cx);
+                            }))
+                            .child(div().w_3().text_color(rgb(TEXT_MUTED)).child(if collapsed {
+                                "▶"
+                            } else {
+                                "▼"
+                            }))
+                            .child(div().flex_1().truncate().child(label))
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .child(group.indices.len().to_string()),
+                            ),
+                    )
+                    .when(!collapsed, |el| {
+                        el.children(group.indices.iter().filter_map(|&i| {
+                            let session = sessions.get(i)?;
+                            Some(self.render_session_item(
+                                i,
+                                session,
+                                active_index,
+                                layout_mode,
+                                cx,
+                            ))
+                        }))
+                    })
             }))
     }
 
@@ -104,24 +247,119 @@ impl SashikiApp {
         let branch = session.branch().map(|s| s.to_string());
         let is_main = session.is_main();
         let is_locked = session.is_locked();
-        let color = session.color().primary;
+        let read_only = session.is_read_only();
+        let restored = session.is_restored();
+        let color = session.color().on(BG_BASE);
         let status = session.status();
         let visible_in_parallel = session.is_visible_in_parallel();
+        let overlap_count = self
+            .repo
+            .conflict_report
+            .overlap_file_count(session.worktree_path());
 
         let is_selected = match layout_mode {
             LayoutMode::Single => i == active_index,
             LayoutMode::Parallel => visible_in_parallel,
         };
+        let is_nav_cursor = self
+            .repo
+            .list_nav
+            .is_session_selected(session.worktree_path());
+        // The task description, then the title of the program running
+        let tooltip = [
+            session.description().map(str::to_string),
+            session.active_terminal_title(cx),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n");
+        let attention = session.attention().filter(|_| i != active_index);
+        let recording = session.is_recording();
+        let auto_checkpoint = session.auto_checkpoint();
+        let bell_muted = session.is_bell_muted();
+        let has_note = self.notes.noted.contains(session.name());
+        let note_open = self.notes.session.as_deref() == Some(session.name());
+        let timeline_open = self.timeline.session.as_deref() == Some(session.name());
+        // Squash, merge and delete wait while a git operation on this
+        // worktree is running or queued
+        let busy = self.repo.git_queue.involves(session.worktree_path());
+        // Remote links need a branch and an origin on GitHub or GitLab
+        let has_remote = self.repo.remote.is_some() && !is_main && branch.is_some();
+        let pull_request = branch
+            .as_ref()
+            .and_then(|b| self.repo.pull_requests.get(b))
+            .cloned();
+        let has_pull_request = pull_request.is_some();
+        let resources = self.resources.usage(session.worktree_path()).cloned();
+        // How long the worktree has had uncommitted changes, with a
+        // breakdown of them
+        let dirty = session.events().dirty_since().and_then(|since| {
+            let age = std::time::Duration::from_secs((unix_now() - since).max(0) as u64);
+            let summary = dirty::summary(age)?;
+            let counts = self
+                .repo
+                .file_cache
+                .get(session.worktree_path())
+                .map(|files| DirtyCounts::of(&files.changed_files, files.unlisted_untracked))
+                .unwrap_or_default();
+            let details = format!(
+                "Uncommitted changes for {}\n{}",
+                dirty::describe_age(age),
+                counts.describe()
+            );
+            Some((summary, details, self.dirty_thresholds.level(age)))
+        });
+        let minimap = self
+            .minimaps
+            .tail(session.worktree_path())
+            .filter(|_| self.minimaps.enabled && !is_selected)
+            .map(|tail| render_minimap(i, tail));
 
         div()
             .id(format!("session-{}", i))
             .px_3()
             .py_2()
+            .border_l_2()
+            .border_color(rgb(color))
             .cursor_pointer()
             .when(is_selected, |el| el.bg(rgb(BG_SURFACE0)))
+            .when(is_nav_cursor, |el| el.bg(rgb(BG_SURFACE2)))
             .hover(|el| el.bg(rgb(BG_SURFACE1)))
+            .when(!tooltip.is_empty(), |el| {
+                el.tooltip(TextTooltip::build(tooltip))
+            })
+            .on_mouse_down(
+                gpui::MouseButton::Right,
+                cx.listener(move |this, _, window, cx| {
+                    // A session without its directory offers recovery instead
+                    if this
+                        .repo
+                        .session_manager
+                        .sessions()
+                        .get(i)
+                        .is_some_and(|s| s.is_missing())
+                    {
+                        this.open_missing_worktree_dialog(i, window, cx);
+                    } else {
+                        this.open_tags_dialog(i, window, cx);
+                    }
+                }),
+            )
+            .on_drag(
+                DraggedSession {
+                    index: i,
+                    name: session.name().to_string(),
+                    color,
+                },
+                |dragged, _, _, cx| cx.new(|_| dragged.clone()),
+            )
+            .drag_over::<DraggedSession>(|el, _, _, _| el.border_t_2().border_color(rgb(BLUE)))
+            .on_drop(cx.listener(move |this, dragged: &DraggedSession, _, cx| {
+                this.drop_session_on(dragged.index, i, cx);
+            }))
             .on_click(cx.listener(move |this, _, window, cx| {
-                match this.session_manager.layout_mode() {
+                match this.repo.session_manager.layout_mode() {
                     LayoutMode::Single => {
                         this.on_session_selected(i, window, cx);
                     }
@@ -154,25 +392,401 @@ impl SashikiApp {
                             SessionStatus::Focused => rgb(GREEN),
                             SessionStatus::Running => rgb(YELLOW),
                             SessionStatus::Stopped => rgb(TEXT_MUTED),
+                            SessionStatus::Missing => rgb(RED),
                         })
                         .text_sm()
                         .child(status.symbol()),
                 )
             })
             .child(div().w_2().h_2().rounded_full().bg(rgb(color)))
-            .child(self.render_session_name_section(name, branch, is_main, is_locked))
-            .when(layout_mode == LayoutMode::Single && !is_main, |el| {
+            .child(
+                self.render_session_name_section(name, branch, is_main, is_locked, restored)
+                    .when_some(minimap, |el, minimap| el.child(minimap)),
+            )
+            .when_some(dirty, |el, (summary, details, level)| {
                 el.child(
                     div()
-                        .id(format!("delete-{}", i))
+                        .id(format!("dirty-{}", i))
+                        .px_1()
+                        .text_xs()
+                        .text_color(rgb(match level {
+                            DirtyLevel::Normal => TEXT_MUTED,
+                            DirtyLevel::Warn => YELLOW,
+                            DirtyLevel::Alert => RED,
+                        }))
+                        .tooltip(TextTooltip::build(details))
+                        .child(summary),
+                )
+            })
+            .when_some(resources, |el, usage| {
+                el.child(
+                    div()
+                        .id(format!("resources-{}", i))
+                        .px_1()
+                        .text_xs()
+                        .text_color(rgb(match usage.level() {
+                            UsageLevel::Normal => TEXT_MUTED,
+                            UsageLevel::High => YELLOW,
+                            UsageLevel::Critical => RED,
+                        }))
+                        .tooltip(TextTooltip::build(usage.details()))
+                        .child(usage.summary()),
+                )
+            })
+            .when_some(attention, |el, reason| {
+                el.child(
+                    div()
+                        .id(format!("attention-{}", i))
+                        .px_1()
+                        .rounded_sm()
+                        .bg(rgb(match reason {
+                            AttentionReason::Output => BLUE,
+                            AttentionReason::Bell => PEACH,
+                            AttentionReason::Exit(_) => RED,
+                        }))
+                        .text_color(rgb(BG_BASE))
+                        .text_xs()
+                        .tooltip(TextTooltip::build(reason.describe()))
+                        .child("!"),
+                )
+            })
+            .when(overlap_count > 0, |el| {
+                el.child(
+                    div()
+                        .id(format!("conflicts-{}", i))
+                        .px_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .bg(rgb(YELLOW))
+                        .text_color(rgb(BG_BASE))
+                        .text_xs()
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.open_conflicts_dialog(i, cx);
+                        }))
+                        .child(format!("⚠ {}", overlap_count)),
+                )
+            })
+            .when_some(pull_request, |el, pr| {
+                el.child(
+                    div()
+                        .id(format!("pull-request-{}", i))
+                        .px_1()
+                        .rounded_sm()
+                        .cursor_pointer()
+                        .bg(rgb(match pr.state {
+                            PullRequestState::Open => GREEN,
+                            PullRequestState::Merged => MAUVE,
+                            PullRequestState::Closed => RED,
+                        }))
+                        .text_color(rgb(BG_BASE))
+                        .text_xs()
+                        .tooltip(TextTooltip::build(format!(
+                            "{} · {}",
+                            pr.state.label(),
+                            pr.title
+                        )))
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.open_pull_request(i, cx);
+                        }))
+                        .child(format!("#{}", pr.number)),
+                )
+            })
+            .when(has_remote, |el| {
+                el.child(
+                    div()
+                        .id(format!("remote-{}", i))
                         .px_1()
                         .cursor_pointer()
                         .text_xs()
                         .text_color(rgb(TEXT_MUTED))
-                        .hover(|el| el.text_color(rgb(RED)))
+                        .hover(|el| el.text_color(rgb(BLUE)))
+                        .tooltip(TextTooltip::build("Open branch on remote"))
                         .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
-                            this.open_delete_dialog(i, cx);
+                            cx.stop_propagation();
+                            this.open_branch_on_remote(i, cx);
                         }))
+                        .child("↗"),
+                )
+            })
+            .when(has_remote && !has_pull_request, |el| {
+                el.child(
+                    div()
+                        .id(format!("create-pr-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .hover(|el| el.text_color(rgb(GREEN)))
+                        .tooltip(TextTooltip::build("Create pull request"))
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.open_pull_request(i, cx);
+                        }))
+                        .child("⇡"),
+                )
+            })
+            .child(
+                div()
+                    .id(format!("read-only-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(if read_only {
+                        rgb(YELLOW)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .hover(|el| el.text_color(rgb(YELLOW)))
+                    .tooltip(TextTooltip::build(if read_only {
+                        "Read-only · click to allow changes"
+                    } else {
+                        "Mark read-only"
+                    }))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.toggle_session_read_only(i, cx);
+                    }))
+                    .child(if read_only { "🔒" } else { "🔓" }),
+            )
+            .child(
+                div()
+                    .id(format!("snapshot-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(rgb(TEXT_MUTED))
+                    .hover(|el| el.text_color(rgb(GREEN)))
+                    .tooltip(TextTooltip::build("Snapshot working tree"))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.take_snapshot(i, cx);
+                    }))
+                    .child("◉"),
+            )
+            .child(
+                div()
+                    .id(format!("snapshots-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(rgb(TEXT_MUTED))
+                    .hover(|el| el.text_color(rgb(BLUE)))
+                    .tooltip(TextTooltip::build("Restore snapshot…"))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.open_snapshots_dialog(i, cx);
+                    }))
+                    .child("↶"),
+            )
+            .child(
+                div()
+                    .id(format!("checkpoints-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(if auto_checkpoint {
+                        rgb(GREEN)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .hover(|el| el.text_color(rgb(BLUE)))
+                    .tooltip(TextTooltip::build(if auto_checkpoint {
+                        "Auto checkpoints on · Checkpoints…"
+                    } else {
+                        "Checkpoints…"
+                    }))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.open_checkpoints_dialog(i, cx);
+                    }))
+                    .child("⏱"),
+            )
+            .when(transcript::SUPPORTED, |el| {
+                el.child(
+                    div()
+                        .id(format!("transcript-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(if recording { rgb(RED) } else { rgb(TEXT_MUTED) })
+                        .hover(|el| el.text_color(rgb(BLUE)))
+                        .tooltip(TextTooltip::build(if recording {
+                            "Recording output · Transcript…"
+                        } else {
+                            "Transcript…"
+                        }))
+                        .on_click(cx.listener(
+                            move |this, _event: &gpui::ClickEvent, window, cx| {
+                                cx.stop_propagation();
+                                this.open_transcript_dialog(i, window, cx);
+                            },
+                        ))
+                        .child(if recording { "⏺" } else { "≡" }),
+                )
+            })
+            .child(
+                div()
+                    .id(format!("bell-mute-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(if bell_muted {
+                        rgb(PEACH)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .hover(|el| el.text_color(rgb(PEACH)))
+                    .tooltip(TextTooltip::build(if bell_muted {
+                        "Bell muted · click to unmute"
+                    } else {
+                        "Mute bell"
+                    }))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.toggle_session_bell_mute(i, cx);
+                    }))
+                    .child(if bell_muted { "🔕" } else { "🔔" }),
+            )
+            .when(!self.agents.profiles.is_empty(), |el| {
+                el.child(self.render_agent_button(format!("agent-{}", i), i, "▶", cx))
+            })
+            .child(
+                div()
+                    .id(format!("notes-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(if has_note || note_open {
+                        rgb(YELLOW)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .hover(|el| el.text_color(rgb(YELLOW)))
+                    .tooltip(TextTooltip::build(if note_open {
+                        "Close notes"
+                    } else if has_note {
+                        "Has notes · Notes…"
+                    } else {
+                        "Notes…"
+                    }))
+                    .on_click(
+                        cx.listener(move |this, _event: &gpui::ClickEvent, window, cx| {
+                            cx.stop_propagation();
+                            this.toggle_session_notes(i, window, cx);
+                        }),
+                    )
+                    .child(if has_note { "📝" } else { "✎" }),
+            )
+            .child(
+                div()
+                    .id(format!("timeline-{}", i))
+                    .px_1()
+                    .cursor_pointer()
+                    .text_xs()
+                    .text_color(if timeline_open {
+                        rgb(BLUE)
+                    } else {
+                        rgb(TEXT_MUTED)
+                    })
+                    .hover(|el| el.text_color(rgb(BLUE)))
+                    .tooltip(TextTooltip::build(if timeline_open {
+                        "Close activity"
+                    } else {
+                        "Activity…"
+                    }))
+                    .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                        cx.stop_propagation();
+                        this.toggle_session_timeline(i, cx);
+                    }))
+                    .child("🕘"),
+            )
+            .when(!is_main && !read_only, |el| {
+                el.child(
+                    div()
+                        .id(format!("sync-files-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .hover(|el| el.text_color(rgb(BLUE)))
+                        .tooltip(TextTooltip::build("Sync files from main…"))
+                        .on_click(cx.listener(move |this, _event: &gpui::ClickEvent, _, cx| {
+                            cx.stop_propagation();
+                            this.open_sync_files_dialog(i, cx);
+                        }))
+                        .child("⇄"),
+                )
+            })
+            .when(!is_main && !read_only, |el| {
+                el.child(
+                    div()
+                        .id(format!("squash-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .when(busy, |el| el.opacity(0.4))
+                        .when(!busy, |el| {
+                            el.hover(|el| el.text_color(rgb(MAUVE)))
+                                .on_click(cx.listener(
+                                    move |this, _event: &gpui::ClickEvent, window, cx| {
+                                        cx.stop_propagation();
+                                        this.open_squash_dialog(i, window, cx);
+                                    },
+                                ))
+                        })
+                        .tooltip(TextTooltip::build(if busy {
+                            "Waiting for a git operation"
+                        } else {
+                            "Squash branch…"
+                        }))
+                        .child("⇊"),
+                )
+            })
+            .when(!is_main, |el| {
+                el.child(
+                    div()
+                        .id(format!("merge-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .when(busy, |el| el.opacity(0.4))
+                        .when(!busy, |el| {
+                            el.hover(|el| el.text_color(rgb(GREEN)))
+                                .on_click(cx.listener(
+                                    move |this, _event: &gpui::ClickEvent, _, cx| {
+                                        cx.stop_propagation();
+                                        this.open_merge_dialog(i, cx);
+                                    },
+                                ))
+                        })
+                        .tooltip(TextTooltip::build(if busy {
+                            "Waiting for a git operation"
+                        } else {
+                            "Merge into main…"
+                        }))
+                        .child("⤵"),
+                )
+            })
+            .when(layout_mode == LayoutMode::Single && !is_main, |el| {
+                el.child(
+                    div()
+                        .id(format!("delete-{}", i))
+                        .px_1()
+                        .cursor_pointer()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .when(busy, |el| el.opacity(0.4))
+                        .when(!busy, |el| {
+                            el.hover(|el| el.text_color(rgb(RED))).on_click(cx.listener(
+                                move |this, _event: &gpui::ClickEvent, _, cx| {
+                                    this.open_delete_dialog(i, cx);
+                                },
+                            ))
+                        })
                         .child("×"),
                 )
             })
@@ -184,7 +798,8 @@ impl SashikiApp {
         branch: Option<String>,
         is_main: bool,
         is_locked: bool,
-    ) -> impl IntoElement {
+        restored: bool,
+    ) -> Div {
         div()
             .flex_1()
             .flex()
@@ -198,7 +813,8 @@ impl SashikiApp {
                     .gap_2()
                     .child(div().text_color(rgb(TEXT)).text_sm().truncate().child(name))
                     .when(is_main, |el| el.child(render_main_badge()))
-                    .when(is_locked, |el| el.child(render_locked_badge())),
+                    .when(is_locked, |el| el.child(render_locked_badge()))
+                    .when(restored, |el| el.child(render_restored_badge())),
             )
             .when_some(branch, |el, b| {
                 el.child(
@@ -211,6 +827,127 @@ impl SashikiApp {
             })
     }
 
+    fn render_stash_section(&self, cx: &Context<Self>) -> impl IntoElement {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        // Stashing and applying change the worktree; dropping does not
+        let read_only = self
+            .repo
+            .session_manager
+            .active_session()
+            .is_some_and(|s| s.is_read_only());
+
+        div()
+            .border_t_1()
+            .border_color(rgb(BG_SURFACE0))
+            .flex()
+            .flex_col()
+            .child(
+                div()
+                    .h_8()
+                    .px_3()
+                    .flex()
+                    .items_center()
+                    .justify_between()
+                    .child(
+                        div()
+                            .text_color(rgb(BLUE))
+                            .text_xs()
+                            .font_weight(gpui::FontWeight::BOLD)
+                            .child(format!("Stashes ({})", self.repo.stashes.len())),
+                    )
+                    .when(!read_only, |el| {
+                        el.child(
+                            div()
+                                .id("stash-push-btn")
+                                .px_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                .text_xs()
+                                .text_color(rgb(GREEN))
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.open_stash_dialog(window, cx);
+                                }))
+                                .child("+ Stash"),
+                        )
+                    }),
+            )
+            .child(
+                div()
+                    .id("stash-list")
+                    .max_h_48()
+                    .overflow_y_scroll()
+                    .children(self.repo.stashes.iter().map(|stash| {
+                        let index = stash.index;
+                        let detail = match &stash.branch {
+                            Some(branch) => {
+                                format!("{} · ⎇ {}", format_age(now - stash.timestamp), branch)
+                            }
+                            None => format_age(now - stash.timestamp),
+                        };
+
+                        div()
+                            .id(("stash", index))
+                            .px_3()
+                            .py_1()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .flex()
+                                    .flex_col()
+                                    .overflow_hidden()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .text_xs()
+                                            .truncate()
+                                            .child(stash.message.clone()),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .text_xs()
+                                            .truncate()
+                                            .child(detail),
+                                    ),
+                            )
+                            .when(!read_only, |el| {
+                                el.child(render_stash_button(
+                                    ("stash-apply", index),
+                                    "apply",
+                                    BLUE,
+                                    cx.listener(move |this, _, _, cx| {
+                                        this.apply_stash(index, false, cx);
+                                    }),
+                                ))
+                                .child(render_stash_button(
+                                    ("stash-pop", index),
+                                    "pop",
+                                    GREEN,
+                                    cx.listener(move |this, _, _, cx| {
+                                        this.apply_stash(index, true, cx);
+                                    }),
+                                ))
+                            })
+                            .child(render_stash_button(
+                                ("stash-drop", index),
+                                "×",
+                                RED,
+                                cx.listener(move |this, _, _, cx| {
+                                    this.open_stash_drop_dialog(index, cx);
+                                }),
+                            ))
+                    })),
+            )
+    }
+
     fn render_create_button(&self, cx: &Context<Self>) -> impl IntoElement {
         div()
             .border_t_1()
@@ -257,3 +994,82 @@ impl SashikiApp {
             )
     }
 }
+
+/// Session row being dragged to a new place in the list
+#[derive(Clone)]
+pub struct DraggedSession {
+    index: usize,
+    name: String,
+    color: u32,
+}
+
+impl Render for DraggedSession {
+    fn render(&mut self, _window: &mut gpui::Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_3()
+            .py_1()
+            .rounded_sm()
+            .border_l_2()
+            .border_color(rgb(self.color))
+            .bg(rgb(BG_SURFACE1))
+            .text_sm()
+            .text_color(rgb(TEXT))
+            .child(self.name.clone())
+    }
+}
+
+/// Small text button used on stash rows
+fn render_stash_button(
+    id: impl Into<gpui::ElementId>,
+    label: &'static str,
+    hover_color: u32,
+    on_click: impl Fn(&gpui::ClickEvent, &mut gpui::Window, &mut gpui::App) + 'static,
+) -> impl IntoElement {
+    div()
+        .id(id)
+        .px_1()
+        .cursor_pointer()
+        .text_xs()
+        .text_color(rgb(TEXT_MUTED))
+        .hover(move |el| el.text_color(rgb(hover_color)))
+        .on_click(on_click)
+        .child(label)
+}
+
+/// Human-readable age for a duration in seconds ("5m ago")
+pub(crate) fn format_age(seconds: i64) -> String {
+    match seconds.max(0) {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86_400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86_400),
+    }
+}
+
+/// Rows drawn in a terminal thumbnail
+const THUMBNAIL_ROWS: usize = 6;
+
+/// Last rows of a session's terminal in a tiny font, brighter while it
+/// prints; hovering shows more of them
+fn render_minimap(i: usize, tail: &TerminalTail) -> impl IntoElement {
+    let recent = tail
+        .last_output
+        .is_some_and(|last| last.elapsed() < busy::RECENT_OUTPUT);
+    let rows = &tail.rows[tail.rows.len().saturating_sub(THUMBNAIL_ROWS)..];
+    div()
+        .id(("minimap", i))
+        .mt_1()
+        .px_1()
+        .py_0p5()
+        .rounded_sm()
+        .bg(rgb(BG_MANTLE))
+        .font_family(MONOSPACE_FONT)
+        .text_size(px(6.0))
+        .line_height(px(7.0))
+        .text_color(rgb(if recent { TEXT_SECONDARY } else { TEXT_MUTED }))
+        .tooltip(TextTooltip::build(tail.rows.join("\n")))
+        .children(
+            rows.iter()
+                .map(|row| div().h(px(7.0)).truncate().child(row.clone())),
+        )
+}
@@ -0,0 +1,558 @@
+//! Snapshot dialogs rendering: the list of snapshots and checkpoints, restoring
+//! one and the diff since one (see `crate::app::snapshots`)
+
+use crate::app::SashikiApp;
+use crate::dialog::SnapshotKind;
+use crate::git::Snapshot;
+use crate::theme::*;
+use crate::ui::TextTooltip;
+use crate::ui::sidebar::format_age;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+/// Lines of a snapshot diff shown before the rest is cut off
+pub(crate) const MAX_SNAPSHOT_DIFF_LINES: usize = 2000;
+
+impl SashikiApp {
+    pub fn render_snapshots_dialog(
+        &self,
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshots: &[Snapshot],
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let session = self.repo.session_manager.sessions().get(target_index);
+        let session_name = session.map(|s| s.name().to_string()).unwrap_or_default();
+        let read_only = session.is_some_and(|s| s.is_read_only());
+        let auto_checkpoint = session.is_some_and(|s| s.auto_checkpoint());
+        let (title, empty) = match kind {
+            SnapshotKind::Manual => ("Snapshots", "No snapshots yet"),
+            SnapshotKind::Checkpoint => ("Checkpoints", "No checkpoints yet"),
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+
+        let list = div()
+            .id("snapshot-list")
+            .max_h_96()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .when(snapshots.is_empty(), |el| {
+                el.child(
+                    div()
+                        .px_4()
+                        .py_3()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child(empty),
+                )
+            })
+            .children(snapshots.iter().enumerate().map(|(i, snapshot)| {
+                let restore = snapshot.clone();
+                let compare = snapshot.clone();
+                let age = format_age(now - snapshot.timestamp);
+                let when = match kind {
+                    SnapshotKind::Checkpoint if !snapshot.subject.is_empty() => {
+                        format!("{} · {}", age, snapshot.subject)
+                    }
+                    _ => age,
+                };
+                let stat = if snapshot.stat.is_empty() {
+                    "no changes".to_string()
+                } else {
+                    snapshot.stat.clone()
+                };
+
+                div()
+                    .id(("snapshot", i))
+                    .px_4()
+                    .py_2()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                    .child(
+                        div()
+                            .flex_1()
+                            .flex()
+                            .flex_col()
+                            .overflow_hidden()
+                            .child(div().text_color(rgb(TEXT)).text_xs().truncate().child(when))
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .text_xs()
+                                    .truncate()
+                                    .child(stat),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id(("snapshot-diff", i))
+                            .px_2()
+                            .py_1()
+                            .cursor_pointer()
+                            .rounded_sm()
+                            .text_xs()
+                            .text_color(rgb(TEXT_SECONDARY))
+                            .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                            .tooltip(TextTooltip::build(
+                                "Changes in the working tree since this point",
+                            ))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.show_snapshot_diff(compare.clone(), cx);
+                            }))
+                            .child("Diff"),
+                    )
+                    .when(!read_only, |el| {
+                        el.child(
+                            div()
+                                .id(("snapshot-restore", i))
+                                .px_2()
+                                .py_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .text_xs()
+                                .text_color(rgb(BLUE))
+                                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.request_restore_snapshot(restore.clone(), cx);
+                                }))
+                                .child("Restore…"),
+                        )
+                    })
+            }));
+
+        div()
+            .id("snapshots-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.close_snapshot_dialog(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("snapshots-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_snapshot_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("snapshots-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .justify_between()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child(format!("{}: {}", title, session_name)),
+                                    )
+                                    .when(kind == SnapshotKind::Manual, |el| {
+                                        el.child(
+                                            div()
+                                                .id("snapshot-take")
+                                                .px_2()
+                                                .py_1()
+                                                .cursor_pointer()
+                                                .rounded_sm()
+                                                .text_xs()
+                                                .text_color(rgb(GREEN))
+                                                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                                .on_click(cx.listener(move |this, _, _, cx| {
+                                                    this.take_snapshot(target_index, cx);
+                                                }))
+                                                .child("+ Snapshot now"),
+                                        )
+                                    })
+                                    .when(kind == SnapshotKind::Checkpoint, |el| {
+                                        el.child(
+                                            div()
+                                                .id("checkpoint-auto")
+                                                .px_2()
+                                                .py_1()
+                                                .cursor_pointer()
+                                                .rounded_sm()
+                                                .text_xs()
+                                                .text_color(rgb(if auto_checkpoint {
+                                                    GREEN
+                                                } else {
+                                                    TEXT_MUTED
+                                                }))
+                                                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                                .on_click(cx.listener(move |this, _, _, cx| {
+                                                    this.toggle_session_auto_checkpoint(
+                                                        target_index,
+                                                        cx,
+                                                    );
+                                                }))
+                                                .child(if auto_checkpoint {
+                                                    "Auto: on"
+                                                } else {
+                                                    "Auto: off"
+                                                }),
+                                        )
+                                    }),
+                            )
+                            .child(list)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .child(
+                                        div()
+                                            .id("close-snapshots")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_snapshot_dialog(cx);
+                                            }))
+                                            .child("Close"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_snapshot_restore_dialog(
+        &self,
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshot: &Snapshot,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let session_name = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .map(|s| s.name().to_string())
+            .unwrap_or_default();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let (question, warning) = match kind {
+            SnapshotKind::Manual => (
+                format!(
+                    "Restore {} to the snapshot from {}?",
+                    session_name,
+                    format_age(now - snapshot.timestamp)
+                ),
+                "The index and working tree are replaced with the snapshot. Uncommitted changes made since are lost unless you take another snapshot first.",
+            ),
+            SnapshotKind::Checkpoint => (
+                format!(
+                    "Restore {} to the checkpoint from {}?",
+                    session_name,
+                    format_age(now - snapshot.timestamp)
+                ),
+                "The index and working tree are replaced with the checkpoint. The current state is saved as a snapshot first, so this can be undone from Snapshots.",
+            ),
+        };
+
+        div()
+            .id("snapshot-restore-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.open_snapshot_list(target_index, kind, cx);
+                } else if key == "enter" {
+                    this.confirm_restore_snapshot(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("snapshot-restore-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_snapshot_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("snapshot-restore-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(YELLOW))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Restore Snapshot"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(question))
+                                    .child(
+                                        div()
+                                            .font_family(MONOSPACE_FONT)
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child(snapshot.stat.clone()),
+                                    )
+                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(warning)),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-snapshot-restore")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(move |this, _, _, cx| {
+                                                this.open_snapshot_list(target_index, kind, cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-snapshot-restore")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(YELLOW))
+                                            .hover(|el| el.bg(rgb(PEACH)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_restore_snapshot(cx);
+                                            }))
+                                            .child("Restore"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    /// Working tree changes since a snapshot or checkpoint, as a unified diff
+    pub fn render_snapshot_diff_dialog(
+        &self,
+        target_index: usize,
+        kind: SnapshotKind,
+        snapshot: &Snapshot,
+        diff: &str,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let title = format!(
+            "Changes since the {} from {}",
+            match kind {
+                SnapshotKind::Manual => "snapshot",
+                SnapshotKind::Checkpoint => "checkpoint",
+            },
+            format_age(now - snapshot.timestamp)
+        );
+        let total = diff.lines().count();
+
+        let body = div()
+            .id("snapshot-diff-body")
+            .max_h_96()
+            .overflow_y_scroll()
+            .px_4()
+            .py_2()
+            .font_family(MONOSPACE_FONT)
+            .text_xs()
+            .when(diff.is_empty(), |el| {
+                el.text_color(rgb(TEXT_MUTED))
+                    .child("No changes since this point")
+            })
+            .children(diff.lines().take(MAX_SNAPSHOT_DIFF_LINES).map(|line| {
+                div()
+                    .whitespace_nowrap()
+                    .text_color(rgb(diff_line_color(line)))
+                    .child(line.to_string())
+            }))
+            .when(total > MAX_SNAPSHOT_DIFF_LINES, |el| {
+                el.child(
+                    div()
+                        .pt_1()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child(format!("… {} more lines", total - MAX_SNAPSHOT_DIFF_LINES)),
+                )
+            });
+
+        div()
+            .id("snapshot-diff-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, _, cx| {
+                if event.keystroke.key == "escape" {
+                    this.open_snapshot_list(target_index, kind, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("snapshot-diff-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_snapshot_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("snapshot-diff-dialog")
+                            .occlude()
+                            .w(px(720.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child(title),
+                            )
+                            .child(body)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .child(
+                                        div()
+                                            .id("snapshot-diff-back")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(move |this, _, _, cx| {
+                                                this.open_snapshot_list(target_index, kind, cx);
+                                            }))
+                                            .child("Back"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
+
+/// Colour of a line of unified diff output
+pub(crate) fn diff_line_color(line: &str) -> u32 {
+    if line.starts_with("diff --git") {
+        YELLOW
+    } else if line.starts_with("@@") {
+        BLUE
+    } else if line.starts_with('+') && !line.starts_with("+++") {
+        GREEN
+    } else if line.starts_with('-') && !line.starts_with("---") {
+        RED
+    } else {
+        TEXT_SECONDARY
+    }
+}
@@ -0,0 +1,209 @@
+//! Soft wrapping of long lines in the file view
+//!
+//! A line wider than the view is shown on several rows. A row breaks after
+//! the last space that fits, or inside a word that is wider than a row by
+//! itself. Rows after the first are indented like the line they continue
+//! (a hanging indent of at most half a row). Widths are counted in columns
+//! as the tab expansion counts them, wide CJK characters taking two, and
+//! lines are wrapped after their tabs are expanded.
+//!
+//! `WrapLayout` maps logical lines to rows and back, so line numbers, going
+//! to a line and the virtualized rows all keep working on logical lines.
+
+use super::tab_stops::char_columns;
+use std::ops::Range;
+
+/// Narrowest row wrapped to, so a tiny view still makes progress
+const MIN_COLUMNS: usize = 8;
+
+/// Columns of the indent repeated on the rows after the first
+pub fn hanging_indent(line: &str, columns: usize) -> usize {
+    let indent: usize = line
+        .chars()
+        .take_while(|c| *c == ' ')
+        .map(char_columns)
+        .sum();
+    indent.min(columns.max(MIN_COLUMNS) / 2)
+}
+
+/// Byte ranges of `line` (tabs expanded) shown on each row when rows are
+/// `columns` wide; an empty line is one empty row
+pub fn wrap_line(line: &str, columns: usize) -> Vec<Range<usize>> {
+    let columns = columns.max(MIN_COLUMNS);
+    let indent = hanging_indent(line, columns);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    // Where the row can break: after the last space on it
+    let mut last_break = None;
+    for (at, c) in line.char_indices() {
+        let limit = if rows.is_empty() {
+            columns
+        } else {
+            columns - indent
+        };
+        let char_width = char_columns(c);
+        if width + char_width > limit && at > start {
+            let end = last_break.filter(|&end| end > start).unwrap_or(at);
+            rows.push(start..end);
+            width = line[end..at].chars().map(char_columns).sum();
+            start = end;
+            last_break = None;
+        }
+        width += char_width;
+        if c == ' ' {
+            last_break = Some(at + 1);
+        }
+    }
+    rows.push(start..line.len());
+    rows
+}
+
+/// Where the rows of each line start once the lines are wrapped to a width
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrapLayout {
+    columns: usize,
+    /// First row of each line, then the row count
+    starts: Vec<usize>,
+}
+
+impl WrapLayout {
+    /// Wrap `lines` (tabs expanded) to rows `columns` wide
+    pub fn compute<'a>(lines: impl IntoIterator<Item = &'a str>, columns: usize) -> Self {
+        let mut starts = vec![0];
+        let mut rows = 0;
+        for line in lines {
+            rows += wrap_line(line, columns).len();
+            starts.push(rows);
+        }
+        Self { columns, starts }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.starts[self.starts.len() - 1]
+    }
+
+    /// First row of `line` (0-based; past the end gives the row count)
+    pub fn first_row(&self, line: usize) -> usize {
+        self.starts[line.min(self.line_count())]
+    }
+
+    /// Line shown on `row`, and which of its rows that is
+    pub fn line_at(&self, row: usize) -> (usize, usize) {
+        let line = self
+            .starts
+            .partition_point(|&start| start <= row)
+            .saturating_sub(1)
+            .min(self.line_count().saturating_sub(1));
+        (line, row.saturating_sub(self.starts[line]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(line: &str, columns: usize) -> Vec<&str> {
+        wrap_line(line, columns)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_rows_break_after_spaces() {
+        assert_eq!(
+            rows("the quick brown fox jumps", 10),
+            ["the quick ", "brown fox ", "jumps"]
+        );
+        // A word longer than a row is cut
+        assert_eq!(
+            rows("abcdefghijklmnopqrstu", 8),
+            ["abcdefgh", "ijklmnop", "qrstu"]
+        );
+        assert_eq!(rows("short", 80), ["short"]);
+        assert_eq!(rows("", 80), [""]);
+    }
+
+    #[test]
+    fn test_continuation_rows_keep_the_indent() {
+        // Rows after the first have room for 20 - 4 columns
+        let line = "    let value = compute(first, second);";
+        assert_eq!(hanging_indent(line, 20), 4);
+        assert_eq!(
+            rows(line, 20),
+            ["    let value = ", "compute(first, ", "second);"]
+        );
+        // The indent never takes more than half a row
+        assert_eq!(hanging_indent(&" ".repeat(30), 20), 10);
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        // 12 wide characters in rows 10 columns wide: 5 per row
+        assert_eq!(
+            rows("日本語のテキストを折り返す", 10),
+            ["日本語のテ", "キストを折", "り返す"]
+        );
+        // A wide character never straddles two rows
+        assert_eq!(rows("abcdefghi日本", 10), ["abcdefghi", "日本"]);
+    }
+
+    #[test]
+    fn test_layout_maps_lines_to_rows_and_back() {
+        let lines = [
+            "short",
+            "日本語のテキストを折り返す",
+            "",
+            "a b c d e f g h i j k",
+        ];
+        let layout = WrapLayout::compute(lines, 10);
+        assert_eq!(layout.line_count(), 4);
+        // 1 + 3 + 1 + 3 rows
+        assert_eq!(layout.row_count(), 8);
+        assert_eq!(
+            (0..4)
+                .map(|line| layout.first_row(line))
+                .collect::<Vec<_>>(),
+            [0, 1, 4, 5]
+        );
+        let mapped: Vec<_> = (0..8).map(|row| layout.line_at(row)).collect();
+        assert_eq!(
+            mapped,
+            [
+                (0, 0),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (2, 0),
+                (3, 0),
+                (3, 1),
+                (3, 2)
+            ]
+        );
+        // Rows past the end stay on the last line
+        assert_eq!(layout.line_at(20).0, 3);
+        assert_eq!(layout.first_row(9), 8);
+    }
+
+    #[test]
+    fn test_layout_rewraps_when_the_width_changes() {
+        let lines = ["the quick brown fox jumps over the lazy dog", "ok"];
+        let narrow = WrapLayout::compute(lines, 10);
+        let wide = WrapLayout::compute(lines, 40);
+        assert_eq!((narrow.row_count(), wide.row_count()), (6, 3));
+        assert_eq!(narrow.first_row(1), 5);
+        assert_eq!(wide.first_row(1), 2);
+        assert_eq!(wide.line_at(2), (1, 0));
+        assert_eq!(WrapLayout::compute(lines, 80).row_count(), 2);
+        assert_eq!(WrapLayout::compute([], 80).row_count(), 0);
+    }
+}
@@ -0,0 +1,355 @@
+//! Squash dialog rendering: squashing or dropping the commits of a session's
+//! branch (see `crate::app::squash`)
+
+use crate::app::SashikiApp;
+use crate::dialog::SquashMode;
+use crate::git::BranchCommit;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, rgb,
+    rgba,
+};
+use std::collections::HashSet;
+
+impl SashikiApp {
+    pub fn render_squash_dialog(
+        &self,
+        target_index: usize,
+        commits: &[BranchCommit],
+        mode: SquashMode,
+        dropped: &HashSet<String>,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let session_name = self
+            .repo
+            .session_manager
+            .sessions()
+            .get(target_index)
+            .map(|s| s.name().to_string())
+            .unwrap_or_default();
+        let input_value = self.squash_message_input.clone();
+        let can_confirm = match mode {
+            SquashMode::SquashAll => !commits.is_empty() && !input_value.trim().is_empty(),
+            SquashMode::Drop => !dropped.is_empty(),
+        };
+        let (summary, confirm_label) = match mode {
+            SquashMode::SquashAll => (
+                format!("Combine {} commits into one:", commits.len()),
+                "Squash",
+            ),
+            SquashMode::Drop => (
+                format!("Drop {} of {} commits:", dropped.len(), commits.len()),
+                "Drop",
+            ),
+        };
+
+        let mode_tab = |id: &'static str, tab: SquashMode, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .py_1()
+                .cursor_pointer()
+                .rounded_sm()
+                .text_xs()
+                .text_color(rgb(TEXT))
+                .when(mode == tab, |el| el.bg(rgb(BG_SURFACE1)))
+                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.set_squash_mode(tab, cx);
+                }))
+                .child(label)
+        };
+
+        let list = div()
+            .id("squash-commit-list")
+            .max_h_64()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .when(commits.is_empty(), |el| {
+                el.child(
+                    div()
+                        .py_2()
+                        .text_xs()
+                        .text_color(rgb(TEXT_MUTED))
+                        .child("No commits since main"),
+                )
+            })
+            .children(commits.iter().enumerate().map(|(i, commit)| {
+                let is_dropped = dropped.contains(&commit.hash);
+                let hash = commit.hash.clone();
+
+                div()
+                    .id(("squash-commit", i))
+                    .py_0p5()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .text_xs()
+                    .when(mode == SquashMode::Drop, |el| {
+                        el.cursor_pointer()
+                            .hover(|el| el.bg(rgb(BG_SURFACE0)))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.toggle_squash_drop(&hash, cx);
+                            }))
+                            .child(
+                                div()
+                                    .text_color(if is_dropped {
+                                        rgb(RED)
+                                    } else {
+                                        rgb(TEXT_MUTED)
+                                    })
+                                    .child(if is_dropped { "☑" } else { "☐" }),
+                            )
+                    })
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .font_family(MONOSPACE_FONT)
+                            .text_color(rgb(TEXT_MUTED))
+                            .child(commit.short_hash().to_string()),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .truncate()
+                            .text_color(if is_dropped && mode == SquashMode::Drop {
+                                rgb(TEXT_MUTED)
+                            } else {
+                                rgb(TEXT)
+                            })
+                            .when(is_dropped && mode == SquashMode::Drop, |el| {
+                                el.line_through()
+                            })
+                            .child(commit.subject.clone()),
+                    )
+            }));
+
+        div()
+            .id("squash-container")
+            .track_focus(&self.squash_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(move |this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_squash_dialog(window, cx);
+                } else if key == "enter" {
+                    this.confirm_squash(window, cx);
+                } else if mode != SquashMode::SquashAll {
+                    // The message is only edited when squashing
+                } else if key == "backspace" {
+                    this.squash_message_input.pop();
+                    cx.notify();
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.squash_message_input.push_str(text);
+                    cx.notify();
+                }
+            }))
+            .child(
+                div()
+                    .id("squash-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_squash_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("squash-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .justify_between()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child(format!("Squash Branch: {}", session_name)),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .gap_1()
+                                            .child(mode_tab(
+                                                "squash-mode-all",
+                                                SquashMode::SquashAll,
+                                                "Squash all",
+                                            ))
+                                            .child(mode_tab(
+                                                "squash-mode-drop",
+                                                SquashMode::Drop,
+                                                "Drop",
+                                            )),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child(summary),
+                                    )
+                                    .child(list)
+                                    .when(mode == SquashMode::SquashAll, |el| {
+                                        el.child(
+                                            div()
+                                                .text_color(rgb(TEXT_SECONDARY))
+                                                .text_sm()
+                                                .child("Commit message:"),
+                                        )
+                                        .child(
+                                            div()
+                                                .id("squash-message-input")
+                                                .w_full()
+                                                .px_3()
+                                                .py_2()
+                                                .bg(rgb(BG_SURFACE0))
+                                                .border_1()
+                                                .border_color(rgb(BLUE))
+                                                .rounded_sm()
+                                                .cursor_text()
+                                                .text_color(rgb(TEXT))
+                                                .text_sm()
+                                                .child(format!("{}_", input_value)),
+                                        )
+                                    })
+                                    .child(div().text_color(rgb(YELLOW)).text_xs().child(
+                                        "The branch history is rewritten; pushed commits will need a force push.",
+                                    )),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-squash")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.close_squash_dialog(window, cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-squash")
+                                            .px_4()
+                                            .py_2()
+                                            .rounded_sm()
+                                            .text_xs()
+                                            .when(can_confirm, |el| {
+                                                el.cursor_pointer()
+                                                    .bg(rgb(GREEN))
+                                                    .hover(|el| el.bg(rgb(TEAL)))
+                                                    .text_color(rgb(BG_BASE))
+                                                    .on_click(cx.listener(|this, _, window, cx| {
+                                                        this.confirm_squash(window, cx);
+                                                    }))
+                                            })
+                                            .when(!can_confirm, |el| {
+                                                el.bg(rgb(BG_SURFACE0)).text_color(rgb(TEXT_MUTED))
+                                            })
+                                            .child(confirm_label),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_squashing_dialog(&self) -> AnyElement {
+        div()
+            .id("squashing-dialog-container")
+            .absolute()
+            .inset_0()
+            .child(
+                div()
+                    .id("squashing-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY)),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("squashing-dialog")
+                            .occlude()
+                            .w_64()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(YELLOW))
+                            .rounded_md()
+                            .shadow_lg()
+                            .p_4()
+                            .flex()
+                            .flex_col()
+                            .items_center()
+                            .gap_3()
+                            .child(
+                                div()
+                                    .text_color(rgb(YELLOW))
+                                    .text_sm()
+                                    .child("Rewriting branch..."),
+                            )
+                            .child(
+                                div()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .text_xs()
+                                    .child("Please wait"),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,313 @@
+//! Stash dialogs rendering: stashing changes and dropping an entry (see
+//! `crate::app::stash`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, rgb,
+    rgba,
+};
+
+impl SashikiApp {
+    pub fn render_stash_dialog(&self, cx: &Context<Self>) -> AnyElement {
+        let input_value = self.stash_message_input.clone();
+        let include_untracked = self.stash_include_untracked;
+
+        div()
+            .id("stash-dialog-container")
+            .track_focus(&self.stash_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_stash_dialog(window, cx);
+                } else if key == "enter" {
+                    this.submit_stash_push(window, cx);
+                } else if key == "backspace" {
+                    this.stash_message_input.pop();
+                    cx.notify();
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    this.stash_message_input.push_str(text);
+                    cx.notify();
+                }
+            }))
+            .child(
+                div()
+                    .id("stash-dialog-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_stash_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("stash-dialog")
+                            .occlude()
+                            .w_80()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(TEXT))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Stash Changes"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT_SECONDARY))
+                                            .text_sm()
+                                            .child("Message (optional):"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("stash-message-input")
+                                            .w_full()
+                                            .px_3()
+                                            .py_2()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .cursor_text()
+                                            .text_color(if input_value.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .text_sm()
+                                            .child(if input_value.is_empty() {
+                                                "work in progress".to_string()
+                                            } else {
+                                                format!("{}_", input_value)
+                                            }),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("stash-include-untracked")
+                                            .flex()
+                                            .items_center()
+                                            .gap_2()
+                                            .cursor_pointer()
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.stash_include_untracked =
+                                                    !this.stash_include_untracked;
+                                                cx.notify();
+                                            }))
+                                            .child(
+                                                div()
+                                                    .text_color(if include_untracked {
+                                                        rgb(BLUE)
+                                                    } else {
+                                                        rgb(TEXT_MUTED)
+                                                    })
+                                                    .child(if include_untracked {
+                                                        "☑"
+                                                    } else {
+                                                        "☐"
+                                                    }),
+                                            )
+                                            .child("Include untracked files"),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-stash")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.close_stash_dialog(window, cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("submit-stash")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(GREEN))
+                                            .hover(|el| el.bg(rgb(TEAL)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, window, cx| {
+                                                this.submit_stash_push(window, cx);
+                                            }))
+                                            .child("Stash"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+
+    pub fn render_stash_drop_dialog(
+        &self,
+        index: usize,
+        message: &str,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        let message = message.to_string();
+
+        div()
+            .id("stash-drop-container")
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_stash_drop_dialog(cx);
+                } else if key == "enter" {
+                    this.confirm_stash_drop(cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("stash-drop-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, _, cx| {
+                            this.close_stash_drop_dialog(cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("stash-drop-dialog")
+                            .occlude()
+                            .w_96()
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(RED))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .text_color(rgb(RED))
+                                    .font_weight(gpui::FontWeight::BOLD)
+                                    .child("Drop Stash"),
+                            )
+                            .child(
+                                div()
+                                    .p_4()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_3()
+                                    .child(div().text_color(rgb(TEXT)).text_sm().child(format!(
+                                        "Drop stash@{{{}}} \"{}\"?",
+                                        index, message
+                                    )))
+                                    .child(
+                                        div()
+                                            .text_color(rgb(YELLOW))
+                                            .text_xs()
+                                            .child("The stashed changes will be discarded."),
+                                    ),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .justify_end()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .id("cancel-stash-drop")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(BG_SURFACE1))
+                                            .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.close_stash_drop_dialog(cx);
+                                            }))
+                                            .child("Cancel"),
+                                    )
+                                    .child(
+                                        div()
+                                            .id("confirm-stash-drop")
+                                            .px_4()
+                                            .py_2()
+                                            .cursor_pointer()
+                                            .rounded_sm()
+                                            .bg(rgb(RED))
+                                            .hover(|el| el.bg(rgb(MAROON)))
+                                            .text_xs()
+                                            .text_color(rgb(BG_BASE))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.confirm_stash_drop(cx);
+                                            }))
+                                            .child("Drop"),
+                                    ),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,93 @@
+//! Tab expansion for the file and diff views
+//!
+//! Text layout draws a tab as a single narrow advance, so tab-indented lines
+//! never line up. Lines are shown with each tab expanded to spaces up to the
+//! next multiple of `sashiki.viewer.tabWidth` columns, counting columns as a
+//! terminal does (wide CJK characters take two). Only the shown text is
+//! expanded: the file content and diffs stay as read, so discarding hunks and
+//! diffing see the real bytes.
+
+use unicode_width::UnicodeWidthChar;
+
+/// Columns of `c` on screen (control characters take none)
+pub fn char_columns(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Spaces a tab at `column` expands to
+pub fn tab_span(column: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    tab_width - column % tab_width
+}
+
+/// Append `text` to `out` with its tabs expanded, `column` being the column
+/// `out` ends at. Returns the column after `text`.
+pub fn push_expanded(out: &mut String, text: &str, mut column: usize, tab_width: usize) -> usize {
+    for c in text.chars() {
+        if c == '\t' {
+            let span = tab_span(column, tab_width);
+            out.extend(std::iter::repeat_n(' ', span));
+            column += span;
+        } else {
+            out.push(c);
+            column += char_columns(c);
+        }
+    }
+    column
+}
+
+/// `line` with its tabs expanded to the next tab stop
+pub fn expand_tabs(line: &str, tab_width: usize) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let mut expanded = String::with_capacity(line.len() + tab_width * 2);
+    push_expanded(&mut expanded, line, 0, tab_width);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tabs_expand_to_the_next_stop() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tx", 4), "ab  x");
+        assert_eq!(expand_tabs("abcd\tx", 4), "abcd    x");
+        assert_eq!(expand_tabs("a\tb\tc", 8), "a       b       c");
+        assert_eq!(expand_tabs("no tabs", 4), "no tabs");
+        // A zero width from a bad setting still moves on
+        assert_eq!(expand_tabs("\tx", 0), " x");
+    }
+
+    #[test]
+    fn test_mixed_tab_and_space_indentation_lines_up() {
+        let lines = [
+            "\tlet x = 1;",
+            "    let y = 2;",
+            "  \tlet z = 3;",
+            " \t \tw",
+        ];
+        let expanded: Vec<String> = lines.iter().map(|l| expand_tabs(l, 4)).collect();
+        assert_eq!(expanded[0], "    let x = 1;");
+        assert_eq!(expanded[1], "    let y = 2;");
+        // Spaces before a tab are absorbed by the tab stop
+        assert_eq!(expanded[2], "    let z = 3;");
+        assert_eq!(expanded[3], "        w");
+    }
+
+    #[test]
+    fn test_tabs_after_wide_characters() {
+        // "日本" takes four columns, so the tab fills a whole stop
+        assert_eq!(expand_tabs("日本\tx", 4), "日本    x");
+        // "日" ends at column 2, "a" at 3: one space to the stop
+        assert_eq!(expand_tabs("日a\tx", 4), "日a x");
+        // Columns are counted, not bytes (each kanji is three bytes)
+        assert_eq!(expand_tabs("日\tx", 8), "日      x");
+
+        let mut out = String::from("ｶﾅ");
+        let column = push_expanded(&mut out, "\t", 2, 4);
+        assert_eq!((out.as_str(), column), ("ｶﾅ  ", 4));
+    }
+}
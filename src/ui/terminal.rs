@@ -1,36 +1,41 @@
 //! Terminal panel rendering
 
+use crate::app::ResizeDrag;
 use crate::app::SashikiApp;
-use crate::session::{LayoutMode, SessionStatus};
+use crate::session::{LayoutMode, SessionColor, SessionStatus};
 use crate::theme::*;
 use crate::ui::{render_locked_badge, render_main_badge};
-use crate::app::ResizeDrag;
 use gpui::{
     AnyElement, Context, DefiniteLength, IntoElement, ParentElement, Styled, div, prelude::*, rgb,
 };
 
 /// Properties for rendering a terminal header
 struct TerminalHeaderProps {
+    session_index: usize,
     name: String,
     branch: Option<String>,
-    color: u32,
+    color: SessionColor,
     status: SessionStatus,
     is_main: bool,
     is_locked: bool,
     path_display: String,
     show_verify_button: bool,
+    /// Window title set by the program running in the terminal
+    title: Option<String>,
+    /// Whether the terminal is in keyboard copy mode
+    copy_mode: bool,
 }
 
 impl SashikiApp {
     pub fn render_terminal_area(&self, cx: &Context<Self>) -> AnyElement {
-        match self.session_manager.layout_mode() {
+        match self.repo.session_manager.layout_mode() {
             LayoutMode::Single => self.render_single_mode(cx),
             LayoutMode::Parallel => self.render_parallel_mode(cx),
         }
     }
 
     fn render_single_mode(&self, cx: &Context<Self>) -> AnyElement {
-        if self.session_manager.is_empty() {
+        if self.repo.session_manager.is_empty() {
             return div()
                 .flex_1()
                 .flex()
@@ -41,10 +46,10 @@ impl SashikiApp {
                 .into_any_element();
         }
 
-        let active_index = self.session_manager.active_index();
+        let active_index = self.repo.session_manager.active_index();
 
         if self.show_verify_terminal {
-            let ratio = self.terminal_split_ratio;
+            let ratio = self.layout.terminal_split_ratio;
             div()
                 .flex_1()
                 .flex()
@@ -61,7 +66,7 @@ impl SashikiApp {
                 .child(self.render_resize_handle_v(
                     ResizeDrag::TerminalSplit {
                         start_x: 0.0,
-                        initial_ratio: self.terminal_split_ratio,
+                        initial_ratio: self.layout.terminal_split_ratio,
                     },
                     cx,
                 ))
@@ -80,12 +85,12 @@ impl SashikiApp {
     }
 
     fn render_parallel_mode(&self, cx: &Context<Self>) -> AnyElement {
-        let parallel_sessions = self.session_manager.parallel_sessions();
+        let parallel_sessions = self.repo.session_manager.parallel_sessions();
         if parallel_sessions.is_empty() {
             return self.render_single_mode(cx);
         }
 
-        let active_index = self.session_manager.active_index();
+        let active_index = self.repo.session_manager.active_index();
         let count = parallel_sessions.len();
 
         let (rows, cols) = match count {
@@ -136,17 +141,22 @@ impl SashikiApp {
         is_focused: bool,
         cx: &Context<Self>,
     ) -> AnyElement {
-        let sessions = self.session_manager.sessions();
+        let sessions = self.repo.session_manager.sessions();
         let session = &sessions[session_index];
-        let color = session.color().primary;
+        let color = session.color();
         let name = session.name().to_string();
         let branch = session.branch().map(|s| s.to_string());
         let is_main = session.is_main();
         let is_locked = session.is_locked();
+        let read_only = session.is_read_only();
         let status = session.status();
         let path_display = session.worktree_path().to_string_lossy().to_string();
         let show_verify_button =
-            is_focused && self.session_manager.layout_mode() == LayoutMode::Single;
+            is_focused && self.repo.session_manager.layout_mode() == LayoutMode::Single;
+        let title = session.active_terminal_title(cx);
+        let copy_mode = session
+            .active_terminal()
+            .is_some_and(|terminal| terminal.read(cx).is_copy_mode());
 
         let terminal_content: AnyElement = if let Some(terminal) = session.active_terminal() {
             div()
@@ -157,6 +167,23 @@ impl SashikiApp {
                 .overflow_hidden()
                 .child(terminal.clone())
                 .into_any_element()
+        } else if status == SessionStatus::Missing {
+            div()
+                .flex_1()
+                .flex()
+                .flex_col()
+                .items_center()
+                .justify_center()
+                .gap_1()
+                .bg(rgb(BG_BASE))
+                .text_color(rgb(TEXT_MUTED))
+                .child(
+                    div()
+                        .text_color(rgb(RED))
+                        .child(format!("Worktree directory not found: {}", path_display)),
+                )
+                .child("Right-click the session in the sidebar to prune or recreate it")
+                .into_any_element()
         } else {
             div()
                 .flex_1()
@@ -176,7 +203,10 @@ impl SashikiApp {
             .overflow_hidden()
             .border_2()
             .border_color(if is_focused {
-                rgb(color)
+                rgb(color.on(BG_BASE))
+            } else if self.repo.session_manager.layout_mode() == LayoutMode::Parallel {
+                // Tell the panes of parallel mode apart by their session
+                rgb(color.tint(BG_BASE, 45))
             } else {
                 rgb(BG_SURFACE0)
             })
@@ -184,6 +214,7 @@ impl SashikiApp {
             .m_1()
             .child(self.render_terminal_header(
                 TerminalHeaderProps {
+                    session_index,
                     name,
                     branch,
                     color,
@@ -192,43 +223,52 @@ impl SashikiApp {
                     is_locked,
                     path_display,
                     show_verify_button,
+                    title,
+                    copy_mode,
                 },
                 cx,
             ))
+            .when(read_only, |el| {
+                el.child(
+                    div()
+                        .px_3()
+                        .py_1()
+                        .bg(rgb(BG_MANTLE))
+                        .text_xs()
+                        .text_color(rgb(YELLOW))
+                        .child("🔒 Read-only session: Sashiki won't change this worktree (the terminal still can)"),
+                )
+            })
             .child(terminal_content)
             .into_any_element()
     }
 
-    fn render_verify_terminal_panel(
-        &self,
-        session_index: usize,
-        _cx: &Context<Self>,
-    ) -> AnyElement {
-        let sessions = self.session_manager.sessions();
+    fn render_verify_terminal_panel(&self, session_index: usize, cx: &Context<Self>) -> AnyElement {
+        let sessions = self.repo.session_manager.sessions();
         let session = &sessions[session_index];
-        let color = session.color().primary;
+        let color = session.color().on(BG_MANTLE);
+        let title = session.terminal_title(1, cx);
 
-        let terminal_content: AnyElement =
-            if let Some(terminal) = session.get_terminal(1) {
-                div()
-                    .flex_1()
-                    .w_full()
-                    .flex()
-                    .flex_col()
-                    .overflow_hidden()
-                    .child(terminal.clone())
-                    .into_any_element()
-            } else {
-                div()
-                    .flex_1()
-                    .flex()
-                    .items_center()
-                    .justify_center()
-                    .bg(rgb(BG_BASE))
-                    .text_color(rgb(TEXT_MUTED))
-                    .child("Verify terminal not started")
-                    .into_any_element()
-            };
+        let terminal_content: AnyElement = if let Some(terminal) = session.get_terminal(1) {
+            div()
+                .flex_1()
+                .w_full()
+                .flex()
+                .flex_col()
+                .overflow_hidden()
+                .child(terminal.clone())
+                .into_any_element()
+        } else {
+            div()
+                .flex_1()
+                .flex()
+                .items_center()
+                .justify_center()
+                .bg(rgb(BG_BASE))
+                .text_color(rgb(TEXT_MUTED))
+                .child("Verify terminal not started")
+                .into_any_element()
+        };
 
         div()
             .flex_1()
@@ -248,13 +288,23 @@ impl SashikiApp {
                     .bg(rgb(BG_MANTLE))
                     .border_b_2()
                     .border_color(rgb(color))
+                    .gap_2()
                     .child(
                         div()
                             .text_color(rgb(color))
                             .text_sm()
                             .font_weight(gpui::FontWeight::BOLD)
                             .child("Verify"),
-                    ),
+                    )
+                    .when_some(title, |el, title| {
+                        el.child(
+                            div()
+                                .text_color(rgb(TEXT_SECONDARY))
+                                .text_xs()
+                                .truncate()
+                                .child(title),
+                        )
+                    }),
             )
             .child(terminal_content)
             .into_any_element()
@@ -266,6 +316,7 @@ impl SashikiApp {
         cx: &Context<Self>,
     ) -> impl IntoElement {
         let TerminalHeaderProps {
+            session_index,
             name,
             branch,
             color,
@@ -274,9 +325,14 @@ impl SashikiApp {
             is_locked,
             path_display,
             show_verify_button,
+            title,
+            copy_mode,
         } = props;
 
         let verify_active = self.show_verify_terminal;
+        // The header is tinted with the session's color
+        let tint = color.tint(BG_MANTLE, 12);
+        let color = color.on(BG_MANTLE);
 
         div()
             .h_8()
@@ -284,7 +340,7 @@ impl SashikiApp {
             .flex()
             .items_center()
             .justify_between()
-            .bg(rgb(BG_MANTLE))
+            .bg(rgb(tint))
             .border_b_2()
             .border_color(rgb(color))
             .child(
@@ -298,6 +354,7 @@ impl SashikiApp {
                                 SessionStatus::Focused => rgb(GREEN),
                                 SessionStatus::Running => rgb(YELLOW),
                                 SessionStatus::Stopped => rgb(TEXT_MUTED),
+                                SessionStatus::Missing => rgb(RED),
                             })
                             .text_sm()
                             .child(status.symbol()),
@@ -311,13 +368,42 @@ impl SashikiApp {
                             .child(name),
                     )
                     .when(is_main, |el| el.child(render_main_badge()))
-                    .when(is_locked, |el| el.child(render_locked_badge())),
+                    .when(is_locked, |el| el.child(render_locked_badge()))
+                    .when(copy_mode, |el| {
+                        el.child(
+                            div()
+                                .px_1()
+                                .bg(rgb(MAUVE))
+                                .text_color(rgb(BG_BASE))
+                                .text_xs()
+                                .rounded_sm()
+                                .child("COPY"),
+                        )
+                    })
+                    .when_some(title, |el, title| {
+                        el.child(
+                            div()
+                                .text_color(rgb(TEXT_SECONDARY))
+                                .text_xs()
+                                .max_w_64()
+                                .truncate()
+                                .child(title),
+                        )
+                    }),
             )
             .child(
                 div()
                     .flex()
                     .items_center()
                     .gap_2()
+                    .when(!self.agents.profiles.is_empty(), |el| {
+                        el.child(self.render_agent_button(
+                            ("agent-launcher", session_index),
+                            session_index,
+                            "Agent ▾",
+                            cx,
+                        ))
+                    })
                     .when(show_verify_button, |el| {
                         el.child(
                             div()
@@ -341,7 +427,8 @@ impl SashikiApp {
                                 .on_click(cx.listener(|this, _, _, cx| {
                                     this.show_verify_terminal = !this.show_verify_terminal;
                                     if this.show_verify_terminal {
-                                        this.session_manager
+                                        this.repo
+                                            .session_manager
                                             .ensure_active_session_terminal_count(2, cx);
                                     }
                                     cx.notify();
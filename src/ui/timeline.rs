@@ -0,0 +1,116 @@
+//! Activity timeline panel rendering (see `crate::activity`)
+
+use crate::activity::{self, ActivityEvent};
+use crate::app::SashikiApp;
+use crate::theme::*;
+use crate::ui::sidebar::format_age;
+use gpui::{AnyElement, Context, IntoElement, ParentElement, Styled, div, prelude::*, px, rgb};
+
+/// Height of the timeline panel
+const TIMELINE_PANEL_HEIGHT: f32 = 180.0;
+
+impl SashikiApp {
+    /// Timeline of the session named `name` below the terminals, newest first
+    pub fn render_timeline_panel(&self, name: &str, cx: &Context<Self>) -> AnyElement {
+        let sessions = self.repo.session_manager.sessions();
+        let index = sessions.iter().position(|s| s.name() == name);
+        let entries = index
+            .map(|i| sessions[i].events().entries())
+            .unwrap_or_default();
+        let now = activity::unix_now();
+
+        div()
+            .id("timeline-panel")
+            .h(px(TIMELINE_PANEL_HEIGHT))
+            .flex_shrink_0()
+            .flex()
+            .flex_col()
+            .bg(rgb(BG_MANTLE))
+            .border_t_1()
+            .border_color(rgb(BG_SURFACE0))
+            .child(
+                div()
+                    .h_7()
+                    .px_2()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .bg(rgb(BG_BASE))
+                    .border_b_1()
+                    .border_color(rgb(BG_SURFACE0))
+                    .text_xs()
+                    .text_color(rgb(TEXT_SECONDARY))
+                    .child(
+                        div()
+                            .text_color(rgb(TEXT))
+                            .child(format!("Activity · {}", name)),
+                    )
+                    .child(div().flex_1())
+                    .when_some(index, |this, index| {
+                        this.child(
+                            div()
+                                .id("timeline-mark")
+                                .px_1()
+                                .cursor_pointer()
+                                .rounded_sm()
+                                .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    this.open_mark_moment_dialog(index, window, cx);
+                                }))
+                                .child("Mark moment"),
+                        )
+                    })
+                    .child(
+                        div()
+                            .id("timeline-close")
+                            .px_1()
+                            .cursor_pointer()
+                            .rounded_sm()
+                            .hover(|el| el.bg(rgb(BG_SURFACE1)))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.close_timeline(cx);
+                            }))
+                            .child("×"),
+                    ),
+            )
+            .child(
+                div()
+                    .id("timeline-body")
+                    .flex_1()
+                    .px_2()
+                    .py_1()
+                    .overflow_y_scroll()
+                    .text_xs()
+                    .when(entries.is_empty(), |this| {
+                        this.text_color(rgb(TEXT_MUTED))
+                            .child("Nothing recorded yet")
+                    })
+                    .children(entries.into_iter().map(|entry| {
+                        let color = match entry.event {
+                            ActivityEvent::Attention { .. } => YELLOW,
+                            ActivityEvent::Note { .. } => BLUE,
+                            ActivityEvent::HeadMoved { .. } => GREEN,
+                            _ => TEXT,
+                        };
+                        div()
+                            .flex()
+                            .gap_3()
+                            .child(
+                                div()
+                                    .w(px(64.0))
+                                    .flex_shrink_0()
+                                    .text_color(rgb(TEXT_MUTED))
+                                    .child(format_age(now - entry.at)),
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_color(rgb(color))
+                                    .child(entry.event.describe()),
+                            )
+                    })),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,282 @@
+//! Transcript dialog rendering (see `crate::transcript`)
+
+use crate::app::SashikiApp;
+use crate::theme::*;
+use crate::transcript::{self, ROW_HEIGHT};
+use crate::ui::TextTooltip;
+use crate::ui::virtual_rows::VisibleWindow;
+use gpui::{
+    AnyElement, Context, IntoElement, KeyDownEvent, ParentElement, Styled, div, prelude::*, px,
+    rgb, rgba,
+};
+
+impl SashikiApp {
+    pub fn render_transcript_dialog(&self, target_index: usize, cx: &Context<Self>) -> AnyElement {
+        let Some(session) = self.repo.session_manager.sessions().get(target_index) else {
+            return div().into_any_element();
+        };
+        let session_name = session.branch().unwrap_or(session.name()).to_string();
+        let recording = session.is_recording();
+        let state = &self.transcript;
+        let current_line = state.current_line();
+        let match_count = if state.query.is_empty() {
+            String::new()
+        } else if state.matches.is_empty() {
+            "No matches".to_string()
+        } else {
+            format!("{}/{}", state.current + 1, state.matches.len())
+        };
+
+        // Rows are virtualized; the list has a fixed height, so it is known
+        // before the first layout
+        let list_height = 420.0;
+        let scroll_top = -f32::from(self.transcript_scroll.offset().y);
+        let window = VisibleWindow::compute(state.lines.len(), ROW_HEIGHT, scroll_top, list_height);
+
+        let list = div()
+            .id("transcript-lines")
+            .h(px(list_height))
+            .overflow_y_scroll()
+            .track_scroll(&self.transcript_scroll)
+            .px_4()
+            .font_family(MONOSPACE_FONT)
+            .text_xs()
+            .child(div().h(px(window.top_padding)))
+            .children(
+                window
+                    .slice(&state.lines)
+                    .iter()
+                    .zip(window.range.clone())
+                    .map(|(line, i)| {
+                        let is_match = state.matches.binary_search(&i).is_ok();
+                        div()
+                            .h(px(ROW_HEIGHT))
+                            .flex_shrink_0()
+                            .whitespace_nowrap()
+                            .overflow_hidden()
+                            .text_color(if line == transcript::FILE_SEPARATOR {
+                                rgb(TEXT_MUTED)
+                            } else {
+                                rgb(TEXT)
+                            })
+                            .when(is_match, |el| el.bg(rgb(BG_SURFACE1)))
+                            .when(current_line == Some(i), |el| {
+                                el.bg(rgb(YELLOW)).text_color(rgb(BG_BASE))
+                            })
+                            .child(line.clone())
+                    }),
+            )
+            .child(div().h(px(window.bottom_padding)))
+            .when(state.lines.is_empty(), |el| {
+                el.flex()
+                    .items_center()
+                    .justify_center()
+                    .text_color(rgb(TEXT_MUTED))
+                    .child(if state.loading {
+                        "Loading…"
+                    } else {
+                        "Nothing recorded yet"
+                    })
+            });
+
+        let footer_button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_3()
+                .py_2()
+                .cursor_pointer()
+                .rounded_sm()
+                .bg(rgb(BG_SURFACE1))
+                .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                .text_xs()
+                .text_color(rgb(TEXT))
+                .child(label)
+        };
+
+        div()
+            .id("transcript-container")
+            .track_focus(&self.transcript_dialog_focus)
+            .absolute()
+            .inset_0()
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let key = &event.keystroke.key;
+                if key == "escape" {
+                    this.close_transcript_dialog(window, cx);
+                } else if key == "enter" {
+                    this.step_transcript_match(!event.keystroke.modifiers.shift, cx);
+                } else if key == "backspace" {
+                    let mut query = this.transcript.query.clone();
+                    query.pop();
+                    this.set_transcript_query(query, cx);
+                } else if !event.keystroke.modifiers.control
+                    && let Some(text) = event.keystroke.key_char.as_deref()
+                    && !text.chars().any(char::is_control)
+                {
+                    let query = format!("{}{}", this.transcript.query, text);
+                    this.set_transcript_query(query, cx);
+                }
+            }))
+            .child(
+                div()
+                    .id("transcript-backdrop")
+                    .absolute()
+                    .inset_0()
+                    .bg(rgba(OVERLAY))
+                    .on_mouse_down(
+                        gpui::MouseButton::Left,
+                        cx.listener(|this, _, window, cx| {
+                            this.close_transcript_dialog(window, cx);
+                        }),
+                    ),
+            )
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        div()
+                            .id("transcript-dialog")
+                            .occlude()
+                            .w(px(760.))
+                            .bg(rgb(BG_BASE))
+                            .border_1()
+                            .border_color(rgb(BG_SURFACE1))
+                            .rounded_md()
+                            .shadow_lg()
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_b_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .items_center()
+                                    .justify_between()
+                                    .child(
+                                        div()
+                                            .text_color(rgb(TEXT))
+                                            .font_weight(gpui::FontWeight::BOLD)
+                                            .child(format!("Transcript of {}", session_name)),
+                                    )
+                                    .when(transcript::SUPPORTED, |el| {
+                                        el.child(
+                                            div()
+                                                .id("transcript-recording")
+                                                .px_2()
+                                                .py_1()
+                                                .cursor_pointer()
+                                                .rounded_sm()
+                                                .text_xs()
+                                                .bg(rgb(BG_SURFACE1))
+                                                .hover(|el| el.bg(rgb(BG_SURFACE2)))
+                                                .text_color(if recording {
+                                                    rgb(RED)
+                                                } else {
+                                                    rgb(TEXT_SECONDARY)
+                                                })
+                                                .on_click(cx.listener(move |this, _, _, cx| {
+                                                    this.toggle_session_recording(target_index, cx);
+                                                }))
+                                                .child(if recording {
+                                                    "⏺ Recording"
+                                                } else {
+                                                    "○ Not recording"
+                                                }),
+                                        )
+                                    }),
+                            )
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_2()
+                                    .flex()
+                                    .items_center()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .flex_1()
+                                            .px_3()
+                                            .py_1()
+                                            .bg(rgb(BG_SURFACE0))
+                                            .border_1()
+                                            .border_color(rgb(BLUE))
+                                            .rounded_sm()
+                                            .text_sm()
+                                            .text_color(if state.query.is_empty() {
+                                                rgb(TEXT_MUTED)
+                                            } else {
+                                                rgb(TEXT)
+                                            })
+                                            .child(if state.query.is_empty() {
+                                                "Type to search".to_string()
+                                            } else {
+                                                format!("{}_", state.query)
+                                            }),
+                                    )
+                                    .child(
+                                        div()
+                                            .min_w(px(72.))
+                                            .text_xs()
+                                            .text_color(rgb(TEXT_MUTED))
+                                            .child(match_count),
+                                    )
+                                    .child(
+                                        footer_button("transcript-prev", "↑")
+                                            .tooltip(TextTooltip::build(
+                                                "Previous match (Shift+Enter)",
+                                            ))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.step_transcript_match(false, cx);
+                                            })),
+                                    )
+                                    .child(
+                                        footer_button("transcript-next", "↓")
+                                            .tooltip(TextTooltip::build("Next match (Enter)"))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.step_transcript_match(true, cx);
+                                            })),
+                                    ),
+                            )
+                            .child(list)
+                            .child(
+                                div()
+                                    .px_4()
+                                    .py_3()
+                                    .border_t_1()
+                                    .border_color(rgb(BG_SURFACE0))
+                                    .flex()
+                                    .gap_2()
+                                    .child(
+                                        footer_button("transcript-copy", "Copy visible").on_click(
+                                            cx.listener(|this, _, _, cx| {
+                                                this.copy_visible_transcript(cx);
+                                            }),
+                                        ),
+                                    )
+                                    .child(
+                                        footer_button("transcript-folder", "Open folder").on_click(
+                                            cx.listener(|this, _, _, cx| {
+                                                this.open_transcript_folder(cx);
+                                            }),
+                                        ),
+                                    )
+                                    .child(footer_button("transcript-reload", "Reload").on_click(
+                                        cx.listener(|this, _, _, cx| {
+                                            this.reload_transcript(cx);
+                                        }),
+                                    ))
+                                    .child(div().flex_1())
+                                    .child(footer_button("transcript-close", "Close").on_click(
+                                        cx.listener(|this, _, window, cx| {
+                                            this.close_transcript_dialog(window, cx);
+                                        }),
+                                    )),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,209 @@
+//! Row virtualization for long lists with a fixed row height
+//!
+//! Only the rows inside the scroll viewport (plus a few rows of overscan) get
+//! elements; the rest of the list is represented by two spacers so the
+//! scrollbar keeps its size.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Rows built above and below the viewport so fast scrolling does not show gaps
+pub const OVERSCAN_ROWS: usize = 8;
+
+/// Part of a list that needs elements this frame
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VisibleWindow {
+    pub range: Range<usize>,
+    /// Height of the rows skipped above `range`
+    pub top_padding: f32,
+    /// Height of the rows skipped below `range`
+    pub bottom_padding: f32,
+}
+
+impl VisibleWindow {
+    pub fn compute(len: usize, row_height: f32, scroll_top: f32, viewport_height: f32) -> Self {
+        let scroll_top = scroll_top.max(0.0);
+        let first_visible = (scroll_top / row_height).floor() as usize;
+        let last_visible = ((scroll_top + viewport_height.max(0.0)) / row_height).ceil() as usize;
+
+        let start = first_visible.saturating_sub(OVERSCAN_ROWS).min(len);
+        let end = last_visible
+            .saturating_add(OVERSCAN_ROWS)
+            .min(len)
+            .max(start);
+
+        Self {
+            range: start..end,
+            top_padding: start as f32 * row_height,
+            bottom_padding: (len - end) as f32 * row_height,
+        }
+    }
+
+    /// The rows inside the window
+    pub fn slice<'a, T>(&self, rows: &'a [T]) -> &'a [T] {
+        let end = self.range.end.min(rows.len());
+        &rows[self.range.start.min(end)..end]
+    }
+}
+
+/// Smallest scroll change that brings row `index` fully into view
+pub fn reveal(index: usize, row_height: f32, scroll_top: f32, viewport_height: f32) -> f32 {
+    let row_top = index as f32 * row_height;
+    let row_bottom = row_top + row_height;
+    if row_top < scroll_top {
+        row_top
+    } else if row_bottom > scroll_top + viewport_height {
+        (row_bottom - viewport_height).max(0.0)
+    } else {
+        scroll_top
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Anchor {
+    /// Path of the first visible row
+    path: PathBuf,
+    /// How far that row is scrolled past the top of the viewport
+    offset: f32,
+    /// Scroll top the anchor was taken at
+    scroll_top: f32,
+}
+
+/// Keeps the row at the top of the viewport in place when the list is rebuilt.
+///
+/// Rows are anchored by path, not index, so files appearing or disappearing
+/// above the viewport do not shift what the user is looking at.
+#[derive(Debug, Default)]
+pub struct ScrollAnchor {
+    anchor: Option<Anchor>,
+}
+
+impl ScrollAnchor {
+    /// Scroll top to use for `rows` this frame.
+    ///
+    /// A scroll by the user since the last frame always wins. A list scrolled
+    /// to the very top stays there, so new rows at the top become visible.
+    pub fn resolve<T>(
+        &mut self,
+        rows: &[T],
+        path_of: impl Fn(&T) -> &Path,
+        scroll_top: f32,
+        row_height: f32,
+    ) -> f32 {
+        let top = match &self.anchor {
+            Some(anchor) if scroll_top > 0.0 && (anchor.scroll_top - scroll_top).abs() < 0.5 => {
+                rows.iter()
+                    .position(|row| path_of(row) == anchor.path)
+                    .map(|index| index as f32 * row_height + anchor.offset)
+                    .unwrap_or(scroll_top)
+            }
+            _ => scroll_top,
+        };
+
+        let first = (top.max(0.0) / row_height).floor() as usize;
+        self.anchor = rows.get(first).map(|row| Anchor {
+            path: path_of(row).to_path_buf(),
+            offset: top - first as f32 * row_height,
+            scroll_top: top,
+        });
+        top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::FileTreeNode;
+    use std::collections::HashSet;
+
+    const ROW: f32 = 20.0;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_window_covers_viewport_plus_overscan() {
+        let window = VisibleWindow::compute(1000, ROW, 400.0, 200.0);
+        assert_eq!(window.range, 20 - OVERSCAN_ROWS..30 + OVERSCAN_ROWS);
+        assert_eq!(window.top_padding, (20 - OVERSCAN_ROWS) as f32 * ROW);
+        assert_eq!(
+            window.bottom_padding,
+            (1000 - 30 - OVERSCAN_ROWS) as f32 * ROW
+        );
+
+        // Clamped at both ends
+        let window = VisibleWindow::compute(5, ROW, 0.0, 200.0);
+        assert_eq!(window.range, 0..5);
+        assert_eq!(window.bottom_padding, 0.0);
+        assert_eq!(VisibleWindow::compute(0, ROW, 50.0, 200.0).range, 0..0);
+    }
+
+    #[test]
+    fn test_reveal_scrolls_minimally() {
+        assert_eq!(reveal(5, ROW, 0.0, 200.0), 0.0);
+        assert_eq!(reveal(2, ROW, 100.0, 200.0), 40.0);
+        assert_eq!(reveal(15, ROW, 0.0, 200.0), 120.0);
+    }
+
+    #[test]
+    fn test_anchor_keeps_top_row_when_rows_inserted_above() {
+        let mut anchor = ScrollAnchor::default();
+        let rows = paths(&["a", "b", "c", "d", "e"]);
+        assert_eq!(anchor.resolve(&rows, |p| p, 50.0, ROW), 50.0); // "c" + 10px
+
+        let rows = paths(&["0", "1", "a", "b", "c", "d", "e"]);
+        assert_eq!(anchor.resolve(&rows, |p| p, 50.0, ROW), 90.0);
+
+        // The user scrolls: their position wins over the anchor
+        assert_eq!(anchor.resolve(&rows, |p| p, 20.0, ROW), 20.0);
+    }
+
+    #[test]
+    fn test_anchor_stays_pinned_to_top_and_survives_removed_row() {
+        let mut anchor = ScrollAnchor::default();
+        let rows = paths(&["a", "b"]);
+        assert_eq!(anchor.resolve(&rows, |p| p, 0.0, ROW), 0.0);
+        let rows = paths(&["new", "a", "b"]);
+        assert_eq!(anchor.resolve(&rows, |p| p, 0.0, ROW), 0.0);
+
+        // Anchored row disappears: the scroll position is kept as is
+        anchor.resolve(&rows, |p| p, 40.0, ROW);
+        let rows = paths(&["new", "a"]);
+        assert_eq!(anchor.resolve(&rows, |p| p, 40.0, ROW), 40.0);
+    }
+
+    #[test]
+    fn test_bench_5000_entries_builds_only_visible_rows() {
+        // 50 directories x 100 files, all expanded: 5050 rows
+        let files = (0..5000).map(|i| {
+            (
+                PathBuf::from(format!("dir{:02}/file{:03}.rs", i / 100, i % 100)),
+                None,
+            )
+        });
+        let tree = FileTreeNode::from_files(files);
+        let expanded: HashSet<PathBuf> = tree.children.iter().map(|c| c.path.clone()).collect();
+        let rows = tree.visible_rows(&expanded, &HashSet::new(), &HashSet::new());
+        assert_eq!(rows.len(), 5050);
+
+        let viewport = 800.0;
+        let visible = (viewport / ROW) as usize;
+        for scroll_top in [0.0, 12_345.0, 5050.0 * ROW - viewport] {
+            let window = VisibleWindow::compute(rows.len(), ROW, scroll_top, viewport);
+            let mut built = 0;
+            for _row in window.slice(&rows) {
+                built += 1;
+            }
+            assert!(
+                built <= visible + 1 + 2 * OVERSCAN_ROWS,
+                "built {} rows",
+                built
+            );
+            assert!(built >= visible);
+
+            let total = window.top_padding + built as f32 * ROW + window.bottom_padding;
+            assert_eq!(total, rows.len() as f32 * ROW);
+        }
+    }
+}
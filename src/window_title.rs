@@ -0,0 +1,115 @@
+//! OS window title
+//!
+//! With several windows open, each one is named after its repository and
+//! active session, e.g. "• sashiki — fix-login [sashiki/fix-login]". The
+//! format is `sashiki.window.titleFormat` with the placeholders `{repo}`,
+//! `{session}`, `{branch}` and `{status}` (a bullet while any session wants
+//! attention). Unknown placeholders are left as they are. Without a
+//! repository the title is plain "Sashiki".
+
+/// Title format when `sashiki.window.titleFormat` is not set
+pub const DEFAULT_TITLE_FORMAT: &str = "{status}{repo} — {session} [{branch}]";
+
+/// Title without an open repository
+pub const APP_TITLE: &str = "Sashiki";
+
+/// `{status}` while a session wants attention
+const ATTENTION_MARK: &str = "• ";
+
+/// What the title placeholders expand to
+#[derive(Debug, Clone, Copy)]
+pub struct TitleParts<'a> {
+    pub repo: &'a str,
+    pub session: &'a str,
+    /// None on a detached HEAD
+    pub branch: Option<&'a str>,
+    /// Whether any session wants attention
+    pub attention: bool,
+}
+
+impl TitleParts<'_> {
+    fn value(&self, key: &str) -> Option<&str> {
+        match key {
+            "repo" => Some(self.repo),
+            "session" => Some(self.session),
+            "branch" => Some(self.branch.unwrap_or("detached")),
+            "status" => Some(if self.attention { ATTENTION_MARK } else { "" }),
+            _ => None,
+        }
+    }
+}
+
+/// `format` with its placeholders replaced (`APP_TITLE` if that leaves
+/// nothing)
+pub fn expand(format: &str, parts: &TitleParts) -> String {
+    let mut title = String::new();
+    let mut rest = format;
+    while let Some(open) = rest.find('{') {
+        title.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            // Unclosed brace: the rest is literal
+            rest = &rest[open..];
+            break;
+        };
+        match parts.value(&after[..close]) {
+            Some(value) => title.push_str(value),
+            None => title.push_str(&rest[open..open + close + 2]),
+        }
+        rest = &after[close + 1..];
+    }
+    title.push_str(rest);
+
+    let title = title.trim();
+    if title.is_empty() {
+        APP_TITLE.to_string()
+    } else {
+        title.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTS: TitleParts = TitleParts {
+        repo: "sashiki",
+        session: "fix-login",
+        branch: Some("sashiki/fix-login"),
+        attention: false,
+    };
+
+    #[test]
+    fn test_default_format() {
+        assert_eq!(
+            expand(DEFAULT_TITLE_FORMAT, &PARTS),
+            "sashiki — fix-login [sashiki/fix-login]"
+        );
+        let waiting = TitleParts {
+            attention: true,
+            branch: None,
+            ..PARTS
+        };
+        assert_eq!(
+            expand(DEFAULT_TITLE_FORMAT, &waiting),
+            "• sashiki — fix-login [detached]"
+        );
+    }
+
+    #[test]
+    fn test_missing_placeholders() {
+        assert_eq!(expand("{session}", &PARTS), "fix-login");
+        assert_eq!(expand("Sashiki: {repo}", &PARTS), "Sashiki: sashiki");
+        assert_eq!(expand("{repo} {status}", &PARTS), "sashiki");
+        assert_eq!(expand("", &PARTS), APP_TITLE);
+        assert_eq!(expand("{status}", &PARTS), APP_TITLE);
+    }
+
+    #[test]
+    fn test_unknown_keys_are_literal() {
+        assert_eq!(expand("{repo} {host}", &PARTS), "sashiki {host}");
+        assert_eq!(expand("{}{repo}", &PARTS), "{}sashiki");
+        assert_eq!(expand("{{repo}}", &PARTS), "{{repo}}");
+        assert_eq!(expand("{repo} {branch", &PARTS), "sashiki {branch");
+    }
+}
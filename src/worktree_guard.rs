@@ -0,0 +1,235 @@
+//! Keeping file reads and writes inside a worktree
+//!
+//! Paths of files come from git, the file tree and the template globs, all
+//! relative to a worktree. Joined with the worktree root they can still lead
+//! elsewhere: through `..`, or through a symlink inside the worktree (agents
+//! create these) that points outside it. `check` looks at every part of the
+//! path that exists and refuses the path when one of them is a symlink whose
+//! target is not under the worktree root, naming that link.
+//!
+//! Links meant to leave the worktree (a shared `.env`, say) are allowed by
+//! `sashiki.files.allowSymlink` globs, matched against the link's path
+//! relative to the worktree. They are read from the user's own config only,
+//! so the repository being guarded cannot allow its own links.
+
+use crate::git;
+use crate::settings::Settings;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GuardError {
+    #[error("{} is outside the worktree", .0.display())]
+    Outside(PathBuf),
+    #[error(
+        "{} is a symlink leading outside the worktree (to {}); allow it with {}",
+        link.display(),
+        target.display(),
+        git::CONFIG_ALLOW_SYMLINK
+    )]
+    EscapingLink { link: PathBuf, target: PathBuf },
+    #[error("Cannot resolve the worktree {}: {message}", root.display())]
+    Root { root: PathBuf, message: String },
+}
+
+/// Links allowed to leave their worktree (see `set_allowed_links`)
+static ALLOWED_LINKS: RwLock<Vec<glob::Pattern>> = RwLock::new(Vec::new());
+
+/// Apply `sashiki.files.allowSymlink` to the checks from now on. Returns a
+/// warning per pattern that is not a valid glob.
+pub fn set_allowed_links(settings: &Settings) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let patterns = settings
+        .get_all(git::CONFIG_ALLOW_SYMLINK)
+        .into_iter()
+        .filter_map(|pattern| match glob::Pattern::new(&pattern) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warnings.push(format!(
+                    "Ignoring {} = {}: {}",
+                    git::CONFIG_ALLOW_SYMLINK,
+                    pattern,
+                    e
+                ));
+                None
+            }
+        })
+        .collect();
+    *ALLOWED_LINKS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = patterns;
+    warnings
+}
+
+/// `path` (relative to `root`, or under it) as a full path, once it is
+/// known not to lead outside `root`
+pub fn check(root: &Path, path: &Path) -> Result<PathBuf, GuardError> {
+    let allowed = ALLOWED_LINKS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    check_with(root, path, &allowed)
+}
+
+fn check_with(root: &Path, path: &Path, allowed: &[glob::Pattern]) -> Result<PathBuf, GuardError> {
+    let relative = if path.is_absolute() {
+        path.strip_prefix(root)
+            .map_err(|_| GuardError::Outside(path.to_path_buf()))?
+    } else {
+        path
+    };
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(GuardError::Outside(path.to_path_buf()));
+    }
+    let canonical_root = root.canonicalize().map_err(|e| GuardError::Root {
+        root: root.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let mut current = root.to_path_buf();
+    let mut link = PathBuf::new();
+    for component in relative.components() {
+        current.push(component);
+        link.push(component);
+        let Ok(metadata) = std::fs::symlink_metadata(&current) else {
+            // Nothing below a missing part can be a link yet
+            break;
+        };
+        if !metadata.file_type().is_symlink() {
+            continue;
+        }
+        let target = link_target(&current);
+        let allowed = || {
+            let link = link.to_string_lossy().replace('\\', "/");
+            allowed.iter().any(|pattern| pattern.matches(&link))
+        };
+        if !target.starts_with(&canonical_root) && !allowed() {
+            return Err(GuardError::EscapingLink { link, target });
+        }
+    }
+    Ok(root.join(relative))
+}
+
+/// Where the link at `path` leads; for a dangling link, where a write
+/// through it would land
+fn link_target(path: &Path) -> PathBuf {
+    if let Ok(target) = path.canonicalize() {
+        return target;
+    }
+    let parent = path.parent().unwrap_or(path);
+    let base = parent
+        .canonicalize()
+        .unwrap_or_else(|_| parent.to_path_buf());
+    let joined = match std::fs::read_link(path) {
+        Ok(target) => base.join(target),
+        Err(_) => return path.to_path_buf(),
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// A worktree with an in-tree link and links leading out, next to a
+    /// directory outside it
+    fn tree() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("worktree");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "fn f() {}\n").unwrap();
+        std::fs::write(outside.join("secret"), "key\n").unwrap();
+        symlink("src/lib.rs", root.join("lib.rs")).unwrap();
+        symlink("src", root.join("code")).unwrap();
+        symlink(&outside, root.join("escape")).unwrap();
+        symlink("../outside/secret", root.join(".env")).unwrap();
+        symlink("../outside/new", root.join("dangling")).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn test_links_inside_the_worktree_are_followed() {
+        let (_dir, root) = tree();
+        for path in [
+            "src/lib.rs",
+            "lib.rs",
+            "code/lib.rs",
+            "src/new.rs",
+            "new/deep/file",
+        ] {
+            assert_eq!(
+                check_with(&root, Path::new(path), &[]),
+                Ok(root.join(path)),
+                "{}",
+                path
+            );
+        }
+        let full = root.join("code/lib.rs");
+        assert_eq!(check_with(&root, &full, &[]), Ok(full.clone()));
+        assert_eq!(std::fs::read_to_string(full).unwrap(), "fn f() {}\n");
+    }
+
+    #[test]
+    fn test_paths_leaving_the_worktree_are_refused() {
+        let (dir, root) = tree();
+        let outside = dir.path().join("outside").canonicalize().unwrap();
+        for path in [
+            "../outside/secret",
+            "src/../../outside/secret",
+            "/etc/passwd",
+        ] {
+            assert_eq!(
+                check_with(&root, Path::new(path), &[]),
+                Err(GuardError::Outside(PathBuf::from(path)))
+            );
+        }
+        // Reading or writing through the link names the link
+        for (path, link, target) in [
+            ("escape/secret", "escape", outside.clone()),
+            ("escape/new/file", "escape", outside.clone()),
+            (".env", ".env", outside.join("secret")),
+            ("dangling", "dangling", outside.join("new")),
+        ] {
+            assert_eq!(
+                check_with(&root, Path::new(path), &[]),
+                Err(GuardError::EscapingLink {
+                    link: PathBuf::from(link),
+                    target
+                }),
+                "{}",
+                path
+            );
+        }
+        let message = check_with(&root, Path::new(".env"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(message.starts_with(".env is a symlink leading outside the worktree"));
+    }
+
+    #[test]
+    fn test_allowed_links_may_leave_the_worktree() {
+        let (_dir, root) = tree();
+        let allowed = [glob::Pattern::new(".env").unwrap()];
+        assert_eq!(
+            check_with(&root, Path::new(".env"), &allowed),
+            Ok(root.join(".env"))
+        );
+        assert!(check_with(&root, Path::new("escape/secret"), &allowed).is_err());
+    }
+}